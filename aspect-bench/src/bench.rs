@@ -0,0 +1,71 @@
+//! Overhead benchmarks in Criterion reports.
+
+use crate::gate::advise;
+use aspect_core::{Aspect, JoinPoint, Location};
+use criterion::measurement::Measurement;
+use criterion::{BenchmarkGroup, Criterion};
+use std::hint::black_box;
+
+/// Benchmark `target` called directly and through `aspect`, as the
+/// `baseline` and `advised` functions of the Criterion group `name`.
+///
+/// Criterion reports both side by side, and compares each with the
+/// baseline saved by `--save-baseline`, so that a slower aspect shows as a
+/// regression of `advised` alone.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use aspect_bench::bench_overhead;
+/// use aspect_bench::criterion::{criterion_group, criterion_main, Criterion};
+/// use aspect_std::CounterAspect;
+///
+/// fn parse(input: &str) -> Vec<u64> {
+///     input.split(',').filter_map(|field| field.parse().ok()).collect()
+/// }
+///
+/// fn overhead(c: &mut Criterion) {
+///     let input = "1,2,3,".repeat(1000);
+///     bench_overhead(c, "parse", &CounterAspect::new(), || parse(&input));
+/// }
+///
+/// criterion_group!(benches, overhead);
+/// criterion_main!(benches);
+/// ```
+pub fn bench_overhead<A, R, F>(c: &mut Criterion, name: &'static str, aspect: &A, target: F)
+where
+    A: Aspect + ?Sized,
+    R: 'static,
+    F: Fn() -> R,
+{
+    let mut group = c.benchmark_group(name);
+    bench_overhead_in(&mut group, name, aspect, target);
+    group.finish();
+}
+
+/// Add the `baseline` and `advised` functions of [`bench_overhead`] to an
+/// existing group, e.g. one with its own sample size or throughput.
+pub fn bench_overhead_in<M, A, R, F>(
+    group: &mut BenchmarkGroup<'_, M>,
+    name: &'static str,
+    aspect: &A,
+    target: F,
+) where
+    M: Measurement,
+    A: Aspect + ?Sized,
+    R: 'static,
+    F: Fn() -> R,
+{
+    let ctx = JoinPoint::new(
+        name,
+        module_path!(),
+        Location {
+            file: file!(),
+            line: line!(),
+        },
+    );
+    group.bench_function("baseline", |b| b.iter(|| black_box(target())));
+    group.bench_function("advised", |b| {
+        b.iter(|| black_box(advise(aspect, &ctx, &target)))
+    });
+}
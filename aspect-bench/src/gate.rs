@@ -0,0 +1,347 @@
+//! Overhead measurement of an aspect and budgets failing tests.
+
+use aspect_core::aspect::downcast_result;
+use aspect_core::{Aspect, JoinPoint, Location, ProceedingJoinPoint};
+use std::any::Any;
+use std::fmt;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// The time of a target called directly and through an aspect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Overhead {
+    /// The median time of a direct call
+    pub baseline: Duration,
+
+    /// The median time of a call through the aspect
+    pub advised: Duration,
+}
+
+impl Overhead {
+    /// The time the aspect adds to a call, as a percentage of the time of
+    /// a direct call. Negative when the difference is below the noise of
+    /// the measurement.
+    pub fn percent(&self) -> f64 {
+        let baseline = self.baseline.as_secs_f64();
+        if baseline == 0.0 {
+            return 0.0;
+        }
+        (self.advised.as_secs_f64() - baseline) / baseline * 100.0
+    }
+
+    /// The time the aspect adds to a call.
+    pub fn added(&self) -> Duration {
+        self.advised.saturating_sub(self.baseline)
+    }
+}
+
+impl fmt::Display for Overhead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} advised vs {:?} baseline ({:+.1}%)",
+            self.advised,
+            self.baseline,
+            self.percent()
+        )
+    }
+}
+
+/// Measures the overhead of an aspect on a target, and fails when it is
+/// over a budget.
+///
+/// The target is called alternately directly and through the
+/// [`around`](Aspect::around) advice of the aspect, as `#[aspect]` weaves
+/// it: the result is boxed for the aspect and unboxed after. Each sample
+/// times a batch of calls, sized from a warm-up so that all samples take
+/// about the measurement time, and the median of the samples is kept, so
+/// that a few calls interrupted by the scheduler do not fail the gate.
+///
+/// Overheads measured in debug builds say little about release builds:
+/// run the gates with `cargo test --release`.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_bench::OverheadGate;
+/// use aspect_std::CounterAspect;
+/// use std::time::Duration;
+///
+/// fn checksum(data: &[u8]) -> u32 {
+///     data.iter().fold(0u32, |sum, byte| sum.rotate_left(5) ^ u32::from(*byte))
+/// }
+///
+/// let data = vec![7u8; 16 * 1024];
+/// let overhead = OverheadGate::new("checksum")
+///     .samples(20)
+///     .measurement_time(Duration::from_millis(100))
+///     .measure(&CounterAspect::new(), || checksum(&data));
+/// println!("{}", overhead);
+/// ```
+#[derive(Debug, Clone)]
+pub struct OverheadGate {
+    name: &'static str,
+    samples: usize,
+    warm_up_time: Duration,
+    measurement_time: Duration,
+}
+
+impl OverheadGate {
+    /// Create a gate for the target named `name`, the function name of the
+    /// join points the aspect sees, with 50 samples over 1 second after a
+    /// 300 ms warm-up.
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            samples: 50,
+            warm_up_time: Duration::from_millis(300),
+            measurement_time: Duration::from_secs(1),
+        }
+    }
+
+    /// Take `samples` samples of each kind of call, at least 3.
+    pub fn samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(3);
+        self
+    }
+
+    /// Call the target for `time` before measuring, to warm caches and
+    /// size the batches.
+    pub fn warm_up_time(mut self, time: Duration) -> Self {
+        self.warm_up_time = time;
+        self
+    }
+
+    /// Spend about `time` measuring, in total.
+    pub fn measurement_time(mut self, time: Duration) -> Self {
+        self.measurement_time = time;
+        self
+    }
+
+    /// Measure the overhead of `aspect` on `target`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the aspect returns an error instead of calling the target.
+    pub fn measure<A, R, F>(&self, aspect: &A, target: F) -> Overhead
+    where
+        A: Aspect + ?Sized,
+        R: 'static,
+        F: Fn() -> R,
+    {
+        let ctx = JoinPoint::new(
+            self.name,
+            module_path!(),
+            Location {
+                file: file!(),
+                line: line!(),
+            },
+        );
+        let direct = || {
+            black_box(target());
+        };
+        let advised = || {
+            black_box(advise(aspect, &ctx, &target));
+        };
+
+        // The slower of the two sizes the batches
+        let mut calls = 0u32;
+        let warm_up = Instant::now();
+        while warm_up.elapsed() < self.warm_up_time || calls == 0 {
+            direct();
+            advised();
+            calls += 1;
+        }
+        let per_call = warm_up.elapsed() / calls;
+        let per_sample = self.measurement_time / (2 * self.samples as u32);
+        let batch =
+            (per_sample.as_nanos() / per_call.as_nanos().max(1)).clamp(1, u32::MAX as u128) as u32;
+
+        let mut baseline = Vec::with_capacity(self.samples);
+        let mut with_aspect = Vec::with_capacity(self.samples);
+        for sample in 0..self.samples {
+            // Alternate which goes first, so that neither gets the warmer caches
+            if sample % 2 == 0 {
+                baseline.push(time_batch(batch, direct));
+                with_aspect.push(time_batch(batch, advised));
+            } else {
+                with_aspect.push(time_batch(batch, advised));
+                baseline.push(time_batch(batch, direct));
+            }
+        }
+
+        Overhead {
+            baseline: median(baseline),
+            advised: median(with_aspect),
+        }
+    }
+
+    /// Measure the overhead of `aspect` on `target`, and panic if it is
+    /// over `percent` percent of the time of a direct call.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the overhead is over budget, or if the aspect returns an
+    /// error instead of calling the target.
+    #[track_caller]
+    pub fn assert_below<A, R, F>(&self, percent: f64, aspect: &A, target: F) -> Overhead
+    where
+        A: Aspect + ?Sized,
+        R: 'static,
+        F: Fn() -> R,
+    {
+        let overhead = self.measure(aspect, target);
+        if overhead.percent() > percent {
+            panic!(
+                "overhead of {} on `{}` is over the {}% budget: {}",
+                std::any::type_name::<A>(),
+                self.name,
+                percent,
+                overhead
+            );
+        }
+        overhead
+    }
+}
+
+impl Default for OverheadGate {
+    fn default() -> Self {
+        Self::new("target")
+    }
+}
+
+/// Measure the overhead of `aspect` on `target` with the default
+/// [`OverheadGate`], and panic if it is over `percent` percent of the time
+/// of a direct call.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use aspect_bench::assert_overhead_below;
+/// use aspect_std::CounterAspect;
+///
+/// fn parse(input: &str) -> Vec<u64> {
+///     input.split(',').filter_map(|field| field.parse().ok()).collect()
+/// }
+///
+/// let input = "1,2,3,".repeat(1000);
+/// assert_overhead_below(5.0, &CounterAspect::new(), || parse(&input));
+/// ```
+#[track_caller]
+pub fn assert_overhead_below<A, R, F>(percent: f64, aspect: &A, target: F) -> Overhead
+where
+    A: Aspect + ?Sized,
+    R: 'static,
+    F: Fn() -> R,
+{
+    OverheadGate::default().assert_below(percent, aspect, target)
+}
+
+/// Call `target` through the `around` advice of `aspect`, as woven code
+/// does.
+pub(crate) fn advise<A, R, F>(aspect: &A, ctx: &JoinPoint, target: &F) -> R
+where
+    A: Aspect + ?Sized,
+    R: 'static,
+    F: Fn() -> R,
+{
+    let mut proceed = || Ok(Box::new(target()) as Box<dyn Any>);
+    let pjp = ProceedingJoinPoint::borrowed(&mut proceed, ctx.clone());
+    match aspect
+        .around(pjp)
+        .and_then(|result| downcast_result(ctx.function_name, result))
+    {
+        Ok(result) => result,
+        Err(error) => panic!("aspect rejected the call: {}", error),
+    }
+}
+
+/// The time of one call, from a batch of `calls` calls.
+fn time_batch(calls: u32, f: impl Fn()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..calls {
+        f();
+    }
+    start.elapsed() / calls
+}
+
+fn median(mut samples: Vec<Duration>) -> Duration {
+    samples.sort_unstable();
+    samples[samples.len() / 2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_core::AspectError;
+
+    /// Spins for about `n` multiplications.
+    fn spin(n: u64) -> u64 {
+        let mut x = black_box(n);
+        for _ in 0..n {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+        }
+        x
+    }
+
+    /// Spins three times as long as the target around each call.
+    struct Heavy;
+
+    impl Aspect for Heavy {
+        fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+            black_box(spin(3000));
+            pjp.proceed()
+        }
+    }
+
+    fn gate() -> OverheadGate {
+        OverheadGate::new("spin")
+            .samples(15)
+            .warm_up_time(Duration::from_millis(20))
+            .measurement_time(Duration::from_millis(150))
+    }
+
+    #[test]
+    fn test_percent() {
+        let overhead = Overhead {
+            baseline: Duration::from_micros(100),
+            advised: Duration::from_micros(110),
+        };
+        assert!((overhead.percent() - 10.0).abs() < 1e-9);
+        assert_eq!(overhead.added(), Duration::from_micros(10));
+        assert_eq!(
+            overhead.to_string(),
+            "110µs advised vs 100µs baseline (+10.0%)"
+        );
+    }
+
+    #[test]
+    fn test_measure_heavy_aspect() {
+        let overhead = gate().measure(&Heavy, || spin(1000));
+        assert!(overhead.percent() > 100.0, "{}", overhead);
+    }
+
+    #[test]
+    #[should_panic(expected = "on `spin` is over the 50% budget")]
+    fn test_assert_below_fails_over_budget() {
+        gate().assert_below(50.0, &Heavy, || spin(1000));
+    }
+
+    #[test]
+    fn test_advise_returns_result() {
+        struct NoOp;
+        impl Aspect for NoOp {}
+
+        let ctx = JoinPoint::new(
+            "answer",
+            "tests",
+            Location {
+                file: "gate.rs",
+                line: 1,
+            },
+        );
+        assert_eq!(advise(&NoOp, &ctx, &|| 42), 42);
+    }
+}
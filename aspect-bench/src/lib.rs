@@ -0,0 +1,37 @@
+//! # aspect-bench
+//!
+//! Overhead measurement of aspects, for benchmarks and for tests failing
+//! when an aspect gets slower.
+//!
+//! This crate provides:
+//! - [`assert_overhead_below`]: Fail a test when an aspect adds more than a
+//!   percentage to the time of a call
+//! - [`OverheadGate`]: The same, with the measurement configured
+//! - [`bench_overhead`]: The target with and without the aspect, side by side
+//!   in Criterion reports
+//!
+//! Add it as a dev-dependency. The gates are regular tests, so they run in
+//! CI like any other; run them with `--release`, as overheads measured in
+//! debug builds say little about release builds.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use aspect_bench::assert_overhead_below;
+//! use aspect_std::MetricsAspect;
+//!
+//! #[test]
+//! fn metrics_overhead_on_checkout() {
+//!     let cart = Cart::sample();
+//!     assert_overhead_below(2.0, &MetricsAspect::new(), || checkout(&cart));
+//! }
+//! ```
+
+pub mod bench;
+pub mod gate;
+
+pub use bench::{bench_overhead, bench_overhead_in};
+pub use gate::{assert_overhead_below, Overhead, OverheadGate};
+
+// For benchmarks of the same version of Criterion
+pub use criterion;
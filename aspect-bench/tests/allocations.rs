@@ -0,0 +1,163 @@
+//! Heap allocations of the code `#[aspect]` generates.
+//!
+//! The wrapper must not allocate for itself: the join point is built on
+//! the stack, the original function is borrowed by the
+//! `ProceedingJoinPoint`, and the arguments are captured only for aspects
+//! that [read them](Aspect::reads_args). What is left is the boxed result
+//! `Aspect::around` returns, free for `()` and other zero-sized types.
+//! `#[aspect(static ...)]` needs neither.
+
+use aspect_core::prelude::*;
+use aspect_core::StaticAspect;
+use aspect_macros::aspect;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counts the allocations of each thread, so that the threads of the test
+/// harness do not count.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// The number of allocations `f` makes.
+fn allocations(f: impl FnOnce()) -> u64 {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+/// Counts calls without allocating, with the default `around`.
+struct Counter;
+
+static CALLS: AtomicU64 = AtomicU64::new(0);
+
+impl Aspect for Counter {
+    fn before(&self, _ctx: &JoinPoint) {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn after(&self, _ctx: &JoinPoint, _result: &dyn std::any::Any) {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+static SEEN: AtomicU64 = AtomicU64::new(0);
+
+/// Records the argument of the calls, which must be captured.
+struct ArgReader;
+
+impl Aspect for ArgReader {
+    fn before(&self, ctx: &JoinPoint) {
+        if let Some(&x) = ctx.arg("x").and_then(|arg| arg.value::<u32>()) {
+            SEEN.store(x.into(), Ordering::Relaxed);
+        }
+    }
+
+    fn reads_args(&self) -> bool {
+        true
+    }
+}
+
+/// The same, as a `StaticAspect`.
+struct StaticCounter;
+
+impl StaticAspect for StaticCounter {
+    fn before(&self, _ctx: &JoinPoint) {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn after<R>(&self, _ctx: &JoinPoint, _result: &R) {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+static COUNTER: StaticCounter = StaticCounter;
+
+#[aspect(Counter)]
+fn tick() {}
+
+#[aspect(Counter)]
+fn record(x: u32) {
+    black_box(x);
+}
+
+#[aspect(Counter)]
+fn double(x: u32) -> u32 {
+    x * 2
+}
+
+#[aspect(ArgReader)]
+fn read(x: u32) {
+    black_box(x);
+}
+
+#[aspect(Counter)]
+fn check(name: impl AsRef<str>) -> Result<(), String> {
+    if name.as_ref().is_empty() {
+        Err("empty".into())
+    } else {
+        Ok(())
+    }
+}
+
+#[aspect(static StaticCounter)]
+fn static_double(x: u32) -> u32 {
+    x * 2
+}
+
+#[aspect(static &COUNTER)]
+fn static_check(name: &str) -> Result<usize, String> {
+    match name.len() {
+        0 => Err("empty".into()),
+        len => Ok(len),
+    }
+}
+
+#[test]
+fn test_wrapper_allocations() {
+    // Warm up the thread local and the panic machinery of the first call
+    tick();
+    let calls = CALLS.load(Ordering::Relaxed);
+
+    assert_eq!(allocations(tick), 0);
+    assert_eq!(CALLS.load(Ordering::Relaxed), calls + 2);
+
+    // No aspect reads the arguments, so none is captured
+    assert_eq!(allocations(|| record(21)), 0);
+    assert_eq!(allocations(|| check("alice").unwrap()), 0);
+
+    // The boxed result only
+    assert_eq!(allocations(|| assert_eq!(double(21), 42)), 1);
+
+    // The aspect reads them: the argument list and the captured `u32`
+    assert_eq!(allocations(|| read(21)), 2);
+    assert_eq!(SEEN.load(Ordering::Relaxed), 21);
+}
+
+#[test]
+fn test_static_wrapper_allocations() {
+    static_double(1);
+    let calls = CALLS.load(Ordering::Relaxed);
+
+    assert_eq!(allocations(|| assert_eq!(static_double(21), 42)), 0);
+    assert_eq!(allocations(|| assert_eq!(static_check("alice"), Ok(5))), 0);
+    assert!(CALLS.load(Ordering::Relaxed) >= calls + 4);
+}
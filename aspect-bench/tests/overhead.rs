@@ -0,0 +1,57 @@
+//! Overhead budgets of the standard aspects, on a call of about 100 µs in
+//! release builds.
+//!
+//! Debug builds are too slow and noisy for the budgets, so the gates are
+//! ignored there: run them with `cargo test --release -p aspect-bench`.
+
+use aspect_bench::OverheadGate;
+use aspect_core::Aspect;
+use aspect_std::{CounterAspect, MetricsAspect, ValidationAspect};
+use std::hint::black_box;
+use std::time::Duration;
+
+/// A pseudo-random walk the optimizer cannot shorten.
+fn walk(steps: u64) -> u64 {
+    let mut x = black_box(steps);
+    for _ in 0..steps {
+        x = x
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+    }
+    x
+}
+
+fn gate(name: &'static str) -> OverheadGate {
+    OverheadGate::new(name)
+        .samples(25)
+        .warm_up_time(Duration::from_millis(50))
+        .measurement_time(Duration::from_millis(400))
+}
+
+struct NoOp;
+
+impl Aspect for NoOp {}
+
+#[test]
+#[cfg_attr(debug_assertions, ignore = "overhead gates need --release")]
+fn test_noop_overhead() {
+    gate("walk").assert_below(10.0, &NoOp, || walk(100_000));
+}
+
+#[test]
+#[cfg_attr(debug_assertions, ignore = "overhead gates need --release")]
+fn test_counter_overhead() {
+    gate("walk").assert_below(10.0, &CounterAspect::new(), || walk(100_000));
+}
+
+#[test]
+#[cfg_attr(debug_assertions, ignore = "overhead gates need --release")]
+fn test_metrics_overhead() {
+    gate("walk").assert_below(10.0, &MetricsAspect::new(), || walk(100_000));
+}
+
+#[test]
+#[cfg_attr(debug_assertions, ignore = "overhead gates need --release")]
+fn test_validation_overhead() {
+    gate("walk").assert_below(10.0, &ValidationAspect::new(), || walk(100_000));
+}
@@ -3,9 +3,14 @@
 //! Measures the performance impact of using aspects compared to
 //! hand-written code.
 
-use aspect_core::{Aspect, AspectError, JoinPoint, Location, ProceedingJoinPoint};
+use aspect_core::pointcut::{FunctionInfo, Matcher, Pointcut};
+use aspect_core::{Aspect, AspectError, JoinPoint, Location, ProceedingJoinPoint, StaticAspect};
+use aspect_macros::aspect;
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use std::any::Any;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll, Waker};
 
 // ============================================================================
 // Test Aspects
@@ -60,6 +65,34 @@ impl Aspect for ComplexAspect {
     }
 }
 
+/// Counts entries and exits, woven dynamically or statically
+struct CallCounter;
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+impl Aspect for CallCounter {
+    fn before(&self, _ctx: &JoinPoint) {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn after(&self, _ctx: &JoinPoint, _result: &dyn Any) {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The same as [`CallCounter`], as a `StaticAspect`
+struct StaticCallCounter;
+
+impl StaticAspect for StaticCallCounter {
+    fn before(&self, _ctx: &JoinPoint) {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn after<R>(&self, _ctx: &JoinPoint, _result: &R) {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
 // ============================================================================
 // Test Functions
 // ============================================================================
@@ -74,14 +107,14 @@ fn baseline_function(x: i32) -> i32 {
 #[inline(never)]
 fn noop_aspect_function(x: i32) -> i32 {
     let aspect = NoOpAspect;
-    let ctx = JoinPoint {
-        function_name: "noop_aspect_function",
-        module_path: "benchmark",
-        location: Location {
+    let ctx = JoinPoint::new(
+        "noop_aspect_function",
+        "benchmark",
+        Location {
             file: "benches/aspect_overhead.rs",
             line: 0,
         },
-    };
+    );
 
     aspect.before(&ctx);
     let result = baseline_function(x);
@@ -93,14 +126,14 @@ fn noop_aspect_function(x: i32) -> i32 {
 #[inline(never)]
 fn simple_aspect_function(x: i32) -> i32 {
     let aspect = SimpleAspect::new();
-    let ctx = JoinPoint {
-        function_name: "simple_aspect_function",
-        module_path: "benchmark",
-        location: Location {
+    let ctx = JoinPoint::new(
+        "simple_aspect_function",
+        "benchmark",
+        Location {
             file: "benches/aspect_overhead.rs",
             line: 0,
         },
-    };
+    );
 
     aspect.before(&ctx);
     let result = baseline_function(x);
@@ -112,14 +145,14 @@ fn simple_aspect_function(x: i32) -> i32 {
 #[inline(never)]
 fn complex_aspect_function(x: i32) -> Result<i32, AspectError> {
     let aspect = ComplexAspect;
-    let ctx = JoinPoint {
-        function_name: "complex_aspect_function",
-        module_path: "benchmark",
-        location: Location {
+    let ctx = JoinPoint::new(
+        "complex_aspect_function",
+        "benchmark",
+        Location {
             file: "benches/aspect_overhead.rs",
             line: 0,
         },
-    };
+    );
 
     let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(baseline_function(x)) as Box<dyn Any>), ctx);
 
@@ -127,6 +160,89 @@ fn complex_aspect_function(x: i32) -> Result<i32, AspectError> {
     Ok(*result.downcast::<i32>().unwrap())
 }
 
+/// Instrumented by hand like [`CallCounter`] does. Like the woven
+/// functions, left for the compiler to inline, since `#[inline(never)]`
+/// would apply to the original function `#[aspect]` wraps.
+fn hand_instrumented_function(x: i32) -> i32 {
+    CALLS.fetch_add(1, Ordering::Relaxed);
+    let result = baseline_function(x);
+    CALLS.fetch_add(1, Ordering::Relaxed);
+    result
+}
+
+#[aspect(static StaticCallCounter)]
+fn static_woven_function(x: i32) -> i32 {
+    baseline_function(x)
+}
+
+#[aspect(CallCounter)]
+fn dynamic_woven_function(x: i32) -> i32 {
+    baseline_function(x)
+}
+
+#[inline(never)]
+fn fallible_function(x: i32) -> Result<i32, String> {
+    if x >= 0 {
+        Ok(x * 2)
+    } else {
+        Err(format!("negative: {}", x))
+    }
+}
+
+/// Instrumented by hand like [`CallCounter`] does for functions returning
+/// `Result`: exits are counted on success only.
+fn hand_instrumented_fallible(x: i32) -> Result<i32, String> {
+    CALLS.fetch_add(1, Ordering::Relaxed);
+    let result = fallible_function(x);
+    if result.is_ok() {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+    }
+    result
+}
+
+#[aspect(static StaticCallCounter)]
+fn static_woven_fallible(x: i32) -> Result<i32, String> {
+    fallible_function(x)
+}
+
+#[aspect(CallCounter)]
+fn dynamic_woven_fallible(x: i32) -> Result<i32, String> {
+    fallible_function(x)
+}
+
+#[inline(never)]
+async fn baseline_async(x: i32) -> i32 {
+    x * 2
+}
+
+async fn hand_instrumented_async(x: i32) -> i32 {
+    CALLS.fetch_add(1, Ordering::Relaxed);
+    let result = baseline_async(x).await;
+    CALLS.fetch_add(1, Ordering::Relaxed);
+    result
+}
+
+#[aspect(static StaticCallCounter)]
+async fn static_woven_async(x: i32) -> i32 {
+    baseline_async(x).await
+}
+
+#[aspect(CallCounter)]
+async fn dynamic_woven_async(x: i32) -> i32 {
+    baseline_async(x).await
+}
+
+/// The output of `future`, which must be ready the first time it is polled,
+/// so that no executor is measured.
+fn ready<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut context = Context::from_waker(Waker::noop());
+    match future.as_mut().poll(&mut context) {
+        Poll::Ready(output) => output,
+        Poll::Pending => unreachable!("benchmarked futures never wait"),
+    }
+}
+
 // ============================================================================
 // Benchmarks
 // ============================================================================
@@ -178,28 +294,28 @@ fn bench_aspect_overhead_comparison(c: &mut Criterion) {
 fn bench_joinpoint_creation(c: &mut Criterion) {
     c.bench_function("joinpoint_creation", |b| {
         b.iter(|| {
-            black_box(JoinPoint {
-                function_name: "test",
-                module_path: "test::module",
-                location: Location {
+            black_box(JoinPoint::new(
+                "test",
+                "test::module",
+                Location {
                     file: "test.rs",
                     line: 42,
                 },
-            })
+            ))
         })
     });
 }
 
 fn bench_proceedingjoinpoint(c: &mut Criterion) {
     c.bench_function("proceedingjoinpoint_proceed", |b| {
-        let ctx = JoinPoint {
-            function_name: "test",
-            module_path: "test::module",
-            location: Location {
+        let ctx = JoinPoint::new(
+            "test",
+            "test::module",
+            Location {
                 file: "test.rs",
                 line: 42,
             },
-        };
+        );
 
         b.iter(|| {
             let pjp =
@@ -209,6 +325,67 @@ fn bench_proceedingjoinpoint(c: &mut Criterion) {
     });
 }
 
+/// Functions woven by `#[aspect]`, statically and dynamically, against the
+/// same instrumentation written by hand, for plain, `Result` and `async`
+/// functions.
+fn bench_weaving_modes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("weaving/sync");
+    group.bench_function("hand_written", |b| {
+        b.iter(|| hand_instrumented_function(black_box(42)))
+    });
+    group.bench_function("static", |b| {
+        b.iter(|| static_woven_function(black_box(42)))
+    });
+    group.bench_function("dynamic", |b| {
+        b.iter(|| dynamic_woven_function(black_box(42)))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("weaving/result");
+    group.bench_function("hand_written", |b| {
+        b.iter(|| hand_instrumented_fallible(black_box(42)))
+    });
+    group.bench_function("static", |b| {
+        b.iter(|| static_woven_fallible(black_box(42)))
+    });
+    group.bench_function("dynamic", |b| {
+        b.iter(|| dynamic_woven_fallible(black_box(42)))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("weaving/async");
+    group.bench_function("hand_written", |b| {
+        b.iter(|| ready(hand_instrumented_async(black_box(42))))
+    });
+    group.bench_function("static", |b| {
+        b.iter(|| ready(static_woven_async(black_box(42))))
+    });
+    group.bench_function("dynamic", |b| {
+        b.iter(|| ready(dynamic_woven_async(black_box(42))))
+    });
+    group.finish();
+}
+
+fn bench_pointcut_matching(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pointcut_matching");
+    let pointcut = Pointcut::parse(
+        "(execution(pub fn save*(..)) || execution(pub fn *_user(..))) \
+         && within(crate::api) && !within(crate::api::internal)",
+    )
+    .unwrap();
+    let compiled = pointcut.compile();
+    let function = FunctionInfo::new("update_user", "crate::api::users", "pub");
+
+    group.bench_function("tree", |b| {
+        b.iter(|| black_box(&pointcut).matches(black_box(&function)))
+    });
+    group.bench_function("compiled", |b| {
+        b.iter(|| black_box(&compiled).matches(black_box(&function)))
+    });
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_baseline,
@@ -217,7 +394,9 @@ criterion_group!(
     bench_complex_aspect,
     bench_aspect_overhead_comparison,
     bench_joinpoint_creation,
-    bench_proceedingjoinpoint
+    bench_proceedingjoinpoint,
+    bench_weaving_modes,
+    bench_pointcut_matching
 );
 
 criterion_main!(benches);
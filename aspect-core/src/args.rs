@@ -0,0 +1,481 @@
+//! Captured function arguments.
+//!
+//! The `#[aspect]` macro records the arguments of every advised call in
+//! [`JoinPoint::args`](crate::JoinPoint::args), so aspects can key caches on
+//! them, validate them, or include them in logs. Calls none of whose aspects
+//! [read them](crate::Aspect::reads_args) have no arguments recorded.
+//!
+//! What is captured depends on the argument's type:
+//!
+//! - the name and type name are always available;
+//! - a hash is available when the type implements `Hash`, computed with
+//!   [`ArgHasher`];
+//! - a clone of the value is available when the type is
+//!   `Clone + Send + Sync + 'static`. For reference arguments (`&T`, `&str`)
+//!   the referent is captured as its owned form (`T`, `String`) when that
+//!   owned form qualifies.
+//!
+//! Arguments whose type mentions lifetimes, generic parameters, `impl Trait`
+//! or trait objects never have their value captured. Arguments bound by a
+//! pattern other than a name, such as `(a, b): (u32, u32)` or `_: T`, are
+//! captured whole, named `arg` followed by their position, e.g. `arg0`.
+//!
+//! Arguments whose type implements [`Redact`] are marked as redacted: their
+//! value is still captured (so they can be hashed or inspected), but they are
+//! never shown by `Debug`. With `std`, the rules of the `redaction` module
+//! redact more arguments, by name, type or value.
+
+use alloc::sync::Arc;
+use core::any::Any;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use siphasher::sip128::{Hasher128, SipHasher13};
+
+type DebugFn = fn(&(dyn Any + Send + Sync), &mut fmt::Formatter<'_>) -> fmt::Result;
+
+/// Marker for types holding secrets, such as passwords or tokens.
+///
+/// Captured arguments of these types are [redacted](Arg::is_redacted), so
+/// logging and audit aspects print them as `<redacted>` instead of their
+/// `Debug` representation.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::Redact;
+///
+/// #[derive(Clone, Debug)]
+/// struct Password(String);
+///
+/// impl Redact for Password {}
+/// ```
+pub trait Redact {}
+
+/// A captured function argument.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::Arg;
+///
+/// let arg = Arg::new("user_id", &42u64);
+/// assert_eq!(arg.name, "user_id");
+/// assert_eq!(arg.value::<u64>(), Some(&42));
+/// assert!(arg.hash().is_some());
+/// assert_eq!(format!("{:?}", arg), "user_id: 42");
+/// ```
+#[derive(Clone)]
+pub struct Arg {
+    /// The parameter name
+    pub name: &'static str,
+
+    /// The parameter type, as returned by `core::any::type_name`
+    pub type_name: &'static str,
+
+    hash: Option<u128>,
+    value: Option<Arc<dyn Any + Send + Sync>>,
+    debug: Option<DebugFn>,
+    redacted: bool,
+}
+
+impl Arg {
+    /// Creates a fully captured argument from a value.
+    pub fn new<T>(name: &'static str, value: &T) -> Self
+    where
+        T: Clone + Hash + fmt::Debug + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            type_name: core::any::type_name::<T>(),
+            hash: Some(hash_of(value)),
+            value: Some(Arc::new(value.clone())),
+            debug: Some(debug_fn::<T>),
+            redacted: false,
+        }
+    }
+
+    /// Creates an argument carrying only its name and type.
+    pub fn opaque(name: &'static str, type_name: &'static str) -> Self {
+        Self {
+            name,
+            type_name,
+            hash: None,
+            value: None,
+            debug: None,
+            redacted: false,
+        }
+    }
+
+    /// Marks the argument as holding a secret, hiding it from `Debug`.
+    pub fn redact(mut self) -> Self {
+        self.redacted = true;
+        self
+    }
+
+    /// Returns `true` if the argument holds a secret and must not be shown:
+    /// its type implements [`Redact`], it was [redacted](Self::redact), or
+    /// a rule registered in the `redaction` module redacts it (with `std`).
+    pub fn is_redacted(&self) -> bool {
+        #[cfg(feature = "std")]
+        if !self.redacted {
+            return crate::redaction::matches(self);
+        }
+        self.redacted
+    }
+
+    /// Returns the [`ArgHasher`] hash of the argument, if its type
+    /// implements `Hash`.
+    pub fn hash(&self) -> Option<u128> {
+        self.hash
+    }
+
+    /// Returns the captured value if it was captured and has type `T`.
+    pub fn value<T: Any>(&self) -> Option<&T> {
+        self.value.as_deref()?.downcast_ref()
+    }
+
+    /// Returns the captured value as `dyn Any`, if it was captured.
+    pub fn any(&self) -> Option<&(dyn Any + Send + Sync)> {
+        self.value.as_deref()
+    }
+
+    /// Returns a `Debug` view of the value, if it was captured, is `Debug`
+    /// and is not redacted.
+    pub fn debug(&self) -> Option<impl fmt::Debug + '_> {
+        if self.is_redacted() {
+            return None;
+        }
+        let value = self.value.as_deref()?;
+        let debug = self.debug?;
+        Some(DebugValue { value, debug })
+    }
+}
+
+impl fmt::Debug for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.debug() {
+            Some(value) => write!(f, "{}: {:?}", self.name, value),
+            None if self.is_redacted() => write!(f, "{}: <redacted>", self.name),
+            None => write!(f, "{}: <{}>", self.name, self.type_name),
+        }
+    }
+}
+
+struct DebugValue<'a> {
+    value: &'a (dyn Any + Send + Sync),
+    debug: DebugFn,
+}
+
+impl fmt::Debug for DebugValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.debug)(self.value, f)
+    }
+}
+
+/// The hasher of [`Arg::hash`], and of the keys aspects derive from the
+/// hashes of arguments.
+///
+/// It is SipHash-1-3 with fixed keys, both zero, and a 128-bit output.
+/// Unlike `DefaultHasher`, whose algorithm may change from one Rust release
+/// to the next, it gives a value the same hash in every build and process,
+/// so keys derived from it can be shared, e.g. in Redis; and its width
+/// makes distinct arguments with the same hash a practical impossibility.
+/// It is not meant to resist collisions crafted on purpose.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::{Arg, ArgHasher};
+/// use std::hash::Hash;
+///
+/// let mut hasher = ArgHasher::new();
+/// Arg::new("user_id", &42u64).hash().hash(&mut hasher);
+/// let key: u128 = hasher.finish128();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ArgHasher(SipHasher13);
+
+impl ArgHasher {
+    /// A hasher with nothing written yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The 128-bit hash of what was written.
+    pub fn finish128(&self) -> u128 {
+        self.0.finish128().into()
+    }
+}
+
+impl Hasher for ArgHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes);
+    }
+
+    /// The first 64 bits of [`finish128`](Self::finish128).
+    fn finish(&self) -> u64 {
+        self.finish128() as u64
+    }
+}
+
+fn hash_of<T: Hash + ?Sized>(value: &T) -> u128 {
+    let mut hasher = ArgHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish128()
+}
+
+fn debug_fn<T: fmt::Debug + 'static>(
+    value: &(dyn Any + Send + Sync),
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    match value.downcast_ref::<T>() {
+        Some(value) => fmt::Debug::fmt(value, f),
+        None => f.write_str("<?>"),
+    }
+}
+
+/// Support code for the `#[aspect]` macro. Not public API.
+///
+/// Capture uses autoref-based specialization: the macro calls probe methods
+/// on `&&&Probe(&arg)`, and method resolution picks the impl with the most
+/// capabilities the argument type actually has.
+#[doc(hidden)]
+pub mod __private {
+    use super::*;
+    use alloc::borrow::ToOwned;
+
+    pub struct Probe<'a, T: ?Sized>(pub &'a T);
+
+    impl<T: ?Sized> Probe<'_, T> {
+        pub fn type_name(&self) -> &'static str {
+            core::any::type_name::<T>()
+        }
+    }
+
+    pub trait HashCapture {
+        fn capture_hash(&self) -> Option<u128>;
+    }
+
+    impl<T: Hash + ?Sized> HashCapture for &Probe<'_, T> {
+        fn capture_hash(&self) -> Option<u128> {
+            Some(hash_of(self.0))
+        }
+    }
+
+    pub trait NoHashCapture {
+        fn capture_hash(&self) -> Option<u128>;
+    }
+
+    impl<T: ?Sized> NoHashCapture for Probe<'_, T> {
+        fn capture_hash(&self) -> Option<u128> {
+            None
+        }
+    }
+
+    pub type Captured = (Option<Arc<dyn Any + Send + Sync>>, Option<DebugFn>);
+
+    pub trait DebugValueCapture {
+        fn capture_value(&self) -> Captured;
+    }
+
+    impl<T: ToOwned + ?Sized> DebugValueCapture for &&Probe<'_, T>
+    where
+        T::Owned: fmt::Debug + Send + Sync + 'static,
+    {
+        fn capture_value(&self) -> Captured {
+            (
+                Some(Arc::new(self.0.to_owned())),
+                Some(debug_fn::<T::Owned>),
+            )
+        }
+    }
+
+    pub trait ValueCapture {
+        fn capture_value(&self) -> Captured;
+    }
+
+    impl<T: ToOwned + ?Sized> ValueCapture for &Probe<'_, T>
+    where
+        T::Owned: Send + Sync + 'static,
+    {
+        fn capture_value(&self) -> Captured {
+            (Some(Arc::new(self.0.to_owned())), None)
+        }
+    }
+
+    pub trait NoValueCapture {
+        fn capture_value(&self) -> Captured;
+    }
+
+    impl<T: ?Sized> NoValueCapture for Probe<'_, T> {
+        fn capture_value(&self) -> Captured {
+            (None, None)
+        }
+    }
+
+    pub trait RedactCapture {
+        fn capture_redacted(&self) -> bool;
+    }
+
+    impl<T: Redact + ?Sized> RedactCapture for &Probe<'_, T> {
+        fn capture_redacted(&self) -> bool {
+            true
+        }
+    }
+
+    pub trait NoRedactCapture {
+        fn capture_redacted(&self) -> bool;
+    }
+
+    impl<T: ?Sized> NoRedactCapture for Probe<'_, T> {
+        fn capture_redacted(&self) -> bool {
+            false
+        }
+    }
+
+    /// Builds an [`Arg`] from the results of the probes.
+    pub fn arg(
+        name: &'static str,
+        type_name: &'static str,
+        hash: Option<u128>,
+        (value, debug): Captured,
+        redacted: bool,
+    ) -> Arg {
+        Arg {
+            name,
+            type_name,
+            hash,
+            value,
+            debug,
+            redacted,
+        }
+    }
+}
+
+#[cfg(test)]
+// The extra borrows are what select the probe impl
+#[allow(clippy::needless_borrow)]
+mod tests {
+    use super::__private::*;
+    use super::*;
+
+    struct Opaque;
+
+    #[derive(Clone)]
+    struct NoDebug(u8);
+
+    #[test]
+    fn test_hash_is_stable() {
+        // Hashes must not change between builds, as shared stores key on
+        // them
+        assert_eq!(
+            format!("{:032x}", hash_of(&7u32)),
+            "bd3f140718d65d9680a6fd459ffc8e45"
+        );
+        assert_eq!(
+            format!("{:032x}", hash_of("alice")),
+            "834ce2acab83c03e312a7ba14848fa33"
+        );
+    }
+
+    #[test]
+    fn test_probe_full_capture() {
+        let x = 7u32;
+        let arg = arg(
+            "x",
+            Probe(&x).type_name(),
+            (&&Probe(&x)).capture_hash(),
+            (&&&Probe(&x)).capture_value(),
+            (&&Probe(&x)).capture_redacted(),
+        );
+
+        assert_eq!(arg.type_name, "u32");
+        assert_eq!(arg.hash(), Some(hash_of(&7u32)));
+        assert_eq!(arg.value::<u32>(), Some(&7));
+        assert_eq!(format!("{:?}", arg), "x: 7");
+    }
+
+    #[test]
+    fn test_probe_borrowed_str_captures_owned() {
+        let s: &str = "alice";
+        let arg = arg(
+            "name",
+            Probe(s).type_name(),
+            (&&Probe(s)).capture_hash(),
+            (&&&Probe(s)).capture_value(),
+            (&&Probe(s)).capture_redacted(),
+        );
+
+        assert_eq!(arg.type_name, "str");
+        assert_eq!(arg.hash(), Some(hash_of("alice")));
+        assert_eq!(arg.value::<String>().map(String::as_str), Some("alice"));
+    }
+
+    #[test]
+    fn test_probe_without_debug() {
+        let v = NoDebug(1);
+        let arg = arg(
+            "v",
+            Probe(&v).type_name(),
+            (&&Probe(&v)).capture_hash(),
+            (&&&Probe(&v)).capture_value(),
+            (&&Probe(&v)).capture_redacted(),
+        );
+
+        assert!(arg.hash().is_none());
+        assert_eq!(arg.value::<NoDebug>().map(|v| v.0), Some(1));
+        assert!(arg.debug().is_none());
+        assert!(format!("{:?}", arg).starts_with("v: <"));
+    }
+
+    #[test]
+    fn test_probe_opaque() {
+        let o = Opaque;
+        let arg = arg(
+            "o",
+            Probe(&o).type_name(),
+            (&&Probe(&o)).capture_hash(),
+            (&&&Probe(&o)).capture_value(),
+            (&&Probe(&o)).capture_redacted(),
+        );
+
+        assert!(arg.hash().is_none());
+        assert!(arg.any().is_none());
+    }
+
+    #[derive(Clone, Debug, Hash)]
+    struct Token(String);
+
+    impl Redact for Token {}
+
+    #[test]
+    fn test_probe_redacted() {
+        let t = Token("s3cr3t".to_string());
+        let arg = arg(
+            "token",
+            Probe(&t).type_name(),
+            (&&Probe(&t)).capture_hash(),
+            (&&&Probe(&t)).capture_value(),
+            (&&Probe(&t)).capture_redacted(),
+        );
+
+        assert!(arg.is_redacted());
+        assert!(arg.hash().is_some());
+        assert_eq!(arg.value::<Token>().map(|t| t.0.as_str()), Some("s3cr3t"));
+        assert!(arg.debug().is_none());
+        assert_eq!(format!("{:?}", arg), "token: <redacted>");
+        assert!(!(&&Probe(&1u8)).capture_redacted());
+    }
+
+    #[test]
+    fn test_arg_redact() {
+        let arg = Arg::new("password", &"hunter2".to_string()).redact();
+        assert_eq!(format!("{:?}", arg), "password: <redacted>");
+    }
+
+    #[test]
+    fn test_arg_value_wrong_type() {
+        let arg = Arg::new("id", &1u64);
+        assert_eq!(arg.value::<u32>(), None);
+    }
+}
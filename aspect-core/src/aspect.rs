@@ -5,7 +5,13 @@
 
 use crate::error::AspectError;
 use crate::joinpoint::{JoinPoint, ProceedingJoinPoint};
-use std::any::Any;
+use crate::snapshot::AspectSnapshot;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::any::Any;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
 
 /// The core trait for defining aspects.
 ///
@@ -114,6 +120,31 @@ pub trait Aspect: Send + Sync {
     /// ```
     fn after_error(&self, _ctx: &JoinPoint, _error: &AspectError) {}
 
+    /// Advice executed after the target function completes successfully,
+    /// before [`after`](Self::after), which may change the value it
+    /// returned.
+    ///
+    /// The value is that of the function, `T` for functions returning
+    /// `Result<T, E>`. [`ReturnValue`] only gives it as its own type, so
+    /// that advice cannot replace it with a value of another type, which
+    /// would fail the call. Aspects of higher precedence, which wrap the
+    /// others, see the value as those changed it. `#[aspect(static ...)]`
+    /// and the getters of `#[aspect_fields]` do not call it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use aspect_core::prelude::*;
+    /// # struct TrimAspect;
+    /// # impl Aspect for TrimAspect {
+    /// fn after_returning_mut(&self, _ctx: &JoinPoint, result: &mut ReturnValue<'_>) {
+    ///     // Normalize the strings the function returns
+    ///     result.update(|name: &mut String| *name = name.trim().to_string());
+    /// }
+    /// # }
+    /// ```
+    fn after_returning_mut(&self, _ctx: &JoinPoint, _result: &mut ReturnValue<'_>) {}
+
     /// Advice that wraps the entire target function execution.
     ///
     /// This is the most powerful advice type, allowing you to:
@@ -129,7 +160,10 @@ pub trait Aspect: Send + Sync {
     ///
     /// # Returns
     ///
-    /// The result of the function execution (or a modified result)
+    /// The result of the function execution (or a modified result). A
+    /// modified result must have the type of the function's result, `T` for
+    /// functions returning `Result<T, E>`; woven code reports a value of
+    /// another type as an error (see [`downcast_result`]).
     ///
     /// # Example
     ///
@@ -152,14 +186,13 @@ pub trait Aspect: Send + Sync {
     /// ```
     fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
         // Default implementation: call before, proceed, then after/after_error
-        let ctx = pjp.context().clone();
-
-        self.before(&ctx);
+        self.before(pjp.context());
 
-        let result = pjp.proceed();
+        let (mut result, ctx) = pjp.proceed_with_context();
 
-        match &result {
+        match &mut result {
             Ok(value) => {
+                self.after_returning_mut(&ctx, &mut ReturnValue::new(value.as_mut()));
                 self.after(&ctx, value.as_ref());
             }
             Err(error) => {
@@ -169,6 +202,1019 @@ pub trait Aspect: Send + Sync {
 
         result
     }
+
+    /// Advice that wraps an asynchronous execution, `proceed`, which runs
+    /// the target when awaited.
+    ///
+    /// This is what adapters driving asynchronous work, such as tower
+    /// services, call instead of [`around`](Self::around), which cannot wait
+    /// for a future without blocking. `#[aspect]` does not call it: see
+    /// [`AsyncAspect`] for `async fn`s.
+    ///
+    /// The default implementation calls `before`, awaits `proceed`, then
+    /// calls `after_returning_mut` and `after`, or `after_error`. Aspects
+    /// whose advice is in `around` override it to await `proceed` where
+    /// `around` proceeds.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use aspect_core::prelude::*;
+    /// # use aspect_core::aspect::BoxFuture;
+    /// # use std::any::Any;
+    /// # struct MyAspect;
+    /// # impl Aspect for MyAspect {
+    /// fn around_async<'a>(
+    ///     &'a self,
+    ///     ctx: &'a JoinPoint,
+    ///     proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    /// ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+    ///     Box::pin(async move {
+    ///         let start = std::time::Instant::now();
+    ///         let result = proceed.await;
+    ///         println!("{} took {:?}", ctx.function_name, start.elapsed());
+    ///         result
+    ///     })
+    /// }
+    /// # }
+    /// ```
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            self.before(ctx);
+            let mut result = proceed.await;
+            match &mut result {
+                Ok(value) => {
+                    self.after_returning_mut(ctx, &mut ReturnValue::new(value.as_mut()));
+                    self.after(ctx, value.as_ref());
+                }
+                Err(error) => self.after_error(ctx, error),
+            }
+            result
+        })
+    }
+
+    /// Where the aspect goes in a stack of aspects when no explicit order
+    /// is given: aspects of higher precedence wrap those of lower
+    /// precedence.
+    ///
+    /// The registry uses it for aspects registered with
+    /// `register_by_precedence`, and `#[aspect(A, B, ...)]` to order the
+    /// aspects it lists. The default is [`Precedence::DEFAULT`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use aspect_core::prelude::*;
+    /// # struct AuditAspect;
+    /// # impl Aspect for AuditAspect {
+    /// fn precedence(&self) -> Precedence {
+    ///     Precedence::OBSERVABILITY
+    /// }
+    /// # }
+    /// ```
+    fn precedence(&self) -> Precedence {
+        Precedence::DEFAULT
+    }
+
+    /// Whether the advice reads the arguments of the calls, in
+    /// [`JoinPoint::args`].
+    ///
+    /// `#[aspect]` captures the arguments of a call, which allocates, only
+    /// when one of the aspects of the function reads them; the others see
+    /// no arguments. The default is `false`: aspects reading the
+    /// arguments, or handing the joinpoint to code that may, such as a
+    /// user-provided key function, must override it to return `true`, or
+    /// they see `args` empty.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use aspect_core::prelude::*;
+    /// # struct ArgLogger;
+    /// # impl Aspect for ArgLogger {
+    /// fn before(&self, ctx: &JoinPoint) {
+    ///     println!("{} called with {:?}", ctx.function_name, ctx.args);
+    /// }
+    ///
+    /// fn reads_args(&self) -> bool {
+    ///     true
+    /// }
+    /// # }
+    /// ```
+    fn reads_args(&self) -> bool {
+        false
+    }
+
+    /// The live state of the aspect, for debugging, e.g. dumped with
+    /// `AspectRegistry::snapshot_all`.
+    ///
+    /// The default is an empty snapshot, named after the type of the
+    /// aspect; stateful aspects add their state to it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use aspect_core::prelude::*;
+    /// # use std::sync::atomic::{AtomicU64, Ordering};
+    /// # struct CallCounter { calls: AtomicU64 }
+    /// # impl Aspect for CallCounter {
+    /// fn snapshot(&self) -> AspectSnapshot {
+    ///     AspectSnapshot::new("CallCounter").with("calls", self.calls.load(Ordering::Relaxed))
+    /// }
+    /// # }
+    /// ```
+    fn snapshot(&self) -> AspectSnapshot {
+        AspectSnapshot::new(core::any::type_name_of_val(self))
+    }
+
+    /// The name the [reentrancy](crate::reentrancy) guard knows the aspect
+    /// by: woven functions called from its advice run without the aspects
+    /// of the same name.
+    ///
+    /// The default is the name of the type of the aspect. Aspects applying
+    /// another aspect, such as a wrapper rolling it out, return its name;
+    /// aspects applying several return `None`, and guard each of them.
+    fn reentrancy_name(&self) -> Option<&'static str> {
+        Some(core::any::type_name::<Self>())
+    }
+}
+
+/// The class of concerns an aspect belongs to, which decides where it goes
+/// in a stack of aspects when no explicit order is given.
+///
+/// A precedence is an execution order, as given to the registry: lower
+/// orders run first, wrapping higher ones. The classes, from the outermost,
+/// are:
+///
+/// 1. [`SECURITY`](Self::SECURITY): rejects unauthorized calls before they
+///    cost anything
+/// 2. [`RESILIENCE`](Self::RESILIENCE): rate limits, circuit breakers,
+///    retries and timeouts, protecting what is inside
+/// 3. [`CACHING`](Self::CACHING): answers without opening a transaction
+/// 4. [`TRANSACTION`](Self::TRANSACTION): wraps the call, and nothing else,
+///    in a transaction
+/// 5. [`DEFAULT`](Self::DEFAULT): aspects that declare nothing
+/// 6. [`OBSERVABILITY`](Self::OBSERVABILITY): logging, timing, metrics and
+///    tracing of the calls that ran
+///
+/// The classes are 100 apart, leaving room for orders between them, e.g.
+/// `Precedence::new(Precedence::SECURITY.order() + 10)` for an aspect that
+/// needs the caller authenticated by a security aspect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Precedence(i32);
+
+impl Precedence {
+    /// Authentication and authorization.
+    pub const SECURITY: Self = Self(-400);
+
+    /// Rate limits, circuit breakers, retries and timeouts.
+    pub const RESILIENCE: Self = Self(-300);
+
+    /// Caching of results.
+    pub const CACHING: Self = Self(-200);
+
+    /// Transactions.
+    pub const TRANSACTION: Self = Self(-100);
+
+    /// Aspects that declare no precedence.
+    pub const DEFAULT: Self = Self(0);
+
+    /// Logging, timing, metrics and tracing.
+    pub const OBSERVABILITY: Self = Self(100);
+
+    /// A precedence with the given execution order.
+    pub const fn new(order: i32) -> Self {
+        Self(order)
+    }
+
+    /// The execution order: lower orders run first, wrapping higher ones.
+    pub const fn order(self) -> i32 {
+        self.0
+    }
+
+    /// Whether the precedence is of the [`SECURITY`](Self::SECURITY)
+    /// class: lower than halfway to [`RESILIENCE`](Self::RESILIENCE).
+    /// Aspects of that class are never bypassed by the
+    /// [reentrancy](crate::reentrancy) guard.
+    pub const fn is_security(self) -> bool {
+        self.0 < (Self::SECURITY.0 + Self::RESILIENCE.0) / 2
+    }
+}
+
+impl Default for Precedence {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Unboxes the result that around advice returned for `function`, whose
+/// result has type `T`.
+///
+/// Advice may replace the result of a call, e.g. with a cached or fallback
+/// value, but only with a value of the same type. Woven code unboxes the
+/// result with this function, so that a value of another type fails the
+/// call with an [`AspectError::WeavingError`] naming the function and the
+/// expected type, rather than with a panic. Functions that do not return a
+/// `Result` have no way to return the error and still panic, with its
+/// message.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::aspect::downcast_result;
+/// use std::any::Any;
+///
+/// let result: Box<dyn Any> = Box::new(42u32);
+/// assert_eq!(downcast_result::<u32>("answer", result).unwrap(), 42);
+///
+/// let replaced: Box<dyn Any> = Box::new("forty-two");
+/// let err = downcast_result::<u32>("answer", replaced).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "Weaving error: aspect around() replaced the result of answer \
+///      with a value that is not a u32"
+/// );
+/// ```
+pub fn downcast_result<T: 'static>(function: &str, result: Box<dyn Any>) -> Result<T, AspectError> {
+    match result.downcast::<T>() {
+        Ok(result) => Ok(*result),
+        Err(_) => Err(AspectError::weaving(alloc::format!(
+            "aspect around() replaced the result of {} with a value that is not a {}",
+            function,
+            core::any::type_name::<T>()
+        ))),
+    }
+}
+
+/// The value a function returned, as [`Aspect::after_returning_mut`] sees
+/// it: typed accessors over a `&mut dyn Any`, which keep the value of its
+/// type.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::ReturnValue;
+///
+/// let mut answer = 41u32;
+/// let mut result = ReturnValue::new(&mut answer);
+/// assert!(result.update(|n: &mut u32| *n += 1));
+/// assert_eq!(result.get::<u32>(), Some(&42));
+///
+/// // Values of other types are left alone
+/// assert!(!result.update(|s: &mut String| s.clear()));
+/// assert_eq!(result.replace(String::from("42")), Err(String::from("42")));
+/// assert_eq!(result.replace(7u32), Ok(42));
+/// assert_eq!(answer, 7);
+/// ```
+pub struct ReturnValue<'a> {
+    value: &'a mut dyn Any,
+}
+
+impl<'a> ReturnValue<'a> {
+    /// The return value `value`.
+    pub fn new(value: &'a mut dyn Any) -> Self {
+        Self { value }
+    }
+
+    /// Whether the value is a `T`.
+    pub fn is<T: Any>(&self) -> bool {
+        self.value.is::<T>()
+    }
+
+    /// The value, if it is a `T`.
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+
+    /// The value, to change in place, if it is a `T`.
+    pub fn get_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.value.downcast_mut()
+    }
+
+    /// Changes the value with `f` if it is a `T`, returning whether it is.
+    pub fn update<T: Any>(&mut self, f: impl FnOnce(&mut T)) -> bool {
+        self.get_mut().map(f).is_some()
+    }
+
+    /// Replaces the value with `value` if it is a `T`, returning the
+    /// previous one, or `value` back otherwise.
+    pub fn replace<T: Any>(&mut self, value: T) -> Result<T, T> {
+        match self.get_mut() {
+            Some(current) => Ok(core::mem::replace(current, value)),
+            None => Err(value),
+        }
+    }
+}
+
+impl fmt::Debug for ReturnValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReturnValue").finish_non_exhaustive()
+    }
+}
+
+/// A boxed future, as returned by asynchronous advice.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Asynchronous advice for `async fn`s.
+///
+/// Around advice cannot wrap an `async fn` yet, so `#[aspect]` only calls
+/// [`Aspect::before`], [`Aspect::after_returning_mut`], [`Aspect::after`]
+/// and [`Aspect::after_error`] for them. Aspects that need to await
+/// something before the function runs, such as a permission lookup,
+/// implement this trait as well: for an `async fn`, `#[aspect]` awaits
+/// [`before_async`](Self::before_async) right after `before`, and does not
+/// run the function if it fails. A function returning `Result` then
+/// returns the error; any other function panics.
+///
+/// Synchronous functions never call `before_async`.
+///
+/// The signature of `before_async` is the one `#[async_trait]` gives to
+/// `async fn before_async(&self, ctx: &JoinPoint) -> Result<(), AspectError>`,
+/// so that the trait can be implemented with the [async-trait] crate as
+/// well as by hand. Either way, `Arc<dyn AsyncAspect>` is an aspect
+/// object, which [`AsyncAdapter`] also makes of synchronous aspects.
+///
+/// [async-trait]: https://docs.rs/async-trait
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::prelude::*;
+/// use aspect_core::aspect::BoxFuture;
+///
+/// struct QuotaAspect;
+///
+/// impl Aspect for QuotaAspect {}
+///
+/// impl AsyncAspect for QuotaAspect {
+///     fn before_async<'s, 'c, 'f>(
+///         &'s self,
+///         ctx: &'c JoinPoint,
+///     ) -> BoxFuture<'f, Result<(), AspectError>>
+///     where
+///         's: 'f,
+///         'c: 'f,
+///         Self: 'f,
+///     {
+///         Box::pin(async move {
+///             // e.g. ask a quota service whether `ctx.function_name` may run
+///             Ok(())
+///         })
+///     }
+/// }
+/// ```
+///
+/// With `#[async_trait]`:
+///
+/// ```rust,ignore
+/// #[async_trait::async_trait]
+/// impl AsyncAspect for QuotaAspect {
+///     async fn before_async(&self, ctx: &JoinPoint) -> Result<(), AspectError> {
+///         quota::check(ctx.function_name).await
+///     }
+/// }
+/// ```
+pub trait AsyncAspect: Aspect {
+    /// Advice awaited before the target `async fn` runs; an error prevents
+    /// the call.
+    fn before_async<'s, 'c, 'f>(
+        &'s self,
+        ctx: &'c JoinPoint,
+    ) -> BoxFuture<'f, Result<(), AspectError>>
+    where
+        's: 'f,
+        'c: 'f,
+        Self: 'f;
+}
+
+/// A synchronous aspect as an [`AsyncAspect`], whose `before_async` does
+/// nothing, so that registries of `Arc<dyn AsyncAspect>` can hold both.
+///
+/// All the advice of the aspect is delegated to it, `around_async`
+/// included.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::prelude::*;
+/// use aspect_core::aspect::AsyncAdapter;
+/// use std::sync::Arc;
+///
+/// struct Tracer;
+///
+/// impl Aspect for Tracer {}
+///
+/// let aspects: Vec<Arc<dyn AsyncAspect>> = vec![Arc::new(AsyncAdapter::new(Tracer))];
+/// ```
+pub struct AsyncAdapter<A: ?Sized> {
+    aspect: Arc<A>,
+}
+
+impl<A: Aspect> AsyncAdapter<A> {
+    /// Lift `aspect` into an [`AsyncAspect`].
+    pub fn new(aspect: A) -> Self {
+        Self::shared(Arc::new(aspect))
+    }
+}
+
+impl<A: Aspect + ?Sized> AsyncAdapter<A> {
+    /// Lift an aspect shared with other code, e.g. an `Arc<dyn Aspect>`,
+    /// into an [`AsyncAspect`].
+    pub fn shared(aspect: Arc<A>) -> Self {
+        Self { aspect }
+    }
+
+    /// The lifted aspect.
+    pub fn inner(&self) -> &Arc<A> {
+        &self.aspect
+    }
+}
+
+impl<A: ?Sized> Clone for AsyncAdapter<A> {
+    fn clone(&self) -> Self {
+        Self {
+            aspect: Arc::clone(&self.aspect),
+        }
+    }
+}
+
+impl<A: Aspect + ?Sized> Aspect for AsyncAdapter<A> {
+    fn before(&self, ctx: &JoinPoint) {
+        self.aspect.before(ctx)
+    }
+
+    fn after(&self, ctx: &JoinPoint, result: &dyn Any) {
+        self.aspect.after(ctx, result)
+    }
+
+    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
+        self.aspect.after_error(ctx, error)
+    }
+
+    fn after_returning_mut(&self, ctx: &JoinPoint, result: &mut ReturnValue<'_>) {
+        self.aspect.after_returning_mut(ctx, result)
+    }
+
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        self.aspect.around(pjp)
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        self.aspect.around_async(ctx, proceed)
+    }
+
+    fn precedence(&self) -> Precedence {
+        self.aspect.precedence()
+    }
+
+    fn reads_args(&self) -> bool {
+        self.aspect.reads_args()
+    }
+
+    fn snapshot(&self) -> AspectSnapshot {
+        self.aspect.snapshot()
+    }
+
+    fn reentrancy_name(&self) -> Option<&'static str> {
+        self.aspect.reentrancy_name()
+    }
+}
+
+impl<A: Aspect + ?Sized> AsyncAspect for AsyncAdapter<A> {
+    fn before_async<'s, 'c, 'f>(
+        &'s self,
+        _ctx: &'c JoinPoint,
+    ) -> BoxFuture<'f, Result<(), AspectError>>
+    where
+        's: 'f,
+        'c: 'f,
+        Self: 'f,
+    {
+        Box::pin(core::future::ready(Ok(())))
+    }
+}
+
+/// An aspect whose advice is called directly on the concrete types of the
+/// function, for `#[aspect(static ...)]`.
+///
+/// [`Aspect`] is object safe: its advice takes results as `&dyn Any` and
+/// `around` receives the function as a boxed [`ProceedingJoinPoint`]. The
+/// advice of a `StaticAspect` is generic over the result type instead, and
+/// `around` receives the function as a closure, so that `#[aspect(static
+/// ...)]`, which knows the type of the aspect at compile time, weaves
+/// calls that inline like hand-written instrumentation: no `Arc<dyn
+/// Aspect>`, no `Box<dyn Any>`, no allocation. The join point is built on
+/// the stack, without the arguments, whose capture allocates.
+///
+/// For an `async fn`, `#[aspect(static ...)]` calls `before` and `after`
+/// around the awaited function, not `around`.
+///
+/// The trait is not in the prelude, where its methods would be ambiguous
+/// with those of [`Aspect`] for types implementing both.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::prelude::*;
+/// use aspect_core::StaticAspect;
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// static CALLS: AtomicU64 = AtomicU64::new(0);
+///
+/// struct CallCounter;
+///
+/// impl StaticAspect for CallCounter {
+///     fn before(&self, _ctx: &JoinPoint) {
+///         CALLS.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// # let ctx = JoinPoint::new("answer", "my::mod", Location { file: "a.rs", line: 1 });
+/// // What `#[aspect(static CallCounter)]` generates for `fn answer() -> u32`
+/// let answer = StaticAspect::around(&CallCounter, &ctx, || 42);
+/// assert_eq!((answer, CALLS.load(Ordering::Relaxed)), (42, 1));
+/// ```
+pub trait StaticAspect {
+    /// Advice executed before the target function runs.
+    fn before(&self, _ctx: &JoinPoint) {}
+
+    /// Advice executed after the target function returns `result`.
+    fn after<R>(&self, _ctx: &JoinPoint, _result: &R) {}
+
+    /// Advice wrapping the target function, which `proceed` runs.
+    ///
+    /// The default implementation calls `before`, the function and `after`.
+    fn around<R>(&self, ctx: &JoinPoint, proceed: impl FnOnce() -> R) -> R {
+        self.before(ctx);
+        let result = proceed();
+        self.after(ctx, &result);
+        result
+    }
+
+    /// The name the [reentrancy](crate::reentrancy) guard knows the aspect
+    /// by, as for [`Aspect::reentrancy_name`].
+    fn reentrancy_name(&self) -> Option<&'static str> {
+        Some(core::any::type_name::<Self>())
+    }
+}
+
+/// Lets `#[aspect(static ...)]` take a reference, e.g. to a `static` aspect.
+impl<T: StaticAspect + ?Sized> StaticAspect for &T {
+    fn before(&self, ctx: &JoinPoint) {
+        (**self).before(ctx)
+    }
+
+    fn after<R>(&self, ctx: &JoinPoint, result: &R) {
+        (**self).after(ctx, result)
+    }
+
+    fn around<R>(&self, ctx: &JoinPoint, proceed: impl FnOnce() -> R) -> R {
+        (**self).around(ctx, proceed)
+    }
+
+    fn reentrancy_name(&self) -> Option<&'static str> {
+        (**self).reentrancy_name()
+    }
+}
+
+/// An aspect confined to the thread it runs on, for `#[aspect(local ...)]`
+/// and the thread-local registry of `aspect-runtime`.
+///
+/// [`Aspect`] requires `Send + Sync`, which rules out aspects holding `Rc`
+/// based caches, `RefCell`s or GUI handles. A `LocalAspect` has the same
+/// advice without the bounds; in exchange it is only ever called on one
+/// thread: `#[aspect(local ...)]` evaluates it on the calling thread, e.g.
+/// as a clone of a `thread_local!` aspect, of which each thread has its own
+/// instance.
+///
+/// Every [`Aspect`] is a `LocalAspect` too. The trait is neither in the
+/// prelude nor at the crate root, where glob imports would make the methods
+/// of [`Aspect`] ambiguous.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::prelude::*;
+/// use aspect_core::aspect::LocalAspect;
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+///
+/// /// Collects the names of the called functions for a view on this thread.
+/// #[derive(Clone)]
+/// struct CallLog(Rc<RefCell<Vec<&'static str>>>);
+///
+/// impl LocalAspect for CallLog {
+///     fn before(&self, ctx: &JoinPoint) {
+///         self.0.borrow_mut().push(ctx.function_name);
+///     }
+/// }
+///
+/// thread_local! {
+///     static CALL_LOG: CallLog = CallLog(Rc::default());
+/// }
+///
+/// // What `#[aspect(local CALL_LOG.with(CallLog::clone))]` does for
+/// // `fn refresh()`
+/// let log = CALL_LOG.with(CallLog::clone);
+/// # let ctx = JoinPoint::new("refresh", "my::ui", Location { file: "a.rs", line: 1 });
+/// let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn std::any::Any>), ctx);
+/// LocalAspect::around(&log, pjp).unwrap();
+/// assert_eq!(*log.0.borrow(), ["refresh"]);
+/// ```
+pub trait LocalAspect {
+    /// Advice executed before the target function runs.
+    fn before(&self, _ctx: &JoinPoint) {}
+
+    /// Advice executed after the target function completes successfully.
+    fn after(&self, _ctx: &JoinPoint, _result: &dyn Any) {}
+
+    /// Advice executed when the target function fails.
+    fn after_error(&self, _ctx: &JoinPoint, _error: &AspectError) {}
+
+    /// Advice executed before `after`, which may change the value the
+    /// target function returned, as [`Aspect::after_returning_mut`].
+    fn after_returning_mut(&self, _ctx: &JoinPoint, _result: &mut ReturnValue<'_>) {}
+
+    /// Advice that wraps the entire target function execution.
+    ///
+    /// The default implementation calls `before`, proceeds, then calls
+    /// `after_returning_mut` and `after`, or `after_error`, like
+    /// [`Aspect::around`].
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        self.before(pjp.context());
+        let (mut result, ctx) = pjp.proceed_with_context();
+        match &mut result {
+            Ok(value) => {
+                self.after_returning_mut(&ctx, &mut ReturnValue::new(value.as_mut()));
+                self.after(&ctx, value.as_ref());
+            }
+            Err(error) => self.after_error(&ctx, error),
+        }
+        result
+    }
+
+    /// Where the aspect goes in a stack of aspects when no explicit order
+    /// is given, as for [`Aspect::precedence`].
+    fn precedence(&self) -> Precedence {
+        Precedence::DEFAULT
+    }
+
+    /// Whether the advice reads the arguments of the calls, as for
+    /// [`Aspect::reads_args`].
+    fn reads_args(&self) -> bool {
+        false
+    }
+
+    /// The name the [reentrancy](crate::reentrancy) guard knows the aspect
+    /// by, as for [`Aspect::reentrancy_name`].
+    fn reentrancy_name(&self) -> Option<&'static str> {
+        Some(core::any::type_name::<Self>())
+    }
+}
+
+impl<A: Aspect + ?Sized> LocalAspect for A {
+    fn before(&self, ctx: &JoinPoint) {
+        Aspect::before(self, ctx)
+    }
+
+    fn after(&self, ctx: &JoinPoint, result: &dyn Any) {
+        Aspect::after(self, ctx, result)
+    }
+
+    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
+        Aspect::after_error(self, ctx, error)
+    }
+
+    fn after_returning_mut(&self, ctx: &JoinPoint, result: &mut ReturnValue<'_>) {
+        Aspect::after_returning_mut(self, ctx, result)
+    }
+
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        Aspect::around(self, pjp)
+    }
+
+    fn precedence(&self) -> Precedence {
+        Aspect::precedence(self)
+    }
+
+    fn reads_args(&self) -> bool {
+        Aspect::reads_args(self)
+    }
+
+    fn reentrancy_name(&self) -> Option<&'static str> {
+        Aspect::reentrancy_name(self)
+    }
+}
+
+/// Support code for the `#[aspect]` macro. Not public API.
+///
+/// The macro calls `(&&AsyncProbe(&aspect)).before_async(ctx)`; autoref-based
+/// specialization picks [`AsyncAspect::before_async`] when the aspect
+/// implements it and a no-op otherwise.
+///
+/// Errors of woven functions returning `Result` do not go through the
+/// aspects: the woven code keeps the error aside and gives the aspects a
+/// `function_error` standing for it, which it swaps back for the original
+/// error if the aspects return it. An error the aspects return instead is
+/// converted with `(&&&ErrorProbe::of(..)).error_from_aspect(..)`, which
+/// picks `From<AspectError>`, then `From<String>` with its `Debug` text,
+/// and fails to compile for error types with neither.
+#[doc(hidden)]
+pub mod __private {
+    use super::*;
+    use crate::reentrancy;
+    use alloc::string::String;
+    use core::fmt;
+    use core::marker::PhantomData;
+
+    /// The source of the error [`function_error`] creates.
+    #[derive(Debug)]
+    pub struct FunctionError;
+
+    impl fmt::Display for FunctionError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("error returned by the advised function")
+        }
+    }
+
+    impl core::error::Error for FunctionError {}
+
+    /// The error the aspects see for `error`, returned by the function.
+    pub fn function_error<E: fmt::Debug>(error: &E) -> AspectError {
+        AspectError::execution_with_source(alloc::format!("{:?}", error), FunctionError)
+    }
+
+    /// Whether `error` is one [`function_error`] created.
+    pub fn is_function_error(error: &AspectError) -> bool {
+        match error {
+            AspectError::ExecutionError {
+                source: Some(source),
+                ..
+            } => source.is::<FunctionError>(),
+            _ => false,
+        }
+    }
+
+    /// The payload of a panic of the function of `#[aspect(unwind ...)]`,
+    /// kept aside to resume the panic once the aspects saw it.
+    #[cfg(feature = "std")]
+    pub type PanicPayload = Box<dyn Any + Send>;
+
+    /// Run `f`, the function of `#[aspect(unwind ...)]`. When it panics,
+    /// the payload is kept in `slot` and the aspects get the error of the
+    /// panic.
+    #[cfg(feature = "std")]
+    pub fn catch_panic<T>(
+        slot: &mut Option<PanicPayload>,
+        f: impl FnOnce() -> T,
+    ) -> Result<T, AspectError> {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).map_err(|payload| {
+            let error = panic_error(&*payload);
+            *slot = Some(payload);
+            error
+        })
+    }
+
+    /// The error the aspects see for a panic of the function.
+    #[cfg(feature = "std")]
+    pub fn panic_error(payload: &(dyn Any + Send)) -> AspectError {
+        AspectError::panic(payload, std::backtrace::Backtrace::disabled())
+    }
+
+    /// Await `future`, the async function of `#[aspect(unwind ...)]`,
+    /// returning the payload of its panic, if it panics, as error.
+    #[cfg(feature = "std")]
+    pub async fn catch_unwind<F: Future>(future: F) -> Result<F::Output, PanicPayload> {
+        use core::task::Poll;
+
+        let mut future = core::pin::pin!(future);
+        core::future::poll_fn(|cx| {
+            match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                future.as_mut().poll(cx)
+            })) {
+                Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+                Ok(Poll::Pending) => Poll::Pending,
+                Err(payload) => Poll::Ready(Err(payload)),
+            }
+        })
+        .await
+    }
+
+    /// The indexes of `aspects` by [`Aspect::precedence`], the outermost
+    /// first; aspects of the same precedence keep their order.
+    pub fn by_precedence<const N: usize>(aspects: &[&dyn Aspect; N]) -> [usize; N] {
+        let mut order: [usize; N] = core::array::from_fn(|i| i);
+        order.sort_by_key(|&i| aspects[i].precedence().order());
+        order
+    }
+
+    /// The indexes of the aspects of `aspects` to weave into a call made
+    /// now, by precedence as [`by_precedence`] orders them, in the first
+    /// `len` of them: those the [reentrancy](crate::reentrancy) guard does
+    /// not skip.
+    pub fn woven<const N: usize>(aspects: &[&dyn Aspect; N]) -> ([usize; N], usize) {
+        let mut order = by_precedence(aspects);
+        let mut len = 0;
+        for i in 0..N {
+            if !reentrancy::skips(aspects[order[i]]) {
+                order.swap(len, i);
+                len += 1;
+            }
+        }
+        (order, len)
+    }
+
+    /// The aspects of `#[aspect(A, B, ...)]`, woven by precedence, but for
+    /// those the [reentrancy](crate::reentrancy) guard skips.
+    pub struct Stack<'a, const N: usize> {
+        aspects: [&'a dyn Aspect; N],
+        order: [usize; N],
+        len: usize,
+    }
+
+    impl<'a, const N: usize> Stack<'a, N> {
+        pub fn new(aspects: [&'a dyn Aspect; N]) -> Self {
+            let (order, len) = woven(&aspects);
+            Self {
+                aspects,
+                order,
+                len,
+            }
+        }
+
+        /// Run `pjp` through the aspects from the `depth`th outermost.
+        fn around_from(
+            &self,
+            depth: usize,
+            pjp: ProceedingJoinPoint,
+        ) -> Result<Box<dyn Any>, AspectError> {
+            let Some(&index) = self.order[..self.len].get(depth) else {
+                return pjp.proceed();
+            };
+            let aspect = self.aspects[index];
+            let ctx = pjp.context().clone();
+            let mut inner = Some(reentrancy::proceeding(move || {
+                self.around_from(depth + 1, pjp)
+            }));
+            let mut proceed = || (inner.take().expect("proceeded more than once"))();
+            reentrancy::advice(aspect, || {
+                aspect.around(ProceedingJoinPoint::borrowed(&mut proceed, ctx))
+            })
+        }
+    }
+
+    impl<const N: usize> Aspect for Stack<'_, N> {
+        fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+            self.around_from(0, pjp)
+        }
+
+        fn reads_args(&self) -> bool {
+            self.aspects.iter().any(|aspect| aspect.reads_args())
+        }
+
+        /// None: each of the aspects is guarded as it advises.
+        fn reentrancy_name(&self) -> Option<&'static str> {
+            None
+        }
+    }
+
+    /// The aspect of `#[aspect(local ...)]`, whose [`LocalAspect`] advice
+    /// the woven code calls as it calls that of an [`Aspect`].
+    pub struct Local<'a, A: ?Sized> {
+        pub aspect: &'a A,
+    }
+
+    impl<A: LocalAspect + ?Sized> Local<'_, A> {
+        pub fn before(&self, ctx: &JoinPoint) {
+            LocalAspect::before(self.aspect, ctx)
+        }
+
+        pub fn after(&self, ctx: &JoinPoint, result: &dyn Any) {
+            LocalAspect::after(self.aspect, ctx, result)
+        }
+
+        pub fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
+            LocalAspect::after_error(self.aspect, ctx, error)
+        }
+
+        pub fn after_returning_mut(&self, ctx: &JoinPoint, result: &mut ReturnValue<'_>) {
+            LocalAspect::after_returning_mut(self.aspect, ctx, result)
+        }
+
+        pub fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+            LocalAspect::around(self.aspect, pjp)
+        }
+
+        pub fn reads_args(&self) -> bool {
+            LocalAspect::reads_args(self.aspect)
+        }
+
+        pub fn precedence(&self) -> Precedence {
+            LocalAspect::precedence(self.aspect)
+        }
+
+        pub fn reentrancy_name(&self) -> Option<&'static str> {
+            LocalAspect::reentrancy_name(self.aspect)
+        }
+    }
+
+    /// The error type `E` of a woven function.
+    pub struct ErrorProbe<E>(PhantomData<fn() -> E>);
+
+    impl<E> ErrorProbe<E> {
+        /// The probe for the error kept in `slot`.
+        pub fn of(_slot: &Option<E>) -> Self {
+            Self(PhantomData)
+        }
+
+        /// The probe for the error of the function returning `future`.
+        pub fn of_future<T, F: Future<Output = Result<T, E>>>(_future: &F) -> Self {
+            Self(PhantomData)
+        }
+    }
+
+    pub trait FromAspectError<E> {
+        fn error_from_aspect(&self, function: &str, error: AspectError) -> E;
+    }
+
+    impl<E: From<AspectError>> FromAspectError<E> for &&ErrorProbe<E> {
+        fn error_from_aspect(&self, _function: &str, error: AspectError) -> E {
+            E::from(error)
+        }
+    }
+
+    pub trait FromAspectErrorText<E> {
+        fn error_from_aspect(&self, function: &str, error: AspectError) -> E;
+    }
+
+    impl<E: From<String>> FromAspectErrorText<E> for &ErrorProbe<E> {
+        fn error_from_aspect(&self, _function: &str, error: AspectError) -> E {
+            E::from(alloc::format!("{:?}", error))
+        }
+    }
+
+    /// Implemented by no type: the bound the woven code puts on error types
+    /// with neither `From<AspectError>` nor `From<String>`, failing to
+    /// compile rather than when an aspect first fails a call.
+    #[diagnostic::on_unimplemented(
+        message = "the error type `{Self}` of a woven function cannot be built from an `AspectError`",
+        label = "an aspect failing this call needs an error of type `{Self}`",
+        note = "implement `From<AspectError>` or `From<String>` for `{Self}`"
+    )]
+    pub trait ErrorFromAspect {}
+
+    pub trait NoFromAspectError<E> {
+        fn error_from_aspect(&self, function: &str, error: AspectError) -> E
+        where
+            E: ErrorFromAspect;
+    }
+
+    impl<E> NoFromAspectError<E> for ErrorProbe<E> {
+        fn error_from_aspect(&self, _function: &str, _error: AspectError) -> E
+        where
+            E: ErrorFromAspect,
+        {
+            unreachable!("no error type implements ErrorFromAspect")
+        }
+    }
+
+    pub struct AsyncProbe<'a, T>(pub &'a T);
+
+    pub trait AsyncBefore {
+        fn before_async<'a>(&'a self, ctx: &'a JoinPoint)
+            -> BoxFuture<'a, Result<(), AspectError>>;
+    }
+
+    impl<T: AsyncAspect> AsyncBefore for &AsyncProbe<'_, T> {
+        fn before_async<'a>(
+            &'a self,
+            ctx: &'a JoinPoint,
+        ) -> BoxFuture<'a, Result<(), AspectError>> {
+            self.0.before_async(ctx)
+        }
+    }
+
+    pub trait NoAsyncBefore {
+        fn before_async<'a>(&'a self, ctx: &'a JoinPoint)
+            -> BoxFuture<'a, Result<(), AspectError>>;
+    }
+
+    impl<T> NoAsyncBefore for AsyncProbe<'_, T> {
+        fn before_async<'a>(
+            &'a self,
+            _ctx: &'a JoinPoint,
+        ) -> BoxFuture<'a, Result<(), AspectError>> {
+            Box::pin(core::future::ready(Ok(())))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -201,4 +1247,164 @@ mod tests {
         assert_send::<CountingAspect>();
         assert_sync::<CountingAspect>();
     }
+
+    struct DenyAll;
+
+    impl Aspect for DenyAll {}
+
+    impl AsyncAspect for DenyAll {
+        fn before_async<'s, 'c, 'f>(
+            &'s self,
+            _ctx: &'c JoinPoint,
+        ) -> BoxFuture<'f, Result<(), AspectError>>
+        where
+            's: 'f,
+            'c: 'f,
+            Self: 'f,
+        {
+            Box::pin(async { Err(AspectError::execution("denied")) })
+        }
+    }
+
+    /// Polls a future that is ready without waiting.
+    fn now<T>(future: BoxFuture<'_, T>) -> T {
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        let mut future = future;
+        match future.as_mut().poll(&mut cx) {
+            std::task::Poll::Ready(value) => value,
+            std::task::Poll::Pending => panic!("future not ready"),
+        }
+    }
+
+    /// Records advice by the size of the results it sees.
+    #[derive(Default)]
+    struct SizeAspect {
+        log: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl StaticAspect for SizeAspect {
+        fn before(&self, ctx: &JoinPoint) {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("before {}", ctx.function_name));
+        }
+
+        fn after<R>(&self, _ctx: &JoinPoint, result: &R) {
+            let size = std::mem::size_of_val(result);
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("after {} bytes", size));
+        }
+    }
+
+    #[test]
+    fn test_static_aspect_around() {
+        let ctx = JoinPoint::new(
+            "f",
+            "test",
+            crate::joinpoint::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        );
+        let aspect = SizeAspect::default();
+        assert_eq!(aspect.around(&ctx, || 7u64), 7);
+        assert_eq!(StaticAspect::around(&aspect, &ctx, || ()), ());
+        assert_eq!(
+            *aspect.log.lock().unwrap(),
+            ["before f", "after 8 bytes", "before f", "after 0 bytes"]
+        );
+    }
+
+    #[test]
+    #[allow(clippy::needless_borrow)]
+    fn test_async_probe_dispatch() {
+        use __private::{AsyncBefore as _, AsyncProbe, NoAsyncBefore as _};
+
+        let ctx = JoinPoint::new(
+            "f",
+            "test",
+            crate::joinpoint::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        );
+
+        let deny = DenyAll;
+        let probe = AsyncProbe(&deny);
+        assert!(now((&&probe).before_async(&ctx)).is_err());
+
+        let counting = CountingAspect::default();
+        let probe = AsyncProbe(&counting);
+        assert!(now((&&probe).before_async(&ctx)).is_ok());
+    }
+
+    #[test]
+    fn test_around_async() {
+        let ctx = JoinPoint::new(
+            "f",
+            "test",
+            crate::joinpoint::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        );
+        let counting = CountingAspect::default();
+        let aspect: &dyn Aspect = &counting;
+
+        let proceed = Box::pin(async { Ok(Box::new(42) as Box<dyn Any>) });
+        let result = now(aspect.around_async(&ctx, proceed)).unwrap();
+        assert_eq!(result.downcast_ref::<i32>(), Some(&42));
+
+        let proceed = Box::pin(async { Err(AspectError::execution("failed")) });
+        assert!(now(aspect.around_async(&ctx, proceed)).is_err());
+
+        let count = |counter: &std::sync::atomic::AtomicUsize| {
+            counter.load(std::sync::atomic::Ordering::SeqCst)
+        };
+        assert_eq!(count(&counting.before_count), 2);
+        assert_eq!(count(&counting.after_count), 1);
+    }
+
+    #[test]
+    fn test_async_adapter() {
+        let ctx = JoinPoint::new(
+            "f",
+            "test",
+            crate::joinpoint::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        );
+        let counting = Arc::new(CountingAspect::default());
+        let aspects: Vec<Arc<dyn AsyncAspect>> = vec![
+            Arc::new(AsyncAdapter::shared(counting.clone())),
+            Arc::new(DenyAll),
+        ];
+
+        assert!(now(aspects[0].before_async(&ctx)).is_ok());
+        assert!(now(aspects[1].before_async(&ctx)).is_err());
+
+        // The advice of the lifted aspect runs
+        let proceed = Box::pin(async { Ok(Box::new(42) as Box<dyn Any>) });
+        assert!(now(aspects[0].around_async(&ctx, proceed)).is_ok());
+        let count = |counter: &std::sync::atomic::AtomicUsize| {
+            counter.load(std::sync::atomic::Ordering::SeqCst)
+        };
+        assert_eq!(count(&counting.before_count), 1);
+        assert_eq!(count(&counting.after_count), 1);
+    }
+
+    #[test]
+    fn test_downcast_result() {
+        let result: Box<dyn Any> = Box::new(String::from("ok"));
+        assert_eq!(downcast_result::<String>("f", result).unwrap(), "ok");
+
+        let result: Box<dyn Any> = Box::new(1u8);
+        let err = downcast_result::<String>("f", result).unwrap_err();
+        assert!(matches!(err, AspectError::WeavingError { .. }));
+        assert!(err.to_string().contains("String"), "{}", err);
+    }
 }
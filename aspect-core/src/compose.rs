@@ -0,0 +1,295 @@
+//! Composing several aspects into one.
+//!
+//! [`Aspects`] builds a [`ComposedAspect`] from aspects listed in nesting
+//! order, the first one outermost:
+//!
+//! ```text
+//! Aspects::new().with(A).with(B).with(C).build()
+//!
+//! A → B → C → function → C → B → A
+//! ```
+//!
+//! Unlike the aspects listed in `#[aspect(A, B, C)]`, which are ordered by
+//! their [`precedence`](Aspect::precedence), the composed aspects keep the
+//! order they were added in. The composition is itself an [`Aspect`], so a
+//! stack of aspects defined once can be woven into functions with
+//! `#[aspect]`, typically as a clone of a static, or registered in the
+//! registry with one pointcut.
+//!
+//! # Example
+//!
+//! ```rust
+//! use aspect_core::prelude::*;
+//! use aspect_core::{Aspects, ComposedAspect};
+//! use std::any::Any;
+//! use std::sync::{Arc, Mutex};
+//!
+//! struct Trace(&'static str, Arc<Mutex<Vec<String>>>);
+//!
+//! impl Aspect for Trace {
+//!     fn before(&self, _ctx: &JoinPoint) {
+//!         self.1.lock().unwrap().push(format!("enter {}", self.0));
+//!     }
+//!
+//!     fn after(&self, _ctx: &JoinPoint, _result: &dyn Any) {
+//!         self.1.lock().unwrap().push(format!("exit {}", self.0));
+//!     }
+//! }
+//!
+//! let log = Arc::new(Mutex::new(Vec::new()));
+//! let stack: ComposedAspect = Aspects::new()
+//!     .with(Trace("auth", log.clone()))
+//!     .with(Trace("timing", log.clone()))
+//!     .build();
+//!
+//! let ctx = JoinPoint::new("handler", "app", Location { file: "app.rs", line: 1 });
+//! let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn Any>), ctx);
+//! stack.around(pjp).unwrap();
+//! assert_eq!(
+//!     *log.lock().unwrap(),
+//!     ["enter auth", "enter timing", "exit timing", "exit auth"]
+//! );
+//! ```
+
+use crate::aspect::{Aspect, BoxFuture, Precedence, ReturnValue};
+use crate::error::AspectError;
+use crate::joinpoint::{JoinPoint, ProceedingJoinPoint};
+use crate::reentrancy;
+use crate::snapshot::{AspectSnapshot, SnapshotValue};
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+
+/// Builder of a [`ComposedAspect`], see the [module documentation](self).
+#[derive(Clone, Default)]
+pub struct Aspects {
+    aspects: Vec<Arc<dyn Aspect>>,
+}
+
+impl Aspects {
+    /// No aspects yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `aspect`, nested inside the aspects added before it.
+    pub fn with(self, aspect: impl Aspect + 'static) -> Self {
+        self.with_shared(Arc::new(aspect))
+    }
+
+    /// Add an aspect shared with other compositions or a registry, nested
+    /// inside the aspects added before it.
+    pub fn with_shared(mut self, aspect: Arc<dyn Aspect>) -> Self {
+        self.aspects.push(aspect);
+        self
+    }
+
+    /// The aspect applying the aspects added, the first one outermost.
+    pub fn build(self) -> ComposedAspect {
+        ComposedAspect {
+            aspects: self.aspects.into(),
+        }
+    }
+}
+
+/// Aspects applied as one, the first one outermost, built with
+/// [`Aspects`].
+///
+/// Clones share the aspects, and their state.
+#[derive(Clone)]
+pub struct ComposedAspect {
+    aspects: Arc<[Arc<dyn Aspect>]>,
+}
+
+impl ComposedAspect {
+    /// The composed aspects, the outermost first.
+    pub fn aspects(&self) -> &[Arc<dyn Aspect>] {
+        &self.aspects
+    }
+
+    /// Run `pjp` through the aspects from the `depth`th outermost.
+    fn around_from(
+        &self,
+        depth: usize,
+        pjp: ProceedingJoinPoint,
+    ) -> Result<Box<dyn Any>, AspectError> {
+        let Some(aspect) = self.aspects.get(depth) else {
+            return pjp.proceed();
+        };
+        if reentrancy::skips(&**aspect) {
+            return self.around_from(depth + 1, pjp);
+        }
+        let ctx = pjp.context().clone();
+        let mut inner = Some(reentrancy::proceeding(move || {
+            self.around_from(depth + 1, pjp)
+        }));
+        let mut proceed = || (inner.take().expect("proceeded more than once"))();
+        reentrancy::advice(&**aspect, || {
+            aspect.around(ProceedingJoinPoint::borrowed(&mut proceed, ctx))
+        })
+    }
+
+    /// Run `advice` for each of `aspects` the reentrancy guard does not
+    /// skip, guarding it as its advice.
+    fn advise<'a>(
+        aspects: impl Iterator<Item = &'a Arc<dyn Aspect>>,
+        mut advice: impl FnMut(&dyn Aspect),
+    ) {
+        for aspect in aspects {
+            if !reentrancy::skips(&**aspect) {
+                reentrancy::advice(&**aspect, || advice(&**aspect));
+            }
+        }
+    }
+}
+
+/// Advice called directly, as for `async fn`s, runs in nesting order: the
+/// outermost aspect first before the function, and last after it.
+impl Aspect for ComposedAspect {
+    fn before(&self, ctx: &JoinPoint) {
+        Self::advise(self.aspects.iter(), |aspect| aspect.before(ctx));
+    }
+
+    fn after(&self, ctx: &JoinPoint, result: &dyn Any) {
+        Self::advise(self.aspects.iter().rev(), |aspect| {
+            aspect.after(ctx, result)
+        });
+    }
+
+    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
+        Self::advise(self.aspects.iter().rev(), |aspect| {
+            aspect.after_error(ctx, error)
+        });
+    }
+
+    fn after_returning_mut(&self, ctx: &JoinPoint, result: &mut ReturnValue<'_>) {
+        Self::advise(self.aspects.iter().rev(), |aspect| {
+            aspect.after_returning_mut(ctx, result)
+        });
+    }
+
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        self.around_from(0, pjp)
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        mut proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        for aspect in self.aspects.iter().rev() {
+            proceed = aspect.around_async(ctx, proceed);
+        }
+        proceed
+    }
+
+    /// That of the outermost aspect.
+    fn precedence(&self) -> Precedence {
+        self.aspects
+            .first()
+            .map_or(Precedence::DEFAULT, |aspect| aspect.precedence())
+    }
+
+    /// Whether any of the composed aspects does.
+    fn reads_args(&self) -> bool {
+        self.aspects.iter().any(|aspect| aspect.reads_args())
+    }
+
+    /// The snapshots of the composed aspects, the outermost first.
+    fn snapshot(&self) -> AspectSnapshot {
+        let aspects: Vec<SnapshotValue> = self
+            .aspects
+            .iter()
+            .map(|aspect| aspect.snapshot().into())
+            .collect();
+        AspectSnapshot::new("ComposedAspect").with("aspects", aspects)
+    }
+
+    /// None: each of the composed aspects is guarded as it advises.
+    fn reentrancy_name(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::joinpoint::Location;
+    use alloc::format;
+    use alloc::string::{String, ToString};
+    use std::sync::Mutex;
+
+    struct Trace {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Aspect for Trace {
+        fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+            self.log.lock().unwrap().push(format!("> {}", self.name));
+            let result = pjp.proceed();
+            self.log.lock().unwrap().push(format!("< {}", self.name));
+            result
+        }
+
+        fn precedence(&self) -> Precedence {
+            Precedence::OBSERVABILITY
+        }
+    }
+
+    fn joinpoint() -> JoinPoint {
+        JoinPoint::new(
+            "handler",
+            "app",
+            Location {
+                file: "app.rs",
+                line: 1,
+            },
+        )
+    }
+
+    #[test]
+    fn test_nesting_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let composed = Aspects::new()
+            .with(Trace {
+                name: "outer",
+                log: log.clone(),
+            })
+            .with_shared(Arc::new(Trace {
+                name: "inner",
+                log: log.clone(),
+            }))
+            .build();
+
+        let log_call = log.clone();
+        let pjp = ProceedingJoinPoint::new(
+            move || {
+                log_call.lock().unwrap().push("call".to_string());
+                Ok(Box::new(7) as Box<dyn Any>)
+            },
+            joinpoint(),
+        );
+        let result = composed.around(pjp).unwrap();
+        assert_eq!(*result.downcast::<i32>().unwrap(), 7);
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["> outer", "> inner", "call", "< inner", "< outer"]
+        );
+        assert_eq!(composed.precedence(), Precedence::OBSERVABILITY);
+        assert_eq!(composed.aspects().len(), 2);
+    }
+
+    #[test]
+    fn test_empty_composition_proceeds() {
+        let composed = Aspects::new().build();
+        let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(1) as Box<dyn Any>), joinpoint());
+        assert_eq!(*composed.around(pjp).unwrap().downcast::<i32>().unwrap(), 1);
+        assert_eq!(composed.precedence(), Precedence::DEFAULT);
+        assert_eq!(
+            composed.snapshot().to_string(),
+            r#"{"aspect":"ComposedAspect","state":{"aspects":[]}}"#
+        );
+    }
+}
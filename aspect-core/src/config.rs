@@ -0,0 +1,738 @@
+//! Per-function configuration of aspects.
+//!
+//! `#[aspect(RateLimitAspect, max = 10, window = "1s")]` builds the aspect
+//! of a function from `key = value` parameters instead of a constructor
+//! expression: the macro collects them in [`AspectArgs`] and passes them
+//! to the [`FromAspectArgs`] implementation of the aspect type, once per
+//! function. Values are expressions, typically literals, converted to an
+//! [`ArgValue`]; aspects read them back with [`AspectArgs::get`] and
+//! [`AspectArgs::require`], which also parse durations such as `"250ms"`.
+//!
+//! The aspect declares the parameters it takes in
+//! [`FromAspectArgs::PARAMS`], which the macro checks the literal
+//! parameters against at compile time: unknown keys, missing required
+//! parameters and literals of the wrong type do not compile.
+//!
+//! # Example
+//!
+//! ```rust
+//! use aspect_core::config::{AspectArgs, FromAspectArgs, Param};
+//! use aspect_core::prelude::*;
+//! use std::time::Duration;
+//!
+//! #[derive(Clone)]
+//! struct SlowCallAspect {
+//!     threshold: Duration,
+//!     warn: bool,
+//! }
+//!
+//! impl Aspect for SlowCallAspect {}
+//!
+//! impl FromAspectArgs for SlowCallAspect {
+//!     const PARAMS: &'static [Param] = &[
+//!         Param::required::<Duration>("threshold"),
+//!         Param::optional::<bool>("warn"),
+//!     ];
+//!
+//!     fn from_aspect_args(args: &AspectArgs) -> Result<Self, AspectError> {
+//!         args.check(Self::PARAMS)?;
+//!         Ok(Self {
+//!             threshold: args.require("threshold")?,
+//!             warn: args.get("warn")?.unwrap_or(false),
+//!         })
+//!     }
+//! }
+//!
+//! // What `#[aspect(SlowCallAspect, threshold = "250ms")]` builds
+//! let args = AspectArgs::new("SlowCallAspect").with("threshold", "250ms");
+//! let aspect = SlowCallAspect::from_aspect_args(&args).unwrap();
+//! assert_eq!(aspect.threshold, Duration::from_millis(250));
+//! assert!(!aspect.warn);
+//! ```
+
+use crate::error::AspectError;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// Aspects that `#[aspect(Type, key = value, ...)]` can build from
+/// parameters.
+///
+/// The macro checks the literal parameters against [`PARAMS`](Self::PARAMS)
+/// at compile time. Parameters given as other expressions are only known
+/// when the aspect is built, on the first call of the function, so
+/// `from_aspect_args` should check them too, with [`AspectArgs::check`];
+/// an error then makes that call panic with it.
+pub trait FromAspectArgs: Sized {
+    /// The parameters the aspect takes.
+    const PARAMS: &'static [Param];
+
+    /// Build the aspect from the parameters given to the macro.
+    fn from_aspect_args(args: &AspectArgs) -> Result<Self, AspectError>;
+}
+
+/// A parameter an aspect takes, see [`FromAspectArgs::PARAMS`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Param {
+    name: &'static str,
+    kind: ArgKind,
+    expected: &'static str,
+    required: bool,
+}
+
+impl Param {
+    /// The parameter `name`, read as a `T`, which must be given.
+    pub const fn required<T: FromArgValue>(name: &'static str) -> Self {
+        Self {
+            name,
+            kind: T::KIND,
+            expected: T::EXPECTED,
+            required: true,
+        }
+    }
+
+    /// The parameter `name`, read as a `T`, which may be left out.
+    pub const fn optional<T: FromArgValue>(name: &'static str) -> Self {
+        Self {
+            required: false,
+            ..Self::required::<T>(name)
+        }
+    }
+
+    /// The key of the parameter.
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Whether the parameter must be given.
+    pub const fn is_required(&self) -> bool {
+        self.required
+    }
+}
+
+/// The values a type of parameter accepts, see [`FromArgValue::KIND`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgKind {
+    /// Integers from `min` to `max`
+    Int {
+        /// The smallest integer accepted
+        min: i128,
+        /// The largest integer accepted
+        max: i128,
+    },
+    /// Integers and floating-point numbers
+    Number,
+    /// Booleans
+    Bool,
+    /// Strings
+    Str,
+    /// Durations, and strings such as `"1s"` that parse as one
+    Duration,
+}
+
+impl ArgKind {
+    /// Whether `value` is one of these values.
+    pub fn accepts(&self, value: &ArgValue) -> bool {
+        match (self, value) {
+            (ArgKind::Int { min, max }, ArgValue::Int(value)) => (*min..=*max).contains(value),
+            (ArgKind::Number, ArgValue::Int(_) | ArgValue::Float(_))
+            | (ArgKind::Bool, ArgValue::Bool(_))
+            | (ArgKind::Str, ArgValue::Str(_))
+            | (ArgKind::Duration, ArgValue::Duration(_)) => true,
+            (ArgKind::Duration, ArgValue::Str(text)) => parse_duration(text).is_some(),
+            _ => false,
+        }
+    }
+}
+
+/// The value of a parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgValue {
+    /// An integer, of any integer type
+    Int(i128),
+    /// A floating-point number
+    Float(f64),
+    /// A boolean
+    Bool(bool),
+    /// A string, e.g. a duration such as `"1s"`
+    Str(String),
+    /// A duration
+    Duration(Duration),
+}
+
+macro_rules! int_values {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for ArgValue {
+                fn from(value: $ty) -> Self {
+                    ArgValue::Int(value as i128)
+                }
+            }
+
+            impl FromArgValue for $ty {
+                const EXPECTED: &'static str = concat!("an integer fitting in ", stringify!($ty));
+                const KIND: ArgKind = ArgKind::Int {
+                    min: <$ty>::MIN as i128,
+                    max: <$ty>::MAX as i128,
+                };
+
+                fn from_arg_value(value: &ArgValue) -> Option<Self> {
+                    match value {
+                        ArgValue::Int(value) => (*value).try_into().ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+int_values!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl From<f32> for ArgValue {
+    fn from(value: f32) -> Self {
+        ArgValue::Float(value as f64)
+    }
+}
+
+impl From<f64> for ArgValue {
+    fn from(value: f64) -> Self {
+        ArgValue::Float(value)
+    }
+}
+
+impl From<bool> for ArgValue {
+    fn from(value: bool) -> Self {
+        ArgValue::Bool(value)
+    }
+}
+
+impl From<&str> for ArgValue {
+    fn from(value: &str) -> Self {
+        ArgValue::Str(value.to_string())
+    }
+}
+
+impl From<String> for ArgValue {
+    fn from(value: String) -> Self {
+        ArgValue::Str(value)
+    }
+}
+
+impl From<Duration> for ArgValue {
+    fn from(value: Duration) -> Self {
+        ArgValue::Duration(value)
+    }
+}
+
+/// Types a parameter can be read as.
+pub trait FromArgValue: Sized {
+    /// What the value should be, for error messages.
+    const EXPECTED: &'static str;
+
+    /// The values of this type, for the compile-time checks of
+    /// `#[aspect(Type, key = value)]`.
+    const KIND: ArgKind;
+
+    /// The value as `Self`, if it is one.
+    fn from_arg_value(value: &ArgValue) -> Option<Self>;
+}
+
+impl FromArgValue for f64 {
+    const EXPECTED: &'static str = "a number";
+    const KIND: ArgKind = ArgKind::Number;
+
+    fn from_arg_value(value: &ArgValue) -> Option<Self> {
+        match value {
+            ArgValue::Float(value) => Some(*value),
+            ArgValue::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+}
+
+impl FromArgValue for bool {
+    const EXPECTED: &'static str = "a boolean";
+    const KIND: ArgKind = ArgKind::Bool;
+
+    fn from_arg_value(value: &ArgValue) -> Option<Self> {
+        match value {
+            ArgValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromArgValue for String {
+    const EXPECTED: &'static str = "a string";
+    const KIND: ArgKind = ArgKind::Str;
+
+    fn from_arg_value(value: &ArgValue) -> Option<Self> {
+        match value {
+            ArgValue::Str(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromArgValue for Duration {
+    const EXPECTED: &'static str = "a duration such as \"1s\" or \"250ms\"";
+    const KIND: ArgKind = ArgKind::Duration;
+
+    fn from_arg_value(value: &ArgValue) -> Option<Self> {
+        match value {
+            ArgValue::Duration(value) => Some(*value),
+            ArgValue::Str(value) => parse_duration(value),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a duration written as a number and a unit: `ns`, `us`, `ms`,
+/// `s`, `m` or `h`, e.g. `"1s"`, `"250ms"` or `"1.5h"`.
+///
+/// ```rust
+/// use aspect_core::config::parse_duration;
+/// use std::time::Duration;
+///
+/// assert_eq!(parse_duration("250ms"), Some(Duration::from_millis(250)));
+/// assert_eq!(parse_duration("1.5m"), Some(Duration::from_secs(90)));
+/// assert_eq!(parse_duration("10"), None);
+/// ```
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let split = text.find(|c: char| c.is_ascii_alphabetic())?;
+    let (number, unit) = text.split_at(split);
+    let nanos_per_unit: u64 = match unit {
+        "ns" => 1,
+        "us" => 1_000,
+        "ms" => 1_000_000,
+        "s" => 1_000_000_000,
+        "m" => 60_000_000_000,
+        "h" => 3_600_000_000_000,
+        _ => return None,
+    };
+    let number = number.trim();
+    // Whole numbers exactly, fractions as closely as `f64` allows
+    match number.parse::<u64>() {
+        Ok(whole) => Some(Duration::from_nanos(whole.checked_mul(nanos_per_unit)?)),
+        Err(_) => {
+            let number: f64 = number.parse().ok()?;
+            Duration::try_from_secs_f64(number * nanos_per_unit as f64 / 1e9).ok()
+        }
+    }
+}
+
+/// The `key = value` parameters given to `#[aspect(Type, ...)]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AspectArgs {
+    aspect: &'static str,
+    args: Vec<(&'static str, ArgValue)>,
+}
+
+impl AspectArgs {
+    /// No parameters yet, for the aspect type named `aspect`.
+    pub fn new(aspect: &'static str) -> Self {
+        Self {
+            aspect,
+            args: Vec::new(),
+        }
+    }
+
+    /// Add the parameter `key = value`.
+    pub fn with(mut self, key: &'static str, value: impl Into<ArgValue>) -> Self {
+        self.args.push((key, value.into()));
+        self
+    }
+
+    /// The name of the aspect type, as written in the attribute.
+    pub fn aspect(&self) -> &'static str {
+        self.aspect
+    }
+
+    /// The keys of the parameters, in the order they were given.
+    pub fn keys(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.args.iter().map(|(key, _)| *key)
+    }
+
+    /// The value of the parameter `key`, as given.
+    pub fn value(&self, key: &str) -> Option<&ArgValue> {
+        self.args
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value)
+    }
+
+    /// The parameter `key` as a `T`, if it was given.
+    ///
+    /// Fails if its value is not a `T`.
+    pub fn get<T: FromArgValue>(&self, key: &str) -> Result<Option<T>, AspectError> {
+        let Some(value) = self.value(key) else {
+            return Ok(None);
+        };
+        match T::from_arg_value(value) {
+            Some(value) => Ok(Some(value)),
+            None => Err(AspectError::weaving(format!(
+                "parameter `{}` of {} must be {}, not {:?}",
+                key,
+                self.aspect,
+                T::EXPECTED,
+                value
+            ))),
+        }
+    }
+
+    /// The parameter `key` as a `T`; fails if it was not given.
+    pub fn require<T: FromArgValue>(&self, key: &str) -> Result<T, AspectError> {
+        self.get(key)?.ok_or_else(|| {
+            AspectError::weaving(format!("{} requires parameter `{}`", self.aspect, key))
+        })
+    }
+
+    /// Fails if a parameter not in `params` was given, a required one was
+    /// not, or one is not of the type it is read as.
+    pub fn check(&self, params: &[Param]) -> Result<(), AspectError> {
+        let known: Vec<&str> = params.iter().map(|param| param.name).collect();
+        self.only(&known)?;
+        for param in params {
+            let Some(value) = self.value(param.name) else {
+                if param.required {
+                    return Err(AspectError::weaving(format!(
+                        "{} requires parameter `{}`",
+                        self.aspect, param.name
+                    )));
+                }
+                continue;
+            };
+            if !param.kind.accepts(value) {
+                return Err(AspectError::weaving(format!(
+                    "parameter `{}` of {} must be {}, not {:?}",
+                    param.name, self.aspect, param.expected, value
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails if a parameter other than `known` was given.
+    pub fn only(&self, known: &[&str]) -> Result<(), AspectError> {
+        match self.keys().find(|key| !known.contains(key)) {
+            Some(key) => Err(AspectError::weaving(format!(
+                "{} has no parameter `{}` (expected one of: {})",
+                self.aspect,
+                key,
+                known.join(", ")
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The compile-time checks of `#[aspect(Type, key = value)]`. Not public
+/// API.
+#[doc(hidden)]
+pub mod __private {
+    use super::{ArgKind, Param};
+
+    /// A parameter value as the macro sees it.
+    pub enum Literal {
+        /// An integer literal
+        Int(i128),
+        /// A floating-point literal
+        Float,
+        /// A boolean literal
+        Bool,
+        /// A string literal, and whether it parses as a duration
+        Str {
+            /// Whether the string parses as a duration
+            duration: bool,
+        },
+        /// Any other expression, only known once evaluated
+        Expr,
+    }
+
+    impl ArgKind {
+        const fn accepts_literal(&self, literal: &Literal) -> bool {
+            match (self, literal) {
+                (ArgKind::Int { min, max }, Literal::Int(value)) => {
+                    *min <= *value && *value <= *max
+                }
+                (ArgKind::Number, Literal::Int(_) | Literal::Float)
+                | (ArgKind::Bool, Literal::Bool)
+                | (ArgKind::Str, Literal::Str { .. })
+                | (ArgKind::Duration, Literal::Str { duration: true })
+                | (_, Literal::Expr) => true,
+                _ => false,
+            }
+        }
+    }
+
+    /// The error found by [`check`], empty if there is none, formatted in
+    /// a fixed buffer as `const` code cannot allocate.
+    pub struct Message {
+        bytes: [u8; 512],
+        len: usize,
+    }
+
+    impl Message {
+        const fn new() -> Self {
+            Self {
+                bytes: [0; 512],
+                len: 0,
+            }
+        }
+
+        /// Appends `text`, truncated to the room left.
+        const fn push(mut self, text: &str) -> Self {
+            let text = text.as_bytes();
+            let mut i = 0;
+            while i < text.len() && self.len < self.bytes.len() {
+                self.bytes[self.len] = text[i];
+                self.len += 1;
+                i += 1;
+            }
+            self
+        }
+
+        /// Whether no error was found.
+        pub const fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        /// The error.
+        pub const fn as_str(&self) -> &str {
+            match core::str::from_utf8(self.bytes.split_at(self.len).0) {
+                Ok(text) => text,
+                Err(_) => "invalid aspect parameters",
+            }
+        }
+    }
+
+    const fn str_eq(a: &str, b: &str) -> bool {
+        let (a, b) = (a.as_bytes(), b.as_bytes());
+        if a.len() != b.len() {
+            return false;
+        }
+        let mut i = 0;
+        while i < a.len() {
+            if a[i] != b[i] {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    /// Checks the parameters `given` to the aspect type named `aspect`
+    /// against the `params` it takes, with the messages of
+    /// [`AspectArgs::check`](super::AspectArgs::check).
+    pub const fn check(aspect: &str, params: &[Param], given: &[(&str, Literal)]) -> Message {
+        let mut i = 0;
+        while i < given.len() {
+            let (key, literal) = &given[i];
+            let mut found = None;
+            let mut j = 0;
+            while j < params.len() {
+                if str_eq(params[j].name, key) {
+                    found = Some(&params[j]);
+                }
+                j += 1;
+            }
+            match found {
+                None => {
+                    let mut message = Message::new()
+                        .push(aspect)
+                        .push(" has no parameter `")
+                        .push(key)
+                        .push("` (expected one of: ");
+                    let mut j = 0;
+                    while j < params.len() {
+                        if j > 0 {
+                            message = message.push(", ");
+                        }
+                        message = message.push(params[j].name);
+                        j += 1;
+                    }
+                    return message.push(")");
+                }
+                Some(param) if !param.kind.accepts_literal(literal) => {
+                    return Message::new()
+                        .push("parameter `")
+                        .push(key)
+                        .push("` of ")
+                        .push(aspect)
+                        .push(" must be ")
+                        .push(param.expected);
+                }
+                Some(_) => {}
+            }
+            i += 1;
+        }
+
+        let mut j = 0;
+        while j < params.len() {
+            if params[j].required {
+                let mut given_it = false;
+                let mut i = 0;
+                while i < given.len() {
+                    if str_eq(given[i].0, params[j].name) {
+                        given_it = true;
+                    }
+                    i += 1;
+                }
+                if !given_it {
+                    return Message::new()
+                        .push(aspect)
+                        .push(" requires parameter `")
+                        .push(params[j].name)
+                        .push("`");
+                }
+            }
+            j += 1;
+        }
+        Message::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_and_require() {
+        let args = AspectArgs::new("RateLimitAspect")
+            .with("max", 10)
+            .with("window", "1s")
+            .with("burst", -1);
+
+        assert_eq!(args.require::<u64>("max").unwrap(), 10);
+        assert_eq!(
+            args.require::<Duration>("window").unwrap(),
+            Duration::from_secs(1)
+        );
+        assert_eq!(args.get::<bool>("per_function").unwrap(), None);
+
+        assert_eq!(
+            args.require::<u64>("burst").unwrap_err().to_string(),
+            "Weaving error: parameter `burst` of RateLimitAspect must be an integer fitting in u64, not Int(-1)"
+        );
+        assert_eq!(
+            args.require::<bool>("per_function")
+                .unwrap_err()
+                .to_string(),
+            "Weaving error: RateLimitAspect requires parameter `per_function`"
+        );
+    }
+
+    #[test]
+    fn test_only() {
+        let args = AspectArgs::new("DeadlineAspect").with("budjet", "1s");
+        assert!(args.only(&["budjet"]).is_ok());
+        assert_eq!(
+            args.only(&["budget"]).unwrap_err().to_string(),
+            "Weaving error: DeadlineAspect has no parameter `budjet` (expected one of: budget)"
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        const PARAMS: &[Param] = &[
+            Param::required::<u32>("max"),
+            Param::optional::<Duration>("window"),
+        ];
+        let args = AspectArgs::new("Quota").with("max", 2).with("window", "1s");
+        assert!(args.check(PARAMS).is_ok());
+
+        let errors = [
+            (
+                AspectArgs::new("Quota").with("max", 2).with("burst", 1),
+                "Quota has no parameter `burst` (expected one of: max, window)",
+            ),
+            (
+                AspectArgs::new("Quota").with("window", "1s"),
+                "Quota requires parameter `max`",
+            ),
+            (
+                AspectArgs::new("Quota").with("max", -1),
+                "parameter `max` of Quota must be an integer fitting in u32, not Int(-1)",
+            ),
+            (
+                AspectArgs::new("Quota").with("max", 1).with("window", "soon"),
+                "parameter `window` of Quota must be a duration such as \"1s\" or \"250ms\", not Str(\"soon\")",
+            ),
+        ];
+        for (args, message) in errors {
+            assert_eq!(
+                args.check(PARAMS).unwrap_err().to_string(),
+                format!("Weaving error: {}", message)
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_literals() {
+        use __private::{check, Literal};
+
+        const PARAMS: &[Param] = &[
+            Param::required::<u32>("max"),
+            Param::optional::<Duration>("window"),
+        ];
+        // What the macro checks, at compile time
+        const {
+            assert!(check(
+                "Quota",
+                PARAMS,
+                &[("max", Literal::Int(2)), ("window", Literal::Expr)],
+            )
+            .is_empty())
+        };
+
+        let errors = [
+            (
+                check(
+                    "Quota",
+                    PARAMS,
+                    &[("max", Literal::Int(2)), ("burst", Literal::Int(1))],
+                ),
+                "Quota has no parameter `burst` (expected one of: max, window)",
+            ),
+            (
+                check(
+                    "Quota",
+                    PARAMS,
+                    &[("window", Literal::Str { duration: true })],
+                ),
+                "Quota requires parameter `max`",
+            ),
+            (
+                check("Quota", PARAMS, &[("max", Literal::Int(-1))]),
+                "parameter `max` of Quota must be an integer fitting in u32",
+            ),
+            (
+                check(
+                    "Quota",
+                    PARAMS,
+                    &[
+                        ("max", Literal::Int(1)),
+                        ("window", Literal::Str { duration: false }),
+                    ],
+                ),
+                "parameter `window` of Quota must be a duration such as \"1s\" or \"250ms\"",
+            ),
+        ];
+        for (message, expected) in errors {
+            assert_eq!(message.as_str(), expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("1s"), Some(Duration::from_secs(1)));
+        assert_eq!(parse_duration(" 2 h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("500us"), Some(Duration::from_micros(500)));
+        assert_eq!(parse_duration("1d"), None);
+        assert_eq!(parse_duration("-1s"), None);
+        assert_eq!(parse_duration("ms"), None);
+    }
+}
@@ -1,7 +1,12 @@
 //! Error types for aspect execution.
 
-use std::error::Error;
-use std::fmt;
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::error::Error;
+use core::fmt;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::{any::Any, backtrace::Backtrace};
 
 /// Errors that can occur during aspect execution.
 ///
@@ -25,6 +30,57 @@ pub enum AspectError {
 
     /// A custom error defined by user code
     Custom(Box<dyn Error + Send + Sync>),
+
+    /// The advised function panicked and the panic was caught
+    Panic {
+        /// The panic message, if the payload was a string
+        payload: String,
+        /// Where the panic happened; only captured when enabled through
+        /// `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`
+        #[cfg(feature = "std")]
+        backtrace: Backtrace,
+    },
+
+    /// The caller was not authorized to call the advised function
+    Denied {
+        /// What the call requires, e.g. a set of roles
+        required: String,
+        /// What the caller has
+        actual: String,
+    },
+
+    /// A rate limit rejected the call without running it
+    RateLimited {
+        /// What the limit applies to, e.g. the advised function
+        target: String,
+        /// How long until a call can get through, if known
+        retry_after: Option<Duration>,
+    },
+
+    /// An open circuit breaker rejected the call without running it
+    CircuitOpen {
+        /// What the circuit protects, e.g. the advised function
+        target: String,
+        /// How long until the circuit lets a trial call through
+        reopens_in: Duration,
+    },
+
+    /// The call, or its wait to start, took longer than allowed
+    Timeout {
+        /// What timed out, e.g. the advised function
+        target: String,
+        /// How long it had been running or waiting when it gave up
+        elapsed: Duration,
+    },
+
+    /// Load shedding rejected the call without running it, the service
+    /// being overloaded
+    Overloaded {
+        /// What the call was shed from, e.g. the advised function
+        target: String,
+        /// How long the caller should back off, if known
+        retry_after: Option<Duration>,
+    },
 }
 
 impl AspectError {
@@ -94,6 +150,145 @@ impl AspectError {
     pub fn custom(error: impl Error + Send + Sync + 'static) -> Self {
         Self::Custom(Box::new(error))
     }
+
+    /// Creates a panic error from a payload returned by
+    /// `std::panic::catch_unwind`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use aspect_core::error::AspectError;
+    /// use std::backtrace::Backtrace;
+    ///
+    /// let payload = std::panic::catch_unwind(|| panic!("boom")).unwrap_err();
+    /// let err = AspectError::panic(&*payload, Backtrace::disabled());
+    /// assert_eq!(err.to_string(), "Panic: boom");
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn panic(payload: &(dyn Any + Send), backtrace: Backtrace) -> Self {
+        let payload = if let Some(s) = payload.downcast_ref::<&str>() {
+            s.to_string()
+        } else if let Some(s) = payload.downcast_ref::<String>() {
+            s.clone()
+        } else {
+            "Box<dyn Any>".to_string()
+        };
+        Self::Panic { payload, backtrace }
+    }
+
+    /// Creates an authorization denial.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use aspect_core::error::AspectError;
+    ///
+    /// let err = AspectError::denied("role admin", "roles [\"user\"]");
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Access denied: requires role admin, caller has roles [\"user\"]"
+    /// );
+    /// ```
+    pub fn denied(required: impl Into<String>, actual: impl Into<String>) -> Self {
+        Self::Denied {
+            required: required.into(),
+            actual: actual.into(),
+        }
+    }
+
+    /// Creates a rate limit rejection.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use aspect_core::error::AspectError;
+    /// use std::time::Duration;
+    ///
+    /// let err = AspectError::rate_limited("send_email", Some(Duration::from_millis(250)));
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Rate limit exceeded for send_email, retry after 250ms"
+    /// );
+    /// ```
+    pub fn rate_limited(target: impl Into<String>, retry_after: Option<Duration>) -> Self {
+        Self::RateLimited {
+            target: target.into(),
+            retry_after,
+        }
+    }
+
+    /// Creates an open circuit rejection.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use aspect_core::error::AspectError;
+    /// use std::time::Duration;
+    ///
+    /// let err = AspectError::circuit_open("fetch_quote", Duration::from_secs(30));
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Circuit breaker is OPEN for fetch_quote - failing fast, half-open in 30s"
+    /// );
+    /// ```
+    pub fn circuit_open(target: impl Into<String>, reopens_in: Duration) -> Self {
+        Self::CircuitOpen {
+            target: target.into(),
+            reopens_in,
+        }
+    }
+
+    /// Creates a timeout.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use aspect_core::error::AspectError;
+    /// use std::time::Duration;
+    ///
+    /// let err = AspectError::timeout("load_user", Duration::from_millis(520));
+    /// assert_eq!(err.to_string(), "Timed out after 520ms: load_user");
+    /// ```
+    pub fn timeout(target: impl Into<String>, elapsed: Duration) -> Self {
+        Self::Timeout {
+            target: target.into(),
+            elapsed,
+        }
+    }
+
+    /// Creates a load shedding rejection.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use aspect_core::error::AspectError;
+    /// use std::time::Duration;
+    ///
+    /// let err = AspectError::overloaded("search", Some(Duration::from_secs(1)));
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Overloaded, shedding load for search, retry after 1s"
+    /// );
+    /// ```
+    pub fn overloaded(target: impl Into<String>, retry_after: Option<Duration>) -> Self {
+        Self::Overloaded {
+            target: target.into(),
+            retry_after,
+        }
+    }
+
+    /// How long the caller should wait before trying again, for rate limit,
+    /// open circuit and load shedding rejections, e.g. for a `Retry-After`
+    /// header.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } | Self::Overloaded { retry_after, .. } => {
+                *retry_after
+            }
+            Self::CircuitOpen { reopens_in, .. } => Some(*reopens_in),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for AspectError {
@@ -106,6 +301,38 @@ impl fmt::Display for AspectError {
                 write!(f, "Weaving error: {}", message)
             }
             Self::Custom(err) => write!(f, "Custom error: {}", err),
+            Self::Panic { payload, .. } => write!(f, "Panic: {}", payload),
+            Self::Denied { required, actual } => {
+                write!(f, "Access denied: requires {}, caller has {}", required, actual)
+            }
+            Self::RateLimited {
+                target,
+                retry_after,
+            } => {
+                write!(f, "Rate limit exceeded for {}", target)?;
+                match retry_after {
+                    Some(retry_after) => write!(f, ", retry after {:?}", retry_after),
+                    None => Ok(()),
+                }
+            }
+            Self::CircuitOpen { target, reopens_in } => write!(
+                f,
+                "Circuit breaker is OPEN for {} - failing fast, half-open in {:?}",
+                target, reopens_in
+            ),
+            Self::Timeout { target, elapsed } => {
+                write!(f, "Timed out after {:?}: {}", elapsed, target)
+            }
+            Self::Overloaded {
+                target,
+                retry_after,
+            } => {
+                write!(f, "Overloaded, shedding load for {}", target)?;
+                match retry_after {
+                    Some(retry_after) => write!(f, ", retry after {:?}", retry_after),
+                    None => Ok(()),
+                }
+            }
         }
     }
 }
@@ -170,12 +397,59 @@ mod tests {
 
     #[test]
     fn test_custom_error() {
-        let io_err = io::Error::new(io::ErrorKind::Other, "custom");
+        let io_err = io::Error::other("custom");
         let err = AspectError::custom(io_err);
 
         assert!(matches!(err, AspectError::Custom(_)));
     }
 
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_panic_error() {
+        let payload = std::panic::catch_unwind(|| panic!("index {} out of range", 3)).unwrap_err();
+        let err = AspectError::panic(&*payload, Backtrace::disabled());
+
+        assert!(matches!(err, AspectError::Panic { .. }));
+        assert_eq!(err.to_string(), "Panic: index 3 out of range");
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn test_denied_error() {
+        let err = AspectError::denied("any of roles [\"admin\"]", "no roles");
+
+        assert!(matches!(err, AspectError::Denied { .. }));
+        assert_eq!(
+            err.to_string(),
+            "Access denied: requires any of roles [\"admin\"], caller has no roles"
+        );
+    }
+
+    #[test]
+    fn test_rejection_errors() {
+        let err = AspectError::rate_limited("send", None);
+        assert_eq!(err.to_string(), "Rate limit exceeded for send");
+        assert_eq!(err.retry_after(), None);
+
+        let err = AspectError::rate_limited("send", Some(Duration::from_secs(2)));
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(2)));
+
+        let err = AspectError::circuit_open("fetch", Duration::from_secs(5));
+        assert!(matches!(err, AspectError::CircuitOpen { .. }));
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(5)));
+
+        let err = AspectError::timeout("load", Duration::from_millis(10));
+        assert!(matches!(err, AspectError::Timeout { .. }));
+        assert_eq!(err.retry_after(), None);
+        assert!(AspectError::execution("boom").retry_after().is_none());
+
+        let err = AspectError::overloaded("search", None);
+        assert_eq!(err.to_string(), "Overloaded, shedding load for search");
+        assert_eq!(err.retry_after(), None);
+        let err = AspectError::overloaded("search", Some(Duration::from_millis(100)));
+        assert_eq!(err.retry_after(), Some(Duration::from_millis(100)));
+    }
+
     #[test]
     fn test_from_string() {
         let err: AspectError = "error message".into();
@@ -3,9 +3,14 @@
 //! A joinpoint represents a specific point in program execution where an aspect
 //! can be applied, such as a function call.
 
+use crate::args::Arg;
 use crate::error::AspectError;
-use std::any::Any;
-use std::fmt;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt;
 
 /// Information about a specific point in program execution.
 ///
@@ -17,21 +22,26 @@ use std::fmt;
 /// ```rust
 /// use aspect_core::prelude::*;
 ///
-/// let jp = JoinPoint {
-///     function_name: "process_data",
-///     module_path: "my_app::data",
-///     location: Location {
+/// let jp = JoinPoint::new(
+///     "process_data",
+///     "my_app::data",
+///     Location {
 ///         file: "src/data.rs",
 ///         line: 42,
 ///     },
-/// };
+/// );
 ///
 /// println!("Executing: {} at {}:{}",
 ///     jp.function_name,
 ///     jp.location.file,
 ///     jp.location.line);
 /// ```
-#[derive(Debug, Clone)]
+///
+/// Fields may be added, so outside this crate it is built with
+/// [`JoinPoint::new`] or `Default`, and [`with_args`](JoinPoint::with_args),
+/// rather than a struct literal.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
 pub struct JoinPoint {
     /// The name of the function being called
     pub function_name: &'static str,
@@ -41,6 +51,10 @@ pub struct JoinPoint {
 
     /// Source code location information
     pub location: Location,
+
+    /// Arguments of the call, in declaration order (see [`crate::args`]);
+    /// [`Arg::is_redacted`] honours the rules of `redaction`
+    pub args: Vec<Arg>,
 }
 
 impl JoinPoint {
@@ -57,6 +71,7 @@ impl JoinPoint {
     ///     Location { file: "src/lib.rs", line: 100 },
     /// );
     /// ```
+    #[inline]
     pub fn new(
         function_name: &'static str,
         module_path: &'static str,
@@ -66,9 +81,31 @@ impl JoinPoint {
             function_name,
             module_path,
             location,
+            args: Vec::new(),
         }
     }
 
+    /// Sets the captured arguments.
+    #[inline]
+    pub fn with_args(mut self, args: Vec<Arg>) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Returns the argument with the given parameter name.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use aspect_core::prelude::*;
+    /// let jp = JoinPoint::new("get", "db", Location { file: "a.rs", line: 1 })
+    ///     .with_args(vec![Arg::new("id", &7u64)]);
+    /// assert_eq!(jp.arg("id").and_then(|a| a.value::<u64>()), Some(&7));
+    /// ```
+    pub fn arg(&self, name: &str) -> Option<&Arg> {
+        self.args.iter().find(|arg| arg.name == name)
+    }
+
     /// Returns the fully qualified name of the function.
     ///
     /// # Example
@@ -81,6 +118,60 @@ impl JoinPoint {
     pub fn qualified_name(&self) -> String {
         format!("{}::{}", self.module_path, self.function_name)
     }
+
+    /// Returns the kind of the joinpoint.
+    ///
+    /// The accessors `#[aspect_fields]` generates are joinpoints named
+    /// after the field they read or write, `get(Account.balance)` or
+    /// `set(Account.balance)`; all others are function executions.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use aspect_core::prelude::*;
+    /// # let location = Location { file: "a.rs", line: 1 };
+    /// let jp = JoinPoint::new("set(Account.balance)", "bank", location);
+    /// assert_eq!(jp.kind(), JoinPointKind::FieldSet);
+    /// assert_eq!(jp.field(), Some(("Account", "balance")));
+    ///
+    /// let jp = JoinPoint::new("transfer", "bank", location);
+    /// assert_eq!(jp.kind(), JoinPointKind::Execution);
+    /// assert_eq!(jp.field(), None);
+    /// ```
+    pub fn kind(&self) -> JoinPointKind {
+        parse_field_access(self.function_name).map_or(JoinPointKind::Execution, |(kind, ..)| kind)
+    }
+
+    /// Returns the struct and the field read or written, for the
+    /// joinpoints of field accessors.
+    pub fn field(&self) -> Option<(&'static str, &'static str)> {
+        parse_field_access(self.function_name).map(|(_, struct_name, field)| (struct_name, field))
+    }
+}
+
+/// What happens at a [`JoinPoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JoinPointKind {
+    /// A function runs
+    Execution,
+
+    /// A field is read through its accessor
+    FieldGet,
+
+    /// A field is written through its accessor
+    FieldSet,
+}
+
+/// The kind, struct and field of the joinpoint named `name`, if it is that
+/// of a field accessor.
+fn parse_field_access(name: &'static str) -> Option<(JoinPointKind, &'static str, &'static str)> {
+    let (kind, access) = if let Some(access) = name.strip_prefix("get(") {
+        (JoinPointKind::FieldGet, access)
+    } else {
+        (JoinPointKind::FieldSet, name.strip_prefix("set(")?)
+    };
+    let (struct_name, field) = access.strip_suffix(')')?.split_once('.')?;
+    Some((kind, struct_name, field))
 }
 
 impl fmt::Display for JoinPoint {
@@ -96,7 +187,7 @@ impl fmt::Display for JoinPoint {
 /// Source code location information.
 ///
 /// Indicates where in the source code a joinpoint occurs.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Location {
     /// The source file path
     pub file: &'static str,
@@ -136,12 +227,20 @@ impl fmt::Display for Location {
 /// ```
 pub struct ProceedingJoinPoint<'a> {
     /// The original function to execute
-    inner: Box<dyn FnOnce() -> Result<Box<dyn Any>, AspectError> + 'a>,
+    inner: Proceed<'a>,
 
     /// Context information about this joinpoint
     context: JoinPoint,
 }
 
+/// The function a [`ProceedingJoinPoint`] runs.
+enum Proceed<'a> {
+    Boxed(Box<dyn FnOnce() -> Result<Box<dyn Any>, AspectError> + 'a>),
+
+    /// Borrowed from the caller, who calls it only once
+    Borrowed(&'a mut dyn FnMut() -> Result<Box<dyn Any>, AspectError>),
+}
+
 impl<'a> ProceedingJoinPoint<'a> {
     /// Creates a new ProceedingJoinPoint.
     ///
@@ -154,7 +253,36 @@ impl<'a> ProceedingJoinPoint<'a> {
         F: FnOnce() -> Result<Box<dyn Any>, AspectError> + 'a,
     {
         Self {
-            inner: Box::new(f),
+            inner: Proceed::Boxed(Box::new(f)),
+            context,
+        }
+    }
+
+    /// Creates a ProceedingJoinPoint running a function borrowed from the
+    /// caller, which unlike [`new`](Self::new) does not allocate. This is
+    /// what `#[aspect]` generates, with the original function in an
+    /// `Option` that `f` takes, since `f` is only called once.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use aspect_core::prelude::*;
+    /// # use std::any::Any;
+    /// # let jp = JoinPoint::new("answer", "my::mod", Location { file: "a.rs", line: 1 });
+    /// let mut original = Some(|| 42);
+    /// let mut f = || {
+    ///     let original = original.take().expect("proceeded twice");
+    ///     Ok(Box::new(original()) as Box<dyn Any>)
+    /// };
+    /// let pjp = ProceedingJoinPoint::borrowed(&mut f, jp);
+    /// assert_eq!(pjp.proceed().unwrap().downcast_ref::<i32>(), Some(&42));
+    /// ```
+    pub fn borrowed(
+        f: &'a mut dyn FnMut() -> Result<Box<dyn Any>, AspectError>,
+        context: JoinPoint,
+    ) -> Self {
+        Self {
+            inner: Proceed::Borrowed(f),
             context,
         }
     }
@@ -183,7 +311,44 @@ impl<'a> ProceedingJoinPoint<'a> {
     /// # }
     /// ```
     pub fn proceed(self) -> Result<Box<dyn Any>, AspectError> {
-        (self.inner)()
+        self.proceed_with_context().0
+    }
+
+    /// Proceeds like [`proceed`](Self::proceed), and gives the joinpoint
+    /// context back, so that advice running after the function does not
+    /// need to clone it.
+    pub fn proceed_with_context(self) -> (Result<Box<dyn Any>, AspectError>, JoinPoint) {
+        let result = match self.inner {
+            Proceed::Boxed(f) => f(),
+            Proceed::Borrowed(f) => f(),
+        };
+        (result, self.context)
+    }
+
+    /// Proceeds like [`proceed`](Self::proceed), then replaces the value
+    /// the function returned with `f` of it if it is a `T`; values of other
+    /// types are returned unchanged. The replacement has the type of the
+    /// value, which woven code can always unbox, unlike a value boxed by
+    /// hand.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use aspect_core::prelude::*;
+    /// # use std::any::Any;
+    /// # struct UppercaseAspect;
+    /// # impl Aspect for UppercaseAspect {
+    /// fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+    ///     pjp.proceed_map(|name: String| name.to_uppercase())
+    /// }
+    /// # }
+    /// ```
+    pub fn proceed_map<T: Any>(self, f: impl FnOnce(T) -> T) -> Result<Box<dyn Any>, AspectError> {
+        let value = self.proceed()?;
+        Ok(match value.downcast::<T>() {
+            Ok(value) => Box::new(f(*value)),
+            Err(value) => value,
+        })
     }
 
     /// Returns a reference to the joinpoint context.
@@ -221,28 +386,28 @@ mod tests {
 
     #[test]
     fn test_joinpoint_qualified_name() {
-        let jp = JoinPoint {
-            function_name: "my_func",
-            module_path: "crate::module",
-            location: Location {
+        let jp = JoinPoint::new(
+            "my_func",
+            "crate::module",
+            Location {
                 file: "src/lib.rs",
                 line: 10,
             },
-        };
+        );
 
         assert_eq!(jp.qualified_name(), "crate::module::my_func");
     }
 
     #[test]
     fn test_joinpoint_display() {
-        let jp = JoinPoint {
-            function_name: "test",
-            module_path: "mod",
-            location: Location {
+        let jp = JoinPoint::new(
+            "test",
+            "mod",
+            Location {
                 file: "test.rs",
                 line: 42,
             },
-        };
+        );
 
         let display = format!("{}", jp);
         assert!(display.contains("test"));
@@ -251,16 +416,33 @@ mod tests {
         assert!(display.contains("42"));
     }
 
+    #[test]
+    fn test_joinpoint_kind() {
+        let location = Location {
+            file: "test.rs",
+            line: 1,
+        };
+        let jp = JoinPoint::new("get(Pair.0)", "geometry", location);
+        assert_eq!(jp.kind(), JoinPointKind::FieldGet);
+        assert_eq!(jp.field(), Some(("Pair", "0")));
+
+        for name in ["get", "get(Pair)", "set(Pair.0", "reset(Pair.0)"] {
+            let jp = JoinPoint::new(name, "geometry", location);
+            assert_eq!(jp.kind(), JoinPointKind::Execution, "{}", name);
+            assert_eq!(jp.field(), None);
+        }
+    }
+
     #[test]
     fn test_proceeding_joinpoint() {
-        let jp = JoinPoint {
-            function_name: "test",
-            module_path: "test",
-            location: Location {
+        let jp = JoinPoint::new(
+            "test",
+            "test",
+            Location {
                 file: "test.rs",
                 line: 1,
             },
-        };
+        );
 
         let pjp = ProceedingJoinPoint::new(
             || Ok(Box::new(42) as Box<dyn Any>),
@@ -273,4 +455,27 @@ mod tests {
         let value = result.downcast_ref::<i32>().unwrap();
         assert_eq!(*value, 42);
     }
+
+    #[test]
+    fn test_proceed_with_context() {
+        let jp = JoinPoint::new(
+            "test",
+            "test",
+            Location {
+                file: "test.rs",
+                line: 1,
+            },
+        );
+        let mut calls = 0;
+        let mut f = || {
+            calls += 1;
+            Ok(Box::new(()) as Box<dyn Any>)
+        };
+
+        let pjp = ProceedingJoinPoint::borrowed(&mut f, jp);
+        let (result, context) = pjp.proceed_with_context();
+        assert!(result.unwrap().is::<()>());
+        assert_eq!(context.function_name, "test");
+        assert_eq!(calls, 1);
+    }
 }
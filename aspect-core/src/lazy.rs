@@ -0,0 +1,98 @@
+//! Values initialized on first use, for the statics of woven code.
+//!
+//! `#[aspect(Type, key = value)]` keeps the aspect it builds in a static of
+//! this type: `std::sync::LazyLock` with `std`, and without it a lazy
+//! value behind a spin lock, as there is no lock of the operating system
+//! to wait on.
+
+#[cfg(feature = "std")]
+pub use std::sync::LazyLock as Lazy;
+
+#[cfg(not(feature = "std"))]
+pub use spin::Lazy;
+
+#[cfg(not(feature = "std"))]
+mod spin {
+    use core::cell::UnsafeCell;
+    use core::mem::MaybeUninit;
+    use core::ops::Deref;
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    const UNINIT: u8 = 0;
+    const RUNNING: u8 = 1;
+    const READY: u8 = 2;
+
+    /// A value computed by `init` the first time it is dereferenced.
+    pub struct Lazy<T> {
+        state: AtomicU8,
+        init: fn() -> T,
+        value: UnsafeCell<MaybeUninit<T>>,
+    }
+
+    // SAFETY: the value is only written once, by the thread that moved
+    // `state` to `RUNNING`, and only read once `state` is `READY`
+    unsafe impl<T: Send + Sync> Sync for Lazy<T> {}
+
+    impl<T> Lazy<T> {
+        /// A value computed by `init` on first use.
+        pub const fn new(init: fn() -> T) -> Self {
+            Self {
+                state: AtomicU8::new(UNINIT),
+                init,
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            }
+        }
+
+        /// The value, computing it if it is the first use.
+        pub fn force(this: &Self) -> &T {
+            loop {
+                match this.state.compare_exchange_weak(
+                    UNINIT,
+                    RUNNING,
+                    Ordering::Acquire,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => {
+                        /// Lets the next use try again if `init` panics.
+                        struct Retry<'a>(&'a AtomicU8);
+
+                        impl Drop for Retry<'_> {
+                            fn drop(&mut self) {
+                                self.0.store(UNINIT, Ordering::Release);
+                            }
+                        }
+
+                        let retry = Retry(&this.state);
+                        let value = (this.init)();
+                        // SAFETY: only this thread got to move `state`
+                        // from `UNINIT`, and nothing reads the value yet
+                        unsafe { (*this.value.get()).write(value) };
+                        core::mem::forget(retry);
+                        this.state.store(READY, Ordering::Release);
+                    }
+                    // SAFETY: `READY` is only stored once the value is
+                    // written
+                    Err(READY) => return unsafe { (*this.value.get()).assume_init_ref() },
+                    Err(_) => core::hint::spin_loop(),
+                }
+            }
+        }
+    }
+
+    impl<T> Deref for Lazy<T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            Self::force(self)
+        }
+    }
+
+    impl<T> Drop for Lazy<T> {
+        fn drop(&mut self) {
+            if *self.state.get_mut() == READY {
+                // SAFETY: the value was written
+                unsafe { self.value.get_mut().assume_init_drop() };
+            }
+        }
+    }
+}
@@ -43,24 +43,66 @@
 //! ## Thread Safety
 //!
 //! All aspects must implement `Send + Sync` to be used across thread boundaries.
+//!
+//! ## `no_std`
+//!
+//! Without its default `std` feature the crate is `no_std` and only needs
+//! `alloc`. Aspects, join points, errors, pointcuts and [`Symbol`]s are
+//! available, and so is `#[aspect]`, whose generated code only refers to
+//! `core` and `alloc`. Panics carry no backtrace, woven functions are not
+//! guarded against [reentrancy], and `#[aspect(unwind ...)]`
+//! needs `std`.
 
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 #![deny(missing_docs)]
 
+extern crate alloc;
+
+pub mod args;
 pub mod aspect;
+pub mod compose;
+pub mod config;
 pub mod error;
 pub mod joinpoint;
+mod lazy;
 pub mod pointcut;
+pub mod provider;
+#[cfg(feature = "std")]
+pub mod redaction;
+pub mod reentrancy;
+pub mod rollout;
+pub mod snapshot;
+pub mod switch;
+pub mod symbol;
 
 // Re-export core types
-pub use aspect::Aspect;
+pub use args::{Arg, ArgHasher, Redact};
+pub use aspect::{Aspect, AsyncAspect, Precedence, ReturnValue, StaticAspect};
+pub use compose::{Aspects, ComposedAspect};
+pub use config::{AspectArgs, FromAspectArgs};
 pub use error::AspectError;
-pub use joinpoint::{JoinPoint, Location, ProceedingJoinPoint};
+pub use joinpoint::{JoinPoint, JoinPointKind, Location, ProceedingJoinPoint};
+pub use snapshot::AspectSnapshot;
+pub use symbol::Symbol;
 
 /// Prelude module for convenient imports
 pub mod prelude {
-    pub use crate::aspect::Aspect;
-    pub use crate::joinpoint::{JoinPoint, Location, ProceedingJoinPoint};
+    pub use crate::args::{Arg, Redact};
+    pub use crate::aspect::{Aspect, AsyncAspect, Precedence, ReturnValue};
     pub use crate::error::AspectError;
+    pub use crate::joinpoint::{JoinPoint, JoinPointKind, Location, ProceedingJoinPoint};
+    pub use crate::snapshot::AspectSnapshot;
+}
+
+/// What the code `#[aspect]` generates needs of `alloc` and `std`, named
+/// through this crate so that it builds in `no_std` crates too. Not public
+/// API.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::lazy::Lazy;
+    pub use alloc::boxed::Box;
+    pub use alloc::vec;
+    pub use alloc::vec::Vec;
 }
 
 #[cfg(test)]
@@ -101,14 +143,14 @@ mod tests {
     #[test]
     fn test_aspect_trait() {
         let aspect = TestAspect::default();
-        let ctx = JoinPoint {
-            function_name: "test_function",
-            module_path: "test::module",
-            location: Location {
+        let ctx = JoinPoint::new(
+            "test_function",
+            "test::module",
+            Location {
                 file: "test.rs",
                 line: 42,
             },
-        };
+        );
 
         aspect.before(&ctx);
         aspect.after(&ctx, &42);
@@ -121,14 +163,14 @@ mod tests {
 
     #[test]
     fn test_joinpoint_creation() {
-        let jp = JoinPoint {
-            function_name: "my_function",
-            module_path: "my::module",
-            location: Location {
+        let jp = JoinPoint::new(
+            "my_function",
+            "my::module",
+            Location {
                 file: "src/lib.rs",
                 line: 100,
             },
-        };
+        );
 
         assert_eq!(jp.function_name, "my_function");
         assert_eq!(jp.module_path, "my::module");
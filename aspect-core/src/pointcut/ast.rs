@@ -2,6 +2,8 @@
 
 use super::pattern::{ExecutionPattern, ModulePattern};
 use super::parser::parse_pointcut;
+use alloc::boxed::Box;
+use alloc::string::String;
 
 /// A pointcut expression that matches joinpoints (functions).
 #[derive(Debug, Clone, PartialEq)]
@@ -49,6 +51,7 @@ impl Pointcut {
     }
 
     /// Create a NOT pointcut.
+    #[allow(clippy::should_implement_trait)]
     pub fn not(self) -> Pointcut {
         Pointcut::Not(Box::new(self))
     }
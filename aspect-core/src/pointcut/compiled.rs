@@ -0,0 +1,316 @@
+//! Pointcuts compiled for matching at run time.
+//!
+//! Matching a [`Pointcut`] walks its tree recursively and builds the
+//! `path::` prefix of every `within` on each call. [`Pointcut::compile`]
+//! flattens the tree once into a vector of ops instead: predicates set a
+//! single boolean, and jumps skip what `&&` and `||` do not need, so that
+//! matching is one loop without recursion, allocation or a stack. The
+//! strings compared for equality are interned when compiling, so that
+//! they are compared as the [`Symbol`]s of the [`FunctionInfo`].
+
+use super::ast::Pointcut;
+use super::matcher::{FunctionInfo, Matcher};
+use super::pattern::NamePattern;
+use crate::symbol::Symbol;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// A pointcut compiled by [`Pointcut::compile`].
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::pointcut::{FunctionInfo, Matcher, Pointcut};
+///
+/// let pointcut = Pointcut::parse("execution(pub fn *(..)) && within(crate::api)").unwrap();
+/// let matcher = pointcut.compile();
+/// assert!(matcher.matches(&FunctionInfo::new("save", "crate::api::users", "pub")));
+/// assert!(!matcher.matches(&FunctionInfo::new("save", "crate::db", "pub")));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledMatcher {
+    ops: Vec<Op>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Op {
+    /// Match any function
+    True,
+    Visibility(Symbol),
+    ExactName(Symbol),
+    Name(NamePattern),
+    ReturnType(String),
+
+    /// The module `path` or one of its submodules, `prefix` being `path::`
+    Module {
+        path: Symbol,
+        prefix: String,
+    },
+
+    /// Negate the value
+    Not,
+
+    /// Go to the op at the index if the value is false, for `&&`
+    JumpIfFalse(usize),
+
+    /// Go to the op at the index if the value is true, for `||`
+    JumpIfTrue(usize),
+}
+
+impl Pointcut {
+    /// Compile the pointcut into a [`CompiledMatcher`], which matches the
+    /// same functions faster. Worth it for pointcuts matched many times,
+    /// like those of the aspect registry.
+    pub fn compile(&self) -> CompiledMatcher {
+        let mut ops = Vec::new();
+        compile_into(self, &mut ops);
+        CompiledMatcher { ops }
+    }
+}
+
+fn compile_into(pointcut: &Pointcut, ops: &mut Vec<Op>) {
+    match pointcut {
+        Pointcut::Execution(pattern) => {
+            let mut predicates = Vec::new();
+            if let Some(visibility) = &pattern.visibility {
+                predicates.push(Op::Visibility(Symbol::intern(visibility.as_str())));
+            }
+            match &pattern.name {
+                NamePattern::Wildcard => {}
+                NamePattern::Exact(name) => predicates.push(Op::ExactName(Symbol::intern(name))),
+                name => predicates.push(Op::Name(name.clone())),
+            }
+            if let Some(return_type) = &pattern.return_type {
+                predicates.push(Op::ReturnType(return_type.clone()));
+            }
+            if predicates.is_empty() {
+                predicates.push(Op::True);
+            }
+
+            // The checks of the pattern are an `&&` of their own
+            let mut jumps = Vec::new();
+            let last = predicates.len() - 1;
+            for (i, predicate) in predicates.into_iter().enumerate() {
+                ops.push(predicate);
+                if i < last {
+                    jumps.push(ops.len());
+                    ops.push(Op::JumpIfFalse(0));
+                }
+            }
+            patch(ops, &jumps);
+        }
+        Pointcut::Within(pattern) => ops.push(Op::Module {
+            path: Symbol::intern(&pattern.path),
+            prefix: format!("{}::", pattern.path),
+        }),
+        Pointcut::And(left, right) => {
+            compile_into(left, ops);
+            let jump = ops.len();
+            ops.push(Op::JumpIfFalse(0));
+            compile_into(right, ops);
+            patch(ops, &[jump]);
+        }
+        Pointcut::Or(left, right) => {
+            compile_into(left, ops);
+            let jump = ops.len();
+            ops.push(Op::JumpIfTrue(0));
+            compile_into(right, ops);
+            patch(ops, &[jump]);
+        }
+        Pointcut::Not(inner) => {
+            compile_into(inner, ops);
+            ops.push(Op::Not);
+        }
+    }
+}
+
+/// Have the jumps at `jumps` go past the last op.
+fn patch(ops: &mut [Op], jumps: &[usize]) {
+    let end = ops.len();
+    for &jump in jumps {
+        match &mut ops[jump] {
+            Op::JumpIfFalse(target) | Op::JumpIfTrue(target) => *target = end,
+            op => unreachable!("{:?} is not a jump", op),
+        }
+    }
+}
+
+impl Matcher for CompiledMatcher {
+    fn matches(&self, function: &FunctionInfo) -> bool {
+        let mut value = true;
+        let mut next = 0;
+        while let Some(op) = self.ops.get(next) {
+            next += 1;
+            match op {
+                Op::True => value = true,
+                Op::Visibility(visibility) => value = function.visibility == *visibility,
+                Op::ExactName(name) => value = function.name == *name,
+                Op::Name(name) => value = name.matches(&function.name),
+                Op::ReturnType(expected) => {
+                    value = function
+                        .return_type
+                        .is_some_and(|actual| actual.contains(expected.as_str()))
+                }
+                Op::Module { path, prefix } => {
+                    value = function.module_path == *path
+                        || function.module_path.starts_with(prefix.as_str())
+                }
+                Op::Not => value = !value,
+                Op::JumpIfFalse(target) if !value => next = *target,
+                Op::JumpIfTrue(target) if value => next = *target,
+                Op::JumpIfFalse(_) | Op::JumpIfTrue(_) => {}
+            }
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pointcut::pattern::Visibility;
+
+    #[test]
+    fn test_compiled_matches_like_pointcut() {
+        let pointcuts = [
+            "execution(pub fn *(..))",
+            "execution(fn *(..))",
+            "execution(pub fn save*(..) -> Result)",
+            "within(crate::api)",
+            "execution(pub fn *(..)) && within(crate::api)",
+            "within(crate::api) || execution(fn *_user(..))",
+            "!within(crate::api) && !execution(pub(crate) fn *(..))",
+            "(within(crate::db) || within(crate::api)) && !execution(fn *save*(..))",
+        ];
+        let functions = [
+            FunctionInfo::new("save_user", "crate::api", "pub")
+                .with_return_type("Result<(), Error>"),
+            FunctionInfo::new("save", "crate::api::users", "pub(crate)"),
+            FunctionInfo::new("load_user", "crate::apis", ""),
+            FunctionInfo::new("query", "crate::db", "pub"),
+            FunctionInfo::new("main", "crate", ""),
+        ];
+        for pointcut in pointcuts {
+            let parsed = Pointcut::parse(pointcut).unwrap();
+            let compiled = parsed.compile();
+            for function in &functions {
+                assert_eq!(
+                    compiled.matches(function),
+                    parsed.matches(function),
+                    "{} on {:?}",
+                    pointcut,
+                    function
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_compile() {
+        let pointcut =
+            Pointcut::parse("execution(pub fn get*(..)) || !within(crate::api)").unwrap();
+        assert_eq!(
+            pointcut.compile().ops,
+            [
+                Op::Visibility(Symbol::intern("pub")),
+                Op::JumpIfFalse(3),
+                Op::Name(NamePattern::Prefix("get".to_string())),
+                Op::JumpIfTrue(6),
+                Op::Module {
+                    path: Symbol::intern("crate::api"),
+                    prefix: "crate::api::".to_string(),
+                },
+                Op::Not,
+            ]
+        );
+        assert_eq!(Pointcut::all_functions().compile().ops, [Op::True]);
+        assert_eq!(
+            Pointcut::parse("execution(fn save(..))")
+                .unwrap()
+                .compile()
+                .ops,
+            [Op::ExactName(Symbol::intern("save"))]
+        );
+    }
+
+    mod proptests {
+        use super::*;
+        use crate::pointcut::pattern::{ExecutionPattern, ModulePattern};
+        use proptest::prelude::*;
+
+        fn arb_name() -> impl Strategy<Value = String> {
+            prop::sample::select(vec!["save", "save_user", "load", "user"]).prop_map(String::from)
+        }
+
+        fn arb_module() -> impl Strategy<Value = String> {
+            prop::sample::select(vec![
+                "crate",
+                "crate::api",
+                "crate::api::users",
+                "crate::apis",
+            ])
+            .prop_map(String::from)
+        }
+
+        fn arb_visibility() -> impl Strategy<Value = &'static str> {
+            prop::sample::select(vec!["pub", "pub(crate)", "pub(super)", ""])
+        }
+
+        fn arb_pointcut() -> impl Strategy<Value = Pointcut> {
+            let name = prop_oneof![
+                Just(NamePattern::Wildcard),
+                arb_name().prop_map(NamePattern::Exact),
+                arb_name().prop_map(NamePattern::Prefix),
+                arb_name().prop_map(NamePattern::Suffix),
+                arb_name().prop_map(NamePattern::Contains),
+            ];
+            let visibility = prop::option::of(prop_oneof![
+                Just(Visibility::Public),
+                Just(Visibility::Crate),
+                Just(Visibility::Super),
+                Just(Visibility::Private),
+            ]);
+            let return_type = prop::option::of(prop::sample::select(vec!["Result", "u32"]));
+            let leaf = prop_oneof![
+                (visibility, name, return_type).prop_map(|(visibility, name, return_type)| {
+                    Pointcut::Execution(ExecutionPattern {
+                        visibility,
+                        name,
+                        return_type: return_type.map(String::from),
+                    })
+                }),
+                arb_module().prop_map(|path| Pointcut::Within(ModulePattern::new(path))),
+            ];
+            leaf.prop_recursive(4, 32, 2, |inner| {
+                prop_oneof![
+                    (inner.clone(), inner.clone()).prop_map(|(left, right)| left.and(right)),
+                    (inner.clone(), inner.clone()).prop_map(|(left, right)| left.or(right)),
+                    inner.prop_map(Pointcut::not),
+                ]
+            })
+        }
+
+        fn arb_function() -> impl Strategy<Value = FunctionInfo> {
+            let return_type = prop::option::of(prop::sample::select(vec!["Result<u32>", "()"]));
+            (arb_name(), arb_module(), arb_visibility(), return_type).prop_map(
+                |(name, module, visibility, return_type)| FunctionInfo {
+                    name: name.into(),
+                    module_path: module.into(),
+                    visibility: visibility.into(),
+                    return_type: return_type.map(Symbol::intern),
+                },
+            )
+        }
+
+        proptest! {
+            #[test]
+            fn compiled_matches_like_pointcut(
+                pointcut in arb_pointcut(),
+                function in arb_function()
+            ) {
+                prop_assert_eq!(pointcut.compile().matches(&function), pointcut.matches(&function));
+            }
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Which functions a set of pointcuts covers.
+//!
+//! [`coverage`] matches every pointcut against every function once, and
+//! reports the functions each pointcut matches, the functions no pointcut
+//! matches, the pairs of pointcuts matching the same functions, and the
+//! modules no pointcut reaches at all. Tools listing the functions of a
+//! program, such as `cargo aspect report`, can build on it.
+//!
+//! # Example
+//!
+//! ```rust
+//! use aspect_core::pointcut::coverage::coverage;
+//! use aspect_core::pointcut::{FunctionInfo, Pointcut};
+//!
+//! let pointcuts = [
+//!     Pointcut::parse("within(crate::api)").unwrap(),
+//!     Pointcut::parse("execution(pub fn save*(..))").unwrap(),
+//! ];
+//! let functions = [
+//!     FunctionInfo::new("save_user", "crate::api", "pub"),
+//!     FunctionInfo::new("get_user", "crate::api", "pub"),
+//!     FunctionInfo::new("connect", "crate::db", "pub"),
+//! ];
+//!
+//! let report = coverage(&pointcuts, &functions);
+//! assert_eq!(report.covered().count(), 2);
+//! assert_eq!(report.overlaps()[0].functions, [0]);
+//! assert_eq!(report.uncovered_modules(), ["crate::db"]);
+//! ```
+
+use super::ast::Pointcut;
+use super::matcher::{FunctionInfo, Matcher};
+use crate::symbol::Symbol;
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The pointcuts matching a function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionCoverage {
+    /// The function
+    pub function: FunctionInfo,
+
+    /// Indexes of the pointcuts matching the function, in increasing order
+    pub pointcuts: Vec<usize>,
+}
+
+impl FunctionCoverage {
+    /// Whether a pointcut matches the function.
+    pub fn is_covered(&self) -> bool {
+        !self.pointcuts.is_empty()
+    }
+}
+
+/// Two pointcuts matching some of the same functions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlap {
+    /// Indexes of the pointcuts, the lower first
+    pub pointcuts: (usize, usize),
+
+    /// Indexes of the functions both match, in increasing order
+    pub functions: Vec<usize>,
+}
+
+/// What [`coverage`] found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageReport {
+    functions: Vec<FunctionCoverage>,
+    matched: Vec<Vec<usize>>,
+    overlaps: Vec<Overlap>,
+    uncovered_modules: Vec<Symbol>,
+}
+
+impl CoverageReport {
+    /// Every function, in the order given, with the pointcuts matching it.
+    pub fn functions(&self) -> &[FunctionCoverage] {
+        &self.functions
+    }
+
+    /// The functions some pointcut matches.
+    pub fn covered(&self) -> impl Iterator<Item = &FunctionCoverage> {
+        self.functions
+            .iter()
+            .filter(|function| function.is_covered())
+    }
+
+    /// The functions no pointcut matches.
+    pub fn uncovered(&self) -> impl Iterator<Item = &FunctionCoverage> {
+        self.functions
+            .iter()
+            .filter(|function| !function.is_covered())
+    }
+
+    /// The share of the functions some pointcut matches, from 0 to 1; 1
+    /// when there are no functions.
+    pub fn ratio(&self) -> f64 {
+        match self.functions.len() {
+            0 => 1.0,
+            total => self.covered().count() as f64 / total as f64,
+        }
+    }
+
+    /// Indexes of the functions the `pointcut`th pointcut matches, in
+    /// increasing order.
+    pub fn matched_by(&self, pointcut: usize) -> &[usize] {
+        &self.matched[pointcut]
+    }
+
+    /// Indexes of the pointcuts matching no function.
+    pub fn unused_pointcuts(&self) -> Vec<usize> {
+        (0..self.matched.len())
+            .filter(|&pointcut| self.matched[pointcut].is_empty())
+            .collect()
+    }
+
+    /// The pairs of pointcuts matching some of the same functions, by the
+    /// index of their first then second pointcut.
+    pub fn overlaps(&self) -> &[Overlap] {
+        &self.overlaps
+    }
+
+    /// The modules none of whose functions a pointcut matches, sorted.
+    /// Modules are those of the functions, each apart from its submodules.
+    pub fn uncovered_modules(&self) -> &[Symbol] {
+        &self.uncovered_modules
+    }
+}
+
+/// Match `pointcuts` against `functions`.
+pub fn coverage(pointcuts: &[Pointcut], functions: &[FunctionInfo]) -> CoverageReport {
+    let matchers: Vec<_> = pointcuts.iter().map(Pointcut::compile).collect();
+
+    let mut matched = vec![Vec::new(); pointcuts.len()];
+    let functions: Vec<FunctionCoverage> = functions
+        .iter()
+        .enumerate()
+        .map(|(index, function)| {
+            let pointcuts: Vec<usize> = matchers
+                .iter()
+                .enumerate()
+                .filter(|(_, matcher)| matcher.matches(function))
+                .map(|(pointcut, _)| pointcut)
+                .collect();
+            for &pointcut in &pointcuts {
+                matched[pointcut].push(index);
+            }
+            FunctionCoverage {
+                function: *function,
+                pointcuts,
+            }
+        })
+        .collect();
+
+    let mut overlaps: BTreeMap<(usize, usize), Vec<usize>> = BTreeMap::new();
+    for (index, function) in functions.iter().enumerate() {
+        for (i, &first) in function.pointcuts.iter().enumerate() {
+            for &second in &function.pointcuts[i + 1..] {
+                overlaps.entry((first, second)).or_default().push(index);
+            }
+        }
+    }
+    let overlaps = overlaps
+        .into_iter()
+        .map(|(pointcuts, functions)| Overlap {
+            pointcuts,
+            functions,
+        })
+        .collect();
+
+    // Whether each module has a covered function
+    let mut modules: BTreeMap<Symbol, bool> = BTreeMap::new();
+    for function in &functions {
+        *modules.entry(function.function.module_path).or_default() |= function.is_covered();
+    }
+    let uncovered_modules = modules
+        .into_iter()
+        .filter(|(_, covered)| !covered)
+        .map(|(module, _)| module)
+        .collect();
+
+    CoverageReport {
+        functions,
+        matched,
+        overlaps,
+        uncovered_modules,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pointcuts(patterns: &[&str]) -> Vec<Pointcut> {
+        patterns
+            .iter()
+            .map(|pattern| Pointcut::parse(pattern).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_coverage() {
+        let pointcuts = pointcuts(&[
+            "within(crate::api)",
+            "execution(pub fn save*(..))",
+            "execution(pub fn delete*(..)) && within(crate::api)",
+            "within(crate::admin)",
+        ]);
+        let functions = [
+            FunctionInfo::new("save_user", "crate::api", "pub"),
+            FunctionInfo::new("delete_user", "crate::api::users", "pub"),
+            FunctionInfo::new("save_row", "crate::db", "pub"),
+            FunctionInfo::new("connect", "crate::db", "pub"),
+            FunctionInfo::new("main", "crate", "pub"),
+            FunctionInfo::new("parse", "crate::cli", "fn"),
+        ];
+
+        let report = coverage(&pointcuts, &functions);
+        let matching: Vec<&[usize]> = report
+            .functions()
+            .iter()
+            .map(|function| function.pointcuts.as_slice())
+            .collect();
+        assert_eq!(matching, [&[0, 1][..], &[0, 2], &[1], &[], &[], &[]]);
+        assert_eq!(report.matched_by(1), [0, 2]);
+        assert_eq!(report.unused_pointcuts(), [3]);
+        assert_eq!(report.ratio(), 0.5);
+        let uncovered: Vec<&str> = report
+            .uncovered()
+            .map(|function| function.function.name.as_str())
+            .collect();
+        assert_eq!(uncovered, ["connect", "main", "parse"]);
+
+        assert_eq!(
+            report.overlaps(),
+            [
+                Overlap {
+                    pointcuts: (0, 1),
+                    functions: vec![0],
+                },
+                Overlap {
+                    pointcuts: (0, 2),
+                    functions: vec![1],
+                },
+            ]
+        );
+        // `crate::db` has a covered function
+        assert_eq!(report.uncovered_modules(), ["crate", "crate::cli"]);
+    }
+
+    #[test]
+    fn test_empty() {
+        let report = coverage(&[], &[]);
+        assert_eq!(report.ratio(), 1.0);
+        assert!(report.overlaps().is_empty());
+        assert!(report.uncovered_modules().is_empty());
+    }
+}
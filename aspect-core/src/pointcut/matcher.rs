@@ -2,29 +2,35 @@
 
 use super::ast::Pointcut;
 use super::pattern::{ExecutionPattern, ModulePattern};
+use crate::joinpoint::{JoinPoint, Location};
+use crate::symbol::Symbol;
+use alloc::vec;
 
 /// Information about a function for pointcut matching.
-#[derive(Debug, Clone)]
+///
+/// The strings are interned [`Symbol`]s, so that function infos are cheap
+/// to copy, compare and hash, like the registry does to cache matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct FunctionInfo {
     /// Function name
-    pub name: String,
+    pub name: Symbol,
 
     /// Module path (e.g., "crate::api::users")
-    pub module_path: String,
+    pub module_path: Symbol,
 
     /// Visibility as a string ("pub", "pub(crate)", etc.)
-    pub visibility: String,
+    pub visibility: Symbol,
 
     /// Return type as a string (simplified)
-    pub return_type: Option<String>,
+    pub return_type: Option<Symbol>,
 }
 
 impl FunctionInfo {
     /// Create function info for testing.
     pub fn new(
-        name: impl Into<String>,
-        module_path: impl Into<String>,
-        visibility: impl Into<String>,
+        name: impl Into<Symbol>,
+        module_path: impl Into<Symbol>,
+        visibility: impl Into<Symbol>,
     ) -> Self {
         Self {
             name: name.into(),
@@ -35,10 +41,24 @@ impl FunctionInfo {
     }
 
     /// Set the return type.
-    pub fn with_return_type(mut self, return_type: impl Into<String>) -> Self {
+    pub fn with_return_type(mut self, return_type: impl Into<Symbol>) -> Self {
         self.return_type = Some(return_type.into());
         self
     }
+
+    /// The join point of a call of the function, where its location and
+    /// arguments are unknown.
+    pub fn join_point(&self) -> JoinPoint {
+        JoinPoint {
+            function_name: self.name.as_str(),
+            module_path: self.module_path.as_str(),
+            location: Location {
+                file: "unknown",
+                line: 0,
+            },
+            args: vec![],
+        }
+    }
 }
 
 /// Matcher trait for evaluating pointcuts against functions.
@@ -19,11 +19,15 @@
 //! ```
 
 pub mod ast;
+pub mod compiled;
+pub mod coverage;
 pub mod matcher;
 pub mod parser;
 pub mod pattern;
 
 pub use ast::Pointcut;
+pub use compiled::CompiledMatcher;
+pub use coverage::{coverage, CoverageReport};
 pub use matcher::{FunctionInfo, Matcher};
 pub use parser::parse_pointcut;
 pub use pattern::{ExecutionPattern, ModulePattern, NamePattern, Visibility};
@@ -8,6 +8,10 @@
 
 use super::ast::Pointcut;
 use super::pattern::{ExecutionPattern, ModulePattern, NamePattern, Visibility};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
 /// Parse a pointcut expression from a string.
 ///
@@ -32,8 +36,8 @@ pub fn parse_pointcut(input: &str) -> Result<Pointcut, String> {
     }
 
     // Handle NOT operator (highest precedence)
-    if input.starts_with('!') {
-        let inner = parse_pointcut(input[1..].trim())?;
+    if let Some(rest) = input.strip_prefix('!') {
+        let inner = parse_pointcut(rest.trim())?;
         return Ok(Pointcut::Not(Box::new(inner)));
     }
 
@@ -168,12 +172,12 @@ fn parse_within(input: &str) -> Result<Pointcut, String> {
 /// Parse visibility from the beginning of a string.
 /// Returns (Option<Visibility>, remaining_string)
 fn parse_visibility(input: &str) -> (Option<Visibility>, &str) {
-    if input.starts_with("pub(crate) ") {
-        (Some(Visibility::Crate), &input[11..])
-    } else if input.starts_with("pub(super) ") {
-        (Some(Visibility::Super), &input[11..])
-    } else if input.starts_with("pub ") {
-        (Some(Visibility::Public), &input[4..])
+    if let Some(rest) = input.strip_prefix("pub(crate) ") {
+        (Some(Visibility::Crate), rest)
+    } else if let Some(rest) = input.strip_prefix("pub(super) ") {
+        (Some(Visibility::Super), rest)
+    } else if let Some(rest) = input.strip_prefix("pub ") {
+        (Some(Visibility::Public), rest)
     } else {
         (None, input)
     }
@@ -185,10 +189,10 @@ fn parse_name_pattern(name: &str) -> NamePattern {
         NamePattern::Wildcard
     } else if name.starts_with('*') && name.ends_with('*') && name.len() > 2 {
         NamePattern::Contains(name[1..name.len() - 1].to_string())
-    } else if name.starts_with('*') {
-        NamePattern::Suffix(name[1..].to_string())
-    } else if name.ends_with('*') {
-        NamePattern::Prefix(name[..name.len() - 1].to_string())
+    } else if let Some(suffix) = name.strip_prefix('*') {
+        NamePattern::Suffix(suffix.to_string())
+    } else if let Some(prefix) = name.strip_suffix('*') {
+        NamePattern::Prefix(prefix.to_string())
     } else {
         NamePattern::Exact(name.to_string())
     }
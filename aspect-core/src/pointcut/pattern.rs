@@ -1,5 +1,8 @@
 //! Pattern types for matching functions.
 
+use alloc::format;
+use alloc::string::String;
+
 /// Function visibility pattern.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Visibility {
@@ -16,12 +19,16 @@ pub enum Visibility {
 impl Visibility {
     /// Check if a visibility string matches this pattern.
     pub fn matches(&self, vis: &str) -> bool {
-        match (self, vis) {
-            (Visibility::Public, "pub") => true,
-            (Visibility::Crate, "pub(crate)") => true,
-            (Visibility::Super, "pub(super)") => true,
-            (Visibility::Private, "") => true,
-            _ => false,
+        self.as_str() == vis
+    }
+
+    /// The visibility string this pattern matches.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::Public => "pub",
+            Visibility::Crate => "pub(crate)",
+            Visibility::Super => "pub(super)",
+            Visibility::Private => "",
         }
     }
 }
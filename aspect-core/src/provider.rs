@@ -0,0 +1,113 @@
+//! Aspects provided by crates to others.
+//!
+//! Any crate can provide aspects, as `aspect-std` does: it lists them in
+//! the `[package.metadata.aspect]` table of its `Cargo.toml`, and expands
+//! `aspect_macros::aspect_provider!()` at its root. `cargo aspect list`
+//! then shows them in every workspace depending on the crate, and the
+//! compiler driver matches their pointcuts in its weaving plan.
+//!
+//! # Manifest
+//!
+//! ```toml
+//! [[package.metadata.aspect.provides]]
+//! # Path of the aspect type from the root of the crate
+//! name = "RetryAspect"
+//! # One line shown by `cargo aspect list` (optional)
+//! description = "Retry failed calls with backoff"
+//! # Functions the aspect is meant for, matched in weaving plans (optional)
+//! pointcut = "execution(pub fn *(..)) && within(crate::client)"
+//! # "before", "after", "after_error" or "around" (the default)
+//! advice = "around"
+//! # Lower runs first, like for #[advice] (optional, 0 by default)
+//! order = 10
+//! # Features of the crate the type needs (optional)
+//! features = ["std"]
+//! ```
+//!
+//! Tools read the table from `cargo metadata`, and ignore the keys they do
+//! not know, so that later versions of this format can add some.
+//!
+//! # Registration
+//!
+//! ```ignore
+//! aspect_macros::aspect_provider!();
+//! ```
+//!
+//! checks the manifest as the crate compiles: unknown keys, invalid
+//! pointcuts, and names that are not types implementing
+//! [`Aspect`](crate::Aspect) (with their features enabled) are compile
+//! errors. It also defines `ASPECT_PROVIDER`, an [`AspectProvider`] with
+//! the contents of the manifest, for programs discovering the aspects of
+//! their dependencies at run time.
+
+/// The `[package.metadata]` key of the manifest.
+pub const METADATA_KEY: &str = "aspect";
+
+/// A crate providing aspects, as declared in its manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AspectProvider {
+    /// Name of the package, e.g. `aspect-std`
+    pub package: &'static str,
+
+    /// Name of the crate in paths, e.g. `aspect_std`
+    pub crate_name: &'static str,
+
+    /// Version of the package
+    pub version: &'static str,
+
+    /// The aspects provided, in the order of the manifest
+    pub aspects: &'static [ProvidedAspect],
+}
+
+impl AspectProvider {
+    /// The aspect named `name` (its path from the crate root).
+    pub fn get(&self, name: &str) -> Option<&ProvidedAspect> {
+        self.aspects.iter().find(|aspect| aspect.name == name)
+    }
+}
+
+/// An aspect of an [`AspectProvider`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProvidedAspect {
+    /// Path of the aspect type from the crate root, e.g. `LoggingAspect`
+    pub name: &'static str,
+
+    /// One line describing the aspect, empty when the manifest has none
+    pub description: &'static str,
+
+    /// Pointcut of the functions the aspect is meant for
+    pub pointcut: Option<&'static str>,
+
+    /// Advice type: "before", "after", "after_error" or "around"
+    pub advice: &'static str,
+
+    /// Execution order (lower runs first)
+    pub order: i32,
+
+    /// Features of the crate the aspect type needs
+    pub features: &'static [&'static str],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        const PROVIDER: AspectProvider = AspectProvider {
+            package: "retry-aspects",
+            crate_name: "retry_aspects",
+            version: "1.0.0",
+            aspects: &[ProvidedAspect {
+                name: "RetryAspect",
+                description: "Retry failed calls",
+                pointcut: None,
+                advice: "around",
+                order: 0,
+                features: &[],
+            }],
+        };
+        assert_eq!(PROVIDER.get("RetryAspect").unwrap().advice, "around");
+        assert!(PROVIDER.get("retry").is_none());
+    }
+}
@@ -0,0 +1,150 @@
+//! Redaction rules shared by every aspect showing arguments.
+//!
+//! Arguments of [`Redact`](crate::Redact) types are always redacted. The
+//! rules registered here redact more, for the whole process: arguments
+//! whose parameter name matches a pattern, arguments of a given type, or
+//! arguments a custom redactor picks. [`Arg::is_redacted`] consults them,
+//! so [`Arg`]'s `Debug`, [`Arg::debug`] and the logging, audit, tracing
+//! and error reporting aspects of `aspect-std` all hide these arguments
+//! without being configured one by one. Aspects of other crates rendering
+//! arguments should likewise check [`Arg::is_redacted`].
+//!
+//! Rules only ever add redaction. Checking them costs one atomic load
+//! while none is registered. Available with the `std` feature.
+//!
+//! # Example
+//!
+//! ```rust
+//! use aspect_core::{redaction, Arg};
+//!
+//! redaction::redact_name("*ssn*");
+//! redaction::redact_type::<std::net::IpAddr>();
+//!
+//! let ssn = Arg::new("customer_ssn", &"078-05-1120".to_string());
+//! assert!(ssn.is_redacted());
+//! assert_eq!(format!("{:?}", ssn), "customer_ssn: <redacted>");
+//! ```
+
+use crate::args::Arg;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{PoisonError, RwLock};
+
+/// A custom redactor: whether to redact an argument.
+type RedactFn = Box<dyn Fn(&Arg) -> bool + Send + Sync>;
+
+enum Rule {
+    /// Glob over the parameter name
+    Name(String),
+    /// Type name, as in [`Arg::type_name`]
+    Type(&'static str),
+    Custom(RedactFn),
+}
+
+impl Rule {
+    fn matches(&self, arg: &Arg) -> bool {
+        match self {
+            Self::Name(pattern) => name_matches(pattern, arg.name),
+            Self::Type(type_name) => arg.type_name == *type_name,
+            Self::Custom(redact) => redact(arg),
+        }
+    }
+}
+
+/// Whether any rule is registered, checked before taking the lock.
+static ANY_RULES: AtomicBool = AtomicBool::new(false);
+
+static RULES: RwLock<Vec<Rule>> = RwLock::new(Vec::new());
+
+fn register(rule: Rule) {
+    RULES
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(rule);
+    ANY_RULES.store(true, Ordering::Release);
+}
+
+/// Redact the arguments whose parameter name matches `pattern`, a
+/// case-insensitive glob over the whole name where `*` matches any
+/// sequence of characters, e.g. `*card_number*`.
+pub fn redact_name(pattern: &str) {
+    register(Rule::Name(pattern.to_string()));
+}
+
+/// Redact the arguments of type `T`.
+///
+/// Types are compared by name with the type captured for the parameter,
+/// which is the referent for reference parameters: `redact_type::<str>()`
+/// redacts `&str` parameters, `redact_type::<String>()` `String` and
+/// `&String` ones.
+pub fn redact_type<T: ?Sized + 'static>() {
+    register(Rule::Type(core::any::type_name::<T>()));
+}
+
+/// Redact the arguments for which `redact` returns `true`, e.g. based on
+/// their value.
+///
+/// It runs whenever an argument is checked, so it should be quick, and must
+/// neither register rules nor check arguments itself.
+pub fn redact_if(redact: impl Fn(&Arg) -> bool + Send + Sync + 'static) {
+    register(Rule::Custom(Box::new(redact)));
+}
+
+/// Remove every registered rule.
+pub fn clear() {
+    RULES
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+    ANY_RULES.store(false, Ordering::Release);
+}
+
+/// Whether a registered rule redacts `arg`.
+pub fn matches(arg: &Arg) -> bool {
+    if !ANY_RULES.load(Ordering::Acquire) {
+        return false;
+    }
+    RULES
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .any(|rule| rule.matches(arg))
+}
+
+/// Whether `name` matches `pattern`, a glob where `*` matches any sequence
+/// of characters, ignoring ASCII case.
+pub fn name_matches(pattern: &str, name: &str) -> bool {
+    glob_match(pattern.as_bytes(), name.as_bytes())
+}
+
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.iter().position(|&c| c == b'*') {
+        None => pattern.eq_ignore_ascii_case(text),
+        Some(star) => {
+            let (prefix, rest) = pattern.split_at(star);
+            if text.len() < prefix.len() || !text[..star].eq_ignore_ascii_case(prefix) {
+                return false;
+            }
+            let text = &text[star..];
+            (0..=text.len()).any(|i| glob_match(&rest[1..], &text[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_matches() {
+        assert!(name_matches("*token*", "refresh_token"));
+        assert!(name_matches("*token*", "token"));
+        assert!(name_matches("pass*", "Passphrase"));
+        assert!(!name_matches("pass*", "bypass"));
+        assert!(name_matches("ssn", "SSN"));
+        assert!(!name_matches("ssn", "ssn_hash"));
+        assert!(name_matches("*_id*key", "user_id_api_key"));
+    }
+}
@@ -0,0 +1,291 @@
+//! Guard keeping advice from instrumenting itself.
+//!
+//! Advice calling a function that is itself advised, such as a logging
+//! aspect calling a woven helper, would run the aspect again from within
+//! its own advice, nesting without end when the helper is advised by the
+//! same aspect. Woven code therefore marks the aspect whose advice runs,
+//! with [`advice`], and functions called from that advice run without that
+//! aspect: the other aspects of the function still apply. The aspect is
+//! known by its [`Aspect::reentrancy_name`], the name of its type.
+//!
+//! What the advice proceeds to, the next aspect or the advised function,
+//! runs as the woven function was called, outside the advice: woven code
+//! wraps it with [`proceeding`].
+//!
+//! Aspects of [`Precedence::SECURITY`] are never skipped: a function
+//! checking access checks it also when advice calls it, e.g. a fallback
+//! computing its value with a protected function. Their own advice must
+//! therefore not call functions they advise.
+//!
+//! The kill switch of [`switch`](crate::switch), while off, skips
+//! every aspect the same way, again but for those of
+//! [`Precedence::SECURITY`].
+//!
+//! The aspects of the framework itself are also never advised: the
+//! registry matches no function of the [`FRAMEWORK_CRATES`], as if every
+//! pointcut ended with `&& !within(aspect_std)`.
+//!
+//! The guard is per thread: advice handing work to other threads or tasks
+//! does not carry it, and the asynchronous advice of `async fn`s, which
+//! awaits, is not guarded. Without the `std` feature, there is no guard.
+//!
+//! ```rust
+//! use aspect_core::prelude::*;
+//! use aspect_core::reentrancy;
+//!
+//! struct Logger;
+//! impl Aspect for Logger {}
+//!
+//! struct Auth;
+//! impl Aspect for Auth {
+//!     fn precedence(&self) -> Precedence {
+//!         Precedence::SECURITY
+//!     }
+//! }
+//!
+//! // What the advice proceeds to
+//! let target = reentrancy::proceeding(|| assert!(!reentrancy::skips(&Logger)));
+//! reentrancy::advice(&Logger, || {
+//!     assert!(reentrancy::skips(&Logger));
+//!     assert!(!reentrancy::skips(&Auth));
+//!     target();
+//! });
+//! assert!(!reentrancy::in_advice());
+//! ```
+//!
+//! [`Aspect::reentrancy_name`]: crate::Aspect::reentrancy_name
+
+use crate::aspect::{LocalAspect, Precedence};
+use crate::switch;
+#[cfg(feature = "std")]
+use core::cell::Cell;
+
+/// The crates whose functions no pointcut of the registry matches.
+pub const FRAMEWORK_CRATES: &[&str] = &["aspect_core", "aspect_runtime", "aspect_std"];
+
+/// How many nested advice the guard tells apart; deeper, it skips every
+/// aspect but those of [`Precedence::SECURITY`].
+#[cfg(feature = "std")]
+const DEPTH: usize = 8;
+
+/// The aspects whose advice is running on a thread, the outermost first.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy)]
+struct Advising {
+    names: [&'static str; DEPTH],
+    len: usize,
+}
+
+#[cfg(feature = "std")]
+impl Advising {
+    const NONE: Self = Self {
+        names: [""; DEPTH],
+        len: 0,
+    };
+
+    fn contains(&self, name: &str) -> bool {
+        self.len > DEPTH || self.names[..self.len].contains(&name)
+    }
+
+    fn with(mut self, name: &'static str) -> Self {
+        if let Some(slot) = self.names.get_mut(self.len) {
+            *slot = name;
+        }
+        self.len += 1;
+        self
+    }
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static ADVISING: Cell<Advising> = const { Cell::new(Advising::NONE) };
+}
+
+/// Whether the advice of some aspect is running on this thread, outside
+/// the function it advises.
+#[cfg(feature = "std")]
+#[inline]
+pub fn in_advice() -> bool {
+    ADVISING.with(|state| state.get().len > 0)
+}
+
+/// Whether advice is running, never without the `std` feature.
+#[cfg(not(feature = "std"))]
+#[inline]
+pub fn in_advice() -> bool {
+    false
+}
+
+/// Whether the advice of the aspect named `name` is running on this
+/// thread, outside the function it advises.
+#[cfg(feature = "std")]
+#[inline]
+pub fn advising(name: &str) -> bool {
+    ADVISING.with(|state| state.get().contains(name))
+}
+
+/// Whether the advice of an aspect is running, never without the `std`
+/// feature.
+#[cfg(not(feature = "std"))]
+#[inline]
+pub fn advising(_name: &str) -> bool {
+    false
+}
+
+/// Whether woven functions called now run without `aspect`: its advice is
+/// running, or the kill switch of [`switch`](crate::switch) is off, and it
+/// is not of [`Precedence::SECURITY`].
+///
+/// Aspects without a [name](crate::Aspect::reentrancy_name), which apply
+/// several others, are never skipped: they skip those of theirs that are.
+#[inline]
+pub fn skips<A: LocalAspect + ?Sized>(aspect: &A) -> bool {
+    skips_named(
+        LocalAspect::reentrancy_name(aspect),
+        LocalAspect::precedence(aspect),
+    )
+}
+
+/// Whether woven functions called now run without the aspect named
+/// `name`, of `precedence`, as for [`skips`].
+#[inline]
+pub fn skips_named(name: Option<&str>, precedence: Precedence) -> bool {
+    name.is_some_and(|name| !precedence.is_security() && (!switch::enabled() || advising(name)))
+}
+
+/// Runs `f`, advice of `aspect`, with woven functions called from it
+/// running without `aspect`.
+#[inline]
+pub fn advice<A: LocalAspect + ?Sized, R>(aspect: &A, f: impl FnOnce() -> R) -> R {
+    advice_as(LocalAspect::reentrancy_name(aspect), f)
+}
+
+/// Runs `f`, advice of the aspect named `name`, as [`advice`] does; with
+/// no name, `f` just runs.
+#[cfg(feature = "std")]
+pub fn advice_as<R>(name: Option<&'static str>, f: impl FnOnce() -> R) -> R {
+    match name {
+        Some(name) => with_state(ADVISING.with(Cell::get).with(name), f),
+        None => f(),
+    }
+}
+
+/// Runs `f`, advice, unguarded without the `std` feature.
+#[cfg(not(feature = "std"))]
+#[inline]
+pub fn advice_as<R>(_name: Option<&'static str>, f: impl FnOnce() -> R) -> R {
+    f()
+}
+
+/// Wraps `f`, what advice about to run proceeds to, to run as things are
+/// now, outside that advice: the advised function, called from the advice
+/// of other aspects or not, runs with the aspects woven functions it calls
+/// would have run with.
+#[cfg(feature = "std")]
+pub fn proceeding<R>(f: impl FnOnce() -> R) -> impl FnOnce() -> R {
+    let outside = ADVISING.with(Cell::get);
+    move || with_state(outside, f)
+}
+
+/// `f` itself, without the `std` feature.
+#[cfg(not(feature = "std"))]
+#[inline]
+pub fn proceeding<R>(f: impl FnOnce() -> R) -> impl FnOnce() -> R {
+    f
+}
+
+/// Whether `module_path` is in one of the [`FRAMEWORK_CRATES`].
+pub fn is_framework_module(module_path: &str) -> bool {
+    let krate = module_path.split("::").next().unwrap_or_default();
+    FRAMEWORK_CRATES.contains(&krate)
+}
+
+/// Runs `f` with the state of the thread set to `advising`, restoring it
+/// afterwards, also when `f` panics.
+#[cfg(feature = "std")]
+fn with_state<R>(advising: Advising, f: impl FnOnce() -> R) -> R {
+    struct Restore(Advising);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            ADVISING.with(|state| state.set(self.0));
+        }
+    }
+
+    let _restore = Restore(ADVISING.with(|state| state.replace(advising)));
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Aspect;
+
+    struct Logger;
+    impl Aspect for Logger {}
+
+    struct Metrics;
+    impl Aspect for Metrics {}
+
+    struct Auth;
+    impl Aspect for Auth {
+        fn precedence(&self) -> Precedence {
+            Precedence::SECURITY
+        }
+    }
+
+    #[test]
+    fn test_nesting() {
+        assert!(!in_advice());
+        let target = proceeding(|| {
+            assert!(!in_advice());
+            advice(&Metrics, || assert!(skips(&Metrics) && !skips(&Logger)));
+            assert!(!in_advice());
+        });
+        advice(&Logger, || {
+            assert!(in_advice());
+            assert!(skips(&Logger));
+            assert!(!skips(&Metrics));
+            advice(&Metrics, || assert!(skips(&Logger) && skips(&Metrics)));
+            assert!(skips(&Logger) && !skips(&Metrics));
+            target();
+            assert!(skips(&Logger));
+        });
+        assert!(!in_advice());
+
+        // Restored when the advice panics
+        let _ = std::panic::catch_unwind(|| advice(&Logger, || panic!("failed advice")));
+        assert!(!in_advice());
+    }
+
+    #[test]
+    fn test_security_never_skipped() {
+        advice(&Auth, || {
+            assert!(advising(core::any::type_name::<Auth>()));
+            assert!(!skips(&Auth));
+        });
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        fn nest(depth: usize) {
+            if depth == 0 {
+                assert!(skips(&Logger));
+                assert!(!skips(&Auth));
+                return;
+            }
+            advice_as(Some("deep"), || nest(depth - 1));
+        }
+        nest(DEPTH + 1);
+        assert!(!in_advice());
+    }
+
+    #[test]
+    fn test_framework_modules() {
+        assert!(is_framework_module("aspect_std"));
+        assert!(is_framework_module("aspect_std::logging"));
+        assert!(is_framework_module("aspect_runtime::registry"));
+        assert!(!is_framework_module("aspect_stdx::logging"));
+        assert!(!is_framework_module("my_app::aspect_std"));
+    }
+}
@@ -0,0 +1,176 @@
+//! Percentage-based rollout decisions, stable per key.
+//!
+//! A rollout applies new behavior, such as an aspect being canaried, to a
+//! percentage of the keys of a program: user ids, tenants, hosts. Each key
+//! is hashed to one of [`BUCKETS`] buckets with FNV-1a, which does not
+//! depend on the process, the platform or the Rust version, so a key gets
+//! the same decision on every call, on every instance and after restarts.
+//! Keys in the buckets below the percentage are included: raising it only
+//! adds keys, and lowering it only removes some.
+//!
+//! ```rust
+//! use aspect_core::rollout;
+//!
+//! assert_eq!(rollout::includes("user-42", 25.0), rollout::includes("user-42", 25.0));
+//! assert!(rollout::includes("user-42", 100.0));
+//! assert!(!rollout::includes("user-42", 0.0));
+//! ```
+
+use crate::JoinPoint;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::fmt;
+
+/// The number of buckets keys are hashed to, giving percentages a
+/// resolution of 0.01%.
+pub const BUCKETS: u32 = 10_000;
+
+/// The bucket of `key`, below [`BUCKETS`].
+pub fn bucket(key: &str) -> u32 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in key.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    (hash % u64::from(BUCKETS)) as u32
+}
+
+/// Whether `key` is among the `percent` percent of keys a rollout
+/// includes.
+pub fn includes(key: &str, percent: f64) -> bool {
+    f64::from(bucket(key)) < percent * f64::from(BUCKETS) / 100.0
+}
+
+/// The function extracting the rollout key of a call.
+type KeyFn = Arc<dyn Fn(&JoinPoint) -> Option<String> + Send + Sync>;
+
+/// A rollout to a percentage of the keys calls are made for.
+///
+/// Calls without a key are only included once the rollout reaches 100%.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::rollout::Rollout;
+///
+/// // By the first argument, a user id
+/// let rollout = Rollout::new(10.0, |ctx| {
+///     ctx.args.first()?.value::<u64>().map(|id| id.to_string())
+/// });
+/// assert_eq!(rollout.percent(), 10.0);
+/// ```
+#[derive(Clone)]
+pub struct Rollout {
+    percent: f64,
+    key: KeyFn,
+}
+
+impl Rollout {
+    /// Roll out to `percent` percent of the keys `key` extracts from
+    /// calls.
+    ///
+    /// `percent` is clamped to `0.0..=100.0`.
+    pub fn new<F>(percent: f64, key: F) -> Self
+    where
+        F: Fn(&JoinPoint) -> Option<String> + Send + Sync + 'static,
+    {
+        Self {
+            percent: percent.clamp(0.0, 100.0),
+            key: Arc::new(key),
+        }
+    }
+
+    /// The percentage of keys included.
+    pub fn percent(&self) -> f64 {
+        self.percent
+    }
+
+    /// The same rollout, to `percent` percent of the keys.
+    pub fn with_percent(mut self, percent: f64) -> Self {
+        self.percent = percent.clamp(0.0, 100.0);
+        self
+    }
+
+    /// Whether the call `ctx` is included.
+    pub fn includes(&self, ctx: &JoinPoint) -> bool {
+        if self.percent >= 100.0 {
+            return true;
+        }
+        match (self.key)(ctx) {
+            Some(key) => includes(&key, self.percent),
+            None => false,
+        }
+    }
+}
+
+impl fmt::Debug for Rollout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rollout")
+            .field("percent", &self.percent)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Arg, Location};
+    use alloc::format;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    fn call(user: u64) -> JoinPoint {
+        JoinPoint {
+            function_name: "checkout",
+            module_path: "shop",
+            location: Location {
+                file: "shop.rs",
+                line: 1,
+            },
+            args: vec![Arg::new("user", &user)],
+        }
+    }
+
+    #[test]
+    fn test_bucket_is_stable() {
+        // FNV-1a of the key, whatever the process
+        assert_eq!(bucket(""), (0xcbf2_9ce4_8422_2325u64 % 10_000) as u32);
+        assert_eq!(bucket("user-42"), bucket("user-42"));
+        assert!(bucket("user-42") < BUCKETS);
+    }
+
+    #[test]
+    fn test_includes_percentage() {
+        let included = (0..10_000)
+            .filter(|i| includes(&format!("user-{}", i), 20.0))
+            .count();
+        assert!((1_800..2_200).contains(&included), "included {}", included);
+
+        // Raising the percentage keeps the keys already included
+        for i in 0..1_000 {
+            let key = format!("user-{}", i);
+            if includes(&key, 5.0) {
+                assert!(includes(&key, 50.0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_rollout() {
+        let rollout = Rollout::new(30.0, |ctx| {
+            ctx.args.first()?.value::<u64>().map(|id| id.to_string())
+        });
+        for user in 0..100 {
+            assert_eq!(
+                rollout.includes(&call(user)),
+                includes(&user.to_string(), 30.0)
+            );
+        }
+
+        // Calls without a key wait for the full rollout
+        let keyless = Rollout::new(99.0, |_| None);
+        assert!(!keyless.includes(&call(1)));
+        assert!(keyless.with_percent(100.0).includes(&call(1)));
+        assert_eq!(Rollout::new(150.0, |_| None).percent(), 100.0);
+    }
+}
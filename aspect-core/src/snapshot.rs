@@ -0,0 +1,261 @@
+//! Snapshots of the live state of aspects, for debugging.
+//!
+//! Stateful aspects describe their state, such as the state of a circuit
+//! breaker or the hit rate of a cache, in an [`AspectSnapshot`] returned by
+//! [`Aspect::snapshot`](crate::Aspect::snapshot). Its values are the
+//! [`SnapshotValue`]s JSON can hold, and it is displayed as JSON, so that
+//! operators can dump it as is:
+//!
+//! ```rust
+//! use aspect_core::snapshot::AspectSnapshot;
+//!
+//! let snapshot = AspectSnapshot::new("CircuitBreakerAspect")
+//!     .named("payments")
+//!     .with("state", "open")
+//!     .with("failures", 5u64);
+//! assert_eq!(
+//!     snapshot.to_string(),
+//!     r#"{"aspect":"CircuitBreakerAspect","name":"payments","state":{"state":"open","failures":5}}"#
+//! );
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// The state of an aspect at some point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AspectSnapshot {
+    aspect: String,
+    name: Option<String>,
+    state: Vec<(String, SnapshotValue)>,
+}
+
+impl AspectSnapshot {
+    /// An empty snapshot of an aspect of type `aspect`.
+    pub fn new(aspect: impl Into<String>) -> Self {
+        Self {
+            aspect: aspect.into(),
+            name: None,
+            state: Vec::new(),
+        }
+    }
+
+    /// Add the entry `key` to the state.
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<SnapshotValue>) -> Self {
+        self.state.push((key.into(), value.into()));
+        self
+    }
+
+    /// Name the snapshot after the aspect instance, e.g. its name in a
+    /// registry.
+    pub fn named(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// The type of the aspect.
+    pub fn aspect(&self) -> &str {
+        &self.aspect
+    }
+
+    /// The name of the aspect instance, if it has one.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The entries of the state, in the order they were added.
+    pub fn state(&self) -> &[(String, SnapshotValue)] {
+        &self.state
+    }
+
+    /// The entry `key` of the state.
+    pub fn get(&self, key: &str) -> Option<&SnapshotValue> {
+        self.state
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+}
+
+/// As a JSON object with the fields `aspect`, `name` if it has one, and
+/// `state`.
+impl fmt::Display for AspectSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("{\"aspect\":")?;
+        write_json_string(f, &self.aspect)?;
+        if let Some(name) = &self.name {
+            f.write_str(",\"name\":")?;
+            write_json_string(f, name)?;
+        }
+        f.write_str(",\"state\":")?;
+        write_json_object(f, &self.state)?;
+        f.write_str("}")
+    }
+}
+
+/// A value in a snapshot, one of those JSON can hold.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotValue {
+    /// No value
+    Null,
+    /// A boolean
+    Bool(bool),
+    /// A signed integer
+    Int(i64),
+    /// An unsigned integer
+    UInt(u64),
+    /// A floating-point number; `null` in JSON unless finite
+    Float(f64),
+    /// A string
+    Str(String),
+    /// A list of values
+    List(Vec<SnapshotValue>),
+    /// Values by key, in order
+    Map(Vec<(String, SnapshotValue)>),
+}
+
+/// As JSON.
+impl fmt::Display for SnapshotValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotValue::Null => f.write_str("null"),
+            SnapshotValue::Bool(value) => write!(f, "{}", value),
+            SnapshotValue::Int(value) => write!(f, "{}", value),
+            SnapshotValue::UInt(value) => write!(f, "{}", value),
+            SnapshotValue::Float(value) if value.is_finite() => write!(f, "{}", value),
+            SnapshotValue::Float(_) => f.write_str("null"),
+            SnapshotValue::Str(value) => write_json_string(f, value),
+            SnapshotValue::List(values) => {
+                f.write_str("[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_str("]")
+            }
+            SnapshotValue::Map(entries) => write_json_object(f, entries),
+        }
+    }
+}
+
+fn write_json_object(
+    f: &mut fmt::Formatter<'_>,
+    entries: &[(String, SnapshotValue)],
+) -> fmt::Result {
+    f.write_str("{")?;
+    for (index, (key, value)) in entries.iter().enumerate() {
+        if index > 0 {
+            f.write_str(",")?;
+        }
+        write_json_string(f, key)?;
+        write!(f, ":{}", value)?;
+    }
+    f.write_str("}")
+}
+
+fn write_json_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+macro_rules! snapshot_values {
+    ($($variant:ident($as:ty): $($ty:ty),*;)*) => {
+        $($(
+            impl From<$ty> for SnapshotValue {
+                fn from(value: $ty) -> Self {
+                    SnapshotValue::$variant(value as $as)
+                }
+            }
+        )*)*
+    };
+}
+
+snapshot_values! {
+    Int(i64): i8, i16, i32, i64, isize;
+    UInt(u64): u8, u16, u32, u64, usize;
+    Float(f64): f32, f64;
+}
+
+impl From<bool> for SnapshotValue {
+    fn from(value: bool) -> Self {
+        SnapshotValue::Bool(value)
+    }
+}
+
+impl From<&str> for SnapshotValue {
+    fn from(value: &str) -> Self {
+        SnapshotValue::Str(value.into())
+    }
+}
+
+impl From<String> for SnapshotValue {
+    fn from(value: String) -> Self {
+        SnapshotValue::Str(value)
+    }
+}
+
+impl<T: Into<SnapshotValue>> From<Option<T>> for SnapshotValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(SnapshotValue::Null, Into::into)
+    }
+}
+
+impl<T: Into<SnapshotValue>> From<Vec<T>> for SnapshotValue {
+    fn from(values: Vec<T>) -> Self {
+        SnapshotValue::List(values.into_iter().map(Into::into).collect())
+    }
+}
+
+/// The snapshot as the JSON object it is displayed as, e.g. to list the
+/// snapshots of several aspects.
+impl From<AspectSnapshot> for SnapshotValue {
+    fn from(snapshot: AspectSnapshot) -> Self {
+        let mut entries = Vec::with_capacity(3);
+        entries.push((String::from("aspect"), SnapshotValue::Str(snapshot.aspect)));
+        if let Some(name) = snapshot.name {
+            entries.push((String::from("name"), SnapshotValue::Str(name)));
+        }
+        entries.push((String::from("state"), SnapshotValue::Map(snapshot.state)));
+        SnapshotValue::Map(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    #[test]
+    fn test_json() {
+        let snapshot = AspectSnapshot::new("CachingAspect")
+            .with("entries", 2usize)
+            .with("hit_rate", 0.5)
+            .with("ttl_ms", None::<u64>)
+            .with("keys", vec!["a\"b", "c\n"]);
+        assert_eq!(
+            snapshot.to_string(),
+            r#"{"aspect":"CachingAspect","state":{"entries":2,"hit_rate":0.5,"ttl_ms":null,"keys":["a\"b","c\n"]}}"#
+        );
+        assert_eq!(snapshot.get("entries"), Some(&SnapshotValue::UInt(2)));
+        assert_eq!(SnapshotValue::Float(f64::NAN).to_string(), "null");
+
+        // Listed, snapshots are displayed the same
+        let listed = SnapshotValue::from(vec![snapshot.clone().named("users")]);
+        assert_eq!(listed.to_string(), format!("[{}]", snapshot.named("users")));
+    }
+}
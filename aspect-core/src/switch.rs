@@ -0,0 +1,97 @@
+//! Global kill switch bypassing advice.
+//!
+//! Operators can turn the aspects of a running program off without
+//! redeploying it: with `ASPECT_DISABLE=1` in its environment, or by calling
+//! [`set_enabled`]`(false)`, e.g. from an admin endpoint. Woven functions
+//! then run without their aspects, and the registry runs executions without
+//! the aspects matching them.
+//!
+//! Aspects of the [`SECURITY`](crate::Precedence::SECURITY) precedence
+//! class, such as authorization, keep running: turning them off would open
+//! every protected function to every caller, which is never what an
+//! emergency switch for faulty advice should do. Static aspects, of
+//! `#[aspect(static ...)]`, declare no precedence and are all bypassed, so
+//! access checks must not be static aspects. The first time the switch
+//! turns off, it logs a warning.
+//!
+//! The switch is checked on every call, as one relaxed atomic load; the
+//! environment is only read the first time. Unlike `ASPECT_COMPILE_OUT`,
+//! which the macros read as they expand to leave functions unwoven, it
+//! needs no rebuild.
+//!
+//! ```rust
+//! use aspect_core::switch;
+//!
+//! switch::set_enabled(false);
+//! assert!(!switch::enabled());
+//! switch::set_enabled(true);
+//! ```
+
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// Environment variable turning all aspects off when set to a value other
+/// than empty or `0`.
+pub const DISABLE_ENV: &str = "ASPECT_DISABLE";
+
+const UNSET: u8 = 0;
+const ENABLED: u8 = 1;
+const DISABLED: u8 = 2;
+
+static STATE: AtomicU8 = AtomicU8::new(UNSET);
+
+/// Whether aspects are enabled: unless `ASPECT_DISABLE` is set, or
+/// [`set_enabled`] turned them off. Security aspects run either way.
+#[inline]
+pub fn enabled() -> bool {
+    match STATE.load(Ordering::Relaxed) {
+        ENABLED => true,
+        DISABLED => false,
+        _ => enabled_from_env(),
+    }
+}
+
+/// Turn the aspects but those of security on or off, whatever
+/// `ASPECT_DISABLE` says.
+pub fn set_enabled(enabled: bool) {
+    STATE.store(if enabled { ENABLED } else { DISABLED }, Ordering::Relaxed);
+    if !enabled {
+        warn_disabled();
+    }
+}
+
+/// Initialize the switch from the environment, unless [`set_enabled`] did
+/// meanwhile.
+#[cold]
+fn enabled_from_env() -> bool {
+    let state = if disabled_by_env() { DISABLED } else { ENABLED };
+    match STATE.compare_exchange(UNSET, state, Ordering::Relaxed, Ordering::Relaxed) {
+        Ok(_) if state == DISABLED => {
+            warn_disabled();
+            false
+        }
+        Ok(_) => true,
+        Err(current) => current == ENABLED,
+    }
+}
+
+/// Log, the first time the switch turns off, that the aspects are off.
+#[cold]
+fn warn_disabled() {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        log::warn!(
+            "aspects disabled by the kill switch: woven functions run without their advice, \
+             but for that of security aspects"
+        );
+    }
+}
+
+#[cfg(feature = "std")]
+fn disabled_by_env() -> bool {
+    std::env::var_os(DISABLE_ENV).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+#[cfg(not(feature = "std"))]
+fn disabled_by_env() -> bool {
+    false
+}
@@ -0,0 +1,268 @@
+//! Interned strings for function identities.
+//!
+//! The same function names, module paths and visibilities are matched,
+//! hashed and compared over and over: by the registry on every call of an
+//! advised function, and by the driver for every function of a crate. A
+//! [`Symbol`] interns such a string once for the whole process, after which
+//! it is a `Copy` handle that compares and hashes as an integer, and whose
+//! text is `&'static str`.
+//!
+//! Interned strings are never freed, which is what makes them `'static`:
+//! intern identities, of which there are a bounded number, not arbitrary
+//! data.
+
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as Map;
+use alloc::string::String;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+#[cfg(feature = "std")]
+use std::collections::HashMap as Map;
+#[cfg(feature = "std")]
+use std::sync::{OnceLock, RwLock};
+
+/// A string interned in the symbol table of the process.
+///
+/// Two symbols are equal exactly when their strings are, which is one
+/// integer comparison.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::Symbol;
+///
+/// let name = Symbol::intern("save_user");
+/// assert_eq!(name, Symbol::intern(&String::from("save_user")));
+/// assert_ne!(name, Symbol::intern("load_user"));
+/// assert_eq!(name.as_str(), "save_user");
+/// assert!(name.starts_with("save"));
+/// ```
+#[derive(Clone, Copy)]
+pub struct Symbol {
+    index: u32,
+    text: &'static str,
+}
+
+struct Interner {
+    symbols: Map<&'static str, Symbol>,
+}
+
+impl Interner {
+    /// The symbol of `text`, added to the table if missing.
+    fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(text) {
+            return *symbol;
+        }
+        let index = u32::try_from(self.symbols.len()).expect("too many interned symbols");
+        let text: &'static str = Box::leak(text.into());
+        let symbol = Symbol { index, text };
+        self.symbols.insert(text, symbol);
+        symbol
+    }
+}
+
+#[cfg(feature = "std")]
+fn interner() -> &'static RwLock<Interner> {
+    static INTERNER: OnceLock<RwLock<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(|| {
+        RwLock::new(Interner {
+            symbols: Map::new(),
+        })
+    })
+}
+
+/// Runs `f` on the symbol table of `no_std` builds, behind a spin lock as
+/// there is no lock of the operating system to wait on.
+#[cfg(not(feature = "std"))]
+fn with_interner<R>(f: impl FnOnce(&mut Interner) -> R) -> R {
+    use core::cell::UnsafeCell;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    struct Table {
+        locked: AtomicBool,
+        interner: UnsafeCell<Interner>,
+    }
+
+    // SAFETY: the interner is only reached with `locked` held
+    unsafe impl Sync for Table {}
+
+    static TABLE: Table = Table {
+        locked: AtomicBool::new(false),
+        interner: UnsafeCell::new(Interner {
+            symbols: Map::new(),
+        }),
+    };
+
+    /// Releases the lock, also when `f` panics.
+    struct Unlock;
+
+    impl Drop for Unlock {
+        fn drop(&mut self) {
+            TABLE.locked.store(false, Ordering::Release);
+        }
+    }
+
+    while TABLE
+        .locked
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        core::hint::spin_loop();
+    }
+    let _unlock = Unlock;
+    // SAFETY: the lock is held until `_unlock` is dropped
+    f(unsafe { &mut *TABLE.interner.get() })
+}
+
+impl Symbol {
+    /// The symbol of `text`, interning it if it is the first time.
+    #[cfg(feature = "std")]
+    pub fn intern(text: &str) -> Symbol {
+        if let Some(symbol) = interner().read().unwrap().symbols.get(text) {
+            return *symbol;
+        }
+        // Another thread may have interned it in the meantime, which
+        // `Interner::intern` checks again
+        interner().write().unwrap().intern(text)
+    }
+
+    /// The symbol of `text`, interning it if it is the first time.
+    #[cfg(not(feature = "std"))]
+    pub fn intern(text: &str) -> Symbol {
+        with_interner(|interner| interner.intern(text))
+    }
+
+    /// The interned string.
+    pub fn as_str(&self) -> &'static str {
+        self.text
+    }
+
+    /// The number of the symbol, unique in the process.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Symbol) -> bool {
+        self.index == other.index
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+/// Symbols sort like their strings, not in the order they were interned.
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Symbol) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Symbol) -> core::cmp::Ordering {
+        if self == other {
+            core::cmp::Ordering::Equal
+        } else {
+            self.text.cmp(other.text)
+        }
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.text == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.text == *other
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.text
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        self.text
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(text: &str) -> Symbol {
+        Symbol::intern(text)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(text: &String) -> Symbol {
+        Symbol::intern(text)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(text: String) -> Symbol {
+        Symbol::intern(&text)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.text, f)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_intern() {
+        let save = Symbol::intern("symbol_test::save");
+        let again = Symbol::intern(&format!("symbol_test::{}", "save"));
+        assert_eq!(save, again);
+        assert_eq!(save.index(), again.index());
+        assert!(std::ptr::eq(save.as_str(), again.as_str()));
+
+        let load = Symbol::intern("symbol_test::load");
+        assert_ne!(save, load);
+        assert_eq!(save, "symbol_test::save");
+        assert_eq!(load.to_string(), "symbol_test::load");
+        assert_eq!(format!("{:?}", load), "\"symbol_test::load\"");
+        assert!(load < save);
+
+        let set: HashSet<Symbol> = [save, load, again].into_iter().collect();
+        assert_eq!(set.len(), 2);
+        assert_eq!(Symbol::from(""), "");
+    }
+
+    #[test]
+    fn test_intern_across_threads() {
+        let symbols: Vec<Symbol> = (0..8)
+            .map(|_| std::thread::spawn(|| Symbol::intern("symbol_test::shared")))
+            .map(|thread| thread.join().unwrap())
+            .collect();
+        assert!(symbols.iter().all(|symbol| *symbol == symbols[0]));
+    }
+}
@@ -0,0 +1,72 @@
+//! Aspects built from `key = value` parameters, `#[aspect(Type, ...)]`.
+
+use aspect_core::config::Param;
+use aspect_core::prelude::*;
+use aspect_core::{AspectArgs, FromAspectArgs};
+use aspect_macros::aspect;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// Lets the first `max` calls through, counting them in a state shared by
+/// its clones.
+#[derive(Clone)]
+struct Quota {
+    max: u32,
+    calls: Arc<AtomicU32>,
+}
+
+impl Aspect for Quota {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn std::any::Any>, AspectError> {
+        if self.calls.fetch_add(1, Ordering::SeqCst) >= self.max {
+            return Err(AspectError::execution("quota exceeded"));
+        }
+        pjp.proceed()
+    }
+}
+
+impl FromAspectArgs for Quota {
+    const PARAMS: &'static [Param] = &[Param::required::<u32>("max")];
+
+    fn from_aspect_args(args: &AspectArgs) -> Result<Self, AspectError> {
+        args.check(Self::PARAMS)?;
+        Ok(Self {
+            max: args.require("max")?,
+            calls: Arc::default(),
+        })
+    }
+}
+
+#[aspect(Quota, max = 2)]
+fn ping() -> Result<&'static str, String> {
+    Ok("pong")
+}
+
+#[aspect(Quota, max = 1)]
+fn other() -> Result<&'static str, String> {
+    Ok("pong")
+}
+
+#[test]
+fn test_parameters_build_one_aspect_per_function() {
+    assert_eq!(ping(), Ok("pong"));
+    assert_eq!(ping(), Ok("pong"));
+    assert!(ping().unwrap_err().contains("quota exceeded"));
+
+    // Another function has its own aspect, with its own parameters
+    assert_eq!(other(), Ok("pong"));
+    assert!(other().is_err());
+}
+
+/// Not a literal, so only checked when the aspect is built.
+const NEGATIVE: i64 = -1;
+
+#[aspect(Quota, max = NEGATIVE)]
+fn misconfigured() {}
+
+#[test]
+#[should_panic(
+    expected = "invalid parameters for the aspect of misconfigured: Weaving error: parameter `max` of Quota must be an integer fitting in u32, not Int(-1)"
+)]
+fn test_invalid_parameters_panic() {
+    misconfigured();
+}
@@ -0,0 +1,141 @@
+//! Functions woven with aspects composed by `Aspects`.
+
+use aspect_core::prelude::*;
+use aspect_core::{Aspects, ComposedAspect};
+use aspect_macros::aspect;
+use std::any::Any;
+use std::cell::RefCell;
+use std::sync::LazyLock;
+
+thread_local! {
+    static EVENTS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+fn record(event: String) {
+    EVENTS.with(|events| events.borrow_mut().push(event));
+}
+
+fn events() -> Vec<String> {
+    EVENTS.with(|events| events.take())
+}
+
+/// Records its advice under its name.
+struct Trace(&'static str, Precedence);
+
+impl Aspect for Trace {
+    fn before(&self, _ctx: &JoinPoint) {
+        record(format!("{} before", self.0));
+    }
+
+    fn after(&self, _ctx: &JoinPoint, _result: &dyn Any) {
+        record(format!("{} after", self.0));
+    }
+
+    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
+        record(format!(
+            "{} error in {}: {}",
+            self.0, ctx.function_name, error
+        ));
+    }
+
+    fn precedence(&self) -> Precedence {
+        self.1
+    }
+}
+
+/// Defined once, the aspects keep the order they were added in whatever
+/// their precedence.
+static API: LazyLock<ComposedAspect> = LazyLock::new(|| {
+    Aspects::new()
+        .with(Trace("log", Precedence::OBSERVABILITY))
+        .with(Trace("auth", Precedence::SECURITY))
+        .build()
+});
+
+#[aspect(API.clone())]
+fn get_user(id: u64) -> Result<u64, String> {
+    record("get_user".to_string());
+    if id == 0 {
+        return Err("no user 0".to_string());
+    }
+    Ok(id)
+}
+
+#[aspect(API.clone())]
+async fn load_user(id: u64) -> u64 {
+    record("load_user".to_string());
+    id
+}
+
+/// Runs a future that never has to wait.
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    match future.as_mut().poll(&mut cx) {
+        std::task::Poll::Ready(output) => output,
+        std::task::Poll::Pending => panic!("future not ready"),
+    }
+}
+
+#[test]
+fn test_composition_nests_in_order() {
+    assert_eq!(get_user(1), Ok(1));
+    assert_eq!(
+        events(),
+        [
+            "log before",
+            "auth before",
+            "get_user",
+            "auth after",
+            "log after"
+        ]
+    );
+
+    assert!(get_user(0).is_err());
+    let events = events();
+    assert_eq!(
+        events[3],
+        "auth error in get_user: Execution error: \"no user 0\""
+    );
+    assert!(events[4].starts_with("log error"));
+}
+
+#[test]
+fn test_composition_on_async_fn() {
+    assert_eq!(block_on(load_user(2)), 2);
+    assert_eq!(
+        events(),
+        [
+            "log before",
+            "auth before",
+            "load_user",
+            "auth after",
+            "log after"
+        ]
+    );
+}
+
+#[aspect(API.clone(), Trace("cache", Precedence::CACHING))]
+fn cached_user(id: u64) -> u64 {
+    record("cached_user".to_string());
+    id
+}
+
+#[test]
+fn test_composition_ordered_by_its_outermost_aspect() {
+    // Listed with other aspects, the composition has the precedence of
+    // `log`, its outermost aspect
+    assert_eq!(cached_user(3), 3);
+    assert_eq!(
+        events(),
+        [
+            "cache before",
+            "log before",
+            "auth before",
+            "cached_user",
+            "auth after",
+            "log after",
+            "cache after"
+        ]
+    );
+}
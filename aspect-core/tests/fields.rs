@@ -0,0 +1,117 @@
+//! Field accessors woven with `#[aspect_fields]`.
+
+use aspect_core::prelude::*;
+use aspect_macros::aspect_fields;
+use std::any::Any;
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Records the accesses the aspect sees.
+#[derive(Clone, Default)]
+struct Audit {
+    events: Arc<Mutex<Vec<String>>>,
+}
+
+impl Audit {
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut self.events.lock().unwrap())
+    }
+}
+
+impl Aspect for Audit {
+    fn before(&self, ctx: &JoinPoint) {
+        let (struct_name, field) = ctx.field().unwrap();
+        let value = match ctx.kind() {
+            JoinPointKind::FieldSet => format!(" = {:?}", ctx.args[0].value::<u64>()),
+            _ => String::new(),
+        };
+        self.events.lock().unwrap().push(format!(
+            "{:?} {}.{}{}",
+            ctx.kind(),
+            struct_name,
+            field,
+            value
+        ));
+    }
+
+    fn after(&self, ctx: &JoinPoint, result: &dyn Any) {
+        if let Some(value) = result.downcast_ref::<u64>() {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("{} -> {}", ctx.function_name, value));
+        }
+    }
+}
+
+static AUDIT: LazyLock<Audit> = LazyLock::new(Audit::default);
+
+/// Refuses to write more than a limit.
+struct Limit(u64);
+
+impl Aspect for Limit {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        match pjp.context().args[0].value::<u64>() {
+            Some(&value) if value > self.0 => Err(AspectError::execution("over the limit")),
+            _ => pjp.proceed(),
+        }
+    }
+}
+
+#[aspect_fields(AUDIT.clone(), Limit(1_000))]
+struct Account {
+    #[aspect_field]
+    balance: u64,
+    #[aspect_field(get)]
+    owner: String,
+    id: u32,
+}
+
+#[aspect_fields(AUDIT.clone())]
+struct Slot<T: Clone + Send + Sync + 'static> {
+    #[aspect_field]
+    value: T,
+}
+
+#[test]
+fn test_field_accessors() {
+    let mut account = Account {
+        balance: 10,
+        owner: "ada".to_string(),
+        id: 1,
+    };
+
+    account.set_balance(*account.balance() + 90).unwrap();
+    assert_eq!(account.balance, 100);
+    assert_eq!(account.owner(), "ada");
+    assert_eq!(account.id, 1);
+    assert_eq!(
+        AUDIT.take(),
+        [
+            "FieldGet Account.balance",
+            "get(Account.balance) -> 10",
+            "FieldSet Account.balance = Some(100)",
+            "FieldGet Account.owner",
+        ]
+    );
+
+    // The aspects can refuse a write
+    let refused = account.set_balance(5_000).unwrap_err();
+    assert_eq!(refused.to_string(), "Execution error: over the limit");
+    assert_eq!(account.balance, 100);
+
+    AUDIT.take();
+
+    // Values of generic types are not captured, as for functions, but
+    // `after` sees them
+    let mut slot = Slot { value: 1u64 };
+    slot.set_value(2).unwrap();
+    assert_eq!(*slot.value(), 2);
+    assert_eq!(
+        AUDIT.take(),
+        [
+            "FieldSet Slot.value = None",
+            "FieldGet Slot.value",
+            "get(Slot.value) -> 2",
+        ]
+    );
+}
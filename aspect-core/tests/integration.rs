@@ -47,14 +47,14 @@ impl Aspect for TestAspect {
 #[test]
 fn test_aspect_lifecycle() {
     let aspect = TestAspect::new();
-    let ctx = JoinPoint {
-        function_name: "test_fn",
-        module_path: "test",
-        location: Location {
+    let ctx = JoinPoint::new(
+        "test_fn",
+        "test",
+        Location {
             file: "test.rs",
             line: 1,
         },
-    };
+    );
 
     aspect.before(&ctx);
     aspect.after(&ctx, &42);
@@ -68,14 +68,14 @@ fn test_aspect_lifecycle() {
 #[test]
 fn test_aspect_error_handling() {
     let aspect = TestAspect::new();
-    let ctx = JoinPoint {
-        function_name: "failing_fn",
-        module_path: "test",
-        location: Location {
+    let ctx = JoinPoint::new(
+        "failing_fn",
+        "test",
+        Location {
             file: "test.rs",
             line: 10,
         },
-    };
+    );
 
     aspect.before(&ctx);
     aspect.after_error(&ctx, &AspectError::execution("test error"));
@@ -88,14 +88,14 @@ fn test_aspect_error_handling() {
 
 #[test]
 fn test_proceeding_joinpoint() {
-    let ctx = JoinPoint {
-        function_name: "wrapped_fn",
-        module_path: "test",
-        location: Location {
+    let ctx = JoinPoint::new(
+        "wrapped_fn",
+        "test",
+        Location {
             file: "test.rs",
             line: 20,
         },
-    };
+    );
 
     let executed = Arc::new(Mutex::new(false));
     let executed_clone = Arc::clone(&executed);
@@ -113,3 +113,45 @@ fn test_proceeding_joinpoint() {
     assert!(*executed.lock().unwrap());
     assert!(result.is_ok());
 }
+
+/// Denies the functions in its list, the way `#[async_trait]` users write it.
+struct AsyncDenyList(Vec<&'static str>);
+
+impl Aspect for AsyncDenyList {}
+
+#[async_trait::async_trait]
+impl AsyncAspect for AsyncDenyList {
+    async fn before_async(&self, ctx: &JoinPoint) -> Result<(), AspectError> {
+        std::future::ready(()).await;
+        if self.0.contains(&ctx.function_name) {
+            return Err(AspectError::execution("denied"));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_async_trait_impl() {
+    let ctx = |function_name| {
+        JoinPoint::new(
+            function_name,
+            "test",
+            Location {
+                file: "test.rs",
+                line: 30,
+            },
+        )
+    };
+    let block_on = |future: aspect_core::aspect::BoxFuture<'_, Result<(), AspectError>>| {
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        let mut future = future;
+        match future.as_mut().poll(&mut cx) {
+            std::task::Poll::Ready(result) => result,
+            std::task::Poll::Pending => panic!("future not ready"),
+        }
+    };
+
+    let aspect: Arc<dyn AsyncAspect> = Arc::new(AsyncDenyList(vec!["drop_table"]));
+    assert!(block_on(aspect.before_async(&ctx("select"))).is_ok());
+    assert!(block_on(aspect.before_async(&ctx("drop_table"))).is_err());
+}
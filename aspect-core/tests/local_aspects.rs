@@ -0,0 +1,114 @@
+//! Functions woven with thread-confined aspects, `#[aspect(local ...)]`.
+
+use aspect_core::aspect::LocalAspect;
+use aspect_core::prelude::*;
+use aspect_macros::aspect;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::rc::Rc;
+
+/// Memoizes results in an `Rc` shared by the clones made on one thread.
+#[derive(Clone, Default)]
+struct RcCache {
+    entries: Rc<RefCell<HashMap<u64, u64>>>,
+    misses: Rc<RefCell<Vec<u64>>>,
+}
+
+impl LocalAspect for RcCache {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let key = match pjp
+            .context()
+            .args
+            .first()
+            .and_then(|arg| arg.value::<u64>())
+        {
+            Some(key) => *key,
+            None => return pjp.proceed(),
+        };
+        if let Some(value) = self.entries.borrow().get(&key) {
+            return Ok(Box::new(*value));
+        }
+        self.misses.borrow_mut().push(key);
+        let result = pjp.proceed()?;
+        if let Some(value) = result.downcast_ref::<u64>() {
+            self.entries.borrow_mut().insert(key, *value);
+        }
+        Ok(result)
+    }
+
+    fn reads_args(&self) -> bool {
+        true
+    }
+}
+
+thread_local! {
+    static CACHE: RcCache = RcCache::default();
+}
+
+#[aspect(local CACHE.with(RcCache::clone))]
+fn square(x: u64) -> u64 {
+    x * x
+}
+
+#[test]
+fn test_local_aspect_keeps_state_per_thread() {
+    assert_eq!(square(3), 9);
+    assert_eq!(square(3), 9);
+    assert_eq!(square(4), 16);
+    assert_eq!(CACHE.with(|cache| cache.misses.borrow().clone()), [3, 4]);
+
+    // Another thread starts with its own, empty cache
+    let misses = std::thread::spawn(|| {
+        square(3);
+        CACHE.with(|cache| cache.misses.borrow().clone())
+    });
+    assert_eq!(misses.join().unwrap(), [3]);
+}
+
+/// Fails calls with a message built on this thread.
+struct Reject(Rc<str>);
+
+impl LocalAspect for Reject {
+    fn around(&self, _pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        Err(AspectError::execution(self.0.to_string()))
+    }
+}
+
+#[aspect(local Reject(Rc::from("offline")))]
+fn sync_now() -> Result<u32, String> {
+    Ok(1)
+}
+
+#[test]
+fn test_local_aspect_errors_reach_the_caller() {
+    let err = sync_now().unwrap_err();
+    assert!(err.contains("offline"), "{}", err);
+}
+
+/// Counts the calls of `async fn`s on this thread.
+struct Calls(Rc<RefCell<Vec<&'static str>>>);
+
+impl LocalAspect for Calls {
+    fn after(&self, ctx: &JoinPoint, _result: &dyn Any) {
+        self.0.borrow_mut().push(ctx.function_name);
+    }
+}
+
+thread_local! {
+    static CALLS: Rc<RefCell<Vec<&'static str>>> = Rc::default();
+}
+
+#[aspect(local Calls(CALLS.with(Rc::clone)))]
+async fn load(id: u64) -> u64 {
+    id
+}
+
+#[test]
+fn test_local_aspect_on_async_fn() {
+    let mut future = std::pin::pin!(load(5));
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    assert_eq!(future.as_mut().poll(&mut cx), std::task::Poll::Ready(5));
+    assert_eq!(CALLS.with(|calls| calls.borrow().clone()), ["load"]);
+}
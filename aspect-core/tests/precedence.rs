@@ -0,0 +1,169 @@
+//! Functions woven with several aspects, ordered by their precedence.
+
+use aspect_core::prelude::*;
+use aspect_macros::aspect;
+use std::any::Any;
+use std::cell::RefCell;
+
+thread_local! {
+    static EVENTS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+fn record(event: String) {
+    EVENTS.with(|events| events.borrow_mut().push(event));
+}
+
+fn events() -> Vec<String> {
+    EVENTS.with(|events| events.take())
+}
+
+/// Records its advice under its name.
+struct Trace(&'static str, Precedence);
+
+impl Aspect for Trace {
+    fn before(&self, _ctx: &JoinPoint) {
+        record(format!("{} before", self.0));
+    }
+
+    fn after(&self, _ctx: &JoinPoint, _result: &dyn Any) {
+        record(format!("{} after", self.0));
+    }
+
+    fn after_error(&self, _ctx: &JoinPoint, error: &AspectError) {
+        record(format!("{} error: {}", self.0, error));
+    }
+
+    fn precedence(&self) -> Precedence {
+        self.1
+    }
+}
+
+/// Fails every call in its asynchronous advice.
+struct Throttle;
+
+impl Aspect for Throttle {
+    fn precedence(&self) -> Precedence {
+        Precedence::RESILIENCE
+    }
+}
+
+#[async_trait::async_trait]
+impl AsyncAspect for Throttle {
+    async fn before_async(&self, ctx: &JoinPoint) -> Result<(), AspectError> {
+        std::future::ready(()).await;
+        Err(AspectError::rate_limited(ctx.function_name, None))
+    }
+}
+
+/// Runs a future that never has to wait.
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    match future.as_mut().poll(&mut cx) {
+        std::task::Poll::Ready(output) => output,
+        std::task::Poll::Pending => panic!("future not ready"),
+    }
+}
+
+#[aspect(
+    Trace("log", Precedence::OBSERVABILITY),
+    Trace("auth", Precedence::SECURITY),
+    Trace("cache", Precedence::CACHING)
+)]
+fn lookup(id: u64) -> Result<u64, String> {
+    record("lookup".to_string());
+    if id == 0 {
+        return Err("no record 0".to_string());
+    }
+    Ok(id)
+}
+
+#[test]
+fn test_listed_aspects_run_by_precedence() {
+    assert_eq!(lookup(7), Ok(7));
+    assert_eq!(
+        events(),
+        [
+            "auth before",
+            "cache before",
+            "log before",
+            "lookup",
+            "log after",
+            "cache after",
+            "auth after",
+        ]
+    );
+
+    assert_eq!(lookup(0), Err("no record 0".to_string()));
+    let events = events();
+    assert_eq!(
+        events[..4],
+        ["auth before", "cache before", "log before", "lookup"]
+    );
+    assert!(events[4].starts_with("log error"));
+    assert!(events[6].starts_with("auth error"));
+}
+
+#[aspect(
+    Trace("first", Precedence::DEFAULT),
+    Trace("second", Precedence::DEFAULT)
+)]
+fn unordered() -> u32 {
+    1
+}
+
+#[test]
+fn test_same_precedence_keeps_listed_order() {
+    assert_eq!(unordered(), 1);
+    assert_eq!(
+        events(),
+        [
+            "first before",
+            "second before",
+            "second after",
+            "first after"
+        ]
+    );
+}
+
+#[aspect(
+    Trace("log", Precedence::OBSERVABILITY),
+    Throttle,
+    Trace("auth", Precedence::SECURITY)
+)]
+async fn fetch(id: u64) -> Result<u64, String> {
+    record("fetch".to_string());
+    Ok(id)
+}
+
+#[aspect(
+    Trace("log", Precedence::OBSERVABILITY),
+    Trace("auth", Precedence::SECURITY)
+)]
+async fn fetch_all() -> Vec<u64> {
+    record("fetch_all".to_string());
+    vec![1, 2]
+}
+
+#[test]
+fn test_async_aspects_run_by_precedence() {
+    assert_eq!(block_on(fetch_all()), [1, 2]);
+    assert_eq!(
+        events(),
+        [
+            "auth before",
+            "log before",
+            "fetch_all",
+            "log after",
+            "auth after"
+        ]
+    );
+
+    // The throttle rejects the call before the logging aspect is entered
+    let err = block_on(fetch(1)).unwrap_err();
+    assert!(err.contains("RateLimited"), "{}", err);
+    assert_eq!(
+        events(),
+        ["auth before", "auth error: Rate limit exceeded for fetch"]
+    );
+}
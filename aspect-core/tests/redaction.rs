@@ -0,0 +1,69 @@
+//! Process-wide redaction rules, kept in their own test binary since they
+//! are global.
+
+use aspect_core::prelude::*;
+use aspect_core::{redaction, Arg};
+use aspect_macros::aspect;
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Records how each call's arguments would be shown.
+#[derive(Default)]
+struct Recorder {
+    shown: Mutex<Vec<String>>,
+}
+
+impl Aspect for Recorder {
+    fn before(&self, ctx: &JoinPoint) {
+        let args: Vec<String> = ctx.args.iter().map(|arg| format!("{:?}", arg)).collect();
+        self.shown.lock().unwrap().push(args.join(", "));
+    }
+
+    fn reads_args(&self) -> bool {
+        true
+    }
+}
+
+static RECORDER: LazyLock<Arc<Recorder>> = LazyLock::new(Default::default);
+
+#[aspect(RECORDER.clone())]
+fn sign_in(user: &str, one_time_code: u32) -> bool {
+    !user.is_empty() && one_time_code > 0
+}
+
+#[test]
+fn test_registered_rules() {
+    let user = Arg::new("user", &"alice".to_string());
+    let code = Arg::new("one_time_code", &123456u32);
+    let amount = Arg::new("amount", &-5i64);
+    assert!(!user.is_redacted() && !code.is_redacted() && !amount.is_redacted());
+
+    redaction::redact_name("*_CODE");
+    assert!(code.is_redacted());
+    assert!(code.debug().is_none());
+    assert_eq!(format!("{:?}", code), "one_time_code: <redacted>");
+    assert!(!user.is_redacted());
+
+    redaction::redact_type::<String>();
+    assert!(user.is_redacted());
+    assert!(!amount.is_redacted());
+
+    redaction::redact_if(|arg| arg.value::<i64>().is_some_and(|v| *v < 0));
+    assert!(amount.is_redacted());
+    assert!(!Arg::new("amount", &5i64).is_redacted());
+
+    redaction::clear();
+    assert!(!user.is_redacted() && !code.is_redacted() && !amount.is_redacted());
+
+    // Woven functions capture arguments the same way.
+    redaction::redact_name("one_time_code");
+    assert!(sign_in("alice", 42));
+    redaction::clear();
+    assert!(sign_in("alice", 42));
+    assert_eq!(
+        *RECORDER.shown.lock().unwrap(),
+        [
+            r#"user: "alice", one_time_code: <redacted>"#,
+            r#"user: "alice", one_time_code: 42"#
+        ]
+    );
+}
@@ -0,0 +1,139 @@
+//! Advice calling woven functions, which must not advise them again.
+
+use aspect_core::prelude::*;
+use aspect_macros::aspect;
+use std::any::Any;
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Records the calls it advises, describing them with the woven
+/// [`describe`], which it advises too.
+#[derive(Clone, Default)]
+struct Tracer {
+    events: Arc<Mutex<Vec<String>>>,
+}
+
+impl Tracer {
+    fn push(&self, event: String) {
+        self.events.lock().unwrap().push(event);
+    }
+
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut self.events.lock().unwrap())
+    }
+}
+
+impl Aspect for Tracer {
+    fn before(&self, ctx: &JoinPoint) {
+        self.push(format!("before {}", describe(ctx.function_name)));
+    }
+
+    fn after(&self, ctx: &JoinPoint, _result: &dyn Any) {
+        self.push(format!("after {}", describe(ctx.function_name)));
+    }
+}
+
+static TRACER: LazyLock<Tracer> = LazyLock::new(Tracer::default);
+
+#[aspect(TRACER.clone())]
+fn describe(name: &str) -> String {
+    format!("`{}`", name)
+}
+
+#[aspect(TRACER.clone())]
+fn greet(name: &str) -> String {
+    format!("hello {}", describe(name))
+}
+
+#[test]
+fn test_advice_calling_advised_function() {
+    TRACER.take();
+    assert_eq!(greet("ann"), "hello `ann`");
+    // `describe` is advised when `greet` calls it, not when advice does
+    assert_eq!(
+        TRACER.take(),
+        [
+            "before `greet`",
+            "before `describe`",
+            "after `describe`",
+            "after `greet`",
+        ]
+    );
+
+    // Called from outside advice again, it is advised
+    describe("bob");
+    assert_eq!(TRACER.take(), ["before `describe`", "after `describe`"]);
+}
+
+/// Around advice checking access with the woven [`allowed`].
+#[derive(Clone, Default)]
+struct Guard {
+    checks: Arc<Mutex<Vec<String>>>,
+}
+
+impl Aspect for Guard {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        if !allowed(pjp.context().function_name) {
+            return Err(AspectError::execution("denied"));
+        }
+        pjp.proceed()
+    }
+}
+
+static GUARD: LazyLock<Guard> = LazyLock::new(Guard::default);
+
+#[aspect(GUARD.clone())]
+fn allowed(name: &str) -> bool {
+    GUARD.checks.lock().unwrap().push(name.to_string());
+    name != "drop_tables"
+}
+
+#[aspect(GUARD.clone())]
+fn save(id: u64) -> Result<u64, String> {
+    // Proceeded to from the advice, yet advised
+    assert!(allowed("save_nested"));
+    Ok(id)
+}
+
+#[aspect(GUARD.clone())]
+fn drop_tables() -> Result<(), String> {
+    Ok(())
+}
+
+#[test]
+fn test_around_advice_calling_advised_function() {
+    assert_eq!(save(3), Ok(3));
+    assert!(drop_tables().is_err());
+    let checks = std::mem::take(&mut *GUARD.checks.lock().unwrap());
+    // Each advised call checked once, the check itself only when `save` calls it
+    assert_eq!(checks, ["save", "allowed", "save_nested", "drop_tables"]);
+}
+
+static ASYNC_TRACER: LazyLock<Tracer> = LazyLock::new(Tracer::default);
+
+#[aspect(ASYNC_TRACER.clone())]
+async fn fetch(id: u64) -> u64 {
+    id
+}
+
+/// Runs a future that never has to wait.
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    match future.as_mut().poll(&mut cx) {
+        std::task::Poll::Ready(output) => output,
+        std::task::Poll::Pending => panic!("future not ready"),
+    }
+}
+
+#[test]
+fn test_async_advice_calling_advised_function() {
+    assert_eq!(block_on(fetch(5)), 5);
+    assert_eq!(ASYNC_TRACER.take(), ["before `fetch`", "after `fetch`"]);
+    // `describe` is advised by `TRACER`, which the async advice never ran
+    assert!(TRACER
+        .events
+        .lock()
+        .unwrap()
+        .iter()
+        .all(|event| !event.contains("fetch")));
+}
@@ -0,0 +1,60 @@
+//! Around advice replacing the results of woven functions.
+
+use aspect_core::prelude::*;
+use aspect_macros::aspect;
+use std::any::Any;
+
+/// Replaces the result of every call with `value`, without calling the
+/// function.
+#[derive(Clone, Copy)]
+struct Replace<T>(T);
+
+impl<T: Clone + Send + Sync + 'static> Aspect for Replace<T> {
+    fn around(&self, _pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        Ok(Box::new(self.0.clone()))
+    }
+}
+
+#[aspect(Replace(7u32))]
+fn replaced_with_same_type() -> Result<u32, String> {
+    Ok(1)
+}
+
+#[aspect(Replace("seven"))]
+fn replaced_with_other_type() -> Result<u32, String> {
+    Ok(1)
+}
+
+#[aspect(Replace(7u32))]
+fn plain_replaced_with_same_type() -> u32 {
+    1
+}
+
+#[aspect(Replace("seven"))]
+fn plain_replaced_with_other_type() -> u32 {
+    1
+}
+
+#[test]
+fn test_result_replaced_with_same_type() {
+    assert_eq!(replaced_with_same_type(), Ok(7));
+    assert_eq!(plain_replaced_with_same_type(), 7);
+}
+
+#[test]
+fn test_result_replaced_with_other_type_is_an_error() {
+    let err = replaced_with_other_type().unwrap_err();
+    assert!(err.contains("WeavingError"), "{}", err);
+    assert!(
+        err.contains("replaced the result of replaced_with_other_type"),
+        "{}",
+        err
+    );
+    assert!(err.contains("not a u32"), "{}", err);
+}
+
+#[test]
+#[should_panic(expected = "replaced the result of plain_replaced_with_other_type")]
+fn test_plain_result_replaced_with_other_type_panics() {
+    plain_replaced_with_other_type();
+}
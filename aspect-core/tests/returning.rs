@@ -0,0 +1,109 @@
+//! Return values changed by `after_returning_mut` and `proceed_map`.
+
+use aspect_core::prelude::*;
+use aspect_macros::aspect;
+use std::any::Any;
+use std::sync::{Arc, LazyLock, Mutex};
+
+#[derive(Debug, Clone, PartialEq)]
+struct Response {
+    body: String,
+    headers: Vec<(&'static str, String)>,
+}
+
+/// Trims the strings functions return.
+struct Trim;
+
+impl Aspect for Trim {
+    fn after_returning_mut(&self, _ctx: &JoinPoint, result: &mut ReturnValue<'_>) {
+        result.update(|s: &mut String| *s = s.trim().to_string());
+    }
+}
+
+/// Adds a header naming the function to responses, and records the
+/// bodies it sees after the aspects it wraps.
+#[derive(Clone, Default)]
+struct Enrich {
+    seen: Arc<Mutex<Vec<String>>>,
+}
+
+impl Aspect for Enrich {
+    fn after_returning_mut(&self, ctx: &JoinPoint, result: &mut ReturnValue<'_>) {
+        if let Some(response) = result.get_mut::<Response>() {
+            response
+                .headers
+                .push(("x-handler", ctx.function_name.to_string()));
+            self.seen.lock().unwrap().push(response.body.clone());
+        }
+    }
+}
+
+static ENRICH: LazyLock<Enrich> = LazyLock::new(Enrich::default);
+
+/// Upper-cases the bodies of responses, in `around`.
+struct Shout;
+
+impl Aspect for Shout {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        pjp.proceed_map(|mut response: Response| {
+            response.body = response.body.to_uppercase();
+            response
+        })
+    }
+}
+
+fn response(body: &str) -> Response {
+    Response {
+        body: body.to_string(),
+        headers: Vec::new(),
+    }
+}
+
+#[aspect(Trim)]
+fn greeting(name: &str) -> String {
+    format!("  hello {}  ", name)
+}
+
+#[aspect(ENRICH.clone(), Shout)]
+fn get_user(id: u64) -> Result<Response, String> {
+    if id == 0 {
+        return Err("no user 0".to_string());
+    }
+    Ok(response(&format!("user {}", id)))
+}
+
+#[aspect(Trim)]
+async fn load_name(id: u64) -> String {
+    format!(" user {} ", id)
+}
+
+#[aspect(ENRICH.clone(), Trim)]
+async fn load_user(id: u64) -> Result<Response, String> {
+    Ok(response(&format!("user {}", id)))
+}
+
+/// Runs a future that never has to wait.
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    match future.as_mut().poll(&mut cx) {
+        std::task::Poll::Ready(output) => output,
+        std::task::Poll::Pending => panic!("future not ready"),
+    }
+}
+
+#[test]
+fn test_return_value_changed() {
+    assert_eq!(greeting("ada"), "hello ada");
+    assert_eq!(block_on(load_name(7)), "user 7");
+
+    // Outer aspects see the value as the inner ones changed it
+    let user = get_user(7).unwrap();
+    assert_eq!(user.body, "USER 7");
+    assert_eq!(user.headers, [("x-handler", "get_user".to_string())]);
+    assert_eq!(get_user(0), Err("no user 0".to_string()));
+
+    let user = block_on(load_user(7)).unwrap();
+    assert_eq!(user.headers, [("x-handler", "load_user".to_string())]);
+    assert_eq!(*ENRICH.seen.lock().unwrap(), ["USER 7", "user 7"]);
+}
@@ -0,0 +1,7 @@
+//! Woven code that must not compile.
+
+#[test]
+fn ui() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
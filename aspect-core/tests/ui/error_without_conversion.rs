@@ -0,0 +1,28 @@
+use aspect_core::prelude::*;
+use aspect_macros::aspect;
+
+/// Could fail any call it advises.
+struct Guard;
+
+impl Aspect for Guard {}
+
+/// Neither `From<AspectError>` nor `From<String>`.
+#[derive(Debug)]
+enum BankError {
+    Insufficient,
+}
+
+#[aspect(Guard)]
+fn withdraw(balance: u64, amount: u64) -> Result<u64, BankError> {
+    balance.checked_sub(amount).ok_or(BankError::Insufficient)
+}
+
+#[aspect(Guard)]
+async fn deposit(balance: u64, amount: u64) -> Result<u64, BankError> {
+    Ok(balance + amount)
+}
+
+fn main() {
+    let _ = withdraw(10, 4);
+    let _ = deposit(10, 4);
+}
@@ -0,0 +1,23 @@
+//! The getters of `#[aspect_fields]` hand the field to `after` as
+//! `&dyn Any`, so fields of borrowed types only get setters.
+
+use aspect_core::prelude::*;
+use aspect_macros::aspect_fields;
+
+struct Audit;
+
+impl Aspect for Audit {}
+
+#[aspect_fields(Audit)]
+struct Name<'a> {
+    #[aspect_field]
+    name: &'a str,
+}
+
+#[aspect_fields(Audit)]
+struct Slot<T: Clone> {
+    #[aspect_field(get)]
+    value: T,
+}
+
+fn main() {}
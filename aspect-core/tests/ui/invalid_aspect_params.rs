@@ -0,0 +1,44 @@
+//! The literal parameters of `#[aspect(Type, key = value)]` are checked
+//! against the parameters the aspect takes as the crate compiles.
+
+use aspect_core::config::Param;
+use aspect_core::prelude::*;
+use aspect_core::{AspectArgs, FromAspectArgs};
+use aspect_macros::aspect;
+use std::time::Duration;
+
+#[derive(Clone)]
+struct Quota;
+
+impl Aspect for Quota {}
+
+impl FromAspectArgs for Quota {
+    const PARAMS: &'static [Param] = &[
+        Param::required::<u32>("max"),
+        Param::optional::<Duration>("window"),
+    ];
+
+    fn from_aspect_args(args: &AspectArgs) -> Result<Self, AspectError> {
+        args.check(Self::PARAMS)?;
+        Ok(Self)
+    }
+}
+
+#[aspect(Quota, max = 2, burst = 1)]
+fn unknown() {}
+
+#[aspect(Quota, window = "1s")]
+fn missing() {}
+
+#[aspect(Quota, max = -1)]
+fn negative() {}
+
+#[aspect(Quota, max = 1, window = "soon")]
+fn not_a_duration() {}
+
+fn main() {
+    unknown();
+    missing();
+    negative();
+    not_a_duration();
+}
@@ -0,0 +1,171 @@
+//! Panics of woven functions not returning `Result`, with `unwind`.
+
+use aspect_core::prelude::*;
+use aspect_macros::aspect;
+use std::any::Any;
+use std::panic;
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Records the advice the aspects see.
+#[derive(Clone, Default)]
+struct Recorder {
+    events: Arc<Mutex<Vec<String>>>,
+}
+
+impl Recorder {
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut self.events.lock().unwrap())
+    }
+}
+
+impl Aspect for Recorder {
+    fn before(&self, ctx: &JoinPoint) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("before {}", ctx.function_name));
+    }
+
+    fn after(&self, ctx: &JoinPoint, _result: &dyn Any) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("after {}", ctx.function_name));
+    }
+
+    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("after_error {}: {}", ctx.function_name, error));
+    }
+}
+
+static RECORDER: LazyLock<Recorder> = LazyLock::new(Recorder::default);
+
+/// Returns 0 for the calls that panic.
+struct Recover;
+
+impl Aspect for Recover {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        match pjp.proceed() {
+            Err(AspectError::Panic { .. }) => Ok(Box::new(0u32)),
+            result => result,
+        }
+    }
+}
+
+#[aspect(unwind RECORDER.clone())]
+fn divide(a: u32, b: u32) -> u32 {
+    a / b
+}
+
+#[aspect(RECORDER.clone())]
+fn divide_without_unwind(a: u32, b: u32) -> u32 {
+    a / b
+}
+
+#[aspect(unwind Recover, RECORDER.clone())]
+fn divide_or_zero(a: u32, b: u32) -> u32 {
+    a / b
+}
+
+#[aspect(unwind RECORDER.clone())]
+async fn load(id: u64) -> u64 {
+    assert!(id != 0, "no record 0");
+    id
+}
+
+#[aspect(unwind RECORDER.clone(), Recover)]
+async fn load_all(id: u64) -> u64 {
+    assert!(id != 0, "no record 0");
+    id
+}
+
+/// Runs a future that never has to wait.
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    match future.as_mut().poll(&mut cx) {
+        std::task::Poll::Ready(output) => output,
+        std::task::Poll::Pending => panic!("future not ready"),
+    }
+}
+
+/// The message of the panic of `f`.
+fn panic_message(f: impl FnOnce()) -> String {
+    let payload = panic::catch_unwind(panic::AssertUnwindSafe(f)).unwrap_err();
+    match payload.downcast::<&str>() {
+        Ok(message) => message.to_string(),
+        Err(payload) => *payload.downcast::<String>().unwrap(),
+    }
+}
+
+#[test]
+fn test_unwind() {
+    panic::set_hook(Box::new(|_| {}));
+
+    // The aspects see the panic, which still reaches the caller
+    assert_eq!(divide(6, 3), 2);
+    assert_eq!(
+        panic_message(|| {
+            divide(6, 0);
+        }),
+        "attempt to divide by zero"
+    );
+    assert_eq!(
+        RECORDER.take(),
+        [
+            "before divide",
+            "after divide",
+            "before divide",
+            "after_error divide: Panic: attempt to divide by zero",
+        ]
+    );
+
+    // Without `unwind`, the advice after the function does not run
+    panic_message(|| {
+        divide_without_unwind(6, 0);
+    });
+    assert_eq!(RECORDER.take(), ["before divide_without_unwind"]);
+
+    // An aspect can return a value instead
+    assert_eq!(divide_or_zero(6, 0), 0);
+    assert_eq!(
+        RECORDER.take(),
+        [
+            "before divide_or_zero",
+            "after_error divide_or_zero: Panic: attempt to divide by zero",
+        ]
+    );
+
+    // Async functions, where every aspect sees the panic
+    assert_eq!(block_on(load(7)), 7);
+    assert_eq!(
+        panic_message(|| {
+            block_on(load(0));
+        }),
+        "no record 0"
+    );
+    assert_eq!(
+        RECORDER.take(),
+        [
+            "before load",
+            "after load",
+            "before load",
+            "after_error load: Panic: no record 0",
+        ]
+    );
+    panic_message(|| {
+        block_on(load_all(0));
+    });
+    assert_eq!(
+        RECORDER.take(),
+        [
+            "before load_all",
+            "after_error load_all: Panic: no record 0"
+        ]
+    );
+
+    let _ = panic::take_hook();
+}
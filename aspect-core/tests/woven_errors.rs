@@ -0,0 +1,108 @@
+//! Errors of woven functions returning `Result`.
+
+use aspect_core::prelude::*;
+use aspect_macros::aspect;
+use std::any::Any;
+use std::rc::Rc;
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Records the errors the aspects see.
+#[derive(Clone, Default)]
+struct Recorder {
+    errors: Arc<Mutex<Vec<String>>>,
+}
+
+impl Aspect for Recorder {
+    fn after_error(&self, _ctx: &JoinPoint, error: &AspectError) {
+        self.errors.lock().unwrap().push(error.to_string());
+    }
+}
+
+static RECORDER: LazyLock<Recorder> = LazyLock::new(Recorder::default);
+
+/// Fails every call without running the function.
+struct Reject;
+
+impl Aspect for Reject {
+    fn around(&self, _pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        Err(AspectError::denied("admin", "guest"))
+    }
+}
+
+/// Not an `Error`, only built from the errors of the aspects.
+#[derive(Debug, PartialEq)]
+enum BankError {
+    Insufficient { missing: u64 },
+    Rejected,
+}
+
+impl From<AspectError> for BankError {
+    fn from(_error: AspectError) -> Self {
+        BankError::Rejected
+    }
+}
+
+#[aspect(RECORDER.clone())]
+fn withdraw(balance: u64, amount: u64) -> Result<u64, BankError> {
+    balance
+        .checked_sub(amount)
+        .ok_or_else(|| BankError::Insufficient {
+            missing: amount - balance,
+        })
+}
+
+#[aspect(RECORDER.clone())]
+fn shared_error() -> Result<(), Rc<str>> {
+    Err(Rc::from("not Send"))
+}
+
+#[derive(Debug, PartialEq)]
+enum ApiError {
+    NotFound,
+    Aspect(String),
+}
+
+impl From<AspectError> for ApiError {
+    fn from(error: AspectError) -> Self {
+        ApiError::Aspect(error.to_string())
+    }
+}
+
+#[aspect(Reject)]
+fn rejected_with_from_aspect_error() -> Result<(), ApiError> {
+    Err(ApiError::NotFound)
+}
+
+#[aspect(Reject)]
+fn rejected_with_string() -> Result<(), String> {
+    Ok(())
+}
+
+#[test]
+fn test_error_returned_unchanged() {
+    assert_eq!(withdraw(10, 4), Ok(6));
+    assert_eq!(
+        withdraw(10, 15),
+        Err(BankError::Insufficient { missing: 5 })
+    );
+    assert!(RECORDER
+        .errors
+        .lock()
+        .unwrap()
+        .contains(&"Execution error: Insufficient { missing: 5 }".to_string()));
+
+    assert_eq!(&*shared_error().unwrap_err(), "not Send");
+}
+
+#[test]
+fn test_replaced_error_converted() {
+    assert_eq!(
+        rejected_with_from_aspect_error(),
+        Err(ApiError::Aspect(
+            "Access denied: requires admin, caller has guest".to_string()
+        ))
+    );
+
+    let err = rejected_with_string().unwrap_err();
+    assert!(err.starts_with("Denied"), "{}", err);
+}
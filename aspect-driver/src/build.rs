@@ -0,0 +1,341 @@
+//! Pointcut-driven weaving from build scripts.
+//!
+//! [`weave_in_out_dir`] weaves the sources of a directory that is not part
+//! of the crate's module tree into `OUT_DIR`, and generates a module file
+//! declaring them, so the woven code is pulled in with a single `include!`:
+//!
+//! ```ignore
+//! // build.rs
+//! use aspect_driver::build::{weave_in_out_dir, WeaveConfig};
+//! use aspect_driver::source::parse_aspect_spec;
+//!
+//! fn main() {
+//!     weave_in_out_dir(WeaveConfig {
+//!         aspects: vec![parse_aspect_spec(
+//!             "execution(pub fn *(..))=crate::aspects::LOGGER.clone()",
+//!         )
+//!         .unwrap()],
+//!         ..WeaveConfig::default()
+//!     })
+//!     .unwrap();
+//! }
+//!
+//! // src/lib.rs: declares one module per file of weave/
+//! include!(concat!(env!("OUT_DIR"), "/aspect/modules.rs"));
+//! ```
+//!
+//! Sources are woven by [`SourceWeaver`], so the crate has to depend on
+//! `aspect-core` and `aspect-macros`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use syn::spanned::Spanned;
+
+use crate::r#match::RegisteredAspect;
+use crate::source::{byte_offset, line_offsets, rust_files, SourceWeaver, WovenFunction};
+
+/// Configuration of [`weave_in_out_dir`].
+#[derive(Debug, Clone)]
+pub struct WeaveConfig {
+    /// Directory of the sources to weave, relative to `CARGO_MANIFEST_DIR`
+    /// (default: `weave`)
+    pub src_dir: PathBuf,
+
+    /// Directory to write to (default: `$OUT_DIR/aspect`)
+    pub out_dir: Option<PathBuf>,
+
+    /// Module path the generated modules are included at, used to match
+    /// `within(...)` pointcuts (default: `crate`)
+    pub module_prefix: String,
+
+    /// Aspects to apply
+    pub aspects: Vec<RegisteredAspect>,
+
+    /// Print `cargo:rerun-if-changed` for the sources (default: true)
+    pub rerun_if_changed: bool,
+}
+
+impl Default for WeaveConfig {
+    fn default() -> Self {
+        Self {
+            src_dir: PathBuf::from("weave"),
+            out_dir: None,
+            module_prefix: "crate".to_string(),
+            aspects: Vec::new(),
+            rerun_if_changed: true,
+        }
+    }
+}
+
+/// Result of [`weave_in_out_dir`].
+#[derive(Debug, Clone)]
+pub struct BuildReport {
+    /// The generated module file to `include!`
+    pub modules_file: PathBuf,
+
+    /// Woven source files
+    pub files: Vec<PathBuf>,
+
+    /// Functions that got aspects, in all files
+    pub woven: Vec<WovenFunction>,
+}
+
+/// One module of the generated module tree.
+#[derive(Default)]
+struct ModuleNode {
+    /// Woven file holding the module's items
+    file: Option<PathBuf>,
+    /// Inner attributes of that file, as outer attributes
+    attributes: Vec<String>,
+    children: BTreeMap<String, ModuleNode>,
+}
+
+/// Weave every `.rs` file of `config.src_dir` into the output directory
+/// and generate `modules.rs` declaring a module per file.
+///
+/// `a.rs` becomes module `a`, and `a/b.rs` or `a/b/mod.rs` module `a::b`.
+/// Inner attributes and doc comments of a file, which `include!` does not
+/// accept, are moved to its module declaration.
+///
+/// Meant to be called from a build script: relative paths are resolved
+/// against `CARGO_MANIFEST_DIR`, and the output goes to `OUT_DIR` unless
+/// `config.out_dir` is set.
+pub fn weave_in_out_dir(config: WeaveConfig) -> Result<BuildReport, String> {
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    let src_dir = manifest_dir.join(&config.src_dir);
+    let out_dir = match &config.out_dir {
+        Some(out_dir) => manifest_dir.join(out_dir),
+        None => std::env::var_os("OUT_DIR")
+            .map(|out_dir| PathBuf::from(out_dir).join("aspect"))
+            .ok_or("OUT_DIR is not set; call weave_in_out_dir from build.rs or set out_dir")?,
+    };
+
+    if config.rerun_if_changed {
+        println!("cargo:rerun-if-changed={}", src_dir.display());
+    }
+
+    let mut weaver = SourceWeaver::new();
+    for aspect in &config.aspects {
+        weaver.register(aspect.clone());
+    }
+
+    let mut report = BuildReport {
+        modules_file: out_dir.join("modules.rs"),
+        files: Vec::new(),
+        woven: Vec::new(),
+    };
+    let mut root = ModuleNode::default();
+    for path in rust_files(&src_dir)? {
+        let relative = path.strip_prefix(&src_dir).unwrap();
+        if config.rerun_if_changed {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+
+        let segments = module_segments(relative);
+        let module_path = std::iter::once(config.module_prefix.as_str())
+            .chain(segments.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join("::");
+        let file = path.to_string_lossy().to_string();
+        let source = fs::read_to_string(&path).map_err(|e| format!("{}: {}", file, e))?;
+
+        let woven = weaver.weave_source(&source, &file, &module_path)?;
+        let (source, attributes) = strip_inner_attributes(&woven.source, &file)?;
+
+        let output = out_dir.join(relative);
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+        }
+        write_if_changed(&output, &source)?;
+
+        let node = segments.iter().fold(&mut root, |node, segment| {
+            node.children.entry(segment.clone()).or_default()
+        });
+        node.file = Some(output.clone());
+        node.attributes = attributes;
+        report.files.push(output);
+        report.woven.extend(woven.woven);
+    }
+
+    let mut modules = String::from("// Generated by aspect-driver; do not edit.\n");
+    for (name, node) in &root.children {
+        render_module(&mut modules, name, node, 0);
+    }
+    fs::create_dir_all(&out_dir).map_err(|e| format!("{}: {}", out_dir.display(), e))?;
+    write_if_changed(&report.modules_file, &modules)?;
+
+    Ok(report)
+}
+
+/// Module path segments of a file relative to the source directory.
+fn module_segments(relative: &Path) -> Vec<String> {
+    let mut segments: Vec<String> = relative
+        .with_extension("")
+        .iter()
+        .map(|segment| segment.to_string_lossy().to_string())
+        .collect();
+    if segments.last().map(String::as_str) == Some("mod") {
+        segments.pop();
+    }
+    segments
+}
+
+/// Blank out the inner attributes of `source`, keeping line numbers, and
+/// return them as outer attributes.
+fn strip_inner_attributes(source: &str, file: &str) -> Result<(String, Vec<String>), String> {
+    let ast = syn::parse_file(source).map_err(|e| format!("failed to parse {}: {}", file, e))?;
+    let lines = line_offsets(source);
+    let mut stripped = source.to_string();
+    let mut attributes = Vec::new();
+
+    for attr in &ast.attrs {
+        let span = attr.span();
+        let (start, end) = (
+            byte_offset(source, &lines, span.start()),
+            byte_offset(source, &lines, span.end()),
+        );
+        // Same length in bytes, so later offsets stay valid
+        let blank: String = source[start..end]
+            .chars()
+            .map(|c| match c {
+                '\n' => "\n".to_string(),
+                c => " ".repeat(c.len_utf8()),
+            })
+            .collect();
+        stripped.replace_range(start..end, &blank);
+
+        let mut outer = attr.clone();
+        outer.style = syn::AttrStyle::Outer;
+        attributes.push(quote::quote!(#outer).to_string());
+    }
+
+    Ok((stripped, attributes))
+}
+
+fn render_module(out: &mut String, name: &str, node: &ModuleNode, depth: usize) {
+    let indent = "    ".repeat(depth);
+    for attribute in &node.attributes {
+        let _ = writeln!(out, "{}{}", indent, attribute);
+    }
+    let _ = writeln!(out, "{}pub mod {} {{", indent, name);
+    if let Some(file) = &node.file {
+        let _ = writeln!(out, "{}    include!({:?});", indent, file.to_string_lossy());
+    }
+    for (child, node) in &node.children {
+        render_module(out, child, node, depth + 1);
+    }
+    let _ = writeln!(out, "{}}}", indent);
+}
+
+/// Avoid touching unchanged outputs, so they do not trigger rebuilds.
+fn write_if_changed(path: &Path, contents: &str) -> Result<(), String> {
+    if fs::read_to_string(path).is_ok_and(|existing| existing == contents) {
+        return Ok(());
+    }
+    fs::write(path, contents).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::parse_aspect_spec;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("aspect-build-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_module_segments() {
+        assert_eq!(module_segments(Path::new("api.rs")), vec!["api"]);
+        assert_eq!(
+            module_segments(Path::new("api/users.rs")),
+            vec!["api", "users"]
+        );
+        assert_eq!(module_segments(Path::new("api/mod.rs")), vec!["api"]);
+    }
+
+    #[test]
+    fn test_strip_inner_attributes() {
+        let source = "//! Users API\n#![allow(dead_code)]\n\npub fn get() {}\n";
+        let (stripped, attributes) = strip_inner_attributes(source, "api.rs").unwrap();
+
+        assert_eq!(stripped.lines().count(), source.lines().count());
+        assert_eq!(stripped.lines().nth(3), Some("pub fn get() {}"));
+        assert!(stripped.lines().take(2).all(|line| line.trim().is_empty()));
+        assert_eq!(
+            attributes,
+            vec!["# [doc = \" Users API\"]", "# [allow (dead_code)]"]
+        );
+    }
+
+    #[test]
+    fn test_weave_in_out_dir() {
+        let dir = temp_dir("weave");
+        let src_dir = dir.join("weave");
+        fs::create_dir_all(src_dir.join("api")).unwrap();
+        fs::write(src_dir.join("util.rs"), "pub fn helper() {}\n").unwrap();
+        fs::write(
+            src_dir.join("api/mod.rs"),
+            "//! The API\npub fn index() {}\n",
+        )
+        .unwrap();
+        fs::write(
+            src_dir.join("api/users.rs"),
+            "pub fn get_user() {}\nfn check() {}\n",
+        )
+        .unwrap();
+
+        let report = weave_in_out_dir(WeaveConfig {
+            src_dir: src_dir.clone(),
+            out_dir: Some(dir.join("out")),
+            aspects: vec![parse_aspect_spec("within(crate::api)=Logger::new()").unwrap()],
+            rerun_if_changed: false,
+            ..WeaveConfig::default()
+        })
+        .unwrap();
+
+        let names: Vec<_> = report
+            .woven
+            .iter()
+            .map(|w| w.function.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "crate::api::index",
+                "crate::api::users::get_user",
+                "crate::api::users::check"
+            ]
+        );
+        assert_eq!(report.files.len(), 3);
+
+        let users = fs::read_to_string(dir.join("out/api/users.rs")).unwrap();
+        assert!(users.starts_with("#[::aspect_macros::aspect(Logger::new())] pub fn get_user()"));
+        let util = fs::read_to_string(dir.join("out/util.rs")).unwrap();
+        assert_eq!(util, "pub fn helper() {}\n");
+
+        let modules = fs::read_to_string(&report.modules_file).unwrap();
+        let api = dir.join("out/api/mod.rs");
+        let users = dir.join("out/api/users.rs");
+        assert!(modules.contains(&format!(
+            "# [doc = \" The API\"]\npub mod api {{\n    include!({:?});\n    pub mod users {{\n        include!({:?});\n    }}\n}}\n",
+            api.to_string_lossy(),
+            users.to_string_lossy()
+        )));
+        assert!(modules.contains("pub mod util {"));
+
+        syn::parse_file(&modules).unwrap();
+        syn::parse_file(&fs::read_to_string(&api).unwrap()).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
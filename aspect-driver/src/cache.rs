@@ -0,0 +1,374 @@
+//! Incremental analysis cache.
+//!
+//! Extracting metadata for every function on every build is wasteful for
+//! large crates. [`AnalysisCache`] keeps the [`FunctionMetadata`] of each
+//! function, keyed by its definition path, together with a hash of the
+//! source file it is defined in; an entry is reused for as long as that
+//! file is unchanged.
+//!
+//! The cache lives in `target/aspect/`, one JSON file per crate. A cache
+//! written by another version of the driver is discarded, as is one that
+//! cannot be read.
+//!
+//! Tools that analyze whole crates tell whether a crate changed with its
+//! [`crate_fingerprint`], a hash of all its sources.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::FunctionMetadata;
+
+/// Version of the cache file format.
+pub const CACHE_VERSION: u32 = 4;
+
+/// Cache hit and miss counts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    /// Functions whose metadata was reused
+    pub hits: usize,
+
+    /// Functions extracted because they were not cached
+    pub misses: usize,
+
+    /// Functions extracted because their file changed
+    pub stale: usize,
+
+    /// Entries dropped because their function no longer exists
+    pub removed: usize,
+}
+
+impl CacheStats {
+    pub fn print_summary(&self) {
+        println!("\n=== Analysis Cache ===");
+        println!("Reused: {}", self.hits);
+        println!("Extracted: {}", self.misses + self.stale);
+        println!("  Not cached: {}", self.misses);
+        println!("  File changed: {}", self.stale);
+        if self.removed > 0 {
+            println!("Removed: {}", self.removed);
+        }
+    }
+}
+
+/// Contents of a cache file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    driver_version: String,
+    entries: BTreeMap<String, CacheEntry>,
+}
+
+/// Cached metadata of one function.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Hash of the source file the function is defined in
+    file_hash: u64,
+    function: FunctionMetadata,
+}
+
+/// Function metadata cached between builds.
+#[derive(Debug)]
+pub struct AnalysisCache {
+    path: PathBuf,
+    entries: BTreeMap<String, CacheEntry>,
+    /// Definition paths looked up or inserted in this build
+    seen: HashSet<String>,
+    stats: CacheStats,
+}
+
+impl AnalysisCache {
+    /// Load the cache at `path`, or start an empty one if there is none
+    /// or it was written by another driver version.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let entries = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<CacheFile>(&contents).ok())
+            .filter(|file| {
+                file.version == CACHE_VERSION && file.driver_version == env!("CARGO_PKG_VERSION")
+            })
+            .map(|file| file.entries)
+            .unwrap_or_default();
+
+        Self {
+            path,
+            entries,
+            seen: HashSet::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// The cache file of a crate in `dir` (e.g., `target/aspect`).
+    ///
+    /// `crate_id` tells apart crates with the same name, such as the
+    /// library and binary of a package.
+    pub fn path_in(dir: &Path, crate_name: &str, crate_id: u64) -> PathBuf {
+        dir.join(format!("{}-{:016x}.json", crate_name, crate_id))
+    }
+
+    /// Cached metadata of `def_path`, if its file still has `file_hash`.
+    pub fn get(&mut self, def_path: &str, file_hash: u64) -> Option<FunctionMetadata> {
+        self.seen.insert(def_path.to_string());
+        match self.entries.get(def_path) {
+            Some(entry) if entry.file_hash == file_hash => {
+                self.stats.hits += 1;
+                Some(entry.function.clone())
+            }
+            Some(_) => {
+                self.stats.stale += 1;
+                None
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Cache the metadata of `def_path`, extracted from a file with
+    /// `file_hash`.
+    pub fn insert(&mut self, def_path: &str, file_hash: u64, function: FunctionMetadata) {
+        self.seen.insert(def_path.to_string());
+        self.entries.insert(
+            def_path.to_string(),
+            CacheEntry {
+                file_hash,
+                function,
+            },
+        );
+    }
+
+    pub fn stats(&self) -> &CacheStats {
+        &self.stats
+    }
+
+    /// Write the cache back, dropping functions that were not seen in this
+    /// build, and return the statistics.
+    pub fn save(mut self) -> Result<CacheStats, String> {
+        let before = self.entries.len();
+        let seen = &self.seen;
+        self.entries.retain(|def_path, _| seen.contains(def_path));
+        self.stats.removed = before - self.entries.len();
+
+        let file = CacheFile {
+            version: CACHE_VERSION,
+            driver_version: env!("CARGO_PKG_VERSION").to_string(),
+            entries: self.entries,
+        };
+        let contents = serde_json::to_string(&file).map_err(|e| e.to_string())?;
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+        }
+        // Written aside and renamed, so concurrent builds never read a
+        // partial file
+        let partial = self.path.with_extension("json.partial");
+        fs::write(&partial, contents).map_err(|e| format!("{}: {}", partial.display(), e))?;
+        fs::rename(&partial, &self.path).map_err(|e| format!("{}: {}", self.path.display(), e))?;
+
+        Ok(self.stats)
+    }
+}
+
+/// Hash of a source file's contents (64-bit FNV-1a), stable across builds
+/// and platforms.
+pub fn content_hash(contents: &[u8]) -> u64 {
+    extend_hash(0xcbf2_9ce4_8422_2325, contents)
+}
+
+fn extend_hash(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Fingerprint of the sources of the crate whose root file is `root`: a
+/// hash of the paths, relative to the directory of `root`, and contents
+/// of the `.rs` files in that directory and below, hidden and `target`
+/// directories aside.
+///
+/// It changes with any source of the crate, and with sources of other
+/// crates that happen to be below it; it does not depend on where the
+/// crate is.
+pub fn crate_fingerprint(root: &Path) -> Result<u64, String> {
+    let dir = root.parent().unwrap_or_else(|| Path::new(""));
+    let mut files = Vec::new();
+    collect_rs_files(dir, &mut files)?;
+    files.sort();
+
+    let mut hash = content_hash(root.file_name().unwrap_or_default().as_encoded_bytes());
+    for file in files {
+        let contents = fs::read(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+        let relative = file.strip_prefix(dir).unwrap_or(&file);
+        hash = extend_hash(hash, relative.to_string_lossy().as_bytes());
+        hash = extend_hash(hash, &[0]);
+        hash = extend_hash(hash, &content_hash(&contents).to_le_bytes());
+    }
+    Ok(hash)
+}
+
+fn collect_rs_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    })
+    .map_err(|e| format!("{}: {}", dir.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let path = dir.join(&*name);
+        let file_type = entry
+            .file_type()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if file_type.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                collect_rs_files(&path, files)?;
+            }
+        } else if name.ends_with(".rs") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{SourceLocation, Visibility};
+
+    fn function(name: &str) -> FunctionMetadata {
+        FunctionMetadata {
+            name: name.to_string(),
+            simple_name: name.rsplit("::").next().unwrap().to_string(),
+            module_path: "crate::api".to_string(),
+            visibility: Visibility::Public,
+            is_async: false,
+            is_closure: false,
+            generics: vec![],
+            return_type: "()".to_string(),
+            is_trait_method: false,
+            trait_name: None,
+            impl_type: None,
+            attributes: vec![],
+            location: SourceLocation {
+                file: "src/api.rs".to_string(),
+                line: 1,
+                column: 1,
+                end_line: 1,
+                end_column: 1,
+            },
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("aspect-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(content_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(content_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(content_hash(b"fn a() {}"), content_hash(b"fn b() {}"));
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let dir = temp_dir("round-trip");
+        let path = AnalysisCache::path_in(&dir, "demo", 42);
+        assert!(path.ends_with("demo-000000000000002a.json"));
+
+        let mut cache = AnalysisCache::load(&path);
+        assert!(cache.get("api::get", 1).is_none());
+        cache.insert("api::get", 1, function("api::get"));
+        cache.insert("api::put", 1, function("api::put"));
+        cache.insert("api::old", 2, function("api::old"));
+        let stats = cache.save().unwrap();
+        assert_eq!(stats.misses, 1);
+
+        // api::put's file changed and api::old is gone
+        let mut cache = AnalysisCache::load(&path);
+        assert_eq!(cache.get("api::get", 1).unwrap().name, "api::get");
+        assert!(cache.get("api::put", 3).is_none());
+        cache.insert("api::put", 3, function("api::put"));
+        assert_eq!(
+            cache.stats(),
+            &CacheStats {
+                hits: 1,
+                misses: 0,
+                stale: 1,
+                removed: 0,
+            }
+        );
+        let stats = cache.save().unwrap();
+        assert_eq!(stats.removed, 1);
+
+        let mut cache = AnalysisCache::load(&path);
+        assert!(cache.get("api::old", 2).is_none());
+        assert!(cache.get("api::put", 3).is_some());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unreadable_cache_is_discarded() {
+        let dir = temp_dir("unreadable");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("demo.json");
+
+        fs::write(&path, "not json").unwrap();
+        let mut cache = AnalysisCache::load(&path);
+        assert!(cache.get("api::get", 1).is_none());
+
+        let outdated = serde_json::json!({
+            "version": CACHE_VERSION + 1,
+            "driver_version": env!("CARGO_PKG_VERSION"),
+            "entries": {},
+        });
+        fs::write(&path, outdated.to_string()).unwrap();
+        let cache = AnalysisCache::load(&path);
+        assert!(cache.entries.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_crate_fingerprint() {
+        let dir = std::env::temp_dir().join(format!("aspect-fingerprint-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let src = dir.join("src");
+        fs::create_dir_all(src.join("api")).unwrap();
+        fs::create_dir_all(src.join(".hidden")).unwrap();
+        fs::write(src.join("lib.rs"), "pub mod api;\n").unwrap();
+        fs::write(src.join("api/mod.rs"), "pub fn get() {}\n").unwrap();
+        let root = src.join("lib.rs");
+
+        let fingerprint = crate_fingerprint(&root).unwrap();
+        assert_eq!(crate_fingerprint(&root).unwrap(), fingerprint);
+        assert_ne!(
+            crate_fingerprint(&src.join("main.rs")).unwrap(),
+            fingerprint
+        );
+
+        // Only Rust sources outside hidden directories count
+        fs::write(src.join("notes.txt"), "").unwrap();
+        fs::write(src.join(".hidden/scratch.rs"), "").unwrap();
+        assert_eq!(crate_fingerprint(&root).unwrap(), fingerprint);
+
+        fs::write(src.join("api/mod.rs"), "pub fn get() {}\npub fn put() {}\n").unwrap();
+        let changed = crate_fingerprint(&root).unwrap();
+        assert_ne!(changed, fingerprint);
+        fs::rename(src.join("api/mod.rs"), src.join("api/routes.rs")).unwrap();
+        assert_ne!(crate_fingerprint(&root).unwrap(), changed);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(crate_fingerprint(&root).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+//! Running the compiler driver under cargo.
+//!
+//! `cargo aspect build` runs cargo with aspect-rustc-driver as
+//! `RUSTC_WORKSPACE_WRAPPER`. Cargo then runs the driver with the path of
+//! rustc as its first argument, followed by the rustc arguments, and gives
+//! no way to add flags of its own. cargo-aspect passes the `--aspect-*`
+//! flags in [`ENCODED_ARGS_ENV`] instead, separated by `0x1f` like
+//! `CARGO_ENCODED_RUSTFLAGS`, and has the driver write the analysis of
+//! every crate to [`RESULTS_DIR_ENV`].
+
+use std::path::Path;
+
+/// `--aspect-*` flags of the driver, separated by `0x1f`.
+pub const ENCODED_ARGS_ENV: &str = "ASPECT_ENCODED_ARGS";
+
+/// Directory the driver writes the JSON analysis of each crate to, in
+/// `<crate>-<kind>.json` (see `filter::CrateTarget::report_name`).
+pub const RESULTS_DIR_ENV: &str = "ASPECT_RESULTS_DIR";
+
+/// Set to `1`, fails the build when a pointcut matches no function, like
+/// `--aspect-deny-unmatched`.
+pub const DENY_UNMATCHED_ENV: &str = "ASPECT_DENY_UNMATCHED";
+
+/// Set to `1`, fails the build when a function matched by the pointcut of
+/// advice cannot be woven, like `--aspect-deny-unwoven`.
+pub const DENY_UNWOVEN_ENV: &str = "ASPECT_DENY_UNWOVEN";
+
+/// Separator of the flags in [`ENCODED_ARGS_ENV`].
+const SEPARATOR: char = '\x1f';
+
+/// Encode flags for [`ENCODED_ARGS_ENV`]; they may contain spaces, as
+/// pointcuts do.
+pub fn encode_args(args: &[String]) -> String {
+    args.join(&SEPARATOR.to_string())
+}
+
+/// The flags encoded by [`encode_args`].
+pub fn decode_args(encoded: &str) -> Vec<String> {
+    if encoded.is_empty() {
+        return Vec::new();
+    }
+    encoded.split(SEPARATOR).map(str::to_string).collect()
+}
+
+/// Remove the rustc path cargo passes first to a rustc wrapper from
+/// `args`, the arguments of the driver without its program name. Returns
+/// whether the driver runs as a wrapper.
+pub fn strip_wrapped_rustc(args: &mut Vec<String>) -> bool {
+    let wrapped = args
+        .first()
+        .and_then(|arg| Path::new(arg).file_stem())
+        .is_some_and(|stem| stem == "rustc");
+    if wrapped {
+        args.remove(0);
+    }
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_encoded_args() {
+        let flags = args(&[
+            "--aspect-pointcut",
+            "execution(pub fn *(..))",
+            "--aspect-verbose",
+        ]);
+        let encoded = encode_args(&flags);
+        assert_eq!(
+            encoded,
+            "--aspect-pointcut\x1fexecution(pub fn *(..))\x1f--aspect-verbose"
+        );
+        assert_eq!(decode_args(&encoded), flags);
+        assert!(decode_args("").is_empty());
+    }
+
+    #[test]
+    fn test_strip_wrapped_rustc() {
+        let mut wrapped = args(&["/home/u/.rustup/toolchains/nightly/bin/rustc", "-vV"]);
+        assert!(strip_wrapped_rustc(&mut wrapped));
+        assert_eq!(wrapped, ["-vV"]);
+
+        let mut direct = args(&["src/main.rs", "--edition", "2021"]);
+        assert!(!strip_wrapped_rustc(&mut direct));
+        assert_eq!(direct.len(), 3);
+    }
+}
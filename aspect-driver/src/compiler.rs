@@ -86,10 +86,8 @@ impl AspectCompiler {
             return Err("No input files specified".to_string());
         }
 
-        if self.config.aspects.is_empty() {
-            if self.config.verbose {
-                println!("Warning: No aspects registered - nothing to weave");
-            }
+        if self.config.aspects.is_empty() && self.config.verbose {
+            println!("Warning: No aspects registered - nothing to weave");
         }
 
         if self.config.verbose {
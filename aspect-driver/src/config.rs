@@ -0,0 +1,584 @@
+//! `aspect.toml` configuration files.
+//!
+//! Instead of passing many `--aspect-*` flags, the compiler driver can
+//! read its configuration from an `aspect.toml`:
+//!
+//! ```toml
+//! pointcuts = ["execution(pub fn api::*(..))"]
+//! fields = ["set(Account.balance)"]
+//! passes = ["tools/check-policy --strict"]
+//! closures = true
+//! deny_unmatched = true
+//! deny_unwoven = true
+//!
+//! [[aspects]]
+//! name = "tracing"
+//! pointcut = "execution(pub fn *(..))"
+//! advice = "before"
+//! hook = "crate::trace::enter"
+//! priority = 10
+//!
+//! [output]
+//! format = "json"
+//! file = "target/aspect/analysis.json"
+//!
+//! [cache]
+//! dir = "target/aspect"
+//!
+//! [crates]
+//! only = ["shop", "shop-api"]
+//! tests = false
+//!
+//! [profiles.ci]
+//! deny_unmatched = true
+//! output = { format = "sarif", file = "target/aspect/analysis.sarif" }
+//!
+//! [profiles.release]
+//! enabled = ["tracing"]
+//! ```
+//!
+//! Aspects run in order of decreasing priority, and in the order of the
+//! file for equal priorities. Relative paths are relative to the directory
+//! of the file.
+//!
+//! A profile, selected with `--aspect-profile`, has the same settings as
+//! the file. Its lists are added to those of the file, its other values
+//! replace them. Its `enabled` list, if any, keeps only the aspects (by
+//! name, or by hook when unnamed) and pointcuts of the file it names,
+//! before its own are added. Profiles can also be written `[profile.dev]`,
+//! like in Cargo.toml, though not both ways in one file; cargo-aspect
+//! applies the profile named after the cargo profile of the build when
+//! none is selected.
+//!
+//! The driver reads the `aspect.toml` of the crate it compiles. cargo-aspect
+//! reads the one at the root of the workspace instead, and passes it to the
+//! driver with `--aspect-config`.
+//!
+//! Command-line flags override the values of the file; pointcuts, advice
+//! and field patterns given on the command line are added to those of the
+//! file.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::field::FieldPattern;
+use crate::filter::CrateFilter;
+use crate::pass::CommandPass;
+use crate::r#match::{parse_pointcut, AdviceHook, AdviceType};
+
+/// Name of the configuration file looked up in the crate directory.
+pub const CONFIG_FILE: &str = "aspect.toml";
+
+/// Contents of an `aspect.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Pointcuts to report matches for
+    pub pointcuts: Vec<String>,
+
+    /// Advice to weave
+    pub aspects: Vec<AspectEntry>,
+
+    /// Field get/set joinpoints to report
+    pub fields: Vec<String>,
+
+    /// Commands run as analysis passes (see [`CommandPass`])
+    pub passes: Vec<String>,
+
+    /// Print the analysis as it runs
+    pub verbose: Option<bool>,
+
+    /// Dry run: print the weaving plan instead of weaving
+    pub plan: Option<bool>,
+
+    /// Fail when a pointcut matches no function
+    pub deny_unmatched: Option<bool>,
+
+    /// Fail when a function matched by the pointcut of an aspect cannot be
+    /// woven, such as an async function
+    pub deny_unwoven: Option<bool>,
+
+    /// Also analyze closures
+    pub closures: Option<bool>,
+
+    /// Where and how results are written
+    pub output: OutputSection,
+
+    /// Analysis cache
+    pub cache: CacheSection,
+
+    /// Crates to analyze and weave
+    pub crates: CratesSection,
+
+    /// In a profile, the aspects and pointcuts of the file that stay
+    /// enabled; all of them when absent
+    pub enabled: Option<Vec<String>>,
+
+    /// Named settings applied over the others (see [`ConfigFile::profile`])
+    #[serde(alias = "profile")]
+    pub profiles: BTreeMap<String, ConfigFile>,
+}
+
+/// An `[[aspects]]` entry: a hook called for the functions matched by a
+/// pointcut.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AspectEntry {
+    /// Name of the aspect, for messages
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Pointcut expression selecting the advised functions
+    pub pointcut: String,
+
+    /// When the hook runs: "before" or "after"
+    pub advice: String,
+
+    /// Path of the hook function (e.g., "crate::trace::enter")
+    pub hook: String,
+
+    /// Priority (higher = runs first)
+    #[serde(default)]
+    pub priority: i32,
+}
+
+impl AspectEntry {
+    /// Name of the aspect, its hook when it has none.
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.hook)
+    }
+}
+
+/// The `[output]` table.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputSection {
+    /// Format of the output file: "text", "json", "sarif" or "dot"
+    pub format: Option<String>,
+
+    /// File to write the results to
+    pub file: Option<PathBuf>,
+
+    /// Directory to write the woven source to
+    pub emit_source: Option<PathBuf>,
+}
+
+/// The `[cache]` table.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheSection {
+    /// Directory of the analysis cache
+    pub dir: Option<PathBuf>,
+
+    /// Set to false to disable the cache
+    pub enabled: Option<bool>,
+}
+
+/// The `[crates]` table.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CratesSection {
+    /// Only these crates, all of them when empty
+    pub only: Vec<String>,
+
+    /// Never these crates
+    pub exclude: Vec<String>,
+
+    /// Set to false to skip test harnesses
+    pub tests: Option<bool>,
+
+    /// Set to false to skip benchmarks
+    pub benches: Option<bool>,
+}
+
+impl ConfigFile {
+    /// Parse and check the contents of a configuration file.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let config: Self = toml::from_str(contents).map_err(|e| e.to_string())?;
+
+        config.check()?;
+        if config.enabled.is_some() {
+            return Err("`enabled` is only allowed in profiles".to_string());
+        }
+        for (name, profile) in &config.profiles {
+            if !profile.profiles.is_empty() {
+                return Err(format!("profile '{}': profiles cannot be nested", name));
+            }
+            profile
+                .check()
+                .map_err(|e| format!("profile '{}': {}", name, e))?;
+            for enabled in profile.enabled.iter().flatten() {
+                if !config
+                    .aspects
+                    .iter()
+                    .any(|aspect| aspect.label() == enabled)
+                    && !config.pointcuts.contains(enabled)
+                {
+                    return Err(format!(
+                        "profile '{}': no aspect or pointcut '{}' to enable",
+                        name, enabled
+                    ));
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Every pointcut of `contents`, profiles included, with its key
+    /// (e.g. `aspects[1].pointcut`), without checking them.
+    pub fn unchecked_pointcuts(contents: &str) -> Result<Vec<(String, String)>, String> {
+        let config: Self = toml::from_str(contents).map_err(|e| e.to_string())?;
+        let mut pointcuts = config.pointcut_keys("");
+        for (name, profile) in &config.profiles {
+            pointcuts.extend(profile.pointcut_keys(&format!("profiles.{}.", name)));
+        }
+        Ok(pointcuts)
+    }
+
+    fn pointcut_keys(&self, prefix: &str) -> Vec<(String, String)> {
+        let pointcuts = self
+            .pointcuts
+            .iter()
+            .enumerate()
+            .map(|(i, pointcut)| (format!("{}pointcuts[{}]", prefix, i), pointcut.clone()));
+        let aspects = self.aspects.iter().enumerate().map(|(i, aspect)| {
+            (
+                format!("{}aspects[{}].pointcut", prefix, i),
+                aspect.pointcut.clone(),
+            )
+        });
+        pointcuts.chain(aspects).collect()
+    }
+
+    fn check(&self) -> Result<(), String> {
+        for pointcut in &self.pointcuts {
+            parse_pointcut(pointcut).map_err(|e| format!("pointcut '{}': {}", pointcut, e))?;
+        }
+        self.advice_hooks()?;
+        self.field_patterns()?;
+        self.command_passes()?;
+        Ok(())
+    }
+
+    /// Read the configuration file at `path`, resolving its relative paths
+    /// against its directory.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let mut config =
+            Self::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        config.resolve_paths(base);
+        for profile in config.profiles.values_mut() {
+            profile.resolve_paths(base);
+        }
+
+        Ok(config)
+    }
+
+    fn resolve_paths(&mut self, base: &Path) {
+        for path in [
+            &mut self.output.file,
+            &mut self.output.emit_source,
+            &mut self.cache.dir,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if path.is_relative() {
+                *path = base.join(&*path);
+            }
+        }
+    }
+
+    /// The settings of the profile `name` applied over the others.
+    pub fn profile(&self, name: &str) -> Result<Self, String> {
+        let profile = self.profiles.get(name).ok_or_else(|| {
+            let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            format!(
+                "no profile '{}' (profiles: {})",
+                name,
+                if names.is_empty() {
+                    "none".to_string()
+                } else {
+                    names.join(", ")
+                }
+            )
+        })?;
+
+        let mut config = Self {
+            profiles: BTreeMap::new(),
+            ..self.clone()
+        };
+        if let Some(enabled) = &profile.enabled {
+            config
+                .aspects
+                .retain(|aspect| enabled.iter().any(|name| name == aspect.label()));
+            config
+                .pointcuts
+                .retain(|pointcut| enabled.contains(pointcut));
+        }
+        config.pointcuts.extend(profile.pointcuts.iter().cloned());
+        config.aspects.extend(profile.aspects.iter().cloned());
+        config.fields.extend(profile.fields.iter().cloned());
+        config.passes.extend(profile.passes.iter().cloned());
+
+        config.verbose = profile.verbose.or(config.verbose);
+        config.plan = profile.plan.or(config.plan);
+        config.deny_unmatched = profile.deny_unmatched.or(config.deny_unmatched);
+        config.deny_unwoven = profile.deny_unwoven.or(config.deny_unwoven);
+        config.closures = profile.closures.or(config.closures);
+
+        let output = &profile.output;
+        config.output.format = output.format.clone().or(config.output.format);
+        config.output.file = output.file.clone().or(config.output.file);
+        config.output.emit_source = output.emit_source.clone().or(config.output.emit_source);
+
+        config.cache.dir = profile.cache.dir.clone().or(config.cache.dir);
+        config.cache.enabled = profile.cache.enabled.or(config.cache.enabled);
+
+        let crates = &profile.crates;
+        config.crates.only.extend(crates.only.iter().cloned());
+        config.crates.exclude.extend(crates.exclude.iter().cloned());
+        config.crates.tests = crates.tests.or(config.crates.tests);
+        config.crates.benches = crates.benches.or(config.crates.benches);
+
+        Ok(config)
+    }
+
+    /// The `aspect.toml` in `dir`, if there is one.
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        Some(dir.join(CONFIG_FILE)).filter(|path| path.is_file())
+    }
+
+    /// The advice of the `[[aspects]]` entries, highest priority first.
+    pub fn advice_hooks(&self) -> Result<Vec<AdviceHook>, String> {
+        let mut aspects: Vec<&AspectEntry> = self.aspects.iter().collect();
+        aspects.sort_by_key(|aspect| std::cmp::Reverse(aspect.priority));
+
+        aspects
+            .into_iter()
+            .map(|aspect| {
+                let advice_type: AdviceType = aspect.advice.parse()?;
+                AdviceHook::new(advice_type, &aspect.pointcut, &aspect.hook)
+                    .map_err(|e| format!("aspect '{}': {}", aspect.label(), e))
+            })
+            .collect()
+    }
+
+    /// The field joinpoint patterns.
+    pub fn field_patterns(&self) -> Result<Vec<FieldPattern>, String> {
+        self.fields
+            .iter()
+            .map(|field| {
+                FieldPattern::parse(field).map_err(|e| format!("field '{}': {}", field, e))
+            })
+            .collect()
+    }
+
+    /// Apply the `[crates]` table to `filter`.
+    pub fn apply_crate_filter(&self, filter: &mut CrateFilter) {
+        filter.only.extend(self.crates.only.iter().cloned());
+        filter.exclude.extend(self.crates.exclude.iter().cloned());
+        filter.tests = self.crates.tests.unwrap_or(filter.tests);
+        filter.benches = self.crates.benches.unwrap_or(filter.benches);
+    }
+
+    /// The analysis passes to run.
+    pub fn command_passes(&self) -> Result<Vec<CommandPass>, String> {
+        self.passes
+            .iter()
+            .map(|pass| CommandPass::parse(pass).map_err(|e| format!("pass '{}': {}", pass, e)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+pointcuts = ["execution(pub fn api::*(..))"]
+fields = ["set(Account.balance)"]
+deny_unmatched = true
+passes = ["check-policy --strict"]
+
+[[aspects]]
+pointcut = "execution(pub fn *(..))"
+advice = "after"
+hook = "crate::trace::exit"
+
+[[aspects]]
+name = "tracing"
+pointcut = "execution(pub fn *(..))"
+advice = "before"
+hook = "crate::trace::enter"
+priority = 10
+
+[output]
+format = "json"
+file = "target/analysis.json"
+
+[cache]
+enabled = false
+
+[crates]
+exclude = ["shop-api"]
+benches = false
+
+[profiles.ci]
+pointcuts = ["within(crate::db)"]
+deny_unmatched = false
+deny_unwoven = true
+output = { format = "sarif", file = "target/analysis.sarif" }
+"#;
+
+    #[test]
+    fn test_parse_config() {
+        let config = ConfigFile::parse(CONFIG).unwrap();
+        assert_eq!(config.pointcuts, ["execution(pub fn api::*(..))"]);
+        assert_eq!(config.deny_unmatched, Some(true));
+        assert_eq!(config.verbose, None);
+        assert_eq!(config.output.format.as_deref(), Some("json"));
+        assert_eq!(config.cache.enabled, Some(false));
+
+        // Highest priority first
+        let hooks = config.advice_hooks().unwrap();
+        assert_eq!(hooks[0].path, "crate::trace::enter");
+        assert_eq!(hooks[0].advice_type, AdviceType::Before);
+        assert_eq!(hooks[1].path, "crate::trace::exit");
+
+        assert_eq!(config.field_patterns().unwrap()[0].field_pattern, "balance");
+        assert_eq!(config.command_passes().unwrap()[0].args, ["--strict"]);
+
+        let mut filter = CrateFilter::default();
+        config.apply_crate_filter(&mut filter);
+        assert_eq!(filter.exclude, ["shop-api"]);
+        assert!(filter.tests);
+        assert!(!filter.benches);
+        assert_eq!(ConfigFile::parse("").unwrap(), ConfigFile::default());
+    }
+
+    #[test]
+    fn test_parse_config_errors() {
+        assert!(ConfigFile::parse("pointcuts = [\"bogus(x)\"]").is_err());
+        assert!(ConfigFile::parse("fields = [\"Account\"]").is_err());
+        assert!(ConfigFile::parse("verbsoe = true").is_err());
+        assert!(ConfigFile::parse("passes = [\"\"]").is_err());
+
+        let err = ConfigFile::parse(
+            "[[aspects]]\nname = \"timing\"\npointcut = \"execution(fn *(..))\"\n\
+             advice = \"around\"\nhook = \"crate::t\"",
+        )
+        .unwrap_err();
+        assert!(err.contains("aspect 'timing'"), "{}", err);
+    }
+
+    #[test]
+    fn test_unchecked_pointcuts() {
+        let pointcuts =
+            ConfigFile::unchecked_pointcuts("pointcuts = [\"bogus(x)\"]\n\n[profiles.ci]\n")
+                .unwrap();
+        assert_eq!(
+            pointcuts,
+            [("pointcuts[0]".to_string(), "bogus(x)".to_string())]
+        );
+
+        let keys: Vec<String> = ConfigFile::unchecked_pointcuts(CONFIG)
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "pointcuts[0]",
+                "aspects[0].pointcut",
+                "aspects[1].pointcut",
+                "profiles.ci.pointcuts[0]"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_profile() {
+        let config = ConfigFile::parse(CONFIG).unwrap();
+        let ci = config.profile("ci").unwrap();
+        assert_eq!(
+            ci.pointcuts,
+            ["execution(pub fn api::*(..))", "within(crate::db)"]
+        );
+        assert_eq!(ci.deny_unmatched, Some(false));
+        assert_eq!(ci.deny_unwoven, Some(true));
+        assert_eq!(config.deny_unwoven, None);
+        assert_eq!(ci.output.format.as_deref(), Some("sarif"));
+        assert_eq!(ci.cache.enabled, Some(false));
+        assert_eq!(ci.aspects.len(), 2);
+        assert!(ci.profiles.is_empty());
+
+        let err = config.profile("release").unwrap_err();
+        assert_eq!(err, "no profile 'release' (profiles: ci)");
+        assert!(ConfigFile::parse("[profiles.a.profiles.b]").is_err());
+        assert!(ConfigFile::parse("[profiles.a]\npointcuts = [\"bogus(x)\"]").is_err());
+    }
+
+    #[test]
+    fn test_profile_enabled() {
+        // Written like in Cargo.toml, not mixed with `[profiles.*]`
+        let contents = format!(
+            "{}\n[profile.dev]\nverbose = true\n\n\
+             [profile.release]\nenabled = [\"crate::trace::exit\"]\n\n\
+             [[profile.release.aspects]]\nname = \"metrics\"\n\
+             pointcut = \"within(crate::api)\"\nadvice = \"after\"\nhook = \"crate::m\"\n",
+            CONFIG.replace("[profiles.ci]", "[profile.ci]")
+        );
+        let config = ConfigFile::parse(&contents).unwrap();
+        assert_eq!(
+            config.profiles.keys().collect::<Vec<_>>(),
+            ["ci", "dev", "release"]
+        );
+        assert_eq!(config.profile("dev").unwrap().aspects.len(), 2);
+
+        // The unnamed aspect, by its hook, then the aspect of the profile
+        let release = config.profile("release").unwrap();
+        let labels: Vec<&str> = release.aspects.iter().map(AspectEntry::label).collect();
+        assert_eq!(labels, ["crate::trace::exit", "metrics"]);
+        assert!(release.pointcuts.is_empty());
+        assert_eq!(release.enabled, None);
+
+        let err = ConfigFile::parse(
+            "[[aspects]]\nname = \"tracing\"\npointcut = \"name(get)\"\n\
+             advice = \"before\"\nhook = \"crate::t\"\n\n[profile.release]\nenabled = [\"tracng\"]",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            "profile 'release': no aspect or pointcut 'tracng' to enable"
+        );
+        assert!(ConfigFile::parse("enabled = []").is_err());
+    }
+
+    #[test]
+    fn test_load_config() {
+        let dir = std::env::temp_dir().join(format!("aspect-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(CONFIG_FILE), CONFIG).unwrap();
+
+        let path = ConfigFile::find(&dir).unwrap();
+        let config = ConfigFile::load(&path).unwrap();
+        assert_eq!(config.output.file, Some(dir.join("target/analysis.json")));
+        assert_eq!(
+            config.profiles["ci"].output.file,
+            Some(dir.join("target/analysis.sarif"))
+        );
+        assert_eq!(config.cache.dir, None);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(ConfigFile::find(&dir), None);
+    }
+}
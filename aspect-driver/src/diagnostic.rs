@@ -0,0 +1,348 @@
+//! Diagnostics of the compiler driver, and its exit codes.
+//!
+//! With `--aspect-diagnostics json`, aspect-rustc-driver writes its
+//! diagnostics to standard error as JSON, one [`Diagnostic`] per line:
+//!
+//! ```json
+//! {"code":"AR002","severity":"error","span":null,"message":"pointcut `name(fetch_usr)` matches no function","help":[]}
+//! ```
+//!
+//! Its exit code ([`DriverExit`]) tells why it failed, so that
+//! cargo-aspect and CI can tell a compilation failure from a weaving
+//! failure or an unmatched pointcut without parsing messages.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::lint::SyntaxError;
+use crate::pass::{Finding, Severity};
+use crate::types::{FunctionMetadata, SourceLocation};
+use crate::unmatched::UnmatchedPointcut;
+
+/// Function matched by a pointcut (a note, in SARIF logs only)
+pub const POINTCUT_MATCH: &str = "AR001";
+/// Pointcut matching no function
+pub const UNMATCHED_POINTCUT: &str = "AR002";
+/// Matched function that cannot be woven as expected
+pub const WEAVING_WARNING: &str = "AR003";
+/// Finding of a custom analysis pass
+pub const ANALYSIS_PASS: &str = "AR004";
+/// Advice that cannot be woven, such as a hook that does not exist
+pub const WEAVING_ERROR: &str = "AR005";
+/// Pointcut that does not parse
+pub const POINTCUT_SYNTAX: &str = "AR006";
+/// Pointcut that parses but is likely a mistake, such as a redundant clause
+pub const POINTCUT_LINT: &str = "AR007";
+
+/// Exit codes of aspect-rustc-driver.
+///
+/// Failing to weave aborts the compilation, and exits with
+/// `WeavingFailed` rather than `CompilationFailed`. Otherwise, when several
+/// failures happen, the first one gives the exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriverExit {
+    Success = 0,
+    /// rustc reported errors
+    CompilationFailed = 1,
+    /// Invalid `--aspect-*` flags or `aspect.toml`
+    Usage = 2,
+    /// Advice could not be woven
+    WeavingFailed = 3,
+    /// A pointcut matched no function, with `--aspect-deny-unmatched`
+    UnmatchedPointcut = 4,
+    /// An analysis pass reported an error
+    AnalysisError = 5,
+    /// An output file could not be written
+    OutputFailed = 6,
+    /// A matched function could not be woven, with `--aspect-deny-unwoven`
+    Unwoven = 7,
+}
+
+impl DriverExit {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// The exit of exit code `code`, `None` for codes the driver does not
+    /// use (e.g., 101 when rustc crashes).
+    pub fn from_code(code: i32) -> Option<Self> {
+        [
+            DriverExit::Success,
+            DriverExit::CompilationFailed,
+            DriverExit::Usage,
+            DriverExit::WeavingFailed,
+            DriverExit::UnmatchedPointcut,
+            DriverExit::AnalysisError,
+            DriverExit::OutputFailed,
+            DriverExit::Unwoven,
+        ]
+        .into_iter()
+        .find(|exit| exit.code() == code)
+    }
+}
+
+impl fmt::Display for DriverExit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DriverExit::Success => "success",
+            DriverExit::CompilationFailed => "compilation failed",
+            DriverExit::Usage => "invalid aspect configuration",
+            DriverExit::WeavingFailed => "weaving failed",
+            DriverExit::UnmatchedPointcut => "unmatched pointcut",
+            DriverExit::AnalysisError => "analysis pass error",
+            DriverExit::OutputFailed => "failed to write output",
+            DriverExit::Unwoven => "matched function not woven",
+        })
+    }
+}
+
+/// How the driver writes its diagnostics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiagnosticFormat {
+    /// Messages for people
+    #[default]
+    Human,
+    /// One JSON [`Diagnostic`] per line
+    Json,
+}
+
+impl DiagnosticFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "human" => Some(DiagnosticFormat::Human),
+            "json" => Some(DiagnosticFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// A diagnostic of the driver.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// Stable code, e.g. [`UNMATCHED_POINTCUT`]
+    pub code: String,
+
+    pub severity: Severity,
+
+    /// Location it is about, if any
+    pub span: Option<SourceLocation>,
+
+    pub message: String,
+
+    /// Hints on fixing it
+    #[serde(default)]
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(code: &str, severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            code: code.to_string(),
+            severity,
+            span: None,
+            message: message.into(),
+            help: Vec::new(),
+        }
+    }
+
+    /// A pointcut matching no function: an error when `deny` is set.
+    pub fn unmatched(pointcut: &UnmatchedPointcut, deny: bool) -> Self {
+        let severity = if deny {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+        let mut diagnostic = Self::new(
+            UNMATCHED_POINTCUT,
+            severity,
+            format!("pointcut `{}` matches no function", pointcut.pointcut),
+        );
+        for near_miss in &pointcut.near_misses {
+            diagnostic
+                .help
+                .extend(near_miss.suggestions.iter().map(|suggestion| {
+                    format!(
+                        "`{}` matches nothing; did you mean `{}`?",
+                        near_miss.clause, suggestion
+                    )
+                }));
+        }
+        diagnostic
+    }
+
+    /// A function matched by the pointcut of advice that cannot be woven,
+    /// for `reason`: an error when `deny` is set.
+    pub fn unwoven(function: &FunctionMetadata, reason: &str, deny: bool) -> Self {
+        let severity = if deny {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+        let mut diagnostic = Self::new(
+            WEAVING_WARNING,
+            severity,
+            format!("`{}` is matched but not woven: {}", function.name, reason),
+        );
+        diagnostic.span = Some(function.location.clone()).filter(|location| location.line > 0);
+        diagnostic
+    }
+
+    /// A pointcut that does not parse; `location` is where the pointcut
+    /// starts, and the span is narrowed to the error.
+    pub fn pointcut_syntax(
+        pointcut: &str,
+        error: &SyntaxError,
+        location: Option<&SourceLocation>,
+    ) -> Self {
+        let mut diagnostic = Self::new(
+            POINTCUT_SYNTAX,
+            Severity::Error,
+            format!("invalid pointcut `{}`: {}", pointcut, error.message),
+        );
+        diagnostic.span = location.map(|location| {
+            let column = location.column + pointcut[..error.offset].chars().count();
+            SourceLocation {
+                column,
+                end_line: location.line,
+                end_column: column + error.len,
+                ..location.clone()
+            }
+        });
+        diagnostic.help.extend(error.help.clone());
+        diagnostic
+    }
+
+    /// A lint warning `warning` on a pointcut starting at `location`.
+    pub fn pointcut_lint(pointcut: &str, warning: &str, location: Option<&SourceLocation>) -> Self {
+        let mut diagnostic = Self::new(
+            POINTCUT_LINT,
+            Severity::Warning,
+            format!("in pointcut `{}`: {}", pointcut, warning),
+        );
+        diagnostic.span = location.cloned();
+        diagnostic
+    }
+
+    /// A finding of an analysis pass, on the function it is about.
+    pub fn finding(finding: &Finding, functions: &[FunctionMetadata]) -> Self {
+        let message = format!("[{}] {}", finding.pass, finding.message);
+        let mut diagnostic = Self::new(ANALYSIS_PASS, finding.severity, message);
+        diagnostic.span = finding
+            .function
+            .as_deref()
+            .and_then(|name| functions.iter().find(|function| function.name == name))
+            .map(|function| function.location.clone())
+            .filter(|location| location.line > 0);
+        diagnostic
+    }
+
+    /// The diagnostic as one line of JSON.
+    pub fn to_json_line(&self) -> String {
+        serde_json::to_string(self).expect("diagnostics are always serializable")
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)?;
+        if let Some(span) = &self.span {
+            write!(f, "\n  --> {}:{}:{}", span.file, span.line, span.column)?;
+        }
+        for help in &self.help {
+            write!(f, "\n  help: {}", help)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unmatched::NearMiss;
+
+    #[test]
+    fn test_driver_exit() {
+        assert_eq!(DriverExit::UnmatchedPointcut.code(), 4);
+        assert_eq!(DriverExit::from_code(3), Some(DriverExit::WeavingFailed));
+        assert_eq!(DriverExit::from_code(7), Some(DriverExit::Unwoven));
+        assert_eq!(DriverExit::from_code(101), None);
+        assert_eq!(DriverExit::WeavingFailed.to_string(), "weaving failed");
+        assert_eq!(
+            DiagnosticFormat::parse("json"),
+            Some(DiagnosticFormat::Json)
+        );
+        assert_eq!(DiagnosticFormat::parse("short"), None);
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let unmatched = UnmatchedPointcut {
+            pointcut: "name(fetch_usr)".to_string(),
+            near_misses: vec![NearMiss {
+                clause: "name(fetch_usr)".to_string(),
+                suggestions: vec!["name(fetch_user)".to_string()],
+            }],
+        };
+        let diagnostic = Diagnostic::unmatched(&unmatched, true);
+        assert_eq!(
+            diagnostic.to_string(),
+            "error[AR002]: pointcut `name(fetch_usr)` matches no function\n  \
+             help: `name(fetch_usr)` matches nothing; did you mean `name(fetch_user)`?"
+        );
+
+        let line = diagnostic.to_json_line();
+        assert!(!line.contains('\n'));
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["code"], "AR002");
+        assert_eq!(json["severity"], "error");
+        assert_eq!(json["span"], serde_json::Value::Null);
+        let parsed: Diagnostic = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed, diagnostic);
+
+        let finding = Finding {
+            pass: "policy".to_string(),
+            ..Finding::new(Severity::Warning, Some("api::get"), "not traced")
+        };
+        let diagnostic = Diagnostic::finding(&finding, &[]);
+        assert_eq!(
+            diagnostic.to_string(),
+            "warning[AR004]: [policy] not traced"
+        );
+        assert_eq!(diagnostic.span, None);
+
+        let functions =
+            crate::syntax::analyze_source("\npub async fn get() {}", "src/lib.rs", "crate")
+                .unwrap();
+        let diagnostic = Diagnostic::unwoven(&functions[0], crate::plan::ASYNC_NOT_WOVEN, true);
+        assert_eq!(
+            diagnostic.to_string(),
+            "error[AR003]: `get` is matched but not woven: async functions are not woven by \
+             the compiler driver; use #[aspect] instead\n  --> src/lib.rs:2:1"
+        );
+    }
+
+    #[test]
+    fn test_pointcut_diagnostics() {
+        let pointcut = "within(crate::api) && nme(get)";
+        let error = crate::lint::check_syntax(pointcut).unwrap_err();
+        let location = SourceLocation {
+            file: "src/lib.rs".to_string(),
+            line: 4,
+            column: 22,
+            end_line: 4,
+            end_column: 52,
+        };
+        let diagnostic = Diagnostic::pointcut_syntax(pointcut, &error, Some(&location));
+        assert_eq!(
+            diagnostic.to_string(),
+            "error[AR006]: invalid pointcut `within(crate::api) && nme(get)`: \
+             unknown pointcut `nme`\n  --> src/lib.rs:4:44\n  help: did you mean `name`?"
+        );
+        assert_eq!(diagnostic.span.unwrap().end_column, 47);
+
+        let diagnostic = Diagnostic::pointcut_lint("name(a) && name(a)", "twice", None);
+        assert_eq!(diagnostic.code, POINTCUT_LINT);
+        assert_eq!(diagnostic.severity, Severity::Warning);
+    }
+}
@@ -0,0 +1,550 @@
+//! Woven source, for inspection ("expand mode").
+//!
+//! The compiler driver weaves advice into MIR, which is hard to read. This
+//! module writes the source of a crate as if the hook calls had been
+//! written by hand, so the result of weaving can be read, diffed and even
+//! committed:
+//!
+//! ```text
+//! pub fn add(a: i32, b: i32) -> i32 { crate::trace::enter("api::add"); let __aspect_result = (move || -> i32 {
+//!     a + b
+//! })(); crate::trace::exit("api::add"); __aspect_result }
+//! ```
+//!
+//! Before hooks are called at the start of the body. When there are after
+//! hooks, the body becomes a closure so they also run on early returns and
+//! `?`, as in MIR. Calls are spliced into the lines of the opening and
+//! closing braces, so line numbers are preserved.
+//!
+//! Woven functions are found by where their body ends, so functions
+//! generated by macros are not shown.
+//!
+//! [`function_diff`] shows what weaving changes as a unified diff of the
+//! lines of the woven functions only.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use syn::spanned::Spanned;
+
+use crate::plan::{PlannedAdvice, WeavingPlan};
+use crate::r#match::AdviceType;
+use crate::source::{byte_offset, line_offsets};
+use crate::types::FunctionMetadata;
+
+/// Name of the local holding the result of a function with after advice.
+const RESULT_LOCAL: &str = "__aspect_result";
+
+/// Result of expanding one source file.
+#[derive(Debug, Clone)]
+pub struct ExpandedSource {
+    /// The source with hook calls
+    pub source: String,
+
+    /// Names of the functions expanded
+    pub expanded: Vec<String>,
+}
+
+/// Result of writing the woven sources of a crate.
+#[derive(Debug, Clone, Default)]
+pub struct EmitReport {
+    /// Source files written
+    pub files: Vec<PathBuf>,
+
+    /// Number of functions shown with their advice
+    pub expanded: usize,
+
+    /// Woven functions that could not be found in the source
+    pub not_found: Vec<String>,
+}
+
+/// Add the hook calls of `woven` functions to `source`.
+///
+/// `file` is only used in errors. Functions not found in `source` are left
+/// out of [`ExpandedSource::expanded`].
+pub fn expand_source(
+    source: &str,
+    file: &str,
+    woven: &[(&FunctionMetadata, &[PlannedAdvice])],
+) -> Result<ExpandedSource, String> {
+    let ast = syn::parse_file(source).map_err(|e| format!("failed to parse {}: {}", file, e))?;
+    let mut bodies = Vec::new();
+    collect_bodies(&ast.items, &mut bodies);
+
+    let lines = line_offsets(source);
+    let offset = |at| byte_offset(source, &lines, at);
+    let mut insertions = Vec::new();
+    let mut expanded = Vec::new();
+
+    for (function, advice) in woven {
+        let location = &function.location;
+        let Some((sig, block)) = bodies.iter().find(|(_, block)| {
+            let end = block.span().end();
+            end.line == location.end_line && end.column + 1 == location.end_column
+        }) else {
+            continue;
+        };
+
+        let calls = |advice_type| -> String {
+            advice
+                .iter()
+                .filter(|advice| advice.advice_type == advice_type)
+                .map(|advice| format!(" {}({:?});", advice.hook, function.name))
+                .collect()
+        };
+        let (before, after) = (calls(AdviceType::Before), calls(AdviceType::After));
+
+        let open = offset(block.brace_token.span.open().end());
+        if after.is_empty() {
+            insertions.push((open, before));
+        } else {
+            // `impl Trait` cannot be written as the closure's return type
+            let return_type = match &sig.output {
+                syn::ReturnType::Type(_, ty) => {
+                    let ty = &source[offset(ty.span().start())..offset(ty.span().end())];
+                    let is_impl = ty
+                        .split(|c: char| !c.is_alphanumeric() && c != '_')
+                        .any(|word| word == "impl");
+                    if is_impl {
+                        String::new()
+                    } else {
+                        format!(" -> {}", ty)
+                    }
+                }
+                syn::ReturnType::Default => String::new(),
+            };
+            insertions.push((
+                open,
+                format!(
+                    "{} let {} = (move ||{} {{",
+                    before, RESULT_LOCAL, return_type
+                ),
+            ));
+            insertions.push((
+                offset(block.brace_token.span.close().start()),
+                format!("}})();{} {} ", after, RESULT_LOCAL),
+            ));
+        }
+        expanded.push(function.name.clone());
+    }
+
+    // Splice from the end so earlier offsets stay valid
+    let mut source = source.to_string();
+    insertions.sort_by_key(|(offset, _)| *offset);
+    for (offset, text) in insertions.into_iter().rev() {
+        source.insert_str(offset, &text);
+    }
+
+    Ok(ExpandedSource { source, expanded })
+}
+
+/// Write the source `files` of a crate to `out_dir`, with the hook calls
+/// planned by `plan` added to its `functions`.
+///
+/// Files are written at their path relative to the current directory, or
+/// to the root for files outside it; files without woven functions are
+/// copied as they are.
+pub fn emit_sources(
+    out_dir: &Path,
+    files: &[String],
+    functions: &[FunctionMetadata],
+    plan: &WeavingPlan,
+) -> Result<EmitReport, String> {
+    let woven = plan.woven_functions();
+    let mut report = EmitReport::default();
+    let mut expanded = BTreeSet::new();
+
+    for file in files {
+        let source = fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?;
+        let in_file: Vec<_> = functions
+            .iter()
+            .filter(|function| function.location.file == *file)
+            .filter_map(|function| {
+                woven
+                    .get(function.name.as_str())
+                    .map(|advice| (function, *advice))
+            })
+            .collect();
+
+        let contents = if in_file.is_empty() {
+            source
+        } else {
+            let result = expand_source(&source, file, &in_file)?;
+            expanded.extend(result.expanded);
+            result.source
+        };
+
+        let path = out_dir.join(relative_path(Path::new(file)));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+        }
+        fs::write(&path, contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+        report.files.push(path);
+    }
+
+    report.expanded = expanded.len();
+    report.not_found = woven
+        .keys()
+        .filter(|name| !expanded.contains(**name))
+        .map(|name| name.to_string())
+        .collect();
+    Ok(report)
+}
+
+/// A unified diff of `original` and its `expanded` source, restricted to
+/// the lines of the `functions` expanded: one hunk per function, or per
+/// group of nested functions, named after the outermost. Both files are
+/// named `path`, under `a/` and `b/` like in git; the diff is empty when
+/// nothing changed.
+///
+/// Expansion keeps line numbers, so lines are compared one to one.
+pub fn function_diff(
+    original: &str,
+    expanded: &str,
+    path: &str,
+    functions: &[&FunctionMetadata],
+) -> String {
+    let old: Vec<&str> = original.lines().collect();
+    let new: Vec<&str> = expanded.lines().collect();
+
+    // Line ranges of the functions, nested ones merged into the outermost
+    let mut ranges: Vec<(usize, usize, &str)> = functions
+        .iter()
+        .map(|function| {
+            let location = &function.location;
+            (location.line, location.end_line, function.name.as_str())
+        })
+        .collect();
+    ranges.sort();
+    let mut merged: Vec<(usize, usize, &str)> = Vec::new();
+    for (start, end, name) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end, name)),
+        }
+    }
+
+    let mut out = String::new();
+    for (start, end, name) in merged {
+        let end = end.min(old.len()).min(new.len());
+        if start == 0 || start > end || old[start - 1..end] == new[start - 1..end] {
+            continue;
+        }
+        if out.is_empty() {
+            out.push_str(&format!("--- a/{}\n+++ b/{}\n", path, path));
+        }
+        let len = end - start + 1;
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@ {}\n",
+            start, len, start, len, name
+        ));
+
+        // Changed lines in runs: the old ones, then the new ones
+        let mut line = start - 1;
+        while line < end {
+            if old[line] == new[line] {
+                out.push_str(&format!(" {}\n", old[line]));
+                line += 1;
+                continue;
+            }
+            let run = line;
+            while line < end && old[line] != new[line] {
+                line += 1;
+            }
+            for removed in &old[run..line] {
+                out.push_str(&format!("-{}\n", removed));
+            }
+            for added in &new[run..line] {
+                out.push_str(&format!("+{}\n", added));
+            }
+        }
+    }
+    out
+}
+
+/// Path of `file` under the output directory.
+fn relative_path(file: &Path) -> PathBuf {
+    let file = std::env::current_dir()
+        .ok()
+        .and_then(|dir| file.strip_prefix(dir).ok())
+        .unwrap_or(file);
+    file.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+/// The signature and body of every function with a body in `items`,
+/// including methods, default trait methods and nested functions.
+fn collect_bodies<'a>(
+    items: &'a [syn::Item],
+    bodies: &mut Vec<(&'a syn::Signature, &'a syn::Block)>,
+) {
+    for item in items {
+        match item {
+            syn::Item::Fn(func) => push_body(&func.sig, &func.block, bodies),
+            syn::Item::Mod(module) => {
+                if let Some((_, items)) = &module.content {
+                    collect_bodies(items, bodies);
+                }
+            }
+            syn::Item::Impl(block) => {
+                for item in &block.items {
+                    if let syn::ImplItem::Fn(func) = item {
+                        push_body(&func.sig, &func.block, bodies);
+                    }
+                }
+            }
+            syn::Item::Trait(block) => {
+                for item in &block.items {
+                    if let syn::TraitItem::Fn(func) = item {
+                        if let Some(body) = &func.default {
+                            push_body(&func.sig, body, bodies);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn push_body<'a>(
+    sig: &'a syn::Signature,
+    block: &'a syn::Block,
+    bodies: &mut Vec<(&'a syn::Signature, &'a syn::Block)>,
+) {
+    bodies.push((sig, block));
+    for stmt in &block.stmts {
+        if let syn::Stmt::Item(item) = stmt {
+            collect_bodies(std::slice::from_ref(item), bodies);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::r#match::AdviceHook;
+    use crate::types::{SourceLocation, Visibility};
+
+    const SOURCE: &str = "\
+pub fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+pub struct Parser;
+
+impl Parser {
+    pub fn parse(&self, input: &str) -> Result<u32, String> {
+        fn digits(input: &str) -> &str {
+            input.trim()
+        }
+        let n = digits(input).parse().map_err(|_| \"not a number\")?;
+        Ok(n)
+    }
+}
+
+pub fn numbers() -> impl Iterator<Item = u32> {
+    0..3
+}
+";
+
+    /// A function of `SOURCE` ending at `end_line`, column `end_column`.
+    fn function(name: &str, end_line: usize, end_column: usize) -> FunctionMetadata {
+        FunctionMetadata {
+            name: name.to_string(),
+            simple_name: name.rsplit("::").next().unwrap().to_string(),
+            module_path: "crate".to_string(),
+            visibility: Visibility::Public,
+            is_async: false,
+            is_closure: false,
+            generics: vec![],
+            return_type: "()".to_string(),
+            is_trait_method: false,
+            trait_name: None,
+            impl_type: None,
+            attributes: vec![],
+            location: SourceLocation {
+                file: "src/lib.rs".to_string(),
+                line: 1,
+                column: 1,
+                end_line,
+                end_column,
+            },
+        }
+    }
+
+    fn advice(advice_type: AdviceType, hook: &str) -> PlannedAdvice {
+        PlannedAdvice {
+            advice_type,
+            hook: hook.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_expand_before_advice() {
+        let add = function("add", 3, 2);
+        let before = [advice(AdviceType::Before, "crate::trace::enter")];
+
+        let result = expand_source(SOURCE, "src/lib.rs", &[(&add, &before)]).unwrap();
+        assert_eq!(result.expanded, ["add"]);
+        assert!(result
+            .source
+            .starts_with("pub fn add(a: i32, b: i32) -> i32 { crate::trace::enter(\"add\");\n"));
+        assert_eq!(result.source.lines().count(), SOURCE.lines().count());
+    }
+
+    #[test]
+    fn test_expand_after_advice() {
+        let parse = function("Parser::parse", 14, 6);
+        let digits = function("Parser::parse::digits", 11, 10);
+        let numbers = function("numbers", 19, 2);
+        let missing = function("generated", 40, 2);
+        let both = [
+            advice(AdviceType::Before, "crate::trace::enter"),
+            advice(AdviceType::After, "crate::trace::exit"),
+        ];
+        let after = [advice(AdviceType::After, "crate::trace::exit")];
+
+        let woven = [
+            (&parse, &both[..]),
+            (&digits, &after[..]),
+            (&numbers, &after[..]),
+            (&missing, &after[..]),
+        ];
+        let result = expand_source(SOURCE, "src/lib.rs", &woven).unwrap();
+        assert_eq!(
+            result.expanded,
+            ["Parser::parse", "Parser::parse::digits", "numbers"]
+        );
+
+        let lines: Vec<&str> = result.source.lines().collect();
+        assert_eq!(lines.len(), SOURCE.lines().count());
+        assert_eq!(
+            lines[7],
+            "    pub fn parse(&self, input: &str) -> Result<u32, String> { \
+             crate::trace::enter(\"Parser::parse\"); \
+             let __aspect_result = (move || -> Result<u32, String> {"
+        );
+        assert_eq!(
+            lines[8],
+            "        fn digits(input: &str) -> &str { \
+             let __aspect_result = (move || -> &str {"
+        );
+        assert_eq!(
+            lines[10],
+            "        })(); crate::trace::exit(\"Parser::parse::digits\"); __aspect_result }"
+        );
+        assert_eq!(
+            lines[13],
+            "    })(); crate::trace::exit(\"Parser::parse\"); __aspect_result }"
+        );
+        // `impl Trait` is left to inference
+        assert_eq!(
+            lines[16],
+            "pub fn numbers() -> impl Iterator<Item = u32> { let __aspect_result = (move || {"
+        );
+
+        assert!(syn::parse_file(&result.source).is_ok());
+    }
+
+    #[test]
+    fn test_emit_sources() {
+        let dir = std::env::temp_dir().join(format!("aspect-expand-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let src = dir.join("crate/src");
+        fs::create_dir_all(&src).unwrap();
+        let lib = src.join("lib.rs").to_string_lossy().to_string();
+        let util = src.join("util.rs").to_string_lossy().to_string();
+        fs::write(&lib, SOURCE).unwrap();
+        fs::write(&util, "pub fn helper() {}\n").unwrap();
+
+        let mut add = function("add", 3, 2);
+        add.location.file = lib.clone();
+        let mut generated = function("generated", 40, 2);
+        generated.location.file = lib.clone();
+        let functions = [add, generated];
+        let hooks = [
+            AdviceHook::parse(AdviceType::Before, "name(add)=crate::trace::enter").unwrap(),
+            AdviceHook::parse(AdviceType::Before, "name(generated)=crate::trace::enter").unwrap(),
+        ];
+        let plan = WeavingPlan::new(&functions, &[], &hooks);
+
+        let out_dir = dir.join("out");
+        let report =
+            emit_sources(&out_dir, &[lib.clone(), util.clone()], &functions, &plan).unwrap();
+        assert_eq!(report.files.len(), 2);
+        assert_eq!(report.expanded, 1);
+        assert_eq!(report.not_found, ["generated"]);
+
+        // Absolute paths outside the current directory keep all their
+        // components
+        let written = out_dir.join(relative_path(Path::new(&lib)));
+        assert!(written.ends_with("crate/src/lib.rs"));
+        assert!(fs::read_to_string(written)
+            .unwrap()
+            .contains("crate::trace::enter(\"add\");"));
+        let copied = out_dir.join(relative_path(Path::new(&util)));
+        assert_eq!(fs::read_to_string(copied).unwrap(), "pub fn helper() {}\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_function_diff() {
+        let mut parse = function("Parser::parse", 14, 6);
+        parse.location.line = 8;
+        let mut digits = function("Parser::parse::digits", 11, 10);
+        digits.location.line = 9;
+        let mut numbers = function("numbers", 19, 2);
+        numbers.location.line = 17;
+        let after = [advice(AdviceType::After, "crate::trace::exit")];
+        let woven = [(&parse, &after[..]), (&digits, &after[..])];
+        let expanded = expand_source(SOURCE, "src/lib.rs", &woven).unwrap().source;
+
+        let diff = function_diff(
+            SOURCE,
+            &expanded,
+            "src/lib.rs",
+            &[&digits, &parse, &numbers],
+        );
+        assert_eq!(
+            diff,
+            "--- a/src/lib.rs\n+++ b/src/lib.rs\n\
+             @@ -8,7 +8,7 @@ Parser::parse\n\
+             -    pub fn parse(&self, input: &str) -> Result<u32, String> {\n\
+             -        fn digits(input: &str) -> &str {\n\
+             +    pub fn parse(&self, input: &str) -> Result<u32, String> { \
+             let __aspect_result = (move || -> Result<u32, String> {\n\
+             +        fn digits(input: &str) -> &str { \
+             let __aspect_result = (move || -> &str {\n\
+             \x20            input.trim()\n\
+             -        }\n\
+             +        })(); crate::trace::exit(\"Parser::parse::digits\"); __aspect_result }\n\
+             \x20        let n = digits(input).parse().map_err(|_| \"not a number\")?;\n\
+             \x20        Ok(n)\n\
+             -    }\n\
+             +    })(); crate::trace::exit(\"Parser::parse\"); __aspect_result }\n"
+        );
+        assert_eq!(function_diff(SOURCE, SOURCE, "src/lib.rs", &[&parse]), "");
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path(Path::new("src/api.rs")),
+            Path::new("src/api.rs")
+        );
+        // Never outside the output directory
+        assert_eq!(
+            relative_path(Path::new("../shared/util.rs")),
+            Path::new("shared/util.rs")
+        );
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(
+            relative_path(&cwd.join("src/lib.rs")),
+            Path::new("src/lib.rs")
+        );
+    }
+}
@@ -67,6 +67,7 @@ pub fn extract_all_functions() -> Vec<FunctionMetadata> {
 ///     let span = tcx.def_span(def_id);
 ///     let source_map = tcx.sess.source_map();
 ///     let location = source_map.lookup_char_pos(span.lo());
+///     let end = source_map.lookup_char_pos(span.hi());
 ///
 ///     // Extract generics
 ///     let generics = tcx.generics_of(def_id);
@@ -81,13 +82,16 @@ pub fn extract_all_functions() -> Vec<FunctionMetadata> {
 ///         module_path,
 ///         visibility,
 ///         is_async: tcx.asyncness(def_id).is_async(),
+///         is_closure: false,
 ///         is_const: tcx.is_const_fn(def_id),
 ///         generics: generic_params,
 ///         return_type,
 ///         location: SourceLocation {
 ///             file: location.file.name.prefer_local().to_string(),
 ///             line: location.line,
-///             column: location.col.0,
+///             column: location.col.0 + 1,
+///             end_line: end.line,
+///             end_column: end.col.0 + 1,
 ///         },
 ///         is_trait_method: tcx.trait_of_item(def_id).is_some(),
 ///         trait_name: tcx.trait_of_item(def_id)
@@ -100,31 +104,33 @@ pub fn extract_function() -> Option<FunctionMetadata> {
     None
 }
 
-/// Extract visibility from HIR.
+/// Extract visibility from the module an item is visible in.
 ///
 /// # Full Implementation
 ///
+/// See `mir_analyzer::MirAnalyzer::extract_visibility`, which this sketches:
+///
 /// ```ignore
-/// use rustc_middle::ty::TyCtxt;
-/// use rustc_hir::def_id::LocalDefId;
-/// use rustc_hir::Visibility as HirVis;
+/// use rustc_middle::ty::{self, TyCtxt};
+/// use rustc_hir::def_id::{LocalDefId, CRATE_DEF_ID};
 ///
 /// fn extract_visibility<'tcx>(
 ///     tcx: TyCtxt<'tcx>,
 ///     local_def_id: LocalDefId,
 /// ) -> Visibility {
-///     let hir_id = tcx.local_def_id_to_hir_id(local_def_id);
-///     let node = tcx.hir().get(hir_id);
-///
-///     match node {
-///         rustc_hir::Node::Item(item) => {
-///             match item.vis.node {
-///                 HirVis::Public => Visibility::Public,
-///                 HirVis::Restricted { .. } => Visibility::Restricted,
-///                 HirVis::Inherited => Visibility::Private,
-///             }
-///         }
-///         _ => Visibility::Private,
+///     let module = match tcx.visibility(local_def_id) {
+///         ty::Visibility::Public => return Visibility::Public,
+///         ty::Visibility::Restricted(module) => module,
+///     };
+///
+///     let parent = tcx.parent_module_from_def_id(local_def_id).to_def_id();
+///     if module == parent {
+///         Visibility::Private
+///     } else if module == CRATE_DEF_ID.to_def_id() {
+///         Visibility::Crate
+///     } else {
+///         // pub(super) when `module` is the parent of `parent`
+///         Visibility::Restricted(format!("crate::{}", tcx.def_path_str(module)))
 ///     }
 /// }
 /// ```
@@ -201,11 +207,14 @@ pub fn extract_module_path() -> String {
 /// ) -> SourceLocation {
 ///     let source_map = tcx.sess.source_map();
 ///     let loc = source_map.lookup_char_pos(span.lo());
+///     let end = source_map.lookup_char_pos(span.hi());
 ///
 ///     SourceLocation {
 ///         file: loc.file.name.prefer_local().to_string(),
 ///         line: loc.line,
-///         column: loc.col.0,
+///         column: loc.col.0 + 1,
+///         end_line: end.line,
+///         end_column: end.col.0 + 1,
 ///     }
 /// }
 /// ```
@@ -215,6 +224,8 @@ pub fn extract_source_location() -> SourceLocation {
         file: "src/lib.rs".to_string(),
         line: 1,
         column: 1,
+        end_line: 1,
+        end_column: 1,
     }
 }
 
@@ -267,12 +278,19 @@ mod tests {
                 module_path: "crate".to_string(),
                 visibility: Visibility::Public,
                 is_async: false,
+                is_closure: false,
                 generics: vec![],
                 return_type: "()".to_string(),
+                is_trait_method: false,
+                trait_name: None,
+                impl_type: None,
+                attributes: vec![],
                 location: SourceLocation {
                     file: "test.rs".to_string(),
                     line: 1,
                     column: 1,
+                    end_line: 1,
+                    end_column: 1,
                 },
             },
             FunctionMetadata {
@@ -281,12 +299,19 @@ mod tests {
                 module_path: "crate".to_string(),
                 visibility: Visibility::Private,
                 is_async: false,
+                is_closure: false,
                 generics: vec![],
                 return_type: "()".to_string(),
+                is_trait_method: false,
+                trait_name: None,
+                impl_type: None,
+                attributes: vec![],
                 location: SourceLocation {
                     file: "test.rs".to_string(),
                     line: 5,
                     column: 1,
+                    end_line: 5,
+                    end_column: 1,
                 },
             },
         ];
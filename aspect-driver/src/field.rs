@@ -0,0 +1,305 @@
+//! Field get/set joinpoints.
+//!
+//! Besides function executions, reads and writes of struct fields are
+//! joinpoints: `get(User.balance)` selects the reads of `balance` in
+//! `User`, `set(User.*)` the writes of any of its fields, and a bare
+//! `User.balance` both. Field accesses are found in MIR (see
+//! `MirAnalyzer::extract_field_accesses`) and only reported for now;
+//! advice is not woven into them. On stable Rust, `#[aspect_fields]`
+//! weaves aspects into accessors of the fields instead, whose joinpoints
+//! are named as [`FieldAccess`] displays, e.g. `get(User.balance)`.
+//!
+//! The struct part of a pattern is a path matched against the end of the
+//! struct's path (`User` or `model::User`), or against all of it when it
+//! starts with `crate::` (`crate::model::User`). Path segments and the
+//! field may be `*` or have a `*` prefix or suffix.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::SourceLocation;
+
+/// Whether a field is read or written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldAccessKind {
+    /// The field is read, moved or borrowed immutably
+    Get,
+    /// The field is assigned or borrowed mutably
+    Set,
+}
+
+impl fmt::Display for FieldAccessKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FieldAccessKind::Get => "get",
+            FieldAccessKind::Set => "set",
+        })
+    }
+}
+
+/// A read or write of a struct field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldAccess {
+    /// Read or write
+    pub kind: FieldAccessKind,
+
+    /// Path of the struct as rustc prints it, without `crate::` for
+    /// structs of the crate (e.g., "model::User")
+    pub struct_path: String,
+
+    /// Field name, or its index in a tuple struct
+    pub field: String,
+
+    /// Fully qualified name of the function accessing the field
+    pub function: String,
+
+    /// Source location of the access
+    pub location: SourceLocation,
+}
+
+impl fmt::Display for FieldAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({}.{})", self.kind, self.struct_path, self.field)
+    }
+}
+
+/// A field joinpoint pattern: `get(<struct>.<field>)`,
+/// `set(<struct>.<field>)` or `<struct>.<field>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldPattern {
+    /// Accesses selected, `None` for both reads and writes
+    pub kind: Option<FieldAccessKind>,
+
+    /// Struct path pattern
+    pub struct_pattern: String,
+
+    /// Field name pattern
+    pub field_pattern: String,
+}
+
+impl FieldPattern {
+    /// Parse a field joinpoint pattern.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        let (kind, pattern) = if let Some(pattern) = input.strip_prefix("get(") {
+            (Some(FieldAccessKind::Get), pattern.strip_suffix(')'))
+        } else if let Some(pattern) = input.strip_prefix("set(") {
+            (Some(FieldAccessKind::Set), pattern.strip_suffix(')'))
+        } else {
+            (None, Some(input))
+        };
+        let pattern = pattern.ok_or("Missing closing parenthesis")?.trim();
+
+        let (struct_pattern, field_pattern) = pattern
+            .rsplit_once('.')
+            .ok_or_else(|| format!("expected <struct>.<field>, got '{}'", pattern))?;
+        let valid = |part: &str, separators: bool| {
+            !part.is_empty()
+                && part.split("::").all(|segment| {
+                    !segment.is_empty()
+                        && segment
+                            .chars()
+                            .all(|c| c.is_alphanumeric() || c == '_' || c == '*')
+                })
+                && (separators || !part.contains("::"))
+        };
+        if !valid(struct_pattern, true) {
+            return Err(format!("invalid struct pattern '{}'", struct_pattern));
+        }
+        if !valid(field_pattern, false) {
+            return Err(format!("invalid field pattern '{}'", field_pattern));
+        }
+
+        Ok(Self {
+            kind,
+            struct_pattern: struct_pattern.to_string(),
+            field_pattern: field_pattern.to_string(),
+        })
+    }
+
+    /// Whether the pattern selects an access of `kind` to `field` of the
+    /// struct at `struct_path`.
+    pub fn matches_field(&self, kind: FieldAccessKind, struct_path: &str, field: &str) -> bool {
+        self.kind.is_none_or(|k| k == kind)
+            && self.matches_struct(struct_path)
+            && wildcard_matches(&self.field_pattern, field)
+    }
+
+    /// Whether the pattern selects `access`.
+    pub fn matches(&self, access: &FieldAccess) -> bool {
+        self.matches_field(access.kind, &access.struct_path, &access.field)
+    }
+
+    /// Whether the struct pattern matches the last segments of
+    /// `struct_path`, or all of them for a `crate::` pattern, ignoring its
+    /// generic arguments.
+    fn matches_struct(&self, struct_path: &str) -> bool {
+        let struct_path = struct_path.split('<').next().unwrap_or(struct_path);
+        let path: Vec<&str> = struct_path.split("::").collect();
+        let (pattern, anchored) = match self.struct_pattern.strip_prefix("crate::") {
+            Some(pattern) => (pattern, true),
+            None => (self.struct_pattern.as_str(), false),
+        };
+        let pattern: Vec<&str> = pattern.split("::").collect();
+
+        (pattern.len() == path.len() || !anchored && pattern.len() < path.len())
+            && pattern
+                .iter()
+                .zip(&path[path.len() - pattern.len()..])
+                .all(|(pattern, segment)| wildcard_matches(pattern, segment))
+    }
+}
+
+impl fmt::Display for FieldPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            Some(kind) => write!(
+                f,
+                "{}({}.{})",
+                kind, self.struct_pattern, self.field_pattern
+            ),
+            None => write!(f, "{}.{}", self.struct_pattern, self.field_pattern),
+        }
+    }
+}
+
+/// Whether `pattern` (`name`, `*`, `prefix*`, `*suffix` or `*infix*`)
+/// matches `name`.
+fn wildcard_matches(pattern: &str, name: &str) -> bool {
+    if pattern == "*" {
+        true
+    } else if pattern.len() > 2 && pattern.starts_with('*') && pattern.ends_with('*') {
+        name.contains(&pattern[1..pattern.len() - 1])
+    } else if let Some(suffix) = pattern.strip_prefix('*') {
+        name.ends_with(suffix)
+    } else if let Some(prefix) = pattern.strip_suffix('*') {
+        name.starts_with(prefix)
+    } else {
+        name == pattern
+    }
+}
+
+/// The accesses selected by any of `patterns`.
+pub fn select_accesses(accesses: Vec<FieldAccess>, patterns: &[FieldPattern]) -> Vec<FieldAccess> {
+    accesses
+        .into_iter()
+        .filter(|access| patterns.iter().any(|pattern| pattern.matches(access)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn access(kind: FieldAccessKind, struct_path: &str, field: &str) -> FieldAccess {
+        FieldAccess {
+            kind,
+            struct_path: struct_path.to_string(),
+            field: field.to_string(),
+            function: "bank::deposit".to_string(),
+            location: SourceLocation {
+                file: "src/bank.rs".to_string(),
+                line: 12,
+                column: 5,
+                end_line: 12,
+                end_column: 30,
+            },
+        }
+    }
+
+    #[test]
+    fn test_parse_field_pattern() {
+        let pattern = FieldPattern::parse("get(crate::bank::Account.balance)").unwrap();
+        assert_eq!(pattern.kind, Some(FieldAccessKind::Get));
+        assert_eq!(pattern.struct_pattern, "crate::bank::Account");
+        assert_eq!(pattern.field_pattern, "balance");
+        assert_eq!(pattern.to_string(), "get(crate::bank::Account.balance)");
+
+        let pattern = FieldPattern::parse(" Account.* ").unwrap();
+        assert_eq!(pattern.kind, None);
+        assert_eq!(pattern.to_string(), "Account.*");
+        assert_eq!(
+            FieldPattern::parse("set(Pair.0)").unwrap().field_pattern,
+            "0"
+        );
+
+        assert!(FieldPattern::parse("get(Account.balance").is_err());
+        assert!(FieldPattern::parse("Account").is_err());
+        assert!(FieldPattern::parse("Account.").is_err());
+        assert!(FieldPattern::parse("crate::.balance").is_err());
+        assert!(FieldPattern::parse("Account<T>.balance").is_err());
+    }
+
+    #[test]
+    fn test_field_pattern_matches() {
+        use FieldAccessKind::{Get, Set};
+
+        let read = access(Get, "bank::Account", "balance");
+        let write = access(Set, "bank::Account", "balance");
+
+        let pattern = FieldPattern::parse("get(Account.balance)").unwrap();
+        assert!(pattern.matches(&read));
+        assert!(!pattern.matches(&write));
+
+        let pattern = FieldPattern::parse("bank::Account.bal*").unwrap();
+        assert!(pattern.matches(&read) && pattern.matches(&write));
+
+        for pattern in [
+            "set(*.balance)",
+            "crate::bank::Account.balance",
+            "set(crate::*::Account.*)",
+            "set(Acc*.*)",
+        ] {
+            assert!(
+                FieldPattern::parse(pattern).unwrap().matches(&write),
+                "{}",
+                pattern
+            );
+        }
+        for pattern in [
+            "set(ount.balance)",
+            "set(crate::Account.*)",
+            "set(Account.owner)",
+        ] {
+            assert!(
+                !FieldPattern::parse(pattern).unwrap().matches(&write),
+                "{}",
+                pattern
+            );
+        }
+
+        // Generic arguments are ignored
+        let generic = access(Get, "bank::Ledger<u64>", "entries");
+        assert!(FieldPattern::parse("Ledger.entries")
+            .unwrap()
+            .matches(&generic));
+    }
+
+    #[test]
+    fn test_select_accesses() {
+        use FieldAccessKind::{Get, Set};
+
+        let accesses = vec![
+            access(Get, "bank::Account", "balance"),
+            access(Set, "bank::Account", "balance"),
+            access(Get, "bank::Account", "owner"),
+            access(Set, "std::ops::Range<usize>", "start"),
+        ];
+        let patterns = [
+            FieldPattern::parse("set(Account.*)").unwrap(),
+            FieldPattern::parse("Range.start").unwrap(),
+        ];
+
+        let selected = select_accesses(accesses, &patterns);
+        let selected: Vec<String> = selected.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            selected,
+            [
+                "set(bank::Account.balance)",
+                "set(std::ops::Range<usize>.start)"
+            ]
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Which crates the compiler driver analyzes and weaves.
+//!
+//! Used as `RUSTC_WRAPPER`, the driver is run for every crate cargo
+//! compiles, dependencies included. A [`CrateFilter`] limits the analysis
+//! to some of them, with `--aspect-only-crate`, `--aspect-exclude`,
+//! `--aspect-no-tests` and `--aspect-no-benches`; the other crates are
+//! compiled by plain rustc.
+
+use std::path::{Path, PathBuf};
+
+/// Kind of target a rustc invocation compiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetKind {
+    /// Libraries, binaries and everything else
+    Crate,
+    /// Test harnesses (`--test`)
+    Test,
+    /// Test harnesses of the `benches/` directory
+    Bench,
+}
+
+/// The crate a rustc invocation compiles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrateTarget {
+    /// Name of the crate, as rustc sees it (`my_crate`)
+    pub crate_name: String,
+
+    /// `--crate-type`: `lib`, `bin`, `proc-macro`...
+    pub crate_type: String,
+
+    pub kind: TargetKind,
+
+    /// The crate root source file
+    pub root: PathBuf,
+}
+
+impl CrateTarget {
+    /// The crate compiled by `rustc_args` (without the program name),
+    /// `None` when they compile no crate (e.g., `rustc -vV`).
+    ///
+    /// Without `--crate-name`, the crate is named after its root file,
+    /// like rustc does. `cargo bench` compiles benchmarks as tests, so test
+    /// harnesses with their root in a `benches` directory are benchmarks.
+    pub fn from_rustc_args(rustc_args: &[String]) -> Option<Self> {
+        let root = rustc_args
+            .iter()
+            .find(|arg| !arg.starts_with('-') && arg.ends_with(".rs"))
+            .map(Path::new)?;
+
+        let value = |flag: &str| {
+            rustc_args
+                .iter()
+                .position(|arg| arg == flag)
+                .map(|i| rustc_args.get(i + 1).cloned())
+        };
+        let crate_name = match value("--crate-name") {
+            Some(name) => name?,
+            None => normalize(root.file_stem()?.to_str()?),
+        };
+        let crate_type = value("--crate-type")
+            .flatten()
+            .unwrap_or_else(|| "bin".to_string());
+
+        let kind = if !rustc_args.iter().any(|arg| arg == "--test") {
+            TargetKind::Crate
+        } else if root.components().any(|c| c.as_os_str() == "benches") {
+            TargetKind::Bench
+        } else {
+            TargetKind::Test
+        };
+
+        Some(Self {
+            crate_name,
+            crate_type,
+            kind,
+            root: root.to_path_buf(),
+        })
+    }
+
+    /// Name of the file the analysis of the crate is written to:
+    /// `<crate>-<type>.json`, or `<crate>-test.json` and
+    /// `<crate>-bench.json` for test harnesses.
+    pub fn report_name(&self) -> String {
+        let kind = match self.kind {
+            TargetKind::Crate => &self.crate_type,
+            TargetKind::Test => "test",
+            TargetKind::Bench => "bench",
+        };
+        format!("{}-{}.json", self.crate_name, kind)
+    }
+}
+
+/// Which crates to analyze and weave.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrateFilter {
+    /// Only these crates, all of them when empty
+    pub only: Vec<String>,
+
+    /// Never these crates
+    pub exclude: Vec<String>,
+
+    /// Test harnesses
+    pub tests: bool,
+
+    /// Benchmarks
+    pub benches: bool,
+}
+
+impl Default for CrateFilter {
+    fn default() -> Self {
+        Self {
+            only: Vec::new(),
+            exclude: Vec::new(),
+            tests: true,
+            benches: true,
+        }
+    }
+}
+
+impl CrateFilter {
+    /// Parse a comma-separated list of crate names. Package names are
+    /// accepted for crate names: `my-crate` is `my_crate`.
+    pub fn parse_names(list: &str) -> Result<Vec<String>, String> {
+        let names: Vec<String> = list
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(normalize)
+            .collect();
+        if names.is_empty() {
+            return Err("expected crate names".to_string());
+        }
+        Ok(names)
+    }
+
+    /// Whether `target` is analyzed and woven.
+    pub fn includes(&self, target: &CrateTarget) -> bool {
+        let name = |names: &[String]| names.iter().any(|n| normalize(n) == target.crate_name);
+
+        match target.kind {
+            TargetKind::Test if !self.tests => return false,
+            TargetKind::Bench if !self.benches => return false,
+            _ => {}
+        }
+        (self.only.is_empty() || name(&self.only)) && !name(&self.exclude)
+    }
+}
+
+/// The crate name of a package name.
+fn normalize(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn target(crate_name: &str, kind: TargetKind) -> CrateTarget {
+        CrateTarget {
+            crate_name: crate_name.to_string(),
+            crate_type: "lib".to_string(),
+            kind,
+            root: PathBuf::from("src/lib.rs"),
+        }
+    }
+
+    #[test]
+    fn test_crate_target() {
+        let lib = CrateTarget::from_rustc_args(&args(&[
+            "--crate-name",
+            "shop",
+            "--edition=2021",
+            "src/lib.rs",
+            "--crate-type",
+            "lib",
+        ]))
+        .unwrap();
+        assert_eq!(lib, target("shop", TargetKind::Crate));
+        assert_eq!(lib.report_name(), "shop-lib.json");
+
+        let test =
+            CrateTarget::from_rustc_args(&args(&["tests/fixtures/my-app.rs", "--test"])).unwrap();
+        assert_eq!(test.crate_name, "my_app");
+        assert_eq!(test.kind, TargetKind::Test);
+        assert_eq!(test.report_name(), "my_app-test.json");
+
+        let bench = CrateTarget::from_rustc_args(&args(&[
+            "--crate-name",
+            "checkout",
+            "benches/checkout.rs",
+            "--test",
+        ]))
+        .unwrap();
+        assert_eq!(bench.kind, TargetKind::Bench);
+        assert_eq!(bench.report_name(), "checkout-bench.json");
+
+        assert_eq!(CrateTarget::from_rustc_args(&args(&["-vV"])), None);
+        assert_eq!(
+            CrateTarget::from_rustc_args(&args(&[
+                "-",
+                "--crate-name",
+                "___",
+                "--print=file-names"
+            ])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_crate_filter() {
+        let all = CrateFilter::default();
+        assert!(all.includes(&target("serde", TargetKind::Crate)));
+        assert!(all.includes(&target("shop", TargetKind::Bench)));
+
+        let filter = CrateFilter {
+            only: CrateFilter::parse_names("shop, shop-api").unwrap(),
+            exclude: vec!["shop-api".to_string()],
+            tests: false,
+            benches: true,
+        };
+        assert_eq!(filter.only, ["shop", "shop_api"]);
+        assert!(filter.includes(&target("shop", TargetKind::Crate)));
+        assert!(filter.includes(&target("shop", TargetKind::Bench)));
+        assert!(!filter.includes(&target("shop", TargetKind::Test)));
+        assert!(!filter.includes(&target("shop_api", TargetKind::Crate)));
+        assert!(!filter.includes(&target("serde", TargetKind::Crate)));
+
+        assert!(CrateFilter::parse_names(" , ").is_err());
+    }
+}
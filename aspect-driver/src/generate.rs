@@ -1,7 +1,9 @@
 //! Code generation for aspect weaving.
 //!
 //! This module generates the code that applies aspects to functions by
-//! transforming the function body to include aspect calls.
+//! transforming the function body to include aspect calls. The generated
+//! source is a preview of the weaving; the compiler weaves before/after
+//! advice into MIR directly (see `weave`, with the `rustc` feature).
 
 use crate::r#match::{AdviceType, RegisteredAspect};
 use crate::types::FunctionMetadata;
@@ -25,6 +27,7 @@ pub struct GeneratedFunction {
 /// Code generator for aspect weaving.
 pub struct AspectCodeGenerator {
     /// Counter for generating unique names
+    #[allow(dead_code)]
     id_counter: usize,
 }
 
@@ -52,7 +55,7 @@ impl AspectCodeGenerator {
             // No aspects, return original
             return GeneratedFunction {
                 original: function.clone(),
-                code: format!("// Original function (no aspects)\n"),
+                code: "// Original function (no aspects)\n".to_string(),
                 aspects: vec![],
                 original_renamed: false,
             };
@@ -105,7 +108,7 @@ impl AspectCodeGenerator {
 
         // Wrapper function
         let wrapper_name = self.simple_function_name(function);
-        code.push_str(&format!("// Wrapper function with aspects\n"));
+        code.push_str("// Wrapper function with aspects\n");
         code.push_str(&format!("{} fn {}(", self.visibility_str(function), wrapper_name));
 
         // Parameters (simplified)
@@ -176,7 +179,7 @@ impl AspectCodeGenerator {
              fn {original_name}(...) {{ ... }}\n\n"
         ));
 
-        code.push_str(&format!("// Wrapper with around advice\n"));
+        code.push_str("// Wrapper with around advice\n");
         code.push_str(&format!("{} fn {}(", self.visibility_str(function), wrapper_name));
         code.push_str("...) ");
 
@@ -247,16 +250,12 @@ impl AspectCodeGenerator {
     }
 
     /// Get visibility as string.
-    fn visibility_str(&self, function: &FunctionMetadata) -> &'static str {
-        match function.visibility {
-            crate::types::Visibility::Public => "pub",
-            crate::types::Visibility::Crate => "pub(crate)",
-            crate::types::Visibility::Restricted => "pub(in ...)",
-            crate::types::Visibility::Private => "",
-        }
+    fn visibility_str(&self, function: &FunctionMetadata) -> String {
+        function.visibility.to_string()
     }
 
     /// Generate a unique identifier.
+    #[allow(dead_code)]
     fn unique_id(&mut self) -> usize {
         let id = self.id_counter;
         self.id_counter += 1;
@@ -270,47 +269,6 @@ impl Default for AspectCodeGenerator {
     }
 }
 
-/// Transform a function's MIR/HIR to apply aspects.
-///
-/// # Full Implementation (requires rustc)
-///
-/// ```ignore
-/// use rustc_middle::mir::{Body, BasicBlock, Statement};
-/// use rustc_middle::ty::TyCtxt;
-///
-/// fn transform_mir<'tcx>(
-///     tcx: TyCtxt<'tcx>,
-///     body: &mut Body<'tcx>,
-///     aspects: &[RegisteredAspect],
-/// ) {
-///     // Get entry basic block
-///     let entry_block = &mut body.basic_blocks_mut()[BasicBlock::from_u32(0)];
-///
-///     // Insert before advice
-///     for aspect in aspects.iter().filter(|a| a.advice_type == AdviceType::Before) {
-///         let before_call = generate_before_call(tcx, aspect);
-///         entry_block.statements.insert(0, before_call);
-///     }
-///
-///     // Find return statements
-///     for block in body.basic_blocks_mut() {
-///         if let Some(terminator) = &mut block.terminator {
-///             if is_return(terminator) {
-///                 // Insert after advice before return
-///                 for aspect in aspects.iter().filter(|a| a.advice_type == AdviceType::After) {
-///                     let after_call = generate_after_call(tcx, aspect);
-///                     block.statements.push(after_call);
-///                 }
-///             }
-///         }
-///     }
-/// }
-/// ```
-pub fn transform_mir() -> String {
-    // Placeholder - would modify MIR in place
-    String::from("// MIR transformation requires rustc integration")
-}
-
 /// Generate aspect initialization code.
 pub fn generate_aspect_init(aspect: &RegisteredAspect) -> String {
     format!("let aspect = {}::new();", aspect.aspect_name)
@@ -371,12 +329,19 @@ mod tests {
             module_path: "crate::api".to_string(),
             visibility: Visibility::Public,
             is_async: false,
+            is_closure: false,
             generics: vec![],
             return_type: "User".to_string(),
+            is_trait_method: false,
+            trait_name: None,
+            impl_type: None,
+            attributes: vec![],
             location: SourceLocation {
                 file: "src/api.rs".to_string(),
                 line: 42,
                 column: 1,
+                end_line: 42,
+                end_column: 1,
             },
         }
     }
@@ -7,7 +7,7 @@
 //!
 //! **Phase 3 Week 9-10 - Implementation In Progress**
 //!
-//! This requires:
+//! The `rustc` feature requires:
 //! - Nightly Rust (unstable compiler APIs)
 //! - rustc-dev component installed
 //! - rust-toolchain.toml configures the correct version
@@ -24,6 +24,13 @@
 //! MIR extraction → Pointcut matching → Code weaving
 //! ```
 //!
+//! Weaving happens in the `mir_built` query: see `weave::MirWeaver` for
+//! how before/after advice hooks are inserted into matched functions.
+//!
+//! The compiler integration is behind the `rustc` feature. Without it the
+//! crate builds on stable Rust, and [`source::SourceWeaver`] weaves by
+//! rewriting a copy of the crate's sources instead.
+//!
 //! # Usage
 //!
 //! ```ignore
@@ -37,7 +44,7 @@
 //! }
 //! ```
 
-#![feature(rustc_private)]
+#![cfg_attr(feature = "rustc", feature(rustc_private))]
 #![allow(unused_imports)]
 
 pub mod extract;
@@ -45,17 +52,73 @@ pub mod types;
 pub mod r#match;
 pub mod generate;
 
+// Source-to-source weaving, the stable alternative to the compiler driver
+pub mod source;
+
+// Weaving from build scripts into OUT_DIR
+pub mod build;
+
+// Machine-readable analysis results
+pub mod report;
+
+// Function metadata cached between builds
+pub mod cache;
+
+// Dry-run weaving plans
+pub mod plan;
+
+// Pointcuts matching no function
+pub mod unmatched;
+
+// Field get/set joinpoints
+pub mod field;
+
+// Woven source for inspection
+pub mod expand;
+
+// Timing breakdown of the analysis phases
+pub mod timing;
+
+// aspect.toml configuration files
+pub mod config;
+
+// Custom analysis passes
+pub mod pass;
+
+// Approximate analysis from source, for stable toolchains
+pub mod syntax;
+
+// Crates and targets the driver analyzes
+pub mod filter;
+
+// Driver diagnostics and exit codes
+pub mod diagnostic;
+
+// Running the driver as cargo's rustc wrapper
+pub mod cargo;
+
+// Pointcut checks that need no compilation
+pub mod lint;
+
+// Aspects declared by the packages providing them
+pub mod provider;
+
 // Phase 3 Week 9-10: Actual compiler integration
 // Requires nightly Rust with rustc-dev component
 pub mod compiler;
 
 // Phase 3 Week 11-12: Real MIR analysis
+#[cfg(feature = "rustc")]
 pub mod mir_analyzer;
 
+// MIR weaving of before/after advice into matched functions
+#[cfg(feature = "rustc")]
+pub mod weave;
+
 use std::path::PathBuf;
 
 /// Configuration for the aspect compiler driver.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct AspectConfig {
     /// Enable verbose output
     pub verbose: bool,
@@ -70,17 +133,6 @@ pub struct AspectConfig {
     pub source_files: Vec<PathBuf>,
 }
 
-impl Default for AspectConfig {
-    fn default() -> Self {
-        Self {
-            verbose: false,
-            pointcuts: Vec::new(),
-            aspects: Vec::new(),
-            source_files: Vec::new(),
-        }
-    }
-}
-
 /// Main entry point for the aspect compiler driver.
 ///
 /// This would invoke rustc with custom callbacks to perform aspect weaving.
@@ -184,6 +236,7 @@ fn extract_functions() -> Vec<types::FunctionMetadata> {
 }
 
 /// Build rustc arguments from aspect configuration.
+#[allow(dead_code)]
 fn build_rustc_args(config: &AspectConfig) -> Vec<String> {
     let mut args = vec!["rustc".to_string()];
 
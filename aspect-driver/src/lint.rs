@@ -0,0 +1,549 @@
+//! Pointcut checks that need no compilation.
+//!
+//! [`check_syntax`] parses a pointcut like [`parse_pointcut`], but tells
+//! where in the pointcut an error is, so that it can be shown with a caret.
+//! [`lint`] then looks for mistakes that parse, such as a clause that is
+//! repeated, or `within` clauses no function can satisfy together.
+//!
+//! The pointcuts themselves are found in `#[advice]` attributes with
+//! [`advice_pointcuts`], and in aspect.toml with [`config_pointcuts`].
+
+use std::fmt;
+
+use crate::config::ConfigFile;
+use crate::r#match::{find_operator, parse_pointcut, PointcutExpr};
+use crate::types::SourceLocation;
+use crate::unmatched::edit_distance;
+
+/// Pointcut primitives, as written before their `(`.
+const PRIMITIVES: [&str; 5] = ["execution", "within", "name", "annotated", "test"];
+
+/// A pointcut that does not parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// Byte offset of the error in the pointcut
+    pub offset: usize,
+
+    /// Length of the erroneous text, at least 1
+    pub len: usize,
+
+    pub message: String,
+
+    /// How to fix it, if known
+    pub help: Option<String>,
+}
+
+impl SyntaxError {
+    fn new(offset: usize, len: usize, message: impl Into<String>) -> Self {
+        Self {
+            offset,
+            len: len.max(1),
+            message: message.into(),
+            help: None,
+        }
+    }
+
+    /// The pointcut, and a line of carets under the error:
+    ///
+    /// ```text
+    /// within(crate::api) && nme(get)
+    ///                       ^^^ unknown pointcut `nme`
+    /// ```
+    pub fn snippet(&self, pointcut: &str) -> String {
+        let column = pointcut
+            .get(..self.offset)
+            .map_or(self.offset, |before| before.chars().count());
+        format!(
+            "{}\n{}{} {}",
+            pointcut,
+            " ".repeat(column),
+            "^".repeat(self.len),
+            self.message
+        )
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+/// Parse `pointcut` like [`parse_pointcut`], with the location of errors.
+pub fn check_syntax(pointcut: &str) -> Result<PointcutExpr, SyntaxError> {
+    // Unbalanced parentheses would otherwise be reported on the clause
+    // the parser happens to split at
+    let mut open = Vec::new();
+    for (offset, c) in pointcut.char_indices() {
+        match c {
+            '(' => open.push(offset),
+            ')' if open.pop().is_none() => {
+                return Err(SyntaxError::new(offset, 1, "unmatched `)`"));
+            }
+            _ => {}
+        }
+    }
+    if let Some(&offset) = open.last() {
+        return Err(SyntaxError::new(offset, 1, "unclosed `(`"));
+    }
+
+    parse_spanned(pointcut, 0)
+}
+
+/// [`parse_pointcut`] of `input`, which starts at byte `base` of the
+/// pointcut. Operators are split exactly like the parser does; the leaf
+/// clauses are parsed by it.
+fn parse_spanned(input: &str, base: usize) -> Result<PointcutExpr, SyntaxError> {
+    let base = base + (input.len() - input.trim_start().len());
+    let input = input.trim();
+
+    if input.is_empty() {
+        return Err(SyntaxError::new(base, 1, "expected a pointcut"));
+    }
+
+    if let Some(negated) = input.strip_prefix('!') {
+        let inner = parse_spanned(negated, base + 1)?;
+        return Ok(PointcutExpr::Not(Box::new(inner)));
+    }
+
+    for operator in ["&&", "||"] {
+        if let Some(pos) = find_operator(input, operator) {
+            let left = Box::new(parse_spanned(&input[..pos], base)?);
+            let right = Box::new(parse_spanned(&input[pos + 2..], base + pos + 2)?);
+            return Ok(if operator == "&&" {
+                PointcutExpr::And(left, right)
+            } else {
+                PointcutExpr::Or(left, right)
+            });
+        }
+    }
+
+    if input.starts_with('(') && input.ends_with(')') {
+        return parse_spanned(&input[1..input.len() - 1], base + 1);
+    }
+
+    parse_pointcut(input).map_err(|message| leaf_error(input, base, message))
+}
+
+/// Where the error `message` of the clause `input` is.
+fn leaf_error(input: &str, base: usize, message: String) -> SyntaxError {
+    let Some(open) = input.find('(') else {
+        let mut error = SyntaxError::new(
+            base,
+            input.len(),
+            format!("expected a pointcut, found `{}`", input),
+        );
+        error.help = Some(match closest_primitive(input) {
+            Some("test") => "did you mean `test()`?".to_string(),
+            Some(primitive) => format!("did you mean `{}(...)`?", primitive),
+            None => "pointcuts are `execution(...)`, `within(...)`, `name(...)`, \
+                     `annotated(...)` or `test()`"
+                .to_string(),
+        });
+        return error;
+    };
+
+    let name = input[..open].trim_end();
+    if !PRIMITIVES.contains(&name) {
+        let mut error = SyntaxError::new(base, name.len(), format!("unknown pointcut `{}`", name));
+        error.help = Some(match closest_primitive(name) {
+            Some(primitive) => format!("did you mean `{}`?", primitive),
+            None => {
+                "pointcuts are `execution`, `within`, `name`, `annotated` or `test`".to_string()
+            }
+        });
+        return error;
+    }
+
+    // An error in the pattern, between the parentheses
+    let close = input.rfind(')').unwrap_or(input.len());
+    let pattern = &input[open + 1..close];
+    let start = open + 1 + (pattern.len() - pattern.trim_start().len());
+    if pattern.trim().is_empty() {
+        SyntaxError::new(base + open, close + 1 - open, message)
+    } else {
+        SyntaxError::new(base + start, pattern.trim().len(), message)
+    }
+}
+
+/// The primitive `name` is a typo of, if it is close to one.
+fn closest_primitive(name: &str) -> Option<&'static str> {
+    PRIMITIVES
+        .into_iter()
+        .map(|primitive| (edit_distance(name, primitive), primitive))
+        .filter(|(distance, _)| *distance <= 2)
+        .min()
+        .map(|(_, primitive)| primitive)
+}
+
+/// Mistakes in a pointcut that parses: clauses that are redundant, and
+/// combinations that match no function, or every function.
+pub fn lint(expr: &PointcutExpr) -> Vec<String> {
+    let mut warnings = Vec::new();
+    lint_expr(expr, &mut warnings);
+    warnings
+}
+
+fn lint_expr(expr: &PointcutExpr, warnings: &mut Vec<String>) {
+    match expr {
+        PointcutExpr::And(..) | PointcutExpr::Or(..) => {
+            let is_and = matches!(expr, PointcutExpr::And(..));
+            let mut operands = Vec::new();
+            flatten(expr, is_and, &mut operands);
+            lint_operands(&operands, is_and, warnings);
+            for operand in operands {
+                lint_expr(operand, warnings);
+            }
+        }
+        PointcutExpr::Not(inner) => {
+            if let PointcutExpr::Not(clause) = inner.as_ref() {
+                warnings.push(format!("`{}` is a double negation of `{}`", expr, clause));
+            }
+            lint_expr(inner, warnings);
+        }
+        _ => {}
+    }
+}
+
+/// The operands of a chain of `&&` (`is_and`) or `||`.
+fn flatten<'a>(expr: &'a PointcutExpr, is_and: bool, operands: &mut Vec<&'a PointcutExpr>) {
+    match expr {
+        PointcutExpr::And(left, right) if is_and => {
+            flatten(left, is_and, operands);
+            flatten(right, is_and, operands);
+        }
+        PointcutExpr::Or(left, right) if !is_and => {
+            flatten(left, is_and, operands);
+            flatten(right, is_and, operands);
+        }
+        operand => operands.push(operand),
+    }
+}
+
+fn lint_operands(operands: &[&PointcutExpr], is_and: bool, warnings: &mut Vec<String>) {
+    let operator = if is_and { "&&" } else { "||" };
+    for (i, a) in operands.iter().enumerate() {
+        for b in &operands[i + 1..] {
+            if a == b {
+                warnings.push(format!("`{}` appears twice in a `{}` chain", a, operator));
+                continue;
+            }
+            if is_complement(a, b) {
+                warnings.push(if is_and {
+                    format!("`{} && {}` never matches", a, b)
+                } else {
+                    format!("`{} || {}` matches every function", a, b)
+                });
+                continue;
+            }
+            let (PointcutExpr::Within(a), PointcutExpr::Within(b)) = (a, b) else {
+                continue;
+            };
+            let (narrow, broad) = if is_submodule(a, b) {
+                (a, b)
+            } else if is_submodule(b, a) {
+                (b, a)
+            } else {
+                if is_and {
+                    warnings.push(format!(
+                        "`within({}) && within({})` never matches: no function is in both",
+                        a, b
+                    ));
+                }
+                continue;
+            };
+            warnings.push(if is_and {
+                format!("`within({})` is redundant with `within({})`", broad, narrow)
+            } else {
+                format!("`within({})` is redundant with `within({})`", narrow, broad)
+            });
+        }
+    }
+}
+
+fn is_complement(a: &PointcutExpr, b: &PointcutExpr) -> bool {
+    matches!(a, PointcutExpr::Not(inner) if inner.as_ref() == b)
+        || matches!(b, PointcutExpr::Not(inner) if inner.as_ref() == a)
+}
+
+/// Whether `within(module)` only matches functions `within(parent)` does.
+fn is_submodule(module: &str, parent: &str) -> bool {
+    module == parent || module.starts_with(&format!("{}::", parent))
+}
+
+/// The pointcuts of the `#[advice(pointcut = "...")]` attributes of
+/// `source`, with the location of their first character in `file`.
+pub fn advice_pointcuts(source: &str, file: &str) -> Result<Vec<(String, SourceLocation)>, String> {
+    let syntax = syn::parse_file(source).map_err(|e| format!("{}: {}", file, e))?;
+    let mut pointcuts = Vec::new();
+    collect_advice(&syntax.items, file, &mut pointcuts);
+    Ok(pointcuts)
+}
+
+fn collect_advice(items: &[syn::Item], file: &str, pointcuts: &mut Vec<(String, SourceLocation)>) {
+    for item in items {
+        match item {
+            syn::Item::Fn(func) => advice_attrs(&func.attrs, file, pointcuts),
+            syn::Item::Mod(module) => {
+                if let Some((_, items)) = &module.content {
+                    collect_advice(items, file, pointcuts);
+                }
+            }
+            syn::Item::Impl(item_impl) => {
+                for item in &item_impl.items {
+                    if let syn::ImplItem::Fn(method) = item {
+                        advice_attrs(&method.attrs, file, pointcuts);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn advice_attrs(
+    attrs: &[syn::Attribute],
+    file: &str,
+    pointcuts: &mut Vec<(String, SourceLocation)>,
+) {
+    let advice = attrs.iter().filter(|attr| {
+        attr.path()
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "advice")
+    });
+    for attr in advice {
+        // Malformed attributes are left for the compiler to report
+        let _ = attr.parse_nested_meta(|meta| {
+            let value = meta.value()?;
+            if meta.path.is_ident("pointcut") {
+                let pointcut: syn::LitStr = value.parse()?;
+                let start = pointcut.span().start();
+                let end = pointcut.span().end();
+                pointcuts.push((
+                    pointcut.value(),
+                    SourceLocation {
+                        file: file.to_string(),
+                        line: start.line,
+                        // Past the opening quote
+                        column: start.column + 2,
+                        end_line: end.line,
+                        end_column: end.column,
+                    },
+                ));
+            } else {
+                value.parse::<syn::Expr>()?;
+            }
+            Ok(())
+        });
+    }
+}
+
+/// The pointcuts of the aspect.toml `contents`, profiles included, with
+/// the location of their first character in `file` when it can be found.
+pub fn config_pointcuts(
+    contents: &str,
+    file: &str,
+) -> Result<Vec<(String, Option<SourceLocation>)>, String> {
+    let mut searched_from = 0;
+    let pointcuts = ConfigFile::unchecked_pointcuts(contents)?
+        .into_iter()
+        .map(|(_, pointcut)| {
+            // Pointcuts are listed in the order of the file, except for
+            // aspects written before `pointcuts`
+            let location = find_string(contents, &pointcut, searched_from)
+                .or_else(|| find_string(contents, &pointcut, 0))
+                .map(|offset| {
+                    searched_from = offset + 1;
+                    location_at(contents, offset, pointcut.len(), file)
+                });
+            (pointcut, location)
+        })
+        .collect();
+    Ok(pointcuts)
+}
+
+/// Offset of the quoted TOML string `value` in `contents`, from `from` on.
+fn find_string(contents: &str, value: &str, from: usize) -> Option<usize> {
+    ['"', '\''].into_iter().find_map(|quote| {
+        let quoted = format!("{}{}{}", quote, value, quote);
+        contents
+            .get(from..)?
+            .find(&quoted)
+            .map(|offset| from + offset + 1)
+    })
+}
+
+fn location_at(contents: &str, offset: usize, len: usize, file: &str) -> SourceLocation {
+    let before = &contents[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |newline| newline + 1) + 1;
+    SourceLocation {
+        file: file.to_string(),
+        line,
+        column,
+        end_line: line,
+        end_column: column + len,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(pointcut: &str) -> SyntaxError {
+        check_syntax(pointcut).unwrap_err()
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let pointcut = "within(crate::api) && nme(get)";
+        let unknown = error(pointcut);
+        assert_eq!((unknown.offset, unknown.len), (22, 3));
+        assert_eq!(unknown.help.as_deref(), Some("did you mean `name`?"));
+        assert_eq!(
+            unknown.snippet(pointcut),
+            "within(crate::api) && nme(get)\n                      ^^^ unknown pointcut `nme`"
+        );
+
+        let unclosed = error("execution(pub fn *(..) || name(get)");
+        assert_eq!(
+            (unclosed.offset, unclosed.message.as_str()),
+            (9, "unclosed `(`")
+        );
+        assert_eq!(error("name(get))").offset, 9);
+
+        // Errors of the pattern point into the parentheses
+        let pattern = error("!( name(get) || execution( pub *(..) ))");
+        assert_eq!((pattern.offset, pattern.len), (27, 9));
+        assert_eq!(error("name() && within(a)").offset, 4);
+
+        let missing = error("name(get) && ");
+        assert_eq!(
+            (missing.offset, missing.message.as_str()),
+            (12, "expected a pointcut")
+        );
+        let bare = error("within");
+        assert_eq!((bare.offset, bare.len), (0, 6));
+        assert_eq!(bare.help.as_deref(), Some("did you mean `within(...)`?"));
+        assert_eq!(
+            error("test").help.as_deref(),
+            Some("did you mean `test()`?")
+        );
+        let argument = error("within(a) && test(a)");
+        assert_eq!((argument.offset, argument.len), (18, 1));
+    }
+
+    #[test]
+    fn test_check_syntax_agrees_with_parser() {
+        for pointcut in [
+            "execution(pub fn *(..))",
+            "  within(crate::api) && !(name(get) || annotated(route))",
+            "((name(a)))",
+            "execution(pub async fn fetch_*(..))",
+            "name(a) || ",
+            "execution(pub *(..))",
+            "nme(get)",
+            "!!name(a)",
+            "test() && !within(crate::bench)",
+            "",
+        ] {
+            assert_eq!(
+                check_syntax(pointcut).ok(),
+                parse_pointcut(pointcut).ok(),
+                "{}",
+                pointcut
+            );
+        }
+    }
+
+    fn lints(pointcut: &str) -> Vec<String> {
+        lint(&parse_pointcut(pointcut).unwrap())
+    }
+
+    #[test]
+    fn test_lint() {
+        assert!(lints("execution(pub fn *(..)) && within(crate::api)").is_empty());
+        assert!(lints("within(crate::api) || within(crate::db)").is_empty());
+
+        assert_eq!(
+            lints("name(get) && within(a) && name(get)"),
+            ["`name(get)` appears twice in a `&&` chain"]
+        );
+        assert_eq!(
+            lints("name(get) && !name(get)"),
+            ["`name(get) && !name(get)` never matches"]
+        );
+        assert_eq!(
+            lints("name(get) || !name(get)"),
+            ["`name(get) || !name(get)` matches every function"]
+        );
+        assert_eq!(
+            lints("within(crate::api) && within(crate::db)"),
+            ["`within(crate::api) && within(crate::db)` never matches: no function is in both"]
+        );
+        assert_eq!(
+            lints("within(crate::api) && within(crate::api::v1)"),
+            ["`within(crate::api)` is redundant with `within(crate::api::v1)`"]
+        );
+        assert_eq!(
+            lints("within(crate::api::v1) || within(crate::api)"),
+            ["`within(crate::api::v1)` is redundant with `within(crate::api)`"]
+        );
+        // `crate::apiv2` is not in `crate::api`
+        assert_eq!(lints("within(crate::api) && within(crate::apiv2)").len(), 1);
+        assert_eq!(
+            lints("name(a) || !!name(b)"),
+            ["`!!name(b)` is a double negation of `name(b)`"]
+        );
+    }
+
+    #[test]
+    fn test_advice_pointcuts() {
+        let source = r#"
+use aspect_macros::advice;
+
+#[advice(pointcut = "execution(pub fn *(..))", advice = "around", order = 10)]
+fn api_logger(pjp: ProceedingJoinPoint) {}
+
+mod db {
+    #[aspect_macros::advice(order = 1, pointcut = "within(crate::db)")]
+    fn db_timer(pjp: ProceedingJoinPoint) {}
+}
+
+#[aspect(Logger)]
+fn get() {}
+"#;
+        let pointcuts = advice_pointcuts(source, "src/lib.rs").unwrap();
+        let found: Vec<_> = pointcuts
+            .iter()
+            .map(|(pointcut, location)| (pointcut.as_str(), location.line, location.column))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("execution(pub fn *(..))", 4, 22),
+                ("within(crate::db)", 8, 52)
+            ]
+        );
+        assert!(advice_pointcuts("fn (", "src/lib.rs").is_err());
+    }
+
+    #[test]
+    fn test_config_pointcuts() {
+        let contents = "pointcuts = [\"name(a)\", 'nme(b)']\n\n\
+                        [[aspects]]\nname = \"trace\"\nadvice = \"before\"\nhook = \"trace\"\npointcut = \"name(a)\"\n";
+        let pointcuts = config_pointcuts(contents, "aspect.toml").unwrap();
+        let found: Vec<_> = pointcuts
+            .iter()
+            .map(|(pointcut, location)| {
+                let location = location.as_ref().unwrap();
+                (pointcut.as_str(), location.line, location.column)
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [("name(a)", 1, 15), ("nme(b)", 1, 26), ("name(a)", 7, 13)]
+        );
+    }
+}
@@ -2,12 +2,27 @@
 //!
 //! This module matches FunctionMetadata against pointcut expressions to
 //! determine which aspects should be applied to which functions.
+//!
+//! `execution(...)` and `within(...)` are evaluated by aspect-core's
+//! [`Pointcut`] matcher on the function's [`FunctionInfo`], so they select
+//! the same functions at compile time as at runtime. The driver adds
+//! `async` in execution patterns, `name(...)`, `annotated(...)` and
+//! `test()`, which matches the tests of the crate.
+//!
+//! [`FunctionInfo`]: aspect_core::pointcut::FunctionInfo
 
-use crate::types::{FunctionMetadata, MatchedFunction, Visibility};
+use crate::types::{FunctionMetadata, MatchedFunction};
+use aspect_core::pointcut::{Matcher, ModulePattern, Pointcut};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
 use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
 
 /// Aspect registry entry.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RegisteredAspect {
     /// Aspect type name
     pub aspect_name: String,
@@ -15,24 +30,104 @@ pub struct RegisteredAspect {
     /// Pointcut expression
     pub pointcut: String,
 
-    /// Advice type (before, after, around)
+    /// Advice type (before, after, after_error, around)
     pub advice_type: AdviceType,
 
     /// Priority (higher = runs first)
     pub priority: i32,
 }
 
+/// Advice to weave: a hook function called for every function matched by
+/// a pointcut.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdviceHook {
+    /// When the hook runs (before or after only)
+    pub advice_type: AdviceType,
+
+    /// Pointcut expression selecting the advised functions
+    pub pointcut: String,
+
+    /// Path of the hook function (e.g., "crate::trace::enter")
+    pub path: String,
+}
+
+impl AdviceHook {
+    /// Parse a `<pointcut>=<path>` command-line specification.
+    pub fn parse(advice_type: AdviceType, spec: &str) -> Result<Self, String> {
+        let (pointcut, path) = spec
+            .rsplit_once('=')
+            .ok_or_else(|| format!("expected <pointcut>=<hook path>, got '{}'", spec))?;
+
+        Self::new(advice_type, pointcut, path)
+    }
+
+    /// Advice calling the hook at `path` for the functions matched by
+    /// `pointcut`.
+    ///
+    /// Around and after-error advice cannot be woven into MIR, as they
+    /// would need the body moved into a closure; use `#[aspect]` for them
+    /// instead.
+    pub fn new(advice_type: AdviceType, pointcut: &str, path: &str) -> Result<Self, String> {
+        if advice_type == AdviceType::Around || advice_type == AdviceType::AfterError {
+            return Err(format!(
+                "{} advice cannot be woven into MIR, use #[aspect] instead",
+                advice_type
+            ));
+        }
+
+        let (pointcut, path) = (pointcut.trim(), path.trim());
+        parse_pointcut(pointcut)?;
+        if path.is_empty() || path.split("::").any(str::is_empty) {
+            return Err(format!("invalid hook path '{}'", path));
+        }
+
+        Ok(Self {
+            advice_type,
+            pointcut: pointcut.to_string(),
+            path: path.to_string(),
+        })
+    }
+}
+
 /// Type of advice to apply.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum AdviceType {
     /// Run before function execution
     Before,
     /// Run after function execution
     After,
+    /// Run when function execution fails
+    AfterError,
     /// Wrap function execution
     Around,
 }
 
+impl fmt::Display for AdviceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AdviceType::Before => "before",
+            AdviceType::After => "after",
+            AdviceType::AfterError => "after_error",
+            AdviceType::Around => "around",
+        })
+    }
+}
+
+impl std::str::FromStr for AdviceType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "before" => Ok(AdviceType::Before),
+            "after" => Ok(AdviceType::After),
+            "after_error" => Ok(AdviceType::AfterError),
+            "around" => Ok(AdviceType::Around),
+            _ => Err(format!("unknown advice type '{}'", s)),
+        }
+    }
+}
+
 /// Pointcut matching engine.
 pub struct PointcutMatcher {
     /// Registered aspects to match against
@@ -55,8 +150,9 @@ impl PointcutMatcher {
     /// Match all registered aspects against a function.
     ///
     /// Returns all aspects that match the function, sorted by priority.
-    pub fn match_function(&self, function: &FunctionMetadata) -> Vec<MatchedFunction> {
-        let mut matches = Vec::new();
+    /// Up to two matches are stored inline, without allocating.
+    pub fn match_function(&self, function: &FunctionMetadata) -> SmallVec<[MatchedFunction; 2]> {
+        let mut matches: SmallVec<[MatchedFunction; 2]> = SmallVec::new();
 
         for aspect in &self.aspects {
             if self.matches_pointcut(function, &aspect.pointcut) {
@@ -79,7 +175,9 @@ impl PointcutMatcher {
     }
 
     /// Match a function against a pointcut expression.
-    fn matches_pointcut(&self, function: &FunctionMetadata, pointcut: &str) -> bool {
+    ///
+    /// Invalid pointcuts match nothing.
+    pub fn matches_pointcut(&self, function: &FunctionMetadata, pointcut: &str) -> bool {
         // Parse and evaluate pointcut
         match parse_pointcut(pointcut) {
             Ok(expr) => self.evaluate_pointcut(&expr, function),
@@ -87,12 +185,64 @@ impl PointcutMatcher {
         }
     }
 
+    /// Match `functions` against each of `pointcuts`, in parallel.
+    ///
+    /// Returns the `(function, pointcut)` matches pointcut by pointcut, in
+    /// the order of `functions`, as matching them one by one would. Each
+    /// pointcut is parsed once; invalid pointcuts match nothing.
+    pub fn match_pointcuts(
+        &self,
+        functions: &[FunctionMetadata],
+        pointcuts: &[String],
+    ) -> Vec<(FunctionMetadata, String)> {
+        pointcuts
+            .iter()
+            .filter_map(|pointcut| Some((pointcut, parse_pointcut(pointcut).ok()?)))
+            .flat_map(|(pointcut, expr)| {
+                functions
+                    .par_iter()
+                    .filter(|function| self.evaluate_pointcut(&expr, function))
+                    .map(|function| (function.clone(), pointcut.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Explain why a function is not matched by a pointcut expression, or
+    /// return `None` if it is.
+    pub fn mismatch_reason(&self, function: &FunctionMetadata, pointcut: &str) -> Option<String> {
+        match parse_pointcut(pointcut) {
+            Ok(expr) => self.explain_mismatch(&expr, function),
+            Err(e) => Some(e),
+        }
+    }
+
+    fn explain_mismatch(&self, expr: &PointcutExpr, function: &FunctionMetadata) -> Option<String> {
+        match expr {
+            PointcutExpr::And(left, right) => self
+                .explain_mismatch(left, function)
+                .or_else(|| self.explain_mismatch(right, function)),
+            PointcutExpr::Or(left, right) => {
+                let left = self.explain_mismatch(left, function)?;
+                let right = self.explain_mismatch(right, function)?;
+                Some(format!("{}, and {}", left, right))
+            }
+            PointcutExpr::Not(inner) => self
+                .evaluate_pointcut(inner, function)
+                .then(|| format!("excluded by !{}", inner)),
+            leaf => (!self.evaluate_pointcut(leaf, function))
+                .then(|| format!("does not match {}", leaf)),
+        }
+    }
+
     /// Evaluate a parsed pointcut expression.
     fn evaluate_pointcut(&self, expr: &PointcutExpr, function: &FunctionMetadata) -> bool {
         match expr {
             PointcutExpr::Execution(pattern) => self.matches_execution(function, pattern),
             PointcutExpr::Within(pattern) => self.matches_within(function, pattern),
             PointcutExpr::Name(pattern) => self.matches_name(function, pattern),
+            PointcutExpr::Annotated(path) => function.has_attribute(path),
+            PointcutExpr::Test => function.is_test(),
             PointcutExpr::And(left, right) => {
                 self.evaluate_pointcut(left, function) && self.evaluate_pointcut(right, function)
             }
@@ -105,33 +255,20 @@ impl PointcutMatcher {
 
     /// Match execution pattern.
     fn matches_execution(&self, function: &FunctionMetadata, pattern: &str) -> bool {
-        // Parse pattern: "pub fn *(..)" -> visibility="pub", name="*"
-        let parts: Vec<&str> = pattern.split_whitespace().collect();
-
-        // Check visibility
-        if parts.contains(&"pub") && !function.is_public() {
-            return false;
-        }
-
-        // Check function keyword
-        if !parts.contains(&"fn") {
+        let (is_async, pattern) = split_async(pattern);
+        if is_async && !function.is_async {
             return false;
         }
 
-        // Check name pattern
-        if let Some(name_part) = parts.iter().find(|p| p.contains('(') || p.ends_with('*')) {
-            let name = name_part.trim_end_matches("(..)");
-            if !function.matches_name_pattern(name) {
-                return false;
-            }
+        match execution_pointcut(&pattern) {
+            Ok(pointcut) => pointcut.matches(&function.to_function_info()),
+            Err(_) => false,
         }
-
-        true
     }
 
     /// Match within pattern (module path).
     fn matches_within(&self, function: &FunctionMetadata, pattern: &str) -> bool {
-        function.is_in_module(pattern)
+        ModulePattern::new(pattern).matches(&function.to_function_info())
     }
 
     /// Match name pattern.
@@ -164,6 +301,10 @@ pub enum PointcutExpr {
     Within(String),
     /// name(pattern)
     Name(String),
+    /// annotated(attribute path)
+    Annotated(String),
+    /// test(), the test functions
+    Test,
     /// expr1 && expr2
     And(Box<PointcutExpr>, Box<PointcutExpr>),
     /// expr1 || expr2
@@ -172,12 +313,39 @@ pub enum PointcutExpr {
     Not(Box<PointcutExpr>),
 }
 
+impl fmt::Display for PointcutExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Nested operators are parenthesized, so the precedence of the
+        // parser does not matter when reading the result
+        let operand = |expr: &PointcutExpr| match expr {
+            PointcutExpr::And(..) | PointcutExpr::Or(..) => format!("({})", expr),
+            _ => expr.to_string(),
+        };
+        match self {
+            PointcutExpr::Execution(pattern) => write!(f, "execution({})", pattern),
+            PointcutExpr::Within(pattern) => write!(f, "within({})", pattern),
+            PointcutExpr::Name(pattern) => write!(f, "name({})", pattern),
+            PointcutExpr::Annotated(path) => write!(f, "annotated({})", path),
+            PointcutExpr::Test => f.write_str("test()"),
+            PointcutExpr::And(left, right) => {
+                write!(f, "{} && {}", operand(left), operand(right))
+            }
+            PointcutExpr::Or(left, right) => {
+                write!(f, "{} || {}", operand(left), operand(right))
+            }
+            PointcutExpr::Not(inner) => write!(f, "!{}", operand(inner)),
+        }
+    }
+}
+
 /// Parse a pointcut expression.
 ///
 /// Supports:
 /// - `execution(pub fn *(..))`
 /// - `within(crate::module)`
 /// - `name("fetch_*")`
+/// - `annotated(get)`
+/// - `test()`
 /// - `expr1 && expr2`
 /// - `expr1 || expr2`
 /// - `!expr`
@@ -185,21 +353,21 @@ pub fn parse_pointcut(input: &str) -> Result<PointcutExpr, String> {
     let input = input.trim();
 
     // Handle NOT
-    if input.starts_with('!') {
-        let inner = parse_pointcut(&input[1..].trim())?;
+    if let Some(negated) = input.strip_prefix('!') {
+        let inner = parse_pointcut(negated.trim())?;
         return Ok(PointcutExpr::Not(Box::new(inner)));
     }
 
     // Handle AND/OR
     if let Some(pos) = find_operator(input, "&&") {
-        let left = parse_pointcut(&input[..pos].trim())?;
-        let right = parse_pointcut(&input[pos + 2..].trim())?;
+        let left = parse_pointcut(input[..pos].trim())?;
+        let right = parse_pointcut(input[pos + 2..].trim())?;
         return Ok(PointcutExpr::And(Box::new(left), Box::new(right)));
     }
 
     if let Some(pos) = find_operator(input, "||") {
-        let left = parse_pointcut(&input[..pos].trim())?;
-        let right = parse_pointcut(&input[pos + 2..].trim())?;
+        let left = parse_pointcut(input[..pos].trim())?;
+        let right = parse_pointcut(input[pos + 2..].trim())?;
         return Ok(PointcutExpr::Or(Box::new(left), Box::new(right)));
     }
 
@@ -211,6 +379,7 @@ pub fn parse_pointcut(input: &str) -> Result<PointcutExpr, String> {
     // Handle primitive patterns
     if input.starts_with("execution(") {
         let pattern = extract_pattern(input, "execution")?;
+        execution_pointcut(&split_async(&pattern).1)?;
         Ok(PointcutExpr::Execution(pattern))
     } else if input.starts_with("within(") {
         let pattern = extract_pattern(input, "within")?;
@@ -218,13 +387,44 @@ pub fn parse_pointcut(input: &str) -> Result<PointcutExpr, String> {
     } else if input.starts_with("name(") {
         let pattern = extract_pattern(input, "name")?;
         Ok(PointcutExpr::Name(pattern))
+    } else if input.starts_with("annotated(") {
+        let pattern = extract_pattern(input, "annotated")?;
+        Ok(PointcutExpr::Annotated(pattern))
+    } else if let Some(rest) = input.strip_prefix("test(") {
+        let pattern = rest
+            .strip_suffix(')')
+            .ok_or("Missing closing parenthesis")?;
+        if !pattern.trim().is_empty() {
+            return Err(format!("test() takes no pattern, got '{}'", pattern.trim()));
+        }
+        Ok(PointcutExpr::Test)
     } else {
         Err(format!("Unknown pointcut pattern: {}", input))
     }
 }
 
+/// Split the `async` keyword, which aspect-core's execution patterns do not
+/// have, from an execution pattern.
+fn split_async(pattern: &str) -> (bool, String) {
+    let mut is_async = false;
+    let rest: Vec<&str> = pattern
+        .split_whitespace()
+        .filter(|part| {
+            let keyword = *part == "async";
+            is_async |= keyword;
+            !keyword
+        })
+        .collect();
+    (is_async, rest.join(" "))
+}
+
+/// Parse an execution pattern with aspect-core's parser.
+fn execution_pointcut(pattern: &str) -> Result<Pointcut, String> {
+    Pointcut::parse(&format!("execution({})", pattern))
+}
+
 /// Find operator position outside of parentheses.
-fn find_operator(input: &str, operator: &str) -> Option<usize> {
+pub(crate) fn find_operator(input: &str, operator: &str) -> Option<usize> {
     let mut depth = 0;
     let chars: Vec<char> = input.chars().collect();
     let op_chars: Vec<char> = operator.chars().collect();
@@ -261,31 +461,86 @@ fn extract_pattern(input: &str, prefix: &str) -> Result<String, String> {
     Ok(input[start..end].trim().trim_matches('"').to_string())
 }
 
-/// Load registered aspects from the global registry.
-///
-/// This would integrate with aspect-runtime's AspectRegistry.
+/// Environment variable naming the registration manifest directory.
 ///
-/// # Example (Conceptual)
-///
-/// ```ignore
-/// use aspect_runtime::AspectRegistry;
+/// When it is set, every `#[advice]` expansion writes an entry into
+/// `$ASPECT_REGISTRY_DIR/<crate name>/`.
+pub const REGISTRY_DIR_ENV: &str = "ASPECT_REGISTRY_DIR";
+
+/// Registration manifest entry written by the `#[advice]` macro.
+#[derive(Debug, Deserialize)]
+struct ManifestEntry {
+    name: String,
+    pointcut: String,
+    advice: String,
+    order: i32,
+}
+
+/// Load the aspects registered by `#[advice]` from the manifest in
+/// `$ASPECT_REGISTRY_DIR`.
 ///
-/// pub fn load_from_registry() -> Vec<RegisteredAspect> {
-///     let registry = AspectRegistry::global();
-///     registry.list_aspects()
-///         .into_iter()
-///         .map(|a| RegisteredAspect {
-///             aspect_name: a.type_name,
-///             pointcut: a.pointcut,
-///             advice_type: a.advice_type,
-///             priority: a.priority,
-///         })
-///         .collect()
-/// }
-/// ```
+/// Returns nothing when the variable is unset. Unreadable entries are
+/// skipped; use [`load_registry_manifest`] to report them.
 pub fn load_from_registry() -> Vec<RegisteredAspect> {
-    // Placeholder - would load from aspect-runtime
-    Vec::new()
+    let Some(dir) = std::env::var_os(REGISTRY_DIR_ENV) else {
+        return Vec::new();
+    };
+    let dir = Path::new(&dir);
+
+    let mut aspects = Vec::new();
+    for crate_dir in read_dir_sorted(dir).unwrap_or_default() {
+        for path in read_dir_sorted(&crate_dir).unwrap_or_default() {
+            aspects.extend(read_manifest_entry(&path).ok());
+        }
+    }
+    aspects
+}
+
+/// Load the aspects registered by `#[advice]` in every crate of the
+/// manifest directory `dir`.
+///
+/// The runtime runs advice with a lower `order` first, so an aspect's
+/// priority is its negated order. Aspects are sorted by crate and file
+/// name, so the result does not depend on the order of expansion.
+pub fn load_registry_manifest(dir: &Path) -> Result<Vec<RegisteredAspect>, String> {
+    let mut aspects = Vec::new();
+    for crate_dir in read_dir_sorted(dir)? {
+        if crate_dir.is_dir() {
+            for path in read_dir_sorted(&crate_dir)? {
+                aspects.push(read_manifest_entry(&path)?);
+            }
+        }
+    }
+    Ok(aspects)
+}
+
+/// Entries of `dir`, sorted by name.
+fn read_dir_sorted(dir: &Path) -> Result<Vec<std::path::PathBuf>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut paths = entries
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("{}: {}", dir.display(), e))?;
+    paths.sort();
+    Ok(paths)
+}
+
+/// Read one manifest entry and check its pointcut.
+fn read_manifest_entry(path: &Path) -> Result<RegisteredAspect, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let entry: ManifestEntry =
+        serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    parse_pointcut(&entry.pointcut).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(RegisteredAspect {
+        aspect_name: entry.name,
+        pointcut: entry.pointcut,
+        advice_type: entry
+            .advice
+            .parse()
+            .map_err(|e| format!("{}: {}", path.display(), e))?,
+        priority: entry.order.saturating_neg(),
+    })
 }
 
 /// Match all functions against all registered aspects.
@@ -304,7 +559,7 @@ pub fn match_all(
     for function in functions {
         let matches = matcher.match_function(function);
         if !matches.is_empty() {
-            results.insert(function.name.clone(), matches);
+            results.insert(function.name.clone(), matches.into_vec());
         }
     }
 
@@ -314,7 +569,7 @@ pub fn match_all(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::SourceLocation;
+    use crate::types::{SourceLocation, Visibility};
 
     fn sample_function(name: &str, visibility: Visibility, module: &str) -> FunctionMetadata {
         // Extract simple name (last component after ::)
@@ -326,16 +581,47 @@ mod tests {
             module_path: module.to_string(),
             visibility,
             is_async: false,
+            is_closure: false,
             generics: vec![],
             return_type: "()".to_string(),
+            is_trait_method: false,
+            trait_name: None,
+            impl_type: None,
+            attributes: vec![],
             location: SourceLocation {
                 file: "test.rs".to_string(),
                 line: 1,
                 column: 1,
+                end_line: 1,
+                end_column: 1,
             },
         }
     }
 
+    #[test]
+    fn test_parse_hook() {
+        let hook = AdviceHook::parse(
+            AdviceType::Before,
+            "execution(pub fn *(..)) = crate::trace::enter",
+        )
+        .unwrap();
+        assert_eq!(hook.pointcut, "execution(pub fn *(..))");
+        assert_eq!(hook.path, "crate::trace::enter");
+        assert_eq!(hook.advice_type, AdviceType::Before);
+    }
+
+    #[test]
+    fn test_parse_hook_errors() {
+        assert!(AdviceHook::parse(AdviceType::After, "execution(pub fn *(..))").is_err());
+        assert!(AdviceHook::parse(AdviceType::After, "execution(pub fn *(..))=crate::").is_err());
+        assert!(AdviceHook::parse(AdviceType::After, "bogus(x)=crate::trace::exit").is_err());
+
+        let err =
+            AdviceHook::parse(AdviceType::Around, "execution(pub fn *(..))=crate::t").unwrap_err();
+        assert!(err.contains("around advice"));
+        assert!(AdviceHook::new(AdviceType::AfterError, "execution(fn *(..))", "t::e").is_err());
+    }
+
     #[test]
     fn test_parse_execution() {
         let expr = parse_pointcut("execution(pub fn *(..))").unwrap();
@@ -380,6 +666,57 @@ mod tests {
         assert_eq!(matches.len(), 0);
     }
 
+    #[test]
+    fn test_match_async_execution() {
+        let mut matcher = PointcutMatcher::new();
+        matcher.register(RegisteredAspect {
+            aspect_name: "AsyncLogger".to_string(),
+            pointcut: "execution(pub async fn *(..))".to_string(),
+            advice_type: AdviceType::Around,
+            priority: 0,
+        });
+
+        let sync_fn = sample_function("fetch", Visibility::Public, "crate::api");
+        let async_fn = FunctionMetadata {
+            is_async: true,
+            ..sync_fn.clone()
+        };
+
+        assert_eq!(matcher.match_function(&async_fn).len(), 1);
+        assert_eq!(matcher.match_function(&sync_fn).len(), 0);
+    }
+
+    #[test]
+    fn test_match_like_runtime() {
+        let matcher = PointcutMatcher::new();
+        let functions = [
+            sample_function("api::save_user", Visibility::Public, "crate::api"),
+            sample_function("api::save_order", Visibility::Crate, "crate::api"),
+            sample_function("admin::save_user", Visibility::Public, "crate::admin"),
+            sample_function("apis::load", Visibility::Public, "crate::apis"),
+        ];
+
+        for pointcut in [
+            "execution(pub fn *(..))",
+            "execution(pub(crate) fn save*(..))",
+            "execution(fn *_user(..)) && !within(crate::admin)",
+            "within(crate::api)",
+        ] {
+            let runtime = Pointcut::parse(pointcut).unwrap();
+            for function in &functions {
+                assert_eq!(
+                    matcher.matches_pointcut(function, pointcut),
+                    runtime.matches(&function.to_function_info()),
+                    "{} on {}",
+                    pointcut,
+                    function.name
+                );
+            }
+        }
+
+        assert!(parse_pointcut("execution(pub *(..))").is_err());
+    }
+
     #[test]
     fn test_match_within() {
         let mut matcher = PointcutMatcher::new();
@@ -419,6 +756,77 @@ mod tests {
         assert_eq!(matcher.match_function(&other_public).len(), 0);
     }
 
+    #[test]
+    fn test_match_annotated() {
+        let matcher = PointcutMatcher::new();
+        let mut handler = sample_function("api::list_users", Visibility::Public, "crate::api");
+        handler.attributes = vec!["actix_web::get(\"/users\")".to_string()];
+        let plain = sample_function("api::helper", Visibility::Public, "crate::api");
+
+        assert!(matches!(
+            parse_pointcut("annotated(get)").unwrap(),
+            PointcutExpr::Annotated(_)
+        ));
+        assert!(matcher.matches_pointcut(&handler, "annotated(get)"));
+        assert!(matcher.matches_pointcut(&handler, "annotated(actix_web::get)"));
+        assert!(!matcher.matches_pointcut(&plain, "annotated(get)"));
+        assert!(matcher.matches_pointcut(&plain, "within(crate::api) && !annotated(get)"));
+    }
+
+    #[test]
+    fn test_match_tests() {
+        let matcher = PointcutMatcher::new();
+        let mut unit = sample_function("tests::test_add", Visibility::Private, "crate::tests");
+        unit.attributes = vec!["test".to_string()];
+        let mut async_test =
+            sample_function("tests::test_fetch", Visibility::Private, "crate::tests");
+        async_test.attributes = vec!["tokio::test(flavor = \"multi_thread\")".to_string()];
+        let mut case = sample_function("tests::test_parse", Visibility::Private, "crate::tests");
+        case.attributes = vec!["test_case(\"1\")".to_string()];
+        let helper = sample_function("tests::fixture", Visibility::Private, "crate::tests");
+
+        assert_eq!(parse_pointcut(" test( ) ").unwrap(), PointcutExpr::Test);
+        assert!(parse_pointcut("test(foo)").is_err());
+        for test in [&unit, &async_test, &case] {
+            assert!(matcher.matches_pointcut(test, "test()"), "{}", test.name);
+        }
+        assert!(!matcher.matches_pointcut(&helper, "test()"));
+        assert!(matcher.matches_pointcut(&helper, "within(crate::tests) && !test()"));
+    }
+
+    #[test]
+    fn test_mismatch_reason() {
+        let matcher = PointcutMatcher::new();
+        let public = sample_function("api::get", Visibility::Public, "crate::api");
+        let private = sample_function("api::check", Visibility::Private, "crate::api");
+        let internal = sample_function("internal::get", Visibility::Public, "crate::internal");
+
+        let pointcut = "execution(pub fn *(..)) && !within(crate::internal)";
+        assert_eq!(matcher.mismatch_reason(&public, pointcut), None);
+        assert_eq!(
+            matcher.mismatch_reason(&private, pointcut).as_deref(),
+            Some("does not match execution(pub fn *(..))")
+        );
+        assert_eq!(
+            matcher.mismatch_reason(&internal, pointcut).as_deref(),
+            Some("excluded by !within(crate::internal)")
+        );
+        assert_eq!(
+            matcher
+                .mismatch_reason(&internal, "name(put) || within(crate::api)")
+                .as_deref(),
+            Some("does not match name(put), and does not match within(crate::api)")
+        );
+    }
+
+    #[test]
+    fn test_display_pointcut() {
+        let expr = parse_pointcut("within(a) && !(name(b) || annotated(c))").unwrap();
+        assert_eq!(expr.to_string(), "within(a) && !(name(b) || annotated(c))");
+        let expr = parse_pointcut("test() && within(a)").unwrap();
+        assert_eq!(expr.to_string(), "test() && within(a)");
+    }
+
     #[test]
     fn test_priority_ordering() {
         let mut matcher = PointcutMatcher::new();
@@ -443,6 +851,43 @@ mod tests {
         assert_eq!(matches[1].aspect, "Low");
     }
 
+    #[test]
+    fn test_match_pointcuts() {
+        let functions: Vec<_> = (0..100)
+            .map(|i| {
+                let module = if i % 2 == 0 {
+                    "crate::api"
+                } else {
+                    "crate::internal"
+                };
+                sample_function(&format!("f{}", i), Visibility::Public, module)
+            })
+            .collect();
+        let pointcuts = vec![
+            "within(crate::internal)".to_string(),
+            "not a pointcut".to_string(),
+            "name(f1*)".to_string(),
+        ];
+
+        let matcher = PointcutMatcher::new();
+        let matches = matcher.match_pointcuts(&functions, &pointcuts);
+        let sequential: Vec<_> = pointcuts
+            .iter()
+            .flat_map(|pointcut| {
+                functions
+                    .iter()
+                    .filter(|function| matcher.matches_pointcut(function, pointcut))
+                    .map(move |function| (function.name.clone(), pointcut.clone()))
+            })
+            .collect();
+        let matches: Vec<_> = matches
+            .into_iter()
+            .map(|(function, pointcut)| (function.name, pointcut))
+            .collect();
+        assert_eq!(matches.len(), 50 + 11);
+        assert_eq!(matches, sequential);
+    }
+
     #[test]
     fn test_extract_pattern() {
         let pattern = extract_pattern("execution(pub fn *(..))", "execution").unwrap();
@@ -463,4 +908,60 @@ mod tests {
         let input = "execution(pub && fn) && within(a)";
         assert_eq!(find_operator(input, "&&"), Some(21));
     }
+
+    #[test]
+    fn test_load_registry_manifest() {
+        let dir = std::env::temp_dir().join(format!("aspect-registry-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("app")).unwrap();
+        fs::create_dir_all(dir.join("audit")).unwrap();
+
+        // As written by the #[advice] macro
+        fs::write(
+            dir.join("app/api_logger-0000000000000001.json"),
+            "{\"name\":\"api_logger\",\"pointcut\":\"execution(pub fn *(..)) && \
+             within(crate::api)\",\"advice\":\"around\",\"order\":10}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("audit/on_error-0000000000000002.json"),
+            r#"{"name":"on_error","pointcut":"name(\"save_*\")","advice":"after_error","order":0}"#,
+        )
+        .unwrap();
+
+        let aspects = load_registry_manifest(&dir).unwrap();
+        assert_eq!(aspects.len(), 2);
+        assert_eq!(aspects[0].aspect_name, "api_logger");
+        assert_eq!(aspects[0].advice_type, AdviceType::Around);
+        assert_eq!(aspects[0].priority, -10);
+        assert_eq!(aspects[1].pointcut, "name(\"save_*\")");
+        assert_eq!(aspects[1].advice_type, AdviceType::AfterError);
+
+        // Registry entries are exchanged as JSON as well
+        let json = serde_json::to_value(&aspects[1]).unwrap();
+        assert_eq!(json["advice_type"], "after_error");
+        let back: RegisteredAspect = serde_json::from_value(json).unwrap();
+        assert_eq!(back, aspects[1]);
+
+        let matches = match_all(
+            &[sample_function(
+                "crate::api::save_user",
+                Visibility::Public,
+                "crate::api",
+            )],
+            &aspects,
+        );
+        assert_eq!(matches["crate::api::save_user"].len(), 2);
+
+        fs::write(
+            dir.join("app/bad-0000000000000003.json"),
+            r#"{"name":"bad"}"#,
+        )
+        .unwrap();
+        let err = load_registry_manifest(&dir).unwrap_err();
+        assert!(err.contains("bad-0000000000000003.json"), "{}", err);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(load_registry_manifest(&dir).is_err());
+    }
 }
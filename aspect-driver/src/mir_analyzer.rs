@@ -6,32 +6,59 @@
 // without requiring per-function annotations.
 
 // Import rustc internal APIs
+extern crate rustc_data_structures;
 extern crate rustc_middle;
 extern crate rustc_hir;
 extern crate rustc_span;
 
-use rustc_middle::ty::TyCtxt;
-use rustc_middle::mir::Body;
-use rustc_hir::def_id::LocalDefId;
+use rustc_data_structures::sync::par_map;
+use rustc_middle::ty::{self, Ty, TyCtxt};
+use rustc_middle::ty::print::with_no_trimmed_paths;
+use rustc_middle::mir::visit::{MutatingUseContext, NonMutatingUseContext, PlaceContext, Visitor};
+use rustc_middle::mir::{Body, Location, Place, ProjectionElem};
+use rustc_hir::def_id::{DefId, LocalDefId, CRATE_DEF_ID, LOCAL_CRATE};
+use rustc_span::symbol::{kw, sym};
+use rustc_span::{FileName, Span};
 use std::collections::HashMap;
 
-use crate::types::{FunctionMetadata, SourceLocation, Visibility};
+use crate::cache::{content_hash, AnalysisCache};
+use crate::field::{FieldAccess, FieldAccessKind, FieldPattern};
+use crate::types::{
+    normalize_attribute, normalize_type_name, FunctionMetadata, GenericParam, SourceLocation,
+    Visibility,
+};
 
 /// Analyzes MIR to extract function metadata for aspect weaving
 pub struct MirAnalyzer<'tcx> {
     tcx: TyCtxt<'tcx>,
     verbose: bool,
+    /// Also extract closures
+    closures: bool,
 }
 
 impl<'tcx> MirAnalyzer<'tcx> {
     pub fn new(tcx: TyCtxt<'tcx>, verbose: bool) -> Self {
-        Self { tcx, verbose }
+        Self {
+            tcx,
+            verbose,
+            closures: false,
+        }
+    }
+
+    /// Also extract closures, which are left out by default
+    pub fn with_closures(mut self, closures: bool) -> Self {
+        self.closures = closures;
+        self
     }
 
     /// Extract all function metadata from the crate
     ///
-    /// This iterates through all items in the crate and extracts
-    /// metadata for functions that can have aspects applied.
+    /// This iterates through all definitions in the crate and extracts
+    /// metadata for functions and methods that can have aspects applied,
+    /// i.e. those with a body.
+    ///
+    /// Functions are extracted on rustc's threads, so in parallel when the
+    /// compiler runs with `-Z threads=N`; the order does not change.
     pub fn extract_all_functions(&self) -> Vec<FunctionMetadata> {
         let mut functions = Vec::new();
 
@@ -40,21 +67,79 @@ impl<'tcx> MirAnalyzer<'tcx> {
             println!("Extracting function metadata from compiled code...");
         }
 
-        // Get all local definition IDs in the crate using the map
-        let hir_map = self.tcx.hir();
+        let extracted: Vec<Option<FunctionMetadata>> =
+            par_map(self.function_def_ids(), |def_id| self.extract_function_metadata(def_id));
+        for metadata in extracted.into_iter().flatten() {
+            if self.verbose {
+                println!("  Found function: {}", metadata.name);
+            }
+            functions.push(metadata);
+        }
+
+        if self.verbose {
+            println!("Total functions found: {}", functions.len());
+        }
 
-        // Iterate through all items
-        for item_id in hir_map.items() {
-            let item = hir_map.item(item_id);
+        functions
+    }
 
-            // Check if this is a function (struct variant syntax)
-            if matches!(item.kind, rustc_hir::ItemKind::Fn { .. }) {
-                if let Some(metadata) = self.extract_function_metadata(item_id.owner_id.def_id) {
-                    if self.verbose {
-                        println!("  Found function: {}", metadata.name);
+    /// Extract all function metadata, reusing `cache` for functions whose
+    /// source file is unchanged
+    ///
+    /// Only the file a function is defined in is checked, so metadata
+    /// derived from other files (e.g. the path of a trait moved elsewhere)
+    /// is refreshed when the function's own file changes.
+    pub fn extract_all_functions_cached(&self, cache: &mut AnalysisCache) -> Vec<FunctionMetadata> {
+        let source_map = self.tcx.sess.source_map();
+        let mut file_hashes = HashMap::new();
+        let mut functions = Vec::new();
+
+        if self.verbose {
+            println!("=== MIR Analysis ===");
+            println!("Extracting function metadata from compiled code (cached)...");
+        }
+
+        // Look up every function, then extract the ones not cached like
+        // `extract_all_functions` does
+        let lookups: Vec<_> = self
+            .function_def_ids()
+            .into_iter()
+            .map(|def_id| {
+                let def_path = self.tcx.def_path_str(def_id.to_def_id());
+                let file = source_map.lookup_source_file(self.tcx.def_span(def_id).lo());
+                let file_hash = *file_hashes.entry(file.start_pos).or_insert_with(|| {
+                    file.src.as_deref().map(|src| content_hash(src.as_bytes()))
+                });
+                let cached = file_hash.and_then(|hash| cache.get(&def_path, hash));
+                (def_id, def_path, file_hash, cached)
+            })
+            .collect();
+        let misses: Vec<LocalDefId> = lookups
+            .iter()
+            .filter(|(_, _, _, cached)| cached.is_none())
+            .map(|(def_id, ..)| *def_id)
+            .collect();
+        let extracted: Vec<Option<FunctionMetadata>> =
+            par_map(misses, |def_id| self.extract_function_metadata(def_id));
+        let mut extracted = extracted.into_iter();
+
+        for (_, def_path, file_hash, cached) in lookups {
+            let metadata = match cached {
+                Some(metadata) => Some(metadata),
+                None => {
+                    let metadata = extracted.next().flatten();
+                    if let (Some(metadata), Some(hash)) = (&metadata, file_hash) {
+                        cache.insert(&def_path, hash, metadata.clone());
                     }
-                    functions.push(metadata);
+                    metadata
+                }
+            };
+
+            if let Some(metadata) = metadata {
+                if self.verbose {
+                    println!("  Found function: {}", metadata.name);
                 }
+                functions.push(metadata);
             }
         }
 
@@ -65,10 +150,45 @@ impl<'tcx> MirAnalyzer<'tcx> {
         functions
     }
 
+    /// Free functions, functions nested in other functions, inherent and
+    /// trait impl methods, and default trait methods: every function of
+    /// the crate with a body, and closures if enabled
+    fn function_def_ids(&self) -> Vec<LocalDefId> {
+        let tcx = self.tcx;
+        let items = tcx.hir_crate_items(());
+        let mut def_ids: Vec<LocalDefId> = items
+            .definitions()
+            .filter(|&def_id| {
+                matches!(
+                    tcx.def_kind(def_id),
+                    rustc_hir::def::DefKind::Fn | rustc_hir::def::DefKind::AssocFn
+                ) && tcx.hir_node_by_def_id(def_id).body_id().is_some()
+            })
+            .collect();
+
+        // Closures are not HIR owners, but they own their body. The bodies
+        // of async functions and blocks are coroutines, not closures.
+        if self.closures {
+            def_ids.extend(items.body_owners().filter(|&def_id| {
+                tcx.def_kind(def_id) == rustc_hir::def::DefKind::Closure
+                    && matches!(
+                        tcx.type_of(def_id).instantiate_identity().kind(),
+                        ty::Closure(..)
+                    )
+            }));
+        }
+
+        def_ids
+    }
+
     /// Extract metadata for a single function
-    fn extract_function_metadata(&self, def_id: LocalDefId) -> Option<FunctionMetadata> {
+    pub fn extract_function_metadata(&self, def_id: LocalDefId) -> Option<FunctionMetadata> {
         let tcx = self.tcx;
 
+        if tcx.def_kind(def_id) == rustc_hir::def::DefKind::Closure {
+            return self.extract_closure_metadata(def_id);
+        }
+
         // Get the full definition path
         let def_path = tcx.def_path_str(def_id.to_def_id());
 
@@ -87,8 +207,20 @@ impl<'tcx> MirAnalyzer<'tcx> {
         // Get source location
         let location = self.extract_source_location(def_id);
 
-        // Get return type (simplified for now)
-        let return_type = "()".to_string(); // TODO: Extract actual return type
+        // Get return type
+        let return_type = self.extract_return_type(def_id, is_async);
+
+        // Get generic parameters
+        let generics = self.extract_generics(def_id);
+
+        // Get the trait and impl a method belongs to
+        let (is_trait_method, trait_name, impl_type) = self.extract_method_context(def_id);
+
+        // Get attributes, which `#[test]` is no longer among
+        let mut attributes = self.extract_attributes(def_id);
+        if self.is_test(def_id) {
+            attributes.push("test".to_string());
+        }
 
         Some(FunctionMetadata {
             name: def_path,
@@ -96,93 +228,403 @@ impl<'tcx> MirAnalyzer<'tcx> {
             module_path,
             visibility,
             is_async,
-            generics: vec![], // TODO: Extract generic parameters
+            is_closure: false,
+            generics,
             return_type,
+            is_trait_method,
+            trait_name,
+            impl_type,
+            attributes,
             location,
         })
     }
 
+    /// Extract metadata for a closure
+    ///
+    /// A closure is named after the function it is in, is private, and
+    /// has no generics of its own.
+    fn extract_closure_metadata(&self, def_id: LocalDefId) -> Option<FunctionMetadata> {
+        let tcx = self.tcx;
+        let ty::Closure(_, args) = *tcx.type_of(def_id).instantiate_identity().kind() else {
+            return None;
+        };
+        let output = args.as_closure().sig().output();
+        let output = tcx.erase_regions(tcx.instantiate_bound_regions_with_erased(output));
+
+        let name = tcx.def_path_str(def_id.to_def_id());
+        let simple_name = name.rsplit("::").next().unwrap_or(&name).to_string();
+
+        Some(FunctionMetadata {
+            name,
+            simple_name,
+            module_path: self.extract_module_path(def_id),
+            visibility: Visibility::Private,
+            is_async: false,
+            is_closure: true,
+            generics: Vec::new(),
+            return_type: normalize_type_name(&with_no_trimmed_paths!(output.to_string())),
+            is_trait_method: false,
+            trait_name: None,
+            impl_type: None,
+            attributes: self.extract_attributes(def_id),
+            location: self.extract_source_location(def_id),
+        })
+    }
+
+    /// Whether a function is a test
+    ///
+    /// Built with `--test`, `#[test]` (and the `#[tokio::test]`-like
+    /// macros expanding to it) leaves the function without the attribute,
+    /// next to a const of the same name marked `#[rustc_test_marker]` that
+    /// describes the test to the harness.
+    fn is_test(&self, def_id: LocalDefId) -> bool {
+        use rustc_hir::def::DefKind;
+
+        let tcx = self.tcx;
+        let name = tcx.item_name(def_id.to_def_id());
+        tcx.hir_module_items(tcx.parent_module_from_def_id(def_id))
+            .definitions()
+            .any(|item| {
+                tcx.def_kind(item) == DefKind::Const
+                    && tcx.item_name(item.to_def_id()) == name
+                    && tcx.has_attr(item, sym::rustc_test_marker)
+            })
+    }
+
     /// Extract the module path for a definition
+    ///
+    /// This is the module the definition is in, so methods, nested
+    /// functions and closures are in the module of their impl block or
+    /// function.
     fn extract_module_path(&self, def_id: LocalDefId) -> String {
-        let def_path = self.tcx.def_path(def_id.to_def_id());
-        let mut parts = Vec::new();
-
-        for data in def_path.data.iter() {
-            // Use Debug formatting as a fallback for extracting the name
-            let name = format!("{:?}", data.data);
-            // Extract just the name part (strip enum variant syntax)
-            let clean_name = name.split('(')
-                .next()
-                .unwrap_or(&name)
-                .trim()
-                .to_string();
-            if !clean_name.is_empty() {
-                parts.push(clean_name);
-            }
-        }
-
-        if parts.is_empty() {
+        let module = self.tcx.parent_module_from_def_id(def_id).to_def_id();
+        if module == CRATE_DEF_ID.to_def_id() {
             "crate".to_string()
         } else {
-            // Remove the last part (function name) to get module path
-            parts.pop();
-            if parts.is_empty() {
-                "crate".to_string()
-            } else {
-                format!("crate::{}", parts.join("::"))
-            }
+            with_no_trimmed_paths!(format!("crate::{}", self.tcx.def_path_str(module)))
         }
     }
 
     /// Extract visibility information
+    ///
+    /// rustc only records the module an item is visible in, so visibilities
+    /// meaning the same are not told apart: `pub(self)` is private,
+    /// `pub(in crate)` is `pub(crate)`, and an item of the crate root
+    /// visible in the crate root is private.
     fn extract_visibility(&self, def_id: LocalDefId) -> Visibility {
-        let vis = self.tcx.visibility(def_id);
+        let tcx = self.tcx;
+        let module = match tcx.visibility(def_id) {
+            ty::Visibility::Public => return Visibility::Public,
+            ty::Visibility::Restricted(module) => module,
+        };
 
-        if vis.is_public() {
-            Visibility::Public
-        } else {
+        let parent = tcx.parent_module_from_def_id(def_id).to_def_id();
+        if module == parent {
             Visibility::Private
+        } else if module == CRATE_DEF_ID.to_def_id() {
+            Visibility::Crate
+        } else if parent
+            .as_local()
+            .is_some_and(|parent| tcx.parent_module_from_def_id(parent).to_def_id() == module)
+        {
+            Visibility::Super
+        } else {
+            Visibility::Restricted(with_no_trimmed_paths!(format!(
+                "crate::{}",
+                tcx.def_path_str(module)
+            )))
         }
     }
 
     /// Check if a function is async
+    ///
+    /// `async fn` is lowered to a function returning a coroutine, so the
+    /// signature alone does not tell; the asyncness recorded in the HIR
+    /// header does. Functions returning `impl Future` are not async.
     fn is_async_fn(&self, def_id: LocalDefId) -> bool {
-        // Check if this is an async function using the asyncness from DefKind
         use rustc_hir::def::DefKind;
 
-        // Get the definition kind
         let def_kind = self.tcx.def_kind(def_id);
-
-        // Check if it's a function and examine its signature
-        if matches!(def_kind, DefKind::Fn | DefKind::AssocFn) {
-            // Get the function signature - asyncness is encoded in the ABI
-            let _fn_sig = self.tcx.fn_sig(def_id);
-            // For async functions, the ABI will be different
-            // For now, return false as a conservative default
-            // TODO: Properly detect async functions from signature or HIR
+        if !matches!(def_kind, DefKind::Fn | DefKind::AssocFn) {
             return false;
         }
 
-        false
+        if let Some(sig) = self.tcx.hir_node_by_def_id(def_id).fn_sig() {
+            return sig.header.is_async();
+        }
+
+        // No HIR signature (e.g. a required trait method): fall back to
+        // the asyncness query
+        self.tcx.asyncness(def_id).is_async()
+    }
+
+    /// Render the return type of a function
+    ///
+    /// Paths are printed in full rather than trimmed to what is in scope,
+    /// and lifetimes are erased, so the rendering does not depend on the
+    /// surrounding code. For async functions this is the awaited type
+    /// rather than the opaque future.
+    fn extract_return_type(&self, def_id: LocalDefId, is_async: bool) -> String {
+        let tcx = self.tcx;
+        let sig = tcx.fn_sig(def_id).instantiate_identity();
+        let output = tcx.erase_regions(tcx.instantiate_bound_regions_with_erased(sig.output()));
+
+        let output = if is_async {
+            self.future_output(output).unwrap_or(output)
+        } else {
+            output
+        };
+
+        normalize_type_name(&with_no_trimmed_paths!(output.to_string()))
+    }
+
+    /// The `Output` of the opaque future returned by an async function
+    fn future_output(&self, ty: Ty<'tcx>) -> Option<Ty<'tcx>> {
+        let tcx = self.tcx;
+        let ty::Alias(ty::Opaque, alias) = ty.kind() else {
+            return None;
+        };
+        let future_output = tcx.lang_items().future_output()?;
+
+        tcx.explicit_item_bounds(alias.def_id)
+            .iter_identity_copied()
+            .find_map(|(clause, _)| {
+                let projection = clause.as_projection_clause()?.skip_binder();
+                (projection.projection_term.def_id == future_output)
+                    .then(|| projection.term.as_type())
+                    .flatten()
+            })
+            .map(|output| tcx.erase_regions(output))
+    }
+
+    /// Extract the type parameters of a function and their bounds
+    ///
+    /// Parameters of the enclosing impl or trait come first, as they are
+    /// numbered by rustc; the `Self` of traits and the anonymous
+    /// parameters of `impl Trait` arguments are left out. The implicit
+    /// `Sized` bound is not listed, and `?Sized` is listed where it
+    /// applies, matching how bounds are written in source.
+    fn extract_generics(&self, def_id: LocalDefId) -> Vec<GenericParam> {
+        let tcx = self.tcx;
+
+        let mut params = Vec::new();
+        let mut next = Some(def_id.to_def_id());
+        while let Some(owner) = next {
+            let generics = tcx.generics_of(owner);
+            params.extend(generics.own_params.iter().filter(|param| {
+                matches!(param.kind, ty::GenericParamDefKind::Type { synthetic: false, .. })
+                    && param.name != kw::SelfUpper
+            }));
+            next = generics.parent;
+        }
+        params.sort_by_key(|param| param.index);
+
+        let predicates = tcx.predicates_of(def_id).instantiate_identity(tcx).predicates;
+        let sized = tcx.lang_items().sized_trait();
+
+        params
+            .into_iter()
+            .map(|param| {
+                let is_param =
+                    |ty: Ty<'tcx>| matches!(ty.kind(), ty::Param(p) if p.index == param.index);
+                let mut bounds = Vec::new();
+                let mut is_sized = false;
+
+                for clause in &predicates {
+                    if let Some(predicate) = clause.as_trait_clause() {
+                        let predicate = predicate.skip_binder();
+                        if !is_param(predicate.self_ty()) {
+                            continue;
+                        }
+                        if Some(predicate.def_id()) == sized {
+                            is_sized = true;
+                            continue;
+                        }
+                        bounds.push(self.render_trait_bound(predicate.trait_ref));
+                    } else if let Some(predicate) = clause.as_type_outlives_clause() {
+                        let outlives = predicate.skip_binder();
+                        if is_param(outlives.0) {
+                            bounds.push(outlives.1.to_string());
+                        }
+                    }
+                }
+                if !is_sized {
+                    bounds.push("?Sized".to_string());
+                }
+
+                GenericParam {
+                    name: param.name.to_string(),
+                    bounds,
+                }
+            })
+            .collect()
+    }
+
+    /// Render a trait bound without its `Self` type
+    fn render_trait_bound(&self, trait_ref: ty::TraitRef<'tcx>) -> String {
+        let rendered = with_no_trimmed_paths!(trait_ref.print_only_trait_path().to_string());
+        normalize_type_name(&rendered)
+    }
+
+    /// Extract whether a function is a trait method, its trait, and the
+    /// self type of its impl block
+    ///
+    /// Default methods are found with `trait_of_item`; methods in impl
+    /// blocks through their impl, which names the trait for trait impls.
+    fn extract_method_context(
+        &self,
+        def_id: LocalDefId,
+    ) -> (bool, Option<String>, Option<String>) {
+        let tcx = self.tcx;
+        let def_id = def_id.to_def_id();
+        let render =
+            |def_id: DefId| normalize_type_name(&with_no_trimmed_paths!(tcx.def_path_str(def_id)));
+
+        if let Some(trait_def_id) = tcx.trait_of_item(def_id) {
+            return (true, Some(render(trait_def_id)), None);
+        }
+
+        let Some(impl_def_id) = tcx.impl_of_method(def_id) else {
+            return (false, None, None);
+        };
+        let self_ty = tcx.type_of(impl_def_id).instantiate_identity();
+        let impl_type = normalize_type_name(&with_no_trimmed_paths!(self_ty.to_string()));
+        let trait_name = tcx.trait_id_of_impl(impl_def_id).map(render);
+
+        (trait_name.is_some(), trait_name, Some(impl_type))
+    }
+
+    /// Extract the attributes of a function as written in source
+    ///
+    /// Doc comments are left out. Attribute macros have been expanded by
+    /// now, so only inert attributes (`inline`, `must_use`, tool and
+    /// helper attributes) remain.
+    fn extract_attributes(&self, def_id: LocalDefId) -> Vec<String> {
+        let tcx = self.tcx;
+        let source_map = tcx.sess.source_map();
+
+        tcx.hir()
+            .attrs(tcx.local_def_id_to_hir_id(def_id))
+            .iter()
+            .filter(|attr| !attr.is_doc_comment() && !attr.has_name(sym::doc))
+            .filter_map(|attr| source_map.span_to_snippet(attr.span).ok())
+            .map(|snippet| normalize_attribute(&snippet))
+            .collect()
+    }
+
+    /// Extract the reads and writes of struct fields selected by `patterns`
+    ///
+    /// Every body of the crate is searched, including those of closures
+    /// and async blocks, whose accesses are attributed to the closure.
+    /// Accesses are found in optimized MIR: in optimized builds, a struct
+    /// held in a local may have been split into its fields, and accesses
+    /// to it are no longer seen. Writing or mutably borrowing `a.b.c`
+    /// sets both `b` and `c`.
+    pub fn extract_field_accesses(&self, patterns: &[FieldPattern]) -> Vec<FieldAccess> {
+        use rustc_hir::def::DefKind;
+
+        let tcx = self.tcx;
+        let mut accesses = Vec::new();
+        if patterns.is_empty() {
+            return accesses;
+        }
+
+        for def_id in tcx.hir_crate_items(()).body_owners() {
+            if !matches!(tcx.def_kind(def_id), DefKind::Fn | DefKind::AssocFn | DefKind::Closure) {
+                continue;
+            }
+
+            let body = tcx.optimized_mir(def_id);
+            let mut visitor = FieldAccessVisitor {
+                tcx,
+                body,
+                patterns,
+                found: Vec::new(),
+            };
+            visitor.visit_body(body);
+
+            let function = tcx.def_path_str(def_id.to_def_id());
+            let mut found: Vec<FieldAccess> = visitor
+                .found
+                .into_iter()
+                .map(|(kind, struct_path, field, span)| FieldAccess {
+                    kind,
+                    struct_path,
+                    field,
+                    function: function.clone(),
+                    location: self.span_location(span),
+                })
+                .collect();
+            // One access in the source may be several in MIR
+            found.sort_by(|a, b| {
+                let key = |access: &FieldAccess| {
+                    let location = &access.location;
+                    (location.line, location.column, access.kind, access.to_string())
+                };
+                key(a).cmp(&key(b))
+            });
+            found.dedup();
+
+            if self.verbose {
+                for access in &found {
+                    println!("  Field access: {} in {}", access, access.function);
+                }
+            }
+            accesses.extend(found);
+        }
+
+        accesses
+    }
+
+    /// The source files of the crate, named as in function locations
+    ///
+    /// Files included with `include!` are listed; files of other crates
+    /// and sources generated by the compiler are not.
+    pub fn source_files(&self) -> Vec<String> {
+        self.tcx
+            .sess
+            .source_map()
+            .files()
+            .iter()
+            .filter(|file| file.cnum == LOCAL_CRATE && matches!(file.name, FileName::Real(_)))
+            .map(|file| file.name.prefer_remapped_unconditionaly().to_string())
+            .collect()
     }
 
     /// Extract source location
+    ///
+    /// The span covers the whole function, from its visibility or `fn` to
+    /// the end of its body; columns count characters from 1, the end one
+    /// being just past the closing brace.
     fn extract_source_location(&self, def_id: LocalDefId) -> SourceLocation {
-        let span = self.tcx.def_span(def_id);
+        let hir_id = self.tcx.local_def_id_to_hir_id(def_id);
+        // Functions generated by macros are located at the macro call
+        self.span_location(self.tcx.hir().span_with_body(hir_id))
+    }
+
+    /// The source location of a span, at the macro call for spans of
+    /// macro expansions
+    fn span_location(&self, span: Span) -> SourceLocation {
+        let span = span.source_callsite();
         let source_map = self.tcx.sess.source_map();
 
-        if let Ok(loc) = source_map.lookup_line(span.lo()) {
-            let file_name = loc.sf.name.prefer_remapped_unconditionaly().to_string();
+        if !span.is_dummy() {
+            let start = source_map.lookup_char_pos(span.lo());
+            let end = source_map.lookup_char_pos(span.hi());
             SourceLocation {
-                file: file_name,
-                line: loc.line + 1, // Convert to 1-indexed
-                column: 0, // TODO: Extract actual column
+                file: start.file.name.prefer_remapped_unconditionaly().to_string(),
+                line: start.line,
+                column: start.col.0 + 1,
+                end_line: end.line,
+                end_column: end.col.0 + 1,
             }
         } else {
             SourceLocation {
                 file: "<unknown>".to_string(),
                 line: 0,
                 column: 0,
+                end_line: 0,
+                end_column: 0,
             }
         }
     }
@@ -205,48 +647,72 @@ impl<'tcx> MirAnalyzer<'tcx> {
     }
 }
 
-/// Statistics about the analysis
-#[derive(Debug, Clone, Default)]
-pub struct AnalysisStats {
-    pub total_functions: usize,
-    pub public_functions: usize,
-    pub private_functions: usize,
-    pub async_functions: usize,
-    pub matched_functions: usize,
+/// Collects the struct field accesses of a body selected by field patterns,
+/// as (kind, struct path, field, span)
+struct FieldAccessVisitor<'a, 'tcx> {
+    tcx: TyCtxt<'tcx>,
+    body: &'a Body<'tcx>,
+    patterns: &'a [FieldPattern],
+    found: Vec<(FieldAccessKind, String, String, Span)>,
 }
 
-impl AnalysisStats {
-    pub fn from_functions(functions: &[FunctionMetadata]) -> Self {
-        let total_functions = functions.len();
-        let public_functions = functions.iter()
-            .filter(|f| f.visibility == Visibility::Public)
-            .count();
-        let private_functions = total_functions - public_functions;
-        let async_functions = functions.iter()
-            .filter(|f| f.is_async)
-            .count();
+impl<'tcx> Visitor<'tcx> for FieldAccessVisitor<'_, 'tcx> {
+    fn visit_place(&mut self, place: &Place<'tcx>, context: PlaceContext, location: Location) {
+        // Drops, retags, fake borrows and `let _ = x.f` do not access the
+        // field in the source
+        let kind = match context {
+            PlaceContext::NonMutatingUse(
+                NonMutatingUseContext::Inspect
+                | NonMutatingUseContext::Copy
+                | NonMutatingUseContext::Move
+                | NonMutatingUseContext::SharedBorrow
+                | NonMutatingUseContext::RawBorrow,
+            ) => FieldAccessKind::Get,
+            PlaceContext::MutatingUse(
+                MutatingUseContext::Store
+                | MutatingUseContext::AsmOutput
+                | MutatingUseContext::Call
+                | MutatingUseContext::Yield
+                | MutatingUseContext::SetDiscriminant
+                | MutatingUseContext::Deinit
+                | MutatingUseContext::Borrow
+                | MutatingUseContext::RawBorrow,
+            ) => FieldAccessKind::Set,
+            _ => return,
+        };
 
-        Self {
-            total_functions,
-            public_functions,
-            private_functions,
-            async_functions,
-            matched_functions: 0,
-        }
-    }
+        let tcx = self.tcx;
+        for (base, elem) in place.as_ref().iter_projections() {
+            let ProjectionElem::Field(field, _) = elem else {
+                continue;
+            };
+            // Fields of enum variants and of closure captures are not
+            // struct fields
+            let ty::Adt(adt, _) = base.ty(self.body, tcx).ty.kind() else {
+                continue;
+            };
+            if !adt.is_struct() {
+                continue;
+            }
 
-    pub fn print_summary(&self) {
-        println!("\n=== Analysis Statistics ===");
-        println!("Total functions: {}", self.total_functions);
-        println!("  Public: {}", self.public_functions);
-        println!("  Private: {}", self.private_functions);
-        println!("  Async: {}", self.async_functions);
-        if self.matched_functions > 0 {
-            println!("  Matched by pointcuts: {}", self.matched_functions);
+            let struct_path =
+                normalize_type_name(&with_no_trimmed_paths!(tcx.def_path_str(adt.did())));
+            let field = adt.non_enum_variant().fields[field].name.to_string();
+            if self
+                .patterns
+                .iter()
+                .any(|pattern| pattern.matches_field(kind, &struct_path, &field))
+            {
+                let span = self.body.source_info(location).span;
+                self.found.push((kind, struct_path, field, span));
+            }
         }
     }
 }
 
+// Statistics are shared with the analysis reports
+pub use crate::report::AnalysisStats;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -260,12 +726,19 @@ mod tests {
                 module_path: "crate".to_string(),
                 visibility: Visibility::Public,
                 is_async: false,
+                is_closure: false,
                 generics: vec![],
                 return_type: "()".to_string(),
+                is_trait_method: false,
+                trait_name: None,
+                impl_type: None,
+                attributes: vec![],
                 location: SourceLocation {
                     file: "test.rs".to_string(),
                     line: 1,
                     column: 0,
+                    end_line: 1,
+                    end_column: 0,
                 },
             },
         ];
@@ -0,0 +1,331 @@
+//! Custom analysis passes.
+//!
+//! An [`AnalysisPass`] runs over the results of the analysis, the
+//! functions found, their matches and the weaving plan, and reports
+//! [`Finding`]s: lint-like checks ("every `pub fn` of `api` is traced"),
+//! or weaving policies ("nothing in `crypto` is advised") that fail the
+//! build with an error.
+//!
+//! Tools embedding aspect-driver register passes on a [`PassRegistry`].
+//! The compiler driver runs external programs as passes instead, with
+//! `--aspect-pass <command>`: a [`CommandPass`] writes the [`PassInput`]
+//! as JSON to the program's standard input and reads its findings, a JSON
+//! array, from its standard output:
+//!
+//! ```json
+//! [{ "severity": "error", "function": "crypto::sign", "message": "advised" }]
+//! ```
+
+use std::fmt;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+
+use crate::plan::WeavingPlan;
+use crate::report::AnalysisReport;
+
+/// How serious a finding is; errors fail the build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// Something a pass reports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finding {
+    /// Name of the pass, set by [`PassRegistry::run`]
+    #[serde(default)]
+    pub pass: String,
+
+    /// How serious it is
+    pub severity: Severity,
+
+    /// Fully qualified name of the function it is about, if any
+    #[serde(default)]
+    pub function: Option<String>,
+
+    /// What was found
+    pub message: String,
+}
+
+impl Finding {
+    pub fn new(severity: Severity, function: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            pass: String::new(),
+            severity,
+            function: function.map(str::to_string),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: [{}] ", self.severity, self.pass)?;
+        if let Some(function) = &self.function {
+            write!(f, "{}: ", function)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// What a pass runs over.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PassInput<'a> {
+    /// Functions found, their matches and the field accesses
+    pub report: &'a AnalysisReport,
+
+    /// What is woven where
+    pub plan: &'a WeavingPlan,
+}
+
+/// A custom analysis over the results of the driver.
+pub trait AnalysisPass: Send + Sync {
+    /// Name of the pass, shown with its findings
+    fn name(&self) -> &str;
+
+    /// Analyze `input`. An `Err` means the pass itself failed, and is
+    /// reported as an error finding.
+    fn run(&self, input: &PassInput<'_>) -> Result<Vec<Finding>, String>;
+}
+
+/// The passes to run, in the order they were registered.
+#[derive(Default)]
+pub struct PassRegistry {
+    passes: Vec<Box<dyn AnalysisPass>>,
+}
+
+impl PassRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `pass`, to run after those already registered.
+    pub fn register(&mut self, pass: impl AnalysisPass + 'static) {
+        self.passes.push(Box::new(pass));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    /// Names of the registered passes.
+    pub fn names(&self) -> Vec<&str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// Run every pass, returning all findings.
+    pub fn run(&self, input: &PassInput<'_>) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for pass in &self.passes {
+            let found = pass.run(input).unwrap_or_else(|e| {
+                vec![Finding::new(
+                    Severity::Error,
+                    None,
+                    format!("pass failed: {}", e),
+                )]
+            });
+            findings.extend(found.into_iter().map(|finding| Finding {
+                pass: pass.name().to_string(),
+                ..finding
+            }));
+        }
+        findings
+    }
+}
+
+impl fmt::Debug for PassRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+/// An external program run as a pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandPass {
+    /// Program to run
+    pub program: String,
+
+    /// Its arguments
+    pub args: Vec<String>,
+}
+
+impl CommandPass {
+    /// Parse a command line, split at whitespace.
+    pub fn parse(command: &str) -> Result<Self, String> {
+        let mut words = command.split_whitespace().map(str::to_string);
+        let program = words.next().ok_or("empty pass command")?;
+        Ok(Self {
+            program,
+            args: words.collect(),
+        })
+    }
+}
+
+impl AnalysisPass for CommandPass {
+    fn name(&self) -> &str {
+        &self.program
+    }
+
+    fn run(&self, input: &PassInput<'_>) -> Result<Vec<Finding>, String> {
+        let input = serde_json::to_vec(input).map_err(|e| e.to_string())?;
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to run {}: {}", self.program, e))?;
+
+        // A program exiting without reading its input is not an error
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(&input);
+        }
+        let output = child.wait_with_output().map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(format!("{} exited with {}", self.program, output.status));
+        }
+
+        serde_json::from_slice(&output.stdout)
+            .map_err(|e| format!("invalid findings from {}: {}", self.program, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{FunctionMetadata, SourceLocation, Visibility};
+
+    /// Reports every public function that no advice is woven into.
+    struct Untraced;
+
+    impl AnalysisPass for Untraced {
+        fn name(&self) -> &str {
+            "untraced"
+        }
+
+        fn run(&self, input: &PassInput<'_>) -> Result<Vec<Finding>, String> {
+            let woven = input.plan.woven_functions();
+            Ok(input
+                .report
+                .functions
+                .iter()
+                .filter(|f| f.visibility == Visibility::Public)
+                .filter(|f| !woven.contains_key(f.name.as_str()))
+                .map(|f| Finding::new(Severity::Warning, Some(&f.name), "not traced"))
+                .collect())
+        }
+    }
+
+    struct Failing;
+
+    impl AnalysisPass for Failing {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        fn run(&self, _input: &PassInput<'_>) -> Result<Vec<Finding>, String> {
+            Err("no policy file".to_string())
+        }
+    }
+
+    fn function(name: &str) -> FunctionMetadata {
+        FunctionMetadata {
+            name: name.to_string(),
+            simple_name: name.rsplit("::").next().unwrap().to_string(),
+            module_path: "crate::api".to_string(),
+            visibility: Visibility::Public,
+            is_async: false,
+            is_closure: false,
+            generics: vec![],
+            return_type: "()".to_string(),
+            is_trait_method: false,
+            trait_name: None,
+            impl_type: None,
+            attributes: vec![],
+            location: SourceLocation {
+                file: "src/api.rs".to_string(),
+                line: 3,
+                column: 1,
+                end_line: 5,
+                end_column: 2,
+            },
+        }
+    }
+
+    fn run(registry: &PassRegistry) -> Vec<Finding> {
+        let functions = vec![function("api::get"), function("api::put")];
+        let hooks = [crate::r#match::AdviceHook::parse(
+            crate::r#match::AdviceType::Before,
+            "name(get)=crate::trace::enter",
+        )
+        .unwrap()];
+        let report = AnalysisReport::new(&[], functions.clone(), &[]);
+        let plan = WeavingPlan::new(&functions, &[], &hooks);
+        registry.run(&PassInput {
+            report: &report,
+            plan: &plan,
+        })
+    }
+
+    #[test]
+    fn test_pass_registry() {
+        let mut registry = PassRegistry::new();
+        assert!(registry.is_empty());
+        registry.register(Untraced);
+        registry.register(Failing);
+        assert_eq!(registry.names(), ["untraced", "failing"]);
+
+        let findings = run(&registry);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(
+            findings[0].to_string(),
+            "warning: [untraced] api::put: not traced"
+        );
+        assert_eq!(
+            findings[1].to_string(),
+            "error: [failing] pass failed: no policy file"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_pass() {
+        assert_eq!(
+            CommandPass::parse(" policy-check --strict ").unwrap(),
+            CommandPass {
+                program: "policy-check".to_string(),
+                args: vec!["--strict".to_string()],
+            }
+        );
+        assert!(CommandPass::parse("  ").is_err());
+
+        // Counts the functions of its input
+        let script = r#"n=$(grep -o '"simple_name"' | wc -l | tr -d ' ')
+echo "[{\"severity\": \"note\", \"message\": \"$n functions\"}]""#;
+        let mut registry = PassRegistry::new();
+        registry.register(CommandPass {
+            program: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+        });
+        registry.register(CommandPass::parse("false").unwrap());
+
+        let findings = run(&registry);
+        assert_eq!(findings[0].to_string(), "note: [sh] 2 functions");
+        assert_eq!(findings[1].severity, Severity::Error);
+        assert!(findings[1].message.contains("false exited"));
+    }
+}
@@ -0,0 +1,628 @@
+//! Dry-run weaving plans.
+//!
+//! A [`WeavingPlan`] lists, for each pointcut, the functions that would
+//! receive advice and in what order the advice runs, and the functions
+//! left out and why. It is computed from the analysis results alone, so
+//! it can be produced without weaving anything:
+//!
+//! ```text
+//! aspect-rustc-driver --aspect-plan \
+//!     --aspect-before 'execution(pub fn *(..))=crate::trace::enter' src/lib.rs
+//! ```
+//!
+//! Besides text and JSON, a plan can be rendered as a Graphviz graph with
+//! [`WeavingPlan::to_dot`], or a Mermaid flowchart with
+//! [`WeavingPlan::to_mermaid`], to see which parts of a codebase each
+//! concern touches.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use serde::Serialize;
+
+use crate::r#match::{AdviceHook, AdviceType, PointcutMatcher};
+use crate::types::FunctionMetadata;
+
+/// Why a matched async function is not woven by the compiler driver.
+pub const ASYNC_NOT_WOVEN: &str =
+    "async functions are not woven by the compiler driver; use #[aspect] instead";
+
+/// Why a matched closure is not woven by the compiler driver.
+pub const CLOSURE_NOT_WOVEN: &str = "closures are not woven by the compiler driver";
+
+/// Why the compiler driver cannot weave advice into `function`, if it
+/// cannot.
+pub fn unweavable_reason(function: &FunctionMetadata) -> Option<&'static str> {
+    if function.is_async {
+        Some(ASYNC_NOT_WOVEN)
+    } else if function.is_closure {
+        Some(CLOSURE_NOT_WOVEN)
+    } else {
+        None
+    }
+}
+
+/// One piece of advice: a hook and when it runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedAdvice {
+    /// When the hook runs
+    pub advice_type: AdviceType,
+
+    /// Path of the hook function
+    pub hook: String,
+}
+
+/// A function that would be woven.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedFunction {
+    /// Fully qualified function name
+    pub function: String,
+
+    /// Module the function is in
+    pub module_path: String,
+
+    /// All advice the function receives, from every pointcut, in the
+    /// order it runs
+    pub advice: Vec<PlannedAdvice>,
+}
+
+/// A function left out of a pointcut.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Exclusion {
+    /// Fully qualified function name
+    pub function: String,
+
+    /// Why the function is left out
+    pub reason: String,
+}
+
+/// What happens for one pointcut.
+#[derive(Debug, Clone, Serialize)]
+pub struct PointcutPlan {
+    /// Pointcut expression
+    pub pointcut: String,
+
+    /// Advice attached to the pointcut; empty for analysis-only pointcuts
+    pub advice: Vec<PlannedAdvice>,
+
+    /// Functions matched and woven
+    pub matched: Vec<PlannedFunction>,
+
+    /// Functions not matched, or matched but not weavable
+    pub excluded: Vec<Exclusion>,
+}
+
+/// The weaving plan of a crate.
+#[derive(Debug, Clone, Serialize)]
+pub struct WeavingPlan {
+    /// One entry per pointcut, analysis-only pointcuts first, then
+    /// pointcuts of advice hooks in the order they were given
+    pub pointcuts: Vec<PointcutPlan>,
+}
+
+impl WeavingPlan {
+    /// Plan the weaving of `hooks` into `functions`, and the matching of
+    /// the analysis-only `pointcuts`.
+    ///
+    /// Before hooks run in the order they were given, then the function,
+    /// then after hooks in the order they were given. This mirrors the MIR
+    /// weaver, which also skips async functions, closures and the hooks
+    /// themselves.
+    pub fn new(functions: &[FunctionMetadata], pointcuts: &[String], hooks: &[AdviceHook]) -> Self {
+        let matcher = PointcutMatcher::new();
+
+        let mut expressions: Vec<&str> = Vec::new();
+        for pointcut in pointcuts
+            .iter()
+            .map(String::as_str)
+            .chain(hooks.iter().map(|hook| hook.pointcut.as_str()))
+        {
+            if !expressions.contains(&pointcut) {
+                expressions.push(pointcut);
+            }
+        }
+
+        let exclusion = |function: &FunctionMetadata, pointcut: &str, woven: bool| {
+            if let Some(reason) = matcher.mismatch_reason(function, pointcut) {
+                return Some(reason);
+            }
+            if !woven {
+                None
+            } else if let Some(reason) = unweavable_reason(function) {
+                Some(reason.to_string())
+            } else if hooks.iter().any(|hook| is_hook(function, hook)) {
+                Some("advice hooks are never advised".to_string())
+            } else {
+                None
+            }
+        };
+
+        let pointcuts = expressions
+            .into_iter()
+            .map(|pointcut| {
+                let advice: Vec<PlannedAdvice> = hooks
+                    .iter()
+                    .filter(|hook| hook.pointcut == pointcut)
+                    .map(planned)
+                    .collect();
+
+                let mut matched = Vec::new();
+                let mut excluded = Vec::new();
+                for function in functions {
+                    match exclusion(function, pointcut, !advice.is_empty()) {
+                        Some(reason) => excluded.push(Exclusion {
+                            function: function.name.clone(),
+                            reason,
+                        }),
+                        None => matched.push(PlannedFunction {
+                            function: function.name.clone(),
+                            module_path: function.module_path.clone(),
+                            advice: ordered_advice(hooks, |hook| {
+                                exclusion(function, &hook.pointcut, true).is_none()
+                            }),
+                        }),
+                    }
+                }
+
+                PointcutPlan {
+                    pointcut: pointcut.to_string(),
+                    advice,
+                    matched,
+                    excluded,
+                }
+            })
+            .collect();
+
+        Self { pointcuts }
+    }
+
+    /// The advice of every function that would be woven, in run order,
+    /// by function name.
+    pub fn woven_functions(&self) -> BTreeMap<&str, &[PlannedAdvice]> {
+        self.pointcuts
+            .iter()
+            .flat_map(|pointcut| &pointcut.matched)
+            .filter(|function| !function.advice.is_empty())
+            .map(|function| (function.function.as_str(), function.advice.as_slice()))
+            .collect()
+    }
+
+    /// The functions matched by the pointcut of some advice that the
+    /// compiler driver cannot weave it into, with why, by function name.
+    pub fn unwoven(&self) -> BTreeMap<&str, &str> {
+        self.pointcuts
+            .iter()
+            .filter(|pointcut| !pointcut.advice.is_empty())
+            .flat_map(|pointcut| &pointcut.excluded)
+            .filter(|exclusion| [ASYNC_NOT_WOVEN, CLOSURE_NOT_WOVEN].contains(&&*exclusion.reason))
+            .map(|exclusion| (exclusion.function.as_str(), exclusion.reason.as_str()))
+            .collect()
+    }
+
+    /// The plan as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("weaving plan is always serializable")
+    }
+
+    /// The plan as human-readable text.
+    pub fn to_text(&self) -> String {
+        let mut out = String::from("=== Weaving Plan (dry run) ===\n");
+        for plan in &self.pointcuts {
+            let _ = writeln!(out, "\nPointcut: {}", plan.pointcut);
+            if plan.advice.is_empty() {
+                let _ = writeln!(out, "  Advice: none (analysis only)");
+            }
+            for advice in &plan.advice {
+                let _ = writeln!(out, "  Advice: {} {}", advice.advice_type, advice.hook);
+            }
+
+            let _ = writeln!(out, "  Matched ({}):", plan.matched.len());
+            for function in &plan.matched {
+                let _ = writeln!(out, "    {}", function.function);
+                for (i, advice) in function.advice.iter().enumerate() {
+                    let _ = writeln!(
+                        out,
+                        "      {}. {} {}",
+                        i + 1,
+                        advice.advice_type,
+                        advice.hook
+                    );
+                }
+            }
+
+            let _ = writeln!(out, "  Excluded ({}):", plan.excluded.len());
+            for exclusion in &plan.excluded {
+                let _ = writeln!(out, "    {}: {}", exclusion.function, exclusion.reason);
+            }
+        }
+        out
+    }
+
+    /// The plan as a Graphviz DOT graph: pointcuts and their advice on the
+    /// left, with edges to the functions they match on the right, grouped
+    /// in a cluster per module.
+    ///
+    /// Edges of analysis-only pointcuts are dashed. Render it with e.g.
+    /// `dot -Tsvg plan.dot -o plan.svg`.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph weaving_plan {\n");
+        out.push_str("    rankdir=LR;\n");
+        out.push_str("    node [shape=box, fontname=\"Helvetica\"];\n");
+
+        out.push_str("\n    subgraph cluster_aspects {\n");
+        out.push_str("        label=\"Aspects\";\n");
+        for (i, plan) in self.pointcuts.iter().enumerate() {
+            let mut label = plan.pointcut.clone();
+            for advice in &plan.advice {
+                let _ = write!(label, "\n{} {}", advice.advice_type, advice.hook);
+            }
+            let _ = writeln!(
+                out,
+                "        \"pointcut{}\" [label=\"{}\", shape=ellipse];",
+                i,
+                escape_dot(&label)
+            );
+        }
+        out.push_str("    }\n");
+
+        for (i, (module, functions)) in self.matched_by_module().iter().enumerate() {
+            let _ = writeln!(out, "\n    subgraph cluster_module{} {{", i);
+            let _ = writeln!(out, "        label=\"{}\";", escape_dot(module));
+            for (name, simple_name) in functions {
+                let _ = writeln!(
+                    out,
+                    "        \"fn {}\" [label=\"{}\"];",
+                    escape_dot(name),
+                    escape_dot(simple_name)
+                );
+            }
+            out.push_str("    }\n");
+        }
+
+        out.push('\n');
+        for (i, plan) in self.pointcuts.iter().enumerate() {
+            let style = if plan.advice.is_empty() {
+                " [style=dashed]"
+            } else {
+                ""
+            };
+            for function in &plan.matched {
+                let _ = writeln!(
+                    out,
+                    "    \"pointcut{}\" -> \"fn {}\"{};",
+                    i,
+                    escape_dot(&function.function),
+                    style
+                );
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// The plan as a Mermaid flowchart, laid out like [`to_dot`]: the
+    /// pointcuts, with edges to the functions they match, in a subgraph
+    /// per module. Edges of analysis-only pointcuts are dotted.
+    ///
+    /// Mermaid is rendered by GitHub and GitLab in Markdown files, in a
+    /// ```` ```mermaid ```` block.
+    ///
+    /// [`to_dot`]: WeavingPlan::to_dot
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("flowchart LR\n");
+        out.push_str("    subgraph aspects [\"Aspects\"]\n");
+        for (i, plan) in self.pointcuts.iter().enumerate() {
+            let mut label = escape_mermaid(&plan.pointcut);
+            for advice in &plan.advice {
+                let _ = write!(
+                    label,
+                    "<br/>{} {}",
+                    advice.advice_type,
+                    escape_mermaid(&advice.hook)
+                );
+            }
+            let _ = writeln!(out, "        pointcut{}([\"{}\"])", i, label);
+        }
+        out.push_str("    end\n");
+
+        // Node ids cannot hold paths; functions are numbered instead
+        let mut ids: BTreeMap<&str, usize> = BTreeMap::new();
+        for (i, (module, functions)) in self.matched_by_module().iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "    subgraph module{} [\"{}\"]",
+                i,
+                escape_mermaid(module)
+            );
+            for (name, simple_name) in functions {
+                let id = ids.len();
+                ids.insert(name, id);
+                let _ = writeln!(out, "        fn{}[\"{}\"]", id, escape_mermaid(simple_name));
+            }
+            out.push_str("    end\n");
+        }
+
+        for (i, plan) in self.pointcuts.iter().enumerate() {
+            let arrow = if plan.advice.is_empty() {
+                "-.->"
+            } else {
+                "-->"
+            };
+            for function in &plan.matched {
+                let _ = writeln!(
+                    out,
+                    "    pointcut{} {} fn{}",
+                    i,
+                    arrow,
+                    ids[function.function.as_str()]
+                );
+            }
+        }
+        out
+    }
+
+    /// Every matched function once, by module: its name and simple name.
+    fn matched_by_module(&self) -> BTreeMap<&str, BTreeMap<&str, &str>> {
+        let mut modules: BTreeMap<&str, BTreeMap<&str, &str>> = BTreeMap::new();
+        for function in self.pointcuts.iter().flat_map(|plan| &plan.matched) {
+            let simple_name = function.function.rsplit("::").next().unwrap_or_default();
+            modules
+                .entry(function.module_path.as_str())
+                .or_default()
+                .insert(function.function.as_str(), simple_name);
+        }
+        modules
+    }
+}
+
+/// Escape the text of a Mermaid label, with entity codes.
+fn escape_mermaid(text: &str) -> String {
+    text.replace('#', "#35;")
+        .replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
+/// Escape a DOT string; newlines become line breaks of the label.
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn planned(hook: &AdviceHook) -> PlannedAdvice {
+    PlannedAdvice {
+        advice_type: hook.advice_type,
+        hook: hook.path.clone(),
+    }
+}
+
+/// The hooks selected by `applies`, in the order they run.
+fn ordered_advice(
+    hooks: &[AdviceHook],
+    applies: impl Fn(&AdviceHook) -> bool,
+) -> Vec<PlannedAdvice> {
+    [AdviceType::Before, AdviceType::After]
+        .into_iter()
+        .flat_map(|advice_type| {
+            hooks
+                .iter()
+                .filter(move |hook| hook.advice_type == advice_type)
+        })
+        .filter(|hook| applies(hook))
+        .map(planned)
+        .collect()
+}
+
+/// Whether `function` is the hook function of `hook`.
+fn is_hook(function: &FunctionMetadata, hook: &AdviceHook) -> bool {
+    let path = hook.path.strip_prefix("crate::").unwrap_or(&hook.path);
+    function.name == path || function.name == hook.path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{SourceLocation, Visibility};
+
+    fn function(name: &str, visibility: Visibility) -> FunctionMetadata {
+        let (module, simple_name) = name.rsplit_once("::").unwrap_or(("", name));
+        FunctionMetadata {
+            name: name.to_string(),
+            simple_name: simple_name.to_string(),
+            module_path: format!("crate::{}", module),
+            visibility,
+            is_async: false,
+            is_closure: false,
+            generics: vec![],
+            return_type: "()".to_string(),
+            is_trait_method: false,
+            trait_name: None,
+            impl_type: None,
+            attributes: vec![],
+            location: SourceLocation {
+                file: "src/lib.rs".to_string(),
+                line: 1,
+                column: 1,
+                end_line: 1,
+                end_column: 1,
+            },
+        }
+    }
+
+    fn hook(advice_type: AdviceType, spec: &str) -> AdviceHook {
+        AdviceHook::parse(advice_type, spec).unwrap()
+    }
+
+    #[test]
+    fn test_weaving_plan() {
+        let mut fetch = function("api::fetch", Visibility::Public);
+        fetch.is_async = true;
+        let functions = vec![
+            function("api::add", Visibility::Public),
+            function("api::helper", Visibility::Private),
+            fetch,
+            function("trace::enter", Visibility::Public),
+        ];
+        let hooks = vec![
+            hook(AdviceType::After, "within(crate::api)=crate::trace::exit"),
+            hook(
+                AdviceType::Before,
+                "execution(pub fn *(..))=crate::trace::enter",
+            ),
+        ];
+        let pointcuts = vec!["name(add)".to_string()];
+
+        let plan = WeavingPlan::new(&functions, &pointcuts, &hooks);
+        let expressions: Vec<_> = plan.pointcuts.iter().map(|p| p.pointcut.as_str()).collect();
+        assert_eq!(
+            expressions,
+            vec!["name(add)", "within(crate::api)", "execution(pub fn *(..))"]
+        );
+
+        // Analysis only
+        let names = &plan.pointcuts[0];
+        assert!(names.advice.is_empty());
+        assert_eq!(names.matched.len(), 1);
+        assert_eq!(names.excluded.len(), 3);
+
+        // Before advice runs first, whatever the order of the flags
+        let within = &plan.pointcuts[1];
+        assert_eq!(within.matched[0].function, "api::add");
+        assert_eq!(
+            within.matched[0].advice,
+            vec![planned(&hooks[1]), planned(&hooks[0])]
+        );
+        assert_eq!(within.matched[1].function, "api::helper");
+        assert_eq!(within.matched[1].advice, vec![planned(&hooks[0])]);
+
+        let excluded: Vec<_> = within
+            .excluded
+            .iter()
+            .map(|e| (e.function.as_str(), e.reason.as_str()))
+            .collect();
+        assert_eq!(
+            excluded,
+            vec![
+                ("api::fetch", ASYNC_NOT_WOVEN),
+                ("trace::enter", "does not match within(crate::api)"),
+            ]
+        );
+
+        let public = &plan.pointcuts[2];
+        assert_eq!(public.matched.len(), 1);
+        assert_eq!(public.excluded[2].reason, "advice hooks are never advised");
+
+        let woven = plan.woven_functions();
+        assert_eq!(
+            woven.keys().copied().collect::<Vec<_>>(),
+            ["api::add", "api::helper"]
+        );
+        assert_eq!(woven["api::add"], [planned(&hooks[1]), planned(&hooks[0])]);
+
+        let text = plan.to_text();
+        assert!(text.contains("Pointcut: name(add)\n  Advice: none (analysis only)\n"));
+        assert!(text.contains(
+            "    api::add\n      1. before crate::trace::enter\n      2. after crate::trace::exit\n"
+        ));
+        assert!(text.contains("    api::helper: does not match execution(pub fn *(..))\n"));
+
+        let json: serde_json::Value = serde_json::from_str(&plan.to_json()).unwrap();
+        assert_eq!(json["pointcuts"][1]["advice"][0]["advice_type"], "after");
+        assert_eq!(
+            json["pointcuts"][1]["matched"][0]["module_path"],
+            "crate::api"
+        );
+    }
+
+    #[test]
+    fn test_closures_are_not_woven() {
+        let mut closure = function("api::add::{closure#0}", Visibility::Private);
+        closure.module_path = "crate::api".to_string();
+        closure.is_closure = true;
+        let functions = vec![function("api::add", Visibility::Public), closure];
+        let hooks = vec![hook(
+            AdviceType::Before,
+            "within(crate::api)=crate::trace::enter",
+        )];
+
+        let plan = WeavingPlan::new(&functions, &[], &hooks);
+        let within = &plan.pointcuts[0];
+        assert_eq!(within.matched.len(), 1);
+        assert_eq!(
+            within.excluded,
+            vec![Exclusion {
+                function: "api::add::{closure#0}".to_string(),
+                reason: CLOSURE_NOT_WOVEN.to_string(),
+            }]
+        );
+        assert_eq!(
+            plan.unwoven(),
+            BTreeMap::from([("api::add::{closure#0}", CLOSURE_NOT_WOVEN)])
+        );
+
+        // Analysis-only pointcuts weave nothing to leave out
+        let plan = WeavingPlan::new(&functions, &["within(crate::api)".to_string()], &[]);
+        assert!(plan.unwoven().is_empty());
+    }
+
+    #[test]
+    fn test_weaving_plan_dot() {
+        let functions = vec![
+            function("api::add", Visibility::Public),
+            function("api::users::get", Visibility::Public),
+            function("util::helper", Visibility::Private),
+        ];
+        let hooks = vec![hook(
+            AdviceType::Before,
+            "execution(pub fn *(..))=crate::trace::enter",
+        )];
+        let pointcuts = vec!["name(\"helper\")".to_string()];
+
+        let dot = WeavingPlan::new(&functions, &pointcuts, &hooks).to_dot();
+        assert!(dot.starts_with("digraph weaving_plan {\n    rankdir=LR;\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("\"pointcut0\" [label=\"name(\\\"helper\\\")\", shape=ellipse];"));
+        assert!(dot.contains(
+            "\"pointcut1\" [label=\"execution(pub fn *(..))\\nbefore crate::trace::enter\", \
+             shape=ellipse];"
+        ));
+        assert!(dot.contains(
+            "    subgraph cluster_module0 {\n        label=\"crate::api\";\n        \
+             \"fn api::add\" [label=\"add\"];\n    }\n"
+        ));
+        assert!(dot.contains("label=\"crate::api::users\";"));
+        assert!(dot.contains("label=\"crate::util\";"));
+        assert!(dot.contains("    \"pointcut0\" -> \"fn util::helper\" [style=dashed];\n"));
+        assert!(dot.contains("    \"pointcut1\" -> \"fn api::users::get\";\n"));
+        assert!(!dot.contains("\"pointcut1\" -> \"fn util::helper\""));
+    }
+
+    #[test]
+    fn test_weaving_plan_mermaid() {
+        let functions = vec![
+            function("api::add", Visibility::Public),
+            function("util::helper", Visibility::Private),
+        ];
+        let hooks = vec![hook(
+            AdviceType::Before,
+            "execution(pub fn *(..))=crate::trace::enter",
+        )];
+        let pointcuts = vec!["name(\"helper\")".to_string()];
+
+        let mermaid = WeavingPlan::new(&functions, &pointcuts, &hooks).to_mermaid();
+        assert_eq!(
+            mermaid,
+            "flowchart LR\n    \
+             subgraph aspects [\"Aspects\"]\n        \
+             pointcut0([\"name(#quot;helper#quot;)\"])\n        \
+             pointcut1([\"execution(pub fn *(..))<br/>before crate::trace::enter\"])\n    \
+             end\n    \
+             subgraph module0 [\"crate::api\"]\n        fn0[\"add\"]\n    end\n    \
+             subgraph module1 [\"crate::util\"]\n        fn1[\"helper\"]\n    end\n    \
+             pointcut0 -.-> fn1\n    \
+             pointcut1 --> fn0\n"
+        );
+        assert_eq!(escape_mermaid("Vec<#T>"), "Vec#lt;#35;T#gt;");
+    }
+}
@@ -0,0 +1,296 @@
+//! Aspects provided by the dependencies of a workspace.
+//!
+//! Crates declare the aspects they provide in the `[package.metadata.aspect]`
+//! table of their `Cargo.toml` (see `aspect_core::provider` for the
+//! format). cargo-aspect reads the tables of every package from `cargo
+//! metadata` with [`from_metadata`], lists the aspects, and writes them to
+//! the file named by [`PROVIDERS_FILE_ENV`] for the driver, which matches
+//! their pointcuts like those of `#[advice]` in its weaving plan.
+//!
+//! Unlike the registration manifest of `#[advice]`, these manifests do not
+//! need the providers to be compiled by the driver, so the aspects of
+//! dependencies built before are known too.
+
+use std::fs;
+use std::path::Path;
+
+use aspect_core::provider::METADATA_KEY;
+use serde::{Deserialize, Serialize};
+
+use crate::r#match::{parse_pointcut, AdviceType, RegisteredAspect};
+
+/// Environment variable naming the file of the provided aspects, written
+/// by [`write_providers`] and read by [`load_from_env`].
+pub const PROVIDERS_FILE_ENV: &str = "ASPECT_PROVIDERS_FILE";
+
+/// An aspect a package provides.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvidedAspect {
+    /// Package providing the aspect
+    pub package: String,
+
+    /// Version of the package
+    pub version: String,
+
+    /// Full path of the aspect type, e.g. `aspect_std::LoggingAspect`
+    pub path: String,
+
+    /// One line describing the aspect, empty when there is none
+    pub description: String,
+
+    /// Pointcut of the functions the aspect is meant for
+    pub pointcut: Option<String>,
+
+    /// Advice type (before, after, after_error, around)
+    pub advice_type: AdviceType,
+
+    /// Execution order (lower runs first)
+    pub order: i32,
+
+    /// Features of the package the aspect type needs
+    pub features: Vec<String>,
+}
+
+impl ProvidedAspect {
+    /// The aspect as the registry entry of its pointcut, if it has one.
+    pub fn registered(&self) -> Option<RegisteredAspect> {
+        Some(RegisteredAspect {
+            aspect_name: self.path.clone(),
+            pointcut: self.pointcut.clone()?,
+            advice_type: self.advice_type,
+            priority: self.order.saturating_neg(),
+        })
+    }
+}
+
+/// A `[[package.metadata.aspect.provides]]` entry, with the keys this
+/// version knows; others are ignored.
+#[derive(Debug, Deserialize)]
+struct ManifestEntry {
+    name: String,
+    #[serde(default)]
+    description: String,
+    pointcut: Option<String>,
+    advice: Option<String>,
+    #[serde(default)]
+    order: i32,
+    #[serde(default)]
+    features: Vec<String>,
+}
+
+/// The `[package.metadata.aspect]` table.
+#[derive(Debug, Deserialize)]
+struct ManifestTable {
+    #[serde(default)]
+    provides: Vec<ManifestEntry>,
+}
+
+/// The aspects provided by `package`, a package of `cargo metadata`;
+/// none when it has no `[package.metadata.aspect]` table.
+pub fn from_package(package: &serde_json::Value) -> Result<Vec<ProvidedAspect>, String> {
+    let name = package["name"].as_str().unwrap_or_default();
+    let Some(table) = package["metadata"].get(METADATA_KEY) else {
+        return Ok(Vec::new());
+    };
+    let table = ManifestTable::deserialize(table)
+        .map_err(|e| format!("{}: invalid [package.metadata.aspect]: {}", name, e))?;
+
+    // Paths start with the name of the library, which may differ from
+    // that of the package
+    let crate_name = package["targets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|target| {
+            target["kind"].as_array().is_some_and(|kinds| {
+                kinds.iter().any(|kind| {
+                    matches!(
+                        kind.as_str(),
+                        Some("lib" | "rlib" | "dylib" | "cdylib" | "staticlib" | "proc-macro")
+                    )
+                })
+            })
+        })
+        .and_then(|target| target["name"].as_str())
+        .unwrap_or(name)
+        .replace('-', "_");
+
+    table
+        .provides
+        .into_iter()
+        .map(|entry| {
+            let error = |e: String| format!("{}: aspect '{}': {}", name, entry.name, e);
+            if let Some(pointcut) = &entry.pointcut {
+                parse_pointcut(pointcut).map_err(error)?;
+            }
+            Ok(ProvidedAspect {
+                package: name.to_string(),
+                version: package["version"].as_str().unwrap_or_default().to_string(),
+                path: format!("{}::{}", crate_name, entry.name),
+                description: entry.description,
+                advice_type: match &entry.advice {
+                    Some(advice) => advice.parse().map_err(error)?,
+                    None => AdviceType::Around,
+                },
+                pointcut: entry.pointcut,
+                order: entry.order,
+                features: entry.features,
+            })
+        })
+        .collect()
+}
+
+/// The aspects provided by the packages of `metadata`, the output of
+/// `cargo metadata --format-version 1`, by package name, and the errors
+/// of the manifests that could not be read. Invalid manifests are
+/// skipped, so that one does not hide the others.
+pub fn from_metadata(metadata: &serde_json::Value) -> (Vec<ProvidedAspect>, Vec<String>) {
+    let mut packages: Vec<_> = metadata["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .collect();
+    packages.sort_by_key(|package| package["name"].as_str());
+
+    let (mut aspects, mut errors) = (Vec::new(), Vec::new());
+    for package in packages {
+        match from_package(package) {
+            Ok(provided) => aspects.extend(provided),
+            Err(e) => errors.push(e),
+        }
+    }
+    (aspects, errors)
+}
+
+/// Write `aspects` to `path`, for the driver.
+pub fn write_providers(path: &Path, aspects: &[ProvidedAspect]) -> Result<(), String> {
+    let contents = serde_json::to_string(aspects).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    fs::write(path, contents).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Read the aspects written by [`write_providers`] to `path`.
+pub fn read_providers(path: &Path) -> Result<Vec<ProvidedAspect>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// The provided aspects with a pointcut, from the file named by
+/// `$ASPECT_PROVIDERS_FILE`.
+///
+/// Returns nothing when the variable is unset or the file unreadable,
+/// like [`load_from_registry`](crate::match::load_from_registry).
+pub fn load_from_env() -> Vec<RegisteredAspect> {
+    let Some(path) = std::env::var_os(PROVIDERS_FILE_ENV) else {
+        return Vec::new();
+    };
+    read_providers(Path::new(&path))
+        .unwrap_or_default()
+        .iter()
+        .filter_map(ProvidedAspect::registered)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, metadata: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "name": name,
+            "version": "1.2.0",
+            "targets": [
+                { "name": "retry-cli", "kind": ["bin"] },
+                { "name": "retry-aspects", "kind": ["lib"] },
+            ],
+            "metadata": metadata,
+        })
+    }
+
+    #[test]
+    fn test_from_package() {
+        let retry = package(
+            "retry-aspects",
+            serde_json::json!({ "aspect": { "provides": [
+                { "name": "RetryAspect", "description": "Retry failed calls" },
+                {
+                    "name": "backoff::BackoffAspect",
+                    "pointcut": "within(crate::client)",
+                    "advice": "before",
+                    "order": 5,
+                    "features": ["std"],
+                    "since": "1.2",
+                },
+            ]}}),
+        );
+        let aspects = from_package(&retry).unwrap();
+        assert_eq!(aspects.len(), 2);
+        assert_eq!(aspects[0].path, "retry_aspects::RetryAspect");
+        assert_eq!(aspects[0].version, "1.2.0");
+        assert_eq!(aspects[0].advice_type, AdviceType::Around);
+        assert_eq!(aspects[0].registered(), None);
+
+        assert_eq!(aspects[1].path, "retry_aspects::backoff::BackoffAspect");
+        assert_eq!(aspects[1].features, ["std"]);
+        assert_eq!(
+            aspects[1].registered(),
+            Some(RegisteredAspect {
+                aspect_name: "retry_aspects::backoff::BackoffAspect".to_string(),
+                pointcut: "within(crate::client)".to_string(),
+                advice_type: AdviceType::Before,
+                priority: -5,
+            })
+        );
+
+        assert!(from_package(&package("serde", serde_json::Value::Null))
+            .unwrap()
+            .is_empty());
+
+        let invalid = package(
+            "retry-aspects",
+            serde_json::json!({ "aspect": { "provides": [
+                { "name": "RetryAspect", "pointcut": "call(fn *(..))" },
+            ]}}),
+        );
+        let error = from_package(&invalid).unwrap_err();
+        assert!(
+            error.starts_with("retry-aspects: aspect 'RetryAspect': "),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_from_metadata() {
+        let metadata = serde_json::json!({ "packages": [
+            package("zeta", serde_json::json!({ "aspect": { "provides": [{ "name": "Z" }] } })),
+            package("broken", serde_json::json!({ "aspect": { "provides": [{}] } })),
+            package("alpha", serde_json::json!({ "aspect": { "provides": [{ "name": "A" }] } })),
+        ]});
+        let (aspects, errors) = from_metadata(&metadata);
+        let paths: Vec<_> = aspects.iter().map(|aspect| aspect.path.as_str()).collect();
+        assert_eq!(paths, ["retry_aspects::A", "retry_aspects::Z"]);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("broken: "), "{}", errors[0]);
+    }
+
+    #[test]
+    fn test_write_providers() {
+        let dir = std::env::temp_dir().join(format!("aspect-providers-{}", std::process::id()));
+        let path = dir.join("providers.json");
+        let metadata = serde_json::json!({ "packages": [package(
+            "retry-aspects",
+            serde_json::json!({ "aspect": { "provides": [
+                { "name": "RetryAspect", "pointcut": "execution(pub fn *(..))" },
+            ]}}),
+        )]});
+        let (aspects, _) = from_metadata(&metadata);
+
+        write_providers(&path, &aspects).unwrap();
+        assert_eq!(read_providers(&path).unwrap(), aspects);
+        assert!(read_providers(&dir.join("missing.json")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
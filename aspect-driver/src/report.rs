@@ -0,0 +1,514 @@
+//! Analysis results in machine-readable form.
+//!
+//! The JSON document written by `aspect-rustc-driver --aspect-format json`
+//! is an [`AnalysisReport`]. Its `format_version` is bumped whenever a field
+//! is removed or changes meaning; new fields may be added within a version.
+//!
+//! The same report can be written as [SARIF] 2.1.0 with
+//! [`AnalysisReport::to_sarif`], for GitHub code scanning and IDEs.
+//!
+//! [SARIF]: https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::diagnostic::{ANALYSIS_PASS, POINTCUT_MATCH, UNMATCHED_POINTCUT, WEAVING_WARNING};
+use crate::field::FieldAccess;
+use crate::pass::Finding;
+use crate::plan::unweavable_reason;
+use crate::types::{FunctionMetadata, SourceLocation, Visibility};
+
+/// Version of the JSON analysis format.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Statistics about the analysis
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnalysisStats {
+    pub total_functions: usize,
+    pub public_functions: usize,
+    pub private_functions: usize,
+    pub async_functions: usize,
+    pub matched_functions: usize,
+}
+
+impl AnalysisStats {
+    pub fn from_functions(functions: &[FunctionMetadata]) -> Self {
+        let total_functions = functions.len();
+        let public_functions = functions
+            .iter()
+            .filter(|f| f.visibility == Visibility::Public)
+            .count();
+        let private_functions = total_functions - public_functions;
+        let async_functions = functions.iter().filter(|f| f.is_async).count();
+
+        Self {
+            total_functions,
+            public_functions,
+            private_functions,
+            async_functions,
+            matched_functions: 0,
+        }
+    }
+
+    pub fn print_summary(&self) {
+        println!("\n=== Analysis Statistics ===");
+        println!("Total functions: {}", self.total_functions);
+        println!("  Public: {}", self.public_functions);
+        println!("  Private: {}", self.private_functions);
+        println!("  Async: {}", self.async_functions);
+        if self.matched_functions > 0 {
+            println!("  Matched by pointcuts: {}", self.matched_functions);
+        }
+    }
+}
+
+/// A pointcut and how many functions it matched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PointcutSummary {
+    /// Pointcut expression
+    pub expression: String,
+
+    /// Number of functions matched
+    pub matches: usize,
+}
+
+/// A function matched by a pointcut.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchEntry {
+    /// Fully qualified function name, as in `functions`
+    pub function: String,
+
+    /// Pointcut expression that matched
+    pub pointcut: String,
+}
+
+/// Something about a match that keeps it from being woven as expected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportWarning {
+    /// Fully qualified function name, as in `functions`
+    pub function: String,
+
+    /// What is wrong
+    pub message: String,
+}
+
+/// Complete result of analyzing a crate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisReport {
+    /// Version of this format ([`FORMAT_VERSION`])
+    pub format_version: u32,
+
+    /// Pointcuts in the order they were given
+    pub pointcuts: Vec<PointcutSummary>,
+
+    /// All functions found
+    pub functions: Vec<FunctionMetadata>,
+
+    /// Functions matched by each pointcut
+    pub matches: Vec<MatchEntry>,
+
+    /// Weaving warnings about matched functions
+    pub warnings: Vec<ReportWarning>,
+
+    /// Field reads and writes selected by field patterns
+    #[serde(default)]
+    pub field_accesses: Vec<FieldAccess>,
+
+    /// Findings of custom analysis passes
+    #[serde(default)]
+    pub findings: Vec<Finding>,
+
+    /// Summary statistics
+    pub stats: AnalysisStats,
+}
+
+impl AnalysisReport {
+    /// Build a report from the functions found and the `(function,
+    /// pointcut)` matches.
+    pub fn new(
+        pointcuts: &[String],
+        functions: Vec<FunctionMetadata>,
+        matched: &[(FunctionMetadata, String)],
+    ) -> Self {
+        let pointcuts = pointcuts
+            .iter()
+            .map(|expression| PointcutSummary {
+                expression: expression.clone(),
+                matches: matched.iter().filter(|(_, p)| p == expression).count(),
+            })
+            .collect();
+        let matches = matched
+            .iter()
+            .map(|(function, pointcut)| MatchEntry {
+                function: function.name.clone(),
+                pointcut: pointcut.clone(),
+            })
+            .collect();
+
+        let mut matched_functions: Vec<_> = matched.iter().map(|(f, _)| f).collect();
+        matched_functions.sort_by(|a, b| a.name.cmp(&b.name));
+        matched_functions.dedup_by(|a, b| a.name == b.name);
+
+        // Only before/after advice of synchronous functions can be woven
+        // into MIR
+        let warnings = matched_functions
+            .iter()
+            .filter_map(|function| {
+                unweavable_reason(function).map(|reason| ReportWarning {
+                    function: function.name.clone(),
+                    message: reason.to_string(),
+                })
+            })
+            .collect();
+
+        let mut stats = AnalysisStats::from_functions(&functions);
+        stats.matched_functions = matched_functions.len();
+
+        Self {
+            format_version: FORMAT_VERSION,
+            pointcuts,
+            functions,
+            matches,
+            warnings,
+            field_accesses: Vec::new(),
+            findings: Vec::new(),
+            stats,
+        }
+    }
+
+    /// Add the field accesses selected by field patterns.
+    pub fn with_field_accesses(mut self, field_accesses: Vec<FieldAccess>) -> Self {
+        self.field_accesses = field_accesses;
+        self
+    }
+
+    /// Add the findings of custom analysis passes.
+    pub fn with_findings(mut self, findings: Vec<Finding>) -> Self {
+        self.findings = findings;
+        self
+    }
+
+    /// Read a report written by [`AnalysisReport::to_json`].
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let report: Self = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if report.format_version != FORMAT_VERSION {
+            return Err(format!(
+                "unsupported analysis format version {}",
+                report.format_version
+            ));
+        }
+        Ok(report)
+    }
+
+    /// The report as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("analysis report is always serializable")
+    }
+
+    /// The report as a pretty-printed SARIF 2.1.0 log.
+    ///
+    /// Matches are notes on the matched functions, and pointcuts matching
+    /// nothing and weaving warnings are warnings. Findings of analysis
+    /// passes keep their severity.
+    pub fn to_sarif(&self) -> String {
+        let location = |name: &str| {
+            self.functions
+                .iter()
+                .find(|function| function.name == name)
+                .filter(|function| function.location.line > 0)
+                .map(|function| {
+                    json!([{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": artifact_uri(&function.location.file) },
+                            "region": region(&function.location),
+                        },
+                        "logicalLocations": [{
+                            "fullyQualifiedName": function.name,
+                            "kind": if function.impl_context().is_some() {
+                                "member"
+                            } else {
+                                "function"
+                            },
+                        }],
+                    }])
+                })
+        };
+        let result = |rule_id: &str, level: &str, text: String, locations: Option<Value>| {
+            let mut result = json!({
+                "ruleId": rule_id,
+                "level": level,
+                "message": { "text": text },
+            });
+            if let Some(locations) = locations {
+                result["locations"] = locations;
+            }
+            result
+        };
+
+        let mut results = Vec::new();
+        for entry in &self.matches {
+            results.push(result(
+                POINTCUT_MATCH,
+                "note",
+                format!(
+                    "{} is matched by pointcut `{}`",
+                    entry.function, entry.pointcut
+                ),
+                location(&entry.function),
+            ));
+        }
+        for pointcut in self.pointcuts.iter().filter(|p| p.matches == 0) {
+            results.push(result(
+                UNMATCHED_POINTCUT,
+                "warning",
+                format!("Pointcut `{}` matches no function", pointcut.expression),
+                None,
+            ));
+        }
+        for warning in &self.warnings {
+            results.push(result(
+                WEAVING_WARNING,
+                "warning",
+                format!("{}: {}", warning.function, warning.message),
+                location(&warning.function),
+            ));
+        }
+        for finding in &self.findings {
+            let text = match &finding.function {
+                Some(function) => format!("[{}] {}: {}", finding.pass, function, finding.message),
+                None => format!("[{}] {}", finding.pass, finding.message),
+            };
+            results.push(result(
+                ANALYSIS_PASS,
+                &finding.severity.to_string(),
+                text,
+                finding.function.as_deref().and_then(location),
+            ));
+        }
+
+        let rules = [
+            rule(
+                POINTCUT_MATCH,
+                "pointcut-match",
+                "Function matched by a pointcut",
+                "note",
+            ),
+            rule(
+                UNMATCHED_POINTCUT,
+                "unmatched-pointcut",
+                "Pointcut matching no function",
+                "warning",
+            ),
+            rule(
+                WEAVING_WARNING,
+                "weaving-warning",
+                "Matched function that cannot be woven as expected",
+                "warning",
+            ),
+            rule(
+                ANALYSIS_PASS,
+                "analysis-pass",
+                "Finding of a custom analysis pass",
+                "warning",
+            ),
+        ];
+        let log = json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "aspect-rs",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "informationUri": "https://github.com/yijunyu/aspect-rs",
+                        "rules": rules,
+                    }
+                },
+                "results": results,
+            }],
+        });
+        serde_json::to_string_pretty(&log).expect("SARIF log is always serializable")
+    }
+}
+
+fn rule(id: &str, name: &str, description: &str, level: &str) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "shortDescription": { "text": description },
+        "defaultConfiguration": { "level": level },
+    })
+}
+
+/// SARIF region of a location; columns and the end are left out when
+/// unknown.
+fn region(location: &SourceLocation) -> Value {
+    let mut region = json!({ "startLine": location.line });
+    if location.column > 0 {
+        region["startColumn"] = json!(location.column);
+    }
+    if location.end_line >= location.line && location.end_column > 0 {
+        region["endLine"] = json!(location.end_line);
+        region["endColumn"] = json!(location.end_column);
+    }
+    region
+}
+
+/// Relative paths are kept, so code scanning resolves them against the
+/// repository; absolute paths become `file://` URIs.
+fn artifact_uri(file: &str) -> String {
+    let file = file.replace('\\', "/");
+    if file.starts_with('/') {
+        format!("file://{}", file)
+    } else if file.chars().nth(1) == Some(':') {
+        format!("file:///{}", file)
+    } else {
+        file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pass::Severity;
+
+    fn function(name: &str, visibility: Visibility) -> FunctionMetadata {
+        FunctionMetadata {
+            name: name.to_string(),
+            simple_name: name.rsplit("::").next().unwrap().to_string(),
+            module_path: "crate::api".to_string(),
+            visibility,
+            is_async: false,
+            is_closure: false,
+            generics: vec![],
+            return_type: "()".to_string(),
+            is_trait_method: false,
+            trait_name: None,
+            impl_type: None,
+            attributes: vec![],
+            location: SourceLocation {
+                file: "src/api.rs".to_string(),
+                line: 3,
+                column: 1,
+                end_line: 5,
+                end_column: 2,
+            },
+        }
+    }
+
+    #[test]
+    fn test_report_json() {
+        let get = function("api::get", Visibility::Public);
+        let check = function("api::check", Visibility::Private);
+        let pointcuts = vec![
+            "execution(pub fn *(..))".to_string(),
+            "within(crate::api)".to_string(),
+            "name(unused_*)".to_string(),
+        ];
+        let matched = vec![
+            (get.clone(), pointcuts[0].clone()),
+            (get.clone(), pointcuts[1].clone()),
+            (check.clone(), pointcuts[1].clone()),
+        ];
+
+        let report = AnalysisReport::new(&pointcuts, vec![get, check], &matched);
+        assert_eq!(report.stats.matched_functions, 2);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["format_version"], FORMAT_VERSION);
+        assert_eq!(json["pointcuts"][1]["matches"], 2);
+        assert_eq!(json["pointcuts"][2]["matches"], 0);
+        assert_eq!(json["functions"][0]["visibility"], "public");
+        assert_eq!(json["functions"][1]["location"]["line"], 3);
+        assert_eq!(json["matches"][2]["function"], "api::check");
+        assert_eq!(json["stats"]["total_functions"], 2);
+        assert_eq!(json["stats"]["public_functions"], 1);
+        assert_eq!(json["warnings"], serde_json::json!([]));
+        assert_eq!(json["field_accesses"], serde_json::json!([]));
+        assert_eq!(json["findings"], serde_json::json!([]));
+
+        let read = AnalysisReport::from_json(&report.to_json()).unwrap();
+        assert_eq!(read.matches, report.matches);
+        assert!(AnalysisReport::from_json("{\"format_version\": 1}").is_err());
+    }
+
+    #[test]
+    fn test_report_field_accesses() {
+        let get = function("api::get", Visibility::Public);
+        let access = FieldAccess {
+            kind: crate::field::FieldAccessKind::Set,
+            struct_path: "crate::api::Counter".to_string(),
+            field: "hits".to_string(),
+            function: get.name.clone(),
+            location: get.location.clone(),
+        };
+
+        let report = AnalysisReport::new(&[], vec![get], &[]).with_field_accesses(vec![access]);
+        let json: Value = serde_json::from_str(&report.to_json()).unwrap();
+        let access = &json["field_accesses"][0];
+        assert_eq!(access["kind"], "set");
+        assert_eq!(access["struct_path"], "crate::api::Counter");
+        assert_eq!(access["field"], "hits");
+        assert_eq!(access["function"], "api::get");
+        assert_eq!(access["location"]["line"], 3);
+    }
+
+    #[test]
+    fn test_report_sarif() {
+        let get = function("api::get", Visibility::Public);
+        let mut fetch = function("api::fetch", Visibility::Public);
+        fetch.is_async = true;
+        fetch.location.file = "/work/src/api.rs".to_string();
+        let pointcuts = vec![
+            "execution(pub fn *(..))".to_string(),
+            "name(unused_*)".to_string(),
+        ];
+        let matched = vec![
+            (get.clone(), pointcuts[0].clone()),
+            (fetch.clone(), pointcuts[0].clone()),
+        ];
+        let findings = vec![Finding {
+            pass: "policy".to_string(),
+            ..Finding::new(Severity::Error, Some("api::get"), "advised")
+        }];
+        let report =
+            AnalysisReport::new(&pointcuts, vec![get, fetch], &matched).with_findings(findings);
+        assert_eq!(report.warnings.len(), 1);
+
+        let sarif: Value = serde_json::from_str(&report.to_sarif()).unwrap();
+        assert_eq!(sarif["version"], "2.1.0");
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"].as_array().unwrap().len(), 4);
+
+        let results = run["results"].as_array().unwrap();
+        let rules: Vec<_> = results
+            .iter()
+            .map(|r| r["ruleId"].as_str().unwrap())
+            .collect();
+        assert_eq!(rules, vec!["AR001", "AR001", "AR002", "AR003", "AR004"]);
+
+        let location = &results[0]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/api.rs");
+        assert_eq!(
+            location["region"],
+            json!({ "startLine": 3, "startColumn": 1, "endLine": 5, "endColumn": 2 })
+        );
+        assert_eq!(
+            results[1]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "file:///work/src/api.rs"
+        );
+
+        assert_eq!(results[2]["level"], "warning");
+        assert!(results[2].get("locations").is_none());
+        assert!(results[3]["message"]["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("api::fetch: async functions are not woven"));
+        assert_eq!(results[4]["level"], "error");
+        assert_eq!(results[4]["message"]["text"], "[policy] api::get: advised");
+        assert_eq!(
+            results[4]["locations"][0]["physicalLocation"]["region"]["startLine"],
+            3
+        );
+    }
+}
@@ -0,0 +1,732 @@
+//! Source-to-source aspect weaving on stable Rust.
+//!
+//! Instead of transforming MIR inside rustc, this backend copies a crate,
+//! adds an `#[aspect(...)]` attribute to every function matched by a
+//! pointcut, and builds the copy with stock cargo. The attribute is
+//! expanded by `aspect-macros` as usual, so the woven crate has to depend on
+//! `aspect-core` and `aspect-macros`, and aspect expressions are resolved
+//! where the matched function is defined.
+//!
+//! Attributes are spliced into the original text on the line of the
+//! function, so comments, formatting and line numbers are preserved.
+//!
+//! # Limitations
+//!
+//! - Only free functions are woven, not methods or trait functions
+//! - `const` and `extern` functions, `#[test]` functions and `#[cfg(test)]`
+//!   modules are skipped
+//! - Modules are found by file path (`src/a/b.rs` or `src/a/b/mod.rs` is
+//!   `crate::a::b`), so `#[path]` attributes are not followed
+//! - The copied crate becomes its own workspace, so `workspace = true`
+//!   fields in its manifest are not supported
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+use proc_macro2::LineColumn;
+use syn::spanned::Spanned;
+
+use crate::r#match::{AdviceType, PointcutMatcher, RegisteredAspect};
+use crate::types::{
+    normalize_attribute, normalize_type_name, FunctionMetadata, GenericParam, SourceLocation,
+    Visibility,
+};
+
+/// A function the weaver added aspects to.
+#[derive(Debug, Clone)]
+pub struct WovenFunction {
+    /// The function metadata
+    pub function: FunctionMetadata,
+
+    /// Aspect expressions applied, outermost first
+    pub aspects: Vec<String>,
+}
+
+/// Result of weaving one source file.
+#[derive(Debug, Clone)]
+pub struct WovenSource {
+    /// The rewritten source
+    pub source: String,
+
+    /// Functions that got aspects
+    pub woven: Vec<WovenFunction>,
+}
+
+/// Result of weaving a crate.
+#[derive(Debug, Clone)]
+pub struct WeaveReport {
+    /// Directory of the woven copy
+    pub out_dir: PathBuf,
+
+    /// Number of source files processed
+    pub files: usize,
+
+    /// Functions that got aspects, in all files
+    pub woven: Vec<WovenFunction>,
+}
+
+/// Parse a `<pointcut>=<aspect expression>` specification.
+///
+/// The expression is evaluated on every call of a matched function, like
+/// the argument of `#[aspect(...)]`, e.g.
+/// `execution(pub fn *(..))=crate::aspects::LOGGER.clone()`.
+pub fn parse_aspect_spec(spec: &str) -> Result<RegisteredAspect, String> {
+    // The pointcut ends at the first '=' outside parentheses
+    let mut depth = 0i32;
+    let split = spec.char_indices().find(|&(_, c)| {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        c == '=' && depth == 0
+    });
+    let Some((pos, _)) = split else {
+        return Err(format!(
+            "expected <pointcut>=<aspect expression>, got '{}'",
+            spec
+        ));
+    };
+    let (pointcut, aspect) = (spec[..pos].trim(), spec[pos + 1..].trim());
+
+    crate::r#match::parse_pointcut(pointcut)?;
+    syn::parse_str::<syn::Expr>(aspect)
+        .map_err(|e| format!("invalid aspect expression '{}': {}", aspect, e))?;
+
+    Ok(RegisteredAspect {
+        aspect_name: aspect.to_string(),
+        pointcut: pointcut.to_string(),
+        advice_type: AdviceType::Around,
+        priority: 0,
+    })
+}
+
+/// AST-level weaver applying aspects to the functions matched by their
+/// pointcuts.
+///
+/// Every registered aspect becomes an `#[aspect(...)]` attribute, so all
+/// advice types are supported; the `advice_type` of registrations is
+/// ignored. Aspects with a higher priority are applied outermost.
+///
+/// # Example
+///
+/// ```ignore
+/// use aspect_driver::source::{parse_aspect_spec, run_cargo, SourceWeaver};
+///
+/// let mut weaver = SourceWeaver::new();
+/// weaver.register(parse_aspect_spec(
+///     "execution(pub fn *(..)) && within(crate::api)=::aspect_std::LoggingAspect::new()",
+/// )?);
+///
+/// let report = weaver.weave_crate(Path::new("."), Path::new("target/aspect/woven"))?;
+/// run_cargo(&report.out_dir, "build", &[])?;
+/// ```
+#[derive(Default)]
+pub struct SourceWeaver {
+    matcher: PointcutMatcher,
+}
+
+impl SourceWeaver {
+    /// Create a weaver without aspects.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an aspect with a pointcut.
+    pub fn register(&mut self, aspect: RegisteredAspect) {
+        self.matcher.register(aspect);
+    }
+
+    /// Weave the source of one file whose items are in `module_path`
+    /// (e.g. `crate::api`).
+    ///
+    /// `file` is only used for the locations of woven functions.
+    pub fn weave_source(
+        &self,
+        source: &str,
+        file: &str,
+        module_path: &str,
+    ) -> Result<WovenSource, String> {
+        let ast =
+            syn::parse_file(source).map_err(|e| format!("failed to parse {}: {}", file, e))?;
+
+        let mut insertions = Vec::new();
+        let mut woven = Vec::new();
+        self.weave_items(&ast.items, file, module_path, &mut insertions, &mut woven);
+
+        // Splice from the end so earlier offsets stay valid
+        let lines = line_offsets(source);
+        let mut source = source.to_string();
+        insertions.sort_by_key(|(at, _)| (at.line, at.column));
+        for (at, attributes) in insertions.into_iter().rev() {
+            let offset = byte_offset(&source, &lines, at);
+            source.insert_str(offset, &attributes);
+        }
+
+        Ok(WovenSource { source, woven })
+    }
+
+    fn weave_items(
+        &self,
+        items: &[syn::Item],
+        file: &str,
+        module_path: &str,
+        insertions: &mut Vec<(LineColumn, String)>,
+        woven: &mut Vec<WovenFunction>,
+    ) {
+        for item in items {
+            match item {
+                syn::Item::Fn(func) if is_weavable(func) => {
+                    let function = function_metadata(func, file, module_path);
+                    let aspects: Vec<String> = self
+                        .matcher
+                        .match_function(&function)
+                        .into_iter()
+                        .map(|matched| matched.aspect)
+                        .collect();
+                    if aspects.is_empty() {
+                        continue;
+                    }
+
+                    let attributes: String = aspects
+                        .iter()
+                        .map(|aspect| format!("#[::aspect_macros::aspect({})] ", aspect))
+                        .collect();
+                    insertions.push((insertion_point(func), attributes));
+                    woven.push(WovenFunction { function, aspects });
+                }
+                syn::Item::Mod(module) if !is_test_only(&module.attrs) => {
+                    if let Some((_, items)) = &module.content {
+                        let module_path = format!("{}::{}", module_path, module.ident);
+                        self.weave_items(items, file, &module_path, insertions, woven);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Copy the crate at `crate_dir` to `out_dir` and weave every source
+    /// file under `src`.
+    ///
+    /// `target`, `.git` and `out_dir` itself are not copied. Relative path
+    /// dependencies that point outside the crate are made absolute, so the
+    /// copy builds from anywhere.
+    pub fn weave_crate(&self, crate_dir: &Path, out_dir: &Path) -> Result<WeaveReport, String> {
+        let crate_dir = crate_dir
+            .canonicalize()
+            .map_err(|e| format!("cannot open crate {}: {}", crate_dir.display(), e))?;
+        if !crate_dir.join("Cargo.toml").is_file() {
+            return Err(format!("no Cargo.toml in {}", crate_dir.display()));
+        }
+
+        if out_dir.exists() {
+            fs::remove_dir_all(out_dir)
+                .map_err(|e| format!("cannot clear {}: {}", out_dir.display(), e))?;
+        }
+        fs::create_dir_all(out_dir)
+            .map_err(|e| format!("cannot create {}: {}", out_dir.display(), e))?;
+        let out_dir = out_dir.canonicalize().map_err(|e| e.to_string())?;
+        copy_tree(&crate_dir, &out_dir, &out_dir)?;
+
+        let manifest =
+            fs::read_to_string(crate_dir.join("Cargo.toml")).map_err(|e| e.to_string())?;
+        fs::write(
+            out_dir.join("Cargo.toml"),
+            rewrite_manifest(&manifest, &crate_dir),
+        )
+        .map_err(|e| e.to_string())?;
+
+        let mut report = WeaveReport {
+            out_dir: out_dir.clone(),
+            files: 0,
+            woven: Vec::new(),
+        };
+        let src_dir = out_dir.join("src");
+        for path in rust_files(&src_dir)? {
+            let relative = path.strip_prefix(&src_dir).unwrap();
+            let file = Path::new("src")
+                .join(relative)
+                .to_string_lossy()
+                .to_string();
+            let source = fs::read_to_string(&path).map_err(|e| format!("{}: {}", file, e))?;
+
+            let result = self.weave_source(&source, &file, &module_path_for(relative))?;
+            if !result.woven.is_empty() {
+                fs::write(&path, result.source).map_err(|e| format!("{}: {}", file, e))?;
+            }
+            report.files += 1;
+            report.woven.extend(result.woven);
+        }
+
+        Ok(report)
+    }
+}
+
+/// Run `cargo <command> [args]` on the woven crate in `out_dir`.
+pub fn run_cargo(out_dir: &Path, command: &str, args: &[String]) -> Result<ExitStatus, String> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    Command::new(cargo)
+        .arg(command)
+        .arg("--manifest-path")
+        .arg(out_dir.join("Cargo.toml"))
+        .args(args)
+        .status()
+        .map_err(|e| format!("failed to run cargo: {}", e))
+}
+
+/// Only plain free functions that are not tests can carry `#[aspect]`.
+fn is_weavable(func: &syn::ItemFn) -> bool {
+    func.sig.constness.is_none()
+        && func.sig.abi.is_none()
+        && !func.attrs.iter().any(|attr| attr.path().is_ident("test"))
+        && !is_test_only(&func.attrs)
+}
+
+/// Whether the attributes include `#[cfg(test)]`.
+pub(crate) fn is_test_only(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("cfg")
+            && attr
+                .parse_args::<syn::Ident>()
+                .is_ok_and(|ident| ident == "test")
+    })
+}
+
+/// Attributes go after the existing ones, in front of the visibility or
+/// the signature.
+fn insertion_point(func: &syn::ItemFn) -> LineColumn {
+    match &func.vis {
+        syn::Visibility::Inherited => func.sig.span().start(),
+        vis => vis.span().start(),
+    }
+}
+
+/// The visibility as written; `pub(self)` is private.
+pub(crate) fn visibility_of(vis: &syn::Visibility) -> Visibility {
+    match vis {
+        syn::Visibility::Public(_) => Visibility::Public,
+        syn::Visibility::Restricted(restricted) if restricted.path.is_ident("crate") => {
+            Visibility::Crate
+        }
+        syn::Visibility::Restricted(restricted) if restricted.path.is_ident("super") => {
+            Visibility::Super
+        }
+        syn::Visibility::Restricted(restricted) if restricted.path.is_ident("self") => {
+            Visibility::Private
+        }
+        syn::Visibility::Restricted(restricted) => Visibility::Restricted(
+            restricted
+                .path
+                .segments
+                .iter()
+                .map(|segment| segment.ident.to_string())
+                .collect::<Vec<_>>()
+                .join("::"),
+        ),
+        syn::Visibility::Inherited => Visibility::Private,
+    }
+}
+
+fn function_metadata(func: &syn::ItemFn, file: &str, module_path: &str) -> FunctionMetadata {
+    let visibility = visibility_of(&func.vis);
+    let generics = generic_params(&func.sig.generics);
+    let return_type = match &func.sig.output {
+        syn::ReturnType::Default => "()".to_string(),
+        syn::ReturnType::Type(_, ty) => normalize_type_name(&quote::quote!(#ty).to_string()),
+    };
+    let start = insertion_point(func);
+    let end = func.block.span().end();
+
+    FunctionMetadata {
+        name: format!("{}::{}", module_path, func.sig.ident),
+        simple_name: func.sig.ident.to_string(),
+        module_path: module_path.to_string(),
+        visibility,
+        is_async: func.sig.asyncness.is_some(),
+        is_closure: false,
+        generics,
+        return_type,
+        is_trait_method: false,
+        trait_name: None,
+        impl_type: None,
+        attributes: function_attributes(&func.attrs),
+        location: SourceLocation {
+            file: file.to_string(),
+            line: start.line,
+            column: start.column + 1,
+            end_line: end.line,
+            end_column: end.column + 1,
+        },
+    }
+}
+
+/// Attributes other than doc comments, as written.
+pub(crate) fn function_attributes(attrs: &[syn::Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter(|attr| !attr.path().is_ident("doc"))
+        .map(|attr| {
+            let text = attr
+                .span()
+                .source_text()
+                .unwrap_or_else(|| quote::quote!(#attr).to_string());
+            normalize_attribute(&text)
+        })
+        .collect()
+}
+
+/// Type parameters with their inline and where-clause bounds.
+pub(crate) fn generic_params(generics: &syn::Generics) -> Vec<GenericParam> {
+    generics
+        .type_params()
+        .map(|param| {
+            let where_bounds = generics
+                .where_clause
+                .iter()
+                .flat_map(|clause| &clause.predicates)
+                .filter_map(|predicate| match predicate {
+                    syn::WherePredicate::Type(predicate) => Some(predicate),
+                    _ => None,
+                })
+                .filter(|predicate| {
+                    matches!(&predicate.bounded_ty, syn::Type::Path(ty)
+                        if ty.qself.is_none() && ty.path.is_ident(&param.ident))
+                })
+                .flat_map(|predicate| &predicate.bounds);
+
+            GenericParam {
+                name: param.ident.to_string(),
+                bounds: param
+                    .bounds
+                    .iter()
+                    .chain(where_bounds)
+                    .map(|bound| normalize_type_name(&quote::quote!(#bound).to_string()))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Module path of a file relative to `src`: crate roots (`lib.rs`,
+/// `main.rs`, `bin/*.rs`) are `crate`, `a/b.rs` and `a/b/mod.rs` are
+/// `crate::a::b`.
+fn module_path_for(relative: &Path) -> String {
+    let mut parts: Vec<String> = relative
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect();
+    let file = parts.pop().unwrap_or_default();
+    let stem = file.trim_end_matches(".rs");
+
+    let is_root = (parts.is_empty() && (stem == "lib" || stem == "main"))
+        || (parts.first().map(String::as_str) == Some("bin") && parts.len() <= 2);
+    if is_root {
+        return "crate".to_string();
+    }
+    if stem != "mod" {
+        parts.push(stem.to_string());
+    }
+    std::iter::once("crate".to_string())
+        .chain(parts)
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// Byte offset of the start of each line.
+pub(crate) fn line_offsets(source: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+/// Byte offset of a span position (1-based line, column in characters).
+pub(crate) fn byte_offset(source: &str, lines: &[usize], at: LineColumn) -> usize {
+    let line_start = lines[at.line - 1];
+    source[line_start..]
+        .char_indices()
+        .nth(at.column)
+        .map_or(source.len(), |(i, _)| line_start + i)
+}
+
+/// Recursively copy `from` to `to`, except `target`, `.git` and `skip`.
+fn copy_tree(from: &Path, to: &Path, skip: &Path) -> Result<(), String> {
+    fs::create_dir_all(to).map_err(|e| format!("{}: {}", to.display(), e))?;
+    let entries = fs::read_dir(from).map_err(|e| format!("{}: {}", from.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path();
+        let name = entry.file_name();
+        if name == "target" || name == ".git" || path == skip {
+            continue;
+        }
+
+        let file_type = entry.file_type().map_err(|e| e.to_string())?;
+        if file_type.is_dir() {
+            copy_tree(&path, &to.join(&name), skip)?;
+        } else if file_type.is_file() {
+            fs::copy(&path, to.join(&name)).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// All `.rs` files under `dir`, sorted.
+pub(crate) fn rust_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.is_dir() {
+            files.extend(rust_files(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Make `path = "..."` entries pointing outside the crate absolute, and
+/// make the copy its own workspace.
+fn rewrite_manifest(manifest: &str, crate_dir: &Path) -> String {
+    let mut out = String::new();
+    for line in manifest.lines() {
+        out.push_str(&rewrite_path_entry(line, crate_dir));
+        out.push('\n');
+    }
+    if !manifest.lines().any(|line| line.trim() == "[workspace]") {
+        out.push_str("\n[workspace]\n");
+    }
+    out
+}
+
+fn rewrite_path_entry(line: &str, crate_dir: &Path) -> String {
+    let Some(start) = line.find("path = \"").map(|i| i + "path = \"".len()) else {
+        return line.to_string();
+    };
+    let Some(len) = line[start..].find('"') else {
+        return line.to_string();
+    };
+    let path = Path::new(&line[start..start + len]);
+    if path.is_absolute() {
+        return line.to_string();
+    }
+
+    let target = crate_dir.join(path);
+    let inside = target
+        .canonicalize()
+        .is_ok_and(|target| target.starts_with(crate_dir));
+    if inside {
+        return line.to_string();
+    }
+    let absolute = target.canonicalize().unwrap_or(target);
+    format!(
+        "{}{}{}",
+        &line[..start],
+        absolute.display(),
+        &line[start + len..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"//! Sample module
+
+/// Adds two numbers
+#[inline]
+pub fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+fn helper() {}
+
+pub const fn constant() -> i32 { 1 }
+
+pub mod nested {
+    pub async fn fetch(id: u64) -> Result<String, String> { Ok(id.to_string()) }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    pub fn test_add() {}
+}
+"#;
+
+    fn weaver(specs: &[&str]) -> SourceWeaver {
+        let mut weaver = SourceWeaver::new();
+        for spec in specs {
+            weaver.register(parse_aspect_spec(spec).unwrap());
+        }
+        weaver
+    }
+
+    #[test]
+    fn test_parse_aspect_spec() {
+        let aspect = parse_aspect_spec("execution(pub fn *(..)) = Logger::new(\"a=b\")").unwrap();
+        assert_eq!(aspect.pointcut, "execution(pub fn *(..))");
+        assert_eq!(aspect.aspect_name, "Logger::new(\"a=b\")");
+
+        assert!(parse_aspect_spec("execution(pub fn *(..))").is_err());
+        assert!(parse_aspect_spec("execution(pub fn *(..))=Logger::new(").is_err());
+        assert!(parse_aspect_spec("bogus=Logger::new()").is_err());
+    }
+
+    #[test]
+    fn test_function_visibility() {
+        let visibility = |item: &str| {
+            let func: syn::ItemFn = syn::parse_str(item).unwrap();
+            function_metadata(&func, "src/api.rs", "crate::api").visibility
+        };
+        assert_eq!(visibility("pub fn f() {}"), Visibility::Public);
+        assert_eq!(visibility("pub(crate) fn f() {}"), Visibility::Crate);
+        assert_eq!(visibility("pub(super) fn f() {}"), Visibility::Super);
+        assert_eq!(visibility("pub(self) fn f() {}"), Visibility::Private);
+        assert_eq!(
+            visibility("pub(in crate::api) fn f() {}"),
+            Visibility::Restricted("crate::api".to_string())
+        );
+        assert_eq!(visibility("fn f() {}"), Visibility::Private);
+    }
+
+    #[test]
+    fn test_weave_public_functions() {
+        let weaver = weaver(&["execution(pub fn *(..))=crate::LOGGER.clone()"]);
+        let result = weaver
+            .weave_source(SOURCE, "src/api.rs", "crate::api")
+            .unwrap();
+
+        let names: Vec<_> = result
+            .woven
+            .iter()
+            .map(|w| w.function.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["crate::api::add", "crate::api::nested::fetch"]);
+        assert!(result.woven[1].function.is_async);
+        let location = &result.woven[0].function.location;
+        assert_eq!((location.line, location.column), (5, 1));
+        assert_eq!((location.end_line, location.end_column), (7, 2));
+        let location = &result.woven[1].function.location;
+        assert_eq!((location.line, location.column), (14, 5));
+        assert_eq!((location.end_line, location.end_column), (14, 81));
+        assert_eq!(result.woven[0].function.attributes, vec!["inline"]);
+
+        // Attributes are spliced in without moving any line
+        assert!(result.source.contains(
+            "#[inline]\n#[::aspect_macros::aspect(crate::LOGGER.clone())] pub fn add(a: i32, b: i32) -> i32 {"
+        ));
+        assert!(result
+            .source
+            .contains("    #[::aspect_macros::aspect(crate::LOGGER.clone())] pub async fn fetch("));
+        assert_eq!(result.source.lines().count(), SOURCE.lines().count());
+        assert!(result.source.contains("\nfn helper() {}"));
+        assert!(result.source.contains("\npub const fn constant()"));
+        assert!(result.source.contains("    pub fn test_add() {}"));
+        syn::parse_file(&result.source).unwrap();
+    }
+
+    #[test]
+    fn test_priority_orders_attributes() {
+        let mut weaver = weaver(&["name(helper)=Inner::new()"]);
+        let mut outer = parse_aspect_spec("name(helper)=Outer::new()").unwrap();
+        outer.priority = 10;
+        weaver.register(outer);
+
+        let result = weaver.weave_source(SOURCE, "src/lib.rs", "crate").unwrap();
+        assert_eq!(result.woven.len(), 1);
+        assert_eq!(
+            result.woven[0].aspects,
+            vec!["Outer::new()", "Inner::new()"]
+        );
+        assert!(result.source.contains(
+            "\n#[::aspect_macros::aspect(Outer::new())] #[::aspect_macros::aspect(Inner::new())] fn helper() {}"
+        ));
+    }
+
+    #[test]
+    fn test_generic_params() {
+        let func: syn::ItemFn = syn::parse_quote! {
+            pub fn convert<T: Clone + std::fmt::Debug, U>(t: T) -> U
+            where
+                U: From<T>,
+                T: 'static,
+            {
+                U::from(t)
+            }
+        };
+        let function = function_metadata(&func, "src/lib.rs", "crate");
+
+        assert_eq!(function.generics.len(), 2);
+        assert_eq!(function.generics[0].name, "T");
+        assert_eq!(
+            function.generics[0].bounds,
+            vec!["Clone", "std::fmt::Debug", "'static"]
+        );
+        assert_eq!(function.generics[1].name, "U");
+        assert_eq!(function.generics[1].bounds, vec!["From<T>"]);
+        assert_eq!(function.return_type, "U");
+    }
+
+    #[test]
+    fn test_module_path_for() {
+        assert_eq!(module_path_for(Path::new("lib.rs")), "crate");
+        assert_eq!(module_path_for(Path::new("main.rs")), "crate");
+        assert_eq!(module_path_for(Path::new("bin/tool.rs")), "crate");
+        assert_eq!(module_path_for(Path::new("api.rs")), "crate::api");
+        assert_eq!(
+            module_path_for(Path::new("api/users.rs")),
+            "crate::api::users"
+        );
+        assert_eq!(module_path_for(Path::new("api/mod.rs")), "crate::api");
+    }
+
+    #[test]
+    fn test_weave_crate() {
+        let root = std::env::temp_dir().join(format!("aspect-source-weave-{}", std::process::id()));
+        let crate_dir = root.join("sample");
+        fs::create_dir_all(crate_dir.join("src/api")).unwrap();
+        fs::create_dir_all(root.join("shared")).unwrap();
+        fs::write(
+            crate_dir.join("Cargo.toml"),
+            "[package]\nname = \"sample\"\n\n[dependencies]\nshared = { path = \"../shared\" }\n",
+        )
+        .unwrap();
+        fs::write(
+            crate_dir.join("src/lib.rs"),
+            "pub mod api;\npub fn root() {}\n",
+        )
+        .unwrap();
+        fs::write(
+            crate_dir.join("src/api/mod.rs"),
+            "pub fn get() -> u32 { 1 }\n",
+        )
+        .unwrap();
+        fs::create_dir_all(crate_dir.join("target")).unwrap();
+
+        let weaver = weaver(&["within(crate::api)=Logger::new()"]);
+        let out_dir = crate_dir.join("target/aspect/woven");
+        let report = weaver.weave_crate(&crate_dir, &out_dir).unwrap();
+
+        assert_eq!(report.files, 2);
+        assert_eq!(report.woven.len(), 1);
+        assert_eq!(report.woven[0].function.name, "crate::api::get");
+        assert_eq!(report.woven[0].function.location.file, "src/api/mod.rs");
+        let api = fs::read_to_string(out_dir.join("src/api/mod.rs")).unwrap();
+        assert!(api.starts_with("#[::aspect_macros::aspect(Logger::new())] pub fn get()"));
+        assert!(!out_dir.join("target").exists());
+
+        let manifest = fs::read_to_string(out_dir.join("Cargo.toml")).unwrap();
+        let shared = root.join("shared").canonicalize().unwrap();
+        assert!(manifest.contains(&format!("path = \"{}\"", shared.display())));
+        assert!(manifest.ends_with("[workspace]\n"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,474 @@
+//! Approximate function metadata from source, for stable toolchains.
+//!
+//! The compiler driver needs a nightly toolchain with `rustc-dev`. Where
+//! that is not available, [`analyze_crate`] parses the crate root with
+//! `syn` and follows its `mod` declarations to the other source files,
+//! producing the [`FunctionMetadata`] the driver would, as far as it can
+//! be told from syntax alone:
+//!
+//! - Paths are not resolved: return types, bounds and trait names are as
+//!   written, and the types of impl blocks are qualified with the module
+//!   they are in
+//! - Items generated by macros, and `#[cfg]`-ed out items other than
+//!   `#[cfg(test)]` ones, are not seen
+//! - Nested functions and closures are not listed
+//!
+//! Functions are named like rustc names them: `api::get`,
+//! `api::Client::send` or `<api::Client as Drop>::drop`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use proc_macro2::LineColumn;
+use syn::spanned::Spanned;
+
+use crate::source::{function_attributes, generic_params, is_test_only, visibility_of};
+use crate::types::{normalize_type_name, FunctionMetadata, SourceLocation, Visibility};
+
+/// Types that are never qualified with the module of their impl block.
+const UNQUALIFIED_TYPES: &[&str] = &[
+    "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64",
+    "i128", "isize", "f32", "f64", "String", "Vec", "Option", "Result", "Box",
+];
+
+/// The functions of the crate whose root is `root` (e.g. `src/lib.rs`).
+///
+/// File names in locations are `root` joined with the module file paths;
+/// module files that do not exist are skipped, as they are usually
+/// configured out.
+pub fn analyze_crate(root: &Path) -> Result<Vec<FunctionMetadata>, String> {
+    let mut functions = Vec::new();
+    let dir = root.parent().unwrap_or_else(|| Path::new(""));
+    analyze_file(root, "crate", dir, &mut functions)?;
+    Ok(functions)
+}
+
+/// The functions of one file whose items are in `module_path` (e.g.
+/// `crate::api`). Modules declared without a body are not followed.
+pub fn analyze_source(
+    source: &str,
+    file: &str,
+    module_path: &str,
+) -> Result<Vec<FunctionMetadata>, String> {
+    let ast = syn::parse_file(source).map_err(|e| format!("failed to parse {}: {}", file, e))?;
+    let mut functions = Vec::new();
+    let scope = Scope {
+        file,
+        file_dir: None,
+        module_path: module_path.to_string(),
+        module_dir: PathBuf::new(),
+    };
+    collect_items(&ast.items, &scope, &mut functions)?;
+    Ok(functions)
+}
+
+/// Analyze the file at `path`, whose child module files are in
+/// `module_dir`.
+fn analyze_file(
+    path: &Path,
+    module_path: &str,
+    module_dir: &Path,
+    functions: &mut Vec<FunctionMetadata>,
+) -> Result<(), String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let file = path.to_string_lossy();
+    let ast = syn::parse_file(&source).map_err(|e| format!("failed to parse {}: {}", file, e))?;
+
+    let scope = Scope {
+        file: &file,
+        file_dir: Some(path.parent().unwrap_or_else(|| Path::new(""))),
+        module_path: module_path.to_string(),
+        module_dir: module_dir.to_path_buf(),
+    };
+    collect_items(&ast.items, &scope, functions)
+}
+
+/// Where items are.
+struct Scope<'a> {
+    /// File the items are in
+    file: &'a str,
+
+    /// Directory of the file, `None` when modules are not followed
+    file_dir: Option<&'a Path>,
+
+    /// Module the items are in (e.g. `crate::api`)
+    module_path: String,
+
+    /// Directory of the files of child modules
+    module_dir: PathBuf,
+}
+
+impl Scope<'_> {
+    /// `name` in the module, as rustc prints paths: without `crate`.
+    fn qualify(&self, name: &str) -> String {
+        match self.module_path.strip_prefix("crate::") {
+            Some(module) => format!("{}::{}", module, name),
+            None => name.to_string(),
+        }
+    }
+}
+
+fn collect_items(
+    items: &[syn::Item],
+    scope: &Scope<'_>,
+    functions: &mut Vec<FunctionMetadata>,
+) -> Result<(), String> {
+    for item in items {
+        match item {
+            syn::Item::Fn(func) if !is_test(&func.attrs) => {
+                functions.push(metadata(
+                    scope,
+                    Function {
+                        name: scope.qualify(&func.sig.ident.to_string()),
+                        sig: &func.sig,
+                        attrs: &func.attrs,
+                        visibility: visibility_of(&func.vis),
+                        start: start_of(&func.vis, &func.sig),
+                        end: func.block.span().end(),
+                        impl_generics: None,
+                        trait_name: None,
+                        impl_type: None,
+                    },
+                ));
+            }
+            syn::Item::Impl(item_impl) if !is_test_only(&item_impl.attrs) => {
+                collect_impl(item_impl, scope, functions);
+            }
+            syn::Item::Trait(item_trait) if !is_test_only(&item_trait.attrs) => {
+                let trait_name = scope.qualify(&item_trait.ident.to_string());
+                for item in &item_trait.items {
+                    let syn::TraitItem::Fn(method) = item else {
+                        continue;
+                    };
+                    let Some(body) = &method.default else {
+                        continue;
+                    };
+                    functions.push(metadata(
+                        scope,
+                        Function {
+                            name: format!("{}::{}", trait_name, method.sig.ident),
+                            sig: &method.sig,
+                            attrs: &method.attrs,
+                            visibility: visibility_of(&item_trait.vis),
+                            start: method.sig.span().start(),
+                            end: body.span().end(),
+                            impl_generics: Some(&item_trait.generics),
+                            trait_name: Some(trait_name.clone()),
+                            impl_type: None,
+                        },
+                    ));
+                }
+            }
+            syn::Item::Mod(module) if !is_test_only(&module.attrs) => {
+                collect_module(module, scope, functions)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn collect_impl(
+    item_impl: &syn::ItemImpl,
+    scope: &Scope<'_>,
+    functions: &mut Vec<FunctionMetadata>,
+) {
+    let self_ty = &item_impl.self_ty;
+    let written = normalize_type_name(&quote::quote!(#self_ty).to_string());
+    let impl_type = match &**self_ty {
+        syn::Type::Path(path)
+            if path.qself.is_none()
+                && path.path.segments.len() == 1
+                && !UNQUALIFIED_TYPES
+                    .contains(&path.path.segments[0].ident.to_string().as_str()) =>
+        {
+            scope.qualify(&written)
+        }
+        _ => written,
+    };
+    let trait_name = item_impl.trait_.as_ref().map(|(_, path, _)| {
+        let path = quote::quote!(#path).to_string();
+        normalize_type_name(&path)
+    });
+
+    for item in &item_impl.items {
+        let syn::ImplItem::Fn(method) = item else {
+            continue;
+        };
+        if is_test(&method.attrs) {
+            continue;
+        }
+        let name = match &trait_name {
+            Some(trait_name) => format!("<{} as {}>::{}", impl_type, trait_name, method.sig.ident),
+            None => {
+                let base = impl_type.split('<').next().unwrap_or(&impl_type);
+                format!("{}::{}", base, method.sig.ident)
+            }
+        };
+        // Trait impl items are as visible as the trait
+        let visibility = match trait_name {
+            Some(_) => Visibility::Public,
+            None => visibility_of(&method.vis),
+        };
+
+        functions.push(metadata(
+            scope,
+            Function {
+                name,
+                sig: &method.sig,
+                attrs: &method.attrs,
+                visibility,
+                start: start_of(&method.vis, &method.sig),
+                end: method.block.span().end(),
+                impl_generics: Some(&item_impl.generics),
+                trait_name: trait_name.clone(),
+                impl_type: Some(impl_type.clone()),
+            },
+        ));
+    }
+}
+
+fn collect_module(
+    module: &syn::ItemMod,
+    scope: &Scope<'_>,
+    functions: &mut Vec<FunctionMetadata>,
+) -> Result<(), String> {
+    let name = module.ident.to_string();
+    let module_path = format!("{}::{}", scope.module_path, name);
+    let path_attr = module.attrs.iter().find_map(|attr| match &attr.meta {
+        syn::Meta::NameValue(meta) if meta.path.is_ident("path") => match &meta.value {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(path),
+                ..
+            }) => Some(path.value()),
+            _ => None,
+        },
+        _ => None,
+    });
+
+    if let Some((_, items)) = &module.content {
+        let inner = Scope {
+            file: scope.file,
+            file_dir: scope.file_dir,
+            module_path,
+            module_dir: scope.module_dir.join(&name),
+        };
+        return collect_items(items, &inner, functions);
+    }
+
+    let Some(file_dir) = scope.file_dir else {
+        return Ok(());
+    };
+    // A file given with #[path] is relative to the declaring file, and
+    // its child modules are next to it, as for a mod.rs
+    let (path, module_dir) = match path_attr {
+        Some(path) => {
+            let path = file_dir.join(path);
+            let dir = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+            (path, dir)
+        }
+        None => {
+            let flat = scope.module_dir.join(format!("{}.rs", name));
+            let nested = scope.module_dir.join(&name).join("mod.rs");
+            let path = if flat.is_file() { flat } else { nested };
+            (path, scope.module_dir.join(&name))
+        }
+    };
+    if !path.is_file() {
+        return Ok(());
+    }
+    analyze_file(&path, &module_path, &module_dir, functions)
+}
+
+/// A function as found in the syntax tree.
+struct Function<'a> {
+    name: String,
+    sig: &'a syn::Signature,
+    attrs: &'a [syn::Attribute],
+    visibility: Visibility,
+    start: LineColumn,
+    end: LineColumn,
+    impl_generics: Option<&'a syn::Generics>,
+    trait_name: Option<String>,
+    impl_type: Option<String>,
+}
+
+fn metadata(scope: &Scope<'_>, function: Function<'_>) -> FunctionMetadata {
+    let sig = function.sig;
+    // rustc does not tell `pub(crate)` from private in the crate root
+    let visibility = match function.visibility {
+        Visibility::Crate if scope.module_path == "crate" => Visibility::Private,
+        visibility => visibility,
+    };
+    let return_type = match &sig.output {
+        syn::ReturnType::Default => "()".to_string(),
+        syn::ReturnType::Type(_, ty) => normalize_type_name(&quote::quote!(#ty).to_string()),
+    };
+    // Parameters of the impl block or trait come first, as in rustc
+    let generics = function
+        .impl_generics
+        .map(generic_params)
+        .unwrap_or_default()
+        .into_iter()
+        .chain(generic_params(&sig.generics))
+        .collect();
+
+    FunctionMetadata {
+        name: function.name,
+        simple_name: sig.ident.to_string(),
+        module_path: scope.module_path.clone(),
+        visibility,
+        is_async: sig.asyncness.is_some(),
+        is_closure: false,
+        generics,
+        return_type,
+        is_trait_method: function.trait_name.is_some(),
+        trait_name: function.trait_name,
+        impl_type: function.impl_type,
+        attributes: function_attributes(function.attrs),
+        location: SourceLocation {
+            file: scope.file.to_string(),
+            line: function.start.line,
+            column: function.start.column + 1,
+            end_line: function.end.line,
+            end_column: function.end.column + 1,
+        },
+    }
+}
+
+/// A function starts at its visibility, or at its signature.
+fn start_of(vis: &syn::Visibility, sig: &syn::Signature) -> LineColumn {
+    match vis {
+        syn::Visibility::Inherited => sig.span().start(),
+        vis => vis.span().start(),
+    }
+}
+
+/// Whether the attributes make an item test-only.
+fn is_test(attrs: &[syn::Attribute]) -> bool {
+    is_test_only(attrs) || attrs.iter().any(|attr| attr.path().is_ident("test"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"pub mod api {
+    pub struct Client<T> {
+        inner: T,
+    }
+
+    impl<T: Clone> Client<T> {
+        pub async fn send(&self, body: &str) -> Result<usize, String> {
+            Ok(body.len())
+        }
+    }
+
+    impl<T> Drop for Client<T> {
+        fn drop(&mut self) {}
+    }
+
+    pub trait Service {
+        fn call(&self) -> u32 {
+            0
+        }
+        fn name(&self) -> &str;
+    }
+}
+
+#[inline]
+pub(crate) fn helper<T>(t: T) -> T where T: Copy {
+    t
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_helper() {}
+}
+"#;
+
+    #[test]
+    fn test_analyze_source() {
+        let functions = analyze_source(SOURCE, "src/lib.rs", "crate").unwrap();
+        let names: Vec<&str> = functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "api::Client::send",
+                "<api::Client<T> as Drop>::drop",
+                "api::Service::call",
+                "helper"
+            ]
+        );
+
+        let send = &functions[0];
+        assert_eq!(send.module_path, "crate::api");
+        assert_eq!(send.visibility, Visibility::Public);
+        assert!(send.is_async);
+        assert_eq!(send.return_type, "Result<usize, String>");
+        assert_eq!(send.impl_type.as_deref(), Some("api::Client<T>"));
+        assert_eq!(send.generics[0].bounds, ["Clone"]);
+        assert_eq!(
+            (send.location.line, send.location.column),
+            (7, 9),
+            "starts at `pub`"
+        );
+        assert_eq!((send.location.end_line, send.location.end_column), (9, 10));
+
+        let drop = &functions[1];
+        assert!(drop.is_trait_method);
+        assert_eq!(drop.trait_name.as_deref(), Some("Drop"));
+        assert_eq!(drop.visibility, Visibility::Public);
+
+        let call = &functions[2];
+        assert_eq!(call.trait_name.as_deref(), Some("api::Service"));
+        assert_eq!(call.impl_type, None);
+
+        let helper = &functions[3];
+        assert_eq!(helper.visibility, Visibility::Private);
+        assert_eq!(helper.attributes, ["inline"]);
+        assert_eq!(helper.generics[0].bounds, ["Copy"]);
+        assert_eq!(helper.module_path, "crate");
+    }
+
+    #[test]
+    fn test_analyze_crate() {
+        let dir = std::env::temp_dir().join(format!("aspect-syntax-{}", std::process::id()));
+        let src = dir.join("src");
+        fs::create_dir_all(src.join("api")).unwrap();
+        fs::create_dir_all(src.join("extra")).unwrap();
+        fs::write(
+            src.join("lib.rs"),
+            "pub mod api;\nmod gone;\n#[path = \"extra/util.rs\"]\nmod util;\npub fn root() {}\n",
+        )
+        .unwrap();
+        fs::write(src.join("api.rs"), "pub mod v1;\npub fn get() {}\n").unwrap();
+        fs::write(src.join("api/v1.rs"), "pub fn list() {}\n").unwrap();
+        fs::write(src.join("extra/util.rs"), "fn trim() {}\n").unwrap();
+
+        let functions = analyze_crate(&src.join("lib.rs")).unwrap();
+        let found: Vec<(&str, &str)> = functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.module_path.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("api::v1::list", "crate::api::v1"),
+                ("api::get", "crate::api"),
+                ("util::trim", "crate::util"),
+                ("root", "crate"),
+            ]
+        );
+        assert_eq!(
+            Path::new(&functions[0].location.file),
+            src.join("api/v1.rs")
+        );
+
+        fs::write(src.join("api/v1.rs"), "pub fn list( {}\n").unwrap();
+        assert!(analyze_crate(&src.join("lib.rs")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,112 @@
+//! Time spent in each phase of the analysis.
+//!
+//! [`PhaseTimings`] records how long extraction, matching and the other
+//! phases took, for the breakdown printed with `--aspect-verbose`.
+
+use std::fmt::Write as _;
+use std::time::{Duration, Instant};
+
+/// Durations of the analysis phases, in the order they ran.
+#[derive(Debug, Clone, Default)]
+pub struct PhaseTimings {
+    phases: Vec<(String, Duration)>,
+}
+
+impl PhaseTimings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `phase`, recording how long it took as `name`.
+    pub fn time<T>(&mut self, name: &str, phase: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = phase();
+        self.record(name, start.elapsed());
+        result
+    }
+
+    /// Record that the phase `name` took `duration`.
+    pub fn record(&mut self, name: &str, duration: Duration) {
+        self.phases.push((name.to_string(), duration));
+    }
+
+    pub fn phases(&self) -> &[(String, Duration)] {
+        &self.phases
+    }
+
+    /// Time spent in all phases.
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, duration)| *duration).sum()
+    }
+
+    /// The breakdown, one phase per line with its share of the total.
+    pub fn summary(&self) -> String {
+        let total = self.total();
+        let width = self
+            .phases
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0);
+
+        let mut summary = String::new();
+        for (name, duration) in &self.phases {
+            let share = if total.is_zero() {
+                0.0
+            } else {
+                duration.as_secs_f64() / total.as_secs_f64() * 100.0
+            };
+            let _ = writeln!(
+                summary,
+                "{:width$}  {:>9.3} ms  {:>5.1}%",
+                name,
+                duration.as_secs_f64() * 1000.0,
+                share,
+                width = width
+            );
+        }
+        let _ = writeln!(
+            summary,
+            "{:width$}  {:>9.3} ms",
+            "total",
+            total.as_secs_f64() * 1000.0,
+            width = width
+        );
+        summary
+    }
+
+    pub fn print_summary(&self) {
+        println!("\n=== Timing ===");
+        print!("{}", self.summary());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_timings() {
+        let mut timings = PhaseTimings::new();
+        assert_eq!(timings.time("extraction", || 42), 42);
+        timings.record("matching", Duration::from_millis(30));
+        assert_eq!(timings.phases().len(), 2);
+        assert_eq!(timings.phases()[1].0, "matching");
+        assert!(timings.total() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut timings = PhaseTimings::new();
+        timings.record("extraction", Duration::from_millis(75));
+        timings.record("matching", Duration::from_micros(25_500));
+
+        assert_eq!(
+            timings.summary(),
+            "extraction     75.000 ms   74.6%\n\
+             matching       25.500 ms   25.4%\n\
+             total         100.500 ms\n"
+        );
+        assert_eq!(PhaseTimings::new().summary(), "total      0.000 ms\n");
+    }
+}
@@ -1,29 +1,55 @@
 //! Type definitions for compiler metadata extraction.
 
+use std::fmt;
+
+use aspect_core::pointcut::FunctionInfo;
+use serde::{Deserialize, Serialize};
+
 /// Visibility level of a function.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Visibility {
     /// Public (pub)
     Public,
     /// Public within crate (pub(crate))
     Crate,
-    /// Public within module (pub(in path))
-    Restricted,
+    /// Public within the parent module (pub(super))
+    Super,
+    /// Public within a module (pub(in path)), e.g. `crate::api`
+    Restricted(String),
     /// Private (no pub)
     Private,
 }
 
+/// The visibility as written in Rust, empty for private.
+impl fmt::Display for Visibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Visibility::Public => write!(f, "pub"),
+            Visibility::Crate => write!(f, "pub(crate)"),
+            Visibility::Super => write!(f, "pub(super)"),
+            Visibility::Restricted(path) => write!(f, "pub(in {})", path),
+            Visibility::Private => Ok(()),
+        }
+    }
+}
+
 /// Generic parameter information.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GenericParam {
     /// Parameter name (e.g., "T")
     pub name: String,
-    /// Trait bounds (e.g., ["Clone", "Debug"])
+    /// Trait bounds, inline and from where clauses, normalized with
+    /// [`normalize_type_name`] (e.g., ["Clone", "std::fmt::Debug"])
     pub bounds: Vec<String>,
 }
 
-/// Source code location.
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Source code location: the span of a function.
+///
+/// Lines and columns start at 1, and columns count characters. The end is
+/// exclusive, as in SARIF regions: `end_column` is the column just past the
+/// last character. A line of 0 means the location is unknown.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SourceLocation {
     /// File path
     pub file: String,
@@ -31,13 +57,17 @@ pub struct SourceLocation {
     pub line: usize,
     /// Column number
     pub column: usize,
+    /// Line number of the end
+    pub end_line: usize,
+    /// Column number just past the end
+    pub end_column: usize,
 }
 
 /// Complete metadata for a function extracted from MIR.
 ///
 /// This contains all information needed for pointcut matching and
 /// aspect weaving.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionMetadata {
     /// Fully qualified function name (e.g., "my_crate::api::get_user")
     pub name: String,
@@ -54,12 +84,36 @@ pub struct FunctionMetadata {
     /// Whether the function is async
     pub is_async: bool,
 
-    /// Generic parameters
+    /// Whether this is a closure, named after the function it is in (e.g.,
+    /// "my_crate::api::get_user::{closure#0}")
+    pub is_closure: bool,
+
+    /// Type parameters, including those of the enclosing impl or trait
     pub generics: Vec<GenericParam>,
 
-    /// Return type (as string for now)
+    /// Return type, normalized with [`normalize_type_name`]; the awaited
+    /// type for async functions
     pub return_type: String,
 
+    /// Whether the function is a trait method: a default method of a trait
+    /// or a method of a trait impl
+    pub is_trait_method: bool,
+
+    /// Trait of a trait method (e.g., "std::fmt::Display")
+    pub trait_name: Option<String>,
+
+    /// Self type of the impl block a method is in (e.g., "User"); `None`
+    /// for free functions and default trait methods
+    pub impl_type: Option<String>,
+
+    /// Attributes of the function, normalized with [`normalize_attribute`]
+    /// and without doc comments (e.g., `inline`, `get("/users")`).
+    ///
+    /// The compiler driver sees the function after macro expansion, so
+    /// attribute macros such as `#[tokio::main]` are only listed by the
+    /// source weaver.
+    pub attributes: Vec<String>,
+
     /// Source location
     pub location: SourceLocation,
 }
@@ -76,8 +130,7 @@ impl FunctionMetadata {
         // Extract the simple function name (last component after ::)
         let simple_name = self.name.rsplit("::").next().unwrap_or(&self.name);
 
-        if pattern.ends_with('*') {
-            let prefix = &pattern[..pattern.len() - 1];
+        if let Some(prefix) = pattern.strip_suffix('*') {
             simple_name.starts_with(prefix) || self.name.contains(&format!("::{}", prefix))
         } else {
             simple_name == pattern || self.name.ends_with(&format!("::{}", pattern))
@@ -89,17 +142,186 @@ impl FunctionMetadata {
         self.module_path == module || self.module_path.starts_with(&format!("{}::", module))
     }
 
+    /// Describe the impl block or trait a method is in, e.g.
+    /// `impl Display for User`, `impl User` or `trait Display`.
+    pub fn impl_context(&self) -> Option<String> {
+        match (&self.trait_name, &self.impl_type) {
+            (Some(trait_name), Some(impl_type)) => {
+                Some(format!("impl {} for {}", trait_name, impl_type))
+            }
+            (None, Some(impl_type)) => Some(format!("impl {}", impl_type)),
+            (Some(trait_name), None) => Some(format!("trait {}", trait_name)),
+            (None, None) => None,
+        }
+    }
+
+    /// Check if the function has an attribute with the given path.
+    ///
+    /// A path without `::` also matches the last segment, so `"get"`
+    /// matches both `#[get("/users")]` and `#[actix_web::get("/users")]`.
+    pub fn has_attribute(&self, path: &str) -> bool {
+        let path: String = path.split_whitespace().collect();
+        self.attributes.iter().any(|attribute| {
+            let end = attribute.find(['(', '=']).unwrap_or(attribute.len());
+            let attribute_path: String = attribute[..end].split_whitespace().collect();
+            attribute_path == path
+                || (!path.contains("::") && attribute_path.ends_with(&format!("::{}", path)))
+        })
+    }
+
+    /// Whether the function is a test: `#[test]`, the `#[test]` of an async
+    /// runtime such as `#[tokio::test]`, `#[rstest]` or `#[test_case(..)]`.
+    pub fn is_test(&self) -> bool {
+        ["test", "rstest", "test_case"]
+            .into_iter()
+            .any(|path| self.has_attribute(path))
+    }
+
+    /// The function as seen by aspect-core's pointcut matching, so that
+    /// `execution` and `within` match here exactly as they do at runtime.
+    pub fn to_function_info(&self) -> FunctionInfo {
+        FunctionInfo::new(
+            &self.simple_name,
+            &self.module_path,
+            self.visibility.to_string(),
+        )
+        .with_return_type(&self.return_type)
+    }
+
     /// Check if this function is public (any form of pub).
     pub fn is_public(&self) -> bool {
         matches!(
             self.visibility,
-            Visibility::Public | Visibility::Crate | Visibility::Restricted
+            Visibility::Public | Visibility::Crate | Visibility::Super | Visibility::Restricted(_)
         )
     }
 }
 
+/// Paths of prelude types, printed without them.
+const PRELUDE_PATHS: &[&str] = &[
+    "std::result::",
+    "core::result::",
+    "std::option::",
+    "core::option::",
+    "std::string::",
+    "alloc::string::",
+    "std::vec::",
+    "alloc::vec::",
+    "std::boxed::",
+    "alloc::boxed::",
+];
+
+/// Prelude traits, relative to `std`, `core` or `alloc`, printed without
+/// their paths.
+const PRELUDE_ITEMS: &[&str] = &[
+    "clone::Clone",
+    "marker::Copy",
+    "marker::Send",
+    "marker::Sized",
+    "marker::Sync",
+    "marker::Unpin",
+    "cmp::Eq",
+    "cmp::Ord",
+    "cmp::PartialEq",
+    "cmp::PartialOrd",
+    "convert::AsMut",
+    "convert::AsRef",
+    "convert::From",
+    "convert::Into",
+    "convert::TryFrom",
+    "convert::TryInto",
+    "default::Default",
+    "iter::DoubleEndedIterator",
+    "iter::ExactSizeIterator",
+    "iter::Extend",
+    "iter::IntoIterator",
+    "iter::Iterator",
+    "ops::Drop",
+    "ops::Fn",
+    "ops::FnMut",
+    "ops::FnOnce",
+    "borrow::ToOwned",
+    "string::ToString",
+];
+
+/// Length of the path to strip if `rest` starts with a prelude trait.
+fn prelude_item_path(rest: &str, is_word: impl Fn(char) -> bool) -> Option<usize> {
+    let root = ["std::", "core::", "alloc::"]
+        .into_iter()
+        .find(|root| rest.starts_with(root))?;
+    let after = &rest[root.len()..];
+    PRELUDE_ITEMS
+        .iter()
+        .find(|item| after.starts_with(*item) && !after[item.len()..].starts_with(&is_word))
+        .map(|item| root.len() + item.rfind("::").unwrap() + 2)
+}
+
+/// Normalize the rendering of a type, so the same type is spelled the same
+/// whether it was printed by rustc or from source tokens.
+///
+/// Whitespace is reduced to single spaces after commas, around `->` and
+/// `=`, and between words (`dyn Trait`, `&'a mut T`), and prelude types
+/// and traits lose their paths: `std::result::Result<std::string::String, E>`
+/// becomes `Result<String, E>`, and `core::clone::Clone` becomes `Clone`.
+pub fn normalize_type_name(ty: &str) -> String {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '\'';
+
+    let mut compact = String::with_capacity(ty.len());
+    let mut pending_space = false;
+    for c in ty.chars() {
+        if c.is_whitespace() {
+            pending_space = true;
+            continue;
+        }
+        if pending_space && compact.ends_with(is_word) && is_word(c) {
+            compact.push(' ');
+        }
+        pending_space = false;
+        compact.push(c);
+    }
+
+    let mut out = String::with_capacity(compact.len());
+    let mut rest = compact.as_str();
+    while let Some(c) = rest.chars().next() {
+        let at_boundary = !out.ends_with(|c: char| is_word(c) || c == ':');
+        if let Some(path) = PRELUDE_PATHS
+            .iter()
+            .find(|path| at_boundary && rest.starts_with(*path))
+        {
+            rest = &rest[path.len()..];
+        } else if let Some(len) = prelude_item_path(rest, is_word).filter(|_| at_boundary) {
+            rest = &rest[len..];
+        } else if rest.starts_with("->") {
+            out.push_str(" -> ");
+            rest = &rest[2..];
+        } else {
+            match c {
+                ',' => out.push_str(", "),
+                '=' => out.push_str(" = "),
+                c => out.push(c),
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+/// Normalize the source text of an attribute to its contents: `#[get("/")]`
+/// becomes `get("/")`.
+pub fn normalize_attribute(text: &str) -> String {
+    let text = text.trim();
+    let inner = text
+        .strip_prefix("#!")
+        .or_else(|| text.strip_prefix('#'))
+        .map(str::trim_start)
+        .and_then(|text| text.strip_prefix('['))
+        .and_then(|text| text.strip_suffix(']'))
+        .unwrap_or(text);
+    inner.trim().to_string()
+}
+
 /// Result of pointcut matching.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MatchedFunction {
     /// The function metadata
     pub function: FunctionMetadata,
@@ -122,12 +344,19 @@ mod tests {
             module_path: "my_crate::api".to_string(),
             visibility: Visibility::Public,
             is_async: false,
+            is_closure: false,
             generics: vec![],
             return_type: "User".to_string(),
+            is_trait_method: false,
+            trait_name: None,
+            impl_type: None,
+            attributes: vec![],
             location: SourceLocation {
                 file: "src/api.rs".to_string(),
                 line: 42,
                 column: 1,
+                end_line: 42,
+                end_column: 1,
             },
         }
     }
@@ -161,6 +390,151 @@ mod tests {
         assert!(!private_func.is_public());
     }
 
+    #[test]
+    fn test_to_function_info() {
+        let info = sample_function().to_function_info();
+        assert_eq!(info.name, "fetch_user");
+        assert_eq!(info.module_path, "my_crate::api");
+        assert_eq!(info.visibility, "pub");
+        assert_eq!(info.return_type.as_deref(), Some("User"));
+
+        let crate_func = FunctionMetadata {
+            visibility: Visibility::Crate,
+            ..sample_function()
+        };
+        assert_eq!(crate_func.to_function_info().visibility, "pub(crate)");
+    }
+
+    #[test]
+    fn test_visibility_display() {
+        assert_eq!(Visibility::Public.to_string(), "pub");
+        assert_eq!(Visibility::Crate.to_string(), "pub(crate)");
+        assert_eq!(Visibility::Super.to_string(), "pub(super)");
+        assert_eq!(
+            Visibility::Restricted("crate::api".to_string()).to_string(),
+            "pub(in crate::api)"
+        );
+        assert_eq!(Visibility::Private.to_string(), "");
+
+        let json = serde_json::to_value(Visibility::Restricted("crate::api".to_string())).unwrap();
+        assert_eq!(json, serde_json::json!({ "restricted": "crate::api" }));
+        assert_eq!(serde_json::to_value(Visibility::Super).unwrap(), "super");
+    }
+
+    #[test]
+    fn test_matched_function_json() {
+        let matched = MatchedFunction {
+            function: sample_function(),
+            aspect: "LoggingAspect".to_string(),
+            pointcut: "execution(pub fn *(..))".to_string(),
+        };
+        let json = serde_json::to_string(&matched).unwrap();
+        let back: MatchedFunction = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.function.name, matched.function.name);
+        assert_eq!(back.function.location, matched.function.location);
+        assert_eq!(back.aspect, "LoggingAspect");
+        assert_eq!(back.pointcut, matched.pointcut);
+    }
+
+    #[test]
+    fn test_impl_context() {
+        let func = sample_function();
+        assert_eq!(func.impl_context(), None);
+
+        let method = FunctionMetadata {
+            is_trait_method: true,
+            trait_name: Some("std::fmt::Display".to_string()),
+            impl_type: Some("User".to_string()),
+            ..func.clone()
+        };
+        assert_eq!(
+            method.impl_context().as_deref(),
+            Some("impl std::fmt::Display for User")
+        );
+
+        let inherent = FunctionMetadata {
+            impl_type: Some("User".to_string()),
+            ..func.clone()
+        };
+        assert_eq!(inherent.impl_context().as_deref(), Some("impl User"));
+
+        let default_method = FunctionMetadata {
+            is_trait_method: true,
+            trait_name: Some("Repository".to_string()),
+            ..func
+        };
+        assert_eq!(
+            default_method.impl_context().as_deref(),
+            Some("trait Repository")
+        );
+    }
+
+    #[test]
+    fn test_attributes() {
+        assert_eq!(normalize_attribute("#[inline]"), "inline");
+        assert_eq!(
+            normalize_attribute("# [ get(\"/users\") ]"),
+            "get(\"/users\")"
+        );
+        assert_eq!(
+            normalize_attribute("#![allow(dead_code)]"),
+            "allow(dead_code)"
+        );
+
+        let func = FunctionMetadata {
+            attributes: vec![
+                "actix_web::get(\"/users\")".to_string(),
+                "tokio :: main".to_string(),
+                "must_use = \"check it\"".to_string(),
+            ],
+            ..sample_function()
+        };
+        assert!(func.has_attribute("get"));
+        assert!(func.has_attribute("actix_web::get"));
+        assert!(func.has_attribute("tokio::main"));
+        assert!(func.has_attribute("must_use"));
+        assert!(!func.has_attribute("tokio"));
+        assert!(!func.has_attribute("web::get"));
+        assert!(!func.has_attribute("inline"));
+    }
+
+    #[test]
+    fn test_normalize_type_name() {
+        assert_eq!(normalize_type_name("()"), "()");
+        assert_eq!(
+            normalize_type_name("std::result::Result<std::string::String, MyError>"),
+            "Result<String, MyError>"
+        );
+        // Token-stream rendering, as printed by quote
+        assert_eq!(
+            normalize_type_name("Result < Vec < u8 > , Box < dyn std :: error :: Error > >"),
+            "Result<Vec<u8>, Box<dyn std::error::Error>>"
+        );
+        assert_eq!(normalize_type_name("& 'a mut str"), "&'a mut str");
+        assert_eq!(
+            normalize_type_name("impl Future<Output=core::option::Option<i32>>"),
+            "impl Future<Output = Option<i32>>"
+        );
+        assert_eq!(normalize_type_name("fn(i32)->i32"), "fn(i32) -> i32");
+        assert_eq!(
+            normalize_type_name("core::ops::FnOnce(u8) -> std::string::String"),
+            "FnOnce(u8) -> String"
+        );
+        assert_eq!(
+            normalize_type_name("std::iter::Peekable<I>"),
+            "std::iter::Peekable<I>"
+        );
+        assert_eq!(
+            normalize_type_name("std::clone::CloneToUninit"),
+            "std::clone::CloneToUninit"
+        );
+        // Only whole paths are shortened
+        assert_eq!(
+            normalize_type_name("mystd::vec::Vec<u8>"),
+            "mystd::vec::Vec<u8>"
+        );
+    }
+
     #[test]
     fn test_generic_params() {
         let generic_param = GenericParam {
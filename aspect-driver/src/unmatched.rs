@@ -0,0 +1,425 @@
+//! Pointcuts matching no function.
+//!
+//! A pointcut that matches nothing is almost always a typo, such as
+//! `within(crate::apii)`, and silently leaves functions unadvised.
+//! [`find_unmatched`] finds such pointcuts and, for every clause of them that
+//! matches nothing on its own, the closest names, modules or attributes
+//! of the crate that it could have meant.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::r#match::{parse_pointcut, AdviceHook, PointcutExpr, PointcutMatcher};
+use crate::types::FunctionMetadata;
+
+/// Most suggestions given for one clause.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// A pointcut matching no function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnmatchedPointcut {
+    /// Pointcut expression
+    pub pointcut: String,
+
+    /// Clauses of the pointcut matching no function, with suggestions
+    pub near_misses: Vec<NearMiss>,
+}
+
+/// A clause matching no function, and what it may have meant.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NearMiss {
+    /// The clause, e.g. `within(crate::apii)`
+    pub clause: String,
+
+    /// Similar clauses that match, closest first
+    pub suggestions: Vec<String>,
+}
+
+impl fmt::Display for UnmatchedPointcut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pointcut `{}` matches no function", self.pointcut)?;
+        for near_miss in &self.near_misses {
+            if near_miss.suggestions.is_empty() {
+                continue;
+            }
+            let suggestions: Vec<String> = near_miss
+                .suggestions
+                .iter()
+                .map(|s| format!("`{}`", s))
+                .collect();
+            write!(
+                f,
+                "\n  help: `{}` matches nothing; did you mean {}?",
+                near_miss.clause,
+                suggestions.join(" or ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The analysis-only `pointcuts` and pointcuts of `hooks` matching none of
+/// `functions`, in the order they were given.
+pub fn find_unmatched(
+    functions: &[FunctionMetadata],
+    pointcuts: &[String],
+    hooks: &[AdviceHook],
+) -> Vec<UnmatchedPointcut> {
+    let matcher = PointcutMatcher::new();
+    let mut seen = BTreeSet::new();
+
+    pointcuts
+        .iter()
+        .chain(hooks.iter().map(|hook| &hook.pointcut))
+        .filter(|pointcut| seen.insert(pointcut.as_str()))
+        .filter(|pointcut| {
+            !functions
+                .iter()
+                .any(|function| matcher.matches_pointcut(function, pointcut))
+        })
+        .map(|pointcut| {
+            let expr = parse_pointcut(pointcut).ok();
+            let mut clauses = Vec::new();
+            if let Some(expr) = &expr {
+                leaf_clauses(expr, &mut clauses);
+            }
+            let near_misses = clauses
+                .into_iter()
+                .filter(|clause| {
+                    let clause = clause.to_string();
+                    !functions
+                        .iter()
+                        .any(|function| matcher.matches_pointcut(function, &clause))
+                })
+                .map(|clause| NearMiss {
+                    clause: clause.to_string(),
+                    suggestions: suggest(clause, functions),
+                })
+                .collect();
+
+            UnmatchedPointcut {
+                pointcut: pointcut.clone(),
+                near_misses,
+            }
+        })
+        .collect()
+}
+
+/// The clauses of `expr` that are not negated.
+///
+/// A negated clause matching nothing excludes nothing, which is harmless.
+fn leaf_clauses<'a>(expr: &'a PointcutExpr, clauses: &mut Vec<&'a PointcutExpr>) {
+    match expr {
+        PointcutExpr::And(left, right) | PointcutExpr::Or(left, right) => {
+            leaf_clauses(left, clauses);
+            leaf_clauses(right, clauses);
+        }
+        PointcutExpr::Not(_) => {}
+        leaf => clauses.push(leaf),
+    }
+}
+
+/// Clauses similar to `clause` that match some function.
+fn suggest(clause: &PointcutExpr, functions: &[FunctionMetadata]) -> Vec<String> {
+    match clause {
+        PointcutExpr::Within(path) => {
+            // Every module containing a function, and the modules above it
+            let mut modules = BTreeSet::new();
+            for function in functions {
+                let mut module = function.module_path.as_str();
+                modules.insert(module);
+                while let Some((parent, _)) = module.rsplit_once("::") {
+                    modules.insert(parent);
+                    module = parent;
+                }
+            }
+            let path = path.strip_suffix("::*").unwrap_or(path);
+            nearest(path, modules)
+                .into_iter()
+                .map(|module| format!("within({})", module))
+                .collect()
+        }
+        PointcutExpr::Name(pattern) => {
+            let names = functions.iter().map(|f| f.simple_name.as_str());
+            nearest_names(pattern, names)
+                .into_iter()
+                .map(|name| format!("name({})", name))
+                .collect()
+        }
+        PointcutExpr::Execution(pattern) => {
+            let Some((start, end)) = execution_name(pattern) else {
+                return Vec::new();
+            };
+            let name = &pattern[start..end];
+            let names: Vec<&str> = functions.iter().map(|f| f.simple_name.as_str()).collect();
+            // Only the name can be misspelled: a pattern whose name matches
+            // is unmatched for another reason, like its visibility
+            if names.iter().any(|candidate| name_matches(name, candidate)) {
+                return Vec::new();
+            }
+            nearest_names(name, names)
+                .into_iter()
+                .map(|name| {
+                    format!(
+                        "execution({}{}{})",
+                        &pattern[..start],
+                        name,
+                        &pattern[end..]
+                    )
+                })
+                .collect()
+        }
+        PointcutExpr::Annotated(path) => {
+            let mut attributes = BTreeSet::new();
+            for attribute in functions.iter().flat_map(|f| &f.attributes) {
+                let end = attribute.find(['(', '=']).unwrap_or(attribute.len());
+                let attribute_path: String = attribute[..end].split_whitespace().collect();
+                // A bare name also matches the last segment of a path
+                if !path.contains("::") {
+                    attributes.extend(attribute_path.rsplit("::").next().map(String::from));
+                }
+                attributes.insert(attribute_path);
+            }
+            nearest(path, attributes.iter().map(String::as_str))
+                .into_iter()
+                .map(|attribute| format!("annotated({})", attribute))
+                .collect()
+        }
+        // The crate has no test, nothing is close to one
+        PointcutExpr::Test => Vec::new(),
+        PointcutExpr::And(..) | PointcutExpr::Or(..) | PointcutExpr::Not(_) => Vec::new(),
+    }
+}
+
+/// Byte range of the function name in an execution pattern, e.g. `get*` in
+/// `pub fn get*(..)`.
+fn execution_name(pattern: &str) -> Option<(usize, usize)> {
+    let start = pattern.find("fn ")? + "fn ".len();
+    let end = start + pattern[start..].find('(')?;
+    let name = &pattern[start..end];
+    let start = start + (name.len() - name.trim_start().len());
+    let end = end - (name.len() - name.trim_end().len());
+    (start < end).then_some((start, end))
+}
+
+/// Whether a name pattern (`get`, `get*`, `*_user` or `*user*`) matches
+/// `name`.
+fn name_matches(pattern: &str, name: &str) -> bool {
+    if pattern == "*" {
+        true
+    } else if pattern.len() > 2 && pattern.starts_with('*') && pattern.ends_with('*') {
+        name.contains(&pattern[1..pattern.len() - 1])
+    } else if let Some(suffix) = pattern.strip_prefix('*') {
+        name.ends_with(suffix)
+    } else if let Some(prefix) = pattern.strip_suffix('*') {
+        name.starts_with(prefix)
+    } else {
+        name == pattern
+    }
+}
+
+/// The name patterns closest to `pattern` matching some of `names`.
+///
+/// A prefix pattern is compared with the prefixes of the names and a suffix
+/// pattern with their suffixes, so `fecth_*` suggests `fetch_*`.
+fn nearest_names<'a>(pattern: &str, names: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    if pattern.len() > 2 && pattern.starts_with('*') && pattern.ends_with('*') {
+        return Vec::new();
+    }
+    if let Some(suffix) = pattern.strip_prefix('*') {
+        let suffixes = affixes(names, suffix, |name, at| &name[at..]);
+        return nearest(suffix, suffixes.iter().map(String::as_str))
+            .into_iter()
+            .map(|suffix| format!("*{}", suffix))
+            .collect();
+    }
+    if let Some(prefix) = pattern.strip_suffix('*') {
+        let prefixes = affixes(names, prefix, |name, at| &name[..at]);
+        return nearest(prefix, prefixes.iter().map(String::as_str))
+            .into_iter()
+            .map(|prefix| format!("{}*", prefix))
+            .collect();
+    }
+    nearest(pattern, names)
+}
+
+/// The prefixes or suffixes of `names` about as long as `target`, as split
+/// by `split` at a byte offset.
+fn affixes<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+    target: &str,
+    split: impl Fn(&'a str, usize) -> &'a str,
+) -> BTreeSet<String> {
+    let length = target.chars().count();
+    let slack = (length / 3).max(1);
+    let mut affixes = BTreeSet::new();
+    for name in names {
+        let offsets = name
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(name.len()));
+        for at in offsets {
+            let affix = split(name, at);
+            if affix.chars().count().abs_diff(length) <= slack {
+                affixes.insert(affix.to_string());
+            }
+        }
+    }
+    affixes
+}
+
+/// The candidates closest to `target`, at most a third of its length away.
+fn nearest<'a>(target: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let threshold = (target.chars().count() / 3).max(1);
+    let mut close: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| !candidate.is_empty() && *candidate != target)
+        .map(|candidate| (edit_distance(target, candidate), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .collect();
+    close.sort_unstable();
+    close.dedup();
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+/// Levenshtein distance between two strings, in characters.
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::r#match::AdviceType;
+    use crate::types::{SourceLocation, Visibility};
+
+    fn function(name: &str, visibility: Visibility) -> FunctionMetadata {
+        let (module, simple_name) = name.rsplit_once("::").unwrap_or(("", name));
+        FunctionMetadata {
+            name: name.to_string(),
+            simple_name: simple_name.to_string(),
+            module_path: format!("crate::{}", module),
+            visibility,
+            is_async: false,
+            is_closure: false,
+            generics: vec![],
+            return_type: "()".to_string(),
+            is_trait_method: false,
+            trait_name: None,
+            impl_type: None,
+            attributes: vec![],
+            location: SourceLocation {
+                file: "src/lib.rs".to_string(),
+                line: 1,
+                column: 1,
+                end_line: 1,
+                end_column: 1,
+            },
+        }
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("fetch", "fetch"), 0);
+        assert_eq!(edit_distance("fecth", "fetch"), 2);
+        assert_eq!(edit_distance("api", "apii"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_nearest_names() {
+        let names = ["fetch_user", "fetch_order", "save_user", "delete"];
+        assert_eq!(nearest_names("fecth_user", names), vec!["fetch_user"]);
+        assert_eq!(nearest_names("fecth_*", names), vec!["fetch_*"]);
+        assert_eq!(nearest_names("*_usr", names), vec!["*_user"]);
+        assert!(nearest_names("unrelated", names).is_empty());
+        assert!(nearest_names("*usr*", names).is_empty());
+    }
+
+    #[test]
+    fn test_find_unmatched() {
+        let mut delete = function("api::users::delete_user", Visibility::Private);
+        delete.attributes = vec!["tokio::test".to_string()];
+        let functions = vec![
+            function("api::users::fetch_user", Visibility::Public),
+            function("api::users::save_user", Visibility::Public),
+            delete,
+        ];
+        let pointcuts = vec![
+            "within(crate::api)".to_string(),
+            "within(crate::apii::users) && name(fecth_*)".to_string(),
+            "execution(pub fn delete_user(..))".to_string(),
+            "annotated(tst)".to_string(),
+            "within(crate::api) && !name(nothing)".to_string(),
+        ];
+        let hooks = vec![AdviceHook::parse(
+            AdviceType::Before,
+            "execution(pub fn fetch_usr(..))=crate::trace::enter",
+        )
+        .unwrap()];
+
+        let unmatched = find_unmatched(&functions, &pointcuts, &hooks);
+        let expressions: Vec<_> = unmatched.iter().map(|u| u.pointcut.as_str()).collect();
+        assert_eq!(
+            expressions,
+            vec![
+                "within(crate::apii::users) && name(fecth_*)",
+                "execution(pub fn delete_user(..))",
+                "annotated(tst)",
+                "execution(pub fn fetch_usr(..))",
+            ]
+        );
+
+        assert_eq!(
+            unmatched[0].near_misses,
+            vec![
+                NearMiss {
+                    clause: "within(crate::apii::users)".to_string(),
+                    suggestions: vec!["within(crate::api::users)".to_string()],
+                },
+                NearMiss {
+                    clause: "name(fecth_*)".to_string(),
+                    suggestions: vec!["name(fetch_*)".to_string()],
+                },
+            ]
+        );
+        // The name exists, the function is just private
+        assert!(unmatched[1].near_misses[0].suggestions.is_empty());
+        assert_eq!(
+            unmatched[2].near_misses[0].suggestions,
+            vec!["annotated(test)"]
+        );
+        assert_eq!(
+            unmatched[3].near_misses[0].suggestions,
+            vec!["execution(pub fn fetch_user(..))"]
+        );
+
+        assert_eq!(
+            unmatched[3].to_string(),
+            "pointcut `execution(pub fn fetch_usr(..))` matches no function\n  \
+             help: `execution(pub fn fetch_usr(..))` matches nothing; \
+             did you mean `execution(pub fn fetch_user(..))`?"
+        );
+        assert_eq!(
+            unmatched[1].to_string(),
+            "pointcut `execution(pub fn delete_user(..))` matches no function"
+        );
+    }
+}
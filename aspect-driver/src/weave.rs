@@ -0,0 +1,288 @@
+// weave.rs - MIR weaving of before/after advice into matched functions
+//
+// Requires nightly Rust with rustc-dev component.
+//
+// The driver overrides the `mir_built` query: the MIR of every function
+// matched by a pointcut gets a call to each before hook on entry and a call
+// to each after hook in front of every return. Hooks are plain functions
+// taking the qualified name of the advised function:
+//
+//     pub(crate) fn enter(function_name: &'static str) { ... }
+//
+// They are resolved by path ("crate::trace::enter", "my_tracing::enter")
+// in the crate being compiled or one of its dependencies.
+
+extern crate rustc_data_structures;
+extern crate rustc_hir;
+extern crate rustc_middle;
+extern crate rustc_span;
+
+use rustc_data_structures::steal::Steal;
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::{DefId, LocalDefId, CRATE_DEF_INDEX, LOCAL_CRATE};
+use rustc_middle::mir::interpret::Allocation;
+use rustc_middle::mir::{
+    BasicBlock, BasicBlockData, Body, CallSource, Const, ConstOperand, ConstValue, LocalDecl,
+    Operand, Place, SourceInfo, Terminator, TerminatorKind, UnwindAction, START_BLOCK,
+};
+use rustc_middle::ty::{self, Ty, TyCtxt};
+use rustc_span::source_map::Spanned;
+use rustc_span::Span;
+
+use crate::mir_analyzer::MirAnalyzer;
+pub use crate::r#match::AdviceHook;
+use crate::r#match::{AdviceType, PointcutMatcher};
+
+/// A `mir_built` query provider.
+pub type MirBuiltProvider = for<'tcx> fn(TyCtxt<'tcx>, LocalDefId) -> &'tcx Steal<Body<'tcx>>;
+
+/// An advice hook resolved to its function definition.
+#[derive(Debug, Clone)]
+pub struct ResolvedHook {
+    /// The hook as configured
+    pub hook: AdviceHook,
+
+    /// The hook function
+    pub def_id: DefId,
+}
+
+/// Resolve the hook functions of `hooks`, checking that each one is a
+/// function taking a single `&'static str` and returning `()`.
+pub fn resolve_hooks(tcx: TyCtxt<'_>, hooks: &[AdviceHook]) -> Result<Vec<ResolvedHook>, String> {
+    hooks
+        .iter()
+        .map(|hook| {
+            let def_id = resolve_fn_path(tcx, &hook.path)
+                .ok_or_else(|| format!("advice hook '{}' not found", hook.path))?;
+
+            let sig = tcx.fn_sig(def_id).skip_binder().skip_binder();
+            let takes_name = matches!(
+                sig.inputs(),
+                [input] if matches!(input.kind(), ty::Ref(_, inner, _) if inner.is_str())
+            );
+            if !takes_name || !sig.output().is_unit() || tcx.generics_of(def_id).count() > 0 {
+                return Err(format!(
+                    "advice hook '{}' must be a non-generic fn(&'static str)",
+                    hook.path
+                ));
+            }
+
+            Ok(ResolvedHook {
+                hook: hook.clone(),
+                def_id,
+            })
+        })
+        .collect()
+}
+
+/// Find a function by path, starting from `crate`, the current crate's name
+/// or the name of a dependency.
+fn resolve_fn_path(tcx: TyCtxt<'_>, path: &str) -> Option<DefId> {
+    let mut segments = path.split("::");
+    let root = segments.next()?;
+
+    let krate = if root == "crate" || root == tcx.crate_name(LOCAL_CRATE).as_str() {
+        LOCAL_CRATE
+    } else {
+        *tcx.crates(())
+            .iter()
+            .find(|&&cnum| tcx.crate_name(cnum).as_str() == root)?
+    };
+
+    let mut def_id = DefId {
+        krate,
+        index: CRATE_DEF_INDEX,
+    };
+    for segment in segments {
+        def_id = tcx
+            .module_children(def_id)
+            .iter()
+            .filter(|child| child.ident.name.as_str() == segment)
+            .find_map(|child| child.res.opt_def_id())?;
+    }
+
+    matches!(tcx.def_kind(def_id), DefKind::Fn | DefKind::AssocFn).then_some(def_id)
+}
+
+/// Weaves resolved advice hooks into the MIR of matched functions.
+pub struct MirWeaver<'a, 'tcx> {
+    tcx: TyCtxt<'tcx>,
+    hooks: &'a [ResolvedHook],
+}
+
+impl<'a, 'tcx> MirWeaver<'a, 'tcx> {
+    pub fn new(tcx: TyCtxt<'tcx>, hooks: &'a [ResolvedHook]) -> Self {
+        Self { tcx, hooks }
+    }
+
+    /// The `mir_built` result for `def_id`, with advice woven in if the
+    /// function is matched by a pointcut.
+    ///
+    /// `default` is the provider being overridden.
+    pub fn mir_built(
+        &self,
+        def_id: LocalDefId,
+        default: MirBuiltProvider,
+    ) -> &'tcx Steal<Body<'tcx>> {
+        let built = default(self.tcx, def_id);
+        if !self.is_weavable(def_id) {
+            return built;
+        }
+
+        let Some(function) = MirAnalyzer::new(self.tcx, false).extract_function_metadata(def_id)
+        else {
+            return built;
+        };
+        let matcher = PointcutMatcher::new();
+        let matching = |advice_type| {
+            self.hooks
+                .iter()
+                .filter(move |resolved| resolved.hook.advice_type == advice_type)
+                .filter(|resolved| matcher.matches_pointcut(&function, &resolved.hook.pointcut))
+                .map(|resolved| resolved.def_id)
+                .collect::<Vec<_>>()
+        };
+        let before = matching(AdviceType::Before);
+        let after = matching(AdviceType::After);
+        if before.is_empty() && after.is_empty() {
+            return built;
+        }
+
+        let mut body = built.steal();
+        self.weave(&mut body, &function.name, &before, &after);
+        self.tcx.alloc_steal_mir(body)
+    }
+
+    /// Only plain, non-const, non-async functions are woven, and never the
+    /// hooks themselves.
+    fn is_weavable(&self, def_id: LocalDefId) -> bool {
+        let tcx = self.tcx;
+        matches!(tcx.def_kind(def_id), DefKind::Fn | DefKind::AssocFn)
+            && !tcx.is_const_fn(def_id.to_def_id())
+            && !tcx.asyncness(def_id).is_async()
+            && !self
+                .hooks
+                .iter()
+                .any(|resolved| resolved.def_id == def_id.to_def_id())
+    }
+
+    /// Insert calls to `before` on entry and to `after` in front of every
+    /// return of `body`.
+    ///
+    /// After hooks do not run when the function unwinds.
+    pub fn weave(
+        &self,
+        body: &mut Body<'tcx>,
+        function_name: &str,
+        before: &[DefId],
+        after: &[DefId],
+    ) {
+        let span = body.span;
+        let name = self.function_name_operand(function_name, span);
+
+        if let Some((&first, rest)) = after.split_first() {
+            // Return blocks are collected before any are added
+            let returns: Vec<BasicBlock> = body
+                .basic_blocks
+                .iter_enumerated()
+                .filter(|(_, block)| matches!(block.terminator().kind, TerminatorKind::Return))
+                .map(|(bb, _)| bb)
+                .collect();
+            for bb in returns {
+                // The return moves to a new block, after the hook calls
+                let mut target = self.push_block(body, TerminatorKind::Return, span);
+                for &hook in rest.iter().rev() {
+                    let call = self.hook_call(body, hook, &name, target, span);
+                    target = self.push_block(body, call, span);
+                }
+                let call = self.hook_call(body, first, &name, target, span);
+                body.basic_blocks_mut()[bb].terminator_mut().kind = call;
+            }
+        }
+
+        if !before.is_empty() {
+            // The entry block moves, and the before hooks run in its place
+            let entry = body.basic_blocks[START_BLOCK].clone();
+            let moved = body.basic_blocks_mut().push(entry);
+            for block in body.basic_blocks_mut().iter_mut() {
+                for successor in block.terminator_mut().successors_mut() {
+                    if *successor == START_BLOCK {
+                        *successor = moved;
+                    }
+                }
+            }
+
+            let mut target = moved;
+            for &hook in before.iter().skip(1).rev() {
+                let call = self.hook_call(body, hook, &name, target, span);
+                target = self.push_block(body, call, span);
+            }
+            let call = self.hook_call(body, before[0], &name, target, span);
+            body.basic_blocks_mut()[START_BLOCK] = BasicBlockData {
+                statements: Vec::new(),
+                terminator: Some(Terminator {
+                    source_info: SourceInfo::outermost(span),
+                    kind: call,
+                }),
+                is_cleanup: false,
+            };
+        }
+    }
+
+    /// A call of `hook` with the function name, continuing at `target`.
+    fn hook_call(
+        &self,
+        body: &mut Body<'tcx>,
+        hook: DefId,
+        name: &Operand<'tcx>,
+        target: BasicBlock,
+        span: Span,
+    ) -> TerminatorKind<'tcx> {
+        let destination = body
+            .local_decls
+            .push(LocalDecl::new(self.tcx.types.unit, span));
+        TerminatorKind::Call {
+            func: Operand::function_handle(self.tcx, hook, [], span),
+            args: Box::new([Spanned {
+                node: name.clone(),
+                span,
+            }]),
+            destination: Place::from(destination),
+            target: Some(target),
+            unwind: UnwindAction::Continue,
+            call_source: CallSource::Misc,
+            fn_span: span,
+        }
+    }
+
+    fn push_block(
+        &self,
+        body: &mut Body<'tcx>,
+        kind: TerminatorKind<'tcx>,
+        span: Span,
+    ) -> BasicBlock {
+        body.basic_blocks_mut().push(BasicBlockData {
+            statements: Vec::new(),
+            terminator: Some(Terminator {
+                source_info: SourceInfo::outermost(span),
+                kind,
+            }),
+            is_cleanup: false,
+        })
+    }
+
+    /// A `&'static str` constant holding the function name.
+    fn function_name_operand(&self, function_name: &str, span: Span) -> Operand<'tcx> {
+        let tcx = self.tcx;
+        let alloc = Allocation::from_bytes_byte_aligned_immutable(function_name.as_bytes());
+        let value = ConstValue::Slice {
+            data: tcx.mk_const_alloc(alloc),
+            meta: function_name.len() as u64,
+        };
+        Operand::Constant(Box::new(ConstOperand {
+            span,
+            user_ty: None,
+            const_: Const::from_value(value, Ty::new_static_str(tcx)),
+        }))
+    }
+}
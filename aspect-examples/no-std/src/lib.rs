@@ -0,0 +1,135 @@
+//! A `no_std` sensor driver whose functions are woven with `#[aspect]`.
+//!
+//! CI builds it for `thumbv7em-none-eabihf`, which has no `std`, to check
+//! that the woven code, aspects built from parameters, the aspects of
+//! `aspect-std` without `std` and pointcuts need only `core` and `alloc`:
+//!
+//! ```bash
+//! cargo build -p aspect-no-std --target thumbv7em-none-eabihf
+//! ```
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use aspect_core::config::Param;
+use aspect_core::pointcut::{FunctionInfo, Matcher, Pointcut};
+use aspect_core::prelude::*;
+use aspect_core::{AspectArgs, FromAspectArgs};
+use aspect_macros::aspect;
+use aspect_std::validation::RangeValidator;
+use aspect_std::{CounterAspect, ValidationAspect};
+use core::any::Any;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// The channels of the sensor.
+pub const CHANNELS: u8 = 4;
+
+/// The reads of the sensor, and how they ended.
+pub static READS: CounterAspect = CounterAspect::new();
+
+/// Reads `channel`, failing for channels the sensor does not have.
+#[aspect(&READS)]
+pub fn read(channel: u8) -> Result<u16, String> {
+    if channel < CHANNELS {
+        Ok(u16::from(channel) * 100)
+    } else {
+        Err(format!("no channel {}", channel))
+    }
+}
+
+/// Reads `channel` once the conversion completes.
+#[aspect(&READS)]
+pub async fn read_async(channel: u8) -> Result<u16, String> {
+    read(channel)
+}
+
+/// Sets the gain of `channel`, rejected by the aspect for gains above 16.
+#[aspect(ValidationAspect::new().add_rule(Box::new(RangeValidator::for_arg("gain", 1, 16))))]
+pub fn set_gain(channel: u8, gain: u8) -> Result<(), String> {
+    let _ = (channel, gain);
+    Ok(())
+}
+
+/// Fails the calls after the first `max`, as a battery-powered sensor
+/// only affords so many calibrations. Its clones share the count.
+#[derive(Clone)]
+pub struct PowerBudget {
+    max: u32,
+    used: Arc<AtomicU32>,
+}
+
+impl Aspect for PowerBudget {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        if self.used.fetch_add(1, Ordering::Relaxed) >= self.max {
+            return Err(AspectError::execution("power budget exhausted"));
+        }
+        pjp.proceed()
+    }
+}
+
+impl FromAspectArgs for PowerBudget {
+    const PARAMS: &'static [Param] = &[Param::required::<u32>("max")];
+
+    fn from_aspect_args(args: &AspectArgs) -> Result<Self, AspectError> {
+        args.check(Self::PARAMS)?;
+        Ok(Self {
+            max: args.require("max")?,
+            used: Arc::default(),
+        })
+    }
+}
+
+/// Calibrates `channel`, at most twice.
+#[aspect(PowerBudget, max = 2)]
+pub fn calibrate(channel: u8) -> Result<(), String> {
+    let _ = channel;
+    Ok(())
+}
+
+/// Whether `function` reads the sensor, as the pointcut of a registry
+/// would select it.
+pub fn reads_sensor(function: &FunctionInfo) -> bool {
+    Pointcut::parse("execution(pub fn read*(..)) && within(aspect_no_std)")
+        .is_ok_and(|pointcut| pointcut.matches(function))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_woven_without_std() {
+        READS.reset();
+        assert_eq!(read(2), Ok(200));
+        assert!(read(CHANNELS).is_err());
+        assert_eq!(
+            (READS.calls(), READS.successes(), READS.failures()),
+            (2, 1, 1)
+        );
+
+        assert_eq!(set_gain(0, 8), Ok(()));
+        let err = set_gain(0, 32).unwrap_err();
+        assert!(err.contains("gain must be between 1 and 16"), "{}", err);
+
+        assert_eq!(calibrate(0), Ok(()));
+        assert_eq!(calibrate(1), Ok(()));
+        let err = calibrate(2).unwrap_err();
+        assert!(err.contains("power budget exhausted"), "{}", err);
+
+        assert!(reads_sensor(&FunctionInfo::new(
+            "read",
+            "aspect_no_std",
+            "pub"
+        )));
+        assert!(!reads_sensor(&FunctionInfo::new(
+            "set_gain",
+            "aspect_no_std",
+            "pub"
+        )));
+    }
+}
@@ -56,13 +56,13 @@ struct User {
 }
 
 // Apply logging aspect to a simple function
-#[aspect(Logger::default())]
+#[aspect(Logger)]
 fn greet(name: &str) -> String {
     format!("Hello, {}!", name)
 }
 
 // Apply logging aspect to a function returning Result
-#[aspect(Logger::default())]
+#[aspect(Logger)]
 fn fetch_user(id: u64) -> Result<User, String> {
     if id == 0 {
         Err("Invalid user ID: 0".to_string())
@@ -75,7 +75,7 @@ fn fetch_user(id: u64) -> Result<User, String> {
 }
 
 // Apply logging aspect to a function with multiple parameters
-#[aspect(Logger::default())]
+#[aspect(Logger)]
 fn process_data(input: &str, multiplier: usize) -> String {
     input.repeat(multiplier)
 }
@@ -91,14 +91,14 @@ fn main() {
     // Example 2: Function returning Result (success case)
     println!("2. Calling fetch_user(42):");
     match fetch_user(42) {
-        Ok(user) => println!("   Success: {:?}\n", user),
+        Ok(user) => println!("   Success: {} ({})\n", user.name, user.id),
         Err(e) => println!("   Error: {}\n", e),
     }
 
     // Example 3: Function returning Result (error case)
     println!("3. Calling fetch_user(0) (will fail):");
     match fetch_user(0) {
-        Ok(user) => println!("   Success: {:?}\n", user),
+        Ok(user) => println!("   Success: {} ({})\n", user.name, user.id),
         Err(e) => println!("   Error: {}\n", e),
     }
 
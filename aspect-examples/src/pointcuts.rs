@@ -36,27 +36,27 @@ fn main() {
 
     let functions = vec![
         FunctionInfo {
-            name: "fetch_user".to_string(),
-            module_path: "crate::api::users".to_string(),
-            visibility: "pub".to_string(),
+            name: "fetch_user".into(),
+            module_path: "crate::api::users".into(),
+            visibility: "pub".into(),
             return_type: None,
         },
         FunctionInfo {
-            name: "save_user".to_string(),
-            module_path: "crate::api::users".to_string(),
-            visibility: "pub".to_string(),
+            name: "save_user".into(),
+            module_path: "crate::api::users".into(),
+            visibility: "pub".into(),
             return_type: None,
         },
         FunctionInfo {
-            name: "internal_helper".to_string(),
-            module_path: "crate::internal".to_string(),
-            visibility: "".to_string(),
+            name: "internal_helper".into(),
+            module_path: "crate::internal".into(),
+            visibility: "".into(),
             return_type: None,
         },
         FunctionInfo {
-            name: "delete_all".to_string(),
-            module_path: "crate::admin".to_string(),
-            visibility: "pub".to_string(),
+            name: "delete_all".into(),
+            module_path: "crate::admin".into(),
+            visibility: "pub".into(),
             return_type: None,
         },
     ];
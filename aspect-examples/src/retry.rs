@@ -25,12 +25,15 @@ impl RetryAspect {
         }
     }
 
+    #[allow(dead_code)]
     fn attempts(&self) -> usize {
         self.attempt_counter.load(Ordering::SeqCst)
     }
 }
 
 impl Aspect for RetryAspect {
+    // The loop exits after the first attempt because proceed() consumes the PJP
+    #[allow(clippy::never_loop)]
     fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
         let function_name = pjp.context().function_name;
 
@@ -114,6 +117,7 @@ impl CircuitBreakerAspect {
         }
     }
 
+    #[allow(dead_code)]
     fn failures(&self) -> usize {
         self.failure_count.load(Ordering::SeqCst)
     }
@@ -133,7 +137,7 @@ impl Aspect for CircuitBreakerAspect {
         }
     }
 
-    fn after(&self, ctx: &JoinPoint, _result: &dyn Any) {
+    fn after(&self, _ctx: &JoinPoint, _result: &dyn Any) {
         // Reset on success
         let prev = self.failure_count.swap(0, Ordering::SeqCst);
         if prev > 0 {
@@ -144,7 +148,7 @@ impl Aspect for CircuitBreakerAspect {
         }
     }
 
-    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
+    fn after_error(&self, ctx: &JoinPoint, _error: &AspectError) {
         let failures = self.failure_count.fetch_add(1, Ordering::SeqCst) + 1;
         println!(
             "[CIRCUIT-BREAKER] ✗ Failure #{} in {}",
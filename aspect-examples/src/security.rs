@@ -5,8 +5,8 @@
 
 use aspect_core::prelude::*;
 use aspect_macros::aspect;
-use std::any::Any;
-use std::sync::RwLock;
+use aspect_std::audit::{AuditAspect, StdoutAuditSink};
+use std::sync::{LazyLock, RwLock};
 
 /// Simple user representation
 #[derive(Debug, Clone)]
@@ -61,6 +61,7 @@ impl AuthorizationAspect {
         }
     }
 
+    #[allow(dead_code)]
     fn require_all_roles(roles: &[&str]) -> Self {
         Self {
             required_roles: roles.iter().map(|r| r.to_string()).collect(),
@@ -118,66 +119,37 @@ impl Aspect for AuthorizationAspect {
     }
 }
 
-/// Audit aspect that logs all security-sensitive operations
-#[derive(Default)]
-struct AuditAspect;
-
-impl Aspect for AuditAspect {
-    fn before(&self, ctx: &JoinPoint) {
-        let user = get_current_user()
-            .map(|u| u.username)
-            .unwrap_or_else(|| "anonymous".to_string());
-
-        println!(
-            "[AUDIT] User '{}' accessing {} at {}:{}",
-            user, ctx.function_name, ctx.location.file, ctx.location.line
-        );
-    }
-
-    fn after(&self, ctx: &JoinPoint, _result: &dyn Any) {
-        let user = get_current_user()
-            .map(|u| u.username)
-            .unwrap_or_else(|| "anonymous".to_string());
-
-        println!("[AUDIT] User '{}' completed {}", user, ctx.function_name);
-    }
-
-    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
-        let user = get_current_user()
-            .map(|u| u.username)
-            .unwrap_or_else(|| "anonymous".to_string());
-
-        println!(
-            "[AUDIT] ⚠ User '{}' failed {}: {:?}",
-            user, ctx.function_name, error
-        );
-    }
-}
+/// Audit trail of all security-sensitive operations, shared by every
+/// audited function so records are numbered in one sequence
+static AUDIT: LazyLock<AuditAspect> = LazyLock::new(|| {
+    AuditAspect::new(StdoutAuditSink)
+        .with_principal(|| get_current_user().map(|u| u.username))
+});
 
 // Example protected functions
 
 #[aspect(AuthorizationAspect::require_role("admin"))]
-#[aspect(AuditAspect::default())]
+#[aspect(AUDIT.clone())]
 fn delete_user(user_id: u64) -> Result<(), String> {
     println!("  [SYSTEM] Deleting user {}", user_id);
     Ok(())
 }
 
 #[aspect(AuthorizationAspect::require_any_role(&["admin", "moderator"]))]
-#[aspect(AuditAspect::default())]
+#[aspect(AUDIT.clone())]
 fn ban_user(user_id: u64, reason: &str) -> Result<(), String> {
     println!("  [SYSTEM] Banning user {} (reason: {})", user_id, reason);
     Ok(())
 }
 
 #[aspect(AuthorizationAspect::require_role("user"))]
-#[aspect(AuditAspect::default())]
+#[aspect(AUDIT.clone())]
 fn view_profile(user_id: u64) -> Result<String, String> {
     println!("  [SYSTEM] Fetching profile for user {}", user_id);
     Ok(format!("Profile data for user {}", user_id))
 }
 
-#[aspect(AuditAspect::default())]
+#[aspect(AUDIT.clone())]
 fn public_endpoint() -> String {
     println!("  [SYSTEM] Public endpoint accessed");
     "Public data".to_string()
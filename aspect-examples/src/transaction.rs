@@ -72,13 +72,16 @@ impl Drop for Transaction {
 }
 
 /// Thread-local transaction context
+#[allow(dead_code)]
 static TRANSACTION_CONTEXT: Mutex<Option<Transaction>> = Mutex::new(None);
 
+#[allow(dead_code)]
 fn get_transaction() -> Option<Transaction> {
     // Note: This is simplified - in production you'd use thread-locals
     TRANSACTION_CONTEXT.lock().unwrap().take()
 }
 
+#[allow(dead_code)]
 fn set_transaction(tx: Transaction) {
     *TRANSACTION_CONTEXT.lock().unwrap() = Some(tx);
 }
@@ -90,6 +93,7 @@ struct ConnectionPool {
 }
 
 impl ConnectionPool {
+    #[allow(dead_code)]
     fn new() -> Self {
         Self {
             connections: Vec::new(),
@@ -3,6 +3,10 @@
 //! Demonstrates how to enforce validation rules and constraints
 //! declaratively using aspects, separating validation from business logic.
 
+// The rule-based validators below illustrate the API shape; main() uses the
+// simpler function-level validators.
+#![allow(dead_code)]
+
 use aspect_core::prelude::*;
 use aspect_macros::aspect;
 use std::any::Any;
@@ -145,7 +149,7 @@ impl Aspect for ValidationAspect {
 
 // Example: Simple validation using before advice with argument capture
 fn validate_age(age: i32) -> Result<(), String> {
-    if age < 0 || age > 150 {
+    if !(0..=150).contains(&age) {
         Err(format!("Age must be between 0 and 150 (got: {})", age))
     } else {
         Ok(())
@@ -154,7 +158,7 @@ fn validate_age(age: i32) -> Result<(), String> {
 
 fn validate_username(username: &str) -> Result<(), String> {
     let len = username.len();
-    if len < 3 || len > 20 {
+    if !(3..=20).contains(&len) {
         Err(format!(
             "Username must be 3-20 characters (got: {} chars)",
             len
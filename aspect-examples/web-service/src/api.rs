@@ -0,0 +1,92 @@
+//! The HTTP API of the service: plain axum handlers.
+
+use crate::store::{Store, User};
+use crate::weave;
+use aspect_runtime::global_registry;
+use axum::extract::{Path, State};
+use axum::http::{Method, StatusCode};
+use axum::routing::get;
+use axum::{middleware, Json, Router};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// The handler of a route, whose join point the requests to the route are.
+pub struct Handler {
+    pub method: Method,
+    pub route: &'static str,
+    pub module: &'static str,
+    pub name: &'static str,
+}
+
+/// The handlers of the routes of [`routes`].
+pub const HANDLERS: &[Handler] = &[
+    handler(Method::GET, "/health", "health"),
+    handler(Method::GET, "/metrics", "metrics"),
+    handler(Method::POST, "/users", "create_user"),
+    handler(Method::GET, "/users/{id}", "get_user"),
+];
+
+const fn handler(method: Method, route: &'static str, name: &'static str) -> Handler {
+    Handler {
+        method,
+        route,
+        module: module_path!(),
+        name,
+    }
+}
+
+/// The routes of the service, serving `store`, each request woven as a
+/// call of its handler.
+pub fn routes(store: Store) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/metrics", get(metrics))
+        .route("/users", axum::routing::post(create_user))
+        .route("/users/{id}", get(get_user))
+        .route_layer(middleware::from_fn(weave::request))
+        .with_state(store)
+}
+
+/// Whether the service is up.
+pub async fn health() -> &'static str {
+    "ok"
+}
+
+/// How many times each registered aspect ran.
+pub async fn metrics() -> Json<BTreeMap<String, u64>> {
+    let executions = global_registry()
+        .metrics()
+        .into_iter()
+        .filter_map(|metrics| Some((metrics.name?, metrics.executions)))
+        .collect();
+    Json(executions)
+}
+
+/// The body of a request creating a user.
+#[derive(Deserialize)]
+pub struct NewUser {
+    pub name: String,
+}
+
+/// Creates a user.
+pub async fn create_user(
+    State(store): State<Store>,
+    Json(user): Json<NewUser>,
+) -> Result<(StatusCode, Json<User>), (StatusCode, String)> {
+    match store.save_user(user.name) {
+        Ok(user) => Ok((StatusCode::CREATED, Json(user))),
+        Err(error) => Err((StatusCode::UNPROCESSABLE_ENTITY, error.to_string())),
+    }
+}
+
+/// The user `id`.
+pub async fn get_user(
+    State(store): State<Store>,
+    Path(id): Path<u64>,
+) -> Result<Json<User>, StatusCode> {
+    match store.find_user(id) {
+        Ok(Some(user)) => Ok(Json(user)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
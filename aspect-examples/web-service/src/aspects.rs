@@ -0,0 +1,166 @@
+//! The aspects of the service, bound to its functions by pointcuts only.
+//!
+//! By precedence, the outermost first, a request to a user route runs
+//! through `CorrelationAspect`, [`Authenticate`] and the logging advice,
+//! then its handler calls the store through `AuditAspect`, for the writes,
+//! and `trace_store`. The aspects written with `#[advice]` take their order
+//! explicitly, the others from their [`Precedence`].
+
+use aspect_core::aspect::BoxFuture;
+use aspect_core::pointcut::Pointcut;
+use aspect_core::prelude::*;
+use aspect_macros::advice;
+use aspect_runtime::global_registry;
+use aspect_runtime::once_cell::sync::Lazy;
+use aspect_std::audit::{AuditAspect, AuditRecord, AuditSink};
+use aspect_std::context;
+use aspect_std::correlation::{self, CorrelationAspect};
+use axum::response::Response;
+use std::any::Any;
+use std::sync::{Arc, LazyLock, Mutex, Once};
+
+/// The handlers of the service.
+pub const API: &str = "within(crate::api)";
+
+/// The handlers of the routes only users may call.
+pub const USER_ROUTES: &str = "within(crate::api) && execution(pub fn *_user(..))";
+
+/// The functions of the store writing to it.
+pub const STORE_WRITES: &str = "within(crate::store) && execution(pub fn save*(..))";
+
+#[advice(pointcut = "within(crate::api)", advice = "before", order = 100)]
+fn log_request(ctx: &JoinPoint) {
+    let path = ctx.arg("path").and_then(|arg| arg.value::<String>());
+    println!(
+        "[{}] {} {}",
+        correlation::current().unwrap_or_default(),
+        ctx.function_name,
+        path.map_or("", String::as_str)
+    );
+}
+
+#[advice(pointcut = "within(crate::api)", advice = "after", order = 100)]
+fn log_response(ctx: &JoinPoint, result: &dyn Any) {
+    if let Some(response) = result.downcast_ref::<Response>() {
+        println!(
+            "[{}] {} responded {}",
+            correlation::current().unwrap_or_default(),
+            ctx.function_name,
+            response.status()
+        );
+    }
+}
+
+#[advice(pointcut = "within(crate::store)", advice = "around", order = 110)]
+fn trace_store(pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+    let ctx = pjp.context().clone();
+    let result = pjp.proceed();
+    println!(
+        "[{}]   store: {} by {} {}",
+        correlation::current().unwrap_or_default(),
+        ctx.function_name,
+        context::principal().as_deref().unwrap_or("anonymous"),
+        if result.is_ok() {
+            "succeeded"
+        } else {
+            "failed"
+        }
+    );
+    result
+}
+
+/// Authenticates the requests by their bearer token, awaiting the user of
+/// its session, who becomes the principal of the request.
+///
+/// Requests without a token, or whose token has no session, are denied.
+pub struct Authenticate;
+
+impl Aspect for Authenticate {
+    fn precedence(&self) -> Precedence {
+        Precedence::SECURITY
+    }
+
+    fn reads_args(&self) -> bool {
+        true
+    }
+}
+
+impl AsyncAspect for Authenticate {
+    fn before_async<'s, 'c, 'f>(
+        &'s self,
+        ctx: &'c JoinPoint,
+    ) -> BoxFuture<'f, Result<(), AspectError>>
+    where
+        's: 'f,
+        'c: 'f,
+        Self: 'f,
+    {
+        Box::pin(async move {
+            let token = ctx.arg("token").and_then(|arg| arg.value::<String>());
+            let user = match token {
+                Some(token) => crate::store::session(token).await,
+                None => None,
+            };
+            let user = user.ok_or_else(|| AspectError::denied("a session", "anonymous"))?;
+            context::update(|context| context.principal = Some(user));
+            Ok(())
+        })
+    }
+}
+
+/// The audit trail of the store, kept in memory.
+#[derive(Clone, Default)]
+pub struct AuditTrail(Arc<Mutex<Vec<AuditRecord>>>);
+
+impl AuditTrail {
+    /// The records of the trail, the oldest first.
+    pub fn records(&self) -> Vec<AuditRecord> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl AuditSink for AuditTrail {
+    fn write(&self, record: &AuditRecord) {
+        println!(
+            "[{}]   audit: {}",
+            record.correlation_id.as_deref().unwrap_or_default(),
+            record
+        );
+        self.0.lock().unwrap().push(record.clone());
+    }
+}
+
+/// The audit trail of the writes to the store.
+pub static AUDIT_TRAIL: LazyLock<AuditTrail> = LazyLock::new(AuditTrail::default);
+
+/// Registers the aspects of the service in the global registry, once.
+pub fn register() {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| {
+        Lazy::force(&__register_log_request);
+        Lazy::force(&__register_log_response);
+        Lazy::force(&__register_trace_store);
+
+        let registry = global_registry();
+        registry.register_by_precedence(
+            Arc::new(CorrelationAspect::new()),
+            pointcut(API),
+            Some("correlation".into()),
+        );
+        registry.register_async(
+            Arc::new(Authenticate),
+            pointcut(USER_ROUTES),
+            Authenticate.precedence().order(),
+            Some("authenticate".into()),
+        );
+        registry.register_by_precedence(
+            Arc::new(AuditAspect::new(AUDIT_TRAIL.clone()).with_principal(context::principal)),
+            pointcut(STORE_WRITES),
+            Some("audit".into()),
+        );
+    });
+}
+
+fn pointcut(expression: &str) -> Pointcut {
+    Pointcut::parse(expression).expect("the pointcuts of the service are valid")
+}
@@ -0,0 +1,37 @@
+//! An axum service whose cross-cutting concerns are all bound with
+//! pointcuts.
+//!
+//! No handler or store function names an aspect: [`aspects`] registers
+//! them in the global registry, with `#[advice]` or by precedence, against
+//! pointcuts such as `within(crate::api)`. [`weave`] runs the executions of
+//! the service through the registry, which applies the aspects matching
+//! them, in order:
+//!
+//! 1. `CorrelationAspect` gives each request a correlation id, or keeps the
+//!    one of its `x-correlation-id` header;
+//! 2. `authenticate`, asynchronous advice, awaits the session of the
+//!    request's bearer token and rejects the requests without one;
+//! 3. `log_request` and `log_response` log the requests;
+//! 4. `AuditAspect` records who changed what in the store;
+//! 5. `trace_store` traces the calls of the store.
+//!
+//! The principal and correlation id of a request live in its task context
+//! ([`aspect_std::context`]), so the aspects of the store see those of the
+//! request that calls it.
+//!
+//! See the README of the crate to run the service and inspect its aspects
+//! with `cargo aspect`.
+
+pub mod api;
+pub mod aspects;
+pub mod store;
+pub mod weave;
+
+use axum::Router;
+use store::Store;
+
+/// The service, serving `store`, with its aspects registered.
+pub fn app(store: Store) -> Router {
+    aspects::register();
+    api::routes(store)
+}
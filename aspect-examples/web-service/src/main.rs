@@ -0,0 +1,18 @@
+//! Runs the service on `127.0.0.1:3000`, or the address in `ADDR`.
+//!
+//! The token `demo-token` opens a session of the user `alice`.
+
+use aspect_web_service::store::{self, Store};
+
+#[tokio::main]
+async fn main() {
+    store::open_session("demo-token", "alice");
+    let app = aspect_web_service::app(Store::default());
+
+    let addr = std::env::var("ADDR").unwrap_or_else(|_| "127.0.0.1:3000".to_string());
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .unwrap_or_else(|e| panic!("cannot listen on {}: {}", addr, e));
+    println!("Listening on http://{}", addr);
+    axum::serve(listener, app).await.unwrap();
+}
@@ -0,0 +1,64 @@
+//! Users and sessions kept in memory, standing in for a database.
+
+use crate::weave;
+use aspect_core::{Arg, AspectError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// A user of the service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct User {
+    pub id: u64,
+    pub name: String,
+}
+
+/// The users of the service, shared by the handlers.
+#[derive(Clone, Default)]
+pub struct Store {
+    users: Arc<RwLock<HashMap<u64, User>>>,
+}
+
+impl Store {
+    /// The user `id`, if there is one.
+    pub fn find_user(&self, id: u64) -> Result<Option<User>, AspectError> {
+        weave::call(
+            "find_user",
+            module_path!(),
+            vec![Arg::new("id", &id)],
+            || Ok(self.users.read().unwrap().get(&id).cloned()),
+        )
+    }
+
+    /// Adds a user named `name`, with the next id.
+    pub fn save_user(&self, name: String) -> Result<User, AspectError> {
+        let args = vec![Arg::new("name", &name)];
+        weave::call("save_user", module_path!(), args, || {
+            if name.trim().is_empty() {
+                return Err(AspectError::execution("a user needs a name"));
+            }
+            let mut users = self.users.write().unwrap();
+            let user = User {
+                id: users.len() as u64 + 1,
+                name,
+            };
+            users.insert(user.id, user.clone());
+            Ok(user)
+        })
+    }
+}
+
+/// The user each session token was issued to.
+static SESSIONS: LazyLock<RwLock<HashMap<String, String>>> = LazyLock::new(Default::default);
+
+/// Issues `token` to `user`.
+pub fn open_session(token: impl Into<String>, user: impl Into<String>) {
+    SESSIONS.write().unwrap().insert(token.into(), user.into());
+}
+
+/// The user `token` was issued to, looked up asynchronously as a session
+/// store over the network would be.
+pub async fn session(token: &str) -> Option<String> {
+    tokio::task::yield_now().await;
+    SESSIONS.read().unwrap().get(token).cloned()
+}
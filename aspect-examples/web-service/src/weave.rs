@@ -0,0 +1,128 @@
+//! Where the executions of the service meet the global registry.
+//!
+//! `cargo aspect build` weaves the functions the pointcuts match at compile
+//! time, with the nightly compiler driver. On a stable toolchain, the
+//! service calls the registry itself, from two places: [`request`], the
+//! middleware running each request as a call of the handler of its route,
+//! and [`call`], which the store runs its functions through. Either way,
+//! which aspects apply is decided by the pointcuts alone.
+//!
+//! Functions are named as `cargo aspect` names them, with module paths
+//! starting with `crate`, so that the pointcuts it checks and reports on
+//! are the ones matched at run time.
+
+use crate::api::HANDLERS;
+use aspect_core::pointcut::FunctionInfo;
+use aspect_core::{Arg, AspectError};
+use aspect_runtime::global_registry;
+use aspect_std::context::{self, AspectContext};
+use aspect_std::correlation;
+use axum::extract::{MatchedPath, Request};
+use axum::http::header::AUTHORIZATION;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::any::Any;
+
+/// Runs `request` as a call of the handler of its route, through the
+/// aspects matching the handler, in a task context of its own.
+///
+/// The handler's join point has the `path` of the request as argument,
+/// and its bearer `token` when it has one. The response gets the
+/// correlation id of the request in its `x-correlation-id` header; a
+/// request an aspect rejects gets the status of the error instead.
+pub async fn request(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str);
+    let handler = HANDLERS
+        .iter()
+        .find(|handler| Some(handler.route) == route && handler.method == request.method());
+    let Some(handler) = handler else {
+        return next.run(request).await;
+    };
+
+    let function = function(handler.name, handler.module);
+    let mut args = vec![Arg::new("path", &request.uri().path().to_string())];
+    if let Some(token) = bearer_token(&request) {
+        args.push(Arg::new("token", &token));
+    }
+    let ctx = function.join_point().with_args(args);
+
+    let mut task = AspectContext::new();
+    if let Some(id) = request.headers().get(correlation::HEADER) {
+        task = task.with_correlation_id(id.to_str().unwrap_or_default());
+    }
+    context::scope(task, async {
+        let proceed = Box::pin(async move {
+            let mut response = next.run(request).await;
+            if let Some(id) = correlation::current().and_then(|id| id.parse().ok()) {
+                response.headers_mut().insert(correlation::HEADER, id);
+            }
+            Ok(Box::new(response) as Box<dyn Any>)
+        });
+        match global_registry()
+            .apply_aspects_async(&function, &ctx, proceed)
+            .await
+        {
+            Ok(response) => match response.downcast::<Response>() {
+                Ok(response) => *response,
+                Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            },
+            Err(error) => rejection(&error),
+        }
+    })
+    .await
+}
+
+/// Runs `f`, the function `name` of the module `module_path`, called with
+/// `args`, through the aspects matching it.
+pub fn call<T: Any>(
+    name: &'static str,
+    module_path: &str,
+    args: Vec<Arg>,
+    f: impl FnOnce() -> Result<T, AspectError>,
+) -> Result<T, AspectError> {
+    let function = function(name, module_path);
+    let result = global_registry().invoke(
+        &function,
+        || function.join_point().with_args(args),
+        || f().map(|value| Box::new(value) as Box<dyn Any>),
+    )?;
+    result
+        .downcast::<T>()
+        .map(|value| *value)
+        .map_err(|_| AspectError::execution(format!("{} returned another type", name)))
+}
+
+/// The public function `name` of the module `module_path`, a path of this
+/// crate, named from `crate`.
+fn function(name: &'static str, module_path: &str) -> FunctionInfo {
+    let module = match module_path.split_once("::") {
+        Some((_, path)) => format!("crate::{}", path),
+        None => "crate".to_string(),
+    };
+    FunctionInfo::new(name, module, "pub")
+}
+
+/// The token of the `Authorization: Bearer <token>` header of `request`.
+fn bearer_token(request: &Request) -> Option<String> {
+    let header = request.headers().get(AUTHORIZATION)?;
+    let token = header.to_str().ok()?.strip_prefix("Bearer ")?;
+    Some(token.to_string())
+}
+
+/// The response to a request an aspect rejected with `error`.
+fn rejection(error: &AspectError) -> Response {
+    let status = match error {
+        AspectError::Denied { .. } => StatusCode::UNAUTHORIZED,
+        AspectError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        AspectError::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+        AspectError::CircuitOpen { .. } | AspectError::Overloaded { .. } => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, error.to_string()).into_response()
+}
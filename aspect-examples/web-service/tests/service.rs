@@ -0,0 +1,84 @@
+//! Requests to the service, through the aspects its pointcuts bind.
+
+use aspect_web_service::aspects::AUDIT_TRAIL;
+use aspect_web_service::store::{self, Store, User};
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::response::Response;
+use http_body_util::BodyExt;
+use std::collections::BTreeMap;
+use tower::ServiceExt;
+
+async fn send(request: Request<Body>) -> Response {
+    store::open_session("test-token", "alice");
+    let app = aspect_web_service::app(Store::default());
+    app.oneshot(request).await.unwrap()
+}
+
+async fn body<T: serde::de::DeserializeOwned>(response: Response) -> T {
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+fn correlation_id(response: &Response) -> Option<&str> {
+    let id = response.headers().get("x-correlation-id")?;
+    id.to_str().ok()
+}
+
+#[tokio::test]
+async fn test_user_routes_need_a_session() {
+    let request = Request::get("/users/1").body(Body::empty()).unwrap();
+    assert_eq!(send(request).await.status(), StatusCode::UNAUTHORIZED);
+
+    let request = Request::get("/users/1")
+        .header("authorization", "Bearer unknown")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(request).await.status(), StatusCode::UNAUTHORIZED);
+
+    // Other routes are open
+    let request = Request::get("/health").body(Body::empty()).unwrap();
+    let response = send(request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(correlation_id(&response).map(str::len), Some(16));
+}
+
+#[tokio::test]
+async fn test_create_user_is_audited_with_request_context() {
+    let request = Request::post("/users")
+        .header("authorization", "Bearer test-token")
+        .header("content-type", "application/json")
+        .header("x-correlation-id", "req-42")
+        .body(Body::from(r#"{"name":"bob"}"#))
+        .unwrap();
+    let response = send(request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    // The id of the request is kept
+    assert_eq!(correlation_id(&response), Some("req-42"));
+    let user: User = body(response).await;
+    assert_eq!(user.name, "bob");
+
+    // The audit aspect of the store saw the principal and id of the request
+    let record = AUDIT_TRAIL
+        .records()
+        .into_iter()
+        .find(|record| record.correlation_id.as_deref() == Some("req-42"))
+        .unwrap();
+    assert_eq!(record.function, "crate::store::save_user");
+    assert_eq!(record.principal.as_deref(), Some("alice"));
+}
+
+#[tokio::test]
+async fn test_metrics_count_aspect_executions() {
+    let request = Request::get("/users/7")
+        .header("authorization", "Bearer test-token")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(request).await.status(), StatusCode::NOT_FOUND);
+
+    let request = Request::get("/metrics").body(Body::empty()).unwrap();
+    let executions: BTreeMap<String, u64> = body(send(request).await).await;
+    for name in ["correlation", "authenticate", "log_request", "trace_store"] {
+        assert!(executions[name] > 0, "{} never ran: {:?}", name, executions);
+    }
+}
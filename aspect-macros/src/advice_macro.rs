@@ -1,7 +1,19 @@
 //! Implementation of the #[advice] attribute macro.
 //!
 //! The #[advice] macro allows declarative aspect application using pointcut expressions.
+//! The pointcut is parsed as the macro expands, so that an invalid one is a
+//! compile error, and the expansion constructs its AST instead of parsing
+//! it again at run time.
+//!
+//! When `ASPECT_REGISTRY_DIR` is set (as the aspect driver does), each
+//! expansion also records the advice in a registration manifest, so the
+//! compile-time matcher knows the aspects declared in code: one JSON file
+//! per advice in `$ASPECT_REGISTRY_DIR/<crate name>/`.
+
+use std::io;
+use std::path::{Path, PathBuf};
 
+use aspect_core::pointcut::{NamePattern, Pointcut, Visibility};
 use proc_macro2::TokenStream;
 use quote::quote;
 use syn::{parse::Parse, parse::ParseStream, Error, Expr, ItemFn, LitStr, Result, Token};
@@ -12,28 +24,44 @@ pub struct AdviceArgs {
     /// Pointcut expression string
     pub pointcut: String,
 
+    /// The pointcut expression parsed
+    pub parsed: Pointcut,
+
     /// Advice type: "before", "after", "after_error", or "around"
     pub advice_type: Option<String>,
 
     /// Execution order (lower runs first)
     pub order: i32,
+
+    /// The predicate of `cfg(...)`, which the advice is only compiled under
+    pub cfg: Option<TokenStream>,
 }
 
 impl Parse for AdviceArgs {
     fn parse(input: ParseStream) -> Result<Self> {
-        let mut pointcut = None;
+        let mut pointcut: Option<LitStr> = None;
         let mut advice_type = None;
         let mut order = 0;
+        let mut cfg = None;
 
-        // Parse key-value pairs: pointcut = "...", advice = "...", order = 10
+        // Parse key-value pairs: pointcut = "...", advice = "...", order = 10,
+        // and cfg(...)
         while !input.is_empty() {
             let key: syn::Ident = input.parse()?;
+            if key == "cfg" && input.peek(syn::token::Paren) {
+                let predicate;
+                syn::parenthesized!(predicate in input);
+                cfg = Some(predicate.parse()?);
+                if input.peek(Token![,]) {
+                    input.parse::<Token![,]>()?;
+                }
+                continue;
+            }
             input.parse::<Token![=]>()?;
 
             match key.to_string().as_str() {
                 "pointcut" => {
-                    let value: LitStr = input.parse()?;
-                    pointcut = Some(value.value());
+                    pointcut = Some(input.parse()?);
                 }
                 "advice" => {
                     let value: LitStr = input.parse()?;
@@ -65,11 +93,19 @@ impl Parse for AdviceArgs {
         let pointcut = pointcut.ok_or_else(|| {
             Error::new(input.span(), "Missing required attribute: pointcut")
         })?;
+        let parsed = Pointcut::parse(&pointcut.value()).map_err(|e| {
+            Error::new(
+                pointcut.span(),
+                format!("Invalid pointcut expression: {}", e),
+            )
+        })?;
 
         Ok(AdviceArgs {
-            pointcut,
+            pointcut: pointcut.value(),
+            parsed,
             advice_type,
             order,
+            cfg,
         })
     }
 }
@@ -80,12 +116,18 @@ impl Parse for AdviceArgs {
 /// 1. Wraps the function in an Aspect implementation
 /// 2. Registers it with the global aspect registry
 /// 3. Associates it with the given pointcut pattern
-pub fn transform(args: AdviceArgs, func: ItemFn) -> Result<TokenStream> {
+///
+/// Without `register`, the aspect can never run: only the function is
+/// emitted, unchanged. With `cfg(predicate)`, the aspect and its
+/// registration are only compiled where the predicate holds, and the
+/// advice is not recorded in the manifest, as whether it runs is only
+/// known to the compiler.
+pub fn transform(args: AdviceArgs, func: ItemFn, register: bool) -> Result<TokenStream> {
     let func_name = &func.sig.ident;
     let aspect_struct_name = quote::format_ident!("{}Aspect", func_name);
     let registrar_name = quote::format_ident!("__register_{}", func_name);
 
-    let pointcut_str = &args.pointcut;
+    let pointcut = pointcut_tokens(&args.parsed);
     let order = args.order;
 
     // Determine which advice method to implement based on advice_type
@@ -102,35 +144,74 @@ pub fn transform(args: AdviceArgs, func: ItemFn) -> Result<TokenStream> {
         }
     };
 
+    if !register {
+        return Ok(quote! {
+            #[allow(dead_code)]
+            #func
+        });
+    }
+
+    // Best effort: a manifest that cannot be written only leaves the
+    // advice unknown to the compile-time matcher, the runtime still has it
+    if let (None, Some(dir), Ok(crate_name)) = (
+        &args.cfg,
+        std::env::var_os(REGISTRY_DIR_ENV),
+        std::env::var("CARGO_CRATE_NAME"),
+    ) {
+        let _ = write_manifest_entry(
+            &Path::new(&dir).join(crate_name),
+            &func_name.to_string(),
+            &args,
+        );
+    }
+
+    let (gate, func) = match &args.cfg {
+        Some(predicate) => (
+            quote!(#[cfg(#predicate)]),
+            quote! {
+                #[cfg_attr(not(#predicate), allow(dead_code))]
+                #func
+            },
+        ),
+        None => (TokenStream::new(), quote!(#func)),
+    };
+
     // Generate the aspect struct and registration code
     let output = quote! {
         // Original function (kept for potential direct calls)
         #func
 
         // Generated aspect wrapper
+        #gate
+        #[allow(non_camel_case_types)]
         #[derive(Clone, Copy)]
         struct #aspect_struct_name;
 
+        #gate
         impl aspect_core::Aspect for #aspect_struct_name {
             #advice_impl
+
+            // The advice gets the joinpoint, arguments included
+            fn reads_args(&self) -> bool {
+                true
+            }
         }
 
         // Registration function using once_cell::Lazy
+        #gate
         #[allow(non_upper_case_globals)]
         static #registrar_name: aspect_runtime::once_cell::sync::Lazy<()> =
             aspect_runtime::once_cell::sync::Lazy::new(|| {
-                let pointcut = aspect_core::pointcut::Pointcut::parse(#pointcut_str)
-                    .expect(&format!("Invalid pointcut expression: {}", #pointcut_str));
-
                 aspect_runtime::global_registry().register(
                     std::sync::Arc::new(#aspect_struct_name),
-                    pointcut,
+                    #pointcut,
                     #order,
                     Some(stringify!(#func_name).to_string()),
                 );
             });
 
         // Force registration by referencing the static
+        #gate
         const _: () = {
             let _ = &#registrar_name as &aspect_runtime::once_cell::sync::Lazy<()>;
         };
@@ -139,6 +220,113 @@ pub fn transform(args: AdviceArgs, func: ItemFn) -> Result<TokenStream> {
     Ok(output)
 }
 
+/// Code constructing `pointcut`.
+fn pointcut_tokens(pointcut: &Pointcut) -> TokenStream {
+    match pointcut {
+        Pointcut::Execution(pattern) => {
+            let visibility = match &pattern.visibility {
+                Some(visibility) => {
+                    let visibility = match visibility {
+                        Visibility::Public => quote!(Public),
+                        Visibility::Crate => quote!(Crate),
+                        Visibility::Super => quote!(Super),
+                        Visibility::Private => quote!(Private),
+                    };
+                    quote!(Some(aspect_core::pointcut::Visibility::#visibility))
+                }
+                None => quote!(None),
+            };
+            let name = match &pattern.name {
+                NamePattern::Wildcard => quote!(Wildcard),
+                NamePattern::Exact(name) => quote!(Exact(std::string::String::from(#name))),
+                NamePattern::Prefix(name) => quote!(Prefix(std::string::String::from(#name))),
+                NamePattern::Suffix(name) => quote!(Suffix(std::string::String::from(#name))),
+                NamePattern::Contains(name) => {
+                    quote!(Contains(std::string::String::from(#name)))
+                }
+            };
+            let return_type = match &pattern.return_type {
+                Some(return_type) => quote!(Some(std::string::String::from(#return_type))),
+                None => quote!(None),
+            };
+            quote! {
+                aspect_core::pointcut::Pointcut::Execution(
+                    aspect_core::pointcut::ExecutionPattern {
+                        visibility: #visibility,
+                        name: aspect_core::pointcut::NamePattern::#name,
+                        return_type: #return_type,
+                    }
+                )
+            }
+        }
+        Pointcut::Within(pattern) => {
+            let path = &pattern.path;
+            quote!(aspect_core::pointcut::Pointcut::within_module(#path))
+        }
+        Pointcut::And(left, right) => {
+            let (left, right) = (pointcut_tokens(left), pointcut_tokens(right));
+            quote!(#left.and(#right))
+        }
+        Pointcut::Or(left, right) => {
+            let (left, right) = (pointcut_tokens(left), pointcut_tokens(right));
+            quote!(#left.or(#right))
+        }
+        Pointcut::Not(inner) => {
+            let inner = pointcut_tokens(inner);
+            quote!(#inner.not())
+        }
+    }
+}
+
+/// Environment variable naming the registration manifest directory.
+pub const REGISTRY_DIR_ENV: &str = "ASPECT_REGISTRY_DIR";
+
+/// Manifest entry of the advice `name`, as one line of JSON.
+///
+/// This is the format read by `aspect_driver::r#match::load_registry_manifest`.
+pub fn manifest_entry(name: &str, args: &AdviceArgs) -> String {
+    format!(
+        "{{\"name\":{},\"pointcut\":{},\"advice\":{},\"order\":{}}}\n",
+        json_string(name),
+        json_string(&args.pointcut),
+        json_string(args.advice_type.as_deref().unwrap_or("around")),
+        args.order
+    )
+}
+
+/// Write the manifest entry of the advice `name` into `dir`.
+///
+/// The file is named after the advice and a hash of its entry, so the
+/// expansions of a crate never overwrite one another's entries.
+pub fn write_manifest_entry(dir: &Path, name: &str, args: &AdviceArgs) -> io::Result<PathBuf> {
+    let entry = manifest_entry(name, args);
+    let hash = entry.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    let path = dir.join(format!("{}-{:016x}.json", name, hash));
+
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(&path, entry)?;
+    Ok(path)
+}
+
+/// `value` as a JSON string literal.
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if u32::from(c) < 0x20 => json.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
 /// Generate `before` advice implementation.
 fn generate_before_impl(func: &ItemFn) -> Result<TokenStream> {
     let func_name = &func.sig.ident;
@@ -185,3 +373,132 @@ fn generate_around_impl(func: &ItemFn) -> Result<TokenStream> {
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(pointcut: &str, advice_type: Option<&str>, order: i32) -> AdviceArgs {
+        AdviceArgs {
+            pointcut: pointcut.to_string(),
+            // Not part of the manifest
+            parsed: Pointcut::all_functions(),
+            advice_type: advice_type.map(str::to_string),
+            order,
+            cfg: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_pointcut() {
+        let args: AdviceArgs = syn::parse_str(
+            r#"pointcut = "execution(pub fn *(..)) && within(crate::api)", order = 10"#,
+        )
+        .unwrap();
+        assert_eq!(
+            args.parsed,
+            Pointcut::parse("execution(pub fn *(..)) && within(crate::api)").unwrap()
+        );
+        assert_eq!(args.order, 10);
+
+        let error = syn::parse_str::<AdviceArgs>(r#"pointcut = "call(pub fn *(..))""#)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("Invalid pointcut expression"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_parse_cfg() {
+        let args: AdviceArgs =
+            syn::parse_str(r#"pointcut = "within(crate::api)", cfg(feature = "audit"), order = 1"#)
+                .unwrap();
+        assert_eq!(args.cfg.unwrap().to_string(), "feature = \"audit\"");
+        assert_eq!(args.order, 1);
+
+        let args: AdviceArgs = syn::parse_str(r#"pointcut = "within(crate::api)""#).unwrap();
+        assert!(args.cfg.is_none());
+    }
+
+    #[test]
+    fn test_transform_cfg() {
+        let func: ItemFn = syn::parse_quote! {
+            fn audit(ctx: &aspect_core::JoinPoint) {}
+        };
+        let mut advice = args("within(crate::api)", Some("before"), 0);
+        advice.cfg = Some(quote!(debug_assertions));
+
+        let output = transform(advice, func.clone(), true).unwrap().to_string();
+        let gate = quote!(#[cfg(debug_assertions)]).to_string();
+        // The struct, its impl, the registrar and the const forcing it
+        assert_eq!(output.matches(&gate).count(), 4, "{}", output);
+        assert!(output.contains(&format!("{} impl aspect_core :: Aspect", gate)));
+        assert!(output.contains(&format!("{} const _", gate)));
+
+        let output = transform(args("within(crate::api)", Some("before"), 0), func, false)
+            .unwrap()
+            .to_string();
+        assert!(!output.contains("auditAspect"));
+        assert!(!output.contains("__register_audit"));
+    }
+
+    #[test]
+    fn test_pointcut_tokens() {
+        let pointcut =
+            Pointcut::parse("!(execution(pub(crate) fn save*(..)) || within(crate::db))").unwrap();
+        let tokens = pointcut_tokens(&pointcut).to_string();
+        assert!(!tokens.contains("parse"));
+        for expected in [
+            "Visibility :: Crate",
+            "NamePattern :: Prefix (std :: string :: String :: from (\"save\"))",
+            "return_type : None",
+            "within_module (\"crate::db\")",
+            ". or (",
+            ". not ()",
+        ] {
+            assert!(tokens.contains(expected), "{} in {}", expected, tokens);
+        }
+    }
+
+    #[test]
+    fn test_manifest_entry() {
+        let entry = manifest_entry(
+            "api_logger",
+            &args("execution(pub fn *(..)) && within(crate::api)", None, 10),
+        );
+        assert_eq!(
+            entry,
+            "{\"name\":\"api_logger\",\"pointcut\":\"execution(pub fn *(..)) && \
+             within(crate::api)\",\"advice\":\"around\",\"order\":10}\n"
+        );
+
+        let entry = manifest_entry("audit", &args("name(\"a\\b\")", Some("before"), -1));
+        assert!(entry.contains(r#""pointcut":"name(\"a\\b\")""#));
+        assert!(entry.contains(r#""advice":"before","order":-1"#));
+    }
+
+    #[test]
+    fn test_write_manifest_entry() {
+        let dir = std::env::temp_dir().join(format!("aspect-manifest-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let logger = args("within(crate::api)", Some("before"), 0);
+        let path = write_manifest_entry(&dir, "logger", &logger).unwrap();
+        let file_name = path.file_name().unwrap().to_string_lossy();
+        assert!(file_name.starts_with("logger-"));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            manifest_entry("logger", &logger)
+        );
+
+        // Same name, other pointcut: both entries are kept
+        let other = args("within(crate::db)", Some("before"), 0);
+        assert_ne!(write_manifest_entry(&dir, "logger", &other).unwrap(), path);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
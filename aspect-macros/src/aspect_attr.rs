@@ -1,20 +1,122 @@
 //! Main transformation logic for the #[aspect] attribute macro.
 
 use proc_macro2::TokenStream;
-use syn::{Expr, ItemFn, Result};
+use quote::quote;
+use syn::{Error, ItemFn, Result, ReturnType};
 
-use crate::codegen::generate_aspect_wrapper;
+use crate::codegen::{generate_aspect_wrapper, generate_static_wrapper, is_result_type};
 use crate::parsing::AspectInfo;
 
 /// Transforms a function by applying aspect weaving.
 ///
 /// This is the main entry point for the `#[aspect]` macro transformation.
-pub fn transform(aspect_expr: Expr, func: ItemFn) -> Result<TokenStream> {
-    // Parse the aspect information
-    let aspect_info = AspectInfo::parse(aspect_expr)?;
+///
+/// With `cfg(predicate)`, the woven code is only compiled where the
+/// predicate holds, and the original function, unchanged, where it does
+/// not: builds where the aspect can never run carry no trace of it.
+pub fn transform(aspect_info: AspectInfo, func: ItemFn) -> Result<TokenStream> {
+    // The aspects of a function returning `Result` see its panics as
+    // errors with `CatchPanicAspect`, which also returns them
+    if aspect_info.unwind {
+        if let ReturnType::Type(_, ty) = &func.sig.output {
+            if is_result_type(ty) {
+                return Err(Error::new_spanned(
+                    ty,
+                    "`unwind` is for functions not returning `Result`: weave \
+                     `CatchPanicAspect` to turn the panics of this one into errors",
+                ));
+            }
+        }
+    }
 
     // Generate the wrapped code
-    let output = generate_aspect_wrapper(&aspect_info, &func);
+    let output = if aspect_info.is_static {
+        generate_static_wrapper(&aspect_info, &func)
+    } else {
+        generate_aspect_wrapper(&aspect_info, &func)
+    };
+
+    Ok(match &aspect_info.cfg {
+        Some(predicate) => cfg_gate(predicate, output, &func),
+        None => output,
+    })
+}
+
+/// `woven` where `predicate` holds, `func` verbatim where it does not.
+fn cfg_gate(predicate: &TokenStream, woven: TokenStream, func: &ItemFn) -> TokenStream {
+    let items = match syn::parse2::<syn::File>(woven) {
+        Ok(file) => file.items,
+        Err(e) => return e.to_compile_error(),
+    };
+    quote! {
+        #(
+            #[cfg(#predicate)]
+            #items
+        )*
+
+        #[cfg(not(#predicate))]
+        #func
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_transform_cfg() {
+        let func: ItemFn = parse_quote! {
+            pub fn double(x: u32) -> u32 {
+                x * 2
+            }
+        };
+        let info: AspectInfo = parse_quote!(static CallCounter, cfg(feature = "tracing"));
+        let woven = transform(info, func.clone()).unwrap().to_string();
+
+        let gated = quote!(#[cfg(feature = "tracing")]).to_string();
+        assert_eq!(woven.matches(&gated).count(), 2);
+        assert!(woven.starts_with(&gated));
+        assert!(woven.ends_with(&quote!(#[cfg(not(feature = "tracing"))] #func).to_string()));
+
+        let info: AspectInfo = parse_quote!(static CallCounter);
+        assert!(!transform(info, func).unwrap().to_string().contains("cfg"));
+    }
+
+    #[test]
+    fn test_transform_unwind() {
+        let func: ItemFn = parse_quote! {
+            fn commit(batch: u32) -> u32 {
+                batch
+            }
+        };
+        let info: AspectInfo = parse_quote!(unwind TransactionAspect::new());
+        let woven = transform(info, func).unwrap().to_string();
+        assert!(woven.contains("__private :: catch_panic (& mut __panic"));
+        assert!(woven.contains(":: std :: panic :: resume_unwind (__payload)"));
+
+        let func: ItemFn = parse_quote! {
+            async fn commit(batch: u32) {}
+        };
+        let info: AspectInfo = parse_quote!(unwind TransactionAspect::new(), Logger);
+        let woven = transform(info, func).unwrap().to_string();
+        assert!(woven.contains("__private :: catch_unwind (__future) . await"));
+
+        let func: ItemFn = parse_quote! {
+            fn commit(batch: u32) -> Result<u32, String> {
+                Ok(batch)
+            }
+        };
+        let info: AspectInfo = parse_quote!(unwind TransactionAspect::new());
+        let error = transform(info, func).err().unwrap();
+        assert!(error.to_string().contains("CatchPanicAspect"));
 
-    Ok(output)
+        let func: ItemFn = parse_quote! {
+            fn commit(batch: u32) -> u32 {
+                batch
+            }
+        };
+        let info: AspectInfo = parse_quote!(TransactionAspect::new());
+        assert!(!transform(info, func).unwrap().to_string().contains("catch"));
+    }
 }
@@ -9,6 +9,7 @@ use crate::parsing::AspectInfo;
 /// Generates the aspect-woven code for a function.
 pub fn generate_aspect_wrapper(aspect_info: &AspectInfo, func: &ItemFn) -> TokenStream {
     let original_fn = func;
+    let func = &bind_params(func);
     let fn_name = &func.sig.ident;
     let fn_vis = &func.vis;
     let fn_inputs = &func.sig.inputs;
@@ -17,32 +18,61 @@ pub fn generate_aspect_wrapper(aspect_info: &AspectInfo, func: &ItemFn) -> Token
     let fn_where_clause = &func.sig.generics.where_clause;
     let fn_asyncness = &func.sig.asyncness;
 
-    let aspect_expr = &aspect_info.aspect_expr;
-
-    // Generate the original function with a mangled name
-    let original_fn_name = syn::Ident::new(
-        &format!("__aspect_original_{}", fn_name),
-        fn_name.span(),
-    );
+    // The advice of a `LocalAspect` is called through a wrapper with the
+    // methods of an `Aspect`; a parameterized aspect is built from its type
+    let single_aspect: [Expr; 1];
+    let aspects = if aspect_info.is_local {
+        let aspect_expr = &aspect_info.aspects[0];
+        single_aspect = [syn::parse_quote! {
+            ::aspect_core::aspect::__private::Local { aspect: &#aspect_expr }
+        }];
+        &single_aspect[..]
+    } else if !aspect_info.params.is_empty() {
+        single_aspect = [configured_aspect(
+            &aspect_info.aspects[0],
+            &aspect_info.params,
+            &fn_name.to_string(),
+        )];
+        &single_aspect[..]
+    } else {
+        &aspect_info.aspects[..]
+    };
 
-    let mut original_fn_renamed = original_fn.clone();
-    original_fn_renamed.sig.ident = original_fn_name.clone();
-    // Make the original function private
-    original_fn_renamed.vis = syn::Visibility::Inherited;
+    let (original_fn_renamed, original_fn_name) = rename_original(original_fn);
+    let param_names = param_names(func);
 
-    // Extract parameter names for calling the original function
-    let param_names: Vec<_> = func
-        .sig
-        .inputs
-        .iter()
-        .filter_map(|arg| {
-            if let syn::FnArg::Typed(pat_type) = arg {
-                Some(&pat_type.pat)
+    // Describe the call site, including the arguments, captured only when
+    // one of the aspects reads them as that allocates
+    let fn_name_str = fn_name.to_string();
+    let arg_captures = generate_arg_captures(func);
+    let stacked_async = fn_asyncness.is_some() && aspects.len() > 1;
+    let args = if arg_captures.is_empty() {
+        quote!(::aspect_core::__private::Vec::new())
+    } else {
+        let reads_args = if stacked_async {
+            quote!(__aspects.iter().any(|__aspect| __aspect.reads_args()))
+        } else {
+            quote!(__aspect.reads_args())
+        };
+        quote! {
+            if #reads_args {
+                ::aspect_core::__private::vec![#(#arg_captures),*]
             } else {
-                None
+                ::aspect_core::__private::Vec::new()
             }
-        })
-        .collect();
+        }
+    };
+    let context = quote! {
+        JoinPoint::new(
+            #fn_name_str,
+            module_path!(),
+            Location {
+                file: file!(),
+                line: line!(),
+            },
+        )
+        .with_args(#args)
+    };
 
     // Determine the return type and if it's a Result
     let (return_type, is_result) = match fn_output {
@@ -51,25 +81,45 @@ pub fn generate_aspect_wrapper(aspect_info: &AspectInfo, func: &ItemFn) -> Token
     };
 
     // Generate aspect weaving code using around advice
-    let aspect_call = if fn_asyncness.is_some() {
+    let skip = generate_skip(func, &original_fn_name, &param_names);
+    let aspect_call = if stacked_async {
+        generate_async_stack_call(
+            aspects,
+            &original_fn_name,
+            &context,
+            &param_names,
+            is_result,
+            aspect_info.unwind,
+        )
+    } else if fn_asyncness.is_some() {
         // Async function handling
+        let aspect_expr = &aspects[0];
         generate_async_around_call(
-            aspect_expr,
+            &quote! {
+                let __aspect = #aspect_expr;
+                #skip
+            },
             &original_fn_name,
-            fn_name,
+            &context,
             &param_names,
             &return_type,
             is_result,
+            aspect_info.unwind,
         )
     } else {
         // Sync function handling
+        let bind_aspect = bind_aspects(aspects);
         generate_sync_around_call(
-            aspect_expr,
+            &quote! {
+                #bind_aspect
+                #skip
+            },
             &original_fn_name,
-            fn_name,
+            &context,
             &param_names,
             &return_type,
             is_result,
+            aspect_info.unwind,
         )
     };
 
@@ -84,93 +134,401 @@ pub fn generate_aspect_wrapper(aspect_info: &AspectInfo, func: &ItemFn) -> Token
     }
 }
 
+/// Generates the woven code of `#[aspect(static ...)]`, which calls the
+/// advice of a `StaticAspect` directly on the concrete types of the
+/// function.
+pub fn generate_static_wrapper(aspect_info: &AspectInfo, func: &ItemFn) -> TokenStream {
+    let original_fn = func;
+    let func = &bind_params(func);
+    let fn_name = &func.sig.ident;
+    let fn_vis = &func.vis;
+    let fn_inputs = &func.sig.inputs;
+    let fn_output = &func.sig.output;
+    let fn_generics = &func.sig.generics;
+    let fn_where_clause = &func.sig.generics.where_clause;
+    let fn_asyncness = &func.sig.asyncness;
+
+    let aspect_expr = &aspect_info.aspects[0];
+    let (original_fn_renamed, original_fn_name) = rename_original(original_fn);
+    let param_names = param_names(func);
+
+    // Arguments are not captured, as that allocates. With no arguments,
+    // there is nothing to drop, and not dropping lets the compiler build
+    // only the fields the advice reads.
+    let fn_name_str = fn_name.to_string();
+    let context = quote! {
+        ::core::mem::ManuallyDrop::new(::aspect_core::JoinPoint::new(
+            #fn_name_str,
+            module_path!(),
+            ::aspect_core::Location {
+                file: file!(),
+                line: line!(),
+            },
+        ))
+    };
+
+    let bypass = generate_bypass(func, &original_fn_name, &param_names);
+    let await_original = fn_asyncness.map(|_| quote! { .await });
+    let bind_aspect = quote! {
+        let __aspect = #aspect_expr;
+        let __reentrancy_name = ::aspect_core::StaticAspect::reentrancy_name(&__aspect);
+        if __reentrancy_name.is_some_and(::aspect_core::reentrancy::advising) {
+            return #original_fn_name(#(#param_names),*) #await_original;
+        }
+    };
+    let body = if fn_asyncness.is_some() {
+        quote! {
+            #bind_aspect
+            let __context = #context;
+
+            ::aspect_core::reentrancy::advice_as(__reentrancy_name, || {
+                ::aspect_core::StaticAspect::before(&__aspect, &*__context)
+            });
+            let __result = #original_fn_name(#(#param_names),*).await;
+            ::aspect_core::reentrancy::advice_as(__reentrancy_name, || {
+                ::aspect_core::StaticAspect::after(&__aspect, &*__context, &__result)
+            });
+            __result
+        }
+    } else {
+        quote! {
+            #bind_aspect
+            let __context = #context;
+
+            let __original = ::aspect_core::reentrancy::proceeding(|| #original_fn_name(#(#param_names),*));
+            ::aspect_core::reentrancy::advice_as(__reentrancy_name, || {
+                ::aspect_core::StaticAspect::around(&__aspect, &*__context, __original)
+            })
+        }
+    };
+
+    quote! {
+        #original_fn_renamed
+
+        #fn_vis #fn_asyncness fn #fn_name #fn_generics(#fn_inputs) #fn_output #fn_where_clause {
+            #bypass
+            #body
+        }
+    }
+}
+
+/// The aspect of `#[aspect(Type, key = value, ...)]`: built from the
+/// parameters with `FromAspectArgs` on the first call, kept in a static and
+/// cloned for each call, so that the calls share its state. The literal
+/// parameters are checked against `FromAspectArgs::PARAMS` at compile time.
+fn configured_aspect(aspect_type: &Expr, params: &[(syn::Ident, Expr)], fn_name: &str) -> Expr {
+    let name = match aspect_type {
+        Expr::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string())
+            .unwrap_or_default(),
+        _ => unreachable!("parameters are only parsed after an aspect type"),
+    };
+    let keys: Vec<String> = params.iter().map(|(key, _)| key.to_string()).collect();
+    let values = params.iter().map(|(_, value)| value);
+    let literals = params.iter().map(|(_, value)| literal_kind(value));
+
+    syn::parse_quote! {{
+        const _: () = {
+            let __message = ::aspect_core::config::__private::check(
+                #name,
+                <#aspect_type as ::aspect_core::config::FromAspectArgs>::PARAMS,
+                &[#((#keys, #literals)),*],
+            );
+            if !__message.is_empty() {
+                ::core::panic!("{}", __message.as_str());
+            }
+        };
+        static __ASPECT: ::aspect_core::__private::Lazy<#aspect_type> = ::aspect_core::__private::Lazy::new(|| {
+            let __args = ::aspect_core::config::AspectArgs::new(#name)
+                #(.with(#keys, #values))*;
+            match <#aspect_type as ::aspect_core::config::FromAspectArgs>::from_aspect_args(&__args) {
+                ::core::result::Result::Ok(__aspect) => __aspect,
+                ::core::result::Result::Err(__err) => {
+                    ::core::panic!("invalid parameters for the aspect of {}: {}", #fn_name, __err)
+                }
+            }
+        });
+        ::core::clone::Clone::clone(&*__ASPECT)
+    }}
+}
+
+/// What the compile-time check of a configured aspect knows of the value
+/// of a parameter: its type and, for integers, its value, if it is a
+/// literal.
+fn literal_kind(value: &Expr) -> TokenStream {
+    let (negative, lit) = match value {
+        Expr::Lit(expr) => (false, &expr.lit),
+        Expr::Unary(syn::ExprUnary {
+            op: syn::UnOp::Neg(_),
+            expr,
+            ..
+        }) => match &**expr {
+            Expr::Lit(expr) => (true, &expr.lit),
+            _ => return quote!(::aspect_core::config::__private::Literal::Expr),
+        },
+        _ => return quote!(::aspect_core::config::__private::Literal::Expr),
+    };
+    match lit {
+        syn::Lit::Int(int) => match int.base10_parse::<i128>() {
+            Ok(value) => {
+                let value = if negative { -value } else { value };
+                quote!(::aspect_core::config::__private::Literal::Int(#value))
+            }
+            Err(_) => quote!(::aspect_core::config::__private::Literal::Expr),
+        },
+        syn::Lit::Float(_) => quote!(::aspect_core::config::__private::Literal::Float),
+        syn::Lit::Bool(_) if !negative => quote!(::aspect_core::config::__private::Literal::Bool),
+        syn::Lit::Str(text) if !negative => {
+            let duration = aspect_core::config::parse_duration(&text.value()).is_some();
+            quote!(::aspect_core::config::__private::Literal::Str { duration: #duration })
+        }
+        _ => quote!(::aspect_core::config::__private::Literal::Expr),
+    }
+}
+
+/// Calls the original function directly, without evaluating the static
+/// aspect, when the kill switch of `aspect_core::switch` turned aspects
+/// off: static aspects declare no precedence, so none is a security
+/// aspect, which the switch leaves on.
+fn generate_bypass(
+    func: &ItemFn,
+    original_fn_name: &syn::Ident,
+    param_names: &[&syn::Pat],
+) -> TokenStream {
+    let await_original = func.sig.asyncness.map(|_| quote! { .await });
+    quote! {
+        if !::aspect_core::switch::enabled() {
+            return #original_fn_name(#(#param_names),*) #await_original;
+        }
+    }
+}
+
+/// Calls the original function directly, without `__aspect`, when called
+/// from its own advice or when the kill switch turned it off (see
+/// `aspect_core::reentrancy`). The name of the aspect is kept in
+/// `__reentrancy_name`, to guard its advice with.
+fn generate_skip(
+    func: &ItemFn,
+    original_fn_name: &syn::Ident,
+    param_names: &[&syn::Pat],
+) -> TokenStream {
+    let await_original = func.sig.asyncness.map(|_| quote! { .await });
+    quote! {
+        let __reentrancy_name = __aspect.reentrancy_name();
+        if ::aspect_core::reentrancy::skips_named(__reentrancy_name, __aspect.precedence()) {
+            return #original_fn_name(#(#param_names),*) #await_original;
+        }
+    }
+}
+
+/// The original function, renamed and made private for the wrapper to call,
+/// and its new name.
+fn rename_original(func: &ItemFn) -> (ItemFn, syn::Ident) {
+    let fn_name = &func.sig.ident;
+    let original_fn_name =
+        syn::Ident::new(&format!("__aspect_original_{}", fn_name), fn_name.span());
+
+    let mut original_fn_renamed = func.clone();
+    original_fn_renamed.sig.ident = original_fn_name.clone();
+    // Make the original function private
+    original_fn_renamed.vis = syn::Visibility::Inherited;
+    // Stacked aspects rename an already-renamed function, e.g.
+    // `__aspect_original___aspect_original_f`
+    original_fn_renamed
+        .attrs
+        .push(syn::parse_quote!(#[allow(non_snake_case)]));
+
+    (original_fn_renamed, original_fn_name)
+}
+
+/// The signature of the wrapper of `func`: the parameters bound by a
+/// pattern other than a name, such as `(a, b): (u32, u32)` or `_: T`, are
+/// bound to `arg0`, `arg1`, ... after their position instead, so that the
+/// wrapper can capture them and pass them on to the original function,
+/// which destructures them.
+fn bind_params(func: &ItemFn) -> ItemFn {
+    let mut wrapper = func.clone();
+    for (position, arg) in wrapper.sig.inputs.iter_mut().enumerate() {
+        let syn::FnArg::Typed(pat_type) = arg else {
+            continue;
+        };
+        if matches!(&*pat_type.pat, syn::Pat::Ident(pat_ident) if pat_ident.subpat.is_none()) {
+            continue;
+        }
+        // Hygienic, so that it cannot clash with the names of the other
+        // parameters
+        let ident = quote::format_ident!("arg{}", position, span = proc_macro2::Span::mixed_site());
+        *pat_type.pat = syn::parse_quote!(#ident);
+    }
+    wrapper
+}
+
+/// The parameter patterns of the function, to call the original function
+/// with.
+fn param_names(func: &ItemFn) -> Vec<&syn::Pat> {
+    func.sig
+        .inputs
+        .iter()
+        .filter_map(|arg| {
+            if let syn::FnArg::Typed(pat_type) = arg {
+                Some(&*pat_type.pat)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Binds `__aspect` to the aspect to weave: the only one listed, or a
+/// [`Stack`](aspect_core::aspect::__private::Stack) of them all, applying
+/// them by precedence.
+pub fn bind_aspects(aspects: &[Expr]) -> TokenStream {
+    if let [aspect_expr] = aspects {
+        return quote! {
+            let __aspect = #aspect_expr;
+        };
+    }
+    let names = stacked_names(aspects);
+    quote! {
+        #(let #names = #aspects;)*
+        let __aspect = ::aspect_core::aspect::__private::Stack::new([
+            #(&#names as &dyn ::aspect_core::Aspect),*
+        ]);
+    }
+}
+
+/// The variables holding the aspects of `#[aspect(A, B, ...)]`.
+pub fn stacked_names(aspects: &[Expr]) -> Vec<syn::Ident> {
+    (0..aspects.len())
+        .map(|i| quote::format_ident!("__aspect_{}", i))
+        .collect()
+}
+
 /// Generates aspect weaving code for synchronous functions using around advice.
+///
+/// With `unwind`, a panic of a function not returning `Result` is caught:
+/// the aspects see it as an error, and it resumes unless one of them
+/// returns a value instead.
 fn generate_sync_around_call(
-    aspect_expr: &Expr,
+    bind_aspect: &TokenStream,
     original_fn_name: &syn::Ident,
-    fn_name: &syn::Ident,
-    param_names: &[&Box<syn::Pat>],
+    context: &TokenStream,
+    param_names: &[&syn::Pat],
     return_type: &TokenStream,
     is_result: bool,
+    unwind: bool,
 ) -> TokenStream {
-    let fn_name_str = fn_name.to_string();
-
     if is_result {
         // For Result types, unwrap and propagate errors properly
         quote! {
             use ::aspect_core::prelude::*;
-            use ::std::any::Any;
+            use ::core::any::Any;
 
-            let __aspect = #aspect_expr;
-            let __context = JoinPoint {
-                function_name: #fn_name_str,
-                module_path: module_path!(),
-                location: Location {
-                    file: file!(),
-                    line: line!(),
-                },
+            use ::aspect_core::aspect::__private::{
+                function_error, is_function_error, ErrorProbe, FromAspectError as _,
+                FromAspectErrorText as _, NoFromAspectError as _,
             };
 
-            // Create ProceedingJoinPoint that wraps the original function
-            let __pjp = ProceedingJoinPoint::new(
-                || {
+            #bind_aspect
+            let __context = #context;
+            let __function_name = __context.function_name;
+
+            // Create ProceedingJoinPoint that wraps the original function,
+            // borrowing it rather than boxing it. Its error is kept aside
+            // for the caller, the aspects get an AspectError standing for it
+            let mut __error_slot = ::core::option::Option::None;
+            let __result = {
+                let mut __original = ::core::option::Option::Some(::aspect_core::reentrancy::proceeding(|| {
                     match #original_fn_name(#(#param_names),*) {
-                        Ok(__val) => Ok(Box::new(__val) as Box<dyn Any>),
-                        Err(__err) => Err(AspectError::execution(format!("{:?}", __err))),
+                        Ok(__val) => Ok(::aspect_core::__private::Box::new(__val) as ::aspect_core::__private::Box<dyn Any>),
+                        Err(__err) => {
+                            let __aspect_err = function_error(&__err);
+                            __error_slot = ::core::option::Option::Some(__err);
+                            Err(__aspect_err)
+                        }
                     }
-                },
-                __context,
-            );
+                }));
+                let mut __proceed = || (__original.take().expect("proceeded more than once"))();
+                let __pjp = ProceedingJoinPoint::borrowed(&mut __proceed, __context);
 
-            // Call the aspect's around method
-            match __aspect.around(__pjp) {
-                Ok(__boxed_result) => {
-                    // Downcast the result back to the original Ok type
-                    let __inner = *__boxed_result
-                        .downcast::<_>()
-                        .expect("aspect around() returned wrong type");
-                    Ok(__inner)
-                }
+                // Call the aspect's around method
+                ::aspect_core::reentrancy::advice_as(__reentrancy_name, || __aspect.around(__pjp))
+            };
+
+            // Unbox the result back to the original Ok type
+            match __result.and_then(|__boxed_result| {
+                ::aspect_core::aspect::downcast_result(__function_name, __boxed_result)
+            }) {
+                Ok(__inner) => Ok(__inner),
                 Err(__err) => {
-                    // Convert AspectError back to the function's error type
-                    Err(format!("{:?}", __err).into())
+                    // The function's own error unless an aspect replaced it,
+                    // converted to the function's error type
+                    let __probe = ErrorProbe::of(&__error_slot);
+                    match __error_slot.take() {
+                        Some(__original_err) if is_function_error(&__err) => Err(__original_err),
+                        _ => Err((&&&__probe).error_from_aspect(__function_name, __err)),
+                    }
                 }
             }
         }
     } else {
         // For non-Result types
+        let call = quote!(#original_fn_name(#(#param_names),*));
+        let (slot, call, resume) = if unwind {
+            (
+                quote! {
+                    let mut __panic: ::core::option::Option<
+                        ::aspect_core::aspect::__private::PanicPayload,
+                    > = ::core::option::Option::None;
+                },
+                quote! {
+                    match ::aspect_core::aspect::__private::catch_panic(&mut __panic, || #call) {
+                        Ok(__result) => __result,
+                        Err(__err) => return Err(__err),
+                    }
+                },
+                quote! {
+                    if let ::core::option::Option::Some(__payload) = __panic.take() {
+                        ::std::panic::resume_unwind(__payload);
+                    }
+                },
+            )
+        } else {
+            (TokenStream::new(), call, TokenStream::new())
+        };
         quote! {
             use ::aspect_core::prelude::*;
-            use ::std::any::Any;
-
-            let __aspect = #aspect_expr;
-            let __context = JoinPoint {
-                function_name: #fn_name_str,
-                module_path: module_path!(),
-                location: Location {
-                    file: file!(),
-                    line: line!(),
-                },
+            use ::core::any::Any;
+
+            #bind_aspect
+            let __context = #context;
+            let __function_name = __context.function_name;
+
+            // Create ProceedingJoinPoint that wraps the original function,
+            // borrowing it rather than boxing it
+            #slot
+            let __result = {
+                let mut __original = ::core::option::Option::Some(::aspect_core::reentrancy::proceeding(|| {
+                    let __result = #call;
+                    Ok(::aspect_core::__private::Box::new(__result) as ::aspect_core::__private::Box<dyn Any>)
+                }));
+                let mut __proceed = || (__original.take().expect("proceeded more than once"))();
+                let __pjp = ProceedingJoinPoint::borrowed(&mut __proceed, __context);
+
+                // Call the aspect's around method
+                ::aspect_core::reentrancy::advice_as(__reentrancy_name, || __aspect.around(__pjp))
             };
 
-            // Create ProceedingJoinPoint that wraps the original function
-            let __pjp = ProceedingJoinPoint::new(
-                || {
-                    let __result = #original_fn_name(#(#param_names),*);
-                    Ok(Box::new(__result) as Box<dyn Any>)
-                },
-                __context,
-            );
-
-            // Call the aspect's around method
-            match __aspect.around(__pjp) {
-                Ok(__boxed_result) => {
-                    // Downcast the result back to the original type
-                    *__boxed_result
-                        .downcast::<#return_type>()
-                        .expect("aspect around() returned wrong type")
-                }
+            // Unbox the result back to the original type
+            match __result.and_then(|__boxed_result| {
+                ::aspect_core::aspect::downcast_result::<#return_type>(__function_name, __boxed_result)
+            }) {
+                Ok(__result) => __result,
                 Err(__err) => {
+                    #resume
                     panic!("aspect around() failed: {:?}", __err);
                 }
             }
@@ -180,76 +538,334 @@ fn generate_sync_around_call(
 
 /// Generates aspect weaving code for asynchronous functions using around advice.
 fn generate_async_around_call(
-    aspect_expr: &Expr,
+    bind_aspect: &TokenStream,
     original_fn_name: &syn::Ident,
-    fn_name: &syn::Ident,
-    param_names: &[&Box<syn::Pat>],
+    context: &TokenStream,
+    param_names: &[&syn::Pat],
     _return_type: &TokenStream,
     is_result: bool,
+    unwind: bool,
 ) -> TokenStream {
-    let fn_name_str = fn_name.to_string();
-
     // For async functions, for now we'll use a simpler approach
-    // True async around advice requires async traits (not stable)
+    // True async around advice requires async traits (not stable); aspects
+    // implementing `AsyncAspect` can still await in `before_async`
     if is_result {
         quote! {
             use ::aspect_core::prelude::*;
-            use ::std::any::Any;
-
-            let __aspect = #aspect_expr;
-            let __context = JoinPoint {
-                function_name: #fn_name_str,
-                module_path: module_path!(),
-                location: Location {
-                    file: file!(),
-                    line: line!(),
-                },
-            };
+            use ::core::any::Any;
+
+            #bind_aspect
+            let __context = #context;
+
+            // Not polled before the advice ran
+            let __future = #original_fn_name(#(#param_names),*);
+
+            ::aspect_core::reentrancy::advice_as(__reentrancy_name, || __aspect.before(&__context));
+            {
+                use ::aspect_core::aspect::__private::{
+                    AsyncBefore as _, AsyncProbe, ErrorProbe, FromAspectError as _,
+                    FromAspectErrorText as _, NoAsyncBefore as _, NoFromAspectError as _,
+                };
+                let __probe = AsyncProbe(&__aspect);
+                if let Err(__err) = (&&__probe).before_async(&__context).await {
+                    let __error_probe = ErrorProbe::of_future(&__future);
+                    return Err((&&&__error_probe).error_from_aspect(__context.function_name, __err));
+                }
+            }
 
-            __aspect.before(&__context);
+            let mut __result = __future.await;
 
-            let __result = #original_fn_name(#(#param_names),*).await;
-
-            match &__result {
+            ::aspect_core::reentrancy::advice_as(__reentrancy_name, || match &mut __result {
                 Ok(__val) => {
-                    __aspect.after(&__context, __val as &dyn Any);
+                    __aspect.after_returning_mut(&__context, &mut ::aspect_core::ReturnValue::new(__val));
+                    __aspect.after(&__context, &*__val as &dyn Any);
                 }
                 Err(__err) => {
-                    let __aspect_err = AspectError::execution(format!("{:?}", __err));
+                    let __aspect_err = ::aspect_core::aspect::__private::function_error(&*__err);
                     __aspect.after_error(&__context, &__aspect_err);
                 }
-            }
+            });
 
             __result
         }
     } else {
+        let future = quote!(#original_fn_name(#(#param_names),*));
+        let result = if unwind {
+            let after_error = quote! {
+                ::aspect_core::reentrancy::advice_as(__reentrancy_name, || __aspect.after_error(&__context, &__err));
+            };
+            caught_await(&future, &after_error)
+        } else {
+            quote!(#future.await)
+        };
         quote! {
             use ::aspect_core::prelude::*;
-            use ::std::any::Any;
+            use ::core::any::Any;
 
-            let __aspect = #aspect_expr;
-            let __context = JoinPoint {
-                function_name: #fn_name_str,
-                module_path: module_path!(),
-                location: Location {
-                    file: file!(),
-                    line: line!(),
-                },
-            };
+            #bind_aspect
+            let __context = #context;
 
-            __aspect.before(&__context);
+            ::aspect_core::reentrancy::advice_as(__reentrancy_name, || __aspect.before(&__context));
+            {
+                use ::aspect_core::aspect::__private::{AsyncBefore as _, AsyncProbe, NoAsyncBefore as _};
+                let __probe = AsyncProbe(&__aspect);
+                if let Err(__err) = (&&__probe).before_async(&__context).await {
+                    panic!("aspect before_async() failed: {:?}", __err);
+                }
+            }
 
-            let __result = #original_fn_name(#(#param_names),*).await;
+            let mut __result = #result;
 
-            __aspect.after(&__context, &__result as &dyn Any);
+            ::aspect_core::reentrancy::advice_as(__reentrancy_name, || {
+                __aspect.after_returning_mut(&__context, &mut ::aspect_core::ReturnValue::new(&mut __result));
+                __aspect.after(&__context, &__result as &dyn Any);
+            });
 
             __result
         }
     }
 }
 
+/// Generates the woven code of an async function with several aspects.
+///
+/// Like for a single aspect, each aspect's `before` and `before_async` run
+/// before the function and its `after` or `after_error` after it, the
+/// aspects of higher precedence first before and last after. When a
+/// `before_async` fails, the function does not run and the aspects already
+/// entered see the error in `after_error`. With `unwind`, so does a panic
+/// of the function, which then resumes.
+fn generate_async_stack_call(
+    aspects: &[Expr],
+    original_fn_name: &syn::Ident,
+    context: &TokenStream,
+    param_names: &[&syn::Pat],
+    is_result: bool,
+    unwind: bool,
+) -> TokenStream {
+    let names = stacked_names(aspects);
+    let count = proc_macro2::Literal::usize_unsuffixed(aspects.len());
+    let indexes = (0..aspects.len()).map(proc_macro2::Literal::usize_unsuffixed);
+
+    // The concrete type of each aspect is needed to tell whether it
+    // implements `AsyncAspect`
+    let before_async = quote! {
+        match __index {
+            #(#indexes => (&&AsyncProbe(&#names)).before_async(&__context).await,)*
+            _ => unreachable!(),
+        }
+    };
+    let result = if unwind {
+        let after_error = quote! {
+            for &__index in __order.iter().rev() {
+                ::aspect_core::reentrancy::advice(__aspects[__index], || {
+                    __aspects[__index].after_error(&__context, &__err)
+                });
+            }
+        };
+        caught_await(&quote!(__future), &after_error)
+    } else {
+        quote!(__future.await)
+    };
+    let (fail, after) = if is_result {
+        (
+            quote! {
+                let __error_probe = ErrorProbe::of_future(&__future);
+                return Err((&&&__error_probe).error_from_aspect(__context.function_name, __err));
+            },
+            quote! {
+                match &mut __result {
+                    Ok(__val) => {
+                        __aspects[__index].after_returning_mut(&__context, &mut ::aspect_core::ReturnValue::new(__val));
+                        __aspects[__index].after(&__context, &*__val as &dyn Any);
+                    }
+                    Err(__err) => {
+                        let __aspect_err = ::aspect_core::aspect::__private::function_error(&*__err);
+                        __aspects[__index].after_error(&__context, &__aspect_err);
+                    }
+                }
+            },
+        )
+    } else {
+        (
+            quote! {
+                panic!("aspect before_async() failed: {:?}", __err);
+            },
+            quote! {
+                __aspects[__index].after_returning_mut(&__context, &mut ::aspect_core::ReturnValue::new(&mut __result));
+                __aspects[__index].after(&__context, &__result as &dyn Any);
+            },
+        )
+    };
+
+    quote! {
+        use ::aspect_core::prelude::*;
+        use ::core::any::Any;
+
+        #[allow(unused_imports)]
+        use ::aspect_core::aspect::__private::{
+            woven, AsyncBefore as _, AsyncProbe, ErrorProbe, FromAspectError as _,
+            FromAspectErrorText as _, NoAsyncBefore as _, NoFromAspectError as _,
+        };
+
+        #(let #names = #aspects;)*
+        let __aspects: [&dyn Aspect; #count] = [#(&#names),*];
+        let (__order, __woven) = woven(&__aspects);
+        let __order = &__order[..__woven];
+        let __context = #context;
+
+        // Not polled before the advice ran
+        let __future = #original_fn_name(#(#param_names),*);
+
+        for (__entered, &__index) in __order.iter().enumerate() {
+            ::aspect_core::reentrancy::advice(__aspects[__index], || __aspects[__index].before(&__context));
+            if let Err(__err) = #before_async {
+                for &__outer in __order[..__entered].iter().rev() {
+                    ::aspect_core::reentrancy::advice(__aspects[__outer], || {
+                        __aspects[__outer].after_error(&__context, &__err)
+                    });
+                }
+                #fail
+            }
+        }
+
+        let mut __result = #result;
+
+        for &__index in __order.iter().rev() {
+            ::aspect_core::reentrancy::advice(__aspects[__index], || { #after });
+        }
+
+        __result
+    }
+}
+
+/// Awaits `future`, the async function of `#[aspect(unwind ...)]`: when it
+/// panics, `after_error` runs with the error of the panic as `__err`, and
+/// the panic resumes.
+fn caught_await(future: &TokenStream, after_error: &TokenStream) -> TokenStream {
+    quote! {
+        match ::aspect_core::aspect::__private::catch_unwind(#future).await {
+            Ok(__result) => __result,
+            Err(__payload) => {
+                let __err = ::aspect_core::aspect::__private::panic_error(&*__payload);
+                #after_error
+                ::std::panic::resume_unwind(__payload)
+            }
+        }
+    }
+}
+
+/// Generates one `Arg` expression per captured parameter.
+///
+/// Only parameters bound to a plain identifier are captured. Values are only
+/// cloned for types without lifetimes or generic parameters: autoref-based
+/// specialization cannot tell `T: 'static` apart, so those types only get
+/// the hash probe.
+pub fn generate_arg_captures(func: &ItemFn) -> Vec<TokenStream> {
+    let generic_params: Vec<&syn::Ident> = func
+        .sig
+        .generics
+        .type_params()
+        .map(|param| &param.ident)
+        .collect();
+
+    func.sig
+        .inputs
+        .iter()
+        .filter_map(|arg| {
+            let syn::FnArg::Typed(pat_type) = arg else {
+                return None;
+            };
+            let syn::Pat::Ident(pat_ident) = &*pat_type.pat else {
+                return None;
+            };
+            let ident = &pat_ident.ident;
+            let name = ident.to_string();
+
+            let (probe, capture_value) = match &*pat_type.ty {
+                syn::Type::Reference(reference) => (
+                    quote! { ::aspect_core::args::__private::Probe(&*#ident) },
+                    is_capturable(&reference.elem, &generic_params),
+                ),
+                ty => (
+                    quote! { ::aspect_core::args::__private::Probe(&#ident) },
+                    is_capturable(ty, &generic_params),
+                ),
+            };
+
+            let value = if capture_value {
+                quote! { (&&&__probe).capture_value() }
+            } else {
+                quote! { (::core::option::Option::None, ::core::option::Option::None) }
+            };
+
+            Some(quote! {
+                {
+                    #[allow(unused_imports)]
+                    use ::aspect_core::args::__private::{
+                        DebugValueCapture as _, HashCapture as _, NoHashCapture as _,
+                        NoRedactCapture as _, NoValueCapture as _, RedactCapture as _,
+                        ValueCapture as _,
+                    };
+                    let __probe = #probe;
+                    ::aspect_core::args::__private::arg(
+                        #name,
+                        __probe.type_name(),
+                        (&&__probe).capture_hash(),
+                        #value,
+                        (&&__probe).capture_redacted(),
+                    )
+                }
+            })
+        })
+        .collect()
+}
+
+/// Checks whether a type can have its value captured, i.e. it mentions no
+/// lifetimes, references, generic parameters, `Self` or opaque types.
+fn is_capturable(ty: &syn::Type, generic_params: &[&syn::Ident]) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => {
+            if type_path.qself.is_some() {
+                return false;
+            }
+            if let Some(first) = type_path.path.segments.first() {
+                if first.ident == "Self" || generic_params.contains(&&first.ident) {
+                    return false;
+                }
+            }
+            type_path
+                .path
+                .segments
+                .iter()
+                .all(|segment| match &segment.arguments {
+                    syn::PathArguments::None => true,
+                    syn::PathArguments::AngleBracketed(args) => {
+                        args.args.iter().all(|arg| match arg {
+                            syn::GenericArgument::Type(ty) => is_capturable(ty, generic_params),
+                            syn::GenericArgument::AssocType(assoc) => {
+                                is_capturable(&assoc.ty, generic_params)
+                            }
+                            syn::GenericArgument::Const(_) => true,
+                            _ => false,
+                        })
+                    }
+                    syn::PathArguments::Parenthesized(_) => false,
+                })
+        }
+        syn::Type::Tuple(tuple) => tuple
+            .elems
+            .iter()
+            .all(|ty| is_capturable(ty, generic_params)),
+        syn::Type::Array(array) => is_capturable(&array.elem, generic_params),
+        syn::Type::Slice(slice) => is_capturable(&slice.elem, generic_params),
+        syn::Type::Paren(paren) => is_capturable(&paren.elem, generic_params),
+        syn::Type::Group(group) => is_capturable(&group.elem, generic_params),
+        _ => false,
+    }
+}
+
 /// Checks if a type is a Result type.
-fn is_result_type(ty: &syn::Type) -> bool {
+pub fn is_result_type(ty: &syn::Type) -> bool {
     if let syn::Type::Path(type_path) = ty {
         if let Some(segment) = type_path.path.segments.last() {
             return segment.ident == "Result";
@@ -263,6 +879,77 @@ mod tests {
     use super::*;
     use syn::parse_quote;
 
+    #[test]
+    fn test_generate_static_wrapper() {
+        let info: AspectInfo = parse_quote!(static CallCounter);
+        let func: ItemFn = parse_quote! {
+            pub fn double(x: u32) -> u32 {
+                x * 2
+            }
+        };
+        let woven = generate_static_wrapper(&info, &func).to_string();
+        assert!(woven.contains("fn __aspect_original_double"));
+        assert!(woven.contains(
+            &quote! {
+                let __original = ::aspect_core::reentrancy::proceeding(|| __aspect_original_double(x));
+                ::aspect_core::reentrancy::advice_as(__reentrancy_name, || {
+                    ::aspect_core::StaticAspect::around(&__aspect, &*__context, __original)
+                })
+            }
+            .to_string()
+        ));
+        assert!(!woven.contains("Box"));
+        assert!(!woven.contains("Probe"));
+
+        let func: ItemFn = parse_quote! {
+            async fn fetch(id: u64) -> String {
+                id.to_string()
+            }
+        };
+        let woven = generate_static_wrapper(&info, &func).to_string();
+        assert!(woven.contains("__aspect_original_fetch (id) . await"));
+        assert!(!woven.contains("around"));
+    }
+
+    #[test]
+    fn test_pattern_parameters_are_bound() {
+        let info: AspectInfo = parse_quote!(Logger);
+        let func: ItemFn = parse_quote! {
+            fn area((width, height): (u32, u32), _: bool, scale: u32) -> u32 {
+                width * height * scale
+            }
+        };
+        let woven = generate_aspect_wrapper(&info, &func).to_string();
+
+        // The original destructures what the wrapper captures by name
+        assert!(
+            woven.contains("fn __aspect_original_area ((width , height) : (u32 , u32) , _ : bool")
+        );
+        assert!(woven.contains("fn area (arg0 : (u32 , u32) , arg1 : bool , scale : u32)"));
+        assert!(woven.contains("__aspect_original_area (arg0 , arg1 , scale)"));
+        assert!(woven.contains("\"arg0\""));
+    }
+
+    #[test]
+    fn test_literal_kind() {
+        let cases: [(Expr, TokenStream); 8] = [
+            (parse_quote!(10), quote!(Int(10i128))),
+            (parse_quote!(-1), quote!(Int(-1i128))),
+            (parse_quote!(0.5), quote!(Float)),
+            (parse_quote!(true), quote!(Bool)),
+            (parse_quote!("250ms"), quote!(Str { duration: true })),
+            (parse_quote!("sliding"), quote!(Str { duration: false })),
+            (parse_quote!(-"1s"), quote!(Expr)),
+            (parse_quote!(MAX_CALLS), quote!(Expr)),
+        ];
+        for (value, kind) in cases {
+            assert_eq!(
+                literal_kind(&value).to_string(),
+                quote!(::aspect_core::config::__private::Literal::#kind).to_string()
+            );
+        }
+    }
+
     #[test]
     fn test_is_result_type() {
         let result_type: syn::Type = parse_quote!(Result<i32, String>);
@@ -271,4 +958,34 @@ mod tests {
         let non_result_type: syn::Type = parse_quote!(i32);
         assert!(!is_result_type(&non_result_type));
     }
+
+    #[test]
+    fn test_is_capturable() {
+        let t: syn::Ident = parse_quote!(T);
+        let generics = [&t];
+
+        let capturable: [syn::Type; 5] = [
+            parse_quote!(u64),
+            parse_quote!(String),
+            parse_quote!(Vec<(u8, std::string::String)>),
+            parse_quote!([u8; 4]),
+            parse_quote!(HashMap<String, Vec<i32>>),
+        ];
+        for ty in &capturable {
+            assert!(is_capturable(ty, &generics), "{}", quote!(#ty));
+        }
+
+        let not_capturable: [syn::Type; 7] = [
+            parse_quote!(&str),
+            parse_quote!(Cow<'a, str>),
+            parse_quote!(T),
+            parse_quote!(Vec<T>),
+            parse_quote!(impl Display),
+            parse_quote!(Box<dyn Fn()>),
+            parse_quote!(Self),
+        ];
+        for ty in &not_capturable {
+            assert!(!is_capturable(ty, &generics), "{}", quote!(#ty));
+        }
+    }
 }
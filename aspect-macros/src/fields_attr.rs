@@ -0,0 +1,498 @@
+//! Implementation of the #[aspect_fields] attribute macro.
+//!
+//! The macro generates accessors for the fields of a struct marked with
+//! `#[aspect_field]`: a getter named after the field and a `set_` setter,
+//! which read and write it through the aspects. Their joinpoints are named
+//! after the access, `get(Struct.field)` and `set(Struct.field)`, as the
+//! field patterns of the driver are, so that aspects tell them apart with
+//! `JoinPoint::kind`.
+
+use proc_macro2::{Literal, TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{
+    Attribute, Error, Expr, Fields, Generics, Ident, ItemFn, ItemStruct, Meta, Result, Token, Type,
+    TypeParamBound, WherePredicate,
+};
+
+use crate::codegen::{bind_aspects, generate_arg_captures, stacked_names};
+
+/// The attribute marking the fields to generate accessors for.
+const FIELD_ATTR: &str = "aspect_field";
+
+/// The aspects of `#[aspect_fields(A, B, ...)]`.
+pub struct FieldAspects {
+    /// The expressions that evaluate to the aspect instances, evaluated
+    /// for each access
+    pub aspects: Vec<Expr>,
+}
+
+impl Parse for FieldAspects {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let aspects = Punctuated::<Expr, Token![,]>::parse_terminated(input)?;
+        if aspects.is_empty() {
+            return Err(input.error("expected the aspects to weave into the accessors"));
+        }
+        Ok(Self {
+            aspects: aspects.into_iter().collect(),
+        })
+    }
+}
+
+/// The accessors `#[aspect_field]` asks for.
+#[derive(Debug, PartialEq)]
+struct Accessors {
+    get: bool,
+    set: bool,
+}
+
+/// Transforms the struct, removing the `#[aspect_field]` attributes of its
+/// fields and generating their accessors, woven unless `weave` is false.
+pub fn transform(args: FieldAspects, mut item: ItemStruct, weave: bool) -> Result<TokenStream> {
+    let struct_name = item.ident.clone();
+    let Fields::Named(fields) = &mut item.fields else {
+        return Err(Error::new_spanned(
+            &item,
+            "#[aspect_fields] applies to structs with named fields",
+        ));
+    };
+
+    let mut accessors = Vec::new();
+    for field in &mut fields.named {
+        let Some(wanted) = take_accessors(&mut field.attrs)? else {
+            continue;
+        };
+        let field = Field {
+            struct_name: &struct_name,
+            name: field.ident.as_ref().expect("named field"),
+            ty: &field.ty,
+        };
+        if wanted.get {
+            check_static(&field, &item.generics)?;
+            accessors.push(field.getter(&item.vis, &args.aspects, weave));
+        }
+        if wanted.set {
+            accessors.push(field.setter(&item.vis, &item.generics, &args.aspects, weave));
+        }
+    }
+    if accessors.is_empty() {
+        return Err(Error::new_spanned(
+            &struct_name,
+            "no field is marked with #[aspect_field]",
+        ));
+    }
+
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    Ok(quote! {
+        #item
+
+        impl #impl_generics #struct_name #ty_generics #where_clause {
+            #(#accessors)*
+        }
+    })
+}
+
+/// Removes the `#[aspect_field]` attribute from `attrs`, returning the
+/// accessors it asks for: both for `#[aspect_field]`, or those listed in
+/// `#[aspect_field(get, set)]`.
+fn take_accessors(attrs: &mut Vec<Attribute>) -> Result<Option<Accessors>> {
+    let Some(index) = attrs
+        .iter()
+        .position(|attr| attr.path().is_ident(FIELD_ATTR))
+    else {
+        return Ok(None);
+    };
+    let attr = attrs.remove(index);
+    if let Some(other) = attrs.iter().find(|attr| attr.path().is_ident(FIELD_ATTR)) {
+        return Err(Error::new_spanned(other, "duplicate #[aspect_field]"));
+    }
+
+    let mut accessors = Accessors {
+        get: false,
+        set: false,
+    };
+    match &attr.meta {
+        Meta::Path(_) => {
+            accessors.get = true;
+            accessors.set = true;
+        }
+        Meta::List(_) => attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("get") {
+                accessors.get = true;
+            } else if meta.path.is_ident("set") {
+                accessors.set = true;
+            } else {
+                return Err(meta.error("expected `get` or `set`"));
+            }
+            Ok(())
+        })?,
+        Meta::NameValue(_) => {}
+    }
+    if !accessors.get && !accessors.set {
+        return Err(Error::new_spanned(
+            attr,
+            "expected #[aspect_field], #[aspect_field(get)] or #[aspect_field(set)]",
+        ));
+    }
+    Ok(Some(accessors))
+}
+
+/// Checks that the getter of `field` can hand its value to `after`, which
+/// takes it as `&dyn Any`: the type of the field must be `'static`, so it
+/// may only name the `'static` lifetime and type parameters bounded by
+/// `'static`.
+fn check_static(field: &Field, generics: &Generics) -> Result<()> {
+    let static_params: Vec<&Ident> = generics
+        .type_params()
+        .filter(|param| outlives_static(&param.bounds))
+        .map(|param| &param.ident)
+        .chain(
+            generics
+                .where_clause
+                .iter()
+                .flat_map(|clause| &clause.predicates)
+                .filter_map(|predicate| match predicate {
+                    WherePredicate::Type(predicate) if outlives_static(&predicate.bounds) => {
+                        match &predicate.bounded_ty {
+                            Type::Path(path) => path.path.get_ident(),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                }),
+        )
+        .collect();
+    let borrowed_params: Vec<&Ident> = generics
+        .type_params()
+        .map(|param| &param.ident)
+        .filter(|ident| !static_params.contains(ident))
+        .collect();
+
+    match find_borrowed(field.ty.to_token_stream(), &borrowed_params) {
+        None => Ok(()),
+        Some(found) => Err(Error::new_spanned(
+            field.ty,
+            format!(
+                "the getter of `{}` passes the field to the aspects as `&dyn Any`, so its type must be `'static`, but `{}` is not; \
+                 add a `'static` bound or use #[aspect_field(set)]",
+                field.name, found
+            ),
+        )),
+    }
+}
+
+/// Returns `true` if `bounds` include `'static`.
+fn outlives_static(bounds: &Punctuated<TypeParamBound, Token![+]>) -> bool {
+    bounds.iter().any(
+        |bound| matches!(bound, TypeParamBound::Lifetime(lifetime) if lifetime.ident == "static"),
+    )
+}
+
+/// Returns the first lifetime other than `'static`, or type parameter of
+/// `params`, named in `tokens`.
+fn find_borrowed(tokens: TokenStream, params: &[&Ident]) -> Option<String> {
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == '\'' => {
+                if let Some(TokenTree::Ident(lifetime)) = tokens.next() {
+                    if lifetime != "static" {
+                        return Some(format!("'{}", lifetime));
+                    }
+                }
+            }
+            TokenTree::Ident(ident) if params.contains(&&ident) => {
+                return Some(ident.to_string());
+            }
+            TokenTree::Group(group) => {
+                if let Some(found) = find_borrowed(group.stream(), params) {
+                    return Some(found);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// A field to generate accessors for.
+struct Field<'a> {
+    struct_name: &'a Ident,
+    name: &'a Ident,
+    ty: &'a syn::Type,
+}
+
+impl Field<'_> {
+    /// The joinpoint of an access, named `name`, with the arguments
+    /// `args`.
+    fn context(&self, name: String, args: &[TokenStream]) -> TokenStream {
+        quote! {
+            ::aspect_core::JoinPoint::new(
+                #name,
+                module_path!(),
+                ::aspect_core::Location {
+                    file: file!(),
+                    line: line!(),
+                },
+            )
+            .with_args(::aspect_core::__private::vec![#(#args),*])
+        }
+    }
+
+    /// The getter, returning a reference to the field. As for async
+    /// functions, the aspects get `before` and `after` advice, `after`
+    /// seeing the value of the field.
+    fn getter(&self, vis: &syn::Visibility, aspects: &[Expr], weave: bool) -> TokenStream {
+        let Field { name, ty, .. } = self;
+        let doc = format!("Reads `{}` through the aspects of the struct.", name);
+        if !weave {
+            return quote! {
+                #[doc = #doc]
+                #vis fn #name(&self) -> &#ty {
+                    &self.#name
+                }
+            };
+        }
+
+        let context = self.context(format!("get({}.{})", self.struct_name, name), &[]);
+        let names = stacked_names(aspects);
+        let count = Literal::usize_unsuffixed(aspects.len());
+        quote! {
+            #[doc = #doc]
+            #vis fn #name(&self) -> &#ty {
+                #(let #names = #aspects;)*
+                let __aspects: [&dyn ::aspect_core::Aspect; #count] = [#(&#names),*];
+                let (__order, __woven) = ::aspect_core::aspect::__private::woven(&__aspects);
+                let __order = &__order[..__woven];
+                let __context = #context;
+
+                for &__index in __order.iter() {
+                    ::aspect_core::reentrancy::advice(__aspects[__index], || {
+                        __aspects[__index].before(&__context)
+                    });
+                }
+                let __value = &self.#name;
+                for &__index in __order.iter().rev() {
+                    ::aspect_core::reentrancy::advice(__aspects[__index], || {
+                        __aspects[__index].after(&__context, __value as &dyn ::core::any::Any)
+                    });
+                }
+                __value
+            }
+        }
+    }
+
+    /// The setter, `set_` followed by the name of the field. The write is
+    /// woven like the execution of a function: the aspects get `around`
+    /// advice, and see the new value as the argument named after the
+    /// field. An aspect refusing the write makes the setter return its
+    /// error, leaving the field as it was.
+    fn setter(
+        &self,
+        vis: &syn::Visibility,
+        generics: &syn::Generics,
+        aspects: &[Expr],
+        weave: bool,
+    ) -> TokenStream {
+        let Field { name, ty, .. } = self;
+        let setter = format_ident!("set_{}", name);
+        let doc = format!(
+            "Writes `{}` through the aspects of the struct, returning the error of an aspect refusing it.",
+            name
+        );
+        if !weave {
+            return quote! {
+                #[doc = #doc]
+                #vis fn #setter(&mut self, #name: #ty) -> ::core::result::Result<(), ::aspect_core::AspectError> {
+                    self.#name = #name;
+                    ::core::result::Result::Ok(())
+                }
+            };
+        }
+
+        // The argument is captured as that of a function of the struct's
+        // generic parameters would be
+        let signature: ItemFn = syn::parse_quote!(fn #setter #generics(#name: #ty) {});
+        let context = self.context(
+            format!("set({}.{})", self.struct_name, name),
+            &generate_arg_captures(&signature),
+        );
+        let bind_aspect = bind_aspects(aspects);
+        quote! {
+            #[doc = #doc]
+            #vis fn #setter(&mut self, #name: #ty) -> ::core::result::Result<(), ::aspect_core::AspectError> {
+                #bind_aspect
+                let __reentrancy_name = ::aspect_core::Aspect::reentrancy_name(&__aspect);
+                let __precedence = ::aspect_core::Aspect::precedence(&__aspect);
+                if ::aspect_core::reentrancy::skips_named(__reentrancy_name, __precedence) {
+                    self.#name = #name;
+                    return ::core::result::Result::Ok(());
+                }
+                let __context = #context;
+                let __function_name = __context.function_name;
+
+                let mut __original = ::core::option::Option::Some(|| {
+                    self.#name = #name;
+                    Ok(::aspect_core::__private::Box::new(()) as ::aspect_core::__private::Box<dyn ::core::any::Any>)
+                });
+                let mut __proceed = || (__original.take().expect("proceeded more than once"))();
+                let __pjp = ::aspect_core::ProceedingJoinPoint::borrowed(&mut __proceed, __context);
+
+                let __result = ::aspect_core::reentrancy::advice_as(__reentrancy_name, || ::aspect_core::Aspect::around(&__aspect, __pjp));
+                __result.and_then(|__boxed_result| {
+                    ::aspect_core::aspect::downcast_result::<()>(__function_name, __boxed_result)
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_take_accessors() {
+        let mut attrs: Vec<Attribute> =
+            vec![parse_quote!(#[aspect_field]), parse_quote!(#[doc = "x"])];
+        assert_eq!(
+            take_accessors(&mut attrs).unwrap(),
+            Some(Accessors {
+                get: true,
+                set: true
+            })
+        );
+        assert_eq!(attrs.len(), 1);
+        assert_eq!(take_accessors(&mut attrs).unwrap(), None);
+
+        let mut attrs: Vec<Attribute> = vec![parse_quote!(#[aspect_field(set)])];
+        assert_eq!(
+            take_accessors(&mut attrs).unwrap(),
+            Some(Accessors {
+                get: false,
+                set: true
+            })
+        );
+
+        for attr in [
+            quote!(#[aspect_field(read)]),
+            quote!(#[aspect_field = "get"]),
+            quote!(#[aspect_field()]),
+        ] {
+            let mut attrs = vec![parse_quote!(#attr)];
+            assert!(take_accessors(&mut attrs).is_err(), "{}", attr);
+        }
+        let mut attrs = vec![
+            parse_quote!(#[aspect_field]),
+            parse_quote!(#[aspect_field(get)]),
+        ];
+        assert!(take_accessors(&mut attrs).is_err());
+    }
+
+    #[test]
+    fn test_transform() {
+        let args: FieldAspects = parse_quote!(AuditAspect::default());
+        let item: ItemStruct = parse_quote! {
+            pub struct Account {
+                #[aspect_field]
+                balance: u64,
+                #[aspect_field(get)]
+                owner: String,
+                id: u64,
+            }
+        };
+        let woven = transform(args, item, true).unwrap().to_string();
+        assert!(!woven.contains("# [aspect_field"));
+        assert!(woven.contains("pub fn balance (& self) -> & u64"));
+        assert!(woven.contains(
+            "pub fn set_balance (& mut self , balance : u64) -> :: core :: result :: Result < () , :: aspect_core :: AspectError >"
+        ));
+        assert!(woven.contains("JoinPoint :: new (\"get(Account.balance)\""));
+        assert!(woven.contains("JoinPoint :: new (\"set(Account.balance)\""));
+        assert!(woven.contains("pub fn owner (& self) -> & String"));
+        assert!(!woven.contains("set_owner"));
+        assert!(!woven.contains("fn id"));
+
+        // Plain accessors when aspects are compiled out
+        let args: FieldAspects = parse_quote!(AuditAspect::default());
+        let item: ItemStruct = parse_quote! {
+            struct Account {
+                #[aspect_field(set)]
+                balance: u64,
+            }
+        };
+        let plain = transform(args, item, false).unwrap().to_string();
+
+        // Type parameters bounded by 'static have getters too
+        for item in [
+            quote!(
+                struct Slot<T: Clone + 'static> {
+                    #[aspect_field]
+                    value: Vec<T>,
+                }
+            ),
+            quote!(
+                struct Slot<T>
+                where
+                    T: 'static,
+                {
+                    #[aspect_field(get)]
+                    value: &'static T,
+                }
+            ),
+        ] {
+            let args: FieldAspects = parse_quote!(AuditAspect::default());
+            assert!(transform(args, syn::parse2(item).unwrap(), true).is_ok());
+        }
+        assert!(plain.contains(
+            "fn set_balance (& mut self , balance : u64) -> :: core :: result :: Result < () , :: aspect_core :: AspectError > { self . balance = balance ; :: core :: result :: Result :: Ok (()) }"
+        ));
+        assert!(!plain.contains("JoinPoint"));
+
+        let errors = [
+            (
+                quote!(
+                    struct Pair(#[aspect_field] u64, u64);
+                ),
+                "#[aspect_fields] applies to structs with named fields",
+            ),
+            (
+                quote!(
+                    struct Account {
+                        balance: u64,
+                    }
+                ),
+                "no field is marked with #[aspect_field]",
+            ),
+            (
+                quote!(
+                    struct Name<'a> {
+                        #[aspect_field]
+                        name: &'a str,
+                    }
+                ),
+                "the getter of `name` passes the field to the aspects as `&dyn Any`, so its type must be `'static`, but `'a` is not; \
+                 add a `'static` bound or use #[aspect_field(set)]",
+            ),
+            (
+                quote!(
+                    struct Slot<T: Clone> {
+                        #[aspect_field(get)]
+                        value: Option<T>,
+                    }
+                ),
+                "the getter of `value` passes the field to the aspects as `&dyn Any`, so its type must be `'static`, but `T` is not; \
+                 add a `'static` bound or use #[aspect_field(set)]",
+            ),
+        ];
+        for (item, message) in errors {
+            let args: FieldAspects = parse_quote!(AuditAspect::default());
+            let error = transform(args, syn::parse2(item).unwrap(), true)
+                .err()
+                .unwrap();
+            assert_eq!(error.to_string(), message);
+        }
+    }
+}
@@ -6,12 +6,46 @@
 //! at compile time.
 
 use proc_macro::TokenStream;
-use syn::{parse_macro_input, Expr, ItemFn};
+use syn::{parse_macro_input, ItemFn, ItemStruct};
 
 mod advice_macro;
 mod aspect_attr;
 mod codegen;
+mod fields_attr;
 mod parsing;
+mod provider_macro;
+
+/// Environment variable compiling aspects out when set (to anything but
+/// `0`) as the macros expand: `#[aspect]` leaves functions unchanged,
+/// `#[aspect_fields]` generates plain accessors, and `#[advice]` does not
+/// register its aspect. `cargo aspect bench --compare` sets it for the
+/// build it compares against.
+///
+/// It is not `ASPECT_DISABLE`, the kill switch of `aspect_core::switch`,
+/// which turns woven aspects off at run time.
+const COMPILE_OUT_ENV: &str = "ASPECT_COMPILE_OUT";
+
+fn aspects_disabled() -> bool {
+    std::env::var_os(COMPILE_OUT_ENV).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Reads [`COMPILE_OUT_ENV`] from the expanded code too: Cargo does not
+/// know of the environment proc macros read, but rebuilds crates when a
+/// variable their `option_env!` reads changes, so that setting or
+/// unsetting it does not reuse stale artifacts.
+fn track_compile_out() -> proc_macro2::TokenStream {
+    quote::quote! {
+        let _ = ::core::option_env!("ASPECT_COMPILE_OUT");
+    }
+}
+
+/// Adds [`track_compile_out`] to the body of `func`.
+fn tracking_compile_out(mut func: ItemFn) -> ItemFn {
+    func.block
+        .stmts
+        .insert(0, syn::parse2(track_compile_out()).expect("statement"));
+    func
+}
 
 /// Applies an aspect to a function.
 ///
@@ -35,18 +69,166 @@ mod parsing;
 ///     x * 2
 /// }
 /// ```
+///
+/// With `static` before it, the aspect is a `StaticAspect`, whose advice is
+/// called directly on the concrete types of the function, as cheaply as
+/// hand-written instrumentation:
+///
+/// ```ignore
+/// struct CallCounter;
+///
+/// impl StaticAspect for CallCounter {
+///     fn before(&self, _ctx: &JoinPoint) {
+///         CALLS.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// #[aspect(static CallCounter)]
+/// fn my_function(x: i32) -> i32 {
+///     x * 2
+/// }
+/// ```
+///
+/// With `local` before it, the aspect is a `LocalAspect`, which need not be
+/// `Send + Sync` and is evaluated on the calling thread, e.g. as a clone of
+/// a `thread_local!` holding an `Rc`-based cache:
+///
+/// ```ignore
+/// thread_local! {
+///     static CACHE: RcCache = RcCache::default();
+/// }
+///
+/// #[aspect(local CACHE.with(RcCache::clone))]
+/// fn my_function(x: i32) -> i32 {
+///     x * 2
+/// }
+/// ```
+///
+/// An aspect type followed by `key = value` parameters is built from them
+/// with its `FromAspectArgs` implementation, once per function; the calls
+/// share that instance. Literal parameters are checked against
+/// `FromAspectArgs::PARAMS` as the crate compiles; others, such as
+/// constants, when the aspect is built, an invalid one making the first
+/// call panic:
+///
+/// ```ignore
+/// #[aspect(RateLimitAspect, max = 10, window = "1s")]
+/// fn my_function(x: i32) -> Result<i32, String> {
+///     Ok(x * 2)
+/// }
+/// ```
+///
+/// Several aspects can be listed, separated by commas. They are woven by
+/// the precedence they declare with `Aspect::precedence`, whatever order
+/// they are listed in; aspects of the same precedence keep the listed
+/// order. Here `Auth` checks the caller before `Logger` sees the call:
+///
+/// ```ignore
+/// #[aspect(Logger, Auth::require_role("admin"))]
+/// fn delete_user(id: u64) -> Result<(), String> {
+///     Ok(())
+/// }
+/// ```
+///
+/// A panic of the function bypasses the advice after it. With `unwind`
+/// before the aspects, the panic of a function not returning `Result` is
+/// caught: the aspects see it in `after_error` as an `AspectError::Panic`,
+/// then it resumes, unless an `around` advice returns a value instead.
+/// Here `MetricsAspect` times the calls that panic and counts them as
+/// errors:
+///
+/// ```ignore
+/// #[aspect(unwind MetricsAspect::new())]
+/// fn render(page: &Page) -> Html {
+///     template(page).expand()
+/// }
+/// ```
+///
+/// With `cfg(predicate)` after it, the aspect is only woven where the
+/// predicate holds. Elsewhere the function is compiled unchanged, without
+/// a trace of the aspect, e.g. in release builds here:
+///
+/// ```ignore
+/// #[aspect(Logger, cfg(debug_assertions))]
+/// fn my_function(x: i32) -> i32 {
+///     x * 2
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn aspect(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let aspect_expr = parse_macro_input!(attr as Expr);
-    let func = parse_macro_input!(item as ItemFn);
+    if aspects_disabled() {
+        let Ok(func) = syn::parse::<ItemFn>(item.clone()) else {
+            return item;
+        };
+        return quote::ToTokens::into_token_stream(tracking_compile_out(func)).into();
+    }
 
-    aspect_attr::transform(aspect_expr, func)
+    let aspect_info = parse_macro_input!(attr as parsing::AspectInfo);
+    let func = tracking_compile_out(parse_macro_input!(item as ItemFn));
+
+    aspect_attr::transform(aspect_info, func)
         .unwrap_or_else(|e| e.to_compile_error())
         .into()
 }
 
+/// Weaves aspects into the reads and writes of struct fields.
+///
+/// The fields marked with `#[aspect_field]` get a getter named after them,
+/// returning a reference to the field, and a `set_` setter, with the
+/// visibility of the struct; `#[aspect_field(get)]` or
+/// `#[aspect_field(set)]` generates only one. The aspects are evaluated for
+/// each access, like those of `#[aspect]`, and see the joinpoints
+/// `get(Struct.field)` and `set(Struct.field)`, of kind
+/// `JoinPointKind::FieldGet` and `JoinPointKind::FieldSet`:
+///
+/// - the getter runs `before` and `after` advice, which sees the value of
+///   the field, as for async functions. `after` takes it as `&dyn Any`, so
+///   fields with getters must have `'static` types;
+/// - the setter runs `around` advice, and the new value is the argument
+///   named after the field. It returns `Result<(), AspectError>`: an
+///   aspect refusing the write makes it return the error.
+///
+/// The fields themselves are left as they are: keep them private, so that
+/// other modules only access them through the accessors.
+///
+/// # Example
+///
+/// ```ignore
+/// #[aspect_fields(AuditAspect::default())]
+/// pub struct Account {
+///     #[aspect_field]
+///     balance: u64,
+///     #[aspect_field(get)]
+///     owner: String,
+/// }
+///
+/// account.set_balance(account.balance() + 100)?;
+/// ```
+#[proc_macro_attribute]
+pub fn aspect_fields(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as fields_attr::FieldAspects);
+    let item = parse_macro_input!(item as ItemStruct);
+
+    let track = track_compile_out();
+    match fields_attr::transform(args, item, !aspects_disabled()) {
+        Ok(expanded) => quote::quote! {
+            #expanded
+            const _: () = {
+                #track
+            };
+        },
+        Err(e) => e.to_compile_error(),
+    }
+    .into()
+}
+
 /// Registers an aspect with a pointcut pattern for declarative aspect application.
 ///
+/// The pointcut is parsed as the macro expands: an invalid one is a compile
+/// error. Like for `#[aspect]`, `cfg(predicate)` compiles the aspect and its
+/// registration only where the predicate holds, e.g.
+/// `cfg(feature = "audit")`.
+///
 /// # Example
 ///
 /// ```ignore
@@ -66,9 +248,39 @@ pub fn aspect(attr: TokenStream, item: TokenStream) -> TokenStream {
 #[proc_macro_attribute]
 pub fn advice(attr: TokenStream, item: TokenStream) -> TokenStream {
     let args = parse_macro_input!(attr as advice_macro::AdviceArgs);
-    let func = parse_macro_input!(item as ItemFn);
+    let func = tracking_compile_out(parse_macro_input!(item as ItemFn));
+
+    advice_macro::transform(args, func, !aspects_disabled())
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Declares the crate an aspect provider, with the aspects listed in the
+/// `[package.metadata.aspect]` table of its `Cargo.toml`.
+///
+/// The macro checks the table as the crate compiles, including that every
+/// aspect listed is a type implementing `Aspect` when its features are
+/// enabled, and defines `ASPECT_PROVIDER`, an
+/// `aspect_core::provider::AspectProvider` with its contents. See
+/// `aspect_core::provider` for the format of the table.
+///
+/// # Example
+///
+/// ```toml
+/// [[package.metadata.aspect.provides]]
+/// name = "RetryAspect"
+/// description = "Retry failed calls with backoff"
+/// ```
+///
+/// ```ignore
+/// // At the root of the crate
+/// aspect_macros::aspect_provider!();
+/// ```
+#[proc_macro]
+pub fn aspect_provider(input: TokenStream) -> TokenStream {
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR").map(std::path::PathBuf::from);
 
-    advice_macro::transform(args, func)
+    provider_macro::expand(input.into(), manifest_dir.as_deref())
         .unwrap_or_else(|e| e.to_compile_error())
         .into()
 }
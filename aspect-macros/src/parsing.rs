@@ -1,16 +1,287 @@
 //! Parsing utilities for aspect macro attributes.
 
-use syn::{Expr, Result};
+use proc_macro2::TokenStream;
+use syn::parse::{Parse, ParseStream};
+use syn::{token, Expr, Ident, MetaList, Result, Token};
 
-/// Information about the aspect to apply.
+/// Information about the aspects to apply.
 pub struct AspectInfo {
-    /// The expression that evaluates to the aspect instance
-    pub aspect_expr: Expr,
+    /// The expressions that evaluate to the aspect instances, in the order
+    /// they are listed
+    pub aspects: Vec<Expr>,
+
+    /// Whether the aspect is a `StaticAspect`, `#[aspect(static ...)]`
+    pub is_static: bool,
+
+    /// Whether the aspect is a `LocalAspect`, `#[aspect(local ...)]`
+    pub is_local: bool,
+
+    /// Whether the aspects see the panics of the function,
+    /// `#[aspect(unwind ...)]`
+    pub unwind: bool,
+
+    /// The `key = value` parameters after the aspect, which is then the
+    /// type of a `FromAspectArgs` aspect
+    pub params: Vec<(Ident, Expr)>,
+
+    /// The predicate of `cfg(...)` after the aspects, which they are only
+    /// woven under
+    pub cfg: Option<TokenStream>,
+}
+
+impl Parse for AspectInfo {
+    /// Parse aspect information from the attribute syntax: the aspect
+    /// expressions, separated by commas, or a single one after `static` for
+    /// a `StaticAspect` or `local` for a `LocalAspect`, or a single aspect
+    /// type followed by `key = value` parameters; optionally followed by
+    /// `, cfg(predicate)`. All but `static` aspects can be preceded by
+    /// `unwind`.
+    fn parse(input: ParseStream) -> Result<Self> {
+        let unwind =
+            peek_keyword(input, "unwind") && (input.peek2(Ident) || input.peek2(Token![static]));
+        if unwind {
+            input.parse::<Ident>()?;
+        }
+        let static_token = input.parse::<Option<Token![static]>>()?;
+        let is_static = static_token.is_some();
+        if unwind && is_static {
+            return Err(syn::Error::new_spanned(
+                static_token,
+                "`unwind` does not apply to `static` aspects, which have no `after_error`",
+            ));
+        }
+        let is_local = !is_static && peek_keyword(input, "local") && input.peek2(Ident);
+        if is_local {
+            input.parse::<Ident>()?;
+        }
+        let mut aspects = vec![input.parse()?];
+        let mut params: Vec<(Ident, Expr)> = Vec::new();
+        let mut cfg = None;
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            if cfg.is_some() {
+                return Err(input.error("expected `cfg(...)` after the aspects"));
+            }
+            if peek_cfg(input) {
+                cfg = Some(parse_cfg(input)?);
+            } else if input.peek(Ident) && input.peek2(Token![=]) {
+                let key: Ident = input.parse()?;
+                input.parse::<Token![=]>()?;
+                if params.iter().any(|(other, _)| *other == key) {
+                    return Err(syn::Error::new_spanned(
+                        &key,
+                        format!("duplicate parameter `{}`", key),
+                    ));
+                }
+                params.push((key, input.parse()?));
+            } else if !params.is_empty() {
+                return Err(input.error("expected `key = value` after the parameters"));
+            } else {
+                aspects.push(input.parse()?);
+            }
+        }
+        if let Some((key, _)) = params.first() {
+            if is_static || is_local || aspects.len() > 1 {
+                return Err(syn::Error::new_spanned(
+                    key,
+                    "parameters configure a single aspect, given by its type",
+                ));
+            }
+            if !matches!(aspects[0], Expr::Path(_)) {
+                return Err(syn::Error::new_spanned(
+                    &aspects[0],
+                    "expected the type of the aspect to build from the parameters",
+                ));
+            }
+        }
+        if (is_static || is_local) && aspects.len() > 1 {
+            let keyword = if is_static { "static" } else { "local" };
+            return Err(syn::Error::new_spanned(
+                &aspects[1],
+                format!("`{}` takes a single aspect", keyword),
+            ));
+        }
+        Ok(Self {
+            aspects,
+            is_static,
+            is_local,
+            unwind,
+            params,
+            cfg,
+        })
+    }
+}
+
+/// Whether the input starts with the identifier `keyword`. `local` and
+/// `unwind` are not keywords: they are only taken as such before an
+/// aspect, and alone, or followed by anything else, are variables.
+fn peek_keyword(input: ParseStream, keyword: &str) -> bool {
+    input
+        .fork()
+        .parse::<Ident>()
+        .is_ok_and(|ident| ident == keyword)
 }
 
-impl AspectInfo {
-    /// Parse aspect information from the attribute syntax.
-    pub fn parse(aspect_expr: Expr) -> Result<Self> {
-        Ok(Self { aspect_expr })
+/// Whether the input continues with `cfg(...)`.
+fn peek_cfg(input: ParseStream) -> bool {
+    let fork = input.fork();
+    fork.parse::<Ident>().is_ok_and(|ident| ident == "cfg") && fork.peek(token::Paren)
+}
+
+/// Parse `cfg(predicate)`, returning the predicate.
+fn parse_cfg(input: ParseStream) -> Result<TokenStream> {
+    let cfg: MetaList = input.parse()?;
+    Ok(cfg.tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_parse() {
+        let info: AspectInfo = parse_quote!(Logger::new("api"));
+        assert!(!info.is_static);
+
+        let info: AspectInfo = parse_quote!(static CallCounter);
+        assert!(info.is_static);
+        assert_eq!(info.aspects, [parse_quote!(CallCounter)]);
+        assert!(info.cfg.is_none());
+    }
+
+    #[test]
+    fn test_parse_cfg() {
+        let info: AspectInfo = parse_quote!(Logger::new("api"), cfg(feature = "tracing"));
+        assert_eq!(info.aspects, [parse_quote!(Logger::new("api"))]);
+        assert_eq!(
+            info.cfg.unwrap().to_string(),
+            quote::quote!(feature = "tracing").to_string()
+        );
+
+        let info: AspectInfo = parse_quote!(static CallCounter, cfg(debug_assertions));
+        assert!(info.is_static);
+        assert_eq!(info.cfg.unwrap().to_string(), "debug_assertions");
+
+        let error = syn::parse_str::<AspectInfo>("Logger, cfg(debug_assertions), Timer")
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "expected `cfg(...)` after the aspects");
+    }
+
+    #[test]
+    fn test_parse_several() {
+        let info: AspectInfo = parse_quote!(Logger::new("api"), RateLimiter::new(10), Auth);
+        assert_eq!(
+            info.aspects,
+            [
+                parse_quote!(Logger::new("api")),
+                parse_quote!(RateLimiter::new(10)),
+                parse_quote!(Auth),
+            ]
+        );
+        assert!(info.cfg.is_none());
+
+        let info: AspectInfo = parse_quote!(Logger, Auth, cfg(feature = "api"));
+        assert_eq!(info.aspects.len(), 2);
+        assert!(info.cfg.is_some());
+
+        // Calls other than `cfg(...)` are aspects
+        let info: AspectInfo = parse_quote!(Logger, when(debug_assertions));
+        assert_eq!(info.aspects.len(), 2);
+
+        let error = syn::parse_str::<AspectInfo>("static CallCounter, Logger")
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "`static` takes a single aspect");
+    }
+
+    #[test]
+    fn test_parse_params() {
+        let info: AspectInfo = parse_quote!(
+            RateLimitAspect,
+            max = 10,
+            window = "1s",
+            cfg(feature = "api")
+        );
+        assert_eq!(info.aspects, [parse_quote!(RateLimitAspect)]);
+        let keys: Vec<String> = info.params.iter().map(|(k, _)| k.to_string()).collect();
+        assert_eq!(keys, ["max", "window"]);
+        assert_eq!(info.params[1].1, parse_quote!("1s"));
+        assert!(info.cfg.is_some());
+
+        let errors = [
+            (
+                "RateLimitAspect, max = 1, max = 2",
+                "duplicate parameter `max`",
+            ),
+            (
+                "RateLimitAspect, max = 1, Logger",
+                "expected `key = value` after the parameters",
+            ),
+            (
+                "Logger, RateLimitAspect, max = 1",
+                "parameters configure a single aspect, given by its type",
+            ),
+            (
+                "static Counter, max = 1",
+                "parameters configure a single aspect, given by its type",
+            ),
+            (
+                "RateLimitAspect::new(), max = 1",
+                "expected the type of the aspect to build from the parameters",
+            ),
+        ];
+        for (attr, message) in errors {
+            let error = syn::parse_str::<AspectInfo>(attr).err().unwrap();
+            assert_eq!(error.to_string(), message, "{}", attr);
+        }
+    }
+
+    #[test]
+    fn test_parse_local() {
+        let info: AspectInfo = parse_quote!(local CALL_LOG.with(CallLog::clone), cfg(test));
+        assert!(info.is_local && !info.is_static);
+        assert_eq!(info.aspects, [parse_quote!(CALL_LOG.with(CallLog::clone))]);
+        assert!(info.cfg.is_some());
+
+        // Only before another identifier
+        let info: AspectInfo = parse_quote!(local);
+        assert!(!info.is_local);
+        assert_eq!(info.aspects, [parse_quote!(local)]);
+        let info: AspectInfo = parse_quote!(local.clone());
+        assert!(!info.is_local);
+
+        let error = syn::parse_str::<AspectInfo>("local CallLog, Logger")
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "`local` takes a single aspect");
+    }
+
+    #[test]
+    fn test_parse_unwind() {
+        let info: AspectInfo = parse_quote!(unwind TransactionAspect::new(), Logger);
+        assert!(info.unwind);
+        assert_eq!(info.aspects.len(), 2);
+
+        let info: AspectInfo = parse_quote!(unwind local CALL_LOG.with(CallLog::clone));
+        assert!(info.unwind && info.is_local);
+
+        let info: AspectInfo = parse_quote!(unwind RateLimitAspect, max = 10);
+        assert!(info.unwind);
+        assert_eq!(info.params.len(), 1);
+
+        // Only before another identifier
+        let info: AspectInfo = parse_quote!(unwind.clone());
+        assert!(!info.unwind);
+        let info: AspectInfo = parse_quote!(Logger);
+        assert!(!info.unwind);
+
+        let error = syn::parse_str::<AspectInfo>("unwind static CallCounter")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "`unwind` does not apply to `static` aspects, which have no `after_error`"
+        );
     }
 }
@@ -0,0 +1,340 @@
+//! Implementation of the aspect_provider! macro.
+//!
+//! The macro reads the `[package.metadata.aspect]` table of the Cargo.toml
+//! of the crate it expands in (see `aspect_core::provider` for its format),
+//! checks it, and defines the crate's `ASPECT_PROVIDER` from it.
+
+use std::path::Path;
+
+use aspect_core::pointcut::Pointcut;
+use aspect_core::provider::METADATA_KEY;
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{Error, Result};
+use toml::{Table, Value};
+
+/// Keys of a `[[package.metadata.aspect.provides]]` entry.
+const ENTRY_KEYS: &[&str] = &[
+    "name",
+    "description",
+    "pointcut",
+    "advice",
+    "order",
+    "features",
+];
+
+/// Advice types, as `#[advice]` takes them.
+const ADVICE_TYPES: &[&str] = &["before", "after", "after_error", "around"];
+
+/// One aspect of the manifest.
+#[derive(Debug)]
+pub struct ProvidedEntry {
+    /// Path of the aspect type from the crate root
+    pub name: String,
+
+    /// `name` parsed
+    pub path: syn::Path,
+
+    /// One line describing the aspect, empty when there is none
+    pub description: String,
+
+    /// Pointcut of the functions the aspect is meant for
+    pub pointcut: Option<String>,
+
+    /// Advice type, "around" by default
+    pub advice: String,
+
+    /// Execution order (lower runs first)
+    pub order: i32,
+
+    /// Features of the crate the aspect type needs
+    pub features: Vec<String>,
+}
+
+/// Expand `aspect_provider!(input)` in the crate of the manifest
+/// directory `manifest_dir`.
+pub fn expand(input: TokenStream, manifest_dir: Option<&Path>) -> Result<TokenStream> {
+    if !input.is_empty() {
+        return Err(Error::new_spanned(
+            input,
+            "aspect_provider! takes no arguments: the aspects are listed in the \
+             [package.metadata.aspect] table of Cargo.toml",
+        ));
+    }
+    let Some(manifest_dir) = manifest_dir else {
+        return Err(Error::new(
+            Span::call_site(),
+            "CARGO_MANIFEST_DIR is not set: aspect_provider! reads the Cargo.toml of the crate",
+        ));
+    };
+    let manifest = manifest_dir.join("Cargo.toml");
+    let error = |message: String| Error::new(Span::call_site(), message);
+    let contents = std::fs::read_to_string(&manifest)
+        .map_err(|e| error(format!("{}: {}", manifest.display(), e)))?;
+    let entries =
+        parse_manifest(&contents).map_err(|e| error(format!("{}: {}", manifest.display(), e)))?;
+
+    let aspects = entries.iter().map(|entry| {
+        let ProvidedEntry {
+            name,
+            description,
+            advice,
+            order,
+            features,
+            ..
+        } = entry;
+        let pointcut = match &entry.pointcut {
+            Some(pointcut) => quote!(Some(#pointcut)),
+            None => quote!(None),
+        };
+        quote! {
+            aspect_core::provider::ProvidedAspect {
+                name: #name,
+                description: #description,
+                pointcut: #pointcut,
+                advice: #advice,
+                order: #order,
+                features: &[#(#features),*],
+            }
+        }
+    });
+    let checks = entries.iter().map(|entry| {
+        let path = &entry.path;
+        let features = &entry.features;
+        let gate = if features.is_empty() {
+            TokenStream::new()
+        } else {
+            quote!(#[cfg(all(#(feature = #features),*))])
+        };
+        quote! {
+            #gate
+            provided::<crate::#path>();
+        }
+    });
+    let manifest = manifest.display().to_string();
+
+    Ok(quote! {
+        /// The aspects this crate provides, as listed in the
+        /// `[package.metadata.aspect]` table of its `Cargo.toml`.
+        pub const ASPECT_PROVIDER: aspect_core::provider::AspectProvider =
+            aspect_core::provider::AspectProvider {
+                package: env!("CARGO_PKG_NAME"),
+                crate_name: env!("CARGO_CRATE_NAME"),
+                version: env!("CARGO_PKG_VERSION"),
+                aspects: &[#(#aspects),*],
+            };
+
+        // Expanded again when the manifest changes
+        const _: &[u8] = include_bytes!(#manifest);
+
+        // Every aspect provided is a type implementing Aspect
+        const _: () = {
+            fn provided<T: aspect_core::Aspect>() {}
+
+            #[allow(dead_code)]
+            fn check() {
+                #(#checks)*
+            }
+        };
+    })
+}
+
+/// The aspects listed in the Cargo.toml `contents`.
+pub fn parse_manifest(contents: &str) -> std::result::Result<Vec<ProvidedEntry>, String> {
+    let manifest: Table = contents
+        .parse()
+        .map_err(|e: toml::de::Error| format!("invalid manifest: {}", e.message()))?;
+    let table = manifest
+        .get("package")
+        .and_then(|package| package.get("metadata"))
+        .and_then(|metadata| metadata.get(METADATA_KEY))
+        .ok_or("no [package.metadata.aspect] table")?
+        .as_table()
+        .ok_or("package.metadata.aspect is not a table")?;
+    if let Some(key) = table.keys().find(|key| *key != "provides") {
+        return Err(format!(
+            "unknown key '{}' in [package.metadata.aspect]",
+            key
+        ));
+    }
+    let provides = table
+        .get("provides")
+        .ok_or("no [[package.metadata.aspect.provides]] entry")?
+        .as_array()
+        .ok_or("package.metadata.aspect.provides is not an array of tables")?;
+
+    let mut entries: Vec<ProvidedEntry> = Vec::new();
+    for value in provides {
+        let entry = value
+            .as_table()
+            .ok_or("package.metadata.aspect.provides is not an array of tables")?;
+        let entry =
+            parse_entry(entry).map_err(|e| match entry.get("name").and_then(Value::as_str) {
+                Some(name) => format!("aspect '{}': {}", name, e),
+                None => e,
+            })?;
+        if entries.iter().any(|other| other.name == entry.name) {
+            return Err(format!("aspect '{}' is listed twice", entry.name));
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// One `[[package.metadata.aspect.provides]]` entry.
+fn parse_entry(entry: &Table) -> std::result::Result<ProvidedEntry, String> {
+    if let Some(key) = entry.keys().find(|key| !ENTRY_KEYS.contains(&key.as_str())) {
+        return Err(format!("unknown key '{}'", key));
+    }
+    let string = |key: &str| match entry.get(key) {
+        None => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value.clone())),
+        Some(_) => Err(format!("'{}' is not a string", key)),
+    };
+
+    let name = string("name")?.ok_or("missing 'name'")?;
+    let path: syn::Path =
+        syn::parse_str(&name).map_err(|_| format!("'{}' is not a type path", name))?;
+    if path.leading_colon.is_some()
+        || ["crate", "self", "super"]
+            .iter()
+            .any(|keyword| path.segments[0].ident == keyword)
+    {
+        return Err(format!(
+            "'{}' must be a path from the crate root, without `crate::`",
+            name
+        ));
+    }
+
+    let pointcut = string("pointcut")?;
+    if let Some(pointcut) = &pointcut {
+        Pointcut::parse(pointcut).map_err(|e| format!("invalid pointcut '{}': {}", pointcut, e))?;
+    }
+
+    let advice = string("advice")?.unwrap_or_else(|| "around".to_string());
+    if !ADVICE_TYPES.contains(&advice.as_str()) {
+        return Err(format!(
+            "invalid advice type '{}', must be one of: {}",
+            advice,
+            ADVICE_TYPES.join(", ")
+        ));
+    }
+
+    let order = match entry.get("order") {
+        None => 0,
+        Some(Value::Integer(order)) => {
+            i32::try_from(*order).map_err(|_| format!("order {} is out of range", order))?
+        }
+        Some(_) => return Err("'order' is not an integer".to_string()),
+    };
+
+    let features = match entry.get("features") {
+        None => Vec::new(),
+        Some(Value::Array(features)) => features
+            .iter()
+            .map(|feature| feature.as_str().map(str::to_string))
+            .collect::<Option<_>>()
+            .ok_or("'features' is not an array of strings")?,
+        Some(_) => return Err("'features' is not an array of strings".to_string()),
+    };
+
+    Ok(ProvidedEntry {
+        name,
+        path,
+        description: string("description")?.unwrap_or_default(),
+        pointcut,
+        advice,
+        order,
+        features,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+[package]
+name = "retry-aspects"
+
+[[package.metadata.aspect.provides]]
+name = "RetryAspect"
+description = "Retry failed calls"
+
+[[package.metadata.aspect.provides]]
+name = "backoff::BackoffAspect"
+pointcut = "execution(pub fn *(..)) && within(crate::client)"
+advice = "before"
+order = -5
+features = ["std", "backoff"]
+"#;
+
+    #[test]
+    fn test_parse_manifest() {
+        let entries = parse_manifest(MANIFEST).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let retry = &entries[0];
+        assert_eq!(retry.name, "RetryAspect");
+        assert_eq!(retry.description, "Retry failed calls");
+        assert_eq!(retry.pointcut, None);
+        assert_eq!(retry.advice, "around");
+        assert_eq!(retry.order, 0);
+        assert!(retry.features.is_empty());
+
+        let backoff = &entries[1];
+        assert_eq!(backoff.path.segments.len(), 2);
+        assert_eq!(backoff.advice, "before");
+        assert_eq!(backoff.order, -5);
+        assert_eq!(backoff.features, ["std", "backoff"]);
+    }
+
+    #[test]
+    fn test_parse_manifest_errors() {
+        let error = |entry: &str| {
+            parse_manifest(&format!("[[package.metadata.aspect.provides]]\n{}", entry)).unwrap_err()
+        };
+        assert_eq!(
+            parse_manifest("[package]\nname = \"a\"").unwrap_err(),
+            "no [package.metadata.aspect] table"
+        );
+        assert_eq!(
+            error("name = \"RetryAspect\"\nretries = 3"),
+            "aspect 'RetryAspect': unknown key 'retries'"
+        );
+        assert_eq!(error("description = \"\""), "missing 'name'");
+        assert!(error("name = \"crate::RetryAspect\"").contains("from the crate root"));
+        assert!(error("name = \"Retry Aspect\"").contains("not a type path"));
+        assert!(error("name = \"A\"\npointcut = \"call(fn *(..))\"").contains("invalid pointcut"));
+        assert!(error("name = \"A\"\nadvice = \"during\"").contains("invalid advice type"));
+        assert!(error("name = \"A\"\norder = 4294967296").contains("out of range"));
+        assert!(error("name = \"A\"\nfeatures = [1]").contains("array of strings"));
+        assert_eq!(
+            error("name = \"A\"\n[[package.metadata.aspect.provides]]\nname = \"A\""),
+            "aspect 'A' is listed twice"
+        );
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir = std::env::temp_dir().join(format!("aspect-provider-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Cargo.toml"), MANIFEST).unwrap();
+
+        let output = expand(TokenStream::new(), Some(&dir)).unwrap().to_string();
+        for expected in [
+            "pub const ASPECT_PROVIDER",
+            "name : \"RetryAspect\"",
+            "pointcut : None",
+            "order : - 5i32",
+            "provided :: < crate :: RetryAspect > ()",
+            "# [cfg (all (feature = \"std\" , feature = \"backoff\"))] provided :: < crate :: backoff :: BackoffAspect > ()",
+        ] {
+            assert!(output.contains(expected), "{} in {}", expected, output);
+        }
+
+        assert!(expand(quote!(RetryAspect), Some(&dir)).is_err());
+        assert!(expand(TokenStream::new(), None).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,224 @@
+//! Benchmarks for registry matching throughput
+//!
+//! Measures `find_matching` and `apply_aspects` with 1 to 1000 registered
+//! aspects, for simple pointcuts matching no function and for complex
+//! ones matching every function. Also measures how readers and a writer
+//! of the registry slow each other down, and what calls no aspect applies
+//! to cost.
+
+use aspect_core::pointcut::{FunctionInfo, Pointcut};
+use aspect_core::{Arg, Aspect, JoinPoint, Location, ProceedingJoinPoint};
+use aspect_runtime::AspectRegistry;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SIZES: [usize; 4] = [1, 10, 100, 1000];
+
+/// Aspect doing nothing, so that only the registry is measured
+struct NoOpAspect;
+
+impl Aspect for NoOpAspect {}
+
+/// The pointcut of the `i`th aspect: `simple` ones never match
+/// [`function`], `complex` ones always do.
+fn pointcut(complexity: &str, i: usize) -> Pointcut {
+    let pointcut = match complexity {
+        "simple" => format!("execution(pub fn handler_{}(..))", i),
+        _ => format!(
+            "(execution(pub fn handler_{}*(..)) || execution(pub fn *_user(..))) \
+             && within(crate::api) && !within(crate::api::internal)",
+            i
+        ),
+    };
+    Pointcut::parse(&pointcut).unwrap()
+}
+
+fn registry(complexity: &str, size: usize) -> AspectRegistry {
+    let registry = AspectRegistry::new();
+    for i in 0..size {
+        registry.register(
+            Arc::new(NoOpAspect),
+            pointcut(complexity, i),
+            i as i32,
+            None,
+        );
+    }
+    registry
+}
+
+fn function() -> FunctionInfo {
+    FunctionInfo::new("update_user", "crate::api::users", "pub")
+}
+
+fn bench_find_matching(c: &mut Criterion) {
+    let function = function();
+    for complexity in ["simple", "complex"] {
+        let mut group = c.benchmark_group(format!("find_matching/{}", complexity));
+        for size in SIZES {
+            let registry = registry(complexity, size);
+
+            // What every call but the first of a function costs
+            group.bench_with_input(BenchmarkId::new("cached", size), &size, |b, _| {
+                b.iter(|| black_box(&registry).find_matching(black_box(&function)))
+            });
+
+            // What the first call costs, matching all the pointcuts
+            group.bench_with_input(BenchmarkId::new("uncached", size), &size, |b, _| {
+                b.iter_batched(
+                    || registry.clear_cache(),
+                    |_| black_box(&registry).find_matching(black_box(&function)),
+                    BatchSize::PerIteration,
+                )
+            });
+        }
+        group.finish();
+    }
+}
+
+fn bench_apply_aspects(c: &mut Criterion) {
+    let function = function();
+    let context = JoinPoint::new(
+        "update_user",
+        "crate::api::users",
+        Location {
+            file: "src/api/users.rs",
+            line: 1,
+        },
+    );
+    for complexity in ["simple", "complex"] {
+        let mut group = c.benchmark_group(format!("apply_aspects/{}", complexity));
+        for size in SIZES {
+            let registry = registry(complexity, size);
+            group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+                b.iter(|| {
+                    let pjp = ProceedingJoinPoint::new(
+                        || Ok(Box::new(42) as Box<dyn Any>),
+                        context.clone(),
+                    );
+                    black_box(&registry)
+                        .apply_aspects(black_box(&function), pjp)
+                        .unwrap()
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+/// Calls of a function no aspect applies to, whose join point captures an
+/// argument: built for every call (`eager`), or only when an aspect
+/// matches (`lazy`).
+fn bench_unmatched(c: &mut Criterion) {
+    let mut group = c.benchmark_group("unmatched");
+    let registry = registry("simple", 100);
+    let function = function();
+    let context = || {
+        JoinPoint::new(
+            "update_user",
+            "crate::api::users",
+            Location {
+                file: "src/api/users.rs",
+                line: 1,
+            },
+        )
+        .with_args(vec![Arg::new("id", &42u64)])
+    };
+    let original = || Ok(Box::new(()) as Box<dyn Any>);
+
+    group.bench_function("eager", |b| {
+        b.iter(|| {
+            let pjp = ProceedingJoinPoint::new(original, context());
+            black_box(&registry).apply_aspects(black_box(&function), pjp)
+        })
+    });
+    group.bench_function("lazy", |b| {
+        b.iter(|| black_box(&registry).invoke(black_box(&function), context, original))
+    });
+
+    group.finish();
+}
+
+/// Runs `background` on `threads` threads until `routine` returns.
+fn contended<T>(threads: usize, background: impl Fn() + Sync, routine: impl FnOnce() -> T) -> T {
+    let stop = AtomicBool::new(false);
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    background();
+                }
+            });
+        }
+        let result = routine();
+        stop.store(true, Ordering::Relaxed);
+        result
+    })
+}
+
+/// The `i`th write of a registry changing all the time: registering
+/// aspects, and removing them all every 100.
+fn churn(registry: &AspectRegistry, i: usize) {
+    registry.register(Arc::new(NoOpAspect), pointcut("simple", i % 100), 0, None);
+    if i % 100 == 99 {
+        registry.clear();
+    }
+}
+
+fn bench_contention(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contention");
+    let function = FunctionInfo::new("handler_3", "crate::api", "pub");
+
+    // Lookups while another thread keeps writing
+    let shared = registry("simple", 0);
+    let writes = AtomicUsize::new(0);
+    group.bench_function("read_while_writing", |b| {
+        b.iter_custom(|iters| {
+            let write = || {
+                churn(&shared, writes.fetch_add(1, Ordering::Relaxed));
+                thread::sleep(Duration::from_micros(10));
+            };
+            contended(1, write, || {
+                let start = Instant::now();
+                for _ in 0..iters {
+                    black_box(shared.find_matching(black_box(&function)));
+                }
+                start.elapsed()
+            })
+        })
+    });
+
+    // Writes while other threads keep looking up
+    for readers in [1, 4] {
+        let shared = registry("simple", 0);
+        group.bench_function(BenchmarkId::new("write_while_reading", readers), |b| {
+            b.iter_custom(|iters| {
+                let read = || {
+                    black_box(shared.find_matching(black_box(&function)));
+                };
+                contended(readers, read, || {
+                    let start = Instant::now();
+                    for i in 0..iters {
+                        churn(&shared, i as usize);
+                    }
+                    start.elapsed()
+                })
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_find_matching,
+    bench_apply_aspects,
+    bench_unmatched,
+    bench_contention
+);
+
+criterion_main!(benches);
@@ -0,0 +1,73 @@
+/*
+ * C API of aspect-runtime, with the `ffi` feature.
+ *
+ * Registers aspects whose advice are C callbacks in the global registry of
+ * the Rust library exporting these functions. See the `ffi` module of
+ * aspect-runtime for the details.
+ */
+
+#ifndef ASPECT_RUNTIME_H
+#define ASPECT_RUNTIME_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Statuses of aspect_register */
+#define ASPECT_OK 0
+#define ASPECT_INVALID_ARGUMENT (-1)
+#define ASPECT_INVALID_POINTCUT (-2)
+
+/* A join point; its strings are only valid during the callback. */
+typedef struct AspectJoinPoint {
+    const char *function_name;
+    const char *module_path;
+    const char *file;
+    uint32_t line;
+} AspectJoinPoint;
+
+/* Returns 0 to let the call proceed, or a non-zero status to reject it. */
+typedef int32_t (*AspectBeforeFn)(void *user_data, const AspectJoinPoint *jp);
+typedef void (*AspectAfterFn)(void *user_data, const AspectJoinPoint *jp);
+typedef void (*AspectAfterErrorFn)(void *user_data, const AspectJoinPoint *jp,
+                                   const char *error);
+typedef void (*AspectDropFn)(void *user_data);
+
+/*
+ * The advice of an aspect. Each callback may be NULL. Callbacks are called
+ * from any thread running woven code, possibly at the same time.
+ * drop_user_data is called once the aspect is no longer registered.
+ */
+typedef struct AspectCallbacks {
+    void *user_data;
+    AspectBeforeFn before;
+    AspectAfterFn after;
+    AspectAfterErrorFn after_error;
+    AspectDropFn drop_user_data;
+} AspectCallbacks;
+
+/*
+ * Registers an aspect running the callbacks, which are copied, for the
+ * functions matching the pointcut expression. name may be NULL. Lower
+ * orders run first.
+ */
+int32_t aspect_register(const char *pointcut, const AspectCallbacks *callbacks,
+                        int32_t order, const char *name);
+
+/* Message of the last failure on the calling thread, or NULL. */
+const char *aspect_last_error(void);
+
+/* Number of aspects in the global registry. */
+size_t aspect_count(void);
+
+/* Removes all aspects from the global registry. */
+void aspect_clear(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ASPECT_RUNTIME_H */
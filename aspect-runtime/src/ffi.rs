@@ -0,0 +1,456 @@
+//! C API registering aspects whose advice are C callbacks.
+//!
+//! A host application written in another language, embedding a Rust
+//! library woven with the global registry, hooks the join points of the
+//! library through these functions: [`aspect_register`] registers an
+//! aspect for the functions matching a pointcut, with function pointers as
+//! its `before`, `after` and `after_error` advice. Declarations for C are in
+//! `include/aspect_runtime.h`. Available with the `ffi` feature; the library
+//! exports the functions when built as a `cdylib` or `staticlib`.
+//!
+//! # Example
+//!
+//! ```c
+//! #include "aspect_runtime.h"
+//!
+//! static int32_t audit(void *user_data, const AspectJoinPoint *jp) {
+//!     printf("calling %s::%s\n", jp->module_path, jp->function_name);
+//!     return 0;
+//! }
+//!
+//! AspectCallbacks callbacks = { .before = audit };
+//! if (aspect_register("execution(pub fn *(..))", &callbacks, 0, "audit") != ASPECT_OK) {
+//!     fprintf(stderr, "%s\n", aspect_last_error());
+//! }
+//! ```
+
+use crate::registry::global_registry;
+use aspect_core::aspect::BoxFuture;
+use aspect_core::pointcut::Pointcut;
+use aspect_core::{Aspect, AspectError, JoinPoint, ProceedingJoinPoint};
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::ptr;
+use std::sync::Arc;
+
+/// Status of a successful call.
+pub const ASPECT_OK: i32 = 0;
+
+/// Status of a call given a null pointer or a string that is not UTF-8.
+pub const ASPECT_INVALID_ARGUMENT: i32 = -1;
+
+/// Status of a call given a pointcut expression that does not parse.
+pub const ASPECT_INVALID_POINTCUT: i32 = -2;
+
+/// A join point, as C callbacks see it.
+///
+/// The strings are NUL-terminated and only valid during the callback.
+#[repr(C)]
+#[derive(Debug)]
+pub struct AspectJoinPoint {
+    /// Name of the function
+    pub function_name: *const c_char,
+    /// Module path of the function
+    pub module_path: *const c_char,
+    /// Source file of the function
+    pub file: *const c_char,
+    /// Line of the function in its file
+    pub line: u32,
+}
+
+/// `before` advice: returns 0 to let the call proceed, or a non-zero
+/// status to reject it with an error.
+pub type AspectBeforeFn =
+    Option<unsafe extern "C" fn(user_data: *mut c_void, jp: *const AspectJoinPoint) -> i32>;
+
+/// `after` advice, run when the call succeeds.
+pub type AspectAfterFn =
+    Option<unsafe extern "C" fn(user_data: *mut c_void, jp: *const AspectJoinPoint)>;
+
+/// `after_error` advice, run with the message of the error of a failed
+/// call.
+pub type AspectAfterErrorFn = Option<
+    unsafe extern "C" fn(user_data: *mut c_void, jp: *const AspectJoinPoint, error: *const c_char),
+>;
+
+/// Frees the user data of callbacks.
+pub type AspectDropFn = Option<unsafe extern "C" fn(user_data: *mut c_void)>;
+
+/// The advice of an aspect, as C callbacks.
+///
+/// Each callback may be null, for no such advice. `user_data` is passed to
+/// all of them and to `drop_user_data`, called once the aspect is no
+/// longer registered. Callbacks are called from any thread the library
+/// runs woven code on, possibly at the same time.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct AspectCallbacks {
+    /// Passed to every callback
+    pub user_data: *mut c_void,
+    /// Run before the function, may reject the call
+    pub before: AspectBeforeFn,
+    /// Run after the function succeeded
+    pub after: AspectAfterFn,
+    /// Run after the function failed
+    pub after_error: AspectAfterErrorFn,
+    /// Frees `user_data`
+    pub drop_user_data: AspectDropFn,
+}
+
+/// An aspect running C callbacks.
+struct FfiAspect {
+    name: String,
+    callbacks: AspectCallbacks,
+}
+
+// The callbacks are required to be callable from any thread
+unsafe impl Send for FfiAspect {}
+unsafe impl Sync for FfiAspect {}
+
+/// A join point with its strings as C strings, while a callback runs.
+struct CJoinPoint {
+    strings: [CString; 3],
+    line: u32,
+}
+
+impl CJoinPoint {
+    fn new(ctx: &JoinPoint) -> Self {
+        Self {
+            strings: [
+                c_string(ctx.function_name),
+                c_string(ctx.module_path),
+                c_string(ctx.location.file),
+            ],
+            line: ctx.location.line,
+        }
+    }
+
+    fn raw(&self) -> AspectJoinPoint {
+        AspectJoinPoint {
+            function_name: self.strings[0].as_ptr(),
+            module_path: self.strings[1].as_ptr(),
+            file: self.strings[2].as_ptr(),
+            line: self.line,
+        }
+    }
+}
+
+/// `s` as a C string, cut at its first NUL.
+fn c_string(s: &str) -> CString {
+    let end = s.find('\0').unwrap_or(s.len());
+    CString::new(&s[..end]).unwrap_or_default()
+}
+
+impl FfiAspect {
+    /// Runs the `before` callback, returning the error rejecting the call
+    /// if it does.
+    fn enter(&self, ctx: &JoinPoint) -> Result<(), AspectError> {
+        let Some(before) = self.callbacks.before else {
+            return Ok(());
+        };
+        let jp = CJoinPoint::new(ctx);
+        let status = unsafe { before(self.callbacks.user_data, &jp.raw()) };
+        if status == 0 {
+            return Ok(());
+        }
+        Err(AspectError::execution(format!(
+            "{} rejected by {} with status {}",
+            ctx.function_name, self.name, status
+        )))
+    }
+
+    fn exit(&self, ctx: &JoinPoint, result: &Result<Box<dyn Any>, AspectError>) {
+        match result {
+            Ok(value) => self.after(ctx, value.as_ref()),
+            Err(error) => self.after_error(ctx, error),
+        }
+    }
+}
+
+impl Aspect for FfiAspect {
+    fn before(&self, ctx: &JoinPoint) {
+        // Rejections need `around`
+        let _ = self.enter(ctx);
+    }
+
+    fn after(&self, ctx: &JoinPoint, _result: &dyn Any) {
+        if let Some(after) = self.callbacks.after {
+            let jp = CJoinPoint::new(ctx);
+            unsafe { after(self.callbacks.user_data, &jp.raw()) };
+        }
+    }
+
+    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
+        if let Some(after_error) = self.callbacks.after_error {
+            let jp = CJoinPoint::new(ctx);
+            let error = c_string(&error.to_string());
+            unsafe { after_error(self.callbacks.user_data, &jp.raw(), error.as_ptr()) };
+        }
+    }
+
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        self.enter(pjp.context())?;
+        let (result, ctx) = pjp.proceed_with_context();
+        self.exit(&ctx, &result);
+        result
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            self.enter(ctx)?;
+            let result = proceed.await;
+            self.exit(ctx, &result);
+            result
+        })
+    }
+}
+
+impl Drop for FfiAspect {
+    fn drop(&mut self) {
+        if let Some(drop_user_data) = self.callbacks.drop_user_data {
+            unsafe { drop_user_data(self.callbacks.user_data) };
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Returns `status`, remembering `message` as the last error of the thread.
+fn fail(status: i32, message: String) -> i32 {
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(c_string(&message)));
+    status
+}
+
+/// Reads the C string `s` as UTF-8, `None` when null or invalid.
+///
+/// # Safety
+///
+/// `s` must be null or point to a NUL-terminated string.
+unsafe fn read_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+/// Registers an aspect running `callbacks` for the functions matching
+/// `pointcut`, in the global registry.
+///
+/// `order` places it among the other aspects (lower values run first) and
+/// `name`, which may be null, identifies it in metrics and errors. The
+/// callbacks are copied. Returns [`ASPECT_OK`], or the status of the
+/// failure, whose message is then given by [`aspect_last_error`]; on
+/// failure, `drop_user_data` is not called.
+///
+/// # Safety
+///
+/// `pointcut` and `name` must be null or point to NUL-terminated strings,
+/// `callbacks` must be null or point to a valid [`AspectCallbacks`], and
+/// its callbacks must be safe to call with its `user_data` from any thread
+/// until `drop_user_data` is called.
+#[no_mangle]
+pub unsafe extern "C" fn aspect_register(
+    pointcut: *const c_char,
+    callbacks: *const AspectCallbacks,
+    order: i32,
+    name: *const c_char,
+) -> i32 {
+    let Some(expression) = read_str(pointcut) else {
+        return fail(
+            ASPECT_INVALID_ARGUMENT,
+            "pointcut is null or not UTF-8".to_string(),
+        );
+    };
+    if callbacks.is_null() {
+        return fail(ASPECT_INVALID_ARGUMENT, "callbacks are null".to_string());
+    }
+    let name = match read_str(name) {
+        Some(name) => Some(name.to_string()),
+        None if name.is_null() => None,
+        None => return fail(ASPECT_INVALID_ARGUMENT, "name is not UTF-8".to_string()),
+    };
+    let parsed = match Pointcut::parse(expression) {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            return fail(
+                ASPECT_INVALID_POINTCUT,
+                format!("invalid pointcut `{}`: {}", expression, error),
+            )
+        }
+    };
+
+    let aspect = FfiAspect {
+        name: name.clone().unwrap_or_else(|| "a C aspect".to_string()),
+        callbacks: *callbacks,
+    };
+    global_registry().register(Arc::new(aspect), parsed, order, name);
+    ASPECT_OK
+}
+
+/// Returns the message of the last failure of a function of this API on
+/// the calling thread, or null if there was none.
+///
+/// The string is valid until the next failure on the thread.
+#[no_mangle]
+pub extern "C" fn aspect_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Returns the number of aspects in the global registry.
+#[no_mangle]
+pub extern "C" fn aspect_count() -> usize {
+    global_registry().count()
+}
+
+/// Removes all aspects from the global registry, those registered from
+/// Rust included.
+#[no_mangle]
+pub extern "C" fn aspect_clear() {
+    global_registry().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_core::pointcut::FunctionInfo;
+    use aspect_core::Location;
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::Mutex;
+
+    /// What the callbacks saw, as their user data.
+    #[derive(Default)]
+    struct Seen {
+        calls: Mutex<Vec<String>>,
+        reject: AtomicI32,
+        dropped: AtomicI32,
+    }
+
+    unsafe fn seen<'a>(user_data: *mut c_void) -> &'a Seen {
+        &*(user_data as *const Seen)
+    }
+
+    unsafe fn text(s: *const c_char) -> String {
+        CStr::from_ptr(s).to_string_lossy().into_owned()
+    }
+
+    unsafe extern "C" fn before(user_data: *mut c_void, jp: *const AspectJoinPoint) -> i32 {
+        let seen = seen(user_data);
+        let jp = &*jp;
+        seen.calls.lock().unwrap().push(format!(
+            "before {}::{} {}:{}",
+            text(jp.module_path),
+            text(jp.function_name),
+            text(jp.file),
+            jp.line
+        ));
+        seen.reject.load(Ordering::Relaxed)
+    }
+
+    unsafe extern "C" fn after(user_data: *mut c_void, jp: *const AspectJoinPoint) {
+        let name = text((*jp).function_name);
+        seen(user_data)
+            .calls
+            .lock()
+            .unwrap()
+            .push(format!("after {}", name));
+    }
+
+    unsafe extern "C" fn after_error(
+        user_data: *mut c_void,
+        jp: *const AspectJoinPoint,
+        error: *const c_char,
+    ) {
+        let entry = format!("after_error {}: {}", text((*jp).function_name), text(error));
+        seen(user_data).calls.lock().unwrap().push(entry);
+    }
+
+    unsafe extern "C" fn drop_user_data(user_data: *mut c_void) {
+        seen(user_data).dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn call(ok: bool) -> Result<Box<dyn Any>, AspectError> {
+        let function = FunctionInfo::new("save_user", "app::db", "pub");
+        let ctx = JoinPoint::new(
+            "save_user",
+            "app::db",
+            Location {
+                file: "db.rs",
+                line: 3,
+            },
+        );
+        global_registry().invoke(
+            &function,
+            || ctx,
+            || {
+                if ok {
+                    Ok(Box::new(()) as Box<dyn Any>)
+                } else {
+                    Err(AspectError::execution("duplicate key"))
+                }
+            },
+        )
+    }
+
+    // A single test, as it changes the global registry
+    #[test]
+    fn test_c_aspect() {
+        let seen = Box::leak(Box::new(Seen::default()));
+        let callbacks = AspectCallbacks {
+            user_data: seen as *const Seen as *mut c_void,
+            before: Some(before),
+            after: Some(after),
+            after_error: Some(after_error),
+            drop_user_data: Some(drop_user_data),
+        };
+        let pointcut = c"execution(pub fn save*(..))";
+        let status =
+            unsafe { aspect_register(pointcut.as_ptr(), &callbacks, 0, c"audit".as_ptr()) };
+        assert_eq!(status, ASPECT_OK);
+        assert_eq!(aspect_count(), 1);
+
+        assert!(call(true).is_ok());
+        assert!(call(false).is_err());
+        seen.reject.store(7, Ordering::Relaxed);
+        let error = call(true).unwrap_err().to_string();
+        assert!(
+            error.contains("rejected by audit with status 7"),
+            "{}",
+            error
+        );
+        assert_eq!(
+            *seen.calls.lock().unwrap(),
+            [
+                "before app::db::save_user db.rs:3",
+                "after save_user",
+                "before app::db::save_user db.rs:3",
+                "after_error save_user: Execution error: duplicate key",
+                "before app::db::save_user db.rs:3",
+            ]
+        );
+
+        // Failures
+        assert!(aspect_last_error().is_null());
+        let status = unsafe { aspect_register(c"nonsense".as_ptr(), &callbacks, 0, ptr::null()) };
+        assert_eq!(status, ASPECT_INVALID_POINTCUT);
+        let message = unsafe { text(aspect_last_error()) };
+        assert!(
+            message.starts_with("invalid pointcut `nonsense`"),
+            "{}",
+            message
+        );
+        let status = unsafe { aspect_register(ptr::null(), &callbacks, 0, ptr::null()) };
+        assert_eq!(status, ASPECT_INVALID_ARGUMENT);
+        assert_eq!(seen.dropped.load(Ordering::Relaxed), 0);
+
+        aspect_clear();
+        assert_eq!(aspect_count(), 0);
+        assert_eq!(seen.dropped.load(Ordering::Relaxed), 1);
+    }
+}
@@ -4,8 +4,11 @@
 //!
 //! This crate provides runtime support for aspects, including:
 //! - Global aspect registry for managing aspect-pointcut bindings
+//! - Thread-local registries for aspects that are not `Send + Sync`
 //! - Dynamic aspect application based on pointcut patterns
 //! - Aspect ordering and composition
+//! - Execution counts of aspects, and coverage for `cargo aspect test`
+//! - A C API registering aspects with callback advice (`ffi` feature)
 //!
 //! # Example
 //!
@@ -19,10 +22,17 @@
 //! // global_registry().register(Arc::new(my_aspect), pointcut, 0, Some("logger".into()));
 //! ```
 
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod local;
 pub mod registry;
 
 // Re-export commonly used items
-pub use registry::{global_registry, AspectRegistry, RegisteredAspect, GLOBAL_REGISTRY};
+pub use local::{with_local_registry, LocalRegisteredAspect, LocalRegistry};
+pub use registry::{
+    global_registry, AspectMetrics, AspectRegistry, MatchingAspects, RegisteredAspect,
+    COVERAGE_DIR_ENV, GLOBAL_REGISTRY,
+};
 
 // Re-export once_cell for use in generated code
 pub use once_cell;
@@ -0,0 +1,322 @@
+//! Thread-local aspect registry for aspects that are not `Send + Sync`.
+//!
+//! Aspects wrapping thread-confined resources, such as GUI handles or
+//! `Rc`-based caches, implement [`LocalAspect`] rather than [`Aspect`] and
+//! cannot go in the global [`AspectRegistry`](crate::AspectRegistry). Each
+//! thread has a [`LocalRegistry`] for them instead, reached with
+//! [`with_local_registry`]: the aspects registered in it only apply to the
+//! executions woven on that thread.
+//!
+//! [`Aspect`]: aspect_core::Aspect
+
+use aspect_core::aspect::LocalAspect;
+use aspect_core::pointcut::{CompiledMatcher, FunctionInfo, Matcher, Pointcut};
+use aspect_core::reentrancy;
+use aspect_core::{AspectError, JoinPoint, ProceedingJoinPoint};
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// An aspect registered in a [`LocalRegistry`] with its pointcut and
+/// metadata.
+pub struct LocalRegisteredAspect {
+    /// The aspect instance
+    pub aspect: Rc<dyn LocalAspect>,
+
+    /// The pointcut pattern this aspect matches
+    pub pointcut: Pointcut,
+
+    /// The pointcut compiled, which functions are matched with
+    pub matcher: CompiledMatcher,
+
+    /// Execution order (lower values run first/outermost)
+    pub order: i32,
+
+    /// Optional name for debugging
+    pub name: Option<String>,
+}
+
+/// Registry of the aspects of one thread.
+///
+/// It works like [`AspectRegistry`](crate::AspectRegistry), without
+/// requiring the aspects to be `Send + Sync`; it is neither itself.
+#[derive(Default)]
+pub struct LocalRegistry {
+    /// In execution order
+    aspects: RefCell<Vec<Rc<LocalRegisteredAspect>>>,
+
+    /// The indexes in `aspects` of those matching each function
+    matches: RefCell<HashMap<FunctionInfo, Rc<[usize]>>>,
+}
+
+impl LocalRegistry {
+    /// Create a new empty registry, separate from the thread's one.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an aspect with a pointcut pattern.
+    pub fn register(
+        &self,
+        aspect: Rc<dyn LocalAspect>,
+        pointcut: Pointcut,
+        order: i32,
+        name: Option<String>,
+    ) {
+        let mut aspects = self.aspects.borrow_mut();
+        aspects.push(Rc::new(LocalRegisteredAspect {
+            aspect,
+            matcher: pointcut.compile(),
+            pointcut,
+            order,
+            name,
+        }));
+        // Sort by order (lower values first)
+        aspects.sort_by_key(|a| a.order);
+        self.matches.borrow_mut().clear();
+    }
+
+    /// Register an aspect at the order given by its
+    /// [`precedence`](LocalAspect::precedence).
+    pub fn register_by_precedence(
+        &self,
+        aspect: Rc<dyn LocalAspect>,
+        pointcut: Pointcut,
+        name: Option<String>,
+    ) {
+        let order = aspect.precedence().order();
+        self.register(aspect, pointcut, order, name);
+    }
+
+    /// Find all aspects that match the given function, in execution order.
+    pub fn find_matching(&self, function: &FunctionInfo) -> Vec<Rc<LocalRegisteredAspect>> {
+        let aspects = self.aspects.borrow();
+        let cached = self.matches.borrow().get(function).cloned();
+        let indexes = cached.unwrap_or_else(|| {
+            let framework = reentrancy::is_framework_module(function.module_path.as_str());
+            let indexes: Rc<[usize]> = aspects
+                .iter()
+                .enumerate()
+                .filter(|(_, registered)| !framework && registered.matcher.matches(function))
+                .map(|(index, _)| index)
+                .collect();
+            self.matches
+                .borrow_mut()
+                .insert(*function, Rc::clone(&indexes));
+            indexes
+        });
+        indexes
+            .iter()
+            .map(|&index| Rc::clone(&aspects[index]))
+            .collect()
+    }
+
+    /// Apply all matching aspects to a function execution, lower-order
+    /// aspects wrapping higher-order ones.
+    ///
+    /// The registry is not borrowed while the aspects run, so that their
+    /// advice can register aspects too.
+    pub fn apply_aspects(
+        &self,
+        function: &FunctionInfo,
+        pjp: ProceedingJoinPoint,
+    ) -> Result<Box<dyn Any>, AspectError> {
+        let matching = self.find_matching(function);
+
+        if matching.is_empty() {
+            return pjp.proceed();
+        }
+
+        let context = pjp.context().clone();
+        let original = reentrancy::proceeding(move || pjp.proceed());
+        weave(&matching, ProceedingJoinPoint::new(original, context))
+    }
+
+    /// Run `original` through the aspects matching `function`, building
+    /// its join point with `context` only if an aspect matches, like
+    /// [`AspectRegistry::invoke`](crate::AspectRegistry::invoke).
+    pub fn invoke<'a>(
+        &self,
+        function: &FunctionInfo,
+        context: impl FnOnce() -> JoinPoint,
+        original: impl FnOnce() -> Result<Box<dyn Any>, AspectError> + 'a,
+    ) -> Result<Box<dyn Any>, AspectError> {
+        let matching = self.find_matching(function);
+
+        if matching.is_empty() {
+            return original();
+        }
+
+        let original = reentrancy::proceeding(original);
+        weave(&matching, ProceedingJoinPoint::new(original, context()))
+    }
+
+    /// Get the number of registered aspects.
+    pub fn count(&self) -> usize {
+        self.aspects.borrow().len()
+    }
+
+    /// Clear all registered aspects (useful for testing).
+    pub fn clear(&self) {
+        self.aspects.borrow_mut().clear();
+        self.matches.borrow_mut().clear();
+    }
+}
+
+thread_local! {
+    static LOCAL_REGISTRY: LocalRegistry = LocalRegistry::new();
+}
+
+/// Run `f` with the registry of the current thread.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::pointcut::{FunctionInfo, Pointcut};
+/// use aspect_core::prelude::*;
+/// use aspect_core::aspect::LocalAspect;
+/// use aspect_runtime::local::with_local_registry;
+/// use std::any::Any;
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// struct Redraws(Rc<Cell<u32>>);
+///
+/// impl LocalAspect for Redraws {
+///     fn after(&self, _ctx: &JoinPoint, _result: &dyn Any) {
+///         self.0.set(self.0.get() + 1);
+///     }
+/// }
+///
+/// let redraws = Rc::new(Cell::new(0));
+/// with_local_registry(|registry| {
+///     let pointcut = Pointcut::parse("within(crate::ui)").unwrap();
+///     registry.register(Rc::new(Redraws(redraws.clone())), pointcut, 0, None);
+///
+///     let function = FunctionInfo::new("render", "crate::ui", "pub");
+///     let ctx = || JoinPoint::new("render", "crate::ui", Location { file: file!(), line: line!() });
+///     registry.invoke(&function, ctx, || Ok(Box::new(()) as Box<dyn Any>)).unwrap();
+/// });
+/// assert_eq!(redraws.get(), 1);
+/// ```
+pub fn with_local_registry<R>(f: impl FnOnce(&LocalRegistry) -> R) -> R {
+    LOCAL_REGISTRY.with(f)
+}
+
+/// Run `pjp`, which runs the function outside the advice, as
+/// [`proceeding`](aspect_core::reentrancy::proceeding) wraps it, through
+/// those of `matching` the reentrancy guard does not skip, with
+/// lower-order aspects wrapping higher-order ones.
+fn weave(
+    matching: &[Rc<LocalRegisteredAspect>],
+    mut pjp: ProceedingJoinPoint,
+) -> Result<Box<dyn Any>, AspectError> {
+    // Each aspect wraps the previous one, and sees the same join point
+    for registered in matching.iter().rev() {
+        if reentrancy::skips(&*registered.aspect) {
+            continue;
+        }
+        let aspect = Rc::clone(&registered.aspect);
+        let context = pjp.context().clone();
+        let inner_pjp = pjp;
+        pjp = ProceedingJoinPoint::new(
+            reentrancy::proceeding(move || {
+                reentrancy::advice(&*aspect, || aspect.around(inner_pjp))
+            }),
+            context,
+        );
+    }
+
+    pjp.proceed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_core::{Location, Precedence};
+
+    /// Records its advice in a log shared with the test, which a
+    /// `Send + Sync` aspect could not hold.
+    struct Trace {
+        name: &'static str,
+        log: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl LocalAspect for Trace {
+        fn before(&self, ctx: &JoinPoint) {
+            self.log
+                .borrow_mut()
+                .push(format!("{} {}", self.name, ctx.function_name));
+        }
+
+        fn precedence(&self) -> Precedence {
+            match self.name {
+                "auth" => Precedence::SECURITY,
+                _ => Precedence::OBSERVABILITY,
+            }
+        }
+    }
+
+    fn joinpoint() -> JoinPoint {
+        JoinPoint::new(
+            "render",
+            "crate::ui",
+            Location {
+                file: "ui.rs",
+                line: 1,
+            },
+        )
+    }
+
+    #[test]
+    fn test_local_aspects_apply_by_order() {
+        let registry = LocalRegistry::new();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        for name in ["log", "auth"] {
+            registry.register_by_precedence(
+                Rc::new(Trace {
+                    name,
+                    log: log.clone(),
+                }),
+                Pointcut::parse("within(crate::ui)").unwrap(),
+                Some(name.into()),
+            );
+        }
+
+        let render = FunctionInfo::new("render", "crate::ui", "pub");
+        let result = registry.apply_aspects(
+            &render,
+            ProceedingJoinPoint::new(|| Ok(Box::new(7) as Box<dyn Any>), joinpoint()),
+        );
+        assert_eq!(*result.unwrap().downcast::<i32>().unwrap(), 7);
+        assert_eq!(*log.borrow(), ["auth render", "log render"]);
+
+        // Functions outside the pointcut are not advised
+        let save = FunctionInfo::new("save", "crate::db", "pub");
+        assert!(registry.find_matching(&save).is_empty());
+        assert_eq!(registry.count(), 2);
+        registry.clear();
+        assert!(registry.find_matching(&render).is_empty());
+    }
+
+    #[test]
+    fn test_each_thread_has_its_own_registry() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        with_local_registry(|registry| {
+            registry.register(
+                Rc::new(Trace {
+                    name: "log",
+                    log: log.clone(),
+                }),
+                Pointcut::parse("within(crate::ui)").unwrap(),
+                0,
+                None,
+            );
+        });
+        assert_eq!(with_local_registry(LocalRegistry::count), 1);
+        let other = std::thread::spawn(|| with_local_registry(LocalRegistry::count));
+        assert_eq!(other.join().unwrap(), 0);
+        with_local_registry(LocalRegistry::clear);
+    }
+}
@@ -2,11 +2,68 @@
 //!
 //! The registry allows aspects to be registered with pointcut patterns,
 //! and then automatically applied to matching functions at runtime.
+//!
+//! The registry counts how many times each aspect runs. When
+//! `ASPECT_COVERAGE_DIR` is set (as `cargo aspect test` does), the first
+//! execution of each named aspect is also recorded in
+//! `$ASPECT_COVERAGE_DIR/<process id>.txt`, one aspect name per line, so
+//! that aspects that never ran can be reported after the test run.
+//!
+//! With [`set_measure_overhead`](AspectRegistry::set_measure_overhead), the
+//! registry also times each execution of an aspect, separating the time
+//! spent in its advice from the time spent in the function it advises, so
+//! that [`metrics`](AspectRegistry::metrics) tell the latency the aspects
+//! add without a profiler. It is off by default, as it reads the clock
+//! around every layer.
+//!
+//! The registered aspects are an immutable snapshot, replaced as a whole
+//! when aspects are registered or cleared (read-copy-update): lookups read
+//! the current snapshot without locking it, so they never wait for
+//! registrations, nor registrations for them. The aspects matching a
+//! function are cached in the snapshot, keyed by the interned symbols of
+//! its [`FunctionInfo`], in a map also replaced as a whole the first time
+//! a function is looked up, so that cache hits do not lock either.
+//!
+//! The kill switch of [`aspect_core::switch`], `ASPECT_DISABLE=1` or
+//! [`set_global_enabled`](AspectRegistry::set_global_enabled), makes the
+//! registry run executions without their aspects, but for security ones.
+//! Executions started from the advice of an aspect run without that
+//! aspect, and the functions of the framework are never matched, see
+//! [`aspect_core::reentrancy`].
+//!
+//! A registered aspect can be rolled out to a percentage of the keys
+//! executions are made for, such as user ids, with
+//! [`set_rollout`](AspectRegistry::set_rollout): executions outside the
+//! rollout run without it, see [`aspect_core::rollout`].
+//!
+//! Aspects with asynchronous advice are registered with
+//! [`register_async`](AspectRegistry::register_async), in the same registry
+//! as the others, and `async` executions are woven with
+//! [`apply_aspects_async`](AspectRegistry::apply_aspects_async).
 
-use aspect_core::pointcut::{FunctionInfo, Matcher, Pointcut};
-use aspect_core::{Aspect, ProceedingJoinPoint};
+use arc_swap::ArcSwap;
+use aspect_core::aspect::BoxFuture;
+use aspect_core::pointcut::{CompiledMatcher, FunctionInfo, Matcher, Pointcut};
+use aspect_core::rollout::Rollout;
+use aspect_core::{reentrancy, switch};
+use aspect_core::{
+    Aspect, AspectError, AspectSnapshot, AsyncAspect, JoinPoint, Precedence, ProceedingJoinPoint,
+    ReturnValue,
+};
 use once_cell::sync::Lazy;
-use std::sync::{Arc, RwLock};
+use smallvec::SmallVec;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Environment variable naming the directory aspect executions are
+/// recorded in.
+pub const COVERAGE_DIR_ENV: &str = "ASPECT_COVERAGE_DIR";
 
 /// A registered aspect with its associated pointcut and metadata.
 #[derive(Clone)]
@@ -17,11 +74,109 @@ pub struct RegisteredAspect {
     /// The pointcut pattern this aspect matches
     pub pointcut: Pointcut,
 
+    /// The pointcut compiled, which functions are matched with
+    pub matcher: CompiledMatcher,
+
     /// Execution order (lower values run first/outermost)
     pub order: i32,
 
     /// Optional name for debugging
     pub name: Option<String>,
+
+    /// Number of times the aspect ran, shared by the clones of the entry
+    pub executions: Arc<AtomicU64>,
+
+    /// The executions the aspect is rolled out to, all if `None`
+    pub rollout: Option<Rollout>,
+
+    /// The time its measured executions took, shared by the clones of the
+    /// entry
+    overhead: Arc<Overhead>,
+}
+
+impl RegisteredAspect {
+    /// Whether the aspect applies to the execution `ctx`, by its rollout.
+    fn includes(&self, ctx: &JoinPoint) -> bool {
+        self.rollout
+            .as_ref()
+            .is_none_or(|rollout| rollout.includes(ctx))
+    }
+
+    /// Count an execution of the aspect, recording the first one in the
+    /// coverage directory if there is one.
+    fn record_execution(&self) {
+        if self.executions.fetch_add(1, Ordering::Relaxed) > 0 {
+            return;
+        }
+        if let (Some(dir), Some(name)) = (std::env::var_os(COVERAGE_DIR_ENV), &self.name) {
+            // Coverage is best effort, it must not fail the program
+            let _ = record_coverage(Path::new(&dir), name);
+        }
+    }
+}
+
+/// The time spent in the executions of an aspect measured by the registry,
+/// in nanoseconds.
+#[derive(Default)]
+struct Overhead {
+    measured: AtomicU64,
+    advice: AtomicU64,
+    target: AtomicU64,
+}
+
+impl Overhead {
+    /// Record an execution which spent `total` in the aspect, `inner` of it
+    /// in what the aspect proceeded to and `target` in the advised function.
+    fn record(&self, total: Duration, inner: u64, target: u64) {
+        let total = total.as_nanos() as u64;
+        self.measured.fetch_add(1, Ordering::Relaxed);
+        self.advice
+            .fetch_add(total.saturating_sub(inner), Ordering::Relaxed);
+        self.target.fetch_add(target, Ordering::Relaxed);
+    }
+}
+
+/// The aspects matching a function, in execution order.
+///
+/// Up to two are stored inline, so that looking up the aspects of most
+/// functions does not allocate.
+pub type MatchingAspects = SmallVec<[Arc<RegisteredAspect>; 2]>;
+
+/// Execution statistics of a registered aspect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AspectMetrics {
+    pub name: Option<String>,
+    pub order: i32,
+    pub executions: u64,
+
+    /// Executions timed while overhead was measured, see
+    /// [`AspectRegistry::set_measure_overhead`]
+    pub measured: u64,
+
+    /// Time the measured executions spent in the advice of the aspect, not
+    /// counting the aspects it wraps nor the advised function
+    pub advice_time: Duration,
+
+    /// Time the measured executions spent in the advised function
+    pub target_time: Duration,
+}
+
+impl AspectMetrics {
+    /// The latency the aspect adds to an execution on average, `None` if
+    /// none was measured.
+    pub fn mean_overhead(&self) -> Option<Duration> {
+        let measured = u32::try_from(self.measured).ok().filter(|&n| n > 0)?;
+        Some(self.advice_time / measured)
+    }
+
+    /// The time spent in the advice of the aspect relative to the time
+    /// spent in the function it advises, `None` if none was measured.
+    pub fn overhead_ratio(&self) -> Option<f64> {
+        if self.measured == 0 || self.target_time.is_zero() {
+            return None;
+        }
+        Some(self.advice_time.as_secs_f64() / self.target_time.as_secs_f64())
+    }
 }
 
 /// Global aspect registry for managing aspect-pointcut bindings.
@@ -29,14 +184,38 @@ pub struct RegisteredAspect {
 /// The registry is thread-safe and can be accessed from anywhere in the program.
 /// Aspects are matched against functions using their pointcut patterns.
 pub struct AspectRegistry {
-    aspects: RwLock<Vec<RegisteredAspect>>,
+    snapshot: ArcSwap<Snapshot>,
+
+    /// Whether executions are timed, see
+    /// [`set_measure_overhead`](Self::set_measure_overhead)
+    measure_overhead: AtomicBool,
+}
+
+/// The registered aspects at some point.
+#[derive(Default)]
+struct Snapshot {
+    /// In execution order, shared with the snapshots that follow
+    aspects: Vec<Arc<RegisteredAspect>>,
+
+    /// The indexes in `aspects` of those matching each function
+    matches: ArcSwap<HashMap<FunctionInfo, Arc<[usize]>>>,
+}
+
+impl Snapshot {
+    fn new(aspects: Vec<Arc<RegisteredAspect>>) -> Self {
+        Self {
+            aspects,
+            matches: ArcSwap::default(),
+        }
+    }
 }
 
 impl AspectRegistry {
-    /// Create a new empty registry.
-    fn new() -> Self {
+    /// Create a new empty registry, separate from the global one.
+    pub fn new() -> Self {
         Self {
-            aspects: RwLock::new(Vec::new()),
+            snapshot: ArcSwap::from_pointee(Snapshot::default()),
+            measure_overhead: AtomicBool::new(false),
         }
     }
 
@@ -60,27 +239,134 @@ impl AspectRegistry {
         order: i32,
         name: Option<String>,
     ) {
-        let mut aspects = self.aspects.write().unwrap();
-        aspects.push(RegisteredAspect {
+        let registered = Arc::new(RegisteredAspect {
             aspect,
+            matcher: pointcut.compile(),
             pointcut,
             order,
             name,
+            executions: Arc::new(AtomicU64::new(0)),
+            rollout: None,
+            overhead: Arc::default(),
         });
+        self.snapshot.rcu(|current| {
+            let mut aspects = current.aspects.clone();
+            aspects.push(Arc::clone(&registered));
 
-        // Sort by order (lower values first)
-        aspects.sort_by_key(|a| a.order);
+            // Sort by order (lower values first)
+            aspects.sort_by_key(|a| a.order);
+            Snapshot::new(aspects)
+        });
+    }
+
+    /// Register an aspect at the order given by its
+    /// [`precedence`](Aspect::precedence).
+    ///
+    /// Aspects registered this way compose by their class, so that e.g.
+    /// authorization wraps caching, which wraps logging, whatever order they
+    /// are registered in. Aspects with the same precedence run in
+    /// registration order.
+    pub fn register_by_precedence(
+        &self,
+        aspect: Arc<dyn Aspect>,
+        pointcut: Pointcut,
+        name: Option<String>,
+    ) {
+        let order = aspect.precedence().order();
+        self.register(aspect, pointcut, order, name);
+    }
+
+    /// Register an aspect with asynchronous advice with a pointcut pattern.
+    ///
+    /// In [`apply_aspects_async`](Self::apply_aspects_async), its
+    /// [`before_async`](AsyncAspect::before_async) is awaited right after
+    /// `before`, and an error prevents the execution, as `#[aspect]` does
+    /// for `async fn`s. Synchronous executions only run its [`Aspect`]
+    /// advice.
+    ///
+    /// Lists mixing both kinds of aspects can lift the synchronous ones
+    /// with [`AsyncAdapter`](aspect_core::aspect::AsyncAdapter) to register
+    /// them all here.
+    pub fn register_async(
+        &self,
+        aspect: Arc<dyn AsyncAspect>,
+        pointcut: Pointcut,
+        order: i32,
+        name: Option<String>,
+    ) {
+        self.register(Arc::new(BeforeAsync(aspect)), pointcut, order, name);
+    }
+
+    /// Roll the aspects registered as `name` out to the executions
+    /// `rollout` includes, or to all of them with `None`, returning whether
+    /// there are any.
+    ///
+    /// Each key gets the same decision on every execution, so raising the
+    /// percentage step by step canaries a new aspect on a growing set of
+    /// users. Executions outside the rollout run as if the aspect was not
+    /// registered, and are not counted in its [`metrics`](Self::metrics).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use aspect_core::rollout::Rollout;
+    /// use aspect_runtime::AspectRegistry;
+    ///
+    /// let registry = AspectRegistry::new();
+    /// // registry.register(Arc::new(audit), pointcut, 0, Some("audit".into()));
+    /// let by_user = Rollout::new(5.0, |ctx| {
+    ///     ctx.args.first()?.value::<u64>().map(|id| id.to_string())
+    /// });
+    /// registry.set_rollout("audit", Some(by_user));
+    /// ```
+    pub fn set_rollout(&self, name: &str, rollout: Option<Rollout>) -> bool {
+        let mut found = false;
+        self.snapshot.rcu(|current| {
+            found = false;
+            let aspects = current
+                .aspects
+                .iter()
+                .map(|registered| {
+                    if registered.name.as_deref() != Some(name) {
+                        return Arc::clone(registered);
+                    }
+                    found = true;
+                    Arc::new(RegisteredAspect {
+                        rollout: rollout.clone(),
+                        ..RegisteredAspect::clone(registered)
+                    })
+                })
+                .collect();
+            Snapshot::new(aspects)
+        });
+        found
     }
 
     /// Find all aspects that match the given function.
     ///
     /// Returns aspects in execution order (sorted by `order` field).
-    pub fn find_matching(&self, function: &FunctionInfo) -> Vec<RegisteredAspect> {
-        let aspects = self.aspects.read().unwrap();
-        aspects
+    pub fn find_matching(&self, function: &FunctionInfo) -> MatchingAspects {
+        let snapshot = self.snapshot.load();
+        let cached = snapshot.matches.load().get(function).cloned();
+        let indexes = cached.unwrap_or_else(|| {
+            let framework = reentrancy::is_framework_module(function.module_path.as_str());
+            let indexes: Arc<[usize]> = snapshot
+                .aspects
+                .iter()
+                .enumerate()
+                .filter(|(_, registered)| !framework && registered.matcher.matches(function))
+                .map(|(index, _)| index)
+                .collect();
+            snapshot.matches.rcu(|matches| {
+                let mut matches = HashMap::clone(matches);
+                matches.insert(*function, Arc::clone(&indexes));
+                matches
+            });
+            indexes
+        });
+        indexes
             .iter()
-            .filter(|registered| registered.pointcut.matches(function))
-            .cloned()
+            .map(|&index| Arc::clone(&snapshot.aspects[index]))
             .collect()
     }
 
@@ -90,8 +376,8 @@ impl AspectRegistry {
     pub fn apply_aspects(
         &self,
         function: &FunctionInfo,
-        mut pjp: ProceedingJoinPoint,
-    ) -> Result<Box<dyn std::any::Any>, aspect_core::AspectError> {
+        pjp: ProceedingJoinPoint,
+    ) -> Result<Box<dyn Any>, AspectError> {
         let matching = self.find_matching(function);
 
         if matching.is_empty() {
@@ -99,30 +385,393 @@ impl AspectRegistry {
             return pjp.proceed();
         }
 
-        // Apply aspects in order (outermost first)
-        // Each aspect wraps the previous one
+        let context = pjp.context().clone();
+        let original = reentrancy::proceeding(move || pjp.proceed());
+        let pjp = ProceedingJoinPoint::new(original, context);
+        weave(matching, pjp, self.measures_overhead())
+    }
+
+    /// Run `original` through the aspects matching `function`, like
+    /// [`apply_aspects`](Self::apply_aspects), building its join point with
+    /// `context` only if an aspect matches: calls of functions that no
+    /// aspect applies to cost the lookup of their aspects and nothing else.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use aspect_core::pointcut::FunctionInfo;
+    /// use aspect_core::{JoinPoint, Location};
+    /// use aspect_runtime::AspectRegistry;
+    /// use std::any::Any;
+    ///
+    /// let registry = AspectRegistry::new();
+    /// let function = FunctionInfo::new("save_user", "crate::db", "pub");
+    /// let result = registry.invoke(
+    ///     &function,
+    ///     || JoinPoint::new("save_user", "crate::db", Location { file: file!(), line: line!() }),
+    ///     || Ok(Box::new(42) as Box<dyn Any>),
+    /// );
+    /// assert_eq!(*result.unwrap().downcast::<i32>().unwrap(), 42);
+    /// ```
+    pub fn invoke<'a>(
+        &self,
+        function: &FunctionInfo,
+        context: impl FnOnce() -> JoinPoint,
+        original: impl FnOnce() -> Result<Box<dyn Any>, AspectError> + 'a,
+    ) -> Result<Box<dyn Any>, AspectError> {
+        let matching = self.find_matching(function);
+
+        if matching.is_empty() {
+            return original();
+        }
+
+        let original = reentrancy::proceeding(original);
+        let pjp = ProceedingJoinPoint::new(original, context());
+        weave(matching, pjp, self.measures_overhead())
+    }
+
+    /// Apply all matching aspects to an asynchronous execution, `proceed`,
+    /// through their [`around_async`](Aspect::around_async) advice.
+    ///
+    /// Like [`apply_aspects`](Self::apply_aspects), lower-order aspects
+    /// wrap higher-order ones.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use aspect_core::pointcut::FunctionInfo;
+    /// use aspect_core::{JoinPoint, Location};
+    /// use aspect_runtime::AspectRegistry;
+    /// use std::any::Any;
+    ///
+    /// let registry = AspectRegistry::new();
+    /// let function = FunctionInfo::new("save_user", "crate::db", "pub");
+    /// let ctx = JoinPoint::new("save_user", "crate::db", Location { file: file!(), line: line!() });
+    /// let future = registry.apply_aspects_async(
+    ///     &function,
+    ///     &ctx,
+    ///     Box::pin(async { Ok(Box::new(42) as Box<dyn Any>) }),
+    /// );
+    /// // Awaited by the executor of the program
+    /// # drop(future);
+    /// ```
+    pub fn apply_aspects_async<'a>(
+        &self,
+        function: &FunctionInfo,
+        ctx: &'a JoinPoint,
+        mut proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        let mut matching = self.find_matching(function);
+        matching.retain(|registered| {
+            registered.includes(ctx) && !reentrancy::skips(&*registered.aspect)
+        });
+        for registered in &matching {
+            registered.record_execution();
+        }
+
+        let mut target = None;
+        if self.measures_overhead() {
+            let nanos = Arc::new(AtomicU64::new(0));
+            proceed = timed_async(proceed, Arc::clone(&nanos));
+            target = Some(nanos);
+        }
         for registered in matching.iter().rev() {
             let aspect = Arc::clone(&registered.aspect);
-            let inner_pjp = pjp;
-
-            // Create a new ProceedingJoinPoint that wraps the aspect application
-            pjp = ProceedingJoinPoint::new(
-                move || aspect.around(inner_pjp),
-                function_info_to_joinpoint(function),
-            );
+            let inner = proceed;
+            proceed = match &target {
+                None => Box::pin(async move { aspect.around_async(ctx, inner).await }),
+                Some(target) => {
+                    let overhead = Arc::clone(&registered.overhead);
+                    let target = Arc::clone(target);
+                    let nanos = Arc::new(AtomicU64::new(0));
+                    let inner = timed_async(inner, Arc::clone(&nanos));
+                    Box::pin(async move {
+                        let start = Instant::now();
+                        let result = aspect.around_async(ctx, inner).await;
+                        overhead.record(
+                            start.elapsed(),
+                            nanos.load(Ordering::Relaxed),
+                            target.load(Ordering::Relaxed),
+                        );
+                        result
+                    })
+                }
+            };
         }
 
-        pjp.proceed()
+        proceed
     }
 
     /// Get the number of registered aspects.
     pub fn count(&self) -> usize {
-        self.aspects.read().unwrap().len()
+        self.snapshot.load().aspects.len()
+    }
+
+    /// Turn the aspects of the program on or off, as the kill switch of
+    /// [`aspect_core::switch`]: those of every registry and those woven
+    /// with `#[aspect]`.
+    ///
+    /// While they are off, executions run without the aspects matching
+    /// them; it is an emergency way to bypass faulty advice without
+    /// redeploying, as `ASPECT_DISABLE=1` is at startup. Aspects of
+    /// [`Precedence::SECURITY`](aspect_core::Precedence::SECURITY), such
+    /// as authorization, keep running.
+    pub fn set_global_enabled(&self, enabled: bool) {
+        switch::set_enabled(enabled);
+    }
+
+    /// Whether aspects are enabled, see
+    /// [`set_global_enabled`](Self::set_global_enabled).
+    pub fn global_enabled(&self) -> bool {
+        switch::enabled()
+    }
+
+    /// Time the executions woven from now on, or stop timing them.
+    ///
+    /// Each layer of advice is timed, as well as the function it advises,
+    /// and the time spent in the advice of each aspect, without the layers
+    /// it wraps, is added up in its [`metrics`](Self::metrics). Times are
+    /// wall-clock: the advice of `async` executions includes the time they
+    /// spend waiting.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use aspect_runtime::AspectRegistry;
+    ///
+    /// let registry = AspectRegistry::new();
+    /// registry.set_measure_overhead(true);
+    /// // ... run the program ...
+    /// for metrics in registry.metrics() {
+    ///     if let Some(overhead) = metrics.mean_overhead() {
+    ///         println!("{:?} adds {:?}", metrics.name, overhead);
+    ///     }
+    /// }
+    /// ```
+    pub fn set_measure_overhead(&self, enabled: bool) {
+        self.measure_overhead.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Whether executions are timed, see
+    /// [`set_measure_overhead`](Self::set_measure_overhead).
+    pub fn measures_overhead(&self) -> bool {
+        self.measure_overhead.load(Ordering::Relaxed)
+    }
+
+    /// Execution statistics of the registered aspects, in execution order.
+    pub fn metrics(&self) -> Vec<AspectMetrics> {
+        self.snapshot
+            .load()
+            .aspects
+            .iter()
+            .map(|registered| {
+                let overhead = &registered.overhead;
+                AspectMetrics {
+                    name: registered.name.clone(),
+                    order: registered.order,
+                    executions: registered.executions.load(Ordering::Relaxed),
+                    measured: overhead.measured.load(Ordering::Relaxed),
+                    advice_time: Duration::from_nanos(overhead.advice.load(Ordering::Relaxed)),
+                    target_time: Duration::from_nanos(overhead.target.load(Ordering::Relaxed)),
+                }
+            })
+            .collect()
+    }
+
+    /// The live state of the registered aspects, in execution order, each
+    /// named as it was registered.
+    ///
+    /// Displayed, each snapshot is a JSON object; listed in a
+    /// [`SnapshotValue`](aspect_core::snapshot::SnapshotValue), they are a
+    /// JSON array, e.g. for a debugging endpoint:
+    ///
+    /// ```rust
+    /// use aspect_core::snapshot::SnapshotValue;
+    /// use aspect_runtime::registry::global_registry;
+    ///
+    /// let json = SnapshotValue::from(global_registry().snapshot_all()).to_string();
+    /// println!("{}", json);
+    /// ```
+    pub fn snapshot_all(&self) -> Vec<AspectSnapshot> {
+        self.snapshot
+            .load()
+            .aspects
+            .iter()
+            .map(|registered| {
+                let snapshot = registered.aspect.snapshot();
+                match &registered.name {
+                    Some(name) => snapshot.named(name.as_str()),
+                    None => snapshot,
+                }
+            })
+            .collect()
+    }
+
+    /// Forget the cached matches, so that functions are matched against
+    /// the pointcuts again (useful for benchmarking matching).
+    pub fn clear_cache(&self) {
+        self.snapshot.load().matches.store(Arc::default());
     }
 
     /// Clear all registered aspects (useful for testing).
     pub fn clear(&self) {
-        self.aspects.write().unwrap().clear();
+        self.snapshot.store(Arc::default());
+    }
+
+    /// The registered aspects, in execution order.
+    pub fn aspects(&self) -> Vec<Arc<RegisteredAspect>> {
+        self.snapshot.load().aspects.clone()
+    }
+
+    /// Replace the registered aspects with `aspects`, e.g. those returned
+    /// by [`aspects`](Self::aspects) earlier, keeping their execution
+    /// counts.
+    pub fn restore(&self, mut aspects: Vec<Arc<RegisteredAspect>>) {
+        aspects.sort_by_key(|a| a.order);
+        self.snapshot.store(Arc::new(Snapshot::new(aspects)));
+    }
+}
+
+impl Default for AspectRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Run `pjp`, which runs the function outside the advice, as
+/// [`proceeding`](aspect_core::reentrancy::proceeding) wraps it, through
+/// those of `matching` its execution is rolled out to and the reentrancy
+/// guard does not skip, with lower-order aspects wrapping higher-order
+/// ones, timing each of them if `measure`.
+fn weave(
+    mut matching: MatchingAspects,
+    mut pjp: ProceedingJoinPoint,
+    measure: bool,
+) -> Result<Box<dyn Any>, AspectError> {
+    matching.retain(|registered| {
+        registered.includes(pjp.context()) && !reentrancy::skips(&*registered.aspect)
+    });
+    for registered in &matching {
+        registered.record_execution();
+    }
+
+    let mut target = None;
+    if measure {
+        let nanos = Arc::new(AtomicU64::new(0));
+        pjp = timed(pjp, Arc::clone(&nanos));
+        target = Some(nanos);
+    }
+
+    // Apply aspects in order (outermost first)
+    // Each aspect wraps the previous one, and sees the same join point
+    for registered in matching.iter().rev() {
+        let aspect = Arc::clone(&registered.aspect);
+        let context = pjp.context().clone();
+        let inner_pjp = pjp;
+
+        // Create a new ProceedingJoinPoint that wraps the aspect application
+        pjp = match &target {
+            None => ProceedingJoinPoint::new(
+                reentrancy::proceeding(move || {
+                    reentrancy::advice(&*aspect, || aspect.around(inner_pjp))
+                }),
+                context,
+            ),
+            Some(target) => {
+                let overhead = Arc::clone(&registered.overhead);
+                let target = Arc::clone(target);
+                let nanos = Arc::new(AtomicU64::new(0));
+                let inner_pjp = timed(inner_pjp, Arc::clone(&nanos));
+                let advice = reentrancy::proceeding(move || {
+                    let start = Instant::now();
+                    let result = reentrancy::advice(&*aspect, || aspect.around(inner_pjp));
+                    overhead.record(
+                        start.elapsed(),
+                        nanos.load(Ordering::Relaxed),
+                        target.load(Ordering::Relaxed),
+                    );
+                    result
+                });
+                ProceedingJoinPoint::new(advice, context)
+            }
+        };
+    }
+
+    pjp.proceed()
+}
+
+/// `pjp`, adding the time it takes to proceed to `nanos`.
+fn timed(pjp: ProceedingJoinPoint<'_>, nanos: Arc<AtomicU64>) -> ProceedingJoinPoint<'_> {
+    let context = pjp.context().clone();
+    let proceed = move || {
+        let start = Instant::now();
+        let result = pjp.proceed();
+        nanos.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        result
+    };
+    ProceedingJoinPoint::new(proceed, context)
+}
+
+/// `proceed`, adding the time it takes to complete to `nanos`.
+fn timed_async(
+    proceed: BoxFuture<'_, Result<Box<dyn Any>, AspectError>>,
+    nanos: Arc<AtomicU64>,
+) -> BoxFuture<'_, Result<Box<dyn Any>, AspectError>> {
+    Box::pin(async move {
+        let start = Instant::now();
+        let result = proceed.await;
+        nanos.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        result
+    })
+}
+
+/// An aspect registered with [`AspectRegistry::register_async`], whose
+/// `around_async` awaits `before_async` before proceeding.
+struct BeforeAsync(Arc<dyn AsyncAspect>);
+
+impl Aspect for BeforeAsync {
+    fn before(&self, ctx: &JoinPoint) {
+        self.0.before(ctx)
+    }
+
+    fn after(&self, ctx: &JoinPoint, result: &dyn Any) {
+        self.0.after(ctx, result)
+    }
+
+    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
+        self.0.after_error(ctx, error)
+    }
+
+    fn after_returning_mut(&self, ctx: &JoinPoint, result: &mut ReturnValue<'_>) {
+        self.0.after_returning_mut(ctx, result)
+    }
+
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        self.0.around(pjp)
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        let proceed = Box::pin(async move {
+            self.0.before_async(ctx).await?;
+            proceed.await
+        });
+        self.0.around_async(ctx, proceed)
+    }
+
+    fn precedence(&self) -> Precedence {
+        self.0.precedence()
+    }
+
+    fn reads_args(&self) -> bool {
+        self.0.reads_args()
+    }
+
+    fn snapshot(&self) -> AspectSnapshot {
+        self.0.snapshot()
     }
 }
 
@@ -145,16 +794,15 @@ pub fn global_registry() -> &'static AspectRegistry {
     &GLOBAL_REGISTRY
 }
 
-/// Helper to convert FunctionInfo to JoinPoint
-fn function_info_to_joinpoint(info: &FunctionInfo) -> aspect_core::JoinPoint {
-    aspect_core::JoinPoint {
-        function_name: Box::leak(info.name.clone().into_boxed_str()),
-        module_path: Box::leak(info.module_path.clone().into_boxed_str()),
-        location: aspect_core::Location {
-            file: "unknown",
-            line: 0,
-        },
-    }
+/// Record that the aspect `name` ran, in the file of this process in the
+/// coverage directory `dir`.
+fn record_coverage(dir: &Path, name: &str) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(format!("{}.txt", std::process::id())))?;
+    writeln!(file, "{}", name)
 }
 
 #[cfg(test)]
@@ -202,9 +850,9 @@ mod tests {
         assert_eq!(registry.count(), 1);
 
         let function = FunctionInfo {
-            name: "test_func".to_string(),
-            module_path: "test::module".to_string(),
-            visibility: "pub".to_string(),
+            name: "test_func".into(),
+            module_path: "test::module".into(),
+            visibility: "pub".into(),
             return_type: None,
         };
 
@@ -236,9 +884,9 @@ mod tests {
         registry.register(aspect1, pointcut, 10, Some("first".into()));
 
         let function = FunctionInfo {
-            name: "test_func".to_string(),
-            module_path: "test::module".to_string(),
-            visibility: "pub".to_string(),
+            name: "test_func".into(),
+            module_path: "test::module".into(),
+            visibility: "pub".into(),
             return_type: None,
         };
 
@@ -246,6 +894,53 @@ mod tests {
         assert_eq!(matching.len(), 2);
         assert_eq!(matching[0].name.as_deref(), Some("first"));
         assert_eq!(matching[1].name.as_deref(), Some("second"));
+        assert!(!matching.spilled());
+    }
+
+    #[test]
+    fn test_register_by_precedence() {
+        struct Classed(Precedence);
+
+        impl Aspect for Classed {
+            fn precedence(&self) -> Precedence {
+                self.0
+            }
+        }
+
+        let registry = AspectRegistry::new();
+        let pointcut = Pointcut::parse("execution(pub fn *(..))").unwrap();
+        for (name, precedence) in [
+            ("logging", Precedence::OBSERVABILITY),
+            ("caching", Precedence::CACHING),
+            ("auth", Precedence::SECURITY),
+            ("plain", Precedence::DEFAULT),
+        ] {
+            registry.register_by_precedence(
+                Arc::new(Classed(precedence)),
+                pointcut.clone(),
+                Some(name.into()),
+            );
+        }
+        // An explicit order still takes its place among them
+        registry.register(
+            Arc::new(Classed(Precedence::DEFAULT)),
+            pointcut,
+            -250,
+            Some("retry".into()),
+        );
+
+        let function = FunctionInfo {
+            name: "test_func".into(),
+            module_path: "test::module".into(),
+            visibility: "pub".into(),
+            return_type: None,
+        };
+        let names: Vec<_> = registry
+            .find_matching(&function)
+            .iter()
+            .map(|a| a.name.clone().unwrap())
+            .collect();
+        assert_eq!(names, ["auth", "retry", "caching", "plain", "logging"]);
     }
 
     #[test]
@@ -263,29 +958,470 @@ mod tests {
 
         // Should match
         let func1 = FunctionInfo {
-            name: "save_user".to_string(),
-            module_path: "crate::api".to_string(),
-            visibility: "pub".to_string(),
+            name: "save_user".into(),
+            module_path: "crate::api".into(),
+            visibility: "pub".into(),
             return_type: None,
         };
         assert_eq!(registry.find_matching(&func1).len(), 1);
 
         // Should not match (wrong module)
         let func2 = FunctionInfo {
-            name: "save_user".to_string(),
-            module_path: "crate::internal".to_string(),
-            visibility: "pub".to_string(),
+            name: "save_user".into(),
+            module_path: "crate::internal".into(),
+            visibility: "pub".into(),
             return_type: None,
         };
         assert_eq!(registry.find_matching(&func2).len(), 0);
 
         // Should not match (not public)
         let func3 = FunctionInfo {
-            name: "save_user".to_string(),
-            module_path: "crate::api".to_string(),
-            visibility: "".to_string(),
+            name: "save_user".into(),
+            module_path: "crate::api".into(),
+            visibility: "".into(),
             return_type: None,
         };
         assert_eq!(registry.find_matching(&func3).len(), 0);
     }
+
+    #[test]
+    fn test_matches_cache() {
+        let registry = AspectRegistry::new();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let aspect = |name: &str| {
+            Arc::new(TestAspect {
+                name: name.to_string(),
+                called: calls.clone(),
+            })
+        };
+        let function = FunctionInfo::new("save_user", "crate::api", "pub");
+
+        registry.register(
+            aspect("db"),
+            Pointcut::parse("within(crate::db)").unwrap(),
+            0,
+            None,
+        );
+        assert_eq!(registry.find_matching(&function).len(), 0);
+        assert_eq!(registry.snapshot.load().matches.load().len(), 1);
+
+        // Registering forgets the cached matches
+        let pointcut = Pointcut::parse("within(crate::api)").unwrap();
+        registry.register(aspect("api"), pointcut, -1, Some("api".into()));
+        let matching = registry.find_matching(&function);
+        assert_eq!(matching.len(), 1);
+        assert_eq!(matching[0].name.as_deref(), Some("api"));
+        assert_eq!(registry.find_matching(&function).len(), 1);
+
+        registry.clear_cache();
+        assert!(registry.snapshot.load().matches.load().is_empty());
+        assert_eq!(registry.find_matching(&function).len(), 1);
+
+        registry.clear();
+        assert!(registry.snapshot.load().matches.load().is_empty());
+        assert_eq!(registry.find_matching(&function).len(), 0);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let registry = AspectRegistry::new();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        for (name, pointcut) in [
+            ("api", "execution(pub fn *(..)) && within(crate::api)"),
+            ("db", "within(crate::db)"),
+        ] {
+            let aspect = Arc::new(TestAspect {
+                name: name.to_string(),
+                called: calls.clone(),
+            });
+            let pointcut = Pointcut::parse(pointcut).unwrap();
+            registry.register(aspect, pointcut, 0, Some(name.into()));
+        }
+
+        let function = FunctionInfo {
+            name: "save_user".into(),
+            module_path: "crate::api".into(),
+            visibility: "pub".into(),
+            return_type: None,
+        };
+        for _ in 0..2 {
+            let pjp = ProceedingJoinPoint::new(
+                || Ok(Box::new(()) as Box<dyn Any>),
+                function.join_point(),
+            );
+            registry.apply_aspects(&function, pjp).unwrap();
+        }
+
+        let executions: Vec<_> = registry
+            .metrics()
+            .into_iter()
+            .map(|metrics| (metrics.name.unwrap(), metrics.executions))
+            .collect();
+        assert_eq!(executions, [("api".to_string(), 2), ("db".to_string(), 0)]);
+    }
+
+    #[test]
+    fn test_measure_overhead() {
+        struct Slow;
+
+        impl Aspect for Slow {
+            fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+                std::thread::sleep(Duration::from_millis(10));
+                pjp.proceed()
+            }
+        }
+
+        let registry = AspectRegistry::new();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let light = Arc::new(TestAspect {
+            name: "light".to_string(),
+            called: calls.clone(),
+        });
+        let pointcut = Pointcut::parse("within(crate::api)").unwrap();
+        registry.register(light, pointcut.clone(), 0, Some("light".into()));
+        registry.register(Arc::new(Slow), pointcut, 1, Some("slow".into()));
+
+        let function = FunctionInfo::new("save_user", "crate::api", "pub");
+        let run = || {
+            registry.invoke(
+                &function,
+                || function.join_point(),
+                || {
+                    std::thread::sleep(Duration::from_millis(10));
+                    Ok(Box::new(()) as Box<dyn Any>)
+                },
+            )
+        };
+        run().unwrap();
+        assert!(!registry.measures_overhead());
+        assert!(registry
+            .metrics()
+            .iter()
+            .all(|metrics| metrics.measured == 0 && metrics.mean_overhead().is_none()));
+
+        registry.set_measure_overhead(true);
+        run().unwrap();
+        run().unwrap();
+        let metrics = registry.metrics();
+        let (light, slow) = (&metrics[0], &metrics[1]);
+        assert_eq!((light.executions, light.measured), (3, 2));
+        assert_eq!((slow.executions, slow.measured), (3, 2));
+
+        // Each aspect is charged its own advice only, not the layers it wraps
+        assert!(slow.advice_time >= Duration::from_millis(20));
+        assert!(slow.mean_overhead().unwrap() >= Duration::from_millis(10));
+        assert!(light.advice_time < slow.advice_time);
+        for metrics in &metrics {
+            assert!(metrics.target_time >= Duration::from_millis(20));
+        }
+        assert!(slow.overhead_ratio().unwrap() > light.overhead_ratio().unwrap());
+    }
+
+    #[test]
+    fn test_snapshot_all() {
+        struct Counter;
+
+        impl Aspect for Counter {
+            fn snapshot(&self) -> AspectSnapshot {
+                AspectSnapshot::new("Counter").with("calls", 3u64)
+            }
+        }
+
+        let registry = AspectRegistry::new();
+        let pointcut = Pointcut::parse("within(crate::api)").unwrap();
+        registry.register(Arc::new(Counter), pointcut.clone(), 0, Some("api".into()));
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let aspect = Arc::new(TestAspect {
+            name: "log".to_string(),
+            called: calls,
+        });
+        registry.register(aspect, pointcut, 1, None);
+
+        let snapshots = registry.snapshot_all();
+        assert_eq!(
+            snapshots[0].to_string(),
+            r#"{"aspect":"Counter","name":"api","state":{"calls":3}}"#
+        );
+        // Aspects without state are listed by type
+        assert!(snapshots[1].aspect().ends_with("TestAspect"));
+        assert!(snapshots[1].name().is_none() && snapshots[1].state().is_empty());
+    }
+
+    #[test]
+    fn test_invoke() {
+        let registry = AspectRegistry::new();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        for (name, order) in [("outer", 0), ("inner", 1)] {
+            let aspect = Arc::new(TestAspect {
+                name: name.to_string(),
+                called: calls.clone(),
+            });
+            let pointcut = Pointcut::parse("within(crate::api)").unwrap();
+            registry.register(aspect, pointcut, order, Some(name.into()));
+        }
+        let context = || {
+            JoinPoint::new(
+                "handler",
+                "crate::api",
+                aspect_core::Location {
+                    file: "api.rs",
+                    line: 7,
+                },
+            )
+        };
+
+        // No aspect matches: the join point is never built
+        let function = FunctionInfo::new("query", "crate::db", "pub");
+        let result = registry.invoke(
+            &function,
+            || unreachable!("join point of an unadvised call"),
+            || Ok(Box::new(1) as Box<dyn Any>),
+        );
+        assert_eq!(*result.unwrap().downcast::<i32>().unwrap(), 1);
+        assert!(calls.lock().unwrap().is_empty());
+
+        // Every matching aspect sees the join point built
+        let function = FunctionInfo::new("save_user", "crate::api", "pub");
+        let result = registry.invoke(&function, context, || Ok(Box::new(2) as Box<dyn Any>));
+        assert_eq!(*result.unwrap().downcast::<i32>().unwrap(), 2);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                "outer:before:handler",
+                "inner:before:handler",
+                "inner:after:handler",
+                "outer:after:handler",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rollout() {
+        let registry = AspectRegistry::new();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        for name in ["stable", "canary"] {
+            let aspect = Arc::new(TestAspect {
+                name: name.to_string(),
+                called: calls.clone(),
+            });
+            let pointcut = Pointcut::parse("within(crate::api)").unwrap();
+            registry.register(aspect, pointcut, 0, Some(name.into()));
+        }
+        let by_user = Rollout::new(50.0, |ctx| {
+            ctx.args.first()?.value::<u64>().map(|id| id.to_string())
+        });
+        assert!(registry.set_rollout("canary", Some(by_user)));
+        assert!(!registry.set_rollout("missing", None));
+
+        let function = FunctionInfo::new("save_user", "crate::api", "pub");
+        let call = |user: u64| {
+            let mut context = function.join_point();
+            context.args.push(aspect_core::Arg::new("user", &user));
+            registry
+                .invoke(&function, || context, || Ok(Box::new(()) as Box<dyn Any>))
+                .unwrap();
+            std::mem::take(&mut *calls.lock().unwrap())
+        };
+
+        let mut canaried = 0;
+        for user in 0..100 {
+            let seen = call(user);
+            let included = aspect_core::rollout::includes(&user.to_string(), 50.0);
+            assert_eq!(seen.iter().any(|c| c.starts_with("canary:")), included);
+            assert!(seen.contains(&"stable:before:save_user".to_string()));
+            // The same user gets the same decision
+            assert_eq!(call(user), seen);
+            canaried += included as u64;
+        }
+        let executions: Vec<_> = registry
+            .metrics()
+            .into_iter()
+            .map(|metrics| metrics.executions)
+            .collect();
+        assert_eq!(executions, [200, canaried * 2]);
+
+        // Back to every execution
+        assert!(registry.set_rollout("canary", None));
+        assert_eq!(call(1).len(), 4);
+    }
+
+    #[test]
+    fn test_reentrancy() {
+        static REGISTRY: Lazy<AspectRegistry> = Lazy::new(AspectRegistry::new);
+        static CALLS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+        /// Calls `helper`, which it advises, from its advice.
+        struct Reentrant;
+
+        impl Aspect for Reentrant {
+            fn before(&self, ctx: &JoinPoint) {
+                CALLS
+                    .lock()
+                    .unwrap()
+                    .push(format!("before:{}", ctx.function_name));
+                helper();
+            }
+        }
+
+        fn helper() {
+            let function = FunctionInfo::new("helper", "crate::api", "pub");
+            REGISTRY
+                .invoke(
+                    &function,
+                    || function.join_point(),
+                    || {
+                        CALLS.lock().unwrap().push("helper".into());
+                        Ok(Box::new(()) as Box<dyn Any>)
+                    },
+                )
+                .unwrap();
+        }
+
+        let pointcut = Pointcut::parse("execution(pub fn *(..))").unwrap();
+        REGISTRY.register(Arc::new(Reentrant), pointcut, 0, None);
+
+        // Unadvised from the advice, advised from the function it advises
+        let function = FunctionInfo::new("save_user", "crate::api", "pub");
+        REGISTRY
+            .invoke(
+                &function,
+                || function.join_point(),
+                || {
+                    helper();
+                    Ok(Box::new(()) as Box<dyn Any>)
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            *CALLS.lock().unwrap(),
+            [
+                "before:save_user",
+                "helper",
+                "before:helper",
+                "helper",
+                "helper"
+            ]
+        );
+
+        // The functions of the framework are never advised
+        let function = FunctionInfo::new("log", "aspect_std::logging", "pub");
+        assert!(REGISTRY.find_matching(&function).is_empty());
+    }
+
+    /// Denies every execution in `before_async`.
+    struct DenyAsync(Arc<Mutex<Vec<String>>>);
+
+    impl Aspect for DenyAsync {
+        fn after_error(&self, ctx: &JoinPoint, _error: &AspectError) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("deny:error:{}", ctx.function_name));
+        }
+    }
+
+    impl AsyncAspect for DenyAsync {
+        fn before_async<'s, 'c, 'f>(
+            &'s self,
+            _ctx: &'c JoinPoint,
+        ) -> BoxFuture<'f, Result<(), AspectError>>
+        where
+            's: 'f,
+            'c: 'f,
+            Self: 'f,
+        {
+            Box::pin(async { Err(AspectError::execution("denied")) })
+        }
+    }
+
+    /// Polls a future that is ready without waiting.
+    fn now<T>(mut future: BoxFuture<'_, T>) -> T {
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        match future.as_mut().poll(&mut cx) {
+            std::task::Poll::Ready(value) => value,
+            std::task::Poll::Pending => panic!("future not ready"),
+        }
+    }
+
+    #[test]
+    fn test_apply_aspects_async() {
+        use aspect_core::aspect::AsyncAdapter;
+
+        let registry = AspectRegistry::new();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let outer = TestAspect {
+            name: "outer".to_string(),
+            called: calls.clone(),
+        };
+        let mixed: [Arc<dyn AsyncAspect>; 2] = [
+            Arc::new(AsyncAdapter::new(outer)),
+            Arc::new(DenyAsync(calls.clone())),
+        ];
+        registry.register_async(
+            mixed[0].clone(),
+            Pointcut::parse("within(crate::api)").unwrap(),
+            0,
+            None,
+        );
+        registry.register_async(
+            mixed[1].clone(),
+            Pointcut::parse("execution(pub fn delete_*(..))").unwrap(),
+            1,
+            None,
+        );
+        let ctx = |name| {
+            JoinPoint::new(
+                name,
+                "crate::api",
+                aspect_core::Location {
+                    file: "api.rs",
+                    line: 7,
+                },
+            )
+        };
+        let ran = Arc::new(Mutex::new(0));
+        let proceed = || {
+            let ran = ran.clone();
+            Box::pin(async move {
+                *ran.lock().unwrap() += 1;
+                Ok(Box::new(()) as Box<dyn Any>)
+            }) as BoxFuture<'static, _>
+        };
+
+        let function = FunctionInfo::new("save_user", "crate::api", "pub");
+        let save = ctx("save_user");
+        assert!(now(registry.apply_aspects_async(&function, &save, proceed())).is_ok());
+
+        // Denied before running, inside the outer aspect
+        let function = FunctionInfo::new("delete_user", "crate::api", "pub");
+        let delete = ctx("delete_user");
+        assert!(now(registry.apply_aspects_async(&function, &delete, proceed())).is_err());
+
+        assert_eq!(*ran.lock().unwrap(), 1);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                "outer:before:save_user",
+                "outer:after:save_user",
+                "outer:before:delete_user",
+                "deny:error:delete_user",
+            ]
+        );
+
+        registry.set_measure_overhead(true);
+        assert!(now(registry.apply_aspects_async(&function, &delete, proceed())).is_err());
+        let measured: Vec<_> = registry.metrics().iter().map(|m| m.measured).collect();
+        assert_eq!(measured, [1, 1]);
+    }
+
+    #[test]
+    fn test_record_coverage() {
+        let dir = std::env::temp_dir().join(format!("aspect-coverage-{}", std::process::id()));
+        record_coverage(&dir, "api").unwrap();
+        record_coverage(&dir, "db").unwrap();
+
+        let file = dir.join(format!("{}.txt", std::process::id()));
+        assert_eq!(std::fs::read_to_string(file).unwrap(), "api\ndb\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,61 @@
+//! Aspects registered by `#[advice]`
+
+use aspect_core::pointcut::{FunctionInfo, Pointcut};
+use aspect_core::prelude::*;
+use aspect_macros::advice;
+use std::any::Any;
+
+#[advice(
+    pointcut = "(execution(pub fn save*(..)) || within(crate::api)) && !within(crate::api::internal)",
+    advice = "around",
+    order = 5
+)]
+fn persistence_advice(pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+    pjp.proceed()
+}
+
+#[test]
+fn test_advice_registers_parsed_pointcut() {
+    aspect_runtime::once_cell::sync::Lazy::force(&__register_persistence_advice);
+
+    let function = FunctionInfo::new("save_user", "crate::db", "pub");
+    let matching = aspect_runtime::global_registry().find_matching(&function);
+    let advice = matching
+        .iter()
+        .find(|aspect| aspect.name.as_deref() == Some("persistence_advice"))
+        .unwrap();
+    assert_eq!(advice.order, 5);
+    assert_eq!(
+        advice.pointcut,
+        Pointcut::parse(
+            "(execution(pub fn save*(..)) || within(crate::api)) && !within(crate::api::internal)"
+        )
+        .unwrap()
+    );
+}
+
+#[advice(pointcut = "execution(pub fn load*(..))", cfg(all()))]
+fn enabled_advice(pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+    pjp.proceed()
+}
+
+// Neither an aspect nor a registrar is compiled: referring to them would not
+// build
+#[advice(pointcut = "execution(pub fn load*(..))", cfg(any()))]
+fn disabled_advice(pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+    pjp.proceed()
+}
+
+#[test]
+fn test_advice_cfg() {
+    aspect_runtime::once_cell::sync::Lazy::force(&__register_enabled_advice);
+
+    let function = FunctionInfo::new("load_user", "crate::db", "pub");
+    let names: Vec<_> = aspect_runtime::global_registry()
+        .find_matching(&function)
+        .iter()
+        .filter_map(|aspect| aspect.name.clone())
+        .collect();
+    assert!(names.contains(&"enabled_advice".to_string()));
+    assert!(!names.contains(&"disabled_advice".to_string()));
+}
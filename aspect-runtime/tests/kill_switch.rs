@@ -0,0 +1,127 @@
+//! The kill switch bypassing advice, `ASPECT_DISABLE` or
+//! `set_global_enabled`, but for that of security aspects.
+//!
+//! It is global to the process, so this file holds a single test.
+
+use aspect_core::pointcut::{FunctionInfo, Pointcut};
+use aspect_core::prelude::*;
+use aspect_core::StaticAspect;
+use aspect_macros::aspect;
+use aspect_runtime::AspectRegistry;
+use std::any::Any;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+static ADVISED: AtomicU32 = AtomicU32::new(0);
+static CHECKED: AtomicU32 = AtomicU32::new(0);
+
+/// Counts the calls it advises.
+#[derive(Default)]
+struct Counting;
+
+impl Aspect for Counting {
+    fn before(&self, _ctx: &JoinPoint) {
+        ADVISED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Counts the calls it checks, as a security aspect.
+struct Checking;
+
+impl Aspect for Checking {
+    fn before(&self, _ctx: &JoinPoint) {
+        CHECKED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::SECURITY
+    }
+}
+
+/// Counts the calls it advises, with static dispatch.
+struct StaticCounting;
+
+impl StaticAspect for StaticCounting {
+    fn before(&self, _ctx: &JoinPoint) {
+        ADVISED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[aspect(Counting)]
+fn double(x: u32) -> u32 {
+    x * 2
+}
+
+#[aspect(Counting)]
+async fn triple(x: u32) -> u32 {
+    x * 3
+}
+
+#[aspect(static StaticCounting)]
+fn square(x: u32) -> u32 {
+    x * x
+}
+
+#[aspect(Checking, Counting)]
+fn negate(x: i32) -> i32 {
+    -x
+}
+
+/// Calls every woven function, returning how many calls were advised, and
+/// how many checked.
+fn advised_calls(registry: &AspectRegistry) -> (u32, u32) {
+    let before = ADVISED.load(Ordering::SeqCst);
+    let checked = CHECKED.load(Ordering::SeqCst);
+
+    assert_eq!(double(2), 4);
+    let mut future = std::pin::pin!(triple(2));
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    assert_eq!(future.as_mut().poll(&mut cx), std::task::Poll::Ready(6));
+    assert_eq!(square(3), 9);
+    assert_eq!(negate(1), -1);
+
+    let function = FunctionInfo::new("save_user", "crate::db", "pub");
+    let result = registry.invoke(
+        &function,
+        || {
+            JoinPoint::new(
+                "save_user",
+                "crate::db",
+                Location {
+                    file: file!(),
+                    line: line!(),
+                },
+            )
+        },
+        || Ok(Box::new(1) as Box<dyn Any>),
+    );
+    assert_eq!(*result.unwrap().downcast::<i32>().unwrap(), 1);
+
+    (
+        ADVISED.load(Ordering::SeqCst) - before,
+        CHECKED.load(Ordering::SeqCst) - checked,
+    )
+}
+
+#[test]
+fn test_kill_switch() {
+    // Read on the first check
+    std::env::set_var("ASPECT_DISABLE", "1");
+
+    let registry = AspectRegistry::new();
+    let pointcut = Pointcut::parse("within(crate::db)").unwrap();
+    registry.register(Arc::new(Counting), pointcut.clone(), 0, None);
+    registry.register(Arc::new(Checking), pointcut, 1, None);
+
+    // Security aspects still check the calls
+    assert!(!registry.global_enabled());
+    assert_eq!(advised_calls(&registry), (0, 2));
+
+    registry.set_global_enabled(true);
+    assert_eq!(advised_calls(&registry), (5, 2));
+
+    registry.set_global_enabled(false);
+    assert!(!aspect_core::switch::enabled());
+    assert_eq!(advised_calls(&registry), (0, 2));
+}
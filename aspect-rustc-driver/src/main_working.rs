@@ -1,38 +1,127 @@
 //! aspect-rustc-driver - WORKING VERSION with MIR extraction
 //!
 //! This binary successfully extracts function metadata from Rust MIR
-//! using rustc-driver integration.
+//! using rustc-driver integration, and weaves before/after advice hooks
+//! into the functions matched by `--aspect-before`/`--aspect-after`.
 
 #![feature(rustc_private)]
 
 extern crate rustc_driver;
 extern crate rustc_interface;
+extern crate rustc_data_structures;
+extern crate rustc_hir;
 extern crate rustc_middle;
+extern crate rustc_span;
 
-use rustc_driver::{Callbacks, RunCompiler};
+use rustc_data_structures::steal::Steal;
+use rustc_driver::{catch_with_exit_code, Callbacks, RunCompiler};
+use rustc_hir::def_id::{LocalDefId, LOCAL_CRATE};
 use rustc_interface::interface;
+use rustc_middle::mir::Body;
 use rustc_middle::ty::TyCtxt;
+use rustc_span::Symbol;
 use std::path::PathBuf;
-use std::sync::Mutex;
-
+use std::sync::{Mutex, OnceLock};
+
+use aspect_driver::cache::AnalysisCache;
+use aspect_driver::cargo::{
+    decode_args, strip_wrapped_rustc, DENY_UNMATCHED_ENV, DENY_UNWOVEN_ENV, ENCODED_ARGS_ENV,
+    RESULTS_DIR_ENV,
+};
+use aspect_driver::config::ConfigFile;
+use aspect_driver::diagnostic::{Diagnostic, DiagnosticFormat, DriverExit, WEAVING_ERROR};
+use aspect_driver::expand::emit_sources;
+use aspect_driver::field::{FieldAccess, FieldPattern};
+use aspect_driver::filter::{CrateFilter, CrateTarget};
 use aspect_driver::mir_analyzer::{MirAnalyzer, AnalysisStats};
+use aspect_driver::pass::{CommandPass, Finding, PassInput, PassRegistry, Severity};
+use aspect_driver::plan::WeavingPlan;
+use aspect_driver::provider;
+use aspect_driver::r#match::{
+    load_from_registry, parse_pointcut, AdviceType, PointcutMatcher, REGISTRY_DIR_ENV,
+};
+use aspect_driver::report::AnalysisReport;
+use aspect_driver::timing::PhaseTimings;
 use aspect_driver::types::{FunctionMetadata, Visibility};
+use aspect_driver::unmatched::find_unmatched;
+use aspect_driver::weave::{resolve_hooks, AdviceHook, MirBuiltProvider, MirWeaver, ResolvedHook};
 
 /// Global configuration (needed for query provider function pointers)
 static CONFIG: Mutex<Option<AspectConfig>> = Mutex::new(None);
 static RESULTS: Mutex<Option<AnalysisResults>> = Mutex::new(None);
 
+/// The `mir_built` provider replaced by `weave_mir_built`
+static DEFAULT_MIR_BUILT: OnceLock<MirBuiltProvider> = OnceLock::new();
+/// Advice hooks, resolved on first use
+static HOOKS: OnceLock<Vec<ResolvedHook>> = OnceLock::new();
+/// Why the advice hooks could not be resolved
+static WEAVING_FAILURE: Mutex<Option<String>> = Mutex::new(None);
+
 #[derive(Debug, Clone)]
 struct AspectConfig {
     pointcuts: Vec<String>,
+    advice: Vec<AdviceHook>,
     verbose: bool,
     output_file: Option<PathBuf>,
+    output_format: OutputFormat,
+    /// Directory of the analysis cache, `None` to disable it
+    cache_dir: Option<PathBuf>,
+    /// Dry run: print the weaving plan instead of weaving
+    plan: bool,
+    /// Fail when a pointcut matches no function
+    deny_unmatched: bool,
+    /// Fail when a function matched by the pointcut of advice cannot be
+    /// woven
+    deny_unwoven: bool,
+    /// Also analyze closures
+    closures: bool,
+    /// Field get/set joinpoints to report
+    fields: Vec<FieldPattern>,
+    /// Directory to write the woven source to
+    emit_source: Option<PathBuf>,
+    /// External programs run as analysis passes
+    passes: Vec<CommandPass>,
+    /// Crates and targets to analyze and weave
+    crates: CrateFilter,
+    /// How diagnostics are written to stderr
+    diagnostics: DiagnosticFormat,
+    /// The `aspect.toml` read, if any
+    config_file: Option<PathBuf>,
+}
+
+/// Format of the `--aspect-output` file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// Human-readable summary
+    Text,
+    /// Versioned JSON document (see `aspect_driver::report`)
+    Json,
+    /// SARIF 2.1.0 log, for code scanning and IDEs
+    Sarif,
+    /// Graphviz graph of the weaving plan
+    Dot,
+}
+
+impl OutputFormat {
+    fn parse(format: &str) -> Option<Self> {
+        match format {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            "sarif" => Some(OutputFormat::Sarif),
+            "dot" => Some(OutputFormat::Dot),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 struct AnalysisResults {
+    /// Pointcuts of the command line, then those registered by `#[advice]`
+    pointcuts: Vec<String>,
     functions: Vec<FunctionMetadata>,
     matched_functions: Vec<(FunctionMetadata, String)>, // (function, pointcut)
+    field_accesses: Vec<FieldAccess>,
+    source_files: Vec<String>,
 }
 
 /// Analysis function called with TyCtxt - this is where the magic happens!
@@ -44,9 +133,26 @@ fn analyze_crate_with_aspects(tcx: TyCtxt<'_>, (): ()) {
         println!("=== aspect-rustc-driver: MIR Analysis ===\n");
     }
 
-    // Extract all functions from MIR
-    let analyzer = MirAnalyzer::new(tcx, config.verbose);
-    let functions = analyzer.extract_all_functions();
+    // Extract all functions from MIR, reusing cached metadata of
+    // unchanged files
+    let mut timings = PhaseTimings::new();
+    let analyzer = MirAnalyzer::new(tcx, config.verbose).with_closures(config.closures);
+    let functions = timings.time("extraction", || match &config.cache_dir {
+        Some(cache_dir) => {
+            let crate_name = tcx.crate_name(LOCAL_CRATE);
+            let crate_id = tcx.stable_crate_id(LOCAL_CRATE).as_u64();
+            let path = AnalysisCache::path_in(cache_dir, crate_name.as_str(), crate_id);
+            let mut cache = AnalysisCache::load(path);
+            let functions = analyzer.extract_all_functions_cached(&mut cache);
+            match cache.save() {
+                Ok(stats) if config.verbose => stats.print_summary(),
+                Ok(_) => {}
+                Err(e) => eprintln!("Warning: could not save the analysis cache: {}", e),
+            }
+            functions
+        }
+        None => analyzer.extract_all_functions(),
+    });
 
     if config.verbose {
         println!("\n✅ Extracted {} functions from MIR", functions.len());
@@ -56,45 +162,39 @@ fn analyze_crate_with_aspects(tcx: TyCtxt<'_>, (): ()) {
     let stats = AnalysisStats::from_functions(&functions);
     stats.print_summary();
 
-    // Apply simple pointcut matching
-    let mut matched_functions = Vec::new();
+    // Aspects declared with #[advice], in this crate (expanded by now)
+    // or in the crates it depends on, and those provided by dependencies
+    let mut pointcuts = config.pointcuts.clone();
+    for aspect in load_from_registry().into_iter().chain(provider::load_from_env()) {
+        if config.verbose {
+            println!(
+                "Registered aspect: {} ({} advice, order {}) on \"{}\"",
+                aspect.aspect_name, aspect.advice_type, -aspect.priority, aspect.pointcut
+            );
+        }
+        if !pointcuts.contains(&aspect.pointcut) {
+            pointcuts.push(aspect.pointcut);
+        }
+    }
+
+    // Match with the same semantics as aspect-core at runtime, on all
+    // cores
+    let matcher = PointcutMatcher::new();
+    let matched_functions =
+        timings.time("matching", || matcher.match_pointcuts(&functions, &pointcuts));
 
-    if !config.pointcuts.is_empty() {
+    if !pointcuts.is_empty() {
         if config.verbose {
             println!("\n=== Pointcut Matching ===");
-        }
 
-        for pointcut_str in &config.pointcuts {
-            if config.verbose {
+            for pointcut_str in &pointcuts {
                 println!("\nPointcut: \"{}\"", pointcut_str);
-            }
-
-            // Simple pattern matching (full PointcutMatcher integration coming next)
-            let mut match_count = 0;
-            for func in &functions {
-                let matches = match pointcut_str.as_str() {
-                    "execution(pub fn *(..))" => func.is_public(),
-                    s if s.starts_with("within(") => {
-                        // Extract module from within(module::path)
-                        let module = s.trim_start_matches("within(").trim_end_matches(')');
-                        func.module_path.contains(module) || func.name.contains(module)
-                    }
-                    _ => {
-                        // Default: match by name pattern
-                        func.name.contains(pointcut_str)
-                    }
-                };
 
-                if matches {
-                    if config.verbose {
-                        println!("  ✓ Matched: {}", func.name);
-                    }
-                    matched_functions.push((func.clone(), pointcut_str.clone()));
+                let mut match_count = 0;
+                for (func, _) in matched_functions.iter().filter(|(_, p)| p == pointcut_str) {
+                    println!("  ✓ Matched: {}", func.name);
                     match_count += 1;
                 }
-            }
-
-            if config.verbose {
                 println!("  Total matches: {}", match_count);
             }
         }
@@ -103,40 +203,176 @@ fn analyze_crate_with_aspects(tcx: TyCtxt<'_>, (): ()) {
         println!("Total functions matched: {}", matched_functions.len());
     }
 
+    // Field get/set joinpoints
+    let mut field_accesses = Vec::new();
+    if !config.fields.is_empty() {
+        field_accesses = timings.time("field analysis", || {
+            analyzer.extract_field_accesses(&config.fields)
+        });
+        println!("\n=== Field Accesses ===");
+        println!("Total field accesses matched: {}", field_accesses.len());
+    }
+
+    if config.verbose {
+        timings.print_summary();
+    }
+
     // Store results
     *RESULTS.lock().unwrap() = Some(AnalysisResults {
+        pointcuts,
         functions,
         matched_functions,
+        field_accesses,
+        source_files: analyzer.source_files(),
+    });
+}
+
+/// `mir_built` provider weaving advice hooks into matched functions.
+fn weave_mir_built(tcx: TyCtxt<'_>, def_id: LocalDefId) -> &Steal<Body<'_>> {
+    let hooks = HOOKS.get_or_init(|| {
+        let advice = CONFIG.lock().unwrap().as_ref().unwrap().advice.clone();
+        resolve_hooks(tcx, &advice).unwrap_or_else(|e| {
+            *WEAVING_FAILURE.lock().unwrap() = Some(e.clone());
+            tcx.dcx().fatal(e)
+        })
     });
+    let default = *DEFAULT_MIR_BUILT.get().unwrap();
+    MirWeaver::new(tcx, hooks).mir_built(def_id, default)
+}
+
+/// Have cargo rebuild the crate when the flags given by cargo-aspect or
+/// the `aspect.toml` change, by recording them in its dep-info.
+fn track_inputs(config: &mut interface::Config, config_file: Option<PathBuf>) {
+    let env: Vec<(&str, Option<String>)> = [ENCODED_ARGS_ENV, DENY_UNMATCHED_ENV, DENY_UNWOVEN_ENV]
+        .into_iter()
+        .map(|name| (name, std::env::var(name).ok()))
+        .collect();
+    config.psess_created = Some(Box::new(move |psess| {
+        let env_depinfo = psess.env_depinfo.get_mut();
+        for (name, value) in &env {
+            env_depinfo.insert((Symbol::intern(name), value.as_deref().map(Symbol::intern)));
+        }
+        if let Some(path) = config_file.as_ref().and_then(|path| path.to_str()) {
+            psess.file_depinfo.get_mut().insert(Symbol::intern(path));
+        }
+    }));
 }
 
 struct AspectCallbacks;
 
+/// Callbacks of the crates left to plain rustc
+struct PlainCallbacks {
+    config_file: Option<PathBuf>,
+}
+
+impl Callbacks for PlainCallbacks {
+    fn config(&mut self, config: &mut interface::Config) {
+        track_inputs(config, self.config_file.clone());
+    }
+}
+
 impl Callbacks for AspectCallbacks {
     fn config(&mut self, config: &mut interface::Config) {
         let aspect_config = CONFIG.lock().unwrap().clone().unwrap();
+        track_inputs(config, aspect_config.config_file.clone());
 
         if aspect_config.verbose {
             println!("=== aspect-rustc-driver: Configuring compiler ===");
             println!("Pointcuts registered: {}", aspect_config.pointcuts.len());
         }
 
-        // Use override_queries to intercept analysis phase
-        config.override_queries = Some(|_sess, providers| {
-            providers.analysis = analyze_crate_with_aspects;
-        });
+        // Use override_queries to intercept analysis phase, and MIR
+        // building when there is advice to weave
+        if aspect_config.advice.is_empty() || aspect_config.plan {
+            config.override_queries = Some(|_sess, providers| {
+                providers.analysis = analyze_crate_with_aspects;
+            });
+        } else {
+            config.override_queries = Some(|_sess, providers| {
+                providers.analysis = analyze_crate_with_aspects;
+                let _ = DEFAULT_MIR_BUILT.set(providers.mir_built);
+                providers.mir_built = weave_mir_built;
+            });
+        }
     }
 }
 
 fn main() {
     let mut args: Vec<String> = std::env::args().collect();
 
+    // Run by cargo as rustc wrapper: drop the path of rustc, and take the
+    // flags cargo-aspect passes in the environment
+    let mut rest = args.split_off(1);
+    let wrapped = strip_wrapped_rustc(&mut rest);
+    args.extend(rest);
+    if let Ok(encoded) = std::env::var(ENCODED_ARGS_ENV) {
+        args.extend(decode_args(&encoded));
+    }
+
     // Parse aspect-specific flags
     let mut aspect_config = AspectConfig {
         pointcuts: Vec::new(),
+        advice: Vec::new(),
         verbose: false,
         output_file: None,
+        output_format: OutputFormat::Text,
+        cache_dir: Some(
+            std::env::var_os("CARGO_TARGET_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("target"))
+                .join("aspect"),
+        ),
+        plan: false,
+        // Set by `cargo aspect --deny-unmatched-pointcuts` and `--deny-unwoven`
+        deny_unmatched: std::env::var_os(DENY_UNMATCHED_ENV).is_some_and(|v| v != "0"),
+        deny_unwoven: std::env::var_os(DENY_UNWOVEN_ENV).is_some_and(|v| v != "0"),
+        closures: false,
+        fields: Vec::new(),
+        emit_source: None,
+        passes: Vec::new(),
+        crates: CrateFilter::default(),
+        diagnostics: DiagnosticFormat::Human,
+        config_file: None,
+    };
+
+    // Start from aspect.toml, given with --aspect-config or found in the
+    // crate directory, with the settings of --aspect-profile; the flags
+    // below override it
+    let value_of = |flag: &str| match args.iter().position(|arg| arg == flag) {
+        Some(i) => match args.get(i + 1) {
+            Some(value) => Some(value.clone()),
+            None => {
+                eprintln!("Error: {} requires a value", flag);
+                std::process::exit(DriverExit::Usage.code());
+            }
+        },
+        None => None,
+    };
+    let config_path = match value_of("--aspect-config") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => std::env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .or_else(|| std::env::current_dir().ok())
+            .and_then(|dir| ConfigFile::find(&dir)),
     };
+    let profile = value_of("--aspect-profile");
+    if profile.is_some() && config_path.is_none() {
+        eprintln!("Error: --aspect-profile requires an aspect.toml");
+        std::process::exit(DriverExit::Usage.code());
+    }
+    if let Some(path) = &config_path {
+        let applied = ConfigFile::load(path)
+            .and_then(|file| match &profile {
+                Some(profile) => file.profile(profile),
+                None => Ok(file),
+            })
+            .and_then(|file| apply_config_file(&mut aspect_config, &file));
+        if let Err(e) = applied {
+            eprintln!("Error: {}", e);
+            std::process::exit(DriverExit::Usage.code());
+        }
+    }
+    aspect_config.config_file = config_path.clone();
 
     let mut rustc_args = Vec::new();
     rustc_args.push(args[0].clone());
@@ -150,20 +386,168 @@ fn main() {
             }
             "--aspect-pointcut" => {
                 if i + 1 < args.len() {
+                    if let Err(e) = parse_pointcut(&args[i + 1]) {
+                        eprintln!("Error: --aspect-pointcut: {}", e);
+                        std::process::exit(DriverExit::Usage.code());
+                    }
                     aspect_config.pointcuts.push(args[i + 1].clone());
                     i += 2;
                 } else {
                     eprintln!("Error: --aspect-pointcut requires a value");
-                    std::process::exit(1);
+                    std::process::exit(DriverExit::Usage.code());
+                }
+            }
+            flag @ ("--aspect-before" | "--aspect-after") => {
+                let advice_type = if flag == "--aspect-before" {
+                    AdviceType::Before
+                } else {
+                    AdviceType::After
+                };
+                if i + 1 < args.len() {
+                    match AdviceHook::parse(advice_type, &args[i + 1]) {
+                        Ok(hook) => aspect_config.advice.push(hook),
+                        Err(e) => {
+                            eprintln!("Error: {}: {}", flag, e);
+                            std::process::exit(DriverExit::Usage.code());
+                        }
+                    }
+                    i += 2;
+                } else {
+                    eprintln!("Error: {} requires a <pointcut>=<hook path> value", flag);
+                    std::process::exit(DriverExit::Usage.code());
+                }
+            }
+            "--aspect-config" | "--aspect-profile" => {
+                // Read above
+                i += 2;
+            }
+            "--aspect-format" => {
+                let format = args.get(i + 1).and_then(|format| OutputFormat::parse(format));
+                aspect_config.output_format = match format {
+                    Some(format) => format,
+                    None => {
+                        eprintln!(
+                            "Error: --aspect-format requires 'text', 'json', 'sarif' or 'dot'"
+                        );
+                        std::process::exit(DriverExit::Usage.code());
+                    }
+                };
+                i += 2;
+            }
+            "--aspect-cache-dir" => {
+                if i + 1 < args.len() {
+                    aspect_config.cache_dir = Some(PathBuf::from(&args[i + 1]));
+                    i += 2;
+                } else {
+                    eprintln!("Error: --aspect-cache-dir requires a value");
+                    std::process::exit(DriverExit::Usage.code());
+                }
+            }
+            "--aspect-no-cache" => {
+                aspect_config.cache_dir = None;
+                i += 1;
+            }
+            "--aspect-plan" => {
+                aspect_config.plan = true;
+                i += 1;
+            }
+            "--aspect-deny-unmatched" => {
+                aspect_config.deny_unmatched = true;
+                i += 1;
+            }
+            "--aspect-deny-unwoven" => {
+                aspect_config.deny_unwoven = true;
+                i += 1;
+            }
+            "--aspect-closures" => {
+                aspect_config.closures = true;
+                i += 1;
+            }
+            "--aspect-field" => {
+                if i + 1 < args.len() {
+                    match FieldPattern::parse(&args[i + 1]) {
+                        Ok(pattern) => aspect_config.fields.push(pattern),
+                        Err(e) => {
+                            eprintln!("Error: --aspect-field: {}", e);
+                            std::process::exit(DriverExit::Usage.code());
+                        }
+                    }
+                    i += 2;
+                } else {
+                    eprintln!("Error: --aspect-field requires a value");
+                    std::process::exit(DriverExit::Usage.code());
                 }
             }
+            "--aspect-emit-source" => {
+                if i + 1 < args.len() {
+                    aspect_config.emit_source = Some(PathBuf::from(&args[i + 1]));
+                    i += 2;
+                } else {
+                    eprintln!("Error: --aspect-emit-source requires a directory");
+                    std::process::exit(DriverExit::Usage.code());
+                }
+            }
+            "--aspect-pass" => {
+                if i + 1 < args.len() {
+                    match CommandPass::parse(&args[i + 1]) {
+                        Ok(pass) => aspect_config.passes.push(pass),
+                        Err(e) => {
+                            eprintln!("Error: --aspect-pass: {}", e);
+                            std::process::exit(DriverExit::Usage.code());
+                        }
+                    }
+                    i += 2;
+                } else {
+                    eprintln!("Error: --aspect-pass requires a command");
+                    std::process::exit(DriverExit::Usage.code());
+                }
+            }
+            flag @ ("--aspect-only-crate" | "--aspect-exclude") => {
+                if i + 1 < args.len() {
+                    let names = match CrateFilter::parse_names(&args[i + 1]) {
+                        Ok(names) => names,
+                        Err(e) => {
+                            eprintln!("Error: {}: {}", flag, e);
+                            std::process::exit(DriverExit::Usage.code());
+                        }
+                    };
+                    if flag == "--aspect-only-crate" {
+                        aspect_config.crates.only.extend(names);
+                    } else {
+                        aspect_config.crates.exclude.extend(names);
+                    }
+                    i += 2;
+                } else {
+                    eprintln!("Error: {} requires crate names", flag);
+                    std::process::exit(DriverExit::Usage.code());
+                }
+            }
+            "--aspect-no-tests" => {
+                aspect_config.crates.tests = false;
+                i += 1;
+            }
+            "--aspect-no-benches" => {
+                aspect_config.crates.benches = false;
+                i += 1;
+            }
+            "--aspect-diagnostics" => {
+                let format = args.get(i + 1).and_then(|format| DiagnosticFormat::parse(format));
+                aspect_config.diagnostics = match format {
+                    Some(format) => format,
+                    None => {
+                        eprintln!("Error: --aspect-diagnostics requires 'human' or 'json'");
+                        std::process::exit(DriverExit::Usage.code());
+                    }
+                };
+                i += 2;
+            }
             "--aspect-output" => {
                 if i + 1 < args.len() {
                     aspect_config.output_file = Some(PathBuf::from(&args[i + 1]));
                     i += 2;
                 } else {
                     eprintln!("Error: --aspect-output requires a value");
-                    std::process::exit(1);
+                    std::process::exit(DriverExit::Usage.code());
                 }
             }
             arg => {
@@ -173,39 +557,362 @@ fn main() {
         }
     }
 
+    if aspect_config.plan && aspect_config.output_format == OutputFormat::Sarif {
+        eprintln!("Error: --aspect-plan writes 'text', 'json' or 'dot'");
+        std::process::exit(DriverExit::Usage.code());
+    }
+
     if aspect_config.verbose {
         println!("aspect-rustc-driver starting");
+        if let Some(path) = &config_path {
+            match &profile {
+                Some(profile) => println!("Config: {} (profile {})", path.display(), profile),
+                None => println!("Config: {}", path.display()),
+            }
+        }
         println!("Pointcuts: {:?}", aspect_config.pointcuts);
+        for hook in &aspect_config.advice {
+            println!("Advice: {:?} {} -> {}", hook.advice_type, hook.pointcut, hook.path);
+        }
         println!("Rustc args: {:?}", rustc_args);
         println!();
     }
 
+    // Have #[advice] record the aspects it declares, replacing what this
+    // crate recorded in a previous build
+    let registry_dir = std::env::var_os(REGISTRY_DIR_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            let dir = std::env::var_os("CARGO_TARGET_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("target"))
+                .join("aspect")
+                .join("registry");
+            std::env::set_var(REGISTRY_DIR_ENV, &dir);
+            dir
+        });
+    if let Some(i) = rustc_args.iter().position(|arg| arg == "--crate-name") {
+        if let Some(crate_name) = rustc_args.get(i + 1) {
+            let _ = std::fs::remove_dir_all(registry_dir.join(crate_name));
+        }
+    }
+
+    // Crates filtered out are compiled as by rustc
+    let target = CrateTarget::from_rustc_args(&rustc_args[1..]);
+    if let Some(target) = &target {
+        if !aspect_config.crates.includes(target) {
+            if aspect_config.verbose {
+                println!("Skipping {} ({:?}): filtered out", target.crate_name, target.kind);
+            }
+            let mut callbacks = PlainCallbacks {
+                config_file: aspect_config.config_file.clone(),
+            };
+            std::process::exit(catch_with_exit_code(|| {
+                RunCompiler::new(&rustc_args, &mut callbacks).run()
+            }));
+        }
+    }
+
     // Store config in global state
     *CONFIG.lock().unwrap() = Some(aspect_config.clone());
 
     // Run compiler
     let mut callbacks = AspectCallbacks;
-    RunCompiler::new(&rustc_args, &mut callbacks).run();
+    let compiled = catch_with_exit_code(|| RunCompiler::new(&rustc_args, &mut callbacks).run());
+
+    // Failing to resolve the hooks aborts the compilation; say why
+    if let Some(e) = WEAVING_FAILURE.lock().unwrap().take() {
+        if aspect_config.diagnostics == DiagnosticFormat::Json {
+            let diagnostic = Diagnostic::new(WEAVING_ERROR, Severity::Error, e);
+            eprintln!("{}", diagnostic.to_json_line());
+        }
+        std::process::exit(DriverExit::WeavingFailed.code());
+    }
+    if compiled != 0 {
+        std::process::exit(DriverExit::CompilationFailed.code());
+    }
+
+    // A pointcut matching nothing is almost always a typo. Advice
+    // registered with #[advice] may well be meant for other crates.
+    let unmatched = match RESULTS.lock().unwrap().as_ref() {
+        Some(results) => find_unmatched(
+            &results.functions,
+            &aspect_config.pointcuts,
+            &aspect_config.advice,
+        ),
+        None => Vec::new(),
+    };
+    let level = if aspect_config.deny_unmatched { "error" } else { "warning" };
+    for pointcut in &unmatched {
+        match aspect_config.diagnostics {
+            DiagnosticFormat::Human => eprintln!("{}: {}", level, pointcut),
+            DiagnosticFormat::Json => eprintln!(
+                "{}",
+                Diagnostic::unmatched(pointcut, aspect_config.deny_unmatched).to_json_line()
+            ),
+        }
+    }
+    // The first failure gives the exit code
+    let mut failure = None;
+    if aspect_config.deny_unmatched && !unmatched.is_empty() {
+        failure = Some(DriverExit::UnmatchedPointcut);
+    }
+
+    // Matched functions the advice cannot be woven into, such as async
+    // functions, are warnings of the report, unless denied
+    if aspect_config.deny_unwoven {
+        if let Some(results) = RESULTS.lock().unwrap().as_ref() {
+            let plan = WeavingPlan::new(
+                &results.functions,
+                &results.pointcuts,
+                &aspect_config.advice,
+            );
+            let unwoven = plan.unwoven();
+            for (name, reason) in &unwoven {
+                let Some(function) = results.functions.iter().find(|f| f.name == *name) else {
+                    continue;
+                };
+                let diagnostic = Diagnostic::unwoven(function, reason, true);
+                match aspect_config.diagnostics {
+                    DiagnosticFormat::Human => eprintln!("{}", diagnostic),
+                    DiagnosticFormat::Json => eprintln!("{}", diagnostic.to_json_line()),
+                }
+            }
+            if !unwoven.is_empty() {
+                failure.get_or_insert(DriverExit::Unwoven);
+            }
+        }
+    }
 
-    // Output results
+    // Custom analysis passes; their errors fail the build
+    let mut passes = PassRegistry::new();
+    for pass in &aspect_config.passes {
+        passes.register(pass.clone());
+    }
+    let mut findings: Vec<Finding> = Vec::new();
+    if !passes.is_empty() {
+        if let Some(results) = RESULTS.lock().unwrap().as_ref() {
+            let report = AnalysisReport::new(
+                &results.pointcuts,
+                results.functions.clone(),
+                &results.matched_functions,
+            )
+            .with_field_accesses(results.field_accesses.clone());
+            let plan = WeavingPlan::new(
+                &results.functions,
+                &results.pointcuts,
+                &aspect_config.advice,
+            );
+            findings = passes.run(&PassInput {
+                report: &report,
+                plan: &plan,
+            });
+        }
+    }
     if let Some(results) = RESULTS.lock().unwrap().as_ref() {
-        println!("\n=== Aspect Weaving Analysis Complete ===");
-        println!("Functions analyzed: {}", results.functions.len());
-        println!("Functions matched by pointcuts: {}", results.matched_functions.len());
+        for finding in &findings {
+            match aspect_config.diagnostics {
+                DiagnosticFormat::Human => eprintln!("{}", finding),
+                DiagnosticFormat::Json => eprintln!(
+                    "{}",
+                    Diagnostic::finding(finding, &results.functions).to_json_line()
+                ),
+            }
+        }
+    }
+    if findings.iter().any(|finding| finding.severity == Severity::Error) {
+        failure.get_or_insert(DriverExit::AnalysisError);
+    }
+
+    // The analysis of every crate, for cargo-aspect to report
+    if let (Some(dir), Some(target)) = (std::env::var_os(RESULTS_DIR_ENV), &target) {
+        if let Some(results) = RESULTS.lock().unwrap().as_ref() {
+            let dir = PathBuf::from(dir);
+            let path = dir.join(target.report_name());
+            let written = std::fs::create_dir_all(&dir).and_then(|()| {
+                write_report_file(
+                    &path,
+                    OutputFormat::Json,
+                    &results.pointcuts,
+                    results,
+                    &findings,
+                )
+            });
+            if let Err(e) = written {
+                eprintln!("Error writing {}: {}", path.display(), e);
+                failure.get_or_insert(DriverExit::OutputFailed);
+            }
+        }
+    }
+
+    // Write the source as woven, also in dry runs
+    if let Some(dir) = &aspect_config.emit_source {
+        if let Some(results) = RESULTS.lock().unwrap().as_ref() {
+            let plan = WeavingPlan::new(
+                &results.functions,
+                &results.pointcuts,
+                &aspect_config.advice,
+            );
+            match emit_sources(dir, &results.source_files, &results.functions, &plan) {
+                Ok(report) => {
+                    for name in &report.not_found {
+                        eprintln!(
+                            "warning: `{}` is woven, but was not found in its source file",
+                            name
+                        );
+                    }
+                    println!(
+                        "\n✅ Woven source of {} functions written to: {}",
+                        report.expanded,
+                        dir.display()
+                    );
+                }
+                Err(e) => {
+                    eprintln!("Error writing woven source: {}", e);
+                    std::process::exit(DriverExit::OutputFailed.code());
+                }
+            }
+        }
+    }
+
+    // Output the weaving plan of a dry run
+    if aspect_config.plan {
+        if let Some(results) = RESULTS.lock().unwrap().as_ref() {
+            let plan = WeavingPlan::new(
+                &results.functions,
+                &results.pointcuts,
+                &aspect_config.advice,
+            );
+            let contents = match aspect_config.output_format {
+                OutputFormat::Json => plan.to_json(),
+                OutputFormat::Dot => plan.to_dot(),
+                _ => plan.to_text(),
+            };
+            match &aspect_config.output_file {
+                Some(output_path) => match std::fs::write(output_path, contents + "\n") {
+                    Ok(()) => println!("\n✅ Weaving plan written to: {}", output_path.display()),
+                    Err(e) => {
+                        eprintln!("Error writing output: {}", e);
+                        failure.get_or_insert(DriverExit::OutputFailed);
+                    }
+                },
+                None => println!("\n{}", contents),
+            }
+        }
+        if let Some(failure) = failure {
+            std::process::exit(failure.code());
+        }
+        return;
+    }
+
+    // Output results; under cargo, cargo-aspect reports them
+    let quiet = wrapped && !aspect_config.verbose;
+    if let Some(results) = RESULTS.lock().unwrap().as_ref() {
+        if !quiet {
+            println!("\n=== Aspect Weaving Analysis Complete ===");
+            println!("Functions analyzed: {}", results.functions.len());
+            println!("Functions matched by pointcuts: {}", results.matched_functions.len());
+        }
 
         // Write output file if requested
         if let Some(ref output_path) = aspect_config.output_file {
-            if let Err(e) = write_output_file(output_path, results) {
+            let written = match aspect_config.output_format {
+                OutputFormat::Text => write_output_file(output_path, results),
+                OutputFormat::Json | OutputFormat::Sarif => write_report_file(
+                    output_path,
+                    aspect_config.output_format,
+                    &results.pointcuts,
+                    results,
+                    &findings,
+                ),
+                // The graph of what was woven
+                OutputFormat::Dot => {
+                    let plan = WeavingPlan::new(
+                        &results.functions,
+                        &results.pointcuts,
+                        &aspect_config.advice,
+                    );
+                    std::fs::write(output_path, plan.to_dot())
+                }
+            };
+            if let Err(e) = written {
                 eprintln!("Error writing output: {}", e);
+                failure.get_or_insert(DriverExit::OutputFailed);
             } else {
                 println!("\n✅ Analysis written to: {}", output_path.display());
             }
         }
 
-        println!("\n✅ SUCCESS: Automatic aspect weaving analysis complete!");
+        if !quiet {
+            println!("\n✅ SUCCESS: Automatic aspect weaving analysis complete!");
+        }
+    }
+
+    if let Some(failure) = failure {
+        std::process::exit(failure.code());
     }
 }
 
+/// Apply the settings of an `aspect.toml`: its pointcuts, advice and
+/// field patterns come first, its other values replace the defaults.
+fn apply_config_file(config: &mut AspectConfig, file: &ConfigFile) -> Result<(), String> {
+    config.pointcuts.extend(file.pointcuts.iter().cloned());
+    config.advice.extend(file.advice_hooks()?);
+    config.fields.extend(file.field_patterns()?);
+    config.passes.extend(file.command_passes()?);
+    file.apply_crate_filter(&mut config.crates);
+
+    config.verbose = file.verbose.unwrap_or(config.verbose);
+    config.plan = file.plan.unwrap_or(config.plan);
+    // `cargo aspect --deny-unmatched-pointcuts` and `--deny-unwoven` are
+    // flags, so the file cannot undo them
+    config.deny_unmatched |= file.deny_unmatched == Some(true);
+    config.deny_unwoven |= file.deny_unwoven == Some(true);
+    config.closures = file.closures.unwrap_or(config.closures);
+
+    if let Some(format) = &file.output.format {
+        config.output_format = OutputFormat::parse(format).ok_or_else(|| {
+            format!("output format must be 'text', 'json', 'sarif' or 'dot', not '{}'", format)
+        })?;
+    }
+    if file.output.file.is_some() {
+        config.output_file = file.output.file.clone();
+    }
+    if file.output.emit_source.is_some() {
+        config.emit_source = file.output.emit_source.clone();
+    }
+
+    if file.cache.dir.is_some() {
+        config.cache_dir = file.cache.dir.clone();
+    }
+    if file.cache.enabled == Some(false) {
+        config.cache_dir = None;
+    }
+
+    Ok(())
+}
+
+fn write_report_file(
+    path: &PathBuf,
+    format: OutputFormat,
+    pointcuts: &[String],
+    results: &AnalysisResults,
+    findings: &[Finding],
+) -> std::io::Result<()> {
+    let report = AnalysisReport::new(
+        pointcuts,
+        results.functions.clone(),
+        &results.matched_functions,
+    )
+    .with_field_accesses(results.field_accesses.clone())
+    .with_findings(findings.to_vec());
+    let contents = match format {
+        OutputFormat::Sarif => report.to_sarif(),
+        _ => report.to_json(),
+    };
+    std::fs::write(path, contents + "\n")
+}
+
 fn write_output_file(path: &PathBuf, results: &AnalysisResults) -> std::io::Result<()> {
     use std::fs::File;
     use std::io::Write;
@@ -221,7 +928,14 @@ fn write_output_file(path: &PathBuf, results: &AnalysisResults) -> std::io::Resu
     for func in &results.functions {
         writeln!(file, "  • {} ({:?})", func.name, func.visibility)?;
         writeln!(file, "    Module: {}", func.module_path)?;
-        writeln!(file, "    Location: {}:{}", func.location.file, func.location.line)?;
+        if let Some(context) = func.impl_context() {
+            writeln!(file, "    In: {}", context)?;
+        }
+        writeln!(
+            file,
+            "    Location: {}:{}:{}",
+            func.location.file, func.location.line, func.location.column
+        )?;
     }
 
     writeln!(file)?;
@@ -234,5 +948,18 @@ fn write_output_file(path: &PathBuf, results: &AnalysisResults) -> std::io::Resu
         writeln!(file, "    Pointcut: {}", pointcut)?;
     }
 
+    if !results.field_accesses.is_empty() {
+        writeln!(file)?;
+        writeln!(file, "Field Accesses:")?;
+        for access in &results.field_accesses {
+            writeln!(file, "  • {} in {}", access, access.function)?;
+            writeln!(
+                file,
+                "    Location: {}:{}:{}",
+                access.location.file, access.location.line, access.location.column
+            )?;
+        }
+    }
+
     Ok(())
 }
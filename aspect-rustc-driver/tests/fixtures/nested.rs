@@ -0,0 +1,35 @@
+//! Sample crate for the extraction tests: methods, a nested function and
+//! a closure.
+
+pub mod shapes {
+    pub struct Square(pub f64);
+
+    impl Square {
+        pub fn area(&self) -> f64 {
+            fn square(x: f64) -> f64 {
+                x * x
+            }
+            square(self.0)
+        }
+    }
+
+    pub trait Describe {
+        fn describe(&self) -> String {
+            "shape".to_string()
+        }
+    }
+
+    impl Describe for Square {}
+
+    pub fn total(squares: &[Square]) -> f64 {
+        squares.iter().map(|square| square.area()).sum()
+    }
+}
+
+fn main() {
+    use shapes::Describe;
+
+    let squares = [shapes::Square(2.0)];
+    assert_eq!(shapes::total(&squares), 4.0);
+    assert_eq!(squares[0].describe(), "shape");
+}
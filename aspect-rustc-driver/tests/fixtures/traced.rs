@@ -0,0 +1,52 @@
+//! Sample crate for the weaving tests: `api` functions are advised by the
+//! `trace` hooks without any annotation.
+
+mod trace {
+    use std::cell::RefCell;
+
+    thread_local! {
+        static EVENTS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    pub(crate) fn enter(function_name: &'static str) {
+        EVENTS.with(|events| events.borrow_mut().push(format!("enter {}", function_name)));
+    }
+
+    pub(crate) fn exit(function_name: &'static str) {
+        EVENTS.with(|events| events.borrow_mut().push(format!("exit {}", function_name)));
+    }
+
+    pub(crate) fn events() -> Vec<String> {
+        EVENTS.with(|events| events.borrow().clone())
+    }
+}
+
+pub mod api {
+    pub fn add(a: i32, b: i32) -> i32 {
+        a + b
+    }
+
+    pub fn classify(n: i32) -> &'static str {
+        if n < 0 {
+            return "negative";
+        }
+        helper(n)
+    }
+
+    fn helper(n: i32) -> &'static str {
+        if n == 0 {
+            "zero"
+        } else {
+            "positive"
+        }
+    }
+}
+
+fn main() {
+    assert_eq!(api::add(2, 3), 5);
+    assert_eq!(api::classify(-1), "negative");
+    assert_eq!(api::classify(4), "positive");
+    for event in trace::events() {
+        println!("{}", event);
+    }
+}
@@ -0,0 +1,435 @@
+//! Analysis output tests: run the driver on `fixtures/traced.rs` and read
+//! the file written by `--aspect-output`.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn sysroot() -> String {
+    let output = Command::new("rustc")
+        .args(["--print", "sysroot"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to run rustc");
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// Analyze the fixture, returning the contents of the output file.
+fn analyze(name: &str, driver_args: &[&str]) -> String {
+    analyze_fixture("traced.rs", name, driver_args)
+}
+
+/// Analyze a fixture of `tests/fixtures`, returning the contents of the
+/// output file.
+fn analyze_fixture(fixture: &str, name: &str, driver_args: &[&str]) -> String {
+    std::fs::read_to_string(run_driver(fixture, name, driver_args)).unwrap()
+}
+
+/// Run the driver on a fixture, returning the path of the output file.
+fn run_driver(fixture: &str, name: &str, driver_args: &[&str]) -> PathBuf {
+    let output = driver(fixture, name, driver_args)
+        .output()
+        .expect("failed to run aspect-rustc-driver");
+    assert!(
+        output.status.success(),
+        "analysis failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+/// The driver command analyzing a fixture into the output file `name`.
+fn driver(fixture: &str, name: &str, driver_args: &[&str]) -> Command {
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_aspect-rustc-driver"));
+    command
+        .arg(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures")
+                .join(fixture),
+        )
+        .args(["--edition", "2021", "--crate-type", "bin", "--sysroot"])
+        .arg(sysroot())
+        .arg("-o")
+        .arg(out_dir.join(format!("{}.bin", name)))
+        .args(["--aspect-pointcut", "execution(pub fn *(..))"])
+        .arg("--aspect-cache-dir")
+        .arg(out_dir.join("cache"))
+        .arg("--aspect-output")
+        .arg(out_dir.join(name))
+        .args(driver_args);
+    command
+}
+
+#[test]
+fn test_json_output() {
+    let json: serde_json::Value =
+        serde_json::from_str(&analyze("analysis.json", &["--aspect-format", "json"])).unwrap();
+
+    assert_eq!(json["format_version"], 2);
+    assert_eq!(
+        json["pointcuts"][0]["expression"],
+        "execution(pub fn *(..))"
+    );
+    assert_eq!(json["pointcuts"][0]["matches"], 2);
+
+    let functions = json["functions"].as_array().unwrap();
+    let add = functions.iter().find(|f| f["name"] == "api::add").unwrap();
+    assert_eq!(add["visibility"], "public");
+    let visibility = |name: &str| {
+        let function = functions.iter().find(|f| f["name"] == name).unwrap();
+        function["visibility"].clone()
+    };
+    assert_eq!(visibility("trace::enter"), "crate");
+    assert_eq!(visibility("api::helper"), "private");
+    assert!(add["location"]["file"]
+        .as_str()
+        .unwrap()
+        .ends_with("traced.rs"));
+
+    let matched: Vec<_> = json["matches"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["function"].as_str().unwrap())
+        .collect();
+    assert_eq!(matched, vec!["api::add", "api::classify"]);
+    assert_eq!(json["stats"]["matched_functions"], 2);
+}
+
+#[test]
+fn test_sarif_output() {
+    let sarif: serde_json::Value =
+        serde_json::from_str(&analyze("analysis.sarif", &["--aspect-format", "sarif"])).unwrap();
+
+    assert_eq!(sarif["version"], "2.1.0");
+    let results = sarif["runs"][0]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(|r| r["ruleId"] == "AR001"));
+    // The whole of `api::add`, up to its closing brace
+    assert_eq!(
+        results[0]["locations"][0]["physicalLocation"]["region"],
+        serde_json::json!({ "startLine": 25, "startColumn": 5, "endLine": 27, "endColumn": 6 })
+    );
+}
+
+#[test]
+fn test_weaving_plan() {
+    let plan: serde_json::Value = serde_json::from_str(&analyze(
+        "plan.json",
+        &[
+            "--aspect-plan",
+            "--aspect-format",
+            "json",
+            "--aspect-before",
+            "within(crate::api)=crate::trace::enter",
+        ],
+    ))
+    .unwrap();
+
+    let pointcuts = plan["pointcuts"].as_array().unwrap();
+    assert_eq!(pointcuts.len(), 2);
+    let within = &pointcuts[1];
+    assert_eq!(within["pointcut"], "within(crate::api)");
+    assert_eq!(within["advice"][0]["advice_type"], "before");
+
+    let mut matched: Vec<_> = within["matched"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["function"].as_str().unwrap())
+        .collect();
+    matched.sort();
+    assert_eq!(matched, vec!["api::add", "api::classify", "api::helper"]);
+    assert_eq!(
+        within["matched"][0]["advice"][0]["hook"],
+        "crate::trace::enter"
+    );
+
+    let main = within["excluded"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|e| e["function"] == "main")
+        .unwrap();
+    assert_eq!(main["reason"], "does not match within(crate::api)");
+}
+
+#[test]
+fn test_config_file() {
+    let config = Path::new(env!("CARGO_TARGET_TMPDIR")).join("aspect.toml");
+    std::fs::write(
+        &config,
+        r#"
+plan = true
+
+[[aspects]]
+pointcut = "name(classify)"
+advice = "before"
+hook = "crate::trace::exit"
+
+[[aspects]]
+pointcut = "name(classify)"
+advice = "before"
+hook = "crate::trace::enter"
+priority = 10
+
+[output]
+format = "text"
+"#,
+    )
+    .unwrap();
+
+    // --aspect-format overrides the format of the file
+    let plan: serde_json::Value = serde_json::from_str(&analyze(
+        "config-plan.json",
+        &[
+            "--aspect-config",
+            config.to_str().unwrap(),
+            "--aspect-format",
+            "json",
+        ],
+    ))
+    .unwrap();
+
+    let classify = &plan["pointcuts"][1];
+    assert_eq!(classify["pointcut"], "name(classify)");
+    // Highest priority first
+    let advice = &classify["matched"][0]["advice"];
+    assert_eq!(advice[0]["hook"], "crate::trace::enter");
+    assert_eq!(advice[1]["hook"], "crate::trace::exit");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_analysis_pass() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Counts the pointcuts of its input
+    let pass = Path::new(env!("CARGO_TARGET_TMPDIR")).join("count-matches");
+    std::fs::write(
+        &pass,
+        r#"#!/bin/sh
+n=$(grep -o '"pointcut":' | wc -l | tr -d ' ')
+echo "[{\"severity\": \"note\", \"message\": \"$n matches\"}]"
+"#,
+    )
+    .unwrap();
+    std::fs::set_permissions(&pass, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let json: serde_json::Value = serde_json::from_str(&analyze(
+        "pass.json",
+        &[
+            "--aspect-format",
+            "json",
+            "--aspect-pass",
+            pass.to_str().unwrap(),
+        ],
+    ))
+    .unwrap();
+
+    let finding = &json["findings"][0];
+    assert_eq!(finding["pass"], pass.to_str().unwrap());
+    assert_eq!(finding["severity"], "note");
+    assert!(finding["message"].as_str().unwrap().ends_with(" matches"));
+}
+
+#[test]
+fn test_weaving_plan_dot() {
+    let dot = analyze(
+        "plan.dot",
+        &[
+            "--aspect-plan",
+            "--aspect-format",
+            "dot",
+            "--aspect-before",
+            "within(crate::api)=crate::trace::enter",
+        ],
+    );
+
+    assert!(dot.starts_with("digraph weaving_plan {"));
+    assert!(dot.contains("label=\"within(crate::api)\\nbefore crate::trace::enter\""));
+    assert!(dot.contains("label=\"crate::api\";"));
+    assert!(dot.contains("\"pointcut1\" -> \"fn api::add\";"));
+}
+
+#[test]
+fn test_methods_nested_functions_and_closures() {
+    let functions = |name: &str, driver_args: &[&str]| {
+        let mut args = vec!["--aspect-format", "json"];
+        args.extend(driver_args);
+        let json: serde_json::Value =
+            serde_json::from_str(&analyze_fixture("nested.rs", name, &args)).unwrap();
+        json["functions"].as_array().unwrap().clone()
+    };
+    let find = |functions: &[serde_json::Value], suffix: &str| {
+        functions
+            .iter()
+            .find(|f| f["name"].as_str().unwrap().ends_with(suffix))
+            .cloned()
+    };
+
+    let without_closures = functions("nested.json", &[]);
+    let area = find(&without_closures, "::area").unwrap();
+    assert_eq!(area["module_path"], "crate::shapes");
+    assert_eq!(area["impl_type"], "shapes::Square");
+    let describe = find(&without_closures, "::describe").unwrap();
+    assert_eq!(describe["trait_name"], "shapes::Describe");
+    // Nested functions are in the module of the function around them
+    let square = find(&without_closures, "::area::square").unwrap();
+    assert_eq!(square["module_path"], "crate::shapes");
+    assert_eq!(square["visibility"], "private");
+    assert!(find(&without_closures, "{closure#0}").is_none());
+
+    let with_closures = functions("nested-closures.json", &["--aspect-closures"]);
+    let closure = find(&with_closures, "::total::{closure#0}").unwrap();
+    assert_eq!(closure["is_closure"], true);
+    assert_eq!(closure["module_path"], "crate::shapes");
+    assert_eq!(closure["return_type"], "f64");
+    assert_eq!(with_closures.len(), without_closures.len() + 1);
+}
+
+#[test]
+fn test_field_accesses() {
+    let json: serde_json::Value = serde_json::from_str(&analyze_fixture(
+        "nested.rs",
+        "nested-fields.json",
+        &["--aspect-format", "json", "--aspect-field", "Square.*"],
+    ))
+    .unwrap();
+
+    // `square(self.0)` in `Square::area` is the only access
+    let accesses = json["field_accesses"].as_array().unwrap();
+    assert_eq!(accesses.len(), 1, "{:#?}", accesses);
+    assert_eq!(accesses[0]["kind"], "get");
+    assert_eq!(accesses[0]["struct_path"], "shapes::Square");
+    assert_eq!(accesses[0]["field"], "0");
+    assert!(accesses[0]["function"]
+        .as_str()
+        .unwrap()
+        .ends_with("::area"));
+    assert_eq!(accesses[0]["location"]["line"], 12);
+}
+
+#[test]
+fn test_emit_source() {
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("expanded");
+    let _ = std::fs::remove_dir_all(&out_dir);
+    analyze(
+        "expanded.txt",
+        &[
+            "--aspect-before",
+            "within(crate::api)=crate::trace::enter",
+            "--aspect-after",
+            "name(classify)=crate::trace::exit",
+            "--aspect-emit-source",
+            out_dir.to_str().unwrap(),
+        ],
+    );
+
+    // The driver runs in the package directory
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/traced.rs");
+    let original = std::fs::read_to_string(&fixture).unwrap();
+    let expanded = std::fs::read_to_string(out_dir.join("tests/fixtures/traced.rs")).unwrap();
+    assert_eq!(expanded.lines().count(), original.lines().count());
+    assert!(expanded
+        .contains("    pub fn add(a: i32, b: i32) -> i32 { crate::trace::enter(\"api::add\");\n"));
+    assert!(expanded.contains(
+        "    pub fn classify(n: i32) -> &'static str { crate::trace::enter(\"api::classify\"); \
+         let __aspect_result = (move || -> &'static str {\n"
+    ));
+    assert!(
+        expanded.contains("    })(); crate::trace::exit(\"api::classify\"); __aspect_result }\n")
+    );
+    // Hooks are never advised
+    assert!(!expanded.contains("crate::trace::enter(\"trace::enter\")"));
+}
+
+#[test]
+fn test_text_output_is_the_default() {
+    let text = analyze("analysis.txt", &[]);
+    assert!(text.starts_with("=== Aspect Weaving Analysis Results ==="));
+}
+
+#[test]
+fn test_excluded_crate() {
+    // Compiled as by rustc: no analysis, so no output file
+    let _ = std::fs::remove_file(Path::new(env!("CARGO_TARGET_TMPDIR")).join("excluded.json"));
+    let output_file = run_driver(
+        "traced.rs",
+        "excluded.json",
+        &["--aspect-exclude", "traced"],
+    );
+    assert!(!output_file.exists());
+
+    let json = analyze(
+        "only.json",
+        &["--aspect-only-crate", "traced", "--aspect-format", "json"],
+    );
+    assert!(json.contains("\"api::add\""));
+}
+
+#[test]
+fn test_diagnostics_and_exit_codes() {
+    let output = driver(
+        "traced.rs",
+        "unmatched.json",
+        &[
+            "--aspect-pointcut",
+            "name(fetch_usr)",
+            "--aspect-deny-unmatched",
+            "--aspect-diagnostics",
+            "json",
+        ],
+    )
+    .output()
+    .unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    let diagnostics: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "AR002");
+    assert_eq!(diagnostics[0]["severity"], "error");
+
+    // A hook that does not exist
+    let output = driver(
+        "traced.rs",
+        "unwoven.json",
+        &["--aspect-before", "name(add)=crate::missing::hook"],
+    )
+    .output()
+    .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+
+    let output = driver("traced.rs", "usage.json", &["--aspect-format", "yaml"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_config_profile() {
+    let config = Path::new(env!("CARGO_TARGET_TMPDIR")).join("profiles.toml");
+    std::fs::write(
+        &config,
+        r#"
+[profiles.strict]
+pointcuts = ["name(fetch_usr)"]
+deny_unmatched = true
+"#,
+    )
+    .unwrap();
+    let config = config.to_str().unwrap();
+
+    analyze("profile-default.json", &["--aspect-config", config]);
+    let output = driver(
+        "traced.rs",
+        "profile-strict.json",
+        &["--aspect-config", config, "--aspect-profile", "strict"],
+    )
+    .output()
+    .unwrap();
+    assert_eq!(output.status.code(), Some(4));
+}
@@ -0,0 +1,102 @@
+//! End-to-end weaving tests: compile `fixtures/traced.rs` with the driver
+//! and run the result.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn sysroot() -> String {
+    let output = Command::new("rustc")
+        .args(["--print", "sysroot"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to run rustc");
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// Compile the fixture with extra driver arguments, returning the path of
+/// the executable.
+fn compile(name: &str, driver_args: &[&str]) -> PathBuf {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let exe = out_dir.join(name);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aspect-rustc-driver"))
+        .arg(manifest_dir.join("tests/fixtures/traced.rs"))
+        .args(["--edition", "2021", "--crate-type", "bin", "--sysroot"])
+        .arg(sysroot())
+        .arg("-o")
+        .arg(&exe)
+        .args(driver_args)
+        .output()
+        .expect("failed to run aspect-rustc-driver");
+    assert!(
+        output.status.success(),
+        "compilation failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    exe
+}
+
+fn run(exe: &Path) -> Vec<String> {
+    let output = Command::new(exe)
+        .output()
+        .expect("failed to run woven binary");
+    assert!(output.status.success());
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn test_before_and_after_advice_woven() {
+    let exe = compile(
+        "traced_woven",
+        &[
+            "--aspect-before",
+            "execution(pub fn *(..))=crate::trace::enter",
+            "--aspect-after",
+            "execution(pub fn *(..))=crate::trace::exit",
+        ],
+    );
+
+    // Private functions and the hooks themselves are not advised, and every
+    // return path runs the after hook
+    assert_eq!(
+        run(&exe),
+        vec![
+            "enter api::add",
+            "exit api::add",
+            "enter api::classify",
+            "exit api::classify",
+            "enter api::classify",
+            "exit api::classify",
+        ]
+    );
+}
+
+#[test]
+fn test_without_advice_nothing_is_woven() {
+    let exe = compile("traced_plain", &[]);
+    assert!(run(&exe).is_empty());
+}
+
+#[test]
+fn test_unknown_hook_is_an_error() {
+    let output = Command::new(env!("CARGO_BIN_EXE_aspect-rustc-driver"))
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/traced.rs"))
+        .args(["--edition", "2021", "--crate-type", "bin", "--sysroot"])
+        .arg(sysroot())
+        .arg("-o")
+        .arg(Path::new(env!("CARGO_TARGET_TMPDIR")).join("traced_error"))
+        .args([
+            "--aspect-before",
+            "execution(pub fn *(..))=crate::trace::missing",
+        ])
+        .output()
+        .expect("failed to run aspect-rustc-driver");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("advice hook 'crate::trace::missing' not found"));
+}
@@ -0,0 +1,102 @@
+//! Benchmarks for standard aspects under concurrency
+//!
+//! Measures a call through an aspect alone, and while other threads keep
+//! calling other functions through the same aspect.
+
+use aspect_core::{Aspect, JoinPoint, Location, ProceedingJoinPoint};
+use aspect_std::{MetricsAspect, RateLimitAspect, TimingAspect};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const THREADS: [usize; 2] = [1, 4];
+
+/// A call of `function_name` through `aspect`.
+fn call(aspect: &(impl Aspect + ?Sized), function_name: &'static str) {
+    let context = JoinPoint::new(
+        function_name,
+        "bench",
+        Location {
+            file: "benches/concurrency.rs",
+            line: 1,
+        },
+    );
+    let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn Any>), context);
+    let _ = black_box(aspect.around(pjp));
+}
+
+/// Names of the functions called by the other threads.
+fn functions() -> &'static [&'static str] {
+    static FUNCTIONS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    FUNCTIONS.get_or_init(|| {
+        (0..64)
+            .map(|i| &*Box::leak(format!("function_{}", i).into_boxed_str()))
+            .collect()
+    })
+}
+
+/// Benchmarks `iters` calls through `aspect`, while `threads` threads keep
+/// calling other functions through it.
+fn contended(aspect: &(impl Aspect + ?Sized), threads: usize, iters: u64) -> Duration {
+    let functions = functions();
+    let stop = AtomicBool::new(false);
+    thread::scope(|scope| {
+        for thread in 0..threads {
+            let stop = &stop;
+            scope.spawn(move || {
+                let mut i = thread;
+                while !stop.load(Ordering::Relaxed) {
+                    call(aspect, functions[i % functions.len()]);
+                    i += threads;
+                }
+            });
+        }
+        let start = Instant::now();
+        for _ in 0..iters {
+            call(aspect, "measured");
+        }
+        let elapsed = start.elapsed();
+        stop.store(true, Ordering::Relaxed);
+        elapsed
+    })
+}
+
+/// Benchmarks `aspect` alone and under contention, in the group `name`.
+fn bench_aspect(c: &mut Criterion, name: &str, aspect: impl Fn() -> Box<dyn Aspect>) {
+    let mut group = c.benchmark_group(name);
+
+    let uncontended = aspect();
+    group.bench_function("uncontended", |b| {
+        b.iter(|| call(&*uncontended, "measured"))
+    });
+
+    for threads in THREADS {
+        let shared = aspect();
+        group.bench_function(BenchmarkId::new("contended", threads), |b| {
+            b.iter_custom(|iters| contended(&*shared, threads, iters))
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_timing(c: &mut Criterion) {
+    bench_aspect(c, "timing", || Box::new(TimingAspect::new()));
+}
+
+fn bench_rate_limit(c: &mut Criterion) {
+    // A limit never reached, so that every call takes a token
+    bench_aspect(c, "rate_limit", || {
+        Box::new(RateLimitAspect::new(u64::MAX, Duration::from_secs(1)))
+    });
+}
+
+fn bench_metrics(c: &mut Criterion) {
+    bench_aspect(c, "metrics", || Box::new(MetricsAspect::new()));
+}
+
+criterion_group!(benches, bench_timing, bench_rate_limit, bench_metrics);
+criterion_main!(benches);
@@ -0,0 +1,402 @@
+//! Aspects as actix-web middleware.
+//!
+//! [`AspectMiddleware`] applies an aspect to the requests of an app, scope
+//! or resource through [`Aspect::around_async`], so the aspects it supports
+//! are those the tower adapter does. Available with the `actix-web`
+//! feature.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use aspect_core::{Arg, Aspect, AspectError, JoinPoint, Location, Symbol};
+use parking_lot::Mutex;
+use std::any::Any;
+use std::future::{poll_fn, ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::Poll;
+
+/// Actix futures are not `Send`.
+type LocalBoxFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+
+/// Middleware applying an aspect to requests, as calls of their routes.
+///
+/// The join point of a request is named after the pattern of the resource
+/// it matches, e.g. `/users/{id}`, or after the middleware when it matches
+/// none. Its module is `actix` and its arguments are the `method`
+/// ([`Method`](actix_web::http::Method)) and `path` (`String`) of the
+/// request.
+///
+/// The aspect sees the status of the response as the result of the call,
+/// and responses with a server error status and errors of the service as
+/// failures, which [`after_error`](Aspect::after_error) gets as
+/// [`AspectError`]s; the response or error itself reaches the client
+/// unchanged. A request the aspect rejects fails with `403 Forbidden` for
+/// [`AspectError::Denied`], `429 Too Many Requests` for
+/// [`AspectError::RateLimited`], `504 Gateway Timeout` for
+/// [`AspectError::Timeout`] and `503 Service Unavailable` otherwise, with a
+/// `Retry-After` header when the error tells how long to wait.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::actix::AspectMiddleware;
+/// use aspect_std::TimingAspect;
+/// use actix_web::{web, App};
+///
+/// let timing = Arc::new(TimingAspect::new());
+/// let app = App::new()
+///     .wrap(AspectMiddleware::shared(timing.clone(), "api"))
+///     .route("/users/{id}", web::get().to(get_user));
+/// // Later: timing.get_stats("/users/{id}")
+/// ```
+pub struct AspectMiddleware<A: ?Sized> {
+    aspect: Arc<A>,
+    name: &'static str,
+    location: Location,
+}
+
+impl<A: Aspect> AspectMiddleware<A> {
+    /// Apply `aspect` to requests, as calls of their routes, or of `name`.
+    #[track_caller]
+    pub fn new(aspect: A, name: &'static str) -> Self {
+        Self::shared(Arc::new(aspect), name)
+    }
+}
+
+impl<A: Aspect + ?Sized> AspectMiddleware<A> {
+    /// Apply an aspect shared with other code, e.g. an `Arc<dyn Aspect>`,
+    /// to requests, as calls of their routes, or of `name`.
+    #[track_caller]
+    pub fn shared(aspect: Arc<A>, name: &'static str) -> Self {
+        let caller = std::panic::Location::caller();
+        Self {
+            aspect,
+            name,
+            location: Location {
+                file: caller.file(),
+                line: caller.line(),
+            },
+        }
+    }
+}
+
+impl<A: ?Sized> Clone for AspectMiddleware<A> {
+    fn clone(&self) -> Self {
+        Self {
+            aspect: self.aspect.clone(),
+            name: self.name,
+            location: self.location,
+        }
+    }
+}
+
+impl<S, A, B> Transform<S, ServiceRequest> for AspectMiddleware<A>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    A: Aspect + ?Sized + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = AspectMiddlewareService<S, A>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, ()>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AspectMiddlewareService {
+            service: Rc::new(service),
+            middleware: self.clone(),
+        }))
+    }
+}
+
+/// The service of [`AspectMiddleware`].
+pub struct AspectMiddlewareService<S, A: ?Sized> {
+    service: Rc<S>,
+    middleware: AspectMiddleware<A>,
+}
+
+impl<S, A: ?Sized> AspectMiddlewareService<S, A> {
+    fn join_point(&self, request: &ServiceRequest) -> JoinPoint {
+        let name = match request.match_pattern() {
+            Some(pattern) => Symbol::intern(&pattern).as_str(),
+            None => self.middleware.name,
+        };
+        JoinPoint::new(name, "actix", self.middleware.location).with_args(vec![
+            Arg::new("method", request.method()),
+            Arg::new("path", &request.path().to_string()),
+        ])
+    }
+}
+
+/// Lets the `Send` future the aspect proceeds with wait for the service,
+/// whose future is not `Send`.
+#[derive(Default)]
+struct Handshake {
+    /// Whether the aspect proceeded
+    proceeded: bool,
+    /// What the aspect sees of the outcome of the service, once known
+    outcome: Option<Result<u16, AspectError>>,
+}
+
+impl<S, A, B> Service<ServiceRequest> for AspectMiddlewareService<S, A>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    A: Aspect + ?Sized + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<Result<ServiceResponse<B>, Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let ctx = self.join_point(&request);
+        let service = self.service.clone();
+        let aspect = self.middleware.aspect.clone();
+
+        Box::pin(async move {
+            let handshake = Arc::new(Mutex::new(Handshake::default()));
+            let proceed = {
+                let handshake = handshake.clone();
+                Box::pin(poll_fn(move |_| {
+                    let mut handshake = handshake.lock();
+                    handshake.proceeded = true;
+                    match handshake.outcome.take() {
+                        Some(outcome) => {
+                            Poll::Ready(outcome.map(|status| Box::new(status) as Box<dyn Any>))
+                        }
+                        // Polled again once the service responded
+                        None => Poll::Pending,
+                    }
+                }))
+            };
+            let mut around = aspect.around_async(&ctx, proceed);
+            let mut request = Some(request);
+            let mut call = None;
+            let mut response = None;
+
+            let result = poll_fn(|cx| loop {
+                if let Poll::Ready(result) = around.as_mut().poll(cx) {
+                    return Poll::Ready(result);
+                }
+                if handshake.lock().proceeded {
+                    if let Some(request) = request.take() {
+                        call = Some(Box::pin(service.call(request)));
+                    }
+                }
+                let Some(future) = call.as_mut() else {
+                    return Poll::Pending;
+                };
+                let Poll::Ready(outcome) = future.as_mut().poll(cx) else {
+                    return Poll::Pending;
+                };
+                call = None;
+                let seen = match &outcome {
+                    Ok(response) if !response.status().is_server_error() => {
+                        Ok(response.status().as_u16())
+                    }
+                    Ok(response) => Err(AspectError::execution(format!(
+                        "{} responded {}",
+                        ctx.function_name,
+                        response.status()
+                    ))),
+                    Err(error) => Err(AspectError::execution(format!(
+                        "{} failed: {}",
+                        ctx.function_name, error
+                    ))),
+                };
+                handshake.lock().outcome = Some(seen);
+                response = Some(outcome);
+            })
+            .await;
+
+            match (response, result) {
+                // What the service returned, as is
+                (Some(outcome), _) => outcome,
+                (None, Err(AspectError::Denied { required, actual })) => {
+                    Err(actix_web::error::ErrorForbidden(format!(
+                        "requires {}, caller has {}",
+                        required, actual
+                    )))
+                }
+                (None, Err(error)) => Err(rejection(error)),
+                (None, Ok(_)) => Err(actix_web::error::ErrorInternalServerError(format!(
+                    "aspect answered {} without calling the service",
+                    ctx.function_name
+                ))),
+            }
+        })
+    }
+}
+
+/// The error for a request the aspect rejected with `error`.
+fn rejection(error: AspectError) -> Error {
+    use actix_web::http::{header, StatusCode};
+
+    let status = match error {
+        AspectError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        AspectError::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    let mut response = actix_web::HttpResponse::new(status);
+    if let Some(wait) = error.retry_after() {
+        // In whole seconds, rounded up
+        let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, header::HeaderValue::from(seconds));
+    }
+    actix_web::error::InternalError::from_response(error, response).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CircuitBreakerAspect, RateLimitAspect, TimingAspect};
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, try_call_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+    use std::time::Duration;
+
+    /// Responds with the status in the path, e.g. `/status/503`.
+    async fn status(code: web::Path<u16>) -> HttpResponse {
+        HttpResponse::build(StatusCode::from_u16(*code).unwrap()).body(code.to_string())
+    }
+
+    fn run(test: impl Future<Output = ()>) {
+        actix_web::rt::System::new().block_on(test);
+    }
+
+    #[test]
+    fn test_timing_per_route() {
+        run(async {
+            let timing = Arc::new(TimingAspect::new());
+            let app = init_service(
+                App::new().service(
+                    web::resource("/status/{code}")
+                        .wrap(AspectMiddleware::shared(timing.clone(), "api"))
+                        .route(web::get().to(status)),
+                ),
+            )
+            .await;
+
+            let response =
+                call_service(&app, TestRequest::get().uri("/status/200").to_request()).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            // Server errors reach the client unchanged
+            let response =
+                call_service(&app, TestRequest::get().uri("/status/500").to_request()).await;
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+            assert_eq!(actix_web::test::read_body(response).await, "500");
+
+            assert_eq!(timing.get_stats("/status/{code}").unwrap().count, 2);
+        });
+    }
+
+    /// Records the join points and what advice saw of the outcome.
+    #[derive(Default)]
+    struct Recorder {
+        log: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl Aspect for Recorder {
+        fn before(&self, ctx: &JoinPoint) {
+            let method = ctx
+                .arg("method")
+                .unwrap()
+                .value::<actix_web::http::Method>();
+            let path = ctx.arg("path").unwrap().value::<String>();
+            self.log.lock().unwrap().push(format!(
+                "{} {} {}",
+                ctx.function_name,
+                method.unwrap(),
+                path.unwrap()
+            ));
+        }
+
+        fn after(&self, _ctx: &JoinPoint, result: &dyn Any) {
+            let status = result.downcast_ref::<u16>().unwrap();
+            self.log.lock().unwrap().push(format!("after {}", status));
+        }
+
+        fn after_error(&self, _ctx: &JoinPoint, error: &AspectError) {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("after_error {}", error));
+        }
+    }
+
+    #[test]
+    fn test_join_points_and_outcomes() {
+        run(async {
+            let recorder = Arc::new(Recorder::default());
+            let app = init_service(
+                App::new()
+                    .wrap(AspectMiddleware::shared(recorder.clone(), "app"))
+                    .route("/status/{code}", web::get().to(status)),
+            )
+            .await;
+
+            for uri in ["/status/204", "/status/503", "/unknown"] {
+                call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            }
+            assert_eq!(
+                *recorder.log.lock().unwrap(),
+                [
+                    "/status/{code} GET /status/204",
+                    "after 204",
+                    "/status/{code} GET /status/503",
+                    "after_error Execution error: /status/{code} responded 503 Service Unavailable",
+                    "app GET /unknown",
+                    "after 404",
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn test_rejected_requests() {
+        run(async {
+            let app = init_service(
+                App::new()
+                    .wrap(AspectMiddleware::new(
+                        RateLimitAspect::new(1, Duration::from_secs(60)),
+                        "api",
+                    ))
+                    .route("/status/{code}", web::get().to(status)),
+            )
+            .await;
+            let request = || TestRequest::get().uri("/status/200").to_request();
+            assert_eq!(call_service(&app, request()).await.status(), StatusCode::OK);
+            let error = try_call_service(&app, request()).await.unwrap_err();
+            let response = error.error_response();
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(response.headers().get("retry-after").unwrap(), "60");
+
+            let breaker: Arc<dyn Aspect> =
+                Arc::new(CircuitBreakerAspect::new(2, Duration::from_secs(60)));
+            let app = init_service(
+                App::new()
+                    .wrap(AspectMiddleware::shared(breaker, "api"))
+                    .route("/status/{code}", web::get().to(status)),
+            )
+            .await;
+            for code in [502, 503] {
+                let request = TestRequest::get().uri(&format!("/status/{}", code));
+                let response = call_service(&app, request.to_request()).await;
+                assert_eq!(response.status().as_u16(), code);
+            }
+            // Two server errors opened the circuit
+            let request = TestRequest::get().uri("/status/200").to_request();
+            let error = try_call_service(&app, request).await.unwrap_err();
+            let response = error.error_response();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+            assert_eq!(response.headers().get("retry-after").unwrap(), "60");
+        });
+    }
+}
@@ -0,0 +1,387 @@
+//! Allocation tracking aspect backed by a counting global allocator.
+//!
+//! Requires the `alloc-tracking` feature, and [`CountingAllocator`] to be
+//! installed as the global allocator of the program:
+//!
+//! ```rust,ignore
+//! use aspect_std::alloc::CountingAllocator;
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: CountingAllocator = CountingAllocator::system();
+//! ```
+
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, Precedence, ProceedingJoinPoint};
+use parking_lot::Mutex;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::any::Any;
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::ops::Sub;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    static DEALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    static BYTES_ALLOCATED: Cell<u64> = const { Cell::new(0) };
+    static BYTES_FREED: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Set once the counting allocator has served an allocation.
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+fn add(counter: &'static std::thread::LocalKey<Cell<u64>>, value: u64) {
+    // Ignore allocations made while the thread is being torn down
+    let _ = counter.try_with(|counter| counter.set(counter.get().wrapping_add(value)));
+}
+
+/// Global allocator counting the allocations of each thread before
+/// delegating to another allocator ([`System`] by default).
+///
+/// Counting costs a few thread-local increments per allocation.
+pub struct CountingAllocator<A = System> {
+    inner: A,
+}
+
+impl CountingAllocator<System> {
+    /// Count allocations served by the system allocator.
+    pub const fn system() -> Self {
+        Self { inner: System }
+    }
+}
+
+impl<A> CountingAllocator<A> {
+    /// Count allocations served by `inner`.
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+
+    /// Returns `true` if a `CountingAllocator` is the global allocator, as
+    /// far as can be told from it having served an allocation.
+    pub fn is_installed() -> bool {
+        INSTALLED.load(Ordering::Relaxed)
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record_alloc(layout.size());
+        unsafe { self.inner.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record_alloc(layout.size());
+        unsafe { self.inner.alloc_zeroed(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        record_dealloc(layout.size());
+        unsafe { self.inner.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // Counted as a new allocation replacing the old one
+        record_dealloc(layout.size());
+        record_alloc(new_size);
+        unsafe { self.inner.realloc(ptr, layout, new_size) }
+    }
+}
+
+fn record_alloc(size: usize) {
+    INSTALLED.store(true, Ordering::Relaxed);
+    add(&ALLOCATIONS, 1);
+    add(&BYTES_ALLOCATED, size as u64);
+}
+
+fn record_dealloc(size: usize) {
+    add(&DEALLOCATIONS, 1);
+    add(&BYTES_FREED, size as u64);
+}
+
+/// Allocation counts, either totals for a thread or the difference between
+/// two points in time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    /// Number of allocations, including reallocations
+    pub allocations: u64,
+    /// Number of deallocations, including the memory replaced by
+    /// reallocations
+    pub deallocations: u64,
+    /// Bytes allocated
+    pub bytes_allocated: u64,
+    /// Bytes freed
+    pub bytes_freed: u64,
+}
+
+impl AllocStats {
+    /// Totals for the current thread since it started.
+    ///
+    /// Always zero unless [`CountingAllocator`] is the global allocator.
+    pub fn current_thread() -> Self {
+        Self {
+            allocations: ALLOCATIONS.with(Cell::get),
+            deallocations: DEALLOCATIONS.with(Cell::get),
+            bytes_allocated: BYTES_ALLOCATED.with(Cell::get),
+            bytes_freed: BYTES_FREED.with(Cell::get),
+        }
+    }
+
+    /// Bytes allocated and not freed; negative if more was freed.
+    pub fn net_bytes(&self) -> i64 {
+        self.bytes_allocated as i64 - self.bytes_freed as i64
+    }
+}
+
+impl Sub for AllocStats {
+    type Output = AllocStats;
+
+    fn sub(self, earlier: AllocStats) -> AllocStats {
+        AllocStats {
+            allocations: self.allocations.wrapping_sub(earlier.allocations),
+            deallocations: self.deallocations.wrapping_sub(earlier.deallocations),
+            bytes_allocated: self.bytes_allocated.wrapping_sub(earlier.bytes_allocated),
+            bytes_freed: self.bytes_freed.wrapping_sub(earlier.bytes_freed),
+        }
+    }
+}
+
+/// Allocation statistics of one function.
+#[derive(Debug, Clone, Default)]
+pub struct FunctionAllocStats {
+    /// Function name
+    pub name: String,
+    /// Number of calls
+    pub calls: u64,
+    /// Allocations made by all calls together
+    pub total: AllocStats,
+    /// Most bytes allocated by a single call
+    pub max_bytes_per_call: u64,
+}
+
+impl FunctionAllocStats {
+    /// Average number of allocations per call.
+    pub fn allocations_per_call(&self) -> f64 {
+        if self.calls > 0 {
+            self.total.allocations as f64 / self.calls as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// Average number of bytes allocated per call.
+    pub fn bytes_per_call(&self) -> f64 {
+        if self.calls > 0 {
+            self.total.bytes_allocated as f64 / self.calls as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Aspect reporting the allocations made inside advised functions.
+///
+/// Counts include everything the function allocates on the calling thread,
+/// including in the functions it calls; allocations made on other threads
+/// are not attributed to it. They also include the one small allocation the
+/// woven code makes to box a non-empty return value. [`CountingAllocator`]
+/// has to be the global allocator, otherwise all counts stay zero and a
+/// warning is logged.
+///
+/// Executions woven through [`around_async`](Aspect::around_async), such as
+/// those of the tower adapter, move between threads, so they are not
+/// counted but fail with [`AspectError::WeavingError`].
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::alloc::{AllocTrackingAspect, CountingAllocator};
+/// use aspect_macros::aspect;
+/// use std::sync::LazyLock;
+///
+/// #[global_allocator]
+/// static ALLOCATOR: CountingAllocator = CountingAllocator::system();
+///
+/// static ALLOCS: LazyLock<AllocTrackingAspect> = LazyLock::new(AllocTrackingAspect::new);
+///
+/// #[aspect(ALLOCS.clone())]
+/// fn render_page(items: &[Item]) -> String {
+///     items.iter().map(|item| item.to_html()).collect()
+/// }
+///
+/// // Later:
+/// ALLOCS.print_stats();
+/// ```
+#[derive(Clone, Default)]
+pub struct AllocTrackingAspect {
+    stats: Arc<Mutex<HashMap<String, FunctionAllocStats>>>,
+}
+
+impl AllocTrackingAspect {
+    /// Create a new allocation tracking aspect.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get statistics for a specific function.
+    pub fn get_stats(&self, function_name: &str) -> Option<FunctionAllocStats> {
+        self.stats.lock().get(function_name).cloned()
+    }
+
+    /// Get all function statistics, most bytes allocated first.
+    pub fn all_stats(&self) -> Vec<FunctionAllocStats> {
+        let mut stats: Vec<_> = self.stats.lock().values().cloned().collect();
+        stats.sort_by_key(|stat| Reverse(stat.total.bytes_allocated));
+        stats
+    }
+
+    /// Print statistics for all functions, most bytes allocated first.
+    pub fn print_stats(&self) {
+        let stats = self.all_stats();
+        if stats.is_empty() {
+            println!("No allocation data collected.");
+            return;
+        }
+
+        println!("\n=== Allocation Statistics ===");
+        println!(
+            "{:<30} {:>10} {:>14} {:>16} {:>14} {:>16}",
+            "Function", "Calls", "Allocations", "Bytes", "Allocs/call", "Max bytes/call"
+        );
+        println!("{:-<105}", "");
+        for stat in stats {
+            println!(
+                "{:<30} {:>10} {:>14} {:>16} {:>14.1} {:>16}",
+                stat.name,
+                stat.calls,
+                stat.total.allocations,
+                stat.total.bytes_allocated,
+                stat.allocations_per_call(),
+                stat.max_bytes_per_call
+            );
+        }
+        println!();
+    }
+
+    /// Clear all statistics.
+    pub fn clear(&self) {
+        self.stats.lock().clear();
+    }
+
+    fn record(&self, function_name: &str, delta: AllocStats) {
+        let mut stats = self.stats.lock();
+        // Only allocate the name the first time, not to count it
+        if !stats.contains_key(function_name) {
+            let stat = FunctionAllocStats {
+                name: function_name.to_string(),
+                ..Default::default()
+            };
+            stats.insert(function_name.to_string(), stat);
+        }
+        let stat = stats.get_mut(function_name).expect("inserted above");
+        stat.calls += 1;
+        stat.total.allocations += delta.allocations;
+        stat.total.deallocations += delta.deallocations;
+        stat.total.bytes_allocated += delta.bytes_allocated;
+        stat.total.bytes_freed += delta.bytes_freed;
+        stat.max_bytes_per_call = stat.max_bytes_per_call.max(delta.bytes_allocated);
+    }
+}
+
+impl Aspect for AllocTrackingAspect {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let function_name = pjp.context().function_name;
+        if !CountingAllocator::<System>::is_installed() {
+            log::warn!(
+                "CountingAllocator is not the global allocator; allocations in {} are not counted",
+                function_name
+            );
+        }
+        let before = AllocStats::current_thread();
+
+        let result = pjp.proceed();
+
+        let delta = AllocStats::current_thread() - before;
+        self.record(function_name, delta);
+        result
+    }
+
+    /// Fails, as allocations are counted per thread.
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        _proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            Err(AspectError::weaving(format!(
+                "AllocTrackingAspect cannot advise the asynchronous execution of {}",
+                ctx.function_name
+            )))
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::OBSERVABILITY
+    }
+}
+
+#[cfg(test)]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator::system();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_macros::aspect;
+    use std::sync::LazyLock;
+
+    static ALLOCS: LazyLock<AllocTrackingAspect> = LazyLock::new(AllocTrackingAspect::new);
+
+    #[aspect(ALLOCS.clone())]
+    fn build(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("item {}", i)).collect()
+    }
+
+    #[aspect(ALLOCS.clone())]
+    fn sum(values: &[u64]) -> u64 {
+        values.iter().sum()
+    }
+
+    #[test]
+    fn test_counts_allocations_in_function() {
+        assert!(CountingAllocator::<System>::is_installed());
+
+        let boxes = build(10);
+        let _ = build(10);
+        assert_eq!(sum(&[1, 2, 3]), 6);
+        drop(boxes);
+
+        let stats = ALLOCS.get_stats("build").unwrap();
+        assert_eq!(stats.calls, 2);
+        // Ten strings and the vector holding them, per call
+        let per_call = 10 * (std::mem::size_of::<String>() + "item 0".len()) as u64;
+        assert!(stats.total.allocations >= 22, "{:?}", stats);
+        assert!(stats.total.bytes_allocated >= 2 * per_call);
+        assert!(stats.max_bytes_per_call >= per_call);
+        assert!(stats.allocations_per_call() >= 11.0);
+
+        // Only the boxed return value
+        let stats = ALLOCS.get_stats("sum").unwrap();
+        assert!(stats.total.allocations <= 1, "{:?}", stats);
+        assert_eq!(ALLOCS.all_stats()[0].name, "build");
+    }
+
+    #[test]
+    fn test_stats_difference() {
+        let before = AllocStats::current_thread();
+        let buffer = vec![0u8; 1024];
+        drop(buffer);
+        let delta = AllocStats::current_thread() - before;
+
+        assert_eq!(delta.allocations, 1);
+        assert_eq!(delta.deallocations, 1);
+        assert_eq!(delta.bytes_allocated, 1024);
+        assert_eq!(delta.net_bytes(), 0);
+    }
+}
@@ -0,0 +1,459 @@
+//! Audit logging aspect recording who called what, when, and how it ended.
+
+use crate::logging::{LogLevel, LogSink};
+use crate::redact::Redactor;
+use crate::time::{self, Duration, Instant, SystemTime, UNIX_EPOCH};
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, Precedence, ProceedingJoinPoint};
+use parking_lot::Mutex;
+use std::any::Any;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+/// Supplies the identity of the caller for audit records.
+///
+/// Implemented for closures returning `Option<String>`.
+pub trait PrincipalProvider: Send + Sync {
+    /// The current principal, or `None` for anonymous callers.
+    fn principal(&self) -> Option<String>;
+}
+
+impl<F> PrincipalProvider for F
+where
+    F: Fn() -> Option<String> + Send + Sync,
+{
+    fn principal(&self) -> Option<String> {
+        self()
+    }
+}
+
+/// How an audited call ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditOutcome {
+    /// The call returned successfully.
+    Success,
+    /// The call failed with the given error message.
+    Failure(String),
+}
+
+/// One audited call.
+#[derive(Debug, Clone)]
+pub struct AuditRecord {
+    /// Position of the record in the audit trail. Numbers are assigned
+    /// without gaps in the order records are written, so a missing or
+    /// reordered number reveals tampering with the trail.
+    pub sequence: u64,
+    /// When the call started
+    pub timestamp: SystemTime,
+    /// Who made the call, if known
+    pub principal: Option<String>,
+    /// The [correlation id](crate::correlation) of the request the call
+    /// was made for, if any
+    pub correlation_id: Option<String>,
+    /// The [contextual fields](crate::mdc) of the request
+    pub fields: Vec<(String, String)>,
+    /// Fully qualified name of the called function
+    pub function: String,
+    /// Summary of the captured arguments, with sensitive values masked
+    pub args: String,
+    /// How the call ended
+    pub outcome: AuditOutcome,
+    /// How long the call took
+    pub duration: Duration,
+}
+
+impl fmt::Display for AuditRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let since_epoch = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        write!(
+            f,
+            "#{} {}.{:03} principal={} ",
+            self.sequence,
+            since_epoch.as_secs(),
+            since_epoch.subsec_millis(),
+            self.principal.as_deref().unwrap_or("anonymous"),
+        )?;
+        if let Some(id) = &self.correlation_id {
+            write!(f, "correlation_id={} ", id)?;
+        }
+        for (key, value) in &self.fields {
+            write!(f, "{}={} ", key, value)?;
+        }
+        write!(f, "function={}({}) ", self.function, self.args)?;
+        match &self.outcome {
+            AuditOutcome::Success => write!(f, "outcome=success")?,
+            AuditOutcome::Failure(message) => write!(f, "outcome=failure error={:?}", message)?,
+        }
+        write!(f, " duration={:?}", self.duration)
+    }
+}
+
+/// Destination for audit records.
+///
+/// Sinks are called while the aspect holds its sequence lock, so records
+/// reach the sink in sequence order.
+pub trait AuditSink: Send + Sync {
+    /// Write one record.
+    fn write(&self, record: &AuditRecord);
+}
+
+/// Audit sink printing records to standard output.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutAuditSink;
+
+impl AuditSink for StdoutAuditSink {
+    fn write(&self, record: &AuditRecord) {
+        println!("[AUDIT] {}", record);
+    }
+}
+
+/// Audit sink appending one line per record to a file.
+#[derive(Debug)]
+pub struct FileAuditSink {
+    file: Mutex<File>,
+}
+
+impl FileAuditSink {
+    /// Open `path` for appending, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl AuditSink for FileAuditSink {
+    fn write(&self, record: &AuditRecord) {
+        if let Err(err) = writeln!(self.file.lock(), "{}", record) {
+            log::error!("failed to write audit record #{}: {}", record.sequence, err);
+        }
+    }
+}
+
+/// Audit sink sending records over a channel, e.g. to a background writer.
+#[derive(Debug, Clone)]
+pub struct ChannelAuditSink {
+    sender: Sender<AuditRecord>,
+}
+
+impl ChannelAuditSink {
+    /// Send records to `sender`.
+    pub fn new(sender: Sender<AuditRecord>) -> Self {
+        Self { sender }
+    }
+}
+
+impl AuditSink for ChannelAuditSink {
+    fn write(&self, record: &AuditRecord) {
+        if self.sender.send(record.clone()).is_err() {
+            log::error!("audit receiver dropped; record #{} lost", record.sequence);
+        }
+    }
+}
+
+/// Audit sink writing records through a [`LogSink`], so that the audit
+/// trail goes wherever the logs of the application go.
+///
+/// Records are written at the `Info` level with the `audit` target by
+/// default.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_std::audit::{AuditAspect, LogAuditSink};
+/// use aspect_std::logging::LogFacadeSink;
+///
+/// let audit = AuditAspect::new(LogAuditSink::new(LogFacadeSink).with_target("security"));
+/// ```
+#[derive(Clone)]
+pub struct LogAuditSink {
+    sink: Arc<dyn LogSink>,
+    level: LogLevel,
+    target: &'static str,
+}
+
+impl LogAuditSink {
+    /// Write records to `sink`.
+    pub fn new(sink: impl LogSink + 'static) -> Self {
+        Self {
+            sink: Arc::new(sink),
+            level: LogLevel::Info,
+            target: "audit",
+        }
+    }
+
+    /// Set the level of the records.
+    pub fn with_level(mut self, level: LogLevel) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Set the target of the records.
+    pub fn with_target(mut self, target: &'static str) -> Self {
+        self.target = target;
+        self
+    }
+}
+
+impl AuditSink for LogAuditSink {
+    fn write(&self, record: &AuditRecord) {
+        if self.sink.enabled(self.level, self.target) {
+            self.sink
+                .write(self.level, self.target, &record.to_string());
+        }
+    }
+}
+
+/// Aspect writing an [`AuditRecord`] for every call to an [`AuditSink`].
+///
+/// Clones share the sink and the sequence counter, so one aspect (typically
+/// in a static) yields a single, gapless trail across all audited functions.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::audit::{AuditAspect, FileAuditSink};
+/// use aspect_macros::aspect;
+/// use std::sync::LazyLock;
+///
+/// static AUDIT: LazyLock<AuditAspect> = LazyLock::new(|| {
+///     AuditAspect::new(FileAuditSink::open("audit.log").unwrap())
+///         .with_principal(|| current_user().map(|u| u.name))
+/// });
+///
+/// #[aspect(AUDIT.clone())]
+/// fn delete_user(user_id: u64) -> Result<(), String> {
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct AuditAspect {
+    sink: Arc<dyn AuditSink>,
+    principal: Option<Arc<dyn PrincipalProvider>>,
+    redactor: Redactor,
+    sequence: Arc<Mutex<u64>>,
+}
+
+impl AuditAspect {
+    /// Create an audit aspect writing to `sink`.
+    pub fn new(sink: impl AuditSink + 'static) -> Self {
+        Self {
+            sink: Arc::new(sink),
+            principal: None,
+            redactor: Redactor::default(),
+            sequence: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    /// Identify callers with `provider`. Without one, records have no
+    /// principal.
+    pub fn with_principal(mut self, provider: impl PrincipalProvider + 'static) -> Self {
+        self.principal = Some(Arc::new(provider));
+        self
+    }
+
+    /// Set how sensitive arguments are masked ([`Redactor::default`] by
+    /// default).
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// Start numbering records at `sequence`, e.g. to continue an existing
+    /// trail after a restart.
+    pub fn with_starting_sequence(self, sequence: u64) -> Self {
+        *self.sequence.lock() = sequence;
+        self
+    }
+
+    /// The sequence number the next record will get.
+    pub fn next_sequence(&self) -> u64 {
+        *self.sequence.lock()
+    }
+
+    /// What is known of a call when it starts.
+    fn start(&self) -> Started {
+        Started {
+            timestamp: time::system_now(),
+            principal: self.principal.as_ref().and_then(|p| p.principal()),
+            correlation_id: crate::correlation::current(),
+            fields: crate::mdc::current(),
+            start: time::now(),
+        }
+    }
+
+    /// Writes the record of a call that has ended with `result`.
+    fn write(&self, started: Started, ctx: &JoinPoint, result: &Result<Box<dyn Any>, AspectError>) {
+        let mut record = AuditRecord {
+            sequence: 0,
+            timestamp: started.timestamp,
+            principal: started.principal,
+            correlation_id: started.correlation_id,
+            fields: started.fields,
+            function: ctx.qualified_name(),
+            args: self.redactor.format_args(&ctx.args),
+            outcome: match result {
+                Ok(_) => AuditOutcome::Success,
+                Err(err) => AuditOutcome::Failure(err.to_string()),
+            },
+            duration: time::elapsed(started.start),
+        };
+
+        let mut sequence = self.sequence.lock();
+        record.sequence = *sequence;
+        *sequence += 1;
+        self.sink.write(&record);
+    }
+}
+
+/// The part of an audit record known when the call starts.
+struct Started {
+    timestamp: SystemTime,
+    principal: Option<String>,
+    correlation_id: Option<String>,
+    fields: Vec<(String, String)>,
+    start: Instant,
+}
+
+impl Aspect for AuditAspect {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let ctx = pjp.context().clone();
+        let started = self.start();
+        let result = pjp.proceed();
+        self.write(started, &ctx, &result);
+        result
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            let started = self.start();
+            let result = proceed.await;
+            self.write(started, ctx, &result);
+            result
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::OBSERVABILITY
+    }
+
+    fn reads_args(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_core::{Arg, JoinPoint, Location};
+    use std::sync::mpsc;
+
+    fn call(aspect: &AuditAspect, ok: bool) {
+        let ctx = JoinPoint::new(
+            "delete_user",
+            "app::admin",
+            Location {
+                file: "admin.rs",
+                line: 10,
+            },
+        )
+        .with_args(vec![
+            Arg::new("user_id", &42u64),
+            Arg::new("admin_token", &"t0ken".to_string()),
+        ]);
+        let pjp = ProceedingJoinPoint::new(
+            move || {
+                if ok {
+                    Ok(Box::new(()) as Box<dyn Any>)
+                } else {
+                    Err(AspectError::execution("not found"))
+                }
+            },
+            ctx,
+        );
+        let _ = aspect.around(pjp);
+    }
+
+    #[test]
+    fn test_log_audit_sink() {
+        use crate::logging::MemorySink;
+
+        let logs = MemorySink::new();
+        let audit = AuditAspect::new(LogAuditSink::new(logs.clone()).with_level(LogLevel::Warn));
+        call(&audit, false);
+
+        let records = logs.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].level, LogLevel::Warn);
+        assert_eq!(records[0].target, "audit");
+        assert!(records[0].message.starts_with("#0 "));
+        assert!(records[0].message.contains("outcome=failure"));
+    }
+
+    #[test]
+    fn test_records_sent_in_sequence() {
+        let (sender, receiver) = mpsc::channel();
+        let audit = AuditAspect::new(ChannelAuditSink::new(sender))
+            .with_principal(|| Some("alice".to_string()));
+
+        call(&audit, true);
+        crate::correlation::scope("req-1", || {
+            crate::mdc::scope([("tenant", "acme")], || call(&audit.clone(), false))
+        });
+
+        let first = receiver.recv().unwrap();
+        assert_eq!(first.sequence, 0);
+        assert_eq!(first.principal.as_deref(), Some("alice"));
+        assert_eq!(first.function, "app::admin::delete_user");
+        assert_eq!(first.args, "user_id: 42, admin_token: ***");
+        assert_eq!(first.outcome, AuditOutcome::Success);
+        assert_eq!(first.correlation_id, None);
+        assert!(first.fields.is_empty());
+
+        let second = receiver.recv().unwrap();
+        assert_eq!(second.sequence, 1);
+        assert_eq!(second.correlation_id.as_deref(), Some("req-1"));
+        assert_eq!(second.fields, [("tenant".to_string(), "acme".to_string())]);
+        assert!(second.to_string().contains(
+            " principal=alice correlation_id=req-1 tenant=acme function=app::admin::delete_user("
+        ));
+        assert_eq!(
+            second.outcome,
+            AuditOutcome::Failure("Execution error: not found".to_string())
+        );
+        assert_eq!(audit.next_sequence(), 2);
+    }
+
+    #[test]
+    fn test_file_sink_appends_lines() {
+        let path = std::env::temp_dir().join(format!("aspect-audit-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let audit = AuditAspect::new(FileAuditSink::open(&path).unwrap()).with_starting_sequence(7);
+        call(&audit, true);
+        call(&audit, false);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("#7 "));
+        assert!(lines[0].contains(
+            "principal=anonymous function=app::admin::delete_user(user_id: 42, admin_token: ***) outcome=success"
+        ));
+        assert!(lines[1].starts_with("#8 "));
+        assert!(lines[1].contains("outcome=failure error=\"Execution error: not found\""));
+    }
+}
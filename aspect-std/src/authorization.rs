@@ -1,14 +1,42 @@
-//! Authorization aspect for role-based access control.
+//! Authorization aspect for role- and attribute-based access control.
 
-use aspect_core::{Aspect, JoinPoint};
-use std::collections::HashSet;
+pub mod policy;
+
+use crate::time::{self, Duration, Instant};
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, AsyncAspect, JoinPoint, Precedence, ProceedingJoinPoint};
+use parking_lot::Mutex;
+use policy::{Decision, Policy, Subject};
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::sync::Arc;
 
+type DeniedFn = dyn Fn(&JoinPoint) -> Box<dyn Any> + Send + Sync;
+type PrincipalFn = dyn Fn() -> Option<String> + Send + Sync;
+type LookupFn = dyn Fn(String) -> BoxFuture<'static, Subject> + Send + Sync;
+
 /// Role-based access control aspect.
 ///
 /// Enforces authorization checks before function execution based on
 /// required roles or permissions.
 ///
+/// For rules beyond role sets, [`require_policy`](Self::require_policy)
+/// evaluates a [`Policy`] over attributes of the caller, the called function
+/// and its arguments.
+///
+/// A denied call does not run and fails with [`AspectError::Denied`], which
+/// functions returning `Result` get back as their error. Functions not
+/// returning `Result` cannot report the error and panic instead, unless a
+/// value to return is configured with
+/// [`on_denied_return`](Self::on_denied_return) or
+/// [`on_denied_with`](Self::on_denied_with).
+///
+/// When finding out the caller's roles needs I/O, use
+/// [`require_policy_async`](Self::require_policy_async): `async fn`s await
+/// the lookup through [`AsyncAspect`], and
+/// [`with_cache_ttl`](Self::with_cache_ttl) avoids repeating it on every call.
+///
 /// # Example
 ///
 /// ```rust,ignore
@@ -28,9 +56,64 @@ use std::sync::Arc;
 /// ```
 #[derive(Clone)]
 pub struct AuthorizationAspect {
-    required_roles: Arc<HashSet<String>>,
-    role_provider: Arc<dyn Fn() -> HashSet<String> + Send + Sync>,
-    mode: AuthMode,
+    policy: Arc<dyn Policy>,
+    subjects: SubjectSource,
+    on_denied: OnDenied,
+}
+
+/// Where the subject of a call comes from.
+#[derive(Clone)]
+enum SubjectSource {
+    /// A synchronous provider called on every check
+    Sync(Arc<dyn Fn() -> Subject + Send + Sync>),
+    /// An asynchronous lookup keyed by principal, with optional caching
+    Async {
+        principal: Arc<PrincipalFn>,
+        lookup: Arc<LookupFn>,
+        cache: SubjectCache,
+    },
+}
+
+/// Subjects resolved by an asynchronous lookup, keyed by principal.
+#[derive(Clone, Default)]
+struct SubjectCache {
+    ttl: Option<Duration>,
+    entries: Arc<Mutex<HashMap<String, (Instant, Subject)>>>,
+}
+
+impl SubjectCache {
+    fn get(&self, principal: &str) -> Option<Subject> {
+        let ttl = self.ttl?;
+        let mut entries = self.entries.lock();
+        match entries.get(principal) {
+            Some((cached_at, subject)) if time::elapsed(*cached_at) < ttl => Some(subject.clone()),
+            Some(_) => {
+                entries.remove(principal);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, principal: String, subject: Subject) {
+        let Some(ttl) = self.ttl else {
+            return;
+        };
+        let mut entries = self.entries.lock();
+        entries.retain(|_, (cached_at, _)| time::elapsed(*cached_at) < ttl);
+        entries.insert(principal, (time::now(), subject));
+    }
+}
+
+/// What to do with a denied call.
+#[derive(Clone)]
+enum OnDenied {
+    /// Fail with `AspectError::Denied`
+    Error,
+    /// Panic with the denial message
+    Panic,
+    /// Return the fallback's value instead of calling the function
+    Return(Arc<DeniedFn>),
 }
 
 /// Authorization mode.
@@ -59,14 +142,7 @@ impl AuthorizationAspect {
     where
         F: Fn() -> HashSet<String> + Send + Sync + 'static,
     {
-        let mut roles = HashSet::new();
-        roles.insert(role.to_string());
-
-        Self {
-            required_roles: Arc::new(roles),
-            role_provider: Arc::new(role_provider),
-            mode: AuthMode::RequireAll,
-        }
+        Self::require_roles(&[role], role_provider, AuthMode::RequireAll)
     }
 
     /// Create an authorization aspect that requires multiple roles.
@@ -88,74 +164,298 @@ impl AuthorizationAspect {
     where
         F: Fn() -> HashSet<String> + Send + Sync + 'static,
     {
-        let role_set: HashSet<String> = roles.iter().map(|r| r.to_string()).collect();
+        Self::require_policy(policy::roles(roles, mode), move || {
+            Subject::new().with_roles(role_provider())
+        })
+    }
 
+    /// Create an authorization aspect that evaluates `policy` for the
+    /// subject returned by `subject_provider`.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use aspect_std::authorization::policy::{self, Policy, Subject};
+    ///
+    /// // Editors may change their own records, admins any record
+    /// let auth = AuthorizationAspect::require_policy(
+    ///     policy::has_role("admin")
+    ///         .or(policy::has_role("editor").and(policy::owner::<u64>("owner_id"))),
+    ///     || current_subject(),
+    /// );
+    /// ```
+    pub fn require_policy<P, F>(policy: P, subject_provider: F) -> Self
+    where
+        P: Policy + 'static,
+        F: Fn() -> Subject + Send + Sync + 'static,
+    {
         Self {
-            required_roles: Arc::new(role_set),
-            role_provider: Arc::new(role_provider),
-            mode,
+            policy: Arc::new(policy),
+            subjects: SubjectSource::Sync(Arc::new(subject_provider)),
+            on_denied: OnDenied::Error,
         }
     }
 
-    /// Check if the current user is authorized.
-    fn check_authorization(&self) -> Result<(), String> {
-        let current_roles = (self.role_provider)();
+    /// Create an authorization aspect that evaluates `policy` for a subject
+    /// looked up asynchronously, e.g. from a database or a token
+    /// introspection endpoint.
+    ///
+    /// `principal` cheaply identifies the caller (a user ID or token, from a
+    /// task-local for instance), or returns `None` for anonymous callers,
+    /// who get an empty [`Subject`]. `lookup` resolves the principal to a
+    /// subject.
+    ///
+    /// `async fn`s await the lookup. Synchronous functions cannot, so they
+    /// are only authorized from the cache (see
+    /// [`with_cache_ttl`](Self::with_cache_ttl)) and are denied when the
+    /// subject is not cached.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use aspect_std::authorization::policy::{self, Subject};
+    /// use std::sync::LazyLock;
+    /// use std::time::Duration;
+    ///
+    /// static AUTH: LazyLock<AuthorizationAspect> = LazyLock::new(|| {
+    ///     AuthorizationAspect::require_policy_async(
+    ///         policy::has_role("admin"),
+    ///         || CURRENT_USER.try_with(|user| user.clone()).ok(),
+    ///         |user| async move { Subject::new().with_roles(db::roles_of(&user).await) },
+    ///     )
+    ///     .with_cache_ttl(Duration::from_secs(30))
+    /// });
+    ///
+    /// #[aspect(AUTH.clone())]
+    /// async fn delete_user(user_id: u64) -> Result<(), String> {
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn require_policy_async<P, K, L, Fut>(policy: P, principal: K, lookup: L) -> Self
+    where
+        P: Policy + 'static,
+        K: Fn() -> Option<String> + Send + Sync + 'static,
+        L: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Subject> + Send + 'static,
+    {
+        Self {
+            policy: Arc::new(policy),
+            subjects: SubjectSource::Async {
+                principal: Arc::new(principal),
+                lookup: Arc::new(move |principal| Box::pin(lookup(principal))),
+                cache: SubjectCache::default(),
+            },
+            on_denied: OnDenied::Error,
+        }
+    }
 
-        let authorized = match self.mode {
-            AuthMode::RequireAll => {
-                // User must have ALL required roles
-                self.required_roles.iter().all(|r| current_roles.contains(r))
-            }
-            AuthMode::RequireAny => {
-                // User must have ANY of the required roles
-                self.required_roles.iter().any(|r| current_roles.contains(r))
-            }
+    /// Cache subjects found by an asynchronous lookup for `ttl`, per
+    /// principal.
+    ///
+    /// Clones share the cache, so keep the aspect in a static and advise
+    /// functions with clones of it. Role changes take up to `ttl` to apply.
+    /// Has no effect on aspects with a synchronous provider.
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        if let SubjectSource::Async { cache, .. } = &mut self.subjects {
+            cache.ttl = Some(ttl);
+        }
+        self
+    }
+
+    /// Panic on denial instead of returning an error, even for functions
+    /// returning `Result`.
+    pub fn panic_on_denied(mut self) -> Self {
+        self.on_denied = OnDenied::Panic;
+        self
+    }
+
+    /// Return a clone of `value` from denied calls instead of failing.
+    ///
+    /// `value` must have the advised function's success type: `T` for
+    /// functions returning `Result<T, E>`, or the return type itself
+    /// otherwise.
+    pub fn on_denied_return<T: Clone + Send + Sync + 'static>(self, value: T) -> Self {
+        self.on_denied_with(move |_| value.clone())
+    }
+
+    /// Return the result of `f` from denied calls instead of failing; see
+    /// [`on_denied_return`](Self::on_denied_return).
+    pub fn on_denied_with<T: 'static>(
+        mut self,
+        f: impl Fn(&JoinPoint) -> T + Send + Sync + 'static,
+    ) -> Self {
+        self.on_denied = OnDenied::Return(Arc::new(move |ctx| Box::new(f(ctx))));
+        self
+    }
+
+    /// Check if the current user is authorized to call the function at `ctx`.
+    fn check_authorization(&self, ctx: &JoinPoint) -> Result<(), AspectError> {
+        let subject = match &self.subjects {
+            SubjectSource::Sync(provider) => provider(),
+            SubjectSource::Async {
+                principal, cache, ..
+            } => match principal() {
+                None => Subject::new(),
+                Some(principal) => cache.get(&principal).ok_or_else(|| {
+                    AspectError::denied(
+                        "a subject cached by an async lookup",
+                        format!("id {}, not cached", principal),
+                    )
+                })?,
+            },
         };
+        self.evaluate(&subject, ctx)
+    }
 
-        if authorized {
-            Ok(())
-        } else {
-            let required: Vec<_> = self.required_roles.iter().cloned().collect();
-            let mode_str = match self.mode {
-                AuthMode::RequireAll => "all",
-                AuthMode::RequireAny => "any",
-            };
-            Err(format!(
-                "Access denied: requires {} of roles {:?}",
-                mode_str, required
-            ))
+    /// Like [`check_authorization`](Self::check_authorization), awaiting an
+    /// asynchronous lookup if the subject is not cached.
+    async fn check_authorization_async(&self, ctx: &JoinPoint) -> Result<(), AspectError> {
+        let subject = match &self.subjects {
+            SubjectSource::Sync(provider) => provider(),
+            SubjectSource::Async {
+                principal,
+                lookup,
+                cache,
+            } => match principal() {
+                None => Subject::new(),
+                Some(principal) => match cache.get(&principal) {
+                    Some(subject) => subject,
+                    None => {
+                        let subject = lookup(principal.clone()).await;
+                        cache.insert(principal, subject.clone());
+                        subject
+                    }
+                },
+            },
+        };
+        self.evaluate(&subject, ctx)
+    }
+
+    fn evaluate(&self, subject: &Subject, ctx: &JoinPoint) -> Result<(), AspectError> {
+        match self.policy.evaluate(subject, ctx) {
+            Decision::Permit => Ok(()),
+            Decision::Deny(required) => Err(AspectError::denied(required, describe(subject))),
+        }
+    }
+
+    /// The result of a denied call, as configured with `on_denied_*`.
+    fn deny(&self, ctx: &JoinPoint, err: AspectError) -> Result<Box<dyn Any>, AspectError> {
+        log::warn!("Authorization failed for {}: {}", ctx.function_name, err);
+        match &self.on_denied {
+            OnDenied::Error => Err(err),
+            OnDenied::Panic => panic!("Authorization failed for {}: {}", ctx.function_name, err),
+            OnDenied::Return(fallback) => Ok(fallback(ctx)),
         }
     }
 }
 
+/// Describes what a subject has, for denial errors.
+fn describe(subject: &Subject) -> String {
+    let mut roles: Vec<_> = subject.roles.iter().collect();
+    roles.sort();
+    let roles = if roles.is_empty() {
+        "no roles".to_string()
+    } else {
+        format!("roles {:?}", roles)
+    };
+    match &subject.id {
+        Some(id) => format!("id {} and {}", id, roles),
+        None => roles,
+    }
+}
+
 impl Aspect for AuthorizationAspect {
-    fn before(&self, ctx: &JoinPoint) {
-        if let Err(msg) = self.check_authorization() {
-            panic!("Authorization failed for {}: {}", ctx.function_name, msg);
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        match self.check_authorization(pjp.context()) {
+            Ok(()) => pjp.proceed(),
+            Err(err) => self.deny(pjp.context(), err),
         }
     }
+
+    /// Awaits asynchronous subject lookups, as
+    /// [`before_async`](AsyncAspect::before_async) does, and only proceeds
+    /// with authorized calls.
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            match self.check_authorization_async(ctx).await {
+                Ok(()) => proceed.await,
+                Err(err) => self.deny(ctx, err),
+            }
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::SECURITY
+    }
+
+    fn reads_args(&self) -> bool {
+        matches!(self.on_denied, OnDenied::Return(_))
+    }
+}
+
+/// Authorizes `async fn`s, awaiting asynchronous subject lookups.
+///
+/// Values configured with [`on_denied_return`](AuthorizationAspect::on_denied_return)
+/// cannot be returned from an `async fn` yet, so denied `async fn`s fail
+/// with [`AspectError::Denied`] instead.
+impl AsyncAspect for AuthorizationAspect {
+    fn before_async<'s, 'c, 'f>(
+        &'s self,
+        ctx: &'c JoinPoint,
+    ) -> BoxFuture<'f, Result<(), AspectError>>
+    where
+        's: 'f,
+        'c: 'f,
+        Self: 'f,
+    {
+        Box::pin(async move {
+            let result = self.check_authorization_async(ctx).await;
+            if let Err(err) = &result {
+                log::warn!("Authorization failed for {}: {}", ctx.function_name, err);
+                if let OnDenied::Panic = self.on_denied {
+                    panic!("Authorization failed for {}: {}", ctx.function_name, err);
+                }
+            }
+            result
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aspect_macros::aspect;
+    use std::sync::atomic::{AtomicBool, Ordering};
 
     fn mock_roles(roles: Vec<&str>) -> HashSet<String> {
         roles.into_iter().map(|s| s.to_string()).collect()
     }
 
+    fn ctx() -> JoinPoint {
+        JoinPoint::new(
+            "delete_user",
+            "test",
+            aspect_core::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        )
+    }
+
     #[test]
     fn test_require_role_success() {
         let auth = AuthorizationAspect::require_role("admin", || mock_roles(vec!["admin"]));
 
-        assert!(auth.check_authorization().is_ok());
+        assert!(auth.check_authorization(&ctx()).is_ok());
     }
 
     #[test]
     fn test_require_role_failure() {
         let auth = AuthorizationAspect::require_role("admin", || mock_roles(vec!["user"]));
 
-        assert!(auth.check_authorization().is_err());
+        assert!(auth.check_authorization(&ctx()).is_err());
     }
 
     #[test]
@@ -166,7 +466,7 @@ mod tests {
             AuthMode::RequireAll,
         );
 
-        assert!(auth.check_authorization().is_ok());
+        assert!(auth.check_authorization(&ctx()).is_ok());
     }
 
     #[test]
@@ -177,7 +477,7 @@ mod tests {
             AuthMode::RequireAll,
         );
 
-        assert!(auth.check_authorization().is_err());
+        assert!(auth.check_authorization(&ctx()).is_err());
     }
 
     #[test]
@@ -188,7 +488,7 @@ mod tests {
             AuthMode::RequireAny,
         );
 
-        assert!(auth.check_authorization().is_ok());
+        assert!(auth.check_authorization(&ctx()).is_ok());
     }
 
     #[test]
@@ -199,14 +499,14 @@ mod tests {
             AuthMode::RequireAny,
         );
 
-        assert!(auth.check_authorization().is_err());
+        assert!(auth.check_authorization(&ctx()).is_err());
     }
 
     #[test]
     fn test_empty_roles() {
         let auth = AuthorizationAspect::require_role("admin", || mock_roles(vec![]));
 
-        assert!(auth.check_authorization().is_err());
+        assert!(auth.check_authorization(&ctx()).is_err());
     }
 
     #[test]
@@ -215,6 +515,194 @@ mod tests {
             mock_roles(vec!["user", "moderator", "admin"])
         });
 
-        assert!(auth.check_authorization().is_ok());
+        assert!(auth.check_authorization(&ctx()).is_ok());
+    }
+
+    #[test]
+    fn test_denial_message() {
+        let auth = AuthorizationAspect::require_role("admin", || mock_roles(vec!["user"]));
+
+        assert_eq!(
+            auth.check_authorization(&ctx()).unwrap_err().to_string(),
+            "Access denied: requires all of roles [\"admin\"], caller has roles [\"user\"]"
+        );
+    }
+
+    #[test]
+    fn test_require_policy() {
+        let auth = AuthorizationAspect::require_policy(
+            policy::attribute_equals("department", "billing"),
+            || Subject::new().with_attribute("department", "sales"),
+        );
+
+        match auth.check_authorization(&ctx()).unwrap_err() {
+            AspectError::Denied { required, actual } => {
+                assert_eq!(required, "attribute department=billing");
+                assert_eq!(actual, "no roles");
+            }
+            other => panic!("expected a denial, got {:?}", other),
+        }
+    }
+
+    fn call(auth: &AuthorizationAspect) -> Result<Box<dyn Any>, AspectError> {
+        auth.around(ProceedingJoinPoint::new(
+            || Ok(Box::new("deleted") as Box<dyn Any>),
+            ctx(),
+        ))
+    }
+
+    #[test]
+    fn test_around_denies_without_panicking() {
+        let auth = AuthorizationAspect::require_role("admin", || mock_roles(vec!["admin"]));
+        assert_eq!(*call(&auth).unwrap().downcast::<&str>().unwrap(), "deleted");
+
+        let auth = AuthorizationAspect::require_role("admin", || mock_roles(vec![]));
+        assert!(matches!(call(&auth), Err(AspectError::Denied { .. })));
+    }
+
+    #[test]
+    fn test_on_denied_return() {
+        let auth = AuthorizationAspect::require_role("admin", || mock_roles(vec!["user"]))
+            .on_denied_return("forbidden");
+        assert_eq!(
+            *call(&auth).unwrap().downcast::<&str>().unwrap(),
+            "forbidden"
+        );
+
+        let auth = AuthorizationAspect::require_policy(policy::has_role("admin"), || {
+            Subject::new().with_id("7")
+        })
+        .on_denied_with(|ctx| ctx.function_name.len());
+        assert_eq!(*call(&auth).unwrap().downcast::<usize>().unwrap(), 11);
+    }
+
+    #[test]
+    #[should_panic(expected = "Authorization failed for delete_user")]
+    fn test_panic_on_denied() {
+        let auth =
+            AuthorizationAspect::require_role("admin", || mock_roles(vec![])).panic_on_denied();
+        let _ = call(&auth);
+    }
+
+    /// Runs a future that never has to wait, as the lookups in these tests.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        match future.as_mut().poll(&mut cx) {
+            std::task::Poll::Ready(output) => output,
+            std::task::Poll::Pending => panic!("future not ready"),
+        }
+    }
+
+    thread_local! {
+        static CURRENT_USER: std::cell::RefCell<Option<String>> =
+            const { std::cell::RefCell::new(None) };
+    }
+
+    static LOOKUPS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    static ASYNC_AUTH: std::sync::LazyLock<AuthorizationAspect> = std::sync::LazyLock::new(|| {
+        AuthorizationAspect::require_policy_async(
+            policy::has_role("admin"),
+            || CURRENT_USER.with(|user| user.borrow().clone()),
+            |user| async move {
+                LOOKUPS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                match user.as_str() {
+                    "root" => Subject::new().with_id(user).with_role("admin"),
+                    _ => Subject::new().with_id(user),
+                }
+            },
+        )
+        .with_cache_ttl(Duration::from_secs(60))
+    });
+
+    #[aspect_macros::aspect(ASYNC_AUTH.clone())]
+    async fn purge(id: u64) -> Result<u64, String> {
+        Ok(id)
+    }
+
+    #[aspect_macros::aspect(ASYNC_AUTH.clone())]
+    fn purge_now(id: u64) -> Result<u64, String> {
+        Ok(id)
+    }
+
+    #[test]
+    fn test_async_lookup_is_awaited_and_cached() {
+        let as_user = |user: &str| CURRENT_USER.with(|u| *u.borrow_mut() = Some(user.to_string()));
+
+        as_user("root");
+        // Synchronous functions only see cached subjects
+        assert!(purge_now(1).unwrap_err().contains("not cached"));
+        assert_eq!(block_on(purge(1)), Ok(1));
+        assert_eq!(block_on(purge(2)), Ok(2));
+        assert_eq!(purge_now(3), Ok(3));
+        assert_eq!(LOOKUPS.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        as_user("guest");
+        let err = block_on(purge(4)).unwrap_err();
+        assert!(err.contains("Denied"), "{}", err);
+        assert!(err.contains("id guest"), "{}", err);
+        assert_eq!(LOOKUPS.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_async_path_checks_sync_provider() {
+        let auth = AuthorizationAspect::require_role("admin", || mock_roles(vec!["user"]));
+        let result = block_on(auth.before_async(&ctx()));
+        assert!(matches!(result, Err(AspectError::Denied { .. })));
+    }
+
+    fn call_async(auth: &AuthorizationAspect) -> Result<Box<dyn Any>, AspectError> {
+        let ctx = ctx();
+        let proceed: BoxFuture<'_, _> = Box::pin(async { Ok(Box::new("deleted") as Box<dyn Any>) });
+        block_on(auth.around_async(&ctx, proceed))
+    }
+
+    #[test]
+    fn test_around_async_denies() {
+        let auth = AuthorizationAspect::require_role("admin", || mock_roles(vec!["admin"]));
+        assert_eq!(
+            *call_async(&auth).unwrap().downcast::<&str>().unwrap(),
+            "deleted"
+        );
+
+        let auth = AuthorizationAspect::require_role("admin", || mock_roles(vec!["guest"]));
+        assert!(matches!(call_async(&auth), Err(AspectError::Denied { .. })));
+
+        let auth = auth.on_denied_return("forbidden");
+        assert_eq!(
+            *call_async(&auth).unwrap().downcast::<&str>().unwrap(),
+            "forbidden"
+        );
+    }
+
+    static IS_ADMIN: AtomicBool = AtomicBool::new(false);
+
+    fn current_roles() -> HashSet<String> {
+        if IS_ADMIN.load(Ordering::Relaxed) {
+            mock_roles(vec!["admin"])
+        } else {
+            mock_roles(vec!["guest"])
+        }
+    }
+
+    #[aspect(AuthorizationAspect::require_role("admin", current_roles))]
+    fn read_secret() -> Result<String, String> {
+        Ok("secret".to_string())
+    }
+
+    #[aspect(crate::FallbackAspect::with(|| read_secret().unwrap_or_else(|_| "denied".to_string())))]
+    fn fetch_secret() -> Result<String, String> {
+        Err("unavailable".to_string())
+    }
+
+    #[test]
+    fn test_checked_when_called_from_advice() {
+        // The fallback advice of `fetch_secret` reads the secret with the
+        // protected `read_secret`, whose check the reentrancy guard keeps
+        IS_ADMIN.store(false, Ordering::Relaxed);
+        assert_eq!(fetch_secret().unwrap(), "denied");
+        IS_ADMIN.store(true, Ordering::Relaxed);
+        assert_eq!(fetch_secret().unwrap(), "secret");
     }
 }
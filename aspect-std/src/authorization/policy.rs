@@ -0,0 +1,401 @@
+//! Attribute-based access control policies for
+//! [`AuthorizationAspect`](super::AuthorizationAspect).
+
+use super::AuthMode;
+use aspect_core::JoinPoint;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+/// The caller an access decision is made for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Subject {
+    /// Identifier of the caller, e.g. a user ID
+    pub id: Option<String>,
+    /// Roles held by the caller
+    pub roles: HashSet<String>,
+    /// Other attributes of the caller, e.g. department or tenant
+    pub attributes: HashMap<String, String>,
+}
+
+impl Subject {
+    /// Create an anonymous subject with no roles or attributes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the caller's identifier.
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Add a role.
+    pub fn with_role(mut self, role: impl Into<String>) -> Self {
+        self.roles.insert(role.into());
+        self
+    }
+
+    /// Add several roles.
+    pub fn with_roles<R: Into<String>>(mut self, roles: impl IntoIterator<Item = R>) -> Self {
+        self.roles.extend(roles.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set an attribute.
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.insert(key.into(), value.into());
+        self
+    }
+
+    /// Returns `true` if the subject holds `role`.
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.contains(role)
+    }
+
+    /// The value of attribute `key`, if set.
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes.get(key).map(String::as_str)
+    }
+}
+
+/// Outcome of evaluating a [`Policy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// Access is allowed.
+    Permit,
+    /// Access is refused; the message says what was required.
+    Deny(String),
+}
+
+impl Decision {
+    /// Returns `true` for [`Decision::Permit`].
+    pub fn is_permit(&self) -> bool {
+        matches!(self, Decision::Permit)
+    }
+}
+
+/// An access rule evaluated against the caller and the called function.
+///
+/// Closures taking `(&Subject, &JoinPoint)` and returning `bool` are
+/// policies; use [`rule`] to give one a description for denial messages.
+/// Policies combine with [`and`](Policy::and), [`or`](Policy::or) and
+/// [`not`](Policy::not), or [`all_of`] and [`any_of`] for lists.
+///
+/// The join point gives access to captured arguments, so rules can depend
+/// on what is being accessed, not just on who is asking.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::{Arg, JoinPoint, Location};
+/// use aspect_std::authorization::policy::{self, Policy, Subject};
+///
+/// // Admins can edit any record, everyone else only their own
+/// let can_edit = policy::has_role("admin").or(policy::owner::<u64>("owner_id"));
+///
+/// let ctx = JoinPoint::new("edit_record", "app", Location { file: "app.rs", line: 1 })
+///     .with_args(vec![Arg::new("owner_id", &42u64)]);
+///
+/// assert!(can_edit.evaluate(&Subject::new().with_id("42"), &ctx).is_permit());
+/// assert!(!can_edit.evaluate(&Subject::new().with_id("7"), &ctx).is_permit());
+/// ```
+pub trait Policy: Send + Sync {
+    /// Decide whether `subject` may call the function at `ctx`.
+    fn evaluate(&self, subject: &Subject, ctx: &JoinPoint) -> Decision;
+
+    /// Permit only if both `self` and `other` permit.
+    fn and<P: Policy>(self, other: P) -> And<Self, P>
+    where
+        Self: Sized,
+    {
+        And(self, other)
+    }
+
+    /// Permit if either `self` or `other` permits.
+    fn or<P: Policy>(self, other: P) -> Or<Self, P>
+    where
+        Self: Sized,
+    {
+        Or(self, other)
+    }
+
+    /// Permit exactly when `self` denies.
+    fn not(self) -> Not<Self>
+    where
+        Self: Sized,
+    {
+        Not(self)
+    }
+}
+
+impl<F> Policy for F
+where
+    F: Fn(&Subject, &JoinPoint) -> bool + Send + Sync,
+{
+    fn evaluate(&self, subject: &Subject, ctx: &JoinPoint) -> Decision {
+        if self(subject, ctx) {
+            Decision::Permit
+        } else {
+            Decision::Deny(format!("policy for {}", ctx.function_name))
+        }
+    }
+}
+
+/// Policy combining two policies with AND; see [`Policy::and`].
+#[derive(Debug, Clone)]
+pub struct And<A, B>(A, B);
+
+impl<A: Policy, B: Policy> Policy for And<A, B> {
+    fn evaluate(&self, subject: &Subject, ctx: &JoinPoint) -> Decision {
+        match self.0.evaluate(subject, ctx) {
+            Decision::Permit => self.1.evaluate(subject, ctx),
+            deny => deny,
+        }
+    }
+}
+
+/// Policy combining two policies with OR; see [`Policy::or`].
+#[derive(Debug, Clone)]
+pub struct Or<A, B>(A, B);
+
+impl<A: Policy, B: Policy> Policy for Or<A, B> {
+    fn evaluate(&self, subject: &Subject, ctx: &JoinPoint) -> Decision {
+        match (self.0.evaluate(subject, ctx), self.1.evaluate(subject, ctx)) {
+            (Decision::Deny(a), Decision::Deny(b)) => Decision::Deny(format!("{} or {}", a, b)),
+            _ => Decision::Permit,
+        }
+    }
+}
+
+/// Policy negating another policy; see [`Policy::not`].
+#[derive(Debug, Clone)]
+pub struct Not<P>(P);
+
+impl<P: Policy> Policy for Not<P> {
+    fn evaluate(&self, subject: &Subject, ctx: &JoinPoint) -> Decision {
+        match self.0.evaluate(subject, ctx) {
+            Decision::Permit => Decision::Deny("negated policy to be denied".to_string()),
+            Decision::Deny(_) => Decision::Permit,
+        }
+    }
+}
+
+/// Policy described by a name used in denial messages; see [`rule`].
+pub struct Rule<F> {
+    description: String,
+    check: F,
+}
+
+impl<F> Policy for Rule<F>
+where
+    F: Fn(&Subject, &JoinPoint) -> bool + Send + Sync,
+{
+    fn evaluate(&self, subject: &Subject, ctx: &JoinPoint) -> Decision {
+        if (self.check)(subject, ctx) {
+            Decision::Permit
+        } else {
+            Decision::Deny(self.description.clone())
+        }
+    }
+}
+
+/// A policy from a predicate, denying with `description` as the requirement.
+///
+/// ```rust
+/// use aspect_std::authorization::policy;
+///
+/// let in_eu = policy::rule("caller in the EU region", |subject, _ctx| {
+///     subject.attribute("region") == Some("eu")
+/// });
+/// ```
+pub fn rule<F>(description: impl Into<String>, check: F) -> Rule<F>
+where
+    F: Fn(&Subject, &JoinPoint) -> bool + Send + Sync,
+{
+    Rule {
+        description: description.into(),
+        check,
+    }
+}
+
+/// Policy permitting only if every policy in a list permits; see [`all_of`].
+pub struct AllOf(Vec<Box<dyn Policy>>);
+
+impl Policy for AllOf {
+    fn evaluate(&self, subject: &Subject, ctx: &JoinPoint) -> Decision {
+        self.0
+            .iter()
+            .map(|policy| policy.evaluate(subject, ctx))
+            .find(|decision| !decision.is_permit())
+            .unwrap_or(Decision::Permit)
+    }
+}
+
+/// Policy permitting if any policy in a list permits; see [`any_of`].
+pub struct AnyOf(Vec<Box<dyn Policy>>);
+
+impl Policy for AnyOf {
+    fn evaluate(&self, subject: &Subject, ctx: &JoinPoint) -> Decision {
+        let mut reasons = Vec::new();
+        for policy in &self.0 {
+            match policy.evaluate(subject, ctx) {
+                Decision::Permit => return Decision::Permit,
+                Decision::Deny(reason) => reasons.push(reason),
+            }
+        }
+        Decision::Deny(reasons.join(" or "))
+    }
+}
+
+/// Permit only if all `policies` permit. An empty list permits.
+pub fn all_of(policies: Vec<Box<dyn Policy>>) -> AllOf {
+    AllOf(policies)
+}
+
+/// Permit if any of `policies` permits. An empty list denies.
+pub fn any_of(policies: Vec<Box<dyn Policy>>) -> AnyOf {
+    AnyOf(policies)
+}
+
+/// Policy checking roles, as used by
+/// [`AuthorizationAspect::require_roles`](super::AuthorizationAspect::require_roles).
+#[derive(Debug, Clone)]
+pub struct Roles {
+    required: HashSet<String>,
+    mode: AuthMode,
+}
+
+impl Policy for Roles {
+    fn evaluate(&self, subject: &Subject, _ctx: &JoinPoint) -> Decision {
+        let authorized = match self.mode {
+            AuthMode::RequireAll => self.required.iter().all(|r| subject.has_role(r)),
+            AuthMode::RequireAny => self.required.iter().any(|r| subject.has_role(r)),
+        };
+
+        if authorized {
+            Decision::Permit
+        } else {
+            let required: Vec<_> = self.required.iter().cloned().collect();
+            let mode_str = match self.mode {
+                AuthMode::RequireAll => "all",
+                AuthMode::RequireAny => "any",
+            };
+            Decision::Deny(format!("{} of roles {:?}", mode_str, required))
+        }
+    }
+}
+
+/// Permit subjects holding `role`.
+pub fn has_role(role: &str) -> Roles {
+    roles(&[role], AuthMode::RequireAll)
+}
+
+/// Permit subjects holding all or any of `roles`, depending on `mode`.
+pub fn roles(roles: &[&str], mode: AuthMode) -> Roles {
+    Roles {
+        required: roles.iter().map(|r| r.to_string()).collect(),
+        mode,
+    }
+}
+
+/// Permit subjects whose attribute `key` equals `value`.
+pub fn attribute_equals(
+    key: &'static str,
+    value: impl Into<String>,
+) -> Rule<impl Fn(&Subject, &JoinPoint) -> bool + Send + Sync> {
+    let value = value.into();
+    rule(format!("attribute {}={}", key, value), move |subject, _| {
+        subject.attribute(key) == Some(value.as_str())
+    })
+}
+
+/// Permit subjects whose [`id`](Subject::id) equals the argument `arg`.
+///
+/// The argument must be captured by value with type `T`; its
+/// [`Display`] form is compared to the subject's ID. Calls where the
+/// argument is missing or has another type are denied.
+pub fn owner<T>(arg: &'static str) -> Rule<impl Fn(&Subject, &JoinPoint) -> bool + Send + Sync>
+where
+    T: Display + 'static,
+{
+    rule(format!("caller to own {}", arg), move |subject, ctx| {
+        let owner = ctx
+            .arg(arg)
+            .and_then(|arg| arg.value::<T>())
+            .map(ToString::to_string);
+        owner.is_some() && owner == subject.id
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_core::{Arg, Location};
+
+    fn ctx(owner_id: u64) -> JoinPoint {
+        JoinPoint::new(
+            "edit_record",
+            "app",
+            Location {
+                file: "app.rs",
+                line: 1,
+            },
+        )
+        .with_args(vec![Arg::new("owner_id", &owner_id)])
+    }
+
+    #[test]
+    fn test_owner_or_admin() {
+        let policy = has_role("admin").or(owner::<u64>("owner_id"));
+
+        let alice = Subject::new().with_id("1");
+        let admin = Subject::new().with_id("2").with_role("admin");
+        assert!(policy.evaluate(&alice, &ctx(1)).is_permit());
+        assert!(policy.evaluate(&admin, &ctx(1)).is_permit());
+        assert_eq!(
+            policy.evaluate(&alice, &ctx(2)),
+            Decision::Deny("all of roles [\"admin\"] or caller to own owner_id".to_string())
+        );
+        // Missing or differently typed arguments never match
+        assert!(!owner::<u32>("owner_id")
+            .evaluate(&alice, &ctx(1))
+            .is_permit());
+        assert!(!owner::<u64>("id")
+            .evaluate(&Subject::new(), &ctx(1))
+            .is_permit());
+    }
+
+    #[test]
+    fn test_combinators() {
+        let eu = attribute_equals("region", "eu");
+        let subject = Subject::new()
+            .with_roles(["editor"])
+            .with_attribute("region", "eu");
+
+        assert!(has_role("editor")
+            .and(eu)
+            .evaluate(&subject, &ctx(0))
+            .is_permit());
+        assert_eq!(
+            has_role("editor")
+                .and(attribute_equals("region", "us"))
+                .evaluate(&subject, &ctx(0)),
+            Decision::Deny("attribute region=us".to_string())
+        );
+        assert!(!has_role("editor")
+            .not()
+            .evaluate(&subject, &ctx(0))
+            .is_permit());
+
+        let closure = |subject: &Subject, _: &JoinPoint| subject.id.is_some();
+        assert!(!closure.evaluate(&subject, &ctx(0)).is_permit());
+
+        assert!(all_of(vec![]).evaluate(&subject, &ctx(0)).is_permit());
+        assert!(!any_of(vec![]).evaluate(&subject, &ctx(0)).is_permit());
+        assert!(
+            any_of(vec![Box::new(closure), Box::new(has_role("editor"))])
+                .evaluate(&subject, &ctx(0))
+                .is_permit()
+        );
+    }
+}
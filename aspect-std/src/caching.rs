@@ -1,21 +1,76 @@
 //! Generic caching/memoization aspect.
 
-use aspect_core::{Aspect, AspectError, ProceedingJoinPoint};
-use std::any::Any;
-use std::time::Duration;
+use crate::time::{Clock, Duration, SystemClock};
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{
+    ArgHasher, Aspect, AspectError, AspectSnapshot, JoinPoint, Precedence, ProceedingJoinPoint,
+};
+use parking_lot::RwLock;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
-/// Generic caching aspect with TTL support.
+pub mod invalidation;
+pub mod shared;
+pub mod store;
+
+pub use invalidation::InvalidationAspect;
+#[cfg(feature = "cached")]
+pub use shared::IoCachedStore;
+#[cfg(feature = "redis")]
+pub use shared::RedisStore;
+pub use shared::{RemoteStore, SharedStore};
+#[cfg(feature = "cached")]
+pub use store::CachedStore;
+#[cfg(feature = "moka")]
+pub use store::MokaStore;
+pub use store::{
+    CacheKey, CacheStore, CachedValue, EvictionPolicy, MemoryStore, MemoryStoreConfig,
+};
+
+/// Generic caching aspect with TTL support and bounded size.
+///
+/// Results are keyed on the advised function and a hash of its arguments,
+/// computed by a [`KeyExtractor`] ([`AllArgs`] by default), so
+/// `fetch_user(1)` and `fetch_user(2)` are cached separately. Calls whose key
+/// cannot be computed, for example because an argument type is not `Hash`,
+/// bypass the cache. Errors are never cached.
+///
+/// Cached values are handed out as clones, so the return type has to be
+/// registered with [`cacheable`](Self::cacheable) unless it is a primitive
+/// or `String`. Results of unregistered types are simply not cached.
+///
+/// The cache can be bounded by entry count ([`with_max_size`](Self::with_max_size))
+/// and by estimated memory ([`with_max_memory`](Self::with_max_memory)); when
+/// full, entries are evicted according to the [`EvictionPolicy`].
+/// [`memory_usage`](Self::memory_usage) reports the estimated footprint. Entries
+/// live in a [`CacheStore`]: an in-process [`MemoryStore`] by default, or
+/// any other store given to [`with_store`](Self::with_store), such as a
+/// [`SharedStore`] shared by several processes through Redis.
+///
+/// `#[aspect(...)]` evaluates its expression on every call, so share one
+/// instance through a static for the cache to persist between calls.
+///
+/// Entries are removed when the data behind them changes with
+/// [`invalidate`](Self::invalidate) and
+/// [`invalidate_prefix`](Self::invalidate_prefix), or by an
+/// [`invalidator`](Self::invalidator) applied to the functions writing it.
 ///
 /// # Example
 ///
 /// ```rust,ignore
 /// use aspect_std::CachingAspect;
 /// use aspect_macros::aspect;
+/// use std::sync::LazyLock;
 /// use std::time::Duration;
 ///
-/// let cache = CachingAspect::new().with_ttl(Duration::from_secs(60));
+/// static CACHE: LazyLock<CachingAspect> = LazyLock::new(|| {
+///     CachingAspect::new().with_ttl(Duration::from_secs(60))
+/// });
 ///
-/// #[aspect(cache.clone())]
+/// #[aspect(CACHE.clone())]
 /// fn expensive_query(id: u64) -> Result<String, String> {
 ///     // Expensive operation - will be cached
 ///     Ok(format!("Result for {}", id))
@@ -23,30 +78,398 @@ use std::time::Duration;
 /// ```
 #[derive(Clone)]
 pub struct CachingAspect {
-    max_size: usize,
-    ttl: Option<Duration>,
+    config: MemoryStoreConfig,
+    clock: Arc<dyn Clock>,
+    store: Arc<dyn CacheStore>,
+    custom_store: bool,
+    key_extractor: Arc<dyn KeyExtractor>,
+    cloners: Arc<RwLock<HashMap<TypeId, Cloner>>>,
+    counters: Arc<Counters>,
+}
+
+/// Computes the cache key of a call from its joinpoint.
+///
+/// Return `None` to bypass the cache for a call. Any closure of type
+/// `Fn(&JoinPoint) -> Option<u128>` is a key extractor.
+///
+/// Calls of a function with the same key share a cached result, so keys
+/// must tell apart the calls returning different results: derive them from
+/// the arguments with [`ArgHasher`], whose hashes are wide enough not to
+/// collide and the same in every process, as stores shared by processes
+/// need.
+pub trait KeyExtractor: Send + Sync {
+    /// Returns the key for this call, or `None` if it should not be cached.
+    fn extract(&self, ctx: &JoinPoint) -> Option<u128>;
+}
+
+impl<F> KeyExtractor for F
+where
+    F: Fn(&JoinPoint) -> Option<u128> + Send + Sync,
+{
+    fn extract(&self, ctx: &JoinPoint) -> Option<u128> {
+        self(ctx)
+    }
+}
+
+/// Keys on the hashes of all arguments.
+///
+/// Yields no key if any argument is not hashable.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AllArgs;
+
+impl KeyExtractor for AllArgs {
+    fn extract(&self, ctx: &JoinPoint) -> Option<u128> {
+        let mut hasher = ArgHasher::new();
+        for arg in &ctx.args {
+            arg.hash()?.hash(&mut hasher);
+        }
+        Some(hasher.finish128())
+    }
+}
+
+/// Keys on the hashes of the named arguments only.
+///
+/// Yields no key if a named argument is missing or not hashable.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_std::caching::{CachingAspect, SelectedArgs};
+///
+/// // Ignore the `trace_id` argument when caching
+/// let cache = CachingAspect::new().with_key_extractor(SelectedArgs::new(["user_id"]));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SelectedArgs {
+    names: Vec<&'static str>,
+}
+
+impl SelectedArgs {
+    /// Key on the arguments with these parameter names.
+    pub fn new(names: impl IntoIterator<Item = &'static str>) -> Self {
+        Self {
+            names: names.into_iter().collect(),
+        }
+    }
+}
+
+impl KeyExtractor for SelectedArgs {
+    fn extract(&self, ctx: &JoinPoint) -> Option<u128> {
+        let mut hasher = ArgHasher::new();
+        for name in &self.names {
+            ctx.arg(name)?.hash()?.hash(&mut hasher);
+        }
+        Some(hasher.finish128())
+    }
+}
+
+/// Cache statistics, see [`CachingAspect::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups served from the cache
+    pub hits: u64,
+    /// Lookups that ran the function
+    pub misses: u64,
+    /// Entries dropped to stay within capacity or memory limits
+    pub evictions: u64,
+    /// Entries dropped because their TTL elapsed
+    pub expirations: u64,
+    /// Current number of entries
+    pub entries: usize,
+    /// Estimated memory used by cached entries, in bytes
+    pub memory: usize,
+}
+
+/// Estimates the memory used by a cached value.
+type SizeFn = Arc<dyn Fn(&(dyn Any + Send + Sync)) -> usize + Send + Sync>;
+
+/// Type-erased clone and size functions for one cacheable type.
+#[derive(Clone)]
+pub(crate) struct Cloner {
+    pub(crate) store: fn(&dyn Any) -> Option<CachedValue>,
+    pub(crate) load: fn(&(dyn Any + Send + Sync)) -> Option<Box<dyn Any>>,
+    size: SizeFn,
+}
+
+impl Cloner {
+    pub(crate) fn of<T: Clone + Send + Sync + 'static>() -> Self {
+        Self::sized::<T>(|_| std::mem::size_of::<T>())
+    }
+
+    fn sized<T: Clone + Send + Sync + 'static>(
+        size: impl Fn(&T) -> usize + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            store: |value| {
+                value
+                    .downcast_ref::<T>()
+                    .map(|value| Arc::new(value.clone()) as CachedValue)
+            },
+            load: |value| {
+                value
+                    .downcast_ref::<T>()
+                    .map(|value| Box::new(value.clone()) as Box<dyn Any>)
+            },
+            size: Arc::new(move |value| value.downcast_ref::<T>().map_or(0, &size)),
+        }
+    }
+}
+
+pub(crate) fn default_cloners() -> HashMap<TypeId, Cloner> {
+    macro_rules! cloners {
+        ($($ty:ty),*) => {
+            HashMap::from([$((TypeId::of::<$ty>(), Cloner::of::<$ty>())),*])
+        };
+    }
+    let mut cloners = cloners!(
+        (),
+        bool,
+        char,
+        i8,
+        i16,
+        i32,
+        i64,
+        i128,
+        isize,
+        u8,
+        u16,
+        u32,
+        u64,
+        u128,
+        usize,
+        f32,
+        f64
+    );
+    cloners.insert(
+        TypeId::of::<String>(),
+        Cloner::sized::<String>(|s| std::mem::size_of::<String>() + s.capacity()),
+    );
+    cloners
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
 }
 
 impl CachingAspect {
     /// Create a new caching aspect with no size limit.
     pub fn new() -> Self {
+        let config = MemoryStoreConfig::default();
         Self {
-            max_size: usize::MAX,
-            ttl: None,
+            config,
+            clock: Arc::new(SystemClock),
+            store: Arc::new(MemoryStore::with_config(config)),
+            custom_store: false,
+            key_extractor: Arc::new(AllArgs),
+            cloners: Arc::new(RwLock::new(default_cloners())),
+            counters: Arc::default(),
         }
     }
 
-    /// Set maximum cache size.
-    pub fn with_max_size(mut self, max_size: usize) -> Self {
-        self.max_size = max_size;
-        self
+    /// Set maximum cache size (number of entries).
+    pub fn with_max_size(self, max_size: usize) -> Self {
+        self.configure_memory(|config| config.max_size = max_size)
+    }
+
+    /// Cap the memory used by cached entries, in bytes: inserting evicts
+    /// entries until the new one fits, and results larger than the cap are
+    /// not cached.
+    ///
+    /// Sizes are estimates: `size_of::<T>()` unless a size function was
+    /// given to [`cacheable_sized`](Self::cacheable_sized), which should be
+    /// used for types owning heap data, plus
+    /// [`MemoryStore::ENTRY_OVERHEAD`] per entry.
+    pub fn with_max_memory(self, bytes: usize) -> Self {
+        self.configure_memory(|config| config.max_memory = bytes)
     }
 
     /// Set time-to-live for cache entries.
-    pub fn with_ttl(mut self, ttl: Duration) -> Self {
-        self.ttl = Some(ttl);
+    pub fn with_ttl(self, ttl: Duration) -> Self {
+        self.configure_memory(|config| config.ttl = Some(ttl))
+    }
+
+    /// Set which entries are evicted first when the cache is full.
+    pub fn with_eviction_policy(self, policy: EvictionPolicy) -> Self {
+        self.configure_memory(|config| config.policy = policy)
+    }
+
+    /// Expire entries by the time of `clock` instead of the system clock,
+    /// e.g. a [`ManualClock`](crate::time::ManualClock) in tests.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self.configure_memory(|_| {})
+    }
+
+    /// Keep entries in `store` instead of the built-in [`MemoryStore`].
+    ///
+    /// Size, memory, TTL, eviction and clock settings are then up to the
+    /// store: the corresponding builder methods only configure the built-in
+    /// `MemoryStore` and log a warning when called after this.
+    pub fn with_store(mut self, store: impl CacheStore + 'static) -> Self {
+        self.store = Arc::new(store);
+        self.custom_store = true;
+        self
+    }
+
+    /// Set how cache keys are computed from a call (default: [`AllArgs`]).
+    pub fn with_key_extractor(mut self, extractor: impl KeyExtractor + 'static) -> Self {
+        self.key_extractor = Arc::new(extractor);
+        self
+    }
+
+    /// Allow results of type `T` to be cached.
+    ///
+    /// For functions returning `Result<T, E>`, register `T`.
+    pub fn cacheable<T: Clone + Send + Sync + 'static>(self) -> Self {
+        self.cloners
+            .write()
+            .insert(TypeId::of::<T>(), Cloner::of::<T>());
+        self
+    }
+
+    /// Allow results of type `T` to be cached, estimating their memory use
+    /// in bytes with `size`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use aspect_std::CachingAspect;
+    ///
+    /// let cache = CachingAspect::new()
+    ///     .with_max_memory(64 * 1024 * 1024)
+    ///     .cacheable_sized::<Vec<u8>>(|v| v.capacity());
+    /// ```
+    pub fn cacheable_sized<T: Clone + Send + Sync + 'static>(
+        self,
+        size: impl Fn(&T) -> usize + Send + Sync + 'static,
+    ) -> Self {
+        self.cloners
+            .write()
+            .insert(TypeId::of::<T>(), Cloner::sized::<T>(size));
+        self
+    }
+
+    /// The store holding the cached entries.
+    pub fn store(&self) -> &dyn CacheStore {
+        &*self.store
+    }
+
+    /// Number of cached entries (including expired ones not yet evicted).
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    /// Returns `true` if nothing is cached.
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    /// Estimated memory used by the cached entries, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.store.memory_usage()
+    }
+
+    /// Remove all cached entries. Statistics are kept.
+    pub fn clear(&self) {
+        self.store.clear();
+    }
+
+    /// The key the result of the call `ctx` is cached under, or `None` if
+    /// calls like it bypass the cache.
+    pub fn key(&self, ctx: &JoinPoint) -> Option<CacheKey> {
+        Some(CacheKey {
+            module_path: ctx.module_path,
+            function_name: ctx.function_name,
+            hash: self.key_extractor.extract(ctx)?,
+        })
+    }
+
+    /// Remove the entry cached under `key`, if any.
+    pub fn invalidate(&self, key: &CacheKey) {
+        self.store.remove(key);
+    }
+
+    /// Remove the entries of the functions whose path starts with
+    /// `prefix`, such as `"app::users::get_user"` or `"app::users"`,
+    /// returning how many were removed. The prefix is made of whole path
+    /// segments, so `"app::user"` leaves those of `app::users` alone.
+    ///
+    /// Stores that cannot list their entries, such as a [`SharedStore`],
+    /// are cleared.
+    pub fn invalidate_prefix(&self, prefix: &str) -> usize {
+        self.store.remove_matching(&|key| key.starts_with(prefix))
+    }
+
+    /// An aspect removing entries of this cache whenever a function it is
+    /// applied to returns successfully; all of them unless narrowed down,
+    /// see [`InvalidationAspect`].
+    ///
+    /// It shares the store of the cache, so make it after configuring the
+    /// cache.
+    pub fn invalidator(&self) -> InvalidationAspect {
+        InvalidationAspect::new(self.store.clone())
+    }
+
+    /// Remove the expired entries now, returning how many were removed
+    /// (when the store can tell). Otherwise they are removed as they are
+    /// looked up, or to make room.
+    pub fn purge_expired(&self) -> usize {
+        self.store.purge_expired()
+    }
+
+    /// Hit, miss and eviction counts along with the current size.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            ..self.store.stats()
+        }
+    }
+
+    fn configure_memory(mut self, configure: impl FnOnce(&mut MemoryStoreConfig)) -> Self {
+        configure(&mut self.config);
+        if self.custom_store {
+            log::warn!(
+                "CachingAspect uses a custom store; size, memory, TTL, eviction and clock settings have to be set on the store"
+            );
+        } else {
+            self.store =
+                Arc::new(MemoryStore::with_config(self.config).with_clock(self.clock.clone()));
+        }
         self
     }
+
+    fn lookup(&self, key: &CacheKey) -> Option<Box<dyn Any>> {
+        let value = self.store.get(key).and_then(|value| {
+            let cloner = self.cloners.read().get(&(*value).type_id()).cloned()?;
+            (cloner.load)(&*value)
+        });
+
+        let counter = match value {
+            Some(_) => &self.counters.hits,
+            None => &self.counters.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    fn insert(&self, key: CacheKey, result: &dyn Any) {
+        let Some(cloner) = self.cloners.read().get(&result.type_id()).cloned() else {
+            log::debug!(
+                "result of {}::{} is not cacheable; register its type with CachingAspect::cacheable",
+                key.module_path,
+                key.function_name
+            );
+            return;
+        };
+        let Some(value) = (cloner.store)(result) else {
+            return;
+        };
+        let size = (cloner.size)(&*value);
+        self.store.insert(key, value, size);
+    }
 }
 
 impl Default for CachingAspect {
@@ -57,16 +480,71 @@ impl Default for CachingAspect {
 
 impl Aspect for CachingAspect {
     fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
-        // For now, always proceed (full impl would require key extraction)
-        // This is a simplified version - full caching requires function signature analysis
-        println!("[CACHE] Checking cache for {}", pjp.context().function_name);
-        pjp.proceed()
+        let Some(key) = self.key(pjp.context()) else {
+            return pjp.proceed();
+        };
+
+        if let Some(cached) = self.lookup(&key) {
+            return Ok(cached);
+        }
+
+        let result = pjp.proceed()?;
+        self.insert(key, &*result);
+        Ok(result)
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            let Some(key) = self.key(ctx) else {
+                return proceed.await;
+            };
+
+            if let Some(cached) = self.lookup(&key) {
+                return Ok(cached);
+            }
+
+            let result = proceed.await?;
+            self.insert(key, &*result);
+            Ok(result)
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::CACHING
+    }
+
+    /// The [`stats`](Self::stats) of the cache, with its hit rate.
+    fn snapshot(&self) -> AspectSnapshot {
+        let stats = self.stats();
+        let lookups = stats.hits + stats.misses;
+        let hit_rate = (lookups > 0).then(|| stats.hits as f64 / lookups as f64);
+        AspectSnapshot::new("CachingAspect")
+            .with("entries", stats.entries)
+            .with("memory", stats.memory)
+            .with("hits", stats.hits)
+            .with("misses", stats.misses)
+            .with("hit_rate", hit_rate)
+            .with("evictions", stats.evictions)
+            .with("expirations", stats.expirations)
+    }
+
+    fn reads_args(&self) -> bool {
+        true
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::time::ManualClock;
+    use aspect_core::snapshot::SnapshotValue;
+    use aspect_macros::aspect;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::LazyLock;
 
     #[test]
     fn test_caching_aspect() {
@@ -74,7 +552,348 @@ mod tests {
             .with_max_size(100)
             .with_ttl(Duration::from_secs(60));
 
-        assert_eq!(aspect.max_size, 100);
-        assert_eq!(aspect.ttl, Some(Duration::from_secs(60)));
+        assert_eq!(aspect.config.max_size, 100);
+        assert_eq!(aspect.config.ttl, Some(Duration::from_secs(60)));
+    }
+
+    static USER_CACHE: LazyLock<CachingAspect> = LazyLock::new(CachingAspect::new);
+    static USER_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    #[aspect(USER_CACHE.clone())]
+    fn fetch_user(id: u64) -> Result<String, String> {
+        USER_CALLS.fetch_add(1, Ordering::SeqCst);
+        if id == 0 {
+            Err("no user 0".to_string())
+        } else {
+            Ok(format!("user{}", id))
+        }
+    }
+
+    #[test]
+    fn test_keyed_on_arguments() {
+        assert_eq!(fetch_user(1).unwrap(), "user1");
+        assert_eq!(fetch_user(2).unwrap(), "user2");
+        assert_eq!(fetch_user(1).unwrap(), "user1");
+        assert_eq!(USER_CALLS.load(Ordering::SeqCst), 2);
+
+        // Errors are not cached
+        assert!(fetch_user(0).is_err());
+        assert!(fetch_user(0).is_err());
+        assert_eq!(USER_CALLS.load(Ordering::SeqCst), 4);
+        assert_eq!(USER_CACHE.len(), 2);
+    }
+
+    static AREA_CACHE: LazyLock<CachingAspect> = LazyLock::new(CachingAspect::new);
+    static AREA_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    #[aspect(AREA_CACHE.clone())]
+    fn area((width, height): (u32, u32), _: bool) -> u32 {
+        AREA_CALLS.fetch_add(1, Ordering::SeqCst);
+        width * height
+    }
+
+    #[test]
+    fn test_keyed_on_pattern_arguments() {
+        assert_eq!(area((2, 3), true), 6);
+        assert_eq!(area((4, 5), true), 20);
+        assert_eq!(area((2, 3), false), 6);
+        assert_eq!(AREA_CALLS.load(Ordering::SeqCst), 3);
+
+        assert_eq!(area((2, 3), true), 6);
+        assert_eq!(AREA_CALLS.load(Ordering::SeqCst), 3);
+        assert_eq!(AREA_CACHE.len(), 3);
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Report {
+        lines: Vec<String>,
+    }
+
+    static REPORT_CACHE: LazyLock<CachingAspect> = LazyLock::new(|| {
+        CachingAspect::new()
+            .cacheable::<Report>()
+            .with_key_extractor(SelectedArgs::new(["name"]))
+    });
+    static REPORT_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    #[aspect(REPORT_CACHE.clone())]
+    fn build_report(name: &str, request_id: u64) -> Report {
+        REPORT_CALLS.fetch_add(1, Ordering::SeqCst);
+        Report {
+            lines: vec![format!("{} ({})", name, request_id)],
+        }
+    }
+
+    #[test]
+    fn test_selected_args_and_custom_type() {
+        let first = build_report("daily", 1);
+        let second = build_report("daily", 2);
+        build_report("weekly", 3);
+
+        assert_eq!(first, second);
+        assert_eq!(REPORT_CALLS.load(Ordering::SeqCst), 2);
+    }
+
+    fn joinpoint(id: u64) -> JoinPoint {
+        JoinPoint::new(
+            "lookup",
+            "test",
+            aspect_core::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        )
+        .with_args(vec![aspect_core::Arg::new("id", &id)])
+    }
+
+    fn call(cache: &CachingAspect, id: u64, calls: &AtomicUsize) -> u64 {
+        let pjp = ProceedingJoinPoint::new(
+            || {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(Box::new(id * 10) as Box<dyn Any>)
+            },
+            joinpoint(id),
+        );
+        *cache.around(pjp).unwrap().downcast::<u64>().unwrap()
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let cache = CachingAspect::new().with_max_size(2);
+        let calls = AtomicUsize::new(0);
+
+        call(&cache, 1, &calls);
+        call(&cache, 2, &calls);
+        call(&cache, 1, &calls); // 1 is now more recent than 2
+        call(&cache, 3, &calls); // evicts 2
+        assert_eq!(cache.len(), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        call(&cache, 1, &calls);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        call(&cache, 2, &calls);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert_eq!(cache.stats().evictions, 2);
+    }
+
+    #[test]
+    fn test_lfu_eviction() {
+        let cache = CachingAspect::new()
+            .with_max_size(2)
+            .with_eviction_policy(EvictionPolicy::Lfu);
+        let calls = AtomicUsize::new(0);
+
+        call(&cache, 1, &calls);
+        call(&cache, 1, &calls);
+        call(&cache, 1, &calls);
+        call(&cache, 2, &calls);
+        call(&cache, 3, &calls); // evicts 2, used once
+        call(&cache, 1, &calls);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        call(&cache, 2, &calls);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_fifo_eviction() {
+        let cache = CachingAspect::new()
+            .with_max_size(2)
+            .with_eviction_policy(EvictionPolicy::Fifo);
+        let calls = AtomicUsize::new(0);
+
+        call(&cache, 1, &calls);
+        call(&cache, 2, &calls);
+        call(&cache, 1, &calls);
+        call(&cache, 3, &calls); // evicts 1 despite the recent hit
+        call(&cache, 2, &calls);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        call(&cache, 1, &calls);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_max_memory() {
+        let entry = std::mem::size_of::<u64>() + MemoryStore::ENTRY_OVERHEAD;
+        let cache = CachingAspect::new().with_max_memory(2 * entry);
+        let calls = AtomicUsize::new(0);
+
+        call(&cache, 1, &calls);
+        call(&cache, 2, &calls);
+        call(&cache, 3, &calls);
+
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.memory, 2 * entry);
+        assert_eq!(cache.memory_usage(), 2 * entry);
+        assert_eq!(stats.evictions, 1);
+    }
+
+    #[test]
+    fn test_memory_usage_of_sized_values() {
+        let cache = CachingAspect::new()
+            .with_max_memory(4096)
+            .cacheable_sized::<Vec<u8>>(|v| v.capacity());
+        for id in 0..16u64 {
+            let value = || Ok(Box::new(vec![0u8; 1000]) as Box<dyn Any>);
+            let pjp = ProceedingJoinPoint::new(value, joinpoint(id));
+            cache.around(pjp).unwrap();
+            assert!(cache.memory_usage() <= 4096);
+        }
+        assert_eq!(cache.len(), 3);
+        assert_eq!(
+            cache.memory_usage(),
+            3 * (1000 + MemoryStore::ENTRY_OVERHEAD)
+        );
+
+        cache.clear();
+        assert_eq!(cache.memory_usage(), 0);
+    }
+
+    #[test]
+    fn test_stats() {
+        let cache = CachingAspect::new();
+        let calls = AtomicUsize::new(0);
+
+        call(&cache, 1, &calls);
+        call(&cache, 1, &calls);
+        call(&cache, 2, &calls);
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 2);
+        assert_eq!(stats.entries, 2);
+
+        cache.clear();
+        assert_eq!(cache.stats().memory, 0);
+        assert_eq!(cache.stats().hits, 1);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let cache = CachingAspect::new();
+        assert_eq!(cache.snapshot().get("hit_rate"), Some(&SnapshotValue::Null));
+
+        let calls = AtomicUsize::new(0);
+        call(&cache, 1, &calls);
+        call(&cache, 1, &calls);
+
+        let snapshot = cache.snapshot();
+        assert_eq!(snapshot.aspect(), "CachingAspect");
+        assert_eq!(snapshot.get("entries"), Some(&1usize.into()));
+        assert_eq!(snapshot.get("hit_rate"), Some(&0.5.into()));
+    }
+
+    #[test]
+    fn test_ttl_expiry() {
+        let clock = ManualClock::new();
+        let cache = CachingAspect::new()
+            .with_ttl(Duration::from_secs(60))
+            .with_clock(clock.clone());
+        let calls = AtomicUsize::new(0);
+
+        call(&cache, 1, &calls);
+        clock.advance(Duration::from_secs(59));
+        call(&cache, 1, &calls);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        clock.advance(Duration::from_secs(1));
+        call(&cache, 1, &calls);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(cache.stats().expirations, 1);
+    }
+
+    #[test]
+    fn test_unhashable_argument_bypasses_cache() {
+        let cache = CachingAspect::new();
+        let ctx = JoinPoint::new(
+            "f",
+            "test",
+            aspect_core::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        )
+        .with_args(vec![aspect_core::Arg::opaque("x", "f64")]);
+
+        assert_eq!(AllArgs.extract(&ctx), None);
+        let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(1u8) as Box<dyn Any>), ctx);
+        cache.around(pjp).unwrap();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_custom_store_is_shared_between_clones() {
+        let store = MemoryStore::new();
+        let cache = CachingAspect::new().with_store(store);
+        let other = cache.clone();
+        let calls = AtomicUsize::new(0);
+
+        call(&cache, 1, &calls);
+        call(&other, 1, &calls);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(other.len(), 1);
+        assert_eq!(cache.stats().hits, 1);
+    }
+
+    #[test]
+    fn test_memory_settings_keep_custom_store() {
+        let cache = CachingAspect::new()
+            .with_store(MemoryStore::new())
+            .with_max_size(1);
+        let calls = AtomicUsize::new(0);
+
+        // An unbounded store: neither entry evicts the other
+        call(&cache, 1, &calls);
+        call(&cache, 2, &calls);
+        call(&cache, 1, &calls);
+        call(&cache, 2, &calls);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let cache = CachingAspect::new();
+        let calls = AtomicUsize::new(0);
+        call(&cache, 1, &calls);
+        call(&cache, 2, &calls);
+
+        cache.invalidate(&cache.key(&joinpoint(1)).unwrap());
+        call(&cache, 1, &calls);
+        call(&cache, 2, &calls);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        assert_eq!(cache.invalidate_prefix("other"), 0);
+        assert_eq!(cache.invalidate_prefix("test::look"), 0);
+        assert_eq!(cache.invalidate_prefix("test::lookup"), 2);
+        assert!(cache.is_empty());
+    }
+
+    #[cfg(feature = "moka")]
+    #[test]
+    fn test_moka_store_across_threads() {
+        let cache = CachingAspect::new().with_store(MokaStore::new(100).build());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let cache = cache.clone();
+                let calls = calls.clone();
+                std::thread::spawn(move || {
+                    for id in 0..10 {
+                        assert_eq!(call(&cache, id, &calls), id * 10);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Concurrent misses on the same key may each run the function
+        assert!(calls.load(Ordering::SeqCst) >= 10);
+        assert_eq!(cache.len(), 10);
+        let stats = cache.stats();
+        assert_eq!(stats.hits + stats.misses, 40);
     }
 }
@@ -0,0 +1,259 @@
+//! Keeping a [`CachingAspect`](super::CachingAspect) coherent with the
+//! functions writing the data it caches.
+
+use super::store::{CacheKey, CacheStore};
+use super::KeyExtractor;
+use aspect_core::{Aspect, AspectSnapshot, JoinPoint, Precedence};
+use std::any::Any;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Removes entries of a cache whenever a function it is applied to returns
+/// successfully, so that reads are not served results the write made stale.
+///
+/// Made by [`CachingAspect::invalidator`](super::CachingAspect::invalidator)
+/// and bound to the writing functions, typically by a pointcut such as
+/// `execution(pub fn update_*(..)) || execution(pub fn delete_*(..))`.
+/// It removes all the entries of the cache, unless narrowed down to those
+/// of some functions with [`with_prefix`](Self::with_prefix), or to the
+/// entry of a read with [`with_entry`](Self::with_entry). Failed calls
+/// remove nothing.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_core::pointcut::Pointcut;
+/// use aspect_runtime::global_registry;
+/// use aspect_std::caching::{CachingAspect, SelectedArgs};
+/// use std::sync::{Arc, LazyLock};
+///
+/// static USERS: LazyLock<CachingAspect> = LazyLock::new(CachingAspect::new);
+///
+/// #[aspect(USERS.clone())]
+/// fn get_user(id: u64) -> Result<String, String> { /* ... */ }
+///
+/// // In the `users` module of the `my_app` crate, `update_user(id, name)`
+/// // removes the entry of `get_user(id)`, and `delete_users(..)` all the
+/// // entries of the module
+/// global_registry().register_by_precedence(
+///     Arc::new(USERS.invalidator().with_entry(
+///         "my_app::users",
+///         "get_user",
+///         SelectedArgs::new(["id"]),
+///     )),
+///     Pointcut::parse("execution(pub fn update_user(..))").unwrap(),
+///     None,
+/// );
+/// global_registry().register_by_precedence(
+///     Arc::new(USERS.invalidator().with_prefix("my_app::users")),
+///     Pointcut::parse("execution(pub fn delete_*(..))").unwrap(),
+///     None,
+/// );
+/// ```
+#[derive(Clone)]
+pub struct InvalidationAspect {
+    store: Arc<dyn CacheStore>,
+    targets: Vec<Target>,
+    invalidations: Arc<AtomicU64>,
+}
+
+/// Entries an [`InvalidationAspect`] removes.
+#[derive(Clone)]
+enum Target {
+    /// Those of the functions whose path starts with the prefix
+    Prefix(String),
+    /// That of the read keyed on the arguments of the write
+    Entry {
+        module_path: &'static str,
+        function_name: &'static str,
+        key: Arc<dyn KeyExtractor>,
+    },
+}
+
+impl InvalidationAspect {
+    pub(super) fn new(store: Arc<dyn CacheStore>) -> Self {
+        Self {
+            store,
+            targets: Vec::new(),
+            invalidations: Arc::default(),
+        }
+    }
+
+    /// Remove the entries of the functions whose path starts with
+    /// `prefix`, see [`CachingAspect::invalidate_prefix`](super::CachingAspect::invalidate_prefix).
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.targets.push(Target::Prefix(prefix.into()));
+        self
+    }
+
+    /// Remove the entry of the function `module_path::function_name`
+    /// keyed on the arguments of the write by `key`.
+    ///
+    /// `key` must compute the key the cache computes for the read: for a
+    /// read cached on all its arguments, [`SelectedArgs`](super::SelectedArgs)
+    /// naming the arguments of the write with the same values, in order.
+    /// Writes for which `key` yields no key remove all the entries of the
+    /// function.
+    pub fn with_entry(
+        mut self,
+        module_path: &'static str,
+        function_name: &'static str,
+        key: impl KeyExtractor + 'static,
+    ) -> Self {
+        self.targets.push(Target::Entry {
+            module_path,
+            function_name,
+            key: Arc::new(key),
+        });
+        self
+    }
+
+    /// Number of calls that removed entries.
+    pub fn invalidations(&self) -> u64 {
+        self.invalidations.load(Ordering::Relaxed)
+    }
+
+    fn invalidate(&self, ctx: &JoinPoint) {
+        if self.targets.is_empty() {
+            self.store.clear();
+        }
+        for target in &self.targets {
+            match target {
+                Target::Prefix(prefix) => {
+                    self.store.remove_matching(&|key| key.starts_with(prefix));
+                }
+                Target::Entry {
+                    module_path,
+                    function_name,
+                    key,
+                } => match key.extract(ctx) {
+                    Some(hash) => self.store.remove(&CacheKey {
+                        module_path,
+                        function_name,
+                        hash,
+                    }),
+                    None => {
+                        self.store.remove_matching(&|key| {
+                            key.module_path == *module_path && key.function_name == *function_name
+                        });
+                    }
+                },
+            }
+        }
+        self.invalidations.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Aspect for InvalidationAspect {
+    fn after(&self, ctx: &JoinPoint, _result: &dyn Any) {
+        self.invalidate(ctx);
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::CACHING
+    }
+
+    /// The number of targets, none meaning the whole cache, and of calls
+    /// that removed entries.
+    fn snapshot(&self) -> AspectSnapshot {
+        AspectSnapshot::new("InvalidationAspect")
+            .with("targets", self.targets.len())
+            .with("invalidations", self.invalidations())
+    }
+
+    fn reads_args(&self) -> bool {
+        self.targets
+            .iter()
+            .any(|target| matches!(target, Target::Entry { .. }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::caching::{CachingAspect, InvalidationAspect, SelectedArgs};
+    use aspect_macros::aspect;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::LazyLock;
+
+    static USERS: LazyLock<CachingAspect> = LazyLock::new(CachingAspect::new);
+    static USER_UPDATES: LazyLock<InvalidationAspect> = LazyLock::new(|| {
+        USERS
+            .invalidator()
+            .with_entry(module_path!(), "get_user", SelectedArgs::new(["id"]))
+    });
+    static USER_READS: AtomicUsize = AtomicUsize::new(0);
+
+    #[aspect(USERS.clone())]
+    fn get_user(id: u64) -> Result<String, String> {
+        USER_READS.fetch_add(1, Ordering::SeqCst);
+        Ok(format!("user{}", id))
+    }
+
+    #[aspect(USERS.clone())]
+    fn count_users() -> Result<u64, String> {
+        Ok(2)
+    }
+
+    #[aspect(USER_UPDATES.clone())]
+    fn update_user(id: u64, name: &str) -> Result<(), String> {
+        if name.is_empty() {
+            return Err(format!("user {} needs a name", id));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalidates_entry_of_write() {
+        get_user(1).unwrap();
+        get_user(2).unwrap();
+        count_users().unwrap();
+        assert_eq!(USERS.len(), 3);
+
+        // Failed writes change nothing
+        assert!(update_user(1, "").is_err());
+        assert_eq!(USERS.len(), 3);
+
+        update_user(1, "alice").unwrap();
+        assert_eq!(USERS.len(), 2);
+        get_user(1).unwrap();
+        get_user(2).unwrap();
+        assert_eq!(USER_READS.load(Ordering::SeqCst), 3);
+        assert_eq!(USER_UPDATES.invalidations(), 1);
+    }
+
+    static REPORTS: LazyLock<CachingAspect> = LazyLock::new(CachingAspect::new);
+
+    #[aspect(REPORTS.clone())]
+    fn daily_report(day: u32) -> Result<u32, String> {
+        Ok(day)
+    }
+
+    #[aspect(REPORTS.clone())]
+    fn weekly_report(week: u32) -> Result<u32, String> {
+        Ok(week)
+    }
+
+    #[aspect(REPORTS.invalidator().with_prefix(concat!(module_path!(), "::daily_report")))]
+    fn add_sale() -> Result<(), String> {
+        Ok(())
+    }
+
+    #[aspect(REPORTS.invalidator())]
+    fn reset_sales() -> Result<(), String> {
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalidates_prefix_or_all() {
+        daily_report(1).unwrap();
+        daily_report(2).unwrap();
+        weekly_report(1).unwrap();
+
+        add_sale().unwrap();
+        assert_eq!(REPORTS.len(), 1);
+
+        daily_report(1).unwrap();
+        reset_sales().unwrap();
+        assert!(REPORTS.is_empty());
+    }
+}
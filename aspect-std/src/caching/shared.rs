@@ -0,0 +1,721 @@
+//! Caches shared between processes, for [`CachingAspect`](super::CachingAspect).
+
+use super::store::{CacheKey, CacheStore, CachedValue};
+use crate::time::Duration;
+use aspect_core::AspectError;
+use parking_lot::RwLock;
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Storage for the serialized entries of a [`SharedStore`], such as a
+/// Redis or memcached server.
+///
+/// Keys are strings and values bytes; the [`SharedStore`] in front turns
+/// cached results into bytes and back. Implementations report failures,
+/// which the store logs and treats as misses, so that an unavailable cache
+/// slows calls down instead of failing them.
+pub trait RemoteStore: Send + Sync {
+    /// The bytes stored under `key`, if any.
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AspectError>;
+
+    /// Store `value` under `key`, expiring after `ttl` if given.
+    fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> Result<(), AspectError>;
+
+    /// Remove the entry under `key`, if any.
+    fn remove(&self, key: &str) -> Result<(), AspectError>;
+
+    /// Remove all the entries of this store, but not those of other
+    /// stores sharing the server.
+    fn clear(&self) -> Result<(), AspectError>;
+
+    /// Number of entries of this store.
+    fn len(&self) -> Result<usize, AspectError>;
+
+    /// Returns `true` if the store holds no entries.
+    fn is_empty(&self) -> Result<bool, AspectError> {
+        self.len().map(|len| len == 0)
+    }
+}
+
+type Encode =
+    Arc<dyn Fn(&(dyn Any + Send + Sync)) -> Option<Result<Vec<u8>, AspectError>> + Send + Sync>;
+type Decode = Arc<dyn Fn(&[u8]) -> Option<CachedValue> + Send + Sync>;
+
+/// The functions turning values of one type into bytes and back.
+#[derive(Clone)]
+struct Codec {
+    tag: &'static str,
+    encode: Encode,
+    decode: Decode,
+}
+
+/// A [`CacheStore`] keeping entries in a [`RemoteStore`], so that every
+/// process using the same remote store shares the cache.
+///
+/// Results are only shared if their type has a codec, registered with
+/// [`codec`](Self::codec) or, with the `serde` feature, `json`; others
+/// are not cached. Each entry is tagged with
+/// the name of its type, and an entry whose type has no codec in the
+/// reading process, e.g. one written by another version of the service, is
+/// a miss. The type must also be [`cacheable`](super::CachingAspect::cacheable)
+/// by the aspect.
+///
+/// Keys are the module path and name of the function, and the hash of the
+/// arguments computed by the aspect's [`KeyExtractor`](super::KeyExtractor).
+/// The hashes of the default extractors are only stable between processes
+/// built by the same version of Rust.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::caching::{CachingAspect, RedisStore, SharedStore};
+/// use std::time::Duration;
+///
+/// let redis = RedisStore::new("redis://127.0.0.1/")?.with_prefix("myapp:cache:");
+/// let cache = CachingAspect::new().cacheable::<User>().with_store(
+///     SharedStore::new(redis)
+///         .json::<User>()
+///         .with_ttl(Duration::from_secs(300)),
+/// );
+/// ```
+pub struct SharedStore<R> {
+    remote: R,
+    ttl: Option<Duration>,
+    by_type: RwLock<HashMap<TypeId, Codec>>,
+    by_tag: RwLock<HashMap<&'static str, Codec>>,
+}
+
+impl<R: RemoteStore> SharedStore<R> {
+    /// Keep entries in `remote`, without expiry.
+    pub fn new(remote: R) -> Self {
+        Self {
+            remote,
+            ttl: None,
+            by_type: RwLock::new(HashMap::new()),
+            by_tag: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Expire entries this long after they were inserted.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Share results of type `T`, turned into bytes by `encode` and back
+    /// by `decode`, which returns `None` for bytes it cannot read. A value
+    /// `encode` fails on is logged and not shared.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let store = SharedStore::new(remote).codec::<u64>(
+    ///     |n| Ok(n.to_le_bytes().to_vec()),
+    ///     |bytes| Some(u64::from_le_bytes(bytes.try_into().ok()?)),
+    /// );
+    /// ```
+    pub fn codec<T: Send + Sync + 'static>(
+        self,
+        encode: impl Fn(&T) -> Result<Vec<u8>, AspectError> + Send + Sync + 'static,
+        decode: impl Fn(&[u8]) -> Option<T> + Send + Sync + 'static,
+    ) -> Self {
+        let codec = Codec {
+            tag: type_name::<T>(),
+            encode: Arc::new(move |value| value.downcast_ref::<T>().map(&encode)),
+            decode: Arc::new(move |bytes| {
+                decode(bytes).map(|value| Arc::new(value) as CachedValue)
+            }),
+        };
+        self.by_tag.write().insert(codec.tag, codec.clone());
+        self.by_type.write().insert(TypeId::of::<T>(), codec);
+        self
+    }
+
+    /// Share results of type `T` as JSON. Available with the `serde`
+    /// feature.
+    #[cfg(feature = "serde")]
+    pub fn json<T>(self) -> Self
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Send + Sync + 'static,
+    {
+        self.codec::<T>(
+            |value| {
+                serde_json::to_vec(value)
+                    .map_err(|err| AspectError::execution_with_source("cannot encode as JSON", err))
+            },
+            |bytes| serde_json::from_slice(bytes).ok(),
+        )
+    }
+
+    /// The remote store.
+    pub fn remote(&self) -> &R {
+        &self.remote
+    }
+
+    fn key(key: &CacheKey) -> String {
+        format!(
+            "{}::{}:{:032x}",
+            key.module_path, key.function_name, key.hash
+        )
+    }
+
+    /// `value` as bytes: the tag of its type, a newline, and its encoding;
+    /// `None` if its type has no codec.
+    fn encode(&self, value: &(dyn Any + Send + Sync)) -> Option<Result<Vec<u8>, AspectError>> {
+        let codec = self.by_type.read().get(&value.type_id()).cloned()?;
+        Some((codec.encode)(value)?.map(|encoded| {
+            let mut bytes = Vec::with_capacity(codec.tag.len() + 1 + encoded.len());
+            bytes.extend_from_slice(codec.tag.as_bytes());
+            bytes.push(b'\n');
+            bytes.extend_from_slice(&encoded);
+            bytes
+        }))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<CachedValue> {
+        let newline = bytes.iter().position(|byte| *byte == b'\n')?;
+        let tag = std::str::from_utf8(&bytes[..newline]).ok()?;
+        let codec = self.by_tag.read().get(tag).cloned()?;
+        (codec.decode)(&bytes[newline + 1..])
+    }
+}
+
+/// Logs a failure of the remote store, which the cache treats as a miss.
+fn log_failure<T>(operation: &str, result: Result<T, AspectError>) -> Option<T> {
+    result
+        .inspect_err(|error| log::warn!("shared cache {} failed: {}", operation, error))
+        .ok()
+}
+
+impl<R: RemoteStore> CacheStore for SharedStore<R> {
+    fn get(&self, key: &CacheKey) -> Option<CachedValue> {
+        let bytes = log_failure("get", self.remote.get(&Self::key(key)))??;
+        self.decode(&bytes)
+    }
+
+    fn insert(&self, key: CacheKey, value: CachedValue, _size: usize) {
+        let bytes = match self.encode(&*value) {
+            Some(Ok(bytes)) => bytes,
+            Some(Err(error)) => {
+                log::warn!(
+                    "result of {}::{} is not shared: {}",
+                    key.module_path,
+                    key.function_name,
+                    error
+                );
+                return;
+            }
+            None => {
+                log::debug!(
+                    "result of {}::{} is not shared; register its type with SharedStore::codec",
+                    key.module_path,
+                    key.function_name
+                );
+                return;
+            }
+        };
+        log_failure("set", self.remote.set(&Self::key(&key), &bytes, self.ttl));
+    }
+
+    fn remove(&self, key: &CacheKey) {
+        log_failure("remove", self.remote.remove(&Self::key(key)));
+    }
+
+    fn clear(&self) {
+        log_failure("clear", self.remote.clear());
+    }
+
+    fn len(&self) -> usize {
+        log_failure("len", self.remote.len()).unwrap_or(0)
+    }
+}
+
+#[cfg(feature = "redis")]
+mod redis_store {
+    use super::RemoteStore;
+    use crate::time::Duration;
+    use aspect_core::AspectError;
+    use r2d2::{CustomizeConnection, Pool};
+    use redis::{Client, Commands, Connection, RedisError, RedisResult};
+
+    /// A [`RemoteStore`] keeping entries in Redis, under a key prefix.
+    /// Available with the `redis` feature.
+    ///
+    /// Entries with a TTL expire on the server. [`clear`](RemoteStore::clear)
+    /// and [`len`](RemoteStore::len) scan the keys with the prefix, so give
+    /// each cache a prefix of its own.
+    ///
+    /// Commands are synchronous: they block the calling thread, including
+    /// when the cache advises an async function, so they time out after
+    /// [`with_timeout`](Self::with_timeout). Concurrent calls run on
+    /// connections of their own, from a pool of up to
+    /// [`with_max_connections`](Self::with_max_connections) opened as
+    /// needed, so one slow command does not hold up the others.
+    ///
+    /// Connection failures and timeouts are reported to the
+    /// [`SharedStore`](super::SharedStore), which treats them as misses;
+    /// broken connections are replaced on the next call.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use aspect_std::caching::{CachingAspect, RedisStore, SharedStore};
+    /// use std::time::Duration;
+    ///
+    /// let redis = RedisStore::new("redis://127.0.0.1/")
+    ///     .unwrap()
+    ///     .with_prefix("myapp:users:")
+    ///     .with_timeout(Duration::from_millis(50));
+    /// let cache = CachingAspect::new().with_store(SharedStore::new(redis).codec::<String>(
+    ///     |s| Ok(s.as_bytes().to_vec()),
+    ///     |bytes| String::from_utf8(bytes.to_vec()).ok(),
+    /// ));
+    /// ```
+    pub struct RedisStore {
+        client: Client,
+        pool: Pool<Client>,
+        max_connections: u32,
+        timeout: Duration,
+        prefix: String,
+    }
+
+    /// Sets the read and write timeouts of the connections of the pool.
+    #[derive(Debug)]
+    struct Timeouts(Duration);
+
+    impl CustomizeConnection<Connection, RedisError> for Timeouts {
+        fn on_acquire(&self, conn: &mut Connection) -> RedisResult<()> {
+            conn.set_read_timeout(Some(self.0))?;
+            conn.set_write_timeout(Some(self.0))
+        }
+    }
+
+    impl RedisStore {
+        /// Default of [`with_max_connections`](Self::with_max_connections).
+        pub const DEFAULT_MAX_CONNECTIONS: u32 = 10;
+
+        /// Default of [`with_timeout`](Self::with_timeout).
+        pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+        /// Create a store using the Redis server at `url`.
+        ///
+        /// Connections are opened on first use.
+        pub fn new(url: &str) -> RedisResult<Self> {
+            let client = Client::open(url)?;
+            Ok(Self {
+                pool: Self::pool(
+                    &client,
+                    Self::DEFAULT_MAX_CONNECTIONS,
+                    Self::DEFAULT_TIMEOUT,
+                ),
+                client,
+                max_connections: Self::DEFAULT_MAX_CONNECTIONS,
+                timeout: Self::DEFAULT_TIMEOUT,
+                prefix: "aspect:cache:".to_string(),
+            })
+        }
+
+        /// Set the prefix of the Redis keys holding the entries.
+        pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+            self.prefix = prefix.into();
+            self
+        }
+
+        /// Open at most `max` connections, calls waiting for one beyond
+        /// that.
+        pub fn with_max_connections(mut self, max: u32) -> Self {
+            self.max_connections = max.max(1);
+            self.pool = Self::pool(&self.client, self.max_connections, self.timeout);
+            self
+        }
+
+        /// Fail commands, and waits for a connection, taking longer than
+        /// `timeout`.
+        pub fn with_timeout(mut self, timeout: Duration) -> Self {
+            // Sockets reject a zero timeout
+            self.timeout = timeout.max(Duration::from_millis(1));
+            self.pool = Self::pool(&self.client, self.max_connections, self.timeout);
+            self
+        }
+
+        fn pool(client: &Client, max_connections: u32, timeout: Duration) -> Pool<Client> {
+            Pool::builder()
+                .max_size(max_connections)
+                .min_idle(Some(0))
+                .connection_timeout(timeout)
+                .connection_customizer(Box::new(Timeouts(timeout)))
+                .build_unchecked(client.clone())
+        }
+
+        /// Run `command` on a connection of the pool.
+        fn run<T>(
+            &self,
+            command: impl FnOnce(&mut Connection) -> RedisResult<T>,
+        ) -> Result<T, AspectError> {
+            let mut conn = self.pool.get().map_err(|err| {
+                AspectError::execution_with_source("cache store unavailable", err)
+            })?;
+            command(&mut conn)
+                .map_err(|err| AspectError::execution_with_source("cache store unavailable", err))
+        }
+
+        fn keys(&self, conn: &mut Connection) -> RedisResult<Vec<String>> {
+            let pattern = format!("{}*", self.prefix);
+            let keys = conn.scan_match::<_, String>(pattern)?.collect();
+            Ok(keys)
+        }
+    }
+
+    impl RemoteStore for RedisStore {
+        fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AspectError> {
+            self.run(|conn| conn.get(format!("{}{}", self.prefix, key)))
+        }
+
+        fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> Result<(), AspectError> {
+            let key = format!("{}{}", self.prefix, key);
+            self.run(|conn| match ttl {
+                Some(ttl) => {
+                    let millis = u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX).max(1);
+                    conn.pset_ex(key, value, millis)
+                }
+                None => conn.set(key, value),
+            })
+        }
+
+        fn remove(&self, key: &str) -> Result<(), AspectError> {
+            self.run(|conn| conn.del(format!("{}{}", self.prefix, key)))
+        }
+
+        fn clear(&self) -> Result<(), AspectError> {
+            self.run(|conn| {
+                let keys = self.keys(conn)?;
+                if keys.is_empty() {
+                    return Ok(());
+                }
+                conn.del(keys)
+            })
+        }
+
+        fn len(&self) -> Result<usize, AspectError> {
+            self.run(|conn| self.keys(conn).map(|keys| keys.len()))
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+pub use redis_store::RedisStore;
+
+#[cfg(feature = "cached")]
+mod io_cached_store {
+    use super::RemoteStore;
+    use crate::time::Duration;
+    use aspect_core::AspectError;
+    use cached::IOCached;
+    use std::fmt::Display;
+
+    /// A [`RemoteStore`] over an [`IOCached`] store of the `cached` crate,
+    /// such as its `RedisCache` or `DiskCache`, so that a [`SharedStore`](super::SharedStore)
+    /// in front of it makes it a store of a [`CachingAspect`](crate::caching::CachingAspect).
+    /// Available with the `cached` feature.
+    ///
+    /// Entries expire after the lifespan the store was built with: the TTL
+    /// of the [`SharedStore`](super::SharedStore) does not apply. `IOCached`
+    /// stores cannot list their entries, so [`clear`](RemoteStore::clear)
+    /// and [`len`](RemoteStore::len) fail, and so does invalidating entries
+    /// by prefix: invalidate them one at a time with
+    /// [`CachingAspect::invalidate`](crate::caching::CachingAspect::invalidate).
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// use aspect_std::caching::{CachingAspect, IoCachedStore, SharedStore};
+    /// use cached::RedisCache;
+    /// use std::time::Duration;
+    ///
+    /// let redis = RedisCache::new("myapp:users:", Duration::from_secs(300)).build()?;
+    /// let cache = CachingAspect::new()
+    ///     .cacheable::<User>()
+    ///     .with_store(SharedStore::new(IoCachedStore::new(redis)).json::<User>());
+    /// ```
+    pub struct IoCachedStore<C> {
+        cache: C,
+    }
+
+    impl<C> IoCachedStore<C> {
+        /// A store keeping its entries in `cache`.
+        pub fn new(cache: C) -> Self {
+            Self { cache }
+        }
+
+        /// The `cached` store entries are kept in.
+        pub fn cache(&self) -> &C {
+            &self.cache
+        }
+    }
+
+    fn failed(error: impl Display) -> AspectError {
+        AspectError::execution(format!("cached store: {}", error))
+    }
+
+    impl<C> RemoteStore for IoCachedStore<C>
+    where
+        C: IOCached<String, Vec<u8>> + Send + Sync,
+        C::Error: Display,
+    {
+        fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AspectError> {
+            self.cache.cache_get(&key.to_string()).map_err(failed)
+        }
+
+        fn set(&self, key: &str, value: &[u8], _ttl: Option<Duration>) -> Result<(), AspectError> {
+            self.cache
+                .cache_set(key.to_string(), value.to_vec())
+                .map(drop)
+                .map_err(failed)
+        }
+
+        fn remove(&self, key: &str) -> Result<(), AspectError> {
+            self.cache
+                .cache_remove(&key.to_string())
+                .map(drop)
+                .map_err(failed)
+        }
+
+        fn clear(&self) -> Result<(), AspectError> {
+            Err(failed("IOCached stores cannot be cleared"))
+        }
+
+        fn len(&self) -> Result<usize, AspectError> {
+            Err(failed("IOCached stores cannot count their entries"))
+        }
+    }
+}
+
+#[cfg(feature = "cached")]
+pub use io_cached_store::IoCachedStore;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::caching::CachingAspect;
+    use aspect_core::{Aspect, JoinPoint, Location, ProceedingJoinPoint};
+    use parking_lot::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A remote store in memory, shared by its clones as a server is by
+    /// processes.
+    #[derive(Clone, Default)]
+    struct Server {
+        entries: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+        down: Arc<Mutex<bool>>,
+    }
+
+    impl Server {
+        fn check(&self) -> Result<(), AspectError> {
+            if *self.down.lock() {
+                return Err(AspectError::execution("connection refused"));
+            }
+            Ok(())
+        }
+    }
+
+    impl RemoteStore for Server {
+        fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AspectError> {
+            self.check()?;
+            Ok(self.entries.lock().get(key).cloned())
+        }
+
+        fn set(&self, key: &str, value: &[u8], _ttl: Option<Duration>) -> Result<(), AspectError> {
+            self.check()?;
+            self.entries.lock().insert(key.to_string(), value.to_vec());
+            Ok(())
+        }
+
+        fn remove(&self, key: &str) -> Result<(), AspectError> {
+            self.check()?;
+            self.entries.lock().remove(key);
+            Ok(())
+        }
+
+        fn clear(&self) -> Result<(), AspectError> {
+            self.check()?;
+            self.entries.lock().clear();
+            Ok(())
+        }
+
+        fn len(&self) -> Result<usize, AspectError> {
+            self.check()?;
+            Ok(self.entries.lock().len())
+        }
+    }
+
+    fn string_store(server: &Server) -> SharedStore<Server> {
+        SharedStore::new(server.clone()).codec::<String>(
+            |s| Ok(s.as_bytes().to_vec()),
+            |bytes| String::from_utf8(bytes.to_vec()).ok(),
+        )
+    }
+
+    fn key(hash: u128) -> CacheKey {
+        CacheKey {
+            module_path: "app::users",
+            function_name: "name",
+            hash,
+        }
+    }
+
+    #[test]
+    fn test_shared_between_stores() {
+        let server = Server::default();
+        let first = string_store(&server);
+        let second = string_store(&server);
+
+        first.insert(key(1), Arc::new("alice".to_string()), 5);
+        let value = second.get(&key(1)).unwrap();
+        assert_eq!(value.downcast_ref::<String>().unwrap(), "alice");
+        assert!(server
+            .entries
+            .lock()
+            .contains_key("app::users::name:00000000000000000000000000000001"));
+        assert_eq!(second.len(), 1);
+
+        second.remove(&key(1));
+        assert!(first.get(&key(1)).is_none());
+    }
+
+    #[test]
+    fn test_types_without_codec() {
+        let server = Server::default();
+        let store = string_store(&server);
+
+        // Not encoded
+        store.insert(key(1), Arc::new(42u32), 4);
+        assert_eq!(store.len(), 0);
+
+        // Written by a process with a codec for u32, not decoded here
+        let writer = SharedStore::new(server.clone())
+            .codec::<u32>(|n| Ok(n.to_le_bytes().to_vec()), |_| None);
+        writer.insert(key(2), Arc::new(42u32), 4);
+        assert_eq!(store.len(), 1);
+        assert!(store.get(&key(2)).is_none());
+    }
+
+    #[test]
+    fn test_caching_aspect_across_processes() {
+        let server = Server::default();
+        let calls = AtomicUsize::new(0);
+        let call = |cache: &CachingAspect| {
+            let ctx = JoinPoint::new(
+                "name",
+                "app::users",
+                Location {
+                    file: "users.rs",
+                    line: 1,
+                },
+            );
+            let pjp = ProceedingJoinPoint::new(
+                || {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Ok(Box::new("alice".to_string()) as Box<dyn Any>)
+                },
+                ctx,
+            );
+            *cache.around(pjp).unwrap().downcast::<String>().unwrap()
+        };
+
+        let first = CachingAspect::new().with_store(string_store(&server));
+        let second = CachingAspect::new().with_store(string_store(&server));
+        assert_eq!(call(&first), "alice");
+        assert_eq!(call(&second), "alice");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(second.stats().hits, 1);
+    }
+
+    #[test]
+    fn test_unavailable_remote_is_a_miss() {
+        let server = Server::default();
+        let store = string_store(&server);
+        store.insert(key(1), Arc::new("alice".to_string()), 5);
+
+        *server.down.lock() = true;
+        assert!(store.get(&key(1)).is_none());
+        assert_eq!(store.len(), 0);
+        store.insert(key(2), Arc::new("bob".to_string()), 3);
+
+        *server.down.lock() = false;
+        assert!(store.get(&key(1)).is_some());
+        assert!(store.get(&key(2)).is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json() {
+        let server = Server::default();
+        let store = SharedStore::new(server.clone()).json::<Vec<u32>>();
+
+        store.insert(key(1), Arc::new(vec![1u32, 2, 3]), 12);
+        let bytes = server.entries.lock().values().next().cloned().unwrap();
+        assert!(bytes.ends_with(b"\n[1,2,3]"));
+        let value = store.get(&key(1)).unwrap();
+        assert_eq!(value.downcast_ref::<Vec<u32>>().unwrap(), &[1, 2, 3]);
+
+        // JSON has no maps with keys that are not strings: not shared
+        let store = store.json::<HashMap<(u32, u32), u32>>();
+        store.insert(key(2), Arc::new(HashMap::from([((1u32, 2u32), 3u32)])), 12);
+        assert_eq!(server.entries.lock().len(), 1);
+        assert!(store.get(&key(2)).is_none());
+    }
+
+    #[cfg(feature = "cached")]
+    #[test]
+    fn test_io_cached_store() {
+        use cached::IOCached;
+
+        /// An `IOCached` store in memory, standing in for `RedisCache`.
+        #[derive(Default)]
+        struct Io(Mutex<HashMap<String, Vec<u8>>>);
+
+        impl IOCached<String, Vec<u8>> for Io {
+            type Error = String;
+
+            fn cache_get(&self, k: &String) -> Result<Option<Vec<u8>>, String> {
+                Ok(self.0.lock().get(k).cloned())
+            }
+
+            fn cache_set(&self, k: String, v: Vec<u8>) -> Result<Option<Vec<u8>>, String> {
+                Ok(self.0.lock().insert(k, v))
+            }
+
+            fn cache_remove(&self, k: &String) -> Result<Option<Vec<u8>>, String> {
+                Ok(self.0.lock().remove(k))
+            }
+
+            fn cache_set_refresh(&mut self, _refresh: bool) -> bool {
+                false
+            }
+        }
+
+        let store = SharedStore::new(IoCachedStore::new(Io::default())).codec::<String>(
+            |s| Ok(s.as_bytes().to_vec()),
+            |bytes| String::from_utf8(bytes.to_vec()).ok(),
+        );
+        store.insert(key(1), Arc::new("alice".to_string()), 5);
+        let value = store.get(&key(1)).unwrap();
+        assert_eq!(value.downcast_ref::<String>().unwrap(), "alice");
+        assert!(store
+            .remote()
+            .cache()
+            .0
+            .lock()
+            .contains_key("app::users::name:00000000000000000000000000000001"));
+
+        store.remove(&key(1));
+        assert!(store.get(&key(1)).is_none());
+
+        // Counting and clearing fail, which the store logs
+        store.insert(key(2), Arc::new("bob".to_string()), 3);
+        assert_eq!(store.len(), 0);
+        store.clear();
+        assert!(store.get(&key(2)).is_some());
+    }
+}
@@ -0,0 +1,806 @@
+//! Storage backends for [`CachingAspect`](super::CachingAspect).
+
+use super::CacheStats;
+use crate::time::{Clock, Duration, Instant, SystemClock};
+use parking_lot::Mutex;
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// A cached function result.
+pub type CachedValue = Arc<dyn Any + Send + Sync>;
+
+/// Identifies a cached result: the advised function and its argument key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    /// Module path of the advised function
+    pub module_path: &'static str,
+    /// Name of the advised function
+    pub function_name: &'static str,
+    /// Key computed by the [`KeyExtractor`](super::KeyExtractor)
+    pub hash: u128,
+}
+
+impl CacheKey {
+    /// Whether the path of the function, `module_path::function_name`,
+    /// starts with the whole segments of `prefix`: `app::user` covers
+    /// `app::user::get` but neither `app::users::get` nor
+    /// `app::user_admin::get`. The empty prefix covers every function.
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        if prefix.is_empty() {
+            return true;
+        }
+        match prefix.strip_prefix(self.module_path) {
+            Some(rest) => rest.is_empty() || rest.strip_prefix("::") == Some(self.function_name),
+            None => self
+                .module_path
+                .strip_prefix(prefix)
+                .is_some_and(|rest| rest.starts_with("::")),
+        }
+    }
+}
+
+/// Where [`CachingAspect`](super::CachingAspect) keeps its entries.
+///
+/// Stores are shared by all clones of an aspect and must be safe to use from
+/// many threads (and tokio tasks) at once. Capacity, eviction and expiry are
+/// the store's business; the aspect only gets, inserts and removes.
+pub trait CacheStore: Send + Sync {
+    /// Returns the value stored under `key`, if present and not expired.
+    fn get(&self, key: &CacheKey) -> Option<CachedValue>;
+
+    /// Stores `value`, whose estimated memory use is `size` bytes.
+    fn insert(&self, key: CacheKey, value: CachedValue, size: usize);
+
+    /// Removes the entry for `key`, if any.
+    fn remove(&self, key: &CacheKey);
+
+    /// Removes all entries.
+    fn clear(&self);
+
+    /// Removes the entries whose key satisfies `matches`, returning how
+    /// many were removed.
+    ///
+    /// Stores that cannot list their keys clear all their entries instead,
+    /// which keeps the cache coherent at the cost of the other entries.
+    fn remove_matching(&self, matches: &dyn Fn(&CacheKey) -> bool) -> usize {
+        let _ = matches;
+        let removed = self.len();
+        self.clear();
+        removed
+    }
+
+    /// Removes the expired entries now rather than when next looked up or
+    /// when the store is full, returning how many were removed.
+    ///
+    /// Stores without expiry, or expiring entries by themselves, need not
+    /// implement it.
+    fn purge_expired(&self) -> usize {
+        0
+    }
+
+    /// Number of stored entries.
+    fn len(&self) -> usize;
+
+    /// Estimated memory used by the stored entries, in bytes.
+    fn memory_usage(&self) -> usize {
+        self.stats().memory
+    }
+
+    /// Returns `true` if the store holds no entries.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Eviction and usage statistics. `hits` and `misses` are filled in by
+    /// the aspect and can be left at zero.
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.len(),
+            ..CacheStats::default()
+        }
+    }
+}
+
+/// Which entry to drop when the cache is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvictionPolicy {
+    /// Least recently used (default)
+    #[default]
+    Lru,
+    /// Least frequently used, ties broken by recency
+    Lfu,
+    /// Oldest inserted
+    Fifo,
+}
+
+/// Limits of a [`MemoryStore`].
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryStoreConfig {
+    /// Maximum number of entries
+    pub max_size: usize,
+    /// Limit on the estimated memory of stored entries, in bytes, which
+    /// inserting evicts entries to stay within
+    pub max_memory: usize,
+    /// Time-to-live of entries
+    pub ttl: Option<Duration>,
+    /// Which entries to evict first
+    pub policy: EvictionPolicy,
+}
+
+impl Default for MemoryStoreConfig {
+    fn default() -> Self {
+        Self {
+            max_size: usize::MAX,
+            max_memory: usize::MAX,
+            ttl: None,
+            policy: EvictionPolicy::Lru,
+        }
+    }
+}
+
+/// The default, in-process [`CacheStore`].
+///
+/// Entries are ordered by eviction rank in a `BTreeMap`, so evicting under
+/// any [`EvictionPolicy`] is O(log n). All operations take a single mutex.
+///
+/// The memory of an entry is the size of its value plus
+/// [`ENTRY_OVERHEAD`](Self::ENTRY_OVERHEAD) for the store's own
+/// bookkeeping, and [`memory_usage`](CacheStore::memory_usage) never exceeds
+/// [`MemoryStoreConfig::max_memory`].
+pub struct MemoryStore {
+    config: MemoryStoreConfig,
+    clock: Arc<dyn Clock>,
+    state: Mutex<MemoryState>,
+}
+
+struct MemoryEntry {
+    value: CachedValue,
+    inserted: Instant,
+    size: usize,
+    /// Current position in `MemoryState::order`
+    rank: (u64, u64),
+    uses: u64,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    entries: HashMap<CacheKey, MemoryEntry>,
+    /// Entries ordered by eviction priority, first is evicted first
+    order: BTreeMap<(u64, u64), CacheKey>,
+    tick: u64,
+    memory: usize,
+    evictions: u64,
+    expirations: u64,
+}
+
+impl MemoryState {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn remove(&mut self, key: &CacheKey) -> Option<MemoryEntry> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.rank);
+        self.memory -= entry.size;
+        Some(entry)
+    }
+
+    /// Removes the entries for which `expired` holds, counting them as
+    /// expirations.
+    fn remove_expired(&mut self, expired: impl Fn(&MemoryEntry) -> bool) -> usize {
+        let keys: Vec<CacheKey> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| expired(entry))
+            .map(|(key, _)| *key)
+            .collect();
+        self.expirations += keys.len() as u64;
+        for key in &keys {
+            self.remove(key);
+        }
+        keys.len()
+    }
+
+    fn evict_first(&mut self) -> bool {
+        let Some((_, key)) = self.order.pop_first() else {
+            return false;
+        };
+        if let Some(entry) = self.entries.remove(&key) {
+            self.memory -= entry.size;
+        }
+        self.evictions += 1;
+        true
+    }
+
+    /// Records a use of `key` according to the eviction policy.
+    fn touch(&mut self, key: &CacheKey, policy: EvictionPolicy) {
+        let tick = self.next_tick();
+        let Some(entry) = self.entries.get_mut(key) else {
+            return;
+        };
+        entry.uses += 1;
+        let rank = match policy {
+            EvictionPolicy::Lru => (0, tick),
+            EvictionPolicy::Lfu => (entry.uses, tick),
+            EvictionPolicy::Fifo => return,
+        };
+        let old = std::mem::replace(&mut entry.rank, rank);
+        self.order.remove(&old);
+        self.order.insert(rank, *key);
+    }
+}
+
+impl MemoryStore {
+    /// Approximate memory used by the store for each entry besides its
+    /// value: the key and entry in the map, their place in the eviction
+    /// order and the reference counts of the value.
+    pub const ENTRY_OVERHEAD: usize = 2 * std::mem::size_of::<CacheKey>()
+        + std::mem::size_of::<MemoryEntry>()
+        + std::mem::size_of::<(u64, u64)>()
+        + 2 * std::mem::size_of::<usize>();
+
+    /// Create an unbounded store.
+    pub fn new() -> Self {
+        Self::with_config(MemoryStoreConfig::default())
+    }
+
+    /// Create a store with the given limits.
+    pub fn with_config(config: MemoryStoreConfig) -> Self {
+        Self {
+            config,
+            clock: Arc::new(SystemClock),
+            state: Mutex::new(MemoryState::default()),
+        }
+    }
+
+    /// Expire entries by the time of `clock` instead of the system clock,
+    /// e.g. a [`ManualClock`](crate::time::ManualClock) in tests.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// The limits of this store.
+    pub fn config(&self) -> &MemoryStoreConfig {
+        &self.config
+    }
+
+    fn is_expired(&self, entry: &MemoryEntry, now: Instant) -> bool {
+        self.config
+            .ttl
+            .is_some_and(|ttl| now.duration_since(entry.inserted) >= ttl)
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CacheStore for MemoryStore {
+    fn get(&self, key: &CacheKey) -> Option<CachedValue> {
+        let mut state = self.state.lock();
+        let entry = state.entries.get(key)?;
+        if self.is_expired(entry, self.clock.now()) {
+            state.remove(key);
+            state.expirations += 1;
+            return None;
+        }
+
+        let value = entry.value.clone();
+        state.touch(key, self.config.policy);
+        Some(value)
+    }
+
+    fn insert(&self, key: CacheKey, value: CachedValue, size: usize) {
+        let config = &self.config;
+        let size = size.saturating_add(Self::ENTRY_OVERHEAD);
+        if config.max_size == 0 || size > config.max_memory {
+            return;
+        }
+
+        let now = self.clock.now();
+        let mut state = self.state.lock();
+        state.remove(&key);
+
+        let full = |state: &MemoryState| {
+            state.entries.len() >= config.max_size || state.memory + size > config.max_memory
+        };
+        if full(&state) {
+            state.remove_expired(|entry| self.is_expired(entry, now));
+        }
+        while full(&state) && state.evict_first() {}
+
+        let tick = state.next_tick();
+        let rank = match config.policy {
+            EvictionPolicy::Lfu => (1, tick),
+            EvictionPolicy::Lru | EvictionPolicy::Fifo => (0, tick),
+        };
+        state.order.insert(rank, key);
+        state.memory += size;
+        state.entries.insert(
+            key,
+            MemoryEntry {
+                value,
+                inserted: now,
+                size,
+                rank,
+                uses: 1,
+            },
+        );
+    }
+
+    fn remove(&self, key: &CacheKey) {
+        self.state.lock().remove(key);
+    }
+
+    fn clear(&self) {
+        let mut state = self.state.lock();
+        state.entries.clear();
+        state.order.clear();
+        state.memory = 0;
+    }
+
+    fn remove_matching(&self, matches: &dyn Fn(&CacheKey) -> bool) -> usize {
+        let mut state = self.state.lock();
+        let keys: Vec<CacheKey> = state
+            .entries
+            .keys()
+            .filter(|key| matches(key))
+            .copied()
+            .collect();
+        for key in &keys {
+            state.remove(key);
+        }
+        keys.len()
+    }
+
+    fn purge_expired(&self) -> usize {
+        if self.config.ttl.is_none() {
+            return 0;
+        }
+        let now = self.clock.now();
+        self.state
+            .lock()
+            .remove_expired(|entry| self.is_expired(entry, now))
+    }
+
+    fn len(&self) -> usize {
+        self.state.lock().entries.len()
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.state.lock().memory
+    }
+
+    fn stats(&self) -> CacheStats {
+        let state = self.state.lock();
+        CacheStats {
+            evictions: state.evictions,
+            expirations: state.expirations,
+            entries: state.entries.len(),
+            memory: state.memory,
+            ..CacheStats::default()
+        }
+    }
+}
+
+#[cfg(feature = "moka")]
+pub use self::moka_store::MokaStore;
+
+#[cfg(feature = "moka")]
+mod moka_store {
+    use super::*;
+    use moka::notification::RemovalCause;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// A [`CacheStore`] backed by a concurrent [`moka`] cache.
+    ///
+    /// Unlike [`MemoryStore`], lookups don't serialize on a single lock, which
+    /// suits caches shared by many threads or tokio tasks. Available with the
+    /// `moka` feature.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// use aspect_std::caching::{CachingAspect, MokaStore};
+    /// use std::time::Duration;
+    ///
+    /// let cache = CachingAspect::new()
+    ///     .with_store(MokaStore::new(10_000).with_ttl(Duration::from_secs(300)).build());
+    /// ```
+    pub struct MokaStore {
+        cache: moka::sync::Cache<CacheKey, WeightedValue>,
+        counters: Arc<Counters>,
+    }
+
+    #[derive(Clone)]
+    struct WeightedValue {
+        value: CachedValue,
+        weight: u32,
+    }
+
+    #[derive(Default)]
+    struct Counters {
+        evictions: AtomicU64,
+        expirations: AtomicU64,
+    }
+
+    /// Builder for a [`MokaStore`].
+    pub struct MokaStoreBuilder {
+        max_capacity: u64,
+        weighted: bool,
+        ttl: Option<Duration>,
+        tti: Option<Duration>,
+    }
+
+    impl MokaStore {
+        /// Start building a store holding at most `max_entries` entries.
+        #[allow(clippy::new_ret_no_self)]
+        pub fn new(max_entries: u64) -> MokaStoreBuilder {
+            MokaStoreBuilder {
+                max_capacity: max_entries,
+                weighted: false,
+                ttl: None,
+                tti: None,
+            }
+        }
+    }
+
+    impl MokaStoreBuilder {
+        /// Bound the store by estimated memory (in bytes) instead of entry count.
+        pub fn max_memory(mut self, bytes: u64) -> Self {
+            self.max_capacity = bytes;
+            self.weighted = true;
+            self
+        }
+
+        /// Expire entries this long after they were inserted.
+        pub fn with_ttl(mut self, ttl: Duration) -> Self {
+            self.ttl = Some(ttl);
+            self
+        }
+
+        /// Expire entries this long after they were last read.
+        pub fn with_idle_timeout(mut self, tti: Duration) -> Self {
+            self.tti = Some(tti);
+            self
+        }
+
+        /// Build the store.
+        pub fn build(self) -> MokaStore {
+            let counters = Arc::new(Counters::default());
+            let listener_counters = counters.clone();
+            let mut builder = moka::sync::Cache::builder()
+                .max_capacity(self.max_capacity)
+                .eviction_listener(move |_key, _value, cause| match cause {
+                    RemovalCause::Size => {
+                        listener_counters.evictions.fetch_add(1, Ordering::Relaxed);
+                    }
+                    RemovalCause::Expired => {
+                        listener_counters
+                            .expirations
+                            .fetch_add(1, Ordering::Relaxed);
+                    }
+                    RemovalCause::Explicit | RemovalCause::Replaced => {}
+                });
+            if self.weighted {
+                builder = builder.weigher(|_key, value: &WeightedValue| value.weight);
+            }
+            if let Some(ttl) = self.ttl {
+                builder = builder.time_to_live(ttl);
+            }
+            if let Some(tti) = self.tti {
+                builder = builder.time_to_idle(tti);
+            }
+            MokaStore {
+                cache: builder.build(),
+                counters,
+            }
+        }
+    }
+
+    impl CacheStore for MokaStore {
+        fn get(&self, key: &CacheKey) -> Option<CachedValue> {
+            self.cache.get(key).map(|entry| entry.value)
+        }
+
+        fn insert(&self, key: CacheKey, value: CachedValue, size: usize) {
+            let weight = u32::try_from(size).unwrap_or(u32::MAX);
+            self.cache.insert(key, WeightedValue { value, weight });
+        }
+
+        fn remove(&self, key: &CacheKey) {
+            self.cache.invalidate(key);
+        }
+
+        fn clear(&self) {
+            self.cache.invalidate_all();
+        }
+
+        fn remove_matching(&self, matches: &dyn Fn(&CacheKey) -> bool) -> usize {
+            let keys: Vec<CacheKey> = self
+                .cache
+                .iter()
+                .map(|(key, _)| *key)
+                .filter(|key| matches(key))
+                .collect();
+            for key in &keys {
+                self.cache.invalidate(key);
+            }
+            keys.len()
+        }
+
+        /// Runs the pending maintenance of the cache, which removes the
+        /// expired entries; how many is not known.
+        fn purge_expired(&self) -> usize {
+            self.cache.run_pending_tasks();
+            0
+        }
+
+        fn len(&self) -> usize {
+            self.cache.run_pending_tasks();
+            self.cache.entry_count() as usize
+        }
+
+        fn stats(&self) -> CacheStats {
+            self.cache.run_pending_tasks();
+            CacheStats {
+                evictions: self.counters.evictions.load(Ordering::Relaxed),
+                expirations: self.counters.expirations.load(Ordering::Relaxed),
+                entries: self.cache.entry_count() as usize,
+                memory: self.cache.weighted_size() as usize,
+                ..CacheStats::default()
+            }
+        }
+    }
+}
+
+#[cfg(feature = "cached")]
+pub use self::cached_store::CachedStore;
+
+#[cfg(feature = "cached")]
+mod cached_store {
+    use super::*;
+    use cached::Cached;
+
+    /// A [`CacheStore`] over a [`Cached`] store of the `cached` crate, such
+    /// as its `SizedCache`, `TimedCache` or `TimedSizedCache`, for their
+    /// eviction and expiry policies. Available with the `cached`
+    /// feature.
+    ///
+    /// The store is behind a lock, as `Cached` lookups take it mutably to
+    /// record hits and recency. `Cached` stores cannot list their keys, so
+    /// invalidating entries by prefix clears the store.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// use aspect_std::caching::{CachedStore, CachingAspect};
+    /// use cached::TimedSizedCache;
+    /// use std::time::Duration;
+    ///
+    /// let cache = CachingAspect::new().with_store(CachedStore::new(
+    ///     TimedSizedCache::with_size_and_lifespan(10_000, Duration::from_secs(300)),
+    /// ));
+    /// ```
+    pub struct CachedStore<C> {
+        cache: Mutex<C>,
+    }
+
+    impl<C> CachedStore<C> {
+        /// A store keeping its entries in `cache`.
+        pub fn new(cache: C) -> Self {
+            Self {
+                cache: Mutex::new(cache),
+            }
+        }
+
+        /// Runs `f` on the `cached` store, e.g. to read its metrics.
+        pub fn with_cache<R>(&self, f: impl FnOnce(&mut C) -> R) -> R {
+            f(&mut self.cache.lock())
+        }
+    }
+
+    impl<C: Cached<CacheKey, CachedValue> + Send> CacheStore for CachedStore<C> {
+        fn get(&self, key: &CacheKey) -> Option<CachedValue> {
+            self.cache.lock().cache_get(key).cloned()
+        }
+
+        fn insert(&self, key: CacheKey, value: CachedValue, _size: usize) {
+            self.cache.lock().cache_set(key, value);
+        }
+
+        fn remove(&self, key: &CacheKey) {
+            self.cache.lock().cache_remove(key);
+        }
+
+        fn clear(&self) {
+            self.cache.lock().cache_clear();
+        }
+
+        fn len(&self) -> usize {
+            self.cache.lock().cache_size()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(hash: u128) -> CacheKey {
+        CacheKey {
+            module_path: "test",
+            function_name: "f",
+            hash,
+        }
+    }
+
+    fn value(v: u64) -> CachedValue {
+        Arc::new(v)
+    }
+
+    fn cached(store: &dyn CacheStore, hash: u128) -> Option<u64> {
+        store
+            .get(&key(hash))
+            .map(|v| *v.downcast_ref::<u64>().unwrap())
+    }
+
+    #[test]
+    fn test_memory_store_roundtrip() {
+        let store = MemoryStore::new();
+        store.insert(key(1), value(10), 8);
+
+        assert_eq!(cached(&store, 1), Some(10));
+        assert_eq!(cached(&store, 2), None);
+        assert_eq!(store.memory_usage(), 8 + MemoryStore::ENTRY_OVERHEAD);
+        assert_eq!(store.stats().memory, store.memory_usage());
+
+        store.remove(&key(1));
+        assert!(store.is_empty());
+        assert_eq!(store.memory_usage(), 0);
+    }
+
+    #[test]
+    fn test_memory_store_remove_matching() {
+        let store = MemoryStore::new();
+        for hash in 0..4 {
+            store.insert(key(hash), value(hash as u64), 8);
+        }
+
+        assert_eq!(store.remove_matching(&|key| key.hash % 2 == 0), 2);
+        assert_eq!(cached(&store, 0), None);
+        assert_eq!(cached(&store, 1), Some(1));
+        assert_eq!(store.memory_usage(), 2 * (8 + MemoryStore::ENTRY_OVERHEAD));
+        assert_eq!(store.stats().evictions, 0);
+    }
+
+    #[test]
+    fn test_key_starts_with() {
+        let key = CacheKey {
+            module_path: "app::users",
+            function_name: "get_user",
+            hash: 0,
+        };
+        for prefix in ["", "app", "app::users", "app::users::get_user"] {
+            assert!(key.starts_with(prefix), "{}", prefix);
+        }
+        for prefix in [
+            "ap",
+            "app::use",
+            "app::users:",
+            "app::users::get",
+            "app::users::list",
+            "app::users::get_user_name",
+            "app::usersx",
+            "app::users::get_user::",
+        ] {
+            assert!(!key.starts_with(prefix), "{}", prefix);
+        }
+    }
+
+    #[test]
+    fn test_key_starts_with_whole_segments() {
+        let key = |module_path| CacheKey {
+            module_path,
+            function_name: "get",
+            hash: 0,
+        };
+        assert!(key("app::user").starts_with("app::user"));
+        assert!(key("app::user::admin").starts_with("app::user"));
+        assert!(!key("app::users").starts_with("app::user"));
+        assert!(!key("app::user_admin").starts_with("app::user"));
+    }
+
+    #[test]
+    fn test_memory_store_config() {
+        let store = MemoryStore::with_config(MemoryStoreConfig {
+            max_size: 1,
+            ..MemoryStoreConfig::default()
+        });
+        store.insert(key(1), value(10), 8);
+        store.insert(key(2), value(20), 8);
+
+        assert_eq!(cached(&store, 1), None);
+        assert_eq!(cached(&store, 2), Some(20));
+        assert_eq!(store.stats().evictions, 1);
+    }
+
+    #[test]
+    fn test_memory_store_purge_expired() {
+        let store = MemoryStore::new();
+        store.insert(key(1), value(10), 8);
+        assert_eq!(store.purge_expired(), 0);
+        assert_eq!(store.len(), 1);
+
+        // Entries expire as soon as they are stored
+        let store = MemoryStore::with_config(MemoryStoreConfig {
+            ttl: Some(Duration::ZERO),
+            ..MemoryStoreConfig::default()
+        });
+        store.insert(key(1), value(10), 8);
+        store.insert(key(2), value(20), 8);
+        assert_eq!(store.purge_expired(), 2);
+        assert!(store.is_empty());
+        assert_eq!(store.memory_usage(), 0);
+        assert_eq!(store.stats().expirations, 2);
+    }
+
+    #[test]
+    fn test_memory_store_max_memory() {
+        let overhead = MemoryStore::ENTRY_OVERHEAD;
+        let entry = 100 + overhead;
+        let store = MemoryStore::with_config(MemoryStoreConfig {
+            max_memory: 3 * entry,
+            ..MemoryStoreConfig::default()
+        });
+        for hash in 0..10 {
+            store.insert(key(hash), value(hash as u64), 100);
+            assert!(store.memory_usage() <= 3 * entry);
+        }
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.memory_usage(), 3 * entry);
+        assert_eq!(store.stats().evictions, 7);
+
+        // An entry larger than the limit is not stored at all
+        store.insert(key(10), value(10), 3 * entry);
+        assert_eq!(cached(&store, 10), None);
+        assert_eq!(store.len(), 3);
+
+        // Nor is one whose value alone fits, but not with its overhead
+        store.insert(key(11), value(11), 3 * entry - overhead + 1);
+        assert_eq!(cached(&store, 11), None);
+
+        // A large entry evicts as many as needed
+        store.insert(key(12), value(12), 2 * entry);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.memory_usage(), 2 * entry + overhead);
+    }
+
+    #[cfg(feature = "cached")]
+    #[test]
+    fn test_cached_store() {
+        let store = CachedStore::new(cached::SizedCache::with_size(2));
+        store.insert(key(1), value(10), 8);
+        store.insert(key(2), value(20), 8);
+        assert_eq!(cached(&store, 1), Some(10));
+
+        // The least recently used entry is evicted
+        store.insert(key(3), value(30), 8);
+        assert_eq!(store.len(), 2);
+        assert_eq!(cached(&store, 2), None);
+        assert_eq!(
+            store.with_cache(|cache| cached::Cached::cache_misses(cache)),
+            Some(1)
+        );
+
+        store.remove(&key(1));
+        assert_eq!(cached(&store, 1), None);
+        assert_eq!(store.remove_matching(&|key| key.hash == 3), 1);
+        assert!(store.is_empty());
+    }
+}
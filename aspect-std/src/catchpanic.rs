@@ -0,0 +1,220 @@
+//! Aspect converting panics into errors.
+
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, ProceedingJoinPoint};
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::Once;
+use std::task::{Context, Poll};
+
+thread_local! {
+    /// Number of `CatchPanicAspect` calls running on this thread.
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+    /// Backtrace of the last panic caught on this thread.
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+/// Chains a panic hook recording the backtrace at the panic site for panics
+/// that a `CatchPanicAspect` is about to catch.
+fn install_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if DEPTH.with(Cell::get) > 0 {
+                BACKTRACE.with(|backtrace| *backtrace.borrow_mut() = Some(Backtrace::capture()));
+            }
+            previous(info);
+        }));
+    });
+}
+
+/// Aspect catching panics in the advised function and returning them as
+/// [`AspectError::Panic`].
+///
+/// Apply it innermost, so that outer aspects such as
+/// [`CircuitBreakerAspect`](crate::CircuitBreakerAspect) or
+/// [`MetricsAspect`](crate::MetricsAspect) see a panic as an ordinary failure
+/// instead of being unwound past. The backtrace in the error points at the
+/// panic site when backtraces are enabled (`RUST_BACKTRACE=1`).
+///
+/// The first use installs a process-wide panic hook that records backtraces
+/// and then defers to the previously installed hook, so panic messages are
+/// still printed as usual.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::{CatchPanicAspect, CircuitBreakerAspect};
+/// use aspect_macros::aspect;
+///
+/// #[aspect(BREAKER.clone())]
+/// #[aspect(CatchPanicAspect::new())]
+/// fn parse(input: String) -> Result<u32, String> {
+///     Ok(input.parse::<u32>().unwrap())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CatchPanicAspect;
+
+/// Decrements the depth counter, including when unwinding.
+struct DepthGuard;
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+impl CatchPanicAspect {
+    /// Create a new panic-catching aspect.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// A future catching the panics of each poll of the future it wraps, with
+/// the backtrace of the panic site.
+pub(crate) struct CatchUnwind<F>(pub(crate) F);
+
+impl<F: Future + Unpin> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, Box<dyn Any + Send>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        install_hook();
+        DEPTH.with(|depth| depth.set(depth.get() + 1));
+        let _guard = DepthGuard;
+        match panic::catch_unwind(AssertUnwindSafe(|| Pin::new(&mut self.0).poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+/// The error of a caught panic, with the backtrace recorded by the hook.
+fn panic_error(function_name: &str, payload: Box<dyn Any + Send>) -> AspectError {
+    let backtrace = BACKTRACE
+        .with(|backtrace| backtrace.borrow_mut().take())
+        .unwrap_or_else(Backtrace::disabled);
+    let err = AspectError::panic(&*payload, backtrace);
+    log::error!("{} panicked: {}", function_name, err);
+    err
+}
+
+impl Aspect for CatchPanicAspect {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        install_hook();
+        let function_name = pjp.context().function_name;
+
+        let result = {
+            DEPTH.with(|depth| depth.set(depth.get() + 1));
+            let _guard = DepthGuard;
+            panic::catch_unwind(AssertUnwindSafe(|| pjp.proceed()))
+        };
+
+        result.unwrap_or_else(|payload| Err(panic_error(function_name, payload)))
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            CatchUnwind(proceed)
+                .await
+                .unwrap_or_else(|payload| Err(panic_error(ctx.function_name, payload)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CircuitBreakerAspect;
+    use aspect_core::{JoinPoint, Location};
+    use std::time::Duration;
+
+    fn pjp<'a>(
+        f: impl FnOnce() -> Result<Box<dyn Any>, AspectError> + 'a,
+    ) -> ProceedingJoinPoint<'a> {
+        ProceedingJoinPoint::new(
+            f,
+            JoinPoint::new(
+                "parse",
+                "test",
+                Location {
+                    file: "test.rs",
+                    line: 1,
+                },
+            ),
+        )
+    }
+
+    #[test]
+    fn test_panic_becomes_error() {
+        let err = CatchPanicAspect::new()
+            .around(pjp(|| panic!("bad input: {}", "x")))
+            .unwrap_err();
+
+        match err {
+            AspectError::Panic { payload, .. } => assert_eq!(payload, "bad input: x"),
+            other => panic!("expected a panic error, got {:?}", other),
+        }
+        assert_eq!(DEPTH.with(Cell::get), 0);
+    }
+
+    #[test]
+    fn test_async_panic_becomes_error() {
+        let ctx = JoinPoint::new(
+            "parse",
+            "test",
+            Location {
+                file: "test.rs",
+                line: 1,
+            },
+        );
+        let proceed: BoxFuture<'_, _> = Box::pin(async { panic!("bad input: {}", "y") });
+        let aspect = CatchPanicAspect::new();
+        let mut call = aspect.around_async(&ctx, proceed);
+
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+        match call.as_mut().poll(&mut cx) {
+            Poll::Ready(Err(AspectError::Panic { payload, .. })) => {
+                assert_eq!(payload, "bad input: y")
+            }
+            _ => panic!("expected a panic error"),
+        }
+        assert_eq!(DEPTH.with(Cell::get), 0);
+    }
+
+    #[test]
+    fn test_results_pass_through() {
+        let aspect = CatchPanicAspect::new();
+
+        let ok = aspect.around(pjp(|| Ok(Box::new(7u32)))).unwrap();
+        assert_eq!(*ok.downcast::<u32>().unwrap(), 7);
+
+        let err = aspect
+            .around(pjp(|| Err(AspectError::execution("invalid"))))
+            .unwrap_err();
+        assert!(matches!(err, AspectError::ExecutionError { .. }));
+    }
+
+    #[test]
+    fn test_circuit_breaker_counts_panics() {
+        let breaker = CircuitBreakerAspect::new(1, Duration::from_secs(60));
+
+        let result = breaker.around(pjp(|| {
+            CatchPanicAspect::new().around(pjp(|| panic!("boom")))
+        }));
+        assert!(result.is_err());
+        assert_eq!(breaker.function_stats("parse").failures, 1);
+    }
+}
@@ -1,10 +1,18 @@
 //! Circuit breaker aspect for fault tolerance.
 
-use aspect_core::{Aspect, AspectError, ProceedingJoinPoint};
+use crate::time::{Clock, Duration, Instant, SystemClock};
+use aspect_core::aspect::BoxFuture;
+use aspect_core::config::Param;
+use aspect_core::snapshot::SnapshotValue;
+use aspect_core::{
+    Aspect, AspectArgs, AspectError, AspectSnapshot, FromAspectArgs, JoinPoint, Precedence,
+    ProceedingJoinPoint,
+};
 use parking_lot::Mutex;
 use std::any::Any;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::mem;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
 
 /// Circuit breaker states following the classic pattern.
 #[derive(Debug, Clone, PartialEq)]
@@ -17,6 +25,69 @@ pub enum CircuitState {
     HalfOpen,
 }
 
+/// Callback invoked with the previous and new state when the circuit changes
+/// state.
+type StateListener = Arc<dyn Fn(&CircuitState, &CircuitState) + Send + Sync>;
+
+/// Call outcomes of one function guarded by a circuit breaker.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CircuitBreakerStats {
+    /// Calls that ran and succeeded
+    pub successes: u64,
+    /// Calls that ran and failed
+    pub failures: u64,
+    /// Calls rejected without running because the circuit was open
+    pub rejected: u64,
+}
+
+/// The calls over which a failure rate is computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RollingWindow {
+    /// The last `n` calls.
+    Calls(usize),
+    /// Calls completed within the last `duration`.
+    Time(Duration),
+}
+
+/// Failure-rate trip condition, see [`CircuitBreakerAspect::with_failure_rate`].
+#[derive(Debug, Clone)]
+struct FailureRate {
+    percentage: f64,
+    window: RollingWindow,
+    min_requests: usize,
+    outcomes: VecDeque<(Instant, bool)>,
+}
+
+impl FailureRate {
+    /// Record the outcome of a call completed at `now` and return whether
+    /// the circuit should open.
+    fn record(&mut self, now: Instant, failed: bool) -> bool {
+        self.outcomes.push_back((now, failed));
+        match self.window {
+            RollingWindow::Calls(n) => {
+                while self.outcomes.len() > n {
+                    self.outcomes.pop_front();
+                }
+            }
+            RollingWindow::Time(duration) => {
+                while self
+                    .outcomes
+                    .front()
+                    .is_some_and(|(at, _)| now.duration_since(*at) > duration)
+                {
+                    self.outcomes.pop_front();
+                }
+            }
+        }
+
+        let total = self.outcomes.len();
+        let failures = self.outcomes.iter().filter(|(_, failed)| *failed).count();
+        total >= self.min_requests
+            && total > 0
+            && failures as f64 * 100.0 / total as f64 >= self.percentage
+    }
+}
+
 /// Circuit breaker aspect for preventing cascading failures.
 ///
 /// Implements the circuit breaker pattern to protect services from
@@ -27,6 +98,15 @@ pub enum CircuitState {
 /// - **Open**: Fast-fail mode after threshold is exceeded
 /// - **Half-Open**: Testing recovery with limited requests
 ///
+/// By default the circuit opens after a number of consecutive failures.
+/// [`with_failure_rate`](Self::with_failure_rate) makes it open based on the
+/// failure percentage over a rolling window instead.
+///
+/// Clones of a breaker share its circuit. Functions calling the same
+/// dependency from places that cannot share a clone, such as
+/// `#[aspect(CircuitBreakerAspect, name = "payments-api", ...)]` on each of
+/// them, share one by name instead, see [`named`](Self::named).
+///
 /// # Example
 ///
 /// ```rust,ignore
@@ -45,9 +125,31 @@ pub enum CircuitState {
 /// ```
 #[derive(Clone)]
 pub struct CircuitBreakerAspect {
+    name: Option<Arc<str>>,
     state: Arc<Mutex<CircuitBreakerState>>,
 }
 
+/// The named breakers of the process, see [`CircuitBreakerAspect::named`].
+static NAMED: Mutex<BTreeMap<String, CircuitBreakerAspect>> = Mutex::new(BTreeMap::new());
+
+/// All the named breakers of the process, by name, such as to report the
+/// state of the dependencies of a service.
+///
+/// # Example
+/// ```rust
+/// use aspect_std::circuitbreaker;
+/// use aspect_std::CircuitBreakerAspect;
+/// use std::time::Duration;
+///
+/// CircuitBreakerAspect::named("inventory-api", 5, Duration::from_secs(30));
+/// for (name, breaker) in circuitbreaker::named_breakers() {
+///     println!("{}: {:?}", name, breaker.state());
+/// }
+/// ```
+pub fn named_breakers() -> BTreeMap<String, CircuitBreakerAspect> {
+    NAMED.lock().clone()
+}
+
 struct CircuitBreakerState {
     circuit_state: CircuitState,
     failure_count: usize,
@@ -55,6 +157,21 @@ struct CircuitBreakerState {
     failure_threshold: usize,
     timeout: Duration,
     half_open_max_requests: usize,
+    failure_rate: Option<FailureRate>,
+    listeners: Vec<StateListener>,
+    stats: HashMap<String, CircuitBreakerStats>,
+    clock: Arc<dyn Clock>,
+}
+
+impl CircuitBreakerState {
+    fn open(&mut self) {
+        self.circuit_state = CircuitState::Open {
+            until: self.clock.now() + self.timeout,
+        };
+        if let Some(rate) = &mut self.failure_rate {
+            rate.outcomes.clear();
+        }
+    }
 }
 
 impl CircuitBreakerAspect {
@@ -73,6 +190,7 @@ impl CircuitBreakerAspect {
     /// ```
     pub fn new(failure_threshold: usize, timeout: Duration) -> Self {
         Self {
+            name: None,
             state: Arc::new(Mutex::new(CircuitBreakerState {
                 circuit_state: CircuitState::Closed,
                 failure_count: 0,
@@ -80,16 +198,166 @@ impl CircuitBreakerAspect {
                 failure_threshold,
                 timeout,
                 half_open_max_requests: 1,
+                failure_rate: None,
+                listeners: Vec::new(),
+                stats: HashMap::new(),
+                clock: Arc::new(SystemClock),
             })),
         }
     }
 
+    /// The breaker named `name` in this process, created with `new` if
+    /// there is none yet, so that all the functions calling one dependency
+    /// trip and recover together.
+    ///
+    /// The threshold and timeout of an existing breaker are kept, and the
+    /// builder methods configure the shared breaker: configure it in one
+    /// place. [`named_breakers`] lists the named breakers.
+    ///
+    /// # Example
+    /// ```rust
+    /// use aspect_std::CircuitBreakerAspect;
+    /// use std::time::Duration;
+    ///
+    /// let charge = CircuitBreakerAspect::named("payments-api", 5, Duration::from_secs(30));
+    /// let refund = CircuitBreakerAspect::named("payments-api", 5, Duration::from_secs(30));
+    /// assert_eq!(refund.name(), Some("payments-api"));
+    /// ```
+    pub fn named(name: &str, failure_threshold: usize, timeout: Duration) -> Self {
+        NAMED
+            .lock()
+            .entry(name.to_string())
+            .or_insert_with(|| Self {
+                name: Some(name.into()),
+                ..Self::new(failure_threshold, timeout)
+            })
+            .clone()
+    }
+
+    /// The named breaker `name`, if one was made.
+    pub fn find(name: &str) -> Option<Self> {
+        NAMED.lock().get(name).cloned()
+    }
+
+    /// The name of the breaker, if it is a [`named`](Self::named) one.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
     /// Set the maximum number of requests to allow in half-open state.
     pub fn with_half_open_requests(self, max_requests: usize) -> Self {
         self.state.lock().half_open_max_requests = max_requests;
         self
     }
 
+    /// Time the open circuit and the rolling window with `clock` instead of
+    /// the system clock, e.g. a [`ManualClock`](crate::time::ManualClock)
+    /// in tests.
+    pub fn with_clock(self, clock: impl Clock + 'static) -> Self {
+        self.state.lock().clock = Arc::new(clock);
+        self
+    }
+
+    /// Open the circuit when at least `percentage` percent of the calls in
+    /// `window` failed, instead of after consecutive failures.
+    ///
+    /// The rate is only evaluated once `window` holds at least
+    /// `min_requests` calls, so a handful of early failures cannot open the
+    /// circuit. The window starts empty whenever the circuit closes.
+    ///
+    /// # Example
+    /// ```rust
+    /// use aspect_std::circuitbreaker::RollingWindow;
+    /// use aspect_std::CircuitBreakerAspect;
+    /// use std::time::Duration;
+    ///
+    /// // Open when half of the calls in the last 10 seconds failed,
+    /// // provided there were at least 20 of them
+    /// let breaker = CircuitBreakerAspect::new(5, Duration::from_secs(30))
+    ///     .with_failure_rate(50.0, RollingWindow::Time(Duration::from_secs(10)), 20);
+    /// ```
+    pub fn with_failure_rate(
+        self,
+        percentage: f64,
+        window: RollingWindow,
+        min_requests: usize,
+    ) -> Self {
+        self.state.lock().failure_rate = Some(FailureRate {
+            percentage,
+            window,
+            min_requests,
+            outcomes: VecDeque::new(),
+        });
+        self
+    }
+
+    /// Call `listener` with the previous and new state whenever the circuit
+    /// opens, half-opens or closes.
+    ///
+    /// Listeners run on the thread of the call causing the change, after the
+    /// breaker's lock is released, so they may query the breaker.
+    ///
+    /// # Example
+    /// ```rust
+    /// use aspect_std::{CircuitBreakerAspect, CircuitState};
+    /// use std::time::Duration;
+    ///
+    /// let breaker = CircuitBreakerAspect::new(5, Duration::from_secs(30))
+    ///     .on_state_change(|from, to| {
+    ///         if matches!(to, CircuitState::Open { .. }) {
+    ///             eprintln!("circuit opened (was {:?})", from);
+    ///         }
+    ///     });
+    /// ```
+    pub fn on_state_change(
+        self,
+        listener: impl Fn(&CircuitState, &CircuitState) + Send + Sync + 'static,
+    ) -> Self {
+        self.state.lock().listeners.push(Arc::new(listener));
+        self
+    }
+
+    /// Outcomes of the calls to `function_name` seen by this breaker.
+    pub fn function_stats(&self, function_name: &str) -> CircuitBreakerStats {
+        self.state
+            .lock()
+            .stats
+            .get(function_name)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Outcomes of the calls seen by this breaker, by function name.
+    pub fn stats(&self) -> HashMap<String, CircuitBreakerStats> {
+        self.state.lock().stats.clone()
+    }
+
+    /// Run `f` on the state, then notify listeners if the circuit changed
+    /// state.
+    fn update<R>(&self, f: impl FnOnce(&mut CircuitBreakerState) -> R) -> R {
+        let mut state = self.state.lock();
+        let before = state.circuit_state.clone();
+        let result = f(&mut state);
+
+        if mem::discriminant(&before) != mem::discriminant(&state.circuit_state) {
+            let after = state.circuit_state.clone();
+            let listeners = state.listeners.clone();
+            drop(state);
+            for listener in listeners {
+                listener(&before, &after);
+            }
+        }
+        result
+    }
+
+    fn count(&self, function_name: &str, f: impl FnOnce(&mut CircuitBreakerStats)) {
+        let mut state = self.state.lock();
+        match state.stats.get_mut(function_name) {
+            Some(stats) => f(stats),
+            None => f(state.stats.entry(function_name.to_string()).or_default()),
+        }
+    }
+
     /// Get the current circuit state.
     pub fn state(&self) -> CircuitState {
         self.state.lock().circuit_state.clone()
@@ -97,110 +365,222 @@ impl CircuitBreakerAspect {
 
     /// Manually reset the circuit breaker to closed state.
     pub fn reset(&self) {
-        let mut state = self.state.lock();
-        state.circuit_state = CircuitState::Closed;
-        state.failure_count = 0;
-        state.success_count = 0;
+        self.update(|state| {
+            state.circuit_state = CircuitState::Closed;
+            state.failure_count = 0;
+            state.success_count = 0;
+            if let Some(rate) = &mut state.failure_rate {
+                rate.outcomes.clear();
+            }
+        })
     }
 
     /// Record a successful call.
     fn record_success(&self) {
-        let mut state = self.state.lock();
-
-        match state.circuit_state {
-            CircuitState::HalfOpen => {
-                state.success_count += 1;
-                // Transition back to closed after successful test
-                if state.success_count >= state.half_open_max_requests {
-                    state.circuit_state = CircuitState::Closed;
+        self.update(|state| {
+            match state.circuit_state {
+                CircuitState::HalfOpen => {
+                    state.success_count += 1;
+                    // Transition back to closed after successful test
+                    if state.success_count >= state.half_open_max_requests {
+                        state.circuit_state = CircuitState::Closed;
+                        state.failure_count = 0;
+                        state.success_count = 0;
+                    }
+                }
+                CircuitState::Closed => {
+                    // Reset failure count on success
+                    state.failure_count = 0;
+                    // Reaching the minimum volume can trip on a success too
+                    let now = state.clock.now();
+                    if state
+                        .failure_rate
+                        .as_mut()
+                        .is_some_and(|rate| rate.record(now, false))
+                    {
+                        state.open();
+                    }
+                }
+                CircuitState::Open { .. } => {
+                    // Shouldn't happen, but reset counts
                     state.failure_count = 0;
                     state.success_count = 0;
                 }
             }
-            CircuitState::Closed => {
-                // Reset failure count on success
-                state.failure_count = 0;
-            }
-            CircuitState::Open { .. } => {
-                // Shouldn't happen, but reset counts
-                state.failure_count = 0;
-                state.success_count = 0;
-            }
-        }
+        })
     }
 
     /// Record a failed call.
     fn record_failure(&self) {
-        let mut state = self.state.lock();
-
-        match state.circuit_state {
-            CircuitState::HalfOpen => {
-                // Failure in half-open state immediately reopens circuit
-                state.circuit_state = CircuitState::Open {
-                    until: Instant::now() + state.timeout,
-                };
-                state.success_count = 0;
-            }
-            CircuitState::Closed => {
-                state.failure_count += 1;
-                if state.failure_count >= state.failure_threshold {
-                    // Open the circuit
-                    state.circuit_state = CircuitState::Open {
-                        until: Instant::now() + state.timeout,
+        self.update(|state| {
+            match state.circuit_state {
+                CircuitState::HalfOpen => {
+                    // Failure in half-open state immediately reopens circuit
+                    state.open();
+                    state.success_count = 0;
+                }
+                CircuitState::Closed => {
+                    state.failure_count += 1;
+                    let trip = match &mut state.failure_rate {
+                        Some(rate) => rate.record(state.clock.now(), true),
+                        None => state.failure_count >= state.failure_threshold,
                     };
+                    if trip {
+                        // Open the circuit
+                        state.open();
+                    }
+                }
+                CircuitState::Open { .. } => {
+                    // Already open, nothing to do
                 }
             }
-            CircuitState::Open { .. } => {
-                // Already open, nothing to do
-            }
-        }
+        })
     }
 
     /// Check if a request should be allowed through.
-    fn should_allow_request(&self) -> Result<(), AspectError> {
-        let mut state = self.state.lock();
-
-        match state.circuit_state {
-            CircuitState::Closed => Ok(()),
-            CircuitState::HalfOpen => Ok(()),
-            CircuitState::Open { until } => {
-                if Instant::now() >= until {
-                    // Timeout expired, transition to half-open
-                    state.circuit_state = CircuitState::HalfOpen;
-                    state.success_count = 0;
-                    Ok(())
-                } else {
-                    Err(AspectError::execution(
-                        "Circuit breaker is OPEN - failing fast",
-                    ))
+    fn should_allow_request(&self, function_name: &str) -> Result<(), AspectError> {
+        self.update(|state| {
+            match state.circuit_state {
+                CircuitState::Closed => Ok(()),
+                CircuitState::HalfOpen => Ok(()),
+                CircuitState::Open { until } => {
+                    let now = state.clock.now();
+                    if now >= until {
+                        // Timeout expired, transition to half-open
+                        state.circuit_state = CircuitState::HalfOpen;
+                        state.success_count = 0;
+                        Ok(())
+                    } else {
+                        Err(AspectError::circuit_open(function_name, until - now))
+                    }
                 }
             }
+        })
+    }
+}
+
+impl CircuitBreakerAspect {
+    /// Records the outcome of a call the circuit let through.
+    fn complete(&self, function_name: &str, result: &Result<Box<dyn Any>, AspectError>) {
+        if result.is_ok() {
+            self.count(function_name, |stats| stats.successes += 1);
+            self.record_success();
+        } else {
+            self.count(function_name, |stats| stats.failures += 1);
+            self.record_failure();
         }
     }
 }
 
 impl Aspect for CircuitBreakerAspect {
     fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let function_name = pjp.context().function_name;
+
         // Check if request should be allowed
-        self.should_allow_request()?;
+        if let Err(e) = self.should_allow_request(function_name) {
+            self.count(function_name, |stats| stats.rejected += 1);
+            return Err(e);
+        }
 
         // Attempt the call
-        match pjp.proceed() {
-            Ok(result) => {
-                self.record_success();
-                Ok(result)
-            }
-            Err(e) => {
-                self.record_failure();
-                Err(e)
+        let result = pjp.proceed();
+        self.complete(function_name, &result);
+        result
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            if let Err(e) = self.should_allow_request(ctx.function_name) {
+                self.count(ctx.function_name, |stats| stats.rejected += 1);
+                return Err(e);
             }
-        }
+
+            let result = proceed.await;
+            self.complete(ctx.function_name, &result);
+            result
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::RESILIENCE
+    }
+
+    /// The circuit state and counts, with the call outcomes by function.
+    fn snapshot(&self) -> AspectSnapshot {
+        let state = self.state.lock();
+        let now = state.clock.now();
+        let (circuit, reopens_in) = match &state.circuit_state {
+            CircuitState::Closed => ("closed", None),
+            CircuitState::Open { until } => (
+                "open",
+                Some(until.saturating_duration_since(now).as_secs_f64() * 1e3),
+            ),
+            CircuitState::HalfOpen => ("half_open", None),
+        };
+        let mut functions: Vec<_> = state.stats.iter().collect();
+        functions.sort_by(|a, b| a.0.cmp(b.0));
+        let functions = functions
+            .into_iter()
+            .map(|(name, stats)| {
+                let outcomes = vec![
+                    ("successes".to_string(), stats.successes.into()),
+                    ("failures".to_string(), stats.failures.into()),
+                    ("rejected".to_string(), stats.rejected.into()),
+                ];
+                (name.clone(), SnapshotValue::Map(outcomes))
+            })
+            .collect();
+
+        let snapshot = match &self.name {
+            Some(name) => AspectSnapshot::new("CircuitBreakerAspect").named(&**name),
+            None => AspectSnapshot::new("CircuitBreakerAspect"),
+        };
+        snapshot
+            .with("state", circuit)
+            .with("reopens_in_ms", reopens_in)
+            .with("failure_count", state.failure_count)
+            .with("failure_threshold", state.failure_threshold)
+            .with("timeout_ms", state.timeout.as_secs_f64() * 1e3)
+            .with("half_open_max_requests", state.half_open_max_requests)
+            .with("functions", SnapshotValue::Map(functions))
+    }
+}
+
+/// Builds the aspect of
+/// `#[aspect(CircuitBreakerAspect, failures = 5, timeout = "30s")]`.
+///
+/// Parameters: `failures` and `timeout` (required), `half_open_requests`,
+/// and `name` to share the [`named`](CircuitBreakerAspect::named) breaker.
+impl FromAspectArgs for CircuitBreakerAspect {
+    const PARAMS: &'static [Param] = &[
+        Param::required::<usize>("failures"),
+        Param::required::<Duration>("timeout"),
+        Param::optional::<usize>("half_open_requests"),
+        Param::optional::<String>("name"),
+    ];
+
+    fn from_aspect_args(args: &AspectArgs) -> Result<Self, AspectError> {
+        args.check(Self::PARAMS)?;
+        let (failures, timeout) = (args.require("failures")?, args.require("timeout")?);
+        let aspect = match args.get::<String>("name")? {
+            Some(name) => Self::named(&name, failures, timeout),
+            None => Self::new(failures, timeout),
+        };
+        Ok(match args.get("half_open_requests")? {
+            Some(max_requests) => aspect.with_half_open_requests(max_requests),
+            None => aspect,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::time::ManualClock;
 
     #[test]
     fn test_circuit_breaker_closed_initially() {
@@ -231,34 +611,49 @@ mod tests {
         breaker.record_failure();
 
         // Should reject requests
-        assert!(breaker.should_allow_request().is_err());
+        match breaker.should_allow_request("test") {
+            Err(AspectError::CircuitOpen { target, reopens_in }) => {
+                assert_eq!(target, "test");
+                assert!(reopens_in > Duration::from_secs(59));
+                assert!(reopens_in <= Duration::from_secs(60));
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
     }
 
     #[test]
     fn test_circuit_transitions_to_half_open() {
-        let breaker = CircuitBreakerAspect::new(1, Duration::from_millis(100));
+        let clock = ManualClock::new();
+        let breaker =
+            CircuitBreakerAspect::new(1, Duration::from_millis(100)).with_clock(clock.clone());
 
         breaker.record_failure();
         assert!(matches!(breaker.state(), CircuitState::Open { .. }));
 
+        // Still open just before the timeout
+        clock.advance(Duration::from_millis(99));
+        assert!(breaker.should_allow_request("test").is_err());
+
         // Wait for timeout
-        std::thread::sleep(Duration::from_millis(150));
+        clock.advance(Duration::from_millis(1));
 
         // Should transition to half-open when checked
-        assert!(breaker.should_allow_request().is_ok());
+        assert!(breaker.should_allow_request("test").is_ok());
         assert_eq!(breaker.state(), CircuitState::HalfOpen);
     }
 
     #[test]
     fn test_circuit_closes_after_success() {
-        let breaker = CircuitBreakerAspect::new(1, Duration::from_millis(50));
+        let clock = ManualClock::new();
+        let breaker =
+            CircuitBreakerAspect::new(1, Duration::from_millis(50)).with_clock(clock.clone());
 
         // Open the circuit
         breaker.record_failure();
-        std::thread::sleep(Duration::from_millis(60));
+        clock.advance(Duration::from_millis(50));
 
         // Allow request (transitions to half-open)
-        breaker.should_allow_request().unwrap();
+        breaker.should_allow_request("test").unwrap();
 
         // Success should close the circuit
         breaker.record_success();
@@ -277,4 +672,236 @@ mod tests {
         breaker.reset();
         assert_eq!(breaker.state(), CircuitState::Closed);
     }
+
+    #[test]
+    fn test_failure_rate_over_calls() {
+        let breaker = CircuitBreakerAspect::new(1, Duration::from_secs(60)).with_failure_rate(
+            50.0,
+            RollingWindow::Calls(4),
+            4,
+        );
+
+        // Interleaved failures never trip the consecutive-failure count
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        breaker.record_success();
+        assert!(matches!(breaker.state(), CircuitState::Open { .. }));
+    }
+
+    #[test]
+    fn test_failure_rate_window_slides() {
+        let breaker = CircuitBreakerAspect::new(1, Duration::from_secs(60)).with_failure_rate(
+            75.0,
+            RollingWindow::Calls(4),
+            2,
+        );
+
+        breaker.record_success();
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed); // 2 of 3
+
+        // The early success drops out of the window: 3 of 4 failed
+        breaker.record_failure();
+        assert!(matches!(breaker.state(), CircuitState::Open { .. }));
+    }
+
+    #[test]
+    fn test_failure_rate_minimum_requests() {
+        let clock = ManualClock::new();
+        let breaker = CircuitBreakerAspect::new(1, Duration::from_secs(60))
+            .with_clock(clock.clone())
+            .with_failure_rate(50.0, RollingWindow::Time(Duration::from_millis(50)), 3);
+
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // Earlier failures expire from the window
+        clock.advance(Duration::from_millis(51));
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(matches!(breaker.state(), CircuitState::Open { .. }));
+    }
+
+    fn name(state: &CircuitState) -> &'static str {
+        match state {
+            CircuitState::Closed => "closed",
+            CircuitState::Open { .. } => "open",
+            CircuitState::HalfOpen => "half-open",
+        }
+    }
+
+    #[test]
+    fn test_state_change_listener() {
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let clock = ManualClock::new();
+        let breaker = CircuitBreakerAspect::new(1, Duration::from_millis(20))
+            .with_clock(clock.clone())
+            .on_state_change({
+                let changes = changes.clone();
+                move |from, to| changes.lock().push((name(from), name(to)))
+            });
+
+        breaker.record_failure();
+        breaker.record_failure(); // already open, no change
+        clock.advance(Duration::from_millis(20));
+        breaker.should_allow_request("test").unwrap();
+        breaker.record_success();
+        breaker.reset(); // already closed, no change
+
+        assert_eq!(
+            *changes.lock(),
+            vec![
+                ("closed", "open"),
+                ("open", "half-open"),
+                ("half-open", "closed"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_function_stats() {
+        let breaker = CircuitBreakerAspect::new(2, Duration::from_secs(60));
+        let call = |name: &'static str, ok: bool| {
+            let ctx = aspect_core::JoinPoint::new(
+                name,
+                "test",
+                aspect_core::Location {
+                    file: "test.rs",
+                    line: 1,
+                },
+            );
+            let pjp = ProceedingJoinPoint::new(
+                move || {
+                    if ok {
+                        Ok(Box::new(()) as Box<dyn Any>)
+                    } else {
+                        Err(AspectError::execution("failed"))
+                    }
+                },
+                ctx,
+            );
+            let _ = breaker.around(pjp);
+        };
+
+        call("a", true);
+        call("a", false);
+        call("b", false); // opens the circuit
+        call("b", true);
+
+        assert_eq!(
+            breaker.function_stats("a"),
+            CircuitBreakerStats {
+                successes: 1,
+                failures: 1,
+                rejected: 0
+            }
+        );
+        assert_eq!(breaker.function_stats("b").rejected, 1);
+        assert_eq!(breaker.stats().len(), 2);
+        assert_eq!(breaker.function_stats("c"), CircuitBreakerStats::default());
+    }
+
+    #[test]
+    fn test_from_aspect_args() {
+        let args = AspectArgs::new("CircuitBreakerAspect")
+            .with("failures", 2)
+            .with("timeout", "30s");
+        let breaker = CircuitBreakerAspect::from_aspect_args(&args).unwrap();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.record_failure();
+        assert!(matches!(breaker.state(), CircuitState::Open { .. }));
+
+        let args = AspectArgs::new("CircuitBreakerAspect").with("failures", 2);
+        assert!(CircuitBreakerAspect::from_aspect_args(&args)
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("requires parameter `timeout`"));
+    }
+
+    #[aspect_macros::aspect(
+        CircuitBreakerAspect,
+        name = "test-payments",
+        failures = 2,
+        timeout = "60s"
+    )]
+    fn charge(fail: bool) -> Result<(), String> {
+        if fail {
+            return Err("payments down".to_string());
+        }
+        Ok(())
+    }
+
+    #[aspect_macros::aspect(
+        CircuitBreakerAspect,
+        name = "test-payments",
+        failures = 2,
+        timeout = "60s"
+    )]
+    fn refund(fail: bool) -> Result<(), String> {
+        if fail {
+            return Err("payments down".to_string());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_named_breaker_shared_by_functions() {
+        // Failures of either function trip the breaker of both
+        assert!(charge(true).is_err());
+        assert!(refund(true).is_err());
+        assert!(charge(false).unwrap_err().contains("CircuitOpen"));
+        assert!(refund(false).unwrap_err().contains("CircuitOpen"));
+
+        let breaker = CircuitBreakerAspect::find("test-payments").unwrap();
+        assert!(matches!(breaker.state(), CircuitState::Open { .. }));
+        assert_eq!(breaker.function_stats("refund").rejected, 1);
+        assert!(named_breakers().contains_key("test-payments"));
+        assert!(breaker
+            .snapshot()
+            .to_string()
+            .starts_with(r#"{"aspect":"CircuitBreakerAspect","name":"test-payments","#));
+
+        // Recovering, they recover together
+        breaker.reset();
+        assert_eq!(charge(false), Ok(()));
+        assert_eq!(refund(false), Ok(()));
+    }
+
+    #[test]
+    fn test_named_keeps_first_configuration() {
+        let first = CircuitBreakerAspect::named("test-named", 1, Duration::from_secs(60));
+        let second = CircuitBreakerAspect::named("test-named", 10, Duration::from_secs(1));
+        second.record_failure();
+        assert!(matches!(first.state(), CircuitState::Open { .. }));
+
+        assert!(CircuitBreakerAspect::find("test-unknown").is_none());
+        assert_eq!(CircuitBreakerAspect::new(1, Duration::ZERO).name(), None);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let breaker = CircuitBreakerAspect::new(1, Duration::from_secs(60));
+        assert_eq!(breaker.snapshot().get("state"), Some(&"closed".into()));
+
+        breaker.record_failure();
+        breaker.count("fetch", |stats| stats.failures += 1);
+        let snapshot = breaker.snapshot();
+        assert_eq!(snapshot.get("state"), Some(&"open".into()));
+        assert!(matches!(
+            snapshot.get("reopens_in_ms"),
+            Some(SnapshotValue::Float(ms)) if *ms > 59_000.0
+        ));
+        assert!(snapshot
+            .to_string()
+            .contains(r#""functions":{"fetch":{"successes":0,"failures":1,"rejected":0}}"#));
+    }
 }
@@ -0,0 +1,453 @@
+//! Concurrency limiting (bulkhead) aspect.
+
+use crate::time::{self, Duration};
+use aspect_core::aspect::BoxFuture;
+use aspect_core::config::Param;
+use aspect_core::{
+    Aspect, AspectArgs, AspectError, FromAspectArgs, JoinPoint, Precedence, ProceedingJoinPoint,
+};
+use parking_lot::{Condvar, Mutex};
+use std::any::Any;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+/// Bulkhead aspect bounding the number of simultaneous executions.
+///
+/// Unlike [`RateLimitAspect`](crate::RateLimitAspect), which bounds how often
+/// functions are called, this bounds how many calls run at the same time,
+/// protecting downstream resources such as connection pools from overload.
+///
+/// By default, calls beyond the limit are rejected immediately with
+/// [`AspectError::Overloaded`], telling callers when to retry if
+/// [`with_retry_after`](Self::with_retry_after) is set. With
+/// [`with_queue`](Self::with_queue), a bounded number of callers block until
+/// a slot frees up, optionally giving up after
+/// [`with_queue_timeout`](Self::with_queue_timeout). Waiting callers are not
+/// guaranteed to be admitted in arrival order. Callers giving up fail with
+/// [`AspectError::Timeout`].
+///
+/// Asynchronous callers, such as the tower and HTTP adapters, wait for a
+/// slot without blocking their thread. They give up after the queue timeout
+/// with the `tokio` feature, which times the wait; without it, they wait
+/// until admitted.
+///
+/// Clones share the same slots, so one aspect (or its clones) can guard a
+/// group of functions together.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::ConcurrencyLimitAspect;
+/// use aspect_macros::aspect;
+/// use std::sync::LazyLock;
+/// use std::time::Duration;
+///
+/// static DB_BULKHEAD: LazyLock<ConcurrencyLimitAspect> = LazyLock::new(|| {
+///     ConcurrencyLimitAspect::new(10)
+///         .with_queue(50)
+///         .with_queue_timeout(Duration::from_millis(200))
+/// });
+///
+/// #[aspect(DB_BULKHEAD.clone())]
+/// fn query(sql: String) -> Result<Vec<String>, String> {
+///     Ok(vec![])
+/// }
+/// ```
+#[derive(Clone)]
+pub struct ConcurrencyLimitAspect {
+    max_in_flight: usize,
+    max_queued: usize,
+    queue_timeout: Option<Duration>,
+    retry_after: Option<Duration>,
+    slots: Arc<Slots>,
+}
+
+#[derive(Default)]
+struct Slots {
+    state: Mutex<SlotState>,
+    released: Condvar,
+}
+
+#[derive(Default)]
+struct SlotState {
+    in_flight: usize,
+    queued: usize,
+    /// Asynchronous callers waiting for a slot, woken on every release
+    wakers: Vec<Waker>,
+}
+
+/// A held execution slot, released on drop (including on panic).
+struct Permit<'a> {
+    slots: &'a Slots,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let wakers = {
+            let mut state = self.slots.state.lock();
+            state.in_flight -= 1;
+            std::mem::take(&mut state.wakers)
+        };
+        self.slots.released.notify_one();
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+/// A queued asynchronous caller, ready with a slot once one is free.
+struct Queued<'a> {
+    slots: &'a Slots,
+    max_in_flight: usize,
+    /// Whether the caller still counts as queued
+    waiting: bool,
+}
+
+impl<'a> Future for Queued<'a> {
+    type Output = Permit<'a>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let slots = self.slots;
+        let mut state = slots.state.lock();
+        if state.in_flight >= self.max_in_flight {
+            state.wakers.push(cx.waker().clone());
+            return Poll::Pending;
+        }
+        state.in_flight += 1;
+        state.queued -= 1;
+        self.waiting = false;
+        Poll::Ready(Permit { slots })
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        // Callers giving up leave the queue
+        if self.waiting {
+            self.slots.state.lock().queued -= 1;
+        }
+    }
+}
+
+impl ConcurrencyLimitAspect {
+    /// Create a limiter allowing at most `max_in_flight` simultaneous calls.
+    ///
+    /// # Example
+    /// ```rust
+    /// use aspect_std::ConcurrencyLimitAspect;
+    ///
+    /// let bulkhead = ConcurrencyLimitAspect::new(4);
+    /// assert_eq!(bulkhead.in_flight(), 0);
+    /// ```
+    pub fn new(max_in_flight: usize) -> Self {
+        Self {
+            max_in_flight,
+            max_queued: 0,
+            queue_timeout: None,
+            retry_after: None,
+            slots: Arc::default(),
+        }
+    }
+
+    /// Let up to `max_queued` callers wait for a slot instead of being
+    /// rejected.
+    pub fn with_queue(mut self, max_queued: usize) -> Self {
+        self.max_queued = max_queued;
+        self
+    }
+
+    /// Reject queued callers that have waited longer than `timeout`.
+    ///
+    /// Without a timeout, queued callers wait indefinitely. Has no effect
+    /// unless a queue is configured with [`with_queue`](Self::with_queue).
+    pub fn with_queue_timeout(mut self, timeout: Duration) -> Self {
+        self.queue_timeout = Some(timeout);
+        self
+    }
+
+    /// Tell rejected callers to retry after `retry_after`.
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
+    /// Number of calls currently executing.
+    pub fn in_flight(&self) -> usize {
+        self.slots.state.lock().in_flight
+    }
+
+    /// Number of callers currently waiting for a slot.
+    pub fn queued(&self) -> usize {
+        self.slots.state.lock().queued
+    }
+
+    fn acquire(&self, function_name: &str) -> Result<Permit<'_>, AspectError> {
+        let mut state = self.slots.state.lock();
+
+        if state.in_flight >= self.max_in_flight {
+            if state.queued >= self.max_queued {
+                return Err(AspectError::overloaded(function_name, self.retry_after));
+            }
+
+            state.queued += 1;
+            let queued_at = time::now();
+            let deadline = self.queue_timeout.map(|timeout| queued_at + timeout);
+            while state.in_flight >= self.max_in_flight {
+                match deadline {
+                    Some(deadline) => {
+                        let remaining = deadline.saturating_duration_since(time::now());
+                        if self
+                            .slots
+                            .released
+                            .wait_for(&mut state, remaining)
+                            .timed_out()
+                            && state.in_flight >= self.max_in_flight
+                        {
+                            state.queued -= 1;
+                            return Err(AspectError::timeout(
+                                function_name,
+                                time::elapsed(queued_at),
+                            ));
+                        }
+                    }
+                    None => self.slots.released.wait(&mut state),
+                }
+            }
+            state.queued -= 1;
+        }
+
+        state.in_flight += 1;
+        Ok(Permit { slots: &self.slots })
+    }
+
+    /// Like [`acquire`](Self::acquire), waiting for a slot without blocking
+    /// the thread.
+    async fn acquire_async(&self, function_name: &str) -> Result<Permit<'_>, AspectError> {
+        {
+            let mut state = self.slots.state.lock();
+            if state.in_flight < self.max_in_flight {
+                state.in_flight += 1;
+                return Ok(Permit { slots: &self.slots });
+            }
+            if state.queued >= self.max_queued {
+                return Err(AspectError::overloaded(function_name, self.retry_after));
+            }
+            state.queued += 1;
+        }
+
+        let queued = Queued {
+            slots: &self.slots,
+            max_in_flight: self.max_in_flight,
+            waiting: true,
+        };
+        #[cfg(feature = "tokio")]
+        if let Some(timeout) = self.queue_timeout {
+            let queued_at = time::now();
+            return tokio::time::timeout(timeout, queued)
+                .await
+                .map_err(|_| AspectError::timeout(function_name, time::elapsed(queued_at)));
+        }
+        Ok(queued.await)
+    }
+}
+
+impl Aspect for ConcurrencyLimitAspect {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let _permit = self.acquire(pjp.context().function_name)?;
+        pjp.proceed()
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            let _permit = self.acquire_async(ctx.function_name).await?;
+            proceed.await
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::RESILIENCE
+    }
+}
+
+/// Builds the aspect of `#[aspect(ConcurrencyLimitAspect, max = 4)]`.
+///
+/// Parameters: `max` (required), `queue`, `queue_timeout` and `retry_after`.
+impl FromAspectArgs for ConcurrencyLimitAspect {
+    const PARAMS: &'static [Param] = &[
+        Param::required::<usize>("max"),
+        Param::optional::<usize>("queue"),
+        Param::optional::<Duration>("queue_timeout"),
+        Param::optional::<Duration>("retry_after"),
+    ];
+
+    fn from_aspect_args(args: &AspectArgs) -> Result<Self, AspectError> {
+        args.check(Self::PARAMS)?;
+        let mut aspect = Self::new(args.require("max")?);
+        if let Some(max_queued) = args.get("queue")? {
+            aspect = aspect.with_queue(max_queued);
+        }
+        if let Some(timeout) = args.get("queue_timeout")? {
+            aspect = aspect.with_queue_timeout(timeout);
+        }
+        if let Some(retry_after) = args.get("retry_after")? {
+            aspect = aspect.with_retry_after(retry_after);
+        }
+        Ok(aspect)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_rejects_beyond_limit() {
+        let limiter = ConcurrencyLimitAspect::new(2);
+
+        let first = limiter.acquire("f").unwrap();
+        let _second = limiter.acquire("f").unwrap();
+        assert_eq!(limiter.in_flight(), 2);
+        assert!(matches!(
+            limiter.acquire("f").err().unwrap(),
+            AspectError::Overloaded {
+                retry_after: None,
+                ..
+            }
+        ));
+
+        drop(first);
+        assert_eq!(limiter.in_flight(), 1);
+        assert!(limiter.acquire("f").is_ok());
+    }
+
+    #[test]
+    fn test_queued_caller_gets_released_slot() {
+        let limiter = ConcurrencyLimitAspect::new(1).with_queue(1);
+        let permit = limiter.acquire("f").unwrap();
+
+        let waiter = {
+            let limiter = limiter.clone();
+            thread::spawn(move || limiter.acquire("f").is_ok())
+        };
+        while limiter.queued() == 0 {
+            thread::yield_now();
+        }
+        // The queue is full
+        assert!(limiter.acquire("f").is_err());
+
+        drop(permit);
+        assert!(waiter.join().unwrap());
+        assert_eq!(limiter.queued(), 0);
+        assert_eq!(limiter.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_queue_timeout() {
+        let limiter = ConcurrencyLimitAspect::new(1)
+            .with_queue(4)
+            .with_queue_timeout(Duration::from_millis(50));
+        let _permit = limiter.acquire("f").unwrap();
+
+        let start = time::now();
+        let err = limiter.acquire("f").err().unwrap();
+        assert!(time::elapsed(start) >= Duration::from_millis(50));
+        match err {
+            AspectError::Timeout { target, elapsed } => {
+                assert_eq!(target, "f");
+                assert!(elapsed >= Duration::from_millis(50));
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+        assert_eq!(limiter.queued(), 0);
+    }
+
+    fn ctx() -> JoinPoint {
+        JoinPoint::new(
+            "query",
+            "test",
+            aspect_core::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        )
+    }
+
+    #[test]
+    fn test_around_releases_slot_on_error() {
+        let limiter = ConcurrencyLimitAspect::new(1);
+
+        let pjp = ProceedingJoinPoint::new(|| Err(AspectError::execution("failed")), ctx());
+        assert!(limiter.around(pjp).is_err());
+        assert_eq!(limiter.in_flight(), 0);
+    }
+
+    fn call_async<'a>(
+        limiter: &'a ConcurrencyLimitAspect,
+        ctx: &'a JoinPoint,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        limiter.around_async(ctx, Box::pin(async { Ok(Box::new(1) as Box<dyn Any>) }))
+    }
+
+    #[test]
+    fn test_around_async_limits_calls() {
+        let ctx = ctx();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let closed = ConcurrencyLimitAspect::new(0).with_retry_after(Duration::from_secs(1));
+        match call_async(&closed, &ctx).as_mut().poll(&mut cx) {
+            Poll::Ready(Err(err)) => assert_eq!(err.retry_after(), Some(Duration::from_secs(1))),
+            _ => panic!("expected a rejection"),
+        }
+
+        let limiter = ConcurrencyLimitAspect::new(1).with_queue(1);
+        let permit = limiter.acquire("query").unwrap();
+        let mut queued = call_async(&limiter, &ctx);
+        assert!(queued.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(limiter.queued(), 1);
+        // The queue is full
+        let rejected = call_async(&limiter, &ctx).as_mut().poll(&mut cx);
+        assert!(matches!(rejected, Poll::Ready(Err(_))));
+
+        drop(permit);
+        assert!(matches!(queued.as_mut().poll(&mut cx), Poll::Ready(Ok(_))));
+        assert_eq!(limiter.queued(), 0);
+        assert_eq!(limiter.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_cancelled_async_caller_leaves_queue() {
+        let ctx = ctx();
+        let mut cx = Context::from_waker(Waker::noop());
+        let limiter = ConcurrencyLimitAspect::new(1).with_queue(1);
+        let _permit = limiter.acquire("query").unwrap();
+
+        let mut queued = call_async(&limiter, &ctx);
+        assert!(queued.as_mut().poll(&mut cx).is_pending());
+        drop(queued);
+        assert_eq!(limiter.queued(), 0);
+        assert_eq!(limiter.in_flight(), 1);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_async_queue_timeout() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let limiter = ConcurrencyLimitAspect::new(1)
+            .with_queue(1)
+            .with_queue_timeout(Duration::from_millis(20));
+        let _permit = limiter.acquire("query").unwrap();
+
+        let ctx = ctx();
+        let err = runtime.block_on(call_async(&limiter, &ctx)).err().unwrap();
+        assert!(matches!(err, AspectError::Timeout { .. }));
+        assert_eq!(limiter.queued(), 0);
+    }
+}
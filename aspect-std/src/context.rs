@@ -0,0 +1,288 @@
+//! Task-local context of requests, for aspects in async services.
+//!
+//! Thread-locals, such as the one of [`deadline`](crate::deadline), do not
+//! follow a request through an async service, whose tasks move between
+//! threads. An [`AspectContext`] set with [`scope`] does: the principal
+//! making the request, its correlation id and its deadline are readable and
+//! writable by aspects and the code they advise anywhere in the task, and
+//! [`spawn_with_context`] passes them on to the tasks it spawns. So do its
+//! position in a distributed trace and its baggage, which
+//! [`propagation`](crate::propagation) exchanges with other services.
+//!
+//! The readers make providers for other aspects, e.g.
+//! `AuditAspect::new(sink).with_principal(aspect_std::context::principal)`.
+//! Available with the `tokio` feature.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use aspect_std::context::{self, AspectContext};
+//!
+//! async fn handle(request: Request) -> Response {
+//!     let cx = AspectContext::new()
+//!         .with_principal(request.user())
+//!         .with_correlation_id(request.header("x-request-id"));
+//!     context::scope(cx, async {
+//!         // Audited as the user, even in the spawned task
+//!         context::spawn_with_context(audited_cleanup()).await.unwrap();
+//!         audited_work().await
+//!     })
+//!     .await
+//! }
+//! ```
+
+use crate::propagation::{Baggage, TraceParent};
+use crate::time::{self, Duration, Instant};
+use std::cell::RefCell;
+use std::future::Future;
+use tokio::task::JoinHandle;
+
+tokio::task_local! {
+    static CONTEXT: RefCell<AspectContext>;
+}
+
+/// What aspects know of the request a task works for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AspectContext {
+    /// Who makes the request, e.g. a user ID, `None` when anonymous
+    pub principal: Option<String>,
+
+    /// Identifies the request across logs and services
+    pub correlation_id: Option<String>,
+
+    /// When the request must be done by
+    pub deadline: Option<Instant>,
+
+    /// The trace of the request and the span it is made in, from the
+    /// `traceparent` header
+    pub trace_parent: Option<TraceParent>,
+
+    /// Properties of the request passed on to the services it calls, from
+    /// the `baggage` header
+    pub baggage: Baggage,
+
+    /// The [contextual fields](crate::mdc) of the log and audit records of
+    /// the request
+    pub fields: Vec<(String, String)>,
+}
+
+impl AspectContext {
+    /// An empty context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the principal making the request.
+    pub fn with_principal(mut self, principal: impl Into<String>) -> Self {
+        self.principal = Some(principal.into());
+        self
+    }
+
+    /// Set the correlation id of the request.
+    pub fn with_correlation_id(mut self, id: impl Into<String>) -> Self {
+        self.correlation_id = Some(id.into());
+        self
+    }
+
+    /// Set the deadline of the request, `budget` from now.
+    pub fn with_budget(mut self, budget: Duration) -> Self {
+        self.deadline = Some(time::now() + budget);
+        self
+    }
+
+    /// Set the position of the request in a distributed trace.
+    pub fn with_trace_parent(mut self, trace_parent: TraceParent) -> Self {
+        self.trace_parent = Some(trace_parent);
+        self
+    }
+
+    /// Set the baggage of the request.
+    pub fn with_baggage(mut self, baggage: Baggage) -> Self {
+        self.baggage = baggage;
+        self
+    }
+
+    /// Set the contextual field `key` of the request to `value`.
+    pub fn with_field(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let (key, value) = (key.into(), value.into());
+        match self.fields.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.fields.push((key, value)),
+        }
+        self
+    }
+}
+
+/// Runs `future` with `context` as its task-local context.
+///
+/// Scopes nest: the context of the enclosing scope is restored when
+/// `future` completes.
+pub async fn scope<F: Future>(context: AspectContext, future: F) -> F::Output {
+    CONTEXT.scope(RefCell::new(context), future).await
+}
+
+/// Runs `f` with `context` as the task-local context, synchronously.
+pub fn sync_scope<R>(context: AspectContext, f: impl FnOnce() -> R) -> R {
+    CONTEXT.sync_scope(RefCell::new(context), f)
+}
+
+/// Returns a copy of the context of the current task, or `None` outside a
+/// [`scope`].
+pub fn current() -> Option<AspectContext> {
+    CONTEXT.try_with(|context| context.borrow().clone()).ok()
+}
+
+/// Changes the context of the current task with `f`.
+///
+/// Returns `false`, without calling `f`, outside a [`scope`].
+pub fn update(f: impl FnOnce(&mut AspectContext)) -> bool {
+    CONTEXT
+        .try_with(|context| f(&mut context.borrow_mut()))
+        .is_ok()
+}
+
+/// Returns the principal of the current task, if any.
+pub fn principal() -> Option<String> {
+    CONTEXT
+        .try_with(|context| context.borrow().principal.clone())
+        .ok()
+        .flatten()
+}
+
+/// Returns the correlation id of the current task, if any.
+pub fn correlation_id() -> Option<String> {
+    CONTEXT
+        .try_with(|context| context.borrow().correlation_id.clone())
+        .ok()
+        .flatten()
+}
+
+/// Returns the deadline of the current task, if any.
+pub fn deadline() -> Option<Instant> {
+    CONTEXT
+        .try_with(|context| context.borrow().deadline)
+        .ok()
+        .flatten()
+}
+
+/// Returns the time left before the deadline of the current task, if any.
+pub fn remaining() -> Option<Duration> {
+    deadline().map(|deadline| deadline.saturating_duration_since(time::now()))
+}
+
+/// Spawns `future` on the Tokio runtime with a copy of the context of the
+/// current task, which `tokio::spawn` alone loses.
+///
+/// Changes made by either task afterwards are not seen by the other.
+/// Outside a [`scope`], the task is spawned without context.
+///
+/// # Panics
+///
+/// Panics when called outside a Tokio runtime, as `tokio::spawn` does.
+#[track_caller]
+pub fn spawn_with_context<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    match current() {
+        Some(context) => tokio::spawn(scope(context, future)),
+        None => tokio::spawn(future),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_scope() {
+        assert_eq!(current(), None);
+        assert!(!update(|context| context.principal = Some("alice".into())));
+
+        let context = AspectContext::new()
+            .with_principal("alice")
+            .with_correlation_id("req-1");
+        block_on(scope(context, async {
+            assert_eq!(principal().as_deref(), Some("alice"));
+            assert_eq!(correlation_id().as_deref(), Some("req-1"));
+            assert_eq!(deadline(), None);
+
+            // Nested scopes see their own context only
+            scope(AspectContext::new().with_principal("bob"), async {
+                assert_eq!(principal().as_deref(), Some("bob"));
+                assert_eq!(correlation_id(), None);
+            })
+            .await;
+
+            tokio::task::yield_now().await;
+            assert!(update(|context| context.principal = None));
+            assert_eq!(principal(), None);
+            assert_eq!(correlation_id().as_deref(), Some("req-1"));
+        }));
+        assert_eq!(current(), None);
+    }
+
+    #[test]
+    fn test_spawn_with_context() {
+        let context = AspectContext::new()
+            .with_principal("alice")
+            .with_budget(Duration::from_secs(60));
+        block_on(scope(context, async {
+            let handle = spawn_with_context(async {
+                update(|context| context.principal = Some("bob".into()));
+                (principal(), remaining())
+            });
+            let (spawned, remaining) = handle.await.unwrap();
+            assert_eq!(spawned.as_deref(), Some("bob"));
+            assert!(remaining.unwrap() > Duration::from_secs(30));
+            // The copy was changed, not the context of this task
+            assert_eq!(principal().as_deref(), Some("alice"));
+
+            // Plain `tokio::spawn` loses the context
+            assert_eq!(tokio::spawn(async { current() }).await.unwrap(), None);
+        }));
+    }
+
+    #[test]
+    fn test_audit_principal() {
+        use crate::audit::{AuditRecord, AuditSink};
+        use crate::AuditAspect;
+        use aspect_core::{Aspect, JoinPoint, Location, ProceedingJoinPoint};
+        use std::any::Any;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone, Default)]
+        struct Records(Arc<Mutex<Vec<AuditRecord>>>);
+
+        impl AuditSink for Records {
+            fn write(&self, record: &AuditRecord) {
+                self.0.lock().unwrap().push(record.clone());
+            }
+        }
+
+        let records = Records::default();
+        let audit = AuditAspect::new(records.clone()).with_principal(principal);
+        let ctx = JoinPoint::new(
+            "delete_user",
+            "app::admin",
+            Location {
+                file: "src/admin.rs",
+                line: 3,
+            },
+        );
+        let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn Any>), ctx);
+        sync_scope(AspectContext::new().with_principal("alice"), || {
+            audit.around(pjp).unwrap();
+        });
+        let records = records.0.lock().unwrap();
+        assert_eq!(records[0].principal.as_deref(), Some("alice"));
+    }
+}
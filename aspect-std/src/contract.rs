@@ -0,0 +1,326 @@
+//! Design-by-contract aspect combining preconditions, postconditions and
+//! invariants.
+
+use crate::validation::{PostConditionRule, ValidationRule};
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, ProceedingJoinPoint};
+use std::any::Any;
+use std::sync::Arc;
+
+/// Check that must hold before and after every advised call.
+type InvariantFn = Arc<dyn Fn() -> bool + Send + Sync>;
+
+/// Named invariant.
+#[derive(Clone)]
+struct Invariant {
+    description: String,
+    check: InvariantFn,
+}
+
+/// When contracts are checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Enforcement {
+    /// Check contracts in every build.
+    #[default]
+    Always,
+    /// Check contracts only when debug assertions are enabled, like
+    /// `debug_assert!`. Release builds skip the checks entirely.
+    DebugOnly,
+}
+
+impl Enforcement {
+    /// Returns `true` if contracts are checked in this build.
+    pub fn is_active(self) -> bool {
+        match self {
+            Enforcement::Always => true,
+            Enforcement::DebugOnly => cfg!(debug_assertions),
+        }
+    }
+}
+
+/// Aspect enforcing a design-by-contract specification.
+///
+/// Each call is checked in the following order:
+///
+/// 1. Invariants, which describe the state of a type and must hold whenever
+///    none of its methods is running
+/// 2. Preconditions, which the caller must satisfy ([`ValidationRule`]s)
+/// 3. The call itself
+/// 4. Postconditions, which the returned value must satisfy
+///    ([`PostConditionRule`]s); failed calls are not checked
+/// 5. Invariants again, including after failed calls
+///
+/// A violation fails the call with an [`AspectError::ExecutionError`] naming
+/// the broken clause.
+///
+/// Invariants read the state they check themselves, so a type's invariant is
+/// enforced by applying one `ContractAspect` (typically in a static) to each
+/// function that operates on that state.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::contract::ContractAspect;
+/// use aspect_std::validation::{rule_for_arg, rule_for_result};
+/// use aspect_macros::aspect;
+/// use std::sync::{LazyLock, Mutex};
+///
+/// static BALANCE: Mutex<i64> = Mutex::new(0);
+///
+/// static ACCOUNT: LazyLock<ContractAspect> = LazyLock::new(|| {
+///     ContractAspect::new()
+///         .invariant("balance is never negative", || *BALANCE.lock().unwrap() >= 0)
+///         .debug_only()
+/// });
+///
+/// #[aspect(ACCOUNT.clone()
+///     .require(rule_for_arg::<i64>("amount", |amount| {
+///         if *amount > 0 { Ok(()) } else { Err("amount must be positive".into()) }
+///     })))]
+/// fn withdraw(amount: i64) -> Result<i64, String> {
+///     let mut balance = BALANCE.lock().unwrap();
+///     *balance -= amount;
+///     Ok(*balance)
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct ContractAspect {
+    preconditions: Vec<Arc<dyn ValidationRule>>,
+    postconditions: Vec<Arc<dyn PostConditionRule>>,
+    invariants: Vec<Invariant>,
+    enforcement: Enforcement,
+}
+
+impl ContractAspect {
+    /// Create a contract without any clauses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a precondition, checked before the call.
+    pub fn require(mut self, rule: impl ValidationRule + 'static) -> Self {
+        self.preconditions.push(Arc::new(rule));
+        self
+    }
+
+    /// Add a postcondition, checked against the value a successful call
+    /// returned.
+    pub fn ensure(mut self, rule: impl PostConditionRule + 'static) -> Self {
+        self.postconditions.push(Arc::new(rule));
+        self
+    }
+
+    /// Add an invariant, checked before and after the call.
+    pub fn invariant(
+        mut self,
+        description: &str,
+        check: impl Fn() -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.invariants.push(Invariant {
+            description: description.to_string(),
+            check: Arc::new(check),
+        });
+        self
+    }
+
+    /// Set when the contract is checked ([`Enforcement::Always`] by
+    /// default).
+    pub fn with_enforcement(mut self, enforcement: Enforcement) -> Self {
+        self.enforcement = enforcement;
+        self
+    }
+
+    /// Only check the contract in builds with debug assertions.
+    pub fn debug_only(self) -> Self {
+        self.with_enforcement(Enforcement::DebugOnly)
+    }
+
+    fn check_invariants(&self, function_name: &str, when: &str) -> Result<(), AspectError> {
+        match self
+            .invariants
+            .iter()
+            .find(|invariant| !(invariant.check)())
+        {
+            Some(invariant) => Err(AspectError::execution(format!(
+                "Invariant violated {} {}: {}",
+                when, function_name, invariant.description
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the invariants and preconditions of a call.
+    fn check_before(&self, ctx: &JoinPoint) -> Result<(), AspectError> {
+        self.check_invariants(ctx.function_name, "before")?;
+        for rule in &self.preconditions {
+            rule.validate(ctx).map_err(|msg| {
+                AspectError::execution(format!(
+                    "Precondition failed for {}: {}",
+                    ctx.function_name, msg
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Checks the postconditions of what a call returned, then the
+    /// invariants.
+    fn check_after(
+        &self,
+        ctx: &JoinPoint,
+        result: &Result<Box<dyn Any>, AspectError>,
+    ) -> Result<(), AspectError> {
+        if let Ok(value) = result {
+            for rule in &self.postconditions {
+                rule.validate(ctx, &**value).map_err(|msg| {
+                    AspectError::execution(format!(
+                        "Postcondition failed for {}: {}",
+                        ctx.function_name, msg
+                    ))
+                })?;
+            }
+        }
+        self.check_invariants(ctx.function_name, "after")
+    }
+}
+
+impl Aspect for ContractAspect {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        if !self.enforcement.is_active() {
+            return pjp.proceed();
+        }
+
+        let ctx = pjp.context().clone();
+        self.check_before(&ctx)?;
+        let result = pjp.proceed();
+        self.check_after(&ctx, &result)?;
+        result
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            if !self.enforcement.is_active() {
+                return proceed.await;
+            }
+
+            self.check_before(ctx)?;
+            let result = proceed.await;
+            self.check_after(ctx, &result)?;
+            result
+        })
+    }
+
+    fn reads_args(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::{rule_for_arg, rule_for_result};
+    use aspect_macros::aspect;
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use std::sync::LazyLock;
+
+    static BALANCE: AtomicI64 = AtomicI64::new(100);
+
+    static ACCOUNT: LazyLock<ContractAspect> = LazyLock::new(|| {
+        ContractAspect::new()
+            .invariant("balance is never negative", || {
+                BALANCE.load(Ordering::SeqCst) >= 0
+            })
+            .require(rule_for_arg::<i64>("amount", |amount| {
+                if *amount > 0 {
+                    Ok(())
+                } else {
+                    Err("amount must be positive".to_string())
+                }
+            }))
+            .ensure(rule_for_result::<i64>(|balance| {
+                if *balance == BALANCE.load(Ordering::SeqCst) {
+                    Ok(())
+                } else {
+                    Err("returned balance is stale".to_string())
+                }
+            }))
+    });
+
+    #[aspect(ACCOUNT.clone())]
+    fn withdraw(amount: i64) -> Result<i64, String> {
+        Ok(BALANCE.fetch_sub(amount, Ordering::SeqCst) - amount)
+    }
+
+    #[aspect(ACCOUNT.clone())]
+    fn deposit(amount: i64) -> Result<i64, String> {
+        Ok(BALANCE.fetch_add(amount, Ordering::SeqCst) + amount)
+    }
+
+    #[aspect(ACCOUNT.clone())]
+    fn report_wrong_balance(amount: i64) -> Result<i64, String> {
+        Ok(amount)
+    }
+
+    // All contract checks share BALANCE, so they run in one test.
+    #[test]
+    fn test_contract_clauses() {
+        assert_eq!(withdraw(30).unwrap(), 70);
+
+        let err = deposit(0).unwrap_err();
+        assert!(
+            err.contains("Precondition failed for deposit: amount must be positive"),
+            "{}",
+            err
+        );
+
+        let err = report_wrong_balance(5).unwrap_err();
+        assert!(
+            err.contains(
+                "Postcondition failed for report_wrong_balance: returned balance is stale"
+            ),
+            "{}",
+            err
+        );
+
+        let err = withdraw(100).unwrap_err();
+        assert!(
+            err.contains("Invariant violated after withdraw: balance is never negative"),
+            "{}",
+            err
+        );
+
+        let err = deposit(10).unwrap_err();
+        assert!(
+            err.contains("Invariant violated before deposit: balance is never negative"),
+            "{}",
+            err
+        );
+        assert_eq!(BALANCE.load(Ordering::SeqCst), -30);
+    }
+
+    #[test]
+    fn test_debug_only_enforcement() {
+        assert!(Enforcement::Always.is_active());
+        assert_eq!(Enforcement::DebugOnly.is_active(), cfg!(debug_assertions));
+
+        let contract = ContractAspect::new()
+            .invariant("never", || false)
+            .debug_only();
+        let pjp = ProceedingJoinPoint::new(
+            || Ok(Box::new(()) as Box<dyn Any>),
+            aspect_core::JoinPoint::new(
+                "noop",
+                "test",
+                aspect_core::Location {
+                    file: "test.rs",
+                    line: 1,
+                },
+            ),
+        );
+        assert_eq!(contract.around(pjp).is_ok(), !cfg!(debug_assertions));
+    }
+}
@@ -0,0 +1,366 @@
+//! Correlation ids identifying the calls made for one request.
+//!
+//! The outermost call advised with [`CorrelationAspect`] generates an id,
+//! which the calls nested in it share: [`current`] returns it anywhere
+//! below, e.g. to set the [`HEADER`] of a response, and the aspects of the
+//! crate include it in what they emit. [`LoggingAspect`](crate::LoggingAspect)
+//! adds it to its records, [`AuditAspect`](crate::AuditAspect) to the audit
+//! trail, and [`MetricsAspect`](crate::MetricsAspect) to the tags of its
+//! measurements when asked to.
+//!
+//! The id is kept in a thread-local, so it follows synchronous call chains.
+//! With the `tokio` feature, it is also the `correlation_id` of the task
+//! context within a `context::scope`, which follows async requests across
+//! threads and is taken as is when already set, e.g. from an incoming
+//! `x-request-id` header.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use aspect_std::correlation::{self, CorrelationAspect};
+//! use aspect_std::LoggingAspect;
+//! use aspect_macros::aspect;
+//!
+//! #[aspect(CorrelationAspect::new(), LoggingAspect::new())]
+//! fn handle(request: Request) -> Response {
+//!     let mut response = render(load_user(request.user_id));
+//!     response.set_header(correlation::HEADER, correlation::current().unwrap());
+//!     response
+//! }
+//!
+//! // Logged with the id of the request
+//! #[aspect(LoggingAspect::new())]
+//! fn load_user(id: u64) -> User {
+//!     User::find(id)
+//! }
+//! ```
+
+#[cfg(feature = "tokio")]
+use crate::context::AspectContext;
+#[cfg(feature = "tokio")]
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, Precedence, ProceedingJoinPoint};
+use parking_lot::Mutex;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// The header correlation ids are conventionally exchanged in.
+pub const HEADER: &str = "x-correlation-id";
+
+thread_local! {
+    static CORRELATION_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Returns the correlation id of the current request, if any: the one of
+/// the task context with the `tokio` feature, or else the one of the
+/// outermost advised call running on this thread.
+pub fn current() -> Option<String> {
+    #[cfg(feature = "tokio")]
+    if let Some(id) = crate::context::correlation_id() {
+        return Some(id);
+    }
+    CORRELATION_ID.with(|id| id.borrow().clone())
+}
+
+/// Generates a new correlation id: 16 random hexadecimal digits.
+pub fn generate() -> String {
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+    let id = RandomState::new().hash_one(SEQUENCE.fetch_add(1, Ordering::Relaxed));
+    format!("{:016x}", id)
+}
+
+/// Runs `f` with `id` as the correlation id of this thread, e.g. one taken
+/// from the headers of an incoming request, so that the calls advised with
+/// [`CorrelationAspect`] in it keep it. The enclosing id is restored when
+/// `f` returns.
+pub fn scope<R>(id: impl Into<String>, f: impl FnOnce() -> R) -> R {
+    let _restore = Restore::Thread(CORRELATION_ID.with(|current| current.replace(Some(id.into()))));
+    f()
+}
+
+/// Restores the correlation id of the caller, also when the call panics.
+enum Restore {
+    /// The id the thread-local had
+    Thread(Option<String>),
+    /// The task context had no id
+    #[cfg(feature = "tokio")]
+    Task,
+}
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        match self {
+            Restore::Thread(previous) => {
+                let previous = previous.take();
+                CORRELATION_ID.with(|id| *id.borrow_mut() = previous);
+            }
+            #[cfg(feature = "tokio")]
+            Restore::Task => {
+                crate::context::update(|context| context.correlation_id = None);
+            }
+        }
+    }
+}
+
+/// The function generating correlation ids.
+type Generator = Arc<dyn Fn() -> String + Send + Sync>;
+
+/// Aspect giving the calls it advises a correlation id, generated by the
+/// outermost one.
+///
+/// A call made without a correlation id gets a new one while it runs, in
+/// the task context when in a `context::scope` with the `tokio` feature,
+/// or else in a thread-local. Calls made with one, nested in an advised
+/// call or in a [`scope`], keep it. With the `tokio` feature,
+/// asynchronous executions woven through
+/// [`around_async`](Aspect::around_async) outside any task context get one
+/// of their own.
+///
+/// The aspect is the outermost of the classes of [`Precedence`], so that
+/// the aspects it is listed with see the id it generates. `async fn`s, which `#[aspect]` weaves with
+/// `before` and `after` advice only, get the id in `before`, remembered in
+/// the aspect value until `after`, which works because `#[aspect(...)]`
+/// evaluates its expression once per call.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::correlation::CorrelationAspect;
+/// use aspect_macros::aspect;
+///
+/// #[aspect(CorrelationAspect::new())]
+/// fn handle(request: Request) -> Response {
+///     route(request)
+/// }
+/// ```
+pub struct CorrelationAspect {
+    generator: Generator,
+    restore: Mutex<Option<Restore>>,
+}
+
+impl CorrelationAspect {
+    /// Create an aspect generating ids with [`generate`].
+    pub fn new() -> Self {
+        Self {
+            generator: Arc::new(generate),
+            restore: Mutex::new(None),
+        }
+    }
+
+    /// Generate ids with `generator` instead, e.g. UUIDs.
+    pub fn with_generator(
+        mut self,
+        generator: impl Fn() -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.generator = Arc::new(generator);
+        self
+    }
+
+    /// Gives the call a new correlation id unless it has one, returning
+    /// the guard restoring the caller's.
+    fn enter(&self) -> Option<Restore> {
+        if current().is_some() {
+            return None;
+        }
+        let id = (self.generator)();
+        #[cfg(feature = "tokio")]
+        let id = {
+            let mut id = Some(id);
+            if crate::context::update(|context| context.correlation_id = id.take()) {
+                return Some(Restore::Task);
+            }
+            id.expect("only taken in a task context")
+        };
+        let previous = CORRELATION_ID.with(|current| current.replace(Some(id)));
+        Some(Restore::Thread(previous))
+    }
+}
+
+impl Default for CorrelationAspect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for CorrelationAspect {
+    fn clone(&self) -> Self {
+        Self {
+            generator: self.generator.clone(),
+            restore: Mutex::new(None),
+        }
+    }
+}
+
+impl Aspect for CorrelationAspect {
+    fn before(&self, _ctx: &JoinPoint) {
+        *self.restore.lock() = self.enter();
+    }
+
+    fn after(&self, _ctx: &JoinPoint, _result: &dyn Any) {
+        self.restore.lock().take();
+    }
+
+    fn after_error(&self, _ctx: &JoinPoint, _error: &AspectError) {
+        self.restore.lock().take();
+    }
+
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let _restore = self.enter();
+        pjp.proceed()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn around_async<'a>(
+        &'a self,
+        _ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            if current().is_some() || crate::context::current().is_some() {
+                let _restore = self.enter();
+                return proceed.await;
+            }
+            // A task context of its own, which the id follows across threads
+            let context = AspectContext::new().with_correlation_id((self.generator)());
+            crate::context::scope(context, proceed).await
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        // Outside security aspects, so that rejected calls are correlated
+        Precedence::new(Precedence::SECURITY.order() - 10)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_core::Location;
+
+    fn joinpoint(name: &'static str) -> JoinPoint {
+        JoinPoint::new(
+            name,
+            "test",
+            Location {
+                file: "test.rs",
+                line: 1,
+            },
+        )
+    }
+
+    fn run(
+        aspect: &CorrelationAspect,
+        f: impl FnOnce() -> Result<Box<dyn Any>, AspectError>,
+    ) -> Result<Box<dyn Any>, AspectError> {
+        aspect.around(ProceedingJoinPoint::new(f, joinpoint("handle")))
+    }
+
+    #[test]
+    fn test_outermost_call_generates_id() {
+        let aspect = CorrelationAspect::new();
+        assert_eq!(current(), None);
+
+        let ids = run(&aspect, || {
+            let outer = current().unwrap();
+            assert_eq!(outer.len(), 16);
+            let inner = run(&aspect, || Ok(Box::new(current())))?;
+            Ok(Box::new((
+                outer,
+                *inner.downcast::<Option<String>>().unwrap(),
+            )))
+        })
+        .unwrap();
+        let (outer, inner) = *ids.downcast::<(String, Option<String>)>().unwrap();
+        assert_eq!(inner, Some(outer));
+        assert_eq!(current(), None);
+
+        // Each request gets its own
+        let first = run(&aspect, || Ok(Box::new(current()))).unwrap();
+        let second = run(&aspect, || Ok(Box::new(current()))).unwrap();
+        assert_ne!(
+            first.downcast::<Option<String>>().unwrap(),
+            second.downcast::<Option<String>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_scope_and_generator() {
+        let aspect = CorrelationAspect::new().with_generator(|| "generated".to_string());
+        scope("req-7", || {
+            run(&aspect, || {
+                assert_eq!(current().as_deref(), Some("req-7"));
+                Ok(Box::new(()))
+            })
+            .unwrap();
+        });
+        run(&aspect, || {
+            assert_eq!(current().as_deref(), Some("generated"));
+            Ok(Box::new(()))
+        })
+        .unwrap();
+        assert_eq!(current(), None);
+    }
+
+    #[test]
+    fn test_before_after() {
+        let aspect = CorrelationAspect::new();
+        let ctx = joinpoint("handle");
+        aspect.before(&ctx);
+        let id = current().unwrap();
+
+        let nested = aspect.clone();
+        nested.before(&ctx);
+        assert_eq!(current(), Some(id));
+        nested.after(&ctx, &());
+
+        aspect.after_error(&ctx, &AspectError::execution("failed"));
+        assert_eq!(current(), None);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_around_async_task_context() {
+        use crate::context::{self, AspectContext};
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let aspect = CorrelationAspect::new().with_generator(|| "generated".to_string());
+        let ctx = joinpoint("handle");
+        let seen = |aspect: &CorrelationAspect| {
+            let proceed =
+                Box::pin(async { Ok(Box::new(context::correlation_id()) as Box<dyn Any>) });
+            let result = runtime
+                .block_on(aspect.around_async(&ctx, proceed))
+                .unwrap();
+            *result.downcast::<Option<String>>().unwrap()
+        };
+
+        // Outside a scope, in a task context of its own
+        assert_eq!(seen(&aspect).as_deref(), Some("generated"));
+
+        // Within one, in its context, keeping the id of the request
+        runtime.block_on(context::scope(AspectContext::new(), async {
+            let proceed = Box::pin(async { Ok(Box::new(current()) as Box<dyn Any>) });
+            let result = aspect.around_async(&ctx, proceed).await.unwrap();
+            assert_eq!(
+                *result.downcast::<Option<String>>().unwrap(),
+                Some("generated".into())
+            );
+            assert_eq!(context::correlation_id(), None);
+        }));
+        let request = AspectContext::new().with_correlation_id("req-1");
+        runtime.block_on(context::scope(request, async {
+            let proceed = Box::pin(async { Ok(Box::new(current()) as Box<dyn Any>) });
+            let result = aspect.around_async(&ctx, proceed).await.unwrap();
+            assert_eq!(
+                *result.downcast::<Option<String>>().unwrap(),
+                Some("req-1".into())
+            );
+        }));
+    }
+}
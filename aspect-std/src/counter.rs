@@ -0,0 +1,131 @@
+//! Call counting aspect, for targets without `std`.
+
+use aspect_core::{Aspect, AspectError, JoinPoint, Precedence};
+use core::any::Any;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts the calls of the functions it advises, and how they ended.
+///
+/// Unlike `MetricsAspect`, available with `std`, it keeps no durations and
+/// no counts per function: three atomic counters, shared by all the
+/// functions it advises, so it works without `std` and without a
+/// lock. Give each function a counter of its own for counts per
+/// function. It needs atomic compare-and-swap, which some microcontrollers
+/// such as the Cortex-M0 lack.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::CounterAspect;
+/// use aspect_macros::aspect;
+///
+/// static SENSOR_READS: CounterAspect = CounterAspect::new();
+///
+/// #[aspect(&SENSOR_READS)]
+/// fn read_sensor() -> Result<u16, SensorError> {
+///     // ...
+/// }
+///
+/// let failure_rate = SENSOR_READS.failures() as f32 / SENSOR_READS.calls() as f32;
+/// ```
+#[derive(Debug, Default)]
+pub struct CounterAspect {
+    calls: AtomicUsize,
+    successes: AtomicUsize,
+    failures: AtomicUsize,
+}
+
+impl CounterAspect {
+    /// Create a counter at zero.
+    pub const fn new() -> Self {
+        Self {
+            calls: AtomicUsize::new(0),
+            successes: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+        }
+    }
+
+    /// The calls started, including those still running.
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// The calls that returned `Ok`.
+    pub fn successes(&self) -> usize {
+        self.successes.load(Ordering::Relaxed)
+    }
+
+    /// The calls that returned an error.
+    pub fn failures(&self) -> usize {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// Set all counts back to zero.
+    pub fn reset(&self) {
+        self.calls.store(0, Ordering::Relaxed);
+        self.successes.store(0, Ordering::Relaxed);
+        self.failures.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Aspect for CounterAspect {
+    fn before(&self, _ctx: &JoinPoint) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn after(&self, _ctx: &JoinPoint, _result: &dyn Any) {
+        self.successes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn after_error(&self, _ctx: &JoinPoint, _error: &AspectError) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::OBSERVABILITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_core::{Location, ProceedingJoinPoint};
+
+    fn joinpoint() -> JoinPoint {
+        JoinPoint::new(
+            "read_sensor",
+            "firmware::sensor",
+            Location {
+                file: "sensor.rs",
+                line: 12,
+            },
+        )
+    }
+
+    #[test]
+    fn test_counts_outcomes() {
+        static COUNTER: CounterAspect = CounterAspect::new();
+
+        for value in [1, 2, 0] {
+            let pjp = ProceedingJoinPoint::new(
+                move || {
+                    if value == 0 {
+                        Err(AspectError::execution("no reading"))
+                    } else {
+                        Ok(Box::new(value) as Box<dyn Any>)
+                    }
+                },
+                joinpoint(),
+            );
+            let _ = COUNTER.around(pjp);
+        }
+
+        assert_eq!(COUNTER.calls(), 3);
+        assert_eq!(COUNTER.successes(), 2);
+        assert_eq!(COUNTER.failures(), 1);
+
+        COUNTER.reset();
+        assert_eq!(COUNTER.calls(), 0);
+        assert_eq!(COUNTER.failures(), 0);
+    }
+}
@@ -0,0 +1,396 @@
+//! Deadline (latency budget) propagation aspect.
+
+#[cfg(feature = "tokio")]
+use crate::context::AspectContext;
+use crate::time::{self, Duration, Instant};
+#[cfg(feature = "tokio")]
+use aspect_core::aspect::BoxFuture;
+use aspect_core::config::Param;
+#[cfg(feature = "tokio")]
+use aspect_core::JoinPoint;
+use aspect_core::{
+    Aspect, AspectArgs, AspectError, FromAspectArgs, Precedence, ProceedingJoinPoint,
+};
+use std::any::Any;
+use std::cell::Cell;
+
+/// The start of the outermost advised call and the deadline of the
+/// innermost one.
+#[derive(Clone, Copy)]
+struct Budget {
+    started: Instant,
+    deadline: Instant,
+}
+
+thread_local! {
+    static DEADLINE: Cell<Option<Budget>> = const { Cell::new(None) };
+}
+
+#[cfg(feature = "tokio")]
+tokio::task_local! {
+    /// The start of the outermost advised asynchronous call of the task
+    static STARTED: Instant;
+}
+
+/// Returns the deadline of the innermost advised call running on this
+/// thread, or that of the task context with the `tokio` feature, whichever
+/// is earlier, if any.
+pub fn current() -> Option<Instant> {
+    let thread = DEADLINE.with(Cell::get).map(|budget| budget.deadline);
+    #[cfg(feature = "tokio")]
+    if let Some(task) = crate::context::deadline() {
+        return Some(thread.map_or(task, |thread| thread.min(task)));
+    }
+    thread
+}
+
+/// The start of the outermost advised asynchronous call of the task, if
+/// any.
+fn task_started() -> Option<Instant> {
+    #[cfg(feature = "tokio")]
+    if let Ok(started) = STARTED.try_with(|started| *started) {
+        return Some(started);
+    }
+    None
+}
+
+/// Returns the time left before the current deadline, if any.
+///
+/// Useful for passing the remaining budget on as a timeout, e.g. to an HTTP
+/// client or database driver.
+pub fn remaining() -> Option<Duration> {
+    current().map(|deadline| deadline.saturating_duration_since(time::now()))
+}
+
+/// Aspect enforcing an end-to-end latency budget across nested calls.
+///
+/// The outermost advised call starts a deadline `budget` from now. Advised
+/// calls made while it runs inherit that deadline (tightened to their own
+/// budget if that is shorter) and fail fast without running once it has
+/// passed. The deadline is cleared when the outermost call returns.
+///
+/// The deadline is kept in a thread-local, so it follows synchronous call
+/// chains; work handed to other threads does not see it. With the `tokio`
+/// feature, asynchronous executions woven through
+/// [`around_async`](Aspect::around_async), such as those of the tower
+/// adapter, keep it in the `deadline` of the task context instead, in a
+/// context of their own outside a `context::scope`, so that it follows the
+/// request across threads. Deadlines set in the task context, e.g. with
+/// `AspectContext::with_budget`, apply to all advised calls of the task.
+///
+/// A call that is already running is not interrupted when the deadline
+/// passes; use [`remaining`] to bound blocking operations inside it.
+///
+/// Calls failing fast return [`AspectError::Timeout`], with the time since
+/// the outermost call started.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::DeadlineAspect;
+/// use aspect_macros::aspect;
+/// use std::time::Duration;
+///
+/// #[aspect(DeadlineAspect::new(Duration::from_millis(500)))]
+/// fn handle_request(id: u64) -> Result<String, String> {
+///     let user = load_user(id)?;
+///     render(user)
+/// }
+///
+/// // Fails fast if `handle_request` has already used up its 500ms
+/// #[aspect(DeadlineAspect::new(Duration::from_millis(200)))]
+/// fn load_user(id: u64) -> Result<String, String> {
+///     let timeout = aspect_std::deadline::remaining().unwrap();
+///     fetch_with_timeout(id, timeout)
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct DeadlineAspect {
+    budget: Duration,
+}
+
+/// Restores the enclosing deadline when a call returns or unwinds.
+struct Scope {
+    previous: Option<Budget>,
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        DEADLINE.with(|deadline| deadline.set(self.previous));
+    }
+}
+
+/// Restores the deadline of the task context when an asynchronous call
+/// completes or is dropped.
+#[cfg(feature = "tokio")]
+struct TaskScope {
+    previous: Option<Instant>,
+}
+
+#[cfg(feature = "tokio")]
+impl Drop for TaskScope {
+    fn drop(&mut self) {
+        crate::context::update(|context| context.deadline = self.previous);
+    }
+}
+
+impl DeadlineAspect {
+    /// Create a deadline aspect with the given budget.
+    pub fn new(budget: Duration) -> Self {
+        Self { budget }
+    }
+
+    /// The budget given to calls starting a deadline.
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+}
+
+impl Aspect for DeadlineAspect {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let now = time::now();
+        let previous = DEADLINE.with(Cell::get);
+        let started = previous
+            .map(|budget| budget.started)
+            .or_else(task_started)
+            .unwrap_or(now);
+
+        let deadline = match current() {
+            Some(deadline) if now >= deadline => {
+                return Err(AspectError::timeout(
+                    pjp.context().function_name,
+                    now - started,
+                ));
+            }
+            Some(deadline) => deadline.min(now + self.budget),
+            None => now + self.budget,
+        };
+        let _scope = Scope { previous };
+        DEADLINE.with(|current| current.set(Some(Budget { started, deadline })));
+
+        pjp.proceed()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            let now = time::now();
+            let started = task_started();
+            let previous = crate::context::deadline();
+
+            let deadline = match previous {
+                Some(deadline) if now >= deadline => {
+                    let elapsed = now - started.unwrap_or(now);
+                    return Err(AspectError::timeout(ctx.function_name, elapsed));
+                }
+                Some(deadline) => deadline.min(now + self.budget),
+                None => now + self.budget,
+            };
+            let call = async move {
+                if crate::context::current().is_none() {
+                    // A task context of its own, which the deadline follows
+                    // across threads
+                    let context = AspectContext {
+                        deadline: Some(deadline),
+                        ..AspectContext::new()
+                    };
+                    return crate::context::scope(context, proceed).await;
+                }
+                crate::context::update(|context| context.deadline = Some(deadline));
+                let _scope = TaskScope { previous };
+                proceed.await
+            };
+            match started {
+                Some(_) => call.await,
+                None => STARTED.scope(now, call).await,
+            }
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::RESILIENCE
+    }
+}
+
+/// Builds the aspect of `#[aspect(DeadlineAspect, budget = "500ms")]`.
+impl FromAspectArgs for DeadlineAspect {
+    const PARAMS: &'static [Param] = &[Param::required::<Duration>("budget")];
+
+    fn from_aspect_args(args: &AspectArgs) -> Result<Self, AspectError> {
+        args.check(Self::PARAMS)?;
+        Ok(Self::new(args.require("budget")?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_core::{JoinPoint, Location};
+
+    fn joinpoint(name: &'static str) -> JoinPoint {
+        JoinPoint::new(
+            name,
+            "test",
+            Location {
+                file: "test.rs",
+                line: 1,
+            },
+        )
+    }
+
+    fn run(
+        aspect: DeadlineAspect,
+        name: &'static str,
+        f: impl FnOnce() -> Result<Box<dyn Any>, AspectError>,
+    ) -> Result<Box<dyn Any>, AspectError> {
+        aspect.around(ProceedingJoinPoint::new(f, joinpoint(name)))
+    }
+
+    #[test]
+    fn test_outermost_call_sets_and_clears_deadline() {
+        assert!(current().is_none());
+
+        run(
+            DeadlineAspect::new(Duration::from_secs(10)),
+            "outer",
+            || {
+                let left = remaining().unwrap();
+                assert!(left > Duration::from_secs(9) && left <= Duration::from_secs(10));
+                Ok(Box::new(()))
+            },
+        )
+        .unwrap();
+
+        assert!(current().is_none());
+    }
+
+    #[test]
+    fn test_nested_call_inherits_shorter_deadline() {
+        run(
+            DeadlineAspect::new(Duration::from_millis(100)),
+            "outer",
+            || {
+                let outer = current().unwrap();
+                run(
+                    DeadlineAspect::new(Duration::from_secs(10)),
+                    "inner",
+                    || {
+                        assert_eq!(current(), Some(outer));
+                        Ok(Box::new(()))
+                    },
+                )?;
+                run(
+                    DeadlineAspect::new(Duration::from_millis(10)),
+                    "inner",
+                    || {
+                        assert!(current().unwrap() < outer);
+                        Ok(Box::new(()))
+                    },
+                )?;
+                assert_eq!(current(), Some(outer));
+                Ok(Box::new(()))
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_nested_call_fails_fast_when_budget_exhausted() {
+        let result = run(
+            DeadlineAspect::new(Duration::from_millis(20)),
+            "outer",
+            || {
+                std::thread::sleep(Duration::from_millis(30));
+                run(DeadlineAspect::new(Duration::from_secs(1)), "inner", || {
+                    panic!("inner call must not run")
+                })
+            },
+        );
+
+        match result.unwrap_err() {
+            AspectError::Timeout { target, elapsed } => {
+                assert_eq!(target, "inner");
+                assert!(elapsed >= Duration::from_millis(30));
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+        assert!(current().is_none());
+    }
+
+    #[cfg(feature = "tokio")]
+    mod task {
+        use super::*;
+        use crate::context;
+
+        fn block_on<F: std::future::Future>(future: F) -> F::Output {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(future)
+        }
+
+        async fn run_async(
+            aspect: DeadlineAspect,
+            name: &'static str,
+            proceed: BoxFuture<'_, Result<Box<dyn Any>, AspectError>>,
+        ) -> Result<Box<dyn Any>, AspectError> {
+            let ctx = joinpoint(name);
+            aspect.around_async(&ctx, proceed).await
+        }
+
+        #[test]
+        fn test_async_call_keeps_deadline_in_task_context() {
+            block_on(async {
+                let result = run_async(
+                    DeadlineAspect::new(Duration::from_secs(10)),
+                    "outer",
+                    Box::pin(async {
+                        let outer = context::deadline().unwrap();
+                        assert_eq!(current(), Some(outer));
+                        run_async(
+                            DeadlineAspect::new(Duration::from_millis(10)),
+                            "inner",
+                            Box::pin(async {
+                                assert!(context::deadline().unwrap() < outer);
+                                Ok(Box::new(()) as Box<dyn Any>)
+                            }),
+                        )
+                        .await?;
+                        assert_eq!(context::deadline(), Some(outer));
+                        Ok(Box::new(()) as Box<dyn Any>)
+                    }),
+                )
+                .await;
+                assert!(result.is_ok());
+                assert!(context::current().is_none());
+            });
+        }
+
+        #[test]
+        fn test_async_call_fails_fast_past_task_deadline() {
+            let request = context::AspectContext::new().with_budget(Duration::ZERO);
+            let result = block_on(context::scope(
+                request,
+                run_async(
+                    DeadlineAspect::new(Duration::from_secs(1)),
+                    "inner",
+                    Box::pin(async { panic!("inner call must not run") }),
+                ),
+            ));
+            assert!(matches!(result, Err(AspectError::Timeout { .. })));
+
+            // Synchronous calls see the deadline of the task context too
+            let request = context::AspectContext::new().with_budget(Duration::ZERO);
+            let result = context::sync_scope(request, || {
+                run(DeadlineAspect::new(Duration::from_secs(1)), "inner", || {
+                    panic!("inner call must not run")
+                })
+            });
+            assert!(matches!(result, Err(AspectError::Timeout { .. })));
+        }
+    }
+}
@@ -0,0 +1,223 @@
+//! Fallback aspect returning a substitute result on failure.
+
+use aspect_core::aspect::BoxFuture;
+use aspect_core::reentrancy;
+use aspect_core::{Aspect, AspectError, JoinPoint, Precedence, ProceedingJoinPoint};
+use std::any::Any;
+use std::sync::Arc;
+
+type FallbackFn = dyn Fn(&JoinPoint, &AspectError) -> Box<dyn Any> + Send + Sync;
+type ErrorFilter = dyn Fn(&AspectError) -> bool + Send + Sync;
+
+/// Aspect replacing a failed call's error with a fallback result.
+///
+/// The fallback must produce the advised function's success type: `T` for
+/// functions returning `Result<T, E>`, or the return type itself otherwise.
+/// A fallback of any other type fails the woven function with an
+/// [`AspectError::WeavingError`], or makes it panic if it does not return a
+/// `Result`.
+///
+/// To fall back when a circuit is open as well as when the call fails,
+/// [`wrap`](Self::wrap) the circuit breaker, or apply `FallbackAspect` as the
+/// outer of the two aspects.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::{CircuitBreakerAspect, FallbackAspect};
+/// use aspect_macros::aspect;
+/// use std::sync::LazyLock;
+/// use std::time::Duration;
+///
+/// static BREAKER: LazyLock<CircuitBreakerAspect> =
+///     LazyLock::new(|| CircuitBreakerAspect::new(5, Duration::from_secs(30)));
+///
+/// #[aspect(FallbackAspect::with(|| cached_response()).wrap(BREAKER.clone()))]
+/// fn fetch_response() -> Result<String, String> {
+///     call_remote_service()
+/// }
+/// ```
+#[derive(Clone)]
+pub struct FallbackAspect {
+    fallback: Arc<FallbackFn>,
+    filter: Option<Arc<ErrorFilter>>,
+    inner: Option<Arc<dyn Aspect>>,
+}
+
+impl FallbackAspect {
+    /// Fall back to the result of `f`.
+    pub fn with<T: 'static>(f: impl Fn() -> T + Send + Sync + 'static) -> Self {
+        Self::from_fn(move |_, _| Box::new(f()))
+    }
+
+    /// Fall back to a clone of `value`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use aspect_std::FallbackAspect;
+    ///
+    /// let fallback = FallbackAspect::value(Vec::<String>::new());
+    /// ```
+    pub fn value<T: Clone + Send + Sync + 'static>(value: T) -> Self {
+        Self::from_fn(move |_, _| Box::new(value.clone()))
+    }
+
+    /// Fall back to the result of `f`, which receives the failed call's join
+    /// point and error.
+    pub fn with_error<T: 'static>(
+        f: impl Fn(&JoinPoint, &AspectError) -> T + Send + Sync + 'static,
+    ) -> Self {
+        Self::from_fn(move |ctx, err| Box::new(f(ctx, err)))
+    }
+
+    fn from_fn(
+        f: impl Fn(&JoinPoint, &AspectError) -> Box<dyn Any> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            fallback: Arc::new(f),
+            filter: None,
+            inner: None,
+        }
+    }
+
+    /// Only fall back for errors matching `predicate`; other errors are
+    /// returned unchanged.
+    pub fn when(
+        mut self,
+        predicate: impl Fn(&AspectError) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.filter = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Run the call through `aspect`, falling back on its errors too.
+    ///
+    /// This lets a single `#[aspect]` attribute combine the fallback with
+    /// e.g. a [`CircuitBreakerAspect`](crate::CircuitBreakerAspect), whose
+    /// fail-fast errors then also produce the fallback.
+    pub fn wrap(mut self, aspect: impl Aspect + 'static) -> Self {
+        self.inner = Some(Arc::new(aspect));
+        self
+    }
+
+    /// The fallback result of a failed call, or `result` unchanged.
+    fn fall_back(
+        &self,
+        ctx: &JoinPoint,
+        result: Result<Box<dyn Any>, AspectError>,
+    ) -> Result<Box<dyn Any>, AspectError> {
+        match result {
+            Err(err) if self.filter.as_ref().is_none_or(|filter| filter(&err)) => {
+                log::debug!("falling back for {}: {}", ctx.function_name, err);
+                Ok((self.fallback)(ctx, &err))
+            }
+            result => result,
+        }
+    }
+}
+
+impl Aspect for FallbackAspect {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let ctx = pjp.context().clone();
+        // The wrapped aspect is guarded against reentrancy on its own
+        let result = match &self.inner {
+            Some(inner) if !reentrancy::skips(&**inner) => {
+                reentrancy::advice(&**inner, || inner.around(pjp))
+            }
+            _ => pjp.proceed(),
+        };
+        self.fall_back(&ctx, result)
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            let result = match &self.inner {
+                Some(inner) => inner.around_async(ctx, proceed).await,
+                None => proceed.await,
+            };
+            self.fall_back(ctx, result)
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        // Outside the circuit breakers and retries it falls back for
+        Precedence::new(Precedence::RESILIENCE.order() - 10)
+    }
+
+    fn reads_args(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CircuitBreakerAspect;
+    use aspect_core::Location;
+    use std::time::Duration;
+
+    fn joinpoint() -> JoinPoint {
+        JoinPoint::new(
+            "fetch",
+            "test",
+            Location {
+                file: "test.rs",
+                line: 1,
+            },
+        )
+    }
+
+    fn call(aspect: &FallbackAspect, ok: bool) -> Result<String, AspectError> {
+        let pjp = ProceedingJoinPoint::new(
+            move || {
+                if ok {
+                    Ok(Box::new("live".to_string()) as Box<dyn Any>)
+                } else {
+                    Err(AspectError::execution("unavailable"))
+                }
+            },
+            joinpoint(),
+        );
+        aspect
+            .around(pjp)
+            .map(|result| *result.downcast::<String>().unwrap())
+    }
+
+    #[test]
+    fn test_fallback_on_error_only() {
+        let fallback = FallbackAspect::with(|| "cached".to_string());
+
+        assert_eq!(call(&fallback, true).unwrap(), "live");
+        assert_eq!(call(&fallback, false).unwrap(), "cached");
+    }
+
+    #[test]
+    fn test_fallback_with_error_and_filter() {
+        let fallback =
+            FallbackAspect::with_error(|ctx, err| format!("{}: {}", ctx.function_name, err))
+                .when(|err| err.to_string().contains("unavailable"));
+        assert_eq!(
+            call(&fallback, false).unwrap(),
+            "fetch: Execution error: unavailable"
+        );
+
+        let fallback = FallbackAspect::value(String::new()).when(|_| false);
+        assert!(call(&fallback, false).is_err());
+    }
+
+    #[test]
+    fn test_fallback_when_circuit_open() {
+        let breaker = CircuitBreakerAspect::new(1, Duration::from_secs(60));
+        let fallback = FallbackAspect::value("cached".to_string()).wrap(breaker.clone());
+
+        assert_eq!(call(&fallback, false).unwrap(), "cached");
+        assert!(matches!(breaker.state(), crate::CircuitState::Open { .. }));
+        // Rejected by the open circuit, still served from the fallback
+        assert_eq!(call(&fallback, true).unwrap(), "cached");
+        assert_eq!(breaker.function_stats("fetch").rejected, 1);
+    }
+}
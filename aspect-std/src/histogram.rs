@@ -0,0 +1,407 @@
+//! Fixed-memory duration histograms with percentile queries.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Number of buckets per power of two above the linear range. Each bucket
+/// spans at most 1/64 of its lower bound, which bounds the relative error of
+/// reported percentiles to about 1.6%.
+const SUB_BUCKETS: u64 = 64;
+
+/// Values below this are counted exactly, one bucket per nanosecond.
+const LINEAR_LIMIT: u64 = 2 * SUB_BUCKETS;
+
+/// Number of chunks of [`SUB_BUCKETS`] buckets an [`AtomicHistogram`] may
+/// allocate, enough for the bucket of `u64::MAX` nanoseconds.
+const CHUNKS: usize = 59;
+
+/// Histogram of durations with log-linear buckets.
+///
+/// Memory stays bounded regardless of how many values are recorded: a few
+/// thousand counters at most, allocated as larger values show up. Percentiles
+/// are reported to within about 1.6% of the true value, in the style of
+/// HdrHistogram.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_std::histogram::Histogram;
+/// use std::time::Duration;
+///
+/// let mut histogram = Histogram::new();
+/// for ms in 1..=100 {
+///     histogram.record(Duration::from_millis(ms));
+/// }
+///
+/// let p99 = histogram.percentile(99.0);
+/// assert!(p99 >= Duration::from_millis(98) && p99 <= Duration::from_millis(100));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+/// Summary of a [`Histogram`] at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistogramSnapshot {
+    /// Number of recorded values
+    pub count: u64,
+    /// Sum of all recorded values
+    pub sum: Duration,
+    /// Smallest recorded value
+    pub min: Duration,
+    /// Largest recorded value
+    pub max: Duration,
+    /// Median
+    pub p50: Duration,
+    /// 90th percentile
+    pub p90: Duration,
+    /// 95th percentile
+    pub p95: Duration,
+    /// 99th percentile
+    pub p99: Duration,
+}
+
+impl HistogramSnapshot {
+    /// Average of the recorded values.
+    pub fn mean(&self) -> Duration {
+        if self.count > 0 {
+            let nanos = self.sum.as_nanos() / u128::from(self.count);
+            Duration::from_nanos(nanos as u64)
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+impl Histogram {
+    /// Create an empty histogram.
+    pub fn new() -> Self {
+        Self {
+            buckets: Vec::new(),
+            count: 0,
+            sum: Duration::ZERO,
+            min: Duration::ZERO,
+            max: Duration::ZERO,
+        }
+    }
+
+    /// Record one duration.
+    pub fn record(&mut self, duration: Duration) {
+        let index = bucket_index(nanos(duration));
+        if index >= self.buckets.len() {
+            self.buckets.resize(index + 1, 0);
+        }
+        self.buckets[index] += 1;
+
+        if self.count == 0 || duration < self.min {
+            self.min = duration;
+        }
+        self.max = self.max.max(duration);
+        self.count += 1;
+        self.sum = self.sum.saturating_add(duration);
+    }
+
+    /// Number of recorded values.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns `true` if nothing has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// The value below which `percentile` percent of the recorded values
+    /// fall, e.g. `percentile(99.0)` for p99.
+    ///
+    /// Returns [`Duration::ZERO`] for an empty histogram.
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0) * self.count as f64).ceil() as u64;
+        if rank <= 1 {
+            return self.min;
+        }
+        if rank >= self.count {
+            return self.max;
+        }
+
+        let mut seen = 0;
+        for (index, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let upper = Duration::from_nanos(bucket_upper(index));
+                return upper.clamp(self.min, self.max);
+            }
+        }
+        self.max
+    }
+
+    /// Number of recorded values not above `duration`, to within the
+    /// precision of the buckets.
+    pub fn count_at_most(&self, duration: Duration) -> u64 {
+        let limit = nanos(duration);
+        self.buckets
+            .iter()
+            .enumerate()
+            .take_while(|&(index, _)| bucket_upper(index) <= limit)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Current count, extremes and common percentiles.
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            count: self.count,
+            sum: self.sum,
+            min: self.min,
+            max: self.max,
+            p50: self.percentile(50.0),
+            p90: self.percentile(90.0),
+            p95: self.percentile(95.0),
+            p99: self.percentile(99.0),
+        }
+    }
+
+    /// Add all values recorded in `other` to this histogram.
+    pub fn merge(&mut self, other: &Histogram) {
+        if other.count == 0 {
+            return;
+        }
+        if other.buckets.len() > self.buckets.len() {
+            self.buckets.resize(other.buckets.len(), 0);
+        }
+        for (bucket, count) in self.buckets.iter_mut().zip(&other.buckets) {
+            *bucket += count;
+        }
+        if self.count == 0 || other.min < self.min {
+            self.min = other.min;
+        }
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+        self.sum = self.sum.saturating_add(other.sum);
+    }
+
+    /// Remove all recorded values.
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// [`Histogram`] recorded into with atomics, for values coming from many
+/// threads.
+///
+/// Recording never locks: buckets are allocated in chunks of 64 counters the
+/// first time a value falls into them, and [`load`](Self::load) copies the
+/// counters into a [`Histogram`] for percentile queries. Values recorded
+/// while loading may be missing from the copy.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_std::histogram::AtomicHistogram;
+/// use std::time::Duration;
+///
+/// let histogram = AtomicHistogram::new();
+/// std::thread::scope(|scope| {
+///     for _ in 0..4 {
+///         scope.spawn(|| histogram.record(Duration::from_millis(5)));
+///     }
+/// });
+///
+/// assert_eq!(histogram.load().count(), 4);
+/// ```
+pub struct AtomicHistogram {
+    chunks: [OnceLock<Box<[AtomicU64]>>; CHUNKS],
+    sum: AtomicU64,
+    min: AtomicU64,
+    max: AtomicU64,
+}
+
+impl AtomicHistogram {
+    /// Create an empty histogram.
+    pub fn new() -> Self {
+        Self {
+            chunks: std::array::from_fn(|_| OnceLock::new()),
+            sum: AtomicU64::new(0),
+            min: AtomicU64::new(u64::MAX),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    /// Record one duration.
+    pub fn record(&self, duration: Duration) {
+        let value = nanos(duration);
+        let index = bucket_index(value);
+        let chunk = self.chunks[index / SUB_BUCKETS as usize]
+            .get_or_init(|| (0..SUB_BUCKETS).map(|_| AtomicU64::new(0)).collect());
+        chunk[index % SUB_BUCKETS as usize].fetch_add(1, Ordering::Relaxed);
+
+        let _ = self
+            .sum
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sum| {
+                Some(sum.saturating_add(value))
+            });
+        self.min.fetch_min(value, Ordering::Relaxed);
+        self.max.fetch_max(value, Ordering::Relaxed);
+    }
+
+    /// Copy the recorded values into a [`Histogram`].
+    pub fn load(&self) -> Histogram {
+        let mut buckets = Vec::new();
+        for (number, chunk) in self.chunks.iter().enumerate() {
+            if let Some(chunk) = chunk.get() {
+                buckets.resize(number * SUB_BUCKETS as usize, 0);
+                buckets.extend(chunk.iter().map(|count| count.load(Ordering::Relaxed)));
+            }
+        }
+        let count = buckets.iter().sum();
+        if count == 0 {
+            return Histogram::new();
+        }
+
+        let max = self.max.load(Ordering::Relaxed);
+        Histogram {
+            buckets,
+            count,
+            sum: Duration::from_nanos(self.sum.load(Ordering::Relaxed)),
+            min: Duration::from_nanos(self.min.load(Ordering::Relaxed).min(max)),
+            max: Duration::from_nanos(max),
+        }
+    }
+}
+
+impl Default for AtomicHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for AtomicHistogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AtomicHistogram")
+            .field(&self.load())
+            .finish()
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+fn bucket_index(value: u64) -> usize {
+    if value < LINEAR_LIMIT {
+        return value as usize;
+    }
+    // Keep the top seven bits: the leading one selects the power of two,
+    // the six below it the sub-bucket.
+    let shift = 63 - value.leading_zeros() as u64 - 6;
+    (SUB_BUCKETS * shift + (value >> shift)) as usize
+}
+
+/// Largest value that falls into bucket `index`.
+fn bucket_upper(index: usize) -> u64 {
+    let index = index as u64;
+    if index < LINEAR_LIMIT {
+        return index;
+    }
+    let shift = index / SUB_BUCKETS - 1;
+    let mantissa = index % SUB_BUCKETS + SUB_BUCKETS;
+    (mantissa << shift) + ((1 << shift) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_bounds() {
+        let values = [0, 1, 127, 128, 129, 255, 256, 1_000, 123_456_789, u64::MAX];
+        for value in values {
+            let index = bucket_index(value);
+            assert!(bucket_upper(index) >= value);
+            if index > 0 {
+                assert!(bucket_upper(index - 1) < value);
+            }
+        }
+        assert_eq!(bucket_index(u64::MAX), 64 * 57 + 127);
+        assert_eq!(bucket_index(u64::MAX) / SUB_BUCKETS as usize, CHUNKS - 1);
+    }
+
+    #[test]
+    fn test_percentiles_within_error_bound() {
+        let mut histogram = Histogram::new();
+        for us in 1..=10_000 {
+            histogram.record(Duration::from_micros(us));
+        }
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count, 10_000);
+        assert_eq!(snapshot.min, Duration::from_micros(1));
+        assert_eq!(snapshot.max, Duration::from_micros(10_000));
+        for (actual, expected) in [
+            (snapshot.p50, 5_000.0),
+            (snapshot.p90, 9_000.0),
+            (snapshot.p95, 9_500.0),
+            (snapshot.p99, 9_900.0),
+        ] {
+            let error = (actual.as_secs_f64() * 1e6 - expected).abs() / expected;
+            assert!(error < 0.02, "{:?} vs {}us", actual, expected);
+        }
+        assert_eq!(snapshot.mean(), Duration::from_nanos(5_000_500));
+    }
+
+    #[test]
+    fn test_empty_and_merge() {
+        let mut a = Histogram::new();
+        assert!(a.is_empty());
+        assert_eq!(a.percentile(99.0), Duration::ZERO);
+        assert_eq!(a.snapshot().mean(), Duration::ZERO);
+
+        let mut b = Histogram::new();
+        b.record(Duration::from_millis(5));
+        a.record(Duration::from_millis(50));
+        a.merge(&b);
+
+        assert_eq!(a.count(), 2);
+        assert_eq!(a.percentile(0.0), Duration::from_millis(5));
+        assert_eq!(a.percentile(100.0), Duration::from_millis(50));
+
+        a.clear();
+        assert!(a.is_empty());
+    }
+
+    #[test]
+    fn test_atomic_matches_histogram() {
+        let atomic = AtomicHistogram::new();
+        assert!(atomic.load().is_empty());
+
+        let mut histogram = Histogram::new();
+        for value in [3, 200, 1_000, 20_000_000, 20_000_000, u64::MAX] {
+            let duration = Duration::from_nanos(value);
+            atomic.record(duration);
+            histogram.record(duration);
+        }
+
+        let loaded = atomic.load();
+        assert_eq!(loaded.buckets, histogram.buckets);
+        assert_eq!(loaded.snapshot().p50, histogram.snapshot().p50);
+        assert_eq!(loaded.min, Duration::from_nanos(3));
+        assert_eq!(loaded.max, Duration::from_nanos(u64::MAX));
+        assert_eq!(loaded.count_at_most(Duration::from_millis(25)), 5);
+        assert_eq!(loaded.count_at_most(Duration::ZERO), 0);
+    }
+}
@@ -0,0 +1,368 @@
+//! Aspects as HTTP middleware, per route.
+//!
+//! [`HttpAspectLayer`] is an [`AspectLayer`] for services of
+//! [`http::Request`]s, such as axum routers and hyper services: each request
+//! is a join point of the route it was routed to, with the method and path
+//! as arguments, so that an aspect like [`TimingAspect`](crate::TimingAspect)
+//! keeps statistics per route. Available with the `http` feature.
+
+use crate::tower::AspectLayer;
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Arg, Aspect, AspectError, JoinPoint, Symbol};
+use std::any::Any;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Finds the route of a request in its extensions, where routers put it.
+pub type RouteFn = for<'a> fn(&'a ::http::Extensions) -> Option<&'a str>;
+
+/// A [`Layer`] applying an aspect to the HTTP requests of the services it
+/// wraps, as calls of their routes.
+///
+/// The join point of a request is named after its route, as found by
+/// [`with_route`](Self::with_route), or after the layer when there is none.
+/// Its module is `http` and its arguments are the `method`
+/// ([`http::Method`]) and `path` (`String`) of the request.
+///
+/// Responses with a server error status are failures for the aspect,
+/// which a circuit breaker counts; they still reach the client unchanged.
+/// A request the aspect rejects gets an empty response, so that the service
+/// keeps the error type of the wrapped one, as axum requires:
+///
+/// - `403 Forbidden` for [`AspectError::Denied`]
+/// - `429 Too Many Requests` for [`AspectError::RateLimited`]
+/// - `504 Gateway Timeout` for [`AspectError::Timeout`]
+/// - `503 Service Unavailable` otherwise, including
+///   [`AspectError::CircuitOpen`] and [`AspectError::Overloaded`]
+///
+/// with a `Retry-After` header when the error tells how long to wait.
+///
+/// # Example
+///
+/// With axum, as a route layer, where the matched route is known:
+///
+/// ```rust,ignore
+/// use aspect_std::http::HttpAspectLayer;
+/// use aspect_std::TimingAspect;
+/// use axum::extract::MatchedPath;
+/// use axum::{routing::get, Router};
+///
+/// let timing = Arc::new(TimingAspect::new());
+/// let app = Router::new()
+///     .route("/users/{id}", get(get_user))
+///     .route_layer(
+///         HttpAspectLayer::shared(timing.clone(), "api")
+///             .with_route(|extensions| extensions.get::<MatchedPath>().map(MatchedPath::as_str)),
+///     );
+/// // Later: timing.get_stats("/users/{id}")
+/// ```
+pub struct HttpAspectLayer<A: ?Sized> {
+    layer: AspectLayer<A>,
+    route: Option<RouteFn>,
+}
+
+impl<A: Aspect> HttpAspectLayer<A> {
+    /// Apply `aspect` to requests, as calls of their routes, or of `name`.
+    #[track_caller]
+    pub fn new(aspect: A, name: &'static str) -> Self {
+        Self::shared(Arc::new(aspect), name)
+    }
+}
+
+impl<A: Aspect + ?Sized> HttpAspectLayer<A> {
+    /// Apply an aspect shared with other code to requests, as calls of
+    /// their routes, or of `name`.
+    #[track_caller]
+    pub fn shared(aspect: Arc<A>, name: &'static str) -> Self {
+        Self {
+            layer: AspectLayer::shared(aspect, name),
+            route: None,
+        }
+    }
+
+    /// Find the route of a request with `route`.
+    ///
+    /// Routes are interned as [`Symbol`]s for the join points: return route
+    /// templates such as `/users/{id}`, never paths, whose number is
+    /// unbounded.
+    pub fn with_route(mut self, route: RouteFn) -> Self {
+        self.route = Some(route);
+        self
+    }
+}
+
+impl<A: ?Sized> Clone for HttpAspectLayer<A> {
+    fn clone(&self) -> Self {
+        Self {
+            layer: self.layer.clone(),
+            route: self.route,
+        }
+    }
+}
+
+impl<S, A: ?Sized> Layer<S> for HttpAspectLayer<A> {
+    type Service = HttpAspectService<S, A>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HttpAspectService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// A service whose HTTP requests an aspect applies to, see
+/// [`HttpAspectLayer`].
+pub struct HttpAspectService<S, A: ?Sized> {
+    inner: S,
+    layer: HttpAspectLayer<A>,
+}
+
+impl<S: Clone, A: ?Sized> Clone for HttpAspectService<S, A> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            layer: self.layer.clone(),
+        }
+    }
+}
+
+impl<S, A: ?Sized> HttpAspectService<S, A> {
+    fn join_point<B>(&self, request: &::http::Request<B>) -> JoinPoint {
+        let layer = &self.layer.layer;
+        let route = self
+            .layer
+            .route
+            .and_then(|route| route(request.extensions()))
+            .map(|route| Symbol::intern(route).as_str());
+        JoinPoint::new(route.unwrap_or(layer.name), "http", layer.location).with_args(vec![
+            Arg::new("method", request.method()),
+            Arg::new("path", &request.uri().path().to_string()),
+        ])
+    }
+}
+
+impl<S, A, B, ResBody> Service<::http::Request<B>> for HttpAspectService<S, A>
+where
+    S: Service<::http::Request<B>, Response = ::http::Response<ResBody>> + Clone + Send + 'static,
+    S::Error: Send + 'static,
+    S::Future: Send,
+    A: Aspect + ?Sized + 'static,
+    B: Send + 'static,
+    ResBody: Default + Send + 'static,
+{
+    type Response = ::http::Response<ResBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: ::http::Request<B>) -> Self::Future {
+        let ctx = self.join_point(&request);
+        // The service made ready is the one to call, keep a clone instead
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let aspect = self.layer.layer.aspect.clone();
+
+        Box::pin(async move {
+            // What the service returned when the aspect sees a failure
+            let mut failure = None;
+            let proceed = Box::pin(async {
+                let error = match inner.call(request).await {
+                    Ok(response) if !response.status().is_server_error() => {
+                        return Ok(Box::new(response) as Box<dyn Any>);
+                    }
+                    Ok(response) => {
+                        let error = format!("{} responded {}", ctx, response.status());
+                        failure = Some(Ok(response));
+                        error
+                    }
+                    Err(error) => {
+                        failure = Some(Err(error));
+                        format!("{} failed", ctx)
+                    }
+                };
+                Err(AspectError::execution(error))
+            });
+            let result = aspect.around_async(&ctx, proceed).await;
+
+            match result {
+                Ok(response) => match response.downcast::<Self::Response>() {
+                    Ok(response) => Ok(*response),
+                    Err(_) => Ok(empty(::http::StatusCode::INTERNAL_SERVER_ERROR)),
+                },
+                Err(error) => match failure {
+                    Some(outcome) => outcome,
+                    None => Ok(rejection(&error)),
+                },
+            }
+        })
+    }
+}
+
+fn empty<ResBody: Default>(status: ::http::StatusCode) -> ::http::Response<ResBody> {
+    let mut response = ::http::Response::default();
+    *response.status_mut() = status;
+    response
+}
+
+/// The response to a request the aspect rejected with `error`.
+fn rejection<ResBody: Default>(error: &AspectError) -> ::http::Response<ResBody> {
+    let mut response = match error {
+        AspectError::Denied { .. } => empty(::http::StatusCode::FORBIDDEN),
+        AspectError::RateLimited { .. } => empty(::http::StatusCode::TOO_MANY_REQUESTS),
+        AspectError::Timeout { .. } => empty(::http::StatusCode::GATEWAY_TIMEOUT),
+        _ => empty(::http::StatusCode::SERVICE_UNAVAILABLE),
+    };
+    if let Some(wait) = error.retry_after() {
+        response.headers_mut().insert(
+            ::http::header::RETRY_AFTER,
+            retry_after_seconds(wait).into(),
+        );
+    }
+    response
+}
+
+/// `wait` in whole seconds, rounded up, for a `Retry-After` header.
+fn retry_after_seconds(wait: Duration) -> u64 {
+    wait.as_secs() + u64::from(wait.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CircuitBreakerAspect, RateLimitAspect, TimingAspect};
+    use ::http::{Request, Response, StatusCode};
+    use std::convert::Infallible;
+    use std::future::{ready, Future, Ready};
+
+    /// Where the router of the tests puts the route of a request.
+    #[derive(Clone)]
+    struct MatchedRoute(&'static str);
+
+    /// Responds with the status in the path, e.g. `/status/503`.
+    #[derive(Clone)]
+    struct Status;
+
+    impl Service<Request<()>> for Status {
+        type Response = Response<String>;
+        type Error = Infallible;
+        type Future = Ready<Result<Response<String>, Infallible>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<()>) -> Self::Future {
+            let status = request.uri().path().trim_start_matches("/status/");
+            let mut response = Response::new(status.to_string());
+            *response.status_mut() = status.parse().unwrap();
+            ready(Ok(response))
+        }
+    }
+
+    fn request(status: u16) -> Request<()> {
+        let mut request = Request::get(format!("/status/{}", status))
+            .body(())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(MatchedRoute("/status/{code}"));
+        request
+    }
+
+    fn route(extensions: &::http::Extensions) -> Option<&str> {
+        extensions.get::<MatchedRoute>().map(|route| route.0)
+    }
+
+    /// Sends a request for `status` to `service`, whose futures are all
+    /// ready.
+    fn send<S>(service: &mut S, status: u16) -> Response<String>
+    where
+        S: Service<Request<()>, Response = Response<String>, Error = Infallible>,
+    {
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+        let Poll::Ready(Ok(())) = service.poll_ready(&mut cx) else {
+            panic!("service not ready");
+        };
+        let mut future = std::pin::pin!(service.call(request(status)));
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(Ok(response)) => response,
+            Poll::Ready(Err(error)) => match error {},
+            Poll::Pending => panic!("response not ready"),
+        }
+    }
+
+    #[test]
+    fn test_join_point_of_route() {
+        let service = HttpAspectLayer::new(TimingAspect::new(), "api").layer(Status);
+        let ctx = service.join_point(&request(200));
+        assert_eq!(ctx.function_name, "api");
+        assert_eq!(ctx.module_path, "http");
+        assert_eq!(
+            ctx.arg("method").unwrap().value(),
+            Some(&::http::Method::GET)
+        );
+        assert_eq!(
+            ctx.arg("path").unwrap().value::<String>().unwrap(),
+            "/status/200"
+        );
+
+        let service = HttpAspectLayer::new(TimingAspect::new(), "api")
+            .with_route(route)
+            .layer(Status);
+        assert_eq!(
+            service.join_point(&request(200)).function_name,
+            "/status/{code}"
+        );
+    }
+
+    #[test]
+    fn test_timing_per_route() {
+        let timing = Arc::new(TimingAspect::new());
+        let mut service = HttpAspectLayer::shared(timing.clone(), "api")
+            .with_route(route)
+            .layer(Status);
+
+        assert_eq!(send(&mut service, 200).body(), "200");
+        // Server errors reach the client unchanged
+        let response = send(&mut service, 500);
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.body(), "500");
+        assert_eq!(timing.get_stats("/status/{code}").unwrap().count, 2);
+    }
+
+    #[test]
+    fn test_rejected_requests() {
+        let layer = HttpAspectLayer::new(RateLimitAspect::new(1, Duration::from_secs(60)), "api");
+        let mut service = layer.layer(Status);
+        assert_eq!(send(&mut service, 200).status(), StatusCode::OK);
+        let response = send(&mut service, 200);
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[::http::header::RETRY_AFTER], "60");
+        assert!(response.body().is_empty());
+
+        let breaker = CircuitBreakerAspect::new(2, Duration::from_secs(60));
+        let mut service = HttpAspectLayer::new(breaker, "api").layer(Status);
+        assert_eq!(send(&mut service, 404).status(), StatusCode::NOT_FOUND);
+        assert_eq!(send(&mut service, 502).status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(send(&mut service, 503).body(), "503");
+        // Two server errors opened the circuit
+        let response = send(&mut service, 200);
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[::http::header::RETRY_AFTER], "60");
+        assert!(response.body().is_empty());
+    }
+
+    #[test]
+    fn test_retry_after_seconds() {
+        assert_eq!(retry_after_seconds(Duration::ZERO), 0);
+        assert_eq!(retry_after_seconds(Duration::from_secs(2)), 2);
+        assert_eq!(retry_after_seconds(Duration::from_millis(2001)), 3);
+    }
+}
@@ -3,14 +3,86 @@
 //! Standard aspects library providing common, production-ready aspects.
 //!
 //! This crate provides a collection of reusable aspects for common cross-cutting concerns:
-//! - **Logging**: Structured logging with configurable levels
+//! - **Logging**: Structured logging with configurable levels, JSON output and secret
+//!   redaction, or plain entry/exit lines through the `log` facade
 //! - **Timing**: Performance monitoring with statistics
-//! - **Caching**: Generic memoization with TTL
-//! - **Metrics**: Counters, gauges, and histograms
-//! - **Rate Limiting**: Token bucket algorithm for throttling
+//! - **Profiling**: Flame graphs of selected functions in folded-stack format
+//! - **Allocation Tracking**: Allocations per function (`alloc-tracking` feature)
+//! - **Caching**: Memoization keyed on arguments, with TTL, LRU/LFU eviction and
+//!   pluggable stores (including [moka](https://docs.rs/moka) with the `moka` feature,
+//!   the stores of [cached](https://docs.rs/cached) with the `cached` feature,
+//!   and Redis, shared between processes, with the `redis` feature), invalidated
+//!   by the functions writing the cached data
+//! - **Metrics**: Counters, gauges, and histograms with percentiles, or bare atomic
+//!   counters
+//! - **Single Flight**: Coalesce concurrent identical calls into one execution
+//! - **Rate Limiting**: Token bucket throttling, in-process or shared through Redis
+//! - **Concurrency Limiting**: Bulkhead bounding simultaneous executions
+//! - **Load Shedding**: Reject a growing fraction of calls while in-flight calls,
+//!   latency or an external pressure signal exceed their thresholds
+//! - **Deadlines**: End-to-end latency budgets across nested calls
+//! - **Correlation**: An id per request, generated by the outermost call and included
+//!   in logs, audit records and metric tags
+//! - **MDC**: Contextual fields such as the tenant or user of a request, set by
+//!   application code or outer aspects and included in logs and audit records
+//! - **Context**: Principal, correlation id and deadline of requests, task-local
+//!   and surviving `tokio::spawn` (`tokio` feature)
 //! - **Circuit Breaker**: Fault tolerance and failure prevention
-//! - **Authorization**: Role-based access control
-//! - **Validation**: Pre/post condition checking
+//! - **Fallback**: Substitute results for failed or rejected calls
+//! - **Sampling**: Apply an expensive aspect to a fraction of calls
+//! - **Rollout**: Apply a new aspect to a stable percentage of users or other keys
+//! - **Panic Catching**: Turn panics into errors other aspects can handle
+//! - **Authorization**: Role- and attribute-based access control
+//! - **Audit**: Sequenced who/what/when records written to pluggable sinks
+//! - **Transactions**: Commit on success, roll back on failure, with nested propagation
+//! - **Validation**: Pre/post condition checking, and `validator::Validate` models
+//!   (`validator` feature)
+//! - **Contracts**: Design by contract with preconditions, postconditions and invariants
+//! - **Sinks**: Push measurements to StatsD/DogStatsD or the `metrics` facade (`metrics` feature)
+//! - **OpenTelemetry**: Span per call (`opentelemetry` feature)
+//! - **Tracing**: Span per call, and join point fields on existing `tracing` spans
+//!   (`tracing` feature)
+//! - **Propagation**: W3C `traceparent` and `baggage` headers in and out of the
+//!   task-local context, keeping distributed traces whole (`tokio` feature)
+//! - **Sentry**: Errors and panics reported as Sentry events, with breadcrumbs of the
+//!   calls leading to them (`sentry` feature)
+//! - **Maintenance**: Expired cache entries, idle rate limit keys and timing
+//!   statistics cleaned up, and statistics exported, on a background thread or
+//!   tokio task
+//! - **Middleware**: Any aspect around the handlers of any framework, as a
+//!   `wrap(next)` middleware
+//! - **Tower**: Any aspect as middleware around a tower service (`tower` feature),
+//!   or per route around HTTP services such as axum routers (`http` feature), and
+//!   as actix-web middleware (`actix-web` feature)
+//!
+//! The aspects are listed in the `[package.metadata.aspect]` table of the
+//! crate's `Cargo.toml`, so `cargo aspect list` shows them in every
+//! workspace depending on the crate, and [`ASPECT_PROVIDER`] at run time.
+//! Other crates can provide aspects the same way, see
+//! [`aspect_core::provider`].
+//!
+//! ## WebAssembly
+//!
+//! The crate builds for `wasm32-unknown-unknown` without optional features,
+//! which CI checks. There, measurements, expirations and time limits read the
+//! clocks of the JavaScript host (see the `time` module), since those of
+//! `std` panic. The target has no threads to block or spawn, so what blocks
+//! the caller or runs in the background is unavailable there: the queue of
+//! `ConcurrencyLimitAspect`, the waits of `ratelimit::LeakyBucket` and
+//! `maintenance::Maintenance::spawn`.
+//!
+//! ## `no_std`
+//!
+//! Without its default `std` feature the crate is `no_std` and only needs
+//! `alloc`, for embedded targets. It then provides [`ValidationAspect`],
+//! [`CounterAspect`] and [`SimpleLoggingAspect`]; the other aspects read
+//! clocks, spawn threads or take locks, and all optional features enable
+//! `std`. [`CounterAspect`] needs atomic compare-and-swap, which some
+//! microcontrollers lack.
+//!
+//! ```toml
+//! aspect-std = { version = "0.1", default-features = false }
+//! ```
 //!
 //! ## Quick Start
 //!
@@ -25,33 +97,200 @@
 //! }
 //! ```
 
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+// Not `alloc`, the name of the allocation tracking module
+extern crate alloc as alloc_crate;
+
+#[cfg(feature = "std")]
 pub mod logging;
+pub mod simplelog;
+#[cfg(feature = "std")]
 pub mod timing;
+#[cfg(feature = "std")]
+pub mod profiling;
+#[cfg(feature = "alloc-tracking")]
+pub mod alloc;
+#[cfg(feature = "std")]
 pub mod caching;
+#[cfg(feature = "std")]
+pub mod singleflight;
+#[cfg(feature = "std")]
 pub mod metrics;
+pub mod counter;
+#[cfg(feature = "std")]
+pub mod histogram;
+#[cfg(feature = "std")]
 pub mod ratelimit;
+#[cfg(feature = "std")]
+pub mod concurrency;
+#[cfg(feature = "std")]
+pub mod loadshed;
+#[cfg(feature = "std")]
+pub mod deadline;
+#[cfg(feature = "std")]
+pub mod correlation;
+#[cfg(feature = "std")]
+pub mod mdc;
+#[cfg(feature = "tokio")]
+pub mod context;
+#[cfg(feature = "tokio")]
+pub mod propagation;
+#[cfg(feature = "std")]
 pub mod circuitbreaker;
+#[cfg(feature = "std")]
+pub mod fallback;
+#[cfg(feature = "std")]
+pub mod sampling;
+#[cfg(feature = "std")]
+pub mod rollout;
+#[cfg(feature = "std")]
+pub mod catchpanic;
+#[cfg(feature = "std")]
 pub mod authorization;
+#[cfg(feature = "std")]
+pub mod audit;
+#[cfg(feature = "std")]
+pub mod transaction;
 pub mod validation;
+#[cfg(feature = "std")]
+pub mod contract;
+#[cfg(feature = "std")]
+pub mod maintenance;
+#[cfg(feature = "std")]
+pub mod sink;
+#[cfg(feature = "std")]
+pub mod redact;
+#[cfg(feature = "std")]
+pub mod time;
+#[cfg(feature = "opentelemetry")]
+pub mod otel;
+#[cfg(feature = "tracing")]
+pub mod tracing;
+#[cfg(feature = "sentry")]
+pub mod sentry;
+#[cfg(feature = "std")]
+pub mod middleware;
+#[cfg(feature = "tower")]
+pub mod tower;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "actix-web")]
+pub mod actix;
 
 // Re-export commonly used types
+#[cfg(feature = "std")]
 pub use logging::LoggingAspect;
+pub use simplelog::SimpleLoggingAspect;
+#[cfg(feature = "std")]
 pub use timing::TimingAspect;
+#[cfg(feature = "std")]
+pub use profiling::ProfilingAspect;
+#[cfg(feature = "alloc-tracking")]
+pub use alloc::AllocTrackingAspect;
+#[cfg(feature = "std")]
 pub use caching::CachingAspect;
+#[cfg(feature = "std")]
+pub use singleflight::SingleFlightAspect;
+#[cfg(feature = "std")]
 pub use metrics::MetricsAspect;
+pub use counter::CounterAspect;
+#[cfg(feature = "prometheus")]
+pub use metrics::PrometheusCollector;
+#[cfg(feature = "std")]
 pub use ratelimit::RateLimitAspect;
+#[cfg(feature = "std")]
+pub use concurrency::ConcurrencyLimitAspect;
+#[cfg(feature = "std")]
+pub use loadshed::LoadShedAspect;
+#[cfg(feature = "std")]
+pub use deadline::DeadlineAspect;
+#[cfg(feature = "std")]
+pub use correlation::CorrelationAspect;
+#[cfg(feature = "std")]
+pub use mdc::MdcAspect;
+#[cfg(feature = "std")]
 pub use circuitbreaker::{CircuitBreakerAspect, CircuitState};
+#[cfg(feature = "std")]
+pub use fallback::FallbackAspect;
+#[cfg(feature = "std")]
+pub use sampling::SamplingAspect;
+#[cfg(feature = "std")]
+pub use rollout::RolloutAspect;
+#[cfg(feature = "std")]
+pub use catchpanic::CatchPanicAspect;
+#[cfg(feature = "std")]
 pub use authorization::{AuthorizationAspect, AuthMode};
+#[cfg(feature = "std")]
+pub use audit::AuditAspect;
+#[cfg(feature = "std")]
+pub use transaction::{TransactionAspect, TransactionManager};
 pub use validation::{ValidationAspect, ValidationRule};
+#[cfg(feature = "std")]
+pub use contract::ContractAspect;
+#[cfg(feature = "opentelemetry")]
+pub use otel::OtelAspect;
+#[cfg(feature = "tracing")]
+pub use tracing::TracingAspect;
+#[cfg(feature = "sentry")]
+pub use sentry::SentryAspect;
+
+// The aspects of the crate, listed in its Cargo.toml
+aspect_macros::aspect_provider!();
 
 /// Prelude module for convenient imports.
 pub mod prelude {
+    #[cfg(feature = "std")]
     pub use crate::logging::LoggingAspect;
+    pub use crate::simplelog::SimpleLoggingAspect;
+    #[cfg(feature = "std")]
     pub use crate::timing::TimingAspect;
+    #[cfg(feature = "std")]
+    pub use crate::profiling::ProfilingAspect;
+    #[cfg(feature = "alloc-tracking")]
+    pub use crate::alloc::AllocTrackingAspect;
+    #[cfg(feature = "std")]
     pub use crate::caching::CachingAspect;
+    #[cfg(feature = "std")]
+    pub use crate::singleflight::SingleFlightAspect;
+    #[cfg(feature = "std")]
     pub use crate::metrics::MetricsAspect;
+    pub use crate::counter::CounterAspect;
+    #[cfg(feature = "std")]
     pub use crate::ratelimit::RateLimitAspect;
+    #[cfg(feature = "std")]
+    pub use crate::concurrency::ConcurrencyLimitAspect;
+    #[cfg(feature = "std")]
+    pub use crate::loadshed::LoadShedAspect;
+    #[cfg(feature = "std")]
+    pub use crate::deadline::DeadlineAspect;
+    #[cfg(feature = "std")]
+    pub use crate::correlation::CorrelationAspect;
+    #[cfg(feature = "std")]
+    pub use crate::mdc::MdcAspect;
+    #[cfg(feature = "std")]
     pub use crate::circuitbreaker::{CircuitBreakerAspect, CircuitState};
+    #[cfg(feature = "std")]
+    pub use crate::fallback::FallbackAspect;
+    #[cfg(feature = "std")]
+    pub use crate::sampling::SamplingAspect;
+    #[cfg(feature = "std")]
+    pub use crate::rollout::RolloutAspect;
+    #[cfg(feature = "std")]
+    pub use crate::catchpanic::CatchPanicAspect;
+    #[cfg(feature = "std")]
     pub use crate::authorization::{AuthorizationAspect, AuthMode};
+    #[cfg(feature = "std")]
+    pub use crate::audit::AuditAspect;
+    #[cfg(feature = "std")]
+    pub use crate::transaction::{TransactionAspect, TransactionManager};
     pub use crate::validation::{ValidationAspect, ValidationRule};
+    #[cfg(feature = "std")]
+    pub use crate::contract::ContractAspect;
+    #[cfg(feature = "opentelemetry")]
+    pub use crate::otel::OtelAspect;
+    #[cfg(feature = "tracing")]
+    pub use crate::tracing::TracingAspect;
+    #[cfg(feature = "sentry")]
+    pub use crate::sentry::SentryAspect;
 }
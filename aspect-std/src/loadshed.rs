@@ -0,0 +1,448 @@
+//! Load shedding aspect.
+
+use crate::time::{Clock, Duration, SystemClock};
+use aspect_core::aspect::BoxFuture;
+use aspect_core::config::Param;
+use aspect_core::{
+    Aspect, AspectArgs, AspectError, AspectSnapshot, FromAspectArgs, JoinPoint, Precedence,
+    ProceedingJoinPoint,
+};
+use std::any::Any;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// The weight of the latest call in the moving average of latencies.
+const LATENCY_WEIGHT: f64 = 0.2;
+
+/// An external measure of the load, 1.0 at the threshold.
+type Pressure = Arc<dyn Fn() -> f64 + Send + Sync>;
+
+/// Aspect rejecting a growing fraction of calls while the service is
+/// overloaded.
+///
+/// A [`RateLimitAspect`](crate::RateLimitAspect) admits a rate fixed in
+/// advance, whatever the service can take at the time, and a
+/// [`ConcurrencyLimitAspect`](crate::ConcurrencyLimitAspect) rejects every
+/// call past its limit. This aspect measures the load of the calls it
+/// advises instead, as the highest of:
+///
+/// - the calls in flight, over [`with_max_in_flight`](Self::with_max_in_flight);
+/// - their latency, a moving average of the durations of the last calls,
+///   over [`with_max_latency`](Self::with_max_latency);
+/// - an external pressure signal, such as the CPU usage or the depth of a
+///   queue scaled so that 1.0 is its threshold, given to
+///   [`with_pressure`](Self::with_pressure).
+///
+/// Below 1.0 every call runs. At a load `l` above it, the fraction
+/// `1 - 1/l` of the calls fails with [`AspectError::Overloaded`] without
+/// running, spread evenly over them, so that the calls admitted bring the
+/// load back to the threshold. At most
+/// [`with_max_shed`](Self::with_max_shed) of them are shed, 90% by default:
+/// the calls still admitted keep measuring the latency, which would
+/// otherwise never recover.
+///
+/// Clones share their measurements, so one aspect (or its clones) can
+/// protect a group of functions together. Asynchronous executions woven
+/// through [`around_async`](Aspect::around_async), such as the requests of
+/// a tower service, are in flight until their future completes; like the
+/// bulkhead, the aspect does nothing to `async fn`s woven with `#[aspect]`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::LoadShedAspect;
+/// use aspect_macros::aspect;
+/// use std::sync::LazyLock;
+/// use std::time::Duration;
+///
+/// static SHED: LazyLock<LoadShedAspect> = LazyLock::new(|| {
+///     LoadShedAspect::new()
+///         .with_max_in_flight(64)
+///         .with_max_latency(Duration::from_millis(250))
+///         .with_retry_after(Duration::from_secs(1))
+/// });
+///
+/// #[aspect(SHED.clone())]
+/// fn search(query: String) -> Result<Vec<String>, String> {
+///     Ok(vec![])
+/// }
+/// ```
+#[derive(Clone)]
+pub struct LoadShedAspect {
+    max_in_flight: Option<usize>,
+    max_latency: Option<Duration>,
+    pressure: Option<Pressure>,
+    max_shed: f64,
+    retry_after: Option<Duration>,
+    clock: Arc<dyn Clock>,
+    state: Arc<State>,
+}
+
+#[derive(Default)]
+struct State {
+    in_flight: AtomicUsize,
+    /// Moving average of the durations of the calls, in nanoseconds, 0
+    /// before the first one
+    latency: AtomicU64,
+    /// Calls made while overloaded, which shed calls are spread over
+    overloaded: AtomicU64,
+    shed: AtomicU64,
+}
+
+/// A call admitted, in flight until dropped (including on panic).
+struct Call<'a> {
+    aspect: &'a LoadShedAspect,
+    start: crate::time::Instant,
+}
+
+impl Call<'_> {
+    /// Records the duration of the call, which completed.
+    fn complete(self) {
+        let elapsed = self.aspect.clock.elapsed(self.start).as_nanos() as f64;
+        let _ = self.aspect.state.latency.fetch_update(
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            |average| {
+                if average == 0 {
+                    return Some(elapsed.max(1.0) as u64);
+                }
+                let average = average as f64;
+                Some((average + (elapsed - average) * LATENCY_WEIGHT).max(1.0) as u64)
+            },
+        );
+    }
+}
+
+impl Drop for Call<'_> {
+    fn drop(&mut self) {
+        self.aspect.state.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl LoadShedAspect {
+    /// Create an aspect shedding no call until thresholds are set.
+    ///
+    /// # Example
+    /// ```rust
+    /// use aspect_std::LoadShedAspect;
+    ///
+    /// let shed = LoadShedAspect::new().with_max_in_flight(8);
+    /// assert_eq!(shed.load(), 0.0);
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            max_in_flight: None,
+            max_latency: None,
+            pressure: None,
+            max_shed: 0.9,
+            retry_after: None,
+            clock: Arc::new(SystemClock),
+            state: Arc::default(),
+        }
+    }
+
+    /// Shed calls when more than `max_in_flight` would run at the same time.
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight.max(1));
+        self
+    }
+
+    /// Shed calls while the recent calls take longer than `max_latency` on
+    /// average.
+    pub fn with_max_latency(mut self, max_latency: Duration) -> Self {
+        self.max_latency = Some(max_latency);
+        self
+    }
+
+    /// Shed calls while `pressure` returns more than 1.0, e.g. the CPU
+    /// usage of the process over the usage it should stay under.
+    ///
+    /// It is called for each call the aspect advises: it should only read
+    /// a value measured elsewhere.
+    pub fn with_pressure(mut self, pressure: impl Fn() -> f64 + Send + Sync + 'static) -> Self {
+        self.pressure = Some(Arc::new(pressure));
+        self
+    }
+
+    /// Shed at most the fraction `max_shed` of the calls (0.9 by default).
+    ///
+    /// `max_shed` is clamped to `0.0..=1.0`. Shedding every call keeps the
+    /// latency from being measured, and from going down.
+    pub fn with_max_shed(mut self, max_shed: f64) -> Self {
+        self.max_shed = max_shed.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Tell rejected callers to retry after `retry_after`.
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
+    /// Time the calls with `clock` instead of the system clock, e.g. a
+    /// [`ManualClock`](crate::time::ManualClock) in tests.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Number of calls currently executing.
+    pub fn in_flight(&self) -> usize {
+        self.state.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// The moving average of the durations of the calls, `None` before the
+    /// first one completes.
+    pub fn latency(&self) -> Option<Duration> {
+        match self.state.latency.load(Ordering::Relaxed) {
+            0 => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+
+    /// The current load, 1.0 at the thresholds.
+    pub fn load(&self) -> f64 {
+        self.load_with(self.in_flight())
+    }
+
+    /// Number of calls shed so far.
+    pub fn shed_count(&self) -> u64 {
+        self.state.shed.load(Ordering::Relaxed)
+    }
+
+    /// The load with `in_flight` calls running.
+    fn load_with(&self, in_flight: usize) -> f64 {
+        let mut load: f64 = 0.0;
+        if let Some(max_in_flight) = self.max_in_flight {
+            load = load.max(in_flight as f64 / max_in_flight as f64);
+        }
+        if let (Some(max_latency), Some(latency)) = (self.max_latency, self.latency()) {
+            load = load.max(latency.as_secs_f64() / max_latency.as_secs_f64());
+        }
+        if let Some(pressure) = &self.pressure {
+            load = load.max(pressure());
+        }
+        load
+    }
+
+    /// Admits a call of `target`, or sheds it.
+    fn admit(&self, target: &str) -> Result<Call<'_>, AspectError> {
+        let in_flight = self.state.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        let call = Call {
+            aspect: self,
+            start: self.clock.now(),
+        };
+
+        let load = self.load_with(in_flight);
+        if load <= 1.0 {
+            return Ok(call);
+        }
+        let ratio = (1.0 - 1.0 / load).min(self.max_shed);
+        // Call n is shed when n * ratio crosses an integer
+        let n = self.state.overloaded.fetch_add(1, Ordering::Relaxed) as f64;
+        if (n * ratio).ceil() < ((n + 1.0) * ratio).ceil() {
+            self.state.shed.fetch_add(1, Ordering::Relaxed);
+            return Err(AspectError::overloaded(target, self.retry_after));
+        }
+        Ok(call)
+    }
+}
+
+impl Default for LoadShedAspect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Aspect for LoadShedAspect {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let call = self.admit(pjp.context().function_name)?;
+        let result = pjp.proceed();
+        call.complete();
+        result
+    }
+
+    /// In flight until `proceed` completes, not only until it is created.
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            let call = self.admit(ctx.function_name)?;
+            let result = proceed.await;
+            call.complete();
+            result
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::RESILIENCE
+    }
+
+    fn snapshot(&self) -> AspectSnapshot {
+        AspectSnapshot::new("LoadShedAspect")
+            .with("load", self.load())
+            .with("in_flight", self.in_flight())
+            .with(
+                "latency_ms",
+                self.latency().map(|latency| latency.as_secs_f64() * 1e3),
+            )
+            .with("shed", self.shed_count())
+    }
+}
+
+/// Builds the aspect of `#[aspect(LoadShedAspect, max_in_flight = 64)]`.
+///
+/// Parameters: `max_in_flight` or `max_latency` (at least one), `max_shed`
+/// and `retry_after`.
+impl FromAspectArgs for LoadShedAspect {
+    const PARAMS: &'static [Param] = &[
+        Param::optional::<usize>("max_in_flight"),
+        Param::optional::<Duration>("max_latency"),
+        Param::optional::<f64>("max_shed"),
+        Param::optional::<Duration>("retry_after"),
+    ];
+
+    fn from_aspect_args(args: &AspectArgs) -> Result<Self, AspectError> {
+        args.check(Self::PARAMS)?;
+        let mut aspect = Self::new();
+        if let Some(max_in_flight) = args.get("max_in_flight")? {
+            aspect = aspect.with_max_in_flight(max_in_flight);
+        }
+        if let Some(max_latency) = args.get("max_latency")? {
+            aspect = aspect.with_max_latency(max_latency);
+        }
+        if aspect.max_in_flight.is_none() && aspect.max_latency.is_none() {
+            return Err(AspectError::weaving(
+                "LoadShedAspect requires parameter `max_in_flight` or `max_latency`",
+            ));
+        }
+        if let Some(max_shed) = args.get("max_shed")? {
+            aspect = aspect.with_max_shed(max_shed);
+        }
+        if let Some(retry_after) = args.get("retry_after")? {
+            aspect = aspect.with_retry_after(retry_after);
+        }
+        Ok(aspect)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::ManualClock;
+    use aspect_core::Location;
+
+    fn joinpoint() -> JoinPoint {
+        JoinPoint::new(
+            "search",
+            "test",
+            Location {
+                file: "test.rs",
+                line: 1,
+            },
+        )
+    }
+
+    /// Calls the advised function, taking `duration` on `clock`.
+    fn call(
+        aspect: &LoadShedAspect,
+        clock: &ManualClock,
+        duration: Duration,
+    ) -> Result<Box<dyn Any>, AspectError> {
+        let clock = clock.clone();
+        let pjp = ProceedingJoinPoint::new(
+            move || {
+                clock.advance(duration);
+                Ok(Box::new(()) as Box<dyn Any>)
+            },
+            joinpoint(),
+        );
+        aspect.around(pjp)
+    }
+
+    #[test]
+    fn test_sheds_in_proportion_to_in_flight() {
+        let aspect = LoadShedAspect::new().with_max_in_flight(2);
+        let _first = aspect.admit("search").unwrap();
+        let _second = aspect.admit("search").unwrap();
+        assert_eq!(aspect.load(), 1.0);
+
+        // A third call would make the load 1.5: a third of them is shed,
+        // the first one included
+        assert!(aspect.admit("search").is_err());
+        assert!(aspect.admit("search").is_ok());
+        assert_eq!(aspect.in_flight(), 2);
+
+        // At a load of 2, half of them
+        let aspect = LoadShedAspect::new().with_max_in_flight(1);
+        let _first = aspect.admit("search").unwrap();
+        let admitted = (0..30).filter(|_| aspect.admit("search").is_ok()).count();
+        assert_eq!(admitted, 15);
+        assert_eq!(aspect.shed_count(), 15);
+        assert_eq!(aspect.in_flight(), 1);
+    }
+
+    #[test]
+    fn test_sheds_on_latency() {
+        let clock = ManualClock::new();
+        let aspect = LoadShedAspect::new()
+            .with_max_latency(Duration::from_millis(100))
+            .with_retry_after(Duration::from_secs(1))
+            .with_clock(clock.clone());
+
+        call(&aspect, &clock, Duration::from_millis(50)).unwrap();
+        assert_eq!(aspect.latency(), Some(Duration::from_millis(50)));
+        assert_eq!(aspect.load(), 0.5);
+
+        // Slow calls raise the average over the threshold
+        for _ in 0..10 {
+            let _ = call(&aspect, &clock, Duration::from_millis(400));
+        }
+        assert!(aspect.load() > 1.0);
+        let errors: Vec<_> = (0..10)
+            .filter_map(|_| call(&aspect, &clock, Duration::from_millis(400)).err())
+            .collect();
+        assert!(!errors.is_empty());
+        match &errors[0] {
+            AspectError::Overloaded {
+                target,
+                retry_after,
+            } => {
+                assert_eq!(target, "search");
+                assert_eq!(*retry_after, Some(Duration::from_secs(1)));
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+
+        // Fast calls bring it back down
+        while aspect.load() > 1.0 {
+            let _ = call(&aspect, &clock, Duration::from_millis(10));
+        }
+        assert!(call(&aspect, &clock, Duration::from_millis(10)).is_ok());
+        assert_eq!(aspect.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_pressure_and_max_shed() {
+        let clock = ManualClock::new();
+        let aspect = LoadShedAspect::new()
+            .with_pressure(|| 100.0)
+            .with_clock(clock.clone());
+        let shed = (0..100)
+            .filter(|_| call(&aspect, &clock, Duration::ZERO).is_err())
+            .count();
+        assert_eq!(shed, 90);
+        assert_eq!(aspect.load(), 100.0);
+
+        let half = LoadShedAspect::new()
+            .with_pressure(|| 100.0)
+            .with_max_shed(0.5)
+            .with_clock(clock.clone());
+        let shed = (0..100)
+            .filter(|_| call(&half, &clock, Duration::ZERO).is_err())
+            .count();
+        assert_eq!(shed, 50);
+    }
+}
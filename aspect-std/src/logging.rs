@@ -1,19 +1,50 @@
 //! Structured logging aspect with configurable levels.
 
-use aspect_core::{Aspect, AspectError, JoinPoint};
+pub mod sink;
+
+#[cfg(feature = "tracing")]
+pub use sink::TracingSink;
+pub use sink::{LogFacadeSink, LogRecord, LogSink, MemorySink, StderrSink};
+
+use crate::redact::Redactor;
+use aspect_core::{Aspect, AspectError, JoinPoint, Precedence};
+use parking_lot::RwLock;
 use std::any::Any;
+use std::fmt::{self, Write};
+use std::str::FromStr;
+use std::sync::Arc;
 
 /// Logging aspect with configurable log levels and output.
 ///
 /// Provides structured logging for function entry, exit, and errors.
+/// Records are emitted through the [`log`] facade by default, so they go to
+/// whatever logger the application installed (`env_logger`, `fern`, ...).
+/// By default the record target is the module path of the advised function,
+/// which means filters such as `RUST_LOG=my_crate::api=debug` apply as usual.
+/// [`with_sink`](Self::with_sink) sends them elsewhere, e.g. to `tracing`
+/// or to a [`MemorySink`] in tests.
+///
+/// Messages are only formatted when the sink has the level enabled for the
+/// target, so a disabled aspect costs a single `log_enabled!` check.
+///
+/// With [`log_args`](Self::log_args), arguments are passed through a
+/// [`Redactor`] first, so passwords, tokens and other secrets are masked.
+///
+/// [`LevelRules`] pick the level per module or function, and can be changed
+/// while the program runs.
+///
+/// Records are plain text by default. [`LogFormat::Json`] emits one JSON
+/// object per record instead, for log aggregators; see [`LogEntry`] for the
+/// fields.
 ///
 /// # Example
 ///
 /// ```rust,ignore
 /// use aspect_std::LoggingAspect;
+/// use aspect_std::logging::LogLevel;
 /// use aspect_macros::aspect;
 ///
-/// #[aspect(LoggingAspect::new())]
+/// #[aspect(LoggingAspect::new().with_level(LogLevel::Debug))]
 /// fn my_function(x: i32) -> Result<i32, String> {
 ///     Ok(x * 2)
 /// }
@@ -21,8 +52,16 @@ use std::any::Any;
 #[derive(Clone)]
 pub struct LoggingAspect {
     level: LogLevel,
+    error_level: LogLevel,
+    target: Option<&'static str>,
     log_args: bool,
     log_result: bool,
+    redactor: Redactor,
+    format: LogFormat,
+    rules: Option<LevelRules>,
+    sink: Arc<dyn LogSink>,
+    #[cfg(feature = "tracing")]
+    span_fields: bool,
 }
 
 /// Log level for the logging aspect.
@@ -40,43 +79,475 @@ pub enum LogLevel {
     Error,
 }
 
+impl From<LogLevel> for log::Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Trace => log::Level::Trace,
+            LogLevel::Debug => log::Level::Debug,
+            LogLevel::Info => log::Level::Info,
+            LogLevel::Warn => log::Level::Warn,
+            LogLevel::Error => log::Level::Error,
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    /// Parses a case-insensitive level name such as `"debug"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "trace" => Ok(LogLevel::Trace),
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            _ => Err(format!("Unknown log level: {}", s)),
+        }
+    }
+}
+
+/// A path and its level; `None` silences the path.
+type LevelRule = (String, Option<LogLevel>);
+
+/// Runtime-adjustable log levels keyed by module path or function.
+///
+/// Each rule maps a path to a level, or to `None` to silence it. A rule for
+/// `my_app::api` applies to every function in `my_app::api` and its
+/// submodules; a rule for `my_app::api::get_user` applies to that function
+/// only. The longest matching path wins, and a rule with an empty path is
+/// the default.
+///
+/// Clones share their rules, so the aspect can be built once (typically in
+/// a static) while another handle to the same rules is changed at runtime,
+/// e.g. from an admin endpoint.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_std::logging::{LevelRules, LogLevel};
+///
+/// let rules = LevelRules::parse("info,my_app::api=debug,my_app::db=trace").unwrap();
+/// assert_eq!(rules.level_for("my_app::api::users", "get"), Some(LogLevel::Debug));
+/// assert_eq!(rules.level_for("my_app::auth", "login"), Some(LogLevel::Info));
+///
+/// rules.set("my_app::api", None);
+/// assert_eq!(rules.level_for("my_app::api::users", "get"), None);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LevelRules {
+    rules: Arc<RwLock<Vec<LevelRule>>>,
+}
+
+impl LevelRules {
+    /// Create an empty rule set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse rules from an `env_logger`-style spec.
+    ///
+    /// The spec is a comma-separated list of `path=level` entries, where a
+    /// bare `level` sets the default and `off` silences the path, e.g.
+    /// `"warn,my_app::api=debug,my_app::noisy=off"`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let rules = Self::new();
+        rules.set_spec(spec)?;
+        Ok(rules)
+    }
+
+    /// Replace all rules with those in `spec` (see [`parse`](Self::parse)).
+    ///
+    /// On error the current rules are kept.
+    pub fn set_spec(&self, spec: &str) -> Result<(), String> {
+        let mut parsed = Vec::new();
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (path, level) = match directive.split_once('=') {
+                Some((path, level)) => (path.trim(), level.trim()),
+                None if directive.parse::<LogLevel>().is_ok() || directive == "off" => {
+                    ("", directive)
+                }
+                None => {
+                    return Err(format!("Missing level for '{}' in log spec", directive));
+                }
+            };
+            let level = match level {
+                "off" => None,
+                level => Some(level.parse()?),
+            };
+            parsed.push((path.to_string(), level));
+        }
+        *self.rules.write() = parsed;
+        Ok(())
+    }
+
+    /// Set the level for `path`, replacing any existing rule for it. `None`
+    /// silences the path.
+    pub fn set(&self, path: &str, level: Option<LogLevel>) {
+        let mut rules = self.rules.write();
+        match rules.iter_mut().find(|(p, _)| p == path) {
+            Some(rule) => rule.1 = level,
+            None => rules.push((path.to_string(), level)),
+        }
+    }
+
+    /// Remove the rule for `path`, if any.
+    pub fn remove(&self, path: &str) {
+        self.rules.write().retain(|(p, _)| p != path);
+    }
+
+    /// Look up the rule for a function.
+    ///
+    /// Returns `None` when no rule matches, `Some(None)` when the function
+    /// is silenced and `Some(Some(level))` otherwise.
+    pub fn rule_for(&self, module_path: &str, function_name: &str) -> Option<Option<LogLevel>> {
+        self.rules
+            .read()
+            .iter()
+            .filter(|(path, _)| path_matches(path, module_path, function_name))
+            .max_by_key(|(path, _)| path.len())
+            .map(|(_, level)| *level)
+    }
+
+    /// The level for a function, or `None` if it is silenced or no rule
+    /// matches.
+    pub fn level_for(&self, module_path: &str, function_name: &str) -> Option<LogLevel> {
+        self.rule_for(module_path, function_name).flatten()
+    }
+}
+
+/// Returns `true` if a rule for `path` applies to `module_path::function_name`.
+fn path_matches(path: &str, module_path: &str, function_name: &str) -> bool {
+    if path.is_empty() {
+        return true;
+    }
+    match module_path.strip_prefix(path) {
+        Some("") => true,
+        Some(rest) => rest.starts_with("::"),
+        None => path
+            .strip_prefix(module_path)
+            .and_then(|rest| rest.strip_prefix("::"))
+            .is_some_and(|name| name == function_name),
+    }
+}
+
+/// Kind of record emitted by the logging aspect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogEvent {
+    /// The function was entered
+    Entry,
+    /// The function returned successfully
+    Exit,
+    /// The function failed
+    Error,
+}
+
+impl LogEvent {
+    /// Stable lowercase name, used as the `event` field in JSON output.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogEvent::Entry => "entry",
+            LogEvent::Exit => "exit",
+            LogEvent::Error => "error",
+        }
+    }
+}
+
+/// One record about to be emitted, as passed to [`LogFormat::Custom`].
+#[derive(Debug, Clone, Copy)]
+pub struct LogEntry<'a> {
+    /// What happened
+    pub event: LogEvent,
+    /// The advised function
+    pub ctx: &'a JoinPoint,
+    /// Redacted argument summary, if argument logging is enabled
+    pub args: Option<&'a str>,
+    /// Type name of the result, if result logging is enabled
+    pub result: Option<&'a str>,
+    /// The error, for [`LogEvent::Error`] records
+    pub error: Option<&'a AspectError>,
+    /// The [correlation id](crate::correlation) of the request, if any
+    pub correlation_id: Option<&'a str>,
+    /// The [contextual fields](crate::mdc) of the request
+    pub fields: &'a [(String, String)],
+}
+
+impl LogEntry<'_> {
+    /// Formats the entry as a human-readable line, ending with
+    /// `correlation_id=<id>` when there is one, then `key=value` for each
+    /// contextual field.
+    pub fn to_text(&self) -> String {
+        let ctx = self.ctx;
+        let line = match self.event {
+            LogEvent::Entry => match self.args {
+                Some(args) => format!(
+                    "[ENTRY] {}({}) ({}:{})",
+                    ctx.function_name, args, ctx.location.file, ctx.location.line
+                ),
+                None => format!(
+                    "[ENTRY] {} ({}:{})",
+                    ctx.function_name, ctx.location.file, ctx.location.line
+                ),
+            },
+            LogEvent::Exit => match self.result {
+                Some(result) => format!("[EXIT] {} (result: {:?})", ctx.function_name, result),
+                None => format!("[EXIT] {}", ctx.function_name),
+            },
+            LogEvent::Error => match self.error {
+                Some(error) => format!("[ERROR] {} failed: {:?}", ctx.function_name, error),
+                None => format!("[ERROR] {} failed", ctx.function_name),
+            },
+        };
+        let mut line = match self.correlation_id {
+            Some(id) => format!("{} correlation_id={}", line, id),
+            None => line,
+        };
+        for (key, value) in self.fields {
+            let _ = write!(line, " {}={}", key, value);
+        }
+        line
+    }
+
+    /// Formats the entry as a single-line JSON object.
+    ///
+    /// Always present: `event` (`"entry"`, `"exit"` or `"error"`),
+    /// `function`, `module`, `file` and `line`. Present when known:
+    /// `correlation_id`, `fields` (object of the contextual fields), `args`
+    /// (redacted summary string), `result` (type name) and `error` (error
+    /// message).
+    pub fn to_json(&self) -> String {
+        let ctx = self.ctx;
+        let mut out = String::from("{");
+        write_json_field(&mut out, "event", self.event.as_str());
+        write_json_field(&mut out, "function", ctx.function_name);
+        write_json_field(&mut out, "module", ctx.module_path);
+        write_json_field(&mut out, "file", ctx.location.file);
+        let _ = write!(out, ",\"line\":{}", ctx.location.line);
+        if let Some(id) = self.correlation_id {
+            write_json_field(&mut out, "correlation_id", id);
+        }
+        if !self.fields.is_empty() {
+            out.push_str(",\"fields\":{");
+            for (key, value) in self.fields {
+                write_json_field(&mut out, key, value);
+            }
+            out.push('}');
+        }
+        if let Some(args) = self.args {
+            write_json_field(&mut out, "args", args);
+        }
+        if let Some(result) = self.result {
+            write_json_field(&mut out, "result", result);
+        }
+        if let Some(error) = self.error {
+            write_json_field(&mut out, "error", &error.to_string());
+        }
+        out.push('}');
+        out
+    }
+}
+
+/// Appends `"key":"value"` to a JSON object under construction.
+fn write_json_field(out: &mut String, key: &str, value: &str) {
+    if !out.ends_with('{') {
+        out.push(',');
+    }
+    write_json_string(out, key);
+    out.push(':');
+    write_json_string(out, value);
+}
+
+fn write_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Formatter for [`LogFormat::Custom`].
+pub type LogFormatter = dyn Fn(&LogEntry<'_>) -> String + Send + Sync;
+
+/// Output format of the logging aspect.
+#[derive(Clone, Default)]
+pub enum LogFormat {
+    /// Human-readable lines such as `[ENTRY] my_function (src/lib.rs:10)`
+    #[default]
+    Text,
+    /// One JSON object per record (see [`LogEntry::to_json`])
+    Json,
+    /// Records formatted by a user-supplied function
+    Custom(Arc<LogFormatter>),
+}
+
+impl LogFormat {
+    /// Format records with `formatter`.
+    pub fn custom(formatter: impl Fn(&LogEntry<'_>) -> String + Send + Sync + 'static) -> Self {
+        LogFormat::Custom(Arc::new(formatter))
+    }
+
+    /// Formats `entry` according to this format.
+    pub fn format(&self, entry: &LogEntry<'_>) -> String {
+        match self {
+            LogFormat::Text => entry.to_text(),
+            LogFormat::Json => entry.to_json(),
+            LogFormat::Custom(formatter) => formatter(entry),
+        }
+    }
+}
+
+impl fmt::Debug for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogFormat::Text => f.write_str("Text"),
+            LogFormat::Json => f.write_str("Json"),
+            LogFormat::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
 impl LoggingAspect {
     /// Create a new logging aspect with Info level.
     pub fn new() -> Self {
         Self {
             level: LogLevel::Info,
+            error_level: LogLevel::Error,
+            target: None,
             log_args: false,
             log_result: false,
+            redactor: Redactor::default(),
+            format: LogFormat::Text,
+            rules: None,
+            sink: Arc::new(LogFacadeSink),
+            #[cfg(feature = "tracing")]
+            span_fields: false,
         }
     }
 
-    /// Set the log level.
+    /// Set the level used for entry and exit records.
     pub fn with_level(mut self, level: LogLevel) -> Self {
         self.level = level;
         self
     }
 
+    /// Pick the entry/exit level per module or function from `rules`,
+    /// falling back to [`with_level`](Self::with_level) where no rule
+    /// matches. Silenced functions produce no records at all.
+    ///
+    /// Rules are evaluated on every call, so changes made through a clone
+    /// of `rules` take effect immediately.
+    pub fn with_level_rules(mut self, rules: LevelRules) -> Self {
+        self.rules = Some(rules);
+        self
+    }
+
+    /// Set the level used for error records (Error by default).
+    pub fn with_error_level(mut self, level: LogLevel) -> Self {
+        self.error_level = level;
+        self
+    }
+
+    /// Use a fixed log target instead of the advised function's module path.
+    pub fn with_target(mut self, target: &'static str) -> Self {
+        self.target = Some(target);
+        self
+    }
+
     /// Enable logging of function arguments (disabled by default).
     pub fn log_args(mut self) -> Self {
         self.log_args = true;
         self
     }
 
+    /// Set how sensitive arguments are masked ([`Redactor::default`] by
+    /// default).
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
     /// Enable logging of function results (disabled by default).
     pub fn log_result(mut self) -> Self {
         self.log_result = true;
         self
     }
 
-    fn log(&self, level: LogLevel, message: &str) {
-        if level as u8 >= self.level as u8 {
-            match level {
-                LogLevel::Trace => log::trace!("{}", message),
-                LogLevel::Debug => log::debug!("{}", message),
-                LogLevel::Info => log::info!("{}", message),
-                LogLevel::Warn => log::warn!("{}", message),
-                LogLevel::Error => log::error!("{}", message),
-            }
+    /// Set the output format ([`LogFormat::Text`] by default).
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Write records to `sink` ([`LogFacadeSink`] by default).
+    pub fn with_sink(mut self, sink: impl LogSink + 'static) -> Self {
+        self.sink = Arc::new(sink);
+        self
+    }
+
+    /// Emit records as JSON lines; shorthand for
+    /// `with_format(LogFormat::Json)`.
+    pub fn json(self) -> Self {
+        self.with_format(LogFormat::Json)
+    }
+
+    /// Also record the metadata of calls as fields of the `tracing` span
+    /// they run in, see [`record_join_point`](crate::tracing::record_join_point),
+    /// so that records correlate with the spans of the application.
+    ///
+    /// Available with the `tracing` feature.
+    #[cfg(feature = "tracing")]
+    pub fn with_span_fields(mut self) -> Self {
+        self.span_fields = true;
+        self
+    }
+
+    fn target(&self, ctx: &JoinPoint) -> &'static str {
+        self.target.unwrap_or(ctx.module_path)
+    }
+
+    /// Level for entry/exit records of `ctx`, or `None` if it is silenced.
+    fn level_for(&self, ctx: &JoinPoint) -> Option<LogLevel> {
+        match &self.rules {
+            Some(rules) => rules
+                .rule_for(ctx.module_path, ctx.function_name)
+                .unwrap_or(Some(self.level)),
+            None => Some(self.level),
+        }
+    }
+
+    fn log(&self, level: LogLevel, entry: LogEntry<'_>) {
+        let target = self.target(entry.ctx);
+        if self.sink.enabled(level, target) {
+            let correlation_id = crate::correlation::current();
+            let fields = crate::mdc::current();
+            let entry = LogEntry {
+                correlation_id: correlation_id.as_deref(),
+                fields: &fields,
+                ..entry
+            };
+            self.sink.write(level, target, &self.format.format(&entry));
+        }
+    }
+
+    fn entry<'a>(&self, event: LogEvent, ctx: &'a JoinPoint) -> LogEntry<'a> {
+        LogEntry {
+            event,
+            ctx,
+            args: None,
+            result: None,
+            error: None,
+            correlation_id: None,
+            fields: &[],
         }
     }
 }
@@ -89,32 +560,122 @@ impl Default for LoggingAspect {
 
 impl Aspect for LoggingAspect {
     fn before(&self, ctx: &JoinPoint) {
-        let message = format!(
-            "[ENTRY] {} ({}:{})",
-            ctx.function_name, ctx.location.file, ctx.location.line
-        );
-        self.log(self.level, &message);
+        let Some(level) = self.level_for(ctx) else {
+            return;
+        };
+        #[cfg(feature = "tracing")]
+        if self.span_fields {
+            let span = ::tracing::Span::current();
+            crate::tracing::record_join_point(&span, ctx, &self.redactor);
+        }
+        if self.log_args {
+            let args = self.redactor.format_args(&ctx.args);
+            self.log(
+                level,
+                LogEntry {
+                    args: Some(&args),
+                    ..self.entry(LogEvent::Entry, ctx)
+                },
+            );
+        } else {
+            self.log(level, self.entry(LogEvent::Entry, ctx));
+        }
     }
 
     fn after(&self, ctx: &JoinPoint, result: &dyn Any) {
-        let mut message = format!("[EXIT] {}", ctx.function_name);
+        let Some(level) = self.level_for(ctx) else {
+            return;
+        };
+        let result = self.log_result.then(|| std::any::type_name_of_val(result));
+        self.log(
+            level,
+            LogEntry {
+                result,
+                ..self.entry(LogEvent::Exit, ctx)
+            },
+        );
+    }
 
-        if self.log_result {
-            message.push_str(&format!(" (result: {:?})", std::any::type_name_of_val(result)));
+    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
+        if self.level_for(ctx).is_none() {
+            return;
         }
+        self.log(
+            self.error_level,
+            LogEntry {
+                error: Some(error),
+                ..self.entry(LogEvent::Error, ctx)
+            },
+        );
+    }
 
-        self.log(self.level, &message);
+    fn precedence(&self) -> Precedence {
+        Precedence::OBSERVABILITY
     }
 
-    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
-        let message = format!("[ERROR] {} failed: {:?}", ctx.function_name, error);
-        self.log(LogLevel::Error, &message);
+    fn reads_args(&self) -> bool {
+        true
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::{Mutex, Once};
+
+    struct CaptureLogger {
+        records: Mutex<Vec<(log::Level, String, String)>>,
+    }
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            !metadata.target().starts_with("quiet")
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                self.records.lock().unwrap().push((
+                    record.level(),
+                    record.target().to_string(),
+                    record.args().to_string(),
+                ));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CaptureLogger = CaptureLogger {
+        records: Mutex::new(Vec::new()),
+    };
+
+    /// Installs the capturing logger and returns the records mentioning `needle`.
+    fn captured(needle: &str) -> Vec<(log::Level, String, String)> {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            log::set_logger(&LOGGER).unwrap();
+            log::set_max_level(log::LevelFilter::Trace);
+        });
+        LOGGER
+            .records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, _, msg)| msg.contains(needle))
+            .cloned()
+            .collect()
+    }
+
+    fn joinpoint(function_name: &'static str, module_path: &'static str) -> JoinPoint {
+        JoinPoint::new(
+            function_name,
+            module_path,
+            aspect_core::Location {
+                file: "test.rs",
+                line: 7,
+            },
+        )
+    }
 
     #[test]
     fn test_logging_aspect_creation() {
@@ -139,16 +700,211 @@ mod tests {
     #[test]
     fn test_logging_aspect_before() {
         let aspect = LoggingAspect::new();
-        let ctx = JoinPoint {
-            function_name: "test_function",
-            module_path: "test::module",
-            location: aspect_core::Location {
+        let ctx = JoinPoint::new(
+            "test_function",
+            "test::module",
+            aspect_core::Location {
                 file: "test.rs",
                 line: 42,
             },
-        };
+        );
 
         // Should not panic
         aspect.before(&ctx);
     }
+
+    #[test]
+    fn test_log_level_conversion() {
+        assert_eq!(log::Level::from(LogLevel::Trace), log::Level::Trace);
+        assert_eq!(log::Level::from(LogLevel::Info), log::Level::Info);
+        assert_eq!(log::Level::from(LogLevel::Error), log::Level::Error);
+    }
+
+    #[test]
+    fn test_records_use_module_path_target() {
+        captured("");
+        let aspect = LoggingAspect::new().with_level(LogLevel::Debug);
+        let ctx = joinpoint("target_fn", "my_app::api");
+
+        aspect.before(&ctx);
+        aspect.after(&ctx, &());
+
+        let records = captured("target_fn");
+        assert_eq!(records.len(), 2);
+        assert!(records
+            .iter()
+            .all(|(level, target, _)| *level == log::Level::Debug && target == "my_app::api"));
+        assert_eq!(records[0].2, "[ENTRY] target_fn (test.rs:7)");
+        assert_eq!(records[1].2, "[EXIT] target_fn");
+    }
+
+    #[test]
+    fn test_custom_target_and_error_level() {
+        captured("");
+        let aspect = LoggingAspect::new()
+            .with_target("audit")
+            .with_error_level(LogLevel::Warn);
+        let ctx = joinpoint("failing_fn", "my_app::db");
+
+        aspect.after_error(&ctx, &AspectError::execution("boom"));
+
+        let records = captured("failing_fn");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].0, log::Level::Warn);
+        assert_eq!(records[0].1, "audit");
+        assert!(records[0].2.contains("boom"));
+    }
+
+    #[test]
+    fn test_disabled_target_is_skipped() {
+        captured("");
+        let aspect = LoggingAspect::new();
+        let ctx = joinpoint("quiet_fn", "quiet::module");
+
+        aspect.before(&ctx);
+
+        assert!(captured("quiet_fn").is_empty());
+    }
+
+    #[test]
+    fn test_args_are_redacted() {
+        captured("");
+        let aspect = LoggingAspect::new().log_args();
+        let ctx = joinpoint("login_fn", "my_app::auth").with_args(vec![
+            aspect_core::Arg::new("user", &"alice".to_string()),
+            aspect_core::Arg::new("password", &"hunter2".to_string()),
+        ]);
+
+        aspect.before(&ctx);
+
+        let records = captured("login_fn");
+        assert_eq!(
+            records[0].2,
+            r#"[ENTRY] login_fn(user: "alice", password: ***) (test.rs:7)"#
+        );
+    }
+
+    #[test]
+    fn test_json_format() {
+        captured("");
+        let aspect = LoggingAspect::new().json().log_args();
+        let ctx = joinpoint("json_fn", "my_app::api")
+            .with_args(vec![aspect_core::Arg::new("name", &"a\"b".to_string())]);
+
+        aspect.before(&ctx);
+        aspect.after_error(&ctx, &AspectError::execution("line1\nline2"));
+
+        let records = captured("json_fn");
+        assert_eq!(
+            records[0].2,
+            r#"{"event":"entry","function":"json_fn","module":"my_app::api","file":"test.rs","line":7,"args":"name: \"a\\\"b\""}"#
+        );
+        assert_eq!(
+            records[1].2,
+            r#"{"event":"error","function":"json_fn","module":"my_app::api","file":"test.rs","line":7,"error":"Execution error: line1\nline2"}"#
+        );
+    }
+
+    #[test]
+    fn test_correlation_id() {
+        captured("");
+        let ctx = joinpoint("correlated_fn", "my_app::api");
+        crate::correlation::scope("req-1", || {
+            LoggingAspect::new().before(&ctx);
+            LoggingAspect::new().json().after(&ctx, &());
+        });
+
+        let records = captured("correlated_fn");
+        assert_eq!(
+            records[0].2,
+            "[ENTRY] correlated_fn (test.rs:7) correlation_id=req-1"
+        );
+        assert_eq!(
+            records[1].2,
+            r#"{"event":"exit","function":"correlated_fn","module":"my_app::api","file":"test.rs","line":7,"correlation_id":"req-1"}"#
+        );
+    }
+
+    #[test]
+    fn test_mdc_fields() {
+        captured("");
+        let ctx = joinpoint("tenant_fn", "my_app::api");
+        crate::mdc::scope([("tenant", "acme"), ("user", "a\"b")], || {
+            LoggingAspect::new().before(&ctx);
+            LoggingAspect::new().json().after(&ctx, &());
+        });
+
+        let records = captured("tenant_fn");
+        assert_eq!(
+            records[0].2,
+            r#"[ENTRY] tenant_fn (test.rs:7) tenant=acme user=a"b"#
+        );
+        assert_eq!(
+            records[1].2,
+            r#"{"event":"exit","function":"tenant_fn","module":"my_app::api","file":"test.rs","line":7,"fields":{"tenant":"acme","user":"a\"b"}}"#
+        );
+    }
+
+    #[test]
+    fn test_custom_format() {
+        captured("");
+        let aspect = LoggingAspect::new().with_format(LogFormat::custom(|entry| {
+            format!("{} {}", entry.event.as_str(), entry.ctx.function_name)
+        }));
+        let ctx = joinpoint("custom_fn", "my_app::api");
+
+        aspect.before(&ctx);
+        aspect.after(&ctx, &());
+
+        let records = captured("custom_fn");
+        assert_eq!(records[0].2, "entry custom_fn");
+        assert_eq!(records[1].2, "exit custom_fn");
+    }
+
+    #[test]
+    fn test_level_rules_parse_and_match() {
+        let rules = LevelRules::parse("warn, app::api=debug, app::api::get_user=trace").unwrap();
+        assert_eq!(
+            rules.level_for("app::api::v1", "list"),
+            Some(LogLevel::Debug)
+        );
+        assert_eq!(
+            rules.level_for("app::api", "get_user"),
+            Some(LogLevel::Trace)
+        );
+        assert_eq!(rules.level_for("app::apis", "list"), Some(LogLevel::Warn));
+
+        assert!(LevelRules::parse("app::api=loud").is_err());
+        assert!(LevelRules::parse("app::api").is_err());
+        assert_eq!(LevelRules::new().rule_for("app", "f"), None);
+    }
+
+    #[test]
+    fn test_level_rules_change_at_runtime() {
+        captured("");
+        let rules = LevelRules::parse("my_app::db=trace").unwrap();
+        let aspect = LoggingAspect::new().with_level_rules(rules.clone());
+        let db = joinpoint("rules_query", "my_app::db");
+        let api = joinpoint("rules_handler", "my_app::api");
+
+        aspect.before(&db);
+        aspect.before(&api);
+        rules.set("my_app::db", None);
+        rules.set("my_app::api", Some(LogLevel::Warn));
+        aspect.before(&db);
+        aspect.before(&api);
+
+        let levels: Vec<_> = captured("rules_")
+            .into_iter()
+            .map(|(level, _, msg)| (level, msg.contains("rules_query")))
+            .collect();
+        assert_eq!(
+            levels,
+            vec![
+                (log::Level::Trace, true),
+                (log::Level::Info, false),
+                (log::Level::Warn, false),
+            ]
+        );
+    }
 }
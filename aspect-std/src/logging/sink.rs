@@ -0,0 +1,216 @@
+//! Destinations of the records of the logging and audit aspects.
+//!
+//! A [`LogSink`] receives formatted records with their level and target.
+//! [`LoggingAspect`](crate::LoggingAspect) writes to the [`log`] facade by
+//! default; [`with_sink`](crate::LoggingAspect::with_sink) switches it to
+//! another logging stack without changing the advised code, and
+//! [`LogAuditSink`](crate::audit::LogAuditSink) writes audit records to the
+//! same sinks.
+
+use super::LogLevel;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// Destination of log records.
+pub trait LogSink: Send + Sync {
+    /// Whether a record at `level` for `target` would be written.
+    ///
+    /// Records are only formatted when it is, so that disabled records cost
+    /// this check alone. All are by default.
+    fn enabled(&self, _level: LogLevel, _target: &str) -> bool {
+        true
+    }
+
+    /// Write the record `message` at `level` for `target`.
+    fn write(&self, level: LogLevel, target: &str, message: &str);
+}
+
+/// Sink writing to the [`log`] facade, to whatever logger the application
+/// installed. This is the default sink.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogFacadeSink;
+
+impl LogSink for LogFacadeSink {
+    fn enabled(&self, level: LogLevel, target: &str) -> bool {
+        log::log_enabled!(target: target, log::Level::from(level))
+    }
+
+    fn write(&self, level: LogLevel, target: &str, message: &str) {
+        log::log!(target: target, log::Level::from(level), "{}", message);
+    }
+}
+
+/// Sink printing records to standard error, as `LEVEL target: message`,
+/// e.g. for tools without a logger.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StderrSink;
+
+impl LogSink for StderrSink {
+    fn write(&self, level: LogLevel, target: &str, message: &str) {
+        eprintln!("{:<5} {}: {}", log::Level::from(level), target, message);
+    }
+}
+
+/// One record written to a [`MemorySink`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    /// Level of the record
+    pub level: LogLevel,
+    /// Target of the record, by default the module of the advised function
+    pub target: String,
+    /// The formatted record
+    pub message: String,
+}
+
+/// Sink keeping records in memory, for tests.
+///
+/// Clones share the records, so a clone given to the aspect can be
+/// inspected through the original.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::prelude::*;
+/// use aspect_std::logging::sink::MemorySink;
+/// use aspect_std::LoggingAspect;
+///
+/// let sink = MemorySink::new();
+/// let aspect = LoggingAspect::new().with_sink(sink.clone());
+/// aspect.before(&JoinPoint::new("f", "app", Location { file: "app.rs", line: 1 }));
+/// assert_eq!(sink.records()[0].message, "[ENTRY] f (app.rs:1)");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemorySink {
+    records: Arc<Mutex<Vec<LogRecord>>>,
+}
+
+impl MemorySink {
+    /// Create an empty sink.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The records written so far, oldest first.
+    pub fn records(&self) -> Vec<LogRecord> {
+        self.records.lock().clone()
+    }
+
+    /// Forget the records written so far.
+    pub fn clear(&self) {
+        self.records.lock().clear();
+    }
+}
+
+impl LogSink for MemorySink {
+    fn write(&self, level: LogLevel, target: &str, message: &str) {
+        self.records.lock().push(LogRecord {
+            level,
+            target: target.to_string(),
+            message: message.to_string(),
+        });
+    }
+}
+
+/// Sink emitting records as `tracing` events.
+///
+/// `tracing` needs targets known at compile time, so the target of a
+/// record is in the `log.target` field of its event, where `tracing-log`
+/// puts those of `log` records too. Available with the `tracing` feature.
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingSink;
+
+/// Applies `$event` to the `tracing` level of `$level`, which must be
+/// constant for its macros.
+#[cfg(feature = "tracing")]
+macro_rules! with_tracing_level {
+    ($level:expr, $event:ident!($($args:tt)*)) => {
+        match $level {
+            LogLevel::Trace => ::tracing::$event!(::tracing::Level::TRACE, $($args)*),
+            LogLevel::Debug => ::tracing::$event!(::tracing::Level::DEBUG, $($args)*),
+            LogLevel::Info => ::tracing::$event!(::tracing::Level::INFO, $($args)*),
+            LogLevel::Warn => ::tracing::$event!(::tracing::Level::WARN, $($args)*),
+            LogLevel::Error => ::tracing::$event!(::tracing::Level::ERROR, $($args)*),
+        }
+    };
+}
+
+#[cfg(feature = "tracing")]
+impl LogSink for TracingSink {
+    fn enabled(&self, level: LogLevel, _target: &str) -> bool {
+        with_tracing_level!(level, enabled!())
+    }
+
+    fn write(&self, level: LogLevel, target: &str, message: &str) {
+        with_tracing_level!(level, event!(log.target = target, "{}", message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LoggingAspect;
+    use aspect_core::{Aspect, AspectError, JoinPoint, Location};
+
+    fn joinpoint() -> JoinPoint {
+        JoinPoint::new(
+            "save_user",
+            "app::db",
+            Location {
+                file: "db.rs",
+                line: 3,
+            },
+        )
+    }
+
+    #[test]
+    fn test_memory_sink() {
+        let sink = MemorySink::new();
+        let aspect = LoggingAspect::new()
+            .with_error_level(LogLevel::Warn)
+            .with_sink(sink.clone());
+
+        aspect.before(&joinpoint());
+        aspect.after_error(&joinpoint(), &AspectError::execution("duplicate key"));
+
+        let records = sink.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0],
+            LogRecord {
+                level: LogLevel::Info,
+                target: "app::db".to_string(),
+                message: "[ENTRY] save_user (db.rs:3)".to_string(),
+            }
+        );
+        assert_eq!(records[1].level, LogLevel::Warn);
+        assert!(records[1].message.contains("duplicate key"));
+
+        sink.clear();
+        assert!(sink.records().is_empty());
+    }
+
+    /// Counts the records it is asked about, and wants none.
+    #[derive(Clone, Default)]
+    struct Disabled(Arc<Mutex<usize>>);
+
+    impl LogSink for Disabled {
+        fn enabled(&self, _level: LogLevel, _target: &str) -> bool {
+            *self.0.lock() += 1;
+            false
+        }
+
+        fn write(&self, _level: LogLevel, _target: &str, _message: &str) {
+            panic!("disabled record written");
+        }
+    }
+
+    #[test]
+    fn test_disabled_sink() {
+        let sink = Disabled::default();
+        let aspect = LoggingAspect::new().with_sink(sink.clone());
+        aspect.before(&joinpoint());
+        aspect.after(&joinpoint(), &());
+        assert_eq!(*sink.0.lock(), 2);
+    }
+}
@@ -0,0 +1,414 @@
+//! Periodic upkeep of aspect state, off the call path.
+//!
+//! Some aspects only clean up their state when calls touch it: expired
+//! cache entries stay until looked up or evicted, rate limiters keep a
+//! bucket for every key ever seen, and timing statistics for every function
+//! or label value ever timed. A [`Maintenance`] runs the upkeep of the
+//! aspects given to it every interval, on a background thread, or a tokio
+//! task with the `tokio` feature, and can export statistics to a sink at the
+//! same pace.
+//!
+//! Maintenance is opt-in: nothing runs unless spawned. Shutting it down
+//! runs the jobs one last time, so that the latest statistics are exported,
+//! then the hooks registered with [`on_shutdown`](Maintenance::on_shutdown).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use aspect_std::maintenance::Maintenance;
+//! use aspect_std::sink::StatsdSink;
+//! use aspect_std::{CachingAspect, RateLimitAspect, TimingAspect};
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! let cache = CachingAspect::new().with_ttl(Duration::from_secs(60));
+//! let limiter = RateLimitAspect::new(100, Duration::from_secs(1)).per_function();
+//! let timing = TimingAspect::new();
+//! let sink = Arc::new(StatsdSink::new("127.0.0.1:8125").unwrap());
+//!
+//! let maintenance = Maintenance::every(Duration::from_secs(30))
+//!     .purge_expired(&cache)
+//!     .purge_idle(&limiter)
+//!     .export(&timing, sink)
+//!     .trim_idle(&timing, Duration::from_secs(3600))
+//!     .on_shutdown(|| log::info!("aspect maintenance stopped"))
+//!     .spawn();
+//!
+//! // ... serve requests
+//!
+//! maintenance.shutdown();
+//! ```
+
+use crate::caching::CachingAspect;
+use crate::ratelimit::RateLimitAspect;
+use crate::sink::MetricsSink;
+use crate::time::Duration;
+use crate::timing::TimingAspect;
+use parking_lot::{Condvar, Mutex};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// A maintenance job, run every interval.
+type Job = Box<dyn Fn() + Send + Sync>;
+
+/// A hook run once maintenance stops.
+type Hook = Box<dyn FnOnce() + Send>;
+
+/// Jobs run periodically once [spawned](Self::spawn), see the
+/// [module documentation](self).
+pub struct Maintenance {
+    interval: Duration,
+    jobs: Vec<Job>,
+    hooks: Vec<Hook>,
+}
+
+impl Maintenance {
+    /// No jobs yet, to be run every `interval`.
+    pub fn every(interval: Duration) -> Self {
+        Self {
+            interval,
+            jobs: Vec::new(),
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Remove the expired entries of `cache`.
+    pub fn purge_expired(self, cache: &CachingAspect) -> Self {
+        let cache = cache.clone();
+        self.job(move || {
+            cache.purge_expired();
+        })
+    }
+
+    /// Forget the keys of `limiter` back to the state of new ones.
+    pub fn purge_idle(self, limiter: &RateLimitAspect) -> Self {
+        let limiter = limiter.clone();
+        self.job(move || {
+            limiter.purge_idle();
+        })
+    }
+
+    /// Export the statistics of `timing` to `sink`.
+    pub fn export(self, timing: &TimingAspect, sink: Arc<dyn MetricsSink>) -> Self {
+        let timing = timing.clone();
+        self.job(move || timing.export(&*sink))
+    }
+
+    /// Remove the statistics of `timing` about functions and label values
+    /// not called for `idle`.
+    ///
+    /// Added after [`export`](Self::export), their last statistics are
+    /// exported before they are removed.
+    pub fn trim_idle(self, timing: &TimingAspect, idle: Duration) -> Self {
+        let timing = timing.clone();
+        self.job(move || {
+            timing.trim_idle(idle);
+        })
+    }
+
+    /// Also run `job`, after the jobs added before it.
+    pub fn job(mut self, job: impl Fn() + Send + Sync + 'static) -> Self {
+        self.jobs.push(Box::new(job));
+        self
+    }
+
+    /// Run `hook` once maintenance stops, after the last run of the jobs.
+    pub fn on_shutdown(mut self, hook: impl FnOnce() + Send + 'static) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Run every job once, now.
+    ///
+    /// A job panicking is logged and does not prevent the others from
+    /// running.
+    pub fn run_once(&self) {
+        for job in &self.jobs {
+            if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                log::warn!("aspect maintenance job panicked");
+            }
+        }
+    }
+
+    /// Run the jobs one last time, then the shutdown hooks.
+    fn finish(self) {
+        self.run_once();
+        for hook in self.hooks {
+            hook();
+        }
+    }
+
+    /// Run the jobs every interval on a background thread, until the
+    /// returned handle is shut down or dropped.
+    pub fn spawn(self) -> MaintenanceHandle {
+        let stop = Arc::new(Stop::default());
+        let signal = stop.clone();
+        let thread = thread::Builder::new()
+            .name("aspect-maintenance".to_string())
+            .spawn(move || {
+                while !signal.wait(self.interval) {
+                    self.run_once();
+                }
+                self.finish();
+            })
+            .expect("failed to spawn the aspect maintenance thread");
+
+        MaintenanceHandle {
+            stop,
+            worker: Some(Worker::Thread(thread)),
+        }
+    }
+
+    /// Run the jobs every interval on a task of the current tokio runtime,
+    /// until the returned handle is shut down or dropped. Available with
+    /// the `tokio` feature.
+    ///
+    /// The jobs run on the runtime's threads, so they should be quick. The
+    /// runtime needs its time driver enabled.
+    ///
+    /// # Panics
+    ///
+    /// Outside of a tokio runtime.
+    #[cfg(feature = "tokio")]
+    pub fn spawn_tokio(self) -> MaintenanceHandle {
+        let stop = Arc::new(Stop::default());
+        let signal = stop.clone();
+        let task = tokio::spawn(async move {
+            while !signal.wait_async(self.interval).await {
+                self.run_once();
+            }
+            self.finish();
+        });
+
+        MaintenanceHandle {
+            stop,
+            worker: Some(Worker::Task(task)),
+        }
+    }
+}
+
+/// Signal telling a worker to stop.
+#[derive(Default)]
+struct Stop {
+    stopped: Mutex<bool>,
+    changed: Condvar,
+    #[cfg(feature = "tokio")]
+    notify: tokio::sync::Notify,
+}
+
+impl Stop {
+    fn signal(&self) {
+        *self.stopped.lock() = true;
+        self.changed.notify_all();
+        #[cfg(feature = "tokio")]
+        self.notify.notify_one();
+    }
+
+    /// Wait up to `timeout` for the signal, returning whether it came.
+    fn wait(&self, timeout: Duration) -> bool {
+        let mut stopped = self.stopped.lock();
+        self.changed
+            .wait_while_for(&mut stopped, |stopped| !*stopped, timeout);
+        *stopped
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn wait_async(&self, timeout: Duration) -> bool {
+        // A signal sent while the jobs ran is kept by `notify`
+        let _ = tokio::time::timeout(timeout, self.notify.notified()).await;
+        *self.stopped.lock()
+    }
+}
+
+enum Worker {
+    Thread(JoinHandle<()>),
+    #[cfg(feature = "tokio")]
+    Task(tokio::task::JoinHandle<()>),
+}
+
+/// Handle on spawned [`Maintenance`].
+///
+/// Dropping it stops the maintenance too, without waiting for it.
+pub struct MaintenanceHandle {
+    stop: Arc<Stop>,
+    worker: Option<Worker>,
+}
+
+impl MaintenanceHandle {
+    /// Stop the maintenance, and wait for the last run of its jobs and its
+    /// shutdown hooks on a thread.
+    ///
+    /// A tokio task is only told to stop, see `shutdown_async` to wait for
+    /// it.
+    pub fn shutdown(mut self) {
+        self.stop.signal();
+        if let Some(Worker::Thread(thread)) = self.worker.take() {
+            if thread.join().is_err() {
+                log::warn!("aspect maintenance thread panicked");
+            }
+        }
+    }
+
+    /// Stop the maintenance, and wait for the last run of its jobs and its
+    /// shutdown hooks, whether on a thread or a tokio task.
+    #[cfg(feature = "tokio")]
+    pub async fn shutdown_async(mut self) {
+        self.stop.signal();
+        let panicked = match self.worker.take() {
+            Some(Worker::Task(task)) => task.await.is_err(),
+            Some(Worker::Thread(thread)) => thread.join().is_err(),
+            None => false,
+        };
+        if panicked {
+            log::warn!("aspect maintenance panicked");
+        }
+    }
+}
+
+impl Drop for MaintenanceHandle {
+    fn drop(&mut self) {
+        self.stop.signal();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::ManualClock;
+    use aspect_core::Aspect;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    /// Records the gauges it receives.
+    #[derive(Default)]
+    struct Gauges(Mutex<Vec<(String, f64)>>);
+
+    impl MetricsSink for Gauges {
+        fn count(&self, _name: &str, _value: u64, _tags: &[(&str, &str)]) {}
+
+        fn timing(&self, _name: &str, _duration: Duration, _tags: &[(&str, &str)]) {}
+
+        fn gauge(&self, name: &str, value: f64, tags: &[(&str, &str)]) {
+            if tags.len() == 1 {
+                self.0
+                    .lock()
+                    .push((format!("{}:{}", name, tags[0].1), value));
+            }
+        }
+    }
+
+    #[test]
+    fn test_run_once() {
+        let clock = ManualClock::new();
+        let timing = TimingAspect::new().with_clock(clock.clone());
+        let limiter = RateLimitAspect::with_backend(
+            crate::ratelimit::TokenBucket::new(1, Duration::from_secs(1)).with_clock(clock.clone()),
+        )
+        .per_function();
+        let sink = Arc::new(Gauges::default());
+        let maintenance = Maintenance::every(Duration::from_secs(60))
+            .purge_idle(&limiter)
+            .export(&timing, sink.clone())
+            .trim_idle(&timing, Duration::from_secs(60))
+            .job(|| panic!("failing job"));
+
+        limiter.around(pjp()).unwrap();
+        timing.around(pjp()).unwrap();
+        clock.advance(Duration::from_secs(60));
+
+        maintenance.run_once();
+        assert!(sink.0.lock().contains(&("calls:query".to_string(), 1.0)));
+        assert!(timing.all_stats().is_empty());
+        // The bucket of `query` refilled and was forgotten
+        assert_eq!(limiter.purge_idle(), 0);
+    }
+
+    fn pjp() -> aspect_core::ProceedingJoinPoint<'static> {
+        let ctx = aspect_core::JoinPoint::new(
+            "query",
+            "test",
+            aspect_core::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        );
+        aspect_core::ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn std::any::Any>), ctx)
+    }
+
+    #[test]
+    fn test_spawn_and_shutdown() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let stopped = Arc::new(AtomicUsize::new(0));
+        let handle = Maintenance::every(Duration::from_millis(5))
+            .job({
+                let runs = runs.clone();
+                move || {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .on_shutdown({
+                let (runs, stopped) = (runs.clone(), stopped.clone());
+                move || stopped.store(runs.load(Ordering::SeqCst), Ordering::SeqCst)
+            })
+            .spawn();
+
+        let start = Instant::now();
+        while runs.load(Ordering::SeqCst) < 2 {
+            assert!(start.elapsed() < Duration::from_secs(10), "jobs never ran");
+            thread::sleep(Duration::from_millis(1));
+        }
+        handle.shutdown();
+
+        // The hook ran after the last run of the jobs
+        let runs = runs.load(Ordering::SeqCst);
+        assert!(runs >= 3);
+        assert_eq!(stopped.load(Ordering::SeqCst), runs);
+    }
+
+    #[test]
+    fn test_shutdown_before_first_run() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let handle = Maintenance::every(Duration::from_secs(3600))
+            .job({
+                let runs = runs.clone();
+                move || {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .spawn();
+        handle.shutdown();
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_spawn_tokio() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let hooked = Arc::new(AtomicUsize::new(0));
+        runtime.block_on(async {
+            let handle = Maintenance::every(Duration::from_millis(5))
+                .job({
+                    let runs = runs.clone();
+                    move || {
+                        runs.fetch_add(1, Ordering::SeqCst);
+                    }
+                })
+                .on_shutdown({
+                    let hooked = hooked.clone();
+                    move || {
+                        hooked.fetch_add(1, Ordering::SeqCst);
+                    }
+                })
+                .spawn_tokio();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            handle.shutdown_async().await;
+        });
+        assert!(runs.load(Ordering::SeqCst) >= 2);
+        assert_eq!(hooked.load(Ordering::SeqCst), 1);
+    }
+}
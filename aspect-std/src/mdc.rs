@@ -0,0 +1,408 @@
+//! Contextual fields of the log and audit records of a request, as the
+//! mapped diagnostic context (MDC) of SLF4J.
+//!
+//! Application code [`put`]s key-value fields, such as the tenant or the
+//! user a request is made for, or an outer [`MdcAspect`] derives them from
+//! the arguments of the calls it advises, and the aspects of the crate add
+//! them to the records of the calls nested in them:
+//! [`LoggingAspect`](crate::LoggingAspect) to its log entries, and
+//! [`AuditAspect`](crate::AuditAspect) to the audit trail.
+//!
+//! The fields are kept in a thread-local, so they follow synchronous call
+//! chains. With the `tokio` feature, they are the `fields` of the task
+//! context within a `context::scope` instead, which follow async requests
+//! across threads and into the tasks `context::spawn_with_context` spawns.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use aspect_std::mdc::{self, MdcAspect};
+//! use aspect_std::LoggingAspect;
+//! use aspect_macros::aspect;
+//!
+//! #[aspect(MdcAspect::new().with_arg("tenant"), LoggingAspect::new())]
+//! fn handle(tenant: String, request: Request) -> Response {
+//!     mdc::put("user", request.user());
+//!     render(load_orders(request.order_ids))
+//! }
+//!
+//! // Logged with `tenant=... user=...`
+//! #[aspect(LoggingAspect::new())]
+//! fn load_orders(ids: Vec<u64>) -> Vec<Order> {
+//!     Order::find_all(ids)
+//! }
+//! ```
+
+#[cfg(feature = "tokio")]
+use crate::context::AspectContext;
+#[cfg(feature = "tokio")]
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Arg, Aspect, AspectError, JoinPoint, Precedence, ProceedingJoinPoint};
+use parking_lot::Mutex;
+use std::any::Any;
+use std::cell::RefCell;
+use std::sync::Arc;
+
+/// Key-value fields, in the order they were first put.
+type Fields = Vec<(String, String)>;
+
+thread_local! {
+    static FIELDS: RefCell<Fields> = const { RefCell::new(Vec::new()) };
+}
+
+/// Runs `f` on the fields of the current request: those of the task
+/// context with the `tokio` feature, or else those of this thread.
+fn with_fields<R>(f: impl FnOnce(&mut Fields) -> R) -> R {
+    #[cfg(feature = "tokio")]
+    let f = {
+        let mut f = Some(f);
+        let mut result = None;
+        crate::context::update(|context| result = f.take().map(|f| f(&mut context.fields)));
+        if let Some(result) = result {
+            return result;
+        }
+        f.expect("only taken in a task context")
+    };
+    FIELDS.with(|fields| f(&mut fields.borrow_mut()))
+}
+
+/// Sets `key` to `value` in `fields`, in place when already set.
+fn insert(fields: &mut Fields, key: String, value: String) {
+    match fields.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, v)) => *v = value,
+        None => fields.push((key, value)),
+    }
+}
+
+/// Returns the fields of the current request, in the order they were
+/// first put.
+pub fn current() -> Vec<(String, String)> {
+    with_fields(|fields| fields.clone())
+}
+
+/// Returns the value of the field `key` of the current request, if set.
+pub fn get(key: &str) -> Option<String> {
+    with_fields(|fields| {
+        fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+    })
+}
+
+/// Sets the field `key` of the current request to `value`, until it is
+/// removed or the enclosing [`scope`] or advised call returns.
+pub fn put(key: impl Into<String>, value: impl Into<String>) {
+    let (key, value) = (key.into(), value.into());
+    with_fields(|fields| insert(fields, key, value));
+}
+
+/// Removes the field `key` of the current request, returning its value.
+pub fn remove(key: &str) -> Option<String> {
+    with_fields(|fields| {
+        let i = fields.iter().position(|(k, _)| k == key)?;
+        Some(fields.remove(i).1)
+    })
+}
+
+/// Runs `f` with `fields` added to those of the current request. The
+/// fields of the caller are restored when `f` returns.
+pub fn scope<K, V, R>(fields: impl IntoIterator<Item = (K, V)>, f: impl FnOnce() -> R) -> R
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    let fields: Fields = fields
+        .into_iter()
+        .map(|(k, v)| (k.into(), v.into()))
+        .collect();
+    let _restore = Restore::enter(fields);
+    f()
+}
+
+/// Restores the fields of the caller, also when the call panics.
+struct Restore(Fields);
+
+impl Restore {
+    /// Adds `added` to the fields of the current request, returning the
+    /// guard restoring the previous ones.
+    fn enter(added: Fields) -> Self {
+        Restore(with_fields(|fields| {
+            let previous = fields.clone();
+            for (key, value) in added {
+                insert(fields, key, value);
+            }
+            previous
+        }))
+    }
+}
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = std::mem::take(&mut self.0);
+        with_fields(|fields| *fields = previous);
+    }
+}
+
+/// Derives the value of a field from a call, `None` leaving it unset.
+type FieldFn = Arc<dyn Fn(&JoinPoint) -> Option<String> + Send + Sync>;
+
+/// Aspect setting fields derived from the calls it advises while they run,
+/// so that the records of the calls nested in them carry them.
+///
+/// The fields of the caller are restored when the call returns. The aspect
+/// is outside the security aspects and inside
+/// [`CorrelationAspect`](crate::CorrelationAspect), so that rejected calls
+/// are logged with the fields. `async fn`s, which `#[aspect]` weaves with
+/// `before` and `after` advice only, get the fields in `before`,
+/// remembered in the aspect value until `after`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::mdc::MdcAspect;
+/// use aspect_macros::aspect;
+///
+/// #[aspect(MdcAspect::new()
+///     .with_arg("tenant")
+///     .with_field("order", |ctx| Some(format!("{:?}", ctx.arg("order")?.debug()?))))]
+/// fn place_order(tenant: String, order: Order) -> Result<(), String> {
+///     process(order)
+/// }
+/// ```
+pub struct MdcAspect {
+    fields: Vec<(String, FieldFn)>,
+    restore: Mutex<Option<Restore>>,
+}
+
+impl MdcAspect {
+    /// Create an aspect setting no fields.
+    pub fn new() -> Self {
+        Self {
+            fields: Vec::new(),
+            restore: Mutex::new(None),
+        }
+    }
+
+    /// Set the field `name` to the argument `name`: strings as they are,
+    /// other values in their `Debug` form. Redacted arguments are left out.
+    pub fn with_arg(self, name: &'static str) -> Self {
+        self.with_field(name, move |ctx: &JoinPoint| {
+            ctx.arg(name).and_then(arg_value)
+        })
+    }
+
+    /// Set the field `key` to what `value` derives from the call.
+    pub fn with_field(
+        mut self,
+        key: impl Into<String>,
+        value: impl Fn(&JoinPoint) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.fields.push((key.into(), Arc::new(value)));
+        self
+    }
+
+    /// The fields derived from the call `ctx`.
+    fn derive(&self, ctx: &JoinPoint) -> Fields {
+        self.fields
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), value(ctx)?)))
+            .collect()
+    }
+
+    /// Sets the fields of the call, returning the guard restoring the
+    /// caller's.
+    fn enter(&self, ctx: &JoinPoint) -> Option<Restore> {
+        let fields = self.derive(ctx);
+        (!fields.is_empty()).then(|| Restore::enter(fields))
+    }
+}
+
+fn arg_value(arg: &Arg) -> Option<String> {
+    if arg.is_redacted() {
+        return None;
+    }
+    match (arg.value::<String>(), arg.value::<&str>()) {
+        (Some(value), _) => Some(value.clone()),
+        (_, Some(value)) => Some(value.to_string()),
+        _ => arg.debug().map(|value| format!("{:?}", value)),
+    }
+}
+
+impl Default for MdcAspect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for MdcAspect {
+    fn clone(&self) -> Self {
+        Self {
+            fields: self.fields.clone(),
+            restore: Mutex::new(None),
+        }
+    }
+}
+
+impl Aspect for MdcAspect {
+    fn before(&self, ctx: &JoinPoint) {
+        *self.restore.lock() = self.enter(ctx);
+    }
+
+    fn after(&self, _ctx: &JoinPoint, _result: &dyn Any) {
+        self.restore.lock().take();
+    }
+
+    fn after_error(&self, _ctx: &JoinPoint, _error: &AspectError) {
+        self.restore.lock().take();
+    }
+
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let _restore = self.enter(pjp.context());
+        pjp.proceed()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            if crate::context::current().is_some() {
+                let _restore = self.enter(ctx);
+                return proceed.await;
+            }
+            // A task context of its own, which the fields follow across
+            // threads, starting from those of this thread
+            let mut context = AspectContext::new();
+            context.fields = current();
+            for (key, value) in self.derive(ctx) {
+                insert(&mut context.fields, key, value);
+            }
+            crate::context::scope(context, proceed).await
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        // Outside security aspects, so that rejected calls are logged with
+        // the fields, and inside the correlation aspect
+        Precedence::new(Precedence::SECURITY.order() - 5)
+    }
+
+    fn reads_args(&self) -> bool {
+        !self.fields.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_core::Location;
+
+    fn joinpoint(name: &'static str) -> JoinPoint {
+        JoinPoint::new(
+            name,
+            "test",
+            Location {
+                file: "test.rs",
+                line: 1,
+            },
+        )
+    }
+
+    fn fields(pairs: &[(&str, &str)]) -> Fields {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_put_get_remove_scope() {
+        assert!(current().is_empty());
+        put("tenant", "acme");
+        put("user", "alice");
+        put("tenant", "globex");
+        assert_eq!(
+            current(),
+            fields(&[("tenant", "globex"), ("user", "alice")])
+        );
+
+        scope([("user", "bob"), ("request", "7")], || {
+            assert_eq!(get("user").as_deref(), Some("bob"));
+            assert_eq!(remove("tenant").as_deref(), Some("globex"));
+            assert_eq!(current(), fields(&[("user", "bob"), ("request", "7")]));
+        });
+        assert_eq!(
+            current(),
+            fields(&[("tenant", "globex"), ("user", "alice")])
+        );
+
+        assert_eq!(remove("tenant").as_deref(), Some("globex"));
+        assert_eq!(remove("tenant"), None);
+        remove("user");
+        assert!(current().is_empty());
+    }
+
+    #[test]
+    fn test_fields_from_args() {
+        let aspect = MdcAspect::new()
+            .with_arg("tenant")
+            .with_arg("id")
+            .with_arg("token")
+            .with_field("caller", |ctx| Some(ctx.function_name.to_string()));
+        let ctx = joinpoint("handle").with_args(vec![
+            Arg::new("tenant", &"acme".to_string()),
+            Arg::new("id", &42u64),
+            Arg::new("token", &"secret".to_string()).redact(),
+        ]);
+        let expected = fields(&[("tenant", "acme"), ("id", "42"), ("caller", "handle")]);
+
+        let seen = aspect
+            .around(ProceedingJoinPoint::new(
+                || Ok(Box::new(current())),
+                ctx.clone(),
+            ))
+            .unwrap();
+        assert_eq!(*seen.downcast::<Fields>().unwrap(), expected);
+        assert!(current().is_empty());
+
+        aspect.before(&ctx);
+        assert_eq!(current(), expected);
+        aspect.after_error(&ctx, &AspectError::execution("failed"));
+        assert!(current().is_empty());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_around_async_task_context() {
+        use crate::context;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let aspect = MdcAspect::new().with_arg("tenant");
+        let ctx = joinpoint("handle").with_args(vec![Arg::new("tenant", &"acme".to_string())]);
+
+        // Outside a scope, in a task context of its own
+        let proceed = Box::pin(async { Ok(Box::new(context::current()) as Box<dyn Any>) });
+        let seen = runtime
+            .block_on(aspect.around_async(&ctx, proceed))
+            .unwrap();
+        let seen = seen.downcast::<Option<AspectContext>>().unwrap().unwrap();
+        assert_eq!(seen.fields, fields(&[("tenant", "acme")]));
+
+        // Within one, in its context until the call returns
+        let request = AspectContext::new().with_field("user", "alice");
+        runtime.block_on(context::scope(request, async {
+            let proceed = Box::pin(async { Ok(Box::new(current()) as Box<dyn Any>) });
+            let seen = aspect.around_async(&ctx, proceed).await.unwrap();
+            assert_eq!(
+                *seen.downcast::<Fields>().unwrap(),
+                fields(&[("user", "alice"), ("tenant", "acme")])
+            );
+            assert_eq!(current(), fields(&[("user", "alice")]));
+        }));
+    }
+}
@@ -1,11 +1,15 @@
 //! Metrics collection aspect (counters, gauges, histograms).
 
-use aspect_core::{Aspect, AspectError, ProceedingJoinPoint};
-use parking_lot::Mutex;
+use crate::histogram::{AtomicHistogram, HistogramSnapshot};
+use crate::sink::MetricsSink;
+use crate::time::{self, Duration};
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, Precedence, ProceedingJoinPoint};
+use parking_lot::RwLock;
 use std::any::Any;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
 
 /// Metrics aspect for collecting function call statistics.
 ///
@@ -24,55 +28,143 @@ use std::time::{Duration, Instant};
 ///
 /// // Print metrics
 /// metrics.print();
+///
+/// // Or read them programmatically
+/// let p99 = metrics.snapshot("api_handler").unwrap().p99;
 /// ```
+///
+/// Durations are recorded in a fixed-memory [`AtomicHistogram`] per function,
+/// from which [`snapshot`](Self::snapshot) reports p50/p90/p95/p99. Only the
+/// last 1024 raw durations are kept, returned by
+/// [`get_histogram`](Self::get_histogram).
+///
+/// With the `prometheus` feature, the collected counters and durations can be
+/// exported with `MetricsAspect::register` or `MetricsAspect::encode`.
+///
+/// The metrics of each function are looked up in a map only written the
+/// first time the function is called. Call counts and durations are then
+/// recorded with atomics, so that calls from many threads never wait for one
+/// another.
 #[derive(Clone)]
 pub struct MetricsAspect {
-    counters: Arc<Mutex<HashMap<String, u64>>>,
-    histograms: Arc<Mutex<HashMap<String, Vec<Duration>>>>,
+    functions: Arc<MetricsByFunction>,
+    sink: Option<Arc<dyn MetricsSink>>,
+    correlation_tag: bool,
+}
+
+/// The metrics of every function called, by name.
+type MetricsByFunction = RwLock<HashMap<String, Arc<FunctionMetrics>>>;
+
+/// Number of raw durations kept per function.
+const RECENT_DURATIONS: usize = 1024;
+
+/// The metrics of one function.
+struct FunctionMetrics {
+    calls: AtomicU64,
+    distribution: AtomicHistogram,
+    /// The last durations in nanoseconds, overwritten in a ring.
+    recent: Box<[AtomicU64]>,
+    /// Number of durations ever written to `recent`.
+    recorded: AtomicU64,
 }
 
 impl MetricsAspect {
     /// Create a new metrics aspect.
     pub fn new() -> Self {
         Self {
-            counters: Arc::new(Mutex::new(HashMap::new())),
-            histograms: Arc::new(Mutex::new(HashMap::new())),
+            functions: Arc::new(RwLock::new(HashMap::new())),
+            sink: None,
+            correlation_tag: false,
         }
     }
 
+    /// Also push measurements to `sink`: a `calls` counter, an `errors`
+    /// counter and a `duration` timing, tagged with the function name.
+    pub fn with_sink(mut self, sink: Arc<dyn MetricsSink>) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+
+    /// Also tag the measurements pushed to the sink with the
+    /// [correlation id](crate::correlation) of the request, as
+    /// `correlation_id`, for backends keeping exemplars or traces of
+    /// individual requests. Each request being a new tag value, do not
+    /// enable it for backends aggregating by tag, such as plain StatsD.
+    pub fn with_correlation_tag(mut self) -> Self {
+        self.correlation_tag = true;
+        self
+    }
+
+    /// The metrics of `function_name`, created the first time it is called.
+    fn function(&self, function_name: &str) -> Arc<FunctionMetrics> {
+        if let Some(metrics) = self.functions.read().get(function_name) {
+            return Arc::clone(metrics);
+        }
+        Arc::clone(
+            self.functions
+                .write()
+                .entry(function_name.to_string())
+                .or_default(),
+        )
+    }
+
     /// Get call count for a function.
     pub fn get_count(&self, function_name: &str) -> u64 {
-        self.counters.lock().get(function_name).copied().unwrap_or(0)
+        self.functions
+            .read()
+            .get(function_name)
+            .map_or(0, |metrics| metrics.calls.load(Ordering::Relaxed))
     }
 
-    /// Get duration histogram for a function.
+    /// Get the durations of the last calls of a function, up to 1024, oldest
+    /// first.
     pub fn get_histogram(&self, function_name: &str) -> Vec<Duration> {
-        self.histograms
-            .lock()
+        self.functions
+            .read()
             .get(function_name)
-            .cloned()
+            .map(|metrics| metrics.recent())
             .unwrap_or_default()
     }
 
+    /// Get the duration distribution of a function, including percentiles.
+    pub fn snapshot(&self, function_name: &str) -> Option<HistogramSnapshot> {
+        self.functions
+            .read()
+            .get(function_name)
+            .and_then(|metrics| metrics.distribution())
+    }
+
+    /// Get the duration distributions of all functions.
+    pub fn snapshots(&self) -> HashMap<String, HistogramSnapshot> {
+        self.functions
+            .read()
+            .iter()
+            .filter_map(|(name, metrics)| Some((name.clone(), metrics.distribution()?)))
+            .collect()
+    }
+
     /// Print all metrics.
     pub fn print(&self) {
         println!("\n=== Metrics ===");
 
-        let counters = self.counters.lock();
+        let functions = self.functions.read();
         println!("\nCall Counts:");
-        for (name, count) in counters.iter() {
-            println!("  {}: {}", name, count);
+        for (name, metrics) in functions.iter() {
+            println!("  {}: {}", name, metrics.calls.load(Ordering::Relaxed));
         }
 
-        drop(counters);
-
-        let histograms = self.histograms.lock();
         println!("\nDuration Histograms:");
-        for (name, durations) in histograms.iter() {
-            if !durations.is_empty() {
-                let total: Duration = durations.iter().sum();
-                let avg = total / durations.len() as u32;
-                println!("  {}: avg={:?}, count={}", name, avg, durations.len());
+        for (name, metrics) in functions.iter() {
+            if let Some(snapshot) = metrics.distribution() {
+                println!(
+                    "  {}: avg={:?}, p50={:?}, p95={:?}, p99={:?}, count={}",
+                    name,
+                    snapshot.mean(),
+                    snapshot.p50,
+                    snapshot.p95,
+                    snapshot.p99,
+                    snapshot.count
+                );
             }
         }
         println!();
@@ -80,8 +172,74 @@ impl MetricsAspect {
 
     /// Clear all metrics.
     pub fn clear(&self) {
-        self.counters.lock().clear();
-        self.histograms.lock().clear();
+        self.functions.write().clear();
+    }
+
+    /// Records a call of `function_name` that took `duration`.
+    fn complete(
+        &self,
+        function_name: &str,
+        metrics: &FunctionMetrics,
+        duration: Duration,
+        result: &Result<Box<dyn Any>, AspectError>,
+    ) {
+        if let Some(sink) = &self.sink {
+            let correlation_id = self
+                .correlation_tag
+                .then(crate::correlation::current)
+                .flatten();
+            let tags = [
+                ("function", function_name),
+                (
+                    "correlation_id",
+                    correlation_id.as_deref().unwrap_or_default(),
+                ),
+            ];
+            let tags = &tags[..if correlation_id.is_some() { 2 } else { 1 }];
+            sink.count("calls", 1, tags);
+            if result.is_err() {
+                sink.count("errors", 1, tags);
+            }
+            sink.timing("duration", duration, tags);
+        }
+        metrics.record(duration);
+    }
+}
+
+impl FunctionMetrics {
+    fn record(&self, duration: Duration) {
+        self.distribution.record(duration);
+        let slot = self.recorded.fetch_add(1, Ordering::Relaxed) as usize % RECENT_DURATIONS;
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        self.recent[slot].store(nanos, Ordering::Relaxed);
+    }
+
+    /// The distribution of the durations, if any was recorded.
+    fn distribution(&self) -> Option<HistogramSnapshot> {
+        let distribution = self.distribution.load();
+        (!distribution.is_empty()).then(|| distribution.snapshot())
+    }
+
+    /// The last durations recorded, oldest first.
+    fn recent(&self) -> Vec<Duration> {
+        let recorded = self.recorded.load(Ordering::Relaxed) as usize;
+        let start = recorded.saturating_sub(RECENT_DURATIONS);
+        (start..recorded)
+            .map(|n| {
+                Duration::from_nanos(self.recent[n % RECENT_DURATIONS].load(Ordering::Relaxed))
+            })
+            .collect()
+    }
+}
+
+impl Default for FunctionMetrics {
+    fn default() -> Self {
+        Self {
+            calls: AtomicU64::new(0),
+            distribution: AtomicHistogram::new(),
+            recent: (0..RECENT_DURATIONS).map(|_| AtomicU64::new(0)).collect(),
+            recorded: AtomicU64::new(0),
+        }
     }
 }
 
@@ -93,29 +251,205 @@ impl Default for MetricsAspect {
 
 impl Aspect for MetricsAspect {
     fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
-        let function_name = pjp.context().function_name.to_string();
-        let start = Instant::now();
-
-        // Increment counter
-        *self.counters.lock().entry(function_name.clone()).or_insert(0) += 1;
+        let function_name = pjp.context().function_name;
+        let start = time::now();
+        let metrics = self.function(function_name);
+        metrics.calls.fetch_add(1, Ordering::Relaxed);
 
         let result = pjp.proceed();
 
-        // Record duration
-        let duration = start.elapsed();
-        self.histograms
-            .lock()
-            .entry(function_name)
-            .or_insert_with(Vec::new)
-            .push(duration);
-
+        self.complete(function_name, &metrics, time::elapsed(start), &result);
         result
     }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            let start = time::now();
+            let metrics = self.function(ctx.function_name);
+            metrics.calls.fetch_add(1, Ordering::Relaxed);
+
+            let result = proceed.await;
+
+            self.complete(ctx.function_name, &metrics, time::elapsed(start), &result);
+            result
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::OBSERVABILITY
+    }
+}
+
+#[cfg(feature = "prometheus")]
+mod prometheus_export {
+    use super::{MetricsAspect, MetricsByFunction};
+    use crate::histogram::Histogram;
+    use prometheus::core::{Collector, Desc};
+    use prometheus::proto::{Bucket, LabelPair, Metric, MetricFamily, MetricType};
+    use prometheus::{HistogramOpts, IntCounterVec, Opts, Registry, TextEncoder};
+    use std::collections::HashMap;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Prometheus collector reading the metrics of a [`MetricsAspect`].
+    ///
+    /// Values are read at scrape time, so the collector stays in sync with the
+    /// aspect without any extra bookkeeping on the call path. Bucket counts
+    /// come from the histogram of the aspect, so a duration within about 1.6%
+    /// of a bucket bound may be counted in the next bucket. It exports:
+    ///
+    /// - `aspect_calls_total{function}`: number of calls
+    /// - `aspect_call_duration_seconds{function}`: call duration histogram
+    #[derive(Clone)]
+    pub struct PrometheusCollector {
+        functions: Arc<MetricsByFunction>,
+        calls_opts: Opts,
+        duration_opts: HistogramOpts,
+        descs: Vec<Desc>,
+    }
+
+    impl PrometheusCollector {
+        fn new(metrics: &MetricsAspect) -> Self {
+            let calls_opts =
+                Opts::new("aspect_calls_total", "Number of calls to advised functions");
+            let duration_opts = HistogramOpts::new(
+                "aspect_call_duration_seconds",
+                "Duration of calls to advised functions",
+            );
+            let descs = [
+                (&calls_opts.name, &calls_opts.help),
+                (
+                    &duration_opts.common_opts.name,
+                    &duration_opts.common_opts.help,
+                ),
+            ]
+            .into_iter()
+            .map(|(name, help)| {
+                Desc::new(
+                    name.clone(),
+                    help.clone(),
+                    vec!["function".to_string()],
+                    HashMap::new(),
+                )
+                .expect("valid metric descriptor")
+            })
+            .collect();
+
+            Self {
+                functions: metrics.functions.clone(),
+                calls_opts,
+                duration_opts,
+                descs,
+            }
+        }
+
+        /// Use custom histogram buckets (in seconds).
+        pub fn with_buckets(mut self, buckets: Vec<f64>) -> Self {
+            self.duration_opts = self.duration_opts.buckets(buckets);
+            self
+        }
+
+        /// The duration histogram of `function`, in the buckets of the
+        /// collector.
+        fn duration_metric(&self, function: &str, histogram: &Histogram) -> Metric {
+            let buckets = self
+                .duration_opts
+                .buckets
+                .iter()
+                .map(|&bound| {
+                    let limit =
+                        Duration::try_from_secs_f64(bound.max(0.0)).unwrap_or(Duration::MAX);
+                    let mut bucket = Bucket::default();
+                    bucket.set_upper_bound(bound);
+                    bucket.set_cumulative_count(histogram.count_at_most(limit));
+                    bucket
+                })
+                .collect();
+            let mut summary = prometheus::proto::Histogram::default();
+            summary.set_sample_count(histogram.count());
+            summary.set_sample_sum(histogram.snapshot().sum.as_secs_f64());
+            summary.set_bucket(buckets);
+
+            let mut label = LabelPair::default();
+            label.set_name("function".to_string());
+            label.set_value(function.to_string());
+            let mut metric = Metric::from_label(vec![label]);
+            metric.set_histogram(summary);
+            metric
+        }
+    }
+
+    impl Collector for PrometheusCollector {
+        fn desc(&self) -> Vec<&Desc> {
+            self.descs.iter().collect()
+        }
+
+        fn collect(&self) -> Vec<MetricFamily> {
+            let calls = IntCounterVec::new(self.calls_opts.clone(), &["function"])
+                .expect("valid counter options");
+            let mut durations = Vec::new();
+            for (name, metrics) in self.functions.read().iter() {
+                calls
+                    .with_label_values(&[name.as_str()])
+                    .inc_by(metrics.calls.load(Ordering::Relaxed));
+                let histogram = metrics.distribution.load();
+                if !histogram.is_empty() {
+                    durations.push(self.duration_metric(name, &histogram));
+                }
+            }
+
+            let mut families = calls.collect();
+            if !durations.is_empty() {
+                let mut family = MetricFamily::default();
+                family.set_name(self.duration_opts.common_opts.name.clone());
+                family.set_help(self.duration_opts.common_opts.help.clone());
+                family.set_field_type(MetricType::HISTOGRAM);
+                family.set_metric(durations);
+                families.push(family);
+            }
+            families
+        }
+    }
+
+    impl MetricsAspect {
+        /// Create a Prometheus collector backed by this aspect's metrics.
+        pub fn prometheus_collector(&self) -> PrometheusCollector {
+            PrometheusCollector::new(self)
+        }
+
+        /// Register this aspect's metrics with a Prometheus registry.
+        ///
+        /// # Example
+        ///
+        /// ```rust,ignore
+        /// let registry = prometheus::Registry::new();
+        /// METRICS.register(&registry)?;
+        /// ```
+        pub fn register(&self, registry: &Registry) -> prometheus::Result<()> {
+            registry.register(Box::new(self.prometheus_collector()))
+        }
+
+        /// Encode the current metrics in the Prometheus text exposition format.
+        pub fn encode(&self) -> String {
+            TextEncoder::new()
+                .encode_to_string(&self.prometheus_collector().collect())
+                .expect("text encoding of collected metrics")
+        }
+    }
 }
 
+#[cfg(feature = "prometheus")]
+pub use prometheus_export::PrometheusCollector;
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use parking_lot::Mutex;
 
     #[test]
     fn test_metrics_aspect() {
@@ -129,9 +463,178 @@ mod tests {
     fn test_metrics_increment() {
         let metrics = MetricsAspect::new();
 
-        *metrics.counters.lock().entry("test".to_string()).or_insert(0) += 1;
-        *metrics.counters.lock().entry("test".to_string()).or_insert(0) += 1;
+        let test = metrics.function("test");
+        test.calls.fetch_add(1, Ordering::Relaxed);
+        test.calls.fetch_add(1, Ordering::Relaxed);
 
         assert_eq!(metrics.get_count("test"), 2);
     }
+
+    #[test]
+    fn test_recent_durations_are_capped() {
+        let metrics = MetricsAspect::new();
+        let test = metrics.function("test");
+        for ms in 0..1500 {
+            test.record(Duration::from_millis(ms));
+        }
+
+        let recent = metrics.get_histogram("test");
+        assert_eq!(recent.len(), RECENT_DURATIONS);
+        assert_eq!(recent[0], Duration::from_millis(1500 - 1024));
+        assert_eq!(recent[1023], Duration::from_millis(1499));
+        assert_eq!(metrics.snapshot("test").unwrap().count, 1500);
+    }
+
+    #[test]
+    fn test_concurrent_calls() {
+        let metrics = MetricsAspect::new();
+        let call = |function_name: &'static str| {
+            let ctx = aspect_core::JoinPoint::new(
+                function_name,
+                "test",
+                aspect_core::Location {
+                    file: "test.rs",
+                    line: 1,
+                },
+            );
+            let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn Any>), ctx);
+            metrics.around(pjp).unwrap();
+        };
+        std::thread::scope(|scope| {
+            for function_name in ["load", "save", "load", "save"] {
+                scope.spawn(move || {
+                    for _ in 0..100 {
+                        call(function_name);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(metrics.get_count("load"), 200);
+        assert_eq!(metrics.get_count("save"), 200);
+        assert_eq!(metrics.get_histogram("save").len(), 200);
+        assert_eq!(metrics.snapshot("load").unwrap().count, 200);
+    }
+
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<String>>);
+
+    impl MetricsSink for RecordingSink {
+        fn count(&self, name: &str, value: u64, tags: &[(&str, &str)]) {
+            self.0.lock().push(format!("{}={} {:?}", name, value, tags));
+        }
+
+        fn timing(&self, name: &str, _duration: Duration, tags: &[(&str, &str)]) {
+            self.0.lock().push(format!("{} {:?}", name, tags));
+        }
+
+        fn gauge(&self, _name: &str, _value: f64, _tags: &[(&str, &str)]) {}
+    }
+
+    #[test]
+    fn test_metrics_sink() {
+        let sink = Arc::new(RecordingSink::default());
+        let metrics = MetricsAspect::new().with_sink(sink.clone());
+        let ctx = aspect_core::JoinPoint::new(
+            "lookup",
+            "test",
+            aspect_core::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        );
+
+        let pjp = ProceedingJoinPoint::new(|| Err(AspectError::execution("miss")), ctx.clone());
+        assert!(metrics.around(pjp).is_err());
+
+        assert_eq!(
+            *sink.0.lock(),
+            vec![
+                "calls=1 [(\"function\", \"lookup\")]",
+                "errors=1 [(\"function\", \"lookup\")]",
+                "duration [(\"function\", \"lookup\")]",
+            ]
+        );
+        assert_eq!(metrics.get_count("lookup"), 1);
+
+        // Tagged with the correlation id when asked to
+        let sink = Arc::new(RecordingSink::default());
+        let metrics = MetricsAspect::new()
+            .with_sink(sink.clone())
+            .with_correlation_tag();
+        let call = || {
+            let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn Any>), ctx.clone());
+            metrics.around(pjp).unwrap();
+        };
+        call();
+        crate::correlation::scope("req-1", call);
+        assert_eq!(
+            sink.0.lock()[2],
+            "calls=1 [(\"function\", \"lookup\"), (\"correlation_id\", \"req-1\")]"
+        );
+        assert_eq!(sink.0.lock()[0], "calls=1 [(\"function\", \"lookup\")]");
+    }
+
+    #[test]
+    fn test_snapshot_percentiles() {
+        let metrics = MetricsAspect::new();
+        assert!(metrics.snapshot("handler").is_none());
+
+        for _ in 0..10 {
+            let ctx = aspect_core::JoinPoint::new(
+                "handler",
+                "test",
+                aspect_core::Location {
+                    file: "test.rs",
+                    line: 1,
+                },
+            );
+            let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn Any>), ctx);
+            metrics.around(pjp).unwrap();
+        }
+
+        let snapshot = metrics.snapshot("handler").unwrap();
+        assert_eq!(snapshot.count, 10);
+        assert!(snapshot.min <= snapshot.p50 && snapshot.p50 <= snapshot.p99);
+        assert!(snapshot.p99 <= snapshot.max);
+        assert_eq!(metrics.snapshots().len(), 1);
+
+        metrics.clear();
+        assert!(metrics.snapshot("handler").is_none());
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn test_prometheus_encode() {
+        let metrics = MetricsAspect::new();
+        let handler = metrics.function("handler");
+        handler.calls.store(3, Ordering::Relaxed);
+        for _ in 0..3 {
+            handler.record(Duration::from_millis(20));
+        }
+
+        let text = metrics.encode();
+        assert!(text.contains("aspect_calls_total{function=\"handler\"} 3"));
+        assert!(text.contains("aspect_call_duration_seconds_count{function=\"handler\"} 3"));
+        assert!(text
+            .contains("aspect_call_duration_seconds_bucket{function=\"handler\",le=\"0.025\"} 3"));
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn test_prometheus_registry() {
+        let metrics = MetricsAspect::new();
+        let registry = prometheus::Registry::new();
+        metrics.register(&registry).unwrap();
+
+        metrics
+            .function("scraped")
+            .calls
+            .store(1, Ordering::Relaxed);
+
+        let families = registry.gather();
+        assert!(families.iter().any(|f| f.name() == "aspect_calls_total"));
+        // Registering the same metric names twice is rejected by the registry
+        assert!(metrics.register(&registry).is_err());
+    }
 }
@@ -0,0 +1,409 @@
+//! Aspects as middleware of any framework.
+//!
+//! [`Middleware`] applies an aspect to the requests a handler gets, for
+//! frameworks without an adapter of their own: the framework calls it with
+//! each request and the next handler, and the aspect sees a call of a
+//! function named after the middleware, whose result is the response. See
+//! the `tower`, `http` and `actix` modules, with the `tower`, `http` and
+//! `actix-web` features, for the adapters of those frameworks.
+
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, Location, ProceedingJoinPoint};
+use std::any::Any;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+/// Turns an aspect into a [`Middleware`].
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_std::middleware::IntoMiddleware;
+/// use aspect_std::TimingAspect;
+///
+/// let timing = TimingAspect::new().into_middleware("greet");
+/// let response = timing.call("world", |name| format!("Hello, {}!", name));
+/// assert_eq!(response.unwrap(), "Hello, world!");
+/// ```
+pub trait IntoMiddleware: Aspect + Sized {
+    /// Apply the aspect to requests, as calls of the function `name`.
+    #[track_caller]
+    fn into_middleware(self, name: &'static str) -> Middleware<Self> {
+        Middleware::shared(Arc::new(self), name)
+    }
+}
+
+impl<A: Aspect> IntoMiddleware for A {}
+
+/// Middleware applying an aspect to the requests of a handler.
+///
+/// Requests are join points of the function `name`, in the module
+/// `middleware`, located where the middleware was created. The aspect can
+/// keep a request from reaching the handler by failing, as
+/// [`RateLimitAspect`] and [`CircuitBreakerAspect`] do; the caller then
+/// gets the [`AspectError`]. It can also replace the response, with one of
+/// the same type, as [`FallbackAspect`] does. Clones share the aspect.
+///
+/// [`call`](Self::call) and [`call_async`](Self::call_async) take handlers
+/// that cannot fail; [`try_call`](Self::try_call) and
+/// [`try_call_async`](Self::try_call_async) take handlers returning a
+/// `Result`, whose errors the aspect sees as failures, e.g. to open a
+/// circuit.
+///
+/// [`RateLimitAspect`]: crate::RateLimitAspect
+/// [`CircuitBreakerAspect`]: crate::CircuitBreakerAspect
+/// [`FallbackAspect`]: crate::FallbackAspect
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_std::middleware::{Middleware, MiddlewareError};
+/// use aspect_std::RateLimitAspect;
+/// use std::time::Duration;
+///
+/// struct Request { path: String }
+///
+/// fn route(request: Request) -> Result<String, String> {
+///     match request.path.as_str() {
+///         "/" => Ok("home".to_string()),
+///         path => Err(format!("no route to {}", path)),
+///     }
+/// }
+///
+/// let limit = Middleware::new(RateLimitAspect::new(1, Duration::from_secs(60)), "router");
+/// let home = || Request { path: "/".to_string() };
+///
+/// assert_eq!(limit.try_call(home(), route).unwrap(), "home");
+/// // Over the limit: the handler is not called
+/// assert!(matches!(limit.try_call(home(), route), Err(MiddlewareError::Rejected(_))));
+/// ```
+pub struct Middleware<A: ?Sized> {
+    aspect: Arc<A>,
+    name: &'static str,
+    location: Location,
+}
+
+impl<A: Aspect> Middleware<A> {
+    /// Apply `aspect` to requests, as calls of the function `name`.
+    #[track_caller]
+    pub fn new(aspect: A, name: &'static str) -> Self {
+        Self::shared(Arc::new(aspect), name)
+    }
+}
+
+impl<A: Aspect + ?Sized> Middleware<A> {
+    /// Apply an aspect shared with other code, e.g. an `Arc<dyn Aspect>`,
+    /// to requests, as calls of the function `name`.
+    #[track_caller]
+    pub fn shared(aspect: Arc<A>, name: &'static str) -> Self {
+        let caller = std::panic::Location::caller();
+        Self {
+            aspect,
+            name,
+            location: Location {
+                file: caller.file(),
+                line: caller.line(),
+            },
+        }
+    }
+
+    /// The aspect applied.
+    pub fn aspect(&self) -> &Arc<A> {
+        &self.aspect
+    }
+
+    /// Pass `request` to `next` through the aspect.
+    ///
+    /// # Errors
+    ///
+    /// The error of the aspect, if it rejects the request.
+    pub fn call<Req, Res: 'static>(
+        &self,
+        request: Req,
+        next: impl FnOnce(Req) -> Res,
+    ) -> Result<Res, AspectError> {
+        self.try_call(request, |request| Ok::<_, AspectError>(next(request)))
+            .map_err(MiddlewareError::into_aspect_error)
+    }
+
+    /// `next` as a handler passing its requests through the aspect, for
+    /// frameworks composing handlers.
+    pub fn wrap<'a, Req, Res: 'static>(
+        &'a self,
+        next: impl FnOnce(Req) -> Res + 'a,
+    ) -> impl FnOnce(Req) -> Result<Res, AspectError> + 'a {
+        move |request| self.call(request, next)
+    }
+
+    /// Pass `request` to `next` through the aspect, which sees the errors
+    /// of `next` as failures.
+    ///
+    /// # Errors
+    ///
+    /// The error of `next`, unless the aspect replaced it with a response,
+    /// or that of the aspect, if it rejects the request.
+    pub fn try_call<Req, Res: 'static, E: fmt::Display>(
+        &self,
+        request: Req,
+        next: impl FnOnce(Req) -> Result<Res, E>,
+    ) -> Result<Res, MiddlewareError<E>> {
+        let ctx = self.join_point();
+        let mut handler_error = None;
+        let mut next = Some(move || next(request));
+        let mut proceed = || {
+            let next = next.take().expect("proceeded more than once");
+            failure(next(), &mut handler_error)
+        };
+        let pjp = ProceedingJoinPoint::borrowed(&mut proceed, ctx.clone());
+        let result = self.aspect.around(pjp);
+        response(&ctx, result, handler_error)
+    }
+
+    /// Pass `request` to the asynchronous handler `next` through the
+    /// aspect.
+    ///
+    /// # Errors
+    ///
+    /// The error of the aspect, if it rejects the request.
+    pub async fn call_async<Req, Res, Fut>(
+        &self,
+        request: Req,
+        next: impl FnOnce(Req) -> Fut,
+    ) -> Result<Res, AspectError>
+    where
+        Res: Send + 'static,
+        Fut: Future<Output = Res> + Send,
+    {
+        let handler = next(request);
+        self.advise_async(async move { Ok::<_, AspectError>(handler.await) })
+            .await
+            .map_err(MiddlewareError::into_aspect_error)
+    }
+
+    /// Pass `request` to the asynchronous handler `next` through the
+    /// aspect, which sees the errors of `next` as failures.
+    ///
+    /// # Errors
+    ///
+    /// The error of `next`, unless the aspect replaced it with a response,
+    /// or that of the aspect, if it rejects the request.
+    pub async fn try_call_async<Req, Res, E, Fut>(
+        &self,
+        request: Req,
+        next: impl FnOnce(Req) -> Fut,
+    ) -> Result<Res, MiddlewareError<E>>
+    where
+        Res: Send + 'static,
+        E: fmt::Display + Send,
+        Fut: Future<Output = Result<Res, E>> + Send,
+    {
+        self.advise_async(next(request)).await
+    }
+
+    /// Run `handler` through the aspect. Futures being lazy, it only runs
+    /// if the aspect proceeds.
+    async fn advise_async<Res, E>(
+        &self,
+        handler: impl Future<Output = Result<Res, E>> + Send,
+    ) -> Result<Res, MiddlewareError<E>>
+    where
+        Res: Send + 'static,
+        E: fmt::Display + Send,
+    {
+        let ctx = self.join_point();
+        let mut handler_error = None;
+        let slot = &mut handler_error;
+        let proceed: BoxFuture<'_, _> = Box::pin(async move { failure(handler.await, slot) });
+        let result = self.aspect.around_async(&ctx, proceed).await;
+        response(&ctx, result, handler_error)
+    }
+
+    fn join_point(&self) -> JoinPoint {
+        JoinPoint::new(self.name, "middleware", self.location)
+    }
+}
+
+impl<A: ?Sized> Clone for Middleware<A> {
+    fn clone(&self) -> Self {
+        Self {
+            aspect: self.aspect.clone(),
+            name: self.name,
+            location: self.location,
+        }
+    }
+}
+
+/// The error of a [`Middleware`] calling a handler that can fail.
+#[derive(Debug)]
+pub enum MiddlewareError<E> {
+    /// The aspect rejected the request, or failed
+    Rejected(AspectError),
+
+    /// The handler failed
+    Handler(E),
+}
+
+impl<E> MiddlewareError<E> {
+    /// The error of the aspect, if it rejected the request.
+    pub fn rejection(&self) -> Option<&AspectError> {
+        match self {
+            Self::Rejected(error) => Some(error),
+            Self::Handler(_) => None,
+        }
+    }
+}
+
+impl MiddlewareError<AspectError> {
+    fn into_aspect_error(self) -> AspectError {
+        match self {
+            Self::Rejected(error) | Self::Handler(error) => error,
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for MiddlewareError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rejected(error) => write!(f, "{}", error),
+            Self::Handler(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for MiddlewareError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Rejected(error) => Some(error),
+            Self::Handler(error) => Some(error),
+        }
+    }
+}
+
+/// The result of a handler for the aspect, keeping its error aside.
+fn failure<Res: 'static, E: fmt::Display>(
+    result: Result<Res, E>,
+    handler_error: &mut Option<E>,
+) -> Result<Box<dyn Any>, AspectError> {
+    match result {
+        Ok(response) => Ok(Box::new(response)),
+        Err(error) => {
+            let aspect_error = AspectError::execution(error.to_string());
+            *handler_error = Some(error);
+            Err(aspect_error)
+        }
+    }
+}
+
+/// The response of the aspect for the caller.
+fn response<Res: 'static, E>(
+    ctx: &JoinPoint,
+    result: Result<Box<dyn Any>, AspectError>,
+    handler_error: Option<E>,
+) -> Result<Res, MiddlewareError<E>> {
+    match result {
+        Ok(response) => match response.downcast::<Res>() {
+            Ok(response) => Ok(*response),
+            Err(_) => Err(MiddlewareError::Rejected(AspectError::execution(format!(
+                "aspect replaced the response of {} with another type",
+                ctx.function_name
+            )))),
+        },
+        // The error of the handler, as is
+        Err(error) => match handler_error {
+            Some(error) => Err(MiddlewareError::Handler(error)),
+            None => Err(MiddlewareError::Rejected(error)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CircuitBreakerAspect, FallbackAspect, TimingAspect};
+    use std::task::{Context, Poll, Waker};
+    use std::time::Duration;
+
+    /// Polls `future` once, as all the futures of these tests are ready.
+    fn now<F: Future>(future: F) -> F::Output {
+        let mut cx = Context::from_waker(Waker::noop());
+        match std::pin::pin!(future).poll(&mut cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future not ready"),
+        }
+    }
+
+    fn halve(n: u32) -> Result<u32, String> {
+        match n % 2 {
+            0 => Ok(n / 2),
+            _ => Err(format!("odd request {}", n)),
+        }
+    }
+
+    #[test]
+    fn test_call_and_wrap() {
+        let timing = Arc::new(TimingAspect::new());
+        let middleware = Middleware::shared(timing.clone(), "double");
+
+        assert_eq!(middleware.call(21, |n| n * 2).unwrap(), 42);
+        let handler = middleware.wrap(|n: u32| n * 2);
+        assert_eq!(handler(4).unwrap(), 8);
+        assert_eq!(timing.get_stats("double").unwrap().count, 2);
+    }
+
+    #[test]
+    fn test_try_call_keeps_handler_error() {
+        let middleware = TimingAspect::new().into_middleware("halve");
+
+        assert_eq!(middleware.try_call(4, halve).unwrap(), 2);
+        match middleware.try_call(3, halve) {
+            Err(MiddlewareError::Handler(error)) => assert_eq!(error, "odd request 3"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_circuit_breaker_rejects() {
+        let middleware =
+            CircuitBreakerAspect::new(2, Duration::from_secs(60)).into_middleware("halve");
+
+        assert!(middleware.try_call(1, halve).is_err());
+        assert!(middleware.try_call(3, halve).is_err());
+        // Open: even requests fail fast without reaching the handler
+        let error = middleware
+            .try_call(2, |_| -> Result<u32, String> { panic!("must not run") })
+            .unwrap_err();
+        assert!(error.rejection().is_some());
+        assert!(error.to_string().contains("OPEN"), "{}", error);
+    }
+
+    #[test]
+    fn test_fallback_replaces_response() {
+        let middleware = FallbackAspect::value(0u32).into_middleware("halve");
+        assert_eq!(middleware.try_call(3, halve).unwrap(), 0);
+
+        let wrong_type = FallbackAspect::value("zero").into_middleware("halve");
+        let error = wrong_type.try_call(3, halve).unwrap_err();
+        assert!(
+            error.to_string().contains("replaced the response of halve"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_async() {
+        let timing = Arc::new(TimingAspect::new());
+        let middleware = Middleware::shared(timing.clone(), "halve");
+
+        assert_eq!(
+            now(middleware.call_async(21, |n| async move { n * 2 })).unwrap(),
+            42
+        );
+        assert!(matches!(
+            now(middleware.try_call_async(3, |n| async move { halve(n) })),
+            Err(MiddlewareError::Handler(_))
+        ));
+        assert_eq!(timing.get_stats("halve").unwrap().count, 2);
+    }
+}
@@ -0,0 +1,263 @@
+//! OpenTelemetry tracing aspect.
+//!
+//! Available with the `opentelemetry` feature.
+
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, Precedence, ProceedingJoinPoint};
+use opentelemetry::trace::{FutureExt, SpanKind, Status, TraceContextExt, Tracer};
+use opentelemetry::{global, Context, KeyValue};
+use std::any::Any;
+
+/// Aspect that wraps each call in an OpenTelemetry span.
+///
+/// Spans are created with the globally installed tracer provider and are
+/// parented to the current OTel context, so a function advised with
+/// `OtelAspect` shows up inside whatever trace is active when it is called.
+/// While the function runs, its span is the current context, which makes
+/// spans from nested calls (advised or not) children of it.
+///
+/// Errors returned by the function are recorded as an `exception` event and
+/// set the span status to `Error`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::OtelAspect;
+/// use aspect_macros::aspect;
+///
+/// #[aspect(OtelAspect::new())]
+/// fn fetch_order(id: u64) -> Result<Order, String> {
+///     db::load_order(id)
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OtelAspect {
+    tracer_name: &'static str,
+    span_kind: SpanKind,
+    qualified_names: bool,
+}
+
+impl OtelAspect {
+    /// Create an aspect using the `aspect-rs` tracer with internal spans.
+    pub fn new() -> Self {
+        Self {
+            tracer_name: "aspect-rs",
+            span_kind: SpanKind::Internal,
+            qualified_names: false,
+        }
+    }
+
+    /// Set the instrumentation scope name passed to `global::tracer`.
+    pub fn with_tracer_name(mut self, name: &'static str) -> Self {
+        self.tracer_name = name;
+        self
+    }
+
+    /// Set the kind of the created spans (Internal by default).
+    pub fn with_span_kind(mut self, kind: SpanKind) -> Self {
+        self.span_kind = kind;
+        self
+    }
+
+    /// Name spans `module::function` instead of just `function`.
+    pub fn qualified_names(mut self) -> Self {
+        self.qualified_names = true;
+        self
+    }
+
+    fn span_name(&self, ctx: &JoinPoint) -> String {
+        if self.qualified_names {
+            ctx.qualified_name()
+        } else {
+            ctx.function_name.to_string()
+        }
+    }
+
+    /// The context of the span of a call of `ctx`, a child of the current
+    /// one.
+    fn start(&self, ctx: &JoinPoint) -> Context {
+        let tracer = global::tracer(self.tracer_name);
+        let span = tracer
+            .span_builder(self.span_name(ctx))
+            .with_kind(self.span_kind.clone())
+            .with_attributes(Self::attributes(ctx))
+            .start_with_context(&tracer, &Context::current());
+        Context::current_with_span(span)
+    }
+
+    /// Ends the span of `cx`, recording the error of `result` if any.
+    fn end(cx: &Context, result: &Result<Box<dyn Any>, AspectError>) {
+        let span = cx.span();
+        if let Err(err) = result {
+            span.record_error(err);
+            span.set_status(Status::error(err.to_string()));
+        }
+        span.end();
+    }
+
+    fn attributes(ctx: &JoinPoint) -> Vec<KeyValue> {
+        vec![
+            KeyValue::new("code.function", ctx.function_name),
+            KeyValue::new("code.namespace", ctx.module_path),
+            KeyValue::new("code.filepath", ctx.location.file),
+            KeyValue::new("code.lineno", i64::from(ctx.location.line)),
+        ]
+    }
+}
+
+impl Default for OtelAspect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Aspect for OtelAspect {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let cx = self.start(pjp.context());
+        let result = {
+            let _guard = cx.clone().attach();
+            pjp.proceed()
+        };
+        Self::end(&cx, &result);
+        result
+    }
+
+    /// Makes the span the current one whenever the execution is polled.
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            let cx = self.start(ctx);
+            let result = proceed.with_context(cx.clone()).await;
+            Self::end(&cx, &result);
+            result
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::OBSERVABILITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_core::Location;
+    use opentelemetry::trace::SpanId;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+    use std::sync::OnceLock;
+
+    fn exporter() -> &'static InMemorySpanExporter {
+        static EXPORTER: OnceLock<InMemorySpanExporter> = OnceLock::new();
+        EXPORTER.get_or_init(|| {
+            let exporter = InMemorySpanExporter::default();
+            let provider = SdkTracerProvider::builder()
+                .with_simple_exporter(exporter.clone())
+                .build();
+            global::set_tracer_provider(provider);
+            exporter
+        })
+    }
+
+    fn finished(name: &str) -> Vec<SpanData> {
+        exporter()
+            .get_finished_spans()
+            .unwrap()
+            .into_iter()
+            .filter(|span| span.name == name)
+            .collect()
+    }
+
+    fn joinpoint(function_name: &'static str) -> JoinPoint {
+        JoinPoint::new(
+            function_name,
+            "app::orders",
+            Location {
+                file: "orders.rs",
+                line: 12,
+            },
+        )
+    }
+
+    #[test]
+    fn test_span_per_call() {
+        exporter();
+        let aspect = OtelAspect::new();
+        let pjp =
+            ProceedingJoinPoint::new(|| Ok(Box::new(7) as Box<dyn Any>), joinpoint("otel_ok"));
+
+        let result = aspect.around(pjp).unwrap();
+        assert_eq!(*result.downcast::<i32>().unwrap(), 7);
+
+        let spans = finished("otel_ok");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].span_kind, SpanKind::Internal);
+        assert_eq!(spans[0].status, Status::Unset);
+        assert!(spans[0]
+            .attributes
+            .contains(&KeyValue::new("code.namespace", "app::orders")));
+    }
+
+    #[test]
+    fn test_error_recorded() {
+        exporter();
+        let aspect = OtelAspect::new().with_span_kind(SpanKind::Server);
+        let pjp = ProceedingJoinPoint::new(
+            || Err(AspectError::execution("db down")),
+            joinpoint("otel_err"),
+        );
+
+        assert!(aspect.around(pjp).is_err());
+
+        let spans = finished("otel_err");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].span_kind, SpanKind::Server);
+        assert!(matches!(spans[0].status, Status::Error { .. }));
+        assert!(spans[0]
+            .events
+            .events
+            .iter()
+            .any(|event| event.name == "exception"));
+    }
+
+    #[test]
+    fn test_nested_calls_share_trace() {
+        exporter();
+        let aspect = OtelAspect::new();
+        let inner_aspect = aspect.clone();
+        let pjp = ProceedingJoinPoint::new(
+            move || {
+                let inner = ProceedingJoinPoint::new(
+                    || Ok(Box::new(()) as Box<dyn Any>),
+                    joinpoint("otel_inner"),
+                );
+                inner_aspect.around(inner)
+            },
+            joinpoint("otel_outer"),
+        );
+
+        aspect.around(pjp).unwrap();
+
+        let outer = &finished("otel_outer")[0];
+        let inner = &finished("otel_inner")[0];
+        assert_eq!(outer.parent_span_id, SpanId::INVALID);
+        assert_eq!(inner.parent_span_id, outer.span_context.span_id());
+        assert_eq!(inner.span_context.trace_id(), outer.span_context.trace_id());
+    }
+
+    #[test]
+    fn test_qualified_span_names() {
+        exporter();
+        let aspect = OtelAspect::new().qualified_names();
+        let pjp = ProceedingJoinPoint::new(
+            || Ok(Box::new(()) as Box<dyn Any>),
+            joinpoint("otel_qualified"),
+        );
+
+        aspect.around(pjp).unwrap();
+
+        assert_eq!(finished("app::orders::otel_qualified").len(), 1);
+    }
+}
@@ -0,0 +1,280 @@
+//! Profiling aspect recording time per call stack in folded-stack format.
+
+use crate::time::{self, Duration};
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, Precedence, ProceedingJoinPoint};
+use parking_lot::Mutex;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+/// One profiled call in progress on this thread.
+struct Frame {
+    name: String,
+    children: Duration,
+}
+
+thread_local! {
+    /// Profiled calls in progress on this thread, outermost first.
+    static STACK: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Pops the frame of a profiled call, including when unwinding, so a panic
+/// does not leave the thread's stack out of sync.
+struct FrameGuard;
+
+impl Drop for FrameGuard {
+    fn drop(&mut self) {
+        STACK.with(|stack| stack.borrow_mut().pop());
+    }
+}
+
+/// Aspect measuring the time spent in advised functions, attributed to the
+/// chain of advised calls leading to them.
+///
+/// Each call's self time (its duration minus that of the advised calls it
+/// made) is added to its stack: the `;`-separated qualified names of the
+/// advised functions it was called from, outermost first. Only functions
+/// selected for profiling are measured, so everything else runs without
+/// overhead.
+///
+/// The profile is written in the folded-stack format, one `stack value`
+/// line per stack with the value in microseconds, which `flamegraph.pl`,
+/// [inferno](https://github.com/jonhoo/inferno) and
+/// [speedscope](https://www.speedscope.app) turn into flame graphs.
+///
+/// Clones share the profile. Stacks are tracked per thread, so a call made
+/// on another thread starts a new stack. Executions woven through
+/// [`around_async`](Aspect::around_async), such as those of the tower
+/// adapter, move between threads, so they are not profiled but fail with
+/// [`AspectError::WeavingError`].
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::ProfilingAspect;
+/// use aspect_macros::aspect;
+/// use std::sync::LazyLock;
+///
+/// static PROFILER: LazyLock<ProfilingAspect> = LazyLock::new(ProfilingAspect::new);
+///
+/// #[aspect(PROFILER.clone())]
+/// fn handle_request() {
+///     parse();
+///     render();
+/// }
+///
+/// // After the run:
+/// PROFILER.write_folded_file("profile.folded").unwrap();
+/// // $ inferno-flamegraph profile.folded > profile.svg
+/// ```
+#[derive(Clone, Default)]
+pub struct ProfilingAspect {
+    profile: Arc<Mutex<HashMap<String, Duration>>>,
+}
+
+impl ProfilingAspect {
+    /// Create a profiler with an empty profile.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total self time recorded for each stack.
+    pub fn profile(&self) -> HashMap<String, Duration> {
+        self.profile.lock().clone()
+    }
+
+    /// The profile in folded-stack format, sorted by stack.
+    pub fn folded(&self) -> String {
+        let profile = self.profile.lock();
+        let mut stacks: Vec<_> = profile.iter().collect();
+        stacks.sort();
+
+        let mut out = String::new();
+        for (stack, time) in stacks {
+            let _ = writeln!(out, "{} {}", stack, time.as_micros());
+        }
+        out
+    }
+
+    /// Write the profile in folded-stack format to `writer`.
+    pub fn write_folded(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(self.folded().as_bytes())
+    }
+
+    /// Write the profile in folded-stack format to the file at `path`,
+    /// replacing it if it exists.
+    pub fn write_folded_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write_folded(File::create(path)?)
+    }
+
+    /// Discard the profile, e.g. to start a new run.
+    pub fn reset(&self) {
+        self.profile.lock().clear();
+    }
+
+    fn record(&self, stack: String, self_time: Duration) {
+        *self.profile.lock().entry(stack).or_default() += self_time;
+    }
+}
+
+impl Aspect for ProfilingAspect {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let name = pjp.context().qualified_name();
+        STACK.with(|stack| {
+            stack.borrow_mut().push(Frame {
+                name,
+                children: Duration::ZERO,
+            })
+        });
+        let guard = FrameGuard;
+        let start = time::now();
+
+        let result = pjp.proceed();
+
+        let elapsed = time::elapsed(start);
+        let (path, self_time) = STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            let path = stack
+                .iter()
+                .map(|frame| frame.name.as_str())
+                .collect::<Vec<_>>()
+                .join(";");
+            let frame = stack.last().expect("profiling stack corrupted");
+            let self_time = elapsed.saturating_sub(frame.children);
+            let depth = stack.len();
+            if depth > 1 {
+                stack[depth - 2].children += elapsed;
+            }
+            (path, self_time)
+        });
+        drop(guard);
+        self.record(path, self_time);
+
+        result
+    }
+
+    /// Fails, as stacks are tracked per thread.
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        _proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            Err(AspectError::weaving(format!(
+                "ProfilingAspect cannot advise the asynchronous execution of {}",
+                ctx.function_name
+            )))
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::OBSERVABILITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_macros::aspect;
+    use std::sync::LazyLock;
+    use std::thread;
+
+    static PROFILER: LazyLock<ProfilingAspect> = LazyLock::new(ProfilingAspect::new);
+
+    #[aspect(PROFILER.clone())]
+    fn request() {
+        thread::sleep(Duration::from_millis(5));
+        parse();
+        parse();
+        let _ = render();
+    }
+
+    #[aspect(PROFILER.clone())]
+    fn parse() {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    #[aspect(PROFILER.clone())]
+    fn render() -> Result<(), String> {
+        thread::sleep(Duration::from_millis(20));
+        Err("template missing".to_string())
+    }
+
+    #[test]
+    fn test_folded_stacks() {
+        request();
+
+        let module = module_path!();
+        let profile = PROFILER.profile();
+        let root = format!("{}::request", module);
+        let parse = format!("{};{}::parse", root, module);
+        let render = format!("{};{}::render", root, module);
+        assert_eq!(profile.len(), 3);
+
+        // Self time excludes the time spent in advised callees
+        assert!(profile[&root] >= Duration::from_millis(5));
+        assert!(profile[&parse] >= Duration::from_millis(20));
+        assert!(profile[&render] >= Duration::from_millis(20));
+
+        let folded = PROFILER.folded();
+        let lines: Vec<_> = folded.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with(&format!("{} ", root)));
+        assert!(lines[1].starts_with(&format!("{} ", parse)));
+        let micros: u128 = lines[2].rsplit(' ').next().unwrap().parse().unwrap();
+        assert_eq!(micros, profile[&render].as_micros());
+
+        let mut out = Vec::new();
+        PROFILER.write_folded(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), folded);
+
+        PROFILER.reset();
+        assert!(PROFILER.folded().is_empty());
+        STACK.with(|stack| assert!(stack.borrow().is_empty()));
+    }
+
+    #[test]
+    #[cfg(feature = "test-util")]
+    fn test_self_time_excludes_callees() {
+        use crate::time::ManualClock;
+        use aspect_core::{JoinPoint, Location};
+
+        let clock = ManualClock::new();
+        let _installed = clock.install();
+        let profiler = ProfilingAspect::new();
+        let call = |name: &'static str, ms: u64, callee: &dyn Fn()| {
+            let ctx = JoinPoint::new(
+                name,
+                "app",
+                Location {
+                    file: "test.rs",
+                    line: 1,
+                },
+            );
+            let pjp = ProceedingJoinPoint::new(
+                || {
+                    clock.advance(Duration::from_millis(ms));
+                    callee();
+                    Ok(Box::new(()) as Box<dyn Any>)
+                },
+                ctx,
+            );
+            profiler.around(pjp).unwrap();
+        };
+
+        call("request", 5, &|| call("parse", 10, &|| {}));
+
+        let profile = profiler.profile();
+        assert_eq!(profile["app::request"], Duration::from_millis(5));
+        assert_eq!(
+            profile["app::request;app::parse"],
+            Duration::from_millis(10)
+        );
+    }
+}
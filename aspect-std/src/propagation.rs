@@ -0,0 +1,625 @@
+//! W3C Trace Context and Baggage propagation between services.
+//!
+//! A request entering a service brings its position in a distributed trace
+//! in a `traceparent` header and properties set by upstream services in a
+//! `baggage` header. [`extract`] reads them into an [`AspectContext`] for
+//! [`context::scope`], and [`inject`] writes those
+//! of the current task into the headers of outbound requests, so that
+//! traces stay whole across services. [`PropagationAspect`], at outbound
+//! boundaries such as the functions of a client, gives each call its own
+//! span in the trace.
+//!
+//! `tracestate` headers are not propagated. Available with the `tokio`
+//! feature, as the task context is.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use aspect_std::context;
+//! use aspect_std::propagation::{self, PropagationAspect};
+//! use aspect_macros::aspect;
+//!
+//! // Inbound, e.g. as an axum middleware
+//! async fn propagate(request: Request, next: Next) -> Response {
+//!     let cx = propagation::extract_headers(request.headers());
+//!     context::scope(cx, next.run(request)).await
+//! }
+//!
+//! // Outbound
+//! #[aspect(PropagationAspect::new())]
+//! async fn fetch_user(client: &reqwest::Client, id: u64) -> reqwest::Result<User> {
+//!     let mut headers = http::HeaderMap::new();
+//!     propagation::inject_headers(&mut headers);
+//!     client.get(user_url(id)).headers(headers).send().await?.json().await
+//! }
+//! ```
+
+use crate::context::{self, AspectContext};
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, Precedence, ProceedingJoinPoint};
+use std::any::Any;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Name of the header carrying the [`TraceParent`].
+pub const TRACEPARENT: &str = "traceparent";
+
+/// Name of the header carrying the [`Baggage`].
+pub const BAGGAGE: &str = "baggage";
+
+/// The position of a request in a distributed trace: its trace, and the
+/// span it was made in.
+///
+/// Formats as, and [parses](Self::parse) from, the value of a
+/// `traceparent` header, e.g.
+/// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TraceParent {
+    /// Identifies the trace, never zero
+    pub trace_id: u128,
+
+    /// Identifies the span the request was made in, never zero
+    pub parent_id: u64,
+
+    /// Trace flags, of which the lowest bit tells whether the trace is
+    /// sampled
+    pub flags: u8,
+}
+
+impl TraceParent {
+    /// A new sampled trace, with random identifiers.
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: ((random_id() as u128) << 64) | random_id() as u128,
+            parent_id: random_id(),
+            flags: 0x01,
+        }
+    }
+
+    /// A new span of the same trace, with a random identifier.
+    pub fn child(&self) -> Self {
+        Self {
+            parent_id: random_id(),
+            ..*self
+        }
+    }
+
+    /// Whether the trace is sampled, i.e. recorded upstream.
+    pub fn is_sampled(&self) -> bool {
+        self.flags & 0x01 != 0
+    }
+
+    /// Parses the value of a `traceparent` header.
+    ///
+    /// Returns `None` when it is invalid, in which case the request starts
+    /// a new trace. Values of versions after `00` may have more fields,
+    /// which are ignored.
+    pub fn parse(header: &str) -> Option<Self> {
+        let mut fields = header.trim().split('-');
+        let version = fields.next()?;
+        let trace_id = fields.next()?;
+        let parent_id = fields.next()?;
+        let flags = fields.next()?;
+        if !is_hex(version, 2) || version == "ff" || (version == "00" && fields.next().is_some()) {
+            return None;
+        }
+        if !is_hex(trace_id, 32) || !is_hex(parent_id, 16) || !is_hex(flags, 2) {
+            return None;
+        }
+        let parsed = Self {
+            trace_id: u128::from_str_radix(trace_id, 16).ok()?,
+            parent_id: u64::from_str_radix(parent_id, 16).ok()?,
+            flags: u8::from_str_radix(flags, 16).ok()?,
+        };
+        (parsed.trace_id != 0 && parsed.parent_id != 0).then_some(parsed)
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id, self.parent_id, self.flags
+        )
+    }
+}
+
+/// Whether `s` is `len` lowercase hexadecimal digits.
+fn is_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// A random, non-zero identifier.
+fn random_id() -> u64 {
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+    loop {
+        let id = RandomState::new().hash_one(SEQUENCE.fetch_add(1, Ordering::Relaxed));
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+/// Properties of a request that services pass on to the services they
+/// call, such as a tenant or an experiment.
+///
+/// Formats as, and [parses](Self::parse) from, the value of a `baggage`
+/// header, e.g. `tenant=acme,region=eu%20west`. Entries keep their order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Baggage {
+    entries: Vec<(String, String)>,
+}
+
+impl Baggage {
+    /// An empty baggage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the entry `key` = `value`, replacing any for `key`.
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.insert(key, value);
+        self
+    }
+
+    /// Sets the entry `key` = `value`, replacing any for `key`.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        let (key, value) = (key.into(), value.into());
+        match self.entries.iter_mut().find(|(k, _)| *k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key, value)),
+        }
+    }
+
+    /// The value of the entry for `key`, if any.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Removes the entry for `key`, returning its value.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    /// The entries, as `(key, value)`.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Parses the value of a `baggage` header.
+    ///
+    /// Invalid entries are skipped, and the properties of entries, after a
+    /// `;`, are dropped.
+    pub fn parse(header: &str) -> Self {
+        let mut baggage = Self::new();
+        for member in header.split(',') {
+            let member = member.split(';').next().unwrap_or_default();
+            let Some((key, value)) = member.split_once('=') else {
+                continue;
+            };
+            let key = key.trim();
+            if key.is_empty() || !key.bytes().all(is_token) {
+                continue;
+            }
+            baggage.insert(key, percent_decode(value.trim()));
+        }
+        baggage
+    }
+}
+
+impl fmt::Display for Baggage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (key, value)) in self.entries.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}=", key)?;
+            for b in value.bytes() {
+                if is_baggage_octet(b) && b != b'%' {
+                    write!(f, "{}", b as char)?;
+                } else {
+                    write!(f, "%{:02X}", b)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `b` may be in a key, a token of RFC 7230.
+fn is_token(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+/// Whether `b` may be in a value without being percent-encoded.
+fn is_baggage_octet(b: u8) -> bool {
+    matches!(b, 0x21 | 0x23..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E)
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(b) => {
+                decoded.push(b);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Returns the context of a request with the trace parent and baggage of
+/// its headers, as looked up by name with `header`.
+///
+/// Invalid headers are ignored. The other fields of the context are for
+/// the caller to set, e.g. with
+/// [`with_principal`](AspectContext::with_principal).
+pub fn extract<'h>(header: impl Fn(&str) -> Option<&'h str>) -> AspectContext {
+    AspectContext {
+        trace_parent: header(TRACEPARENT).and_then(TraceParent::parse),
+        baggage: header(BAGGAGE).map(Baggage::parse).unwrap_or_default(),
+        ..AspectContext::default()
+    }
+}
+
+/// Writes the trace parent and baggage of the current task as headers,
+/// with `set` called with the name and value of each.
+///
+/// Headers are only written for what the task has: none outside a
+/// [`scope`](crate::context::scope).
+pub fn inject(mut set: impl FnMut(&'static str, String)) {
+    let Some(context) = context::current() else {
+        return;
+    };
+    if let Some(trace_parent) = context.trace_parent {
+        set(TRACEPARENT, trace_parent.to_string());
+    }
+    if !context.baggage.is_empty() {
+        set(BAGGAGE, context.baggage.to_string());
+    }
+}
+
+/// Returns the trace parent of the current task, if any.
+pub fn trace_parent() -> Option<TraceParent> {
+    context::current().and_then(|context| context.trace_parent)
+}
+
+/// Returns the baggage of the current task, empty outside a
+/// [`scope`](crate::context::scope).
+pub fn baggage() -> Baggage {
+    context::current()
+        .map(|context| context.baggage)
+        .unwrap_or_default()
+}
+
+/// [`extract`] from the headers of an [`http::Request`].
+/// Available with the `http` feature.
+#[cfg(feature = "http")]
+pub fn extract_headers(headers: &::http::HeaderMap) -> AspectContext {
+    extract(|name| headers.get(name).and_then(|value| value.to_str().ok()))
+}
+
+/// [`inject`] into the headers of an [`http::Request`],
+/// replacing those there. Available with the `http` feature.
+#[cfg(feature = "http")]
+pub fn inject_headers(headers: &mut ::http::HeaderMap) {
+    inject(|name, value| {
+        if let Ok(value) = ::http::HeaderValue::try_from(value) {
+            headers.insert(name, value);
+        }
+    });
+}
+
+/// Aspect giving each call its own span in the trace of the current task,
+/// at outbound boundaries.
+///
+/// While the function runs, the trace parent of the task is a
+/// [child](TraceParent::child) of the one of the caller, so that the
+/// requests it [injects](inject) the headers of are made in their own
+/// span. The trace parent of the caller is restored when it returns.
+///
+/// A call made outside a trace starts a new one, in a task context of its
+/// own when outside any [`scope`](crate::context::scope), unless
+/// [`without_new_traces`](Self::without_new_traces) is set.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::propagation::{self, PropagationAspect};
+/// use aspect_macros::aspect;
+///
+/// #[aspect(PropagationAspect::new())]
+/// fn call_inventory(agent: &ureq::Agent, sku: &str) -> Result<u32, ureq::Error> {
+///     let mut request = agent.get(&inventory_url(sku));
+///     let mut headers = Vec::new();
+///     propagation::inject(|name, value| headers.push((name, value)));
+///     for (name, value) in &headers {
+///         request = request.set(name, value);
+///     }
+///     request.call()?.into_json()
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PropagationAspect {
+    new_traces: bool,
+}
+
+impl PropagationAspect {
+    /// Create an aspect starting traces for calls outside one.
+    pub fn new() -> Self {
+        Self { new_traces: true }
+    }
+
+    /// Do not start traces: calls outside one propagate none.
+    pub fn without_new_traces(mut self) -> Self {
+        self.new_traces = false;
+        self
+    }
+
+    /// Enters the span of a call, returning the guard restoring the trace
+    /// parent of the caller, or `None` outside a scope.
+    fn enter(&self) -> Option<Restore> {
+        let mut previous = None;
+        context::update(|context| {
+            previous = Some(context.trace_parent);
+            context.trace_parent = match context.trace_parent {
+                Some(parent) => Some(parent.child()),
+                None if self.new_traces => Some(TraceParent::new_root()),
+                None => None,
+            };
+        });
+        previous.map(Restore)
+    }
+
+    /// The context of a call outside any scope, if it gets one.
+    fn new_context(&self) -> Option<AspectContext> {
+        self.new_traces
+            .then(|| AspectContext::new().with_trace_parent(TraceParent::new_root()))
+    }
+}
+
+impl Default for PropagationAspect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Restores the trace parent of the caller, also when the call panics.
+struct Restore(Option<TraceParent>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.0;
+        context::update(|context| context.trace_parent = previous);
+    }
+}
+
+impl Aspect for PropagationAspect {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        if let Some(_restore) = self.enter() {
+            return pjp.proceed();
+        }
+        match self.new_context() {
+            Some(context) => context::sync_scope(context, || pjp.proceed()),
+            None => pjp.proceed(),
+        }
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        _ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            if let Some(_restore) = self.enter() {
+                return proceed.await;
+            }
+            match self.new_context() {
+                Some(context) => context::scope(context, proceed).await,
+                None => proceed.await,
+            }
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::OBSERVABILITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_core::Location;
+    use std::collections::HashMap;
+
+    const HEADER: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    fn join_point() -> JoinPoint {
+        JoinPoint::new(
+            "fetch_user",
+            "app::client",
+            Location {
+                file: "src/client.rs",
+                line: 8,
+            },
+        )
+    }
+
+    /// Calls `aspect` around a function returning the headers it injects.
+    fn call(aspect: &PropagationAspect) -> HashMap<&'static str, String> {
+        let pjp = ProceedingJoinPoint::new(
+            || {
+                let mut headers = HashMap::new();
+                inject(|name, value| {
+                    headers.insert(name, value);
+                });
+                Ok(Box::new(headers) as Box<dyn Any>)
+            },
+            join_point(),
+        );
+        *aspect.around(pjp).unwrap().downcast().unwrap()
+    }
+
+    #[test]
+    fn test_trace_parent() {
+        let parsed = TraceParent::parse(HEADER).unwrap();
+        assert_eq!(parsed.trace_id, 0x4bf92f3577b34da6a3ce929d0e0e4736);
+        assert_eq!(parsed.parent_id, 0x00f067aa0ba902b7);
+        assert!(parsed.is_sampled());
+        assert_eq!(parsed.to_string(), HEADER);
+
+        let child = parsed.child();
+        assert_eq!(child.trace_id, parsed.trace_id);
+        assert_ne!(child.parent_id, parsed.parent_id);
+        assert_ne!(TraceParent::new_root().trace_id, parsed.trace_id);
+
+        // Later versions may have more fields
+        let future = "cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra";
+        assert!(!TraceParent::parse(future).unwrap().is_sampled());
+
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6-00f067aa0ba902b7-01",
+        ] {
+            assert_eq!(TraceParent::parse(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_baggage() {
+        let baggage = Baggage::parse(" tenant = acme ,region=eu%20west;ttl=60, invalid,=x");
+        assert_eq!(baggage.len(), 2);
+        assert_eq!(baggage.get("tenant"), Some("acme"));
+        assert_eq!(baggage.get("region"), Some("eu west"));
+        assert_eq!(baggage.to_string(), "tenant=acme,region=eu%20west");
+
+        let mut baggage = baggage.with("tenant", "initech").with("note", "a,b;50%");
+        assert_eq!(
+            baggage.to_string(),
+            "tenant=initech,region=eu%20west,note=a%2Cb%3B50%25"
+        );
+        assert_eq!(Baggage::parse(&baggage.to_string()), baggage);
+        assert_eq!(baggage.remove("region").as_deref(), Some("eu west"));
+        assert_eq!(baggage.iter().count(), 2);
+    }
+
+    #[test]
+    fn test_extract_inject() {
+        let headers = HashMap::from([(TRACEPARENT, HEADER), (BAGGAGE, "tenant=acme")]);
+        let context = extract(|name| headers.get(name).copied()).with_principal("alice");
+        assert_eq!(context.principal.as_deref(), Some("alice"));
+        assert_eq!(context.baggage.get("tenant"), Some("acme"));
+
+        let mut injected = HashMap::new();
+        context::sync_scope(context, || {
+            assert_eq!(trace_parent().unwrap().to_string(), HEADER);
+            inject(|name, value| {
+                injected.insert(name, value);
+            });
+        });
+        assert_eq!(injected[TRACEPARENT], HEADER);
+        assert_eq!(injected[BAGGAGE], "tenant=acme");
+
+        // Nothing to inject outside a scope
+        inject(|name, _| panic!("{} injected", name));
+        assert!(baggage().is_empty());
+    }
+
+    #[test]
+    fn test_propagation_aspect() {
+        let parent = TraceParent::parse(HEADER).unwrap();
+        let context = AspectContext::new()
+            .with_trace_parent(parent)
+            .with_baggage(Baggage::new().with("tenant", "acme"));
+        context::sync_scope(context, || {
+            let headers = call(&PropagationAspect::new());
+            let sent = TraceParent::parse(&headers[TRACEPARENT]).unwrap();
+            assert_eq!(sent.trace_id, parent.trace_id);
+            assert_ne!(sent.parent_id, parent.parent_id);
+            assert_eq!(headers[BAGGAGE], "tenant=acme");
+            // Restored for the caller
+            assert_eq!(trace_parent(), Some(parent));
+        });
+
+        // Outside a trace, a new one is started
+        let headers = call(&PropagationAspect::new());
+        assert!(TraceParent::parse(&headers[TRACEPARENT]).is_some());
+        context::sync_scope(AspectContext::new(), || {
+            assert!(call(&PropagationAspect::new()).contains_key(TRACEPARENT));
+            assert_eq!(trace_parent(), None);
+        });
+        assert!(call(&PropagationAspect::new().without_new_traces()).is_empty());
+    }
+
+    #[test]
+    fn test_propagation_aspect_async() {
+        let parent = TraceParent::parse(HEADER).unwrap();
+        let aspect = PropagationAspect::new();
+        let ctx = join_point();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(context::scope(
+            AspectContext::new().with_trace_parent(parent),
+            async {
+                let proceed = Box::pin(async {
+                    tokio::task::yield_now().await;
+                    Ok(Box::new(trace_parent()) as Box<dyn Any>)
+                });
+                let result = aspect.around_async(&ctx, proceed).await.unwrap();
+                let sent = result.downcast::<Option<TraceParent>>().unwrap().unwrap();
+                assert_eq!(sent.trace_id, parent.trace_id);
+                assert_ne!(sent, parent);
+                assert_eq!(trace_parent(), Some(parent));
+            },
+        ));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_http_headers() {
+        let mut headers = ::http::HeaderMap::new();
+        headers.insert(TRACEPARENT, HEADER.parse().unwrap());
+        let context = extract_headers(&headers);
+        assert_eq!(context.trace_parent.unwrap().to_string(), HEADER);
+
+        let mut outbound = ::http::HeaderMap::new();
+        context::sync_scope(context, || inject_headers(&mut outbound));
+        assert_eq!(outbound[TRACEPARENT], HEADER);
+        assert!(!outbound.contains_key(BAGGAGE));
+    }
+}
@@ -1,11 +1,25 @@
 //! Rate limiting aspect using token bucket algorithm.
 
-use aspect_core::{Aspect, AspectError, ProceedingJoinPoint};
-use parking_lot::Mutex;
+use crate::time::SystemClock;
+use aspect_core::aspect::BoxFuture;
+use aspect_core::config::Param;
+use aspect_core::{
+    ArgHasher, Aspect, AspectArgs, AspectError, AspectSnapshot, FromAspectArgs, JoinPoint,
+    Precedence, ProceedingJoinPoint,
+};
 use std::any::Any;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::hash::Hash;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
+
+pub mod algorithm;
+pub mod backend;
+
+pub use algorithm::{LeakyBucket, RateLimitAlgorithm, SlidingWindowCounter, SlidingWindowLog};
+#[cfg(feature = "redis")]
+pub use backend::RedisBackend;
+pub use backend::{RateLimitBackend, TokenBucket};
 
 /// Rate limiting aspect with token bucket algorithm.
 ///
@@ -28,23 +42,98 @@ use std::time::{Duration, Instant};
 ///     Ok(())
 /// }
 /// ```
+///
+/// Tokens are kept in a [`RateLimitBackend`]: an in-process [`TokenBucket`]
+/// by default, another [`RateLimitAlgorithm`] chosen with
+/// [`with_algorithm`](Self::with_algorithm), or e.g. a `RedisBackend` (with
+/// the `redis` feature) to share the limit across replicas.
+///
+/// The in-process backends refill by the system clock, or by another
+/// [`Clock`](crate::time::Clock) given to their `with_clock`, such as a
+/// [`ManualClock`](crate::time::ManualClock) in tests:
+///
+/// ```rust
+/// use aspect_std::ratelimit::TokenBucket;
+/// use aspect_std::time::{Duration, ManualClock};
+/// use aspect_std::RateLimitAspect;
+///
+/// let clock = ManualClock::new();
+/// let limiter = RateLimitAspect::with_backend(
+///     TokenBucket::new(10, Duration::from_secs(1)).with_clock(clock.clone()),
+/// );
+/// clock.advance(Duration::from_millis(100));
+/// ```
+///
+/// With a [`KeyExtractor`], each user, API key or tenant the calls are made
+/// for gets a limit of its own:
+///
+/// ```rust
+/// use aspect_std::ratelimit::{ArgKey, TokenBucket};
+/// use aspect_std::RateLimitAspect;
+/// use std::time::Duration;
+///
+/// // 100 requests per minute for each API key, remembering 10,000 keys
+/// let limiter = RateLimitAspect::with_backend(
+///     TokenBucket::new(100, Duration::from_secs(60)).with_max_keys(10_000),
+/// )
+/// .with_key_extractor(ArgKey::new(["api_key"]));
+/// ```
 #[derive(Clone)]
 pub struct RateLimitAspect {
-    state: Arc<Mutex<RateLimitState>>,
+    backend: Arc<dyn RateLimitBackend>,
+    per_function: bool,
+    key_extractor: Option<Arc<dyn KeyExtractor>>,
 }
 
-struct RateLimitState {
-    tokens: f64,
-    max_tokens: f64,
-    refill_rate: f64, // tokens per second
-    last_refill: Instant,
-    per_function: bool,
-    function_states: HashMap<String, FunctionRateLimit>,
+/// Bucket key used when limiting all functions together.
+const GLOBAL_KEY: &str = "*";
+
+/// Computes the key of the caller a call is limited for, such as its user,
+/// API key or tenant.
+///
+/// Return `None` for calls that should draw from the bucket shared by the
+/// callers. Any closure of type `Fn(&JoinPoint) -> Option<String>` is a key
+/// extractor.
+pub trait KeyExtractor: Send + Sync {
+    /// Returns the key of the caller of this call, if it has one.
+    fn extract(&self, ctx: &JoinPoint) -> Option<String>;
+}
+
+impl<F> KeyExtractor for F
+where
+    F: Fn(&JoinPoint) -> Option<String> + Send + Sync,
+{
+    fn extract(&self, ctx: &JoinPoint) -> Option<String> {
+        self(ctx)
+    }
+}
+
+/// Keys on the hashes of the named arguments, so that secrets such as API
+/// keys are not kept by the backend.
+///
+/// Yields no key if a named argument is missing or not hashable.
+#[derive(Debug, Clone)]
+pub struct ArgKey {
+    names: Vec<String>,
+}
+
+impl ArgKey {
+    /// Key on the arguments with these parameter names.
+    pub fn new<S: Into<String>>(names: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            names: names.into_iter().map(Into::into).collect(),
+        }
+    }
 }
 
-struct FunctionRateLimit {
-    tokens: f64,
-    last_refill: Instant,
+impl KeyExtractor for ArgKey {
+    fn extract(&self, ctx: &JoinPoint) -> Option<String> {
+        let mut hasher = ArgHasher::new();
+        for name in &self.names {
+            ctx.arg(name)?.hash()?.hash(&mut hasher);
+        }
+        Some(format!("{:032x}", hasher.finish128()))
+    }
 }
 
 impl RateLimitAspect {
@@ -60,106 +149,216 @@ impl RateLimitAspect {
     /// let limiter = RateLimitAspect::new(100, Duration::from_secs(60));
     /// ```
     pub fn new(max_requests: u64, window: Duration) -> Self {
-        let refill_rate = max_requests as f64 / window.as_secs_f64();
+        Self::with_backend(TokenBucket::new(max_requests, window))
+    }
+
+    /// Create an in-process rate limiter using the given algorithm.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// // At most 10 requests in any 1 second span
+    /// let limiter = RateLimitAspect::with_algorithm(
+    ///     10,
+    ///     Duration::from_secs(1),
+    ///     RateLimitAlgorithm::SlidingWindowLog,
+    /// );
+    /// ```
+    pub fn with_algorithm(
+        max_requests: u64,
+        window: Duration,
+        algorithm: RateLimitAlgorithm,
+    ) -> Self {
+        Self {
+            backend: algorithm.backend(max_requests, window).into(),
+            per_function: false,
+            key_extractor: None,
+        }
+    }
 
+    /// Create a rate limiter drawing tokens from `backend`.
+    pub fn with_backend(backend: impl RateLimitBackend + 'static) -> Self {
         Self {
-            state: Arc::new(Mutex::new(RateLimitState {
-                tokens: max_requests as f64,
-                max_tokens: max_requests as f64,
-                refill_rate,
-                last_refill: Instant::now(),
-                per_function: false,
-                function_states: HashMap::new(),
-            })),
+            backend: Arc::new(backend),
+            per_function: false,
+            key_extractor: None,
         }
     }
 
     /// Enable per-function rate limiting.
     ///
     /// When enabled, each function gets its own token bucket.
-    pub fn per_function(self) -> Self {
-        self.state.lock().per_function = true;
+    pub fn per_function(mut self) -> Self {
+        self.per_function = true;
         self
     }
 
-    /// Check if a request is allowed (consumes a token if available).
-    fn try_acquire(&self, function_name: Option<&str>) -> bool {
-        let mut state = self.state.lock();
-        let now = Instant::now();
-
-        if state.per_function {
-            if let Some(name) = function_name {
-                // Per-function rate limiting
-                // Capture values before borrowing function_states
-                let max_tokens = state.max_tokens;
-                let refill_rate = state.refill_rate;
-
-                let func_state = state
-                    .function_states
-                    .entry(name.to_string())
-                    .or_insert_with(|| FunctionRateLimit {
-                        tokens: max_tokens,
-                        last_refill: now,
-                    });
-
-                // Refill tokens
-                let elapsed = now.duration_since(func_state.last_refill).as_secs_f64();
-                func_state.tokens = (func_state.tokens + elapsed * refill_rate).min(max_tokens);
-                func_state.last_refill = now;
-
-                if func_state.tokens >= 1.0 {
-                    func_state.tokens -= 1.0;
-                    true
-                } else {
-                    false
-                }
-            } else {
-                false
-            }
+    /// Limit each caller separately, by the key `extractor` computes for
+    /// its calls: calls with the same key draw from the same bucket (one
+    /// per function and key with [`per_function`](Self::per_function)).
+    ///
+    /// Calls without a key draw from the bucket they would without an
+    /// extractor. The in-process backends keep a bucket for each key seen;
+    /// bound them with e.g. [`TokenBucket::with_max_keys`].
+    pub fn with_key_extractor(mut self, extractor: impl KeyExtractor + 'static) -> Self {
+        self.key_extractor = Some(Arc::new(extractor));
+        self
+    }
+
+    /// The bucket calls to `function_name` by the caller `key` draw from.
+    fn bucket<'a>(&self, function_name: &'a str, key: Option<&str>) -> Cow<'a, str> {
+        let shared = if self.per_function {
+            function_name
         } else {
-            // Global rate limiting
-            let elapsed = now.duration_since(state.last_refill).as_secs_f64();
-            state.tokens = (state.tokens + elapsed * state.refill_rate).min(state.max_tokens);
-            state.last_refill = now;
-
-            if state.tokens >= 1.0 {
-                state.tokens -= 1.0;
-                true
-            } else {
-                false
-            }
+            GLOBAL_KEY
+        };
+        match key {
+            // Function names have no `:`, so keyed buckets are apart
+            Some(key) => Cow::Owned(format!("{}:{}", shared, key)),
+            None => Cow::Borrowed(shared),
+        }
+    }
+
+    /// The bucket the call `ctx` draws from.
+    fn call_bucket<'a>(&self, ctx: &'a JoinPoint) -> Cow<'a, str> {
+        let key = self.key_extractor.as_ref().and_then(|e| e.extract(ctx));
+        self.bucket(ctx.function_name, key.as_deref())
+    }
+
+    /// Take a token for the call `ctx`, or the error rejecting it.
+    fn acquire(&self, ctx: &JoinPoint) -> Result<(), AspectError> {
+        let bucket = self.call_bucket(ctx);
+        if self.backend.acquire(&bucket, 1)? {
+            return Ok(());
         }
+        let retry_after = self.backend.retry_after(&bucket, 1);
+        Err(AspectError::rate_limited(ctx.function_name, retry_after))
+    }
+
+    /// Forget the state of the keys idle long enough to be back to that of
+    /// a new key, returning how many were forgotten; see
+    /// [`RateLimitBackend::purge_idle`].
+    pub fn purge_idle(&self) -> usize {
+        self.backend.purge_idle()
     }
 
     /// Get current token count.
+    ///
+    /// Returns 0 if the backend cannot be reached.
     pub fn available_tokens(&self) -> f64 {
-        let mut state = self.state.lock();
-        let now = Instant::now();
-        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
-        state.tokens = (state.tokens + elapsed * state.refill_rate).min(state.max_tokens);
-        state.last_refill = now;
-        state.tokens
+        self.backend.available(GLOBAL_KEY).unwrap_or(0.0)
     }
 }
 
 impl Aspect for RateLimitAspect {
     fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
-        let function_name = pjp.context().function_name;
+        self.acquire(pjp.context())?;
+        pjp.proceed()
+    }
 
-        if self.try_acquire(Some(function_name)) {
-            pjp.proceed()
-        } else {
-            Err(AspectError::execution(format!(
-                "Rate limit exceeded for {}",
-                function_name
-            )))
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            self.acquire(ctx)?;
+            proceed.await
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::RESILIENCE
+    }
+
+    /// Whether the limit is per function and per caller key, and the
+    /// tokens left under the shared one; `null` when it is per function or
+    /// the backend cannot be reached.
+    fn snapshot(&self) -> AspectSnapshot {
+        let available = match self.per_function {
+            true => None,
+            false => self.backend.available(GLOBAL_KEY).ok(),
+        };
+        AspectSnapshot::new("RateLimitAspect")
+            .with("per_function", self.per_function)
+            .with("per_key", self.key_extractor.is_some())
+            .with("available_tokens", available)
+    }
+
+    fn reads_args(&self) -> bool {
+        self.key_extractor.is_some()
+    }
+}
+
+/// Builds the aspect of `#[aspect(RateLimitAspect, max = 10, window = "1s")]`.
+///
+/// Parameters: `max` and `window` (required), `algorithm` (one of
+/// `"token_bucket"`, `"sliding_window_log"`, `"sliding_window_counter"` and
+/// `"leaky_bucket"`), `per_function`, `key` (the name of the argument
+/// calls are limited by, see [`ArgKey`]) and `max_keys`.
+impl FromAspectArgs for RateLimitAspect {
+    const PARAMS: &'static [Param] = &[
+        Param::required::<u64>("max"),
+        Param::required::<Duration>("window"),
+        Param::optional::<String>("algorithm"),
+        Param::optional::<bool>("per_function"),
+        Param::optional::<String>("key"),
+        Param::optional::<u64>("max_keys"),
+    ];
+
+    fn from_aspect_args(args: &AspectArgs) -> Result<Self, AspectError> {
+        args.check(Self::PARAMS)?;
+        let algorithm = match args.get::<String>("algorithm")?.as_deref() {
+            None | Some("token_bucket") => RateLimitAlgorithm::TokenBucket,
+            Some("sliding_window_log") => RateLimitAlgorithm::SlidingWindowLog,
+            Some("sliding_window_counter") => RateLimitAlgorithm::SlidingWindowCounter,
+            Some("leaky_bucket") => RateLimitAlgorithm::LeakyBucket,
+            Some(other) => {
+                return Err(AspectError::weaving(format!(
+                    "unknown rate limit algorithm {:?}",
+                    other
+                )))
+            }
+        };
+        let max_keys = args.get::<u64>("max_keys")?.map(|n| n as usize);
+        let backend = algorithm.bounded_backend(
+            args.require("max")?,
+            args.require("window")?,
+            SystemClock,
+            max_keys,
+        );
+        let mut aspect = Self {
+            backend: backend.into(),
+            per_function: false,
+            key_extractor: None,
+        };
+        if let Some(true) = args.get("per_function")? {
+            aspect = aspect.per_function();
+        }
+        if let Some(name) = args.get::<String>("key")? {
+            aspect = aspect.with_key_extractor(ArgKey::new([name]));
         }
+        Ok(aspect)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::time::ManualClock;
+
+    /// Call `function_name` through the limiter, returning whether it ran.
+    fn call(limiter: &RateLimitAspect, function_name: &'static str) -> bool {
+        let ctx = aspect_core::JoinPoint::new(
+            function_name,
+            "test",
+            aspect_core::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        );
+        let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn Any>), ctx);
+        limiter.around(pjp).is_ok()
+    }
 
     #[test]
     fn test_rate_limit_basic() {
@@ -167,27 +366,31 @@ mod tests {
 
         // Should allow 5 calls
         for _ in 0..5 {
-            assert!(limiter.try_acquire(Some("test")));
+            assert!(call(&limiter, "test"));
         }
 
         // 6th call should be denied
-        assert!(!limiter.try_acquire(Some("test")));
+        assert!(!call(&limiter, "test"));
     }
 
     #[test]
     fn test_rate_limit_refill() {
-        let limiter = RateLimitAspect::new(2, Duration::from_millis(100));
+        let clock = ManualClock::new();
+        let limiter = RateLimitAspect::with_backend(
+            TokenBucket::new(2, Duration::from_millis(100)).with_clock(clock.clone()),
+        );
 
         // Consume both tokens
-        assert!(limiter.try_acquire(Some("test")));
-        assert!(limiter.try_acquire(Some("test")));
-        assert!(!limiter.try_acquire(Some("test")));
+        assert!(call(&limiter, "test"));
+        assert!(call(&limiter, "test"));
+        assert!(!call(&limiter, "test"));
 
         // Wait for refill
-        std::thread::sleep(Duration::from_millis(150));
+        clock.advance(Duration::from_millis(50));
 
-        // Should have at least 1 token now
-        assert!(limiter.try_acquire(Some("test")));
+        // Should have 1 token now
+        assert!(call(&limiter, "test"));
+        assert!(!call(&limiter, "test"));
     }
 
     #[test]
@@ -195,14 +398,14 @@ mod tests {
         let limiter = RateLimitAspect::new(2, Duration::from_secs(1)).per_function();
 
         // Function A consumes its quota
-        assert!(limiter.try_acquire(Some("func_a")));
-        assert!(limiter.try_acquire(Some("func_a")));
-        assert!(!limiter.try_acquire(Some("func_a")));
+        assert!(call(&limiter, "func_a"));
+        assert!(call(&limiter, "func_a"));
+        assert!(!call(&limiter, "func_a"));
 
         // Function B should still have its quota
-        assert!(limiter.try_acquire(Some("func_b")));
-        assert!(limiter.try_acquire(Some("func_b")));
-        assert!(!limiter.try_acquire(Some("func_b")));
+        assert!(call(&limiter, "func_b"));
+        assert!(call(&limiter, "func_b"));
+        assert!(!call(&limiter, "func_b"));
     }
 
     #[test]
@@ -212,9 +415,174 @@ mod tests {
         let initial = limiter.available_tokens();
         assert!((initial - 10.0).abs() < 0.01);
 
-        limiter.try_acquire(Some("test"));
+        assert!(call(&limiter, "test"));
 
         let after = limiter.available_tokens();
         assert!((after - 9.0).abs() < 0.01);
     }
+
+    #[test]
+    fn test_with_algorithm_per_function() {
+        let limiter = RateLimitAspect::with_algorithm(
+            1,
+            Duration::from_secs(60),
+            RateLimitAlgorithm::SlidingWindowCounter,
+        )
+        .per_function();
+
+        assert!(call(&limiter, "func_a"));
+        assert!(!call(&limiter, "func_a"));
+        assert!(call(&limiter, "func_b"));
+    }
+
+    #[test]
+    fn test_rejection_reports_retry_after() {
+        let limiter = RateLimitAspect::new(1, Duration::from_secs(10));
+        let ctx = aspect_core::JoinPoint::new(
+            "api_call",
+            "test",
+            aspect_core::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        );
+
+        let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn Any>), ctx.clone());
+        assert!(limiter.around(pjp).is_ok());
+
+        let pjp = ProceedingJoinPoint::new(|| panic!("must not run"), ctx);
+        match limiter.around(pjp).unwrap_err() {
+            AspectError::RateLimited {
+                target,
+                retry_after,
+            } => {
+                assert_eq!(target, "api_call");
+                let retry_after = retry_after.unwrap();
+                assert!(retry_after > Duration::from_secs(9));
+                assert!(retry_after <= Duration::from_secs(10));
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+
+    fn call_by(limiter: &RateLimitAspect, user_id: Option<u64>) -> Result<(), AspectError> {
+        let args = user_id.map(|id| aspect_core::Arg::new("user_id", &id));
+        let ctx = aspect_core::JoinPoint::new(
+            "api_call",
+            "test",
+            aspect_core::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        )
+        .with_args(args.into_iter().collect());
+        let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn Any>), ctx);
+        limiter.around(pjp).map(|_| ())
+    }
+
+    #[test]
+    fn test_per_key_limiting() {
+        let limiter = RateLimitAspect::new(2, Duration::from_secs(60))
+            .with_key_extractor(ArgKey::new(["user_id"]));
+
+        // User 1 consumes its quota
+        assert!(call_by(&limiter, Some(1)).is_ok());
+        assert!(call_by(&limiter, Some(1)).is_ok());
+        assert!(matches!(
+            call_by(&limiter, Some(1)),
+            Err(AspectError::RateLimited { .. })
+        ));
+
+        // User 2 and the calls without a user still have theirs
+        assert!(call_by(&limiter, Some(2)).is_ok());
+        assert!(call_by(&limiter, None).is_ok());
+        assert!(call_by(&limiter, None).is_ok());
+        assert!(call_by(&limiter, None).is_err());
+        assert!(call_by(&limiter, Some(2)).is_ok());
+
+        // A closure is a key extractor too
+        let limiter = RateLimitAspect::new(1, Duration::from_secs(60))
+            .with_key_extractor(|ctx: &JoinPoint| Some(ctx.function_name.to_string()));
+        assert!(call_by(&limiter, Some(1)).is_ok());
+        assert!(call_by(&limiter, Some(2)).is_err());
+        assert_eq!(limiter.snapshot().get("per_key"), Some(&true.into()));
+    }
+
+    struct UnavailableBackend;
+
+    impl RateLimitBackend for UnavailableBackend {
+        fn acquire(&self, _key: &str, _tokens: u32) -> Result<bool, AspectError> {
+            Err(AspectError::execution("backend down"))
+        }
+
+        fn release(&self, _key: &str, _tokens: u32) -> Result<(), AspectError> {
+            Err(AspectError::execution("backend down"))
+        }
+
+        fn available(&self, _key: &str) -> Result<f64, AspectError> {
+            Err(AspectError::execution("backend down"))
+        }
+    }
+
+    #[test]
+    fn test_backend_error_rejects_call() {
+        let limiter = RateLimitAspect::with_backend(UnavailableBackend);
+        let ctx = aspect_core::JoinPoint::new(
+            "api_call",
+            "test",
+            aspect_core::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        );
+
+        let pjp = ProceedingJoinPoint::new(|| panic!("must not run"), ctx);
+        let err = limiter.around(pjp).unwrap_err();
+        assert!(err.to_string().contains("backend down"));
+        assert_eq!(limiter.available_tokens(), 0.0);
+        assert_eq!(
+            limiter.snapshot().to_string(),
+            r#"{"aspect":"RateLimitAspect","state":{"per_function":false,"per_key":false,"available_tokens":null}}"#
+        );
+    }
+
+    #[aspect_macros::aspect(RateLimitAspect, max = 2, window = "60s")]
+    fn limited() -> Result<u32, String> {
+        Ok(1)
+    }
+
+    #[aspect_macros::aspect(
+        RateLimitAspect,
+        max = 1,
+        window = "60s",
+        key = "user_id",
+        max_keys = 100
+    )]
+    fn limited_per_user(user_id: u64) -> Result<u64, String> {
+        Ok(user_id)
+    }
+
+    #[test]
+    fn test_configured_with_parameters() {
+        assert_eq!(limited_per_user(1), Ok(1));
+        assert_eq!(limited_per_user(2), Ok(2));
+        assert!(limited_per_user(1).unwrap_err().contains("limit"));
+
+        // The aspect is built once, and its limit shared by the calls
+        assert_eq!(limited(), Ok(1));
+        assert_eq!(limited(), Ok(1));
+        assert!(limited().unwrap_err().contains("limit"));
+
+        let args = AspectArgs::new("RateLimitAspect")
+            .with("max", 1)
+            .with("window", "1s")
+            .with("algorithm", "leaky");
+        assert_eq!(
+            RateLimitAspect::from_aspect_args(&args)
+                .err()
+                .unwrap()
+                .to_string(),
+            "Weaving error: unknown rate limit algorithm \"leaky\""
+        );
+    }
 }
@@ -0,0 +1,610 @@
+//! In-process rate limiting algorithms.
+
+use super::backend::{make_room, RateLimitBackend, TokenBucket};
+use crate::time::{Clock, Duration, Instant, SystemClock};
+use aspect_core::AspectError;
+use parking_lot::Mutex;
+use std::collections::{vec_deque, HashMap, VecDeque};
+use std::sync::Arc;
+
+/// Algorithm used by an in-process rate limiter.
+///
+/// All algorithms allow `max_requests` per `window` on average; they differ
+/// in how they treat bursts and in the memory they need per key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RateLimitAlgorithm {
+    /// Tokens refill continuously; bursts up to `max_requests` are allowed
+    /// after idle periods. Constant memory. (default)
+    #[default]
+    TokenBucket,
+    /// Exact count of requests over the last `window`. Stores one timestamp
+    /// per admitted request, so memory grows with `max_requests`.
+    SlidingWindowLog,
+    /// Approximates the sliding window by weighting the previous fixed
+    /// window's count. Constant memory, small error at window boundaries.
+    SlidingWindowCounter,
+    /// Admits requests at a steady pace of one per `window / max_requests`,
+    /// making callers wait for their slot. Up to `max_requests` callers may
+    /// wait; further requests are rejected. Smooths bursts at the cost of
+    /// latency.
+    LeakyBucket,
+}
+
+impl RateLimitAlgorithm {
+    /// Create an in-process backend using this algorithm.
+    pub fn backend(self, max_requests: u64, window: Duration) -> Box<dyn RateLimitBackend> {
+        self.backend_with_clock(max_requests, window, SystemClock)
+    }
+
+    /// Create an in-process backend using this algorithm, reading the time
+    /// from `clock`.
+    pub fn backend_with_clock(
+        self,
+        max_requests: u64,
+        window: Duration,
+        clock: impl Clock + 'static,
+    ) -> Box<dyn RateLimitBackend> {
+        self.bounded_backend(max_requests, window, clock, None)
+    }
+
+    /// Create an in-process backend using this algorithm, keeping the state
+    /// of at most `max_keys` keys if given (see e.g.
+    /// [`TokenBucket::with_max_keys`]).
+    pub fn bounded_backend(
+        self,
+        max_requests: u64,
+        window: Duration,
+        clock: impl Clock + 'static,
+        max_keys: Option<usize>,
+    ) -> Box<dyn RateLimitBackend> {
+        let max_keys = max_keys.unwrap_or(usize::MAX);
+        match self {
+            Self::TokenBucket => Box::new(
+                TokenBucket::new(max_requests, window)
+                    .with_clock(clock)
+                    .with_max_keys(max_keys),
+            ),
+            Self::SlidingWindowLog => Box::new(
+                SlidingWindowLog::new(max_requests, window)
+                    .with_clock(clock)
+                    .with_max_keys(max_keys),
+            ),
+            Self::SlidingWindowCounter => Box::new(
+                SlidingWindowCounter::new(max_requests, window)
+                    .with_clock(clock)
+                    .with_max_keys(max_keys),
+            ),
+            Self::LeakyBucket => Box::new(
+                LeakyBucket::new(max_requests, window)
+                    .with_clock(clock)
+                    .with_max_keys(max_keys),
+            ),
+        }
+    }
+}
+
+/// Sliding window log backend: remembers when each admitted request
+/// happened and counts those within the last window.
+pub struct SlidingWindowLog {
+    max_requests: u64,
+    window: Duration,
+    logs: Mutex<HashMap<String, VecDeque<Instant>>>,
+    max_keys: Option<usize>,
+    clock: Arc<dyn Clock>,
+}
+
+impl SlidingWindowLog {
+    /// Create a backend allowing `max_requests` per `window` in each key.
+    pub fn new(max_requests: u64, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            logs: Mutex::new(HashMap::new()),
+            max_keys: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Read the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Keep the logs of at most `max_keys` keys: a new key first evicts
+    /// the logs whose entries all expired, then the least recently used.
+    pub fn with_max_keys(mut self, max_keys: usize) -> Self {
+        self.max_keys = Some(max_keys.max(1));
+        self
+    }
+
+    /// Whether every entry of `log` expired by `now`.
+    fn is_expired(&self, log: &VecDeque<Instant>, now: Instant) -> bool {
+        log.back()
+            .is_none_or(|t| now.duration_since(*t) >= self.window)
+    }
+
+    /// Run `f` on the log of `key`, with expired entries removed.
+    fn with_log<R>(&self, key: &str, f: impl FnOnce(&mut VecDeque<Instant>) -> R) -> R {
+        let mut logs = self.logs.lock();
+        let now = self.clock.now();
+        if !logs.contains_key(key) {
+            let idle = |log: &VecDeque<Instant>| self.is_expired(log, now);
+            make_room(&mut logs, self.max_keys, idle, |log| log.back().copied());
+        }
+        let log = logs.entry(key.to_string()).or_default();
+        while log
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= self.window)
+        {
+            log.pop_front();
+        }
+        f(log)
+    }
+
+    /// Run `f` on the entries of the log of `key` not expired by `now`,
+    /// without creating the log or removing anything from it.
+    fn read_log<R>(
+        &self,
+        key: &str,
+        f: impl FnOnce(vec_deque::Iter<'_, Instant>, Instant) -> R,
+    ) -> R {
+        let logs = self.logs.lock();
+        let now = self.clock.now();
+        let empty = VecDeque::new();
+        let log = logs.get(key).unwrap_or(&empty);
+        let expired = log.partition_point(|t| now.duration_since(*t) >= self.window);
+        f(log.range(expired..), now)
+    }
+}
+
+impl RateLimitBackend for SlidingWindowLog {
+    fn acquire(&self, key: &str, tokens: u32) -> Result<bool, AspectError> {
+        Ok(self.with_log(key, |log| {
+            if log.len() as u64 + tokens as u64 > self.max_requests {
+                return false;
+            }
+            let now = self.clock.now();
+            log.extend(std::iter::repeat_n(now, tokens as usize));
+            true
+        }))
+    }
+
+    fn release(&self, key: &str, tokens: u32) -> Result<(), AspectError> {
+        if let Some(log) = self.logs.lock().get_mut(key) {
+            let keep = log.len().saturating_sub(tokens as usize);
+            log.truncate(keep);
+        }
+        Ok(())
+    }
+
+    fn available(&self, key: &str) -> Result<f64, AspectError> {
+        Ok(self.read_log(key, |log, _| {
+            self.max_requests.saturating_sub(log.len() as u64) as f64
+        }))
+    }
+
+    fn retry_after(&self, key: &str, tokens: u32) -> Option<Duration> {
+        self.read_log(key, |mut log, now| {
+            // The entry whose expiry frees the last slot needed
+            let excess = (log.len() as u64 + tokens as u64).checked_sub(self.max_requests + 1);
+            let Some(excess) = excess else {
+                return Some(Duration::ZERO);
+            };
+            let expires = *log.nth(excess as usize)? + self.window;
+            Some(expires.saturating_duration_since(now))
+        })
+    }
+
+    fn purge_idle(&self) -> usize {
+        let now = self.clock.now();
+        let mut logs = self.logs.lock();
+        let before = logs.len();
+        logs.retain(|_, log| !self.is_expired(log, now));
+        before - logs.len()
+    }
+}
+
+/// Sliding window counter backend: keeps counts for the current and
+/// previous fixed windows and estimates the sliding count as
+/// `previous * (1 - elapsed / window) + current`.
+pub struct SlidingWindowCounter {
+    max_requests: u64,
+    window: Duration,
+    counters: Mutex<HashMap<String, WindowCounts>>,
+    max_keys: Option<usize>,
+    clock: Arc<dyn Clock>,
+}
+
+#[derive(Clone, Copy)]
+struct WindowCounts {
+    start: Instant,
+    current: u64,
+    previous: u64,
+}
+
+impl WindowCounts {
+    fn estimate(&self, now: Instant, window: Duration) -> f64 {
+        let elapsed = now.duration_since(self.start).as_secs_f64() / window.as_secs_f64();
+        self.previous as f64 * (1.0 - elapsed) + self.current as f64
+    }
+}
+
+impl SlidingWindowCounter {
+    /// Create a backend allowing `max_requests` per `window` in each key.
+    pub fn new(max_requests: u64, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            counters: Mutex::new(HashMap::new()),
+            max_keys: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Read the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Keep the counts of at most `max_keys` keys: a new key first evicts
+    /// the counts of windows over, then those of the least recent windows.
+    pub fn with_max_keys(mut self, max_keys: usize) -> Self {
+        self.max_keys = Some(max_keys.max(1));
+        self
+    }
+
+    /// Whether the current and previous windows of `counts` are over by
+    /// `now`.
+    fn is_over(&self, counts: &WindowCounts, now: Instant) -> bool {
+        now.duration_since(counts.start) >= self.window * 2
+    }
+
+    /// Run `f` on the counts of `key`, rolled over to the window containing
+    /// `now`.
+    fn with_counts<R>(&self, key: &str, f: impl FnOnce(&mut WindowCounts, Instant) -> R) -> R {
+        let mut counters = self.counters.lock();
+        let now = self.clock.now();
+        if !counters.contains_key(key) {
+            let idle = |counts: &WindowCounts| self.is_over(counts, now);
+            make_room(&mut counters, self.max_keys, idle, |counts| counts.start);
+        }
+        let counts = counters
+            .entry(key.to_string())
+            .or_insert_with(|| WindowCounts {
+                start: now,
+                current: 0,
+                previous: 0,
+            });
+        *counts = self.rolled(*counts, now);
+
+        f(counts, now)
+    }
+
+    /// `counts` rolled over to the window containing `now`.
+    fn rolled(&self, mut counts: WindowCounts, now: Instant) -> WindowCounts {
+        let elapsed = now.duration_since(counts.start);
+        if elapsed >= self.window {
+            let windows = (elapsed.as_nanos() / self.window.as_nanos()) as u32;
+            counts.previous = if windows == 1 { counts.current } else { 0 };
+            counts.current = 0;
+            counts.start += self.window * windows;
+        }
+        counts
+    }
+}
+
+impl RateLimitBackend for SlidingWindowCounter {
+    fn acquire(&self, key: &str, tokens: u32) -> Result<bool, AspectError> {
+        Ok(self.with_counts(key, |counts, now| {
+            if counts.estimate(now, self.window) + tokens as f64 > self.max_requests as f64 {
+                return false;
+            }
+            counts.current += tokens as u64;
+            true
+        }))
+    }
+
+    fn release(&self, key: &str, tokens: u32) -> Result<(), AspectError> {
+        let now = self.clock.now();
+        if let Some(counts) = self.counters.lock().get_mut(key) {
+            *counts = self.rolled(*counts, now);
+            counts.current = counts.current.saturating_sub(tokens as u64);
+        }
+        Ok(())
+    }
+
+    fn available(&self, key: &str) -> Result<f64, AspectError> {
+        let now = self.clock.now();
+        let estimate = match self.counters.lock().get(key) {
+            Some(counts) => self.rolled(*counts, now).estimate(now, self.window),
+            None => 0.0,
+        };
+        Ok((self.max_requests as f64 - estimate).max(0.0))
+    }
+
+    /// Forgets the keys whose current and previous windows are over.
+    fn purge_idle(&self) -> usize {
+        let now = self.clock.now();
+        let mut counters = self.counters.lock();
+        let before = counters.len();
+        counters.retain(|_, counts| !self.is_over(counts, now));
+        before - counters.len()
+    }
+}
+
+/// Leaky bucket backend used as a queue: each key admits one request per
+/// `window / max_requests`, and [`acquire`](RateLimitBackend::acquire)
+/// blocks until the caller's slot comes up.
+pub struct LeakyBucket {
+    capacity: u64,
+    interval: Duration,
+    queues: Mutex<HashMap<String, Instant>>,
+    max_keys: Option<usize>,
+    clock: Arc<dyn Clock>,
+}
+
+impl LeakyBucket {
+    /// Create a backend admitting `max_requests` per `window` in each key,
+    /// with room for `max_requests` waiting callers.
+    pub fn new(max_requests: u64, window: Duration) -> Self {
+        Self {
+            capacity: max_requests,
+            interval: window / max_requests.max(1) as u32,
+            queues: Mutex::new(HashMap::new()),
+            max_keys: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Keep the queues of at most `max_keys` keys: a new key first evicts
+    /// the drained queues, then those drained the soonest.
+    pub fn with_max_keys(mut self, max_keys: usize) -> Self {
+        self.max_keys = Some(max_keys.max(1));
+        self
+    }
+
+    /// Read the time from `clock` instead of the system clock, and wait on
+    /// it: a [`ManualClock`](crate::time::ManualClock) advances to the slot
+    /// of a caller instead of blocking it.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Number of slots already reserved after `now` when the queue drains
+    /// at `drained_at`.
+    fn queued(&self, drained_at: Instant, now: Instant) -> f64 {
+        drained_at.saturating_duration_since(now).as_secs_f64() / self.interval.as_secs_f64()
+    }
+
+    /// Reserve `tokens` slots, returning how long to wait for the first one,
+    /// or `None` if the queue is full.
+    fn reserve(&self, key: &str, tokens: u32) -> Option<Duration> {
+        let mut queues = self.queues.lock();
+        let now = self.clock.now();
+        if !queues.contains_key(key) {
+            let idle = |drained_at: &Instant| *drained_at <= now;
+            make_room(&mut queues, self.max_keys, idle, |drained_at| *drained_at);
+        }
+        let drained_at = queues.entry(key.to_string()).or_insert(now);
+
+        if self.queued(*drained_at, now) + tokens as f64 > self.capacity as f64 {
+            return None;
+        }
+        let start = (*drained_at).max(now);
+        *drained_at = start + self.interval * tokens;
+        Some(start - now)
+    }
+}
+
+impl RateLimitBackend for LeakyBucket {
+    fn acquire(&self, key: &str, tokens: u32) -> Result<bool, AspectError> {
+        match self.reserve(key, tokens) {
+            Some(wait) => {
+                if !wait.is_zero() {
+                    self.clock.sleep(wait);
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn release(&self, key: &str, tokens: u32) -> Result<(), AspectError> {
+        let now = self.clock.now();
+        if let Some(drained_at) = self.queues.lock().get_mut(key) {
+            *drained_at = drained_at
+                .checked_sub(self.interval * tokens)
+                .map_or(now, |t| t.max(now));
+        }
+        Ok(())
+    }
+
+    fn available(&self, key: &str) -> Result<f64, AspectError> {
+        let now = self.clock.now();
+        let queued = match self.queues.lock().get(key) {
+            Some(drained_at) => self.queued(*drained_at, now),
+            None => 0.0,
+        };
+        Ok((self.capacity as f64 - queued).max(0.0))
+    }
+
+    fn retry_after(&self, key: &str, tokens: u32) -> Option<Duration> {
+        let now = self.clock.now();
+        let queued = match self.queues.lock().get(key) {
+            Some(drained_at) => self.queued(*drained_at, now),
+            None => 0.0,
+        };
+        let excess = (queued + tokens as f64 - self.capacity as f64).max(0.0);
+        Some(self.interval.mul_f64(excess))
+    }
+
+    fn purge_idle(&self) -> usize {
+        let now = self.clock.now();
+        let mut queues = self.queues.lock();
+        let before = queues.len();
+        queues.retain(|_, drained_at| *drained_at > now);
+        before - queues.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::ManualClock;
+
+    #[test]
+    fn test_sliding_window_log() {
+        let clock = ManualClock::new();
+        let limiter =
+            SlidingWindowLog::new(3, Duration::from_millis(100)).with_clock(clock.clone());
+
+        for _ in 0..3 {
+            assert!(limiter.acquire("a", 1).unwrap());
+            clock.advance(Duration::from_millis(10));
+        }
+        assert!(!limiter.acquire("a", 1).unwrap());
+        assert_eq!(limiter.available("a").unwrap(), 0.0);
+        // The first call leaves the window 100ms after it was made
+        assert_eq!(limiter.retry_after("a", 1), Some(Duration::from_millis(70)));
+        assert_eq!(limiter.retry_after("b", 3), Some(Duration::ZERO));
+
+        limiter.release("a", 1).unwrap();
+        assert!(limiter.acquire("a", 1).unwrap());
+
+        clock.advance(Duration::from_millis(100));
+        assert_eq!(limiter.available("a").unwrap(), 3.0);
+        assert!(limiter.acquire("a", 3).unwrap());
+    }
+
+    #[test]
+    fn test_sliding_window_counter_weights_previous_window() {
+        let clock = ManualClock::new();
+        let limiter =
+            SlidingWindowCounter::new(4, Duration::from_millis(200)).with_clock(clock.clone());
+
+        assert!(limiter.acquire("a", 4).unwrap());
+        assert!(!limiter.acquire("a", 1).unwrap());
+
+        // A quarter into the next window, three quarters of the previous
+        // count still apply
+        clock.advance(Duration::from_millis(250));
+        let available = limiter.available("a").unwrap();
+        assert!((available - 1.0).abs() < 1e-9, "available = {}", available);
+
+        // Two windows later the old count no longer matters
+        clock.advance(Duration::from_millis(400));
+        assert!(limiter.acquire("a", 4).unwrap());
+    }
+
+    #[test]
+    fn test_leaky_bucket_spaces_requests() {
+        let clock = ManualClock::new();
+        let limiter = LeakyBucket::new(4, Duration::from_millis(400)).with_clock(clock.clone());
+
+        let waits: Vec<_> = (0..4).map(|_| limiter.reserve("a", 1).unwrap()).collect();
+        assert_eq!(waits, [0, 100, 200, 300].map(Duration::from_millis));
+
+        // The queue is full
+        assert!(limiter.reserve("a", 1).is_none());
+        assert_eq!(limiter.available("a").unwrap(), 0.0);
+        assert_eq!(
+            limiter.retry_after("a", 1),
+            Some(Duration::from_millis(100))
+        );
+        assert!(limiter.reserve("b", 1).is_some());
+    }
+
+    #[test]
+    fn test_leaky_bucket_waits_on_its_clock() {
+        let clock = ManualClock::new();
+        let limiter = LeakyBucket::new(2, Duration::from_secs(20)).with_clock(clock.clone());
+
+        assert!(limiter.acquire("a", 1).unwrap());
+        assert!(limiter.acquire("a", 1).unwrap());
+        // The second caller waited its turn without blocking
+        assert_eq!(clock.advanced(), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_purge_idle() {
+        let clock = ManualClock::new();
+        let window = Duration::from_secs(10);
+        let backends = [
+            RateLimitAlgorithm::SlidingWindowLog,
+            RateLimitAlgorithm::SlidingWindowCounter,
+            RateLimitAlgorithm::LeakyBucket,
+        ]
+        .map(|algorithm| algorithm.backend_with_clock(2, window, clock.clone()));
+
+        for backend in &backends {
+            assert!(backend.acquire("a", 1).unwrap());
+            assert_eq!(backend.purge_idle(), 0);
+        }
+        clock.advance(window * 2);
+        for backend in &backends {
+            assert_eq!(backend.purge_idle(), 1);
+            assert_eq!(backend.available("a").unwrap(), 2.0);
+        }
+    }
+
+    #[test]
+    fn test_max_keys() {
+        let clock = ManualClock::new();
+        let window = Duration::from_secs(10);
+        for algorithm in [
+            RateLimitAlgorithm::TokenBucket,
+            RateLimitAlgorithm::SlidingWindowLog,
+            RateLimitAlgorithm::SlidingWindowCounter,
+            RateLimitAlgorithm::LeakyBucket,
+        ] {
+            let backend = algorithm.bounded_backend(1, window, clock.clone(), Some(2));
+            for key in ["a", "b"] {
+                assert!(backend.acquire(key, 1).unwrap(), "{:?}", algorithm);
+                clock.advance(Duration::from_millis(10));
+            }
+
+            // A third key evicts the state of the first, which starts over
+            assert!(backend.acquire("c", 1).unwrap(), "{:?}", algorithm);
+            assert!(backend.acquire("a", 1).unwrap(), "{:?}", algorithm);
+            assert!(!backend.acquire("c", 1).unwrap(), "{:?}", algorithm);
+            clock.advance(window * 2);
+        }
+    }
+
+    #[test]
+    fn test_queries_evict_nothing() {
+        let clock = ManualClock::new();
+        let window = Duration::from_secs(10);
+        for algorithm in [
+            RateLimitAlgorithm::TokenBucket,
+            RateLimitAlgorithm::SlidingWindowLog,
+            RateLimitAlgorithm::SlidingWindowCounter,
+            RateLimitAlgorithm::LeakyBucket,
+        ] {
+            let backend = algorithm.bounded_backend(1, window, clock.clone(), Some(1));
+            assert!(backend.acquire("a", 1).unwrap(), "{:?}", algorithm);
+
+            // Other keys are new, and querying them keeps the state of `a`
+            assert_eq!(backend.available("b").unwrap(), 1.0, "{:?}", algorithm);
+            let wait = backend.retry_after("c", 1);
+            assert!(wait.is_none_or(|wait| wait.is_zero()), "{:?}", algorithm);
+            backend.release("d", 1).unwrap();
+            assert!(!backend.acquire("a", 1).unwrap(), "{:?}", algorithm);
+            clock.advance(window * 2);
+        }
+    }
+
+    #[test]
+    fn test_algorithm_backend() {
+        let backend = RateLimitAlgorithm::SlidingWindowLog.backend(1, Duration::from_secs(60));
+        assert!(backend.acquire("a", 1).unwrap());
+        assert!(!backend.acquire("a", 1).unwrap());
+        assert_eq!(
+            RateLimitAlgorithm::default(),
+            RateLimitAlgorithm::TokenBucket
+        );
+    }
+}
@@ -0,0 +1,663 @@
+//! Token storage for [`RateLimitAspect`](super::RateLimitAspect).
+
+use super::GLOBAL_KEY;
+use crate::time::{Clock, Duration, Instant, SystemClock};
+use aspect_core::AspectError;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Storage for the per-key state of a rate limiter.
+///
+/// Each key names an independent bucket: the aspect uses one shared key, or
+/// the function name when [`per_function`](super::RateLimitAspect::per_function)
+/// is enabled, followed by the key of the caller when it has a
+/// [`KeyExtractor`](super::KeyExtractor). Bucket capacity and refill rate
+/// are properties of the backend.
+///
+/// The in-process backends keep the state of every key they have seen
+/// until it is [purged](Self::purge_idle), or of at most as many keys as
+/// given to their `with_max_keys`.
+///
+/// The default [`TokenBucket`] keeps buckets in process memory. With the
+/// `redis` feature, `RedisBackend` keeps them in Redis so that every
+/// replica of a service draws from the same buckets.
+pub trait RateLimitBackend: Send + Sync {
+    /// Take `tokens` tokens from the bucket `key`.
+    ///
+    /// Returns `Ok(false)` without taking anything if not enough tokens are
+    /// available.
+    fn acquire(&self, key: &str, tokens: u32) -> Result<bool, AspectError>;
+
+    /// Return `tokens` previously acquired tokens to the bucket `key`.
+    ///
+    /// The bucket never grows beyond its capacity, so returning tokens to
+    /// a bucket that does not exist, as full as a new one, does nothing.
+    fn release(&self, key: &str, tokens: u32) -> Result<(), AspectError>;
+
+    /// Number of tokens currently available in the bucket `key`, that of
+    /// a full bucket if it does not exist.
+    ///
+    /// It only reads the state of the backend: querying a key does not
+    /// create its bucket, so it does not evict the buckets of other keys.
+    fn available(&self, key: &str) -> Result<f64, AspectError>;
+
+    /// How long until `tokens` tokens can be taken from the bucket `key`,
+    /// reported to rejected callers as
+    /// [`AspectError::RateLimited`].
+    ///
+    /// Returns `None` when the backend cannot tell, which is the default.
+    fn retry_after(&self, _key: &str, _tokens: u32) -> Option<Duration> {
+        None
+    }
+
+    /// Forget the keys idle long enough for their state to be that of a new
+    /// key, such as a full bucket, returning how many were forgotten.
+    ///
+    /// Keeps the memory of in-process backends proportional to the keys in
+    /// use rather than to all the keys ever seen. Backends expiring idle
+    /// keys by themselves, which is the default, need not implement it.
+    fn purge_idle(&self) -> usize {
+        0
+    }
+}
+
+/// Make room for one more key in `states` if it holds `max_keys` already:
+/// forget the keys back to the state of a new key by `idle`, then, if
+/// there are still too many, the least recently `used` sixteenth of them.
+///
+/// Forgetting a key in use resets its limit, so `max_keys` should exceed
+/// the keys in use at the same time.
+pub(super) fn make_room<T, U: Ord>(
+    states: &mut HashMap<String, T>,
+    max_keys: Option<usize>,
+    idle: impl Fn(&T) -> bool,
+    used: impl Fn(&T) -> U,
+) {
+    let Some(max_keys) = max_keys else {
+        return;
+    };
+    if states.len() < max_keys {
+        return;
+    }
+    states.retain(|_, state| !idle(state));
+    if states.len() < max_keys {
+        return;
+    }
+
+    // Evicting a batch makes the next keys cheap to add
+    let evict = (states.len() + 1 - max_keys).max(states.len() / 16);
+    let mut by_use: Vec<_> = states
+        .iter()
+        .map(|(key, state)| (used(state), key.clone()))
+        .collect();
+    by_use.select_nth_unstable_by(evict - 1, |a, b| a.0.cmp(&b.0));
+    for (_, key) in &by_use[..evict] {
+        states.remove(key);
+    }
+}
+
+/// The time to refill the tokens missing from `available` to take
+/// `tokens`, at `refill_rate` tokens per second.
+fn refill_time(available: f64, tokens: u32, refill_rate: f64) -> Duration {
+    let missing = (tokens as f64 - available).max(0.0);
+    Duration::from_secs_f64(missing / refill_rate)
+}
+
+/// In-process token bucket backend.
+///
+/// Buckets start full and are created on first use. The bucket shared by
+/// all functions, used unless the aspect limits each function separately,
+/// takes no lock: limiting with it only costs a few atomic operations,
+/// however many threads call limited functions.
+pub struct TokenBucket {
+    max_tokens: f64,
+    refill_rate: f64, // tokens per second
+    global: AtomicBucket,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    max_keys: Option<usize>,
+    clock: Arc<dyn Clock>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Token bucket updated with compare-and-swap instead of a lock.
+///
+/// A bucket refilling continuously is described by the single time at
+/// which it will be full again: `full_at`, in nanoseconds since `epoch`.
+/// Taking a token pushes it one token's refill time later, and the tokens
+/// available at any time are those that will have been refilled by then,
+/// so the token count and the time of the last refill are packed in one
+/// atomic integer.
+struct AtomicBucket {
+    clock: Arc<dyn Clock>,
+    epoch: Instant,
+    full_at: AtomicU64,
+    max_tokens: f64,
+    /// Time to refill one token
+    token_nanos: f64,
+    /// Time to refill the empty bucket
+    capacity_nanos: u64,
+}
+
+impl AtomicBucket {
+    fn new(max_tokens: f64, refill_rate: f64, clock: Arc<dyn Clock>) -> Self {
+        let token_nanos = 1e9 / refill_rate;
+        Self {
+            epoch: clock.now(),
+            clock,
+            full_at: AtomicU64::new(0),
+            max_tokens,
+            token_nanos,
+            capacity_nanos: if max_tokens > 0.0 {
+                (max_tokens * token_nanos).ceil() as u64
+            } else {
+                0
+            },
+        }
+    }
+
+    fn now(&self) -> u64 {
+        self.clock.elapsed(self.epoch).as_nanos() as u64
+    }
+
+    /// The time to refill `tokens` tokens.
+    fn refill_nanos(&self, tokens: u32) -> u64 {
+        (tokens as f64 * self.token_nanos) as u64
+    }
+
+    /// Update `full_at` with `f`, given the current time and the time the
+    /// bucket is full at (not before the current time), until no other
+    /// thread updated it in the meantime. Returns `false` if `f` returns
+    /// `None`, leaving the bucket unchanged.
+    fn update(&self, f: impl Fn(u64, u64) -> Option<u64>) -> bool {
+        let mut full_at = self.full_at.load(Ordering::Relaxed);
+        loop {
+            let now = self.now();
+            let Some(updated) = f(now, full_at.max(now)) else {
+                return false;
+            };
+            match self.full_at.compare_exchange_weak(
+                full_at,
+                updated,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(current) => full_at = current,
+            }
+        }
+    }
+
+    fn acquire(&self, tokens: u32) -> bool {
+        let refill = self.refill_nanos(tokens);
+        self.update(|now, full_at| {
+            let updated = full_at.saturating_add(refill);
+            (updated - now <= self.capacity_nanos).then_some(updated)
+        })
+    }
+
+    fn release(&self, tokens: u32) {
+        let refill = self.refill_nanos(tokens);
+        self.update(|now, full_at| Some(full_at.saturating_sub(refill).max(now)));
+    }
+
+    fn available(&self) -> f64 {
+        let now = self.now();
+        let full_at = self.full_at.load(Ordering::Relaxed).max(now);
+        if full_at == now {
+            return self.max_tokens;
+        }
+        let refilling = (full_at - now) as f64 / self.token_nanos;
+        (self.max_tokens - refilling).max(0.0)
+    }
+}
+
+impl TokenBucket {
+    /// Create a backend allowing `max_requests` per `window` in each bucket.
+    pub fn new(max_requests: u64, window: Duration) -> Self {
+        let max_tokens = max_requests as f64;
+        let refill_rate = max_requests as f64 / window.as_secs_f64();
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        Self {
+            max_tokens,
+            refill_rate,
+            global: AtomicBucket::new(max_tokens, refill_rate, clock.clone()),
+            buckets: Mutex::new(HashMap::new()),
+            max_keys: None,
+            clock,
+        }
+    }
+
+    /// Keep the buckets of at most `max_keys` keys, besides the shared one:
+    /// a new key first evicts the full buckets, then the least recently
+    /// used ones.
+    pub fn with_max_keys(mut self, max_keys: usize) -> Self {
+        self.max_keys = Some(max_keys.max(1));
+        self
+    }
+
+    /// Refill the buckets by the time of `clock` instead of the system
+    /// clock, e.g. a [`ManualClock`](crate::time::ManualClock) in tests.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self.global = AtomicBucket::new(self.max_tokens, self.refill_rate, self.clock.clone());
+        self
+    }
+
+    /// Run `f` on the refilled bucket `key`, creating it if needed.
+    fn with_bucket<R>(&self, key: &str, f: impl FnOnce(&mut Bucket) -> R) -> R {
+        let mut buckets = self.buckets.lock();
+        let now = self.clock.now();
+        if !buckets.contains_key(key) {
+            let idle = |bucket: &Bucket| self.is_full(bucket, now);
+            make_room(&mut buckets, self.max_keys, idle, |bucket| {
+                bucket.last_refill
+            });
+        }
+        let bucket = buckets.entry(key.to_string()).or_insert_with(|| Bucket {
+            tokens: self.max_tokens,
+            last_refill: now,
+        });
+
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.last_refill = now;
+
+        f(bucket)
+    }
+
+    /// The tokens of `bucket` once refilled by `now`.
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        (bucket.tokens + elapsed * self.refill_rate).min(self.max_tokens)
+    }
+
+    /// Whether `bucket` has refilled by `now`, like a new one.
+    fn is_full(&self, bucket: &Bucket, now: Instant) -> bool {
+        self.refilled(bucket, now) >= self.max_tokens
+    }
+}
+
+impl RateLimitBackend for TokenBucket {
+    fn acquire(&self, key: &str, tokens: u32) -> Result<bool, AspectError> {
+        if key == GLOBAL_KEY {
+            return Ok(self.global.acquire(tokens));
+        }
+        Ok(self.with_bucket(key, |bucket| {
+            if bucket.tokens >= tokens as f64 {
+                bucket.tokens -= tokens as f64;
+                true
+            } else {
+                false
+            }
+        }))
+    }
+
+    fn release(&self, key: &str, tokens: u32) -> Result<(), AspectError> {
+        if key == GLOBAL_KEY {
+            self.global.release(tokens);
+            return Ok(());
+        }
+        let now = self.clock.now();
+        if let Some(bucket) = self.buckets.lock().get_mut(key) {
+            bucket.tokens = (self.refilled(bucket, now) + tokens as f64).min(self.max_tokens);
+            bucket.last_refill = now;
+        }
+        Ok(())
+    }
+
+    fn available(&self, key: &str) -> Result<f64, AspectError> {
+        if key == GLOBAL_KEY {
+            return Ok(self.global.available());
+        }
+        let now = self.clock.now();
+        Ok(match self.buckets.lock().get(key) {
+            Some(bucket) => self.refilled(bucket, now),
+            None => self.max_tokens,
+        })
+    }
+
+    fn retry_after(&self, key: &str, tokens: u32) -> Option<Duration> {
+        let available = self.available(key).ok()?;
+        Some(refill_time(available, tokens, self.refill_rate))
+    }
+
+    fn purge_idle(&self) -> usize {
+        let now = self.clock.now();
+        let mut buckets = self.buckets.lock();
+        let before = buckets.len();
+        buckets.retain(|_, bucket| !self.is_full(bucket, now));
+        before - buckets.len()
+    }
+}
+
+#[cfg(feature = "redis")]
+mod redis_backend {
+    use super::{refill_time, RateLimitBackend};
+    use aspect_core::AspectError;
+    use parking_lot::Mutex;
+    use redis::{Client, Connection, RedisResult, Script};
+    use std::time::Duration;
+
+    /// Refills the bucket in `KEYS[1]` using the server clock, then takes
+    /// `ARGV[3]` tokens from it (a negative count returns tokens, zero only
+    /// reads the bucket). Returns whether the tokens were taken and the
+    /// remaining tokens as a string, since Lua numbers are truncated to
+    /// integers in replies.
+    const TOKEN_BUCKET_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2])
+local requested = tonumber(ARGV[3])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) + tonumber(time[2]) / 1000000
+
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(state[1]) or capacity
+local ts = tonumber(state[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * rate)
+
+-- Reading a bucket, or returning tokens to one that does not exist and so
+-- is full, leaves it as it is
+if requested == 0 or (requested < 0 and not state[1]) then
+    return {1, tostring(tokens)}
+end
+
+local allowed = 0
+if requested <= tokens then
+    tokens = math.min(capacity, tokens - requested)
+    allowed = 1
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', tostring(now))
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / rate * 1000) + 1000)
+return {allowed, tostring(tokens)}
+"#;
+
+    /// Redis-backed token buckets, shared by every process using the same
+    /// Redis server and key prefix.
+    ///
+    /// Each bucket is a hash updated atomically by a Lua script that reads
+    /// the Redis server clock, so replicas agree on refills regardless of
+    /// their own clocks. Idle buckets expire once they would be full again.
+    /// Requires Redis 5 or later.
+    ///
+    /// Connection failures surface as errors from the advised call; the
+    /// connection is re-established on the next call.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use aspect_std::ratelimit::RedisBackend;
+    /// use aspect_std::RateLimitAspect;
+    /// use std::time::Duration;
+    ///
+    /// let backend = RedisBackend::new("redis://127.0.0.1/", 100, Duration::from_secs(60))
+    ///     .unwrap()
+    ///     .with_prefix("myapp:ratelimit:");
+    /// let limiter = RateLimitAspect::with_backend(backend).per_function();
+    /// ```
+    pub struct RedisBackend {
+        client: Client,
+        connection: Mutex<Option<Connection>>,
+        script: Script,
+        prefix: String,
+        max_tokens: f64,
+        refill_rate: f64,
+    }
+
+    impl RedisBackend {
+        /// Create a backend allowing `max_requests` per `window` in each
+        /// bucket, using the Redis server at `url`.
+        ///
+        /// The connection is opened on first use.
+        pub fn new(url: &str, max_requests: u64, window: Duration) -> RedisResult<Self> {
+            Ok(Self {
+                client: Client::open(url)?,
+                connection: Mutex::new(None),
+                script: Script::new(TOKEN_BUCKET_SCRIPT),
+                prefix: "aspect:ratelimit:".to_string(),
+                max_tokens: max_requests as f64,
+                refill_rate: max_requests as f64 / window.as_secs_f64(),
+            })
+        }
+
+        /// Set the prefix of the Redis keys holding the buckets.
+        pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+            self.prefix = prefix.into();
+            self
+        }
+
+        /// Run the bucket script, returning `(allowed, remaining tokens)`.
+        fn run(&self, key: &str, tokens: i64) -> Result<(bool, f64), AspectError> {
+            let mut connection = self.connection.lock();
+            let result = match &mut *connection {
+                Some(conn) => self.invoke(conn, key, tokens),
+                None => self.client.get_connection().and_then(|mut conn| {
+                    let result = self.invoke(&mut conn, key, tokens);
+                    *connection = Some(conn);
+                    result
+                }),
+            };
+
+            result.map_err(|err| {
+                if err.is_connection_dropped() || err.is_io_error() {
+                    *connection = None;
+                }
+                AspectError::execution_with_source("rate limit backend unavailable", err)
+            })
+        }
+
+        fn invoke(
+            &self,
+            conn: &mut Connection,
+            key: &str,
+            tokens: i64,
+        ) -> RedisResult<(bool, f64)> {
+            let (allowed, remaining): (i64, String) = self
+                .script
+                .key(format!("{}{}", self.prefix, key))
+                .arg(self.max_tokens)
+                .arg(self.refill_rate)
+                .arg(tokens)
+                .invoke(conn)?;
+            Ok((allowed == 1, remaining.parse().unwrap_or(0.0)))
+        }
+    }
+
+    impl RateLimitBackend for RedisBackend {
+        fn acquire(&self, key: &str, tokens: u32) -> Result<bool, AspectError> {
+            self.run(key, tokens as i64).map(|(allowed, _)| allowed)
+        }
+
+        fn release(&self, key: &str, tokens: u32) -> Result<(), AspectError> {
+            self.run(key, -(tokens as i64)).map(|_| ())
+        }
+
+        fn available(&self, key: &str) -> Result<f64, AspectError> {
+            self.run(key, 0).map(|(_, remaining)| remaining)
+        }
+
+        fn retry_after(&self, key: &str, tokens: u32) -> Option<Duration> {
+            let available = self.available(key).ok()?;
+            Some(refill_time(available, tokens, self.refill_rate))
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+pub use redis_backend::RedisBackend;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::ManualClock;
+
+    #[test]
+    fn test_token_bucket_release() {
+        let bucket = TokenBucket::new(2, Duration::from_secs(60));
+
+        assert!(bucket.acquire("a", 2).unwrap());
+        assert!(!bucket.acquire("a", 1).unwrap());
+        bucket.release("a", 1).unwrap();
+        assert!(bucket.acquire("a", 1).unwrap());
+
+        // Releasing never overfills
+        bucket.release("b", 5).unwrap();
+        assert!((bucket.available("b").unwrap() - 2.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_queries_create_no_buckets() {
+        let clock = ManualClock::new();
+        let bucket = TokenBucket::new(2, Duration::from_secs(2))
+            .with_clock(clock.clone())
+            .with_max_keys(1);
+
+        assert!(bucket.acquire("a", 2).unwrap());
+        assert_eq!(bucket.available("b").unwrap(), 2.0);
+        assert_eq!(bucket.retry_after("c", 1), Some(Duration::ZERO));
+        bucket.release("d", 1).unwrap();
+
+        // `a` was not evicted to make room for the keys queried
+        assert_eq!(bucket.buckets.lock().keys().collect::<Vec<_>>(), ["a"]);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(bucket.available("a").unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_global_bucket() {
+        let bucket = TokenBucket::new(3, Duration::from_secs(60));
+
+        assert!((bucket.available(GLOBAL_KEY).unwrap() - 3.0).abs() < 0.01);
+        assert!(bucket.acquire(GLOBAL_KEY, 2).unwrap());
+        assert!(!bucket.acquire(GLOBAL_KEY, 2).unwrap());
+        assert!((bucket.available(GLOBAL_KEY).unwrap() - 1.0).abs() < 0.01);
+
+        // Other keys have buckets of their own
+        assert!(bucket.acquire("a", 3).unwrap());
+
+        bucket.release(GLOBAL_KEY, 5).unwrap();
+        assert!((bucket.available(GLOBAL_KEY).unwrap() - 3.0).abs() < 0.01);
+        assert!(bucket.acquire(GLOBAL_KEY, 3).unwrap());
+        assert!(!bucket.acquire(GLOBAL_KEY, 1).unwrap());
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let bucket = TokenBucket::new(2, Duration::from_secs(2));
+
+        assert_eq!(bucket.retry_after("a", 1), Some(Duration::ZERO));
+        assert!(bucket.acquire("a", 2).unwrap());
+        let wait = bucket.retry_after("a", 1).unwrap();
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1));
+
+        assert!(bucket.acquire(GLOBAL_KEY, 2).unwrap());
+        let wait = bucket.retry_after(GLOBAL_KEY, 2).unwrap();
+        assert!(wait > Duration::from_millis(1900) && wait <= Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_purge_idle() {
+        let clock = ManualClock::new();
+        let bucket = TokenBucket::new(2, Duration::from_secs(2)).with_clock(clock.clone());
+
+        assert!(bucket.acquire("a", 2).unwrap());
+        assert!(bucket.acquire("b", 1).unwrap());
+        clock.advance(Duration::from_secs(1));
+        // `b` is full again, `a` is not
+        assert_eq!(bucket.purge_idle(), 1);
+        assert_eq!(bucket.available("a").unwrap(), 1.0);
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(bucket.purge_idle(), 1);
+        assert!(bucket.buckets.lock().is_empty());
+    }
+
+    #[test]
+    fn test_max_keys() {
+        let clock = ManualClock::new();
+        let bucket = TokenBucket::new(2, Duration::from_secs(2))
+            .with_clock(clock.clone())
+            .with_max_keys(2);
+
+        assert!(bucket.acquire("a", 2).unwrap());
+        clock.advance(Duration::from_millis(100));
+        assert!(bucket.acquire("b", 2).unwrap());
+        clock.advance(Duration::from_millis(100));
+        // `a`, the least recently used, makes room for `c`
+        assert!(bucket.acquire("c", 1).unwrap());
+        let keys = || {
+            let mut keys: Vec<_> = bucket.buckets.lock().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(keys(), ["b", "c"]);
+        assert!(!bucket.acquire("b", 1).unwrap());
+
+        // Full buckets are evicted first
+        clock.advance(Duration::from_secs(2));
+        assert!(bucket.acquire("d", 2).unwrap());
+        assert_eq!(keys(), ["d"]);
+    }
+
+    #[test]
+    fn test_global_bucket_refill() {
+        let clock = ManualClock::new();
+        let bucket = TokenBucket::new(2, Duration::from_millis(100)).with_clock(clock.clone());
+
+        assert!(bucket.acquire(GLOBAL_KEY, 2).unwrap());
+        assert!(!bucket.acquire(GLOBAL_KEY, 1).unwrap());
+        clock.advance(Duration::from_millis(49));
+        assert!(!bucket.acquire(GLOBAL_KEY, 1).unwrap());
+        clock.advance(Duration::from_millis(1));
+        assert!(bucket.acquire(GLOBAL_KEY, 1).unwrap());
+    }
+
+    #[test]
+    fn test_bucket_refill() {
+        let clock = ManualClock::new();
+        let bucket = TokenBucket::new(2, Duration::from_secs(2)).with_clock(clock.clone());
+
+        assert!(bucket.acquire("a", 2).unwrap());
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(bucket.available("a").unwrap(), 0.5);
+        assert_eq!(bucket.retry_after("a", 1), Some(Duration::from_millis(500)));
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(bucket.available("a").unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_global_bucket_across_threads() {
+        let bucket = TokenBucket::new(1000, Duration::from_secs(3600));
+        let acquired: usize = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        (0..400)
+                            .filter(|_| bucket.acquire(GLOBAL_KEY, 1).unwrap())
+                            .count()
+                    })
+                })
+                .collect();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .sum()
+        });
+        assert_eq!(acquired, 1000);
+    }
+
+    #[test]
+    fn test_token_bucket_multiple_tokens() {
+        let bucket = TokenBucket::new(5, Duration::from_secs(60));
+
+        assert!(!bucket.acquire("a", 6).unwrap());
+        assert!(bucket.acquire("a", 3).unwrap());
+        assert!(!bucket.acquire("a", 3).unwrap());
+        assert!(bucket.acquire("a", 2).unwrap());
+    }
+}
@@ -0,0 +1,169 @@
+//! Masking of sensitive arguments before they are logged.
+
+use aspect_core::Arg;
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Parameter name patterns redacted by [`Redactor::default`].
+pub const DEFAULT_PATTERNS: &[&str] = &[
+    "*password*",
+    "*passwd*",
+    "*secret*",
+    "*token*",
+    "*api_key*",
+    "*apikey*",
+    "*credential*",
+    "*private_key*",
+    "*authorization*",
+];
+
+/// Decides which captured arguments are shown and which are masked.
+///
+/// An argument is masked when its type implements
+/// [`Redact`](aspect_core::Redact), when a process-wide rule of
+/// [`aspect_core::redaction`] redacts it, or when its name matches one of
+/// the patterns. Patterns are case-insensitive globs over the whole parameter
+/// name where `*` matches any sequence of characters.
+///
+/// Used by [`LoggingAspect`](crate::LoggingAspect) and
+/// [`AuditAspect`](crate::AuditAspect), both of which start from
+/// [`Redactor::default`].
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::Arg;
+/// use aspect_std::redact::Redactor;
+///
+/// let redactor = Redactor::default().with_pattern("ssn");
+/// let args = [
+///     Arg::new("user", &"alice".to_string()),
+///     Arg::new("ssn", &123456789u64),
+///     Arg::new("api_token", &"abc".to_string()),
+/// ];
+/// assert_eq!(
+///     redactor.format_args(&args),
+///     r#"user: "alice", ssn: ***, api_token: ***"#
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    patterns: Arc<Vec<String>>,
+    mask: &'static str,
+}
+
+impl Redactor {
+    /// Create a redactor that only masks [`Redact`](aspect_core::Redact)
+    /// types.
+    pub fn new() -> Self {
+        Self {
+            patterns: Arc::new(Vec::new()),
+            mask: "***",
+        }
+    }
+
+    /// Also mask arguments whose name matches `pattern`.
+    pub fn with_pattern(mut self, pattern: &str) -> Self {
+        Arc::make_mut(&mut self.patterns).push(pattern.to_lowercase());
+        self
+    }
+
+    /// Also mask arguments whose name matches any of `patterns`.
+    pub fn with_patterns<'a>(self, patterns: impl IntoIterator<Item = &'a str>) -> Self {
+        patterns
+            .into_iter()
+            .fold(self, |redactor, pattern| redactor.with_pattern(pattern))
+    }
+
+    /// Set the text shown in place of masked values (`***` by default).
+    pub fn with_mask(mut self, mask: &'static str) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    /// Returns `true` if `arg` must not be shown.
+    pub fn is_sensitive(&self, arg: &Arg) -> bool {
+        if arg.is_redacted() {
+            return true;
+        }
+        let name = arg.name.to_lowercase();
+        self.patterns
+            .iter()
+            .any(|pattern| glob_match(pattern, &name))
+    }
+
+    /// Formats `args` as `name: value` pairs separated by commas, masking
+    /// sensitive values.
+    pub fn format_args(&self, args: &[Arg]) -> String {
+        let mut out = String::new();
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            if self.is_sensitive(arg) {
+                let _ = write!(out, "{}: {}", arg.name, self.mask);
+            } else {
+                let _ = write!(out, "{:?}", arg);
+            }
+        }
+        out
+    }
+}
+
+impl Default for Redactor {
+    /// A redactor masking [`Redact`](aspect_core::Redact) types and the
+    /// [`DEFAULT_PATTERNS`].
+    fn default() -> Self {
+        Self::new().with_patterns(DEFAULT_PATTERNS.iter().copied())
+    }
+}
+
+/// Matches `text` against a glob where `*` matches any sequence.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(text) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=text.len())
+                .filter(|&i| text.is_char_boundary(i))
+                .any(|i| glob_match(rest, &text[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*token*", "refresh_token"));
+        assert!(glob_match("*token*", "token"));
+        assert!(glob_match("pass*", "passphrase"));
+        assert!(!glob_match("pass*", "bypass"));
+        assert!(glob_match("ssn", "ssn"));
+        assert!(!glob_match("ssn", "ssn_hash"));
+    }
+
+    #[test]
+    fn test_default_patterns_case_insensitive() {
+        let redactor = Redactor::default();
+        assert!(redactor.is_sensitive(&Arg::new("DB_Password", &1u8)));
+        assert!(!redactor.is_sensitive(&Arg::new("user_id", &1u8)));
+    }
+
+    #[test]
+    fn test_redacted_args_and_mask() {
+        let redactor = Redactor::new().with_mask("[hidden]");
+        let args = [
+            Arg::new("password", &"hunter2".to_string()),
+            Arg::new("key", &7u32).redact(),
+        ];
+        assert_eq!(
+            redactor.format_args(&args),
+            r#"password: "hunter2", key: [hidden]"#
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Aspect applying another aspect to a percentage of keys, for canarying.
+
+use aspect_core::aspect::BoxFuture;
+use aspect_core::rollout::Rollout;
+use aspect_core::{
+    Aspect, AspectError, AspectSnapshot, JoinPoint, Precedence, ProceedingJoinPoint, ReturnValue,
+};
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Aspect applying an inner aspect to the calls made for a percentage of
+/// keys, to roll new cross-cutting behavior out gradually.
+///
+/// Unlike [`SamplingAspect`](crate::SamplingAspect), which picks calls, the
+/// decision depends only on the key of the call, hashed as described in
+/// [`aspect_core::rollout`]: a user in the rollout gets the inner aspect on
+/// every call, on every instance of the program. The key is the first
+/// argument of the function, in its `Debug` form, unless
+/// [`with_key`](Self::with_key) extracts another one. Calls without a key,
+/// such as those whose first argument is not captured, only get the inner
+/// aspect once the rollout reaches 100%.
+///
+/// As for sampling, `async fn`s only get the `before` and `after` advice
+/// of the inner aspect, the decision made in `before` being remembered
+/// until `after`. Asynchronous executions woven through
+/// [`around_async`](Aspect::around_async) get its `around_async` advice.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::{AuditAspect, RolloutAspect};
+/// use aspect_macros::aspect;
+/// use std::sync::LazyLock;
+///
+/// // Audit the orders of 5% of the customers first
+/// static AUDIT_CANARY: LazyLock<RolloutAspect> = LazyLock::new(|| {
+///     RolloutAspect::new(AuditAspect::new(), 5.0)
+///         .with_key(|ctx| ctx.args.first()?.value::<Customer>().map(|c| c.id.to_string()))
+/// });
+///
+/// #[aspect(AUDIT_CANARY.clone())]
+/// fn place_order(customer: &Customer, order: Order) -> Result<(), String> {
+///     Ok(())
+/// }
+/// ```
+pub struct RolloutAspect {
+    inner: Arc<dyn Aspect>,
+    rollout: Rollout,
+    included: AtomicBool,
+}
+
+impl RolloutAspect {
+    /// Apply `inner` to the calls of `percent` percent of keys.
+    ///
+    /// `percent` is clamped to `0.0..=100.0`.
+    pub fn new(inner: impl Aspect + 'static, percent: f64) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            rollout: Rollout::new(percent, first_arg),
+            included: AtomicBool::new(false),
+        }
+    }
+
+    /// Extract the key of calls with `key` instead of using their first
+    /// argument.
+    pub fn with_key<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&JoinPoint) -> Option<String> + Send + Sync + 'static,
+    {
+        self.rollout = Rollout::new(self.rollout.percent(), key);
+        self
+    }
+
+    /// The percentage of keys the inner aspect is applied to.
+    pub fn percent(&self) -> f64 {
+        self.rollout.percent()
+    }
+}
+
+/// The first argument of a call, in its `Debug` form.
+fn first_arg(ctx: &JoinPoint) -> Option<String> {
+    Some(format!("{:?}", ctx.args.first()?.debug()?))
+}
+
+impl Clone for RolloutAspect {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            rollout: self.rollout.clone(),
+            included: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Aspect for RolloutAspect {
+    fn before(&self, ctx: &JoinPoint) {
+        let included = self.rollout.includes(ctx);
+        self.included.store(included, Ordering::Relaxed);
+        if included {
+            self.inner.before(ctx);
+        }
+    }
+
+    fn after(&self, ctx: &JoinPoint, result: &dyn Any) {
+        if self.included.load(Ordering::Relaxed) {
+            self.inner.after(ctx, result);
+        }
+    }
+
+    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
+        if self.included.load(Ordering::Relaxed) {
+            self.inner.after_error(ctx, error);
+        }
+    }
+
+    fn after_returning_mut(&self, ctx: &JoinPoint, result: &mut ReturnValue<'_>) {
+        if self.included.load(Ordering::Relaxed) {
+            self.inner.after_returning_mut(ctx, result);
+        }
+    }
+
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        if self.rollout.includes(pjp.context()) {
+            self.inner.around(pjp)
+        } else {
+            pjp.proceed()
+        }
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        if self.rollout.includes(ctx) {
+            self.inner.around_async(ctx, proceed)
+        } else {
+            proceed
+        }
+    }
+
+    fn precedence(&self) -> Precedence {
+        self.inner.precedence()
+    }
+
+    fn snapshot(&self) -> AspectSnapshot {
+        self.inner.snapshot()
+    }
+
+    fn reentrancy_name(&self) -> Option<&'static str> {
+        self.inner.reentrancy_name()
+    }
+
+    fn reads_args(&self) -> bool {
+        self.rollout.percent() < 100.0 || self.inner.reads_args()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_core::{rollout, Arg, Location};
+    use std::sync::atomic::AtomicUsize;
+
+    #[derive(Clone, Default)]
+    struct Counting {
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl Aspect for Counting {
+        fn before(&self, _ctx: &JoinPoint) {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn joinpoint(user: &str) -> JoinPoint {
+        JoinPoint::new(
+            "checkout",
+            "test",
+            Location {
+                file: "test.rs",
+                line: 1,
+            },
+        )
+        .with_args(vec![Arg::new("user", &user.to_string())])
+    }
+
+    /// Whether the inner aspect ran for a call made for `user`.
+    fn applied(aspect: &RolloutAspect, inner: &Counting, user: &str) -> bool {
+        let before = inner.calls.load(Ordering::SeqCst);
+        let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn Any>), joinpoint(user));
+        aspect.around(pjp).unwrap();
+        inner.calls.load(Ordering::SeqCst) > before
+    }
+
+    #[test]
+    fn test_same_key_same_decision() {
+        let inner = Counting::default();
+        let aspect = RolloutAspect::new(inner.clone(), 50.0);
+
+        let mut included = 0;
+        for i in 0..200 {
+            let user = format!("user-{}", i);
+            let first = applied(&aspect, &inner, &user);
+            assert_eq!(applied(&aspect.clone(), &inner, &user), first);
+            // The key is the first argument, as `Debug` shows it
+            assert_eq!(first, rollout::includes(&format!("{:?}", user), 50.0));
+            included += first as usize;
+        }
+        assert!((60..140).contains(&included), "included {}", included);
+    }
+
+    #[test]
+    fn test_with_key() {
+        let inner = Counting::default();
+        let aspect = RolloutAspect::new(inner.clone(), 10.0).with_key(|ctx| {
+            let user = ctx.args.first()?.value::<String>()?;
+            Some(user.trim_start_matches("user-").to_string())
+        });
+        assert_eq!(aspect.percent(), 10.0);
+        for i in 0..100 {
+            assert_eq!(
+                applied(&aspect, &inner, &format!("user-{}", i)),
+                rollout::includes(&i.to_string(), 10.0)
+            );
+        }
+
+        // Calls without a key wait for the full rollout
+        let keyless = RolloutAspect::new(inner.clone(), 90.0).with_key(|_| None);
+        assert!(!applied(&keyless, &inner, "user-1"));
+        let full = RolloutAspect::new(inner.clone(), 100.0).with_key(|_| None);
+        assert!(applied(&full, &inner, "user-1"));
+    }
+
+    #[test]
+    fn test_before_after_share_decision() {
+        let inner = Counting::default();
+        let aspect = RolloutAspect::new(inner.clone(), 100.0);
+        let ctx = joinpoint("user-1");
+
+        let first = aspect.clone();
+        first.before(&ctx);
+        assert!(first.included.load(Ordering::SeqCst));
+        let none = RolloutAspect::new(inner.clone(), 0.0);
+        none.before(&ctx);
+        assert!(!none.included.load(Ordering::SeqCst));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,285 @@
+//! Aspect applying another aspect to a fraction of calls only.
+
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{
+    Aspect, AspectError, AspectSnapshot, JoinPoint, Precedence, ProceedingJoinPoint, ReturnValue,
+};
+use std::any::Any;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// How calls are picked for sampling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SamplingMode {
+    /// Pick each call independently at random with the configured
+    /// probability.
+    #[default]
+    Random,
+    /// Pick calls evenly spaced in call order, e.g. every hundredth call
+    /// at 1%, starting with the first.
+    Deterministic,
+}
+
+/// Aspect applying an inner aspect to a configurable fraction of calls.
+///
+/// Calls that are not sampled run the advised function directly, so an
+/// expensive observability aspect can be kept in production at e.g. 1%.
+///
+/// For synchronous functions the inner aspect's `around` advice runs for
+/// sampled calls, and for asynchronous executions woven through
+/// [`around_async`](Aspect::around_async), such as those of the tower
+/// adapter, its `around_async` advice. `async fn`s only get `before` and
+/// `after` advice: the decision made in `before` is remembered in the aspect value
+/// until `after`, which works because `#[aspect(...)]` evaluates its
+/// expression once per call. Clones share the sampling sequence but not
+/// this per-call decision.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::{LoggingAspect, SamplingAspect};
+/// use aspect_macros::aspect;
+/// use std::sync::LazyLock;
+///
+/// static SAMPLED_LOGGING: LazyLock<SamplingAspect> =
+///     LazyLock::new(|| SamplingAspect::new(LoggingAspect::new(), 0.01));
+///
+/// #[aspect(SAMPLED_LOGGING.clone())]
+/// fn handle_request(id: u64) -> Result<(), String> {
+///     Ok(())
+/// }
+/// ```
+pub struct SamplingAspect {
+    inner: Arc<dyn Aspect>,
+    ratio: f64,
+    mode: SamplingMode,
+    sequence: Arc<AtomicU64>,
+    sampled: AtomicBool,
+}
+
+impl SamplingAspect {
+    /// Apply `inner` to the fraction `ratio` of calls, picked at random.
+    ///
+    /// `ratio` is clamped to `0.0..=1.0`.
+    pub fn new(inner: impl Aspect + 'static, ratio: f64) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            ratio: ratio.clamp(0.0, 1.0),
+            mode: SamplingMode::default(),
+            sequence: Arc::new(AtomicU64::new(RandomState::new().hash_one(0u64))),
+            sampled: AtomicBool::new(false),
+        }
+    }
+
+    /// Set how calls are picked ([`SamplingMode::Random`] by default).
+    pub fn with_mode(mut self, mode: SamplingMode) -> Self {
+        self.mode = mode;
+        if mode == SamplingMode::Deterministic {
+            self.sequence = Arc::new(AtomicU64::new(0));
+        }
+        self
+    }
+
+    /// Pick calls evenly spaced in call order instead of at random.
+    pub fn deterministic(self) -> Self {
+        self.with_mode(SamplingMode::Deterministic)
+    }
+
+    /// The fraction of calls the inner aspect is applied to.
+    pub fn ratio(&self) -> f64 {
+        self.ratio
+    }
+
+    /// Decides whether the next call is sampled.
+    fn sample(&self) -> bool {
+        match self.mode {
+            SamplingMode::Random => {
+                // SplitMix64 over a shared, randomly seeded sequence
+                let mut z = self
+                    .sequence
+                    .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+                    .wrapping_add(0x9E37_79B9_7F4A_7C15);
+                z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                z ^= z >> 31;
+                // The top 53 bits give a uniform value in [0, 1)
+                ((z >> 11) as f64 / (1u64 << 53) as f64) < self.ratio
+            }
+            SamplingMode::Deterministic => {
+                // Call n is sampled when n * ratio crosses an integer
+                let n = self.sequence.fetch_add(1, Ordering::Relaxed) as f64;
+                (n * self.ratio).ceil() < ((n + 1.0) * self.ratio).ceil()
+            }
+        }
+    }
+}
+
+impl Clone for SamplingAspect {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            ratio: self.ratio,
+            mode: self.mode,
+            sequence: self.sequence.clone(),
+            sampled: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Aspect for SamplingAspect {
+    fn before(&self, ctx: &JoinPoint) {
+        let sampled = self.sample();
+        self.sampled.store(sampled, Ordering::Relaxed);
+        if sampled {
+            self.inner.before(ctx);
+        }
+    }
+
+    fn after(&self, ctx: &JoinPoint, result: &dyn Any) {
+        if self.sampled.load(Ordering::Relaxed) {
+            self.inner.after(ctx, result);
+        }
+    }
+
+    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
+        if self.sampled.load(Ordering::Relaxed) {
+            self.inner.after_error(ctx, error);
+        }
+    }
+
+    fn after_returning_mut(&self, ctx: &JoinPoint, result: &mut ReturnValue<'_>) {
+        if self.sampled.load(Ordering::Relaxed) {
+            self.inner.after_returning_mut(ctx, result);
+        }
+    }
+
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        if self.sample() {
+            self.inner.around(pjp)
+        } else {
+            pjp.proceed()
+        }
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        if self.sample() {
+            self.inner.around_async(ctx, proceed)
+        } else {
+            proceed
+        }
+    }
+
+    fn precedence(&self) -> Precedence {
+        self.inner.precedence()
+    }
+
+    fn reads_args(&self) -> bool {
+        self.inner.reads_args()
+    }
+
+    fn snapshot(&self) -> AspectSnapshot {
+        self.inner.snapshot()
+    }
+
+    fn reentrancy_name(&self) -> Option<&'static str> {
+        self.inner.reentrancy_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_core::Location;
+    use std::sync::atomic::AtomicUsize;
+
+    #[derive(Clone, Default)]
+    struct Counting {
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl Aspect for Counting {
+        fn before(&self, _ctx: &JoinPoint) {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn joinpoint() -> JoinPoint {
+        JoinPoint::new(
+            "handle",
+            "test",
+            Location {
+                file: "test.rs",
+                line: 1,
+            },
+        )
+    }
+
+    fn call(aspect: &SamplingAspect) {
+        let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn Any>), joinpoint());
+        aspect.around(pjp).unwrap();
+    }
+
+    #[test]
+    fn test_deterministic_sampling() {
+        let inner = Counting::default();
+        let aspect = SamplingAspect::new(inner.clone(), 0.25).deterministic();
+
+        let mut picked = Vec::new();
+        for i in 0..8 {
+            let before = inner.calls.load(Ordering::SeqCst);
+            call(&aspect.clone());
+            if inner.calls.load(Ordering::SeqCst) > before {
+                picked.push(i);
+            }
+        }
+        assert_eq!(picked, vec![0, 4]);
+    }
+
+    #[test]
+    fn test_random_sampling_ratio() {
+        let inner = Counting::default();
+        let aspect = SamplingAspect::new(inner.clone(), 0.1);
+        for _ in 0..10_000 {
+            call(&aspect);
+        }
+        let sampled = inner.calls.load(Ordering::SeqCst);
+        assert!((800..1200).contains(&sampled), "sampled {} calls", sampled);
+    }
+
+    #[test]
+    fn test_extreme_ratios() {
+        let inner = Counting::default();
+        for _ in 0..100 {
+            call(&SamplingAspect::new(inner.clone(), 0.0));
+        }
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 0);
+
+        let always = SamplingAspect::new(inner.clone(), 2.0);
+        assert_eq!(always.ratio(), 1.0);
+        for _ in 0..100 {
+            call(&always);
+        }
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 100);
+    }
+
+    #[test]
+    fn test_before_after_share_decision() {
+        let inner = Counting::default();
+        let aspect = SamplingAspect::new(inner.clone(), 0.5).deterministic();
+        let ctx = joinpoint();
+
+        let first = aspect.clone();
+        first.before(&ctx);
+        assert!(first.sampled.load(Ordering::SeqCst));
+        let second = aspect.clone();
+        second.before(&ctx);
+        assert!(!second.sampled.load(Ordering::SeqCst));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+    }
+}
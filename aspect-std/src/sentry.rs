@@ -0,0 +1,306 @@
+//! Sentry error-reporting aspect.
+//!
+//! Available with the `sentry` feature.
+
+use crate::catchpanic::CatchUnwind;
+use crate::redact::Redactor;
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, Precedence, ProceedingJoinPoint};
+use sentry_core::protocol::{Breadcrumb, Event, Level, Map, Value};
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Aspect reporting failed and panicking calls to Sentry.
+///
+/// Errors returned by the function are captured as events at the `Error`
+/// level, and panics at the `Fatal` level before they unwind further. An
+/// event is named after the function (its `transaction`), is tagged with
+/// its `function` and `module`, and carries its `file`, `line` and masked
+/// `args` as extra data. Entries and exits of the advised functions are
+/// recorded as breadcrumbs, so that the event of a failure shows the calls
+/// that led to it.
+///
+/// Events go to the client bound to the current Sentry hub, as set up by
+/// `sentry::init`; without one, the aspect does nothing. The
+/// [correlation id](crate::correlation) of the request is the
+/// `correlation_id` tag of the events, and with the `tokio` feature, the
+/// principal of the [task context](crate::context) is their user.
+///
+/// Panics in `async fn`s are not captured, since the aspect does not see
+/// them unwind.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::SentryAspect;
+/// use aspect_macros::aspect;
+///
+/// let _guard = sentry::init("https://key@sentry.io/42");
+///
+/// #[aspect(SentryAspect::new())]
+/// fn charge(order: u64) -> Result<Receipt, PaymentError> {
+///     gateway::charge(order)
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SentryAspect {
+    redactor: Redactor,
+    breadcrumbs: bool,
+}
+
+impl SentryAspect {
+    /// Create an aspect recording breadcrumbs and masking arguments with
+    /// [`Redactor::default`].
+    pub fn new() -> Self {
+        Self {
+            redactor: Redactor::default(),
+            breadcrumbs: true,
+        }
+    }
+
+    /// Do not record entries and exits as breadcrumbs.
+    pub fn without_breadcrumbs(mut self) -> Self {
+        self.breadcrumbs = false;
+        self
+    }
+
+    /// Set how sensitive arguments are masked.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    fn breadcrumb(&self, ctx: &JoinPoint, message: String) {
+        if !self.breadcrumbs {
+            return;
+        }
+        sentry_core::add_breadcrumb(|| Breadcrumb {
+            category: Some("aspect".to_string()),
+            message: Some(message),
+            level: Level::Info,
+            data: location(ctx),
+            ..Default::default()
+        });
+    }
+
+    /// The event reporting `message` about a call of `ctx`.
+    fn event(&self, ctx: &JoinPoint, level: Level, message: String) -> Event<'static> {
+        let mut event = Event {
+            level,
+            message: Some(message),
+            logger: Some("aspect-rs".to_string()),
+            transaction: Some(ctx.qualified_name()),
+            extra: location(ctx),
+            ..Default::default()
+        };
+        event
+            .tags
+            .insert("function".to_string(), ctx.function_name.to_string());
+        event
+            .tags
+            .insert("module".to_string(), ctx.module_path.to_string());
+        if !ctx.args.is_empty() {
+            let args = self.redactor.format_args(&ctx.args);
+            event.extra.insert("args".to_string(), Value::from(args));
+        }
+        if let Some(id) = crate::correlation::current() {
+            event.tags.insert("correlation_id".to_string(), id);
+        }
+        #[cfg(feature = "tokio")]
+        {
+            event.user = crate::context::principal().map(|id| sentry_core::protocol::User {
+                id: Some(id),
+                ..Default::default()
+            });
+        }
+        event
+    }
+
+    /// Reports how a call of `ctx` ended, resuming its panic if it
+    /// panicked.
+    fn complete(
+        &self,
+        ctx: &JoinPoint,
+        result: Result<Result<Box<dyn Any>, AspectError>, Box<dyn Any + Send>>,
+    ) -> Result<Box<dyn Any>, AspectError> {
+        match result {
+            Ok(result) => {
+                match &result {
+                    Ok(value) => self.after(ctx, value.as_ref()),
+                    Err(error) => self.after_error(ctx, error),
+                }
+                result
+            }
+            Err(payload) => {
+                let error = AspectError::panic(&*payload, Backtrace::disabled());
+                let message = format!("{} panicked: {}", ctx.function_name, error);
+                sentry_core::capture_event(self.event(ctx, Level::Fatal, message));
+                panic::resume_unwind(payload)
+            }
+        }
+    }
+}
+
+impl Default for SentryAspect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where the function of `ctx` is defined, as Sentry data.
+fn location(ctx: &JoinPoint) -> Map<String, Value> {
+    let mut data = Map::new();
+    data.insert("file".to_string(), Value::from(ctx.location.file));
+    data.insert("line".to_string(), Value::from(ctx.location.line));
+    data
+}
+
+impl Aspect for SentryAspect {
+    fn before(&self, ctx: &JoinPoint) {
+        self.breadcrumb(ctx, format!("enter {}", ctx.qualified_name()));
+    }
+
+    fn after(&self, ctx: &JoinPoint, _result: &dyn Any) {
+        self.breadcrumb(ctx, format!("exit {}", ctx.qualified_name()));
+    }
+
+    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
+        let message = format!("{} failed: {}", ctx.function_name, error);
+        sentry_core::capture_event(self.event(ctx, Level::Error, message));
+    }
+
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let ctx = pjp.context().clone();
+        self.before(&ctx);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| pjp.proceed()));
+        self.complete(&ctx, result)
+    }
+
+    /// Reports panics as `around` does, resuming them once reported.
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            self.before(ctx);
+            let result = CatchUnwind(proceed).await;
+            self.complete(ctx, result)
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::OBSERVABILITY
+    }
+
+    fn reads_args(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_core::{Arg, Location};
+    use sentry_core::test::with_captured_events;
+
+    fn pjp<'a>(
+        f: impl FnOnce() -> Result<Box<dyn Any>, AspectError> + 'a,
+    ) -> ProceedingJoinPoint<'a> {
+        ProceedingJoinPoint::new(
+            f,
+            JoinPoint::new(
+                "charge",
+                "shop::payment",
+                Location {
+                    file: "payment.rs",
+                    line: 12,
+                },
+            )
+            .with_args(vec![
+                Arg::new("order", &7u64),
+                Arg::new("card_token", &"tok_123".to_string()),
+            ]),
+        )
+    }
+
+    #[test]
+    fn test_error_captured() {
+        let aspect = SentryAspect::new();
+        let events = with_captured_events(|| {
+            let ok = aspect.around(pjp(|| Ok(Box::new(()) as Box<dyn Any>)));
+            assert!(ok.is_ok());
+            let err = aspect.around(pjp(|| Err(AspectError::execution("card declined"))));
+            assert!(err.is_err());
+        });
+
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.level, Level::Error);
+        assert!(event.message.as_deref().unwrap().contains("card declined"));
+        assert_eq!(event.transaction.as_deref(), Some("shop::payment::charge"));
+        assert_eq!(event.tags["function"], "charge");
+        assert_eq!(event.extra["line"], 12);
+        let args = event.extra["args"].as_str().unwrap();
+        assert!(args.contains("order: 7"));
+        assert!(!args.contains("tok_123"));
+
+        // The calls leading to the failure
+        let crumbs: Vec<_> = event
+            .breadcrumbs
+            .iter()
+            .filter_map(|crumb| crumb.message.as_deref())
+            .collect();
+        assert_eq!(
+            crumbs,
+            [
+                "enter shop::payment::charge",
+                "exit shop::payment::charge",
+                "enter shop::payment::charge",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_panic_captured() {
+        let aspect = SentryAspect::new().without_breadcrumbs();
+        let events = with_captured_events(|| {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                let _ = aspect.around(pjp(|| panic!("gateway unreachable")));
+            }));
+            assert!(result.is_err());
+        });
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].level, Level::Fatal);
+        assert!(events[0]
+            .message
+            .as_deref()
+            .unwrap()
+            .contains("gateway unreachable"));
+        assert!(events[0].breadcrumbs.is_empty());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_task_context() {
+        use crate::context::{self, AspectContext};
+
+        let aspect = SentryAspect::new();
+        let cx = AspectContext::new()
+            .with_principal("alice")
+            .with_correlation_id("req-9");
+        let events = with_captured_events(|| {
+            context::sync_scope(cx, || {
+                let _ = aspect.around(pjp(|| Err(AspectError::execution("declined"))));
+            });
+        });
+
+        assert_eq!(events[0].tags["correlation_id"], "req-9");
+        assert_eq!(
+            events[0].user.as_ref().unwrap().id.as_deref(),
+            Some("alice")
+        );
+    }
+}
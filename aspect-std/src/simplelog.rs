@@ -0,0 +1,105 @@
+//! Entry/exit logging aspect, for targets without `std`.
+
+use aspect_core::{Aspect, AspectError, JoinPoint, Precedence};
+use core::any::Any;
+use log::Level;
+
+/// Logs the entry, exit and errors of the functions it advises through the
+/// `log` facade, at one level.
+///
+/// It writes the lines of `LoggingAspect`, available with `std`, in its
+/// default text format, `[ENTRY] read_sensor (src/sensor.rs:12)`,
+/// `[EXIT] read_sensor` and `[ERROR] read_sensor failed: ...`, with the
+/// module of the function as target, but has none of its options: no
+/// arguments, no rules per module, no JSON. It formats nothing itself, so
+/// it works without `std`, with any logger, such as one writing to a serial
+/// port.
+///
+/// Errors are logged at [`Level::Error`] whatever the level of the other
+/// lines.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::SimpleLoggingAspect;
+/// use aspect_macros::aspect;
+/// use log::Level;
+///
+/// #[aspect(SimpleLoggingAspect::with_level(Level::Debug))]
+/// fn read_sensor() -> Result<u16, SensorError> {
+///     // ...
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct SimpleLoggingAspect {
+    level: Level,
+}
+
+impl SimpleLoggingAspect {
+    /// Create a logging aspect writing at [`Level::Info`].
+    pub const fn new() -> Self {
+        Self::with_level(Level::Info)
+    }
+
+    /// Create a logging aspect writing entries and exits at `level`.
+    pub const fn with_level(level: Level) -> Self {
+        Self { level }
+    }
+
+    /// The level of entries and exits.
+    pub fn level(&self) -> Level {
+        self.level
+    }
+}
+
+impl Default for SimpleLoggingAspect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Aspect for SimpleLoggingAspect {
+    fn before(&self, ctx: &JoinPoint) {
+        log::log!(
+            target: ctx.module_path,
+            self.level,
+            "[ENTRY] {} ({}:{})",
+            ctx.function_name,
+            ctx.location.file,
+            ctx.location.line
+        );
+    }
+
+    fn after(&self, ctx: &JoinPoint, _result: &dyn Any) {
+        log::log!(target: ctx.module_path, self.level, "[EXIT] {}", ctx.function_name);
+    }
+
+    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
+        log::log!(
+            target: ctx.module_path,
+            Level::Error,
+            "[ERROR] {} failed: {:?}",
+            ctx.function_name,
+            error
+        );
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::OBSERVABILITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        assert_eq!(SimpleLoggingAspect::new().level(), Level::Info);
+        assert_eq!(SimpleLoggingAspect::default().level(), Level::Info);
+        assert_eq!(
+            SimpleLoggingAspect::with_level(Level::Trace).level(),
+            Level::Trace
+        );
+    }
+}
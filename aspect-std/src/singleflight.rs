@@ -0,0 +1,488 @@
+//! Request coalescing aspect sharing one execution between concurrent
+//! identical calls.
+
+use crate::caching::{default_cloners, AllArgs, CacheKey, CachedValue, Cloner, KeyExtractor};
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, Precedence, ProceedingJoinPoint};
+use parking_lot::{Condvar, Mutex, RwLock};
+use std::any::{Any, TypeId};
+use std::backtrace::Backtrace;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Poll, Waker};
+
+/// Outcome of a call, kept until every waiting caller has a copy.
+enum Outcome {
+    /// The call succeeded with a value of a shareable type
+    Value {
+        value: CachedValue,
+        load: fn(&(dyn Any + Send + Sync)) -> Option<Box<dyn Any>>,
+    },
+    /// The call succeeded, but its result type is not shareable, so waiting
+    /// callers run the function themselves
+    Unshareable,
+    /// The call failed
+    Error(AspectError),
+}
+
+/// One call in progress and the callers waiting for it.
+#[derive(Default)]
+struct Flight {
+    outcome: Mutex<Option<Outcome>>,
+    done: Condvar,
+    /// Asynchronous callers waiting for the call
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl Flight {
+    /// Blocks until the call finishes. Returns `None` if the caller has to
+    /// run the function itself.
+    fn wait(&self) -> Option<Result<Box<dyn Any>, AspectError>> {
+        let mut outcome = self.outcome.lock();
+        while outcome.is_none() {
+            self.done.wait(&mut outcome);
+        }
+        Self::share(outcome.as_ref()?)
+    }
+
+    /// Like [`wait`](Self::wait), without blocking the thread.
+    fn wait_async(&self) -> impl Future<Output = Option<Result<Box<dyn Any>, AspectError>>> + '_ {
+        std::future::poll_fn(move |cx| {
+            let outcome = self.outcome.lock();
+            match outcome.as_ref() {
+                Some(outcome) => Poll::Ready(Self::share(outcome)),
+                None => {
+                    // Registered under the outcome lock, so `finish` wakes it
+                    self.wakers.lock().push(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+    }
+
+    /// The result of the call for a waiting caller.
+    fn share(outcome: &Outcome) -> Option<Result<Box<dyn Any>, AspectError>> {
+        match outcome {
+            Outcome::Value { value, load } => load(&**value).map(Ok),
+            Outcome::Unshareable => None,
+            Outcome::Error(err) => Some(Err(copy_error(err))),
+        }
+    }
+
+    fn finish(&self, outcome: Outcome) {
+        *self.outcome.lock() = Some(outcome);
+        self.done.notify_all();
+        for waker in std::mem::take(&mut *self.wakers.lock()) {
+            waker.wake();
+        }
+    }
+}
+
+/// Reproduces an error for each caller that shared the failed call.
+fn copy_error(err: &AspectError) -> AspectError {
+    match err {
+        AspectError::ExecutionError { message, .. } => AspectError::execution(message.clone()),
+        AspectError::WeavingError { message } => AspectError::weaving(message.clone()),
+        AspectError::Panic { payload, .. } => AspectError::Panic {
+            payload: payload.clone(),
+            backtrace: Backtrace::disabled(),
+        },
+        AspectError::Denied { required, actual } => {
+            AspectError::denied(required.clone(), actual.clone())
+        }
+        AspectError::RateLimited {
+            target,
+            retry_after,
+        } => AspectError::rate_limited(target.clone(), *retry_after),
+        AspectError::CircuitOpen { target, reopens_in } => {
+            AspectError::circuit_open(target.clone(), *reopens_in)
+        }
+        AspectError::Timeout { target, elapsed } => AspectError::timeout(target.clone(), *elapsed),
+        AspectError::Overloaded {
+            target,
+            retry_after,
+        } => AspectError::overloaded(target.clone(), *retry_after),
+        AspectError::Custom(err) => AspectError::execution(err.to_string()),
+    }
+}
+
+/// Aspect coalescing concurrent calls with the same key into one execution.
+///
+/// The first call for a key runs the function; calls with the same key that
+/// arrive while it is running block until it finishes and then return a
+/// clone of its result, or a copy of its error. This keeps a burst of
+/// identical requests, such as the misses after a cache entry expires, from
+/// all reaching the backend. Unlike [`CachingAspect`](crate::CachingAspect),
+/// nothing is kept once the call completes.
+///
+/// Keys are computed like cache keys, by a [`KeyExtractor`] ([`AllArgs`] by
+/// default); calls without a key are not coalesced. Results are shared as
+/// clones, so the return type has to be registered with
+/// [`shareable`](Self::shareable) unless it is a primitive or `String`.
+/// Waiting callers run the function themselves when the result type is not
+/// registered.
+///
+/// Waiting blocks the calling thread, so the aspect is meant for synchronous
+/// functions called from several threads, and for asynchronous executions
+/// woven through [`around_async`](Aspect::around_async), such as those of
+/// the tower adapter, which wait without blocking. Share one instance
+/// through a static so that all calls see the same in-flight set.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::SingleFlightAspect;
+/// use aspect_macros::aspect;
+/// use std::sync::LazyLock;
+///
+/// static COALESCE: LazyLock<SingleFlightAspect> =
+///     LazyLock::new(|| SingleFlightAspect::new().shareable::<Vec<u8>>());
+///
+/// #[aspect(COALESCE.clone())]
+/// fn load_avatar(user_id: u64) -> Result<Vec<u8>, String> {
+///     // Only one concurrent request per user reaches the backend
+///     fetch_from_storage(user_id)
+/// }
+/// ```
+#[derive(Clone)]
+pub struct SingleFlightAspect {
+    key_extractor: Arc<dyn KeyExtractor>,
+    cloners: Arc<RwLock<HashMap<TypeId, Cloner>>>,
+    flights: Arc<Mutex<HashMap<CacheKey, Arc<Flight>>>>,
+    coalesced: Arc<AtomicU64>,
+}
+
+impl SingleFlightAspect {
+    /// Create a new single-flight aspect keyed on all arguments.
+    pub fn new() -> Self {
+        Self {
+            key_extractor: Arc::new(AllArgs),
+            cloners: Arc::new(RwLock::new(default_cloners())),
+            flights: Arc::default(),
+            coalesced: Arc::default(),
+        }
+    }
+
+    /// Set how calls are keyed (default: [`AllArgs`]).
+    pub fn with_key_extractor(mut self, extractor: impl KeyExtractor + 'static) -> Self {
+        self.key_extractor = Arc::new(extractor);
+        self
+    }
+
+    /// Allow results of type `T` to be shared between callers.
+    ///
+    /// For functions returning `Result<T, E>`, register `T`.
+    pub fn shareable<T: Clone + Send + Sync + 'static>(self) -> Self {
+        self.cloners
+            .write()
+            .insert(TypeId::of::<T>(), Cloner::of::<T>());
+        self
+    }
+
+    /// Number of calls currently running.
+    pub fn in_flight(&self) -> usize {
+        self.flights.lock().len()
+    }
+
+    /// Number of calls that waited for another call instead of running.
+    pub fn coalesced_calls(&self) -> u64 {
+        self.coalesced.load(Ordering::Relaxed)
+    }
+
+    /// Joins the flight of the call of `ctx`, or starts one; `None` for calls
+    /// without a key.
+    fn board(&self, ctx: &JoinPoint) -> Option<Role<'_>> {
+        let key = CacheKey {
+            module_path: ctx.module_path,
+            function_name: ctx.function_name,
+            hash: self.key_extractor.extract(ctx)?,
+        };
+
+        let mut flights = self.flights.lock();
+        if let Some(flight) = flights.get(&key).cloned() {
+            self.coalesced.fetch_add(1, Ordering::Relaxed);
+            return Some(Role::Follower(flight));
+        }
+        let flight = Arc::new(Flight::default());
+        flights.insert(key, flight.clone());
+        Some(Role::Leader(Leader {
+            aspect: self,
+            key,
+            flight,
+            outcome: None,
+        }))
+    }
+
+    fn outcome(&self, result: &Result<Box<dyn Any>, AspectError>) -> Outcome {
+        let value = match result {
+            Ok(value) => value,
+            Err(err) => return Outcome::Error(copy_error(err)),
+        };
+        let Some(cloner) = self.cloners.read().get(&(**value).type_id()).cloned() else {
+            return Outcome::Unshareable;
+        };
+        match (cloner.store)(&**value) {
+            Some(value) => Outcome::Value {
+                value,
+                load: cloner.load,
+            },
+            None => Outcome::Unshareable,
+        }
+    }
+}
+
+impl Default for SingleFlightAspect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What a call does in the flight of its key.
+enum Role<'a> {
+    /// Wait for the call already running
+    Follower(Arc<Flight>),
+    /// Run the function for the callers who will wait
+    Leader(Leader<'a>),
+}
+
+/// Ends the flight of the call that runs the function, including when it
+/// panics, so waiting callers are never left blocked.
+struct Leader<'a> {
+    aspect: &'a SingleFlightAspect,
+    key: CacheKey,
+    flight: Arc<Flight>,
+    outcome: Option<Outcome>,
+}
+
+impl Drop for Leader<'_> {
+    fn drop(&mut self) {
+        self.aspect.flights.lock().remove(&self.key);
+        let outcome = self.outcome.take().unwrap_or_else(|| {
+            Outcome::Error(AspectError::execution(format!(
+                "shared call to {} panicked",
+                self.key.function_name
+            )))
+        });
+        self.flight.finish(outcome);
+    }
+}
+
+impl Aspect for SingleFlightAspect {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        match self.board(pjp.context()) {
+            None => pjp.proceed(),
+            Some(Role::Follower(flight)) => match flight.wait() {
+                Some(result) => result,
+                None => pjp.proceed(),
+            },
+            Some(Role::Leader(mut leader)) => {
+                let result = pjp.proceed();
+                leader.outcome = Some(self.outcome(&result));
+                result
+            }
+        }
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            match self.board(ctx) {
+                None => proceed.await,
+                Some(Role::Follower(flight)) => {
+                    if let Some(result) = flight.wait_async().await {
+                        return result;
+                    }
+                    proceed.await
+                }
+                Some(Role::Leader(mut leader)) => {
+                    let result = proceed.await;
+                    leader.outcome = Some(self.outcome(&result));
+                    result
+                }
+            }
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::CACHING
+    }
+
+    fn reads_args(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_macros::aspect;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::{Barrier, LazyLock};
+    use std::thread;
+    use std::time::Duration;
+
+    static COALESCE: LazyLock<SingleFlightAspect> = LazyLock::new(SingleFlightAspect::new);
+    static LOOKUPS: AtomicUsize = AtomicUsize::new(0);
+
+    #[aspect(COALESCE.clone())]
+    fn slow_lookup(id: u64) -> Result<String, String> {
+        LOOKUPS.fetch_add(1, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(100));
+        if id == 0 {
+            Err("no record 0".to_string())
+        } else {
+            Ok(format!("record {}", id))
+        }
+    }
+
+    fn concurrently(
+        n: usize,
+        f: impl Fn() -> Result<String, String> + Sync,
+    ) -> Vec<Result<String, String>> {
+        let barrier = Barrier::new(n);
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..n)
+                .map(|_| {
+                    scope.spawn(|| {
+                        barrier.wait();
+                        f()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        })
+    }
+
+    // Both cases share the LOOKUPS counter, so they run in one test.
+    #[test]
+    fn test_concurrent_calls_share_one_execution() {
+        let results = concurrently(8, || slow_lookup(7));
+        assert!(results.iter().all(|r| r.as_deref() == Ok("record 7")));
+        assert_eq!(LOOKUPS.load(Ordering::SeqCst), 1);
+        assert_eq!(COALESCE.coalesced_calls(), 7);
+        assert_eq!(COALESCE.in_flight(), 0);
+
+        // Errors are shared too, and nothing is remembered afterwards
+        let results = concurrently(4, || slow_lookup(0));
+        assert!(results
+            .iter()
+            .all(|r| r.as_ref().unwrap_err().contains("no record 0")));
+        assert_eq!(LOOKUPS.load(Ordering::SeqCst), 2);
+
+        slow_lookup(7).unwrap();
+        assert_eq!(LOOKUPS.load(Ordering::SeqCst), 3);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Report(u64);
+
+    static REPORTS: LazyLock<SingleFlightAspect> = LazyLock::new(SingleFlightAspect::new);
+    static BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+    #[aspect(REPORTS.clone())]
+    fn build_report(id: u64) -> Report {
+        BUILDS.fetch_add(1, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(50));
+        Report(id)
+    }
+
+    #[test]
+    fn test_unshareable_results_run_separately() {
+        let reports: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..3).map(|_| scope.spawn(|| build_report(1))).collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert!(reports.iter().all(|r| *r == Report(1)));
+        // Report is not shareable, so every caller built its own
+        assert_eq!(BUILDS.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_leader_panic_releases_waiters() {
+        let aspect = SingleFlightAspect::new();
+        let joinpoint = || {
+            aspect_core::JoinPoint::new(
+                "explode",
+                "test",
+                aspect_core::Location {
+                    file: "test.rs",
+                    line: 1,
+                },
+            )
+        };
+        let started = Barrier::new(2);
+
+        thread::scope(|scope| {
+            let leader = scope.spawn(|| {
+                let _ = aspect.around(ProceedingJoinPoint::new(
+                    || {
+                        started.wait();
+                        thread::sleep(Duration::from_millis(50));
+                        panic!("boom")
+                    },
+                    joinpoint(),
+                ));
+            });
+            started.wait();
+            let err = aspect
+                .around(ProceedingJoinPoint::new(
+                    || Ok(Box::new(()) as Box<dyn Any>),
+                    joinpoint(),
+                ))
+                .unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Execution error: shared call to explode panicked"
+            );
+            assert!(leader.join().is_err());
+        });
+        assert_eq!(aspect.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_async_callers_wait_for_leader() {
+        let aspect = SingleFlightAspect::new();
+        let ctx = JoinPoint::new(
+            "load",
+            "test",
+            aspect_core::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        );
+        let runs = &AtomicUsize::new(0);
+        // Pending on its first poll, as a call waiting for I/O
+        let call = || {
+            let mut polled = false;
+            Box::pin(std::future::poll_fn(move |_| {
+                if !std::mem::replace(&mut polled, true) {
+                    return Poll::Pending;
+                }
+                runs.fetch_add(1, Ordering::SeqCst);
+                Poll::Ready(Ok(Box::new(7u32) as Box<dyn Any>))
+            }))
+        };
+        let mut cx = std::task::Context::from_waker(Waker::noop());
+
+        let mut leader = aspect.around_async(&ctx, call());
+        let mut follower = aspect.around_async(&ctx, call());
+        assert!(leader.as_mut().poll(&mut cx).is_pending());
+        assert!(follower.as_mut().poll(&mut cx).is_pending());
+
+        let Poll::Ready(Ok(value)) = leader.as_mut().poll(&mut cx) else {
+            panic!("leader not done");
+        };
+        assert_eq!(*value.downcast::<u32>().unwrap(), 7);
+        let Poll::Ready(Ok(value)) = follower.as_mut().poll(&mut cx) else {
+            panic!("follower not done");
+        };
+        assert_eq!(*value.downcast::<u32>().unwrap(), 7);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(aspect.coalesced_calls(), 1);
+    }
+}
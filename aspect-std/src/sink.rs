@@ -0,0 +1,319 @@
+//! Pluggable metric sinks for pushing measurements to external pipelines.
+
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+/// A destination for measurements recorded by aspects.
+///
+/// [`TimingAspect`](crate::TimingAspect) and [`MetricsAspect`](crate::MetricsAspect)
+/// forward every measurement to their sink (if one is configured) in addition
+/// to keeping their in-memory statistics. Tags are `(key, value)` pairs; the
+/// aspects tag each measurement with the advised function's name.
+///
+/// Implementations must not block the caller for long since they run on the
+/// advised function's call path.
+pub trait MetricsSink: Send + Sync {
+    /// Increment a counter by `value`.
+    fn count(&self, name: &str, value: u64, tags: &[(&str, &str)]);
+
+    /// Record a duration.
+    fn timing(&self, name: &str, duration: Duration, tags: &[(&str, &str)]);
+
+    /// Set a gauge to `value`.
+    fn gauge(&self, name: &str, value: f64, tags: &[(&str, &str)]);
+}
+
+/// StatsD sink sending metrics over UDP.
+///
+/// In plain StatsD mode, tag values are folded into the metric name
+/// (`prefix.name.value`), since StatsD has no notion of tags. In DogStatsD
+/// mode they are sent as `|#key:value` tags, as understood by the Datadog
+/// agent and Telegraf.
+///
+/// Send failures are ignored: metrics are best-effort and must never fail
+/// the advised function.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use aspect_std::sink::StatsdSink;
+/// use aspect_std::TimingAspect;
+/// use std::sync::Arc;
+///
+/// let sink = StatsdSink::new("127.0.0.1:8125").unwrap().dogstatsd();
+/// let timing = TimingAspect::new().with_sink(Arc::new(sink));
+/// ```
+#[derive(Debug)]
+pub struct StatsdSink {
+    socket: UdpSocket,
+    prefix: String,
+    dogstatsd: bool,
+}
+
+impl StatsdSink {
+    /// Create a sink sending to the given StatsD address with the `aspect` prefix.
+    pub fn new(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            prefix: "aspect".to_string(),
+            dogstatsd: false,
+        })
+    }
+
+    /// Set the metric name prefix (use an empty string for none).
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Send tags in the DogStatsD format instead of folding them into names.
+    pub fn dogstatsd(mut self) -> Self {
+        self.dogstatsd = true;
+        self
+    }
+
+    fn format(&self, name: &str, value: &str, kind: &str, tags: &[(&str, &str)]) -> String {
+        let mut line = String::new();
+        if !self.prefix.is_empty() {
+            line.push_str(&self.prefix);
+            line.push('.');
+        }
+        line.push_str(name);
+
+        if !self.dogstatsd {
+            for (_, tag_value) in tags {
+                line.push('.');
+                line.push_str(&sanitize(tag_value));
+            }
+        }
+
+        line.push(':');
+        line.push_str(value);
+        line.push('|');
+        line.push_str(kind);
+
+        if self.dogstatsd && !tags.is_empty() {
+            line.push_str("|#");
+            for (i, (key, tag_value)) in tags.iter().enumerate() {
+                if i > 0 {
+                    line.push(',');
+                }
+                line.push_str(key);
+                line.push(':');
+                line.push_str(&sanitize(tag_value));
+            }
+        }
+
+        line
+    }
+
+    fn send(&self, line: String) {
+        if let Err(err) = self.socket.send(line.as_bytes()) {
+            log::debug!("failed to send statsd metric: {}", err);
+        }
+    }
+}
+
+impl MetricsSink for StatsdSink {
+    fn count(&self, name: &str, value: u64, tags: &[(&str, &str)]) {
+        self.send(self.format(name, &value.to_string(), "c", tags));
+    }
+
+    fn timing(&self, name: &str, duration: Duration, tags: &[(&str, &str)]) {
+        let millis = format!("{:.3}", duration.as_secs_f64() * 1000.0);
+        self.send(self.format(name, &millis, "ms", tags));
+    }
+
+    fn gauge(&self, name: &str, value: f64, tags: &[(&str, &str)]) {
+        self.send(self.format(name, &value.to_string(), "g", tags));
+    }
+}
+
+/// Sink recording measurements through the [`metrics`](https://docs.rs/metrics)
+/// facade (`metrics` feature).
+///
+/// Measurements go to whatever recorder the application installed (for
+/// example `metrics-exporter-prometheus`), so aspects report alongside the
+/// application's own metrics. Names are joined to the prefix with an
+/// underscore: counts become counters, gauges become gauges, and timings
+/// become histograms of seconds named `<prefix>_<name>_seconds`. Tags become
+/// labels.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_std::sink::MetricsFacadeSink;
+/// use aspect_std::{MetricsAspect, TimingAspect};
+/// use std::sync::Arc;
+///
+/// let sink = Arc::new(MetricsFacadeSink::new());
+/// let metrics = MetricsAspect::new().with_sink(sink.clone());
+/// let timing = TimingAspect::new().with_sink(sink);
+/// ```
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone)]
+pub struct MetricsFacadeSink {
+    prefix: String,
+}
+
+#[cfg(feature = "metrics")]
+impl MetricsFacadeSink {
+    /// Create a sink with the `aspect` prefix.
+    pub fn new() -> Self {
+        Self {
+            prefix: "aspect".to_string(),
+        }
+    }
+
+    /// Set the metric name prefix (use an empty string for none).
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    fn name(&self, name: &str) -> String {
+        if self.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}_{}", self.prefix, name)
+        }
+    }
+
+    fn labels(tags: &[(&str, &str)]) -> Vec<metrics::Label> {
+        tags.iter()
+            .map(|(key, value)| metrics::Label::new(key.to_string(), value.to_string()))
+            .collect()
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Default for MetricsFacadeSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl MetricsSink for MetricsFacadeSink {
+    fn count(&self, name: &str, value: u64, tags: &[(&str, &str)]) {
+        metrics::counter!(self.name(name), Self::labels(tags)).increment(value);
+    }
+
+    fn timing(&self, name: &str, duration: Duration, tags: &[(&str, &str)]) {
+        let name = self.name(&format!("{}_seconds", name));
+        metrics::histogram!(name, Self::labels(tags)).record(duration.as_secs_f64());
+    }
+
+    fn gauge(&self, name: &str, value: f64, tags: &[(&str, &str)]) {
+        metrics::gauge!(self.name(name), Self::labels(tags)).set(value);
+    }
+}
+
+/// Replaces characters that have a meaning in the StatsD line protocol.
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            ':' | '|' | '@' | '#' | ',' | ' ' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receiver() -> (UdpSocket, String) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        let addr = socket.local_addr().unwrap().to_string();
+        (socket, addr)
+    }
+
+    fn recv(socket: &UdpSocket) -> String {
+        let mut buf = [0u8; 512];
+        let len = socket.recv(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..len]).into_owned()
+    }
+
+    #[test]
+    fn test_statsd_folds_tags_into_name() {
+        let (socket, addr) = receiver();
+        let sink = StatsdSink::new(addr).unwrap();
+
+        sink.count("calls", 1, &[("function", "fetch_user")]);
+        assert_eq!(recv(&socket), "aspect.calls.fetch_user:1|c");
+
+        sink.timing("duration", Duration::from_micros(1500), &[]);
+        assert_eq!(recv(&socket), "aspect.duration:1.500|ms");
+    }
+
+    #[test]
+    fn test_dogstatsd_tags() {
+        let (socket, addr) = receiver();
+        let sink = StatsdSink::new(addr).unwrap().with_prefix("app").dogstatsd();
+
+        sink.gauge("in_flight", 3.0, &[("function", "a::b"), ("env", "prod")]);
+        assert_eq!(recv(&socket), "app.in_flight:3|g|#function:a__b,env:prod");
+    }
+
+    #[test]
+    fn test_empty_prefix() {
+        let (socket, addr) = receiver();
+        let sink = StatsdSink::new(addr).unwrap().with_prefix("");
+
+        sink.count("calls", 2, &[]);
+        assert_eq!(recv(&socket), "calls:2|c");
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_metrics_facade_sink() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let sink = MetricsFacadeSink::new();
+
+        metrics::with_local_recorder(&recorder, || {
+            sink.count("calls", 2, &[("function", "fetch")]);
+            sink.timing(
+                "duration",
+                Duration::from_millis(250),
+                &[("function", "fetch")],
+            );
+            sink.with_prefix("").gauge("in_flight", 3.0, &[]);
+        });
+
+        let mut values: Vec<_> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                let labels: Vec<_> = key
+                    .labels()
+                    .map(|label| format!("{}={}", label.key(), label.value()))
+                    .collect();
+                (key.name().to_string(), labels, value)
+            })
+            .collect();
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+
+        assert_eq!(values.len(), 3);
+        assert_eq!(values[0].0, "aspect_calls");
+        assert_eq!(values[0].1, vec!["function=fetch"]);
+        assert_eq!(values[0].2, DebugValue::Counter(2));
+        assert_eq!(values[1].0, "aspect_duration_seconds");
+        assert!(matches!(&values[1].2, DebugValue::Histogram(v) if v.len() == 1 && v[0] == 0.25));
+        assert_eq!(values[2].0, "in_flight");
+        assert!(matches!(values[2].2, DebugValue::Gauge(v) if v == 3.0));
+    }
+}
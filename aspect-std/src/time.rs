@@ -0,0 +1,314 @@
+//! Clock types used by the aspects.
+//!
+//! These are those of `std::time`, except on `wasm32-unknown-unknown`,
+//! whose `std` clocks panic: there they come from
+//! [web-time](https://docs.rs/web-time), which reads the clocks of the
+//! browser or JavaScript runtime. Code passing instants to aspects or
+//! reading them back, such as [`deadline::current`](crate::deadline::current),
+//! should use these to build for both. [`Clock::sleep`] blocks the thread,
+//! which that target cannot do.
+//!
+//! Aspects read the time with [`now`] and [`system_now`]. The aspects
+//! measuring or limiting time, [`TimingAspect`](crate::TimingAspect),
+//! [`CircuitBreakerAspect`](crate::CircuitBreakerAspect), the expiry of
+//! [`CachingAspect`](crate::CachingAspect), the backends of
+//! [`RateLimitAspect`](crate::RateLimitAspect) and
+//! [`LoadShedAspect`](crate::LoadShedAspect), read it from a [`Clock`]
+//! given to their `with_clock` instead, the [`SystemClock`] by default,
+//! which is [`now`].
+//!
+//! A [`ManualClock`] is the deterministic clock of tests: given to
+//! `with_clock`, it is the time of that aspect on every thread, and with
+//! the `test-util` feature, installed on a thread with `install`, it is
+//! the time [`now`] and [`system_now`] tell there, so that one clock
+//! drives all the aspects of a test. Blocking waits, such as those of
+//! [`ConcurrencyLimitAspect`](crate::ConcurrencyLimitAspect), always take
+//! real time.
+
+pub use std::time::Duration;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub use web_time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// The current instant: [`Instant::now`], or the time of the
+/// [`ManualClock`] installed on the thread if there is one.
+#[inline]
+pub fn now() -> Instant {
+    #[cfg(feature = "test-util")]
+    if let Some(clock) = installed::clock() {
+        return clock.now();
+    }
+    Instant::now()
+}
+
+/// The current system time: [`SystemTime::now`], or the time of the
+/// [`ManualClock`] installed on the thread if there is one.
+#[inline]
+pub fn system_now() -> SystemTime {
+    #[cfg(feature = "test-util")]
+    if let Some(clock) = installed::clock() {
+        return clock.system_now();
+    }
+    SystemTime::now()
+}
+
+/// The time elapsed since `earlier`, as [`now`] tells it.
+#[inline]
+pub fn elapsed(earlier: Instant) -> Duration {
+    now().saturating_duration_since(earlier)
+}
+
+/// Source of the time of an aspect.
+pub trait Clock: Send + Sync {
+    /// The current instant.
+    fn now(&self) -> Instant;
+
+    /// The time elapsed since `earlier`.
+    fn elapsed(&self, earlier: Instant) -> Duration {
+        self.now().saturating_duration_since(earlier)
+    }
+
+    /// Block the current thread for `duration`.
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// A shared clock, such as the one an aspect hands to the stores it
+/// builds.
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    #[inline]
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+
+    fn elapsed(&self, earlier: Instant) -> Duration {
+        (**self).elapsed(earlier)
+    }
+
+    fn sleep(&self, duration: Duration) {
+        (**self).sleep(duration);
+    }
+}
+
+/// The time [`now`] tells, so that a [`ManualClock`] installed on the
+/// thread still applies. The default clock of the aspects.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        #[cfg(feature = "test-util")]
+        if let Some(clock) = installed::clock() {
+            return clock.sleep(duration);
+        }
+        std::thread::sleep(duration);
+    }
+}
+
+/// A clock standing still until [advanced](Self::advance), for
+/// deterministic tests of time-based aspects.
+///
+/// Clones share their time, so a test keeps one and gives another to the
+/// aspects, on whichever thread they run. With the `test-util` feature, it
+/// can also be installed on a thread with `install`, as the time of all
+/// the aspects running there, including those not given a clock. Sleeping
+/// on it advances it instead of blocking.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_std::time::{Duration, ManualClock};
+/// use aspect_std::CircuitBreakerAspect;
+///
+/// let clock = ManualClock::new();
+/// let breaker = CircuitBreakerAspect::new(1, Duration::from_secs(30)).with_clock(clock.clone());
+/// // ... a failed call opens the circuit
+/// clock.advance(Duration::from_secs(30));
+/// // ... the next call is let through at once
+/// ```
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    start: Instant,
+    system_start: SystemTime,
+    /// Nanoseconds advanced since `start`
+    advanced: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    /// A clock stopped at the current time.
+    pub fn new() -> Self {
+        Self {
+            start: now(),
+            system_start: system_now(),
+            advanced: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Move the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        self.advanced.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    /// How far the clock advanced since it was created.
+    pub fn advanced(&self) -> Duration {
+        Duration::from_nanos(self.advanced.load(Ordering::Relaxed))
+    }
+
+    /// The current system time of the clock.
+    pub fn system_now(&self) -> SystemTime {
+        self.system_start + self.advanced()
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + self.advanced()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+#[cfg(feature = "test-util")]
+pub use installed::InstalledClock;
+
+#[cfg(feature = "test-util")]
+mod installed {
+    use super::ManualClock;
+    use std::cell::RefCell;
+    use std::marker::PhantomData;
+
+    thread_local! {
+        static CLOCK: RefCell<Option<ManualClock>> = const { RefCell::new(None) };
+    }
+
+    pub(super) fn clock() -> Option<ManualClock> {
+        CLOCK.with(|clock| clock.borrow().clone())
+    }
+
+    impl ManualClock {
+        /// Make the clock the time of the current thread until the guard is
+        /// dropped: [`now`](super::now) and [`system_now`](super::system_now)
+        /// tell its time there, so that e.g. a cache entry expires or a
+        /// circuit breaker half-opens exactly when the test says. Other
+        /// threads keep theirs, so tests running in parallel do not see each
+        /// other's clocks. Available with the `test-util` feature.
+        ///
+        /// Installations nest: the clock of the thread before is restored
+        /// when the guard is dropped.
+        ///
+        /// # Example
+        ///
+        /// ```rust,ignore
+        /// use aspect_std::time::{Duration, ManualClock};
+        ///
+        /// let clock = ManualClock::new();
+        /// let _installed = clock.install();
+        /// cache.put(key, value);
+        /// clock.advance(Duration::from_secs(61));
+        /// assert!(cache.get(key).is_none());
+        /// ```
+        pub fn install(&self) -> InstalledClock {
+            let previous = CLOCK.with(|clock| clock.replace(Some(self.clone())));
+            InstalledClock {
+                previous,
+                _thread: PhantomData,
+            }
+        }
+    }
+
+    /// Restores the clock of the thread when dropped, see
+    /// [`ManualClock::install`].
+    #[must_use = "the clock is uninstalled when the guard is dropped"]
+    pub struct InstalledClock {
+        previous: Option<ManualClock>,
+        // The installation belongs to its thread
+        _thread: PhantomData<*const ()>,
+    }
+
+    impl Drop for InstalledClock {
+        fn drop(&mut self) {
+            let previous = self.previous.take();
+            CLOCK.with(|clock| *clock.borrow_mut() = previous);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+
+        let shared = clock.clone();
+        std::thread::spawn(move || shared.advance(Duration::from_secs(5)))
+            .join()
+            .unwrap();
+        assert_eq!(clock.elapsed(start), Duration::from_secs(5));
+
+        clock.sleep(Duration::from_secs(1));
+        assert_eq!(clock.advanced(), Duration::from_secs(6));
+    }
+
+    #[test]
+    #[cfg(feature = "test-util")]
+    fn test_installed_clock() {
+        let clock = ManualClock::new();
+        let installed = clock.install();
+        let start = now();
+        let start_system = system_now();
+        assert_eq!(now(), start);
+
+        // One clock for the aspects given it and those reading `now`
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(elapsed(start), Duration::from_secs(5));
+        assert_eq!(SystemClock.elapsed(start), Duration::from_secs(5));
+        assert_eq!(clock.elapsed(start), Duration::from_secs(5));
+        assert_eq!(
+            system_now().duration_since(start_system).unwrap(),
+            Duration::from_secs(5)
+        );
+        SystemClock.sleep(Duration::from_secs(1));
+        assert_eq!(clock.advanced(), Duration::from_secs(6));
+
+        {
+            let inner = ManualClock::new();
+            let _installed = inner.install();
+            inner.advance(Duration::from_secs(1));
+            assert_eq!(elapsed(start), Duration::from_secs(7));
+        }
+        assert_eq!(elapsed(start), Duration::from_secs(6));
+
+        // Other threads keep the real time
+        std::thread::spawn(move || assert!(elapsed(start) < Duration::from_secs(5)))
+            .join()
+            .unwrap();
+
+        drop(installed);
+        assert!(elapsed(start) < Duration::from_secs(5));
+    }
+}
@@ -1,17 +1,37 @@
 //! Performance monitoring aspect with statistics.
 
-use aspect_core::{Aspect, AspectError, ProceedingJoinPoint};
+use crate::time::{self, Clock, Duration, Instant, SystemClock};
+use crate::histogram::Histogram;
+use crate::sink::MetricsSink;
+use aspect_core::aspect::BoxFuture;
+use aspect_core::snapshot::SnapshotValue;
+use aspect_core::{Aspect, AspectError, AspectSnapshot, JoinPoint, Precedence, ProceedingJoinPoint};
 use parking_lot::Mutex;
 use std::any::Any;
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
+use std::hash::BuildHasher;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
 
 /// Timing aspect that measures function execution time and collects statistics.
 ///
+/// Besides count, total, min and max, each function gets a [`Histogram`] of
+/// its execution times, so percentiles such as p99 are available from
+/// [`FunctionStats`]. Statistics can be printed with
+/// [`print_stats`](Self::print_stats) or shipped to a [`MetricsSink`] with
+/// [`export`](Self::export).
+///
+/// Statistics can additionally be broken down by a label computed from each
+/// call, such as a tenant or an HTTP status class; see
+/// [`with_label`](Self::with_label).
+///
+/// Statistics are kept in shards picked by function name, so that threads
+/// calling different functions rarely wait for one another.
+///
 /// # Example
 ///
 /// ```rust,ignore
+/// use aspect_std::sink::StatsdSink;
 /// use aspect_std::TimingAspect;
 /// use aspect_macros::aspect;
 ///
@@ -25,12 +45,86 @@ use std::time::{Duration, Instant};
 ///
 /// // Later, print statistics
 /// timing.print_stats();
+///
+/// // Or send them elsewhere
+/// timing.export(&StatsdSink::new("127.0.0.1:8125").unwrap());
 /// ```
 #[derive(Clone)]
 pub struct TimingAspect {
-    stats: Arc<Mutex<HashMap<String, FunctionStats>>>,
+    stats: Arc<ShardedStats<String>>,
     threshold_ms: Option<u64>,
     print_on_complete: bool,
+    sink: Option<Arc<dyn MetricsSink>>,
+    label: Option<Label>,
+    labeled_stats: Arc<ShardedStats<(String, String)>>,
+    clock: Arc<dyn Clock>,
+}
+
+/// Number of shards of [`ShardedStats`].
+const SHARDS: usize = 16;
+
+/// Statistics keyed by `K`, in shards picked by the hash of the function
+/// they are about.
+struct ShardedStats<K> {
+    hasher: RandomState,
+    shards: [Mutex<HashMap<K, FunctionStats>>; SHARDS],
+}
+
+impl<K: Clone> ShardedStats<K> {
+    fn new() -> Self {
+        Self {
+            hasher: RandomState::new(),
+            shards: std::array::from_fn(|_| Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The shard of the statistics of `function_name`.
+    fn shard(&self, function_name: &str) -> &Mutex<HashMap<K, FunctionStats>> {
+        &self.shards[self.hasher.hash_one(function_name) as usize % SHARDS]
+    }
+
+    /// The statistics of all the shards.
+    fn entries(&self) -> Vec<(K, FunctionStats)> {
+        self.shards
+            .iter()
+            .flat_map(|shard| {
+                let shard = shard.lock();
+                shard
+                    .iter()
+                    .map(|(key, stat)| (key.clone(), stat.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn clear(&self) {
+        for shard in &self.shards {
+            shard.lock().clear();
+        }
+    }
+
+    /// Keep the statistics for which `keep` holds, returning how many were
+    /// removed.
+    fn retain(&self, keep: impl Fn(&FunctionStats) -> bool) -> usize {
+        let mut removed = 0;
+        for shard in &self.shards {
+            let mut shard = shard.lock();
+            let before = shard.len();
+            shard.retain(|_, stat| keep(stat));
+            removed += before - shard.len();
+        }
+        removed
+    }
+}
+
+/// Computes the label of a call from its joinpoint and outcome.
+type LabelFn = dyn Fn(&JoinPoint, Result<&dyn Any, &AspectError>) -> Option<String> + Send + Sync;
+
+/// Named dimension statistics are broken down by.
+#[derive(Clone)]
+struct Label {
+    name: &'static str,
+    extract: Arc<LabelFn>,
 }
 
 /// Statistics for a single function.
@@ -46,6 +140,10 @@ pub struct FunctionStats {
     pub min_duration: Duration,
     /// Maximum execution time
     pub max_duration: Duration,
+    /// Distribution of execution times
+    pub histogram: Histogram,
+    /// When the last call completed
+    pub last_call: Instant,
 }
 
 impl FunctionStats {
@@ -56,6 +154,8 @@ impl FunctionStats {
             total_duration: Duration::ZERO,
             min_duration: Duration::MAX,
             max_duration: Duration::ZERO,
+            histogram: Histogram::new(),
+            last_call: time::now(),
         }
     }
 
@@ -64,6 +164,28 @@ impl FunctionStats {
         self.total_duration += duration;
         self.min_duration = self.min_duration.min(duration);
         self.max_duration = self.max_duration.max(duration);
+        self.histogram.record(duration);
+    }
+
+    /// Execution time below which `percentile` percent of the calls
+    /// completed, e.g. `percentile(99.0)`.
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        self.histogram.percentile(percentile)
+    }
+
+    /// Median execution time.
+    pub fn p50(&self) -> Duration {
+        self.percentile(50.0)
+    }
+
+    /// 90th percentile execution time.
+    pub fn p90(&self) -> Duration {
+        self.percentile(90.0)
+    }
+
+    /// 99th percentile execution time.
+    pub fn p99(&self) -> Duration {
+        self.percentile(99.0)
     }
 
     /// Get average execution time.
@@ -80,9 +202,13 @@ impl TimingAspect {
     /// Create a new timing aspect.
     pub fn new() -> Self {
         Self {
-            stats: Arc::new(Mutex::new(HashMap::new())),
+            stats: Arc::new(ShardedStats::new()),
             threshold_ms: None,
             print_on_complete: false,
+            sink: None,
+            label: None,
+            labeled_stats: Arc::new(ShardedStats::new()),
+            clock: Arc::new(SystemClock),
         }
     }
 
@@ -98,54 +224,222 @@ impl TimingAspect {
         self
     }
 
+    /// Also send each measured duration to `sink` as a `duration` timing.
+    pub fn with_sink(mut self, sink: Arc<dyn MetricsSink>) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+
+    /// Measure durations with `clock` instead of the system clock, e.g. a
+    /// [`ManualClock`](crate::time::ManualClock) in tests.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Also keep statistics per value of the label `name`, computed by
+    /// `extract` from each call's joinpoint and outcome.
+    ///
+    /// The function result is the return value, or the `Ok` value for
+    /// functions returning `Result`. Calls for which `extract` returns
+    /// `None` only count towards the function's overall statistics. The
+    /// label is also added as a tag to the timings sent to the sink.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use aspect_std::TimingAspect;
+    ///
+    /// // Per-tenant latency, taken from the `tenant` argument
+    /// let timing = TimingAspect::new().with_label("tenant", |ctx, _result| {
+    ///     ctx.arg("tenant")?.value::<String>().cloned()
+    /// });
+    ///
+    /// // Successes and failures separately
+    /// let timing = TimingAspect::new().with_label("outcome", |_ctx, result| {
+    ///     Some(if result.is_ok() { "ok" } else { "error" }.to_string())
+    /// });
+    /// ```
+    pub fn with_label(
+        mut self,
+        name: &'static str,
+        extract: impl Fn(&JoinPoint, Result<&dyn Any, &AspectError>) -> Option<String>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.label = Some(Label {
+            name,
+            extract: Arc::new(extract),
+        });
+        self
+    }
+
     /// Get statistics for a specific function.
     pub fn get_stats(&self, function_name: &str) -> Option<FunctionStats> {
-        self.stats.lock().get(function_name).cloned()
+        self.stats
+            .shard(function_name)
+            .lock()
+            .get(function_name)
+            .cloned()
     }
 
     /// Get all function statistics.
     pub fn all_stats(&self) -> Vec<FunctionStats> {
-        self.stats.lock().values().cloned().collect()
+        self.stats
+            .entries()
+            .into_iter()
+            .map(|(_, stat)| stat)
+            .collect()
+    }
+
+    /// Get statistics for the calls of a function with the label `value`.
+    pub fn get_labeled_stats(&self, function_name: &str, value: &str) -> Option<FunctionStats> {
+        self.labeled_stats
+            .shard(function_name)
+            .lock()
+            .get(&(function_name.to_string(), value.to_string()))
+            .cloned()
     }
 
-    /// Print statistics for all functions.
+    /// Get the statistics of a function for each label value seen, sorted by
+    /// label value.
+    pub fn labeled_stats(&self, function_name: &str) -> Vec<(String, FunctionStats)> {
+        let mut stats: Vec<_> = self
+            .labeled_stats
+            .shard(function_name)
+            .lock()
+            .iter()
+            .filter(|((name, _), _)| name == function_name)
+            .map(|((_, value), stat)| (value.clone(), stat.clone()))
+            .collect();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        stats
+    }
+
+    /// Print statistics for all functions, followed by their breakdown by
+    /// label if one is configured.
     pub fn print_stats(&self) {
-        let stats = self.stats.lock();
+        let stats = self.all_stats();
+        let labeled_stats = self.labeled_stats.entries();
         if stats.is_empty() {
             println!("No timing data collected.");
             return;
         }
 
         println!("\n=== Timing Statistics ===");
-        println!("{:<30} {:>10} {:>15} {:>15} {:>15} {:>15}",
-                 "Function", "Calls", "Total", "Average", "Min", "Max");
-        println!("{:-<100}", "");
-
-        for stat in stats.values() {
+        println!("{:<30} {:>10} {:>15} {:>15} {:>15} {:>15} {:>15} {:>15} {:>15}",
+                 "Function", "Calls", "Total", "Average", "Min", "p50", "p90", "p99", "Max");
+        println!("{:-<148}", "");
+
+        let label_name = self.label.as_ref().map_or("", |label| label.name);
+        let rows =
+            stats
+                .iter()
+                .map(|stat| (stat.name.clone(), stat))
+                .chain(labeled_stats.iter().map(|((name, value), stat)| {
+                    (format!("{}{{{}={}}}", name, label_name, value), stat)
+                }));
+        for (row, stat) in rows {
             println!(
-                "{:<30} {:>10} {:>15.3?} {:>15.3?} {:>15.3?} {:>15.3?}",
-                stat.name,
+                "{:<30} {:>10} {:>15.3?} {:>15.3?} {:>15.3?} {:>15.3?} {:>15.3?} {:>15.3?} {:>15.3?}",
+                row,
                 stat.count,
                 stat.total_duration,
                 stat.average_duration(),
                 stat.min_duration,
+                stat.p50(),
+                stat.p90(),
+                stat.p99(),
                 stat.max_duration
             );
         }
         println!();
     }
 
+    /// Send the current statistics of every function to `sink` as gauges
+    /// tagged with the function name:
+    ///
+    /// - `calls`: number of calls
+    /// - `duration_mean`: average execution time, in seconds
+    /// - `duration_quantile`: execution time in seconds, once per `quantile`
+    ///   tag value `0` (min), `0.5`, `0.9`, `0.99` and `1` (max)
+    ///
+    /// Gauges hold the totals since the statistics were last cleared, so
+    /// exporting periodically is safe. Statistics broken down by label are
+    /// exported as well, with the label as an additional tag.
+    pub fn export(&self, sink: &(impl MetricsSink + ?Sized)) {
+        for stat in self.all_stats() {
+            export_stats(sink, &stat, &[("function", &stat.name)]);
+        }
+        if let Some(label) = &self.label {
+            for ((_, value), stat) in self.labeled_stats.entries() {
+                export_stats(
+                    sink,
+                    &stat,
+                    &[("function", &stat.name), (label.name, &value)],
+                );
+            }
+        }
+    }
+
     /// Clear all statistics.
     pub fn clear(&self) {
-        self.stats.lock().clear();
+        self.stats.clear();
+        self.labeled_stats.clear();
+    }
+
+    /// Remove the statistics of the functions, and label values, not called
+    /// for `idle`, returning how many were removed.
+    ///
+    /// Keeps memory bounded when functions or label values come and go,
+    /// e.g. labels by tenant.
+    pub fn trim_idle(&self, idle: Duration) -> usize {
+        let now = self.clock.now();
+        let active = |stat: &FunctionStats| now.saturating_duration_since(stat.last_call) < idle;
+        self.stats.retain(active) + self.labeled_stats.retain(active)
     }
 
     fn record_timing(&self, function_name: &str, duration: Duration) {
-        let mut stats = self.stats.lock();
-        stats
-            .entry(function_name.to_string())
-            .or_insert_with(|| FunctionStats::new(function_name.to_string()))
-            .record(duration);
+        let now = self.clock.now();
+        let mut stats = self.stats.shard(function_name).lock();
+        // Only allocate the name the first time the function is timed
+        if let Some(stat) = stats.get_mut(function_name) {
+            stat.record(duration);
+            stat.last_call = now;
+            return;
+        }
+        let mut stat = FunctionStats::new(function_name.to_string());
+        stat.record(duration);
+        stat.last_call = now;
+        stats.insert(function_name.to_string(), stat);
+    }
+
+    fn record_labeled_timing(&self, function_name: &str, value: String, duration: Duration) {
+        let now = self.clock.now();
+        let mut stats = self.labeled_stats.shard(function_name).lock();
+        let stat = stats
+            .entry((function_name.to_string(), value))
+            .or_insert_with(|| FunctionStats::new(function_name.to_string()));
+        stat.record(duration);
+        stat.last_call = now;
+    }
+}
+
+/// Sends one function's statistics to `sink` as gauges tagged with `tags`.
+fn export_stats(sink: &(impl MetricsSink + ?Sized), stat: &FunctionStats, tags: &[(&str, &str)]) {
+    sink.gauge("calls", stat.count as f64, tags);
+    sink.gauge("duration_mean", stat.average_duration().as_secs_f64(), tags);
+    for (quantile, value) in [
+        ("0", stat.min_duration),
+        ("0.5", stat.p50()),
+        ("0.9", stat.p90()),
+        ("0.99", stat.p99()),
+        ("1", stat.max_duration),
+    ] {
+        let mut quantile_tags = tags.to_vec();
+        quantile_tags.push(("quantile", quantile));
+        sink.gauge("duration_quantile", value.as_secs_f64(), &quantile_tags);
     }
 }
 
@@ -155,15 +449,31 @@ impl Default for TimingAspect {
     }
 }
 
-impl Aspect for TimingAspect {
-    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
-        let function_name = pjp.context().function_name.to_string();
-        let start = Instant::now();
-
-        let result = pjp.proceed();
-
-        let duration = start.elapsed();
-        self.record_timing(&function_name, duration);
+impl TimingAspect {
+    /// Records a call of `function_name` that took `duration`; `ctx` is only
+    /// needed with a label.
+    fn complete(
+        &self,
+        function_name: &'static str,
+        ctx: Option<&JoinPoint>,
+        duration: Duration,
+        result: &Result<Box<dyn Any>, AspectError>,
+    ) {
+        self.record_timing(function_name, duration);
+        let label = self.label.as_ref().zip(ctx).and_then(|(label, ctx)| {
+            let value = (label.extract)(ctx, result.as_ref().map(|value| &**value))?;
+            Some((label.name, value))
+        });
+        if let Some(sink) = &self.sink {
+            let mut tags = vec![("function", function_name)];
+            if let Some((name, value)) = &label {
+                tags.push((name, value));
+            }
+            sink.timing("duration", duration, &tags);
+        }
+        if let Some((_, value)) = label {
+            self.record_labeled_timing(function_name, value, duration);
+        }
 
         // Check threshold
         if let Some(threshold_ms) = self.threshold_ms {
@@ -179,15 +489,76 @@ impl Aspect for TimingAspect {
         if self.print_on_complete {
             println!("[TIMING] {} took {:?}", function_name, duration);
         }
+    }
+}
+
+impl Aspect for TimingAspect {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let function_name = pjp.context().function_name;
+        // The label may depend on the arguments, which proceeding consumes
+        let ctx = self.label.as_ref().map(|_| pjp.context().clone());
+        let start = self.clock.now();
+
+        let result = pjp.proceed();
+        let elapsed = self.clock.elapsed(start);
 
+        self.complete(function_name, ctx.as_ref(), elapsed, &result);
         result
     }
+
+    /// Times until `proceed` completes, not only until it is created.
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            let start = self.clock.now();
+            let result = proceed.await;
+            let elapsed = self.clock.elapsed(start);
+            self.complete(ctx.function_name, Some(ctx), elapsed, &result);
+            result
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::OBSERVABILITY
+    }
+
+    /// The statistics of each function, sorted by name, with durations in
+    /// milliseconds.
+    fn snapshot(&self) -> AspectSnapshot {
+        let millis = |duration: Duration| duration.as_secs_f64() * 1e3;
+        let mut stats = self.all_stats();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        let functions = stats
+            .into_iter()
+            .map(|stat| {
+                let entries = vec![
+                    ("count".to_string(), stat.count.into()),
+                    ("avg_ms".to_string(), millis(stat.average_duration()).into()),
+                    ("min_ms".to_string(), millis(stat.min_duration).into()),
+                    ("max_ms".to_string(), millis(stat.max_duration).into()),
+                    ("p50_ms".to_string(), millis(stat.p50()).into()),
+                    ("p99_ms".to_string(), millis(stat.p99()).into()),
+                ];
+                (stat.name, SnapshotValue::Map(entries))
+            })
+            .collect();
+
+        AspectSnapshot::new("TimingAspect")
+            .with("threshold_ms", self.threshold_ms)
+            .with("functions", SnapshotValue::Map(functions))
+    }
+
+    fn reads_args(&self) -> bool {
+        self.label.is_some()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use aspect_core::JoinPoint;
 
     #[test]
     fn test_timing_aspect_creation() {
@@ -237,4 +608,229 @@ mod tests {
 
         assert_eq!(aspect.all_stats().len(), 2);
     }
+
+    #[test]
+    fn test_snapshot() {
+        let aspect = TimingAspect::new().with_threshold(50);
+        aspect.record_timing("func2", Duration::from_millis(30));
+        aspect.record_timing("func1", Duration::from_millis(10));
+
+        let snapshot = aspect.snapshot();
+        assert_eq!(snapshot.get("threshold_ms"), Some(&50u64.into()));
+        let Some(SnapshotValue::Map(functions)) = snapshot.get("functions") else {
+            panic!("no functions in {}", snapshot);
+        };
+        assert_eq!(functions[0].0, "func1");
+        let SnapshotValue::Map(func1) = &functions[0].1 else {
+            panic!("no stats in {}", snapshot);
+        };
+        assert_eq!(func1[0], ("count".to_string(), 1u64.into()));
+        assert_eq!(functions[1].0, "func2");
+    }
+
+    #[test]
+    fn test_with_clock() {
+        let clock = crate::time::ManualClock::new();
+        let aspect = TimingAspect::new().with_clock(clock.clone());
+        let ctx = JoinPoint::new(
+            "query",
+            "test",
+            aspect_core::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        );
+        let pjp = ProceedingJoinPoint::new(
+            move || {
+                clock.advance(Duration::from_millis(250));
+                Ok(Box::new(()) as Box<dyn Any>)
+            },
+            ctx,
+        );
+        aspect.around(pjp).unwrap();
+
+        let stats = aspect.get_stats("query").unwrap();
+        assert_eq!(stats.min_duration, Duration::from_millis(250));
+        assert_eq!(stats.max_duration, Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_trim_idle() {
+        let clock = crate::time::ManualClock::new();
+        let aspect = TimingAspect::new().with_clock(clock.clone());
+
+        aspect.record_timing("old", Duration::from_millis(10));
+        clock.advance(Duration::from_secs(30));
+        aspect.record_timing("recent", Duration::from_millis(10));
+        clock.advance(Duration::from_secs(30));
+
+        assert_eq!(aspect.trim_idle(Duration::from_secs(60)), 1);
+        assert!(aspect.get_stats("old").is_none());
+        assert!(aspect.get_stats("recent").is_some());
+        assert_eq!(aspect.trim_idle(Duration::from_secs(60)), 0);
+    }
+
+    #[test]
+    fn test_concurrent_record() {
+        let aspect = TimingAspect::new();
+        let names: Vec<String> = (0..40).map(|i| format!("func{}", i)).collect();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for name in &names {
+                        aspect.record_timing(name, Duration::from_millis(1));
+                    }
+                });
+            }
+        });
+
+        assert_eq!(aspect.all_stats().len(), 40);
+        assert!(aspect.all_stats().iter().all(|stat| stat.count == 4));
+        assert_eq!(aspect.get_stats("func17").unwrap().name, "func17");
+
+        aspect.clear();
+        assert!(aspect.all_stats().is_empty());
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let aspect = TimingAspect::new();
+        for ms in 1..=100 {
+            aspect.record_timing("query", Duration::from_millis(ms));
+        }
+
+        let stats = aspect.get_stats("query").unwrap();
+        for (actual, expected) in [(stats.p50(), 50), (stats.p90(), 90), (stats.p99(), 99)] {
+            let expected = Duration::from_millis(expected);
+            assert!(
+                actual >= expected && actual <= expected.mul_f64(1.02),
+                "{:?} vs {:?}",
+                actual,
+                expected
+            );
+        }
+        assert_eq!(stats.percentile(100.0), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_label_breakdown() {
+        let aspect = TimingAspect::new().with_label("tenant", |ctx, result| {
+            let tenant = ctx.arg("tenant")?.value::<String>()?;
+            Some(match result {
+                Ok(_) => tenant.clone(),
+                Err(_) => format!("{}-failed", tenant),
+            })
+        });
+        let call = |tenant: &str, ok: bool| {
+            let ctx = JoinPoint::new(
+                "query",
+                "test",
+                aspect_core::Location {
+                    file: "test.rs",
+                    line: 1,
+                },
+            )
+            .with_args(vec![aspect_core::Arg::new("tenant", &tenant.to_string())]);
+            let pjp = ProceedingJoinPoint::new(
+                move || {
+                    if ok {
+                        Ok(Box::new(()) as Box<dyn Any>)
+                    } else {
+                        Err(AspectError::execution("failed"))
+                    }
+                },
+                ctx,
+            );
+            let _ = aspect.around(pjp);
+        };
+
+        call("acme", true);
+        call("acme", true);
+        call("globex", true);
+        call("globex", false);
+
+        assert_eq!(aspect.get_stats("query").unwrap().count, 4);
+        assert_eq!(aspect.get_labeled_stats("query", "acme").unwrap().count, 2);
+        let breakdown: Vec<_> = aspect
+            .labeled_stats("query")
+            .into_iter()
+            .map(|(value, stat)| (value, stat.count))
+            .collect();
+        assert_eq!(
+            breakdown,
+            vec![
+                ("acme".to_string(), 2),
+                ("globex".to_string(), 1),
+                ("globex-failed".to_string(), 1)
+            ]
+        );
+
+        let sink = Recorder::default();
+        aspect.export(&sink);
+        assert!(sink.0.lock().contains(&(
+            "calls".to_string(),
+            2.0,
+            "function=query,tenant=acme".to_string()
+        )));
+
+        aspect.clear();
+        assert!(aspect.labeled_stats("query").is_empty());
+    }
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(String, f64, String)>>);
+
+    impl MetricsSink for Recorder {
+        fn count(&self, _name: &str, _value: u64, _tags: &[(&str, &str)]) {}
+
+        fn timing(&self, _name: &str, _duration: Duration, _tags: &[(&str, &str)]) {}
+
+        fn gauge(&self, name: &str, value: f64, tags: &[(&str, &str)]) {
+            let tags: Vec<_> = tags.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            self.0
+                .lock()
+                .push((name.to_string(), value, tags.join(",")));
+        }
+    }
+
+    #[test]
+    fn test_export() {
+        let aspect = TimingAspect::new();
+        aspect.record_timing("load", Duration::from_millis(10));
+        aspect.record_timing("load", Duration::from_millis(30));
+
+        let sink = Recorder::default();
+        aspect.export(&sink);
+
+        let gauges = sink.0.lock();
+        assert_eq!(gauges.len(), 7);
+        assert_eq!(
+            gauges[0],
+            ("calls".to_string(), 2.0, "function=load".to_string())
+        );
+        assert_eq!(
+            gauges[1],
+            (
+                "duration_mean".to_string(),
+                0.02,
+                "function=load".to_string()
+            )
+        );
+        assert_eq!(
+            gauges[2],
+            (
+                "duration_quantile".to_string(),
+                0.01,
+                "function=load,quantile=0".to_string()
+            )
+        );
+        assert_eq!(
+            gauges[6],
+            (
+                "duration_quantile".to_string(),
+                0.03,
+                "function=load,quantile=1".to_string()
+            )
+        );
+    }
 }
@@ -0,0 +1,288 @@
+//! Aspects as tower middleware.
+//!
+//! [`AspectLayer`] applies an aspect to every request of a
+//! [`tower_service::Service`], such as a hyper or tonic service, through
+//! [`Aspect::around_async`]: the aspect sees each request as a call of a
+//! function named after the service, whose result is the response.
+//! Available with the `tower` feature.
+//!
+//! An aspect whose advice is in [`Aspect::around`] only applies here if it
+//! implements `around_async` as well: the default one runs `before` and
+//! `after` advice alone. The aspects of this crate implement it, except
+//! that those tracking calls per thread (`AllocTrackingAspect`,
+//! `ProfilingAspect` and `TransactionAspect`) fail every request with
+//! [`AspectError::WeavingError`] rather than let it through unadvised.
+
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, Location};
+use std::any::Any;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// The error of an [`AspectService`]: that of the inner service, or the
+/// [`AspectError`] of an aspect rejecting the request.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A [`Layer`] applying an aspect to the requests of the services it wraps.
+///
+/// Requests are join points of the function `name`, in the module `tower`,
+/// located where the layer was created. The aspect can keep a request from
+/// reaching the service by failing, as [`RateLimitAspect`] and
+/// [`CircuitBreakerAspect`] do, and is shared by all the services the layer
+/// wraps.
+///
+/// [`RateLimitAspect`]: crate::RateLimitAspect
+/// [`CircuitBreakerAspect`]: crate::CircuitBreakerAspect
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::tower::AspectLayer;
+/// use aspect_std::{RateLimitAspect, TimingAspect};
+/// use std::time::Duration;
+/// use tower::ServiceBuilder;
+///
+/// let service = ServiceBuilder::new()
+///     .layer(AspectLayer::new(TimingAspect::new(), "users"))
+///     .layer(AspectLayer::new(RateLimitAspect::new(100, Duration::from_secs(1)), "users"))
+///     .service(users_service);
+/// ```
+pub struct AspectLayer<A: ?Sized> {
+    pub(crate) aspect: Arc<A>,
+    pub(crate) name: &'static str,
+    pub(crate) location: Location,
+}
+
+impl<A: Aspect> AspectLayer<A> {
+    /// Apply `aspect` to requests, as calls of the function `name`.
+    #[track_caller]
+    pub fn new(aspect: A, name: &'static str) -> Self {
+        Self::shared(Arc::new(aspect), name)
+    }
+}
+
+impl<A: Aspect + ?Sized> AspectLayer<A> {
+    /// Apply an aspect shared with other code, e.g. an `Arc<dyn Aspect>`,
+    /// to requests, as calls of the function `name`.
+    #[track_caller]
+    pub fn shared(aspect: Arc<A>, name: &'static str) -> Self {
+        let caller = std::panic::Location::caller();
+        Self {
+            aspect,
+            name,
+            location: Location {
+                file: caller.file(),
+                line: caller.line(),
+            },
+        }
+    }
+}
+
+impl<A: ?Sized> Clone for AspectLayer<A> {
+    fn clone(&self) -> Self {
+        Self {
+            aspect: self.aspect.clone(),
+            name: self.name,
+            location: self.location,
+        }
+    }
+}
+
+impl<S, A: ?Sized> Layer<S> for AspectLayer<A> {
+    type Service = AspectService<S, A>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AspectService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// A service whose requests an aspect applies to, see [`AspectLayer`].
+pub struct AspectService<S, A: ?Sized> {
+    inner: S,
+    layer: AspectLayer<A>,
+}
+
+impl<S: Clone, A: ?Sized> Clone for AspectService<S, A> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            layer: self.layer.clone(),
+        }
+    }
+}
+
+impl<S, A, Request> Service<Request> for AspectService<S, A>
+where
+    S: Service<Request> + Clone + Send + 'static,
+    S::Response: 'static,
+    S::Error: Into<BoxError>,
+    S::Future: Send,
+    A: Aspect + ?Sized + 'static,
+    Request: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<S::Response, BoxError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        // The service made ready is the one to call, keep a clone instead
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let aspect = self.layer.aspect.clone();
+        let ctx = JoinPoint::new(self.layer.name, "tower", self.layer.location);
+
+        Box::pin(async move {
+            // Only called if the aspect proceeds
+            let proceed = Box::pin(async move {
+                match inner.call(request).await {
+                    Ok(response) => Ok(Box::new(response) as Box<dyn Any>),
+                    Err(error) => Err(AspectError::Custom(error.into())),
+                }
+            });
+            match aspect.around_async(&ctx, proceed).await {
+                Ok(response) => match response.downcast::<S::Response>() {
+                    Ok(response) => Ok(*response),
+                    Err(_) => Err(format!(
+                        "aspect replaced the response of {} with another type",
+                        ctx.function_name
+                    )
+                    .into()),
+                },
+                // The error of the service, as is
+                Err(AspectError::Custom(error)) => Err(error),
+                Err(error) => Err(error.into()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        CircuitBreakerAspect, ConcurrencyLimitAspect, FallbackAspect, MetricsAspect,
+        ProfilingAspect, RateLimitAspect, TimingAspect,
+    };
+    use std::future::{ready, Future, Ready};
+    use std::time::Duration;
+
+    /// Echoes requests, failing on odd ones.
+    #[derive(Clone)]
+    struct Echo;
+
+    impl Service<u32> for Echo {
+        type Response = u32;
+        type Error = BoxError;
+        type Future = Ready<Result<u32, BoxError>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: u32) -> Self::Future {
+            ready(match request % 2 {
+                0 => Ok(request),
+                _ => Err(format!("odd request {}", request).into()),
+            })
+        }
+    }
+
+    /// Sends `request` to `service`, whose futures are all ready.
+    fn send<S: Service<u32>>(service: &mut S, request: u32) -> Result<S::Response, S::Error> {
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+        let Poll::Ready(Ok(())) = service.poll_ready(&mut cx) else {
+            panic!("service not ready");
+        };
+        let mut future = std::pin::pin!(service.call(request));
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => panic!("response not ready"),
+        }
+    }
+
+    #[test]
+    fn test_timing_layer() {
+        let timing = Arc::new(TimingAspect::new());
+        let mut service = AspectLayer::shared(timing.clone(), "echo").layer(Echo);
+
+        assert_eq!(send(&mut service, 2).unwrap(), 2);
+        assert_eq!(
+            send(&mut service, 3).unwrap_err().to_string(),
+            "odd request 3"
+        );
+        assert_eq!(timing.get_stats("echo").unwrap().count, 2);
+    }
+
+    #[test]
+    fn test_rate_limit_layer() {
+        let layer = AspectLayer::new(RateLimitAspect::new(2, Duration::from_secs(60)), "echo");
+        let mut service = layer.layer(Echo);
+        let mut other = layer.layer(Echo);
+
+        assert!(send(&mut service, 2).is_ok());
+        assert!(send(&mut other, 4).is_ok());
+        let error = send(&mut service, 6).unwrap_err();
+        match error.downcast_ref::<AspectError>() {
+            Some(AspectError::RateLimited {
+                target,
+                retry_after,
+            }) => {
+                assert_eq!(target, "echo");
+                assert!(retry_after.is_some());
+            }
+            _ => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn test_circuit_breaker_layer() {
+        let breaker: Arc<dyn Aspect> =
+            Arc::new(CircuitBreakerAspect::new(2, Duration::from_secs(60)));
+        let mut service = AspectLayer::shared(breaker, "echo").layer(Echo);
+
+        assert!(send(&mut service, 1).is_err());
+        assert!(send(&mut service, 3).is_err());
+        // Open: even requests fail fast without reaching the service
+        let error = send(&mut service, 2).unwrap_err();
+        assert!(error.to_string().contains("OPEN"), "{}", error);
+    }
+
+    #[test]
+    fn test_around_aspect_layers() {
+        // Aspects advising in `around` apply to requests too
+        let mut service = AspectLayer::new(ConcurrencyLimitAspect::new(0), "echo").layer(Echo);
+        let error = send(&mut service, 2).unwrap_err();
+        assert!(
+            matches!(error.downcast_ref(), Some(AspectError::Overloaded { .. })),
+            "{}",
+            error
+        );
+
+        let metrics = Arc::new(MetricsAspect::new());
+        let mut service = AspectLayer::shared(metrics.clone(), "echo").layer(Echo);
+        assert!(send(&mut service, 2).is_ok());
+        assert!(send(&mut service, 3).is_err());
+        assert_eq!(metrics.get_count("echo"), 2);
+
+        let mut service = AspectLayer::new(FallbackAspect::value(0u32), "echo").layer(Echo);
+        assert_eq!(send(&mut service, 3).unwrap(), 0);
+
+        // Those that cannot advise requests reject them
+        let mut service = AspectLayer::new(ProfilingAspect::new(), "echo").layer(Echo);
+        let error = send(&mut service, 2).unwrap_err();
+        assert!(
+            matches!(error.downcast_ref(), Some(AspectError::WeavingError { .. })),
+            "{}",
+            error
+        );
+    }
+}
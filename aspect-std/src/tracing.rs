@@ -0,0 +1,350 @@
+//! Join point metadata on `tracing` spans.
+//!
+//! [`record_join_point`] writes what an aspect knows about a call onto a
+//! span, so that records of aspects correlate with the spans the
+//! application already has. [`TracingAspect`] enters a span per call with
+//! these fields, and [`LoggingAspect::with_span_fields`] records them on
+//! the span the call runs in. Available with the `tracing` feature.
+//!
+//! [`LoggingAspect::with_span_fields`]: crate::LoggingAspect::with_span_fields
+
+use crate::redact::Redactor;
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, Precedence, ProceedingJoinPoint};
+use std::any::Any;
+use tracing::field::{display, Empty};
+use tracing::{Instrument, Level, Span};
+
+/// Records the metadata of `ctx` on `span`.
+///
+/// The fields follow the OpenTelemetry conventions, like those of
+/// [`OtelAspect`](crate::otel):
+///
+/// - `code.function`: the name of the function
+/// - `code.namespace`: its module path
+/// - `code.filepath` and `code.lineno`: where it is defined
+/// - `code.args`: its arguments, masked by `redactor`
+///
+/// As always with `tracing`, only the fields the span declared are
+/// recorded: declare those wanted as [`Empty`] where the span is created.
+/// Arguments are only formatted when the span declares `code.args`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::redact::Redactor;
+/// use tracing::field::Empty;
+///
+/// let span = tracing::info_span!("request", code.function = Empty, code.args = Empty);
+/// let _guard = span.enter();
+/// aspect_std::tracing::record_join_point(&Span::current(), &ctx, &Redactor::default());
+/// ```
+pub fn record_join_point(span: &Span, ctx: &JoinPoint, redactor: &Redactor) {
+    if span.is_disabled() {
+        return;
+    }
+    span.record("code.function", ctx.function_name);
+    span.record("code.namespace", ctx.module_path);
+    span.record("code.filepath", ctx.location.file);
+    span.record("code.lineno", ctx.location.line);
+    if span.has_field("code.args") {
+        span.record("code.args", redactor.format_args(&ctx.args));
+    }
+}
+
+/// A span named `call` declaring the fields of [`record_join_point`] and
+/// `error`, at a level that must be constant for `tracing`.
+macro_rules! call_span {
+    ($level:expr) => {
+        ::tracing::span!(
+            $level,
+            "call",
+            code.function = Empty,
+            code.namespace = Empty,
+            code.filepath = Empty,
+            code.lineno = Empty,
+            code.args = Empty,
+            error = Empty,
+        )
+    };
+}
+
+/// Aspect that runs each call in a `tracing` span.
+///
+/// Spans are named `call` and carry the metadata of the join point as
+/// fields, see [`record_join_point`]; errors of the call are recorded in
+/// their `error` field. Being entered while the function runs, they are the
+/// parents of the spans and events of the function, and children of the
+/// span it was called in.
+///
+/// Async calls, through [`Aspect::around_async`], are instrumented with
+/// the span, so that it is entered whenever their future is polled.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::TracingAspect;
+/// use aspect_macros::aspect;
+///
+/// #[aspect(TracingAspect::new().with_level(tracing::Level::DEBUG))]
+/// fn fetch_order(id: u64) -> Result<Order, String> {
+///     db::load_order(id)
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TracingAspect {
+    level: Level,
+    redactor: Redactor,
+}
+
+impl TracingAspect {
+    /// Create an aspect with spans at the `INFO` level.
+    pub fn new() -> Self {
+        Self {
+            level: Level::INFO,
+            redactor: Redactor::default(),
+        }
+    }
+
+    /// Set the level of the spans.
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Set how sensitive arguments are masked ([`Redactor::default`] by
+    /// default).
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// The span of a call of `ctx`, with its metadata recorded.
+    pub fn span(&self, ctx: &JoinPoint) -> Span {
+        let span = match self.level {
+            Level::TRACE => call_span!(Level::TRACE),
+            Level::DEBUG => call_span!(Level::DEBUG),
+            Level::INFO => call_span!(Level::INFO),
+            Level::WARN => call_span!(Level::WARN),
+            _ => call_span!(Level::ERROR),
+        };
+        record_join_point(&span, ctx, &self.redactor);
+        span
+    }
+}
+
+impl Default for TracingAspect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn record_error(span: &Span, result: &Result<Box<dyn Any>, AspectError>) {
+    if let Err(error) = result {
+        span.record("error", display(error));
+    }
+}
+
+impl Aspect for TracingAspect {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let span = self.span(pjp.context());
+        let result = span.in_scope(|| pjp.proceed());
+        record_error(&span, &result);
+        result
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        let span = self.span(ctx);
+        Box::pin(async move {
+            let result = proceed.instrument(span.clone()).await;
+            record_error(&span, &result);
+            result
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::OBSERVABILITY
+    }
+
+    fn reads_args(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LoggingAspect;
+    use aspect_core::{Arg, Location};
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::{self, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+
+    /// Fields recorded on spans, as `(span, field, value)`.
+    type Fields = Arc<Mutex<Vec<(String, String, String)>>>;
+
+    struct Capture(Fields);
+
+    struct Visitor<'a>(&'a Fields, &'a str);
+
+    impl Visit for Visitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            let entry = (
+                self.1.to_string(),
+                field.name().to_string(),
+                value.to_string(),
+            );
+            self.0.lock().unwrap().push(entry);
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.record_str(field, &format!("{:?}", value));
+        }
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> tracing_subscriber::Layer<S> for Capture {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: layer::Context<'_, S>) {
+            attrs.record(&mut Visitor(&self.0, attrs.metadata().name()));
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: layer::Context<'_, S>) {
+            let name = ctx.span(id).unwrap().name();
+            values.record(&mut Visitor(&self.0, name));
+        }
+
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: layer::Context<'_, S>) {
+            event.record(&mut Visitor(&self.0, "event"));
+        }
+    }
+
+    /// Runs `f` with a subscriber capturing fields, and returns them.
+    fn capture(f: impl FnOnce()) -> Vec<(String, String, String)> {
+        let fields = Fields::default();
+        let subscriber = tracing_subscriber::registry().with(Capture(fields.clone()));
+        tracing::subscriber::with_default(subscriber, f);
+        let fields = fields.lock().unwrap();
+        fields.clone()
+    }
+
+    fn field<'a>(
+        fields: &'a [(String, String, String)],
+        span: &str,
+        name: &str,
+    ) -> Option<&'a str> {
+        fields
+            .iter()
+            .find(|(s, n, _)| s == span && n == name)
+            .map(|(_, _, value)| value.as_str())
+    }
+
+    fn join_point() -> JoinPoint {
+        JoinPoint::new(
+            "login",
+            "app::auth",
+            Location {
+                file: "src/auth.rs",
+                line: 12,
+            },
+        )
+        .with_args(vec![
+            Arg::new("user", &"alice".to_string()),
+            Arg::new("password", &"hunter2".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_record_join_point() {
+        let ctx = join_point();
+        let fields = capture(|| {
+            let span = tracing::info_span!("request", code.function = Empty, code.args = Empty);
+            record_join_point(&span, &ctx, &Redactor::default());
+        });
+        assert_eq!(field(&fields, "request", "code.function"), Some("login"));
+        let args = field(&fields, "request", "code.args").unwrap();
+        assert!(args.contains("alice"));
+        assert!(!args.contains("hunter2"));
+        // Not declared by the span
+        assert_eq!(field(&fields, "request", "code.filepath"), None);
+    }
+
+    #[test]
+    fn test_tracing_aspect() {
+        let aspect = TracingAspect::new();
+        let fields = capture(|| {
+            let pjp = ProceedingJoinPoint::new(
+                || {
+                    assert_eq!(Span::current().metadata().unwrap().name(), "call");
+                    Err(AspectError::execution("locked out"))
+                },
+                join_point(),
+            );
+            assert!(aspect.around(pjp).is_err());
+        });
+        assert_eq!(field(&fields, "call", "code.function"), Some("login"));
+        assert_eq!(field(&fields, "call", "code.namespace"), Some("app::auth"));
+        assert_eq!(field(&fields, "call", "code.filepath"), Some("src/auth.rs"));
+        assert_eq!(field(&fields, "call", "code.lineno"), Some("12"));
+        assert!(field(&fields, "call", "error")
+            .unwrap()
+            .contains("locked out"));
+    }
+
+    #[test]
+    fn test_tracing_aspect_async() {
+        let aspect = TracingAspect::new();
+        let ctx = join_point();
+        let fields = capture(|| {
+            let proceed = Box::pin(async {
+                assert_eq!(Span::current().metadata().unwrap().name(), "call");
+                Ok(Box::new(()) as Box<dyn Any>)
+            });
+            let mut future = aspect.around_async(&ctx, proceed);
+            let mut cx = Context::from_waker(std::task::Waker::noop());
+            assert!(matches!(future.as_mut().poll(&mut cx), Poll::Ready(Ok(_))));
+        });
+        assert_eq!(field(&fields, "call", "code.function"), Some("login"));
+        assert_eq!(field(&fields, "call", "error"), None);
+    }
+
+    #[test]
+    fn test_logging_span_fields() {
+        let ctx = join_point();
+        let fields = capture(|| {
+            let span = tracing::info_span!("request", code.function = Empty, code.lineno = Empty);
+            let _guard = span.enter();
+            LoggingAspect::new().with_span_fields().before(&ctx);
+        });
+        assert_eq!(field(&fields, "request", "code.function"), Some("login"));
+        assert_eq!(field(&fields, "request", "code.lineno"), Some("12"));
+
+        // Not without asking
+        let fields = capture(|| {
+            let span = tracing::info_span!("request", code.function = Empty);
+            let _guard = span.enter();
+            LoggingAspect::new().before(&ctx);
+        });
+        assert_eq!(field(&fields, "request", "code.function"), None);
+    }
+
+    #[test]
+    fn test_tracing_sink() {
+        use crate::logging::TracingSink;
+
+        let ctx = join_point();
+        let fields = capture(|| {
+            LoggingAspect::new().with_sink(TracingSink).before(&ctx);
+        });
+        assert_eq!(field(&fields, "event", "log.target"), Some("app::auth"));
+        let message = field(&fields, "event", "message").unwrap();
+        assert!(message.starts_with("[ENTRY] login"), "{}", message);
+    }
+}
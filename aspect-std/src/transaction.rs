@@ -0,0 +1,447 @@
+//! Transaction management aspect with pluggable transaction managers.
+
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, Precedence, ProceedingJoinPoint};
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Begins, commits and rolls back transactions for a [`TransactionAspect`].
+///
+/// Implement this for a database connection pool or any other resource with
+/// transactional semantics. Savepoints are only needed for
+/// [`Propagation::Savepoint`]; the default implementations report them as
+/// unsupported.
+pub trait TransactionManager: Send + Sync + 'static {
+    /// Handle of an open transaction.
+    type Transaction: 'static;
+
+    /// Start a new transaction.
+    fn begin(&self) -> Result<Self::Transaction, AspectError>;
+
+    /// Make the changes of `tx` permanent.
+    fn commit(&self, tx: Self::Transaction) -> Result<(), AspectError>;
+
+    /// Discard the changes of `tx`.
+    fn rollback(&self, tx: Self::Transaction) -> Result<(), AspectError>;
+
+    /// Create a savepoint named `name` within `tx`.
+    fn savepoint(&self, _tx: &mut Self::Transaction, _name: &str) -> Result<(), AspectError> {
+        Err(AspectError::execution("savepoints are not supported"))
+    }
+
+    /// Forget the savepoint `name`, keeping the changes made since.
+    fn release_savepoint(
+        &self,
+        _tx: &mut Self::Transaction,
+        _name: &str,
+    ) -> Result<(), AspectError> {
+        Ok(())
+    }
+
+    /// Discard the changes made since the savepoint `name`.
+    fn rollback_to_savepoint(
+        &self,
+        _tx: &mut Self::Transaction,
+        _name: &str,
+    ) -> Result<(), AspectError> {
+        Err(AspectError::execution("savepoints are not supported"))
+    }
+}
+
+/// How a transactional call relates to a transaction already in progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Propagation {
+    /// Join the current transaction, or start one if there is none.
+    #[default]
+    Required,
+    /// Always start a new transaction, suspending the current one until the
+    /// call completes.
+    RequiresNew,
+    /// Run within a savepoint of the current transaction, so a failed call
+    /// only undoes its own changes; start a transaction if there is none.
+    Savepoint,
+}
+
+thread_local! {
+    /// Open transactions on this thread, innermost last, per manager type.
+    static TRANSACTIONS: RefCell<HashMap<TypeId, Vec<Box<dyn Any>>>> =
+        RefCell::new(HashMap::new());
+}
+
+fn push<M: TransactionManager>(tx: M::Transaction) {
+    TRANSACTIONS.with(|transactions| {
+        transactions
+            .borrow_mut()
+            .entry(TypeId::of::<M>())
+            .or_default()
+            .push(Box::new(tx));
+    });
+}
+
+fn pop<M: TransactionManager>() -> Option<M::Transaction> {
+    TRANSACTIONS.with(|transactions| {
+        let tx = transactions
+            .borrow_mut()
+            .get_mut(&TypeId::of::<M>())?
+            .pop()?;
+        tx.downcast().ok().map(|tx| *tx)
+    })
+}
+
+fn depth<M: TransactionManager>() -> usize {
+    TRANSACTIONS.with(|transactions| {
+        transactions
+            .borrow()
+            .get(&TypeId::of::<M>())
+            .map_or(0, Vec::len)
+    })
+}
+
+/// Puts a transaction taken off the stack back, including when unwinding.
+struct Restore<M: TransactionManager>(Option<M::Transaction>);
+
+impl<M: TransactionManager> Drop for Restore<M> {
+    fn drop(&mut self) {
+        if let Some(tx) = self.0.take() {
+            push::<M>(tx);
+        }
+    }
+}
+
+/// Ends the transaction a call started if the call panics.
+struct Frame<'a, M: TransactionManager> {
+    manager: &'a M,
+    finished: bool,
+}
+
+impl<M: TransactionManager> Drop for Frame<'_, M> {
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Some(tx) = pop::<M>() {
+                let _ = self.manager.rollback(tx);
+            }
+        }
+    }
+}
+
+/// Aspect running the advised function in a transaction.
+///
+/// The transaction is committed when the call succeeds and rolled back when
+/// it fails or panics. How nested transactional calls behave is set by the
+/// [`Propagation`] policy. The advised function and everything it calls can
+/// use the innermost transaction through
+/// [`with_current`](Self::with_current).
+///
+/// Open transactions are tracked per thread and per manager type, so the
+/// aspect suits synchronous functions; async functions only get `before`
+/// and `after` advice and are not wrapped, and executions woven through
+/// [`around_async`](Aspect::around_async), such as those of the tower
+/// adapter, fail with [`AspectError::WeavingError`].
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::transaction::{Propagation, TransactionAspect, TransactionManager};
+/// use aspect_macros::aspect;
+/// use std::sync::LazyLock;
+///
+/// static TX: LazyLock<TransactionAspect<Database>> =
+///     LazyLock::new(|| TransactionAspect::new(Database::connect()));
+///
+/// #[aspect(TX.clone())]
+/// fn transfer(from: u64, to: u64, amount: u64) -> Result<(), String> {
+///     TransactionAspect::<Database>::with_current(|tx| tx.execute("UPDATE accounts ..."))
+///         .expect("called within a transaction")?;
+///     audit_transfer(from, to, amount)
+/// }
+///
+/// // Keeps its audit record even if the transfer is rolled back
+/// #[aspect(TX.clone().with_propagation(Propagation::RequiresNew))]
+/// fn audit_transfer(from: u64, to: u64, amount: u64) -> Result<(), String> {
+///     Ok(())
+/// }
+/// ```
+pub struct TransactionAspect<M: TransactionManager> {
+    manager: Arc<M>,
+    propagation: Propagation,
+}
+
+impl<M: TransactionManager> TransactionAspect<M> {
+    /// Create a transaction aspect using `manager`, with
+    /// [`Propagation::Required`].
+    pub fn new(manager: M) -> Self {
+        Self {
+            manager: Arc::new(manager),
+            propagation: Propagation::default(),
+        }
+    }
+
+    /// Set how calls join transactions already in progress.
+    pub fn with_propagation(mut self, propagation: Propagation) -> Self {
+        self.propagation = propagation;
+        self
+    }
+
+    /// The transaction manager.
+    pub fn manager(&self) -> &M {
+        &self.manager
+    }
+
+    /// Run `f` with the innermost transaction of manager type `M` on this
+    /// thread, or return `None` if there is none.
+    ///
+    /// While `f` runs the transaction is not visible to nested calls of
+    /// `with_current`, which then see the next outer transaction, if any.
+    pub fn with_current<R>(f: impl FnOnce(&mut M::Transaction) -> R) -> Option<R> {
+        let mut restore = Restore::<M>(Some(pop::<M>()?));
+        restore.0.as_mut().map(f)
+    }
+
+    /// Returns `true` if a transaction of manager type `M` is open on this
+    /// thread.
+    pub fn in_transaction() -> bool {
+        depth::<M>() > 0
+    }
+
+    /// Runs the call in a new transaction.
+    fn in_new(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        push::<M>(self.manager.begin()?);
+        let mut frame = Frame {
+            manager: &*self.manager,
+            finished: false,
+        };
+        let result = pjp.proceed();
+        frame.finished = true;
+
+        let tx = pop::<M>().expect("transaction stack corrupted");
+        match result {
+            Ok(value) => self.manager.commit(tx).map(|()| value),
+            Err(err) => {
+                if let Err(rollback_err) = self.manager.rollback(tx) {
+                    log::error!("transaction rollback failed: {}", rollback_err);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Runs the call within a savepoint of the current transaction.
+    fn in_savepoint(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let name = format!("aspect_sp_{}", depth::<M>());
+        let manager = &*self.manager;
+        Self::with_current(|tx| manager.savepoint(tx, &name)).unwrap_or(Ok(()))?;
+
+        let result = pjp.proceed();
+
+        Self::with_current(|tx| match &result {
+            Ok(_) => manager.release_savepoint(tx, &name),
+            Err(_) => manager.rollback_to_savepoint(tx, &name),
+        })
+        .unwrap_or(Ok(()))?;
+        result
+    }
+}
+
+impl<M: TransactionManager> Clone for TransactionAspect<M> {
+    fn clone(&self) -> Self {
+        Self {
+            manager: self.manager.clone(),
+            propagation: self.propagation,
+        }
+    }
+}
+
+impl<M: TransactionManager> Aspect for TransactionAspect<M> {
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        let joined = Self::in_transaction();
+        match self.propagation {
+            Propagation::Required if joined => pjp.proceed(),
+            Propagation::Savepoint if joined => self.in_savepoint(pjp),
+            _ => self.in_new(pjp),
+        }
+    }
+
+    /// Fails, as open transactions are tracked per thread.
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        _proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            Err(AspectError::weaving(format!(
+                "TransactionAspect cannot advise the asynchronous execution of {}",
+                ctx.function_name
+            )))
+        })
+    }
+
+    fn precedence(&self) -> Precedence {
+        Precedence::TRANSACTION
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_macros::aspect;
+    use parking_lot::Mutex;
+    use std::sync::LazyLock;
+
+    /// Records every operation and the rows written by committed
+    /// transactions.
+    #[derive(Default)]
+    struct Journal {
+        log: Mutex<Vec<String>>,
+        committed: Mutex<Vec<String>>,
+        next_id: Mutex<u32>,
+    }
+
+    struct Tx {
+        id: u32,
+        rows: Vec<String>,
+        savepoints: Vec<(String, usize)>,
+    }
+
+    impl Journal {
+        fn record(&self, entry: String) {
+            self.log.lock().push(entry);
+        }
+    }
+
+    impl TransactionManager for Journal {
+        type Transaction = Tx;
+
+        fn begin(&self) -> Result<Tx, AspectError> {
+            let mut next_id = self.next_id.lock();
+            *next_id += 1;
+            self.record(format!("begin {}", *next_id));
+            Ok(Tx {
+                id: *next_id,
+                rows: Vec::new(),
+                savepoints: Vec::new(),
+            })
+        }
+
+        fn commit(&self, tx: Tx) -> Result<(), AspectError> {
+            self.record(format!("commit {}", tx.id));
+            self.committed.lock().extend(tx.rows);
+            Ok(())
+        }
+
+        fn rollback(&self, tx: Tx) -> Result<(), AspectError> {
+            self.record(format!("rollback {}", tx.id));
+            Ok(())
+        }
+
+        fn savepoint(&self, tx: &mut Tx, name: &str) -> Result<(), AspectError> {
+            self.record(format!("savepoint {}", name));
+            tx.savepoints.push((name.to_string(), tx.rows.len()));
+            Ok(())
+        }
+
+        fn release_savepoint(&self, tx: &mut Tx, name: &str) -> Result<(), AspectError> {
+            self.record(format!("release {}", name));
+            tx.savepoints.pop();
+            Ok(())
+        }
+
+        fn rollback_to_savepoint(&self, tx: &mut Tx, name: &str) -> Result<(), AspectError> {
+            self.record(format!("rollback to {}", name));
+            let (_, len) = tx.savepoints.pop().unwrap();
+            tx.rows.truncate(len);
+            Ok(())
+        }
+    }
+
+    fn insert(row: &str) {
+        TransactionAspect::<Journal>::with_current(|tx| tx.rows.push(row.to_string()))
+            .expect("not in a transaction");
+    }
+
+    static TX: LazyLock<TransactionAspect<Journal>> =
+        LazyLock::new(|| TransactionAspect::new(Journal::default()));
+
+    fn take_log() -> (Vec<String>, Vec<String>) {
+        let journal = TX.manager();
+        (
+            std::mem::take(&mut *journal.log.lock()),
+            std::mem::take(&mut *journal.committed.lock()),
+        )
+    }
+
+    #[aspect(TX.clone())]
+    fn outer(fail: bool, nested: Propagation) -> Result<(), String> {
+        insert("outer");
+        let _ = match nested {
+            Propagation::Required => inner_required(),
+            Propagation::RequiresNew => inner_new(),
+            Propagation::Savepoint => inner_savepoint(),
+        };
+        if fail {
+            Err("outer failed".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    #[aspect(TX.clone())]
+    fn inner_required() -> Result<(), String> {
+        insert("inner");
+        Ok(())
+    }
+
+    #[aspect(TX.clone().with_propagation(Propagation::RequiresNew))]
+    fn inner_new() -> Result<(), String> {
+        insert("audit");
+        Ok(())
+    }
+
+    #[aspect(TX.clone().with_propagation(Propagation::Savepoint))]
+    fn inner_savepoint() -> Result<(), String> {
+        insert("partial");
+        Err("inner failed".to_string())
+    }
+
+    #[aspect(TX.clone())]
+    fn panics() -> u32 {
+        insert("lost");
+        panic!("boom")
+    }
+
+    // All cases share the TX journal, so they run in one test.
+    #[test]
+    fn test_propagation() {
+        outer(false, Propagation::Required).unwrap();
+        let (log, committed) = take_log();
+        assert_eq!(log, ["begin 1", "commit 1"]);
+        assert_eq!(committed, ["outer", "inner"]);
+
+        outer(true, Propagation::RequiresNew).unwrap_err();
+        let (log, committed) = take_log();
+        assert_eq!(log, ["begin 2", "begin 3", "commit 3", "rollback 2"]);
+        assert_eq!(committed, ["audit"]);
+
+        outer(false, Propagation::Savepoint).unwrap();
+        let (log, committed) = take_log();
+        assert_eq!(
+            log,
+            [
+                "begin 4",
+                "savepoint aspect_sp_1",
+                "rollback to aspect_sp_1",
+                "commit 4"
+            ]
+        );
+        assert_eq!(committed, ["outer"]);
+
+        assert!(std::panic::catch_unwind(panics).is_err());
+        let (log, committed) = take_log();
+        assert_eq!(log, ["begin 5", "rollback 5"]);
+        assert!(committed.is_empty());
+
+        assert!(!TransactionAspect::<Journal>::in_transaction());
+        assert!(TransactionAspect::<Journal>::with_current(|_| ()).is_none());
+    }
+}
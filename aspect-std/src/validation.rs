@@ -1,8 +1,20 @@
 //! Validation aspect for pre/post condition checking.
 
-use aspect_core::{Aspect, AspectError, JoinPoint, ProceedingJoinPoint};
-use std::any::Any;
-use std::sync::Arc;
+use alloc_crate::boxed::Box;
+use alloc_crate::format;
+use alloc_crate::string::{String, ToString};
+use alloc_crate::sync::Arc;
+use alloc_crate::vec::Vec;
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Arg, Aspect, AspectError, JoinPoint, ProceedingJoinPoint};
+use core::any::{type_name, Any};
+use core::fmt;
+
+#[cfg(feature = "validator")]
+mod validate;
+
+#[cfg(feature = "validator")]
+pub use validate::InvalidArguments;
 
 /// Validation rule trait.
 ///
@@ -20,14 +32,73 @@ pub trait ValidationRule: Send + Sync {
     }
 }
 
+/// Post-condition rule checked against the value a function returned.
+///
+/// The value is the function's return value, or the `Ok` value for functions
+/// returning `Result`; calls that fail are not checked. Most rules are
+/// created with [`rule_for_result`], which downcasts the value for you.
+pub trait PostConditionRule: Send + Sync {
+    /// Validate the returned value.
+    ///
+    /// Returns `Ok(())` if validation passes, or `Err(message)` if it fails.
+    fn validate(&self, ctx: &JoinPoint, result: &dyn Any) -> Result<(), String>;
+
+    /// Get a description of this validation rule.
+    fn description(&self) -> &str {
+        "post-condition"
+    }
+}
+
 /// Validation aspect for enforcing constraints.
 ///
 /// Allows composing multiple validation rules that are checked before
-/// function execution.
+/// function execution, and post-condition rules that are checked against
+/// the returned value.
+///
+/// Rules can inspect the captured arguments of the call: see
+/// [`rule_for_arg`], [`RangeValidator::for_arg`] and
+/// [`NotEmptyValidator::for_arg`]. Post-condition rules are added with
+/// [`add_post_rule`](Self::add_post_rule).
 ///
 /// # Example
 ///
 /// ```rust,ignore
+/// use aspect_std::validation::{rule_for_arg, RangeValidator};
+/// use aspect_std::ValidationAspect;
+/// use aspect_macros::aspect;
+///
+/// #[aspect(ValidationAspect::new()
+///     .add_rule(Box::new(RangeValidator::for_arg("age", 0, 150)))
+///     .add_rule(Box::new(rule_for_arg::<String>("name", |name| {
+///         if name.chars().all(char::is_alphabetic) {
+///             Ok(())
+///         } else {
+///             Err("name must be alphabetic".to_string())
+///         }
+///     }))))]
+/// fn register(name: String, age: i32) -> Result<(), String> {
+///     Ok(())
+/// }
+/// ```
+///
+/// Output contracts are checked after the call and turn a violation into an
+/// error, even though the function itself succeeded:
+///
+/// ```rust,ignore
+/// use aspect_std::validation::rule_for_result;
+///
+/// #[aspect(ValidationAspect::new()
+///     .add_post_rule(Box::new(rule_for_result::<Vec<u32>>(|ids| {
+///         if ids.is_sorted() { Ok(()) } else { Err("ids must be sorted".into()) }
+///     }))))]
+/// fn active_ids() -> Result<Vec<u32>, String> {
+///     Ok(vec![1, 4, 9])
+/// }
+/// ```
+///
+/// Custom rules implement [`ValidationRule`]:
+///
+/// ```rust,ignore
 /// use aspect_std::{ValidationAspect, ValidationRule};
 /// use aspect_macros::aspect;
 ///
@@ -49,12 +120,52 @@ pub trait ValidationRule: Send + Sync {
 /// ```
 pub struct ValidationAspect {
     rules: Vec<Box<dyn ValidationRule>>,
+    post_rules: Vec<Box<dyn PostConditionRule>>,
+    #[cfg(feature = "validator")]
+    models: validate::Models,
 }
 
 impl ValidationAspect {
     /// Create a new validation aspect.
     pub fn new() -> Self {
-        Self { rules: Vec::new() }
+        Self {
+            rules: Vec::new(),
+            post_rules: Vec::new(),
+            #[cfg(feature = "validator")]
+            models: validate::Models::default(),
+        }
+    }
+
+    /// Validate the arguments of type `T` with its `validator::Validate`
+    /// impl, such as one derived with `#[derive(Validate)]`, before the
+    /// rules run. Available with the `validator` feature.
+    ///
+    /// Arguments are recognized by their captured value, so `T` has to be
+    /// `Clone`; arguments of type `&T` are validated too. A call with
+    /// invalid arguments fails with the errors of all of them, as
+    /// [`InvalidArguments`] in an [`AspectError::Custom`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// use aspect_std::ValidationAspect;
+    /// use validator::Validate;
+    ///
+    /// #[derive(Clone, Debug, Validate)]
+    /// struct SignUp {
+    ///     #[validate(email)]
+    ///     email: String,
+    /// }
+    ///
+    /// #[aspect(ValidationAspect::new().validated::<SignUp>())]
+    /// fn sign_up(form: &SignUp) -> Result<(), String> {
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "validator")]
+    pub fn validated<T: validator::Validate + 'static>(mut self) -> Self {
+        self.models.register::<T>();
+        self
     }
 
     /// Add a validation rule.
@@ -63,8 +174,16 @@ impl ValidationAspect {
         self
     }
 
+    /// Add a post-condition rule, checked after each successful call.
+    pub fn add_post_rule(mut self, rule: Box<dyn PostConditionRule>) -> Self {
+        self.post_rules.push(rule);
+        self
+    }
+
     /// Run all validation rules.
     fn validate(&self, ctx: &JoinPoint) -> Result<(), AspectError> {
+        #[cfg(feature = "validator")]
+        self.models.validate(ctx)?;
         for rule in self.rules.iter() {
             if let Err(msg) = rule.validate(ctx) {
                 return Err(AspectError::execution(format!(
@@ -75,6 +194,19 @@ impl ValidationAspect {
         }
         Ok(())
     }
+
+    /// Run all post-condition rules against a returned value.
+    fn validate_result(&self, ctx: &JoinPoint, result: &dyn Any) -> Result<(), AspectError> {
+        for rule in self.post_rules.iter() {
+            if let Err(msg) = rule.validate(ctx, result) {
+                return Err(AspectError::execution(format!(
+                    "Postcondition failed for {}: {}",
+                    ctx.function_name, msg
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Default for ValidationAspect {
@@ -88,21 +220,220 @@ impl Aspect for ValidationAspect {
         // Validate before execution
         self.validate(pjp.context())?;
 
-        // Execute the function
-        let result = pjp.proceed();
+        if self.post_rules.is_empty() {
+            return pjp.proceed();
+        }
+
+        // Execute the function, then check what it returned
+        let ctx = pjp.context().clone();
+        let result = pjp.proceed()?;
+        self.validate_result(&ctx, &*result)?;
+        Ok(result)
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            self.validate(ctx)?;
+            let result = proceed.await?;
+            self.validate_result(ctx, &*result)?;
+            Ok(result)
+        })
+    }
+
+    fn reads_args(&self) -> bool {
+        true
+    }
+}
+
+/// Identifies a captured argument by position or by parameter name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgSelector {
+    /// The argument at this position, counting from 0
+    Index(usize),
+    /// The argument with this parameter name
+    Name(&'static str),
+}
+
+impl ArgSelector {
+    /// Finds the selected argument of a call.
+    pub fn find<'a>(&self, ctx: &'a JoinPoint) -> Option<&'a Arg> {
+        match *self {
+            ArgSelector::Index(index) => ctx.args.get(index),
+            ArgSelector::Name(name) => ctx.arg(name),
+        }
+    }
+}
+
+impl From<usize> for ArgSelector {
+    fn from(index: usize) -> Self {
+        ArgSelector::Index(index)
+    }
+}
 
-        // Note: After-validation is not supported in this simplified version
-        // as it would require cloning the context
-        result
+impl From<&'static str> for ArgSelector {
+    fn from(name: &'static str) -> Self {
+        ArgSelector::Name(name)
     }
 }
 
+impl fmt::Display for ArgSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgSelector::Index(index) => write!(f, "argument {}", index),
+            ArgSelector::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+/// Returns the selected argument's value as a `T`, or an error saying why
+/// it is not available.
+fn typed_arg<T: Any>(selector: ArgSelector, ctx: &JoinPoint) -> Result<&T, String> {
+    let arg = selector
+        .find(ctx)
+        .ok_or_else(|| format!("{} not found", selector))?;
+    arg.value::<T>().ok_or_else(|| {
+        format!(
+            "{} is a {}, not a {}",
+            selector,
+            arg.type_name,
+            type_name::<T>()
+        )
+    })
+}
+
+/// Typed check of a single argument.
+type ArgCheck<T> = Arc<dyn Fn(&T) -> Result<(), String> + Send + Sync>;
+
+/// Validation rule checking one argument with a typed closure; see
+/// [`rule_for_arg`].
+pub struct ArgRule<T> {
+    selector: ArgSelector,
+    check: ArgCheck<T>,
+}
+
+impl<T: Any> ValidationRule for ArgRule<T> {
+    fn validate(&self, ctx: &JoinPoint) -> Result<(), String> {
+        (self.check)(typed_arg::<T>(self.selector, ctx)?)
+    }
+
+    fn description(&self) -> &str {
+        "argument check"
+    }
+}
+
+/// Creates a rule validating the argument selected by `arg` (a position or a
+/// parameter name) as a `T`.
+///
+/// The argument must be captured by value, which requires its type to be
+/// `Clone`; borrowed parameters are captured as their owned type, so a
+/// `&str` parameter is checked as a `String`. The rule fails if the
+/// argument is missing or has another type.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_std::validation::{rule_for_arg, ValidationRule};
+/// use aspect_core::{Arg, JoinPoint, Location};
+///
+/// let adult = rule_for_arg::<i32>(0, |age| {
+///     if *age >= 18 { Ok(()) } else { Err(format!("age {} is under 18", age)) }
+/// });
+///
+/// let ctx = JoinPoint::new("sign_up", "app", Location { file: "app.rs", line: 1 })
+///     .with_args(vec![Arg::new("age", &16i32)]);
+/// assert_eq!(adult.validate(&ctx), Err("age 16 is under 18".to_string()));
+/// ```
+pub fn rule_for_arg<T: Any>(
+    arg: impl Into<ArgSelector>,
+    check: impl Fn(&T) -> Result<(), String> + Send + Sync + 'static,
+) -> ArgRule<T> {
+    ArgRule {
+        selector: arg.into(),
+        check: Arc::new(check),
+    }
+}
+
+/// Typed check of a returned value.
+type ResultCheck<T> = Arc<dyn Fn(&T) -> Result<(), String> + Send + Sync>;
+
+/// Post-condition rule checking the returned value with a typed closure; see
+/// [`rule_for_result`].
+pub struct ResultRule<T> {
+    check: ResultCheck<T>,
+}
+
+impl<T: Any> PostConditionRule for ResultRule<T> {
+    fn validate(&self, _ctx: &JoinPoint, result: &dyn Any) -> Result<(), String> {
+        let value = result
+            .downcast_ref::<T>()
+            .ok_or_else(|| format!("result is not a {}", type_name::<T>()))?;
+        (self.check)(value)
+    }
+
+    fn description(&self) -> &str {
+        "result check"
+    }
+}
+
+/// Creates a post-condition rule validating the returned value as a `T`.
+///
+/// For functions returning `Result<T, E>`, `T` is the `Ok` type. The rule
+/// fails if the value has another type.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_std::validation::{rule_for_result, PostConditionRule};
+/// use aspect_core::{JoinPoint, Location};
+///
+/// let non_empty = rule_for_result::<Vec<String>>(|names| {
+///     if names.is_empty() { Err("no names returned".to_string()) } else { Ok(()) }
+/// });
+///
+/// let ctx = JoinPoint::new("list_names", "app", Location { file: "app.rs", line: 1 });
+/// let names: Vec<String> = Vec::new();
+/// assert_eq!(non_empty.validate(&ctx, &names), Err("no names returned".to_string()));
+/// ```
+pub fn rule_for_result<T: Any>(
+    check: impl Fn(&T) -> Result<(), String> + Send + Sync + 'static,
+) -> ResultRule<T> {
+    ResultRule {
+        check: Arc::new(check),
+    }
+}
+
+/// Reads an argument of any primitive integer type as an `i64`.
+fn integer_value(arg: &Arg) -> Option<i64> {
+    macro_rules! try_types {
+        ($($ty:ty),*) => {
+            $(if let Some(value) = arg.value::<$ty>() {
+                return i64::try_from(*value).ok();
+            })*
+        };
+    }
+    try_types!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+    None
+}
+
 // Common validation rules
 
+/// Extracts a string value from a joinpoint for validation.
+type StringGetter = Arc<dyn Fn(&JoinPoint) -> Option<String> + Send + Sync>;
+
+/// Extracts a numeric value from a joinpoint for validation.
+type NumberGetter = Arc<dyn Fn(&JoinPoint) -> Option<i64> + Send + Sync>;
+
+/// Closure-based validation check.
+type ValidatorFn = Arc<dyn Fn(&JoinPoint) -> Result<(), String> + Send + Sync>;
+
 /// Validates that a value is not empty.
 pub struct NotEmptyValidator {
     field_name: String,
-    getter: Arc<dyn Fn(&JoinPoint) -> Option<String> + Send + Sync>,
+    getter: StringGetter,
 }
 
 impl NotEmptyValidator {
@@ -120,6 +451,21 @@ impl NotEmptyValidator {
             getter: Arc::new(getter),
         }
     }
+
+    /// Create a not-empty validator for a `String` or `&str` argument,
+    /// selected by position or parameter name.
+    ///
+    /// Like the getter form, the check is skipped when the argument is not
+    /// available as a string.
+    pub fn for_arg(arg: impl Into<ArgSelector>) -> Self {
+        let selector = arg.into();
+        Self::new(&selector.to_string(), move |ctx| {
+            selector
+                .find(ctx)
+                .and_then(|arg| arg.value::<String>())
+                .cloned()
+        })
+    }
 }
 
 impl ValidationRule for NotEmptyValidator {
@@ -142,7 +488,7 @@ pub struct RangeValidator {
     field_name: String,
     min: i64,
     max: i64,
-    getter: Arc<dyn Fn(&JoinPoint) -> Option<i64> + Send + Sync>,
+    getter: NumberGetter,
 }
 
 impl RangeValidator {
@@ -164,6 +510,19 @@ impl RangeValidator {
             getter: Arc::new(getter),
         }
     }
+
+    /// Create a range validator for an integer argument, selected by
+    /// position or parameter name.
+    ///
+    /// Any primitive integer type is accepted. Like the getter form, the
+    /// check is skipped when the argument is not available as an integer;
+    /// use [`rule_for_arg`] to require it.
+    pub fn for_arg(arg: impl Into<ArgSelector>, min: i64, max: i64) -> Self {
+        let selector = arg.into();
+        Self::new(&selector.to_string(), min, max, move |ctx| {
+            selector.find(ctx).and_then(integer_value)
+        })
+    }
 }
 
 impl ValidationRule for RangeValidator {
@@ -187,7 +546,7 @@ impl ValidationRule for RangeValidator {
 /// Custom validation rule using a closure.
 pub struct CustomValidator {
     description: String,
-    validator: Arc<dyn Fn(&JoinPoint) -> Result<(), String> + Send + Sync>,
+    validator: ValidatorFn,
 }
 
 impl CustomValidator {
@@ -230,14 +589,14 @@ mod tests {
     #[test]
     fn test_custom_validator() {
         let validator = CustomValidator::new("test", |_ctx| Ok(()));
-        let ctx = JoinPoint {
-            function_name: "test",
-            module_path: "test",
-            location: aspect_core::Location {
+        let ctx = JoinPoint::new(
+            "test",
+            "test",
+            aspect_core::Location {
                 file: "test.rs",
                 line: 1,
             },
-        };
+        );
 
         assert!(validator.validate(&ctx).is_ok());
     }
@@ -245,14 +604,14 @@ mod tests {
     #[test]
     fn test_custom_validator_failure() {
         let validator = CustomValidator::new("test", |_ctx| Err("validation failed".to_string()));
-        let ctx = JoinPoint {
-            function_name: "test",
-            module_path: "test",
-            location: aspect_core::Location {
+        let ctx = JoinPoint::new(
+            "test",
+            "test",
+            aspect_core::Location {
                 file: "test.rs",
                 line: 1,
             },
-        };
+        );
 
         assert!(validator.validate(&ctx).is_err());
     }
@@ -260,14 +619,14 @@ mod tests {
     #[test]
     fn test_not_empty_validator() {
         let validator = NotEmptyValidator::new("username", |_ctx| Some("alice".to_string()));
-        let ctx = JoinPoint {
-            function_name: "test",
-            module_path: "test",
-            location: aspect_core::Location {
+        let ctx = JoinPoint::new(
+            "test",
+            "test",
+            aspect_core::Location {
                 file: "test.rs",
                 line: 1,
             },
-        };
+        );
 
         assert!(validator.validate(&ctx).is_ok());
     }
@@ -275,14 +634,14 @@ mod tests {
     #[test]
     fn test_not_empty_validator_failure() {
         let validator = NotEmptyValidator::new("username", |_ctx| Some("".to_string()));
-        let ctx = JoinPoint {
-            function_name: "test",
-            module_path: "test",
-            location: aspect_core::Location {
+        let ctx = JoinPoint::new(
+            "test",
+            "test",
+            aspect_core::Location {
                 file: "test.rs",
                 line: 1,
             },
-        };
+        );
 
         let result = validator.validate(&ctx);
         assert!(result.is_err());
@@ -292,14 +651,14 @@ mod tests {
     #[test]
     fn test_range_validator() {
         let validator = RangeValidator::new("age", 0, 120, |_ctx| Some(25));
-        let ctx = JoinPoint {
-            function_name: "test",
-            module_path: "test",
-            location: aspect_core::Location {
+        let ctx = JoinPoint::new(
+            "test",
+            "test",
+            aspect_core::Location {
                 file: "test.rs",
                 line: 1,
             },
-        };
+        );
 
         assert!(validator.validate(&ctx).is_ok());
     }
@@ -307,17 +666,173 @@ mod tests {
     #[test]
     fn test_range_validator_failure() {
         let validator = RangeValidator::new("age", 0, 120, |_ctx| Some(150));
-        let ctx = JoinPoint {
-            function_name: "test",
-            module_path: "test",
-            location: aspect_core::Location {
+        let ctx = JoinPoint::new(
+            "test",
+            "test",
+            aspect_core::Location {
                 file: "test.rs",
                 line: 1,
             },
-        };
+        );
 
         let result = validator.validate(&ctx);
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("must be between"));
     }
+
+    fn call_with(args: Vec<Arg>) -> JoinPoint {
+        JoinPoint::new(
+            "register",
+            "test",
+            aspect_core::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        )
+        .with_args(args)
+    }
+
+    #[test]
+    fn test_rule_for_arg_by_index_and_name() {
+        let ctx = call_with(vec![
+            Arg::new("name", &"bob".to_string()),
+            Arg::new("age", &17u8),
+        ]);
+        let adult = |age: &u8| {
+            if *age >= 18 {
+                Ok(())
+            } else {
+                Err("must be an adult".to_string())
+            }
+        };
+
+        assert_eq!(
+            rule_for_arg::<u8>(1, adult).validate(&ctx),
+            Err("must be an adult".to_string())
+        );
+        assert_eq!(
+            rule_for_arg::<u8>("age", adult).validate(&ctx),
+            Err("must be an adult".to_string())
+        );
+        assert!(rule_for_arg::<String>("name", |name| {
+            if name == "bob" {
+                Ok(())
+            } else {
+                Err("unexpected name".to_string())
+            }
+        })
+        .validate(&ctx)
+        .is_ok());
+    }
+
+    #[test]
+    fn test_rule_for_arg_missing_or_mistyped() {
+        let ctx = call_with(vec![Arg::new("age", &17u8)]);
+
+        let err = rule_for_arg::<u8>("years", |_| Ok(()))
+            .validate(&ctx)
+            .unwrap_err();
+        assert_eq!(err, "years not found");
+
+        let err = rule_for_arg::<i32>(0, |_| Ok(()))
+            .validate(&ctx)
+            .unwrap_err();
+        assert_eq!(err, "argument 0 is a u8, not a i32");
+    }
+
+    #[test]
+    fn test_validators_for_args() {
+        let ctx = call_with(vec![
+            Arg::new("name", &String::new()),
+            Arg::new("age", &150u32),
+        ]);
+
+        assert_eq!(
+            NotEmptyValidator::for_arg("name").validate(&ctx),
+            Err("name cannot be empty".to_string())
+        );
+        assert_eq!(
+            RangeValidator::for_arg("age", 0, 120).validate(&ctx),
+            Err("age must be between 0 and 120, got 150".to_string())
+        );
+        assert!(RangeValidator::for_arg(1, 0, 200).validate(&ctx).is_ok());
+    }
+
+    #[aspect_macros::aspect(ValidationAspect::new()
+        .add_rule(Box::new(NotEmptyValidator::for_arg("name")))
+        .add_rule(Box::new(RangeValidator::for_arg("age", 0, 120))))]
+    fn register(name: &str, age: i32) -> Result<String, String> {
+        Ok(format!("{} ({})", name, age))
+    }
+
+    #[test]
+    fn test_validates_woven_function_arguments() {
+        assert_eq!(register("alice", 30).unwrap(), "alice (30)");
+
+        let err = register("", 30).unwrap_err();
+        assert!(err.contains("name cannot be empty"), "{}", err);
+
+        let err = register("alice", -1).unwrap_err();
+        assert!(
+            err.contains("age must be between 0 and 120, got -1"),
+            "{}",
+            err
+        );
+    }
+
+    fn sorted_ids() -> ValidationAspect {
+        ValidationAspect::new().add_post_rule(Box::new(rule_for_result::<Vec<u32>>(|ids| {
+            if ids.windows(2).all(|pair| pair[0] <= pair[1]) {
+                Ok(())
+            } else {
+                Err("ids must be sorted".to_string())
+            }
+        })))
+    }
+
+    #[aspect_macros::aspect(sorted_ids())]
+    fn ids(values: Vec<u32>) -> Result<Vec<u32>, String> {
+        if values.is_empty() {
+            Err("no ids".to_string())
+        } else {
+            Ok(values)
+        }
+    }
+
+    #[aspect_macros::aspect(ValidationAspect::new()
+        .add_post_rule(Box::new(rule_for_result::<u32>(|n| {
+            if *n > 0 { Ok(()) } else { Err("must be positive".to_string()) }
+        }))))]
+    fn count(n: u32) -> u32 {
+        n
+    }
+
+    #[test]
+    fn test_post_rules_check_returned_values() {
+        assert_eq!(ids(vec![1, 2, 3]).unwrap(), vec![1, 2, 3]);
+
+        let err = ids(vec![3, 1]).unwrap_err();
+        assert!(
+            err.contains("Postcondition failed for ids: ids must be sorted"),
+            "{}",
+            err
+        );
+
+        // Failed calls are not checked
+        let err = ids(vec![]).unwrap_err();
+        assert!(err.contains("no ids"), "{}", err);
+
+        assert_eq!(count(3), 3);
+        assert!(std::panic::catch_unwind(|| count(0)).is_err());
+    }
+
+    #[test]
+    fn test_result_rule_type_mismatch() {
+        let ctx = call_with(vec![]);
+        let rule = rule_for_result::<String>(|_| Ok(()));
+        assert_eq!(
+            rule.validate(&ctx, &5u32),
+            Err("result is not a alloc::string::String".to_string())
+        );
+    }
 }
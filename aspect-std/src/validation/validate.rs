@@ -0,0 +1,194 @@
+//! Validating arguments with their [`validator::Validate`] impls, with the
+//! `validator` feature.
+
+use aspect_core::{AspectError, JoinPoint};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use validator::{Validate, ValidationErrors};
+
+type ValidateFn = fn(&(dyn Any + Send + Sync)) -> Result<(), ValidationErrors>;
+
+/// The types whose arguments a [`ValidationAspect`](super::ValidationAspect)
+/// validates, see [`validated`](super::ValidationAspect::validated).
+#[derive(Default)]
+pub(super) struct Models(HashMap<TypeId, ValidateFn>);
+
+impl Models {
+    pub(super) fn register<T: Validate + 'static>(&mut self) {
+        self.0.insert(TypeId::of::<T>(), validate::<T>);
+    }
+
+    /// Validates the arguments of the registered types, reporting the
+    /// errors of all the invalid ones.
+    pub(super) fn validate(&self, ctx: &JoinPoint) -> Result<(), AspectError> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        let errors: Vec<_> = ctx
+            .args
+            .iter()
+            .filter_map(|arg| {
+                let value = arg.any()?;
+                let validate = self.0.get(&(*value).type_id())?;
+                validate(value).err().map(|errors| (arg.name, errors))
+            })
+            .collect();
+        if errors.is_empty() {
+            return Ok(());
+        }
+        Err(AspectError::custom(InvalidArguments {
+            function: ctx.function_name,
+            errors,
+        }))
+    }
+}
+
+fn validate<T: Validate + 'static>(
+    value: &(dyn Any + Send + Sync),
+) -> Result<(), ValidationErrors> {
+    value.downcast_ref::<T>().map_or(Ok(()), T::validate)
+}
+
+/// The error of a call whose arguments failed their `Validate` checks,
+/// carried by an [`AspectError::Custom`].
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use aspect_std::validation::InvalidArguments;
+///
+/// if let Err(err) = create_user(request) {
+///     if let Some(invalid) = InvalidArguments::of(&err) {
+///         for (field, errors) in invalid.get("request").unwrap().field_errors() {
+///             eprintln!("{}: {:?}", field, errors);
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidArguments {
+    /// The function called
+    pub function: &'static str,
+    /// The errors of each invalid argument, by parameter name, in the order
+    /// of the parameters
+    pub errors: Vec<(&'static str, ValidationErrors)>,
+}
+
+impl InvalidArguments {
+    /// The invalid arguments `error` reports, if it reports any.
+    pub fn of(error: &AspectError) -> Option<&Self> {
+        match error {
+            AspectError::Custom(error) => error.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// The errors of the argument `name`, if it was invalid.
+    pub fn get(&self, name: &str) -> Option<&ValidationErrors> {
+        self.errors
+            .iter()
+            .find(|(arg, _)| *arg == name)
+            .map(|(_, errors)| errors)
+    }
+}
+
+/// As `Validation failed for create_user: user (email: ..., name: ...)`.
+impl fmt::Display for InvalidArguments {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Validation failed for {}: ", self.function)?;
+        for (i, (name, errors)) in self.errors.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            let errors = errors.to_string().replace('\n', ", ");
+            write!(f, "{} ({})", name, errors)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for InvalidArguments {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ValidationAspect;
+    use aspect_macros::aspect;
+    use validator::Validate;
+
+    #[derive(Debug, Clone, Hash, Validate)]
+    struct NewUser {
+        #[validate(length(min = 1, max = 20))]
+        name: String,
+        #[validate(email)]
+        email: String,
+    }
+
+    #[derive(Debug, Clone, Validate)]
+    struct Address {
+        #[validate(length(equal = 2))]
+        country: String,
+    }
+
+    fn new_user(name: &str, email: &str) -> NewUser {
+        NewUser {
+            name: name.to_string(),
+            email: email.to_string(),
+        }
+    }
+
+    #[aspect(ValidationAspect::new().validated::<NewUser>().validated::<Address>())]
+    fn create_user(user: NewUser, address: &Address, note: String) -> Result<String, String> {
+        Ok(format!("{} ({}) {}", user.name, address.country, note))
+    }
+
+    #[test]
+    fn test_validates_registered_types() {
+        let fr = Address {
+            country: "FR".to_string(),
+        };
+        assert_eq!(
+            create_user(new_user("ann", "ann@example.com"), &fr, String::new()),
+            Ok("ann (FR) ".to_string())
+        );
+
+        // The errors of every invalid argument are reported
+        let nowhere = Address {
+            country: "France".to_string(),
+        };
+        let err = create_user(new_user("", "ann"), &nowhere, String::new()).unwrap_err();
+        assert!(err.contains("InvalidArguments"), "{}", err);
+        assert!(err.contains(r#"("user", "#) && err.contains(r#"("address", "#));
+        assert!(!err.contains(r#"("note", "#));
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let aspect = ValidationAspect::new().validated::<NewUser>();
+        let ctx = JoinPoint::new(
+            "create_user",
+            "test",
+            aspect_core::Location {
+                file: "test.rs",
+                line: 1,
+            },
+        )
+        .with_args(vec![
+            aspect_core::Arg::new("id", &1u64),
+            aspect_core::Arg::new("user", &new_user("", "ann@example.com")),
+        ]);
+
+        let err = aspect.validate(&ctx).unwrap_err();
+        let invalid = InvalidArguments::of(&err).unwrap();
+        assert_eq!(invalid.function, "create_user");
+        assert_eq!(invalid.errors.len(), 1);
+        let errors = invalid.get("user").unwrap().field_errors();
+        assert_eq!(errors["name"][0].code, "length");
+        assert!(!errors.contains_key("email"));
+        assert!(err
+            .to_string()
+            .contains("Validation failed for create_user: user (name: "));
+    }
+}
@@ -0,0 +1,122 @@
+//! # aspect-test
+//!
+//! Testing utilities for aspects and the code they are woven into.
+//!
+//! This crate provides:
+//! - [`MockAspect`]: An aspect recording every advice it runs with its join point
+//! - [`assert_advised!`] and [`assert_not_advised!`]: Assertions on the recorded advice
+//! - [`ScopedRegistry`]: The global registry, emptied for one test and restored after
+//! - [`ManualClock`]: A clock advanced by the test, given to aspects or installed as
+//!   the time of all the aspects of `aspect-std` on the test's thread
+//!
+//! Add it as a dev-dependency; it enables the `test-util` feature of
+//! `aspect-std` in tests only.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use aspect_macros::aspect;
+//! use aspect_test::{assert_advised, MockAspect};
+//! use std::sync::LazyLock;
+//!
+//! static MOCK: LazyLock<MockAspect> = LazyLock::new(MockAspect::new);
+//!
+//! #[aspect(MOCK.clone())]
+//! fn transfer(amount: u64) -> Result<(), String> {
+//!     Ok(())
+//! }
+//!
+//! #[test]
+//! fn transfer_is_advised() {
+//!     transfer(10).unwrap();
+//!     assert_advised!(transfer, before);
+//!     assert_advised!(transfer, after);
+//! }
+//! ```
+
+pub mod mock;
+pub mod registry;
+
+pub use aspect_std::time::{InstalledClock, ManualClock};
+pub use mock::{Advice, AdviceCall, MockAspect};
+pub use registry::ScopedRegistry;
+
+/// Asserts that an advice ran for a function.
+///
+/// With an aspect, `assert_advised!(mock, function, advice)` checks the
+/// calls of that [`MockAspect`]; without, `assert_advised!(function,
+/// advice)` checks those of all mock aspects on the current thread, see
+/// [`mock::thread_calls`]. The advice is one of `before`, `after`,
+/// `after_error` and `around`.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::prelude::*;
+/// use aspect_test::{assert_advised, assert_not_advised, MockAspect};
+///
+/// let mock = MockAspect::new();
+/// mock.before(&JoinPoint::new("load", "app", Location { file: "app.rs", line: 1 }));
+/// assert_advised!(mock, load, before);
+/// assert_advised!(load, before);
+/// assert_not_advised!(mock, load, after);
+/// ```
+#[macro_export]
+macro_rules! assert_advised {
+    ($function:ident, $advice:ident) => {
+        $crate::mock::check_advised(
+            &$crate::mock::thread_calls(),
+            stringify!($function),
+            $crate::__advice!($advice),
+            true,
+        )
+    };
+    ($mock:expr, $function:ident, $advice:ident) => {
+        $crate::mock::check_advised(
+            &$mock.calls(),
+            stringify!($function),
+            $crate::__advice!($advice),
+            true,
+        )
+    };
+}
+
+/// Asserts that an advice did not run for a function, the opposite of
+/// [`assert_advised!`].
+#[macro_export]
+macro_rules! assert_not_advised {
+    ($function:ident, $advice:ident) => {
+        $crate::mock::check_advised(
+            &$crate::mock::thread_calls(),
+            stringify!($function),
+            $crate::__advice!($advice),
+            false,
+        )
+    };
+    ($mock:expr, $function:ident, $advice:ident) => {
+        $crate::mock::check_advised(
+            &$mock.calls(),
+            stringify!($function),
+            $crate::__advice!($advice),
+            false,
+        )
+    };
+}
+
+/// The [`Advice`] named by an identifier.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __advice {
+    (before) => {
+        $crate::Advice::Before
+    };
+    (after) => {
+        $crate::Advice::After
+    };
+    (after_error) => {
+        $crate::Advice::AfterError
+    };
+    (around) => {
+        $crate::Advice::Around
+    };
+}
@@ -0,0 +1,302 @@
+//! An aspect recording the advice it runs.
+
+use aspect_core::aspect::BoxFuture;
+use aspect_core::{Aspect, AspectError, JoinPoint, ProceedingJoinPoint};
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// The advice of an aspect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Advice {
+    /// [`Aspect::before`]
+    Before,
+    /// [`Aspect::after`]
+    After,
+    /// [`Aspect::after_error`]
+    AfterError,
+    /// [`Aspect::around`], or [`Aspect::around_async`] for async calls
+    Around,
+}
+
+impl fmt::Display for Advice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Advice::Before => "before",
+            Advice::After => "after",
+            Advice::AfterError => "after_error",
+            Advice::Around => "around",
+        })
+    }
+}
+
+/// An advice run by a [`MockAspect`].
+#[derive(Debug, Clone)]
+pub struct AdviceCall {
+    /// Which advice ran
+    pub advice: Advice,
+
+    /// The join point it ran for
+    pub join_point: JoinPoint,
+
+    /// The error, for [`Advice::AfterError`]
+    pub error: Option<String>,
+}
+
+impl fmt::Display for AdviceCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.advice, self.join_point.function_name)
+    }
+}
+
+thread_local! {
+    static THREAD_CALLS: RefCell<Vec<AdviceCall>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The advice run by all [`MockAspect`]s on the current thread, oldest
+/// first.
+///
+/// This is what [`assert_advised!`](crate::assert_advised) checks when not
+/// given an aspect. Each test runs on a thread of its own, so tests running
+/// in parallel do not see each other's calls.
+pub fn thread_calls() -> Vec<AdviceCall> {
+    THREAD_CALLS.with(|calls| calls.borrow().clone())
+}
+
+/// Forget the advice run on the current thread so far.
+pub fn clear_thread_calls() {
+    THREAD_CALLS.with(|calls| calls.borrow_mut().clear());
+}
+
+/// An aspect recording each advice it runs with its join point, for tests
+/// of woven code and of the pointcuts that select it.
+///
+/// Its `around` advice runs `before`, the function and `after` or
+/// `after_error`, as the default one does, and each is recorded. A mock
+/// [`failing_with`](Self::failing_with) an error rejects calls instead,
+/// without running the function, for tests of error paths.
+///
+/// Clones share the calls, so a clone given to the code under test can be
+/// inspected through the original. Calls are also recorded for the thread
+/// they ran on, see [`thread_calls`].
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::prelude::*;
+/// use aspect_test::{assert_advised, Advice, MockAspect};
+/// use std::any::Any;
+///
+/// let mock = MockAspect::new();
+/// let ctx = JoinPoint::new("save_user", "app::db", Location { file: "db.rs", line: 3 });
+/// mock.around(ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn Any>), ctx))
+///     .unwrap();
+///
+/// assert_advised!(mock, save_user, before);
+/// assert_advised!(mock, save_user, after);
+/// assert_eq!(mock.count("save_user", Advice::AfterError), 0);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockAspect {
+    calls: Arc<Mutex<Vec<AdviceCall>>>,
+    error: Option<String>,
+}
+
+impl MockAspect {
+    /// Create a mock running the functions it advises.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reject calls with an execution error with `message`, without
+    /// running the functions.
+    pub fn failing_with(mut self, message: impl Into<String>) -> Self {
+        self.error = Some(message.into());
+        self
+    }
+
+    /// The advice run so far, oldest first.
+    pub fn calls(&self) -> Vec<AdviceCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Whether `advice` ran for the function named `function`.
+    pub fn advised(&self, function: &str, advice: Advice) -> bool {
+        self.count(function, advice) > 0
+    }
+
+    /// How many times `advice` ran for the function named `function`.
+    pub fn count(&self, function: &str, advice: Advice) -> usize {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|call| call.advice == advice && call.join_point.function_name == function)
+            .count()
+    }
+
+    /// Forget the advice run so far.
+    pub fn clear(&self) {
+        self.calls.lock().unwrap().clear();
+    }
+
+    fn record(&self, advice: Advice, ctx: &JoinPoint, error: Option<&AspectError>) {
+        let call = AdviceCall {
+            advice,
+            join_point: ctx.clone(),
+            error: error.map(ToString::to_string),
+        };
+        THREAD_CALLS.with(|calls| calls.borrow_mut().push(call.clone()));
+        self.calls.lock().unwrap().push(call);
+    }
+
+    /// The error rejecting calls, if the mock is failing.
+    fn rejection(&self) -> Option<AspectError> {
+        self.error.as_ref().map(AspectError::execution)
+    }
+}
+
+impl Aspect for MockAspect {
+    fn before(&self, ctx: &JoinPoint) {
+        self.record(Advice::Before, ctx, None);
+    }
+
+    fn after(&self, ctx: &JoinPoint, _result: &dyn Any) {
+        self.record(Advice::After, ctx, None);
+    }
+
+    fn after_error(&self, ctx: &JoinPoint, error: &AspectError) {
+        self.record(Advice::AfterError, ctx, Some(error));
+    }
+
+    fn around(&self, pjp: ProceedingJoinPoint) -> Result<Box<dyn Any>, AspectError> {
+        self.record(Advice::Around, pjp.context(), None);
+        if let Some(error) = self.rejection() {
+            return Err(error);
+        }
+        self.before(pjp.context());
+        let (result, ctx) = pjp.proceed_with_context();
+        match &result {
+            Ok(value) => self.after(&ctx, value.as_ref()),
+            Err(error) => self.after_error(&ctx, error),
+        }
+        result
+    }
+
+    fn around_async<'a>(
+        &'a self,
+        ctx: &'a JoinPoint,
+        proceed: BoxFuture<'a, Result<Box<dyn Any>, AspectError>>,
+    ) -> BoxFuture<'a, Result<Box<dyn Any>, AspectError>> {
+        Box::pin(async move {
+            self.record(Advice::Around, ctx, None);
+            if let Some(error) = self.rejection() {
+                return Err(error);
+            }
+            self.before(ctx);
+            let result = proceed.await;
+            match &result {
+                Ok(value) => self.after(ctx, value.as_ref()),
+                Err(error) => self.after_error(ctx, error),
+            }
+            result
+        })
+    }
+}
+
+/// Panics unless `advice` ran for `function` in `calls` as `expected`.
+#[doc(hidden)]
+#[track_caller]
+pub fn check_advised(calls: &[AdviceCall], function: &str, advice: Advice, expected: bool) {
+    let advised = calls
+        .iter()
+        .any(|call| call.advice == advice && call.join_point.function_name == function);
+    if advised != expected {
+        let recorded: Vec<String> = calls.iter().map(ToString::to_string).collect();
+        panic!(
+            "`{}` was {}advised by `{}`; recorded: [{}]",
+            function,
+            if advised { "" } else { "not " },
+            advice,
+            recorded.join(", ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_core::Location;
+
+    fn join_point(name: &'static str) -> JoinPoint {
+        JoinPoint::new(
+            name,
+            "app::db",
+            Location {
+                file: "db.rs",
+                line: 3,
+            },
+        )
+    }
+
+    #[test]
+    fn test_records_advice() {
+        clear_thread_calls();
+        let mock = MockAspect::new();
+        let clone = mock.clone();
+
+        let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(1) as Box<dyn Any>), join_point("load"));
+        clone.around(pjp).unwrap();
+        let pjp = ProceedingJoinPoint::new(
+            || Err(AspectError::execution("duplicate key")),
+            join_point("save"),
+        );
+        assert!(clone.around(pjp).is_err());
+
+        let calls: Vec<String> = mock.calls().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            calls,
+            [
+                "around load",
+                "before load",
+                "after load",
+                "around save",
+                "before save",
+                "after_error save"
+            ]
+        );
+        assert!(mock.calls()[5]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("duplicate key"));
+        assert!(mock.advised("save", Advice::AfterError));
+        assert!(!mock.advised("load", Advice::AfterError));
+        assert_eq!(thread_calls().len(), 6);
+
+        mock.clear();
+        assert!(clone.calls().is_empty());
+    }
+
+    #[test]
+    fn test_failing_with() {
+        let mock = MockAspect::new().failing_with("unavailable");
+        let pjp = ProceedingJoinPoint::new(
+            || -> Result<Box<dyn Any>, AspectError> { panic!("must not run") },
+            join_point("load"),
+        );
+        let error = mock.around(pjp).unwrap_err();
+        assert!(error.to_string().contains("unavailable"));
+        assert_eq!(mock.count("load", Advice::Around), 1);
+        assert!(!mock.advised("load", Advice::Before));
+    }
+
+    #[test]
+    #[should_panic(expected = "`load` was not advised by `after_error`; recorded: [before load]")]
+    fn test_check_advised_message() {
+        let mock = MockAspect::new();
+        mock.before(&join_point("load"));
+        check_advised(&mock.calls(), "load", Advice::AfterError, true);
+    }
+}
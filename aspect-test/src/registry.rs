@@ -0,0 +1,129 @@
+//! The global registry, scoped to a test.
+
+use aspect_core::pointcut::Pointcut;
+use aspect_core::Aspect;
+use aspect_runtime::{global_registry, AspectRegistry, RegisteredAspect};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Serializes the tests using the global registry.
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// The global registry, for one test at a time.
+///
+/// Woven code finds its aspects in the global registry, which all the tests
+/// of a binary share. A `ScopedRegistry` gives it to a single test: other
+/// tests taking one wait until it is dropped, when the aspects registered
+/// before it was taken are restored, and those registered since are
+/// forgotten.
+///
+/// A test takes the registry once: taking it again before dropping it
+/// deadlocks. `#[advice]` functions register themselves the first time
+/// they are needed; force their registration before taking the registry,
+/// or it is undone with the others when it is dropped.
+///
+/// # Example
+///
+/// ```rust
+/// use aspect_core::pointcut::FunctionInfo;
+/// use aspect_core::{JoinPoint, Location};
+/// use aspect_test::{assert_advised, MockAspect, ScopedRegistry};
+/// use std::any::Any;
+///
+/// let registry = ScopedRegistry::new();
+/// let mock = MockAspect::new();
+/// registry.register(mock.clone(), "execution(pub fn save*(..))", 0);
+///
+/// let function = FunctionInfo::new("save_user", "app::db", "pub");
+/// let ctx = JoinPoint::new("save_user", "app::db", Location { file: "db.rs", line: 3 });
+/// registry
+///     .invoke(&function, || ctx, || Ok(Box::new(()) as Box<dyn Any>))
+///     .unwrap();
+/// assert_advised!(mock, save_user, before);
+/// ```
+pub struct ScopedRegistry {
+    saved: Vec<Arc<RegisteredAspect>>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl ScopedRegistry {
+    /// Take the global registry for the current test, emptied.
+    ///
+    /// Waits for the tests that have it to drop theirs.
+    pub fn new() -> Self {
+        let scoped = Self::inherit();
+        global_registry().clear();
+        scoped
+    }
+
+    /// Take the global registry for the current test, with the aspects
+    /// registered so far.
+    pub fn inherit() -> Self {
+        // A test failing with the registry still releases it
+        let lock = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        Self {
+            saved: global_registry().aspects(),
+            _lock: lock,
+        }
+    }
+
+    /// Register `aspect` for the functions matching `pointcut`, in the
+    /// global registry, until the end of the scope.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pointcut` is not a valid pointcut expression.
+    #[track_caller]
+    pub fn register(&self, aspect: impl Aspect + 'static, pointcut: &str, order: i32) {
+        let parsed = Pointcut::parse(pointcut)
+            .unwrap_or_else(|error| panic!("invalid pointcut `{}`: {}", pointcut, error));
+        global_registry().register(Arc::new(aspect), parsed, order, Some(pointcut.to_string()));
+    }
+}
+
+impl Default for ScopedRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::ops::Deref for ScopedRegistry {
+    type Target = AspectRegistry;
+
+    fn deref(&self) -> &AspectRegistry {
+        global_registry()
+    }
+}
+
+impl Drop for ScopedRegistry {
+    fn drop(&mut self) {
+        global_registry().restore(std::mem::take(&mut self.saved));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockAspect;
+    use aspect_core::pointcut::FunctionInfo;
+
+    #[test]
+    fn test_scoped_registry() {
+        let function = FunctionInfo::new("save_user", "app::db", "pub");
+
+        let registry = ScopedRegistry::new();
+        assert_eq!(registry.count(), 0);
+        registry.register(MockAspect::new(), "execution(pub fn save*(..))", 0);
+        assert_eq!(registry.find_matching(&function).len(), 1);
+        drop(registry);
+
+        // Forgotten with the scope
+        let registry = ScopedRegistry::inherit();
+        assert!(registry.find_matching(&function).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid pointcut `nonsense`")]
+    fn test_invalid_pointcut() {
+        ScopedRegistry::new().register(MockAspect::new(), "nonsense", 0);
+    }
+}
@@ -0,0 +1,61 @@
+//! The testing utilities on woven code and standard aspects.
+
+use aspect_core::prelude::*;
+use aspect_macros::aspect;
+use aspect_std::{CircuitBreakerAspect, CircuitState};
+use aspect_test::{assert_advised, assert_not_advised, ManualClock, MockAspect};
+use std::any::Any;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+static MOCK: LazyLock<MockAspect> = LazyLock::new(MockAspect::new);
+
+#[aspect(MOCK.clone())]
+fn transfer(amount: u64) -> Result<u64, String> {
+    if amount == 0 {
+        return Err("empty transfer".to_string());
+    }
+    Ok(amount)
+}
+
+#[test]
+fn test_woven_function_advised() {
+    assert_eq!(transfer(10), Ok(10));
+    assert_advised!(transfer, before);
+    assert_advised!(transfer, after);
+    assert_not_advised!(transfer, after_error);
+
+    assert!(transfer(0).is_err());
+    assert_advised!(transfer, after_error);
+    // The mock sees the calls of all tests, the thread only this one's
+    assert_advised!(MOCK, transfer, after_error);
+}
+
+#[test]
+fn test_circuit_breaker_with_installed_clock() {
+    let clock = ManualClock::new();
+    let _installed = clock.install();
+    // Without a clock of its own, the breaker reads the installed one
+    let breaker = CircuitBreakerAspect::new(1, Duration::from_secs(30));
+    let ctx = JoinPoint::new(
+        "fetch",
+        "app::client",
+        Location {
+            file: "client.rs",
+            line: 1,
+        },
+    );
+
+    let failing = ProceedingJoinPoint::new(|| Err(AspectError::execution("down")), ctx.clone());
+    assert!(breaker.around(failing).is_err());
+    assert!(matches!(breaker.state(), CircuitState::Open { .. }));
+
+    clock.advance(Duration::from_secs(29));
+    let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn Any>), ctx.clone());
+    assert!(breaker.around(pjp).is_err(), "still open");
+
+    clock.advance(Duration::from_secs(1));
+    let pjp = ProceedingJoinPoint::new(|| Ok(Box::new(()) as Box<dyn Any>), ctx);
+    assert!(breaker.around(pjp).is_ok());
+    assert_eq!(breaker.state(), CircuitState::Closed);
+}
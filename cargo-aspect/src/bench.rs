@@ -0,0 +1,304 @@
+//! `cargo aspect bench --compare`: the overhead of aspects on benchmarks.
+//!
+//! The benchmarks run twice: once woven like by `cargo aspect bench`, and
+//! once built with `ASPECT_COMPILE_OUT` set, which compiles `#[aspect]` and
+//! `#[advice]` out, without the compiler driver and in a target directory
+//! of its own. Each run writes its Criterion results to a directory of its
+//! own (`CRITERION_HOME`), and the mean times of the two runs are compared
+//! benchmark by benchmark.
+
+use anyhow::{Context, Result};
+use log::info;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use super::{run_cargo_command, run_woven_cargo_command, Workspace};
+
+/// Set at compile time, compiles aspects out (see `aspect_macros`).
+pub const COMPILE_OUT_ENV: &str = "ASPECT_COMPILE_OUT";
+
+/// Directory Criterion writes its results to.
+const CRITERION_HOME_ENV: &str = "CRITERION_HOME";
+
+/// Settings of `cargo aspect bench --compare`.
+pub struct Compare<'a> {
+    /// Arguments of `cargo bench`, for both runs
+    pub args: &'a [String],
+
+    pub driver_flags: &'a [String],
+    pub config: Option<&'a Path>,
+    pub profile: Option<&'a str>,
+    pub driver_env: &'a [(&'a str, &'a str)],
+}
+
+impl Compare<'_> {
+    /// Run the benchmarks with and without aspects, and print the
+    /// overhead of each.
+    pub fn run(&self) -> Result<()> {
+        let workspace = Workspace::locate_for(self.args)?;
+        let bench_dir = workspace.target_dir.join("aspect").join("bench");
+        let woven_dir = bench_dir.join("with-aspects");
+        let baseline_dir = bench_dir.join("without-aspects");
+        for dir in [&woven_dir, &baseline_dir] {
+            let _ = fs::remove_dir_all(dir);
+        }
+
+        info!("Running benchmarks with aspects");
+        let woven_home = woven_dir.display().to_string();
+        let mut env = self.driver_env.to_vec();
+        env.push((CRITERION_HOME_ENV, &woven_home));
+        run_woven_cargo_command(
+            "bench",
+            self.args,
+            self.driver_flags,
+            self.config,
+            self.profile,
+            &env,
+        )?;
+
+        info!("Running benchmarks without aspects");
+        let baseline_home = baseline_dir.display().to_string();
+        let baseline_target = bench_dir.join("target").display().to_string();
+        run_cargo_command(
+            "bench",
+            &without_target_dir(self.args),
+            &[
+                (COMPILE_OUT_ENV, "1"),
+                ("CARGO_TARGET_DIR", &baseline_target),
+                (CRITERION_HOME_ENV, &baseline_home),
+            ],
+        )?;
+
+        let rows = compare(
+            &read_estimates(&baseline_dir)?,
+            &read_estimates(&woven_dir)?,
+        );
+        if rows.is_empty() {
+            anyhow::bail!(
+                "no benchmark results: --compare reads the results of Criterion benchmarks"
+            );
+        }
+        print_overhead(&rows);
+        Ok(())
+    }
+}
+
+/// `args` without their `--target-dir`: the run without aspects builds in
+/// a target directory of its own.
+fn without_target_dir(args: &[String]) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            // The arguments of the benchmarks
+            kept.push(arg.clone());
+            kept.extend(args.cloned());
+            break;
+        } else if arg == "--target-dir" {
+            args.next();
+        } else if !arg.starts_with("--target-dir=") {
+            kept.push(arg.clone());
+        }
+    }
+    kept
+}
+
+/// Mean times of the Criterion benchmarks in `dir`, in nanoseconds, by
+/// benchmark id (`group/function`).
+pub fn read_estimates(dir: &Path) -> Result<BTreeMap<String, f64>> {
+    let mut estimates = BTreeMap::new();
+    collect_estimates(dir, dir, &mut estimates)?;
+    Ok(estimates)
+}
+
+fn collect_estimates(root: &Path, dir: &Path, estimates: &mut BTreeMap<String, f64>) -> Result<()> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let estimates_file = path.join("estimates.json");
+        if entry.file_name() != "new" || !estimates_file.is_file() {
+            collect_estimates(root, &path, estimates)?;
+            continue;
+        }
+
+        let json = |path: &Path| -> Result<serde_json::Value> {
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("Cannot read {}", path.display()))?;
+            serde_json::from_str(&contents).with_context(|| format!("Invalid {}", path.display()))
+        };
+        let mean = json(&estimates_file)?["mean"]["point_estimate"]
+            .as_f64()
+            .with_context(|| format!("No mean in {}", estimates_file.display()))?;
+        // The id as given to Criterion; the directories are sanitized
+        let id = json(&path.join("benchmark.json"))
+            .ok()
+            .and_then(|benchmark| benchmark["full_id"].as_str().map(str::to_string))
+            .unwrap_or_else(|| dir.strip_prefix(root).unwrap_or(dir).display().to_string());
+        estimates.insert(id, mean);
+    }
+    Ok(())
+}
+
+/// A benchmark, with its mean time in nanoseconds in each run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub name: String,
+    pub without_aspects: Option<f64>,
+    pub with_aspects: Option<f64>,
+}
+
+impl Row {
+    /// Time added by aspects, in nanoseconds and in percent of the time
+    /// without them.
+    pub fn overhead(&self) -> Option<(f64, f64)> {
+        let (without, with) = (self.without_aspects?, self.with_aspects?);
+        let overhead = with - without;
+        Some((overhead, overhead / without * 100.0))
+    }
+}
+
+/// The benchmarks of both runs, by name.
+pub fn compare(without: &BTreeMap<String, f64>, with: &BTreeMap<String, f64>) -> Vec<Row> {
+    let names: BTreeSet<&String> = without.keys().chain(with.keys()).collect();
+    names
+        .into_iter()
+        .map(|name| Row {
+            name: name.clone(),
+            without_aspects: without.get(name).copied(),
+            with_aspects: with.get(name).copied(),
+        })
+        .collect()
+}
+
+/// Print the benchmarks as a table. Criterion prints its results on
+/// standard output too, so there is no JSON format.
+fn print_overhead(rows: &[Row]) {
+    let width = rows
+        .iter()
+        .map(|row| row.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("Benchmark".len());
+    let time = |ns: Option<f64>| ns.map_or_else(|| "-".to_string(), format_time);
+    println!();
+    println!("=== Aspect Overhead ===");
+    println!();
+    println!(
+        "{:<width$}  {:>15}  {:>15}  Overhead",
+        "Benchmark", "Without aspects", "With aspects"
+    );
+    for row in rows {
+        let overhead = match row.overhead() {
+            Some((ns, percent)) => format!(
+                "{}{} ({:+.1}%)",
+                if ns < 0.0 { "-" } else { "+" },
+                format_time(ns.abs()),
+                percent
+            ),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:<width$}  {:>15}  {:>15}  {}",
+            row.name,
+            time(row.without_aspects),
+            time(row.with_aspects),
+            overhead
+        );
+    }
+}
+
+/// A time in nanoseconds, in the unit that suits it.
+fn format_time(ns: f64) -> String {
+    if ns < 1e3 {
+        format!("{:.2} ns", ns)
+    } else if ns < 1e6 {
+        format!("{:.2} µs", ns / 1e3)
+    } else if ns < 1e9 {
+        format!("{:.2} ms", ns / 1e6)
+    } else {
+        format!("{:.2} s", ns / 1e9)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_without_target_dir() {
+        let args: Vec<String> = [
+            "--target-dir",
+            "out",
+            "--features=simd",
+            "--target-dir=out",
+            "--",
+            "--target-dir",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+        assert_eq!(
+            without_target_dir(&args),
+            ["--features=simd", "--", "--target-dir"]
+        );
+    }
+
+    #[test]
+    fn test_read_estimates() {
+        let dir = std::env::temp_dir().join(format!("cargo-aspect-bench-{}", std::process::id()));
+        let write = |path: &str, contents: &str| {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        };
+        write(
+            "calls/traced/new/estimates.json",
+            r#"{"mean":{"point_estimate":1250.5},"median":{"point_estimate":1200.0}}"#,
+        );
+        write(
+            "calls/traced/new/benchmark.json",
+            r#"{"group_id":"calls","function_id":"traced","full_id":"calls/traced"}"#,
+        );
+        write(
+            "calls/traced/base/estimates.json",
+            r#"{"mean":{"point_estimate":1.0}}"#,
+        );
+        write(
+            "fib_20/new/estimates.json",
+            r#"{"mean":{"point_estimate":80.0}}"#,
+        );
+        write("report/index.html", "");
+
+        let estimates = read_estimates(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            estimates,
+            BTreeMap::from([
+                ("calls/traced".to_string(), 1250.5),
+                ("fib_20".to_string(), 80.0),
+            ])
+        );
+        assert!(read_estimates(&dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compare() {
+        let without = BTreeMap::from([("a".to_string(), 100.0), ("b".to_string(), 2000.0)]);
+        let with = BTreeMap::from([("a".to_string(), 125.0), ("c".to_string(), 5.0)]);
+        let rows = compare(&without, &with);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].overhead(), Some((25.0, 25.0)));
+        assert_eq!(rows[1].overhead(), None);
+        assert_eq!(rows[2].without_aspects, None);
+
+        assert_eq!(format_time(80.0), "80.00 ns");
+        assert_eq!(format_time(1250.5), "1.25 µs");
+        assert_eq!(format_time(3.5e9), "3.50 s");
+    }
+}
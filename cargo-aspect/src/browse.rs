@@ -0,0 +1,608 @@
+//! `cargo aspect browse`: a terminal UI over what the aspects match.
+//!
+//! The workspace is analyzed like by `cargo aspect info`, and its modules,
+//! their functions and the aspects matching each function are shown side
+//! by side, so that large weaving configurations can be explored. A
+//! pointcut typed after `/` narrows the functions down to those it
+//! matches, and Enter opens the source of a function in `$VISUAL` or
+//! `$EDITOR`.
+
+use anyhow::{Context, Result};
+use aspect_driver::config::ConfigFile;
+use aspect_driver::lint::check_syntax;
+use aspect_driver::r#match::{PointcutMatcher, RegisteredAspect};
+use aspect_driver::report::AnalysisReport;
+use aspect_driver::types::FunctionMetadata;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::Command;
+
+use super::{analyze_workspace, load_registry, workspace_config, Workspace};
+
+/// An aspect, or a pointcut without advice, functions are matched against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Aspect {
+    name: String,
+
+    /// Where it is declared, with its kind of advice if any
+    origin: String,
+
+    pointcut: String,
+}
+
+/// The aspects of `#[advice]`, of aspect.toml and the pointcuts of
+/// aspect.toml and `--pointcut`, in that order.
+fn collect_aspects(
+    registered: &[RegisteredAspect],
+    config: Option<&ConfigFile>,
+    extra: &[String],
+) -> Vec<Aspect> {
+    let mut aspects: Vec<Aspect> = registered
+        .iter()
+        .map(|aspect| Aspect {
+            name: aspect.aspect_name.clone(),
+            origin: format!("#[advice], {}", aspect.advice_type),
+            pointcut: aspect.pointcut.clone(),
+        })
+        .collect();
+    if let Some(file) = config {
+        aspects.extend(file.aspects.iter().map(|aspect| Aspect {
+            name: aspect.label().to_string(),
+            origin: format!("aspect.toml, {}", aspect.advice),
+            pointcut: aspect.pointcut.clone(),
+        }));
+        aspects.extend(file.pointcuts.iter().map(|pointcut| Aspect {
+            name: pointcut.clone(),
+            origin: "aspect.toml".to_string(),
+            pointcut: pointcut.clone(),
+        }));
+    }
+    aspects.extend(extra.iter().map(|pointcut| Aspect {
+        name: pointcut.clone(),
+        origin: "--pointcut".to_string(),
+        pointcut: pointcut.clone(),
+    }));
+    aspects
+}
+
+/// The functions of a module of a crate.
+struct Module<'a> {
+    /// Name of the analysis report of the crate (`<crate>-<kind>`)
+    crate_name: &'a str,
+
+    path: &'a str,
+    functions: Vec<&'a FunctionMetadata>,
+}
+
+/// The pane keys act on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Modules,
+    Functions,
+    Aspects,
+}
+
+/// What the event loop does after a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    None,
+    Quit,
+
+    /// Open a file at a line in the editor
+    Open(String, usize),
+}
+
+/// State of the browser.
+struct Browser<'a> {
+    reports: &'a [(String, AnalysisReport)],
+    aspects: &'a [Aspect],
+    matcher: PointcutMatcher,
+
+    /// The modules shown: those with functions `filter` matches
+    modules: Vec<Module<'a>>,
+
+    /// Pointcut the functions shown must match
+    filter: Option<String>,
+
+    /// Pointcut being typed after `/`
+    input: Option<String>,
+
+    pane: Pane,
+    module: ListState,
+    function: ListState,
+    aspect: ListState,
+
+    /// Message of the last action
+    status: String,
+}
+
+impl<'a> Browser<'a> {
+    fn new(reports: &'a [(String, AnalysisReport)], aspects: &'a [Aspect]) -> Self {
+        let mut browser = Self {
+            reports,
+            aspects,
+            matcher: PointcutMatcher::new(),
+            modules: Vec::new(),
+            filter: None,
+            input: None,
+            pane: Pane::Modules,
+            module: ListState::default(),
+            function: ListState::default(),
+            aspect: ListState::default(),
+            status: String::new(),
+        };
+        browser.set_filter(None);
+        browser
+    }
+
+    /// Show the functions `filter` matches only, all of them without it.
+    fn set_filter(&mut self, filter: Option<String>) {
+        let mut modules = Vec::new();
+        for (crate_name, report) in self.reports {
+            let mut by_path: BTreeMap<&str, Vec<&FunctionMetadata>> = BTreeMap::new();
+            for function in &report.functions {
+                if filter
+                    .as_deref()
+                    .is_none_or(|filter| self.matcher.matches_pointcut(function, filter))
+                {
+                    by_path
+                        .entry(function.module_path.as_str())
+                        .or_default()
+                        .push(function);
+                }
+            }
+            modules.extend(by_path.into_iter().map(|(path, functions)| Module {
+                crate_name,
+                path,
+                functions,
+            }));
+        }
+
+        let matched: usize = modules.iter().map(|module| module.functions.len()).sum();
+        self.status = match &filter {
+            Some(filter) => format!("{} functions match `{}`", matched, filter),
+            None => format!("{} functions in {} modules", matched, modules.len()),
+        };
+        self.modules = modules;
+        self.filter = filter;
+        self.pane = Pane::Modules;
+        self.module.select((!self.modules.is_empty()).then_some(0));
+        self.select_module();
+    }
+
+    /// Select the first function of the module selected.
+    fn select_module(&mut self) {
+        self.function
+            .select((!self.functions().is_empty()).then_some(0));
+        self.select_function();
+    }
+
+    /// Select the first aspect of the function selected.
+    fn select_function(&mut self) {
+        self.aspect
+            .select((!self.function_aspects().is_empty()).then_some(0));
+    }
+
+    /// Functions of the module selected.
+    fn functions(&self) -> &[&'a FunctionMetadata] {
+        self.module
+            .selected()
+            .and_then(|i| self.modules.get(i))
+            .map_or(&[], |module| module.functions.as_slice())
+    }
+
+    fn selected_function(&self) -> Option<&'a FunctionMetadata> {
+        self.function
+            .selected()
+            .and_then(|i| self.functions().get(i).copied())
+    }
+
+    /// The aspects matching the function selected.
+    fn function_aspects(&self) -> Vec<&'a Aspect> {
+        let Some(function) = self.selected_function() else {
+            return Vec::new();
+        };
+        self.aspects
+            .iter()
+            .filter(|aspect| self.matcher.matches_pointcut(function, &aspect.pointcut))
+            .collect()
+    }
+
+    /// Move the selection of the current pane by `delta`, within bounds.
+    fn move_selection(&mut self, delta: isize) {
+        let (state, len) = match self.pane {
+            Pane::Modules => (&mut self.module, self.modules.len()),
+            Pane::Functions => {
+                let len = self.functions().len();
+                (&mut self.function, len)
+            }
+            Pane::Aspects => {
+                let len = self.function_aspects().len();
+                (&mut self.aspect, len)
+            }
+        };
+        if len == 0 {
+            return;
+        }
+        let selected = state.selected().unwrap_or(0) as isize + delta;
+        state.select(Some(selected.clamp(0, len as isize - 1) as usize));
+        match self.pane {
+            Pane::Modules => self.select_module(),
+            Pane::Functions => self.select_function(),
+            Pane::Aspects => {}
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Action::Quit;
+        }
+        if let Some(input) = &mut self.input {
+            match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Esc => {
+                    self.input = None;
+                    self.status.clear();
+                }
+                KeyCode::Enter => {
+                    let pointcut = input.trim().to_string();
+                    if pointcut.is_empty() {
+                        self.input = None;
+                        self.set_filter(None);
+                    } else if let Err(error) = check_syntax(&pointcut) {
+                        self.status = format!("invalid pointcut: {}", error.message);
+                    } else {
+                        self.input = None;
+                        self.set_filter(Some(pointcut));
+                    }
+                }
+                _ => {}
+            }
+            return Action::None;
+        }
+
+        match key.code {
+            KeyCode::Char('q') => return Action::Quit,
+            KeyCode::Esc if self.filter.is_some() => self.set_filter(None),
+            KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('/') => {
+                self.input = Some(self.filter.clone().unwrap_or_default());
+                self.status = "pointcut: Enter to apply, Esc to cancel".to_string();
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::PageDown => self.move_selection(10),
+            KeyCode::PageUp => self.move_selection(-10),
+            KeyCode::Home | KeyCode::Char('g') => self.move_selection(isize::MIN / 2),
+            KeyCode::End | KeyCode::Char('G') => self.move_selection(isize::MAX / 2),
+            KeyCode::Tab | KeyCode::Right | KeyCode::Char('l') => {
+                self.pane = match self.pane {
+                    Pane::Modules => Pane::Functions,
+                    Pane::Functions | Pane::Aspects => Pane::Aspects,
+                }
+            }
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h') => {
+                self.pane = match self.pane {
+                    Pane::Modules | Pane::Functions => Pane::Modules,
+                    Pane::Aspects => Pane::Functions,
+                }
+            }
+            KeyCode::Enter if self.pane == Pane::Modules => self.pane = Pane::Functions,
+            KeyCode::Enter | KeyCode::Char('o') => {
+                if let Some(function) = self.selected_function() {
+                    return Action::Open(function.location.file.clone(), function.location.line);
+                }
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [panes, details, status] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(4),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [modules, functions, aspects] = Layout::horizontal([
+            Constraint::Percentage(30),
+            Constraint::Percentage(35),
+            Constraint::Percentage(35),
+        ])
+        .areas(panes);
+
+        let block = |title: String, pane: Pane| {
+            let block = Block::bordered().title(title);
+            if self.pane == pane {
+                block.border_style(Style::new().bold())
+            } else {
+                block
+            }
+        };
+        let highlight = Style::new().add_modifier(Modifier::REVERSED);
+
+        let items: Vec<ListItem> = self
+            .modules
+            .iter()
+            .map(|module| {
+                ListItem::new(Line::from(vec![
+                    Span::raw(module.path),
+                    Span::raw(format!(" ({})", module.functions.len())).dim(),
+                    Span::raw(format!("  {}", module.crate_name)).dim(),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(block("Modules".to_string(), Pane::Modules))
+            .highlight_style(highlight);
+        frame.render_stateful_widget(list, modules, &mut self.module);
+
+        let items: Vec<ListItem> = self
+            .functions()
+            .iter()
+            .map(|function| {
+                let matched = self
+                    .aspects
+                    .iter()
+                    .filter(|aspect| self.matcher.matches_pointcut(function, &aspect.pointcut))
+                    .count();
+                let visibility = function.visibility.to_string();
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!(
+                        "{}{}{}fn {}",
+                        visibility,
+                        if visibility.is_empty() { "" } else { " " },
+                        if function.is_async { "async " } else { "" },
+                        function.simple_name
+                    )),
+                    Span::raw(if matched > 0 {
+                        format!("  [{}]", matched)
+                    } else {
+                        String::new()
+                    })
+                    .dim(),
+                ]))
+            })
+            .collect();
+        let title = match &self.filter {
+            Some(filter) => format!("Functions matching `{}`", filter),
+            None => "Functions".to_string(),
+        };
+        let list = List::new(items)
+            .block(block(title, Pane::Functions))
+            .highlight_style(highlight);
+        frame.render_stateful_widget(list, functions, &mut self.function);
+
+        let items: Vec<ListItem> = self
+            .function_aspects()
+            .iter()
+            .map(|aspect| {
+                ListItem::new(vec![
+                    Line::from(vec![
+                        Span::raw(aspect.name.as_str()),
+                        Span::raw(format!(" ({})", aspect.origin)).dim(),
+                    ]),
+                    Line::from(format!("  {}", aspect.pointcut)).dim(),
+                ])
+            })
+            .collect();
+        let list = List::new(items)
+            .block(block("Aspects".to_string(), Pane::Aspects))
+            .highlight_style(highlight);
+        frame.render_stateful_widget(list, aspects, &mut self.aspect);
+
+        let lines = match self.selected_function() {
+            Some(function) => vec![
+                Line::from(function.name.as_str()).bold(),
+                Line::from(format!(
+                    "{}:{}",
+                    function.location.file, function.location.line
+                )),
+            ],
+            None => vec![Line::from("No function").dim()],
+        };
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered()), details);
+
+        let status_line = match &self.input {
+            Some(input) => Line::from(format!("/{}", input)),
+            None if self.status.is_empty() => Line::from(
+                "/ filter by pointcut  Tab/arrows move  Enter open source  Esc clear filter  q quit",
+            )
+            .dim(),
+            None => Line::from(format!(
+                "{}  (/ filter, Enter open source, q quit)",
+                self.status
+            )),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+    }
+}
+
+/// Browse the functions of the workspace and the aspects matching them.
+pub fn run(config: Option<&Path>, profile: Option<&str>, extra: &[String]) -> Result<()> {
+    if !std::io::stdout().is_terminal() {
+        anyhow::bail!("cargo aspect browse needs a terminal; use `cargo aspect info` or `match`");
+    }
+    let workspace = Workspace::locate()?;
+    let reports = analyze_workspace(&workspace, &[])?;
+    let config = workspace_config(&workspace, config, profile)?;
+    let registered = load_registry(&workspace)?;
+    let aspects = collect_aspects(&registered, config.as_ref().map(|(_, file)| file), extra);
+
+    let mut browser = Browser::new(&reports, &aspects);
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut browser, &workspace.root);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, browser: &mut Browser, root: &Path) -> Result<()> {
+    loop {
+        terminal.draw(|frame| browser.draw(frame))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match browser.handle_key(key) {
+            Action::None => {}
+            Action::Quit => return Ok(()),
+            Action::Open(file, line) => {
+                ratatui::restore();
+                let opened = open_in_editor(&root.join(&file), line);
+                *terminal = ratatui::init();
+                browser.status = match opened {
+                    Ok(true) => format!("Opened {}:{}", file, line),
+                    Ok(false) => format!("{}:{} (set $VISUAL or $EDITOR to open it)", file, line),
+                    Err(e) => format!("{:#}", e),
+                };
+            }
+        }
+    }
+}
+
+/// Open `file` at `line` in `$VISUAL` or `$EDITOR`, given the line as
+/// `+<line>` like vi, emacs and nano take it. `false` when neither is set.
+fn open_in_editor(file: &Path, line: usize) -> Result<bool> {
+    let Some(editor) = ["VISUAL", "EDITOR"].into_iter().find_map(|var| {
+        std::env::var(var)
+            .ok()
+            .filter(|editor| !editor.trim().is_empty())
+    }) else {
+        return Ok(false);
+    };
+    // The editor may come with arguments (`code --wait`)
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    let status = Command::new(program)
+        .args(words)
+        .arg(format!("+{}", line))
+        .arg(file)
+        .status()
+        .with_context(|| format!("Failed to run {}", editor))?;
+    if !status.success() {
+        anyhow::bail!("{} failed with status {}", editor, status);
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_driver::syntax::analyze_source;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn reports() -> Vec<(String, AnalysisReport)> {
+        let source = "pub fn get() {}\nfn helper() {}\npub mod api { pub async fn list() {} }";
+        let functions = analyze_source(source, "src/lib.rs", "crate").unwrap();
+        vec![(
+            "shop-lib".to_string(),
+            AnalysisReport::new(&[], functions, &[]),
+        )]
+    }
+
+    fn aspects() -> Vec<Aspect> {
+        let config = ConfigFile::parse(
+            "pointcuts = [\"name(helper)\"]\n\n\
+             [[aspects]]\nname = \"trace\"\npointcut = \"execution(pub fn *(..))\"\n\
+             advice = \"before\"\nhook = \"crate::trace\"\n",
+        )
+        .unwrap();
+        collect_aspects(&[], Some(&config), &["within(crate::api)".to_string()])
+    }
+
+    #[test]
+    fn test_browser() {
+        let reports = reports();
+        let aspects = aspects();
+        assert_eq!(aspects.len(), 3);
+        assert_eq!(aspects[0].origin, "aspect.toml, before");
+
+        let mut browser = Browser::new(&reports, &aspects);
+        let paths: Vec<_> = browser.modules.iter().map(|module| module.path).collect();
+        assert_eq!(paths, ["crate", "crate::api"]);
+        assert_eq!(browser.selected_function().unwrap().name, "get");
+        let names = |aspects: Vec<&Aspect>| -> Vec<String> {
+            aspects.iter().map(|aspect| aspect.name.clone()).collect()
+        };
+        assert_eq!(names(browser.function_aspects()), ["trace"]);
+
+        // Down the functions, then down the modules
+        browser.handle_key(key(KeyCode::Tab));
+        browser.handle_key(key(KeyCode::Down));
+        assert_eq!(browser.selected_function().unwrap().name, "helper");
+        assert_eq!(names(browser.function_aspects()), ["name(helper)"]);
+        browser.handle_key(key(KeyCode::Left));
+        browser.handle_key(key(KeyCode::End));
+        assert_eq!(browser.selected_function().unwrap().name, "api::list");
+        assert_eq!(
+            names(browser.function_aspects()),
+            ["trace", "within(crate::api)"]
+        );
+        assert_eq!(
+            browser.handle_key(key(KeyCode::Char('o'))),
+            Action::Open("src/lib.rs".to_string(), 3)
+        );
+
+        // Invalid pointcuts are not applied
+        browser.handle_key(key(KeyCode::Char('/')));
+        for c in "nme(get)".chars() {
+            browser.handle_key(key(KeyCode::Char(c)));
+        }
+        browser.handle_key(key(KeyCode::Enter));
+        assert!(browser.input.is_some());
+        assert_eq!(browser.status, "invalid pointcut: unknown pointcut `nme`");
+
+        browser.input = Some("name(get)".to_string());
+        browser.handle_key(key(KeyCode::Enter));
+        assert_eq!(browser.filter.as_deref(), Some("name(get)"));
+        assert_eq!(browser.modules.len(), 1);
+        assert_eq!(browser.functions().len(), 1);
+        assert_eq!(browser.status, "1 functions match `name(get)`");
+
+        // Esc clears the filter, then quits
+        assert_eq!(browser.handle_key(key(KeyCode::Esc)), Action::None);
+        assert_eq!(browser.modules.len(), 2);
+        assert_eq!(browser.handle_key(key(KeyCode::Esc)), Action::Quit);
+    }
+
+    #[test]
+    fn test_draw() {
+        let reports = reports();
+        let aspects = aspects();
+        let mut browser = Browser::new(&reports, &aspects);
+        let mut terminal = Terminal::new(TestBackend::new(100, 12)).unwrap();
+        terminal.draw(|frame| browser.draw(frame)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let text: String = (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect::<String>()
+                    + "\n"
+            })
+            .collect();
+        assert!(text.contains("crate::api (1)"), "{}", text);
+        assert!(text.contains("pub fn get  [1]"), "{}", text);
+        assert!(text.contains("trace (aspect.toml, before)"), "{}", text);
+        assert!(text.contains("src/lib.rs:1"), "{}", text);
+    }
+}
@@ -0,0 +1,318 @@
+//! `cargo aspect check-pointcuts`: every pointcut of the workspace checked
+//! without building it.
+//!
+//! The pointcuts are read from the `#[advice]` attributes of the sources,
+//! from aspect.toml (all of its profiles) and from `--pointcut`. Each is
+//! parsed, syntax errors shown with carets under the erroneous text, and
+//! linted with `aspect_driver::lint`. The functions they are matched
+//! against come from `aspect_driver::syntax`, so neither cargo nor rustc
+//! runs.
+
+use anyhow::{Context, Result};
+use aspect_driver::config::ConfigFile;
+use aspect_driver::diagnostic::Diagnostic;
+use aspect_driver::lint::{advice_pointcuts, check_syntax, config_pointcuts, lint};
+use aspect_driver::pass::Severity;
+use aspect_driver::syntax::analyze_crate;
+use aspect_driver::types::{FunctionMetadata, SourceLocation};
+use aspect_driver::unmatched::find_unmatched;
+use log::{debug, warn};
+use std::fs;
+use std::path::Path;
+
+use super::{print_json, OutputFormat, Workspace};
+
+/// A pointcut found in the workspace, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoundPointcut {
+    pub pointcut: String,
+
+    /// Start of the pointcut, `None` for `--pointcut` or when it cannot be
+    /// told
+    pub location: Option<SourceLocation>,
+}
+
+/// A diagnostic on a pointcut.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub diagnostic: Diagnostic,
+
+    /// The pointcut with carets under a syntax error
+    pub snippet: Option<String>,
+}
+
+/// Check the pointcuts of the workspace, and fail on errors, or on
+/// warnings with `deny_warnings`.
+pub fn run(
+    config: Option<&Path>,
+    extra: &[String],
+    format: OutputFormat,
+    deny_warnings: bool,
+) -> Result<()> {
+    let workspace = Workspace::locate()?;
+    let found = find_pointcuts(&workspace, config, extra)?;
+
+    // Unmatched pointcuts are only reported when every crate could be
+    // analyzed
+    let mut functions = Vec::new();
+    let mut analyzed = true;
+    for root in &workspace.crate_roots {
+        match analyze_crate(root) {
+            Ok(crate_functions) => functions.extend(crate_functions),
+            Err(e) => {
+                warn!("cannot analyze {}: {}", root.display(), e);
+                analyzed = false;
+            }
+        }
+    }
+    debug!(
+        "Found {} pointcuts; {} functions in {} crates",
+        found.len(),
+        functions.len(),
+        workspace.crate_roots.len()
+    );
+
+    let problems = check(&found, analyzed.then_some(functions.as_slice()));
+    report(&found, &problems, format, deny_warnings)
+}
+
+/// The pointcuts of `#[advice]` in the workspace sources, of aspect.toml
+/// (`config` if given) and of `--pointcut`, in that order. Locations are
+/// relative to the workspace root.
+fn find_pointcuts(
+    workspace: &Workspace,
+    config: Option<&Path>,
+    extra: &[String],
+) -> Result<Vec<FoundPointcut>> {
+    let relative = |path: &Path| {
+        path.strip_prefix(&workspace.root)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+    let mut found = Vec::new();
+
+    let mut sources: Vec<_> = workspace
+        .source_files()
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rs"))
+        .collect();
+    sources.sort();
+    for path in sources {
+        let Ok(source) = fs::read_to_string(&path) else {
+            continue;
+        };
+        if !source.contains("advice") {
+            continue;
+        }
+        // Sources that do not parse are left for the compiler to report
+        match advice_pointcuts(&source, &relative(&path)) {
+            Ok(pointcuts) => {
+                found.extend(
+                    pointcuts
+                        .into_iter()
+                        .map(|(pointcut, location)| FoundPointcut {
+                            pointcut,
+                            location: Some(location),
+                        }),
+                )
+            }
+            Err(e) => warn!("skipping {}", e),
+        }
+    }
+
+    let config = match config {
+        Some(path) => Some(path.to_path_buf()),
+        None => ConfigFile::find(&workspace.root),
+    };
+    if let Some(path) = config {
+        let contents =
+            fs::read_to_string(&path).with_context(|| format!("Cannot read {}", path.display()))?;
+        let pointcuts = config_pointcuts(&contents, &relative(&path))
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("Invalid {}", path.display()))?;
+        found.extend(
+            pointcuts
+                .into_iter()
+                .map(|(pointcut, location)| FoundPointcut { pointcut, location }),
+        );
+    }
+
+    found.extend(extra.iter().map(|pointcut| FoundPointcut {
+        pointcut: pointcut.clone(),
+        location: None,
+    }));
+    Ok(found)
+}
+
+/// The problems of the `found` pointcuts: syntax errors, lint warnings,
+/// and, given the `functions` of the workspace, pointcuts matching none of
+/// them.
+pub fn check(found: &[FoundPointcut], functions: Option<&[FunctionMetadata]>) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut valid = Vec::new();
+    for FoundPointcut { pointcut, location } in found {
+        match check_syntax(pointcut) {
+            Err(error) => problems.push(Problem {
+                diagnostic: Diagnostic::pointcut_syntax(pointcut, &error, location.as_ref()),
+                snippet: Some(error.snippet(pointcut)),
+            }),
+            Ok(expr) => {
+                problems.extend(lint(&expr).iter().map(|warning| Problem {
+                    diagnostic: Diagnostic::pointcut_lint(pointcut, warning, location.as_ref()),
+                    snippet: None,
+                }));
+                if !valid.contains(pointcut) {
+                    valid.push(pointcut.clone());
+                }
+            }
+        }
+    }
+
+    if let Some(functions) = functions {
+        for unmatched in find_unmatched(functions, &valid, &[]) {
+            let locations = found
+                .iter()
+                .filter(|found| found.pointcut == unmatched.pointcut)
+                .map(|found| found.location.clone());
+            problems.extend(locations.map(|location| {
+                let mut diagnostic = Diagnostic::unmatched(&unmatched, false);
+                diagnostic.span = location;
+                Problem {
+                    diagnostic,
+                    snippet: None,
+                }
+            }));
+        }
+    }
+    problems
+}
+
+fn report(
+    found: &[FoundPointcut],
+    problems: &[Problem],
+    format: OutputFormat,
+    deny_warnings: bool,
+) -> Result<()> {
+    let count = |severity| {
+        problems
+            .iter()
+            .filter(|problem| problem.diagnostic.severity == severity)
+            .count()
+    };
+    let errors = count(Severity::Error);
+    let warnings = count(Severity::Warning);
+
+    if format == OutputFormat::Json {
+        let diagnostics: Vec<_> = problems.iter().map(|problem| &problem.diagnostic).collect();
+        print_json(&serde_json::json!({
+            "pointcuts": found.len(),
+            "errors": errors,
+            "warnings": warnings,
+            "diagnostics": diagnostics,
+        }))?;
+    } else {
+        for problem in problems {
+            print_problem(problem);
+            println!();
+        }
+        println!(
+            "Checked {} pointcuts: {} errors, {} warnings",
+            found.len(),
+            errors,
+            warnings
+        );
+    }
+
+    if errors > 0 {
+        anyhow::bail!("{} invalid pointcuts", errors);
+    }
+    if deny_warnings && warnings > 0 {
+        anyhow::bail!("{} pointcut warnings, denied by --deny-warnings", warnings);
+    }
+    Ok(())
+}
+
+/// Print a problem like rustc prints diagnostics.
+fn print_problem(problem: &Problem) {
+    let diagnostic = &problem.diagnostic;
+    println!(
+        "{}[{}]: {}",
+        diagnostic.severity, diagnostic.code, diagnostic.message
+    );
+    if let Some(span) = &diagnostic.span {
+        println!("  --> {}:{}:{}", span.file, span.line, span.column);
+    }
+    if let Some(snippet) = &problem.snippet {
+        println!("   |");
+        for line in snippet.lines() {
+            println!("   | {}", line);
+        }
+    }
+    for help in &diagnostic.help {
+        println!("  help: {}", help);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_driver::syntax::analyze_source;
+
+    fn found(pointcut: &str, line: usize) -> FoundPointcut {
+        FoundPointcut {
+            pointcut: pointcut.to_string(),
+            location: Some(SourceLocation {
+                file: "src/lib.rs".to_string(),
+                line,
+                column: 22,
+                end_line: line,
+                end_column: 22 + pointcut.len(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let functions =
+            analyze_source("pub fn get() {}\nfn helper() {}", "src/lib.rs", "crate").unwrap();
+        let found = [
+            found("execution(pub fn *(..))", 1),
+            found("within(crate::api) && nme(get)", 2),
+            found("name(get) && name(get)", 3),
+            found("name(gett)", 4),
+            found("name(gett)", 5),
+        ];
+
+        let problems = check(&found, Some(&functions));
+        let summary: Vec<_> = problems
+            .iter()
+            .map(|problem| {
+                let span = problem.diagnostic.span.as_ref().unwrap();
+                (problem.diagnostic.code.as_str(), span.line, span.column)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("AR006", 2, 44),
+                ("AR007", 3, 22),
+                ("AR002", 4, 22),
+                ("AR002", 5, 22)
+            ]
+        );
+        assert_eq!(
+            problems[0].snippet.as_deref(),
+            Some(
+                "within(crate::api) && nme(get)\n                      ^^^ unknown pointcut `nme`"
+            )
+        );
+        assert_eq!(
+            problems[2].diagnostic.help,
+            ["`name(gett)` matches nothing; did you mean `name(get)`?"]
+        );
+
+        // Without the functions, only syntax and lints are checked
+        assert_eq!(check(&found, None).len(), 2);
+    }
+}
@@ -0,0 +1,191 @@
+//! `cargo aspect clean-aspects`: remove what cargo-aspect and
+//! aspect-rustc-driver generate, and only that.
+//!
+//! Everything generated by default is under `target/aspect`: the analysis
+//! of `info` and its `cargo check`, the results and analysis cache of the
+//! driver, the `#[advice]` registry, coverage and benchmarks. aspect.toml
+//! may put the cache, the woven source (`[output] emit_source`) and the
+//! report (`[output] file`) elsewhere, in any of its profiles: those are
+//! removed too, unless they hold the workspace or one of its crates. The
+//! crates built with weaving are left to `cargo aspect clean`.
+
+use anyhow::{Context, Result};
+use aspect_driver::config::ConfigFile;
+use log::{debug, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{workspace_config, Workspace};
+
+/// Remove the aspect artifacts of the workspace, or with `dry_run` only
+/// print them.
+pub fn run(config: Option<&Path>, dry_run: bool) -> Result<()> {
+    let workspace = Workspace::locate()?;
+    let file = workspace_config(&workspace, config, None)?.map(|(_, file)| file);
+    let mut files = 0;
+    let mut size = 0;
+    for path in artifacts(&workspace.target_dir, file.as_ref())? {
+        if !path.exists() {
+            continue;
+        }
+        if let Some(reason) = kept(&path, &workspace) {
+            warn!("not removing {}: {}", path.display(), reason);
+            continue;
+        }
+        let (count, bytes) = measure(&path);
+        files += count;
+        size += bytes;
+        if dry_run {
+            info!("Would remove {}", path.display());
+            continue;
+        }
+        debug!("Removing {}", path.display());
+        let removed = if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        removed.with_context(|| format!("Cannot remove {}", path.display()))?;
+    }
+
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    info!("{} {} files, {} total", verb, files, format_size(size));
+    Ok(())
+}
+
+/// The artifacts of the workspace with target directory `target_dir`, and
+/// those aspect.toml (`file`) puts elsewhere in any of its profiles.
+fn artifacts(target_dir: &Path, file: Option<&ConfigFile>) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![target_dir.join("aspect")];
+    let Some(file) = file else {
+        return Ok(paths);
+    };
+    let mut configs = vec![file.clone()];
+    for name in file.profiles.keys() {
+        configs.push(file.profile(name).map_err(anyhow::Error::msg)?);
+    }
+    for config in configs {
+        for path in [
+            config.cache.dir,
+            config.output.emit_source,
+            config.output.file,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if !paths.iter().any(|known| path.starts_with(known)) {
+                paths.push(path);
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// Why `path`, set in aspect.toml, is not removed, if it is not.
+fn kept(path: &Path, workspace: &Workspace) -> Option<&'static str> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if workspace.root.starts_with(&path) {
+        Some("it holds the workspace")
+    } else if workspace.target_dir == path {
+        Some("it is the target directory")
+    } else if workspace
+        .crate_roots
+        .iter()
+        .any(|root| root.starts_with(&path))
+    {
+        Some("it holds the sources of a crate")
+    } else {
+        None
+    }
+}
+
+/// The number of files under `path` and their size in bytes.
+fn measure(path: &Path) -> (usize, u64) {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return (0, 0);
+    };
+    if !metadata.is_dir() {
+        return (1, metadata.len());
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return (0, 0);
+    };
+    entries
+        .flatten()
+        .map(|entry| measure(&entry.path()))
+        .fold((0, 0), |(files, size), (count, bytes)| {
+            (files + count, size + bytes)
+        })
+}
+
+/// A size like cargo writes it: `1.5MiB`.
+fn format_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for larger in ["KiB", "MiB", "GiB"] {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = larger;
+    }
+    match unit {
+        "B" => format!("{}B", bytes),
+        _ => format!("{:.1}{}", size, unit),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_artifacts() {
+        let target = Path::new("/shop/target");
+        assert_eq!(
+            artifacts(target, None).unwrap(),
+            [PathBuf::from("/shop/target/aspect")]
+        );
+
+        let mut file = ConfigFile::parse(
+            r#"
+            [cache]
+            dir = "/shop/target/aspect/cache"
+
+            [output]
+            emit_source = "/shop/woven"
+
+            [profiles.ci.output]
+            file = "/shop/aspects.sarif"
+            emit_source = "/shop/woven"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            artifacts(target, Some(&file)).unwrap(),
+            [
+                PathBuf::from("/shop/target/aspect"),
+                PathBuf::from("/shop/woven"),
+                PathBuf::from("/shop/aspects.sarif"),
+            ]
+        );
+
+        file.profiles.clear();
+        assert_eq!(artifacts(target, Some(&file)).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_measure() {
+        let dir = std::env::temp_dir().join(format!("cargo-aspect-clean-{}", std::process::id()));
+        fs::create_dir_all(dir.join("results")).unwrap();
+        fs::write(dir.join("results").join("shop-lib.json"), "{}").unwrap();
+        fs::write(dir.join("registry.json"), "[1, 2]").unwrap();
+        assert_eq!(measure(&dir), (2, 8));
+        assert_eq!(measure(&dir.join("missing")), (0, 0));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(1536), "1.5KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0MiB");
+    }
+}
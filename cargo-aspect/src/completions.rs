@@ -0,0 +1,169 @@
+//! `cargo aspect completions`: tab completion scripts for bash, zsh and
+//! fish.
+//!
+//! The scripts are generated by clap_complete from the command line of
+//! cargo-aspect, that of `cargo` with `aspect` as its only subcommand.
+//! Bash and zsh complete a command with one function, so the functions of
+//! the scripts are renamed, and `cargo` is completed by one that hands
+//! `cargo aspect` to them and the other cargo commands to the completion
+//! of cargo there was. Completions add up in fish, which needs none.
+//!
+//! The profiles of the workspace aspect.toml, when there is one, are
+//! completed after `--aspect-profile`, as they are when the script is
+//! generated.
+
+use anyhow::{Context, Result};
+use clap::builder::{PossibleValue, PossibleValuesParser};
+use clap::{Command, ValueEnum};
+use clap_complete::Shell;
+
+/// A shell cargo-aspect writes completions for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Hands `cargo aspect` to `_cargo_aspect`, and the rest to the completion
+/// of cargo, which bash-completion may load on demand.
+const BASH_DISPATCH: &str = r#"_cargo_aspect_fallback() {
+    complete -p cargo 2>/dev/null | sed -n 's/.* -F \([^ ]*\) .*/\1/p'
+}
+
+_cargo_aspect_dispatch() {
+    if [[ "${COMP_WORDS[1]}" == aspect ]]; then
+        _cargo_aspect "$@"
+        return
+    fi
+    if [[ -z "$_cargo_aspect_cargo" ]] && declare -F _completion_loader >/dev/null; then
+        _completion_loader cargo
+        _cargo_aspect_cargo=$(_cargo_aspect_fallback)
+        _cargo_aspect_register
+    fi
+    if [[ -n "$_cargo_aspect_cargo" && "$_cargo_aspect_cargo" != _cargo_aspect_dispatch ]]; then
+        "$_cargo_aspect_cargo" "$@"
+    fi
+}
+
+_cargo_aspect_register() {
+    if [[ "${BASH_VERSINFO[0]}" -eq 4 && "${BASH_VERSINFO[1]}" -ge 4 || "${BASH_VERSINFO[0]}" -gt 4 ]]; then
+        complete -F _cargo_aspect_dispatch -o nosort -o bashdefault -o default cargo
+    else
+        complete -F _cargo_aspect_dispatch -o bashdefault -o default cargo
+    fi
+}
+
+if [[ "$(_cargo_aspect_fallback)" != _cargo_aspect_dispatch ]]; then
+    _cargo_aspect_cargo=$(_cargo_aspect_fallback)
+fi
+_cargo_aspect_register
+"#;
+
+/// Hands `cargo aspect` to `_cargo_aspect`, and the rest to the completion
+/// of cargo, `_cargo` if compinit found it.
+const ZSH_DISPATCH: &str = r#"_cargo_aspect_dispatch() {
+    if [[ "${words[2]}" == aspect ]]; then
+        _cargo_aspect "$@"
+    else
+        "$_cargo_aspect_cargo" "$@"
+    fi
+}
+
+if [[ "${_comps[cargo]}" != _cargo_aspect_dispatch ]]; then
+    typeset -g _cargo_aspect_cargo="${_comps[cargo]:-_default}"
+fi
+compdef _cargo_aspect_dispatch cargo
+"#;
+
+/// The completion script of `cargo aspect` for `shell`, `command` being
+/// the command line of cargo-aspect (`Cli::command()`), with `profiles`
+/// completed after `--aspect-profile`.
+pub fn script(shell: CompletionShell, mut command: Command, profiles: &[String]) -> Result<String> {
+    if !profiles.is_empty() {
+        let profiles = PossibleValuesParser::new(profiles.iter().map(PossibleValue::new));
+        command = command.mut_subcommand("aspect", |aspect| {
+            aspect.mut_arg("profile", |arg| arg.value_parser(profiles))
+        });
+    }
+
+    let mut generated = Vec::new();
+    let clap_shell = match shell {
+        CompletionShell::Bash => Shell::Bash,
+        CompletionShell::Zsh => Shell::Zsh,
+        CompletionShell::Fish => Shell::Fish,
+    };
+    clap_complete::generate(clap_shell, &mut command, "cargo", &mut generated);
+    let generated = String::from_utf8(generated).context("Invalid completion script")?;
+
+    // The functions of the script, and the registration of the first one
+    // that the dispatch replaces
+    let (renamed, registration, dispatch) = match shell {
+        CompletionShell::Bash => (
+            generated.replace("_cargo", "_cargo_aspect"),
+            "if [[ \"${BASH_VERSINFO[0]}\"",
+            BASH_DISPATCH,
+        ),
+        CompletionShell::Zsh => (
+            generated
+                .strip_prefix("#compdef cargo\n")
+                .unwrap_or(&generated)
+                .replace("_cargo", "_cargo_aspect"),
+            "if [ \"$funcstack[1]\"",
+            ZSH_DISPATCH,
+        ),
+        CompletionShell::Fish => {
+            return Ok(generated.replace("__fish_cargo", "__fish_cargo_aspect"));
+        }
+    };
+    let functions = renamed
+        .rfind(registration)
+        .map(|end| &renamed[..end])
+        .context("Unexpected completion script from clap_complete")?;
+    Ok(format!("{}{}", functions, dispatch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction};
+
+    fn command() -> Command {
+        Command::new("cargo").subcommand(
+            Command::new("aspect")
+                .arg(
+                    Arg::new("profile")
+                        .long("aspect-profile")
+                        .action(ArgAction::Set),
+                )
+                .subcommand(Command::new("build"))
+                .subcommand(Command::new("match").arg(Arg::new("pointcut"))),
+        )
+    }
+
+    #[test]
+    fn test_script() {
+        let profiles = ["ci".to_string(), "release".to_string()];
+        let bash = script(CompletionShell::Bash, command(), &profiles).unwrap();
+        assert!(bash.starts_with("_cargo_aspect() {"));
+        assert!(bash.contains("cargo__subcmd__aspect,build)"));
+        assert!(bash.contains(
+            "--aspect-profile)\n                    COMPREPLY=($(compgen -W \"ci release\""
+        ));
+        assert!(!bash.contains("complete -F _cargo_aspect "));
+        assert!(bash.ends_with("_cargo_aspect_register\n"));
+
+        let zsh = script(CompletionShell::Zsh, command(), &[]).unwrap();
+        assert!(zsh.starts_with("\nautoload -U is-at-least\n\n_cargo_aspect() {"));
+        assert!(!zsh.contains("#compdef"));
+        assert!(!zsh.contains("funcstack"));
+        assert!(!zsh.contains("(( $+functions[_cargo_commands] ))"));
+        assert!(zsh.ends_with("compdef _cargo_aspect_dispatch cargo\n"));
+
+        let fish = script(CompletionShell::Fish, command(), &profiles).unwrap();
+        assert!(fish.contains(
+            "complete -c cargo -n \"__fish_cargo_aspect_needs_command\" -f -a \"aspect\""
+        ));
+        assert!(fish.contains("-l aspect-profile -r -f -a \"ci\\t''\nrelease\\t''\""));
+    }
+}
@@ -0,0 +1,135 @@
+//! Aspect coverage of `cargo aspect test`.
+//!
+//! The tests run with `ASPECT_COVERAGE_DIR` set, and the aspect-runtime
+//! registry of each test process records there the aspects that ran. The
+//! aspects `#[advice]` registered are then reported with the number of
+//! processes they ran in, so that advice no test exercises stands out like
+//! uncovered code.
+
+use anyhow::{Context, Result};
+use aspect_driver::r#match::RegisteredAspect;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Directory the runtime records aspect executions in (see
+/// `aspect_runtime::COVERAGE_DIR_ENV`).
+pub const COVERAGE_DIR_ENV: &str = "ASPECT_COVERAGE_DIR";
+
+/// The aspects that ran, by name, with the number of processes they ran
+/// in.
+pub fn read_executed(coverage_dir: &Path) -> Result<BTreeMap<String, usize>> {
+    let mut executed = BTreeMap::new();
+    if !coverage_dir.exists() {
+        return Ok(executed);
+    }
+    let entries = fs::read_dir(coverage_dir)
+        .with_context(|| format!("cannot read {}", coverage_dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        let contents =
+            fs::read_to_string(&path).with_context(|| format!("cannot read {}", path.display()))?;
+        for name in contents.lines().filter(|line| !line.is_empty()) {
+            *executed.entry(name.to_string()).or_insert(0) += 1;
+        }
+    }
+    Ok(executed)
+}
+
+/// The registered aspects with the number of processes they ran in, then
+/// the names of the aspects that ran without being registered by
+/// `#[advice]` (registered by hand).
+pub fn coverage<'a>(
+    registered: &'a [RegisteredAspect],
+    executed: &'a BTreeMap<String, usize>,
+) -> (Vec<(&'a RegisteredAspect, usize)>, Vec<&'a str>) {
+    let covered = registered
+        .iter()
+        .map(|aspect| {
+            let runs = executed.get(&aspect.aspect_name).copied().unwrap_or(0);
+            (aspect, runs)
+        })
+        .collect();
+    let others = executed
+        .keys()
+        .filter(|name| !registered.iter().any(|aspect| &aspect.aspect_name == *name))
+        .map(String::as_str)
+        .collect();
+    (covered, others)
+}
+
+/// Print the aspect coverage of the test run.
+pub fn print_report(registered: &[RegisteredAspect], executed: &BTreeMap<String, usize>) {
+    let (covered, others) = coverage(registered, executed);
+    if covered.is_empty() && others.is_empty() {
+        return;
+    }
+
+    println!();
+    println!("=== Aspect Coverage ===");
+    for (aspect, runs) in &covered {
+        let status = match runs {
+            0 => "never ran".to_string(),
+            1 => "ran in 1 test process".to_string(),
+            runs => format!("ran in {} test processes", runs),
+        };
+        println!(
+            "  {} ({}): {}",
+            aspect.aspect_name, aspect.advice_type, status
+        );
+        println!("     {}", aspect.pointcut);
+    }
+    if !others.is_empty() {
+        println!("  also ran: {}", others.join(", "));
+    }
+
+    let never: Vec<&str> = covered
+        .iter()
+        .filter(|(_, runs)| *runs == 0)
+        .map(|(aspect, _)| aspect.aspect_name.as_str())
+        .collect();
+    println!();
+    println!(
+        "{} of {} aspects ran",
+        covered.len() - never.len(),
+        covered.len()
+    );
+    if !never.is_empty() {
+        println!("never ran: {}", never.join(", "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_driver::r#match::AdviceType;
+
+    #[test]
+    fn test_coverage() {
+        let dir =
+            std::env::temp_dir().join(format!("cargo-aspect-coverage-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("100.txt"), "api_logger\naudit_log\n").unwrap();
+        fs::write(dir.join("101.txt"), "api_logger\n").unwrap();
+        let executed = read_executed(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(executed["api_logger"], 2);
+
+        let aspect = |name: &str| RegisteredAspect {
+            aspect_name: name.to_string(),
+            pointcut: "execution(pub fn *(..))".to_string(),
+            advice_type: AdviceType::Around,
+            priority: 0,
+        };
+        let registered = [aspect("api_logger"), aspect("db_timer")];
+        let (covered, others) = coverage(&registered, &executed);
+        let runs: Vec<_> = covered
+            .iter()
+            .map(|(aspect, runs)| (aspect.aspect_name.as_str(), *runs))
+            .collect();
+        assert_eq!(runs, [("api_logger", 2), ("db_timer", 0)]);
+        assert_eq!(others, ["audit_log"]);
+
+        assert!(read_executed(&dir).unwrap().is_empty());
+    }
+}
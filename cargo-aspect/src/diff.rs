@@ -0,0 +1,165 @@
+//! `cargo aspect diff`: what weaving does to the source, as a diff.
+//!
+//! The workspace is analyzed like by `cargo aspect info`, the weaving of
+//! the advice of aspect.toml is planned like by `aspect-rustc-driver
+//! --aspect-plan`, and each source file with woven functions is expanded
+//! like by `--aspect-emit-source`. The unified diff of the original and
+//! woven source is printed for the lines of the woven functions only, so
+//! that reviewers can audit the code weaving adds without reading MIR.
+
+use anyhow::{Context, Result};
+use aspect_driver::expand::{expand_source, function_diff};
+use aspect_driver::plan::{PlannedAdvice, WeavingPlan};
+use aspect_driver::r#match::AdviceHook;
+use aspect_driver::report::AnalysisReport;
+use aspect_driver::types::FunctionMetadata;
+use log::info;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use super::{analyze_workspace, functions_in, workspace_config, PackageSelection, Workspace};
+
+/// Settings of `cargo aspect diff`.
+pub struct Diff<'a> {
+    pub packages: &'a PackageSelection,
+
+    /// Only the functions of this module
+    pub module: Option<&'a str>,
+
+    pub config: Option<&'a Path>,
+    pub profile: Option<&'a str>,
+}
+
+impl Diff<'_> {
+    /// Print the diff of the woven functions of the workspace.
+    pub fn run(&self) -> Result<()> {
+        let workspace = Workspace::locate()?;
+        let mut reports = analyze_workspace(&workspace, &self.packages.cargo_args())?;
+        if let Some(selected) = self.packages.selected(&workspace) {
+            reports.retain(|(name, _)| workspace.is_report_of(name, &selected));
+        }
+        let hooks = match workspace_config(&workspace, self.config, self.profile)? {
+            Some((_, file)) => file.advice_hooks().map_err(anyhow::Error::msg)?,
+            None => Vec::new(),
+        };
+        if hooks.is_empty() {
+            info!(
+                "No advice in aspect.toml: weaving changes no function\n  \
+                 #[advice] runs from the aspect registry and is not woven"
+            );
+            return Ok(());
+        }
+
+        let mut woven = 0;
+        let mut files = 0;
+        for (file, functions) in woven_by_file(&reports, self.module, &hooks) {
+            let path = workspace.root.join(&file);
+            let source = fs::read_to_string(&path)
+                .with_context(|| format!("Cannot read {}", path.display()))?;
+            let functions: Vec<(&FunctionMetadata, &[PlannedAdvice])> = functions
+                .iter()
+                .map(|(function, advice)| (*function, advice.as_slice()))
+                .collect();
+            let expanded = expand_source(&source, &file, &functions).map_err(anyhow::Error::msg)?;
+            for (function, _) in &functions {
+                if !expanded.expanded.contains(&function.name) {
+                    info!(
+                        "note: {} is woven but not in {}, generated by a macro?",
+                        function.name, file
+                    );
+                }
+            }
+
+            let changed: Vec<&FunctionMetadata> = functions
+                .iter()
+                .map(|(function, _)| *function)
+                .filter(|function| expanded.expanded.contains(&function.name))
+                .collect();
+            let diff = function_diff(&source, &expanded.source, &file, &changed);
+            if !diff.is_empty() {
+                print!("{}", diff);
+                woven += changed.len();
+                files += 1;
+            }
+        }
+        info!("{} functions woven in {} files", woven, files);
+        Ok(())
+    }
+}
+
+/// The woven functions of the crates of `reports` (those of `module` if
+/// given) with their advice, by source file. A file of several crates is
+/// only taken from the first.
+fn woven_by_file<'a>(
+    reports: &'a [(String, AnalysisReport)],
+    module: Option<&str>,
+    hooks: &[AdviceHook],
+) -> BTreeMap<String, Vec<(&'a FunctionMetadata, Vec<PlannedAdvice>)>> {
+    let mut files: BTreeMap<String, Vec<(&FunctionMetadata, Vec<PlannedAdvice>)>> = BTreeMap::new();
+    let mut crate_files: BTreeMap<&str, &str> = BTreeMap::new();
+    for (name, report) in reports {
+        let plan = WeavingPlan::new(&report.functions, &[], hooks);
+        let advice = plan.woven_functions();
+        for function in functions_in(report, module) {
+            let file = function.location.file.as_str();
+            if *crate_files.entry(file).or_insert(name) != name.as_str() {
+                continue;
+            }
+            if let Some(advice) = advice.get(function.name.as_str()) {
+                files
+                    .entry(file.to_string())
+                    .or_default()
+                    .push((function, advice.to_vec()));
+            }
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_driver::r#match::AdviceType;
+    use aspect_driver::syntax::analyze_source;
+
+    #[test]
+    fn test_woven_by_file() {
+        let report = |name: &str, file: &str, source: &str| {
+            let functions = analyze_source(source, file, "crate").unwrap();
+            (name.to_string(), AnalysisReport::new(&[], functions, &[]))
+        };
+        let reports = [
+            report(
+                "shop-lib",
+                "src/lib.rs",
+                "pub fn get() {}\nfn helper() {}\npub mod api { pub fn list() {} }",
+            ),
+            report("shop-bin", "src/lib.rs", "pub fn get() {}"),
+            report("admin-lib", "admin/src/lib.rs", "pub async fn get() {}"),
+        ];
+        let hooks = [AdviceHook::parse(
+            AdviceType::Before,
+            "execution(pub fn *(..))=crate::trace::enter",
+        )
+        .unwrap()];
+
+        let files = woven_by_file(&reports, None, &hooks);
+        let names: Vec<(&str, Vec<&str>)> = files
+            .iter()
+            .map(|(file, functions)| {
+                let names = functions
+                    .iter()
+                    .map(|(function, _)| function.name.as_str())
+                    .collect();
+                (file.as_str(), names)
+            })
+            .collect();
+        // Async functions are not woven
+        assert_eq!(names, [("src/lib.rs", vec!["get", "api::list"])]);
+        assert_eq!(files["src/lib.rs"][0].1[0].hook, "crate::trace::enter");
+
+        let files = woven_by_file(&reports, Some("api"), &hooks);
+        assert_eq!(files["src/lib.rs"].len(), 1);
+    }
+}
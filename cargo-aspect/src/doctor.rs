@@ -0,0 +1,417 @@
+//! `cargo aspect doctor`: checks of the environment weaving needs.
+//!
+//! aspect-rustc-driver links to the compiler of the nightly toolchain it
+//! was built with, so it only runs with that toolchain and its `rustc-dev`
+//! and `llvm-tools` components installed. The doctor checks those, the
+//! toolchain the workspace selects, the driver binary, and aspect.toml,
+//! and tells how to fix what is wrong. rustup is only queried, never asked
+//! to install anything.
+
+use anyhow::Result;
+use std::path::Path;
+use std::process::Command;
+
+use super::{find_driver, print_json, workspace_config, OutputFormat, Workspace, DRIVER_ENV};
+
+/// Toolchain aspect-rustc-driver is built with, from its
+/// `rust-toolchain.toml`.
+pub const DRIVER_TOOLCHAIN: &str = "nightly-2025-01-15";
+
+/// Components of [`DRIVER_TOOLCHAIN`] the driver needs.
+pub const DRIVER_COMPONENTS: [&str; 2] = ["rustc-dev", "llvm-tools"];
+
+/// Outcome of a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    /// Weaving may work, or not as expected
+    Warning,
+    /// Weaving cannot work
+    Error,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Warning => "warning",
+            Status::Error => "error",
+        }
+    }
+}
+
+/// One check of the doctor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    /// What is checked
+    pub name: String,
+
+    pub status: Status,
+
+    /// What was found
+    pub message: String,
+
+    /// How to fix it, for warnings and errors
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &str, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: Status::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn problem(status: Status, name: &str, message: impl Into<String>, fix: String) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            message: message.into(),
+            fix: Some(fix),
+        }
+    }
+}
+
+/// Run every check, in order. Checks that depend on a failed one are
+/// skipped.
+pub fn run_checks(config: Option<&Path>, profile: Option<&str>) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let rustup = output("rustup", &["--version"]);
+    match &rustup {
+        Some(version) => checks.push(Check::ok("rustup", first_line(version))),
+        None => checks.push(Check::problem(
+            Status::Error,
+            "rustup",
+            "not found",
+            "install rustup from https://rustup.rs".to_string(),
+        )),
+    }
+
+    let toolchain_installed = rustup.is_some() && {
+        let installed = output("rustup", &["toolchain", "list"]).unwrap_or_default();
+        let found = installed
+            .lines()
+            .any(|line| line.starts_with(&format!("{}-", DRIVER_TOOLCHAIN)));
+        checks.push(if found {
+            Check::ok("toolchain", format!("{} installed", DRIVER_TOOLCHAIN))
+        } else {
+            Check::problem(
+                Status::Error,
+                "toolchain",
+                format!("{} is not installed", DRIVER_TOOLCHAIN),
+                format!(
+                    "rustup toolchain install {} --component {}",
+                    DRIVER_TOOLCHAIN,
+                    DRIVER_COMPONENTS.join(" --component ")
+                ),
+            )
+        });
+        found
+    };
+
+    if toolchain_installed {
+        let installed = output(
+            "rustup",
+            &[
+                "component",
+                "list",
+                "--installed",
+                "--toolchain",
+                DRIVER_TOOLCHAIN,
+            ],
+        )
+        .unwrap_or_default();
+        let missing = missing_components(&installed);
+        checks.push(if missing.is_empty() {
+            Check::ok("components", DRIVER_COMPONENTS.join(", "))
+        } else {
+            Check::problem(
+                Status::Error,
+                "components",
+                format!("{} missing", missing.join(", ")),
+                format!(
+                    "rustup component add {} --toolchain {}",
+                    missing.join(" "),
+                    DRIVER_TOOLCHAIN
+                ),
+            )
+        });
+    }
+
+    let workspace = Workspace::locate();
+    if let Ok(workspace) = &workspace {
+        checks.push(check_workspace_toolchain(&workspace.root));
+    }
+
+    checks.push(match find_driver() {
+        None => Check::problem(
+            Status::Error,
+            "driver",
+            "aspect-rustc-driver not found",
+            format!(
+                "cargo +{} install --path aspect-rustc-driver, or set {} to its path",
+                DRIVER_TOOLCHAIN, DRIVER_ENV
+            ),
+        ),
+        Some(driver) if !toolchain_installed => Check::ok(
+            "driver",
+            format!("{} (not run without its toolchain)", driver.display()),
+        ),
+        Some(driver) => {
+            // The driver needs the libraries of its toolchain, which
+            // `rustup run` provides
+            let driver = driver.display().to_string();
+            match output("rustup", &["run", DRIVER_TOOLCHAIN, &driver, "-vV"]) {
+                Some(version) => {
+                    Check::ok("driver", format!("{} ({})", driver, first_line(&version)))
+                }
+                None => Check::problem(
+                    Status::Error,
+                    "driver",
+                    format!("{} does not run with {}", driver, DRIVER_TOOLCHAIN),
+                    format!(
+                        "rebuild it: cargo +{} install --path aspect-rustc-driver --force",
+                        DRIVER_TOOLCHAIN
+                    ),
+                ),
+            }
+        }
+    });
+
+    checks.push(match &workspace {
+        Err(e) => Check::problem(
+            Status::Error,
+            "workspace",
+            format!("{:#}", e),
+            "run cargo aspect doctor in a cargo workspace".to_string(),
+        ),
+        Ok(workspace) => match workspace_config(workspace, config, profile) {
+            Ok(None) => Check::ok("aspect.toml", "none (optional)"),
+            Ok(Some((path, file))) => match file.advice_hooks() {
+                Ok(hooks) => Check::ok(
+                    "aspect.toml",
+                    format!(
+                        "{}: {} pointcuts, {} aspects",
+                        path.display(),
+                        file.pointcuts.len(),
+                        hooks.len()
+                    ),
+                ),
+                Err(e) => Check::problem(
+                    Status::Error,
+                    "aspect.toml",
+                    format!("{}: {}", path.display(), e),
+                    "fix the [[aspects]] entry".to_string(),
+                ),
+            },
+            Err(e) => Check::problem(
+                Status::Error,
+                "aspect.toml",
+                format!("{:#}", e),
+                "fix the file, or pass another one with --config".to_string(),
+            ),
+        },
+    });
+
+    checks
+}
+
+/// Check the toolchain selected in the workspace is the driver's: cargo
+/// runs the driver as a rustc wrapper, with the rustc of that toolchain.
+fn check_workspace_toolchain(root: &Path) -> Check {
+    let pinned = ["rust-toolchain.toml", "rust-toolchain"]
+        .iter()
+        .map(|name| root.join(name))
+        .find(|path| path.is_file())
+        .and_then(|path| {
+            let channel = toolchain_channel(&std::fs::read_to_string(&path).ok()?)?;
+            Some((path, channel))
+        });
+    let fix = format!(
+        "run cargo +{} aspect, or set channel = \"{}\" in rust-toolchain.toml",
+        DRIVER_TOOLCHAIN, DRIVER_TOOLCHAIN
+    );
+
+    match pinned {
+        Some((_, channel)) if channel == DRIVER_TOOLCHAIN => {
+            Check::ok("rust-toolchain.toml", format!("pins {}", channel))
+        }
+        Some((path, channel)) => Check::problem(
+            Status::Warning,
+            "rust-toolchain.toml",
+            format!(
+                "{} pins {}, but the driver needs {}",
+                path.display(),
+                channel,
+                DRIVER_TOOLCHAIN
+            ),
+            fix,
+        ),
+        None => {
+            // Run by cargo, cargo-aspect sees the toolchain of the cargo
+            // command in RUSTUP_TOOLCHAIN
+            let active = std::env::var("RUSTUP_TOOLCHAIN")
+                .ok()
+                .or_else(|| output("rustup", &["show", "active-toolchain"]))
+                .unwrap_or_default();
+            if active.starts_with(DRIVER_TOOLCHAIN) {
+                Check::ok(
+                    "rust-toolchain.toml",
+                    format!("none, {} active", DRIVER_TOOLCHAIN),
+                )
+            } else {
+                Check::problem(
+                    Status::Warning,
+                    "rust-toolchain.toml",
+                    format!(
+                        "none, and {} is active",
+                        first_line(&active)
+                            .split(' ')
+                            .next()
+                            .unwrap_or("no toolchain")
+                    ),
+                    fix,
+                )
+            }
+        }
+    }
+}
+
+/// The channel of a `rust-toolchain.toml`, or of a legacy
+/// `rust-toolchain` file holding just the channel.
+fn toolchain_channel(contents: &str) -> Option<String> {
+    if !contents.contains('[') {
+        return Some(contents.trim().to_string()).filter(|channel| !channel.is_empty());
+    }
+    contents.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "channel").then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Components of [`DRIVER_COMPONENTS`] missing from the output of
+/// `rustup component list --installed` (`rustc-dev-x86_64-unknown-...`).
+fn missing_components(installed: &str) -> Vec<&'static str> {
+    DRIVER_COMPONENTS
+        .into_iter()
+        .filter(|component| {
+            let prefix = format!("{}-", component);
+            !installed.lines().any(|line| line.starts_with(&prefix))
+        })
+        .collect()
+}
+
+/// Standard output of a successful command, `None` if it fails or does
+/// not exist. Toolchains are never installed on demand.
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .env("RUSTUP_AUTO_INSTALL", "0")
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or_default()
+}
+
+/// Print the checks; fails if one of them does.
+pub fn report(checks: &[Check], format: OutputFormat) -> Result<()> {
+    let errors = checks
+        .iter()
+        .filter(|check| check.status == Status::Error)
+        .count();
+    let warnings = checks
+        .iter()
+        .filter(|check| check.status == Status::Warning)
+        .count();
+
+    if format == OutputFormat::Json {
+        let checks: Vec<_> = checks
+            .iter()
+            .map(|check| {
+                serde_json::json!({
+                    "name": check.name,
+                    "status": check.status.as_str(),
+                    "message": check.message,
+                    "fix": check.fix,
+                })
+            })
+            .collect();
+        print_json(
+            &serde_json::json!({ "checks": checks, "errors": errors, "warnings": warnings }),
+        )?;
+    } else {
+        println!("=== Aspect Doctor ===");
+        println!();
+        for check in checks {
+            println!(
+                "{:<8} {}: {}",
+                check.status.as_str(),
+                check.name,
+                check.message
+            );
+            if let Some(fix) = &check.fix {
+                println!("         fix: {}", fix);
+            }
+        }
+        println!();
+        if errors == 0 && warnings == 0 {
+            println!("Everything is ready for weaving");
+        } else {
+            println!("{} errors, {} warnings", errors, warnings);
+        }
+    }
+
+    if errors > 0 {
+        anyhow::bail!("the environment cannot weave aspects ({} errors)", errors);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_driver_toolchain() {
+        // Must stay in sync with the toolchain of the driver
+        let toolchain = include_str!("../../aspect-rustc-driver/rust-toolchain.toml");
+        assert_eq!(
+            toolchain_channel(toolchain).as_deref(),
+            Some(DRIVER_TOOLCHAIN)
+        );
+        for component in DRIVER_COMPONENTS {
+            assert!(toolchain.contains(&format!("\"{}\"", component)));
+        }
+
+        assert_eq!(
+            toolchain_channel("nightly-2025-01-15\n").as_deref(),
+            Some("nightly-2025-01-15")
+        );
+        assert_eq!(
+            toolchain_channel("[toolchain]\nprofile = \"minimal\""),
+            None
+        );
+    }
+
+    #[test]
+    fn test_missing_components() {
+        let installed = "cargo-x86_64-unknown-linux-gnu\n\
+                         llvm-tools-x86_64-unknown-linux-gnu\n\
+                         rustc-x86_64-unknown-linux-gnu";
+        assert_eq!(missing_components(installed), ["rustc-dev"]);
+        assert_eq!(
+            missing_components("rustc-dev-x86_64-unknown-linux-gnu\nllvm-tools-preview"),
+            Vec::<&str>::new()
+        );
+    }
+}
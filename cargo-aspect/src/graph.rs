@@ -0,0 +1,282 @@
+//! `cargo aspect graph`: which functions each aspect applies to, as a
+//! graph.
+//!
+//! The workspace is analyzed like by `cargo aspect info`, and the weaving
+//! plan of each crate is computed like by `aspect-rustc-driver
+//! --aspect-plan`, for the aspects of aspect.toml, the pointcuts of
+//! `#[advice]`, aspect.toml and `--pointcut`. The plans are merged into
+//! one, functions named after their crate, and exported as Graphviz DOT or
+//! Mermaid by `aspect_driver::plan`. With `--svg`, Graphviz renders it.
+
+use anyhow::{Context, Result};
+use aspect_driver::plan::{PlannedAdvice, WeavingPlan};
+use aspect_driver::r#match::{AdviceHook, RegisteredAspect};
+use aspect_driver::report::AnalysisReport;
+use aspect_driver::types::FunctionMetadata;
+use log::debug;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use super::{
+    analyze_workspace, collect_pointcuts, functions_in, load_registry, workspace_config,
+    PackageSelection, Workspace,
+};
+
+/// What `cargo aspect graph` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+
+    /// DOT rendered by Graphviz
+    Svg,
+}
+
+/// Settings of `cargo aspect graph`.
+pub struct Graph<'a> {
+    pub packages: &'a PackageSelection,
+
+    /// Only the functions of this module
+    pub module: Option<&'a str>,
+
+    pub format: GraphFormat,
+
+    /// File to write the graph to, standard output if not given
+    pub output: Option<&'a Path>,
+
+    pub config: Option<&'a Path>,
+    pub profile: Option<&'a str>,
+    pub pointcuts: &'a [String],
+}
+
+impl Graph<'_> {
+    /// Analyze the workspace and write the graph of its aspects.
+    pub fn run(&self) -> Result<()> {
+        let workspace = Workspace::locate()?;
+        let mut reports = analyze_workspace(&workspace, &self.packages.cargo_args())?;
+        if let Some(selected) = self.packages.selected(&workspace) {
+            reports.retain(|(name, _)| workspace.is_report_of(name, &selected));
+        }
+        let config = workspace_config(&workspace, self.config, self.profile)?;
+        let config = config.as_ref().map(|(_, file)| file);
+        let registered = load_registry(&workspace)?;
+
+        let hooks = match config {
+            Some(file) => file.advice_hooks().map_err(anyhow::Error::msg)?,
+            None => Vec::new(),
+        };
+        let pointcuts: Vec<String> = collect_pointcuts(&registered, config, self.pointcuts)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        let plan = workspace_plan(&reports, self.module, &pointcuts, &hooks, &registered);
+        debug!(
+            "{} pointcuts, {} functions woven",
+            plan.pointcuts.len(),
+            plan.woven_functions().len()
+        );
+
+        let graph = match self.format {
+            GraphFormat::Dot => plan.to_dot().into_bytes(),
+            GraphFormat::Mermaid => plan.to_mermaid().into_bytes(),
+            GraphFormat::Svg => render_svg(&plan.to_dot())?,
+        };
+        match self.output {
+            Some(path) => std::fs::write(path, graph)
+                .with_context(|| format!("Cannot write {}", path.display())),
+            None => std::io::stdout()
+                .write_all(&graph)
+                .context("Cannot write the graph"),
+        }
+    }
+}
+
+/// The weaving plans of the crates of `reports` merged into one, the
+/// functions and modules of each crate named after it (`shop::api::get`).
+///
+/// The advice of `#[advice]` is registered at run time rather than woven:
+/// it is added to its pointcut under the name of the aspect.
+pub fn workspace_plan(
+    reports: &[(String, AnalysisReport)],
+    module: Option<&str>,
+    pointcuts: &[String],
+    hooks: &[AdviceHook],
+    registered: &[RegisteredAspect],
+) -> WeavingPlan {
+    let mut merged: Option<WeavingPlan> = None;
+    for (name, report) in reports {
+        let prefix = crate_prefix(reports, name);
+        let functions: Vec<FunctionMetadata> =
+            functions_in(report, module).into_iter().cloned().collect();
+        let mut plan = WeavingPlan::new(&functions, pointcuts, hooks);
+        for pointcut in &mut plan.pointcuts {
+            for function in pointcut.matched.iter_mut() {
+                function.function = format!("{}::{}", prefix, function.function);
+                function.module_path = prefixed_module(prefix, &function.module_path);
+            }
+            for exclusion in pointcut.excluded.iter_mut() {
+                exclusion.function = format!("{}::{}", prefix, exclusion.function);
+            }
+        }
+
+        match &mut merged {
+            // Every crate has the same pointcuts, in the same order
+            Some(merged) => {
+                for (into, from) in merged.pointcuts.iter_mut().zip(plan.pointcuts) {
+                    into.matched.extend(from.matched);
+                    into.excluded.extend(from.excluded);
+                }
+            }
+            None => merged = Some(plan),
+        }
+    }
+
+    let mut plan = merged.unwrap_or_else(|| WeavingPlan::new(&[], pointcuts, hooks));
+    for pointcut in &mut plan.pointcuts {
+        pointcut.advice.extend(
+            registered
+                .iter()
+                .filter(|aspect| aspect.pointcut == pointcut.pointcut)
+                .map(|aspect| PlannedAdvice {
+                    advice_type: aspect.advice_type,
+                    hook: aspect.aspect_name.clone(),
+                }),
+        );
+    }
+    plan
+}
+
+/// The name the functions of the crate of the report `name` are given in
+/// a workspace plan: the crate name, or the report name when a library
+/// and a binary of one package share the crate name.
+pub fn crate_prefix<'a>(reports: &[(String, AnalysisReport)], name: &'a str) -> &'a str {
+    let crate_name = name.rsplit_once('-').map_or(name, |(name, _)| name);
+    let reports_of_crate = reports
+        .iter()
+        .filter(|(other, _)| other.starts_with(&format!("{}-", crate_name)))
+        .count();
+    if reports_of_crate > 1 {
+        name
+    } else {
+        crate_name
+    }
+}
+
+/// `module_path` (`crate::api`) with the crate named `prefix`.
+pub fn prefixed_module(prefix: &str, module_path: &str) -> String {
+    match module_path.strip_prefix("crate") {
+        Some(rest) => format!("{}{}", prefix, rest),
+        None => format!("{}::{}", prefix, module_path),
+    }
+}
+
+/// Render a DOT graph as SVG with Graphviz.
+fn render_svg(dot: &str) -> Result<Vec<u8>> {
+    let mut child = match Command::new("dot")
+        .arg("-Tsvg")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => anyhow::bail!(
+            "--svg needs Graphviz, and `dot` is not on the PATH; install graphviz, or \
+             write DOT without --svg and render it elsewhere"
+        ),
+        Err(e) => return Err(e).context("Failed to run dot"),
+    };
+    child
+        .stdin
+        .take()
+        .context("No stdin for dot")?
+        .write_all(dot.as_bytes())
+        .context("Failed to write to dot")?;
+    let output = child.wait_with_output().context("Failed to run dot")?;
+    if !output.status.success() {
+        anyhow::bail!("dot failed with status {}", output.status);
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_driver::r#match::AdviceType;
+    use aspect_driver::syntax::analyze_source;
+
+    fn report(name: &str, source: &str) -> (String, AnalysisReport) {
+        let functions = analyze_source(source, "src/lib.rs", "crate").unwrap();
+        (name.to_string(), AnalysisReport::new(&[], functions, &[]))
+    }
+
+    #[test]
+    fn test_workspace_plan() {
+        let reports = [
+            report(
+                "shop-lib",
+                "pub fn get() {}\npub mod api { pub fn list() {} }",
+            ),
+            report("shop-bin", "fn main() {}"),
+            report("admin-lib", "pub fn get() {}\nfn helper() {}"),
+        ];
+        let hooks = [AdviceHook::parse(
+            AdviceType::Before,
+            "execution(pub fn *(..))=crate::trace::enter",
+        )
+        .unwrap()];
+        let registered = [RegisteredAspect {
+            aspect_name: "api_logger".to_string(),
+            pointcut: "name(helper)".to_string(),
+            advice_type: AdviceType::Around,
+            priority: 0,
+        }];
+        let pointcuts = ["name(helper)".to_string()];
+
+        let plan = workspace_plan(&reports, None, &pointcuts, &hooks, &registered);
+        let matched = |i: usize| -> Vec<(&str, &str)> {
+            plan.pointcuts[i]
+                .matched
+                .iter()
+                .map(|function| (function.function.as_str(), function.module_path.as_str()))
+                .collect()
+        };
+        assert_eq!(plan.pointcuts[0].pointcut, "name(helper)");
+        assert_eq!(
+            plan.pointcuts[0].advice,
+            [PlannedAdvice {
+                advice_type: AdviceType::Around,
+                hook: "api_logger".to_string(),
+            }]
+        );
+        assert_eq!(matched(0), [("admin::helper", "admin")]);
+        assert_eq!(plan.pointcuts[0].excluded[0].function, "shop-lib::get");
+        assert_eq!(
+            matched(1),
+            [
+                ("shop-lib::get", "shop-lib"),
+                ("shop-lib::api::list", "shop-lib::api"),
+                ("admin::get", "admin"),
+            ]
+        );
+        assert!(plan
+            .to_mermaid()
+            .contains("subgraph module0 [\"admin\"]\n        fn0[\"get\"]\n"));
+
+        let plan = workspace_plan(&reports, Some("api"), &pointcuts, &hooks, &[]);
+        assert_eq!(matched_names(&plan), ["shop-lib::api::list"]);
+        assert!(
+            workspace_plan(&[], None, &pointcuts, &hooks, &[]).pointcuts[1]
+                .matched
+                .is_empty()
+        );
+    }
+
+    fn matched_names(plan: &WeavingPlan) -> Vec<&str> {
+        plan.pointcuts
+            .iter()
+            .flat_map(|pointcut| &pointcut.matched)
+            .map(|function| function.function.as_str())
+            .collect()
+    }
+}
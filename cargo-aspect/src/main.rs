@@ -7,9 +7,47 @@
 //!   cargo aspect test
 //!   cargo aspect check
 
+mod bench;
+mod browse;
+mod check_pointcuts;
+mod clean;
+mod completions;
+mod coverage;
+mod diff;
+mod doctor;
+mod graph;
+mod query;
+mod report;
+mod reporter;
+mod watch;
+mod wrapper;
+
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use aspect_driver::cargo::{
+    encode_args, DENY_UNMATCHED_ENV, DENY_UNWOVEN_ENV, ENCODED_ARGS_ENV, RESULTS_DIR_ENV,
+};
+use aspect_driver::config::{AspectEntry, ConfigFile};
+use aspect_driver::provider::{self, ProvidedAspect, PROVIDERS_FILE_ENV};
+use aspect_driver::r#match::{
+    load_registry_manifest, PointcutMatcher, RegisteredAspect, REGISTRY_DIR_ENV,
+};
+use aspect_driver::report::AnalysisReport;
+use aspect_driver::types::{FunctionMetadata, Visibility};
+use clap::{ArgAction, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use completions::CompletionShell;
+use coverage::COVERAGE_DIR_ENV;
+use graph::GraphFormat;
+use log::{debug, info, warn};
+use reporter::ColorChoice;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::process::{Command, ExitCode};
+use std::time::{Duration, SystemTime};
+use watch::Watch;
+use wrapper::ANALYSIS_DIR_ENV;
+
+/// Path of aspect-rustc-driver, overriding where it is looked up.
+const DRIVER_ENV: &str = "ASPECT_RUSTC_DRIVER";
 
 /// cargo-aspect: Advanced build tool for aspect-oriented Rust
 #[derive(Parser, Debug)]
@@ -34,15 +72,166 @@ struct AspectArgs {
     #[command(subcommand)]
     command: Option<AspectCommand>,
 
-    /// Enable verbose output
-    #[arg(short, long)]
-    verbose: bool,
+    /// Print more details; -vv for everything
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Print warnings and errors only
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Color diagnostics: auto, always or never
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Fail when a pointcut matches no function, instead of warning
+    #[arg(long = "deny-unmatched-pointcuts", visible_alias = "deny-unmatched")]
+    deny_unmatched: bool,
+
+    /// Fail when a function matched by the pointcut of an aspect cannot be
+    /// woven, such as an async function
+    #[arg(long)]
+    deny_unwoven: bool,
+
+    /// Report the functions matched by a pointcut (repeatable)
+    #[arg(long, value_name = "POINTCUT")]
+    pointcut: Vec<String>,
+
+    /// Call a hook before the matched functions: `<pointcut>=<hook path>`
+    #[arg(long, value_name = "POINTCUT=HOOK")]
+    before: Vec<String>,
+
+    /// Call a hook after the matched functions: `<pointcut>=<hook path>`
+    #[arg(long, value_name = "POINTCUT=HOOK")]
+    after: Vec<String>,
+
+    /// aspect.toml to use instead of the one at the workspace root
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Profile of aspect.toml to apply; by default, the one named after the
+    /// cargo profile of the build (`dev`, `release`), if there is one
+    #[arg(
+        long = "aspect-profile",
+        visible_alias = "profile",
+        value_name = "NAME"
+    )]
+    profile: Option<String>,
+
+    /// Output format of info, list, match, doctor and check-pointcuts
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+    format: OutputFormat,
+}
+
+/// How cargo-aspect writes what it reports.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Text for people
+    #[default]
+    Human,
+    /// One JSON document on standard output, for CI and editors
+    Json,
+}
+
+impl AspectArgs {
+    /// The `--aspect-*` flags of the compiler driver.
+    fn driver_flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        for (flag, values) in [
+            ("--aspect-pointcut", &self.pointcut),
+            ("--aspect-before", &self.before),
+            ("--aspect-after", &self.after),
+        ] {
+            for value in values {
+                flags.push(flag.to_string());
+                flags.push(value.clone());
+            }
+        }
+        if self.verbose > 0 {
+            flags.push("--aspect-verbose".to_string());
+        }
+        flags
+    }
+}
+
+/// The packages a command operates on, like cargo's package selection.
+#[derive(Args, Debug, Default, Clone, PartialEq, Eq)]
+struct PackageSelection {
+    /// Package to operate on (repeatable)
+    #[arg(short, long = "package", value_name = "SPEC")]
+    packages: Vec<String>,
+
+    /// Operate on all the packages of the workspace
+    #[arg(long)]
+    workspace: bool,
+
+    /// Package to leave out of --workspace (repeatable)
+    #[arg(long, value_name = "SPEC", requires = "workspace")]
+    exclude: Vec<String>,
+
+    /// Features to activate, separated by spaces or commas (repeatable)
+    #[arg(short = 'F', long)]
+    features: Vec<String>,
+}
+
+impl PackageSelection {
+    /// The selection as cargo arguments.
+    fn cargo_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for package in &self.packages {
+            args.push("--package".to_string());
+            args.push(package.clone());
+        }
+        if self.workspace {
+            args.push("--workspace".to_string());
+        }
+        for (flag, values) in [("--exclude", &self.exclude), ("--features", &self.features)] {
+            for value in values {
+                args.push(flag.to_string());
+                args.push(value.clone());
+            }
+        }
+        args
+    }
+
+    /// The packages of `workspace` selected, `None` when no package is
+    /// selected explicitly and cargo's default applies. Package specs are
+    /// compared by name, without their version (`shop@1.0.0`).
+    fn selected<'a>(&self, workspace: &'a Workspace) -> Option<Vec<&'a str>> {
+        let name = |spec: &str| spec.split('@').next().unwrap_or(spec).to_string();
+        let excluded: Vec<String> = self.exclude.iter().map(|spec| name(spec)).collect();
+        let packages: Vec<String> = self.packages.iter().map(|spec| name(spec)).collect();
+        if self.workspace {
+            Some(
+                workspace
+                    .packages
+                    .keys()
+                    .map(String::as_str)
+                    .filter(|package| !excluded.iter().any(|excluded| excluded == package))
+                    .collect(),
+            )
+        } else if !packages.is_empty() {
+            Some(
+                workspace
+                    .packages
+                    .keys()
+                    .map(String::as_str)
+                    .filter(|package| packages.iter().any(|selected| selected == package))
+                    .collect(),
+            )
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
 enum AspectCommand {
     /// Build the current package with aspect weaving
     Build {
+        #[command(flatten)]
+        packages: PackageSelection,
+
         /// Pass remaining args to cargo build
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
@@ -50,6 +239,9 @@ enum AspectCommand {
 
     /// Check the current package with aspect analysis
     Check {
+        #[command(flatten)]
+        packages: PackageSelection,
+
         /// Pass remaining args to cargo check
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
@@ -57,6 +249,9 @@ enum AspectCommand {
 
     /// Test the current package with aspects enabled
     Test {
+        #[command(flatten)]
+        packages: PackageSelection,
+
         /// Pass remaining args to cargo test
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
@@ -64,6 +259,11 @@ enum AspectCommand {
 
     /// Run benches with aspect weaving
     Bench {
+        /// Run the benchmarks with and without aspects, and print the
+        /// overhead of aspects on each (Criterion benchmarks)
+        #[arg(long)]
+        compare: bool,
+
         /// Pass remaining args to cargo bench
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
@@ -76,8 +276,19 @@ enum AspectCommand {
         args: Vec<String>,
     },
 
+    /// Remove what cargo-aspect and aspect-rustc-driver generated: the
+    /// analysis, caches, results, woven source and reports
+    CleanAspects {
+        /// Only print what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+
     /// Show aspect analysis and weaving info
     Info {
+        #[command(flatten)]
+        packages: PackageSelection,
+
         /// Show detailed function-level information
         #[arg(short, long)]
         detailed: bool,
@@ -87,6 +298,112 @@ enum AspectCommand {
         module: Option<String>,
     },
 
+    /// List the functions of the workspace a pointcut matches
+    Match {
+        /// Pointcut expression, e.g. "execution(pub fn *(..)) && within(crate::api)"
+        pointcut: String,
+
+        /// Only match functions of this module
+        #[arg(short, long)]
+        module: Option<String>,
+
+        /// Also show why the other functions do not match
+        #[arg(long)]
+        explain: bool,
+    },
+
+    /// Browse the modules, functions and the aspects matching them in a
+    /// terminal UI
+    Browse,
+
+    /// Write the functions each aspect applies to as a graph: Graphviz DOT
+    /// by default
+    Graph {
+        #[command(flatten)]
+        packages: PackageSelection,
+
+        /// Only the functions of this module
+        #[arg(short, long)]
+        module: Option<String>,
+
+        /// Write a Mermaid flowchart instead
+        #[arg(long)]
+        mermaid: bool,
+
+        /// Render the graph as SVG with Graphviz (`dot`)
+        #[arg(long, conflicts_with = "mermaid")]
+        svg: bool,
+
+        /// File to write the graph to, instead of standard output
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+
+    /// Show what weaving adds to the source, as a unified diff of the woven
+    /// functions
+    Diff {
+        #[command(flatten)]
+        packages: PackageSelection,
+
+        /// Only the functions of this module
+        #[arg(short, long)]
+        module: Option<String>,
+    },
+
+    /// Report the functions of each module, the aspects applying to them,
+    /// pointcut coverage and the aspects tests ran
+    Report {
+        #[command(flatten)]
+        packages: PackageSelection,
+
+        /// Only the functions of this module
+        #[arg(short, long)]
+        module: Option<String>,
+
+        /// Write a static HTML page, to publish from CI
+        #[arg(long)]
+        html: bool,
+
+        /// File to write the HTML page to, instead of
+        /// target/aspect/report/index.html
+        #[arg(short, long, value_name = "PATH", requires = "html")]
+        output: Option<PathBuf>,
+    },
+
+    /// Analyze the workspace again on every change, printing the
+    /// functions pointcuts newly match or no longer match
+    Watch {
+        /// Also run `cargo aspect check` after each analysis
+        #[arg(long)]
+        check: bool,
+
+        /// Also run `cargo aspect test` after each analysis
+        #[arg(long)]
+        test: bool,
+
+        /// How often to look for changes, in milliseconds
+        #[arg(long, value_name = "MS", default_value_t = 500)]
+        interval: u64,
+    },
+
+    /// Check the toolchain, the compiler driver and aspect.toml
+    Doctor,
+
+    /// Write the tab completion script of `cargo aspect` for a shell
+    Completions {
+        #[arg(value_enum)]
+        shell: CompletionShell,
+    },
+
+    /// Check the pointcuts of `#[advice]`, aspect.toml and `--pointcut`
+    /// without building: syntax errors, likely mistakes and pointcuts
+    /// matching no function
+    CheckPointcuts {
+        /// Fail on warnings too
+        #[arg(long)]
+        deny_warnings: bool,
+    },
+
     /// List all registered aspects and pointcuts
     List {
         /// Show only aspects
@@ -100,24 +417,52 @@ enum AspectCommand {
 }
 
 fn main() -> ExitCode {
+    // Run by cargo as the rustc wrapper of `cargo aspect info`
+    if let Some(analysis_dir) = std::env::var_os(ANALYSIS_DIR_ENV) {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        if args.first().is_some_and(|arg| arg != "aspect") {
+            reporter::init(false, 0, ColorChoice::Auto);
+            return wrapper::run(&PathBuf::from(analysis_dir), &args);
+        }
+    }
+
     let cli = Cli::parse();
 
     match cli.command {
-        CargoCommands::Aspect(args) => match run_aspect_command(args) {
-            Ok(()) => ExitCode::SUCCESS,
-            Err(e) => {
-                eprintln!("Error: {:#}", e);
-                ExitCode::FAILURE
+        CargoCommands::Aspect(args) => {
+            reporter::init(args.quiet, args.verbose, args.color);
+            match run_aspect_command(args) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    log::error!("{:#}", e);
+                    ExitCode::FAILURE
+                }
             }
-        },
+        }
     }
 }
 
 fn run_aspect_command(args: AspectArgs) -> Result<()> {
-    if args.verbose {
-        println!("cargo-aspect v{}", env!("CARGO_PKG_VERSION"));
+    debug!("cargo-aspect v{}", env!("CARGO_PKG_VERSION"));
+
+    // The cargo commands run as quiet and colored as cargo-aspect
+    if args.quiet {
+        std::env::set_var("CARGO_TERM_QUIET", "true");
+    }
+    if args.color != ColorChoice::Auto {
+        std::env::set_var("CARGO_TERM_COLOR", args.color.as_str());
     }
 
+    // Settings read by aspect-rustc-driver when it compiles the crates
+    let mut driver_env = Vec::new();
+    if args.deny_unmatched {
+        driver_env.push((DENY_UNMATCHED_ENV, "1"));
+    }
+    if args.deny_unwoven {
+        driver_env.push((DENY_UNWOVEN_ENV, "1"));
+    }
+    let driver_flags = args.driver_flags();
+
     match args.command {
         None => {
             // No subcommand, show help
@@ -126,121 +471,1216 @@ fn run_aspect_command(args: AspectArgs) -> Result<()> {
             println!("Usage: cargo aspect <COMMAND>");
             println!();
             println!("Commands:");
-            println!("  build   Build with aspect weaving");
-            println!("  check   Check with aspect analysis");
-            println!("  test    Run tests with aspects");
-            println!("  bench   Run benchmarks");
-            println!("  clean   Clean build artifacts");
-            println!("  info    Show aspect information");
-            println!("  list    List aspects and pointcuts");
+            println!("  build            Build with aspect weaving");
+            println!("  check            Check with aspect analysis");
+            println!("  test             Run tests with aspects");
+            println!("  bench            Run benchmarks");
+            println!("  clean            Clean build artifacts");
+            println!("  clean-aspects    Remove aspect artifacts only");
+            println!("  info             Show aspect information");
+            println!("  list             List aspects and pointcuts");
+            println!("  match            List the functions a pointcut matches");
+            println!("  browse           Browse modules, functions and aspects");
+            println!("  graph            Graph the functions each aspect applies to");
+            println!("  diff             Show the source weaving adds");
+            println!("  report           Report aspects and coverage, as HTML");
+            println!("  watch            Analyze again on every change");
+            println!("  doctor           Check the environment for weaving");
+            println!("  check-pointcuts  Check pointcuts without building");
+            println!("  completions      Write a shell completion script");
             println!();
             println!("Run 'cargo aspect <COMMAND> --help' for more information");
             Ok(())
         }
 
-        Some(AspectCommand::Build { args: cargo_args }) => {
-            if args.verbose {
-                println!("Running: cargo build {}", cargo_args.join(" "));
-            }
-            run_cargo_command("build", &cargo_args)
+        Some(AspectCommand::Build {
+            packages,
+            args: cargo_args,
+        }) => {
+            let cargo_args = [packages.cargo_args(), cargo_args].concat();
+            debug!("Running: cargo build {}", cargo_args.join(" "));
+            run_woven_cargo_command(
+                "build",
+                &cargo_args,
+                &driver_flags,
+                args.config.as_deref(),
+                args.profile.as_deref(),
+                &driver_env,
+            )
         }
 
-        Some(AspectCommand::Check { args: cargo_args }) => {
-            if args.verbose {
-                println!("Running: cargo check {}", cargo_args.join(" "));
-            }
-            run_cargo_command("check", &cargo_args)
+        Some(AspectCommand::Check {
+            packages,
+            args: cargo_args,
+        }) => {
+            let cargo_args = [packages.cargo_args(), cargo_args].concat();
+            debug!("Running: cargo check {}", cargo_args.join(" "));
+            run_woven_cargo_command(
+                "check",
+                &cargo_args,
+                &driver_flags,
+                args.config.as_deref(),
+                args.profile.as_deref(),
+                &driver_env,
+            )
         }
 
-        Some(AspectCommand::Test { args: cargo_args }) => {
-            if args.verbose {
-                println!("Running: cargo test {}", cargo_args.join(" "));
-            }
-            run_cargo_command("test", &cargo_args)
+        Some(AspectCommand::Test {
+            packages,
+            args: cargo_args,
+        }) => {
+            // The selection goes before the arguments of the test harness
+            let cargo_args = [packages.cargo_args(), cargo_args].concat();
+            debug!("Running: cargo test {}", cargo_args.join(" "));
+
+            // Have the tests record which aspects run, and #[advice] which
+            // aspects there are, even without the driver
+            let workspace = Workspace::locate_for(&cargo_args)?;
+            let coverage_dir = workspace.target_dir.join("aspect").join("coverage");
+            // Kept even if no aspect runs, for cargo aspect report to tell
+            // that the tests ran
+            let _ = std::fs::remove_dir_all(&coverage_dir);
+            std::fs::create_dir_all(&coverage_dir)
+                .with_context(|| format!("Cannot create {}", coverage_dir.display()))?;
+            let coverage_env = coverage_dir.display().to_string();
+            let registry_env = workspace.registry_dir().display().to_string();
+            let mut env = driver_env.clone();
+            env.push((COVERAGE_DIR_ENV, &coverage_env));
+            env.push((REGISTRY_DIR_ENV, &registry_env));
+
+            let result = run_woven_cargo_command(
+                "test",
+                &cargo_args,
+                &driver_flags,
+                args.config.as_deref(),
+                args.profile.as_deref(),
+                &env,
+            );
+            coverage::print_report(
+                &load_registry(&workspace)?,
+                &coverage::read_executed(&coverage_dir)?,
+            );
+            result
         }
 
-        Some(AspectCommand::Bench { args: cargo_args }) => {
-            if args.verbose {
-                println!("Running: cargo bench {}", cargo_args.join(" "));
+        Some(AspectCommand::Bench {
+            compare,
+            args: cargo_args,
+        }) => {
+            debug!("Running: cargo bench {}", cargo_args.join(" "));
+            if compare {
+                return bench::Compare {
+                    args: &cargo_args,
+                    driver_flags: &driver_flags,
+                    config: args.config.as_deref(),
+                    profile: args.profile.as_deref(),
+                    driver_env: &driver_env,
+                }
+                .run();
             }
-            run_cargo_command("bench", &cargo_args)
+            run_woven_cargo_command(
+                "bench",
+                &cargo_args,
+                &driver_flags,
+                args.config.as_deref(),
+                args.profile.as_deref(),
+                &driver_env,
+            )
         }
 
         Some(AspectCommand::Clean { args: cargo_args }) => {
-            if args.verbose {
-                println!("Running: cargo clean {}", cargo_args.join(" "));
-            }
-            run_cargo_command("clean", &cargo_args)
+            debug!("Running: cargo clean {}", cargo_args.join(" "));
+            run_cargo_command("clean", &cargo_args, &[])
+        }
+
+        Some(AspectCommand::CleanAspects { dry_run }) => {
+            clean::run(args.config.as_deref(), dry_run)
         }
 
         Some(AspectCommand::Info {
+            packages,
             detailed,
             module: filter_module,
         }) => {
+            let workspace = Workspace::locate()?;
+            let mut reports = analyze_workspace(&workspace, &packages.cargo_args())?;
+            // Reports of the crates analyzed before are kept: only those of
+            // the packages selected are shown
+            if let Some(selected) = packages.selected(&workspace) {
+                reports.retain(|(name, _)| workspace.is_report_of(name, &selected));
+            }
+            let config =
+                workspace_config(&workspace, args.config.as_deref(), args.profile.as_deref())?;
+            let registered = load_registry(&workspace)?;
+            let pointcuts = collect_pointcuts(
+                &registered,
+                config.as_ref().map(|(_, file)| file),
+                &args.pointcut,
+            );
+
+            let crates: Vec<CrateStats> = reports
+                .iter()
+                .map(|(name, report)| {
+                    let functions = functions_in(report, filter_module.as_deref());
+                    CrateStats::new(name, functions, &pointcuts)
+                })
+                .collect();
+            let scanned: usize = crates.iter().map(|stats| stats.functions.len()).sum();
+            let matched: usize = crates.iter().map(|stats| stats.matched).sum();
+
+            if args.format == OutputFormat::Json {
+                return print_json(&serde_json::json!({
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "analysis": "source",
+                    "module": filter_module,
+                    "pointcuts": pointcuts,
+                    "crates": crates
+                        .iter()
+                        .map(|stats| stats.to_json(detailed))
+                        .collect::<Vec<_>>(),
+                    "total": { "functions": scanned, "matched": matched },
+                }));
+            }
+
             println!("=== Aspect Information ===");
             println!();
             println!("Framework: aspect-rs v{}", env!("CARGO_PKG_VERSION"));
-            println!("Status: Phase 2 Complete (Proc Macros + Pointcuts)");
-            println!();
+            println!("Analysis: source (approximate, works on stable Rust)");
+            if let Some(module) = &filter_module {
+                println!("Filter: module = {}", module);
+            }
+            for stats in &crates {
+                stats.print(detailed);
+            }
 
-            if detailed {
-                println!("Detailed analysis:");
-                println!("  • Use #[aspect(AspectType)] for per-function aspects");
-                println!("  • Use #[advice(...)] for pointcut-based aspects");
-                println!("  • See documentation for advanced features");
+            println!();
+            if reports.is_empty() {
+                println!("No crates analyzed");
+            } else if pointcuts.is_empty() {
+                println!("Total: {} functions scanned; no pointcuts", scanned);
+                println!("  declare them with #[advice], in aspect.toml or with --pointcut");
+            } else {
+                println!(
+                    "Total: {} functions scanned, {} matched by {} pointcuts",
+                    scanned,
+                    matched,
+                    pointcuts.len()
+                );
             }
+            Ok(())
+        }
 
-            if let Some(module) = filter_module {
-                println!();
-                println!("Filter: module = {}", module);
-                println!("(Module filtering requires Phase 3 - rustc-driver integration)");
+        Some(AspectCommand::Match {
+            pointcut,
+            module,
+            explain,
+        }) => query::run(&pointcut, module.as_deref(), explain, args.format),
+
+        Some(AspectCommand::Browse) => browse::run(
+            args.config.as_deref(),
+            args.profile.as_deref(),
+            &args.pointcut,
+        ),
+
+        Some(AspectCommand::Graph {
+            packages,
+            module,
+            mermaid,
+            svg,
+            output,
+        }) => graph::Graph {
+            packages: &packages,
+            module: module.as_deref(),
+            format: if svg {
+                GraphFormat::Svg
+            } else if mermaid {
+                GraphFormat::Mermaid
+            } else {
+                GraphFormat::Dot
+            },
+            output: output.as_deref(),
+            config: args.config.as_deref(),
+            profile: args.profile.as_deref(),
+            pointcuts: &args.pointcut,
+        }
+        .run(),
+
+        Some(AspectCommand::Diff { packages, module }) => diff::Diff {
+            packages: &packages,
+            module: module.as_deref(),
+            config: args.config.as_deref(),
+            profile: args.profile.as_deref(),
+        }
+        .run(),
+
+        Some(AspectCommand::Report {
+            packages,
+            module,
+            html,
+            output,
+        }) => report::Report {
+            packages: &packages,
+            module: module.as_deref(),
+            html,
+            output: output.as_deref(),
+            config: args.config.as_deref(),
+            profile: args.profile.as_deref(),
+            pointcuts: &args.pointcut,
+        }
+        .run(),
+
+        Some(AspectCommand::Watch {
+            check,
+            test,
+            interval,
+        }) => {
+            let commands = [("check", check), ("test", test)]
+                .into_iter()
+                .filter_map(|(command, enabled)| enabled.then_some(command))
+                .collect();
+            Watch {
+                commands,
+                interval: Duration::from_millis(interval),
+                config: args.config.as_deref(),
+                profile: args.profile.as_deref(),
+                pointcuts: &args.pointcut,
+                driver_flags: &driver_flags,
+                driver_env: &driver_env,
             }
+            .run()
+        }
 
-            println!();
-            println!("Note: Full aspect analysis will be available in Phase 3");
+        Some(AspectCommand::Doctor) => {
+            let checks = doctor::run_checks(args.config.as_deref(), args.profile.as_deref());
+            doctor::report(&checks, args.format)
+        }
+
+        Some(AspectCommand::Completions { shell }) => {
+            // Outside a workspace, or with an invalid aspect.toml, there
+            // are no profiles to complete
+            let profiles: Vec<String> = Workspace::locate()
+                .and_then(|workspace| workspace_config(&workspace, args.config.as_deref(), None))
+                .ok()
+                .flatten()
+                .map(|(_, file)| file.profiles.into_keys().collect())
+                .unwrap_or_default();
+            print!("{}", completions::script(shell, Cli::command(), &profiles)?);
             Ok(())
         }
 
+        Some(AspectCommand::CheckPointcuts { deny_warnings }) => check_pointcuts::run(
+            args.config.as_deref(),
+            &args.pointcut,
+            args.format,
+            deny_warnings,
+        ),
+
         Some(AspectCommand::List {
             aspects,
             pointcuts,
         }) => {
-            println!("=== Registered Aspects ===");
-            println!();
+            let workspace = Workspace::locate()?;
+            let reports = analyze_workspace(&workspace, &[])?;
+            let config =
+                workspace_config(&workspace, args.config.as_deref(), args.profile.as_deref())?;
+            let registered = load_registry(&workspace)?;
+            let provided = discover_providers(&[])?;
+            let functions: Vec<_> = reports
+                .iter()
+                .flat_map(|(_, report)| &report.functions)
+                .collect();
+            print_aspect_list(
+                &registered,
+                &provided,
+                config.as_ref().map(|(_, file)| file),
+                &args.pointcut,
+                &functions,
+                aspects || !pointcuts,
+                pointcuts || !aspects,
+                args.format,
+            )
+        }
+    }
+}
 
-            if aspects || (!aspects && !pointcuts) {
-                println!("Available aspects (from aspect-std):");
-                println!("  • LoggingAspect      - Structured logging");
-                println!("  • TimingAspect       - Performance monitoring");
-                println!("  • CachingAspect      - Memoization");
-                println!("  • MetricsAspect      - Call statistics");
-                println!("  • RateLimitAspect    - Rate limiting");
-                println!("  • CircuitBreaker     - Fault tolerance");
-                println!("  • Authorization      - Access control");
-                println!("  • Validation         - Pre-conditions");
-                println!();
+/// Analyze the crates of the workspace, with cargo-aspect as an
+/// analysis-only rustc wrapper of `cargo check` run with `cargo_args`.
+fn analyze_workspace(
+    workspace: &Workspace,
+    cargo_args: &[String],
+) -> Result<Vec<(String, AnalysisReport)>> {
+    let aspect_dir = workspace.target_dir.join("aspect");
+    let analysis_dir = aspect_dir.join("analysis");
+    // The reports of the last run, while no source changed since
+    if wrapper::fresh_reports(&analysis_dir, &workspace.crate_roots) {
+        debug!(
+            "Reusing the analysis in {}: no source changed",
+            analysis_dir.display()
+        );
+        return wrapper::read_reports(&analysis_dir, None);
+    }
+
+    let wrapper = std::env::current_exe().context("Failed to locate cargo-aspect")?;
+    debug!(
+        "Running: cargo check with {} as rustc wrapper",
+        wrapper.display()
+    );
+
+    // A target directory of its own, so every crate is checked, and thus
+    // analyzed, even if it was built before; reports of crates that are
+    // fresh are kept from the last run
+    let status = Command::new("cargo")
+        .args(["check", "--quiet", "--target-dir"])
+        .arg(aspect_dir.join("check"))
+        .args(cargo_args)
+        .env("RUSTC_WORKSPACE_WRAPPER", &wrapper)
+        .env(ANALYSIS_DIR_ENV, &analysis_dir)
+        .env(REGISTRY_DIR_ENV, workspace.registry_dir())
+        .status()
+        .context("Failed to execute cargo")?;
+    if !status.success() {
+        anyhow::bail!("cargo check failed with status {}", status);
+    }
+
+    if !analysis_dir.exists() {
+        return Ok(Vec::new());
+    }
+    wrapper::read_reports(&analysis_dir, None)
+}
+
+/// Directories of the workspace, from cargo metadata.
+struct Workspace {
+    /// Directory of the workspace manifest
+    root: PathBuf,
+
+    /// Target directory
+    target_dir: PathBuf,
+
+    /// Root source files of the library and binary targets of the
+    /// workspace members
+    crate_roots: Vec<PathBuf>,
+
+    /// Crate names of the library and binary targets, by package
+    packages: BTreeMap<String, Vec<String>>,
+}
+
+impl Workspace {
+    fn locate() -> Result<Self> {
+        Self::locate_for(&[])
+    }
+
+    /// The workspace of `cargo <cmd> <args>`: that of its
+    /// `--manifest-path`, with the target directory of its `--config` and
+    /// `--target-dir`, as cargo resolves them.
+    fn locate_for(args: &[String]) -> Result<Self> {
+        let metadata = cargo_metadata(args, false)?;
+        let dir = |key: &str| {
+            metadata[key]
+                .as_str()
+                .map(PathBuf::from)
+                .with_context(|| format!("No {} in cargo metadata", key))
+        };
+
+        // Tests, examples, benches and build scripts are not analyzed
+        let mut crate_roots = Vec::new();
+        let mut packages = BTreeMap::new();
+        for package in metadata["packages"].as_array().into_iter().flatten() {
+            let targets = package["targets"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|target| {
+                    target["kind"].as_array().is_some_and(|kinds| {
+                        kinds.iter().all(|kind| {
+                            !matches!(
+                                kind.as_str(),
+                                Some("test" | "example" | "bench" | "custom-build")
+                            )
+                        })
+                    })
+                });
+            let mut crate_names = Vec::new();
+            for target in targets {
+                crate_roots.extend(target["src_path"].as_str().map(PathBuf::from));
+                crate_names.extend(target["name"].as_str().map(|name| name.replace('-', "_")));
+            }
+            if let Some(name) = package["name"].as_str() {
+                packages.insert(name.to_string(), crate_names);
+            }
+        }
+
+        let target_dir = match cargo_option(args, "--target-dir").last() {
+            Some(target_dir) => std::env::current_dir()
+                .context("Failed to locate the current directory")?
+                .join(target_dir),
+            None => dir("target_directory")?,
+        };
+        Ok(Self {
+            root: dir("workspace_root")?,
+            target_dir,
+            crate_roots,
+            packages,
+        })
+    }
+
+    /// Where `#[advice]` records the aspects it declares.
+    fn registry_dir(&self) -> PathBuf {
+        self.target_dir.join("aspect").join("registry")
+    }
+
+    /// Whether the analysis report `report` (`<crate>-<kind>`) is of a
+    /// crate of one of `packages`.
+    fn is_report_of(&self, report: &str, packages: &[&str]) -> bool {
+        let Some((crate_name, _)) = report.rsplit_once('-') else {
+            return false;
+        };
+        packages.iter().any(|package| {
+            self.packages
+                .get(*package)
+                .is_some_and(|crates| crates.iter().any(|name| name == crate_name))
+        })
+    }
+
+    /// The sources of the workspace: `*.rs`, `Cargo.toml` and
+    /// `aspect.toml` files, outside of target and hidden directories
+    /// (`.git`).
+    fn source_files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        collect_source_files(&self.root, &self.target_dir, &mut files);
+        files
+    }
+}
+
+/// The output of `cargo metadata` for `cargo <cmd> <args>`, with its
+/// `--manifest-path` and `--config`; for the packages of the workspace
+/// only, or with their dependencies too.
+fn cargo_metadata(args: &[String], with_deps: bool) -> Result<serde_json::Value> {
+    let mut metadata = Command::new("cargo");
+    metadata.args(["metadata", "--format-version", "1"]);
+    if !with_deps {
+        metadata.arg("--no-deps");
+    }
+    for flag in ["--manifest-path", "--config"] {
+        for value in cargo_option(args, flag) {
+            metadata.args([flag, value]);
+        }
+    }
+    let output = metadata
+        .output()
+        .context("Failed to execute cargo metadata")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    serde_json::from_slice(&output.stdout).context("Invalid cargo metadata")
+}
+
+/// The aspects provided by the packages of the workspace of `cargo <cmd>
+/// <args>` and their dependencies. Invalid provider manifests are
+/// reported and skipped.
+fn discover_providers(args: &[String]) -> Result<Vec<ProvidedAspect>> {
+    let (aspects, errors) = provider::from_metadata(&cargo_metadata(args, true)?);
+    for error in errors {
+        warn!("{}", error);
+    }
+    Ok(aspects)
+}
+
+/// Add the sources in `dir` to `files`. Unreadable entries are skipped:
+/// they may be removed while scanning.
+fn collect_source_files(dir: &Path, target_dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if file_type.is_dir() {
+            if !name.starts_with('.') && path != target_dir && name != "target" {
+                collect_source_files(&path, target_dir, files);
             }
+        } else if name.ends_with(".rs") || name == "Cargo.toml" || name == "aspect.toml" {
+            files.push(path);
+        }
+    }
+}
+
+/// The aspects `#[advice]` registered in the crates of the workspace.
+fn load_registry(workspace: &Workspace) -> Result<Vec<RegisteredAspect>> {
+    let registry_dir = workspace.registry_dir();
+    if !registry_dir.exists() {
+        return Ok(Vec::new());
+    }
+    load_registry_manifest(&registry_dir).map_err(anyhow::Error::msg)
+}
 
-            if pointcuts || (!aspects && !pointcuts) {
-                println!("Pointcut syntax:");
-                println!("  execution(pub fn *(..))     - All public functions");
-                println!("  within(crate::api)          - Functions in module");
-                println!("  name(\"fetch_*\")              - Name patterns");
-                println!("  execution(..) && within(..) - Combinations");
+/// The pointcuts of `#[advice]`, of aspect.toml and of `--pointcut`, once
+/// each.
+fn collect_pointcuts<'a>(
+    registered: &'a [RegisteredAspect],
+    config: Option<&'a ConfigFile>,
+    extra: &'a [String],
+) -> Vec<&'a str> {
+    let mut pointcuts: Vec<&str> = registered
+        .iter()
+        .map(|aspect| aspect.pointcut.as_str())
+        .collect();
+    if let Some(file) = config {
+        pointcuts.extend(file.aspects.iter().map(|aspect| aspect.pointcut.as_str()));
+        pointcuts.extend(file.pointcuts.iter().map(String::as_str));
+    }
+    pointcuts.extend(extra.iter().map(String::as_str));
+
+    let mut seen = std::collections::HashSet::new();
+    pointcuts.retain(|pointcut| seen.insert(*pointcut));
+    pointcuts
+}
+
+/// Print the aspects registered by `#[advice]` and in aspect.toml, in the
+/// order they run, and those `provided` by packages, then the pointcuts
+/// they use (and those of `--pointcut`), each with the number of
+/// `functions` it matches.
+#[allow(clippy::too_many_arguments)]
+fn print_aspect_list(
+    registered: &[RegisteredAspect],
+    provided: &[ProvidedAspect],
+    config: Option<&ConfigFile>,
+    extra_pointcuts: &[String],
+    functions: &[&FunctionMetadata],
+    show_aspects: bool,
+    show_pointcuts: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let matcher = PointcutMatcher::new();
+    let matches = |pointcut: &str| {
+        functions
+            .iter()
+            .filter(|function| matcher.matches_pointcut(function, pointcut))
+            .count()
+    };
+    let mut advice: Vec<&RegisteredAspect> = registered.iter().collect();
+    advice.sort_by_key(|aspect| std::cmp::Reverse(aspect.priority));
+    let mut hooks: Vec<&AspectEntry> =
+        config.map_or(Vec::new(), |file| file.aspects.iter().collect());
+    hooks.sort_by_key(|aspect| std::cmp::Reverse(aspect.priority));
+    let mut pointcuts = collect_pointcuts(registered, config, extra_pointcuts);
+    for pointcut in provided
+        .iter()
+        .filter_map(|aspect| aspect.pointcut.as_deref())
+    {
+        if !pointcuts.contains(&pointcut) {
+            pointcuts.push(pointcut);
+        }
+    }
+
+    if format == OutputFormat::Json {
+        let mut list = serde_json::Map::new();
+        if show_aspects {
+            let advice: Vec<_> = advice
+                .iter()
+                .map(|aspect| {
+                    serde_json::json!({
+                        "name": aspect.aspect_name,
+                        "advice": aspect.advice_type.to_string(),
+                        "pointcut": aspect.pointcut,
+                        "order": -aspect.priority,
+                        "matched": matches(&aspect.pointcut),
+                    })
+                })
+                .collect();
+            let hooks: Vec<_> = hooks
+                .iter()
+                .map(|aspect| {
+                    serde_json::json!({
+                        "name": aspect.name,
+                        "advice": aspect.advice,
+                        "pointcut": aspect.pointcut,
+                        "hook": aspect.hook,
+                        "priority": aspect.priority,
+                        "matched": matches(&aspect.pointcut),
+                    })
+                })
+                .collect();
+            let provided: Vec<_> = provided
+                .iter()
+                .map(|aspect| {
+                    serde_json::json!({
+                        "package": aspect.package,
+                        "version": aspect.version,
+                        "path": aspect.path,
+                        "description": aspect.description,
+                        "advice": aspect.advice_type.to_string(),
+                        "pointcut": aspect.pointcut,
+                        "order": aspect.order,
+                        "features": aspect.features,
+                        "matched": aspect.pointcut.as_deref().map(matches),
+                    })
+                })
+                .collect();
+            list.insert("advice".to_string(), advice.into());
+            list.insert("aspects".to_string(), hooks.into());
+            list.insert("provided".to_string(), provided.into());
+        }
+        if show_pointcuts {
+            let pointcuts: Vec<_> = pointcuts
+                .iter()
+                .map(|pointcut| {
+                    serde_json::json!({ "pointcut": pointcut, "matched": matches(pointcut) })
+                })
+                .collect();
+            list.insert("pointcuts".to_string(), pointcuts.into());
+        }
+        return print_json(&list.into());
+    }
+
+    println!("=== Registered Aspects ===");
+    if show_aspects {
+        if !advice.is_empty() {
+            println!();
+            println!("#[advice], in run order:");
+            for (i, aspect) in advice.iter().enumerate() {
+                println!(
+                    "  {}. {} ({}, order {})",
+                    i + 1,
+                    aspect.aspect_name,
+                    aspect.advice_type,
+                    -aspect.priority
+                );
+                println!(
+                    "     {}: {} matched",
+                    aspect.pointcut,
+                    matches(&aspect.pointcut)
+                );
+            }
+        }
+        if !hooks.is_empty() {
+            println!();
+            println!("aspect.toml, in run order:");
+            for (i, aspect) in hooks.iter().enumerate() {
+                println!(
+                    "  {}. {} ({}, priority {})",
+                    i + 1,
+                    aspect.label(),
+                    aspect.advice,
+                    aspect.priority
+                );
+                println!(
+                    "     {} -> {}: {} matched",
+                    aspect.pointcut,
+                    aspect.hook,
+                    matches(&aspect.pointcut)
+                );
+            }
+        }
+        if advice.is_empty() && hooks.is_empty() && provided.is_empty() {
+            println!();
+            println!("No aspects registered");
+            println!("  declare them with #[advice] or in the [[aspects]] of aspect.toml,");
+            println!("  or depend on a crate providing aspects");
+        }
+        let mut package = None;
+        for aspect in provided {
+            if package != Some(&aspect.package) {
+                package = Some(&aspect.package);
                 println!();
+                println!("Provided by {} {}:", aspect.package, aspect.version);
+            }
+            print!("  {}", aspect.path);
+            if !aspect.features.is_empty() {
+                print!(" (features: {})", aspect.features.join(", "));
+            }
+            if !aspect.description.is_empty() {
+                print!(" - {}", aspect.description);
             }
+            println!();
+            if let Some(pointcut) = &aspect.pointcut {
+                println!(
+                    "     {} ({}, order {}): {} matched",
+                    pointcut,
+                    aspect.advice_type,
+                    aspect.order,
+                    matches(pointcut)
+                );
+            }
+        }
+    }
 
-            println!("Note: Dynamic aspect discovery requires Phase 3");
-            Ok(())
+    if show_pointcuts {
+        println!();
+        println!("Pointcuts:");
+        for pointcut in &pointcuts {
+            println!("  {}: {} matched", pointcut, matches(pointcut));
+        }
+        if pointcuts.is_empty() {
+            println!("  none");
+        }
+    }
+    Ok(())
+}
+
+/// Write `value` to standard output, for `--format json`.
+fn print_json(value: &serde_json::Value) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// The functions of `report`, only those of `module` and its submodules
+/// if given.
+fn functions_in<'a>(report: &'a AnalysisReport, module: Option<&str>) -> Vec<&'a FunctionMetadata> {
+    let module = module.map(|module| {
+        if module == "crate" || module.starts_with("crate::") {
+            module.to_string()
+        } else {
+            format!("crate::{}", module)
+        }
+    });
+    report
+        .functions
+        .iter()
+        .filter(|function| {
+            module.as_deref().is_none_or(|module| {
+                function.module_path == module
+                    || function
+                        .module_path
+                        .strip_prefix(module)
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+        })
+        .collect()
+}
+
+/// Statistics of the functions found in one crate.
+struct CrateStats<'a> {
+    name: &'a str,
+    functions: Vec<&'a FunctionMetadata>,
+
+    /// Functions by module path
+    modules: BTreeMap<&'a str, Vec<&'a FunctionMetadata>>,
+
+    /// Functions matched by each pointcut
+    pointcuts: Vec<(&'a str, Vec<&'a FunctionMetadata>)>,
+
+    /// Functions matched by any pointcut
+    matched: usize,
+}
+
+impl<'a> CrateStats<'a> {
+    fn new(name: &'a str, functions: Vec<&'a FunctionMetadata>, pointcuts: &[&'a str]) -> Self {
+        let mut modules: BTreeMap<&str, Vec<&FunctionMetadata>> = BTreeMap::new();
+        for function in &functions {
+            modules
+                .entry(function.module_path.as_str())
+                .or_default()
+                .push(function);
+        }
+        let matcher = PointcutMatcher::new();
+        let matched = functions
+            .iter()
+            .filter(|function| {
+                pointcuts
+                    .iter()
+                    .any(|pointcut| matcher.matches_pointcut(function, pointcut))
+            })
+            .count();
+        let pointcuts = pointcuts
+            .iter()
+            .map(|pointcut| {
+                let matched = functions
+                    .iter()
+                    .copied()
+                    .filter(|function| matcher.matches_pointcut(function, pointcut))
+                    .collect();
+                (*pointcut, matched)
+            })
+            .collect();
+
+        Self {
+            name,
+            functions,
+            modules,
+            pointcuts,
+            matched,
+        }
+    }
+
+    /// Print the statistics; with `detailed`, the functions themselves too.
+    fn print(&self, detailed: bool) {
+        let (public, asynchronous) = counts(&self.functions);
+        println!();
+        println!("{}:", self.name);
+        println!("  Functions: {}", self.functions.len());
+        println!("    Public: {}", public);
+        println!("    Private: {}", self.functions.len() - public);
+        println!("    Async: {}", asynchronous);
+
+        if !self.modules.is_empty() {
+            println!("  Modules:");
+        }
+        for (module, functions) in &self.modules {
+            let (public, asynchronous) = counts(functions);
+            println!(
+                "    {}: {} ({} public, {} async)",
+                module,
+                functions.len(),
+                public,
+                asynchronous
+            );
+            if detailed {
+                for function in functions {
+                    let visibility = function.visibility.to_string();
+                    println!(
+                        "      {}{}fn {}  ({}:{})",
+                        visibility,
+                        if visibility.is_empty() { "" } else { " " },
+                        function.name,
+                        function.location.file,
+                        function.location.line
+                    );
+                }
+            }
+        }
+
+        if !self.pointcuts.is_empty() {
+            println!("  Pointcuts:");
+        }
+        for (pointcut, matched) in &self.pointcuts {
+            println!("    {}: {} matched", pointcut, matched.len());
+            if detailed {
+                for function in matched {
+                    println!("      {}", function.name);
+                }
+            }
         }
     }
+
+    /// The statistics as JSON; with `detailed`, the metadata of the
+    /// functions and the names of those each pointcut matches too.
+    fn to_json(&self, detailed: bool) -> serde_json::Value {
+        let (public, asynchronous) = counts(&self.functions);
+        let modules: Vec<_> = self
+            .modules
+            .iter()
+            .map(|(module, functions)| {
+                let (public, asynchronous) = counts(functions);
+                let mut stats = serde_json::json!({
+                    "module": module,
+                    "functions": functions.len(),
+                    "public": public,
+                    "async": asynchronous,
+                });
+                if detailed {
+                    stats["metadata"] = serde_json::json!(functions);
+                }
+                stats
+            })
+            .collect();
+        let pointcuts: Vec<_> = self
+            .pointcuts
+            .iter()
+            .map(|(pointcut, matched)| {
+                let mut stats = serde_json::json!({
+                    "pointcut": pointcut,
+                    "matched": matched.len(),
+                });
+                if detailed {
+                    stats["functions"] = matched
+                        .iter()
+                        .map(|function| function.name.as_str())
+                        .collect();
+                }
+                stats
+            })
+            .collect();
+
+        serde_json::json!({
+            "name": self.name,
+            "functions": self.functions.len(),
+            "public": public,
+            "private": self.functions.len() - public,
+            "async": asynchronous,
+            "matched": self.matched,
+            "modules": modules,
+            "pointcuts": pointcuts,
+        })
+    }
 }
 
-/// Run a standard cargo command with the given arguments
-fn run_cargo_command(cmd: &str, args: &[String]) -> Result<()> {
+/// How many of `functions` are public, and how many async.
+fn counts(functions: &[&FunctionMetadata]) -> (usize, usize) {
+    let public = functions
+        .iter()
+        .filter(|function| function.visibility == Visibility::Public)
+        .count();
+    let asynchronous = functions
+        .iter()
+        .filter(|function| function.is_async)
+        .count();
+    (public, asynchronous)
+}
+
+/// Run a cargo command with aspect-rustc-driver as the rustc wrapper of
+/// the workspace crates, then report what it wove. Without the driver, the
+/// command runs as is: `#[aspect]` still works, but nothing is woven by
+/// pointcut. It fails instead when a `deny_*` policy is set, since the
+/// policy could not be checked.
+fn run_woven_cargo_command(
+    cmd: &str,
+    args: &[String],
+    driver_flags: &[String],
+    config: Option<&Path>,
+    profile: Option<&str>,
+    env: &[(&str, &str)],
+) -> Result<()> {
+    let Some(driver) = find_driver() else {
+        if let Some(policy) = deny_policy(env, config, profile, cmd, args)? {
+            anyhow::bail!(
+                "aspect-rustc-driver not found, and {} cannot be checked without it\n  \
+                 install it, or set {} to its path",
+                policy,
+                DRIVER_ENV
+            );
+        }
+        warn!(
+            "aspect-rustc-driver not found, building without automatic weaving\n         \
+             install it, or set {} to its path",
+            DRIVER_ENV
+        );
+        return run_cargo_command(cmd, args, env);
+    };
+    let workspace = Workspace::locate_for(args)?;
+    let results_dir = workspace.target_dir.join("aspect").join("results");
+    debug!(
+        "cargo {}: {} profile, target directory {}",
+        cmd,
+        cargo_profile(cmd, args),
+        workspace.target_dir.display()
+    );
+
+    // The aspect.toml of the workspace, for every crate; checked once here
+    // rather than by the driver for each crate
+    let mut driver_flags = driver_flags.to_vec();
+    let profile = match profile {
+        Some(profile) => Some(profile.to_string()),
+        None => workspace_config(&workspace, config, None)?.and_then(|(_, file)| {
+            let name = cargo_profile(cmd, args);
+            let found = file.profiles.contains_key(&name);
+            if found {
+                info!("Using the {} profile of aspect.toml", name);
+            }
+            found.then_some(name)
+        }),
+    };
+    let profile = profile.as_deref();
+
+    // The aspects of the providers, for the weaving plans of the driver
+    let providers_file = workspace.target_dir.join("aspect").join("providers.json");
+    match discover_providers(args) {
+        Ok(provided) => {
+            provider::write_providers(&providers_file, &provided).map_err(anyhow::Error::msg)?
+        }
+        Err(e) => {
+            warn!("Aspect providers not discovered: {:#}", e);
+            let _ = std::fs::remove_file(&providers_file);
+        }
+    }
+    if let Some((path, _)) = workspace_config(&workspace, config, profile)? {
+        driver_flags.push("--aspect-config".to_string());
+        driver_flags.push(path.display().to_string());
+        if let Some(profile) = profile {
+            driver_flags.push("--aspect-profile".to_string());
+            driver_flags.push(profile.to_string());
+        }
+    }
+
+    // The cargo arguments (`--release`, `--target`, `--features`) and
+    // RUSTFLAGS reach the driver as the rustc arguments cargo gives it, like
+    // for plain builds. The driver writes its cache in the target directory
+    // cargo builds in, however that was set.
+    let started = SystemTime::now();
     let status = Command::new("cargo")
         .arg(cmd)
         .args(args)
+        .envs(env.iter().copied())
+        .env("CARGO_TARGET_DIR", &workspace.target_dir)
+        .env("RUSTC_WORKSPACE_WRAPPER", &driver)
+        .env(ENCODED_ARGS_ENV, encode_args(&driver_flags))
+        .env(RESULTS_DIR_ENV, &results_dir)
+        .env(REGISTRY_DIR_ENV, workspace.registry_dir())
+        .env(PROVIDERS_FILE_ENV, &providers_file)
+        .status()
+        .context("Failed to execute cargo")?;
+    if !status.success() {
+        anyhow::bail!("cargo {} failed with status {}", cmd, status);
+    }
+
+    // Crates that were up to date were not compiled again
+    let reports = if results_dir.exists() {
+        wrapper::read_reports(&results_dir, Some(started))?
+    } else {
+        Vec::new()
+    };
+    if !reports.is_empty() {
+        println!();
+        println!("=== Aspect Weaving ===");
+        for (name, report) in &reports {
+            println!(
+                "{}: {} functions, {} matched by {} pointcuts",
+                name,
+                report.stats.total_functions,
+                report.stats.matched_functions,
+                report.pointcuts.len()
+            );
+            for warning in &report.warnings {
+                println!("  warning: {}: {}", warning.function, warning.message);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The cargo profile `cargo <cmd> <args>` builds with: the one given with
+/// `--profile`, or `release` or `dev` like cargo names them.
+fn cargo_profile(cmd: &str, args: &[String]) -> String {
+    // Arguments after `--` are those of the test harness
+    let mut args = args.iter().take_while(|arg| *arg != "--");
+    let mut release = cmd == "bench";
+    while let Some(arg) = args.next() {
+        if let Some(name) = arg.strip_prefix("--profile=") {
+            return name.to_string();
+        }
+        match arg.as_str() {
+            "--profile" => return args.next().cloned().unwrap_or_default(),
+            "--release" | "-r" => release = true,
+            _ => {}
+        }
+    }
+    if release { "release" } else { "dev" }.to_string()
+}
+
+/// The values of the cargo option `flag` (`--target-dir`) in `args`, given
+/// as `flag value` or `flag=value`.
+fn cargo_option<'a>(args: &'a [String], flag: &str) -> Vec<&'a str> {
+    // Arguments after `--` are those of the test harness
+    let mut args = args.iter().take_while(|arg| *arg != "--");
+    let mut values = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            values.extend(args.next().map(String::as_str));
+        } else if let Some(value) = arg
+            .strip_prefix(flag)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            values.push(value);
+        }
+    }
+    values
+}
+
+/// The `deny_*` policy the driver would enforce for `cargo <cmd> <args>`,
+/// set by a flag, the environment, or aspect.toml and its profile.
+fn deny_policy(
+    env: &[(&str, &str)],
+    config: Option<&Path>,
+    profile: Option<&str>,
+    cmd: &str,
+    args: &[String],
+) -> Result<Option<&'static str>> {
+    for (var, flag) in [
+        (DENY_UNMATCHED_ENV, "--deny-unmatched-pointcuts"),
+        (DENY_UNWOVEN_ENV, "--deny-unwoven"),
+    ] {
+        let set = env.iter().any(|(name, value)| *name == var && *value != "0")
+            || std::env::var_os(var).is_some_and(|value| value != "0");
+        if set {
+            return Ok(Some(flag));
+        }
+    }
+
+    let Ok(workspace) = Workspace::locate_for(args) else {
+        return Ok(None);
+    };
+    let Some((_, file)) = workspace_config(&workspace, config, None)? else {
+        return Ok(None);
+    };
+    let profile = profile.map(str::to_string).or_else(|| {
+        let name = cargo_profile(cmd, args);
+        file.profiles.contains_key(&name).then_some(name)
+    });
+    let file = match profile {
+        Some(profile) => file.profile(&profile).map_err(anyhow::Error::msg)?,
+        None => file,
+    };
+    Ok(if file.deny_unmatched == Some(true) {
+        Some("deny_unmatched of aspect.toml")
+    } else if file.deny_unwoven == Some(true) {
+        Some("deny_unwoven of aspect.toml")
+    } else {
+        None
+    })
+}
+
+/// The aspect.toml of the workspace, `config` if given, loaded with the
+/// settings of `profile`.
+fn workspace_config(
+    workspace: &Workspace,
+    config: Option<&Path>,
+    profile: Option<&str>,
+) -> Result<Option<(PathBuf, ConfigFile)>> {
+    let path = match config {
+        Some(path) => Some(
+            std::fs::canonicalize(path)
+                .with_context(|| format!("Cannot read {}", path.display()))?,
+        ),
+        None => ConfigFile::find(&workspace.root),
+    };
+    let Some(path) = path else {
+        if profile.is_some() {
+            anyhow::bail!("--aspect-profile requires an aspect.toml");
+        }
+        return Ok(None);
+    };
+    let mut file = ConfigFile::load(&path).map_err(anyhow::Error::msg)?;
+    if let Some(profile) = profile {
+        file = file.profile(profile).map_err(anyhow::Error::msg)?;
+    }
+    Ok(Some((path, file)))
+}
+
+/// The compiler driver: `$ASPECT_RUSTC_DRIVER`, or aspect-rustc-driver
+/// next to cargo-aspect or on the `PATH`.
+fn find_driver() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(DRIVER_ENV) {
+        return Some(PathBuf::from(path));
+    }
+    let name = format!("aspect-rustc-driver{}", std::env::consts::EXE_SUFFIX);
+    let next_to_exe = std::env::current_exe()
+        .ok()
+        .map(|exe| exe.with_file_name(&name));
+    let on_path: Vec<PathBuf> = std::env::var_os("PATH")
+        .map(|paths| {
+            std::env::split_paths(&paths)
+                .map(|dir| dir.join(&name))
+                .collect()
+        })
+        .unwrap_or_default();
+    next_to_exe
+        .into_iter()
+        .chain(on_path)
+        .find(|path| path.is_file())
+}
+
+/// Run a standard cargo command with the given arguments and environment
+fn run_cargo_command(cmd: &str, args: &[String], env: &[(&str, &str)]) -> Result<()> {
+    let status = Command::new("cargo")
+        .arg(cmd)
+        .args(args)
+        .envs(env.iter().copied())
         .status()
         .context("Failed to execute cargo")?;
 
@@ -260,8 +1700,212 @@ mod tests {
         // Just ensure CLI structure is valid
         let args = AspectArgs {
             command: None,
-            verbose: false,
+            verbose: 0,
+            quiet: false,
+            color: ColorChoice::Auto,
+            deny_unmatched: false,
+            deny_unwoven: false,
+            pointcut: vec![],
+            before: vec![],
+            after: vec![],
+            config: None,
+            profile: None,
+            format: OutputFormat::Human,
         };
-        assert!(!args.verbose);
+        assert_eq!(args.verbose, 0);
+    }
+
+    #[test]
+    fn test_deny_unmatched_flag() {
+        let cli = Cli::try_parse_from(["cargo", "aspect", "--deny-unmatched", "build"]).unwrap();
+        let CargoCommands::Aspect(args) = cli.command;
+        assert!(args.deny_unmatched);
+        assert_eq!(args.format, OutputFormat::Human);
+        assert!(matches!(args.command, Some(AspectCommand::Build { .. })));
+    }
+
+    #[test]
+    fn test_deny_policy_flags() {
+        let cli = Cli::try_parse_from([
+            "cargo",
+            "aspect",
+            "--deny-unmatched-pointcuts",
+            "--deny-unwoven",
+            "test",
+        ])
+        .unwrap();
+        let CargoCommands::Aspect(args) = cli.command;
+        assert!(args.deny_unmatched);
+        assert!(args.deny_unwoven);
+
+        let env = [(DENY_UNWOVEN_ENV, "1")];
+        assert_eq!(
+            deny_policy(&env, None, None, "build", &[]).unwrap(),
+            Some("--deny-unwoven")
+        );
+        let env = [(DENY_UNMATCHED_ENV, "0"), (DENY_UNWOVEN_ENV, "0")];
+        assert_eq!(deny_policy(&env, None, None, "build", &[]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_driver_flags() {
+        let cli = Cli::try_parse_from([
+            "cargo",
+            "aspect",
+            "--pointcut",
+            "execution(pub fn *(..))",
+            "--before",
+            "within(api)=crate::trace::enter",
+            "--profile",
+            "ci",
+            "--format",
+            "json",
+            "build",
+            "--release",
+        ])
+        .unwrap();
+        let CargoCommands::Aspect(args) = cli.command;
+        assert_eq!(
+            args.driver_flags(),
+            [
+                "--aspect-pointcut",
+                "execution(pub fn *(..))",
+                "--aspect-before",
+                "within(api)=crate::trace::enter",
+            ]
+        );
+        assert_eq!(args.profile.as_deref(), Some("ci"));
+        assert_eq!(args.format, OutputFormat::Json);
+        assert!(matches!(
+            args.command,
+            Some(AspectCommand::Build { args, .. }) if args == ["--release"]
+        ));
+    }
+
+    #[test]
+    fn test_cargo_profile() {
+        let args =
+            |args: &[&str]| -> Vec<String> { args.iter().map(|arg| arg.to_string()).collect() };
+        assert_eq!(cargo_profile("build", &[]), "dev");
+        assert_eq!(cargo_profile("build", &args(&["--release"])), "release");
+        assert_eq!(cargo_profile("bench", &[]), "release");
+        assert_eq!(
+            cargo_profile("build", &args(&["--profile", "ci", "--lib"])),
+            "ci"
+        );
+        assert_eq!(cargo_profile("check", &args(&["--profile=ci"])), "ci");
+        assert_eq!(cargo_profile("test", &args(&["--", "--release"])), "dev");
+
+        let build = args(&[
+            "--target-dir",
+            "out",
+            "--config=build.jobs=2",
+            "--config",
+            "profile.dev.debug=0",
+            "--",
+            "--target-dir=tests",
+        ]);
+        assert_eq!(cargo_option(&build, "--target-dir"), ["out"]);
+        assert_eq!(
+            cargo_option(&build, "--config"),
+            ["build.jobs=2", "profile.dev.debug=0"]
+        );
+        assert!(cargo_option(&build, "--target").is_empty());
+
+        let cli = Cli::try_parse_from(["cargo", "aspect", "--aspect-profile", "release", "build"])
+            .unwrap();
+        let CargoCommands::Aspect(args) = cli.command;
+        assert_eq!(args.profile.as_deref(), Some("release"));
+    }
+
+    #[test]
+    fn test_package_selection() {
+        let parse = |args: &[&str]| {
+            let cli = Cli::try_parse_from([&["cargo", "aspect"], args].concat())?;
+            let CargoCommands::Aspect(args) = cli.command;
+            Ok::<_, clap::Error>(args.command)
+        };
+
+        let Some(AspectCommand::Test { packages, args }) = parse(&[
+            "test",
+            "-p",
+            "shop",
+            "-F",
+            "tracing",
+            "--lib",
+            "--",
+            "--nocapture",
+        ])
+        .unwrap() else {
+            panic!("expected the test command");
+        };
+        assert_eq!(packages.packages, ["shop"]);
+        assert_eq!(args, ["--lib", "--", "--nocapture"]);
+        assert_eq!(
+            [packages.cargo_args(), args].concat(),
+            [
+                "--package",
+                "shop",
+                "--features",
+                "tracing",
+                "--lib",
+                "--",
+                "--nocapture"
+            ]
+        );
+        assert!(parse(&["info", "--exclude", "shop-cli"]).is_err());
+
+        let Some(AspectCommand::Info { packages, .. }) =
+            parse(&["info", "--workspace", "--exclude", "shop-cli@0.1.0"]).unwrap()
+        else {
+            panic!("expected the info command");
+        };
+        let workspace = Workspace {
+            root: PathBuf::from("/shop"),
+            target_dir: PathBuf::from("/shop/target"),
+            crate_roots: Vec::new(),
+            packages: BTreeMap::from([
+                ("shop".to_string(), vec!["shop".to_string()]),
+                ("shop-cli".to_string(), vec!["shop_cli".to_string()]),
+            ]),
+        };
+        let selected = packages.selected(&workspace).unwrap();
+        assert_eq!(selected, ["shop"]);
+        assert!(workspace.is_report_of("shop-lib", &selected));
+        assert!(!workspace.is_report_of("shop_cli-bin", &selected));
+        assert!(workspace.is_report_of("shop_cli-bin", &["shop-cli"]));
+        assert_eq!(PackageSelection::default().selected(&workspace), None);
+    }
+
+    #[test]
+    fn test_workspace_config() {
+        let root = std::env::temp_dir().join(format!("cargo-aspect-config-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let workspace = Workspace {
+            target_dir: root.join("target"),
+            crate_roots: Vec::new(),
+            packages: BTreeMap::new(),
+            root,
+        };
+        assert!(workspace_config(&workspace, None, None).unwrap().is_none());
+        assert!(workspace_config(&workspace, None, Some("ci")).is_err());
+
+        std::fs::write(
+            workspace.root.join("aspect.toml"),
+            "pointcuts = [\"name(get)\"]\n\n[profiles.ci]\npointcuts = [\"name(put)\"]\n",
+        )
+        .unwrap();
+        let (path, config) = workspace_config(&workspace, None, Some("ci"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(path, workspace.root.join("aspect.toml"));
+        assert_eq!(config.pointcuts, ["name(get)", "name(put)"]);
+        assert!(workspace_config(&workspace, None, Some("release")).is_err());
+        assert_eq!(
+            workspace.registry_dir(),
+            workspace.root.join("target/aspect/registry")
+        );
+
+        std::fs::remove_dir_all(&workspace.root).unwrap();
     }
 }
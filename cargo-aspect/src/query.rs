@@ -0,0 +1,191 @@
+//! `cargo aspect match`: the functions of the workspace a pointcut matches.
+//!
+//! The workspace is analyzed like by `cargo aspect info`, and the pointcut
+//! given on the command line is matched against every function, so that
+//! a pointcut can be tried out before it is written into `#[advice]` or
+//! aspect.toml. When nothing matches, the clauses that match nothing are
+//! shown with the closest names, modules or attributes there are.
+
+use anyhow::Result;
+use aspect_driver::lint::check_syntax;
+use aspect_driver::r#match::PointcutMatcher;
+use aspect_driver::report::AnalysisReport;
+use aspect_driver::types::FunctionMetadata;
+use aspect_driver::unmatched::find_unmatched;
+
+use super::{analyze_workspace, functions_in, print_json, OutputFormat, Workspace};
+
+/// The functions of one crate, split by whether the pointcut matches them.
+struct CrateMatches<'a> {
+    name: &'a str,
+    matched: Vec<&'a FunctionMetadata>,
+
+    /// The other functions, with why the pointcut does not match them
+    unmatched: Vec<(&'a FunctionMetadata, String)>,
+}
+
+/// Match `pointcut` against the functions of the crates of `reports`,
+/// those in `module` only if given.
+fn match_crates<'a>(
+    reports: &'a [(String, AnalysisReport)],
+    pointcut: &str,
+    module: Option<&str>,
+) -> Vec<CrateMatches<'a>> {
+    let matcher = PointcutMatcher::new();
+    reports
+        .iter()
+        .map(|(name, report)| {
+            let mut matches = CrateMatches {
+                name,
+                matched: Vec::new(),
+                unmatched: Vec::new(),
+            };
+            for function in functions_in(report, module) {
+                match matcher.mismatch_reason(function, pointcut) {
+                    None => matches.matched.push(function),
+                    Some(reason) => matches.unmatched.push((function, reason)),
+                }
+            }
+            matches
+        })
+        .collect()
+}
+
+/// Print the functions `pointcut` matches; with `explain`, why it does not
+/// match the others too.
+pub fn run(
+    pointcut: &str,
+    module: Option<&str>,
+    explain: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    if let Err(error) = check_syntax(pointcut) {
+        let mut message = format!("invalid pointcut\n\n{}", error.snippet(pointcut));
+        if let Some(help) = &error.help {
+            message.push_str(&format!("\n\nhelp: {}", help));
+        }
+        anyhow::bail!(message);
+    }
+
+    let workspace = Workspace::locate()?;
+    let reports = analyze_workspace(&workspace, &[])?;
+    let crates = match_crates(&reports, pointcut, module);
+    let scanned: usize = crates
+        .iter()
+        .map(|matches| matches.matched.len() + matches.unmatched.len())
+        .sum();
+    let matched: usize = crates.iter().map(|matches| matches.matched.len()).sum();
+
+    // Near misses, among all the functions of the workspace
+    let functions: Vec<FunctionMetadata> = reports
+        .iter()
+        .flat_map(|(_, report)| report.functions.iter().cloned())
+        .collect();
+    let near_misses = if matched == 0 {
+        find_unmatched(&functions, &[pointcut.to_string()], &[])
+            .into_iter()
+            .flat_map(|unmatched| unmatched.near_misses)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    if format == OutputFormat::Json {
+        let crates: Vec<_> = crates
+            .iter()
+            .map(|matches| {
+                let mut json = serde_json::json!({
+                    "name": matches.name,
+                    "matched": matches.matched,
+                });
+                if explain {
+                    json["unmatched"] = matches
+                        .unmatched
+                        .iter()
+                        .map(|(function, reason)| {
+                            serde_json::json!({ "name": function.name, "reason": reason })
+                        })
+                        .collect();
+                }
+                json
+            })
+            .collect();
+        return print_json(&serde_json::json!({
+            "pointcut": pointcut,
+            "module": module,
+            "crates": crates,
+            "total": { "functions": scanned, "matched": matched },
+            "near_misses": near_misses,
+        }));
+    }
+
+    for matches in &crates {
+        if matches.matched.is_empty() && !explain {
+            continue;
+        }
+        println!("{}:", matches.name);
+        for function in &matches.matched {
+            let visibility = function.visibility.to_string();
+            println!(
+                "  {}{}fn {}  ({}:{})",
+                visibility,
+                if visibility.is_empty() { "" } else { " " },
+                function.name,
+                function.location.file,
+                function.location.line
+            );
+        }
+        if explain {
+            for (function, reason) in &matches.unmatched {
+                println!("  - {}: {}", function.name, reason);
+            }
+        }
+        println!();
+    }
+
+    println!("{} of {} functions match `{}`", matched, scanned, pointcut);
+    for near_miss in &near_misses {
+        for suggestion in &near_miss.suggestions {
+            println!(
+                "  help: `{}` matches nothing; did you mean `{}`?",
+                near_miss.clause, suggestion
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_driver::syntax::analyze_source;
+
+    #[test]
+    fn test_match_crates() {
+        let source = "pub fn get() {}\nfn helper() {}\npub mod api { pub fn list() {} }";
+        let functions = analyze_source(source, "src/lib.rs", "crate").unwrap();
+        let reports = vec![(
+            "shop-lib".to_string(),
+            AnalysisReport::new(&[], functions, &[]),
+        )];
+
+        let crates = match_crates(&reports, "execution(pub fn *(..))", None);
+        let names = |functions: &[&FunctionMetadata]| -> Vec<String> {
+            functions
+                .iter()
+                .map(|function| function.name.clone())
+                .collect()
+        };
+        assert_eq!(names(&crates[0].matched), ["get", "api::list"]);
+        assert_eq!(crates[0].unmatched.len(), 1);
+        assert_eq!(crates[0].unmatched[0].0.name, "helper");
+        assert_eq!(
+            crates[0].unmatched[0].1,
+            "does not match execution(pub fn *(..))"
+        );
+
+        let crates = match_crates(&reports, "execution(pub fn *(..))", Some("crate::api"));
+        assert_eq!(names(&crates[0].matched), ["api::list"]);
+        assert!(crates[0].unmatched.is_empty());
+    }
+}
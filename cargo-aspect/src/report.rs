@@ -0,0 +1,609 @@
+//! `cargo aspect report`: which aspects apply to the workspace, and to how
+//! much of it, as a summary or a static HTML page.
+//!
+//! The workspace is analyzed like by `cargo aspect info`, and its weaving
+//! planned like by `cargo aspect graph`: the aspects of aspect.toml and the
+//! pointcuts of `#[advice]`, aspect.toml and `--pointcut`. The runs of the
+//! aspects come from the coverage the last `cargo aspect test` recorded.
+//! With `--html`, the report is a single file with its style inline, which
+//! CI can publish as it is.
+
+use anyhow::{Context, Result};
+use aspect_driver::plan::WeavingPlan;
+use aspect_driver::r#match::RegisteredAspect;
+use aspect_driver::report::AnalysisReport;
+use log::info;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use super::coverage;
+use super::graph::{crate_prefix, prefixed_module, workspace_plan};
+use super::{
+    analyze_workspace, collect_pointcuts, functions_in, load_registry, workspace_config,
+    PackageSelection, Workspace,
+};
+
+/// Settings of `cargo aspect report`.
+pub struct Report<'a> {
+    pub packages: &'a PackageSelection,
+
+    /// Only the functions of this module
+    pub module: Option<&'a str>,
+
+    /// Write an HTML page rather than print a summary
+    pub html: bool,
+
+    /// File to write the HTML page to, `target/aspect/report/index.html`
+    /// if not given
+    pub output: Option<&'a Path>,
+
+    pub config: Option<&'a Path>,
+    pub profile: Option<&'a str>,
+    pub pointcuts: &'a [String],
+}
+
+impl Report<'_> {
+    /// Analyze the workspace and report on its aspects.
+    pub fn run(&self) -> Result<()> {
+        let workspace = Workspace::locate()?;
+        let mut reports = analyze_workspace(&workspace, &self.packages.cargo_args())?;
+        if let Some(selected) = self.packages.selected(&workspace) {
+            reports.retain(|(name, _)| workspace.is_report_of(name, &selected));
+        }
+        let config = workspace_config(&workspace, self.config, self.profile)?;
+        let config = config.as_ref().map(|(_, file)| file);
+        let registered = load_registry(&workspace)?;
+
+        let hooks = match config {
+            Some(file) => file.advice_hooks().map_err(anyhow::Error::msg)?,
+            None => Vec::new(),
+        };
+        let pointcuts: Vec<String> = collect_pointcuts(&registered, config, self.pointcuts)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        let plan = workspace_plan(&reports, self.module, &pointcuts, &hooks, &registered);
+
+        // Without a coverage directory, no test ran with cargo aspect test
+        let coverage_dir = workspace.target_dir.join("aspect").join("coverage");
+        let executed = coverage_dir
+            .exists()
+            .then(|| coverage::read_executed(&coverage_dir))
+            .transpose()?;
+        let contents = Contents::new(&reports, self.module, &plan, &registered, executed);
+
+        if !self.html {
+            print!("{}", contents.to_text());
+            return Ok(());
+        }
+        let path = match self.output {
+            Some(path) => path.to_path_buf(),
+            None => default_output(&workspace.target_dir),
+        };
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Cannot create {}", dir.display()))?;
+        }
+        std::fs::write(&path, contents.to_html())
+            .with_context(|| format!("Cannot write {}", path.display()))?;
+        info!("Report written to {}", path.display());
+        Ok(())
+    }
+}
+
+/// Where `--html` writes the report by default.
+fn default_output(target_dir: &Path) -> PathBuf {
+    target_dir.join("aspect").join("report").join("index.html")
+}
+
+/// A function of the report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FunctionRow {
+    /// Name within its module
+    name: String,
+
+    visibility: String,
+
+    /// `file:line`
+    location: String,
+
+    /// The advice applied to the function (`before crate::trace::enter`),
+    /// in the order it runs
+    aspects: Vec<String>,
+
+    /// Why advice matching the function is not woven, if it is not
+    unwoven: Option<String>,
+}
+
+/// A pointcut of the report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PointcutRow {
+    pointcut: String,
+
+    /// The advice of the pointcut, empty for analysis-only pointcuts
+    advice: Vec<String>,
+
+    /// Functions matched and woven
+    matched: usize,
+
+    /// Functions matched but not woven
+    unwoven: usize,
+}
+
+/// An aspect `#[advice]` registered, with the number of test processes it
+/// ran in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AspectRow {
+    name: String,
+    advice_type: String,
+    pointcut: String,
+    runs: usize,
+}
+
+/// What the report shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Contents {
+    crates: usize,
+
+    /// Functions by module, the modules named after their crate
+    modules: BTreeMap<String, Vec<FunctionRow>>,
+
+    pointcuts: Vec<PointcutRow>,
+
+    /// `None` when no `cargo aspect test` recorded coverage
+    aspects: Option<Vec<AspectRow>>,
+
+    /// Aspects that ran in tests without being registered by `#[advice]`
+    other_aspects: Vec<String>,
+}
+
+impl Contents {
+    fn new(
+        reports: &[(String, AnalysisReport)],
+        module: Option<&str>,
+        plan: &WeavingPlan,
+        registered: &[RegisteredAspect],
+        executed: Option<BTreeMap<String, usize>>,
+    ) -> Self {
+        // The advice of each function, from the pointcuts with advice
+        let mut aspects: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for pointcut in &plan.pointcuts {
+            for function in &pointcut.matched {
+                let applied = aspects.entry(function.function.as_str()).or_default();
+                for advice in &pointcut.advice {
+                    let label = format!("{} {}", advice.advice_type, advice.hook);
+                    if !applied.contains(&label) {
+                        applied.push(label);
+                    }
+                }
+            }
+        }
+        let unwoven = plan.unwoven();
+
+        let mut modules: BTreeMap<String, Vec<FunctionRow>> = BTreeMap::new();
+        let mut crates = BTreeSet::new();
+        for (name, report) in reports {
+            let prefix = crate_prefix(reports, name);
+            crates.insert(prefix);
+            for function in functions_in(report, module) {
+                let module_path = prefixed_module(prefix, &function.module_path);
+                let full_name = format!("{}::{}", prefix, function.name);
+                let name = full_name
+                    .strip_prefix(&format!("{}::", module_path))
+                    .unwrap_or(&full_name)
+                    .to_string();
+                modules.entry(module_path).or_default().push(FunctionRow {
+                    name,
+                    visibility: function.visibility.to_string(),
+                    location: format!("{}:{}", function.location.file, function.location.line),
+                    aspects: aspects.get(full_name.as_str()).cloned().unwrap_or_default(),
+                    unwoven: unwoven
+                        .get(full_name.as_str())
+                        .map(|reason| reason.to_string()),
+                });
+            }
+        }
+
+        let pointcuts = plan
+            .pointcuts
+            .iter()
+            .map(|pointcut| PointcutRow {
+                pointcut: pointcut.pointcut.clone(),
+                advice: pointcut
+                    .advice
+                    .iter()
+                    .map(|advice| format!("{} {}", advice.advice_type, advice.hook))
+                    .collect(),
+                matched: pointcut.matched.len(),
+                unwoven: pointcut
+                    .excluded
+                    .iter()
+                    .filter(|exclusion| unwoven.contains_key(exclusion.function.as_str()))
+                    .count(),
+            })
+            .collect();
+
+        let (aspects, other_aspects) = match &executed {
+            Some(executed) => {
+                let (covered, others) = coverage::coverage(registered, executed);
+                let rows = covered
+                    .into_iter()
+                    .map(|(aspect, runs)| AspectRow {
+                        name: aspect.aspect_name.clone(),
+                        advice_type: aspect.advice_type.to_string(),
+                        pointcut: aspect.pointcut.clone(),
+                        runs,
+                    })
+                    .collect();
+                (Some(rows), others.into_iter().map(str::to_string).collect())
+            }
+            None => (None, Vec::new()),
+        };
+
+        Self {
+            crates: crates.len(),
+            modules,
+            pointcuts,
+            aspects,
+            other_aspects,
+        }
+    }
+
+    fn functions(&self) -> usize {
+        self.modules.values().map(Vec::len).sum()
+    }
+
+    fn advised(&self) -> usize {
+        self.modules
+            .values()
+            .flatten()
+            .filter(|function| !function.aspects.is_empty())
+            .count()
+    }
+
+    /// The report as printed on standard output.
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        let functions = self.functions();
+        let _ = writeln!(text, "=== Aspect Report ===");
+        let _ = writeln!(
+            text,
+            "{} functions in {} modules of {} crates, {} with aspects ({})",
+            functions,
+            self.modules.len(),
+            self.crates,
+            self.advised(),
+            percent(self.advised(), functions)
+        );
+
+        if !self.pointcuts.is_empty() {
+            let _ = writeln!(text, "\nPointcuts:");
+        }
+        for pointcut in &self.pointcuts {
+            let _ = writeln!(
+                text,
+                "  {}: {} functions ({})",
+                pointcut.pointcut,
+                pointcut.matched,
+                percent(pointcut.matched, functions)
+            );
+            for advice in &pointcut.advice {
+                let _ = writeln!(text, "     {}", advice);
+            }
+            if pointcut.unwoven > 0 {
+                let _ = writeln!(text, "     {} matched but not woven", pointcut.unwoven);
+            }
+        }
+
+        let _ = writeln!(text, "\nModules:");
+        for (module, functions) in &self.modules {
+            let advised = functions.iter().filter(|f| !f.aspects.is_empty()).count();
+            let _ = writeln!(
+                text,
+                "  {}: {} of {} functions with aspects ({})",
+                module,
+                advised,
+                functions.len(),
+                percent(advised, functions.len())
+            );
+        }
+
+        let _ = writeln!(text, "\nAspects in tests:");
+        match &self.aspects {
+            None => {
+                let _ = writeln!(text, "  no coverage recorded; run cargo aspect test");
+            }
+            Some(aspects) => {
+                for aspect in aspects {
+                    let _ = writeln!(
+                        text,
+                        "  {} ({}): {}",
+                        aspect.name,
+                        aspect.advice_type,
+                        runs(aspect.runs)
+                    );
+                }
+                if !self.other_aspects.is_empty() {
+                    let _ = writeln!(text, "  also ran: {}", self.other_aspects.join(", "));
+                }
+            }
+        }
+        text
+    }
+
+    /// The report as a self-contained HTML page.
+    fn to_html(&self) -> String {
+        let mut html = String::new();
+        let functions = self.functions();
+        html.push_str(HTML_HEAD);
+        let _ = writeln!(
+            html,
+            "<p class=\"summary\">{} functions in {} modules of {} crates, \
+             <strong>{} with aspects ({})</strong></p>",
+            functions,
+            self.modules.len(),
+            self.crates,
+            self.advised(),
+            percent(self.advised(), functions)
+        );
+
+        html.push_str("<h2>Pointcuts</h2>\n");
+        html.push_str(
+            "<table>\n<tr><th>Pointcut</th><th>Advice</th><th>Functions</th>\
+             <th>Coverage</th><th>Not woven</th></tr>\n",
+        );
+        for pointcut in &self.pointcuts {
+            let _ = writeln!(
+                html,
+                "<tr><td><code>{}</code></td><td>{}</td><td class=\"number\">{}</td>\
+                 <td>{}</td><td class=\"number\">{}</td></tr>",
+                escape_html(&pointcut.pointcut),
+                list(&pointcut.advice),
+                pointcut.matched,
+                bar(pointcut.matched, functions),
+                pointcut.unwoven
+            );
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>Aspects in tests</h2>\n");
+        match &self.aspects {
+            None => html.push_str(
+                "<p>No coverage recorded: run <code>cargo aspect test</code> first.</p>\n",
+            ),
+            Some(aspects) => {
+                html.push_str(
+                    "<table>\n<tr><th>Aspect</th><th>Advice</th><th>Pointcut</th>\
+                     <th>Test processes</th></tr>\n",
+                );
+                for aspect in aspects {
+                    let _ = writeln!(
+                        html,
+                        "<tr{}><td>{}</td><td>{}</td><td><code>{}</code></td>\
+                         <td class=\"number\">{}</td></tr>",
+                        if aspect.runs == 0 {
+                            " class=\"never\""
+                        } else {
+                            ""
+                        },
+                        escape_html(&aspect.name),
+                        escape_html(&aspect.advice_type),
+                        escape_html(&aspect.pointcut),
+                        aspect.runs
+                    );
+                }
+                html.push_str("</table>\n");
+                if !self.other_aspects.is_empty() {
+                    let _ = writeln!(
+                        html,
+                        "<p>Also ran: {}</p>",
+                        escape_html(&self.other_aspects.join(", "))
+                    );
+                }
+            }
+        }
+
+        html.push_str("<h2>Modules</h2>\n");
+        for (module, functions) in &self.modules {
+            let advised = functions.iter().filter(|f| !f.aspects.is_empty()).count();
+            let _ = writeln!(
+                html,
+                "<details>\n<summary><code>{}</code> {} of {} functions {}</summary>",
+                escape_html(module),
+                advised,
+                functions.len(),
+                bar(advised, functions.len())
+            );
+            html.push_str(
+                "<table>\n<tr><th>Function</th><th>Visibility</th><th>Location</th>\
+                 <th>Aspects</th></tr>\n",
+            );
+            for function in functions {
+                let mut aspects = list(&function.aspects);
+                if let Some(reason) = &function.unwoven {
+                    let _ = write!(
+                        aspects,
+                        "<span class=\"never\">not woven: {}</span>",
+                        escape_html(reason)
+                    );
+                }
+                let _ = writeln!(
+                    html,
+                    "<tr><td><code>{}</code></td><td><code>{}</code></td><td>{}</td>\
+                     <td>{}</td></tr>",
+                    escape_html(&function.name),
+                    escape_html(&function.visibility),
+                    escape_html(&function.location),
+                    aspects
+                );
+            }
+            html.push_str("</table>\n</details>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Aspect Report</title>
+<style>
+body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 70em; color: #222; }
+table { border-collapse: collapse; margin: 0.5em 0 1.5em; width: 100%; }
+th, td { border-bottom: 1px solid #ddd; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+th { background: #f4f4f4; }
+td.number { text-align: right; }
+code { font-size: 0.9em; }
+summary { cursor: pointer; padding: 0.3em 0; }
+.bar { display: inline-block; width: 6em; height: 0.7em; background: #eee; margin-right: 0.4em; }
+.bar span { display: block; height: 100%; background: #4a8; }
+.never { color: #b33; }
+.advice { display: block; }
+</style>
+</head>
+<body>
+<h1>Aspect Report</h1>
+"#;
+
+/// `part` of `total` as a percentage.
+fn percent(part: usize, total: usize) -> String {
+    if total == 0 {
+        return "0%".to_string();
+    }
+    format!("{:.1}%", part as f64 * 100.0 / total as f64)
+}
+
+/// `part` of `total` as a bar and a percentage.
+fn bar(part: usize, total: usize) -> String {
+    let width = if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    };
+    format!(
+        "<span class=\"bar\"><span style=\"width: {:.0}%\"></span></span>{}",
+        width,
+        percent(part, total)
+    )
+}
+
+/// The advice of a pointcut or a function, one per line.
+fn list(advice: &[String]) -> String {
+    advice
+        .iter()
+        .map(|advice| format!("<code class=\"advice\">{}</code>", escape_html(advice)))
+        .collect()
+}
+
+fn runs(runs: usize) -> String {
+    match runs {
+        0 => "never ran".to_string(),
+        1 => "ran in 1 test process".to_string(),
+        runs => format!("ran in {} test processes", runs),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_driver::r#match::{AdviceHook, AdviceType};
+    use aspect_driver::syntax::analyze_source;
+
+    #[test]
+    fn test_contents() {
+        let functions = analyze_source(
+            "pub fn get() {}\nfn helper() {}\npub mod api { pub fn list() {} pub async fn sync() {} }",
+            "src/lib.rs",
+            "crate",
+        )
+        .unwrap();
+        let reports = [(
+            "shop-lib".to_string(),
+            AnalysisReport::new(&[], functions, &[]),
+        )];
+        let hooks = [AdviceHook::parse(
+            AdviceType::Before,
+            "execution(pub fn *(..))=crate::trace::enter",
+        )
+        .unwrap()];
+        let registered = [RegisteredAspect {
+            aspect_name: "api_logger".to_string(),
+            pointcut: "name(helper)".to_string(),
+            advice_type: AdviceType::Around,
+            priority: 0,
+        }];
+        let pointcuts = ["name(helper)".to_string()];
+        let plan = workspace_plan(&reports, None, &pointcuts, &hooks, &registered);
+
+        let contents = Contents::new(&reports, None, &plan, &registered, None);
+        assert_eq!(contents.crates, 1);
+        assert_eq!(contents.functions(), 4);
+        assert_eq!(contents.advised(), 3);
+        let shop = &contents.modules["shop"];
+        assert_eq!(shop[1].name, "helper");
+        assert_eq!(shop[1].aspects, ["around api_logger"]);
+        assert_eq!(shop[1].location, "src/lib.rs:2");
+        let api = &contents.modules["shop::api"];
+        assert_eq!(api[0].aspects, ["before crate::trace::enter"]);
+        assert!(api[1].aspects.is_empty());
+        assert!(api[1].unwoven.is_some());
+        assert_eq!(
+            contents.pointcuts[1],
+            PointcutRow {
+                pointcut: "execution(pub fn *(..))".to_string(),
+                advice: vec!["before crate::trace::enter".to_string()],
+                matched: 2,
+                unwoven: 1,
+            }
+        );
+
+        let text = contents.to_text();
+        assert!(text.contains("4 functions in 2 modules of 1 crates, 3 with aspects (75.0%)\n"));
+        assert!(text.contains("  shop::api: 1 of 2 functions with aspects (50.0%)\n"));
+        assert!(text.contains("no coverage recorded"));
+
+        let executed = BTreeMap::from([("audit".to_string(), 1)]);
+        let contents = Contents::new(&reports, None, &plan, &registered, Some(executed));
+        assert_eq!(contents.aspects.as_ref().unwrap()[0].runs, 0);
+        assert_eq!(contents.other_aspects, ["audit"]);
+        let html = contents.to_html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<td><code>execution(pub fn *(..))</code></td>"));
+        assert!(html.contains("<tr class=\"never\"><td>api_logger</td>"));
+        assert!(html.contains("<summary><code>shop::api</code> 1 of 2 functions"));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn test_html_helpers() {
+        assert_eq!(
+            escape_html("call<T>(&\"x\")"),
+            "call&lt;T&gt;(&amp;&quot;x&quot;)"
+        );
+        assert_eq!(percent(1, 3), "33.3%");
+        assert_eq!(percent(0, 0), "0%");
+        assert!(bar(1, 4).starts_with("<span class=\"bar\"><span style=\"width: 25%\">"));
+        assert_eq!(
+            default_output(Path::new("/shop/target")),
+            Path::new("/shop/target/aspect/report/index.html")
+        );
+    }
+}
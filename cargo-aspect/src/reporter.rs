@@ -0,0 +1,274 @@
+//! Diagnostics of cargo-aspect, on standard error.
+//!
+//! Diagnostics go through the `log` macros, and the reporter installed by
+//! [`init`] writes them like cargo does: errors and warnings with a
+//! colored `error:` or `warning:`, notes as they are, and the details of
+//! `--verbose` dimmed. Command output, such as the tables of `info` or
+//! the JSON of `--format json`, is written to standard output as before.
+//!
+//! Which diagnostics are written depends on `--quiet` and `--verbose`,
+//! and on the `ASPECT_LOG` filter, which takes precedence over them: a
+//! comma-separated list of levels (`warn`) and module levels
+//! (`wrapper=trace`, `watch=off`), like `RUST_LOG`. Modules are those of
+//! cargo-aspect, without `cargo_aspect::`.
+
+use anstyle::{AnsiColor, Style};
+use clap::ValueEnum;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::io::{IsTerminal, Write};
+
+/// Filter of the diagnostics, overriding `--quiet` and `--verbose`.
+pub const LOG_ENV: &str = "ASPECT_LOG";
+
+/// Whether diagnostics are colored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// When standard error is a terminal, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color diagnostics. `Auto` follows `CARGO_TERM_COLOR`
+    /// if set, like the cargo commands cargo-aspect runs.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => match std::env::var("CARGO_TERM_COLOR").as_deref() {
+                Ok("always") => true,
+                Ok("never") => false,
+                _ => {
+                    std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                        && std::io::stderr().is_terminal()
+                }
+            },
+        }
+    }
+
+    /// The choice as the value of `--color` and `CARGO_TERM_COLOR`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ColorChoice::Auto => "auto",
+            ColorChoice::Always => "always",
+            ColorChoice::Never => "never",
+        }
+    }
+}
+
+/// The most detailed level written, overall and by module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    default: LevelFilter,
+
+    /// Module and level, the last matching one applies
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    /// Filter of `--quiet` (warnings and errors only) and of `--verbose`
+    /// given `verbose` times (`-v` for details, `-vv` for everything).
+    pub fn new(quiet: bool, verbose: u8) -> Self {
+        let default = match (quiet, verbose) {
+            (true, _) => LevelFilter::Warn,
+            (false, 0) => LevelFilter::Info,
+            (false, 1) => LevelFilter::Debug,
+            (false, _) => LevelFilter::Trace,
+        };
+        Self {
+            default,
+            modules: Vec::new(),
+        }
+    }
+
+    /// Apply the directives of an `ASPECT_LOG` filter: `level` sets the
+    /// level of every module, `module=level` that of a module and its
+    /// submodules, and a module alone writes all of its diagnostics.
+    pub fn apply(&mut self, spec: &str) -> Result<(), String> {
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (module, level) = match directive.split_once('=') {
+                Some((module, level)) => (Some(module.trim()), parse_level(level.trim())?),
+                None => match parse_level(directive) {
+                    Ok(level) => (None, level),
+                    Err(_) => (Some(directive), LevelFilter::Trace),
+                },
+            };
+            match module {
+                Some(module) => {
+                    let module = module.strip_prefix("cargo_aspect::").unwrap_or(module);
+                    self.modules.push((module.to_string(), level));
+                }
+                None => self.default = level,
+            }
+        }
+        Ok(())
+    }
+
+    /// The most detailed level written for `target`, a module path.
+    pub fn level(&self, target: &str) -> LevelFilter {
+        let target = target.strip_prefix("cargo_aspect::").unwrap_or(target);
+        self.modules
+            .iter()
+            .rev()
+            .find(|(module, _)| {
+                target == module
+                    || target
+                        .strip_prefix(module.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level.parse().map_err(|_| {
+        format!(
+            "unknown level `{}`; expected off, error, warn, info, debug or trace",
+            level
+        )
+    })
+}
+
+struct Reporter {
+    filter: Filter,
+    color: bool,
+}
+
+impl Log for Reporter {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let line = format_line(record.level(), &record.args().to_string(), self.color);
+            let _ = writeln!(std::io::stderr().lock(), "{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+/// A diagnostic as written on standard error.
+fn format_line(level: Level, message: &str, color: bool) -> String {
+    let (prefix, style) = match level {
+        Level::Error => (Some("error:"), AnsiColor::Red.on_default().bold()),
+        Level::Warn => (Some("warning:"), AnsiColor::Yellow.on_default().bold()),
+        Level::Info => (None, Style::new()),
+        Level::Debug | Level::Trace => (None, Style::new().dimmed()),
+    };
+    let style = if color { style } else { Style::new() };
+    match prefix {
+        Some(prefix) => format!("{style}{prefix}{style:#} {message}"),
+        None => format!("{style}{message}{style:#}"),
+    }
+}
+
+/// Install the reporter, for `--quiet`, `--verbose` and `--color`, and
+/// `ASPECT_LOG` if set. An invalid `ASPECT_LOG` is reported and ignored.
+pub fn init(quiet: bool, verbose: u8, color: ColorChoice) {
+    let mut filter = Filter::new(quiet, verbose);
+    let invalid = match std::env::var(LOG_ENV) {
+        Ok(spec) => {
+            let mut with_env = filter.clone();
+            match with_env.apply(&spec) {
+                Ok(()) => {
+                    filter = with_env;
+                    None
+                }
+                Err(e) => Some(e),
+            }
+        }
+        Err(_) => None,
+    };
+
+    log::set_max_level(filter.max_level());
+    let _ = log::set_boxed_logger(Box::new(Reporter {
+        filter,
+        color: color.enabled(),
+    }));
+    if let Some(e) = invalid {
+        log::warn!("ignoring {}: {}", LOG_ENV, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        assert_eq!(
+            Filter::new(true, 2).level("cargo_aspect"),
+            LevelFilter::Warn
+        );
+        assert_eq!(
+            Filter::new(false, 0).level("cargo_aspect"),
+            LevelFilter::Info
+        );
+        assert_eq!(
+            Filter::new(false, 1).level("cargo_aspect"),
+            LevelFilter::Debug
+        );
+        assert_eq!(
+            Filter::new(false, 3).level("cargo_aspect"),
+            LevelFilter::Trace
+        );
+
+        let mut filter = Filter::new(false, 1);
+        filter
+            .apply("warn, wrapper=trace,cargo_aspect::watch=off,diff")
+            .unwrap();
+        assert_eq!(filter.level("cargo_aspect"), LevelFilter::Warn);
+        assert_eq!(filter.level("cargo_aspect::wrapper"), LevelFilter::Trace);
+        assert_eq!(filter.level("cargo_aspect::wrapperx"), LevelFilter::Warn);
+        assert_eq!(filter.level("cargo_aspect::watch::poll"), LevelFilter::Off);
+        assert_eq!(filter.level("cargo_aspect::diff"), LevelFilter::Trace);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+
+        filter.apply("wrapper=error").unwrap();
+        assert_eq!(filter.level("cargo_aspect::wrapper"), LevelFilter::Error);
+        assert_eq!(
+            filter.apply("watch=loud"),
+            Err(
+                "unknown level `loud`; expected off, error, warn, info, debug or trace".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_line() {
+        assert_eq!(
+            format_line(Level::Error, "cargo check failed", false),
+            "error: cargo check failed"
+        );
+        assert_eq!(
+            format_line(Level::Warn, "skipping src/bad.rs", false),
+            "warning: skipping src/bad.rs"
+        );
+        assert_eq!(
+            format_line(Level::Debug, "Running: cargo build", false),
+            "Running: cargo build"
+        );
+        assert_eq!(
+            format_line(Level::Warn, "skipping", true),
+            "\x1b[1m\x1b[33mwarning:\x1b[0m skipping"
+        );
+        assert_eq!(
+            format_line(Level::Trace, "details", true),
+            "\x1b[2mdetails\x1b[0m"
+        );
+        assert_eq!(format_line(Level::Info, "note", true), "note");
+    }
+}
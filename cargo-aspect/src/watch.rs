@@ -0,0 +1,325 @@
+//! `cargo aspect watch`: the analysis again on every change.
+//!
+//! The sources of the workspace (`*.rs`, `Cargo.toml` and `aspect.toml`)
+//! are polled for changes, which needs no file-system notification
+//! support. After each change, the workspace is analyzed again like by
+//! `cargo aspect info`, and the functions that pointcuts newly match or no
+//! longer match are printed.
+
+use anyhow::Result;
+use log::{error, warn};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use super::{
+    analyze_workspace, collect_pointcuts, load_registry, run_woven_cargo_command, workspace_config,
+    Workspace,
+};
+use aspect_driver::r#match::PointcutMatcher;
+use aspect_driver::report::AnalysisReport;
+
+/// Settings of `cargo aspect watch`.
+pub struct Watch<'a> {
+    /// cargo-aspect commands to run after each analysis (`check`, `test`)
+    pub commands: Vec<&'static str>,
+
+    /// How often the sources are polled
+    pub interval: Duration,
+
+    pub config: Option<&'a Path>,
+    pub profile: Option<&'a str>,
+
+    /// Pointcuts of `--pointcut`
+    pub pointcuts: &'a [String],
+
+    pub driver_flags: &'a [String],
+    pub driver_env: &'a [(&'a str, &'a str)],
+}
+
+impl Watch<'_> {
+    /// Watch until interrupted.
+    pub fn run(&self) -> Result<()> {
+        let workspace = Workspace::locate()?;
+        let mut sources = Sources::scan(&workspace);
+        let mut previous: Option<Matches> = None;
+
+        loop {
+            // Errors, such as code that does not compile, are reported and
+            // the watch goes on
+            match self.analyze(&workspace) {
+                Ok(matches) => {
+                    match &previous {
+                        Some(previous) => print_changes(&previous.changes(&matches)),
+                        None => println!(
+                            "{} functions, {} matched by {} pointcuts",
+                            matches.functions,
+                            matches.matched(),
+                            matches.pointcuts.len()
+                        ),
+                    }
+                    for pointcut in matches.unmatched() {
+                        if previous
+                            .as_ref()
+                            .is_none_or(|p| !p.unmatched().contains(&pointcut))
+                        {
+                            warn!("pointcut `{}` matches no function", pointcut);
+                        }
+                    }
+                    previous = Some(matches);
+                }
+                Err(e) => error!("{:#}", e),
+            }
+            for command in &self.commands {
+                if let Err(e) = run_woven_cargo_command(
+                    command,
+                    &[],
+                    self.driver_flags,
+                    self.config,
+                    self.profile,
+                    self.driver_env,
+                ) {
+                    error!("{:#}", e);
+                }
+            }
+
+            println!();
+            println!("Watching for changes (Ctrl+C to stop)...");
+            let changed = loop {
+                std::thread::sleep(self.interval);
+                let current = Sources::scan(&workspace);
+                let changed = sources.changed(&current);
+                if !changed.is_empty() {
+                    sources = current;
+                    break changed;
+                }
+            };
+            println!();
+            for path in changed {
+                let path = path.strip_prefix(&workspace.root).unwrap_or(&path);
+                println!("Changed: {}", path.display());
+            }
+        }
+    }
+
+    fn analyze(&self, workspace: &Workspace) -> Result<Matches> {
+        let reports = analyze_workspace(workspace, &[])?;
+        let config = workspace_config(workspace, self.config, self.profile)?;
+        let registered = load_registry(workspace)?;
+        let pointcuts = collect_pointcuts(
+            &registered,
+            config.as_ref().map(|(_, file)| file),
+            self.pointcuts,
+        );
+        Ok(Matches::new(&reports, &pointcuts))
+    }
+}
+
+/// Modification times of the sources of the workspace.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Sources(BTreeMap<PathBuf, SystemTime>);
+
+impl Sources {
+    /// Unreadable files are skipped: they may be removed while scanning.
+    fn scan(workspace: &Workspace) -> Self {
+        Sources(
+            workspace
+                .source_files()
+                .into_iter()
+                .filter_map(|path| {
+                    let modified = fs::metadata(&path).ok()?.modified().ok()?;
+                    Some((path, modified))
+                })
+                .collect(),
+        )
+    }
+
+    /// Files added, modified or removed since `self`.
+    fn changed(&self, current: &Sources) -> Vec<PathBuf> {
+        let mut changed: BTreeSet<&PathBuf> = BTreeSet::new();
+        for (path, modified) in &current.0 {
+            if self.0.get(path) != Some(modified) {
+                changed.insert(path);
+            }
+        }
+        changed.extend(self.0.keys().filter(|path| !current.0.contains_key(*path)));
+        changed.into_iter().cloned().collect()
+    }
+}
+
+/// The functions each pointcut of the workspace matches.
+#[derive(Debug, Default)]
+struct Matches {
+    /// Number of functions analyzed
+    functions: usize,
+
+    /// Functions matched, as `<crate>-<kind> <function>`, by pointcut
+    pointcuts: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// A function a pointcut newly matches, or no longer matches.
+#[derive(Debug, PartialEq, Eq)]
+struct Change {
+    matched: bool,
+    pointcut: String,
+    function: String,
+}
+
+impl Matches {
+    fn new(reports: &[(String, AnalysisReport)], pointcuts: &[&str]) -> Self {
+        let matcher = PointcutMatcher::new();
+        let mut matches = Matches::default();
+        for pointcut in pointcuts {
+            matches
+                .pointcuts
+                .insert(pointcut.to_string(), BTreeSet::new());
+        }
+        for (name, report) in reports {
+            matches.functions += report.functions.len();
+            for function in &report.functions {
+                for pointcut in pointcuts {
+                    if matcher.matches_pointcut(function, pointcut) {
+                        matches
+                            .pointcuts
+                            .entry(pointcut.to_string())
+                            .or_default()
+                            .insert(format!("{} {}", name, function.name));
+                    }
+                }
+            }
+        }
+        matches
+    }
+
+    /// Number of functions matched by a pointcut.
+    fn matched(&self) -> usize {
+        self.pointcuts
+            .values()
+            .flatten()
+            .collect::<BTreeSet<_>>()
+            .len()
+    }
+
+    /// Pointcuts matching no function.
+    fn unmatched(&self) -> Vec<&str> {
+        self.pointcuts
+            .iter()
+            .filter(|(_, functions)| functions.is_empty())
+            .map(|(pointcut, _)| pointcut.as_str())
+            .collect()
+    }
+
+    /// What changed from `self` to `current`, by pointcut. A pointcut that
+    /// was added or removed newly matches, or no longer matches, all its
+    /// functions.
+    fn changes(&self, current: &Matches) -> Vec<Change> {
+        let empty = BTreeSet::new();
+        let pointcuts: BTreeSet<&String> = self
+            .pointcuts
+            .keys()
+            .chain(current.pointcuts.keys())
+            .collect();
+
+        let mut changes = Vec::new();
+        for pointcut in pointcuts {
+            let before = self.pointcuts.get(pointcut).unwrap_or(&empty);
+            let after = current.pointcuts.get(pointcut).unwrap_or(&empty);
+            for (matched, functions) in [
+                (true, after.difference(before)),
+                (false, before.difference(after)),
+            ] {
+                changes.extend(functions.map(|function| Change {
+                    matched,
+                    pointcut: pointcut.clone(),
+                    function: function.clone(),
+                }));
+            }
+        }
+        changes
+    }
+}
+
+fn print_changes(changes: &[Change]) {
+    if changes.is_empty() {
+        println!("No change in matches");
+    }
+    for change in changes {
+        println!(
+            "  {} {}: {}",
+            if change.matched { "+" } else { "-" },
+            change.pointcut,
+            change.function
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspect_driver::syntax::analyze_source;
+
+    fn reports(source: &str) -> Vec<(String, AnalysisReport)> {
+        let functions = analyze_source(source, "src/lib.rs", "crate").unwrap();
+        vec![(
+            "shop-lib".to_string(),
+            AnalysisReport::new(&[], functions, &[]),
+        )]
+    }
+
+    #[test]
+    fn test_match_changes() {
+        let pointcuts = ["execution(pub fn *(..))", "name(helper)"];
+        let before = Matches::new(&reports("pub fn get() {}\nfn helper() {}"), &pointcuts);
+        assert_eq!(before.functions, 2);
+        assert_eq!(before.matched(), 2);
+        assert!(before.unmatched().is_empty());
+
+        // `helper` renamed and `put` added
+        let after = Matches::new(
+            &reports("pub fn get() {}\npub fn put() {}\nfn help() {}"),
+            &pointcuts,
+        );
+        assert_eq!(after.unmatched(), ["name(helper)"]);
+
+        let change = |matched, pointcut: &str, function: &str| Change {
+            matched,
+            pointcut: pointcut.to_string(),
+            function: function.to_string(),
+        };
+        assert_eq!(
+            before.changes(&after),
+            [
+                change(true, "execution(pub fn *(..))", "shop-lib put"),
+                change(false, "name(helper)", "shop-lib helper"),
+            ]
+        );
+        assert!(after.changes(&after).is_empty());
+    }
+
+    #[test]
+    fn test_changed_sources() {
+        let now = SystemTime::now();
+        let later = now + Duration::from_secs(1);
+        let sources = |files: &[(&str, SystemTime)]| {
+            Sources(
+                files
+                    .iter()
+                    .map(|(path, time)| (PathBuf::from(path), *time))
+                    .collect(),
+            )
+        };
+
+        let before = sources(&[("src/lib.rs", now), ("src/api.rs", now)]);
+        let after = sources(&[("src/lib.rs", later), ("src/db.rs", now)]);
+        assert_eq!(
+            before.changed(&after),
+            [
+                PathBuf::from("src/api.rs"),
+                PathBuf::from("src/db.rs"),
+                PathBuf::from("src/lib.rs"),
+            ]
+        );
+        assert!(after.changed(&after).is_empty());
+    }
+}
@@ -0,0 +1,245 @@
+//! Analysis-only `RUSTC_WRAPPER` mode, for stable toolchains.
+//!
+//! `cargo aspect info` runs `cargo check` with cargo-aspect itself as the
+//! rustc wrapper. For every crate of the workspace, the wrapper parses the
+//! crate's sources with `aspect_driver::syntax`, writes the functions it
+//! found as an analysis report to `$ASPECT_ANALYSIS_DIR`, and then runs
+//! rustc unchanged. Dependencies are passed straight to rustc.
+//!
+//! This needs neither nightly nor `rustc-dev`, at the price of metadata
+//! that is only as good as syntax allows (see `aspect_driver::syntax`).
+//!
+//! Next to each report, the wrapper writes the fingerprint of the sources
+//! it analyzed (`<crate>-<kind>.fingerprint`). While every crate of the
+//! workspace has the fingerprint of its current sources, the reports are
+//! [`fresh_reports`], and commands reuse them without running cargo.
+
+use anyhow::{Context, Result};
+use aspect_driver::cache::crate_fingerprint;
+use aspect_driver::filter::CrateTarget;
+use aspect_driver::r#match::REGISTRY_DIR_ENV;
+use aspect_driver::report::AnalysisReport;
+use aspect_driver::syntax::analyze_crate;
+use log::{error, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+use std::time::SystemTime;
+
+/// Directory the wrapper writes its reports to; the wrapper mode is on
+/// when it is set.
+pub const ANALYSIS_DIR_ENV: &str = "ASPECT_ANALYSIS_DIR";
+
+/// Run as `RUSTC_WRAPPER`: `args` are the rustc path and its arguments.
+pub fn run(analysis_dir: &Path, args: &[String]) -> ExitCode {
+    let Some((rustc, rustc_args)) = args.split_first() else {
+        error!("expected a rustc command line");
+        return ExitCode::FAILURE;
+    };
+
+    // Only the crates of the workspace are analyzed; failing to analyze
+    // one must not fail the build
+    if std::env::var_os("CARGO_PRIMARY_PACKAGE").is_some() {
+        if let Some(target) = CrateTarget::from_rustc_args(rustc_args) {
+            // #[advice] records the aspects of the crate again as rustc
+            // expands it, like under the compiler driver
+            if let Some(registry_dir) = std::env::var_os(REGISTRY_DIR_ENV) {
+                let _ = fs::remove_dir_all(Path::new(&registry_dir).join(&target.crate_name));
+            }
+            if let Err(e) = write_report(analysis_dir, &target) {
+                warn!("aspect analysis of {}: {:#}", target.crate_name, e);
+            }
+        }
+    }
+
+    match Command::new(rustc).args(rustc_args).status() {
+        Ok(status) => ExitCode::from(status.code().unwrap_or(1).clamp(0, 255) as u8),
+        Err(e) => {
+            error!("failed to run {}: {}", rustc, e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// The sources a report was written from.
+#[derive(Debug, Serialize, Deserialize)]
+struct Fingerprint {
+    /// Version of cargo-aspect that wrote the report
+    version: String,
+
+    /// Root source file of the crate, canonical
+    root: PathBuf,
+
+    /// See [`crate_fingerprint`]
+    fingerprint: u64,
+}
+
+impl Fingerprint {
+    fn of(root: &Path) -> Result<Self> {
+        let root =
+            fs::canonicalize(root).with_context(|| format!("cannot find {}", root.display()))?;
+        let fingerprint = crate_fingerprint(&root).map_err(anyhow::Error::msg)?;
+        Ok(Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            root,
+            fingerprint,
+        })
+    }
+}
+
+fn write_report(analysis_dir: &Path, target: &CrateTarget) -> Result<()> {
+    // Taken before the analysis, so that a source changed meanwhile makes
+    // the report stale
+    let fingerprint = Fingerprint::of(&target.root)?;
+    let functions = analyze_crate(&target.root).map_err(anyhow::Error::msg)?;
+    let report = AnalysisReport::new(&[], functions, &[]);
+
+    fs::create_dir_all(analysis_dir)
+        .with_context(|| format!("cannot create {}", analysis_dir.display()))?;
+    let path = analysis_dir.join(target.report_name());
+    fs::write(&path, report.to_json() + "\n")
+        .with_context(|| format!("cannot write {}", path.display()))?;
+    let path = path.with_extension("fingerprint");
+    fs::write(&path, serde_json::to_string(&fingerprint)? + "\n")
+        .with_context(|| format!("cannot write {}", path.display()))
+}
+
+/// Whether the reports in `analysis_dir` were written by this version of
+/// cargo-aspect from the current sources of every crate of `crate_roots`.
+pub fn fresh_reports(analysis_dir: &Path, crate_roots: &[PathBuf]) -> bool {
+    let Ok(entries) = fs::read_dir(analysis_dir) else {
+        return false;
+    };
+    let fingerprints: HashMap<PathBuf, Fingerprint> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "fingerprint")
+        })
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .filter_map(|json| serde_json::from_str::<Fingerprint>(&json).ok())
+        .map(|fingerprint| (fingerprint.root.clone(), fingerprint))
+        .collect();
+
+    !crate_roots.is_empty()
+        && crate_roots.iter().all(|root| {
+            let Ok(current) = Fingerprint::of(root) else {
+                return false;
+            };
+            let fresh = fingerprints.get(&current.root).is_some_and(|stored| {
+                stored.version == current.version && stored.fingerprint == current.fingerprint
+            });
+            if !fresh {
+                trace!("{} changed since the last analysis", root.display());
+            }
+            fresh
+        })
+}
+
+/// The reports in `analysis_dir`, by file name without `.json`
+/// (`<crate>-<kind>`), sorted; only those written after `since` if given.
+pub fn read_reports(
+    analysis_dir: &Path,
+    since: Option<SystemTime>,
+) -> Result<Vec<(String, AnalysisReport)>> {
+    let mut reports = Vec::new();
+    let entries = fs::read_dir(analysis_dir)
+        .with_context(|| format!("cannot read {}", analysis_dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        let Some(name) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".json"))
+        else {
+            continue;
+        };
+        if let Some(since) = since {
+            if fs::metadata(&path)?.modified()? < since {
+                continue;
+            }
+        }
+        let json = fs::read_to_string(&path)?;
+        let report = AnalysisReport::from_json(&json)
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("invalid report {}", path.display()))?;
+        reports.push((name.to_string(), report));
+    }
+    reports.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_round_trip() {
+        let dir = std::env::temp_dir().join(format!("cargo-aspect-wrapper-{}", std::process::id()));
+        let src = dir.join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("lib.rs"), "pub fn get() {}\nfn put() {}\n").unwrap();
+
+        let target = CrateTarget::from_rustc_args(&[
+            "--crate-name".to_string(),
+            "shop".to_string(),
+            src.join("lib.rs").display().to_string(),
+            "--crate-type".to_string(),
+            "lib".to_string(),
+        ])
+        .unwrap();
+        let analysis_dir = dir.join("analysis");
+        write_report(&analysis_dir, &target).unwrap();
+
+        let reports = read_reports(&analysis_dir, None).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].0, "shop-lib");
+        assert_eq!(reports[0].1.stats.total_functions, 2);
+        assert_eq!(reports[0].1.stats.public_functions, 1);
+
+        let later = SystemTime::now() + std::time::Duration::from_secs(60);
+        assert!(read_reports(&analysis_dir, Some(later)).unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fresh_reports() {
+        let dir = std::env::temp_dir().join(format!("cargo-aspect-fresh-{}", std::process::id()));
+        let src = dir.join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("lib.rs"), "pub fn get() {}\n").unwrap();
+        fs::write(src.join("main.rs"), "fn main() {}\n").unwrap();
+        let lib = [src.join("lib.rs")];
+        let main = src.join("main.rs");
+        let analysis_dir = dir.join("analysis");
+        assert!(!fresh_reports(&analysis_dir, &lib));
+
+        let target = CrateTarget::from_rustc_args(&[
+            "--crate-name".to_string(),
+            "shop".to_string(),
+            lib[0].display().to_string(),
+            "--crate-type".to_string(),
+            "lib".to_string(),
+        ])
+        .unwrap();
+        write_report(&analysis_dir, &target).unwrap();
+        assert!(fresh_reports(&analysis_dir, &lib));
+        assert!(fresh_reports(&analysis_dir, &[src.join("../src/lib.rs")]));
+        // The binary was never analyzed
+        assert!(!fresh_reports(&analysis_dir, &[lib[0].clone(), main]));
+        assert!(!fresh_reports(&analysis_dir, &[]));
+
+        fs::write(src.join("lib.rs"), "pub fn get() {}\npub fn put() {}\n").unwrap();
+        assert!(!fresh_reports(&analysis_dir, &lib));
+        write_report(&analysis_dir, &target).unwrap();
+        assert!(fresh_reports(&analysis_dir, &lib));
+        // Fingerprints are not reports
+        assert_eq!(read_reports(&analysis_dir, None).unwrap().len(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}